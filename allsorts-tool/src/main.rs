@@ -0,0 +1,493 @@
+//! Command line access to the main features of Allsorts.
+//!
+//! Each subcommand is a thin wrapper over the library API, so this also serves as an example of
+//! its use. The tests at the bottom of this file run the subcommands against the fonts in the
+//! Allsorts test suite.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::io::{self, Write};
+use std::process;
+
+use allsorts::bidi::shape_bidi;
+use allsorts::font::{Font, OwnedFont};
+use allsorts::font_data::OwnedFontTableProvider;
+use allsorts::gsub::{FeatureSetting, Features};
+use allsorts::script_itemizer::itemize;
+use allsorts::subset::{subset_font, SubsetInput, SubsetOptions};
+use allsorts::tables::F2Dot14;
+use allsorts::tag::{self, DisplayTag};
+use allsorts::{ttx, validate};
+
+const USAGE: &str = "\
+Usage: allsorts-tool COMMAND [OPTIONS]
+
+Commands:
+  dump FONT                    Print the supported tables of FONT as TTX
+  subset FONT OUTPUT           Write a subset of FONT to OUTPUT
+      --text TEXT              Retain the glyphs for the characters of TEXT
+      --glyphs IDS             Retain the glyphs with these comma separated ids
+  instance FONT OUTPUT         Write a static instance of variable FONT to OUTPUT
+      --axes AXES              Comma separated axis values, such as wght=700,wdth=75
+  outlines FONT                Print glyph outlines, at a variation instance if given
+      --coords COORDS          Comma separated normalised axis coordinates, from -1 to 1
+      --glyphs IDS             Print only the glyphs with these ids
+  validate FONT                Check FONT for structural problems
+      --sanitize OUTPUT        Write a copy of FONT without the invalid tables to OUTPUT
+  shape FONT TEXT              Print the glyphs and positions of shaped TEXT
+      --script TAG             OpenType script tag, detected from TEXT by default
+      --lang TAG               OpenType language tag
+      --features FEATURES      Comma separated feature tags, prefixed with - to disable
+
+Options for all commands:
+      --index N                Index of the font in a collection, 0 by default
+";
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let stdout = io::stdout();
+    match run(&args, &mut stdout.lock()) {
+        Ok(status) => process::exit(status),
+        Err(err) => {
+            eprintln!("error: {}", err);
+            process::exit(2);
+        }
+    }
+}
+
+/// Run the command in `args`, writing its output to `out`. Returns the exit status.
+fn run(args: &[String], out: &mut impl Write) -> Result<i32> {
+    let args = Args::parse(args)?;
+    match args.positional.first().map(String::as_str) {
+        Some("dump") => dump(&args, out),
+        Some("subset") => subset(&args, out),
+        Some("instance") => instance(&args, out),
+        Some("outlines") => outlines(&args, out),
+        Some("validate") => validate(&args, out),
+        Some("shape") => shape(&args, out),
+        Some("help") | None => {
+            out.write_all(USAGE.as_bytes())?;
+            Ok(0)
+        }
+        Some(command) => Err(format!("unknown command '{}'\n\n{}", command, USAGE).into()),
+    }
+}
+
+fn dump(args: &Args, out: &mut impl Write) -> Result<i32> {
+    args.check(2, &["index"])?;
+    let font = load_font(args)?;
+    out.write_all(ttx::dump(&font)?.as_bytes())?;
+    Ok(0)
+}
+
+fn subset(args: &Args, out: &mut impl Write) -> Result<i32> {
+    args.check(3, &["index", "text", "glyphs"])?;
+    let data = fs::read(args.arg(1, "FONT")?)?;
+    let provider = OwnedFontTableProvider::new(data, args.index()?)?;
+    let input = match (args.option("text"), args.option("glyphs")) {
+        (Some(text), None) => SubsetInput::Unicodes(text.chars().map(u32::from).collect()),
+        (None, Some(glyphs)) => SubsetInput::Glyphs(parse_list(glyphs)?),
+        _ => return Err("subset requires one of --text or --glyphs".into()),
+    };
+
+    let subset = subset_font(&provider, &input, &SubsetOptions::default())?;
+    let output = args.arg(2, "OUTPUT")?;
+    fs::write(output, &subset)?;
+    writeln!(out, "wrote {} bytes to {}", subset.len(), output)?;
+    Ok(0)
+}
+
+fn instance(args: &Args, out: &mut impl Write) -> Result<i32> {
+    args.check(3, &["index", "axes"])?;
+    let data = fs::read(args.arg(1, "FONT")?)?;
+    let provider = OwnedFontTableProvider::new(data, args.index()?)?;
+    // Axes that aren't given are left at their default
+    let location = match args.option("axes") {
+        Some(axes) => axes
+            .split(',')
+            .map(|axis| {
+                let mut parts = axis.splitn(2, '=');
+                match (parts.next(), parts.next()) {
+                    (Some(axis_tag), Some(value)) => {
+                        let value = value
+                            .trim()
+                            .parse::<f32>()
+                            .map_err(|_| format!("invalid value '{}'", value))?;
+                        Ok((tag::from_string(axis_tag.trim())?, value))
+                    }
+                    _ => Err(format!("invalid axis '{}', expected TAG=VALUE", axis).into()),
+                }
+            })
+            .collect::<Result<Vec<_>>>()?,
+        None => Vec::new(),
+    };
+
+    let instanced = allsorts::instance::instance(&provider, &location)?;
+    for axis in &instanced.clamped_axes {
+        writeln!(
+            out,
+            "'{}' axis value {} clamped to {}",
+            DisplayTag(axis.axis_tag),
+            axis.requested,
+            axis.clamped
+        )?;
+    }
+    let output = args.arg(2, "OUTPUT")?;
+    fs::write(output, &instanced.data)?;
+    writeln!(out, "wrote {} bytes to {}", instanced.data.len(), output)?;
+    Ok(0)
+}
+
+fn outlines(args: &Args, out: &mut impl Write) -> Result<i32> {
+    args.check(2, &["index", "coords", "glyphs"])?;
+    let mut font = load_font(args)?;
+    let coords = match args.option("coords") {
+        Some(coords) => parse_list::<f32>(coords)?
+            .into_iter()
+            .map(|coord| {
+                if !(-1.0..=1.0).contains(&coord) {
+                    return Err(format!("coordinate {} is outside -1 to 1", coord).into());
+                }
+                Ok(F2Dot14::new((coord * 16384.0).round() as i16 as u16))
+            })
+            .collect::<Result<_>>()?,
+        None => Vec::new(),
+    };
+    let glyphs = match args.option("glyphs") {
+        Some(glyphs) => parse_list(glyphs)?,
+        None => (0..font.num_glyphs()).collect(),
+    };
+
+    // Each contour is printed on a line, with off-curve points in parentheses
+    for glyph in glyphs {
+        let outline = match font.outline(glyph, &coords)? {
+            Some(outline) => outline,
+            None => {
+                writeln!(out, "glyph {}: no outline", glyph)?;
+                continue;
+            }
+        };
+        writeln!(out, "glyph {}:", glyph)?;
+        let mut start = 0;
+        for &end in &outline.end_pts_of_contours {
+            let end = usize::from(end) + 1;
+            let points = (start..end)
+                .map(|i| {
+                    let point = outline.points[i];
+                    if outline.on_curve[i] {
+                        format!("{},{}", point.0, point.1)
+                    } else {
+                        format!("({},{})", point.0, point.1)
+                    }
+                })
+                .collect::<Vec<_>>();
+            writeln!(out, "  {}", points.join(" "))?;
+            start = end;
+        }
+    }
+    Ok(0)
+}
+
+fn validate(args: &Args, out: &mut impl Write) -> Result<i32> {
+    args.check(2, &["index", "sanitize"])?;
+    let data = fs::read(args.arg(1, "FONT")?)?;
+    let index = args.index()?;
+    let report = validate::validate(&data, index)?;
+    for issue in &report.issues {
+        writeln!(
+            out,
+            "{:?}: '{}' table: {:?}",
+            issue.severity,
+            DisplayTag(issue.table_tag),
+            issue.problem
+        )?;
+    }
+
+    if let Some(output) = args.option("sanitize") {
+        match validate::sanitize(&data, index)?.data {
+            Some(sanitized) => {
+                fs::write(output, &sanitized)?;
+                writeln!(out, "wrote sanitized font to {}", output)?;
+            }
+            None => writeln!(out, "unable to sanitize font")?,
+        }
+    }
+
+    if report.is_valid() {
+        writeln!(out, "valid")?;
+        Ok(0)
+    } else {
+        Ok(1)
+    }
+}
+
+fn shape(args: &Args, out: &mut impl Write) -> Result<i32> {
+    args.check(3, &["index", "script", "lang", "features"])?;
+    let mut font = load_font(args)?;
+    let text = args.arg(2, "TEXT")?;
+    let script_tag = args.option("script").map(tag::from_string).transpose()?;
+    let lang_tag = args.option("lang").map(tag::from_string).transpose()?;
+    let features = match args.option("features") {
+        Some(features) => Features::Settings(
+            features
+                .split(',')
+                .map(|feature| {
+                    let feature_tag = tag::from_string(feature.trim_start_matches('-'))?;
+                    if feature.starts_with('-') {
+                        Ok(FeatureSetting::off(feature_tag))
+                    } else {
+                        Ok(FeatureSetting::on(feature_tag))
+                    }
+                })
+                .collect::<Result<_>>()?,
+        ),
+        None => Features::Settings(Vec::new()),
+    };
+
+    let runs = shape_bidi(
+        &mut font,
+        text,
+        None,
+        |run_text| {
+            script_tag.unwrap_or_else(|| {
+                itemize(run_text)
+                    .first()
+                    .map_or(tag::DFLT, |run| run.script_tag)
+            })
+        },
+        lang_tag,
+        &features,
+        true,
+    )?;
+    for run in runs {
+        let glyph_ids = run
+            .glyphs
+            .iter()
+            .map(|(glyph, _)| glyph.glyph_index)
+            .collect::<Vec<_>>();
+        let names = font.glyph_names(&glyph_ids);
+        for ((glyph, position), name) in run.glyphs.iter().zip(names) {
+            writeln!(
+                out,
+                "{}\t{}\tadvance={},{}\toffset={},{}",
+                glyph.glyph_index,
+                name,
+                position.x_advance,
+                position.y_advance,
+                position.x_offset,
+                position.y_offset
+            )?;
+        }
+    }
+    Ok(0)
+}
+
+fn load_font(args: &Args) -> Result<OwnedFont> {
+    let data = fs::read(args.arg(1, "FONT")?)?;
+    Font::from_data(data, args.index()?)?.ok_or_else(|| "font has no suitable cmap table".into())
+}
+
+fn parse_list<T: std::str::FromStr>(list: &str) -> Result<Vec<T>> {
+    list.split(',')
+        .map(|item| {
+            item.trim()
+                .parse()
+                .map_err(|_| format!("invalid value '{}'", item).into())
+        })
+        .collect()
+}
+
+/// Command line arguments: positional arguments and `--name value` options.
+struct Args {
+    positional: Vec<String>,
+    options: HashMap<String, String>,
+}
+
+impl Args {
+    fn parse(args: &[String]) -> Result<Args> {
+        let mut positional = Vec::new();
+        let mut options = HashMap::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if let Some(name) = arg
+                .get(..2)
+                .filter(|&dashes| dashes == "--")
+                .and(arg.get(2..))
+            {
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("missing value for {}", arg))?;
+                options.insert(name.to_string(), value.clone());
+            } else {
+                positional.push(arg.clone());
+            }
+        }
+        Ok(Args {
+            positional,
+            options,
+        })
+    }
+
+    /// Check that the command received `count` positional arguments, including the command
+    /// itself, and only the options in `allowed`.
+    fn check(&self, count: usize, allowed: &[&str]) -> Result<()> {
+        if self.positional.len() > count {
+            return Err(format!("unexpected argument '{}'", self.positional[count]).into());
+        }
+        match self
+            .options
+            .keys()
+            .find(|option| !allowed.contains(&option.as_str()))
+        {
+            Some(option) => Err(format!("unknown option --{}", option).into()),
+            None => Ok(()),
+        }
+    }
+
+    fn arg(&self, index: usize, name: &str) -> Result<&str> {
+        self.positional
+            .get(index)
+            .map(String::as_str)
+            .ok_or_else(|| format!("missing {}\n\n{}", name, USAGE).into())
+    }
+
+    fn option(&self, name: &str) -> Option<&str> {
+        self.options.get(name).map(String::as_str)
+    }
+
+    fn index(&self) -> Result<usize> {
+        match self.option("index") {
+            Some(index) => Ok(index.parse()?),
+            None => Ok(0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use allsorts::tables::FontTableProvider;
+
+    fn fixture(path: &str) -> String {
+        format!("{}/../tests/fonts/{}", env!("CARGO_MANIFEST_DIR"), path)
+    }
+
+    fn run_command(args: &[&str]) -> (i32, String) {
+        let args = args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        let mut out = Vec::new();
+        let status = run(&args, &mut out).unwrap();
+        (status, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_dump() {
+        let font = fixture("opentype/test-font.ttf");
+        let (status, out) = run_command(&["dump", &font]);
+        assert_eq!(status, 0);
+        assert!(out.contains(r#"<map code="0x46" name="F"/>"#));
+    }
+
+    #[test]
+    fn test_subset() {
+        let font = fixture("opentype/Klei.otf");
+        let output = std::env::temp_dir().join("allsorts-tool-subset.otf");
+        let output = output.to_str().unwrap();
+        let (status, _) = run_command(&["subset", &font, output, "--text", "Klei"]);
+        assert_eq!(status, 0);
+
+        let subset = Font::from_data(fs::read(output).unwrap(), 0)
+            .unwrap()
+            .unwrap();
+        assert_eq!(subset.num_glyphs(), 5);
+        fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_instance() {
+        let font = fixture("opentype/test-font-variable.ttf");
+        let output = std::env::temp_dir().join("allsorts-tool-instance.ttf");
+        let (status, out) = run_command(&[
+            "instance",
+            &font,
+            output.to_str().unwrap(),
+            "--axes",
+            "wght=700",
+        ]);
+        assert_eq!(status, 0);
+        assert!(out.starts_with("wrote "));
+
+        let data = fs::read(&output).unwrap();
+        let provider = OwnedFontTableProvider::new(data.clone(), 0).unwrap();
+        assert!(!provider.has_table(tag::FVAR));
+        assert!(!provider.has_table(tag::GVAR));
+        let mut instance = Font::from_data(data, 0).unwrap().unwrap();
+        assert_eq!(instance.horizontal_advance(2), Some(4731));
+        fs::remove_file(&output).unwrap();
+
+        // Values outside the range of an axis are reported
+        let (status, out) = run_command(&[
+            "instance",
+            &font,
+            output.to_str().unwrap(),
+            "--axes",
+            "wght=1000",
+        ]);
+        assert_eq!(status, 0);
+        assert!(out.starts_with("'wght' axis value 1000 clamped to 900\n"));
+        fs::remove_file(&output).unwrap();
+
+        // test-font.ttf is not a variable font
+        let font = fixture("opentype/test-font.ttf");
+        let args = ["instance", &font, output.to_str().unwrap()]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect::<Vec<_>>();
+        assert!(run(&args, &mut Vec::new()).is_err());
+        assert!(!output.exists());
+
+        let args = ["instance", &font, "OUTPUT", "--axes", "wght"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect::<Vec<_>>();
+        assert!(run(&args, &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_outlines() {
+        let font = fixture("opentype/test-font.ttf");
+        let (status, out) = run_command(&["outlines", &font, "--glyphs", "1,2"]);
+        assert_eq!(status, 0);
+        assert_eq!(out, "glyph 1:\nglyph 2:\n  1761,565 2007,565 1884,1032\n");
+    }
+
+    #[test]
+    fn test_validate() {
+        let font = fixture("opentype/test-font.ttf");
+        let (status, out) = run_command(&["validate", &font]);
+        assert_eq!(status, 0);
+        assert!(out.ends_with("valid\n"));
+    }
+
+    #[test]
+    fn test_shape() {
+        let font = fixture("opentype/test-font.ttf");
+        let (status, out) = run_command(&["shape", &font, "F F", "--features", "-kern"]);
+        assert_eq!(status, 0);
+        let glyphs = out
+            .lines()
+            .map(|line| line.split('\t').take(2).collect::<Vec<_>>().join(" "))
+            .collect::<Vec<_>>();
+        // The second F is replaced by the contextual alternate
+        assert_eq!(glyphs, vec!["2 F", "1 space", "2 F.alt01"]);
+    }
+
+    #[test]
+    fn test_bad_arguments() {
+        let args = ["dump".to_string(), "--bogus".to_string(), "1".to_string()];
+        assert!(run(&args, &mut Vec::new()).is_err());
+        assert!(run(&["frobnicate".to_string()], &mut Vec::new()).is_err());
+    }
+}
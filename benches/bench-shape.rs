@@ -146,11 +146,13 @@ fn make_glyph(ch: char, glyph_index: u16) -> RawGlyph<()> {
         glyph_origin: GlyphOrigin::Char(ch),
         small_caps: false,
         multi_subst_dup: false,
+        multi_subst_component: None,
         is_vert_alt: false,
         fake_bold: false,
         fake_italic: false,
         extra_data: (),
         variation: None,
+        stretch: None,
     }
 }
 
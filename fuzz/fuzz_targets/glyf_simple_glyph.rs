@@ -0,0 +1,17 @@
+//! Parse a simple glyph, exercising the decoding of its flags and coordinates.
+//!
+//! The first byte of the input is the number of contours, the remainder is the glyph data
+//! following the glyph header.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use allsorts::binary::read::ReadScope;
+use allsorts::tables::glyf::SimpleGlyph;
+
+fuzz_target!(|data: &[u8]| {
+    if let Some((&number_of_contours, glyph_data)) = data.split_first() {
+        let _ = ReadScope::new(glyph_data).read_dep::<SimpleGlyph>(u16::from(number_of_contours));
+    }
+});
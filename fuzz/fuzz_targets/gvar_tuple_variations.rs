@@ -0,0 +1,43 @@
+//! Decode the tuple variations of a glyph, exercising the packed point number and delta decoders.
+//!
+//! The first two bytes of the input are the number of points in the glyph, the third is the axis
+//! count, and the remainder is the glyph variation data of a single glyph `gvar` table.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use allsorts::binary::read::ReadScope;
+use allsorts::tables::gvar::{GvarTable, PointNumberMode};
+
+const HEADER_SIZE: u32 = 20;
+
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 3 {
+        return;
+    }
+    let num_points = u16::from_be_bytes([data[0], data[1]]);
+    let axis_count = data[2];
+    let glyph_data = &data[3..];
+
+    let mut gvar = Vec::with_capacity(glyph_data.len() + 28);
+    gvar.extend_from_slice(&1u16.to_be_bytes()); // majorVersion
+    gvar.extend_from_slice(&0u16.to_be_bytes()); // minorVersion
+    gvar.extend_from_slice(&u16::from(axis_count).to_be_bytes()); // axisCount
+    gvar.extend_from_slice(&0u16.to_be_bytes()); // sharedTupleCount
+    gvar.extend_from_slice(&0u32.to_be_bytes()); // sharedTuplesOffset
+    gvar.extend_from_slice(&1u16.to_be_bytes()); // glyphCount
+    gvar.extend_from_slice(&1u16.to_be_bytes()); // flags: long offsets
+    gvar.extend_from_slice(&(HEADER_SIZE + 8).to_be_bytes()); // glyphVariationDataArrayOffset
+    gvar.extend_from_slice(&0u32.to_be_bytes());
+    gvar.extend_from_slice(&(glyph_data.len() as u32).to_be_bytes());
+    gvar.extend_from_slice(glyph_data);
+
+    let table = match ReadScope::new(&gvar).read_dep::<GvarTable<'_>>(1) {
+        Ok(table) => table,
+        Err(_) => return,
+    };
+    for &mode in &[PointNumberMode::Strict, PointNumberMode::Lenient] {
+        let _ = table.tuple_variation_store(0, num_points, mode);
+    }
+});
@@ -0,0 +1,84 @@
+/*
+ * C API for Allsorts, available when the crate is built with the `capi` feature.
+ *
+ * See src/capi.rs for documentation of each function.
+ */
+
+#ifndef ALLSORTS_H
+#define ALLSORTS_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct AllsortsFont AllsortsFont;
+typedef struct AllsortsGlyphBuffer AllsortsGlyphBuffer;
+typedef struct AllsortsOutline AllsortsOutline;
+
+typedef struct {
+    uint32_t tag;
+    bool enabled;
+} AllsortsFeature;
+
+typedef struct {
+    uint32_t script_tag;
+    uint32_t lang_tag;
+    const AllsortsFeature *features;
+    size_t num_features;
+    bool right_to_left;
+} AllsortsShapeOptions;
+
+typedef struct {
+    uint16_t glyph_id;
+    int32_t x_advance;
+    int32_t y_advance;
+    int32_t x_offset;
+    int32_t y_offset;
+} AllsortsGlyph;
+
+typedef enum {
+    ALLSORTS_MOVE_TO = 0,
+    ALLSORTS_LINE_TO = 1,
+    ALLSORTS_QUAD_TO = 2,
+    ALLSORTS_CLOSE = 3,
+} AllsortsVerb;
+
+typedef struct {
+    AllsortsVerb verb;
+    float cx;
+    float cy;
+    float x;
+    float y;
+} AllsortsOutlineCommand;
+
+#define ALLSORTS_TAG(a, b, c, d) \
+    (((uint32_t)(a) << 24) | ((uint32_t)(b) << 16) | ((uint32_t)(c) << 8) | (uint32_t)(d))
+
+int allsorts_capi_version(void);
+
+AllsortsFont *allsorts_font_create(const uint8_t *data, size_t len, uint32_t index);
+void allsorts_font_free(AllsortsFont *font);
+uint16_t allsorts_font_num_glyphs(const AllsortsFont *font);
+uint16_t allsorts_font_units_per_em(const AllsortsFont *font);
+
+AllsortsGlyphBuffer *allsorts_shape(AllsortsFont *font, const uint8_t *text, size_t len,
+                                    const AllsortsShapeOptions *options);
+size_t allsorts_glyph_buffer_len(const AllsortsGlyphBuffer *buffer);
+const AllsortsGlyph *allsorts_glyph_buffer_glyphs(const AllsortsGlyphBuffer *buffer);
+void allsorts_glyph_buffer_free(AllsortsGlyphBuffer *buffer);
+
+AllsortsOutline *allsorts_outline_create(AllsortsFont *font, uint16_t glyph_id,
+                                         const int16_t *coords, size_t num_coords);
+size_t allsorts_outline_len(const AllsortsOutline *outline);
+const AllsortsOutlineCommand *allsorts_outline_commands(const AllsortsOutline *outline);
+void allsorts_outline_free(AllsortsOutline *outline);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* ALLSORTS_H */
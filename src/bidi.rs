@@ -0,0 +1,380 @@
+#![deny(missing_docs)]
+
+//! Shaping of bidirectional text.
+//!
+//! Text mixing left-to-right and right-to-left scripts is split into directional runs using the
+//! Unicode Bidirectional Algorithm, as implemented by the `unicode-bidi` crate. Each run is shaped
+//! separately, with mirrored characters such as brackets replaced by their counterparts in
+//! right-to-left runs, and the glyphs of the runs returned in visual order.
+//!
+//! This module requires the `unicode-bidi` feature.
+
+use std::ops::Range;
+
+use unicode_bidi::BidiInfo;
+pub use unicode_bidi::Level;
+
+use crate::error::ShapingError;
+use crate::font::{Font, MatchingPresentation};
+use crate::gpos::{GlyphPosition, TextDirection};
+use crate::gsub::{FeatureSetting, Features, GsubFeatureMask, RawGlyph};
+use crate::tables::FontTableProvider;
+use crate::tag;
+
+/// Pairs of characters with the `Bidi_Mirrored` property that are the mirror image of each other.
+///
+/// <https://www.unicode.org/Public/UCD/latest/ucd/BidiMirroring.txt>
+const MIRRORED_PAIRS: &[(char, char)] = &[
+    ('(', ')'),
+    ('<', '>'),
+    ('[', ']'),
+    ('{', '}'),
+    ('\u{00AB}', '\u{00BB}'), // « »
+    ('\u{0F3A}', '\u{0F3B}'), // Tibetan gug rtags
+    ('\u{0F3C}', '\u{0F3D}'), // Tibetan ang khang
+    ('\u{169B}', '\u{169C}'), // Ogham feather marks
+    ('\u{2039}', '\u{203A}'), // ‹ ›
+    ('\u{2045}', '\u{2046}'), // ⁅ ⁆
+    ('\u{207D}', '\u{207E}'), // superscript parentheses
+    ('\u{208D}', '\u{208E}'), // subscript parentheses
+    ('\u{2208}', '\u{220B}'), // ∈ ∋
+    ('\u{2209}', '\u{220C}'), // ∉ ∌
+    ('\u{220A}', '\u{220D}'), // small element of
+    ('\u{223C}', '\u{223D}'), // ∼ ∽
+    ('\u{2243}', '\u{22CD}'), // ≃ ⋍
+    ('\u{2252}', '\u{2253}'), // ≒ ≓
+    ('\u{2254}', '\u{2255}'), // ≔ ≕
+    ('\u{2264}', '\u{2265}'), // ≤ ≥
+    ('\u{2266}', '\u{2267}'), // ≦ ≧
+    ('\u{2268}', '\u{2269}'), // ≨ ≩
+    ('\u{226A}', '\u{226B}'), // ≪ ≫
+    ('\u{226E}', '\u{226F}'), // ≮ ≯
+    ('\u{2270}', '\u{2271}'), // ≰ ≱
+    ('\u{2272}', '\u{2273}'), // ≲ ≳
+    ('\u{2274}', '\u{2275}'), // ≴ ≵
+    ('\u{2276}', '\u{2277}'), // ≶ ≷
+    ('\u{2278}', '\u{2279}'), // ≸ ≹
+    ('\u{227A}', '\u{227B}'), // ≺ ≻
+    ('\u{227C}', '\u{227D}'), // ≼ ≽
+    ('\u{227E}', '\u{227F}'), // ≾ ≿
+    ('\u{2280}', '\u{2281}'), // ⊀ ⊁
+    ('\u{2282}', '\u{2283}'), // ⊂ ⊃
+    ('\u{2284}', '\u{2285}'), // ⊄ ⊅
+    ('\u{2286}', '\u{2287}'), // ⊆ ⊇
+    ('\u{2288}', '\u{2289}'), // ⊈ ⊉
+    ('\u{228A}', '\u{228B}'), // ⊊ ⊋
+    ('\u{228F}', '\u{2290}'), // ⊏ ⊐
+    ('\u{2291}', '\u{2292}'), // ⊑ ⊒
+    ('\u{22A2}', '\u{22A3}'), // ⊢ ⊣
+    ('\u{22B0}', '\u{22B1}'), // ⊰ ⊱
+    ('\u{22B2}', '\u{22B3}'), // ⊲ ⊳
+    ('\u{22B4}', '\u{22B5}'), // ⊴ ⊵
+    ('\u{22C9}', '\u{22CA}'), // ⋉ ⋊
+    ('\u{22CB}', '\u{22CC}'), // ⋋ ⋌
+    ('\u{22D0}', '\u{22D1}'), // ⋐ ⋑
+    ('\u{22D6}', '\u{22D7}'), // ⋖ ⋗
+    ('\u{22D8}', '\u{22D9}'), // ⋘ ⋙
+    ('\u{22DA}', '\u{22DB}'), // ⋚ ⋛
+    ('\u{22DC}', '\u{22DD}'), // ⋜ ⋝
+    ('\u{22DE}', '\u{22DF}'), // ⋞ ⋟
+    ('\u{22E0}', '\u{22E1}'), // ⋠ ⋡
+    ('\u{22E2}', '\u{22E3}'), // ⋢ ⋣
+    ('\u{22E4}', '\u{22E5}'), // ⋤ ⋥
+    ('\u{22E6}', '\u{22E7}'), // ⋦ ⋧
+    ('\u{22E8}', '\u{22E9}'), // ⋨ ⋩
+    ('\u{22EA}', '\u{22EB}'), // ⋪ ⋫
+    ('\u{22EC}', '\u{22ED}'), // ⋬ ⋭
+    ('\u{22F0}', '\u{22F1}'), // ⋰ ⋱
+    ('\u{2308}', '\u{2309}'), // ⌈ ⌉
+    ('\u{230A}', '\u{230B}'), // ⌊ ⌋
+    ('\u{2329}', '\u{232A}'), // 〈 〉
+    ('\u{2768}', '\u{2769}'), // ❨ ❩
+    ('\u{276A}', '\u{276B}'), // ❪ ❫
+    ('\u{276C}', '\u{276D}'), // ❬ ❭
+    ('\u{276E}', '\u{276F}'), // ❮ ❯
+    ('\u{2770}', '\u{2771}'), // ❰ ❱
+    ('\u{2772}', '\u{2773}'), // ❲ ❳
+    ('\u{2774}', '\u{2775}'), // ❴ ❵
+    ('\u{27C3}', '\u{27C4}'), // ⟃ ⟄
+    ('\u{27C5}', '\u{27C6}'), // ⟅ ⟆
+    ('\u{27D5}', '\u{27D6}'), // ⟕ ⟖
+    ('\u{27E6}', '\u{27E7}'), // ⟦ ⟧
+    ('\u{27E8}', '\u{27E9}'), // ⟨ ⟩
+    ('\u{27EA}', '\u{27EB}'), // ⟪ ⟫
+    ('\u{27EC}', '\u{27ED}'), // ⟬ ⟭
+    ('\u{27EE}', '\u{27EF}'), // ⟮ ⟯
+    ('\u{2983}', '\u{2984}'), // ⦃ ⦄
+    ('\u{2985}', '\u{2986}'), // ⦅ ⦆
+    ('\u{2987}', '\u{2988}'), // ⦇ ⦈
+    ('\u{2989}', '\u{298A}'), // ⦉ ⦊
+    ('\u{298B}', '\u{298C}'), // ⦋ ⦌
+    ('\u{298D}', '\u{2990}'), // ⦍ ⦐
+    ('\u{298E}', '\u{298F}'), // ⦎ ⦏
+    ('\u{2991}', '\u{2992}'), // ⦑ ⦒
+    ('\u{2993}', '\u{2994}'), // ⦓ ⦔
+    ('\u{2995}', '\u{2996}'), // ⦕ ⦖
+    ('\u{2997}', '\u{2998}'), // ⦗ ⦘
+    ('\u{29FC}', '\u{29FD}'), // ⧼ ⧽
+    ('\u{2E02}', '\u{2E03}'), // ⸂ ⸃
+    ('\u{2E04}', '\u{2E05}'), // ⸄ ⸅
+    ('\u{2E09}', '\u{2E0A}'), // ⸉ ⸊
+    ('\u{2E0C}', '\u{2E0D}'), // ⸌ ⸍
+    ('\u{2E1C}', '\u{2E1D}'), // ⸜ ⸝
+    ('\u{2E20}', '\u{2E21}'), // ⸠ ⸡
+    ('\u{2E22}', '\u{2E23}'), // ⸢ ⸣
+    ('\u{2E24}', '\u{2E25}'), // ⸤ ⸥
+    ('\u{2E26}', '\u{2E27}'), // ⸦ ⸧
+    ('\u{2E28}', '\u{2E29}'), // ⸨ ⸩
+    ('\u{3008}', '\u{3009}'), // 〈 〉
+    ('\u{300A}', '\u{300B}'), // 《 》
+    ('\u{300C}', '\u{300D}'), // 「 」
+    ('\u{300E}', '\u{300F}'), // 『 』
+    ('\u{3010}', '\u{3011}'), // 【 】
+    ('\u{3014}', '\u{3015}'), // 〔 〕
+    ('\u{3016}', '\u{3017}'), // 〖 〗
+    ('\u{3018}', '\u{3019}'), // 〘 〙
+    ('\u{301A}', '\u{301B}'), // 〚 〛
+    ('\u{FE59}', '\u{FE5A}'), // small parentheses
+    ('\u{FE5B}', '\u{FE5C}'), // small curly brackets
+    ('\u{FE5D}', '\u{FE5E}'), // small tortoise shell brackets
+    ('\u{FE64}', '\u{FE65}'), // small less-than and greater-than
+    ('\u{FF08}', '\u{FF09}'), // fullwidth parentheses
+    ('\u{FF1C}', '\u{FF1E}'), // fullwidth less-than and greater-than
+    ('\u{FF3B}', '\u{FF3D}'), // fullwidth square brackets
+    ('\u{FF5B}', '\u{FF5D}'), // fullwidth curly brackets
+    ('\u{FF5F}', '\u{FF60}'), // fullwidth white parentheses
+    ('\u{FF62}', '\u{FF63}'), // halfwidth corner brackets
+];
+
+/// A run of text with a single embedding level.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DirectionalRun {
+    /// The byte range of the run in the text.
+    pub range: Range<usize>,
+    /// The embedding level of the run. Odd levels are right-to-left.
+    pub level: Level,
+}
+
+/// A directional run that has been shaped.
+#[derive(Clone, Debug)]
+pub struct ShapedRun {
+    /// The byte range of the run in the text.
+    pub range: Range<usize>,
+    /// The embedding level of the run. Odd levels are right-to-left.
+    pub level: Level,
+    /// The glyphs of the run and their positions, in visual order from left to right.
+    pub glyphs: Vec<(RawGlyph<()>, GlyphPosition)>,
+}
+
+impl DirectionalRun {
+    /// The direction of the text in the run.
+    pub fn direction(&self) -> TextDirection {
+        if self.level.is_rtl() {
+            TextDirection::RightToLeft
+        } else {
+            TextDirection::LeftToRight
+        }
+    }
+}
+
+/// Split `text` into directional runs, in visual order.
+///
+/// Each paragraph is treated as a single line; runs of later paragraphs follow those of earlier
+/// ones. The base direction of each paragraph is determined from its text, or taken from
+/// `default_level` if supplied.
+pub fn directional_runs(text: &str, default_level: Option<Level>) -> Vec<DirectionalRun> {
+    let bidi_info = BidiInfo::new(text, default_level);
+    let mut runs = Vec::new();
+    for paragraph in &bidi_info.paragraphs {
+        let (levels, level_runs) = bidi_info.visual_runs(paragraph, paragraph.range.clone());
+        runs.extend(level_runs.into_iter().map(|range| DirectionalRun {
+            level: levels[range.start],
+            range,
+        }));
+    }
+    runs
+}
+
+/// Returns the character that is the mirror image of `ch`, if there is one.
+///
+/// Characters such as brackets are mirrored when they appear in right-to-left text.
+pub fn mirror_char(ch: char) -> Option<char> {
+    MIRRORED_PAIRS.iter().find_map(|&(left, right)| {
+        if ch == left {
+            Some(right)
+        } else if ch == right {
+            Some(left)
+        } else {
+            None
+        }
+    })
+}
+
+/// Shape bidirectional text.
+///
+/// `text` is split into directional runs, which are shaped separately and returned in visual
+/// order. The script of each run is supplied by `script_tag`, which is passed the text of the run.
+///
+/// In right-to-left runs characters with a mirror image are replaced by it when the font has a
+/// glyph for the mirrored character. If `features` is a mask the `rtlm` feature is also enabled
+/// for these runs, to mirror any remaining glyphs. The glyphs of right-to-left runs are reversed
+/// after shaping and positioning so that every run is in left-to-right visual order.
+pub fn shape_bidi<T: FontTableProvider>(
+    font: &mut Font<T>,
+    text: &str,
+    default_level: Option<Level>,
+    mut script_tag: impl FnMut(&str) -> u32,
+    opt_lang_tag: Option<u32>,
+    features: &Features,
+    kerning: bool,
+) -> Result<Vec<ShapedRun>, ShapingError> {
+    let mut shaped_runs = Vec::new();
+    for run in directional_runs(text, default_level) {
+        let run_text = &text[run.range.clone()];
+        let direction = run.direction();
+        let shaped = match direction {
+            TextDirection::LeftToRight => {
+                let glyphs = font.map_glyphs(run_text, MatchingPresentation::NotRequired);
+                font.shape(
+                    glyphs,
+                    script_tag(run_text),
+                    opt_lang_tag,
+                    features,
+                    kerning,
+                )?
+            }
+            TextDirection::RightToLeft => {
+                let mirrored = mirror_text(font, run_text);
+                let glyphs = font.map_glyphs(&mirrored, MatchingPresentation::NotRequired);
+                let rtl_features = match features {
+                    Features::Mask(feature_mask) => {
+                        Some(Features::Mask(*feature_mask | GsubFeatureMask::RTLM))
+                    }
+                    Features::Custom(_) => None,
+                    Features::Settings(settings) if features.setting(tag::RTLM).is_none() => {
+                        let mut settings = settings.clone();
+                        settings.push(FeatureSetting::on(tag::RTLM));
+                        Some(Features::Settings(settings))
+                    }
+                    Features::Settings(_) => None,
+                };
+                font.shape(
+                    glyphs,
+                    script_tag(run_text),
+                    opt_lang_tag,
+                    rtl_features.as_ref().unwrap_or(features),
+                    kerning,
+                )?
+            }
+        };
+
+        let positions = font.glyph_positions(&shaped, direction);
+        let mut glyphs = shaped
+            .into_iter()
+            .map(|info| info.glyph)
+            .zip(positions)
+            .collect::<Vec<_>>();
+        if direction == TextDirection::RightToLeft {
+            glyphs.reverse();
+        }
+        shaped_runs.push(ShapedRun {
+            range: run.range,
+            level: run.level,
+            glyphs,
+        });
+    }
+
+    Ok(shaped_runs)
+}
+
+/// Replace the characters of `text` that have a mirror image with it, if `font` has a glyph for
+/// the mirrored character.
+fn mirror_text<T: FontTableProvider>(font: &mut Font<T>, text: &str) -> String {
+    text.chars()
+        .map(|ch| match mirror_char(ch) {
+            Some(mirrored) => {
+                let (glyph_index, _) =
+                    font.lookup_glyph_index(mirrored, MatchingPresentation::NotRequired, None);
+                if glyph_index != 0 {
+                    mirrored
+                } else {
+                    ch
+                }
+            }
+            None => ch,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::read::ReadScope;
+    use crate::tables::OpenTypeFont;
+    use crate::tag;
+    use crate::tests::read_fixture;
+
+    #[test]
+    fn test_directional_runs() {
+        // Hebrew in parentheses within a left-to-right paragraph
+        let text = "abc (\u{05D0}\u{05D1})";
+        let runs = directional_runs(text, None);
+        let directions = runs.iter().map(|run| run.direction()).collect::<Vec<_>>();
+        assert_eq!(
+            directions,
+            vec![
+                TextDirection::LeftToRight,
+                TextDirection::RightToLeft,
+                TextDirection::LeftToRight
+            ]
+        );
+        assert_eq!(&text[runs[1].range.clone()], "\u{05D0}\u{05D1}");
+
+        // A right-to-left paragraph puts the later runs first
+        let text = "\u{05D0}\u{05D1} abc";
+        let runs = directional_runs(text, None);
+        assert_eq!(&text[runs[0].range.clone()], "abc");
+        assert_eq!(runs[1].direction(), TextDirection::RightToLeft);
+    }
+
+    #[test]
+    fn test_mirror_char() {
+        assert_eq!(mirror_char('('), Some(')'));
+        assert_eq!(mirror_char(']'), Some('['));
+        assert_eq!(mirror_char('\u{00AB}'), Some('\u{00BB}'));
+        assert_eq!(mirror_char('\u{2264}'), Some('\u{2265}'));
+        assert_eq!(mirror_char('a'), None);
+    }
+
+    #[test]
+    fn test_shape_bidi() {
+        let buffer = read_fixture("tests/fonts/noto/NotoNaskhArabic-Regular.ttf");
+        let opentype_file = ReadScope::new(&buffer).read::<OpenTypeFont<'_>>().unwrap();
+        let provider = opentype_file.table_provider(0).unwrap();
+        let mut font = Font::new(Box::new(provider)).unwrap().unwrap();
+
+        // Arabic in parentheses within a right-to-left paragraph
+        let text = "(\u{0628}\u{062A})";
+        let runs = shape_bidi(
+            &mut font,
+            text,
+            None,
+            |_| tag::ARAB,
+            None,
+            &Features::Mask(GsubFeatureMask::default()),
+            true,
+        )
+        .unwrap();
+        assert_eq!(runs.len(), 1);
+        assert!(runs[0].level.is_rtl());
+
+        // Reversed into visual order, with the brackets mirrored
+        let (open, _) = font.lookup_glyph_index('(', MatchingPresentation::NotRequired, None);
+        let (close, _) = font.lookup_glyph_index(')', MatchingPresentation::NotRequired, None);
+        let glyphs = &runs[0].glyphs;
+        assert_eq!(glyphs.len(), 4);
+        assert_eq!(glyphs[0].0.glyph_index, open);
+        assert_eq!(glyphs[3].0.glyph_index, close);
+        assert_eq!(glyphs[1].0.unicodes.as_slice(), &['\u{062A}']);
+    }
+}
@@ -16,10 +16,12 @@ use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::fmt;
 use std::marker::PhantomData;
-use std::rc::Rc;
+use std::sync::Arc;
 
 #[derive(Debug, Copy, Clone)]
-pub struct ReadEof {}
+pub struct ReadEof {
+    offset: usize,
+}
 
 pub struct ReadBuf<'a> {
     data: Cow<'a, [u8]>,
@@ -59,7 +61,7 @@ pub struct ReadCtxt<'a> {
 }
 
 pub struct ReadCache<T> {
-    map: HashMap<usize, Rc<T>>,
+    map: HashMap<usize, Arc<T>>,
 }
 
 pub trait ReadBinary<'a> {
@@ -237,6 +239,13 @@ impl<'a, T: ReadUnchecked<'a>> CheckIndex for ReadArrayCow<'a, T> {
     }
 }
 
+impl ReadEof {
+    /// The offset from the start of the outermost scope at which the read was attempted.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
 impl<'a> ReadScope<'a> {
     pub fn new(data: &'a [u8]) -> ReadScope<'a> {
         let base = 0;
@@ -261,10 +270,10 @@ impl<'a> ReadScope<'a> {
                 let data = &data[0..length];
                 Ok(ReadScope { base, data })
             } else {
-                Err(ParseError::BadEof)
+                Err(ParseError::BadEof.with_offset(self.base + offset))
             }
         } else {
-            Err(ParseError::BadOffset)
+            Err(ParseError::BadOffset.with_offset(self.base + offset))
         }
     }
 
@@ -283,16 +292,16 @@ impl<'a> ReadScope<'a> {
     pub fn read_cache<'b, T>(
         &self,
         cache: &mut ReadCache<T::HostType>,
-    ) -> Result<Rc<T::HostType>, ParseError>
+    ) -> Result<Arc<T::HostType>, ParseError>
     where
         T: 'static + ReadBinaryDep<'a, Args = ()>,
     {
         match cache.map.entry(self.base) {
             Entry::Vacant(entry) => {
-                let t = Rc::new(self.read::<T>()?);
-                Ok(Rc::clone(entry.insert(t)))
+                let t = Arc::new(self.read::<T>()?);
+                Ok(Arc::clone(entry.insert(t)))
             }
-            Entry::Occupied(entry) => Ok(Rc::clone(entry.get())),
+            Entry::Occupied(entry) => Ok(Arc::clone(entry.get())),
         }
     }
 
@@ -300,17 +309,17 @@ impl<'a> ReadScope<'a> {
         &self,
         cache: &mut ReadCache<T::HostType>,
         state: LayoutCache<Table>,
-    ) -> Result<Rc<T::HostType>, ParseError>
+    ) -> Result<Arc<T::HostType>, ParseError>
     where
         T: 'static + ReadBinaryDep<'a, Args = LayoutCache<Table>>,
         Table: LayoutTableType,
     {
         match cache.map.entry(self.base) {
             Entry::Vacant(entry) => {
-                let t = Rc::new(self.read_dep::<T>(state)?);
-                Ok(Rc::clone(entry.insert(t)))
+                let t = Arc::new(self.read_dep::<T>(state)?);
+                Ok(Arc::clone(entry.insert(t)))
             }
-            Entry::Occupied(entry) => Ok(Rc::clone(entry.get())),
+            Entry::Occupied(entry) => Ok(Arc::clone(entry.get())),
         }
     }
 }
@@ -331,10 +340,16 @@ impl<'a> ReadCtxt<'a> {
     pub fn check(&self, cond: bool) -> Result<(), ParseError> {
         match cond {
             true => Ok(()),
-            false => Err(ParseError::BadValue),
+            false => Err(ParseError::BadValue.with_offset(self.position())),
         }
     }
 
+    /// Check a condition on the value of `field`, returning `ParseError::BadValue` naming the field
+    /// if `false`.
+    pub fn check_field(&self, cond: bool, field: &'static str) -> Result<(), ParseError> {
+        self.check(cond).map_err(|err| err.with_field(field))
+    }
+
     /// Check a condition, returning `ParseError::BadVersion` if `false`.
     ///
     /// Intended for use in checking versions read from data. Example:
@@ -353,7 +368,7 @@ impl<'a> ReadCtxt<'a> {
     pub fn check_version(&self, cond: bool) -> Result<(), ParseError> {
         match cond {
             true => Ok(()),
-            false => Err(ParseError::BadVersion),
+            false => Err(ParseError::BadVersion.with_offset(self.position())),
         }
     }
 
@@ -362,14 +377,22 @@ impl<'a> ReadCtxt<'a> {
     }
 
     pub fn read<T: ReadBinaryDep<'a, Args = ()>>(&mut self) -> Result<T::HostType, ParseError> {
-        T::read_dep(self, ())
+        self.read_dep::<T>(())
     }
 
+    /// Read a `T`. Errors that don't record an offset are given the offset of the start of the
+    /// `T`.
     pub fn read_dep<T: ReadBinaryDep<'a>>(
         &mut self,
         args: T::Args,
     ) -> Result<T::HostType, ParseError> {
-        T::read_dep(self, args)
+        let start = self.position();
+        T::read_dep(self, args).map_err(|err| err.with_offset(start))
+    }
+
+    /// The offset of the read position from the start of the outermost scope.
+    fn position(&self) -> usize {
+        self.scope.base + self.offset
     }
 
     pub fn bytes_available(&self) -> bool {
@@ -379,7 +402,9 @@ impl<'a> ReadCtxt<'a> {
     fn check_avail(&self, length: usize) -> Result<(), ReadEof> {
         match self.offset.checked_add(length) {
             Some(endpos) if endpos <= self.scope.data.len() => Ok(()),
-            _ => Err(ReadEof {}),
+            _ => Err(ReadEof {
+                offset: self.position(),
+            }),
         }
     }
 
@@ -483,11 +508,22 @@ impl<'a> ReadCtxt<'a> {
         // Safe because we have 8 bytes available.
     }
 
+    pub fn read_u16le(&mut self) -> Result<u16, ReadEof> {
+        self.read_u16be().map(u16::swap_bytes)
+    }
+
+    pub fn read_u32le(&mut self) -> Result<u32, ReadEof> {
+        self.read_u32be().map(u32::swap_bytes)
+    }
+
     pub fn read_array<T: ReadUnchecked<'a>>(
         &mut self,
         length: usize,
     ) -> Result<ReadArray<'a, T>, ParseError> {
-        let scope = self.read_scope(length * T::SIZE)?;
+        let size = length
+            .checked_mul(T::SIZE)
+            .ok_or(ParseError::LimitExceeded)?;
+        let scope = self.read_scope(size)?;
         let args = ();
         Ok(ReadArray {
             scope,
@@ -513,7 +549,9 @@ impl<'a> ReadCtxt<'a> {
         let end = self.scope.data[self.offset..]
             .iter()
             .position(|&b| (b >> 4) == nibble || (b & 0xF) == nibble)
-            .ok_or(ReadEof {})?;
+            .ok_or(ReadEof {
+                offset: self.position(),
+            })?;
         self.read_slice(end + 1)
     }
 
@@ -522,7 +560,10 @@ impl<'a> ReadCtxt<'a> {
         length: usize,
         args: T::Args,
     ) -> Result<ReadArray<'a, T>, ParseError> {
-        let scope = self.read_scope(length * T::size(args.clone()))?;
+        let size = length
+            .checked_mul(T::size(args.clone()))
+            .ok_or(ParseError::LimitExceeded)?;
+        let scope = self.read_scope(size)?;
         Ok(ReadArray {
             scope,
             length,
@@ -535,7 +576,9 @@ impl<'a> ReadCtxt<'a> {
             self.offset += length;
             Ok(scope)
         } else {
-            Err(ReadEof {})
+            Err(ReadEof {
+                offset: self.position(),
+            })
         }
     }
 
@@ -916,10 +959,39 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::error::ParseErrorKind;
+    use crate::tag;
 
     #[test]
     fn test_read_u24be() {
         let scope = ReadScope::new(&[1, 2, 3]);
         assert_eq!(scope.read::<U24Be>().unwrap(), 0x10203);
     }
+
+    #[test]
+    fn test_error_offset() {
+        let scope = ReadScope::new(&[0, 1, 0, 2, 0]);
+        let mut ctxt = scope.offset(2).ctxt();
+        assert_eq!(ctxt.read::<U16Be>().unwrap(), 2);
+        let err = ctxt.read::<U16Be>().unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::BadEof);
+        assert_eq!(err.offset(), Some(4));
+    }
+
+    #[test]
+    fn test_check_field() {
+        let scope = ReadScope::new(&[0, 3]);
+        let mut ctxt = scope.ctxt();
+        let format = ctxt.read_u16be().unwrap();
+        let err = ctxt
+            .check_field(format == 4, "format")
+            .map_err(|err| err.with_table(tag::CMAP))
+            .unwrap_err();
+        assert_eq!(err, ParseError::BadValue);
+        assert_eq!(err.field(), Some("format"));
+        assert_eq!(
+            err.to_string(),
+            "invalid value in 'cmap' table reading format at offset 2"
+        );
+    }
 }
@@ -2,6 +2,7 @@
 
 //! Write binary data
 
+use std::convert::TryFrom;
 use std::iter;
 use std::marker::PhantomData;
 
@@ -38,6 +39,19 @@ where
     host: PhantomData<HostType>,
 }
 
+/// A placeholder for an offset to data that has not been written yet, filled in using
+/// WriteContext::write_offset once the data it points at is written.
+///
+/// The offset is relative to `base`, which is usually the start of the table or subtable that
+/// holds it. A placeholder that is never filled in is left as a NULL offset.
+pub struct OffsetPlaceholder<T, HostType>
+where
+    T: WriteBinary<HostType>,
+{
+    placeholder: Placeholder<T, HostType>,
+    base: usize,
+}
+
 /// Trait that describes a type that can be written to a `WriteContext` in binary form.
 pub trait WriteBinary<HostType = Self> {
     /// The type of the value returned by `write`.
@@ -155,6 +169,42 @@ pub trait WriteContext {
     ) -> Result<T::Output, WriteError>
     where
         T: WriteBinary<HostType>;
+
+    /// Return a placeholder for an offset of type `T` from `base` to data that will be written
+    /// later.
+    fn offset_placeholder<'a, T, HostType>(
+        &mut self,
+        base: usize,
+    ) -> Result<OffsetPlaceholder<T, HostType>, WriteError>
+    where
+        T: WriteBinary<HostType> + ReadUnchecked<'a>,
+    {
+        if base > self.bytes_written() {
+            return Err(WriteError::BadValue);
+        }
+        let placeholder = self.placeholder::<T, HostType>()?;
+
+        Ok(OffsetPlaceholder { placeholder, base })
+    }
+
+    /// Consumes the offset placeholder and fills it in with the offset of the current position
+    /// from its base.
+    ///
+    /// This is called immediately before writing the data that the offset points at. An error is
+    /// returned if the offset is too large for `T`.
+    fn write_offset<T, HostType>(
+        &mut self,
+        offset: OffsetPlaceholder<T, HostType>,
+    ) -> Result<T::Output, WriteError>
+    where
+        T: WriteBinary<HostType>,
+        HostType: TryFrom<usize>,
+    {
+        let OffsetPlaceholder { placeholder, base } = offset;
+        let val =
+            HostType::try_from(self.bytes_written() - base).map_err(|_| WriteError::BadValue)?;
+        self.write_placeholder(placeholder, val)
+    }
 }
 
 /// Write `T` into a `WriteBuffer` and return it
@@ -537,6 +587,36 @@ mod tests {
         assert_eq!(ctxt.bytes(), &[1, 0, 2, 3]);
     }
 
+    #[test]
+    fn test_write_offset() {
+        let mut ctxt = WriteBuffer::new();
+        U16Be::write(&mut ctxt, 0xFFFFu16).unwrap();
+        let base = ctxt.bytes_written();
+        U16Be::write(&mut ctxt, 2u16).unwrap(); // count
+        let first = ctxt.offset_placeholder::<U16Be, u16>(base).unwrap();
+        let second = ctxt.offset_placeholder::<U32Be, u32>(base).unwrap();
+        let _null = ctxt.offset_placeholder::<U16Be, u16>(base).unwrap();
+        ctxt.write_offset(second).unwrap();
+        U8::write(&mut ctxt, 2).unwrap();
+        ctxt.write_offset(first).unwrap();
+        U8::write(&mut ctxt, 1).unwrap();
+        assert_eq!(
+            ctxt.bytes(),
+            &[0xFF, 0xFF, 0, 2, 0, 11, 0, 0, 0, 10, 0, 0, 2, 1]
+        );
+
+        // Offsets relative to a position that hasn't been written yet are invalid
+        assert!(ctxt.offset_placeholder::<U16Be, u16>(100).is_err());
+    }
+
+    #[test]
+    fn test_write_offset_overflow() {
+        let mut ctxt = WriteBuffer::new();
+        let offset = ctxt.offset_placeholder::<U16Be, u16>(0).unwrap();
+        ctxt.write_zeros(0x10000).unwrap();
+        assert!(ctxt.write_offset(offset).is_err());
+    }
+
     #[test]
     fn test_write_placeholder_overflow() {
         // Test that trying to write more data than reserved results in an error
@@ -6,18 +6,20 @@ use std::convert::TryFrom;
 use std::fmt;
 
 use bitreader::{BitReader, BitReaderError};
+use rustc_hash::FxHashMap;
 
 use super::BitDepth;
 use crate::binary::read::{
     CheckIndex, ReadArray, ReadBinary, ReadBinaryDep, ReadCtxt, ReadFixedSizeDep, ReadFrom,
     ReadScope,
 };
+use crate::binary::write::{WriteBinary, WriteBuffer, WriteContext};
 use crate::binary::{U16Be, U32Be, I8, U8};
 use crate::bitmap::{
     Bitmap, BitmapGlyph, BitmapMetrics, EmbeddedBitmap, EmbeddedMetrics, EncapsulatedBitmap,
     EncapsulatedFormat, Metrics,
 };
-use crate::error::ParseError;
+use crate::error::{ParseError, ReadWriteError, WriteError};
 use crate::size;
 
 /// Flag in `BitmapInfo` `flags` indicating the direction of small glyph metrics is horizontal.
@@ -322,6 +324,25 @@ pub struct EbdtComponent {
     pub y_offset: i8,
 }
 
+/// The location and data tables of a subset font, produced by
+/// [CBLCTable::subset](struct.CBLCTable.html#method.subset).
+pub struct CBLCSubset {
+    /// The `CBLC` or `EBLC` table.
+    pub cblc: Vec<u8>,
+    /// The `CBDT` or `EBDT` table.
+    pub cbdt: Vec<u8>,
+}
+
+/// A range of consecutive glyphs with the same image format in a subset strike, written as an
+/// `IndexSubTable1`.
+struct SubsetIndexSubTable {
+    first_glyph_index: u16,
+    image_format: u16,
+    image_data_offset: u32,
+    /// Offsets of the glyphs from `image_data_offset`, plus one for the end of the last glyph.
+    offsets: Vec<u32>,
+}
+
 /// Result of `find_strike`.
 pub struct MatchingStrike<'a, 'b> {
     pub(crate) bitmap_size: &'a BitmapSize<'b>,
@@ -628,6 +649,152 @@ impl<'a> CBLCTable<'a> {
     }
 }
 
+impl<'a> CBLCTable<'a> {
+    /// Subset the strikes of this table and the bitmap data in `cbdt` to the glyphs in
+    /// `new_to_old_glyph_id`, which holds the old glyph id of each glyph of the subset font.
+    ///
+    /// Returns the new location and data tables, or `None` if none of the glyphs have a bitmap.
+    /// The version of the tables is kept, so an `EBLC`/`EBDT` pair remains one.
+    ///
+    /// Glyphs whose metrics are held in this table (image formats 5 and 19) are converted to the
+    /// equivalent format with metrics in the data table (formats 7 and 18). Composite bitmaps
+    /// (image formats 8 and 9) are dropped if any of their components are not retained.
+    pub fn subset(
+        &self,
+        cbdt: &CBDTTable<'a>,
+        new_to_old_glyph_id: &[u16],
+    ) -> Result<Option<CBLCSubset>, ReadWriteError> {
+        let mut old_to_new_glyph_id = FxHashMap::default();
+        for (new_id, &old_id) in new_to_old_glyph_id.iter().enumerate() {
+            old_to_new_glyph_id.insert(old_id, u16::try_from(new_id).map_err(WriteError::from)?);
+        }
+
+        let mut data = WriteBuffer::new();
+        U16Be::write(&mut data, cbdt.major_version)?;
+        U16Be::write(&mut data, cbdt.minor_version)?;
+        let mut strikes = Vec::new();
+        for bitmap_size in &self.bitmap_sizes {
+            let mut index_sub_tables: Vec<SubsetIndexSubTable> = Vec::new();
+            for (new_id, &old_id) in new_to_old_glyph_id.iter().enumerate() {
+                let matching_strike = match bitmap_size.index_sub_table_index(old_id) {
+                    Some(index_subtable_index) => MatchingStrike {
+                        bitmap_size,
+                        index_subtable_index,
+                    },
+                    None => continue,
+                };
+                let glyph = match lookup(old_id, &matching_strike, cbdt)? {
+                    Some(glyph) => glyph,
+                    None => continue,
+                };
+                let start = data.bytes_written();
+                let image_format = match glyph.write(&mut data, &old_to_new_glyph_id)? {
+                    Some(image_format) => image_format,
+                    None => continue,
+                };
+                let end = u32::try_from(data.bytes_written()).map_err(WriteError::from)?;
+
+                // Glyph ids fit in u16 since old_to_new_glyph_id was built successfully
+                let new_id = new_id as u16;
+                match index_sub_tables.last_mut() {
+                    Some(index_sub_table)
+                        if index_sub_table.image_format == image_format
+                            && index_sub_table.next_glyph_index() == Some(new_id) =>
+                    {
+                        index_sub_table
+                            .offsets
+                            .push(end - index_sub_table.image_data_offset);
+                    }
+                    _ => {
+                        let image_data_offset = u32::try_from(start).map_err(WriteError::from)?;
+                        index_sub_tables.push(SubsetIndexSubTable {
+                            first_glyph_index: new_id,
+                            image_format,
+                            image_data_offset,
+                            offsets: vec![0, end - image_data_offset],
+                        });
+                    }
+                }
+            }
+            if !index_sub_tables.is_empty() {
+                strikes.push((&bitmap_size.inner, index_sub_tables));
+            }
+        }
+        if strikes.is_empty() {
+            return Ok(None);
+        }
+
+        let mut location = WriteBuffer::new();
+        U16Be::write(&mut location, self.major_version)?;
+        U16Be::write(&mut location, self.minor_version)?;
+        U32Be::write(
+            &mut location,
+            u32::try_from(strikes.len()).map_err(WriteError::from)?,
+        )?;
+        let mut placeholders = Vec::with_capacity(strikes.len());
+        for (info, index_sub_tables) in &strikes {
+            let index_sub_table_array = location.offset_placeholder::<U32Be, u32>(0)?;
+            let index_tables_size = location.placeholder::<U32Be, u32>()?;
+            U32Be::write(
+                &mut location,
+                u32::try_from(index_sub_tables.len()).map_err(WriteError::from)?,
+            )?;
+            U32Be::write(&mut location, 0u32)?; // colorRef
+            SbitLineMetrics::write(&mut location, &info.hori)?;
+            SbitLineMetrics::write(&mut location, &info.vert)?;
+            // index_sub_tables is non-empty and sorted by glyph id
+            U16Be::write(&mut location, index_sub_tables[0].first_glyph_index)?;
+            U16Be::write(
+                &mut location,
+                index_sub_tables[index_sub_tables.len() - 1].last_glyph_index(),
+            )?;
+            U8::write(&mut location, info.ppem_x)?;
+            U8::write(&mut location, info.ppem_y)?;
+            U8::write(&mut location, info.bit_depth as u8)?;
+            I8::write(&mut location, info.flags)?;
+            placeholders.push((index_sub_table_array, index_tables_size));
+        }
+        for ((_, index_sub_tables), (index_sub_table_array, index_tables_size)) in
+            strikes.iter().zip(placeholders)
+        {
+            let array_start = location.bytes_written();
+            location.write_offset(index_sub_table_array)?;
+            let mut offsets = Vec::with_capacity(index_sub_tables.len());
+            for index_sub_table in index_sub_tables {
+                U16Be::write(&mut location, index_sub_table.first_glyph_index)?;
+                U16Be::write(&mut location, index_sub_table.last_glyph_index())?;
+                offsets.push(location.offset_placeholder::<U32Be, u32>(array_start)?);
+            }
+            for (index_sub_table, offset) in index_sub_tables.iter().zip(offsets) {
+                location.write_offset(offset)?;
+                U16Be::write(&mut location, 1u16)?; // indexFormat
+                U16Be::write(&mut location, index_sub_table.image_format)?;
+                U32Be::write(&mut location, index_sub_table.image_data_offset)?;
+                location.write_vec::<U32Be>(index_sub_table.offsets.clone())?;
+            }
+            let size =
+                u32::try_from(location.bytes_written() - array_start).map_err(WriteError::from)?;
+            location.write_placeholder(index_tables_size, size)?;
+        }
+
+        Ok(Some(CBLCSubset {
+            cblc: location.into_inner(),
+            cbdt: data.into_inner(),
+        }))
+    }
+}
+
+impl SubsetIndexSubTable {
+    fn last_glyph_index(&self) -> u16 {
+        // offsets holds one more entry than there are glyphs, and at least one glyph
+        self.first_glyph_index + (self.offsets.len() - 2) as u16
+    }
+
+    fn next_glyph_index(&self) -> Option<u16> {
+        self.last_glyph_index().checked_add(1)
+    }
+}
+
 fn same_size_higher_bit_depth(
     difference: i16,
     current_best_difference: i16,
@@ -819,6 +986,26 @@ impl<'a> ReadBinary<'a> for SbitLineMetrics {
     }
 }
 
+impl WriteBinary<&Self> for SbitLineMetrics {
+    type Output = ();
+
+    fn write<C: WriteContext>(ctxt: &mut C, metrics: &SbitLineMetrics) -> Result<(), WriteError> {
+        I8::write(ctxt, metrics.ascender)?;
+        I8::write(ctxt, metrics.descender)?;
+        U8::write(ctxt, metrics.width_max)?;
+        I8::write(ctxt, metrics.caret_slope_numerator)?;
+        I8::write(ctxt, metrics.caret_slope_denominator)?;
+        I8::write(ctxt, metrics.caret_offset)?;
+        I8::write(ctxt, metrics.min_origin_sb)?;
+        I8::write(ctxt, metrics.min_advance_sb)?;
+        I8::write(ctxt, metrics.max_before_bl)?;
+        I8::write(ctxt, metrics.min_after_bl)?;
+        I8::write(ctxt, metrics.pad1)?;
+        I8::write(ctxt, metrics.pad2)?;
+        Ok(())
+    }
+}
+
 impl<'a> ReadFixedSizeDep<'a> for SbitLineMetrics {
     fn size(_scope: Self::Args) -> usize {
         // 12 fields, all 1 byte
@@ -954,6 +1141,19 @@ impl<'a> ReadFrom<'a> for SmallGlyphMetrics {
     }
 }
 
+impl WriteBinary for SmallGlyphMetrics {
+    type Output = ();
+
+    fn write<C: WriteContext>(ctxt: &mut C, metrics: SmallGlyphMetrics) -> Result<(), WriteError> {
+        U8::write(ctxt, metrics.height)?;
+        U8::write(ctxt, metrics.width)?;
+        I8::write(ctxt, metrics.bearing_x)?;
+        I8::write(ctxt, metrics.bearing_y)?;
+        U8::write(ctxt, metrics.advance)?;
+        Ok(())
+    }
+}
+
 impl<'a> ReadBinary<'a> for BigGlyphMetrics {
     type HostType = Self;
 
@@ -987,6 +1187,22 @@ impl<'a> ReadFixedSizeDep<'a> for BigGlyphMetrics {
     }
 }
 
+impl WriteBinary for BigGlyphMetrics {
+    type Output = ();
+
+    fn write<C: WriteContext>(ctxt: &mut C, metrics: BigGlyphMetrics) -> Result<(), WriteError> {
+        U8::write(ctxt, metrics.height)?;
+        U8::write(ctxt, metrics.width)?;
+        I8::write(ctxt, metrics.hori_bearing_x)?;
+        I8::write(ctxt, metrics.hori_bearing_y)?;
+        U8::write(ctxt, metrics.hori_advance)?;
+        I8::write(ctxt, metrics.vert_bearing_x)?;
+        I8::write(ctxt, metrics.vert_bearing_y)?;
+        U8::write(ctxt, metrics.vert_advance)?;
+        Ok(())
+    }
+}
+
 impl<'a> ReadFrom<'a> for GlyphOffsetPair {
     type ReadType = (U16Be, U16Be);
 
@@ -1276,6 +1492,124 @@ impl<'a> GlyphBitmapData<'a> {
     }
 }
 
+impl<'a> GlyphBitmapData<'a> {
+    /// Write the bitmap to a `CBDT` or `EBDT` table being built for a subset font, returning its
+    /// image format.
+    ///
+    /// Formats 5 and 19 are written as formats 7 and 18 with the metrics included. Returns `None`,
+    /// having written nothing, if the bitmap has a component that is not in `old_to_new_glyph_id`.
+    fn write<C: WriteContext>(
+        &self,
+        ctxt: &mut C,
+        old_to_new_glyph_id: &FxHashMap<u16, u16>,
+    ) -> Result<Option<u16>, WriteError> {
+        let image_format = match self {
+            GlyphBitmapData::Format1 {
+                small_metrics,
+                data,
+            } => {
+                SmallGlyphMetrics::write(ctxt, *small_metrics)?;
+                ctxt.write_bytes(data)?;
+                1
+            }
+            GlyphBitmapData::Format2 {
+                small_metrics,
+                data,
+            } => {
+                SmallGlyphMetrics::write(ctxt, *small_metrics)?;
+                ctxt.write_bytes(data)?;
+                2
+            }
+            GlyphBitmapData::Format5 { big_metrics, data }
+            | GlyphBitmapData::Format7 { big_metrics, data } => {
+                BigGlyphMetrics::write(ctxt, *big_metrics)?;
+                ctxt.write_bytes(data)?;
+                7
+            }
+            GlyphBitmapData::Format6 { big_metrics, data } => {
+                BigGlyphMetrics::write(ctxt, *big_metrics)?;
+                ctxt.write_bytes(data)?;
+                6
+            }
+            GlyphBitmapData::Format8 {
+                small_metrics,
+                components,
+            } => {
+                let components = match remap_components(components, old_to_new_glyph_id) {
+                    Some(components) => components,
+                    None => return Ok(None),
+                };
+                SmallGlyphMetrics::write(ctxt, *small_metrics)?;
+                U8::write(ctxt, 0u8)?; // pad
+                write_components(ctxt, &components)?;
+                8
+            }
+            GlyphBitmapData::Format9 {
+                big_metrics,
+                components,
+            } => {
+                let components = match remap_components(components, old_to_new_glyph_id) {
+                    Some(components) => components,
+                    None => return Ok(None),
+                };
+                BigGlyphMetrics::write(ctxt, *big_metrics)?;
+                write_components(ctxt, &components)?;
+                9
+            }
+            GlyphBitmapData::Format17 {
+                small_metrics,
+                data,
+            } => {
+                SmallGlyphMetrics::write(ctxt, *small_metrics)?;
+                U32Be::write(ctxt, u32::try_from(data.len())?)?;
+                ctxt.write_bytes(data)?;
+                17
+            }
+            GlyphBitmapData::Format18 { big_metrics, data }
+            | GlyphBitmapData::Format19 { big_metrics, data } => {
+                BigGlyphMetrics::write(ctxt, *big_metrics)?;
+                U32Be::write(ctxt, u32::try_from(data.len())?)?;
+                ctxt.write_bytes(data)?;
+                18
+            }
+        };
+
+        Ok(Some(image_format))
+    }
+}
+
+/// Returns `components` with their glyph ids mapped to the subset font, or `None` if any are not
+/// retained.
+fn remap_components(
+    components: &ReadArray<'_, EbdtComponent>,
+    old_to_new_glyph_id: &FxHashMap<u16, u16>,
+) -> Option<Vec<EbdtComponent>> {
+    components
+        .iter()
+        .map(|component| {
+            old_to_new_glyph_id
+                .get(&component.glyph_id)
+                .map(|&glyph_id| EbdtComponent {
+                    glyph_id,
+                    ..component
+                })
+        })
+        .collect()
+}
+
+fn write_components<C: WriteContext>(
+    ctxt: &mut C,
+    components: &[EbdtComponent],
+) -> Result<(), WriteError> {
+    U16Be::write(ctxt, u16::try_from(components.len())?)?;
+    for component in components {
+        U16Be::write(ctxt, component.glyph_id)?;
+        I8::write(ctxt, component.x_offset)?;
+        I8::write(ctxt, component.y_offset)?;
+    }
+    Ok(())
+}
+
 impl<'a> fmt::Debug for GlyphBitmapData<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -1584,6 +1918,97 @@ mod tests {
         assert!(cblc.find_strike(1077, 30, BitDepth::Four).is_none());
     }
 
+    #[test]
+    fn test_subset_cblc() {
+        let cblc_data = read_fixture(Path::new("tests/fonts/opentype/CBLC.bin"));
+        let cblc = ReadScope::new(&cblc_data).read::<CBLCTable<'_>>().unwrap();
+        let cbdt_data = read_fixture(Path::new("tests/fonts/opentype/CBDT.bin"));
+        let cbdt = ReadScope::new(&cbdt_data).read::<CBDTTable<'_>>().unwrap();
+
+        // Glyph 18 has no bitmap, 1077 is Nerd Face U+1F913
+        let subset = cblc.subset(&cbdt, &[0, 4, 5, 18, 1077]).unwrap().unwrap();
+        let subset_cblc = ReadScope::new(&subset.cblc)
+            .read::<CBLCTable<'_>>()
+            .unwrap();
+        let subset_cbdt = ReadScope::new(&subset.cbdt)
+            .read::<CBDTTable<'_>>()
+            .unwrap();
+        assert_eq!(subset_cblc.major_version, cblc.major_version);
+        assert_eq!(subset_cbdt.major_version, cbdt.major_version);
+
+        let strikes = &subset_cblc.bitmap_sizes;
+        assert_eq!(strikes.len(), 1);
+        assert_eq!(strikes[0].inner.ppem_x, cblc.bitmap_sizes[0].inner.ppem_x);
+        assert_eq!(
+            (
+                strikes[0].inner.start_glyph_index,
+                strikes[0].inner.end_glyph_index
+            ),
+            (1, 4)
+        );
+        let ranges = strikes[0]
+            .index_sub_table_records
+            .iter()
+            .map(|rec| rec.first_glyph_index..=rec.last_glyph_index)
+            .collect_vec();
+        assert_eq!(ranges, &[1..=2, 4..=4]);
+
+        let strike = subset_cblc
+            .find_strike(4, 30, BitDepth::ThirtyTwo)
+            .expect("no matching strike");
+        match lookup(4, &strike, &subset_cbdt).unwrap() {
+            Some(GlyphBitmapData::Format17 {
+                data,
+                small_metrics: SmallGlyphMetrics { width, height, .. },
+            }) => {
+                assert_eq!((width, height), (136, 128));
+                assert_eq!(&data[1..4], b"PNG");
+            }
+            _ => panic!("expected PNG data got something else"),
+        }
+        assert!(subset_cblc
+            .find_strike(3, 30, BitDepth::ThirtyTwo)
+            .is_none());
+
+        // No glyphs with bitmaps
+        assert!(cblc.subset(&cbdt, &[0, 18]).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_subset_eblc() {
+        let buffer = read_fixture(Path::new("tests/fonts/opentype/TerminusTTF-4.47.0.ttf"));
+        let scope = ReadScope::new(&buffer);
+        let font_file = scope
+            .read::<FontData<'_>>()
+            .expect("unable to parse font file");
+        let table_provider = font_file
+            .table_provider(0)
+            .expect("unable to create font provider");
+        let eblc_data = table_provider.read_table_data(tag::EBLC).unwrap();
+        let eblc = ReadScope::new(&eblc_data).read::<CBLCTable<'_>>().unwrap();
+        let ebdt_data = table_provider.read_table_data(tag::EBDT).unwrap();
+        let ebdt = ReadScope::new(&ebdt_data).read::<CBDTTable<'_>>().unwrap();
+
+        let subset = eblc.subset(&ebdt, &[0, 10]).unwrap().unwrap();
+        let subset_eblc = ReadScope::new(&subset.cblc)
+            .read::<CBLCTable<'_>>()
+            .unwrap();
+        let subset_ebdt = ReadScope::new(&subset.cbdt)
+            .read::<CBDTTable<'_>>()
+            .unwrap();
+        assert_eq!(subset_eblc.major_version, 2);
+        assert_eq!(subset_eblc.bitmap_sizes.len(), 9);
+
+        // The format 5 bitmap, with metrics in EBLC, is converted to format 7
+        let strike = subset_eblc
+            .find_strike(1, 30, BitDepth::ThirtyTwo)
+            .expect("no matching strike");
+        match lookup(1, &strike, &subset_ebdt).unwrap() {
+            Some(GlyphBitmapData::Format7 { data, .. }) => assert_eq!(data.len(), 64),
+            _ => panic!("expected GlyphBitmapData::Format7 got something else"),
+        }
+    }
+
     #[test]
     fn test_unpack_bit_aligned_data() {
         let data = &[0xD3, 0xAA, 0x70];
@@ -12,9 +12,12 @@ use std::convert::TryFrom;
 use super::{
     BitDepth, Bitmap, BitmapGlyph, EncapsulatedBitmap, EncapsulatedFormat, Metrics, OriginOffset,
 };
+use rustc_hash::FxHashMap;
+
 use crate::binary::read::{CheckIndex, ReadArray, ReadBinaryDep, ReadCtxt, ReadScope};
-use crate::binary::U32Be;
-use crate::error::ParseError;
+use crate::binary::write::{WriteBinary, WriteBuffer, WriteContext};
+use crate::binary::{I16Be, U16Be, U32Be};
+use crate::error::{ParseError, ReadWriteError, WriteError};
 use crate::tag;
 
 /// `sbix` table containing bitmaps.
@@ -172,6 +175,35 @@ impl<'a> Sbix<'a> {
 
         best.map(|(_, strike)| strike)
     }
+
+    /// Subset the strikes of this table to the glyphs in `new_to_old_glyph_id`, which holds the
+    /// old glyph id of each glyph of the subset font, returning the new `sbix` table.
+    ///
+    /// `dupe` glyphs that refer to a glyph that is not retained are replaced with a copy of that
+    /// glyph.
+    pub fn subset(&self, new_to_old_glyph_id: &[u16]) -> Result<Vec<u8>, ReadWriteError> {
+        let mut old_to_new_glyph_id = FxHashMap::default();
+        for (new_id, &old_id) in new_to_old_glyph_id.iter().enumerate() {
+            old_to_new_glyph_id.insert(old_id, u16::try_from(new_id).map_err(WriteError::from)?);
+        }
+
+        let mut w = WriteBuffer::new();
+        U16Be::write(&mut w, 1u16)?; // version
+        U16Be::write(&mut w, self.flags)?;
+        U32Be::write(
+            &mut w,
+            u32::try_from(self.strikes.len()).map_err(WriteError::from)?,
+        )?;
+        let strike_offsets = (0..self.strikes.len())
+            .map(|_| w.offset_placeholder::<U32Be, u32>(0))
+            .collect::<Result<Vec<_>, _>>()?;
+        for (strike, offset) in self.strikes.iter().zip(strike_offsets) {
+            w.write_offset(offset)?;
+            strike.subset(&mut w, new_to_old_glyph_id, &old_to_new_glyph_id)?;
+        }
+
+        Ok(w.into_inner())
+    }
 }
 
 impl<'a> SbixStrike<'a> {
@@ -192,6 +224,55 @@ impl<'a> SbixStrike<'a> {
         }
     }
 
+    fn subset<C: WriteContext>(
+        &self,
+        ctxt: &mut C,
+        new_to_old_glyph_id: &[u16],
+        old_to_new_glyph_id: &FxHashMap<u16, u16>,
+    ) -> Result<(), ReadWriteError> {
+        // The glyph data follows the header: ppem, ppi and the offset of each glyph plus one
+        let header_size = 2 + 2 + 4 * (new_to_old_glyph_id.len() + 1);
+        let mut glyphs = WriteBuffer::new();
+        let mut offsets = Vec::with_capacity(new_to_old_glyph_id.len() + 1);
+        for &old_id in new_to_old_glyph_id {
+            offsets.push(header_size + glyphs.bytes_written());
+            let glyph = match self.read_glyph(old_id)? {
+                Some(glyph) => glyph,
+                None => continue,
+            };
+            I16Be::write(&mut glyphs, glyph.origin_offset_x)?;
+            I16Be::write(&mut glyphs, glyph.origin_offset_y)?;
+            if glyph.graphic_type == tag::DUPE {
+                // The data of a dupe glyph is the id of the glyph whose data it uses
+                let dupe_id = ReadScope::new(glyph.data).read::<U16Be>()?;
+                match old_to_new_glyph_id.get(&dupe_id) {
+                    Some(&new_dupe_id) => {
+                        U32Be::write(&mut glyphs, tag::DUPE)?;
+                        U16Be::write(&mut glyphs, new_dupe_id)?;
+                    }
+                    None => {
+                        let dupe = self.read_glyph(dupe_id)?.ok_or(ParseError::BadValue)?;
+                        U32Be::write(&mut glyphs, dupe.graphic_type)?;
+                        glyphs.write_bytes(dupe.data)?;
+                    }
+                }
+            } else {
+                U32Be::write(&mut glyphs, glyph.graphic_type)?;
+                glyphs.write_bytes(glyph.data)?;
+            }
+        }
+        offsets.push(header_size + glyphs.bytes_written());
+
+        U16Be::write(ctxt, self.ppem)?;
+        U16Be::write(ctxt, self.ppi)?;
+        for offset in offsets {
+            U32Be::write(ctxt, u32::try_from(offset).map_err(WriteError::from)?)?;
+        }
+        ctxt.write_bytes(glyphs.bytes())?;
+
+        Ok(())
+    }
+
     fn glyph_offset_end(&self, glyph_index: u16) -> Result<(usize, usize), ParseError> {
         // The length of the bitmap data for each glyph is variable, and can be determined from the
         // difference between two consecutive offsets. Hence, the length of data for glyph N is
@@ -252,6 +333,7 @@ mod tests {
     use super::*;
 
     use crate::binary::read::ReadScope;
+    use crate::binary::write::WriteBuffer;
     use crate::font_data::FontData;
     use crate::tables::{FontTableProvider, MaxpTable};
     use crate::tag;
@@ -306,4 +388,62 @@ mod tests {
             panic!("expected Some(SbixGlyph) got None");
         }
     }
+
+    fn write_glyph(w: &mut WriteBuffer, graphic_type: u32, data: &[u8]) {
+        I16Be::write(w, 1i16).unwrap();
+        I16Be::write(w, -2i16).unwrap();
+        U32Be::write(w, graphic_type).unwrap();
+        w.write_bytes(data).unwrap();
+    }
+
+    // An sbix table with a single strike: glyph 0 is empty, glyph 1 is a PNG and glyph 2 a dupe
+    // of glyph 1.
+    fn sbix_data() -> Vec<u8> {
+        let mut w = WriteBuffer::new();
+        for &value in &[1u16, 1, 0, 1] {
+            U16Be::write(&mut w, value).unwrap();
+        }
+        U32Be::write(&mut w, 12u32).unwrap();
+        U16Be::write(&mut w, 20u16).unwrap();
+        U16Be::write(&mut w, 72u16).unwrap();
+        for &offset in &[20u32, 20, 32, 42] {
+            U32Be::write(&mut w, offset).unwrap();
+        }
+        write_glyph(&mut w, tag::PNG, b"\x89PNG");
+        write_glyph(&mut w, tag::DUPE, &[0, 1]);
+        w.into_inner()
+    }
+
+    #[test]
+    fn test_subset_sbix() {
+        let data = sbix_data();
+        let sbix = ReadScope::new(&data).read_dep::<Sbix<'_>>(3).unwrap();
+
+        // The dupe glyph refers to the renumbered PNG glyph
+        let subset_data = sbix.subset(&[0, 2, 1]).unwrap();
+        let subset = ReadScope::new(&subset_data)
+            .read_dep::<Sbix<'_>>(3)
+            .unwrap();
+        assert_eq!(subset.flags, 1);
+        assert_eq!(subset.strikes.len(), 1);
+        let strike = &subset.strikes[0];
+        assert_eq!((strike.ppem, strike.ppi), (20, 72));
+        assert!(strike.read_glyph(0).unwrap().is_none());
+        let glyph = strike.read_glyph(1).unwrap().unwrap();
+        assert_eq!(glyph.graphic_type, tag::DUPE);
+        assert_eq!(glyph.data, &[0, 2]);
+        let glyph = strike.read_glyph(2).unwrap().unwrap();
+        assert_eq!(glyph.graphic_type, tag::PNG);
+        assert_eq!((glyph.origin_offset_x, glyph.origin_offset_y), (1, -2));
+        assert_eq!(glyph.data, b"\x89PNG");
+
+        // Without the PNG glyph the dupe glyph is replaced by a copy of it
+        let subset_data = sbix.subset(&[0, 2]).unwrap();
+        let subset = ReadScope::new(&subset_data)
+            .read_dep::<Sbix<'_>>(2)
+            .unwrap();
+        let glyph = subset.strikes[0].read_glyph(1).unwrap().unwrap();
+        assert_eq!(glyph.graphic_type, tag::PNG);
+        assert_eq!(glyph.data, b"\x89PNG");
+    }
 }
@@ -0,0 +1,457 @@
+#![deny(missing_docs)]
+
+//! Boolean operations on flattened glyph outlines.
+//!
+//! TrueType and CFF outlines may be drawn with overlapping contours, such as a stroke crossing a
+//! bowl, which are filled using the non-zero winding rule. Some renderers, including a number of
+//! PDF rasterizers and macOS, don't render overlaps well, showing seams or dropouts where contours
+//! cross. This module computes the union of the contours of an outline, which covers the same
+//! area without any overlaps, and detects whether an outline has overlaps at all so that the
+//! `OVERLAP_SIMPLE` flag of a `glyf` glyph can be set correctly.
+//!
+//! The operations work on polygons, as produced by `GlyphPoints::polygons`, so curves are
+//! approximated by line segments in the result.
+//!
+//! ```
+//! use allsorts::boolean_ops;
+//!
+//! let square = |x: f32, y: f32| vec![(x, y), (x, y + 10.), (x + 10., y + 10.), (x + 10., y)];
+//! let polygons = vec![square(0., 0.), square(5., 5.)];
+//! assert!(boolean_ops::has_overlaps(&polygons));
+//!
+//! let union = boolean_ops::union(&polygons);
+//! assert_eq!(union.len(), 1);
+//! assert_eq!(union[0].len(), 8);
+//! assert!(!boolean_ops::has_overlaps(&union));
+//! ```
+
+use std::collections::{HashMap, HashSet};
+
+use crate::error::ParseError;
+use crate::tables::glyf::{GlyphPoints, Point, SimpleGlyph};
+
+/// Distance below which points are considered to coincide, in font units.
+const EPSILON: f64 = 1e-6;
+
+/// The polygons of an outline, split into edges that only meet at their ends.
+struct Planar {
+    vertices: Vec<(f64, f64)>,
+    edges: Vec<(usize, usize)>,
+}
+
+/// Returns the union of `polygons`, filled with the non-zero winding rule.
+///
+/// Polygons are closed implicitly, so the last point may either repeat the first point or not.
+/// The returned polygons are not closed in this way and have no overlaps. Their direction follows
+/// the TrueType convention, with filled areas on the right, so outer contours are clockwise.
+pub fn union(polygons: &[Vec<(f32, f32)>]) -> Vec<Vec<(f32, f32)>> {
+    let planar = Planar::new(polygons);
+
+    // Keep the edges that separate a filled area from an unfilled one, facing the filled side to
+    // the right.
+    let mut seen = HashSet::new();
+    let mut boundary = Vec::new();
+    for &(a, b) in &planar.edges {
+        let (left, right) = planar.side_windings(a, b);
+        let edge = match (left != 0, right != 0) {
+            (false, true) => (a, b),
+            (true, false) => (b, a),
+            _ => continue,
+        };
+        // Edges that coincide are only needed once
+        if seen.insert(edge) {
+            boundary.push(edge);
+        }
+    }
+
+    planar
+        .link(&boundary)
+        .into_iter()
+        .map(|contour| {
+            simplify(
+                contour
+                    .into_iter()
+                    .map(|vertex| planar.vertices[vertex])
+                    .collect(),
+            )
+        })
+        .filter(|polygon| polygon.len() >= 3)
+        .map(|polygon| {
+            polygon
+                .into_iter()
+                .map(|(x, y)| (x as f32, y as f32))
+                .collect()
+        })
+        .collect()
+}
+
+/// Returns `true` if any of `polygons` overlap, or if any of them overlaps itself.
+///
+/// This is the case when some area is enclosed more than once under the non-zero winding rule,
+/// such as by two crossing strokes. Counter-wound holes, and contours that only touch, are not
+/// overlaps.
+pub fn has_overlaps(polygons: &[Vec<(f32, f32)>]) -> bool {
+    let planar = Planar::new(polygons);
+    planar.edges.iter().any(|&(a, b)| {
+        let (left, right) = planar.side_windings(a, b);
+        (left != 0 && right != 0) || left.abs() > 1 || right.abs() > 1
+    })
+}
+
+/// Returns a simple glyph covering the same area as `outline` without overlapping contours.
+///
+/// Curves are approximated by line segments, so all points of the result are on the curve. The
+/// result has no instructions. Use `Glyph::from_simple_glyph` to make a glyph from it.
+pub fn remove_overlaps(outline: &GlyphPoints) -> Result<SimpleGlyph, ParseError> {
+    let mut glyph = SimpleGlyph {
+        end_pts_of_contours: Vec::new(),
+        instructions: Vec::new(),
+        flags: Vec::new(),
+        coordinates: Vec::new(),
+    };
+    for polygon in union(&outline.polygons()) {
+        // The union lies within the bounds of the outline, so the points fit in an i16
+        let mut points: Vec<Point> = polygon
+            .into_iter()
+            .map(|(x, y)| Point(x.round() as i16, y.round() as i16))
+            .collect();
+        points.dedup();
+        if points.len() > 1 && points.first() == points.last() {
+            points.pop();
+        }
+        if points.len() >= 3 {
+            glyph.push_contour(points.into_iter().map(|point| (point, true)))?;
+        }
+    }
+    Ok(glyph)
+}
+
+impl Planar {
+    fn new(polygons: &[Vec<(f32, f32)>]) -> Self {
+        let mut vertices = Vec::new();
+        let mut vertex_ids = HashMap::new();
+        let mut edges = Vec::new();
+        for polygon in polygons {
+            let ids = polygon
+                .iter()
+                .map(|&(x, y)| {
+                    // Adding 0.0 turns -0.0 into 0.0 so that they're considered the same
+                    let vertex = (f64::from(x) + 0.0, f64::from(y) + 0.0);
+                    *vertex_ids
+                        .entry((vertex.0.to_bits(), vertex.1.to_bits()))
+                        .or_insert_with(|| {
+                            vertices.push(vertex);
+                            vertices.len() - 1
+                        })
+                })
+                .collect::<Vec<_>>();
+            for (i, &a) in ids.iter().enumerate() {
+                let b = ids[(i + 1) % ids.len()];
+                if a != b {
+                    edges.push((a, b));
+                }
+            }
+        }
+
+        let mut planar = Planar { vertices, edges };
+        planar.split_edges();
+        planar
+    }
+
+    /// Split the edges at the points where they cross or touch other edges.
+    fn split_edges(&mut self) {
+        // The vertices each edge is to be split at, with their position along it
+        let mut splits: Vec<Vec<(f64, usize)>> = vec![Vec::new(); self.edges.len()];
+        for i in 0..self.edges.len() {
+            for j in i + 1..self.edges.len() {
+                self.intersect(i, j, &mut splits);
+            }
+        }
+
+        let mut edges = Vec::with_capacity(self.edges.len());
+        for (&(a, b), mut split) in self.edges.iter().zip(splits) {
+            split.sort_by(|x, y| x.0.partial_cmp(&y.0).unwrap_or(std::cmp::Ordering::Equal));
+            let mut from = a;
+            for to in split.into_iter().map(|(_, vertex)| vertex).chain(Some(b)) {
+                if to != from {
+                    edges.push((from, to));
+                    from = to;
+                }
+            }
+        }
+        self.edges = edges;
+    }
+
+    /// Record where edges `i` and `j` need to be split so that they only meet at their ends.
+    fn intersect(&mut self, i: usize, j: usize, splits: &mut [Vec<(f64, usize)>]) {
+        let (a, b) = self.edges[i];
+        let (c, d) = self.edges[j];
+        let (p, p2, q, q2) = (
+            self.vertices[a],
+            self.vertices[b],
+            self.vertices[c],
+            self.vertices[d],
+        );
+        if p.0.max(p2.0) < q.0.min(q2.0) - EPSILON
+            || q.0.max(q2.0) < p.0.min(p2.0) - EPSILON
+            || p.1.max(p2.1) < q.1.min(q2.1) - EPSILON
+            || q.1.max(q2.1) < p.1.min(p2.1) - EPSILON
+        {
+            return;
+        }
+
+        let r = sub(p2, p);
+        let s = sub(q2, q);
+        let (r_len, s_len) = (length(r), length(s));
+        let denom = cross(r, s);
+        if denom.abs() <= EPSILON * r_len * s_len {
+            // Parallel edges only need splitting if they overlap, in which case each is split at
+            // the ends of the other that lie within it.
+            if (cross(sub(q, p), r) / r_len).abs() > EPSILON {
+                return;
+            }
+            for &(vertex, point) in &[(c, q), (d, q2)] {
+                let t = dot(sub(point, p), r) / (r_len * r_len);
+                if t * r_len > EPSILON && (1. - t) * r_len > EPSILON {
+                    splits[i].push((t, vertex));
+                }
+            }
+            for &(vertex, point) in &[(a, p), (b, p2)] {
+                let u = dot(sub(point, q), s) / (s_len * s_len);
+                if u * s_len > EPSILON && (1. - u) * s_len > EPSILON {
+                    splits[j].push((u, vertex));
+                }
+            }
+            return;
+        }
+
+        let t = cross(sub(q, p), s) / denom;
+        let u = cross(sub(q, p), r) / denom;
+        let (t_start, t_end) = (t * r_len <= EPSILON, (1. - t) * r_len <= EPSILON);
+        let (u_start, u_end) = (u * s_len <= EPSILON, (1. - u) * s_len <= EPSILON);
+        if t * r_len < -EPSILON
+            || (t - 1.) * r_len > EPSILON
+            || u * s_len < -EPSILON
+            || (u - 1.) * s_len > EPSILON
+        {
+            return;
+        }
+
+        // Prefer an existing vertex where the edges meet at, or near, the end of one of them
+        let vertex = if t_start {
+            a
+        } else if t_end {
+            b
+        } else if u_start {
+            c
+        } else if u_end {
+            d
+        } else {
+            self.vertices.push((p.0 + t * r.0, p.1 + t * r.1));
+            self.vertices.len() - 1
+        };
+        if !t_start && !t_end {
+            splits[i].push((t, vertex));
+        }
+        if !u_start && !u_end {
+            splits[j].push((u, vertex));
+        }
+    }
+
+    /// The winding numbers of the areas to the left and right of the middle of edge `a`–`b`.
+    fn side_windings(&self, a: usize, b: usize) -> (i32, i32) {
+        let (p, q) = (self.vertices[a], self.vertices[b]);
+        let direction = sub(q, p);
+        let len = length(direction);
+        let offset = (len * 1e-3).min(1e-2) / len;
+        let normal = (-direction.1 * offset, direction.0 * offset);
+        let mid = ((p.0 + q.0) / 2., (p.1 + q.1) / 2.);
+        (
+            self.winding((mid.0 + normal.0, mid.1 + normal.1)),
+            self.winding((mid.0 - normal.0, mid.1 - normal.1)),
+        )
+    }
+
+    /// The non-zero winding number of `point`.
+    fn winding(&self, point: (f64, f64)) -> i32 {
+        let mut winding = 0;
+        for &(a, b) in &self.edges {
+            let (p, q) = (self.vertices[a], self.vertices[b]);
+            let side = cross(sub(q, p), sub(point, p));
+            if p.1 <= point.1 && point.1 < q.1 && side > 0. {
+                winding += 1;
+            } else if q.1 <= point.1 && point.1 < p.1 && side < 0. {
+                winding -= 1;
+            }
+        }
+        winding
+    }
+
+    /// Join directed `edges` into closed contours of vertices.
+    fn link(&self, edges: &[(usize, usize)]) -> Vec<Vec<usize>> {
+        let mut outgoing: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (index, &(from, _)) in edges.iter().enumerate() {
+            outgoing.entry(from).or_default().push(index);
+        }
+
+        let mut used = vec![false; edges.len()];
+        let mut contours = Vec::new();
+        for start_edge in 0..edges.len() {
+            if used[start_edge] {
+                continue;
+            }
+            let start = edges[start_edge].0;
+            let mut contour = Vec::new();
+            let mut edge = start_edge;
+            loop {
+                used[edge] = true;
+                let (from, to) = edges[edge];
+                contour.push(from);
+                if to == start {
+                    break;
+                }
+                let next = outgoing
+                    .get(&to)
+                    .and_then(|candidates| candidates.iter().find(|&&next| !used[next]));
+                match next {
+                    Some(&next) => edge = next,
+                    // The boundary is always closed, but don't loop forever if rounding broke it
+                    None => break,
+                }
+            }
+            contours.push(contour);
+        }
+        contours
+    }
+}
+
+/// Remove the points of `polygon` that lie on a straight line between their neighbours.
+fn simplify(mut polygon: Vec<(f64, f64)>) -> Vec<(f64, f64)> {
+    let mut i = 0;
+    while polygon.len() >= 3 && i < polygon.len() {
+        let n = polygon.len();
+        let prev = polygon[(i + n - 1) % n];
+        let next = polygon[(i + 1) % n];
+        let (to, from) = (sub(polygon[i], prev), sub(next, polygon[i]));
+        let straight =
+            cross(to, from).abs() <= EPSILON * length(to).max(length(from)) && dot(to, from) >= 0.;
+        if straight {
+            polygon.remove(i);
+            i = i.saturating_sub(1);
+        } else {
+            i += 1;
+        }
+    }
+    polygon
+}
+
+fn sub(a: (f64, f64), b: (f64, f64)) -> (f64, f64) {
+    (a.0 - b.0, a.1 - b.1)
+}
+
+fn cross(a: (f64, f64), b: (f64, f64)) -> f64 {
+    a.0 * b.1 - a.1 * b.0
+}
+
+fn dot(a: (f64, f64), b: (f64, f64)) -> f64 {
+    a.0 * b.0 + a.1 * b.1
+}
+
+fn length(a: (f64, f64)) -> f64 {
+    a.0.hypot(a.1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(x: f32, y: f32, size: f32) -> Vec<(f32, f32)> {
+        // Clockwise, as outer TrueType contours are
+        vec![(x, y), (x, y + size), (x + size, y + size), (x + size, y)]
+    }
+
+    fn area(polygon: &[(f32, f32)]) -> f32 {
+        let n = polygon.len();
+        (0..n)
+            .map(|i| {
+                let (a, b) = (polygon[i], polygon[(i + 1) % n]);
+                a.0 * b.1 - b.0 * a.1
+            })
+            .sum::<f32>()
+            / 2.
+    }
+
+    #[test]
+    fn test_union_overlapping_squares() {
+        let union = union(&[square(0., 0., 10.), square(5., 5., 10.)]);
+        assert_eq!(union.len(), 1);
+        // Two squares of area 100 overlapping by 25, wound clockwise
+        assert_eq!(area(&union[0]), -175.);
+    }
+
+    #[test]
+    fn test_union_disjoint_and_hole() {
+        let mut hole = square(2., 2., 6.);
+        hole.reverse();
+        let polygons = vec![square(0., 0., 10.), hole, square(20., 0., 5.)];
+        assert!(!has_overlaps(&polygons));
+        let union = union(&polygons);
+        let mut areas = union
+            .iter()
+            .map(|polygon| area(polygon))
+            .collect::<Vec<_>>();
+        areas.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(areas, vec![-100., -25., 36.]);
+    }
+
+    #[test]
+    fn test_union_touching_and_coincident() {
+        // Squares sharing an edge merge into one rectangle
+        let union = union(&[square(0., 0., 10.), square(10., 0., 10.)]);
+        assert_eq!(union.len(), 1);
+        assert_eq!(union[0].len(), 4);
+        assert_eq!(area(&union[0]), -200.);
+
+        // A contour drawn twice covers its area twice
+        let polygons = vec![square(0., 0., 10.), square(0., 0., 10.)];
+        assert!(has_overlaps(&polygons));
+        let union = super::union(&polygons);
+        assert_eq!(union.len(), 1);
+        assert_eq!(area(&union[0]), -100.);
+    }
+
+    #[test]
+    fn test_remove_overlaps() {
+        // A plus sign made of two crossing bars
+        let vertical = square(40., 0., 20.)
+            .into_iter()
+            .map(|(x, y)| (x, y * 5.))
+            .collect::<Vec<_>>();
+        let horizontal = square(0., 40., 20.)
+            .into_iter()
+            .map(|(x, y)| (x * 5., y))
+            .collect::<Vec<_>>();
+        let mut outline = GlyphPoints::default();
+        for polygon in &[horizontal, vertical] {
+            for &(x, y) in polygon {
+                outline.points.push(Point(x as i16, y as i16));
+                outline.on_curve.push(true);
+            }
+            outline
+                .end_pts_of_contours
+                .push(outline.points.len() as u16 - 1);
+        }
+        assert!(has_overlaps(&outline.polygons()));
+
+        let glyph = remove_overlaps(&outline).unwrap();
+        assert_eq!(glyph.number_of_contours(), 1);
+        assert_eq!(glyph.coordinates.len(), 12);
+        assert!(glyph.flags.iter().all(|flag| flag.is_on_curve()));
+        let points = glyph
+            .coordinates
+            .iter()
+            .map(|&Point(x, y)| (f32::from(x), f32::from(y)))
+            .collect::<Vec<_>>();
+        assert_eq!(area(&points), -3600.);
+    }
+}
@@ -0,0 +1,549 @@
+//! C API for shaping text and extracting glyph outlines.
+//!
+//! Enabled with the `capi` feature. The functions in this module are exported with unmangled
+//! names and the C calling convention so that applications written in other languages can use
+//! Allsorts in place of HarfBuzz and FreeType. `include/allsorts.h` declares them for C.
+//!
+//! Objects are created by the `allsorts_*_create` functions and `allsorts_shape`, and must be
+//! released with the matching `allsorts_*_free` function. Functions that create objects return a
+//! null pointer on failure. Panics are caught at the API boundary and reported as failures too.
+//!
+//! The library is only built as a Rust library by default. To obtain a static library for
+//! linking into a C program run:
+//!
+//! ```text
+//! cargo rustc --release --features capi --crate-type staticlib
+//! ```
+//!
+//! Programs linking the static library also need to link zlib (`-lz`).
+
+use std::os::raw::c_int;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+use std::str;
+
+use crate::font::OwnedFont;
+use crate::gpos::TextDirection;
+use crate::gsub::{FeatureSetting, Features, GsubFeatureMask};
+use crate::tables::glyf::GlyphPoints;
+use crate::tables::F2Dot14;
+use crate::Font;
+
+/// A font, created with `allsorts_font_create`.
+pub struct AllsortsFont {
+    font: OwnedFont,
+}
+
+/// The shaped glyphs of a run of text, created with `allsorts_shape`.
+pub struct AllsortsGlyphBuffer {
+    glyphs: Vec<AllsortsGlyph>,
+}
+
+/// The drawing commands of a glyph outline, created with `allsorts_outline_create`.
+pub struct AllsortsOutline {
+    commands: Vec<AllsortsOutlineCommand>,
+}
+
+/// An OpenType feature to enable or disable when shaping.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct AllsortsFeature {
+    pub tag: u32,
+    pub enabled: bool,
+}
+
+/// Options for `allsorts_shape`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct AllsortsShapeOptions {
+    /// The OpenType script tag of the text.
+    pub script_tag: u32,
+    /// The OpenType language tag, or 0 for the default language system.
+    pub lang_tag: u32,
+    /// The features to apply, an array of `num_features` elements. When `num_features` is 0 the
+    /// default features are applied.
+    pub features: *const AllsortsFeature,
+    pub num_features: usize,
+    /// Shape the text right-to-left.
+    pub right_to_left: bool,
+}
+
+/// A shaped glyph and its position, in font units.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AllsortsGlyph {
+    pub glyph_id: u16,
+    pub x_advance: i32,
+    pub y_advance: i32,
+    pub x_offset: i32,
+    pub y_offset: i32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AllsortsVerb {
+    /// Start a new contour at `(x, y)`.
+    MoveTo = 0,
+    /// Draw a line to `(x, y)`.
+    LineTo = 1,
+    /// Draw a quadratic Bézier curve with control point `(cx, cy)` to `(x, y)`.
+    QuadTo = 2,
+    /// Close the contour with a line back to its start.
+    Close = 3,
+}
+
+/// A glyph outline drawing command, in font units with y pointing up.
+///
+/// Coordinates that are not used by the verb are zero.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AllsortsOutlineCommand {
+    pub verb: AllsortsVerb,
+    pub cx: f32,
+    pub cy: f32,
+    pub x: f32,
+    pub y: f32,
+}
+
+/// Parse a font from `len` bytes at `data`.
+///
+/// The data is copied, so it does not need to outlive the font. `index` selects the font to use
+/// when the data holds a font collection. Returns null if the font could not be parsed or has no
+/// suitable `cmap` sub-table.
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn allsorts_font_create(
+    data: *const u8,
+    len: usize,
+    index: u32,
+) -> *mut AllsortsFont {
+    if data.is_null() {
+        return ptr::null_mut();
+    }
+    let data = slice::from_raw_parts(data, len).to_vec();
+    catch_unwind(|| match Font::from_data(data, index as usize) {
+        Ok(Some(font)) => Box::into_raw(Box::new(AllsortsFont { font })),
+        Ok(None) | Err(_) => ptr::null_mut(),
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+/// Free a font created with `allsorts_font_create`. Passing null does nothing.
+///
+/// # Safety
+///
+/// `font` must be null or a font returned by `allsorts_font_create` that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn allsorts_font_free(font: *mut AllsortsFont) {
+    if !font.is_null() {
+        drop(Box::from_raw(font));
+    }
+}
+
+/// The number of glyphs in the font.
+///
+/// # Safety
+///
+/// `font` must be null or a valid font.
+#[no_mangle]
+pub unsafe extern "C" fn allsorts_font_num_glyphs(font: *const AllsortsFont) -> u16 {
+    match font.as_ref() {
+        Some(font) => font.font.num_glyphs(),
+        None => 0,
+    }
+}
+
+/// The number of font units per em, or 0 if the `head` table could not be read.
+///
+/// # Safety
+///
+/// `font` must be null or a valid font.
+#[no_mangle]
+pub unsafe extern "C" fn allsorts_font_units_per_em(font: *const AllsortsFont) -> u16 {
+    match font.as_ref().map(|font| font.font.head_table()) {
+        Some(Ok(Some(head))) => head.units_per_em,
+        _ => 0,
+    }
+}
+
+/// Shape `len` bytes of UTF-8 `text` as a single run.
+///
+/// Right-to-left runs are returned in visual order, left to right. Splitting text into runs of a
+/// single script and direction, and mirroring characters, are up to the caller.
+///
+/// Returns null if the text is not valid UTF-8 or shaping failed.
+///
+/// # Safety
+///
+/// `font` must be null or a valid font that is not in use by another thread. `text` must point to
+/// `len` readable bytes, and `options.features` to `options.num_features` features.
+#[no_mangle]
+pub unsafe extern "C" fn allsorts_shape(
+    font: *mut AllsortsFont,
+    text: *const u8,
+    len: usize,
+    options: *const AllsortsShapeOptions,
+) -> *mut AllsortsGlyphBuffer {
+    let (font, options) = match (font.as_mut(), options.as_ref()) {
+        (Some(font), Some(options)) => (&mut font.font, options),
+        _ => return ptr::null_mut(),
+    };
+    if text.is_null() || (options.features.is_null() && options.num_features != 0) {
+        return ptr::null_mut();
+    }
+    let text = match str::from_utf8(slice::from_raw_parts(text, len)) {
+        Ok(text) => text,
+        Err(_) => return ptr::null_mut(),
+    };
+    let features = if options.num_features == 0 {
+        Features::Mask(GsubFeatureMask::default())
+    } else {
+        Features::Settings(
+            slice::from_raw_parts(options.features, options.num_features)
+                .iter()
+                .map(|feature| {
+                    if feature.enabled {
+                        FeatureSetting::on(feature.tag)
+                    } else {
+                        FeatureSetting::off(feature.tag)
+                    }
+                })
+                .collect(),
+        )
+    };
+    let script_tag = options.script_tag;
+    let lang_tag = if options.lang_tag == 0 {
+        None
+    } else {
+        Some(options.lang_tag)
+    };
+    let direction = if options.right_to_left {
+        TextDirection::RightToLeft
+    } else {
+        TextDirection::LeftToRight
+    };
+
+    catch_unwind(|| {
+        let infos = match font.shape_text(text, script_tag, lang_tag, &features) {
+            Ok(infos) => infos,
+            Err(_) => return ptr::null_mut(),
+        };
+        let positions = font.glyph_positions(&infos, direction);
+        let mut glyphs = infos
+            .iter()
+            .zip(positions)
+            .map(|(info, position)| AllsortsGlyph {
+                glyph_id: info.glyph.glyph_index,
+                x_advance: position.x_advance,
+                y_advance: position.y_advance,
+                x_offset: position.x_offset,
+                y_offset: position.y_offset,
+            })
+            .collect::<Vec<_>>();
+        if direction == TextDirection::RightToLeft {
+            glyphs.reverse();
+        }
+        Box::into_raw(Box::new(AllsortsGlyphBuffer { glyphs }))
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+/// The number of glyphs in the buffer.
+///
+/// # Safety
+///
+/// `buffer` must be null or a valid glyph buffer.
+#[no_mangle]
+pub unsafe extern "C" fn allsorts_glyph_buffer_len(buffer: *const AllsortsGlyphBuffer) -> usize {
+    buffer.as_ref().map_or(0, |buffer| buffer.glyphs.len())
+}
+
+/// The glyphs in the buffer, an array of `allsorts_glyph_buffer_len` elements that is valid until
+/// the buffer is freed.
+///
+/// # Safety
+///
+/// `buffer` must be null or a valid glyph buffer.
+#[no_mangle]
+pub unsafe extern "C" fn allsorts_glyph_buffer_glyphs(
+    buffer: *const AllsortsGlyphBuffer,
+) -> *const AllsortsGlyph {
+    buffer
+        .as_ref()
+        .map_or(ptr::null(), |buffer| buffer.glyphs.as_ptr())
+}
+
+/// Free a buffer created with `allsorts_shape`. Passing null does nothing.
+///
+/// # Safety
+///
+/// `buffer` must be null or a buffer returned by `allsorts_shape` that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn allsorts_glyph_buffer_free(buffer: *mut AllsortsGlyphBuffer) {
+    if !buffer.is_null() {
+        drop(Box::from_raw(buffer));
+    }
+}
+
+/// Extract the outline of `glyph_id` as drawing commands.
+///
+/// `coords` holds `num_coords` normalised variation coordinates in 2.14 fixed point format, one
+/// per axis. Pass 0 for `num_coords` for the default instance. Returns null if the glyph could
+/// not be read or the font has no `glyf` outlines. Glyphs without contours, such as spaces, have
+/// an outline with no commands.
+///
+/// # Safety
+///
+/// `font` must be null or a valid font that is not in use by another thread. `coords` must point
+/// to `num_coords` readable values.
+#[no_mangle]
+pub unsafe extern "C" fn allsorts_outline_create(
+    font: *mut AllsortsFont,
+    glyph_id: u16,
+    coords: *const i16,
+    num_coords: usize,
+) -> *mut AllsortsOutline {
+    let font = match font.as_mut() {
+        Some(font) => &mut font.font,
+        None => return ptr::null_mut(),
+    };
+    let coords = if num_coords == 0 {
+        Vec::new()
+    } else if coords.is_null() {
+        return ptr::null_mut();
+    } else {
+        slice::from_raw_parts(coords, num_coords)
+            .iter()
+            .map(|&coord| F2Dot14::new(coord as u16))
+            .collect()
+    };
+
+    catch_unwind(|| match font.outline(glyph_id, &coords) {
+        Ok(Some(outline)) => Box::into_raw(Box::new(AllsortsOutline {
+            commands: outline_commands(&outline),
+        })),
+        Ok(None) | Err(_) => ptr::null_mut(),
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+/// The number of commands in the outline.
+///
+/// # Safety
+///
+/// `outline` must be null or a valid outline.
+#[no_mangle]
+pub unsafe extern "C" fn allsorts_outline_len(outline: *const AllsortsOutline) -> usize {
+    outline.as_ref().map_or(0, |outline| outline.commands.len())
+}
+
+/// The commands of the outline, an array of `allsorts_outline_len` elements that is valid until
+/// the outline is freed.
+///
+/// # Safety
+///
+/// `outline` must be null or a valid outline.
+#[no_mangle]
+pub unsafe extern "C" fn allsorts_outline_commands(
+    outline: *const AllsortsOutline,
+) -> *const AllsortsOutlineCommand {
+    outline
+        .as_ref()
+        .map_or(ptr::null(), |outline| outline.commands.as_ptr())
+}
+
+/// Free an outline created with `allsorts_outline_create`. Passing null does nothing.
+///
+/// # Safety
+///
+/// `outline` must be null or an outline returned by `allsorts_outline_create` that has not been
+/// freed.
+#[no_mangle]
+pub unsafe extern "C" fn allsorts_outline_free(outline: *mut AllsortsOutline) {
+    if !outline.is_null() {
+        drop(Box::from_raw(outline));
+    }
+}
+
+/// The version of the C API. This is incremented when the API changes incompatibly.
+#[no_mangle]
+pub extern "C" fn allsorts_capi_version() -> c_int {
+    1
+}
+
+fn catch_unwind<R>(f: impl FnOnce() -> R) -> Option<R> {
+    panic::catch_unwind(AssertUnwindSafe(f)).ok()
+}
+
+/// Convert TrueType quadratic contours into drawing commands.
+///
+/// Consecutive off-curve points have an implied on-curve point midway between them. A contour
+/// that consists only of off-curve points starts at the midpoint of its last and first points.
+fn outline_commands(outline: &GlyphPoints) -> Vec<AllsortsOutlineCommand> {
+    let mut commands = Vec::new();
+    let mut start = 0;
+    for &end in &outline.end_pts_of_contours {
+        let end = usize::from(end) + 1;
+        if end <= start || end > outline.points.len() || end > outline.on_curve.len() {
+            break;
+        }
+        let points = outline.points[start..end]
+            .iter()
+            .map(|point| (f32::from(point.0), f32::from(point.1)))
+            .collect::<Vec<_>>();
+        contour_commands(&points, &outline.on_curve[start..end], &mut commands);
+        start = end;
+    }
+    commands
+}
+
+fn contour_commands(
+    points: &[(f32, f32)],
+    on_curve: &[bool],
+    commands: &mut Vec<AllsortsOutlineCommand>,
+) {
+    let midpoint = |a: (f32, f32), b: (f32, f32)| ((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0);
+    let last = points.len() - 1;
+
+    // Start at the first on-curve point, rotating the remaining points to follow it
+    let (first, rest) = match on_curve.iter().position(|&on| on) {
+        Some(i) => (points[i], i + 1..i + points.len()),
+        None => (midpoint(points[last], points[0]), 0..points.len()),
+    };
+    commands.push(command(AllsortsVerb::MoveTo, (0.0, 0.0), first));
+
+    let mut control = None;
+    for i in rest {
+        let (point, on) = (points[i % points.len()], on_curve[i % points.len()]);
+        match (control, on) {
+            (None, true) => commands.push(command(AllsortsVerb::LineTo, (0.0, 0.0), point)),
+            (None, false) => control = Some(point),
+            (Some(c), true) => {
+                commands.push(command(AllsortsVerb::QuadTo, c, point));
+                control = None;
+            }
+            (Some(c), false) => {
+                commands.push(command(AllsortsVerb::QuadTo, c, midpoint(c, point)));
+                control = Some(point);
+            }
+        }
+    }
+    if let Some(c) = control {
+        commands.push(command(AllsortsVerb::QuadTo, c, first));
+    }
+    commands.push(command(AllsortsVerb::Close, (0.0, 0.0), (0.0, 0.0)));
+}
+
+fn command(verb: AllsortsVerb, control: (f32, f32), to: (f32, f32)) -> AllsortsOutlineCommand {
+    AllsortsOutlineCommand {
+        verb,
+        cx: control.0,
+        cy: control.1,
+        x: to.0,
+        y: to.1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tables::glyf::Point;
+    use crate::tag;
+    use crate::tests::read_fixture;
+
+    fn options(script_tag: u32) -> AllsortsShapeOptions {
+        AllsortsShapeOptions {
+            script_tag,
+            lang_tag: 0,
+            features: ptr::null(),
+            num_features: 0,
+            right_to_left: false,
+        }
+    }
+
+    #[test]
+    fn test_shape() {
+        let data = read_fixture("tests/fonts/opentype/Klei.otf");
+        let text = "Klei";
+        unsafe {
+            let font = allsorts_font_create(data.as_ptr(), data.len(), 0);
+            assert!(!font.is_null());
+            let buffer = allsorts_shape(font, text.as_ptr(), text.len(), &options(tag::LATN));
+            assert!(!buffer.is_null());
+            let glyphs = slice::from_raw_parts(
+                allsorts_glyph_buffer_glyphs(buffer),
+                allsorts_glyph_buffer_len(buffer),
+            );
+            assert_eq!(glyphs.len(), 4);
+            assert!(glyphs.iter().all(|glyph| glyph.x_advance > 0));
+            allsorts_glyph_buffer_free(buffer);
+            allsorts_font_free(font);
+        }
+    }
+
+    #[test]
+    fn test_invalid_input() {
+        let data = b"not a font";
+        unsafe {
+            assert!(allsorts_font_create(data.as_ptr(), data.len(), 0).is_null());
+            assert!(allsorts_font_create(ptr::null(), 0, 0).is_null());
+            let options = options(tag::LATN);
+            assert!(allsorts_shape(ptr::null_mut(), data.as_ptr(), data.len(), &options).is_null());
+            allsorts_font_free(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn test_outline() {
+        let data = read_fixture("tests/fonts/opentype/test-font.ttf");
+        unsafe {
+            let font = allsorts_font_create(data.as_ptr(), data.len(), 0);
+            let outline = allsorts_outline_create(font, 2, ptr::null(), 0);
+            assert!(!outline.is_null());
+            let commands = slice::from_raw_parts(
+                allsorts_outline_commands(outline),
+                allsorts_outline_len(outline),
+            );
+            let verbs = commands
+                .iter()
+                .map(|command| command.verb)
+                .collect::<Vec<_>>();
+            assert_eq!(
+                verbs,
+                vec![
+                    AllsortsVerb::MoveTo,
+                    AllsortsVerb::LineTo,
+                    AllsortsVerb::LineTo,
+                    AllsortsVerb::Close
+                ]
+            );
+            assert_eq!((commands[0].x, commands[0].y), (1761.0, 565.0));
+            allsorts_outline_free(outline);
+            allsorts_font_free(font);
+        }
+    }
+
+    #[test]
+    fn test_off_curve_contour() {
+        // A contour of only off-curve points starts at an implied on-curve point
+        let outline = GlyphPoints {
+            points: vec![Point(0, 0), Point(100, 0), Point(100, 100), Point(0, 100)],
+            on_curve: vec![false; 4],
+            end_pts_of_contours: vec![3],
+        };
+        let commands = outline_commands(&outline);
+        let expected = vec![
+            command(AllsortsVerb::MoveTo, (0.0, 0.0), (0.0, 50.0)),
+            command(AllsortsVerb::QuadTo, (0.0, 0.0), (50.0, 0.0)),
+            command(AllsortsVerb::QuadTo, (100.0, 0.0), (100.0, 50.0)),
+            command(AllsortsVerb::QuadTo, (100.0, 100.0), (50.0, 100.0)),
+            command(AllsortsVerb::QuadTo, (0.0, 100.0), (0.0, 50.0)),
+            command(AllsortsVerb::Close, (0.0, 0.0), (0.0, 0.0)),
+        ];
+        assert_eq!(commands, expected);
+    }
+}
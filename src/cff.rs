@@ -20,6 +20,9 @@ use crate::binary::read::{
 use crate::binary::write::{WriteBinary, WriteBinaryDep, WriteBuffer, WriteContext, WriteCounter};
 use crate::binary::{I16Be, I32Be, U16Be, U24Be, U32Be, U8};
 use crate::error::{ParseError, WriteError};
+use crate::tables::glyf::BoundingBox;
+
+mod charstring;
 
 // CFF Spec: An operator may be preceded by up to a maximum of 48 operands.
 const MAX_OPERANDS: usize = 48;
@@ -502,6 +505,19 @@ impl<'a> CFF<'a> {
         read_string_index_string(&self.string_index, sid)
     }
 
+    /// Returns the bounding box of the outline of `glyph_id` in the font at `font_index`.
+    ///
+    /// The box encloses the curves of the outline rather than their control points, rounded
+    /// outwards to whole units. `None` is returned for glyphs without an outline.
+    pub fn glyph_bounding_box(
+        &self,
+        font_index: usize,
+        glyph_id: u16,
+    ) -> Result<Option<BoundingBox>, ParseError> {
+        let font = self.fonts.get(font_index).ok_or(ParseError::BadIndex)?;
+        charstring::bounding_box(self, font, glyph_id)
+    }
+
     /// The `Vec<u16>` in the output is a mapping from new to old glyph id.
     ///
     /// `glpyh_ids` contains the ids of the glyphs to retain.
@@ -603,6 +619,34 @@ impl<'a> CFF<'a> {
 
         Ok((cff, new_to_old_id))
     }
+
+    /// Create a subset of this CFF font that keeps the ids of the glyphs in `glyph_ids`.
+    ///
+    /// Glyphs that are not retained are replaced with empty glyphs, up to the highest retained
+    /// glyph id. Returns the subset font along with the old id of each glyph, which is the same
+    /// as its new id.
+    pub fn subset_retaining_ids(
+        &self,
+        glyph_ids: &[u16],
+        convert_cff_to_cid_if_more_than_255_glyphs: bool,
+    ) -> Result<(Self, Vec<u16>), ParseError> {
+        let mut glyph_ids = glyph_ids.to_vec();
+        glyph_ids.sort_unstable();
+        let max_glyph_id = glyph_ids.last().copied().unwrap_or(0);
+        let all_glyph_ids = (0..=max_glyph_id).collect::<Vec<_>>();
+        let (mut cff, new_to_old_id) =
+            self.subset(&all_glyph_ids, convert_cff_to_cid_if_more_than_255_glyphs)?;
+        if let MaybeOwnedIndex::Owned(index) = &mut cff.fonts[0].char_strings_index {
+            for (glyph_id, char_string) in index.data.iter_mut().enumerate() {
+                // Keep .notdef as it is always retained
+                if glyph_id != 0 && glyph_ids.binary_search(&(glyph_id as u16)).is_err() {
+                    // An empty Type 2 charstring: endchar
+                    *char_string = vec![14];
+                }
+            }
+        }
+        Ok((cff, new_to_old_id))
+    }
 }
 
 /// Read a string with the given SID from the String INDEX
@@ -787,7 +831,10 @@ impl<'a> ReadBinary<'a> for Index<'a> {
                 return Err(ParseError::BadValue);
             }
 
-            let offset_array_size = (count + 1) * usize::from(off_size);
+            let offset_array_size = count
+                .checked_add(1)
+                .and_then(|count| count.checked_mul(usize::from(off_size)))
+                .ok_or(ParseError::LimitExceeded)?;
             let offset_array = ctxt.read_slice(offset_array_size)?;
 
             let last_offset_index = lookup_offset_index(off_size, offset_array, count);
@@ -2159,7 +2206,7 @@ fn serialise_offset_array(offsets: Vec<usize>) -> Result<(u8, Vec<u8>), WriteErr
     Ok((off_size, offset_array.into_inner()))
 }
 
-const STANDARD_STRINGS: [&str; 391] = [
+pub(crate) const STANDARD_STRINGS: [&str; 391] = [
     ".notdef",
     "space",
     "exclam",
@@ -2816,6 +2863,7 @@ const EXPERT_SUBSET_CHARSET: [u16; 87] = [
 mod tests {
     use super::*;
     use crate::binary::read::ReadScope;
+    use crate::error::ParseErrorKind;
 
     #[test]
     fn test_iter_index() {
@@ -3004,7 +3052,7 @@ mod tests {
     fn test_read_top_dict_operand_limit() {
         let mut ctxt = ReadScope::new(&[0x8c; MAX_OPERANDS + 1]).ctxt();
         match TopDict::read(&mut ctxt) {
-            Err(ParseError::LimitExceeded) => {}
+            Err(err) if err.kind() == ParseErrorKind::LimitExceeded => {}
             _ => panic!("expected Err(ParseError::LimitExceeded) got something else"),
         }
     }
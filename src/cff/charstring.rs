@@ -0,0 +1,570 @@
+//! Measuring the outlines described by Type 2 charstrings.
+//!
+//! Refer to [Technical Note #5177](https://adobe-type-tools.github.io/font-tech-notes/pdfs/5177.Type2.pdf)
+//! for the format of charstrings.
+
+use std::convert::TryFrom;
+
+use super::{CFFVariant, Font, Index, CFF};
+use crate::error::ParseError;
+use crate::tables::glyf::BoundingBox;
+
+// Type 2 Spec: The argument stack may hold up to 48 operands.
+const MAX_ARGS: usize = 48;
+// Type 2 Spec: Subroutines may be nested up to 10 levels deep.
+const MAX_SUBR_DEPTH: usize = 10;
+
+/// The extent of the points of an outline as it is interpreted.
+#[derive(Debug, Copy, Clone)]
+struct Bounds {
+    x_min: f32,
+    y_min: f32,
+    x_max: f32,
+    y_max: f32,
+}
+
+/// Interpreter for the charstring of a single glyph, tracking the bounds of its outline.
+struct BoundsInterpreter<'a, 'b> {
+    cff: &'b CFF<'a>,
+    font: &'b Font<'a>,
+    local_subrs: Option<&'b Index<'a>>,
+    stack: Vec<f32>,
+    x: f32,
+    y: f32,
+    num_stems: usize,
+    /// Set once the first stack clearing operator, which may be preceded by the advance width,
+    /// has been reached.
+    seen_width: bool,
+    /// Set between a moveto and the first segment drawn from it.
+    pending_move: bool,
+    bounds: Option<Bounds>,
+}
+
+/// Returns the bounding box of the outline of `glyph_id` in `font`, or `None` if it has no
+/// outline.
+pub(super) fn bounding_box<'a>(
+    cff: &CFF<'a>,
+    font: &Font<'a>,
+    glyph_id: u16,
+) -> Result<Option<BoundingBox>, ParseError> {
+    let mut interpreter = BoundsInterpreter::new(cff, font);
+    interpreter.run_glyph(glyph_id, 0., 0., 0)?;
+    Ok(interpreter.bounds.map(|bounds| BoundingBox {
+        x_min: to_i16(bounds.x_min.floor()),
+        x_max: to_i16(bounds.x_max.ceil()),
+        y_min: to_i16(bounds.y_min.floor()),
+        y_max: to_i16(bounds.y_max.ceil()),
+    }))
+}
+
+impl<'a, 'b> BoundsInterpreter<'a, 'b> {
+    fn new(cff: &'b CFF<'a>, font: &'b Font<'a>) -> Self {
+        BoundsInterpreter {
+            cff,
+            font,
+            local_subrs: None,
+            stack: Vec::with_capacity(MAX_ARGS),
+            x: 0.,
+            y: 0.,
+            num_stems: 0,
+            seen_width: false,
+            pending_move: false,
+            bounds: None,
+        }
+    }
+
+    /// Interpret the charstring of `glyph_id` with its origin at `x`, `y`.
+    fn run_glyph(&mut self, glyph_id: u16, x: f32, y: f32, depth: usize) -> Result<(), ParseError> {
+        let char_string = self
+            .font
+            .char_strings_index
+            .read_object(usize::from(glyph_id))
+            .ok_or(ParseError::BadIndex)?;
+        self.local_subrs = match self.font.data {
+            CFFVariant::CID(ref cid) => {
+                let font_dict_index = cid
+                    .fd_select
+                    .font_dict_index(glyph_id)
+                    .ok_or(ParseError::BadIndex)?;
+                cid.local_subr_indices
+                    .get(usize::from(font_dict_index))
+                    .ok_or(ParseError::BadIndex)?
+                    .as_ref()
+            }
+            CFFVariant::Type1(ref type1) => type1.local_subr_index.as_ref(),
+        };
+        self.stack.clear();
+        self.x = x;
+        self.y = y;
+        self.num_stems = 0;
+        self.seen_width = false;
+        self.pending_move = false;
+        self.run(char_string, depth)?;
+        Ok(())
+    }
+
+    /// Interpret `char_string`, returning `true` if `endchar` was reached.
+    fn run(&mut self, char_string: &[u8], depth: usize) -> Result<bool, ParseError> {
+        if depth > MAX_SUBR_DEPTH {
+            return Err(ParseError::LimitExceeded);
+        }
+        let mut i = 0;
+        while i < char_string.len() {
+            let b0 = char_string[i];
+            i += 1;
+            match b0 {
+                // hstem, vstem, hstemhm, vstemhm
+                1 | 3 | 18 | 23 => {
+                    self.take_width(self.stack.len() % 2 == 1);
+                    self.num_stems += self.stack.len() / 2;
+                    self.stack.clear();
+                }
+                // vmoveto
+                4 => {
+                    self.take_width(self.stack.len() > 1);
+                    let dy = self.arg(0)?;
+                    self.move_to(0., dy);
+                }
+                // rlineto
+                5 => {
+                    for index in (0..self.stack.len() / 2).map(|n| n * 2) {
+                        let (dx, dy) = (self.stack[index], self.stack[index + 1]);
+                        self.line_to(dx, dy);
+                    }
+                    self.stack.clear();
+                }
+                // hlineto, vlineto
+                6 | 7 => {
+                    let mut horizontal = b0 == 6;
+                    for index in 0..self.stack.len() {
+                        let d = self.stack[index];
+                        if horizontal {
+                            self.line_to(d, 0.);
+                        } else {
+                            self.line_to(0., d);
+                        }
+                        horizontal = !horizontal;
+                    }
+                    self.stack.clear();
+                }
+                // rrcurveto
+                8 => {
+                    for index in (0..self.stack.len() / 6).map(|n| n * 6) {
+                        self.curve_at(index);
+                    }
+                    self.stack.clear();
+                }
+                // callsubr
+                10 => {
+                    let subrs = self.local_subrs.ok_or(ParseError::MissingValue)?;
+                    let subr = self.subr(subrs)?;
+                    if self.run(subr, depth + 1)? {
+                        return Ok(true);
+                    }
+                }
+                // return
+                11 => return Ok(false),
+                12 => {
+                    let b1 = *char_string.get(i).ok_or(ParseError::BadEof)?;
+                    i += 1;
+                    self.escape(b1)?;
+                }
+                // endchar
+                14 => {
+                    self.take_width(self.stack.len() == 1 || self.stack.len() == 5);
+                    if self.stack.len() == 4 {
+                        // The deprecated seac form, which composes two glyphs of the standard
+                        // encoding
+                        let (adx, ady) = (self.stack[0], self.stack[1]);
+                        let base = self.seac_glyph(self.stack[2])?;
+                        let accent = self.seac_glyph(self.stack[3])?;
+                        self.run_glyph(base, 0., 0., depth + 1)?;
+                        self.run_glyph(accent, adx, ady, depth + 1)?;
+                    }
+                    self.stack.clear();
+                    return Ok(true);
+                }
+                // hintmask, cntrmask
+                19 | 20 => {
+                    // Any arguments are the values of an implied vstemhm
+                    self.take_width(self.stack.len() % 2 == 1);
+                    self.num_stems += self.stack.len() / 2;
+                    self.stack.clear();
+                    i += (self.num_stems + 7) / 8;
+                }
+                // rmoveto
+                21 => {
+                    self.take_width(self.stack.len() > 2);
+                    let (dx, dy) = (self.arg(0)?, self.arg(1)?);
+                    self.move_to(dx, dy);
+                }
+                // hmoveto
+                22 => {
+                    self.take_width(self.stack.len() > 1);
+                    let dx = self.arg(0)?;
+                    self.move_to(dx, 0.);
+                }
+                // rcurveline
+                24 => {
+                    let num_curves = self.stack.len().saturating_sub(2) / 6;
+                    for index in (0..num_curves).map(|n| n * 6) {
+                        self.curve_at(index);
+                    }
+                    let (dx, dy) = (self.arg(num_curves * 6)?, self.arg(num_curves * 6 + 1)?);
+                    self.line_to(dx, dy);
+                    self.stack.clear();
+                }
+                // rlinecurve
+                25 => {
+                    if self.stack.len() < 6 {
+                        return Err(ParseError::BadValue);
+                    }
+                    let num_lines = (self.stack.len() - 6) / 2;
+                    for index in (0..num_lines).map(|n| n * 2) {
+                        let (dx, dy) = (self.stack[index], self.stack[index + 1]);
+                        self.line_to(dx, dy);
+                    }
+                    self.curve_at(num_lines * 2);
+                    self.stack.clear();
+                }
+                // vvcurveto, hhcurveto
+                26 | 27 => {
+                    let vertical = b0 == 26;
+                    let (mut d1, mut index) = if self.stack.len() % 2 == 1 {
+                        (self.stack[0], 1)
+                    } else {
+                        (0., 0)
+                    };
+                    while index + 4 <= self.stack.len() {
+                        let args = &self.stack[index..index + 4];
+                        let (da, dxb, dyb, dc) = (args[0], args[1], args[2], args[3]);
+                        if vertical {
+                            self.curve_to(d1, da, dxb, dyb, 0., dc);
+                        } else {
+                            self.curve_to(da, d1, dxb, dyb, dc, 0.);
+                        }
+                        d1 = 0.;
+                        index += 4;
+                    }
+                    self.stack.clear();
+                }
+                // callgsubr
+                29 => {
+                    let cff = self.cff;
+                    let subr = self.subr(&cff.global_subr_index)?;
+                    if self.run(subr, depth + 1)? {
+                        return Ok(true);
+                    }
+                }
+                // vhcurveto, hvcurveto
+                30 | 31 => {
+                    let mut horizontal = b0 == 31;
+                    let mut index = 0;
+                    while index + 4 <= self.stack.len() {
+                        // The final curve may take an extra argument for its last coordinate
+                        let last = self.stack.len() - index == 5;
+                        let args = &self.stack[index..index + 4];
+                        let (da, dxb, dyb, dc) = (args[0], args[1], args[2], args[3]);
+                        let df = if last { self.stack[index + 4] } else { 0. };
+                        if horizontal {
+                            self.curve_to(da, 0., dxb, dyb, df, dc);
+                        } else {
+                            self.curve_to(0., da, dxb, dyb, dc, df);
+                        }
+                        horizontal = !horizontal;
+                        index += if last { 5 } else { 4 };
+                    }
+                    self.stack.clear();
+                }
+                28 => {
+                    let bytes = char_string.get(i..i + 2).ok_or(ParseError::BadEof)?;
+                    self.push(f32::from(i16::from_be_bytes([bytes[0], bytes[1]])))?;
+                    i += 2;
+                }
+                32..=246 => self.push(f32::from(i16::from(b0) - 139))?,
+                247..=250 => {
+                    let b1 = *char_string.get(i).ok_or(ParseError::BadEof)?;
+                    self.push(f32::from((i16::from(b0) - 247) * 256 + i16::from(b1) + 108))?;
+                    i += 1;
+                }
+                251..=254 => {
+                    let b1 = *char_string.get(i).ok_or(ParseError::BadEof)?;
+                    self.push(f32::from(
+                        -(i16::from(b0) - 251) * 256 - i16::from(b1) - 108,
+                    ))?;
+                    i += 1;
+                }
+                255 => {
+                    let bytes = char_string.get(i..i + 4).ok_or(ParseError::BadEof)?;
+                    let fixed = i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                    self.push(fixed as f32 / 65536.)?;
+                    i += 4;
+                }
+                _ => return Err(ParseError::BadValue),
+            }
+        }
+        Ok(false)
+    }
+
+    /// Interpret the two byte operator `12 b1`.
+    fn escape(&mut self, b1: u8) -> Result<(), ParseError> {
+        let args = self.stack.clone();
+        match (b1, args.as_slice()) {
+            // dotsection, deprecated and ignored
+            (0, _) => {}
+            // hflex
+            (34, &[dx1, dx2, dy2, dx3, dx4, dx5, dx6]) => {
+                self.curve_to(dx1, 0., dx2, dy2, dx3, 0.);
+                self.curve_to(dx4, 0., dx5, -dy2, dx6, 0.);
+            }
+            // flex
+            (35, &[dx1, dy1, dx2, dy2, dx3, dy3, dx4, dy4, dx5, dy5, dx6, dy6, _fd]) => {
+                self.curve_to(dx1, dy1, dx2, dy2, dx3, dy3);
+                self.curve_to(dx4, dy4, dx5, dy5, dx6, dy6);
+            }
+            // hflex1
+            (36, &[dx1, dy1, dx2, dy2, dx3, dx4, dx5, dy5, dx6]) => {
+                self.curve_to(dx1, dy1, dx2, dy2, dx3, 0.);
+                self.curve_to(dx4, 0., dx5, dy5, dx6, -(dy1 + dy2 + dy5));
+            }
+            // flex1
+            (37, &[dx1, dy1, dx2, dy2, dx3, dy3, dx4, dy4, dx5, dy5, d6]) => {
+                let dx = dx1 + dx2 + dx3 + dx4 + dx5;
+                let dy = dy1 + dy2 + dy3 + dy4 + dy5;
+                let (dx6, dy6) = if dx.abs() > dy.abs() {
+                    (d6, -dy)
+                } else {
+                    (-dx, d6)
+                };
+                self.curve_to(dx1, dy1, dx2, dy2, dx3, dy3);
+                self.curve_to(dx4, dy4, dx5, dy5, dx6, dy6);
+            }
+            (34..=37, _) => return Err(ParseError::BadValue),
+            // The arithmetic and storage operators are not used by fonts in practice
+            _ => return Err(ParseError::NotImplemented),
+        }
+        self.stack.clear();
+        Ok(())
+    }
+
+    fn push(&mut self, value: f32) -> Result<(), ParseError> {
+        if self.stack.len() == MAX_ARGS {
+            return Err(ParseError::LimitExceeded);
+        }
+        self.stack.push(value);
+        Ok(())
+    }
+
+    fn arg(&self, index: usize) -> Result<f32, ParseError> {
+        self.stack
+            .get(index)
+            .copied()
+            .ok_or(ParseError::MissingValue)
+    }
+
+    /// Drop the advance width from the bottom of the stack if this is the first stack clearing
+    /// operator and `has_width` indicates it's present.
+    fn take_width(&mut self, has_width: bool) {
+        if !self.seen_width {
+            self.seen_width = true;
+            if has_width {
+                self.stack.remove(0);
+            }
+        }
+    }
+
+    /// Pop the biased subroutine number from the stack and return that subroutine of `subrs`.
+    fn subr(&mut self, subrs: &'b Index<'a>) -> Result<&'b [u8], ParseError> {
+        let number = self.stack.pop().ok_or(ParseError::MissingValue)?;
+        let bias = match subrs.count {
+            0..=1239 => 107,
+            1240..=33899 => 1131,
+            _ => 32768,
+        };
+        let index = usize::try_from(number as i32 + bias).map_err(|_| ParseError::BadIndex)?;
+        subrs.read_object(index).ok_or(ParseError::BadIndex)
+    }
+
+    /// Returns the glyph for the standard encoding `code` of a `seac` style `endchar`.
+    fn seac_glyph(&self, code: f32) -> Result<u16, ParseError> {
+        let sid = u8::try_from(code as i32)
+            .ok()
+            .and_then(standard_encoding_sid)
+            .ok_or(ParseError::BadValue)?;
+        let num_glyphs = u16::try_from(self.font.char_strings_index.len())?;
+        (0..num_glyphs)
+            .find(|&glyph_id| self.font.charset.id_for_glyph(glyph_id) == Some(sid))
+            .ok_or(ParseError::BadValue)
+    }
+
+    fn move_to(&mut self, dx: f32, dy: f32) {
+        self.x += dx;
+        self.y += dy;
+        self.pending_move = true;
+        self.stack.clear();
+    }
+
+    fn line_to(&mut self, dx: f32, dy: f32) {
+        self.start_segment();
+        self.x += dx;
+        self.y += dy;
+        self.add_point(self.x, self.y);
+    }
+
+    /// Draw the curve whose six arguments start at `index` on the stack.
+    fn curve_at(&mut self, index: usize) {
+        let args = &self.stack[index..index + 6];
+        let (dx1, dy1, dx2, dy2, dx3, dy3) = (args[0], args[1], args[2], args[3], args[4], args[5]);
+        self.curve_to(dx1, dy1, dx2, dy2, dx3, dy3);
+    }
+
+    fn curve_to(&mut self, dx1: f32, dy1: f32, dx2: f32, dy2: f32, dx3: f32, dy3: f32) {
+        self.start_segment();
+        let (x0, y0) = (self.x, self.y);
+        let (x1, y1) = (x0 + dx1, y0 + dy1);
+        let (x2, y2) = (x1 + dx2, y1 + dy2);
+        let (x3, y3) = (x2 + dx3, y2 + dy3);
+        self.add_point(x3, y3);
+        for t in cubic_extrema(x0, x1, x2, x3)
+            .iter()
+            .chain(cubic_extrema(y0, y1, y2, y3).iter())
+            .filter_map(|&t| t)
+        {
+            self.add_point(cubic_at(x0, x1, x2, x3, t), cubic_at(y0, y1, y2, y3, t));
+        }
+        self.x = x3;
+        self.y = y3;
+    }
+
+    /// Include the start of the contour when its first segment is drawn. A moveto that isn't
+    /// followed by a segment doesn't contribute to the bounds.
+    fn start_segment(&mut self) {
+        if self.pending_move {
+            self.pending_move = false;
+            self.add_point(self.x, self.y);
+        }
+    }
+
+    fn add_point(&mut self, x: f32, y: f32) {
+        self.bounds = Some(match self.bounds {
+            Some(bounds) => Bounds {
+                x_min: bounds.x_min.min(x),
+                y_min: bounds.y_min.min(y),
+                x_max: bounds.x_max.max(x),
+                y_max: bounds.y_max.max(y),
+            },
+            None => Bounds {
+                x_min: x,
+                y_min: y,
+                x_max: x,
+                y_max: y,
+            },
+        });
+    }
+}
+
+/// Returns the parameters between 0 and 1 at which the cubic Bézier with the coordinates `p0` to
+/// `p3` has a local minimum or maximum.
+fn cubic_extrema(p0: f32, p1: f32, p2: f32, p3: f32) -> [Option<f32>; 2] {
+    // The roots of the derivative, divided by 3: a t² + b t + c
+    let a = -p0 + 3. * p1 - 3. * p2 + p3;
+    let b = 2. * (p0 - 2. * p1 + p2);
+    let c = p1 - p0;
+    let in_range = |t: f32| if t > 0. && t < 1. { Some(t) } else { None };
+    if a.abs() < 1e-6 {
+        if b.abs() < 1e-6 {
+            return [None, None];
+        }
+        return [in_range(-c / b), None];
+    }
+    let discriminant = b * b - 4. * a * c;
+    if discriminant < 0. {
+        return [None, None];
+    }
+    let root = discriminant.sqrt();
+    [
+        in_range((-b + root) / (2. * a)),
+        in_range((-b - root) / (2. * a)),
+    ]
+}
+
+fn cubic_at(p0: f32, p1: f32, p2: f32, p3: f32, t: f32) -> f32 {
+    let mt = 1. - t;
+    mt * mt * mt * p0 + 3. * mt * mt * t * p1 + 3. * mt * t * t * p2 + t * t * t * p3
+}
+
+fn to_i16(value: f32) -> i16 {
+    value
+        .max(f32::from(std::i16::MIN))
+        .min(f32::from(std::i16::MAX)) as i16
+}
+
+/// Returns the SID of the glyph name assigned to `code` by the Standard Encoding.
+///
+/// Refer to Appendix B of Technical Note #5176.
+fn standard_encoding_sid(code: u8) -> Option<u16> {
+    let sid = match code {
+        32..=126 => u16::from(code) - 31,
+        161..=175 => u16::from(code) - 65,
+        177..=180 => u16::from(code) - 66,
+        182..=189 => u16::from(code) - 67,
+        191 => 123,
+        193..=200 => u16::from(code) - 69,
+        202 | 203 => u16::from(code) - 70,
+        205..=208 => u16::from(code) - 71,
+        225 => 138,
+        227 => 139,
+        232..=235 => u16::from(code) - 92,
+        241 => 144,
+        245 => 145,
+        248..=251 => u16::from(code) - 102,
+        _ => return None,
+    };
+    Some(sid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cff::STANDARD_STRINGS;
+
+    #[test]
+    fn test_cubic_extrema() {
+        // A symmetric arch peaks halfway along
+        let [t0, t1] = cubic_extrema(0., 100., 100., 0.);
+        assert_eq!(t0.or(t1), Some(0.5));
+        assert_eq!(cubic_at(0., 100., 100., 0., 0.5), 75.);
+        // A monotonic curve has no extrema
+        assert_eq!(cubic_extrema(0., 10., 20., 30.), [None, None]);
+    }
+
+    #[test]
+    fn test_standard_encoding() {
+        let name = |code| standard_encoding_sid(code).map(|sid| STANDARD_STRINGS[usize::from(sid)]);
+        assert_eq!(name(b'A'), Some("A"));
+        assert_eq!(name(b'~'), Some("asciitilde"));
+        assert_eq!(name(161), Some("exclamdown"));
+        assert_eq!(name(175), Some("fl"));
+        assert_eq!(name(177), Some("endash"));
+        assert_eq!(name(180), Some("periodcentered"));
+        assert_eq!(name(182), Some("paragraph"));
+        assert_eq!(name(189), Some("perthousand"));
+        assert_eq!(name(191), Some("questiondown"));
+        assert_eq!(name(193), Some("grave"));
+        assert_eq!(name(200), Some("dieresis"));
+        assert_eq!(name(202), Some("ring"));
+        assert_eq!(name(203), Some("cedilla"));
+        assert_eq!(name(205), Some("hungarumlaut"));
+        assert_eq!(name(208), Some("emdash"));
+        assert_eq!(name(225), Some("AE"));
+        assert_eq!(name(227), Some("ordfeminine"));
+        assert_eq!(name(232), Some("Lslash"));
+        assert_eq!(name(235), Some("ordmasculine"));
+        assert_eq!(name(241), Some("ae"));
+        assert_eq!(name(245), Some("dotlessi"));
+        assert_eq!(name(248), Some("lslash"));
+        assert_eq!(name(251), Some("germandbls"));
+        assert_eq!(name(176), None);
+        assert_eq!(name(31), None);
+    }
+}
@@ -0,0 +1,140 @@
+//! Mapping of text to glyphs ahead of shaping.
+//!
+//! `Font::map_text` is an alternative to `Font::map_glyphs` that optionally normalises the text
+//! first, and reports the characters that the font has no glyph for so that callers are able to
+//! fall back to another font for them.
+
+use unicode_normalization::char::decompose_canonical;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::gsub::RawGlyph;
+use crate::scripts::ScriptType;
+
+/// The Unicode normalisation applied to text by `Font::map_text`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Normalization {
+    /// The text is mapped as is.
+    None,
+    /// The text is canonically composed (NFC). A composed character that the font has no glyph
+    /// for is decomposed again if the font has glyphs for each character of its decomposition.
+    Composed,
+    /// The text is canonically decomposed (NFD).
+    Decomposed,
+}
+
+impl Normalization {
+    /// Returns the normalisation suited to the shaper for `script_tag`.
+    ///
+    /// The Indic shaper expects nukta forms and split vowels to be decomposed, while the Hangul
+    /// shaper composes jamo itself according to the glyphs of the font. Text in other scripts is
+    /// composed.
+    pub fn for_script(script_tag: u32) -> Self {
+        match ScriptType::from(script_tag) {
+            ScriptType::Indic => Normalization::Decomposed,
+            ScriptType::Hangul => Normalization::None,
+            _ => Normalization::Composed,
+        }
+    }
+}
+
+/// Text mapped to glyphs by `Font::map_text`.
+pub struct MappedText {
+    /// The text after normalisation.
+    pub text: String,
+    /// The glyphs for `text`, ready to be passed to `Font::shape`. Characters that the font has
+    /// no glyph for are mapped to `.notdef` (glyph 0).
+    pub glyphs: Vec<RawGlyph<()>>,
+    /// The characters of `text` that the font has no glyph for, in order.
+    pub unmapped: Vec<UnmappedChar>,
+}
+
+/// A character that the font has no glyph for.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct UnmappedChar {
+    /// The byte offset of the character in `MappedText::text`.
+    pub offset: usize,
+    /// The character.
+    pub ch: char,
+}
+
+impl MappedText {
+    /// Returns `true` if the font has a glyph for every character of the text.
+    pub fn is_fully_mapped(&self) -> bool {
+        self.unmapped.is_empty()
+    }
+}
+
+/// Normalise `text`, using `has_glyph` to decide whether composed characters are kept.
+pub(crate) fn normalize(
+    text: &str,
+    normalization: Normalization,
+    mut has_glyph: impl FnMut(char) -> bool,
+) -> String {
+    match normalization {
+        Normalization::None => text.to_string(),
+        Normalization::Decomposed => text.nfd().collect(),
+        Normalization::Composed => {
+            let mut normalized = String::with_capacity(text.len());
+            let mut decomposition = Vec::new();
+            for ch in text.nfc() {
+                if has_glyph(ch) {
+                    normalized.push(ch);
+                    continue;
+                }
+                decomposition.clear();
+                decompose_canonical(ch, |part| decomposition.push(part));
+                if decomposition.len() > 1 && decomposition.iter().all(|&part| has_glyph(part)) {
+                    normalized.extend(decomposition.iter());
+                } else {
+                    normalized.push(ch);
+                }
+            }
+            normalized
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tag;
+
+    #[test]
+    fn test_normalize() {
+        let text = "e\u{301}\u{1E0B}\u{323}";
+        let all = |_| true;
+        assert_eq!(normalize(text, Normalization::None, all), text);
+        assert_eq!(
+            normalize(text, Normalization::Decomposed, all),
+            "e\u{301}d\u{323}\u{307}"
+        );
+        assert_eq!(
+            normalize(text, Normalization::Composed, all),
+            "\u{E9}\u{1E0D}\u{307}"
+        );
+        // Composed characters without a glyph are decomposed if the parts have glyphs
+        let without_composed = |ch| !('\u{80}'..='\u{2FF}').contains(&ch);
+        assert_eq!(
+            normalize(text, Normalization::Composed, without_composed),
+            "e\u{301}\u{1E0D}\u{307}"
+        );
+        let without_acute = |ch| ch != '\u{E9}' && ch != '\u{301}';
+        assert_eq!(
+            normalize(text, Normalization::Composed, without_acute),
+            "\u{E9}\u{1E0D}\u{307}"
+        );
+    }
+
+    #[test]
+    fn test_for_script() {
+        assert_eq!(
+            Normalization::for_script(tag::DEVA),
+            Normalization::Decomposed
+        );
+        assert_eq!(Normalization::for_script(tag::HANG), Normalization::None);
+        assert_eq!(
+            Normalization::for_script(tag::LATN),
+            Normalization::Composed
+        );
+    }
+}
@@ -5,8 +5,36 @@
 use std::num::Wrapping;
 
 use crate::binary::read::ReadScope;
-use crate::binary::U32Be;
+use crate::binary::{long_align, U32Be};
 use crate::error::ParseError;
+use crate::tag;
+
+/// The value that the checksum of a whole font must equal once `checkSumAdjustment` is set.
+pub const FONT_CHECKSUM_MAGIC: u32 = 0xB1B0AFBA;
+
+/// Offset of the `checkSumAdjustment` field in the `head` table.
+pub(crate) const CHECK_SUM_ADJUSTMENT_OFFSET: usize = 8;
+
+/// A checksum stored in a font that does not match the data it covers.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ChecksumMismatch {
+    /// The checksum in the table record of the table with tag `table_tag` is incorrect.
+    Table {
+        /// Tag of the table.
+        table_tag: u32,
+        /// Checksum stored in the table record.
+        stored: u32,
+        /// Checksum calculated from the table data.
+        calculated: u32,
+    },
+    /// The `checkSumAdjustment` field of the `head` table is incorrect.
+    CheckSumAdjustment {
+        /// Value stored in the `head` table.
+        stored: u32,
+        /// Value calculated from the font data.
+        calculated: u32,
+    },
+}
 
 /// Calculate a checksum of `data` according to the OpenType table checksum algorithm
 ///
@@ -19,6 +47,39 @@ pub fn table_checksum(data: &[u8]) -> Result<Wrapping<u32>, ParseError> {
     Ok(array.iter().map(Wrapping).sum())
 }
 
+/// Calculate the checksum of a table for use in its table record
+///
+/// `data` does not need to be padded. If `table_tag` is `head` the `checkSumAdjustment` field is
+/// treated as zero, as required by the OpenType specification.
+pub fn sfnt_table_checksum(table_tag: u32, data: &[u8]) -> Result<Wrapping<u32>, ParseError> {
+    let mut padded = data.to_vec();
+    padded.resize(long_align(data.len()), 0);
+    if table_tag == tag::HEAD {
+        zero_check_sum_adjustment(&mut padded);
+    }
+    table_checksum(&padded)
+}
+
+/// Calculate the value of `checkSumAdjustment` for a whole font file
+///
+/// The current value of `checkSumAdjustment` in `head`, which starts at `head_offset`, is treated
+/// as zero.
+pub fn check_sum_adjustment(font: &[u8], head_offset: usize) -> Result<u32, ParseError> {
+    let mut padded = font.to_vec();
+    padded.resize(long_align(font.len()), 0);
+    let head = padded.get_mut(head_offset..).ok_or(ParseError::BadOffset)?;
+    zero_check_sum_adjustment(head);
+    let font_checksum = table_checksum(&padded)?;
+    Ok((Wrapping(FONT_CHECKSUM_MAGIC) - font_checksum).0)
+}
+
+fn zero_check_sum_adjustment(head: &mut [u8]) {
+    if let Some(field) = head.get_mut(CHECK_SUM_ADJUSTMENT_OFFSET..CHECK_SUM_ADJUSTMENT_OFFSET + 4)
+    {
+        field.copy_from_slice(&[0; 4]);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::Wrapping;
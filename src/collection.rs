@@ -0,0 +1,211 @@
+//! Writing of TrueType and OpenType font collections (TTC).
+//!
+//! <https://docs.microsoft.com/en-us/typography/opentype/spec/otff#font-collections>
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use crate::binary::read::ReadScope;
+use crate::binary::write::{WriteBinary, WriteBuffer, WriteContext};
+use crate::binary::{long_align, U16Be, U32Be};
+use crate::checksum;
+use crate::error::{ReadWriteError, WriteError};
+use crate::sfnt::{self, write_offset_table, OFFSET_TABLE_SIZE};
+use crate::tables::{OpenTypeData, OpenTypeFont, TableRecord, TTCF_MAGIC};
+
+/// A font within the output file along with the data of its tables.
+struct Face<'a> {
+    sfnt_version: u32,
+    tables: Vec<(u32, &'a [u8])>,
+}
+
+/// Build a font collection from the supplied fonts.
+///
+/// Each font may be a single OpenType font or a collection, in which case all of its fonts are
+/// added. Tables that are byte-for-byte identical are only stored once in the output and shared
+/// by all the fonts that use them.
+pub fn write_collection(fonts: &[&[u8]]) -> Result<Vec<u8>, ReadWriteError> {
+    let mut faces = Vec::new();
+    for data in fonts {
+        let font = ReadScope::new(data).read::<OpenTypeFont<'_>>()?;
+        let num_fonts = match &font.data {
+            OpenTypeData::Single(_) => 1,
+            OpenTypeData::Collection(ttc) => ttc.offset_tables.len(),
+        };
+        for index in 0..num_fonts {
+            faces.push(read_face(&font, index)?);
+        }
+    }
+
+    let num_fonts = u32::try_from(faces.len()).map_err(WriteError::from)?;
+    let header_size = 12 + faces.len() * 4;
+    let mut w = WriteBuffer::new();
+    U32Be::write(&mut w, TTCF_MAGIC)?;
+    U16Be::write(&mut w, 1u16)?; // major version
+    U16Be::write(&mut w, 0u16)?; // minor version
+    U32Be::write(&mut w, num_fonts)?;
+    let mut offset = header_size;
+    for face in &faces {
+        U32Be::write(&mut w, u32::try_from(offset).map_err(WriteError::from)?)?;
+        offset += OFFSET_TABLE_SIZE + face.tables.len() * TableRecord::SIZE;
+    }
+
+    // The header and table directories are all multiples of 4 in size so the table data starts
+    // aligned.
+    write_faces(w, &faces)
+}
+
+/// Extract the font at `index` from a font collection into a standalone font.
+///
+/// If `data` is a single font it is rewritten as is, `index` is ignored.
+pub fn extract_font(data: &[u8], index: usize) -> Result<Vec<u8>, ReadWriteError> {
+    let font = ReadScope::new(data).read::<OpenTypeFont<'_>>()?;
+    let face = read_face(&font, index)?;
+    sfnt::write_font(face.sfnt_version, &face.tables)
+}
+
+fn read_face<'a>(font: &OpenTypeFont<'a>, index: usize) -> Result<Face<'a>, ReadWriteError> {
+    let offset_table = font.offset_table(index)?;
+    let mut tables = offset_table
+        .table_records
+        .iter()
+        .map(|record| {
+            record
+                .read_table(&font.scope)
+                .map(|scope| (record.table_tag, scope.data()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    tables.sort_by_key(|&(tag, _)| tag);
+
+    Ok(Face {
+        sfnt_version: offset_table.sfnt_version,
+        tables,
+    })
+}
+
+/// Write the table directory of each face followed by the table data.
+///
+/// `w` must contain everything that precedes the first table directory.
+fn write_faces(mut w: WriteBuffer, faces: &[Face<'_>]) -> Result<Vec<u8>, ReadWriteError> {
+    let directories_size = faces
+        .iter()
+        .map(|face| OFFSET_TABLE_SIZE + face.tables.len() * TableRecord::SIZE)
+        .sum::<usize>();
+
+    // Assign an offset to each distinct table
+    let mut table_offsets: HashMap<&[u8], usize> = HashMap::new();
+    let mut unique_tables = Vec::new();
+    let mut offset = w.len() + directories_size;
+    for face in faces {
+        for &(_, data) in &face.tables {
+            table_offsets.entry(data).or_insert_with(|| {
+                let table_offset = offset;
+                offset += long_align(data.len());
+                unique_tables.push(data);
+                table_offset
+            });
+        }
+    }
+
+    for face in faces {
+        write_offset_table(&mut w, face.sfnt_version, face.tables.len())?;
+        for &(table_tag, data) in &face.tables {
+            let record = TableRecord {
+                table_tag,
+                checksum: checksum::sfnt_table_checksum(table_tag, data)?.0,
+                offset: u32::try_from(table_offsets[data]).map_err(WriteError::from)?,
+                length: u32::try_from(data.len()).map_err(WriteError::from)?,
+            };
+            TableRecord::write(&mut w, &record)?;
+        }
+    }
+
+    for data in unique_tables {
+        w.write_bytes(data)?;
+        w.write_zeros(long_align(data.len()) - data.len())?;
+    }
+
+    Ok(w.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font_data::FontData;
+    use crate::tables::FontTableProvider;
+    use crate::tag;
+    use crate::tests::read_fixture;
+    use std::num::Wrapping;
+
+    fn assert_same_tables(expected: &[u8], expected_index: usize, actual: &[u8], index: usize) {
+        let expected = ReadScope::new(expected).read::<FontData<'_>>().unwrap();
+        let expected_provider = expected.table_provider(expected_index).unwrap();
+        let expected_font = match &expected {
+            FontData::OpenType(font) => font,
+            _ => unreachable!(),
+        };
+        let actual = ReadScope::new(actual).read::<FontData<'_>>().unwrap();
+        let actual_provider = actual.table_provider(index).unwrap();
+
+        let offset_table = expected_font.offset_table(expected_index).unwrap();
+        for record in offset_table.table_records.iter() {
+            let tag = record.table_tag;
+            if tag == tag::HEAD {
+                continue;
+            }
+            assert_eq!(
+                actual_provider.read_table_data(tag).unwrap(),
+                expected_provider.read_table_data(tag).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_write_collection() {
+        let ttf = read_fixture("tests/fonts/opentype/test-font.ttf");
+        let otf = read_fixture("tests/fonts/opentype/Klei.otf");
+        let ttc = write_collection(&[&ttf, &otf, &ttf]).unwrap();
+
+        let font = ReadScope::new(&ttc).read::<OpenTypeFont<'_>>().unwrap();
+        match &font.data {
+            OpenTypeData::Collection(ttc) => assert_eq!(ttc.offset_tables.len(), 3),
+            OpenTypeData::Single(_) => panic!("expected a collection"),
+        }
+        assert_same_tables(&ttf, 0, &ttc, 0);
+        assert_same_tables(&otf, 0, &ttc, 1);
+        assert_same_tables(&ttf, 0, &ttc, 2);
+
+        // The tables of the repeated font should be shared
+        let first = font.offset_table(0).unwrap();
+        let third = font.offset_table(2).unwrap();
+        let first_offsets = first.table_records.iter().map(|record| record.offset);
+        let third_offsets = third.table_records.iter().map(|record| record.offset);
+        assert!(first_offsets.eq(third_offsets));
+        assert!(ttc.len() < 2 * ttf.len() + otf.len());
+    }
+
+    #[test]
+    fn test_extract_font() {
+        let ttf = read_fixture("tests/fonts/opentype/test-font.ttf");
+        let otf = read_fixture("tests/fonts/opentype/Klei.otf");
+        let ttc = write_collection(&[&ttf, &otf]).unwrap();
+
+        let extracted = extract_font(&ttc, 1).unwrap();
+        let font = ReadScope::new(&extracted)
+            .read::<OpenTypeFont<'_>>()
+            .unwrap();
+        match &font.data {
+            OpenTypeData::Single(offset_table) => {
+                assert_eq!(offset_table.sfnt_version, crate::tables::CFF_MAGIC)
+            }
+            OpenTypeData::Collection(_) => panic!("expected a single font"),
+        }
+        assert_same_tables(&otf, 0, &extracted, 0);
+
+        // The checksum of the whole font should equal the magic number
+        assert_eq!(
+            checksum::table_checksum(&extracted).unwrap(),
+            Wrapping(checksum::FONT_CHECKSUM_MAGIC)
+        );
+    }
+}
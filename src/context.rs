@@ -1,7 +1,7 @@
 //! Utilities for performing contextual lookup in gpos and gsub.
 
 use std::marker::PhantomData;
-use std::rc::Rc;
+use std::sync::Arc;
 
 use crate::gdef;
 use crate::layout::{ClassDef, Coverage, GDEFTable};
@@ -21,13 +21,14 @@ pub struct MatchType {
     ignore_bases: bool,
     ignore_ligatures: bool,
     ignore_marks: IgnoreMarks,
+    mark_filtering_set: Option<u16>,
 }
 
 pub enum GlyphTable<'a> {
     Empty,
     ById(&'a [u16]),
-    ByClassDef(Rc<ClassDef>, &'a [u16]),
-    ByCoverage(&'a [Rc<Coverage>]),
+    ByClassDef(Arc<ClassDef>, &'a [u16]),
+    ByCoverage(&'a [Arc<Coverage>]),
 }
 
 impl<'a> GlyphTable<'a> {
@@ -87,6 +88,10 @@ impl LookupFlag {
         (self.0 & 0x0004) != 0
     }
 
+    pub fn get_use_mark_filtering_set(self) -> bool {
+        (self.0 & 0x0010) != 0
+    }
+
     pub fn get_ignore_marks(self) -> IgnoreMarks {
         if (self.0 & 0x8) != 0 {
             IgnoreMarks::IgnoreAllMarks
@@ -104,6 +109,7 @@ impl MatchType {
             ignore_bases: false,
             ignore_ligatures: false,
             ignore_marks: IgnoreMarks::IgnoreAllMarks,
+            mark_filtering_set: None,
         }
     }
 
@@ -112,14 +118,23 @@ impl MatchType {
             ignore_bases: true,
             ignore_ligatures: true,
             ignore_marks: IgnoreMarks::NoIgnoreMarks,
+            mark_filtering_set: None,
         }
     }
 
-    pub fn from_lookup_flag(lookup_flag: LookupFlag) -> MatchType {
+    /// The glyphs matched by a lookup with `lookup_flag`. `mark_filtering_set` is the index of
+    /// the mark glyph set in `GDEF` that is used when the `UseMarkFilteringSet` flag is set.
+    pub fn from_lookup_flag(lookup_flag: LookupFlag, mark_filtering_set: Option<u16>) -> MatchType {
+        let mark_filtering_set = if lookup_flag.get_use_mark_filtering_set() {
+            mark_filtering_set
+        } else {
+            None
+        };
         MatchType {
             ignore_bases: lookup_flag.get_ignore_bases(),
             ignore_ligatures: lookup_flag.get_ignore_ligatures(),
             ignore_marks: lookup_flag.get_ignore_marks(),
+            mark_filtering_set,
         }
     }
 
@@ -127,6 +142,7 @@ impl MatchType {
         if !self.ignore_bases
             && !self.ignore_ligatures
             && self.ignore_marks == IgnoreMarks::NoIgnoreMarks
+            && self.mark_filtering_set.is_none()
         {
             // fast path that doesn't require checking glyph_class
             return true;
@@ -138,13 +154,20 @@ impl MatchType {
         if self.ignore_ligatures && glyph_class == 2 {
             return false;
         }
-        match self.ignore_marks {
-            IgnoreMarks::NoIgnoreMarks => true,
-            IgnoreMarks::IgnoreAllMarks => glyph_class != 3,
-            IgnoreMarks::IgnoreMarksExcept(keep_class) => {
+        if glyph_class != 3 {
+            return true;
+        }
+        match (self.ignore_marks, self.mark_filtering_set) {
+            (IgnoreMarks::IgnoreAllMarks, _) => false,
+            // The mark filtering set takes precedence over the mark attachment type
+            (_, Some(set_index)) => {
+                gdef::mark_glyph_set_contains(opt_gdef_table, set_index, glyph.get_glyph_index())
+            }
+            (IgnoreMarks::NoIgnoreMarks, None) => true,
+            (IgnoreMarks::IgnoreMarksExcept(keep_class), None) => {
                 let mark_attach_class =
                     gdef::mark_attach_class(opt_gdef_table, glyph.get_glyph_index());
-                (glyph_class != 3) || (mark_attach_class == u16::from(keep_class))
+                mark_attach_class == u16::from(keep_class)
             }
         }
     }
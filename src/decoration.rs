@@ -0,0 +1,248 @@
+#![deny(missing_docs)]
+
+//! Metrics for decorating text, such as underlining it or setting it as superscript.
+//!
+//! `DecorationMetrics` gathers the position and thickness of underlines and strikeouts from the
+//! `post` and `OS/2` tables, with the variations of the `MVAR` table applied for an instance of a
+//! variable font.
+//!
+//! Superscript and subscript text is best set with the glyphs of the `sups` and `subs` features,
+//! which are designed for it. When a font doesn't have them, or they don't cover all of the text,
+//! the regular glyphs are scaled down and raised or lowered instead, using the sizes and offsets
+//! the font gives in its `OS/2` table. `Font::shape_script_position` chooses between the two.
+
+use crate::error::ParseError;
+use crate::post;
+use crate::tables::mvar::{self, MvarTable};
+use crate::tables::os2::Os2;
+use crate::tables::F2Dot14;
+use crate::tag;
+
+/// The scale of synthesized superscripts and subscripts, in ems, used when the font doesn't
+/// give one.
+const DEFAULT_SCRIPT_SCALE: f32 = 0.65;
+/// The distance superscripts are raised by, in ems, used when the font doesn't give one.
+const DEFAULT_SUPERSCRIPT_OFFSET: f32 = 0.35;
+/// The distance subscripts are lowered by, in ems, used when the font doesn't give one.
+const DEFAULT_SUBSCRIPT_OFFSET: f32 = 0.15;
+/// The thickness of underlines, in ems, used when the font doesn't give one.
+const DEFAULT_UNDERLINE_THICKNESS: f32 = 1. / 14.;
+/// The position of underlines, in ems, used when the font doesn't give one.
+const DEFAULT_UNDERLINE_POSITION: f32 = -0.1;
+/// The position of strikeouts, in ems, used when the font doesn't give one.
+const DEFAULT_STRIKEOUT_POSITION: f32 = 0.25;
+
+/// The position and thickness of underlines and strikeouts, in font units.
+///
+/// Positions are the distance of the top of the line above the baseline, so underlines usually
+/// have a negative position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecorationMetrics {
+    /// The position of the top of underlines, from `post`.
+    pub underline_position: i16,
+    /// The thickness of underlines, from `post`.
+    pub underline_thickness: i16,
+    /// The position of the top of strikeouts, from `OS/2`.
+    pub strikeout_position: i16,
+    /// The thickness of strikeouts, from `OS/2`.
+    pub strikeout_thickness: i16,
+}
+
+/// Raised or lowered text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptPosition {
+    /// Text raised above the baseline.
+    Superscript,
+    /// Text lowered below the baseline.
+    Subscript,
+}
+
+/// The scaling and offset that turns regular glyphs into superscripts or subscripts.
+///
+/// The glyph outlines and advances are scaled by `x_scale` and `y_scale` about the origin of the
+/// glyph, and the glyphs then moved by `x_offset` and `y_offset` font units. A positive
+/// `y_offset` raises the glyphs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScriptTransform {
+    /// The horizontal scale factor.
+    pub x_scale: f32,
+    /// The vertical scale factor.
+    pub y_scale: f32,
+    /// The horizontal offset in font units.
+    pub x_offset: i16,
+    /// The vertical offset in font units, positive upwards.
+    pub y_offset: i16,
+}
+
+impl DecorationMetrics {
+    /// Gather the decoration metrics from the `post` table header and `OS/2` table of a font with
+    /// `units_per_em`, applying the deltas of the `MVAR` table at the normalised variation
+    /// coordinates `coords`.
+    ///
+    /// Pass an empty slice for the default instance. Thicknesses that are missing or zero are
+    /// replaced with typical values, with strikeouts falling back to the thickness of underlines,
+    /// as are the positions of missing tables.
+    pub fn new(
+        opt_post: Option<&post::Header>,
+        opt_os2: Option<&Os2>,
+        opt_mvar: Option<&MvarTable<'_>>,
+        units_per_em: u16,
+        coords: &[F2Dot14],
+    ) -> Result<Self, ParseError> {
+        let vary = |value_tag, value| match opt_mvar {
+            Some(mvar) => mvar.apply(value_tag, value, coords),
+            None => Ok(value),
+        };
+        let ems = |ems: f32| (ems * f32::from(units_per_em)).round() as i16;
+
+        let (underline_position, underline_thickness) = match opt_post {
+            Some(post) => (
+                vary(mvar::UNDERLINE_OFFSET, post.underline_position)?,
+                vary(mvar::UNDERLINE_SIZE, post.underline_thickness)?,
+            ),
+            None => (ems(DEFAULT_UNDERLINE_POSITION), 0),
+        };
+        let underline_thickness = match underline_thickness {
+            thickness if thickness > 0 => thickness,
+            _ => ems(DEFAULT_UNDERLINE_THICKNESS).max(1),
+        };
+        let (strikeout_position, strikeout_thickness) = match opt_os2 {
+            Some(os2) => (
+                vary(mvar::STRIKEOUT_OFFSET, os2.y_strikeout_position)?,
+                vary(mvar::STRIKEOUT_SIZE, os2.y_strikeout_size)?,
+            ),
+            None => (ems(DEFAULT_STRIKEOUT_POSITION), 0),
+        };
+        let strikeout_thickness = match strikeout_thickness {
+            thickness if thickness > 0 => thickness,
+            _ => underline_thickness,
+        };
+
+        Ok(DecorationMetrics {
+            underline_position,
+            underline_thickness,
+            strikeout_position,
+            strikeout_thickness,
+        })
+    }
+}
+
+impl ScriptPosition {
+    /// The tag of the feature that substitutes glyphs for this position.
+    pub fn feature_tag(self) -> u32 {
+        match self {
+            ScriptPosition::Superscript => tag::SUPS,
+            ScriptPosition::Subscript => tag::SUBS,
+        }
+    }
+}
+
+impl ScriptTransform {
+    /// Returns the transform for `position` given by the `OS/2` table of a font with
+    /// `units_per_em`.
+    ///
+    /// Sizes and offsets that are missing or zero are replaced with typical values. The `OS/2`
+    /// table gives the subscript offset as a distance below the baseline, but some fonts give it
+    /// as a negative value, so only its magnitude is used.
+    pub fn from_os2(opt_os2: Option<&Os2>, units_per_em: u16, position: ScriptPosition) -> Self {
+        let (x_size, y_size, x_offset, y_offset) = match (opt_os2, position) {
+            (Some(os2), ScriptPosition::Superscript) => (
+                os2.y_superscript_x_size,
+                os2.y_superscript_y_size,
+                os2.y_superscript_x_offset,
+                os2.y_superscript_y_offset,
+            ),
+            (Some(os2), ScriptPosition::Subscript) => (
+                os2.y_subscript_x_size,
+                os2.y_subscript_y_size,
+                os2.y_subscript_x_offset,
+                os2.y_subscript_y_offset,
+            ),
+            (None, _) => (0, 0, 0, 0),
+        };
+
+        let units_per_em = f32::from(units_per_em.max(1));
+        let scale = |size: i16| {
+            if size > 0 {
+                f32::from(size) / units_per_em
+            } else {
+                DEFAULT_SCRIPT_SCALE
+            }
+        };
+        let default_offset = |ems: f32| (ems * units_per_em).round() as i16;
+        let y_offset = match position {
+            ScriptPosition::Superscript if y_offset != 0 => {
+                y_offset.checked_abs().unwrap_or(std::i16::MAX)
+            }
+            ScriptPosition::Superscript => default_offset(DEFAULT_SUPERSCRIPT_OFFSET),
+            ScriptPosition::Subscript if y_offset != 0 => {
+                -y_offset.checked_abs().unwrap_or(std::i16::MAX)
+            }
+            ScriptPosition::Subscript => -default_offset(DEFAULT_SUBSCRIPT_OFFSET),
+        };
+
+        ScriptTransform {
+            x_scale: scale(x_size),
+            y_scale: scale(y_size),
+            x_offset,
+            y_offset,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::read::ReadScope;
+    use crate::tables::{FontTableProvider, OpenTypeFont};
+    use crate::tests::read_fixture;
+
+    #[test]
+    fn test_decoration_metrics_defaults() {
+        let metrics = DecorationMetrics::new(None, None, None, 1000, &[]).unwrap();
+        assert_eq!(
+            metrics,
+            DecorationMetrics {
+                underline_position: -100,
+                underline_thickness: 71,
+                strikeout_position: 250,
+                strikeout_thickness: 71,
+            }
+        );
+    }
+
+    #[test]
+    fn test_script_transform_from_os2() {
+        let buffer = read_fixture("tests/fonts/opentype/Klei.otf");
+        let font = ReadScope::new(&buffer).read::<OpenTypeFont<'_>>().unwrap();
+        let provider = font.table_provider(0).unwrap();
+        let os2_data = provider.read_table_data(tag::OS_2).unwrap();
+        let os2 = ReadScope::new(&os2_data)
+            .read_dep::<Os2>(os2_data.len())
+            .unwrap();
+
+        let superscript = ScriptTransform::from_os2(Some(&os2), 1000, ScriptPosition::Superscript);
+        assert_eq!(
+            superscript,
+            ScriptTransform {
+                x_scale: f32::from(os2.y_superscript_x_size) / 1000.,
+                y_scale: f32::from(os2.y_superscript_y_size) / 1000.,
+                x_offset: os2.y_superscript_x_offset,
+                y_offset: os2.y_superscript_y_offset,
+            }
+        );
+        let subscript = ScriptTransform::from_os2(Some(&os2), 1000, ScriptPosition::Subscript);
+        assert_eq!(subscript.y_offset, -os2.y_subscript_y_offset.abs());
+
+        let default = ScriptTransform::from_os2(None, 2048, ScriptPosition::Subscript);
+        assert_eq!(
+            default,
+            ScriptTransform {
+                x_scale: 0.65,
+                y_scale: 0.65,
+                x_offset: 0,
+                y_offset: -307,
+            }
+        );
+    }
+}
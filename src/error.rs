@@ -1,6 +1,7 @@
 //! Error types
 
 use crate::binary::read::ReadEof;
+use crate::tag::DisplayTag;
 use std::fmt;
 
 /// Error returned from font shaping functions
@@ -49,9 +50,9 @@ pub enum IndicError {
     UnexpectedGlyphOrigin,
 }
 
-/// Errors that originate when parsing binary data
-#[derive(Debug, Eq, PartialEq)]
-pub enum ParseError {
+/// The kind of a `ParseError`
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum ParseErrorKind {
     BadEof,
     BadValue,
     BadVersion,
@@ -63,9 +64,111 @@ pub enum ParseError {
     NotImplemented,
 }
 
+/// Errors that originate when parsing binary data
+///
+/// In addition to its `kind`, a `ParseError` records where parsing failed when this is known: the
+/// tag of the table being parsed, the name of the field, and the byte offset from the start of the
+/// data that parsing began with (usually the table). Errors compare equal when their kinds are
+/// equal, regardless of where they occurred.
+///
+/// The constants named after each `ParseErrorKind` create an error without a location.
+#[derive(Debug)]
+pub struct ParseError {
+    kind: ParseErrorKind,
+    location: Option<Box<Location>>,
+}
+
+#[derive(Debug, Default)]
+struct Location {
+    table_tag: Option<u32>,
+    field: Option<&'static str>,
+    offset: Option<usize>,
+}
+
+#[allow(non_upper_case_globals)]
+impl ParseError {
+    pub const BadEof: ParseError = ParseError::new(ParseErrorKind::BadEof);
+    pub const BadValue: ParseError = ParseError::new(ParseErrorKind::BadValue);
+    pub const BadVersion: ParseError = ParseError::new(ParseErrorKind::BadVersion);
+    pub const BadOffset: ParseError = ParseError::new(ParseErrorKind::BadOffset);
+    pub const BadIndex: ParseError = ParseError::new(ParseErrorKind::BadIndex);
+    pub const LimitExceeded: ParseError = ParseError::new(ParseErrorKind::LimitExceeded);
+    pub const MissingValue: ParseError = ParseError::new(ParseErrorKind::MissingValue);
+    pub const CompressionError: ParseError = ParseError::new(ParseErrorKind::CompressionError);
+    pub const NotImplemented: ParseError = ParseError::new(ParseErrorKind::NotImplemented);
+}
+
+impl ParseError {
+    pub const fn new(kind: ParseErrorKind) -> Self {
+        ParseError {
+            kind,
+            location: None,
+        }
+    }
+
+    pub fn kind(&self) -> ParseErrorKind {
+        self.kind
+    }
+
+    /// The tag of the table that was being parsed, if known.
+    pub fn table_tag(&self) -> Option<u32> {
+        self.location
+            .as_ref()
+            .and_then(|location| location.table_tag)
+    }
+
+    /// The name of the field that was being parsed, if known.
+    pub fn field(&self) -> Option<&'static str> {
+        self.location.as_ref().and_then(|location| location.field)
+    }
+
+    /// The byte offset at which parsing failed, if known.
+    pub fn offset(&self) -> Option<usize> {
+        self.location.as_ref().and_then(|location| location.offset)
+    }
+
+    // The `with_` methods below only fill in values that are not already set. Errors gain context
+    // as they propagate outwards, and the innermost context is the most precise.
+
+    /// Record the table that was being parsed.
+    pub fn with_table(mut self, table_tag: u32) -> Self {
+        let location = self.location.get_or_insert_with(Box::default);
+        location.table_tag.get_or_insert(table_tag);
+        self
+    }
+
+    /// Record the field that was being parsed.
+    pub fn with_field(mut self, field: &'static str) -> Self {
+        let location = self.location.get_or_insert_with(Box::default);
+        location.field.get_or_insert(field);
+        self
+    }
+
+    /// Record the byte offset at which parsing failed.
+    pub fn with_offset(mut self, offset: usize) -> Self {
+        let location = self.location.get_or_insert_with(Box::default);
+        location.offset.get_or_insert(offset);
+        self
+    }
+}
+
+impl PartialEq for ParseError {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind
+    }
+}
+
+impl Eq for ParseError {}
+
+impl From<ParseErrorKind> for ParseError {
+    fn from(kind: ParseErrorKind) -> Self {
+        ParseError::new(kind)
+    }
+}
+
 impl From<ReadEof> for ParseError {
-    fn from(_error: ReadEof) -> Self {
-        ParseError::BadEof
+    fn from(error: ReadEof) -> Self {
+        ParseError::BadEof.with_offset(error.offset())
     }
 }
 
@@ -75,19 +178,35 @@ impl From<std::num::TryFromIntError> for ParseError {
     }
 }
 
-impl fmt::Display for ParseError {
+impl fmt::Display for ParseErrorKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            ParseError::BadEof => write!(f, "end of data reached unexpectedly"),
-            ParseError::BadValue => write!(f, "invalid value"),
-            ParseError::BadVersion => write!(f, "unexpected data version"),
-            ParseError::BadOffset => write!(f, "invalid data offset"),
-            ParseError::BadIndex => write!(f, "invalid data index"),
-            ParseError::LimitExceeded => write!(f, "limit exceeded"),
-            ParseError::MissingValue => write!(f, "an expected data value was missing"),
-            ParseError::CompressionError => write!(f, "compression error"),
-            ParseError::NotImplemented => write!(f, "feature not implemented"),
+            ParseErrorKind::BadEof => write!(f, "end of data reached unexpectedly"),
+            ParseErrorKind::BadValue => write!(f, "invalid value"),
+            ParseErrorKind::BadVersion => write!(f, "unexpected data version"),
+            ParseErrorKind::BadOffset => write!(f, "invalid data offset"),
+            ParseErrorKind::BadIndex => write!(f, "invalid data index"),
+            ParseErrorKind::LimitExceeded => write!(f, "limit exceeded"),
+            ParseErrorKind::MissingValue => write!(f, "an expected data value was missing"),
+            ParseErrorKind::CompressionError => write!(f, "compression error"),
+            ParseErrorKind::NotImplemented => write!(f, "feature not implemented"),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if let Some(table_tag) = self.table_tag() {
+            write!(f, " in '{}' table", DisplayTag(table_tag))?;
+        }
+        if let Some(field) = self.field() {
+            write!(f, " reading {}", field)?;
+        }
+        if let Some(offset) = self.offset() {
+            write!(f, " at offset {}", offset)?;
         }
+        Ok(())
     }
 }
 
@@ -144,6 +263,12 @@ impl From<ParseError> for ReadWriteError {
     }
 }
 
+impl From<ReadEof> for ReadWriteError {
+    fn from(error: ReadEof) -> Self {
+        ReadWriteError::Read(ParseError::from(error))
+    }
+}
+
 impl From<WriteError> for ReadWriteError {
     fn from(error: WriteError) -> Self {
         ReadWriteError::Write(error)
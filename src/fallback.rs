@@ -0,0 +1,240 @@
+#![deny(missing_docs)]
+
+//! Shaping text with a chain of fallback fonts.
+//!
+//! A single font rarely covers all of the text it's asked to display. A `FallbackChain` holds
+//! fonts in order of preference and splits text into runs that are each shaped with one font,
+//! choosing for every character the first font in the chain whose `cmap` maps it.
+//!
+//! Text is first split into script runs with `script_itemizer::itemize`, so that each run is
+//! dispatched to the shaper for its script. Characters that don't belong to a particular script,
+//! such as spaces, punctuation and combining marks, stay in the font of the preceding text when
+//! it covers them, as do variation selectors and the rest of emoji sequences.
+
+use std::ops::Range;
+
+use crate::error::ShapingError;
+use crate::font::{Font, MatchingPresentation};
+use crate::gpos::Info;
+use crate::gsub::Features;
+use crate::script_itemizer::{self, script_tag};
+use crate::tables::FontTableProvider;
+use crate::unicode;
+
+/// A list of fonts in order of preference, used to shape text that no one font covers.
+pub struct FallbackChain<T: FontTableProvider> {
+    fonts: Vec<Font<T>>,
+}
+
+/// A run of text in a single script to be shaped with a single font of a `FallbackChain`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FontRun {
+    /// The byte range of the run in the text.
+    pub range: Range<usize>,
+    /// The OpenType script tag of the run.
+    pub script_tag: u32,
+    /// The index of the font in the chain.
+    pub font_index: usize,
+}
+
+/// A font run that has been shaped.
+#[derive(Debug)]
+pub struct ShapedFontRun {
+    /// The byte range of the run in the text.
+    pub range: Range<usize>,
+    /// The OpenType script tag the run was shaped with.
+    pub script_tag: u32,
+    /// The index of the font in the chain the run was shaped with.
+    pub font_index: usize,
+    /// The shaped glyphs of the run.
+    pub infos: Vec<Info>,
+}
+
+impl<T: FontTableProvider> FallbackChain<T> {
+    /// Create a chain from `fonts`, listed in order of preference.
+    pub fn new(fonts: Vec<Font<T>>) -> Self {
+        FallbackChain { fonts }
+    }
+
+    /// The fonts of the chain, in order of preference.
+    pub fn fonts(&self) -> &[Font<T>] {
+        &self.fonts
+    }
+
+    /// Returns the font at `index` in the chain, as used by `FontRun::font_index`.
+    pub fn font_mut(&mut self, index: usize) -> Option<&mut Font<T>> {
+        self.fonts.get_mut(index)
+    }
+
+    /// Append a font to the end of the chain, making it the least preferred.
+    pub fn push(&mut self, font: Font<T>) {
+        self.fonts.push(font)
+    }
+
+    /// Returns the index of the first font in the chain that has a glyph for `ch`.
+    pub fn font_for_char(&mut self, ch: char) -> Option<usize> {
+        self.fonts
+            .iter_mut()
+            .position(|font| font.map_char(ch) != 0)
+    }
+
+    /// Split `text` into runs of a single script and font.
+    ///
+    /// Characters that no font covers are added to the run they're in, or assigned to the first
+    /// font at the start of the text, so that they're shaped as `.notdef`.
+    pub fn itemize(&mut self, text: &str) -> Vec<FontRun> {
+        let mut runs = Vec::new();
+        if self.fonts.is_empty() {
+            return runs;
+        }
+
+        for script_run in script_itemizer::itemize(text) {
+            let mut run_start = script_run.range.start;
+            let mut run_font: Option<usize> = None;
+            let mut prev = None;
+            for (offset, ch) in text[script_run.range.clone()].char_indices() {
+                let font_index = match (run_font, prev) {
+                    (Some(current), Some(prev))
+                        if unicode::is_variation_selector(ch)
+                            || unicode::continues_emoji_sequence(prev, ch) =>
+                    {
+                        current
+                    }
+                    (Some(current), _)
+                        if script_tag(ch).is_none() && self.fonts[current].map_char(ch) != 0 =>
+                    {
+                        current
+                    }
+                    _ => self.font_for_char(ch).or(run_font).unwrap_or(0),
+                };
+                prev = Some(ch);
+
+                match run_font {
+                    Some(current) if current != font_index => {
+                        let i = script_run.range.start + offset;
+                        runs.push(FontRun {
+                            range: run_start..i,
+                            script_tag: script_run.script_tag,
+                            font_index: current,
+                        });
+                        run_start = i;
+                    }
+                    _ => {}
+                }
+                run_font = Some(font_index);
+            }
+
+            if let Some(font_index) = run_font {
+                runs.push(FontRun {
+                    range: run_start..script_run.range.end,
+                    script_tag: script_run.script_tag,
+                    font_index,
+                });
+            }
+        }
+        runs
+    }
+
+    /// Shape `text`, splitting it into runs with `itemize` that are mapped to glyphs and shaped
+    /// with their font.
+    ///
+    /// The language system `opt_lang_tag`, `features` and `kerning` are used for every run, as
+    /// for `script_itemizer::shape_itemized`.
+    pub fn shape(
+        &mut self,
+        text: &str,
+        opt_lang_tag: Option<u32>,
+        features: &Features,
+        kerning: bool,
+    ) -> Result<Vec<ShapedFontRun>, ShapingError> {
+        let mut shaped_runs = Vec::new();
+        for run in self.itemize(text) {
+            let font = &mut self.fonts[run.font_index];
+            let glyphs =
+                font.map_glyphs(&text[run.range.clone()], MatchingPresentation::NotRequired);
+            let infos = font.shape(glyphs, run.script_tag, opt_lang_tag, features, kerning)?;
+            shaped_runs.push(ShapedFontRun {
+                range: run.range,
+                script_tag: run.script_tag,
+                font_index: run.font_index,
+                infos,
+            });
+        }
+        Ok(shaped_runs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font::OwnedFont;
+    use crate::gsub::GsubFeatureMask;
+    use crate::tag;
+    use crate::tests::read_fixture;
+
+    fn chain() -> FallbackChain<crate::font_data::OwnedFontTableProvider> {
+        let load = |path| {
+            OwnedFont::from_data(read_fixture(path), 0)
+                .unwrap()
+                .unwrap()
+        };
+        FallbackChain::new(vec![
+            load("tests/fonts/opentype/Klei.otf"),
+            load("tests/fonts/noto/NotoNaskhArabic-Regular.ttf"),
+        ])
+    }
+
+    fn runs(chain: &mut FallbackChain<impl FontTableProvider>, text: &str) -> Vec<(String, usize)> {
+        chain
+            .itemize(text)
+            .into_iter()
+            .map(|run| (text[run.range].to_string(), run.font_index))
+            .collect()
+    }
+
+    #[test]
+    fn test_font_for_char() {
+        let mut chain = chain();
+        assert_eq!(chain.font_for_char('a'), Some(0));
+        assert_eq!(chain.font_for_char('\u{0628}'), Some(1));
+        assert_eq!(chain.font_for_char('\u{2603}'), None);
+    }
+
+    #[test]
+    fn test_itemize() {
+        let mut chain = chain();
+        assert_eq!(runs(&mut chain, ""), vec![]);
+        assert_eq!(
+            runs(&mut chain, "ab \u{0628}\u{062A} cd"),
+            vec![
+                ("ab ".to_string(), 0),
+                ("\u{0628}\u{062A} ".to_string(), 1),
+                ("cd".to_string(), 0)
+            ]
+        );
+        // Characters that no font covers stay in the run they're in
+        assert_eq!(
+            runs(&mut chain, "a\u{2603}b"),
+            vec![("a\u{2603}b".to_string(), 0)]
+        );
+    }
+
+    #[test]
+    fn test_shape() {
+        let mut chain = chain();
+        let text = "a \u{0628}\u{062A}";
+        let runs = chain
+            .shape(
+                text,
+                None,
+                &Features::Mask(GsubFeatureMask::default()),
+                true,
+            )
+            .unwrap();
+        let summary = runs
+            .iter()
+            .map(|run| (run.script_tag, run.font_index, run.infos.len()))
+            .collect::<Vec<_>>();
+        assert_eq!(summary, vec![(tag::LATN, 0, 2), (tag::ARAB, 1, 2)]);
+    }
+}
@@ -1,30 +1,90 @@
 use std::borrow::Cow;
 use std::convert::{self, TryFrom};
 use std::rc::Rc;
+use std::sync::Arc;
 
 use bitflags::bitflags;
+#[cfg(feature = "shaping")]
 use log::error;
+#[cfg(all(feature = "shaping", feature = "rayon"))]
+use rayon::prelude::*;
 use rustc_hash::FxHashMap;
+#[cfg(feature = "shaping")]
 use tinyvec::tiny_vec;
 
 use crate::big5::unicode_to_big5;
 use crate::binary::read::ReadScope;
+#[cfg(feature = "bitmap")]
 use crate::bitmap::cbdt::{self, CBDTTable, CBLCTable};
+#[cfg(feature = "bitmap")]
 use crate::bitmap::sbix::Sbix as SbixTable;
+#[cfg(feature = "bitmap")]
 use crate::bitmap::{BitDepth, BitmapGlyph};
-use crate::error::{ParseError, ShapingError};
+use crate::cff::CFF;
+#[cfg(feature = "shaping")]
+use crate::charmap::{self, MappedText, Normalization, UnmappedChar};
+use crate::decoration::DecorationMetrics;
+#[cfg(feature = "shaping")]
+use crate::decoration::{ScriptPosition, ScriptTransform};
+#[cfg(feature = "shaping")]
+use crate::error::ShapingError;
+use crate::error::{ParseError, ReadWriteError};
+use crate::font_data::OwnedFontTableProvider;
+use crate::gdef::CaretPosition;
+#[cfg(feature = "shaping")]
+use crate::get_name::fontcode_get_name;
 use crate::glyph_info::GlyphNames;
-use crate::gpos::Info;
-use crate::gsub::{Features, GlyphOrigin, RawGlyph};
-use crate::layout::{new_layout_cache, GDEFTable, LayoutCache, LayoutTable, GPOS, GSUB};
+#[cfg(feature = "shaping")]
+use crate::gpos::{
+    DeviceContext, GlyphPosition, GposPlan, Info, StretchMode, TextDirection, VerticalMetrics,
+};
+#[cfg(feature = "shaping")]
+use crate::gsub::{
+    FeatureInfo, FeatureSetting, Features, GlyphOrigin, GsubFeatureMask, GsubPlan, RawGlyph,
+};
+use crate::layout::GDEFTable;
+#[cfg(feature = "shaping")]
+use crate::layout::{new_layout_cache, FeatureParams, LayoutCache, LayoutTable};
+#[cfg(feature = "shaping")]
+use crate::layout::{GPOS, GSUB};
 use crate::macroman::char_to_macroman;
+use crate::outline_cache::OutlineCache;
+use crate::post::PostTable;
+#[cfg(feature = "shaping")]
+use crate::scripts::{DefaultFeatures, ScriptType};
 use crate::tables::cmap::{Cmap, CmapSubtable, EncodingId, EncodingRecord, PlatformId};
+#[cfg(feature = "shaping")]
+use crate::tables::cmap::{CmapSubtableFormat14, VariationSequenceGlyph};
+#[cfg(feature = "bitmap")]
+use crate::tables::colr::{ColrTable, Paint};
+#[cfg(feature = "bitmap")]
+use crate::tables::cpal::{CpalTable, Rgba};
+use crate::tables::glyf::{estimate_vertical_stem_width, BoundingBox, GlyfTable, GlyphPoints};
+#[cfg(feature = "variations")]
+use crate::tables::gvar::GvarTable;
+#[cfg(feature = "shaping")]
+use crate::tables::kern::KernTable;
+#[cfg(feature = "shaping")]
+use crate::tables::kerx::KerxTable;
+use crate::tables::loca::LocaTable;
+#[cfg(feature = "shaping")]
+use crate::tables::morx::MorxTable;
+use crate::tables::mvar::MvarTable;
 use crate::tables::os2::Os2;
+#[cfg(feature = "bitmap")]
 use crate::tables::svg::SvgTable;
-use crate::tables::{FontTableProvider, HeadTable, HheaTable, MaxpTable};
+#[cfg(feature = "shaping")]
+use crate::tables::vorg::VorgTable;
+#[cfg(feature = "shaping")]
+use crate::tables::vvar::VvarTable;
+use crate::tables::{
+    F2Dot14, FontTableProvider, HeadTable, HheaTable, IndexToLocFormat, MaxpTable,
+};
 use crate::unicode::{self, VariationSelector};
+use crate::{gdef, DOTTED_CIRCLE};
 use crate::{glyph_info, tag};
-use crate::{gpos, gsub, DOTTED_CIRCLE};
+#[cfg(feature = "shaping")]
+use crate::{gpos, gsub, morx};
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum Encoding {
@@ -47,6 +107,224 @@ enum LazyLoad<T> {
     Loaded(Option<T>),
 }
 
+/// Strategy used by `Font::line_metrics` to choose the source of vertical metrics.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum LineMetricsPolicy {
+    /// Use `ascender`, `descender`, and `lineGap` from the `hhea` table.
+    Hhea,
+    /// Use `sTypoAscender`, `sTypoDescender`, and `sTypoLineGap` from the `OS/2` table if the
+    /// `USE_TYPO_METRICS` bit of `fsSelection` is set, otherwise behave like `Hhea`.
+    Typo,
+    /// Use `usWinAscent` and `usWinDescent` from the `OS/2` table with a line gap of zero. This
+    /// matches the behaviour of GDI on Windows.
+    Win,
+    /// Use the typo metrics if `USE_TYPO_METRICS` is set, then `hhea` if its ascender or
+    /// descender are non-zero, then the typo metrics if they are non-zero, and finally the win
+    /// metrics. This matches the behaviour of most browsers.
+    Auto,
+}
+
+/// Resolved vertical metrics of a font in font units.
+///
+/// `descent` follows the `hhea` convention of being negative for a descent below the baseline.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct LineMetrics {
+    pub ascent: i16,
+    pub descent: i16,
+    pub line_gap: i16,
+}
+
+/// Font-wide metrics in font units, returned by `Font::metrics`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct FontMetrics {
+    /// The number of font units per em.
+    pub units_per_em: u16,
+    /// The ascent, descent, and line gap resolved with `LineMetricsPolicy::Auto`.
+    pub line_metrics: LineMetrics,
+    /// The x-height, see `Font::x_height`.
+    pub x_height: Option<i16>,
+    /// The cap-height, see `Font::cap_height`.
+    pub cap_height: Option<i16>,
+}
+
+/// A run of text to be shaped by `Font::shape_runs_parallel`.
+#[cfg(all(feature = "shaping", feature = "rayon"))]
+pub struct TextRun<'a> {
+    pub text: &'a str,
+    pub script_tag: u32,
+    pub opt_lang_tag: Option<u32>,
+    pub features: Features,
+    pub kerning: bool,
+}
+
+/// A `TextRun` after shaping.
+#[cfg(all(feature = "shaping", feature = "rayon"))]
+#[derive(Debug)]
+pub struct ShapedRun {
+    pub infos: Vec<Info>,
+}
+
+/// The lookups and feature resolution used to shape text in a script and language.
+///
+/// A plan is built by `Font::shaping_plan`, which caches it for the script, language, features
+/// and kerning it was built for, and can be reused across calls to `Font::shape_with_plan`. A
+/// plan is only valid for the font that built it.
+#[cfg(feature = "shaping")]
+#[derive(Debug)]
+pub struct ShapingPlan {
+    script_tag: u32,
+    opt_lang_tag: Option<u32>,
+    features: Features,
+    /// Whether kerning is enabled, after applying any explicit `kern` setting in `features`.
+    kerning: bool,
+    opt_gsub_plan: Option<GsubPlan>,
+    opt_gpos_plan: Option<GposPlan>,
+    /// Set when `smcp` is enabled but the font doesn't have it.
+    synthesize_smcp: bool,
+    /// Set when `c2sc` is enabled but the font doesn't have it.
+    synthesize_c2sc: bool,
+}
+
+#[cfg(feature = "shaping")]
+impl ShapingPlan {
+    /// The script the plan shapes text in.
+    pub fn script_tag(&self) -> u32 {
+        self.script_tag
+    }
+
+    /// The language system the plan shapes text in, `None` for the default.
+    pub fn opt_lang_tag(&self) -> Option<u32> {
+        self.opt_lang_tag
+    }
+
+    /// The features the plan was built for.
+    pub fn features(&self) -> &Features {
+        &self.features
+    }
+
+    /// Whether the plan applies kerning.
+    pub fn kerning(&self) -> bool {
+        self.kerning
+    }
+}
+
+/// The scaling of capitals used to synthesize small capitals, set by
+/// `Font::small_caps_synthesis`.
+#[cfg(feature = "shaping")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SmallCapsSynthesis {
+    /// The factor the advance and outline of a capital are scaled horizontally by.
+    pub x_scale: f32,
+    /// The factor the outline of a capital is scaled vertically by.
+    pub y_scale: f32,
+}
+
+#[cfg(feature = "shaping")]
+impl Default for SmallCapsSynthesis {
+    /// Scales capitals to roughly the x-height of a typical font, and a little less in width to
+    /// keep the strokes from looking thin.
+    fn default() -> Self {
+        SmallCapsSynthesis {
+            x_scale: 0.8,
+            y_scale: 0.7,
+        }
+    }
+}
+
+/// The names of a feature for display in a user interface, from the `name` table.
+///
+/// Returned by `Font::feature_names`. Names that the font doesn't have are `None`.
+#[cfg(feature = "shaping")]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct FeatureNames {
+    /// The name of the feature, or for the `size` feature the name of its size range.
+    pub ui_label: Option<String>,
+    /// Tooltip text describing the feature.
+    pub tooltip: Option<String>,
+    /// Sample text illustrating the feature.
+    pub sample_text: Option<String>,
+    /// The names of the parameters of a character variant feature, in order.
+    pub param_labels: Vec<String>,
+}
+
+/// The arguments a `ShapingPlan` is cached under by `Font::shaping_plan`.
+#[cfg(feature = "shaping")]
+type ShapingPlanKey = (u32, Option<u32>, Features, bool);
+
+/// The tables used by `Font::shape`, loaded up front so that shaping only needs shared access to
+/// the font.
+#[cfg(feature = "shaping")]
+struct ShapingTables {
+    opt_gsub_cache: Option<LayoutCache<GSUB>>,
+    opt_gpos_cache: Option<LayoutCache<GPOS>>,
+    opt_gdef_table: Option<Arc<GDEFTable>>,
+    /// Only loaded when the font has no `GSUB` table.
+    opt_morx_data: Option<Arc<[u8]>>,
+    /// Only loaded when the font has no `GPOS` table.
+    opt_kerx_data: Option<Arc<[u8]>>,
+    /// Only loaded when the font has no `GPOS` table.
+    opt_kern_data: Option<Arc<[u8]>>,
+    /// Only loaded when `Font::variation_coords` is not empty.
+    opt_gdef_data: Option<Arc<[u8]>>,
+    /// Only read when `Font::ppem` is set.
+    units_per_em: u16,
+    dotted_circle_index: u16,
+}
+
+/// A source of color glyphs, used by `Font::color_glyph` to choose between the color glyph
+/// formats of a font.
+#[cfg(feature = "bitmap")]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ColorGlyphSource {
+    /// Version 1 paint graphs from the `COLR` table.
+    Colr1,
+    /// Version 0 layers from the `COLR` table.
+    Colr0,
+    /// SVG documents from the `SVG ` table.
+    Svg,
+    /// Images from the `sbix` table.
+    Sbix,
+    /// Bitmaps from the `CBDT` table.
+    Cbdt,
+    /// The outline of the glyph, drawn in the foreground color.
+    Outline,
+}
+
+/// The default order of preference of the color glyph sources: vector formats, then bitmaps,
+/// falling back to the outline.
+#[cfg(feature = "bitmap")]
+pub const DEFAULT_COLOR_GLYPH_SOURCES: [ColorGlyphSource; 6] = [
+    ColorGlyphSource::Colr1,
+    ColorGlyphSource::Colr0,
+    ColorGlyphSource::Svg,
+    ColorGlyphSource::Sbix,
+    ColorGlyphSource::Cbdt,
+    ColorGlyphSource::Outline,
+];
+
+/// A color glyph returned by `Font::color_glyph`.
+#[cfg(feature = "bitmap")]
+pub enum ColorGlyph {
+    /// A paint graph from the `COLR` table.
+    Colr {
+        /// Whether the paint graph is from version 0 or 1 of the table.
+        source: ColorGlyphSource,
+        /// The paint graph. Version 0 glyphs are returned as layers of solid filled glyphs.
+        paint: Paint,
+        /// The colors of the selected palette, which palette indices in `paint` refer to.
+        palette: Vec<Rgba>,
+    },
+    /// An image from the `SVG `, `sbix` or `CBDT` table.
+    Image {
+        /// The table the image is from.
+        source: ColorGlyphSource,
+        /// The image and its metrics.
+        bitmap: BitmapGlyph,
+    },
+    /// No color glyph was found, the outline of the glyph should be drawn.
+    Outline,
+}
+
 #[derive(Eq, PartialEq, Copy, Clone)]
 pub enum MatchingPresentation {
     Required,
@@ -60,6 +338,19 @@ pub enum MatchingPresentation {
 /// the glyph.
 struct GlyphCache(Option<(u16, VariationSelector)>);
 
+/// The outline tables of a font, loaded so that glyphs can be measured while the `Font` is
+/// borrowed.
+enum OutlineTables {
+    Glyf {
+        glyf_data: Arc<[u8]>,
+        loca_data: Arc<[u8]>,
+        num_glyphs: usize,
+        index_to_loc_format: IndexToLocFormat,
+    },
+    Cff(Arc<[u8]>),
+    None,
+}
+
 pub struct Font<T: FontTableProvider> {
     pub font_table_provider: T,
     cmap_table: Box<[u8]>,
@@ -67,18 +358,79 @@ pub struct Font<T: FontTableProvider> {
     hmtx_table: Box<[u8]>,
     pub hhea_table: HheaTable,
     vmtx_table: LazyLoad<Box<[u8]>>,
-    vhea_table: LazyLoad<Rc<HheaTable>>,
+    vhea_table: LazyLoad<Arc<HheaTable>>,
+    #[cfg(feature = "shaping")]
+    vorg_table: LazyLoad<Box<[u8]>>,
+    #[cfg(feature = "shaping")]
+    vvar_table: LazyLoad<Box<[u8]>>,
+    #[cfg(feature = "shaping")]
+    kern_table: LazyLoad<Arc<[u8]>>,
+    #[cfg(feature = "shaping")]
+    kerx_table: LazyLoad<Arc<[u8]>>,
+    #[cfg(feature = "shaping")]
+    morx_table: LazyLoad<Arc<[u8]>>,
+    #[cfg(feature = "shaping")]
+    gdef_table: LazyLoad<Arc<[u8]>>,
+    #[cfg(feature = "bitmap")]
+    colr_table: LazyLoad<Arc<[u8]>>,
+    #[cfg(feature = "bitmap")]
+    cpal_table: LazyLoad<Arc<[u8]>>,
+    glyf_table: LazyLoad<Arc<[u8]>>,
+    loca_table: LazyLoad<Arc<[u8]>>,
+    cff_table: LazyLoad<Arc<[u8]>>,
+    #[cfg(feature = "variations")]
+    gvar_table: LazyLoad<Arc<[u8]>>,
     cmap_subtable_offset: usize,
     pub cmap_subtable_encoding: Encoding,
-    gdef_cache: LazyLoad<Rc<GDEFTable>>,
+    gdef_cache: LazyLoad<Arc<GDEFTable>>,
+    #[cfg(feature = "shaping")]
     gsub_cache: LazyLoad<LayoutCache<GSUB>>,
+    #[cfg(feature = "shaping")]
     gpos_cache: LazyLoad<LayoutCache<GPOS>>,
+    #[cfg(feature = "shaping")]
+    shaping_plans: FxHashMap<ShapingPlanKey, Arc<ShapingPlan>>,
+    #[cfg(feature = "shaping")]
+    default_features: DefaultFeatures,
     os2_us_first_char_index: LazyLoad<u16>,
     glyph_cache: GlyphCache,
     pub glyph_table_flags: GlyphTableFlags,
-    embedded_images: LazyLoad<Rc<Images>>,
+    /// Controls whether glyphs decomposed by the `stch` feature are repeated by `shape`.
+    #[cfg(feature = "shaping")]
+    pub stretch_mode: StretchMode,
+    /// The size in pixels per em that `shape` applies the hinting deltas of `GPOS` device
+    /// tables for. The default of `None` ignores them.
+    #[cfg(feature = "shaping")]
+    pub ppem: Option<u16>,
+    /// The normalised variation coordinates of the instance of a variable font that `shape`
+    /// applies the variation deltas of `GPOS` values for. Empty for the default instance.
+    #[cfg(feature = "shaping")]
+    pub variation_coords: Vec<F2Dot14>,
+    /// When set, `shape` synthesizes the `smcp` and `c2sc` features for fonts that don't have
+    /// them by substituting capitals that are scaled down.
+    ///
+    /// The substituted glyphs have `small_caps` set. Their advances are scaled by adjusting their
+    /// kerning, and `small_caps_outline` returns their scaled outlines.
+    #[cfg(feature = "shaping")]
+    pub small_caps_synthesis: Option<SmallCapsSynthesis>,
+    /// The color glyph sources used by `color_glyph`, in order of preference.
+    #[cfg(feature = "bitmap")]
+    pub color_glyph_sources: Vec<ColorGlyphSource>,
+    /// When set, outlines returned by `outline` are memoized per glyph and variation instance.
+    pub outline_cache: Option<OutlineCache<GlyphPoints>>,
+    #[cfg(feature = "bitmap")]
+    svg_images: LazyLoad<Arc<Images>>,
+    #[cfg(feature = "bitmap")]
+    sbix_images: LazyLoad<Arc<Images>>,
+    #[cfg(feature = "bitmap")]
+    cbdt_images: LazyLoad<Arc<Images>>,
 }
 
+/// A `Font` that owns its font data and so has no lifetime parameter.
+///
+/// Construct with `OwnedFont::from_data`.
+pub type OwnedFont = Font<OwnedFontTableProvider>;
+
+#[cfg(feature = "bitmap")]
 pub enum Images {
     Embedded {
         cblc: tables::CBLC,
@@ -88,6 +440,7 @@ pub enum Images {
     Svg(tables::Svg),
 }
 
+#[cfg(feature = "bitmap")]
 rental! {
     mod tables {
         use super::*;
@@ -125,6 +478,7 @@ bitflags! {
         const SVG  = 1 << 2;
         const SBIX = 1 << 3;
         const CBDT = 1 << 4;
+        const COLR = 1 << 5;
     }
 }
 
@@ -134,19 +488,36 @@ const TABLE_TAG_FLAGS: &[(u32, GlyphTableFlags)] = &[
     (tag::SVG, GlyphTableFlags::SVG),
     (tag::SBIX, GlyphTableFlags::SBIX),
     (tag::CBDT, GlyphTableFlags::CBDT),
+    (tag::COLR, GlyphTableFlags::COLR),
 ];
 
+impl Font<OwnedFontTableProvider> {
+    /// Parse a font file, taking ownership of `data`.
+    ///
+    /// `data` can be any supported font format. `index` selects the font to use when `data` holds
+    /// a font collection. Returns `None` if the font does not have a suitable `cmap` sub-table.
+    pub fn from_data(
+        data: impl Into<Arc<[u8]>>,
+        index: usize,
+    ) -> Result<Option<OwnedFont>, ReadWriteError> {
+        let provider = OwnedFontTableProvider::new(data, index)?;
+        Ok(Font::new(provider)?)
+    }
+}
+
 impl<T: FontTableProvider> Font<T> {
     pub fn new(provider: T) -> Result<Option<Font<T>>, ParseError> {
         let cmap_table = read_and_box_table(&provider, tag::CMAP)?;
 
-        match charmap_info(&cmap_table)? {
+        match charmap_info(&cmap_table).map_err(|err| err.with_table(tag::CMAP))? {
             Some((cmap_subtable_encoding, cmap_subtable_offset)) => {
-                let maxp_table =
-                    ReadScope::new(&provider.read_table_data(tag::MAXP)?).read::<MaxpTable>()?;
+                let maxp_table = ReadScope::new(&provider.read_table_data(tag::MAXP)?)
+                    .read::<MaxpTable>()
+                    .map_err(|err| err.with_table(tag::MAXP))?;
                 let hmtx_table = read_and_box_table(&provider, tag::HMTX)?;
-                let hhea_table =
-                    ReadScope::new(&provider.read_table_data(tag::HHEA)?).read::<HheaTable>()?;
+                let hhea_table = ReadScope::new(&provider.read_table_data(tag::HHEA)?)
+                    .read::<HheaTable>()
+                    .map_err(|err| err.with_table(tag::HHEA))?;
 
                 let mut glyph_table_flags = GlyphTableFlags::empty();
                 for &(table, flag) in TABLE_TAG_FLAGS {
@@ -163,15 +534,58 @@ impl<T: FontTableProvider> Font<T> {
                     hhea_table,
                     vmtx_table: LazyLoad::NotLoaded,
                     vhea_table: LazyLoad::NotLoaded,
+                    #[cfg(feature = "shaping")]
+                    vorg_table: LazyLoad::NotLoaded,
+                    #[cfg(feature = "shaping")]
+                    vvar_table: LazyLoad::NotLoaded,
+                    #[cfg(feature = "shaping")]
+                    kern_table: LazyLoad::NotLoaded,
+                    #[cfg(feature = "shaping")]
+                    kerx_table: LazyLoad::NotLoaded,
+                    #[cfg(feature = "shaping")]
+                    morx_table: LazyLoad::NotLoaded,
+                    #[cfg(feature = "shaping")]
+                    gdef_table: LazyLoad::NotLoaded,
+                    #[cfg(feature = "bitmap")]
+                    colr_table: LazyLoad::NotLoaded,
+                    #[cfg(feature = "bitmap")]
+                    cpal_table: LazyLoad::NotLoaded,
+                    glyf_table: LazyLoad::NotLoaded,
+                    loca_table: LazyLoad::NotLoaded,
+                    cff_table: LazyLoad::NotLoaded,
+                    #[cfg(feature = "variations")]
+                    gvar_table: LazyLoad::NotLoaded,
                     cmap_subtable_offset: usize::try_from(cmap_subtable_offset)?,
                     cmap_subtable_encoding,
                     gdef_cache: LazyLoad::NotLoaded,
+                    #[cfg(feature = "shaping")]
                     gsub_cache: LazyLoad::NotLoaded,
+                    #[cfg(feature = "shaping")]
                     gpos_cache: LazyLoad::NotLoaded,
+                    #[cfg(feature = "shaping")]
+                    shaping_plans: FxHashMap::default(),
+                    #[cfg(feature = "shaping")]
+                    default_features: DefaultFeatures::new(),
                     os2_us_first_char_index: LazyLoad::NotLoaded,
                     glyph_cache: GlyphCache::new(),
                     glyph_table_flags,
-                    embedded_images: LazyLoad::NotLoaded,
+                    #[cfg(feature = "shaping")]
+                    stretch_mode: StretchMode::default(),
+                    #[cfg(feature = "shaping")]
+                    ppem: None,
+                    #[cfg(feature = "shaping")]
+                    small_caps_synthesis: None,
+                    #[cfg(feature = "shaping")]
+                    variation_coords: Vec::new(),
+                    #[cfg(feature = "bitmap")]
+                    color_glyph_sources: DEFAULT_COLOR_GLYPH_SOURCES.to_vec(),
+                    outline_cache: None,
+                    #[cfg(feature = "bitmap")]
+                    svg_images: LazyLoad::NotLoaded,
+                    #[cfg(feature = "bitmap")]
+                    sbix_images: LazyLoad::NotLoaded,
+                    #[cfg(feature = "bitmap")]
+                    cbdt_images: LazyLoad::NotLoaded,
                 }))
             }
             None => Ok(None),
@@ -196,10 +610,17 @@ impl<T: FontTableProvider> Font<T> {
         })
     }
 
+    /// Returns the glyph index of `ch`, or 0 (`.notdef`) if the font does not map it.
+    pub fn map_char(&mut self, ch: char) -> u16 {
+        let (glyph_index, _) = self.lookup_glyph_index(ch, MatchingPresentation::NotRequired, None);
+        glyph_index
+    }
+
     /// Convenience method to shape the supplied glyphs.
     ///
     /// The method maps applies glyph substitution (`gsub`) and glyph positioning (`gpos`). Use
-    /// `map_glyphs` to turn text into glyphs that can be accepted by this method.
+    /// `map_glyphs` to turn text into glyphs that can be accepted by this method. Fonts without a
+    /// `gsub` table have the Apple `morx` table applied instead, if present.
     ///
     /// **Arguments:**
     ///
@@ -209,7 +630,12 @@ impl<T: FontTableProvider> Font<T> {
     /// * `features`: the [OpenType features](https://docs.microsoft.com/en-us/typography/opentype/spec/featuretags) to enable.
     /// * `kerning`: when applying `gpos` if this argument is `true` the `kern` OpenType feature
     ///   is enabled for non-complex scripts. If it is `false` then the `kern` feature is not
-    ///   enabled for non-complex scripts.
+    ///   enabled for non-complex scripts. Fonts without a `gpos` table are kerned using the
+    ///   Apple `kerx` table or the legacy `kern` table, if present, under the same conditions.
+    ///
+    /// Optional positioning features, such as `palt`, are applied when they are included in
+    /// `Features::Custom` or enabled by `Features::Settings`. A `kern` setting in
+    /// `Features::Settings` overrides the `kerning` argument.
     ///
     /// **Error Handling:**
     ///
@@ -257,31 +683,273 @@ impl<T: FontTableProvider> Font<T> {
     /// // number of input characters (19).
     /// assert_eq!(glyph_infos.len(), 18);
     /// ```
+    #[cfg(feature = "shaping")]
     pub fn shape(
         &mut self,
-        mut glyphs: Vec<RawGlyph<()>>,
+        glyphs: Vec<RawGlyph<()>>,
+        script_tag: u32,
+        opt_lang_tag: Option<u32>,
+        features: &Features,
+        kerning: bool,
+    ) -> Result<Vec<Info>, ShapingError> {
+        let plan = self.shaping_plan(script_tag, opt_lang_tag, features, kerning)?;
+        self.shape_with_plan(&plan, glyphs)
+    }
+
+    /// Returns the plan for shaping text with the supplied script, language, features and
+    /// kerning, as used by `shape`.
+    ///
+    /// The plan resolves the features against the font's `GSUB` and `GPOS` tables. Plans are
+    /// cached by the font so that subsequent calls with the same arguments return the same plan.
+    #[cfg(feature = "shaping")]
+    pub fn shaping_plan(
+        &mut self,
         script_tag: u32,
         opt_lang_tag: Option<u32>,
         features: &Features,
         kerning: bool,
+    ) -> Result<Arc<ShapingPlan>, ShapingError> {
+        let key = (script_tag, opt_lang_tag, features.clone(), kerning);
+        if let Some(plan) = self.shaping_plans.get(&key) {
+            return Ok(Arc::clone(plan));
+        }
+
+        // An explicit `kern` setting takes precedence over the `kerning` argument
+        let kerning = features
+            .setting(tag::KERN)
+            .map_or(kerning, |value| value != 0);
+        let opt_gsub_plan = match self.gsub_cache()? {
+            Some(gsub_cache) => Some(GsubPlan::with_default_features(
+                &gsub_cache,
+                &self.default_features,
+                script_tag,
+                opt_lang_tag,
+                features,
+            )?),
+            None => None,
+        };
+        let opt_gpos_plan = match self.gpos_cache()? {
+            Some(gpos_cache) => {
+                let feature_tags = match features {
+                    Features::Mask(_) => Vec::new(),
+                    Features::Custom(features_list) => features_list
+                        .iter()
+                        .map(|feature_info| feature_info.feature_tag)
+                        .collect::<Vec<_>>(),
+                    Features::Settings(settings) => {
+                        let mut feature_tags = settings
+                            .iter()
+                            .map(|setting| setting.feature_tag)
+                            .filter(|&feature_tag| features.setting(feature_tag) != Some(0))
+                            .collect::<Vec<_>>();
+                        feature_tags.sort_unstable();
+                        feature_tags.dedup();
+                        feature_tags
+                    }
+                };
+                Some(GposPlan::with_default_features(
+                    &gpos_cache,
+                    &self.default_features,
+                    kerning,
+                    script_tag,
+                    opt_lang_tag,
+                    &feature_tags,
+                )?)
+            }
+            None => None,
+        };
+
+        let gsub_feature_tags = match self.gsub_cache()? {
+            Some(gsub_cache) => gsub_cache.layout_table.feature_tags(),
+            None => Vec::new(),
+        };
+        let synthesize = |feature_tag| {
+            features.is_enabled(feature_tag) && !gsub_feature_tags.contains(&feature_tag)
+        };
+
+        let plan = Arc::new(ShapingPlan {
+            script_tag,
+            opt_lang_tag,
+            features: features.clone(),
+            kerning,
+            opt_gsub_plan,
+            opt_gpos_plan,
+            synthesize_smcp: synthesize(tag::SMCP),
+            synthesize_c2sc: synthesize(tag::C2SC),
+        });
+        self.shaping_plans.insert(key, Arc::clone(&plan));
+        Ok(plan)
+    }
+
+    /// Returns the features applied by default when shaping each script.
+    #[cfg(feature = "shaping")]
+    pub fn default_features(&self) -> &DefaultFeatures {
+        &self.default_features
+    }
+
+    /// Set the features applied by default when shaping each script.
+    ///
+    /// The positioning features of an overridden script replace the built-in ones in `shape`.
+    /// Its substitution features replace `GsubFeatureMask::default()` as the starting point for
+    /// `Features::Settings`; a `Features::Mask` is always applied as supplied. Plans cached by
+    /// `shaping_plan` are discarded.
+    #[cfg(feature = "shaping")]
+    pub fn set_default_features(&mut self, default_features: DefaultFeatures) {
+        self.default_features = default_features;
+        self.shaping_plans.clear();
+    }
+
+    /// Perform shaping according to a plan returned by `shaping_plan`.
+    ///
+    /// This is equivalent to calling `shape` with the arguments the plan was built for, without
+    /// the cost of looking up the plan. `plan` must have been built by this font.
+    #[cfg(feature = "shaping")]
+    pub fn shape_with_plan(
+        &mut self,
+        plan: &ShapingPlan,
+        mut glyphs: Vec<RawGlyph<()>>,
     ) -> Result<Vec<Info>, ShapingError> {
+        let tables = self.shaping_tables()?;
+        self.synthesize_small_caps(plan, &mut glyphs);
+        let mut infos = self.shape_with_tables(&tables, plan, glyphs)?;
+        self.resolve_anchor_points(&mut infos)?;
+        Ok(infos)
+    }
+
+    /// Substitute capitals for the glyphs that the `smcp` and `c2sc` features would substitute
+    /// small capitals for, when `small_caps_synthesis` is set and the font doesn't have them.
+    #[cfg(feature = "shaping")]
+    fn synthesize_small_caps(&mut self, plan: &ShapingPlan, glyphs: &mut [RawGlyph<()>]) {
+        if self.small_caps_synthesis.is_none() {
+            return;
+        }
+        for glyph in glyphs.iter_mut() {
+            let ch = match glyph.source_char() {
+                Some(ch) => ch,
+                None => continue,
+            };
+            if plan.synthesize_c2sc && ch.is_uppercase() {
+                glyph.small_caps = true;
+            } else if plan.synthesize_smcp && ch.is_lowercase() {
+                // Only characters with a single uppercase character are substituted
+                let mut upper = ch.to_uppercase();
+                if let (Some(upper), None) = (upper.next(), upper.next()) {
+                    let glyph_index = self.map_char(upper);
+                    if glyph_index != 0 {
+                        glyph.glyph_index = glyph_index;
+                        glyph.small_caps = true;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the outline of `glyph` at `variation_coords`, scaled according to
+    /// `small_caps_synthesis` if it's a synthesized small capital.
+    #[cfg(feature = "shaping")]
+    pub fn small_caps_outline<T2>(
+        &mut self,
+        glyph: &RawGlyph<T2>,
+    ) -> Result<Option<GlyphPoints>, ParseError> {
+        let coords = self.variation_coords.clone();
+        let mut outline = self.outline(glyph.glyph_index, &coords)?;
+        if let (true, Some(synthesis), Some(outline)) = (
+            glyph.small_caps,
+            self.small_caps_synthesis,
+            outline.as_mut(),
+        ) {
+            for point in &mut outline.points {
+                point.0 = (f32::from(point.0) * synthesis.x_scale).round() as i16;
+                point.1 = (f32::from(point.1) * synthesis.y_scale).round() as i16;
+            }
+        }
+        Ok(outline)
+    }
+
+    /// Move anchors that reference a contour point to that point of the glyph outline at
+    /// `variation_coords`. See `gpos::resolve_anchor_points`.
+    #[cfg(feature = "shaping")]
+    fn resolve_anchor_points(&mut self, infos: &mut [Info]) -> Result<(), ParseError> {
+        let coords = self.variation_coords.clone();
+        gpos::resolve_anchor_points(infos, |glyph, point_index| {
+            let opt_point = self
+                .outline(glyph, &coords)?
+                .and_then(|outline| outline.points.get(usize::from(point_index)).copied());
+            Ok(opt_point.map(|point| (point.0, point.1)))
+        })
+    }
+
+    /// Load the tables used by `shape`.
+    #[cfg(feature = "shaping")]
+    fn shaping_tables(&mut self) -> Result<ShapingTables, ParseError> {
         let opt_gsub_cache = self.gsub_cache()?;
         let opt_gpos_cache = self.gpos_cache()?;
         let opt_gdef_table = self.gdef_table()?;
-        let opt_gdef_table = opt_gdef_table.as_ref().map(Rc::as_ref);
         let (dotted_circle_index, _) =
             self.lookup_glyph_index(DOTTED_CIRCLE, MatchingPresentation::NotRequired, None);
 
+        let provider = &self.font_table_provider;
+        let opt_morx_data = match opt_gsub_cache {
+            Some(_) => None,
+            None => self.morx_table.get_or_load(|| {
+                Ok(read_and_box_optional_table(provider, tag::MORX)?.map(Arc::from))
+            })?,
+        };
+        let (opt_kerx_data, opt_kern_data) = match opt_gpos_cache {
+            Some(_) => (None, None),
+            None => (
+                self.kerx_table.get_or_load(|| {
+                    Ok(read_and_box_optional_table(provider, tag::KERX)?.map(Arc::from))
+                })?,
+                self.kern_table.get_or_load(|| {
+                    Ok(read_and_box_optional_table(provider, tag::KERN)?.map(Arc::from))
+                })?,
+            ),
+        };
+
+        // The item variation store of `GDEF` is only needed away from the default instance
+        let opt_gdef_data = match opt_gpos_cache {
+            Some(_) if !self.variation_coords.is_empty() => self.gdef_table.get_or_load(|| {
+                Ok(read_and_box_optional_table(provider, tag::GDEF)?.map(Arc::from))
+            })?,
+            _ => None,
+        };
+        let units_per_em = match self.ppem {
+            Some(_) => self.head_table()?.map_or(0, |head| head.units_per_em),
+            None => 0,
+        };
+
+        Ok(ShapingTables {
+            opt_gsub_cache,
+            opt_gpos_cache,
+            opt_gdef_table,
+            opt_morx_data,
+            opt_kerx_data,
+            opt_kern_data,
+            opt_gdef_data,
+            units_per_em,
+            dotted_circle_index,
+        })
+    }
+
+    #[cfg(feature = "shaping")]
+    fn shape_with_tables(
+        &self,
+        tables: &ShapingTables,
+        plan: &ShapingPlan,
+        mut glyphs: Vec<RawGlyph<()>>,
+    ) -> Result<Vec<Info>, ShapingError> {
+        let opt_gdef_table = tables.opt_gdef_table.as_ref().map(Arc::as_ref);
+        let (script_tag, features, kerning) = (plan.script_tag, &plan.features, plan.kerning);
+
         // Apply gsub if table is present
         let num_glyphs = self.num_glyphs();
-        if let Some(gsub_cache) = opt_gsub_cache {
-            let res = gsub::apply(
-                dotted_circle_index,
-                &gsub_cache,
+        if let (Some(gsub_cache), Some(gsub_plan)) = (&tables.opt_gsub_cache, &plan.opt_gsub_plan) {
+            let res = gsub::apply_plan(
+                tables.dotted_circle_index,
+                gsub_cache,
                 opt_gdef_table,
-                script_tag,
-                opt_lang_tag,
-                features,
+                gsub_plan,
                 num_glyphs,
                 &mut glyphs,
             );
@@ -291,26 +959,265 @@ impl<T: FontTableProvider> Font<T> {
                 Ok(()) => {}
                 Err(err) => error!("failed to apply gsub: {}", err),
             }
+        } else if let Some(morx_data) = &tables.opt_morx_data {
+            let res = ReadScope::new(morx_data)
+                .read::<MorxTable<'_>>()
+                .and_then(|morx_table| morx::apply(&morx_table, features, &mut glyphs));
+
+            // As with gsub, the glyphs can still be used in the case of error
+            match res {
+                Ok(()) => {}
+                Err(err) => error!("failed to apply morx: {}", err),
+            }
         }
 
         // Apply gpos if table is present
         let mut infos = Info::init_from_glyphs(opt_gdef_table, glyphs);
-        if let Some(gpos_cache) = opt_gpos_cache {
-            gpos::apply(
-                &gpos_cache,
+        if let (Some(gpos_cache), Some(gpos_plan)) = (&tables.opt_gpos_cache, &plan.opt_gpos_plan) {
+            let opt_item_variation_store = match &tables.opt_gdef_data {
+                Some(gdef_data) => gdef::read_item_variation_store(gdef_data)?,
+                None => None,
+            };
+            let device_context = DeviceContext {
+                opt_ppem: self.ppem,
+                units_per_em: tables.units_per_em,
+                coords: &self.variation_coords,
+                opt_item_variation_store: opt_item_variation_store.as_ref(),
+            };
+            gpos::apply_plan(
+                gpos_cache,
                 opt_gdef_table,
-                kerning,
-                script_tag,
-                opt_lang_tag,
+                gpos_plan,
+                &device_context,
                 &mut infos,
             )?;
         } else {
             gpos::apply_fallback(&mut infos);
+            // Like the `kern` feature, kerning can only be disabled for the default shaper
+            if kerning || ScriptType::from(script_tag) != ScriptType::Default {
+                // The `kerx` table supersedes the `kern` table when a font has both
+                if let Some(kerx_data) = &tables.opt_kerx_data {
+                    let kerx_table = ReadScope::new(kerx_data).read::<KerxTable<'_>>()?;
+                    gpos::apply_kerx_table(&kerx_table, &mut infos)?;
+                } else if let Some(kern_data) = &tables.opt_kern_data {
+                    let kern_table = ReadScope::new(kern_data).read::<KernTable<'_>>()?;
+                    gpos::apply_kern_table(&kern_table, &mut infos)?;
+                }
+            }
+        }
+
+        if let Some(synthesis) = self.small_caps_synthesis {
+            let (maxp, hhea, hmtx) = (&self.maxp_table, &self.hhea_table, &self.hmtx_table);
+            gpos::apply_small_caps_scale(&mut infos, synthesis.x_scale, |glyph| {
+                glyph_info::advance(maxp, hhea, hmtx, glyph).ok()
+            });
+        }
+
+        if self.stretch_mode == StretchMode::Repeat {
+            let (maxp, hhea, hmtx) = (&self.maxp_table, &self.hhea_table, &self.hmtx_table);
+            gpos::apply_stretch(&mut infos, |glyph| {
+                glyph_info::advance(maxp, hhea, hmtx, glyph).ok()
+            });
         }
 
         Ok(infos)
     }
 
+    /// Map and shape a batch of independent runs of text in parallel.
+    ///
+    /// Each run is mapped to glyphs and shaped as if by `shape_text`, with kerning controlled by
+    /// `TextRun::kerning`. The tables used for shaping are loaded before the runs are distributed
+    /// across the rayon thread pool, where they share the font's layout caches. The shaped runs
+    /// are returned in the same order as `runs`.
+    #[cfg(all(feature = "shaping", feature = "rayon"))]
+    pub fn shape_runs_parallel(
+        &mut self,
+        runs: &[TextRun<'_>],
+    ) -> Result<Vec<ShapedRun>, ShapingError>
+    where
+        T: Sync,
+    {
+        let tables = self.shaping_tables()?;
+        let plans = runs
+            .iter()
+            .map(|run| {
+                self.shaping_plan(run.script_tag, run.opt_lang_tag, &run.features, run.kerning)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let glyph_runs = runs
+            .iter()
+            .zip(&plans)
+            .map(|(run, plan)| {
+                let mut glyphs = self.map_glyphs(run.text, MatchingPresentation::NotRequired);
+                self.synthesize_small_caps(plan, &mut glyphs);
+                glyphs
+            })
+            .collect::<Vec<_>>();
+
+        let font = &*self;
+        let mut shaped_runs = plans
+            .par_iter()
+            .zip(glyph_runs)
+            .map(|(plan, glyphs)| {
+                let infos = font.shape_with_tables(&tables, plan, glyphs)?;
+                Ok(ShapedRun { infos })
+            })
+            .collect::<Result<Vec<_>, ShapingError>>()?;
+        // Outlines are loaded through the font, so anchor points are resolved afterwards
+        for shaped_run in &mut shaped_runs {
+            self.resolve_anchor_points(&mut shaped_run.infos)?;
+        }
+        Ok(shaped_runs)
+    }
+
+    /// Map `text` to glyphs and shape them with kerning enabled.
+    ///
+    /// This is a convenience for calling `map_glyphs` with `MatchingPresentation::NotRequired`
+    /// followed by `shape`.
+    #[cfg(feature = "shaping")]
+    pub fn shape_text(
+        &mut self,
+        text: &str,
+        script_tag: u32,
+        opt_lang_tag: Option<u32>,
+        features: &Features,
+    ) -> Result<Vec<Info>, ShapingError> {
+        let glyphs = self.map_glyphs(text, MatchingPresentation::NotRequired);
+        self.shape(glyphs, script_tag, opt_lang_tag, features, true)
+    }
+
+    /// Perform shaping for vertical text.
+    ///
+    /// This behaves like `shape`, additionally applying the `vrt2` feature, or `vert` if the font
+    /// lacks `vrt2`, to substitute glyphs with their vertical alternates. Substituted glyphs have
+    /// `is_vert_alt` set to indicate they're already rotated for vertical text. Kerning is
+    /// disabled as the `kern` feature adjusts horizontal advances.
+    ///
+    /// The result can be passed to `vertical_glyph_positions` to lay out the glyphs.
+    #[cfg(feature = "shaping")]
+    pub fn shape_vertical(
+        &mut self,
+        glyphs: Vec<RawGlyph<()>>,
+        script_tag: u32,
+        opt_lang_tag: Option<u32>,
+        features: &Features,
+    ) -> Result<Vec<Info>, ShapingError> {
+        let features = match features {
+            Features::Mask(feature_mask) => {
+                Features::Mask(*feature_mask | GsubFeatureMask::VRT2_OR_VERT)
+            }
+            Features::Custom(features_list) => {
+                let mut features_list = features_list
+                    .iter()
+                    .map(|feature_info| FeatureInfo {
+                        feature_tag: feature_info.feature_tag,
+                        alternate: feature_info.alternate,
+                    })
+                    .collect::<Vec<_>>();
+                if !features_list
+                    .iter()
+                    .any(|info| info.feature_tag == tag::VERT || info.feature_tag == tag::VRT2)
+                {
+                    features_list.push(FeatureInfo {
+                        feature_tag: tag::VERT,
+                        alternate: None,
+                    });
+                }
+                Features::Custom(features_list)
+            }
+            Features::Settings(settings) => {
+                let mut settings = settings.clone();
+                if features.setting(tag::VERT).is_none() && features.setting(tag::VRT2).is_none() {
+                    settings.push(FeatureSetting::on(tag::VRT2));
+                }
+                Features::Settings(settings)
+            }
+        };
+        self.shape(glyphs, script_tag, opt_lang_tag, &features, false)
+    }
+
+    /// Perform shaping for superscript or subscript text.
+    ///
+    /// If the `sups` or `subs` feature of the font, as selected by `position`, has glyphs for all
+    /// of the non-whitespace glyphs, the text is shaped with the feature enabled and no transform
+    /// is returned. Otherwise the text is shaped as by `shape` and the returned transform, derived
+    /// from the `OS/2` table, should be applied to the glyphs to synthesize the superscripts or
+    /// subscripts. Glyphs are never mixed, as the feature glyphs and synthesized glyphs wouldn't
+    /// match in size or position.
+    #[cfg(feature = "shaping")]
+    pub fn shape_script_position(
+        &mut self,
+        glyphs: Vec<RawGlyph<()>>,
+        script_tag: u32,
+        opt_lang_tag: Option<u32>,
+        features: &Features,
+        kerning: bool,
+        position: ScriptPosition,
+    ) -> Result<(Vec<Info>, Option<ScriptTransform>), ShapingError> {
+        let feature_tag = position.feature_tag();
+        if self.feature_covers_glyphs(feature_tag, script_tag, opt_lang_tag, &glyphs)? {
+            let features = features.with_setting(FeatureSetting::on(feature_tag));
+            let infos = self.shape(glyphs, script_tag, opt_lang_tag, &features, kerning)?;
+            return Ok((infos, None));
+        }
+
+        let infos = self.shape(glyphs, script_tag, opt_lang_tag, features, kerning)?;
+        let head = self.head_table()?.ok_or(ParseError::MissingValue)?;
+        let opt_os2 = self.os2_table()?;
+        let transform = ScriptTransform::from_os2(opt_os2.as_ref(), head.units_per_em, position);
+        Ok((infos, Some(transform)))
+    }
+
+    /// Returns `true` if the `GSUB` feature `feature_tag` would substitute every glyph of
+    /// `glyphs` that isn't whitespace.
+    #[cfg(feature = "shaping")]
+    fn feature_covers_glyphs(
+        &mut self,
+        feature_tag: u32,
+        script_tag: u32,
+        opt_lang_tag: Option<u32>,
+        glyphs: &[RawGlyph<()>],
+    ) -> Result<bool, ParseError> {
+        let gsub_cache = match self.gsub_cache()? {
+            Some(gsub_cache) => gsub_cache,
+            None => return Ok(false),
+        };
+        let opt_gdef_table = self.gdef_table()?;
+        let gsub_table = &gsub_cache.layout_table;
+        let langsys = match gsub_table.find_script_or_default(script_tag)? {
+            Some(script) => match script.find_langsys_or_default(opt_lang_tag)? {
+                Some(langsys) => langsys,
+                None => return Ok(false),
+            },
+            None => return Ok(false),
+        };
+        if gsub_table
+            .find_langsys_feature(langsys, feature_tag)?
+            .is_none()
+        {
+            return Ok(false);
+        }
+
+        for (i, glyph) in glyphs.iter().enumerate() {
+            if glyph.unicodes.iter().all(|ch| ch.is_whitespace()) {
+                continue;
+            }
+            let would_apply = gsub::gsub_feature_would_apply(
+                &gsub_cache,
+                gsub_table,
+                opt_gdef_table.as_ref().map(Arc::as_ref),
+                langsys,
+                feature_tag,
+                glyphs,
+                i,
+            )?;
+            if !would_apply {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
     /// Map text to glyphs.
     ///
     /// This method maps text into glyphs, which can then be passed to `shape`.
@@ -322,6 +1229,12 @@ impl<T: FontTableProvider> Font<T> {
     /// `MatchingPresentation::Required` is passed then a character with emoji presentation,
     /// either by default or requested via variation selector will only map to a glyph if the font
     /// has mapping for the character, and it has the necessary tables for color emoji.
+    ///
+    /// Characters that continue an emoji sequence, such as ZWJ and the emoji joined by it, emoji
+    /// modifiers, and keycaps, inherit emoji presentation from the sequence so that the font is
+    /// able to form a single ligature from it. Variation sequences supported by the format 14
+    /// `cmap` subtable are mapped to the glyph it specifies.
+    #[cfg(feature = "shaping")]
     pub fn map_glyphs(
         &mut self,
         text: &str,
@@ -332,35 +1245,104 @@ impl<T: FontTableProvider> Font<T> {
         // skipped as it was handled as part of the preceding character.
         let mut chars_iter = text.chars().peekable();
         let mut glyphs = Vec::new();
+        let mut prev: Option<(char, VariationSelector)> = None;
         while let Some(ch) = chars_iter.next() {
-            match VariationSelector::try_from(ch) {
-                Ok(_) => {} // filter out variation selectors
-                Err(()) => {
-                    let vs = chars_iter
-                        .peek()
-                        .and_then(|&next| VariationSelector::try_from(next).ok());
-                    let (glyph_index, used_variation) =
-                        self.lookup_glyph_index(ch, match_presentation, vs);
-                    let glyph = RawGlyph {
-                        unicodes: tiny_vec![[char; 1] => ch],
-                        glyph_index,
-                        liga_component_pos: 0,
-                        glyph_origin: GlyphOrigin::Char(ch),
-                        small_caps: false,
-                        multi_subst_dup: false,
-                        is_vert_alt: false,
-                        fake_bold: false,
-                        fake_italic: false,
-                        extra_data: (),
-                        variation: Some(used_variation),
-                    };
-                    glyphs.push(glyph);
-                }
+            if unicode::is_variation_selector(ch) {
+                continue; // filter out variation selectors
             }
+
+            let selector = chars_iter
+                .peek()
+                .copied()
+                .filter(|&next| unicode::is_variation_selector(next));
+            let vs = selector
+                .and_then(|next| VariationSelector::try_from(next).ok())
+                .or_else(|| match prev {
+                    Some((prev_ch, VariationSelector::VS16))
+                        if unicode::continues_emoji_sequence(prev_ch, ch) =>
+                    {
+                        Some(VariationSelector::VS16)
+                    }
+                    _ => None,
+                });
+            let variation_glyph_index =
+                selector.and_then(|selector| self.map_variation_sequence(ch, selector));
+            let (glyph_index, used_variation) = match variation_glyph_index {
+                Some(glyph_index) => (glyph_index, Self::resolve_default_presentation(ch, vs)),
+                None => self.lookup_glyph_index(ch, match_presentation, vs),
+            };
+            prev = Some((ch, used_variation));
+            let glyph = RawGlyph {
+                unicodes: tiny_vec![[char; 1] => ch],
+                glyph_index,
+                liga_component_pos: 0,
+                glyph_origin: GlyphOrigin::Char(ch),
+                small_caps: false,
+                multi_subst_dup: false,
+                multi_subst_component: None,
+                is_vert_alt: false,
+                fake_bold: false,
+                fake_italic: false,
+                stretch: None,
+                extra_data: (),
+                variation: Some(used_variation),
+            };
+            glyphs.push(glyph);
         }
         glyphs
     }
 
+    /// Map `text` to glyphs after applying `normalization`, noting the characters that the font
+    /// has no glyph for.
+    ///
+    /// The glyphs are mapped as by `map_glyphs`. Characters without a glyph are mapped to
+    /// `.notdef` and listed in `MappedText::unmapped`, so that callers are able to fall back to
+    /// another font for them. `Normalization::for_script` returns a normalisation suitable for
+    /// the text of a script.
+    #[cfg(feature = "shaping")]
+    pub fn map_text(
+        &mut self,
+        text: &str,
+        normalization: Normalization,
+        match_presentation: MatchingPresentation,
+    ) -> MappedText {
+        let text = charmap::normalize(text, normalization, |ch| self.map_char(ch) != 0);
+        let glyphs = self.map_glyphs(&text, match_presentation);
+        // `map_glyphs` produces a glyph for each character other than variation selectors
+        let unmapped = text
+            .char_indices()
+            .filter(|&(_, ch)| !unicode::is_variation_selector(ch))
+            .zip(glyphs.iter())
+            .filter(|(_, glyph)| glyph.glyph_index == 0)
+            .map(|((offset, ch), _)| UnmappedChar { offset, ch })
+            .collect();
+        MappedText {
+            text,
+            glyphs,
+            unmapped,
+        }
+    }
+
+    /// Look up the glyph for a variation sequence in the format 14 `cmap` subtable.
+    ///
+    /// Returns `None` if the font has no such subtable, or the sequence should use the default
+    /// glyph of `ch`.
+    #[cfg(feature = "shaping")]
+    fn map_variation_sequence(&self, ch: char, selector: char) -> Option<u16> {
+        let cmap = ReadScope::new(&self.cmap_table).read::<Cmap<'_>>().ok()?;
+        let encoding_record =
+            cmap.find_subtable(PlatformId::UNICODE, EncodingId::UNICODE_VARIATION_SEQUENCES)?;
+        let subtable = cmap
+            .scope
+            .offset(usize::try_from(encoding_record.offset).ok()?)
+            .read::<CmapSubtableFormat14<'_>>()
+            .ok()?;
+        match subtable.map_variation_sequence(ch as u32, selector as u32) {
+            Ok(Some(VariationSequenceGlyph::Glyph(glyph_index))) => Some(glyph_index),
+            Ok(Some(VariationSequenceGlyph::Default)) | Ok(None) | Err(_) => None,
+        }
+    }
+
     fn map_glyph(&self, char_code: u32) -> u16 {
         match ReadScope::new(self.cmap_subtable_data()).read::<CmapSubtable<'_>>() {
             // TODO: Cache the parsed CmapSubtable
@@ -495,6 +1477,35 @@ impl<T: FontTableProvider> Font<T> {
         unique_glyph_names(names, ids.len())
     }
 
+    /// Returns the index of the glyph named `name`, or `None` if there is no such glyph.
+    ///
+    /// The names in the `post` table are searched first. Failing that, `name` is interpreted
+    /// following the Adobe Glyph List conventions, as `uniXXXX`, `uXXXX[XX]`, or a name from the
+    /// ISOAdobe character set, and the character is looked up in `cmap`. This allows glyphs to be
+    /// found in fonts without glyph names, such as those with a version 3 `post` table.
+    pub fn glyph_by_name(&mut self, name: &str) -> Option<u16> {
+        if name == ".notdef" {
+            return Some(0);
+        }
+        let post_data = read_and_box_optional_table(&self.font_table_provider, tag::POST)
+            .ok()
+            .and_then(convert::identity);
+        if let Some(post_data) = post_data {
+            if let Ok(post) = ReadScope::new(&post_data).read::<PostTable<'_>>() {
+                let found = (0..self.num_glyphs())
+                    .find(|&glyph_index| post.glyph_name(glyph_index).ok() == Some(Some(name)));
+                if found.is_some() {
+                    return found;
+                }
+            }
+        }
+        let ch = glyph_info::glyph_name_to_unicode(name).and_then(std::char::from_u32)?;
+        match self.map_char(ch) {
+            0 => None,
+            glyph_index => Some(glyph_index),
+        }
+    }
+
     /// Find an image matching the supplied criteria.
     ///
     /// * `glyph_index` is the glyph to lookup.
@@ -502,45 +1513,150 @@ impl<T: FontTableProvider> Font<T> {
     ///    be returned, favouring being oversize vs. undersized.
     /// * `max_bit_depth` is the maximum accepted bit depth of the bitmap to return. If you accept
     ///   all bit depths then use `BitDepth::ThirtyTwo`.
+    #[cfg(feature = "bitmap")]
     pub fn lookup_glyph_image(
         &mut self,
         glyph_index: u16,
         target_ppem: u16,
         max_bit_depth: BitDepth,
     ) -> Result<Option<BitmapGlyph>, ParseError> {
-        let embedded_bitmaps = match self.embedded_images()? {
-            Some(embedded_bitmaps) => embedded_bitmaps,
-            None => return Ok(None),
-        };
-        match embedded_bitmaps.as_ref() {
-            Images::Embedded { cblc, cbdt } => cblc.rent(|cblc: &CBLCTable<'_>| {
-                let target_ppem = if target_ppem > u16::from(std::u8::MAX) {
-                    std::u8::MAX
-                } else {
-                    target_ppem as u8
-                };
-                let bitmap = match cblc.find_strike(glyph_index, target_ppem, max_bit_depth) {
-                    Some(matching_strike) => {
-                        let cbdt = cbdt.suffix();
-                        cbdt::lookup(glyph_index, &matching_strike, cbdt)?.map(|bitmap| {
-                            BitmapGlyph::try_from((&matching_strike.bitmap_size.inner, bitmap))
-                        })
-                    }
-                    None => None,
-                };
-                bitmap.transpose()
-            }),
-            Images::Sbix(sbix) => {
-                self.lookup_sbix_glyph_bitmap(sbix, false, glyph_index, target_ppem, max_bit_depth)
-            }
-            Images::Svg(svg) => self.lookup_svg_glyph(svg, glyph_index),
+        match self.embedded_images()? {
+            Some(images) => self.lookup_image(&images, glyph_index, target_ppem, max_bit_depth),
+            None => Ok(None),
         }
     }
 
-    /// Perform sbix lookup with `dupe` handling.
+    /// Returns the color glyph for `glyph_index` from the first of `color_glyph_sources` that has
+    /// one.
     ///
-    /// The `dupe` flag indicates if this this a dupe lookup or not. To avoid potential infinite
-    /// recursion we only follow one level of `dupe` indirection.
+    /// * `target_ppem` is the desired size of bitmap images. If an exact match can't be found the
+    ///   nearest one will be returned, favouring being oversize vs. undersized.
+    /// * `palette_index` is the `CPAL` palette used for `COLR` glyphs. The first palette is used
+    ///   if the font doesn't have this palette.
+    /// * `coords` are the normalised variation coordinates of the instance of a variable font.
+    ///   Pass an empty slice for the default instance.
+    ///
+    /// Returns `None` if none of the sources have the glyph.
+    #[cfg(feature = "bitmap")]
+    pub fn color_glyph(
+        &mut self,
+        glyph_index: u16,
+        target_ppem: u16,
+        palette_index: u16,
+        coords: &[F2Dot14],
+    ) -> Result<Option<ColorGlyph>, ParseError> {
+        for source in self.color_glyph_sources.clone() {
+            let color_glyph = match source {
+                ColorGlyphSource::Colr1 | ColorGlyphSource::Colr0 => {
+                    self.lookup_colr_glyph(source, glyph_index, palette_index, coords)?
+                }
+                ColorGlyphSource::Svg | ColorGlyphSource::Sbix | ColorGlyphSource::Cbdt => {
+                    match self.images(source)? {
+                        Some(images) => self
+                            .lookup_image(&images, glyph_index, target_ppem, BitDepth::ThirtyTwo)?
+                            .map(|bitmap| ColorGlyph::Image { source, bitmap }),
+                        None => None,
+                    }
+                }
+                ColorGlyphSource::Outline => Some(ColorGlyph::Outline),
+            };
+            if color_glyph.is_some() {
+                return Ok(color_glyph);
+            }
+        }
+        Ok(None)
+    }
+
+    #[cfg(feature = "bitmap")]
+    fn lookup_colr_glyph(
+        &mut self,
+        source: ColorGlyphSource,
+        glyph_index: u16,
+        palette_index: u16,
+        coords: &[F2Dot14],
+    ) -> Result<Option<ColorGlyph>, ParseError> {
+        if !self.glyph_table_flags.contains(GlyphTableFlags::COLR) {
+            return Ok(None);
+        }
+        let provider = &self.font_table_provider;
+        let colr_data = self
+            .colr_table
+            .get_or_load(|| Ok(read_and_box_optional_table(provider, tag::COLR)?.map(Arc::from)))?;
+        let cpal_data = self
+            .cpal_table
+            .get_or_load(|| Ok(read_and_box_optional_table(provider, tag::CPAL)?.map(Arc::from)))?;
+        let (colr_data, cpal_data) = match (colr_data, cpal_data) {
+            (Some(colr_data), Some(cpal_data)) => (colr_data, cpal_data),
+            _ => return Ok(None),
+        };
+
+        let colr = ReadScope::new(&colr_data)
+            .read::<ColrTable<'_>>()
+            .map_err(|err| err.with_table(tag::COLR))?;
+        let paint = if source == ColorGlyphSource::Colr1 {
+            colr.paint_v1(glyph_index, coords)?
+        } else {
+            colr.paint_v0(glyph_index)?
+        };
+        let paint = match paint {
+            Some(paint) => paint,
+            None => return Ok(None),
+        };
+        let cpal = ReadScope::new(&cpal_data)
+            .read::<CpalTable<'_>>()
+            .map_err(|err| err.with_table(tag::CPAL))?;
+        let palette_index = if usize::from(palette_index) < cpal.num_palettes() {
+            palette_index
+        } else {
+            0
+        };
+        let palette = cpal.palette(palette_index)?;
+
+        Ok(Some(ColorGlyph::Colr {
+            source,
+            paint,
+            palette,
+        }))
+    }
+
+    #[cfg(feature = "bitmap")]
+    fn lookup_image(
+        &self,
+        images: &Images,
+        glyph_index: u16,
+        target_ppem: u16,
+        max_bit_depth: BitDepth,
+    ) -> Result<Option<BitmapGlyph>, ParseError> {
+        match images {
+            Images::Embedded { cblc, cbdt } => cblc.rent(|cblc: &CBLCTable<'_>| {
+                let target_ppem = if target_ppem > u16::from(std::u8::MAX) {
+                    std::u8::MAX
+                } else {
+                    target_ppem as u8
+                };
+                let bitmap = match cblc.find_strike(glyph_index, target_ppem, max_bit_depth) {
+                    Some(matching_strike) => {
+                        let cbdt = cbdt.suffix();
+                        cbdt::lookup(glyph_index, &matching_strike, cbdt)?.map(|bitmap| {
+                            BitmapGlyph::try_from((&matching_strike.bitmap_size.inner, bitmap))
+                        })
+                    }
+                    None => None,
+                };
+                bitmap.transpose()
+            }),
+            Images::Sbix(sbix) => {
+                self.lookup_sbix_glyph_bitmap(sbix, false, glyph_index, target_ppem, max_bit_depth)
+            }
+            Images::Svg(svg) => self.lookup_svg_glyph(svg, glyph_index),
+        }
+    }
+
+    /// Perform sbix lookup with `dupe` handling.
+    ///
+    /// The `dupe` flag indicates if this this a dupe lookup or not. To avoid potential infinite
+    /// recursion we only follow one level of `dupe` indirection.
+    #[cfg(feature = "bitmap")]
     fn lookup_sbix_glyph_bitmap(
         &self,
         sbix: &tables::Sbix,
@@ -583,6 +1699,7 @@ impl<T: FontTableProvider> Font<T> {
         })
     }
 
+    #[cfg(feature = "bitmap")]
     fn lookup_svg_glyph(
         &self,
         svg: &tables::Svg,
@@ -596,32 +1713,70 @@ impl<T: FontTableProvider> Font<T> {
         )
     }
 
-    fn embedded_images(&mut self) -> Result<Option<Rc<Images>>, ParseError> {
+    #[cfg(feature = "bitmap")]
+    fn embedded_images(&mut self) -> Result<Option<Arc<Images>>, ParseError> {
+        for &source in &[
+            ColorGlyphSource::Svg,
+            ColorGlyphSource::Cbdt,
+            ColorGlyphSource::Sbix,
+        ] {
+            if let Some(images) = self.images(source)? {
+                return Ok(Some(images));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the images of the `SVG `, `sbix` or `CBDT` source, if the font has the table.
+    #[cfg(feature = "bitmap")]
+    fn images(&mut self, source: ColorGlyphSource) -> Result<Option<Arc<Images>>, ParseError> {
         let provider = &self.font_table_provider;
         let num_glyphs = usize::from(self.maxp_table.num_glyphs);
         let table_flags = self.glyph_table_flags;
-        self.embedded_images.get_or_load(|| {
-            if table_flags.contains(GlyphTableFlags::SVG) {
-                let images = load_svg(provider).map(Images::Svg)?;
-                Ok(Some(Rc::new(images)))
-            } else if table_flags.contains(GlyphTableFlags::CBDT) {
-                let images =
-                    load_cblc_cbdt(provider).map(|(cblc, cbdt)| Images::Embedded { cblc, cbdt })?;
-                Ok(Some(Rc::new(images)))
-            } else if table_flags.contains(GlyphTableFlags::SBIX) {
-                let images = load_sbix(provider, num_glyphs).map(Images::Sbix)?;
-                Ok(Some(Rc::new(images)))
-            } else {
+        match source {
+            ColorGlyphSource::Svg => self.svg_images.get_or_load(|| {
+                if table_flags.contains(GlyphTableFlags::SVG) {
+                    let images = load_svg(provider).map(Images::Svg)?;
+                    Ok(Some(Arc::new(images)))
+                } else {
+                    Ok(None)
+                }
+            }),
+            ColorGlyphSource::Cbdt => self.cbdt_images.get_or_load(|| {
+                if table_flags.contains(GlyphTableFlags::CBDT) {
+                    let images = load_cblc_cbdt(provider)
+                        .map(|(cblc, cbdt)| Images::Embedded { cblc, cbdt })?;
+                    Ok(Some(Arc::new(images)))
+                } else {
+                    Ok(None)
+                }
+            }),
+            ColorGlyphSource::Sbix => self.sbix_images.get_or_load(|| {
+                if table_flags.contains(GlyphTableFlags::SBIX) {
+                    let images = load_sbix(provider, num_glyphs).map(Images::Sbix)?;
+                    Ok(Some(Arc::new(images)))
+                } else {
+                    Ok(None)
+                }
+            }),
+            ColorGlyphSource::Colr1 | ColorGlyphSource::Colr0 | ColorGlyphSource::Outline => {
                 Ok(None)
             }
-        })
+        }
     }
 
     pub fn supports_emoji(&mut self) -> bool {
+        if self.glyph_table_flags.contains(GlyphTableFlags::COLR) {
+            return true;
+        }
+        #[cfg(feature = "bitmap")]
         match self.embedded_images() {
             Ok(Some(_)) => true,
             _ => false,
         }
+        #[cfg(not(feature = "bitmap"))]
+        self.glyph_table_flags
+            .intersects(GlyphTableFlags::SVG | GlyphTableFlags::SBIX | GlyphTableFlags::CBDT)
     }
 
     /// Returns the horizontal advance of the supplied glyph index.
@@ -632,6 +1787,17 @@ impl<T: FontTableProvider> Font<T> {
         glyph_info::advance(&self.maxp_table, &self.hhea_table, &self.hmtx_table, glyph).ok()
     }
 
+    /// Resolve the output of `shape` into glyph advances and offsets.
+    ///
+    /// See `gpos::glyph_positions` for details.
+    #[cfg(feature = "shaping")]
+    pub fn glyph_positions(&self, infos: &[Info], direction: TextDirection) -> Vec<GlyphPosition> {
+        let (maxp, hhea, hmtx) = (&self.maxp_table, &self.hhea_table, &self.hmtx_table);
+        gpos::glyph_positions(infos, direction, |glyph| {
+            glyph_info::advance(maxp, hhea, hmtx, glyph).ok()
+        })
+    }
+
     pub fn vertical_advance(&mut self, glyph: u16) -> Option<u16> {
         let provider = &self.font_table_provider;
         let vmtx = self
@@ -647,10 +1813,180 @@ impl<T: FontTableProvider> Font<T> {
         }
     }
 
+    /// Returns the y coordinate of the vertical origin of `glyph`, used to position it in
+    /// vertical text.
+    ///
+    /// The origin is read from the `VORG` table if present. Otherwise it's the top of the glyph
+    /// bounding box plus its top side bearing from the `vmtx` table, falling back to the ascender
+    /// of the font.
+    #[cfg(feature = "shaping")]
+    pub fn vertical_origin(&mut self, glyph: u16) -> Option<i16> {
+        self.with_vertical_metrics(&[], |metrics| metrics(glyph).origin_y)
+            .ok()
+    }
+
+    /// Resolve the output of `shape_vertical` into glyph advances and offsets for vertical text.
+    ///
+    /// Advances are read from `vmtx` and origins from `VORG`, adjusted by the `VVAR` table of a
+    /// variable font at the normalised variation coordinates `coords`. Pass an empty slice for
+    /// the default instance. Fonts without vertical metrics use the ascender and descender of
+    /// the font. See `gpos::vertical_glyph_positions` for details.
+    #[cfg(feature = "shaping")]
+    pub fn vertical_glyph_positions(
+        &mut self,
+        infos: &[Info],
+        coords: &[F2Dot14],
+    ) -> Result<Vec<GlyphPosition>, ParseError> {
+        self.with_vertical_metrics(coords, |metrics| {
+            gpos::vertical_glyph_positions(infos, metrics)
+        })
+    }
+
+    /// Returns the positions of the carets between the components of the ligature `glyph`, used
+    /// to place the cursor within a ligature.
+    ///
+    /// Coordinates are adjusted for the normalised variation coordinates `coords` of a variable
+    /// font. Pass an empty slice for the default instance. See `gdef::ligature_carets` for
+    /// details.
+    pub fn ligature_carets(
+        &mut self,
+        glyph: u16,
+        coords: &[F2Dot14],
+    ) -> Result<Vec<CaretPosition>, ParseError> {
+        let opt_gdef_table = self.gdef_table()?;
+        // The item variation store is only needed away from the default instance
+        let opt_gdef_data = if coords.is_empty() {
+            None
+        } else {
+            self.font_table_provider.table_data(tag::GDEF)?
+        };
+        let opt_item_variation_store = match opt_gdef_data {
+            Some(ref gdef_data) => gdef::read_item_variation_store(gdef_data)?,
+            None => None,
+        };
+        gdef::ligature_carets(
+            opt_gdef_table.as_ref().map(Arc::as_ref),
+            opt_item_variation_store.as_ref(),
+            glyph,
+            coords,
+        )
+    }
+
+    /// Returns the position and thickness of underlines and strikeouts.
+    ///
+    /// The metrics are read from the `post` and `OS/2` tables, adjusted by the `MVAR` table of a
+    /// variable font at the normalised variation coordinates `coords`. Pass an empty slice for
+    /// the default instance. See `DecorationMetrics::new` for details.
+    pub fn decoration_metrics(&self, coords: &[F2Dot14]) -> Result<DecorationMetrics, ParseError> {
+        let head = self.head_table()?.ok_or(ParseError::MissingValue)?;
+        let opt_post_data = self.font_table_provider.table_data(tag::POST)?;
+        let opt_post = opt_post_data
+            .as_ref()
+            .map(|data| ReadScope::new(data).read::<PostTable<'_>>())
+            .transpose()?;
+        let opt_os2 = self.os2_table()?;
+        // The `MVAR` table is only needed away from the default instance
+        let opt_mvar_data = if coords.is_empty() {
+            None
+        } else {
+            self.font_table_provider.table_data(tag::MVAR)?
+        };
+        let opt_mvar = opt_mvar_data
+            .as_ref()
+            .map(|data| ReadScope::new(data).read::<MvarTable<'_>>())
+            .transpose()?;
+        DecorationMetrics::new(
+            opt_post.as_ref().map(|post| &post.header),
+            opt_os2.as_ref(),
+            opt_mvar.as_ref(),
+            head.units_per_em,
+            coords,
+        )
+    }
+
+    /// Calls `f` with a function supplying the vertical metrics of glyphs at the normalised
+    /// variation coordinates `coords`.
+    #[cfg(feature = "shaping")]
+    fn with_vertical_metrics<R>(
+        &mut self,
+        coords: &[F2Dot14],
+        f: impl FnOnce(&dyn Fn(u16) -> VerticalMetrics) -> R,
+    ) -> Result<R, ParseError> {
+        let outline_tables = self.outline_tables()?;
+        let provider = &self.font_table_provider;
+        let vmtx_data = self
+            .vmtx_table
+            .get_or_load(|| read_and_box_optional_table(provider, tag::VMTX))?;
+        let vorg_data = self
+            .vorg_table
+            .get_or_load(|| read_and_box_optional_table(provider, tag::VORG))?;
+        let vvar_data = self
+            .vvar_table
+            .get_or_load(|| read_and_box_optional_table(provider, tag::VVAR))?;
+        let vhea = self.vhea_table()?;
+
+        let vmtx = match (&vhea, &vmtx_data) {
+            (Some(vhea), Some(vmtx_data)) => Some((&**vhea, &**vmtx_data)),
+            _ => None,
+        };
+        let vorg = vorg_data
+            .as_ref()
+            .map(|data| ReadScope::new(data).read::<VorgTable<'_>>())
+            .transpose()?;
+        let vvar = vvar_data
+            .as_ref()
+            .map(|data| ReadScope::new(data).read::<VvarTable<'_>>())
+            .transpose()?;
+        let (maxp, hhea) = (&self.maxp_table, &self.hhea_table);
+
+        let metrics = |glyph| {
+            let h_advance = glyph_info::advance(maxp, hhea, &self.hmtx_table, glyph).unwrap_or(0);
+            let mut v_advance = vmtx
+                .and_then(|(vhea, vmtx)| glyph_info::advance(maxp, vhea, vmtx, glyph).ok())
+                .unwrap_or_else(|| {
+                    u16::try_from(i32::from(hhea.ascender) - i32::from(hhea.descender)).unwrap_or(0)
+                });
+            let mut origin_y = match vorg {
+                Some(ref vorg) => vorg.vert_origin_y(glyph),
+                None => {
+                    let tsb = vmtx.and_then(|(vhea, vmtx)| {
+                        glyph_info::side_bearing(maxp, vhea, vmtx, glyph).ok()
+                    });
+                    match (tsb, outline_tables.glyph_bounding_box(glyph)) {
+                        (Some(tsb), Ok(Some(bbox))) => tsb.saturating_add(bbox.y_max),
+                        _ => hhea.ascender,
+                    }
+                }
+            };
+
+            if let Some(ref vvar) = vvar {
+                if let Ok(delta) = vvar.advance_height_delta(glyph, coords) {
+                    v_advance = (f32::from(v_advance) + delta).round().max(0.) as u16;
+                }
+                // Origin deltas only apply to the origins held in `VORG`
+                if let (Some(_), Ok(Some(delta))) = (&vorg, vvar.vert_origin_delta(glyph, coords)) {
+                    origin_y = (f32::from(origin_y) + delta).round() as i16;
+                }
+            }
+
+            VerticalMetrics {
+                h_advance,
+                v_advance,
+                origin_y,
+            }
+        };
+
+        Ok(f(&metrics))
+    }
+
     pub fn head_table(&self) -> Result<Option<HeadTable>, ParseError> {
         self.font_table_provider
             .table_data(tag::HEAD)?
-            .map(|data| ReadScope::new(&data).read::<HeadTable>())
+            .map(|data| {
+                ReadScope::new(&data)
+                    .read::<HeadTable>()
+                    .map_err(|err| err.with_table(tag::HEAD))
+            })
             .transpose()
     }
 
@@ -658,23 +1994,297 @@ impl<T: FontTableProvider> Font<T> {
         load_os2_table(&self.font_table_provider)
     }
 
-    pub fn gdef_table(&mut self) -> Result<Option<Rc<GDEFTable>>, ParseError> {
+    /// Returns the ascent, descent, and line gap of the font resolved according to `policy`.
+    ///
+    /// When the `OS/2` table, or the part of it required by `policy`, is absent the values from
+    /// the `hhea` table are returned.
+    pub fn line_metrics(&self, policy: LineMetricsPolicy) -> Result<LineMetrics, ParseError> {
+        let hhea = LineMetrics {
+            ascent: self.hhea_table.ascender,
+            descent: self.hhea_table.descender,
+            line_gap: self.hhea_table.line_gap,
+        };
+        let (use_typo_metrics, version0) = match self.os2_table()? {
+            Some(os2) => (os2.fs_selection_flags().use_typo_metrics(), os2.version0),
+            None => return Ok(hhea),
+        };
+        let version0 = match version0 {
+            Some(version0) => version0,
+            None => return Ok(hhea),
+        };
+        let typo = LineMetrics {
+            ascent: version0.s_typo_ascender,
+            descent: version0.s_typo_descender,
+            line_gap: version0.s_typo_line_gap,
+        };
+        let win = LineMetrics {
+            ascent: i16::try_from(version0.us_win_ascent).unwrap_or(std::i16::MAX),
+            descent: i16::try_from(version0.us_win_descent)
+                .map(|descent| -descent)
+                .unwrap_or(-std::i16::MAX),
+            line_gap: 0,
+        };
+
+        let metrics = match policy {
+            LineMetricsPolicy::Hhea => hhea,
+            LineMetricsPolicy::Typo if use_typo_metrics => typo,
+            LineMetricsPolicy::Typo => hhea,
+            LineMetricsPolicy::Win => win,
+            LineMetricsPolicy::Auto => {
+                if use_typo_metrics {
+                    typo
+                } else if hhea.ascent != 0 || hhea.descent != 0 {
+                    hhea
+                } else if typo.ascent != 0 || typo.descent != 0 {
+                    typo
+                } else {
+                    win
+                }
+            }
+        };
+        Ok(metrics)
+    }
+
+    /// Returns the x-height of the font in font units.
+    ///
+    /// The value is read from `sxHeight` in the `OS/2` table. When that is absent or zero the
+    /// x-height is measured from the top of the outline of the glyph for 'x' instead. `None` is
+    /// returned if neither source is available.
+    pub fn x_height(&mut self) -> Result<Option<i16>, ParseError> {
+        let sx_height = self
+            .os2_table()?
+            .and_then(|os2| os2.version2to4)
+            .map(|version2to4| version2to4.sx_height);
+        self.height_or_measure(sx_height, 'x')
+    }
+
+    /// Returns the cap-height of the font in font units.
+    ///
+    /// The value is read from `sCapHeight` in the `OS/2` table. When that is absent or zero the
+    /// cap-height is measured from the top of the outline of the glyph for 'H' instead. `None` is
+    /// returned if neither source is available.
+    pub fn cap_height(&mut self) -> Result<Option<i16>, ParseError> {
+        let s_cap_height = self
+            .os2_table()?
+            .and_then(|os2| os2.version2to4)
+            .map(|version2to4| version2to4.s_cap_height);
+        self.height_or_measure(s_cap_height, 'H')
+    }
+
+    fn height_or_measure(
+        &mut self,
+        opt_height: Option<i16>,
+        ch: char,
+    ) -> Result<Option<i16>, ParseError> {
+        match opt_height {
+            Some(height) if height > 0 => Ok(Some(height)),
+            _ => {
+                let (glyph_index, _) =
+                    self.lookup_glyph_index(ch, MatchingPresentation::NotRequired, None);
+                if glyph_index == 0 {
+                    return Ok(None);
+                }
+                let opt_bounding_box = self.glyph_bounding_box(glyph_index)?;
+                Ok(opt_bounding_box.map(|bounding_box| bounding_box.y_max))
+            }
+        }
+    }
+
+    /// Estimate the dominant vertical stem width from the outlines of `l`, `I`, and `H`.
+    ///
+    /// This is useful for populating the PDF `StemV` font descriptor entry when the font does not
+    /// supply an equivalent value. Only `glyf` outlines are currently supported, `None` is returned
+    /// for fonts with other outline formats or if none of the sample glyphs are present.
+    pub fn vertical_stem_width(&mut self) -> Result<Option<u16>, ParseError> {
+        if !self.glyph_table_flags.contains(GlyphTableFlags::GLYF) {
+            return Ok(None);
+        }
+        let glyph_indices = ['l', 'I', 'H']
+            .iter()
+            .map(|&ch| {
+                let (glyph_index, _) =
+                    self.lookup_glyph_index(ch, MatchingPresentation::NotRequired, None);
+                glyph_index
+            })
+            .filter(|&glyph_index| glyph_index != 0)
+            .collect::<Vec<_>>();
+        if glyph_indices.is_empty() {
+            return Ok(None);
+        }
+
+        let head = match self.head_table()? {
+            Some(head) => head,
+            None => return Ok(None),
+        };
+        let loca_data = self.font_table_provider.read_table_data(tag::LOCA)?;
+        let loca = ReadScope::new(&loca_data).read_dep::<LocaTable<'_>>((
+            usize::from(self.num_glyphs()),
+            head.index_to_loc_format,
+        ))?;
+        let glyf_data = self.font_table_provider.read_table_data(tag::GLYF)?;
+        let glyf = ReadScope::new(&glyf_data).read_dep::<GlyfTable<'_>>(&loca)?;
+        let outlines = glyph_indices
+            .into_iter()
+            .map(|glyph_index| glyf.glyph_points(glyph_index))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(estimate_vertical_stem_width(&outlines))
+    }
+
+    /// Returns the bounding box of the outline of the supplied glyph index.
+    ///
+    /// Both `glyf` and `CFF` outlines are supported, the tables are read on first use and kept
+    /// for later calls. `None` is returned for fonts with other outline formats, and for glyphs
+    /// that have no outline.
+    pub fn glyph_bounding_box(
+        &mut self,
+        glyph_index: u16,
+    ) -> Result<Option<BoundingBox>, ParseError> {
+        self.outline_tables()?.glyph_bounding_box(glyph_index)
+    }
+
+    fn outline_tables(&mut self) -> Result<OutlineTables, ParseError> {
+        let provider = &self.font_table_provider;
+        if self.glyph_table_flags.contains(GlyphTableFlags::GLYF) {
+            let glyf_data = self.glyf_table.get_or_load(|| {
+                Ok(read_and_box_optional_table(provider, tag::GLYF)?.map(Arc::from))
+            })?;
+            let loca_data = self.loca_table.get_or_load(|| {
+                Ok(read_and_box_optional_table(provider, tag::LOCA)?.map(Arc::from))
+            })?;
+            match (glyf_data, loca_data, self.head_table()?) {
+                (Some(glyf_data), Some(loca_data), Some(head)) => Ok(OutlineTables::Glyf {
+                    glyf_data,
+                    loca_data,
+                    num_glyphs: usize::from(self.num_glyphs()),
+                    index_to_loc_format: head.index_to_loc_format,
+                }),
+                _ => Ok(OutlineTables::None),
+            }
+        } else if self.glyph_table_flags.contains(GlyphTableFlags::CFF) {
+            let cff_data = self.cff_table.get_or_load(|| {
+                Ok(read_and_box_optional_table(provider, tag::CFF)?.map(Arc::from))
+            })?;
+            Ok(cff_data.map_or(OutlineTables::None, OutlineTables::Cff))
+        } else {
+            Ok(OutlineTables::None)
+        }
+    }
+
+    /// Returns the outline of `glyph` at the normalised variation coordinates `coords`.
+    ///
+    /// Pass an empty slice for the default instance of a variable font. The `glyf`, `loca`, and
+    /// `gvar` tables are read on first use and kept for later calls. Only `glyf` outlines are
+    /// currently supported, `None` is returned for fonts with other outline formats. Without the
+    /// `variations` feature `coords` is ignored and the outline of the default instance is returned.
+    ///
+    /// If `outline_cache` is set the outline is looked up there first, and added to it otherwise.
+    pub fn outline(
+        &mut self,
+        glyph: u16,
+        coords: &[F2Dot14],
+    ) -> Result<Option<GlyphPoints>, ParseError> {
+        if let Some(outline) = self
+            .outline_cache
+            .as_mut()
+            .and_then(|cache| cache.get(glyph, coords))
+        {
+            return Ok(outline);
+        }
+        let outline = self.load_outline(glyph, coords)?;
+        if let Some(cache) = self.outline_cache.as_mut() {
+            cache.insert(glyph, coords, outline.clone());
+        }
+        Ok(outline)
+    }
+
+    fn load_outline(
+        &mut self,
+        glyph: u16,
+        coords: &[F2Dot14],
+    ) -> Result<Option<GlyphPoints>, ParseError> {
+        if !self.glyph_table_flags.contains(GlyphTableFlags::GLYF) {
+            return Ok(None);
+        }
+        let provider = &self.font_table_provider;
+        let glyf_data = self
+            .glyf_table
+            .get_or_load(|| Ok(read_and_box_optional_table(provider, tag::GLYF)?.map(Arc::from)))?;
+        let loca_data = self
+            .loca_table
+            .get_or_load(|| Ok(read_and_box_optional_table(provider, tag::LOCA)?.map(Arc::from)))?;
+        // The variations are only needed away from the default instance
+        #[cfg(feature = "variations")]
+        let gvar_data = if coords.is_empty() {
+            None
+        } else {
+            self.gvar_table.get_or_load(|| {
+                Ok(read_and_box_optional_table(provider, tag::GVAR)?.map(Arc::from))
+            })?
+        };
+        let (glyf_data, loca_data, head) = match (glyf_data, loca_data, self.head_table()?) {
+            (Some(glyf_data), Some(loca_data), Some(head)) => (glyf_data, loca_data, head),
+            _ => return Ok(None),
+        };
+
+        let num_glyphs = usize::from(self.num_glyphs());
+        let loca = ReadScope::new(&loca_data)
+            .read_dep::<LocaTable<'_>>((num_glyphs, head.index_to_loc_format))
+            .map_err(|err| err.with_table(tag::LOCA))?;
+        let glyf = ReadScope::new(&glyf_data)
+            .read_dep::<GlyfTable<'_>>(&loca)
+            .map_err(|err| err.with_table(tag::GLYF))?;
+        #[cfg(feature = "variations")]
+        let points = match gvar_data {
+            Some(gvar_data) => {
+                let gvar = ReadScope::new(&gvar_data)
+                    .read_dep::<GvarTable<'_>>(num_glyphs)
+                    .map_err(|err| err.with_table(tag::GVAR))?;
+                glyf.glyph_points_at(glyph, &gvar, coords)?
+            }
+            None => glyf.glyph_points(glyph)?,
+        };
+        #[cfg(not(feature = "variations"))]
+        let points = {
+            let _ = coords;
+            glyf.glyph_points(glyph)?
+        };
+        Ok(Some(points))
+    }
+
+    /// Returns the units per em, line metrics, x-height, and cap-height of the font.
+    pub fn metrics(&mut self) -> Result<FontMetrics, ParseError> {
+        let head = self.head_table()?.ok_or(ParseError::MissingValue)?;
+        Ok(FontMetrics {
+            units_per_em: head.units_per_em,
+            line_metrics: self.line_metrics(LineMetricsPolicy::Auto)?,
+            x_height: self.x_height()?,
+            cap_height: self.cap_height()?,
+        })
+    }
+
+    pub fn gdef_table(&mut self) -> Result<Option<Arc<GDEFTable>>, ParseError> {
         let provider = &self.font_table_provider;
         self.gdef_cache.get_or_load(|| {
             if let Some(gdef_data) = provider.table_data(tag::GDEF)? {
-                let gdef = ReadScope::new(&gdef_data).read::<GDEFTable>()?;
-                Ok(Some(Rc::new(gdef)))
+                let gdef = ReadScope::new(&gdef_data)
+                    .read::<GDEFTable>()
+                    .map_err(|err| err.with_table(tag::GDEF))?;
+                Ok(Some(Arc::new(gdef)))
             } else {
                 Ok(None)
             }
         })
     }
 
+    #[cfg(feature = "shaping")]
     pub fn gsub_cache(&mut self) -> Result<Option<LayoutCache<GSUB>>, ParseError> {
         let provider = &self.font_table_provider;
         self.gsub_cache.get_or_load(|| {
             if let Some(gsub_data) = provider.table_data(tag::GSUB)? {
-                let gsub = ReadScope::new(&gsub_data).read::<LayoutTable<GSUB>>()?;
+                let gsub = ReadScope::new(&gsub_data)
+                    .read::<LayoutTable<GSUB>>()
+                    .map_err(|err| err.with_table(tag::GSUB))?;
                 let cache = new_layout_cache::<GSUB>(gsub);
                 Ok(Some(cache))
             } else {
@@ -683,11 +2293,14 @@ impl<T: FontTableProvider> Font<T> {
         })
     }
 
+    #[cfg(feature = "shaping")]
     pub fn gpos_cache(&mut self) -> Result<Option<LayoutCache<GPOS>>, ParseError> {
         let provider = &self.font_table_provider;
         self.gpos_cache.get_or_load(|| {
             if let Some(gpos_data) = provider.table_data(tag::GPOS)? {
-                let gpos = ReadScope::new(&gpos_data).read::<LayoutTable<GPOS>>()?;
+                let gpos = ReadScope::new(&gpos_data)
+                    .read::<LayoutTable<GPOS>>()
+                    .map_err(|err| err.with_table(tag::GPOS))?;
                 let cache = new_layout_cache::<GPOS>(gpos);
                 Ok(Some(cache))
             } else {
@@ -696,12 +2309,83 @@ impl<T: FontTableProvider> Font<T> {
         })
     }
 
-    pub fn vhea_table(&mut self) -> Result<Option<Rc<HheaTable>>, ParseError> {
+    /// Returns the parameters of the feature `feature_tag` from the `GSUB` table, or failing that
+    /// the `GPOS` table.
+    ///
+    /// Only the `size`, stylistic set and character variant features have parameters.
+    #[cfg(feature = "shaping")]
+    pub fn feature_params(
+        &mut self,
+        feature_tag: u32,
+    ) -> Result<Option<FeatureParams>, ParseError> {
+        if let Some(gsub_cache) = self.gsub_cache()? {
+            if let Some(params) = gsub_cache.layout_table.feature_params(feature_tag) {
+                return Ok(Some(params.clone()));
+            }
+        }
+        if let Some(gpos_cache) = self.gpos_cache()? {
+            if let Some(params) = gpos_cache.layout_table.feature_params(feature_tag) {
+                return Ok(Some(params.clone()));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the names of the feature `feature_tag` for display, as given by its feature
+    /// parameters.
+    ///
+    /// Returns `None` if the feature has no parameters or the font has no `name` table.
+    #[cfg(feature = "shaping")]
+    pub fn feature_names(&mut self, feature_tag: u32) -> Result<Option<FeatureNames>, ParseError> {
+        let params = match self.feature_params(feature_tag)? {
+            Some(params) => params,
+            None => return Ok(None),
+        };
+        let name_data = match self.font_table_provider.table_data(tag::NAME)? {
+            Some(name_data) => name_data,
+            None => return Ok(None),
+        };
+        let get_name = |name_id: u16| -> Result<Option<String>, ParseError> {
+            if name_id == 0 {
+                return Ok(None);
+            }
+            let name = fontcode_get_name(&name_data, name_id)?;
+            Ok(name.and_then(|name| name.into_string().ok()))
+        };
+
+        let names = match params {
+            FeatureParams::Size(size) => FeatureNames {
+                ui_label: get_name(size.subfamily_name_id)?,
+                ..FeatureNames::default()
+            },
+            FeatureParams::StylisticSet { ui_name_id } => FeatureNames {
+                ui_label: get_name(ui_name_id)?,
+                ..FeatureNames::default()
+            },
+            FeatureParams::CharacterVariant(cv) => {
+                let mut param_labels = Vec::new();
+                for name_id in cv.param_ui_label_name_ids() {
+                    param_labels.extend(get_name(name_id)?);
+                }
+                FeatureNames {
+                    ui_label: get_name(cv.ui_label_name_id)?,
+                    tooltip: get_name(cv.tooltip_name_id)?,
+                    sample_text: get_name(cv.sample_text_name_id)?,
+                    param_labels,
+                }
+            }
+        };
+        Ok(Some(names))
+    }
+
+    pub fn vhea_table(&mut self) -> Result<Option<Arc<HheaTable>>, ParseError> {
         let provider = &self.font_table_provider;
         self.vhea_table.get_or_load(|| {
             if let Some(vhea_data) = provider.table_data(tag::VHEA)? {
-                let vhea = ReadScope::new(&vhea_data).read::<HheaTable>()?;
-                Ok(Some(Rc::new(vhea)))
+                let vhea = ReadScope::new(&vhea_data)
+                    .read::<HheaTable>()
+                    .map_err(|err| err.with_table(tag::VHEA))?;
+                Ok(Some(Arc::new(vhea)))
             } else {
                 Ok(None)
             }
@@ -716,7 +2400,7 @@ impl<T: FontTableProvider> Font<T> {
 impl<T> LazyLoad<T> {
     /// Return loaded value, calls the supplied closure if not already loaded.
     ///
-    /// It's expected that `T` is cheap to clone, either because it's wrapped in an `Rc`
+    /// It's expected that `T` is cheap to clone, either because it's wrapped in an `Arc`
     /// or is `Copy`.
     fn get_or_load(
         &mut self,
@@ -769,6 +2453,55 @@ fn read_and_box_table(
         .map(|table| Box::from(table.into_owned()))
 }
 
+impl OutlineTables {
+    fn glyph_bounding_box(&self, glyph_index: u16) -> Result<Option<BoundingBox>, ParseError> {
+        match self {
+            OutlineTables::Glyf {
+                glyf_data,
+                loca_data,
+                num_glyphs,
+                index_to_loc_format,
+            } => {
+                let loca = ReadScope::new(loca_data)
+                    .read_dep::<LocaTable<'_>>((*num_glyphs, *index_to_loc_format))
+                    .map_err(|err| err.with_table(tag::LOCA))?;
+                let index = usize::from(glyph_index);
+                let (start, end) = match (loca.offsets.get(index), loca.offsets.get(index + 1)) {
+                    (Some(start), Some(end)) => (start, end),
+                    _ => return Ok(None),
+                };
+                match end.checked_sub(start) {
+                    Some(0) => Ok(None),
+                    Some(_) => {
+                        // Only the header of the glyph is needed for its bounding box
+                        let mut ctxt = ReadScope::new(glyf_data)
+                            .offset(usize::try_from(start)?)
+                            .ctxt();
+                        let _number_of_contours = ctxt.read_i16be()?;
+                        let bounding_box = ctxt
+                            .read::<BoundingBox>()
+                            .map_err(|err| err.with_table(tag::GLYF))?;
+                        Ok(Some(bounding_box))
+                    }
+                    None => Err(ParseError::BadOffset),
+                }
+            }
+            OutlineTables::Cff(cff_data) => {
+                let cff = ReadScope::new(cff_data)
+                    .read::<CFF<'_>>()
+                    .map_err(|err| err.with_table(tag::CFF))?;
+                match cff.fonts.first() {
+                    Some(font) if usize::from(glyph_index) < font.char_strings_index.len() => {
+                        cff.glyph_bounding_box(0, glyph_index)
+                    }
+                    _ => Ok(None),
+                }
+            }
+            OutlineTables::None => Ok(None),
+        }
+    }
+}
+
 fn read_and_box_optional_table(
     provider: &impl FontTableProvider,
     tag: u32,
@@ -785,6 +2518,7 @@ fn load_os2_table(provider: &impl FontTableProvider) -> Result<Option<Os2>, Pars
         .transpose()
 }
 
+#[cfg(feature = "bitmap")]
 fn load_cblc_cbdt(
     provider: &impl FontTableProvider,
 ) -> Result<(tables::CBLC, tables::CBDT), ParseError> {
@@ -801,6 +2535,7 @@ fn load_cblc_cbdt(
     Ok((cblc, cbdt))
 }
 
+#[cfg(feature = "bitmap")]
 fn load_sbix(
     provider: &impl FontTableProvider,
     num_glyphs: usize,
@@ -811,6 +2546,7 @@ fn load_sbix(
     })
 }
 
+#[cfg(feature = "bitmap")]
 fn load_svg(provider: &impl FontTableProvider) -> Result<tables::Svg, ParseError> {
     let svg_data = read_and_box_table(provider, tag::SVG)?;
     tables::Svg::try_new_or_drop(svg_data, |data| ReadScope::new(data).read::<SvgTable<'_>>())
@@ -920,7 +2656,9 @@ fn unique_glyph_names<'a>(
 #[cfg(test)]
 mod tests {
     use super::*;
+    #[cfg(feature = "bitmap")]
     use crate::bitmap::{Bitmap, EncapsulatedBitmap};
+    use crate::font_data::DynamicFontTableProvider;
     use crate::tables::OpenTypeFont;
     use crate::tests::read_fixture;
 
@@ -980,33 +2718,590 @@ mod tests {
     }
 
     #[test]
-    fn test_unique_glyph_names() {
-        let names = vec!["A"; 3].into_iter().map(Cow::from);
-        let unique_names = unique_glyph_names(names, 3);
+    fn test_glyph_by_name() {
+        let font_buffer = read_fixture("tests/fonts/opentype/TwitterColorEmoji-SVGinOT.ttf");
+        let mut font = OwnedFont::from_data(font_buffer, 0).unwrap().unwrap();
+        assert_eq!(font.glyph_by_name(".notdef"), Some(0));
+        assert_eq!(font.glyph_by_name("copyright"), Some(5));
+        assert_eq!(font.glyph_by_name("smileface"), Some(71));
+        assert_eq!(font.glyph_by_name("no-such-glyph"), None);
+
+        // Klei has a version 3 post table so names are resolved through cmap
+        let font_buffer = read_fixture("tests/fonts/opentype/Klei.otf");
+        let mut font = OwnedFont::from_data(font_buffer, 0).unwrap().unwrap();
+        let glyph_index = font.map_char('F');
+        assert_ne!(glyph_index, 0);
+        assert_eq!(font.glyph_by_name("F"), Some(glyph_index));
+        assert_eq!(font.glyph_by_name("uni0046"), Some(glyph_index));
+        assert_eq!(font.glyph_by_name("u0046"), Some(glyph_index));
+        assert_eq!(font.glyph_by_name("F.alt01"), None);
+        assert_eq!(font.glyph_by_name("uni25B6"), None);
+    }
+
+    #[test]
+    #[cfg(feature = "shaping")]
+    fn test_map_text() {
+        let font_buffer = read_fixture("tests/fonts/opentype/Klei.otf");
+        let mut font = OwnedFont::from_data(font_buffer, 0).unwrap().unwrap();
+        let mapped = font.map_text(
+            "cafe\u{301} \u{2603}",
+            Normalization::Composed,
+            MatchingPresentation::NotRequired,
+        );
+        assert_eq!(mapped.text, "caf\u{E9} \u{2603}");
+        assert_eq!(mapped.glyphs.len(), 6);
         assert_eq!(
-            unique_names,
-            &[Cow::from("A"), Cow::from("A.alt01"), Cow::from("A.alt02")]
+            mapped.unmapped,
+            vec![UnmappedChar {
+                offset: 6,
+                ch: '\u{2603}'
+            }]
         );
+        assert!(!mapped.is_fully_mapped());
+
+        let mapped = font.map_text(
+            "cafe\u{301}",
+            Normalization::None,
+            MatchingPresentation::NotRequired,
+        );
+        assert_eq!(mapped.glyphs.len(), 5);
+        assert!(mapped.is_fully_mapped());
     }
 
     #[test]
-    fn test_lookup_sbix() {
-        let font_buffer = read_fixture("tests/fonts/sbix/sbix-dupe.ttf");
+    fn test_line_metrics_use_typo_metrics() {
+        // Klei has the USE_TYPO_METRICS bit set
+        let font_buffer = read_fixture("tests/fonts/opentype/Klei.otf");
         let opentype_file = ReadScope::new(&font_buffer)
             .read::<OpenTypeFont<'_>>()
             .unwrap();
         let font_table_provider = opentype_file
             .table_provider(0)
             .expect("error reading font file");
-        let mut font = Font::new(Box::new(font_table_provider))
+        let font = Font::new(Box::new(font_table_provider))
             .expect("error reading font data")
             .expect("missing required font tables");
 
-        // Successfully read bitmap
-        match font.lookup_glyph_image(1, 100, BitDepth::ThirtyTwo) {
-            Ok(Some(BitmapGlyph {
-                bitmap: Bitmap::Encapsulated(EncapsulatedBitmap { data, .. }),
-                ..
+        let line_metrics = |policy| font.line_metrics(policy).unwrap();
+        let hhea = LineMetrics {
+            ascent: 1264,
+            descent: -392,
+            line_gap: 90,
+        };
+        let typo = LineMetrics {
+            ascent: 768,
+            descent: -232,
+            line_gap: 90,
+        };
+        let win = LineMetrics {
+            ascent: 1264,
+            descent: -392,
+            line_gap: 0,
+        };
+        assert_eq!(line_metrics(LineMetricsPolicy::Hhea), hhea);
+        assert_eq!(line_metrics(LineMetricsPolicy::Typo), typo);
+        assert_eq!(line_metrics(LineMetricsPolicy::Win), win);
+        assert_eq!(line_metrics(LineMetricsPolicy::Auto), typo);
+    }
+
+    #[test]
+    fn test_line_metrics_without_use_typo_metrics() {
+        let font_buffer = read_fixture("tests/fonts/opentype/test-font.ttf");
+        let opentype_file = ReadScope::new(&font_buffer)
+            .read::<OpenTypeFont<'_>>()
+            .unwrap();
+        let font_table_provider = opentype_file
+            .table_provider(0)
+            .expect("error reading font file");
+        let font = Font::new(Box::new(font_table_provider))
+            .expect("error reading font data")
+            .expect("missing required font tables");
+
+        let hhea = LineMetrics {
+            ascent: 1454,
+            descent: -20,
+            line_gap: 0,
+        };
+        assert_eq!(font.line_metrics(LineMetricsPolicy::Typo).unwrap(), hhea);
+        assert_eq!(font.line_metrics(LineMetricsPolicy::Auto).unwrap(), hhea);
+    }
+
+    #[test]
+    fn test_x_height_and_cap_height_from_os2() {
+        let font_buffer = read_fixture("tests/fonts/opentype/Klei.otf");
+        let opentype_file = ReadScope::new(&font_buffer)
+            .read::<OpenTypeFont<'_>>()
+            .unwrap();
+        let font_table_provider = opentype_file
+            .table_provider(0)
+            .expect("error reading font file");
+        let mut font = Font::new(Box::new(font_table_provider))
+            .expect("error reading font data")
+            .expect("missing required font tables");
+
+        assert_eq!(font.x_height().unwrap(), Some(506));
+        assert_eq!(font.cap_height().unwrap(), Some(730));
+    }
+
+    #[test]
+    fn test_x_height_and_cap_height_from_outlines() {
+        // This font has a version 1 OS/2 table, which lacks sxHeight and sCapHeight.
+        let font_buffer = read_fixture("tests/fonts/arabic/KacstBook.ttf");
+        let opentype_file = ReadScope::new(&font_buffer)
+            .read::<OpenTypeFont<'_>>()
+            .unwrap();
+        let font_table_provider = opentype_file
+            .table_provider(0)
+            .expect("error reading font file");
+        let mut font = Font::new(Box::new(font_table_provider))
+            .expect("error reading font data")
+            .expect("missing required font tables");
+
+        assert_eq!(font.x_height().unwrap(), Some(993));
+        assert_eq!(font.cap_height().unwrap(), Some(1395));
+    }
+
+    #[test]
+    fn test_glyph_bounding_box_cff() {
+        let font_buffer = read_fixture("tests/fonts/opentype/Klei.otf");
+        let opentype_file = ReadScope::new(&font_buffer)
+            .read::<OpenTypeFont<'_>>()
+            .unwrap();
+        let font_table_provider = opentype_file
+            .table_provider(0)
+            .expect("error reading font file");
+        let mut font = Font::new(Box::new(font_table_provider))
+            .expect("error reading font data")
+            .expect("missing required font tables");
+
+        let (glyph_index, _) =
+            font.lookup_glyph_index('H', MatchingPresentation::NotRequired, None);
+        let bounding_box = font.glyph_bounding_box(glyph_index).unwrap();
+        assert_eq!(
+            bounding_box,
+            Some(BoundingBox {
+                x_min: 54,
+                x_max: 733,
+                y_min: -1,
+                y_max: 730,
+            })
+        );
+        let (space, _) = font.lookup_glyph_index(' ', MatchingPresentation::NotRequired, None);
+        assert_eq!(font.glyph_bounding_box(space).unwrap(), None);
+        assert_eq!(font.glyph_bounding_box(std::u16::MAX).unwrap(), None);
+    }
+
+    #[test]
+    fn test_vertical_stem_width() {
+        let font_buffer = read_fixture("tests/fonts/opentype/Ubuntu Mono with Numderline.ttf");
+        let opentype_file = ReadScope::new(&font_buffer)
+            .read::<OpenTypeFont<'_>>()
+            .unwrap();
+        let font_table_provider = opentype_file
+            .table_provider(0)
+            .expect("error reading font file");
+        let mut font = Font::new(Box::new(font_table_provider))
+            .expect("error reading font data")
+            .expect("missing required font tables");
+
+        assert_eq!(font.vertical_stem_width().unwrap(), Some(82));
+    }
+
+    #[test]
+    fn test_vertical_stem_width_cff() {
+        let font_buffer = read_fixture("tests/fonts/opentype/Klei.otf");
+        let opentype_file = ReadScope::new(&font_buffer)
+            .read::<OpenTypeFont<'_>>()
+            .unwrap();
+        let font_table_provider = opentype_file
+            .table_provider(0)
+            .expect("error reading font file");
+        let mut font = Font::new(Box::new(font_table_provider))
+            .expect("error reading font data")
+            .expect("missing required font tables");
+
+        assert_eq!(font.vertical_stem_width().unwrap(), None);
+    }
+
+    #[cfg(feature = "shaping")]
+    #[test]
+    fn test_outline_metrics_and_shape_text() {
+        let font_buffer = read_fixture("tests/fonts/opentype/Ubuntu Mono with Numderline.ttf");
+        let opentype_file = ReadScope::new(&font_buffer)
+            .read::<OpenTypeFont<'_>>()
+            .unwrap();
+        let font_table_provider = opentype_file
+            .table_provider(0)
+            .expect("error reading font file");
+        let mut font = Font::new(Box::new(font_table_provider))
+            .expect("error reading font data")
+            .expect("missing required font tables");
+
+        let glyph_index = font.map_char('H');
+        assert_ne!(glyph_index, 0);
+        assert_eq!(font.map_char('\u{10FFFF}'), 0);
+
+        let outline = font.outline(glyph_index, &[]).unwrap().unwrap();
+        assert_eq!(outline.end_pts_of_contours.len(), 1);
+        assert_eq!(
+            BoundingBox::from_points(&outline.points),
+            font.glyph_bounding_box(glyph_index).unwrap().unwrap()
+        );
+        // Without a gvar table the outline doesn't vary
+        assert_eq!(
+            font.outline(glyph_index, &[F2Dot14::new(0x4000)])
+                .unwrap()
+                .unwrap(),
+            outline
+        );
+
+        let metrics = font.metrics().unwrap();
+        assert_eq!(metrics.units_per_em, 1000);
+        assert_eq!(
+            metrics.line_metrics,
+            font.line_metrics(LineMetricsPolicy::Auto).unwrap()
+        );
+        assert_eq!(metrics.cap_height, font.cap_height().unwrap());
+
+        let infos = font
+            .shape_text(
+                "Hello",
+                tag::LATN,
+                None,
+                &Features::Mask(GsubFeatureMask::default()),
+            )
+            .unwrap();
+        assert_eq!(infos.len(), 5);
+        assert_eq!(infos[0].glyph.glyph_index, glyph_index);
+    }
+
+    #[test]
+    fn test_outline_cff() {
+        let font_buffer = read_fixture("tests/fonts/opentype/Klei.otf");
+        let opentype_file = ReadScope::new(&font_buffer)
+            .read::<OpenTypeFont<'_>>()
+            .unwrap();
+        let font_table_provider = opentype_file
+            .table_provider(0)
+            .expect("error reading font file");
+        let mut font = Font::new(Box::new(font_table_provider))
+            .expect("error reading font data")
+            .expect("missing required font tables");
+
+        assert_eq!(font.outline(1, &[]).unwrap(), None);
+    }
+
+    #[test]
+    fn test_outline_cache() {
+        let font_buffer = read_fixture("tests/fonts/opentype/Ubuntu Mono with Numderline.ttf");
+        let opentype_file = ReadScope::new(&font_buffer)
+            .read::<OpenTypeFont<'_>>()
+            .unwrap();
+        let font_table_provider = opentype_file
+            .table_provider(0)
+            .expect("error reading font file");
+        let mut font = Font::new(Box::new(font_table_provider))
+            .expect("error reading font data")
+            .expect("missing required font tables");
+        font.outline_cache = Some(OutlineCache::new(1));
+
+        let h = font.map_char('H');
+        let i = font.map_char('I');
+        let outline_h = font.outline(h, &[]).unwrap();
+        assert!(outline_h.is_some());
+        assert_eq!(font.outline(h, &[]).unwrap(), outline_h);
+        let outline_i = font.outline(i, &[]).unwrap();
+        assert_ne!(outline_i, outline_h);
+
+        // Capacity is 1 so only `I` remains cached
+        let cache = font.outline_cache.as_mut().unwrap();
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.get(i, &[]), Some(outline_i));
+        assert_eq!(cache.get(h, &[]), None);
+    }
+
+    #[cfg(feature = "shaping")]
+    #[test]
+    fn test_shape_vertical() {
+        let font_buffer = read_fixture("tests/fonts/noto/NotoSansJP-Regular.otf");
+        let opentype_file = ReadScope::new(&font_buffer)
+            .read::<OpenTypeFont<'_>>()
+            .unwrap();
+        let font_table_provider = opentype_file
+            .table_provider(0)
+            .expect("error reading font file");
+        let mut font = Font::new(Box::new(font_table_provider))
+            .expect("error reading font data")
+            .expect("missing required font tables");
+
+        // Ideographic comma and ideograph one
+        let glyphs = font.map_glyphs("\u{3001}\u{4E00}", MatchingPresentation::NotRequired);
+        let comma = glyphs[0].glyph_index;
+        let infos = font
+            .shape_vertical(
+                glyphs,
+                tag::from_string("hani").unwrap(),
+                None,
+                &Features::Mask(GsubFeatureMask::default()),
+            )
+            .unwrap();
+        assert!(infos[0].glyph.is_vert_alt);
+        assert_ne!(infos[0].glyph.glyph_index, comma);
+        assert!(!infos[1].glyph.is_vert_alt);
+
+        let positions = font.vertical_glyph_positions(&infos, &[]).unwrap();
+        let origin_y = font.vertical_origin(infos[1].glyph.glyph_index).unwrap();
+        assert_eq!(positions[1].x_advance, 0);
+        assert_eq!(positions[1].y_advance, -1000);
+        assert_eq!(positions[1].x_offset, -500);
+        assert_eq!(positions[1].y_offset, -i32::from(origin_y));
+
+        // From `VORG`
+        assert_eq!(font.vertical_origin(0), Some(880));
+        assert_eq!(font.vertical_origin(6896), Some(860));
+    }
+
+    #[cfg(feature = "shaping")]
+    #[test]
+    fn test_shape_feature_settings() {
+        let font_buffer = read_fixture("tests/fonts/opentype/Klei.otf");
+        let opentype_file = ReadScope::new(&font_buffer)
+            .read::<OpenTypeFont<'_>>()
+            .unwrap();
+        let font_table_provider = opentype_file
+            .table_provider(0)
+            .expect("error reading font file");
+        let mut font = Font::new(Box::new(font_table_provider))
+            .expect("error reading font data")
+            .expect("missing required font tables");
+
+        let mut shape = |features: &Features| {
+            let glyphs = font.map_glyphs("Shaping in a jiffy.", MatchingPresentation::NotRequired);
+            font.shape(glyphs, tag::LATN, None, features, true)
+                .unwrap()
+                .len()
+        };
+        // Klei ligates ff, which is enabled by default
+        assert_eq!(shape(&Features::Settings(Vec::new())), 18);
+        assert_eq!(
+            shape(&Features::Settings(vec![FeatureSetting::off(tag::LIGA)])),
+            19
+        );
+        assert_eq!(
+            shape(&Features::Settings(vec![
+                FeatureSetting::off(tag::LIGA),
+                FeatureSetting::on(tag::LIGA),
+            ])),
+            18
+        );
+    }
+
+    #[cfg(feature = "shaping")]
+    #[test]
+    fn test_shaping_plan() {
+        let font_buffer = read_fixture("tests/fonts/opentype/Klei.otf");
+        let mut font = OwnedFont::from_data(font_buffer, 0).unwrap().unwrap();
+
+        let features = Features::Mask(GsubFeatureMask::default());
+        let plan = font.shaping_plan(tag::LATN, None, &features, true).unwrap();
+        let cached = font.shaping_plan(tag::LATN, None, &features, true).unwrap();
+        assert!(Arc::ptr_eq(&plan, &cached));
+        assert_eq!(plan.script_tag(), tag::LATN);
+        assert_eq!(plan.opt_lang_tag(), None);
+        assert!(plan.kerning());
+        let no_liga = Features::Mask(GsubFeatureMask::default() - GsubFeatureMask::LIGA);
+        let other = font.shaping_plan(tag::LATN, None, &no_liga, true).unwrap();
+        assert!(!Arc::ptr_eq(&plan, &other));
+
+        // Klei ligates ff
+        let glyphs = font.map_glyphs("Shaping in a jiffy.", MatchingPresentation::NotRequired);
+        let infos = font.shape_with_plan(&plan, glyphs.clone()).unwrap();
+        assert_eq!(infos.len(), 18);
+        let infos = font.shape_with_plan(&other, glyphs).unwrap();
+        assert_eq!(infos.len(), 19);
+    }
+
+    #[cfg(feature = "shaping")]
+    #[test]
+    fn test_default_features() {
+        use crate::scripts::ScriptFeatures;
+
+        let font_buffer = read_fixture("tests/fonts/opentype/Klei.otf");
+        let mut font = OwnedFont::from_data(font_buffer, 0).unwrap().unwrap();
+        // Klei ligates ff and kerns AV
+        let text = "AVATAR in a jiffy.";
+        let settings = Features::Settings(Vec::new());
+        let infos = font.shape_text(text, tag::LATN, None, &settings).unwrap();
+        assert_eq!(infos.len(), text.len() - 1);
+        assert!(infos.iter().any(|info| info.kerning != 0));
+
+        let mut default_features = DefaultFeatures::new();
+        default_features.insert(
+            tag::LATN,
+            ScriptFeatures {
+                gsub: GsubFeatureMask::default() - GsubFeatureMask::LIGA,
+                gpos: vec![tag::MARK, tag::MKMK],
+            },
+        );
+        font.set_default_features(default_features);
+        let infos = font.shape_text(text, tag::LATN, None, &settings).unwrap();
+        assert_eq!(infos.len(), text.len());
+        assert!(infos.iter().all(|info| info.kerning == 0));
+
+        // An explicit mask still enables the ligature
+        let mask = Features::Mask(GsubFeatureMask::default());
+        let infos = font.shape_text(text, tag::LATN, None, &mask).unwrap();
+        assert_eq!(infos.len(), text.len() - 1);
+    }
+
+    #[cfg(feature = "shaping")]
+    #[test]
+    fn test_map_variation_sequences() {
+        let font_buffer = read_fixture("tests/fonts/noto/NotoSansJP-Regular.otf");
+        let opentype_file = ReadScope::new(&font_buffer)
+            .read::<OpenTypeFont<'_>>()
+            .unwrap();
+        let font_table_provider = opentype_file
+            .table_provider(0)
+            .expect("error reading font file");
+        let mut font = Font::new(Box::new(font_table_provider))
+            .expect("error reading font data")
+            .expect("missing required font tables");
+
+        let default = font.map_glyphs("\u{4E00}", MatchingPresentation::NotRequired);
+        let glyphs = font.map_glyphs(
+            "\u{4FAE}\u{FE00}\u{4E08}\u{E0101}\u{4E00}\u{E0100}",
+            MatchingPresentation::NotRequired,
+        );
+        let glyph_indices = glyphs.iter().map(|g| g.glyph_index).collect::<Vec<_>>();
+        assert_eq!(glyph_indices, vec![6808, 7187, default[0].glyph_index]);
+    }
+
+    #[cfg(feature = "shaping")]
+    #[test]
+    fn test_map_emoji_zwj_sequence() {
+        let font_buffer = read_fixture("tests/fonts/opentype/TwitterColorEmoji-SVGinOT.ttf");
+        let opentype_file = ReadScope::new(&font_buffer)
+            .read::<OpenTypeFont<'_>>()
+            .unwrap();
+        let font_table_provider = opentype_file
+            .table_provider(0)
+            .expect("error reading font file");
+        let mut font = Font::new(Box::new(font_table_provider))
+            .expect("error reading font data")
+            .expect("missing required font tables");
+
+        // MAN, ZWJ, HEAVY BLACK HEART, ZWJ, MAN
+        let glyphs = font.map_glyphs(
+            "\u{1F468}\u{200D}\u{2764}\u{200D}\u{1F468}",
+            MatchingPresentation::Required,
+        );
+        let glyph_indices = glyphs.iter().map(|g| g.glyph_index).collect::<Vec<_>>();
+        assert_eq!(glyph_indices, vec![590, 7, 159, 7, 590]);
+        // The heart has text presentation by default but inherits emoji presentation from the
+        // sequence
+        assert!(glyphs
+            .iter()
+            .all(|g| g.variation == Some(VariationSelector::VS16)));
+
+        // A heart on its own keeps its default presentation
+        let glyphs = font.map_glyphs("\u{2764}", MatchingPresentation::NotRequired);
+        assert_eq!(glyphs[0].variation, Some(VariationSelector::VS15));
+    }
+
+    #[test]
+    fn test_unique_glyph_names() {
+        let names = vec!["A"; 3].into_iter().map(Cow::from);
+        let unique_names = unique_glyph_names(names, 3);
+        assert_eq!(
+            unique_names,
+            &[Cow::from("A"), Cow::from("A.alt01"), Cow::from("A.alt02")]
+        );
+    }
+
+    #[cfg(feature = "bitmap")]
+    #[test]
+    fn test_color_glyph() {
+        let font_buffer = read_fixture("tests/fonts/svg/gzipped.ttf");
+        let opentype_file = ReadScope::new(&font_buffer)
+            .read::<OpenTypeFont<'_>>()
+            .unwrap();
+        let font_table_provider = opentype_file
+            .table_provider(0)
+            .expect("error reading font file");
+        let mut font = Font::new(Box::new(font_table_provider))
+            .expect("error reading font data")
+            .expect("missing required font tables");
+
+        match font.color_glyph(1, 100, 0, &[]) {
+            Ok(Some(ColorGlyph::Image {
+                source: ColorGlyphSource::Svg,
+                bitmap:
+                    BitmapGlyph {
+                        bitmap: Bitmap::Encapsulated(EncapsulatedBitmap { data, .. }),
+                        ..
+                    },
+            })) => assert!(data.starts_with(b"<?xml")),
+            _ => panic!("expected SVG image, got something else"),
+        }
+        // Glyphs without an SVG document fall back to the outline
+        match font.color_glyph(0, 100, 0, &[]) {
+            Ok(Some(ColorGlyph::Outline)) => {}
+            _ => panic!("expected outline, got something else"),
+        }
+
+        // Sources not listed are not used
+        font.color_glyph_sources = vec![ColorGlyphSource::Sbix, ColorGlyphSource::Outline];
+        match font.color_glyph(1, 100, 0, &[]) {
+            Ok(Some(ColorGlyph::Outline)) => {}
+            _ => panic!("expected outline, got something else"),
+        }
+        font.color_glyph_sources = vec![ColorGlyphSource::Colr1, ColorGlyphSource::Cbdt];
+        assert!(font.color_glyph(1, 100, 0, &[]).unwrap().is_none());
+    }
+
+    #[cfg(feature = "bitmap")]
+    #[test]
+    fn test_color_glyph_sbix() {
+        let font_buffer = read_fixture("tests/fonts/sbix/sbix-dupe.ttf");
+        let opentype_file = ReadScope::new(&font_buffer)
+            .read::<OpenTypeFont<'_>>()
+            .unwrap();
+        let font_table_provider = opentype_file
+            .table_provider(0)
+            .expect("error reading font file");
+        let mut font = Font::new(Box::new(font_table_provider))
+            .expect("error reading font data")
+            .expect("missing required font tables");
+
+        match font.color_glyph(2, 100, 0, &[]) {
+            Ok(Some(ColorGlyph::Image {
+                source: ColorGlyphSource::Sbix,
+                bitmap,
+            })) => assert_eq!(bitmap.ppem_x, Some(300)),
+            _ => panic!("expected sbix image, got something else"),
+        }
+    }
+
+    #[cfg(feature = "bitmap")]
+    #[test]
+    fn test_lookup_sbix() {
+        let font_buffer = read_fixture("tests/fonts/sbix/sbix-dupe.ttf");
+        let opentype_file = ReadScope::new(&font_buffer)
+            .read::<OpenTypeFont<'_>>()
+            .unwrap();
+        let font_table_provider = opentype_file
+            .table_provider(0)
+            .expect("error reading font file");
+        let mut font = Font::new(Box::new(font_table_provider))
+            .expect("error reading font data")
+            .expect("missing required font tables");
+
+        // Successfully read bitmap
+        match font.lookup_glyph_image(1, 100, BitDepth::ThirtyTwo) {
+            Ok(Some(BitmapGlyph {
+                bitmap: Bitmap::Encapsulated(EncapsulatedBitmap { data, .. }),
+                ..
             })) => {
                 assert_eq!(data.len(), 224);
             }
@@ -1032,4 +3327,225 @@ mod tests {
             _ => panic!("Expected Ok(None) got something else"),
         }
     }
+
+    #[cfg(feature = "shaping")]
+    #[test]
+    #[cfg(feature = "shaping")]
+    fn test_small_caps_synthesis() {
+        let buffer = read_fixture("tests/fonts/opentype/TerminusTTF-4.47.0.ttf");
+        let mut font = OwnedFont::from_data(buffer, 0).unwrap().unwrap();
+        let upper_a = font.map_char('A');
+        let advance = i32::from(font.horizontal_advance(upper_a).unwrap());
+        let features = Features::Settings(vec![FeatureSetting::on(tag::SMCP)]);
+        let shape = |font: &mut OwnedFont| {
+            let glyphs = font.map_glyphs("aA", MatchingPresentation::NotRequired);
+            font.shape(glyphs, tag::LATN, None, &features, true)
+                .unwrap()
+        };
+
+        // Without synthesis the font's lack of `smcp` leaves the text unchanged
+        let infos = shape(&mut font);
+        assert_ne!(infos[0].glyph.glyph_index, upper_a);
+        assert!(!infos[0].glyph.small_caps);
+
+        font.small_caps_synthesis = Some(SmallCapsSynthesis {
+            x_scale: 0.5,
+            y_scale: 0.5,
+        });
+        let infos = shape(&mut font);
+        let glyphs = infos
+            .iter()
+            .map(|info| (info.glyph.glyph_index, info.glyph.small_caps))
+            .collect::<Vec<_>>();
+        assert_eq!(glyphs, vec![(upper_a, true), (upper_a, false)]);
+        let positions = font.glyph_positions(&infos, TextDirection::LeftToRight);
+        assert_eq!(positions[0].x_advance, advance / 2);
+        assert_eq!(positions[1].x_advance, advance);
+
+        let outline = font.outline(upper_a, &[]).unwrap().unwrap();
+        let small_caps_outline = font.small_caps_outline(&infos[0].glyph).unwrap().unwrap();
+        let scaled_points = outline
+            .points
+            .iter()
+            .map(|point| {
+                let half = |value: i16| (f32::from(value) * 0.5).round() as i16;
+                crate::tables::glyf::Point(half(point.0), half(point.1))
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(small_caps_outline.points, scaled_points);
+        assert_eq!(
+            font.small_caps_outline(&infos[1].glyph).unwrap().unwrap(),
+            outline
+        );
+    }
+
+    #[test]
+    fn test_decoration_metrics() {
+        let buffer = read_fixture("tests/fonts/opentype/Klei.otf");
+        let font = OwnedFont::from_data(buffer, 0).unwrap().unwrap();
+        let post_data = font.font_table_provider.read_table_data(tag::POST).unwrap();
+        let post = ReadScope::new(&post_data).read::<PostTable<'_>>().unwrap();
+        let os2 = font.os2_table().unwrap().unwrap();
+
+        let metrics = font.decoration_metrics(&[]).unwrap();
+        assert_eq!(metrics.underline_position, post.header.underline_position);
+        assert_eq!(metrics.underline_thickness, post.header.underline_thickness);
+        assert_eq!(metrics.strikeout_position, os2.y_strikeout_position);
+        assert_eq!(metrics.strikeout_thickness, os2.y_strikeout_size);
+        // The font isn't variable so the coordinates have no effect
+        assert_eq!(
+            font.decoration_metrics(&[F2Dot14::new(0x4000)]).unwrap(),
+            metrics
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "shaping")]
+    fn test_shape_script_position() {
+        let buffer = read_fixture("tests/fonts/noto/NotoSansTamil-Regular.ttf");
+        let mut font = OwnedFont::from_data(buffer, 0).unwrap().unwrap();
+        let features = Features::Mask(GsubFeatureMask::default());
+        let mut shape = |text| {
+            let glyphs = font.map_glyphs(text, MatchingPresentation::NotRequired);
+            let plain = glyphs
+                .iter()
+                .map(|glyph| glyph.glyph_index)
+                .collect::<Vec<_>>();
+            let (infos, transform) = font
+                .shape_script_position(
+                    glyphs,
+                    tag::TAML,
+                    None,
+                    &features,
+                    true,
+                    ScriptPosition::Superscript,
+                )
+                .unwrap();
+            let substituted = infos
+                .iter()
+                .zip(plain)
+                .any(|(info, glyph_index)| info.glyph.glyph_index != glyph_index);
+            (substituted, transform)
+        };
+
+        // The font has superscript twos, threes and fours
+        assert_eq!(shape("2 3"), (true, None));
+        // but not superscript letters, so the digits aren't substituted either
+        let (substituted, transform) = shape("2\u{0B95}");
+        assert!(!substituted);
+        assert!(transform.unwrap().y_offset > 0);
+    }
+
+    #[test]
+    #[cfg(feature = "shaping")]
+    fn test_feature_names() {
+        let buffer = read_fixture("tests/fonts/arabic/Scheherazade-Regular.ttf");
+        let mut font = OwnedFont::from_data(buffer, 0).unwrap().unwrap();
+        let names = font.feature_names(tag!(b"cv44")).unwrap().unwrap();
+        assert_eq!(names.ui_label, Some(String::from("Meem")));
+        assert_eq!(names.tooltip, None);
+        assert_eq!(names.param_labels, vec!["Standard", "Sindhi-style"]);
+        assert_eq!(font.feature_names(tag::LIGA).unwrap(), None);
+
+        let buffer = read_fixture("tests/fonts/arabic/amiri-regular.ttf");
+        let mut font = OwnedFont::from_data(buffer, 0).unwrap().unwrap();
+        assert_eq!(
+            font.feature_params(tag!(b"ss03")).unwrap(),
+            Some(FeatureParams::StylisticSet { ui_name_id: 259 })
+        );
+        let names = font.feature_names(tag!(b"ss03")).unwrap().unwrap();
+        assert_eq!(
+            names.ui_label,
+            Some(String::from("Localised @ and & symbols"))
+        );
+    }
+
+    #[test]
+    fn test_owned_font() {
+        fn load(path: &str) -> OwnedFont {
+            OwnedFont::from_data(read_fixture(path), 0)
+                .expect("error reading font")
+                .expect("missing required font tables")
+        }
+
+        // The font data is dropped at the end of `load` so the font must own it
+        let mut fonts = vec![
+            load("tests/fonts/opentype/Klei.otf"),
+            load("tests/fonts/woff1/valid-001.woff"),
+            load("tests/fonts/woff2/test-font.woff2"),
+        ];
+        for font in fonts.iter_mut() {
+            let glyph = font.map_char('F');
+            assert_ne!(glyph, 0);
+            #[cfg(feature = "shaping")]
+            {
+                let infos = font
+                    .shape_text(
+                        "FF",
+                        tag::LATN,
+                        None,
+                        &Features::Mask(GsubFeatureMask::default()),
+                    )
+                    .unwrap();
+                assert_eq!(infos.len(), 2);
+                assert_eq!(infos[1].glyph.glyph_index, glyph);
+            }
+        }
+
+        // Tables of an uncompressed font refer to the shared font data
+        let klei = &fonts[0].font_table_provider;
+        let cmap = klei.table_data(tag::CMAP).unwrap().unwrap();
+        let (start, ptr) = (klei.data().as_ptr() as usize, cmap.as_ptr() as usize);
+        assert!(ptr >= start && ptr < start + klei.data().len());
+    }
+
+    #[test]
+    fn test_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+
+        assert_send_sync::<OwnedFont>();
+        assert_send_sync::<Font<DynamicFontTableProvider<'_>>>();
+        #[cfg(feature = "shaping")]
+        assert_send_sync::<LayoutCache<GSUB>>();
+        #[cfg(feature = "shaping")]
+        assert_send_sync::<LayoutCache<GPOS>>();
+    }
+
+    #[cfg(all(feature = "shaping", feature = "rayon"))]
+    #[test]
+    fn test_shape_runs_parallel() {
+        let mut font = OwnedFont::from_data(read_fixture("tests/fonts/opentype/Klei.otf"), 0)
+            .unwrap()
+            .unwrap();
+        let texts = ["Shaping in a jiffy.", "AVATAR", "office", "Wave"];
+        let runs = texts
+            .iter()
+            .cycle()
+            .take(64)
+            .enumerate()
+            .map(|(i, &text)| TextRun {
+                text,
+                script_tag: tag::LATN,
+                opt_lang_tag: None,
+                features: Features::Mask(GsubFeatureMask::default()),
+                kerning: i % 2 == 0,
+            })
+            .collect::<Vec<_>>();
+
+        let shaped_runs = font.shape_runs_parallel(&runs).unwrap();
+        assert_eq!(shaped_runs.len(), runs.len());
+        for (run, shaped_run) in runs.iter().zip(shaped_runs) {
+            let glyphs = font.map_glyphs(run.text, MatchingPresentation::NotRequired);
+            let expected = font
+                .shape(glyphs, run.script_tag, None, &run.features, run.kerning)
+                .unwrap();
+            let summarise = |infos: &[Info]| {
+                infos
+                    .iter()
+                    .map(|info| (info.glyph.glyph_index, info.kerning))
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(summarise(&shaped_run.infos), summarise(&expected));
+        }
+    }
 }
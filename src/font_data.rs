@@ -1,8 +1,16 @@
 //! Top-level font file representation.
 
+pub mod dfont;
+pub mod eot;
+
 use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::sync::Arc;
 
-use crate::binary::read::{ReadBinary, ReadCtxt};
+use self::dfont::DfontFont;
+use self::eot::EotFont;
+use crate::binary::read::{ReadBinary, ReadCtxt, ReadScope};
 use crate::error::{ParseError, ReadWriteError};
 use crate::tables::{FontTableProvider, OpenTypeFont, CFF_MAGIC, TTCF_MAGIC, TTF_MAGIC};
 use crate::woff::{self, WoffFont};
@@ -13,11 +21,40 @@ pub enum FontData<'a> {
     OpenType(OpenTypeFont<'a>),
     Woff(WoffFont<'a>),
     Woff2(Woff2Font<'a>),
+    Eot(EotFont<'a>),
+    Dfont(DfontFont<'a>),
 }
 
+/// The offset of the resource data in a dfont, used to identify them.
+///
+/// dfonts have no magic number but in practice the resource data always follows the 256 byte
+/// header.
+const DFONT_DATA_OFFSET: u32 = 0x100;
+
 /// Generic implementation of the `FontTableProvider` trait
 pub struct DynamicFontTableProvider<'a> {
-    provider: Box<dyn FontTableProvider + 'a>,
+    provider: Box<dyn FontTableProvider + Send + Sync + 'a>,
+}
+
+/// Implementation of the `FontTableProvider` trait that owns its font data
+///
+/// Unlike the providers obtained from `FontData` this type has no lifetime parameter, which makes
+/// it suitable for storing parsed fonts in caches or sending them to other threads. Tables that
+/// are stored uncompressed in the font file refer to the shared `Arc<[u8]>` holding the file.
+/// Tables that need to be decoded (such as those in WOFF and WOFF2 files) are decoded once up
+/// front.
+#[derive(Clone)]
+pub struct OwnedFontTableProvider {
+    data: Arc<[u8]>,
+    tables: BTreeMap<u32, OwnedTableData>,
+}
+
+#[derive(Clone)]
+enum OwnedTableData {
+    /// The table is held in the font data at this range.
+    Shared(Range<usize>),
+    /// The table was decoded from the font data.
+    Decoded(Arc<[u8]>),
 }
 
 impl<'a> ReadBinary<'a> for FontData<'a> {
@@ -31,6 +68,8 @@ impl<'a> ReadBinary<'a> for FontData<'a> {
             TTCF_MAGIC => Ok(FontData::OpenType(OpenTypeFont::read(ctxt)?)),
             woff::MAGIC => Ok(FontData::Woff(WoffFont::read(ctxt)?)),
             woff2::MAGIC => Ok(FontData::Woff2(Woff2Font::read(ctxt)?)),
+            DFONT_DATA_OFFSET => Ok(FontData::Dfont(DfontFont::read(ctxt)?)),
+            _ if eot::is_eot(ctxt.scope()) => Ok(FontData::Eot(EotFont::read(ctxt)?)),
             _ => Err(ParseError::BadVersion),
         }
     }
@@ -44,6 +83,70 @@ impl<'a> FontTableProvider for DynamicFontTableProvider<'a> {
     fn has_table(&self, tag: u32) -> bool {
         self.provider.has_table(tag)
     }
+
+    fn table_tags(&self) -> Option<Vec<u32>> {
+        self.provider.table_tags()
+    }
+}
+
+impl OwnedFontTableProvider {
+    /// Parse `data` and take ownership of it.
+    ///
+    /// `index` selects the font to use when `data` holds a font collection.
+    pub fn new(data: impl Into<Arc<[u8]>>, index: usize) -> Result<Self, ReadWriteError> {
+        let data = data.into();
+        let tables = {
+            let font_data = ReadScope::new(&data).read::<FontData<'_>>()?;
+            let provider = font_data.table_provider(index)?;
+            let tags = provider.table_tags().ok_or(ParseError::MissingValue)?;
+            let mut tables = BTreeMap::new();
+            for tag in tags {
+                let table = match provider.read_table_data(tag)? {
+                    Cow::Borrowed(table) => match shared_range(&data, table) {
+                        Some(range) => OwnedTableData::Shared(range),
+                        None => OwnedTableData::Decoded(Arc::from(table)),
+                    },
+                    Cow::Owned(table) => OwnedTableData::Decoded(Arc::from(table)),
+                };
+                tables.insert(tag, table);
+            }
+            tables
+        };
+        Ok(OwnedFontTableProvider { data, tables })
+    }
+
+    /// The font data that this provider was created from.
+    pub fn data(&self) -> &Arc<[u8]> {
+        &self.data
+    }
+}
+
+/// Returns the range of `table` within `data` if `table` is a sub-slice of it.
+fn shared_range(data: &[u8], table: &[u8]) -> Option<Range<usize>> {
+    let start = (table.as_ptr() as usize).checked_sub(data.as_ptr() as usize)?;
+    let end = start.checked_add(table.len())?;
+    if end <= data.len() {
+        Some(start..end)
+    } else {
+        None
+    }
+}
+
+impl FontTableProvider for OwnedFontTableProvider {
+    fn table_data<'a>(&'a self, tag: u32) -> Result<Option<Cow<'a, [u8]>>, ParseError> {
+        Ok(self.tables.get(&tag).map(|table| match table {
+            OwnedTableData::Shared(range) => Cow::Borrowed(&self.data[range.clone()]),
+            OwnedTableData::Decoded(table) => Cow::Borrowed(table.as_ref()),
+        }))
+    }
+
+    fn has_table(&self, tag: u32) -> bool {
+        self.tables.contains_key(&tag)
+    }
+
+    fn table_tags(&self) -> Option<Vec<u32>> {
+        Some(self.tables.keys().copied().collect())
+    }
 }
 
 impl<'a> FontData<'a> {
@@ -71,6 +174,18 @@ impl<'a> FontData<'a> {
                     provider: Box::new(provider),
                 })
             }
+            FontData::Eot(font) => {
+                let provider = font.table_provider(index)?;
+                Ok(DynamicFontTableProvider {
+                    provider: Box::new(provider),
+                })
+            }
+            FontData::Dfont(font) => {
+                let provider = font.table_provider(index)?;
+                Ok(DynamicFontTableProvider {
+                    provider: Box::new(provider),
+                })
+            }
         }
     }
 }
@@ -0,0 +1,171 @@
+//! Reading of Macintosh resource fork font suitcases (dfont).
+//!
+//! A dfont file is a resource fork stored in the data fork of a file. Each `sfnt` resource in it
+//! contains a complete TrueType or OpenType font.
+//!
+//! <https://developer.apple.com/library/archive/documentation/mac/pdf/MoreMacintoshToolbox.pdf>
+
+use std::convert::TryFrom;
+
+use crate::binary::read::{ReadBinary, ReadCtxt, ReadScope};
+use crate::binary::U24Be;
+use crate::error::ParseError;
+use crate::tables::{OffsetTableFontProvider, OpenTypeFont};
+use crate::tag;
+
+/// The resource type of resources that hold a font.
+pub const SFNT: u32 = tag!(b"sfnt");
+
+/// Size of the resource fork header, a copy of which starts the resource map.
+const HEADER_SIZE: usize = 16;
+
+/// A resource fork font suitcase.
+pub struct DfontFont<'a> {
+    pub scope: ReadScope<'a>,
+    /// The `sfnt` resources in the order they appear in the resource map.
+    pub resources: Vec<SfntResource<'a>>,
+}
+
+/// An `sfnt` resource of a dfont.
+pub struct SfntResource<'a> {
+    pub id: u16,
+    pub data: ReadScope<'a>,
+}
+
+impl<'a> DfontFont<'a> {
+    /// Obtain a `FontTableProvider` for the `sfnt` resource at `index`.
+    pub fn table_provider(&self, index: usize) -> Result<OffsetTableFontProvider<'a>, ParseError> {
+        let resource = self.resources.get(index).ok_or(ParseError::BadIndex)?;
+        resource
+            .data
+            .read::<OpenTypeFont<'a>>()?
+            .into_table_provider(0)
+    }
+}
+
+impl<'a> ReadBinary<'a> for DfontFont<'a> {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let scope = ctxt.scope();
+        let data_offset = usize::try_from(ctxt.read_u32be()?)?;
+        let map_offset = usize::try_from(ctxt.read_u32be()?)?;
+        let data_length = usize::try_from(ctxt.read_u32be()?)?;
+        let map_length = usize::try_from(ctxt.read_u32be()?)?;
+        let data_scope = scope.offset_length(data_offset, data_length)?;
+        let map_scope = scope.offset_length(map_offset, map_length)?;
+
+        // Skip the copy of the header, handle to the next map, file reference number and
+        // attributes
+        let mut map = map_scope.offset(HEADER_SIZE + 4 + 2 + 2).ctxt();
+        let type_list_offset = usize::from(map.read_u16be()?);
+        let _name_list_offset = map.read_u16be()?;
+
+        // Counts in the resource map are stored minus one
+        let type_list = map_scope.offset(type_list_offset);
+        let mut types = type_list.ctxt();
+        let num_types = (usize::from(types.read_u16be()?) + 1) & 0xFFFF;
+        let mut resources = Vec::new();
+        for _ in 0..num_types {
+            let resource_type = types.read_u32be()?;
+            let num_resources = usize::from(types.read_u16be()?) + 1;
+            let reference_list_offset = usize::from(types.read_u16be()?);
+            if resource_type != SFNT {
+                continue;
+            }
+
+            let mut references = type_list.offset(reference_list_offset).ctxt();
+            for _ in 0..num_resources {
+                let id = references.read_u16be()?;
+                let _name_offset = references.read_u16be()?;
+                let _attributes = references.read_u8()?;
+                let offset = usize::try_from(references.read::<U24Be>()?)?;
+                let _handle = references.read_u32be()?;
+
+                let mut resource = data_scope.offset(offset).ctxt();
+                let length = usize::try_from(resource.read_u32be()?)?;
+                let data = resource.read_scope(length)?;
+                resources.push(SfntResource { id, data });
+            }
+        }
+
+        Ok(DfontFont { scope, resources })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::write::{WriteBinary, WriteBuffer, WriteContext};
+    use crate::binary::{U16Be, U32Be, U8};
+    use crate::font_data::FontData;
+    use crate::tables::FontTableProvider;
+    use crate::tests::read_fixture;
+
+    /// Build a dfont containing an `sfnt` resource for each of `fonts`.
+    fn make_dfont(fonts: &[&[u8]]) -> Vec<u8> {
+        let mut data = WriteBuffer::new();
+        let mut offsets = Vec::new();
+        for font in fonts {
+            offsets.push(data.len() as u32);
+            U32Be::write(&mut data, font.len() as u32).unwrap();
+            data.write_bytes(font).unwrap();
+        }
+
+        let mut map = WriteBuffer::new();
+        map.write_zeros(HEADER_SIZE + 4 + 2 + 2).unwrap();
+        U16Be::write(&mut map, 28u16).unwrap(); // type list offset
+        U16Be::write(&mut map, 0u16).unwrap(); // name list offset
+        U16Be::write(&mut map, 0u16).unwrap(); // number of types - 1
+        U32Be::write(&mut map, SFNT).unwrap();
+        U16Be::write(&mut map, fonts.len() as u16 - 1).unwrap();
+        U16Be::write(&mut map, 10u16).unwrap(); // reference list offset
+        for (id, offset) in offsets.into_iter().enumerate() {
+            U16Be::write(&mut map, 128 + id as u16).unwrap();
+            U16Be::write(&mut map, 0xFFFFu16).unwrap(); // no name
+            U8::write(&mut map, 0u8).unwrap();
+            map.write_bytes(&offset.to_be_bytes()[1..]).unwrap();
+            U32Be::write(&mut map, 0u32).unwrap();
+        }
+
+        let mut w = WriteBuffer::new();
+        let data_offset = 256;
+        let map_offset = data_offset + data.len();
+        U32Be::write(&mut w, data_offset as u32).unwrap();
+        U32Be::write(&mut w, map_offset as u32).unwrap();
+        U32Be::write(&mut w, data.len() as u32).unwrap();
+        U32Be::write(&mut w, map.len() as u32).unwrap();
+        w.write_zeros(data_offset - w.len()).unwrap();
+        w.write_bytes(data.bytes()).unwrap();
+        w.write_bytes(map.bytes()).unwrap();
+        w.into_inner()
+    }
+
+    #[test]
+    fn test_read_dfont() {
+        let ttf = read_fixture("tests/fonts/opentype/test-font.ttf");
+        let otf = read_fixture("tests/fonts/opentype/Klei.otf");
+        let dfont = make_dfont(&[&ttf, &otf]);
+        let font_file = ReadScope::new(&dfont).read::<FontData<'_>>().unwrap();
+        let font = match &font_file {
+            FontData::Dfont(font) => font,
+            _ => panic!("expected a dfont"),
+        };
+        let ids = font
+            .resources
+            .iter()
+            .map(|resource| resource.id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![128, 129]);
+
+        assert!(font_file.table_provider(0).unwrap().has_table(tag::GLYF));
+        let provider = font_file.table_provider(1).unwrap();
+        let expected = ReadScope::new(&otf).read::<OpenTypeFont<'_>>().unwrap();
+        let expected = expected.table_provider(0).unwrap();
+        assert_eq!(
+            provider.read_table_data(tag::CFF).unwrap(),
+            expected.read_table_data(tag::CFF).unwrap()
+        );
+        assert!(font_file.table_provider(2).is_err());
+    }
+}
@@ -0,0 +1,284 @@
+//! Reading of the Embedded OpenType (EOT) font format.
+//!
+//! <https://www.w3.org/Submission/EOT/>
+
+use std::borrow::Cow;
+use std::convert::TryFrom;
+
+use crate::binary::read::{ReadBinary, ReadCtxt, ReadScope};
+use crate::error::ParseError;
+use crate::tables::{OffsetTableFontProvider, OpenTypeFont};
+
+/// The magic number found at offset 34 of an EOT file.
+pub const MAGIC: u16 = 0x504C;
+
+/// Offset of the magic number in the EOT header.
+const MAGIC_OFFSET: usize = 34;
+
+const VERSION_1_0: u32 = 0x0001_0000;
+const VERSION_2_1: u32 = 0x0002_0001;
+const VERSION_2_2: u32 = 0x0002_0002;
+
+/// The font data is compressed with MicroType Express.
+const TTEMBED_TTCOMPRESSED: u32 = 0x0000_0004;
+/// The font data has been XOR-ed with `XOR_KEY`.
+const TTEMBED_XORENCRYPTDATA: u32 = 0x1000_0000;
+const XOR_KEY: u8 = 0x50;
+
+/// An Embedded OpenType font.
+///
+/// Fonts compressed with MicroType Express are not supported.
+pub struct EotFont<'a> {
+    pub header: EotHeader,
+    font_data: Cow<'a, [u8]>,
+}
+
+#[derive(Debug, Clone)]
+pub struct EotHeader {
+    pub eot_size: u32,
+    pub font_data_size: u32,
+    pub version: u32,
+    pub flags: u32,
+    pub panose: [u8; 10],
+    pub charset: u8,
+    pub italic: u8,
+    pub weight: u32,
+    pub fs_type: u16,
+    pub unicode_range: [u32; 4],
+    pub code_page_range: [u32; 2],
+    pub check_sum_adjustment: u32,
+    pub family_name: String,
+    pub style_name: String,
+    pub version_name: String,
+    pub full_name: String,
+    /// The URLs the font is allowed to be used on, only present from version 2.1.
+    pub root_strings: Vec<String>,
+}
+
+impl<'a> EotFont<'a> {
+    /// The decrypted SFNT font data wrapped by this EOT file.
+    pub fn font_data(&self) -> &[u8] {
+        &self.font_data
+    }
+
+    /// Obtain a `FontTableProvider` for the wrapped font.
+    pub fn table_provider(
+        &'a self,
+        index: usize,
+    ) -> Result<OffsetTableFontProvider<'a>, ParseError> {
+        ReadScope::new(&self.font_data)
+            .read::<OpenTypeFont<'a>>()?
+            .into_table_provider(index)
+    }
+}
+
+/// Returns true if the data looks like an EOT file.
+pub(crate) fn is_eot(scope: ReadScope<'_>) -> bool {
+    scope
+        .offset_length(MAGIC_OFFSET, 2)
+        .and_then(|magic| magic.ctxt().read_u16le().map_err(ParseError::from))
+        .map(|magic| magic == MAGIC)
+        .unwrap_or(false)
+}
+
+impl<'a> ReadBinary<'a> for EotFont<'a> {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let eot_size = ctxt.read_u32le()?;
+        let font_data_size = ctxt.read_u32le()?;
+        let version = ctxt.read_u32le()?;
+        ctxt.check_version(
+            version == VERSION_1_0 || version == VERSION_2_1 || version == VERSION_2_2,
+        )?;
+        let flags = ctxt.read_u32le()?;
+        let mut panose = [0; 10];
+        panose.copy_from_slice(ctxt.read_slice(10)?);
+        let charset = ctxt.read_u8()?;
+        let italic = ctxt.read_u8()?;
+        let weight = ctxt.read_u32le()?;
+        let fs_type = ctxt.read_u16le()?;
+        let magic = ctxt.read_u16le()?;
+        ctxt.check(magic == MAGIC)?;
+        let mut unicode_range = [0; 4];
+        for range in unicode_range.iter_mut() {
+            *range = ctxt.read_u32le()?;
+        }
+        let mut code_page_range = [0; 2];
+        for range in code_page_range.iter_mut() {
+            *range = ctxt.read_u32le()?;
+        }
+        let check_sum_adjustment = ctxt.read_u32le()?;
+        let _reserved = ctxt.read_slice(4 * 4)?;
+        let family_name = read_name(ctxt)?;
+        let style_name = read_name(ctxt)?;
+        let version_name = read_name(ctxt)?;
+        let full_name = read_name(ctxt)?;
+
+        let mut root_strings = Vec::new();
+        if version >= VERSION_2_1 {
+            let root_string = read_name(ctxt)?;
+            root_strings.extend(
+                root_string
+                    .split('\0')
+                    .filter(|url| !url.is_empty())
+                    .map(String::from),
+            );
+        }
+        if version >= VERSION_2_2 {
+            let _root_string_check_sum = ctxt.read_u32le()?;
+            let _eudc_code_page = ctxt.read_u32le()?;
+            let _padding = ctxt.read_u16le()?;
+            let signature_size = ctxt.read_u16le()?;
+            let _signature = ctxt.read_slice(usize::from(signature_size))?;
+            let _eudc_flags = ctxt.read_u32le()?;
+            let eudc_font_size = usize::try_from(ctxt.read_u32le()?)?;
+            let _eudc_font_data = ctxt.read_slice(eudc_font_size)?;
+        }
+
+        if flags & TTEMBED_TTCOMPRESSED != 0 {
+            return Err(ParseError::NotImplemented);
+        }
+        let data = ctxt.read_slice(usize::try_from(font_data_size)?)?;
+        let font_data = if flags & TTEMBED_XORENCRYPTDATA != 0 {
+            Cow::Owned(data.iter().map(|byte| byte ^ XOR_KEY).collect())
+        } else {
+            Cow::Borrowed(data)
+        };
+
+        Ok(EotFont {
+            header: EotHeader {
+                eot_size,
+                font_data_size,
+                version,
+                flags,
+                panose,
+                charset,
+                italic,
+                weight,
+                fs_type,
+                unicode_range,
+                code_page_range,
+                check_sum_adjustment,
+                family_name,
+                style_name,
+                version_name,
+                full_name,
+                root_strings,
+            },
+            font_data,
+        })
+    }
+}
+
+/// Read a padded, size prefixed, UTF-16LE string.
+fn read_name(ctxt: &mut ReadCtxt<'_>) -> Result<String, ParseError> {
+    let _padding = ctxt.read_u16le()?;
+    let size = usize::from(ctxt.read_u16le()?);
+    ctxt.check(size % 2 == 0)?;
+    let mut name = Vec::with_capacity(size / 2);
+    for _ in 0..size / 2 {
+        name.push(ctxt.read_u16le()?);
+    }
+    String::from_utf16(&name).map_err(|_| ParseError::BadValue)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::write::{WriteBuffer, WriteContext};
+    use crate::error::ParseErrorKind;
+    use crate::font_data::FontData;
+    use crate::tables::FontTableProvider;
+    use crate::tag;
+    use crate::tests::read_fixture;
+
+    fn write_u16le(w: &mut WriteBuffer, value: u16) {
+        w.write_bytes(&value.to_le_bytes()).unwrap();
+    }
+
+    fn write_u32le(w: &mut WriteBuffer, value: u32) {
+        w.write_bytes(&value.to_le_bytes()).unwrap();
+    }
+
+    fn write_name(w: &mut WriteBuffer, name: &str) {
+        let name = name.encode_utf16().collect::<Vec<_>>();
+        write_u16le(w, 0);
+        write_u16le(w, name.len() as u16 * 2);
+        for unit in name {
+            write_u16le(w, unit);
+        }
+    }
+
+    /// Wrap `sfnt` in a version 2.1 EOT file.
+    fn make_eot(sfnt: &[u8], xor: bool) -> Vec<u8> {
+        let mut w = WriteBuffer::new();
+        let flags = if xor { TTEMBED_XORENCRYPTDATA } else { 0 };
+        write_u32le(&mut w, 0); // EOTSize, filled in below
+        write_u32le(&mut w, sfnt.len() as u32);
+        write_u32le(&mut w, VERSION_2_1);
+        write_u32le(&mut w, flags);
+        w.write_zeros(10).unwrap(); // panose
+        w.write_bytes(&[1, 0]).unwrap(); // charset, italic
+        write_u32le(&mut w, 400);
+        write_u16le(&mut w, 0);
+        write_u16le(&mut w, MAGIC);
+        w.write_zeros(4 * 4 + 2 * 4 + 4 + 4 * 4).unwrap();
+        write_name(&mut w, "Family");
+        write_name(&mut w, "Regular");
+        write_name(&mut w, "Version 1.0");
+        write_name(&mut w, "Family Regular");
+        write_name(&mut w, "https://example.com\0https://example.org\0");
+        if xor {
+            let data = sfnt.iter().map(|byte| byte ^ XOR_KEY).collect::<Vec<_>>();
+            w.write_bytes(&data).unwrap();
+        } else {
+            w.write_bytes(sfnt).unwrap();
+        }
+
+        let mut data = w.into_inner();
+        let eot_size = data.len() as u32;
+        data[0..4].copy_from_slice(&eot_size.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn test_read_eot() {
+        let sfnt = read_fixture("tests/fonts/opentype/test-font.ttf");
+        for &xor in &[false, true] {
+            let eot = make_eot(&sfnt, xor);
+            let font_file = ReadScope::new(&eot).read::<FontData<'_>>().unwrap();
+            let font = match &font_file {
+                FontData::Eot(font) => font,
+                _ => panic!("expected an EOT font"),
+            };
+            assert_eq!(font.header.family_name, "Family");
+            assert_eq!(font.header.full_name, "Family Regular");
+            assert_eq!(
+                font.header.root_strings,
+                vec!["https://example.com", "https://example.org"]
+            );
+            assert_eq!(font.font_data(), &sfnt[..]);
+
+            let provider = font_file.table_provider(0).unwrap();
+            let expected = ReadScope::new(&sfnt).read::<OpenTypeFont<'_>>().unwrap();
+            let expected = expected.table_provider(0).unwrap();
+            assert_eq!(
+                provider.read_table_data(tag::HEAD).unwrap(),
+                expected.read_table_data(tag::HEAD).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_read_eot_compressed() {
+        let sfnt = read_fixture("tests/fonts/opentype/test-font.ttf");
+        let mut eot = make_eot(&sfnt, false);
+        eot[12] |= TTEMBED_TTCOMPRESSED as u8;
+        match ReadScope::new(&eot).read::<EotFont<'_>>() {
+            Err(err) if err.kind() == ParseErrorKind::NotImplemented => {}
+            Err(err) => panic!("expected NotImplemented got {:?}", err),
+            Ok(_) => panic!("expected NotImplemented got Ok"),
+        }
+    }
+}
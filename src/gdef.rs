@@ -2,12 +2,61 @@
 //!
 //! <https://docs.microsoft.com/en-us/typography/opentype/spec/gdef>
 
-use crate::layout::GDEFTable;
+use std::convert::TryFrom;
+
+use crate::binary::read::ReadScope;
+use crate::error::ParseError;
+use crate::layout::{CaretValue, GDEFTable};
+use crate::tables::vvar::ItemVariationStore;
+use crate::tables::F2Dot14;
+
+/// The class of a glyph in the glyph class definition table of `GDEF`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GlyphClass {
+    /// A single character, spacing glyph.
+    Base,
+    /// A multiple character, spacing glyph.
+    Ligature,
+    /// A non-spacing combining glyph.
+    Mark,
+    /// A part of a single character, spacing glyph.
+    Component,
+}
+
+impl GlyphClass {
+    fn from_class_value(class_value: u16) -> Option<GlyphClass> {
+        match class_value {
+            1 => Some(GlyphClass::Base),
+            2 => Some(GlyphClass::Ligature),
+            3 => Some(GlyphClass::Mark),
+            4 => Some(GlyphClass::Component),
+            _ => None,
+        }
+    }
+}
+
+pub fn gdef_is_base(opt_gdef_table: Option<&GDEFTable>, glyph_index: u16) -> bool {
+    glyph_class(opt_gdef_table, glyph_index) == 1
+}
+
+pub fn gdef_is_ligature(opt_gdef_table: Option<&GDEFTable>, glyph_index: u16) -> bool {
+    glyph_class(opt_gdef_table, glyph_index) == 2
+}
 
 pub fn gdef_is_mark(opt_gdef_table: Option<&GDEFTable>, glyph_index: u16) -> bool {
     glyph_class(opt_gdef_table, glyph_index) == 3
 }
 
+pub fn gdef_is_component(opt_gdef_table: Option<&GDEFTable>, glyph_index: u16) -> bool {
+    glyph_class(opt_gdef_table, glyph_index) == 4
+}
+
+/// Returns the class of `glyph`, or `None` if the font has no glyph class definitions or the
+/// glyph is not assigned a class.
+pub fn glyph_class_of(opt_gdef_table: Option<&GDEFTable>, glyph: u16) -> Option<GlyphClass> {
+    GlyphClass::from_class_value(glyph_class(opt_gdef_table, glyph))
+}
+
 pub fn glyph_class(opt_gdef_table: Option<&GDEFTable>, glyph: u16) -> u16 {
     match opt_gdef_table {
         Some(ref gdef_table) => match gdef_table.opt_glyph_classdef {
@@ -27,3 +76,310 @@ pub fn mark_attach_class(opt_gdef_table: Option<&GDEFTable>, glyph: u16) -> u16
         None => 0,
     }
 }
+
+/// Returns `true` if `glyph` is in the mark glyph set at `set_index` of `GDEF`.
+///
+/// Returns `false` if the font has no mark glyph sets or there is no set at `set_index`.
+pub fn mark_glyph_set_contains(
+    opt_gdef_table: Option<&GDEFTable>,
+    set_index: u16,
+    glyph: u16,
+) -> bool {
+    match opt_gdef_table {
+        Some(ref gdef_table) => match gdef_table.opt_mark_glyph_sets {
+            Some(ref mark_glyph_sets) => mark_glyph_sets.contains(set_index, glyph),
+            None => false,
+        },
+        None => false,
+    }
+}
+
+/// The position of a caret within a ligature glyph.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CaretPosition {
+    /// A coordinate in font units, along the x-axis in horizontal text or the y-axis in vertical
+    /// text.
+    Coordinate(i16),
+    /// The index of a contour point of the glyph outline. The caret is placed at the position
+    /// of the point after the outline has been hinted.
+    ContourPoint(u16),
+}
+
+/// Returns the positions of the carets between the components of the ligature `glyph`, used by
+/// editors to place the cursor within a ligature.
+///
+/// Coordinates with variation deltas are adjusted by the deltas in `opt_item_variation_store` at
+/// the normalised variation coordinates `coords`. Returns an empty `Vec` if `glyph` has no
+/// carets.
+pub fn ligature_carets(
+    opt_gdef_table: Option<&GDEFTable>,
+    opt_item_variation_store: Option<&ItemVariationStore<'_>>,
+    glyph: u16,
+    coords: &[F2Dot14],
+) -> Result<Vec<CaretPosition>, ParseError> {
+    let caret_values = match opt_gdef_table
+        .and_then(|gdef_table| gdef_table.opt_lig_caret_list.as_ref())
+        .and_then(|lig_caret_list| lig_caret_list.caret_values(glyph))
+    {
+        Some(caret_values) => caret_values,
+        None => return Ok(Vec::new()),
+    };
+
+    caret_values
+        .iter()
+        .map(|caret_value| match *caret_value {
+            CaretValue::Coordinate(coordinate) => Ok(CaretPosition::Coordinate(coordinate)),
+            CaretValue::ContourPoint(point_index) => Ok(CaretPosition::ContourPoint(point_index)),
+            CaretValue::VariableCoordinate {
+                coordinate,
+                delta_set_outer_index,
+                delta_set_inner_index,
+            } => {
+                let delta = match opt_item_variation_store {
+                    Some(item_variation_store) => item_variation_store.delta(
+                        delta_set_outer_index,
+                        delta_set_inner_index,
+                        coords,
+                    )?,
+                    None => 0.,
+                };
+                let coordinate = (f32::from(coordinate) + delta).round() as i16;
+                Ok(CaretPosition::Coordinate(coordinate))
+            }
+        })
+        .collect()
+}
+
+/// Reads the item variation store of a `GDEF` table, which holds the variation deltas of
+/// ligature caret coordinates and `GPOS` values.
+///
+/// Returns `None` if the table predates version 1.3 or has no item variation store.
+pub fn read_item_variation_store(
+    gdef_data: &[u8],
+) -> Result<Option<ItemVariationStore<'_>>, ParseError> {
+    let scope = ReadScope::new(gdef_data);
+    let mut ctxt = scope.ctxt();
+    let major_version = ctxt.read_u16be()?;
+    ctxt.check(major_version == 1)?;
+    let minor_version = ctxt.read_u16be()?;
+    if minor_version < 3 {
+        return Ok(None);
+    }
+    let _glyph_classdef_offset = ctxt.read_u16be()?;
+    let _attach_list_offset = ctxt.read_u16be()?;
+    let _lig_caret_list_offset = ctxt.read_u16be()?;
+    let _mark_attach_classdef_offset = ctxt.read_u16be()?;
+    let _mark_glyph_sets_def_offset = ctxt.read_u16be()?;
+    let item_var_store_offset = usize::try_from(ctxt.read_u32be()?)?;
+    if item_var_store_offset == 0 {
+        return Ok(None);
+    }
+    scope
+        .offset(item_var_store_offset)
+        .read::<ItemVariationStore<'_>>()
+        .map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::write::{WriteBinary, WriteBuffer};
+    use crate::binary::{I16Be, U16Be, U32Be};
+    use crate::context::{Glyph, LookupFlag, MatchType};
+    use crate::font_data::FontData;
+    use crate::tables::FontTableProvider;
+    use crate::tag;
+    use crate::tests::read_fixture;
+
+    /// Builds a version 1.3 `GDEF` table with carets for glyph 10: a coordinate of 500, contour
+    /// point 7, and a coordinate of 1000 with a delta of 100 in a region peaking at 1.0 on the
+    /// only axis.
+    fn gdef_data() -> Vec<u8> {
+        let mut w = WriteBuffer::new();
+        // Header
+        U16Be::write(&mut w, 1u16).unwrap();
+        U16Be::write(&mut w, 3u16).unwrap();
+        U16Be::write(&mut w, 0u16).unwrap(); // glyph class def
+        U16Be::write(&mut w, 0u16).unwrap(); // attach list
+        U16Be::write(&mut w, 18u16).unwrap(); // lig caret list
+        U16Be::write(&mut w, 0u16).unwrap(); // mark attach class def
+        U16Be::write(&mut w, 0u16).unwrap(); // mark glyph sets def
+        U32Be::write(&mut w, 58u32).unwrap(); // item variation store
+
+        // Lig caret list at 18
+        U16Be::write(&mut w, 6u16).unwrap(); // coverage
+        U16Be::write(&mut w, 1u16).unwrap();
+        U16Be::write(&mut w, 12u16).unwrap(); // lig glyph
+                                              // Coverage at 24
+        U16Be::write(&mut w, 1u16).unwrap();
+        U16Be::write(&mut w, 1u16).unwrap();
+        U16Be::write(&mut w, 10u16).unwrap();
+        // Lig glyph at 30
+        U16Be::write(&mut w, 3u16).unwrap();
+        U16Be::write(&mut w, 8u16).unwrap();
+        U16Be::write(&mut w, 12u16).unwrap();
+        U16Be::write(&mut w, 16u16).unwrap();
+        // Caret values
+        U16Be::write(&mut w, 1u16).unwrap();
+        I16Be::write(&mut w, 500i16).unwrap();
+        U16Be::write(&mut w, 2u16).unwrap();
+        U16Be::write(&mut w, 7u16).unwrap();
+        U16Be::write(&mut w, 3u16).unwrap();
+        I16Be::write(&mut w, 1000i16).unwrap();
+        U16Be::write(&mut w, 6u16).unwrap(); // device
+                                             // Variation index table
+        U16Be::write(&mut w, 0u16).unwrap();
+        U16Be::write(&mut w, 0u16).unwrap();
+        U16Be::write(&mut w, 0x8000u16).unwrap();
+
+        // Item variation store at 58
+        U16Be::write(&mut w, 1u16).unwrap();
+        U32Be::write(&mut w, 12u32).unwrap(); // region list
+        U16Be::write(&mut w, 1u16).unwrap();
+        U32Be::write(&mut w, 22u32).unwrap(); // item variation data
+                                              // Region list at 70
+        U16Be::write(&mut w, 1u16).unwrap(); // axis count
+        U16Be::write(&mut w, 1u16).unwrap(); // region count
+        U16Be::write(&mut w, 0u16).unwrap(); // start 0.0
+        U16Be::write(&mut w, 0x4000u16).unwrap(); // peak 1.0
+        U16Be::write(&mut w, 0x4000u16).unwrap(); // end 1.0
+                                                  // Item variation data at 80, one item of one 16-bit delta
+        U16Be::write(&mut w, 1u16).unwrap();
+        U16Be::write(&mut w, 1u16).unwrap();
+        U16Be::write(&mut w, 1u16).unwrap();
+        U16Be::write(&mut w, 0u16).unwrap();
+        I16Be::write(&mut w, 100i16).unwrap();
+
+        w.into_inner()
+    }
+
+    #[test]
+    fn test_ligature_carets() {
+        let data = gdef_data();
+        let gdef = ReadScope::new(&data).read::<GDEFTable>().unwrap();
+        let store = read_item_variation_store(&data).unwrap();
+        let carets =
+            |coords: &[F2Dot14]| ligature_carets(Some(&gdef), store.as_ref(), 10, coords).unwrap();
+
+        assert_eq!(
+            carets(&[]),
+            vec![
+                CaretPosition::Coordinate(500),
+                CaretPosition::ContourPoint(7),
+                CaretPosition::Coordinate(1000)
+            ]
+        );
+        assert_eq!(
+            carets(&[F2Dot14::new(0x4000)])[2],
+            CaretPosition::Coordinate(1100)
+        );
+        assert_eq!(
+            carets(&[F2Dot14::new(0x2000)])[2],
+            CaretPosition::Coordinate(1050)
+        );
+        // Without the item variation store, or for glyphs without carets
+        assert_eq!(
+            ligature_carets(Some(&gdef), None, 10, &[F2Dot14::new(0x4000)]).unwrap()[2],
+            CaretPosition::Coordinate(1000)
+        );
+        assert!(ligature_carets(Some(&gdef), store.as_ref(), 11, &[])
+            .unwrap()
+            .is_empty());
+        assert!(ligature_carets(None, None, 10, &[]).unwrap().is_empty());
+    }
+
+    struct TestGlyph(u16);
+
+    impl Glyph for TestGlyph {
+        fn get_glyph_index(&self) -> u16 {
+            self.0
+        }
+    }
+
+    /// Builds a version 1.2 `GDEF` table where glyph 1 is a base, glyphs 2 and 3 are marks, and
+    /// the only mark glyph set contains glyph 2.
+    fn mark_glyph_sets_gdef_data() -> Vec<u8> {
+        let mut w = WriteBuffer::new();
+        // Header
+        U16Be::write(&mut w, 1u16).unwrap();
+        U16Be::write(&mut w, 2u16).unwrap();
+        U16Be::write(&mut w, 14u16).unwrap(); // glyph class def
+        U16Be::write(&mut w, 0u16).unwrap(); // attach list
+        U16Be::write(&mut w, 0u16).unwrap(); // lig caret list
+        U16Be::write(&mut w, 0u16).unwrap(); // mark attach class def
+        U16Be::write(&mut w, 30u16).unwrap(); // mark glyph sets def
+
+        // Glyph class def at 14
+        U16Be::write(&mut w, 2u16).unwrap();
+        U16Be::write(&mut w, 2u16).unwrap();
+        for &value in &[1u16, 1, 1, 2, 3, 3] {
+            U16Be::write(&mut w, value).unwrap();
+        }
+
+        // Mark glyph sets def at 30
+        U16Be::write(&mut w, 1u16).unwrap();
+        U16Be::write(&mut w, 1u16).unwrap();
+        U32Be::write(&mut w, 8u32).unwrap();
+        // Coverage at 38
+        U16Be::write(&mut w, 1u16).unwrap();
+        U16Be::write(&mut w, 1u16).unwrap();
+        U16Be::write(&mut w, 2u16).unwrap();
+
+        w.into_inner()
+    }
+
+    #[test]
+    fn test_glyph_classes() {
+        let data = mark_glyph_sets_gdef_data();
+        let gdef = ReadScope::new(&data).read::<GDEFTable>().unwrap();
+
+        assert_eq!(glyph_class_of(Some(&gdef), 1), Some(GlyphClass::Base));
+        assert_eq!(glyph_class_of(Some(&gdef), 3), Some(GlyphClass::Mark));
+        assert_eq!(glyph_class_of(Some(&gdef), 4), None);
+        assert_eq!(glyph_class_of(None, 1), None);
+        assert!(gdef_is_base(Some(&gdef), 1));
+        assert!(gdef_is_mark(Some(&gdef), 2));
+        assert!(!gdef_is_ligature(Some(&gdef), 2));
+        assert!(!gdef_is_component(Some(&gdef), 2));
+    }
+
+    #[test]
+    fn test_mark_filtering_set() {
+        let data = mark_glyph_sets_gdef_data();
+        let gdef = ReadScope::new(&data).read::<GDEFTable>().unwrap();
+
+        assert!(mark_glyph_set_contains(Some(&gdef), 0, 2));
+        assert!(!mark_glyph_set_contains(Some(&gdef), 0, 3));
+        assert!(!mark_glyph_set_contains(Some(&gdef), 1, 2));
+
+        // UseMarkFilteringSet skips marks outside the set but keeps other glyphs
+        let match_type = MatchType::from_lookup_flag(LookupFlag(0x0010), Some(0));
+        assert!(match_type.match_glyph(Some(&gdef), &TestGlyph(1)));
+        assert!(match_type.match_glyph(Some(&gdef), &TestGlyph(2)));
+        assert!(!match_type.match_glyph(Some(&gdef), &TestGlyph(3)));
+
+        // IgnoreMarks takes precedence over the mark filtering set
+        let match_type = MatchType::from_lookup_flag(LookupFlag(0x0018), Some(0));
+        assert!(!match_type.match_glyph(Some(&gdef), &TestGlyph(2)));
+
+        // The set is not used without the flag
+        let match_type = MatchType::from_lookup_flag(LookupFlag(0), Some(0));
+        assert!(match_type.match_glyph(Some(&gdef), &TestGlyph(3)));
+    }
+
+    #[test]
+    fn test_ligature_carets_font() {
+        let buffer = read_fixture("tests/fonts/opentype/Klei.otf");
+        let scope = ReadScope::new(&buffer);
+        let font_file = scope.read::<FontData<'_>>().unwrap();
+        let provider = font_file.table_provider(0).unwrap();
+        let gdef_data = provider.read_table_data(tag::GDEF).unwrap();
+        let gdef = ReadScope::new(&gdef_data).read::<GDEFTable>().unwrap();
+
+        assert!(read_item_variation_store(&gdef_data).unwrap().is_none());
+        assert_eq!(
+            ligature_carets(Some(&gdef), None, 763, &[]).unwrap(),
+            vec![CaretPosition::Coordinate(-4)]
+        );
+    }
+}
@@ -5,7 +5,7 @@
 use std::borrow::Cow;
 use std::collections::HashMap;
 
-use crate::binary::read::ReadScope;
+use crate::binary::read::{CheckIndex, ReadScope};
 use crate::error::ParseError;
 use crate::font::Encoding;
 use crate::macroman::macroman_to_char;
@@ -39,6 +39,32 @@ pub fn advance(
     }
 }
 
+/// Retrieve glyph side bearing.
+///
+/// Like `advance` this returns the left side bearing when `hhea` and `hmtx` are supplied, or the
+/// top side bearing when `vhea` and `vmtx` are supplied.
+pub fn side_bearing(
+    maxp: &MaxpTable,
+    hhea: &HheaTable,
+    hmtx_data: &[u8],
+    glyph: u16,
+) -> Result<i16, ParseError> {
+    let glyph = usize::from(glyph);
+    let num_glyphs = usize::from(maxp.num_glyphs);
+    let num_metrics = usize::from(hhea.num_h_metrics);
+    let hmtx = ReadScope::new(hmtx_data).read_dep::<HmtxTable<'_>>((num_glyphs, num_metrics))?;
+
+    if glyph > num_glyphs - 1 {
+        Ok(0)
+    } else if glyph < num_metrics {
+        Ok(hmtx.h_metrics.get_item(glyph).lsb)
+    } else {
+        hmtx.left_side_bearings
+            .check_index(glyph - num_metrics)
+            .map(|_| hmtx.left_side_bearings.get_item(glyph - num_metrics))
+    }
+}
+
 rental! {
     mod rentable {
         use super::*;
@@ -138,3 +164,109 @@ impl CmapMappings {
 fn macroman_to_unicode(ch: u32) -> Option<u32> {
     macroman_to_char(ch as u8).map(|ch| ch as u32)
 }
+
+/// Map a glyph name to a Unicode character.
+///
+/// Names of the form `uniXXXX` and `uXXXX[XX]` are supported along with the names of the
+/// ISOAdobe character set.
+pub fn glyph_name_to_unicode(name: &str) -> Option<u32> {
+    let from_hex = |hex: &str| {
+        if hex
+            .bytes()
+            .all(|b| b.is_ascii_hexdigit() && !b.is_ascii_lowercase())
+        {
+            u32::from_str_radix(hex, 16)
+                .ok()
+                .filter(|&ch| ch <= 0x10FFFF && !(0xD800u32..=0xDFFF).contains(&ch))
+        } else {
+            None
+        }
+    };
+    if name.len() == 7 && name.starts_with("uni") {
+        return from_hex(&name[3..]);
+    }
+    if (5..=7).contains(&name.len()) && name.starts_with('u') {
+        return from_hex(&name[1..]);
+    }
+
+    ISO_ADOBE_UNICODE
+        .iter()
+        .find(|(glyph_name, _)| *glyph_name == name)
+        .map(|&(_, ch)| u32::from(ch))
+}
+
+/// Unicode values for the glyph names of the ISOAdobe character set.
+#[rustfmt::skip]
+const ISO_ADOBE_UNICODE: [(&str, u16); 228] = [
+    ("space", 0x0020), ("exclam", 0x0021), ("quotedbl", 0x0022), ("numbersign", 0x0023),
+    ("dollar", 0x0024), ("percent", 0x0025), ("ampersand", 0x0026), ("quoteright", 0x2019),
+    ("parenleft", 0x0028), ("parenright", 0x0029), ("asterisk", 0x002A), ("plus", 0x002B),
+    ("comma", 0x002C), ("hyphen", 0x002D), ("period", 0x002E), ("slash", 0x002F),
+    ("zero", 0x0030), ("one", 0x0031), ("two", 0x0032), ("three", 0x0033), ("four", 0x0034),
+    ("five", 0x0035), ("six", 0x0036), ("seven", 0x0037), ("eight", 0x0038), ("nine", 0x0039),
+    ("colon", 0x003A), ("semicolon", 0x003B), ("less", 0x003C), ("equal", 0x003D),
+    ("greater", 0x003E), ("question", 0x003F), ("at", 0x0040),
+    ("A", 0x0041), ("B", 0x0042), ("C", 0x0043), ("D", 0x0044), ("E", 0x0045), ("F", 0x0046),
+    ("G", 0x0047), ("H", 0x0048), ("I", 0x0049), ("J", 0x004A), ("K", 0x004B), ("L", 0x004C),
+    ("M", 0x004D), ("N", 0x004E), ("O", 0x004F), ("P", 0x0050), ("Q", 0x0051), ("R", 0x0052),
+    ("S", 0x0053), ("T", 0x0054), ("U", 0x0055), ("V", 0x0056), ("W", 0x0057), ("X", 0x0058),
+    ("Y", 0x0059), ("Z", 0x005A),
+    ("bracketleft", 0x005B), ("backslash", 0x005C), ("bracketright", 0x005D),
+    ("asciicircum", 0x005E), ("underscore", 0x005F), ("quoteleft", 0x2018),
+    ("a", 0x0061), ("b", 0x0062), ("c", 0x0063), ("d", 0x0064), ("e", 0x0065), ("f", 0x0066),
+    ("g", 0x0067), ("h", 0x0068), ("i", 0x0069), ("j", 0x006A), ("k", 0x006B), ("l", 0x006C),
+    ("m", 0x006D), ("n", 0x006E), ("o", 0x006F), ("p", 0x0070), ("q", 0x0071), ("r", 0x0072),
+    ("s", 0x0073), ("t", 0x0074), ("u", 0x0075), ("v", 0x0076), ("w", 0x0077), ("x", 0x0078),
+    ("y", 0x0079), ("z", 0x007A),
+    ("braceleft", 0x007B), ("bar", 0x007C), ("braceright", 0x007D), ("asciitilde", 0x007E),
+    ("exclamdown", 0x00A1), ("cent", 0x00A2), ("sterling", 0x00A3), ("fraction", 0x2044),
+    ("yen", 0x00A5), ("florin", 0x0192), ("section", 0x00A7), ("currency", 0x00A4),
+    ("quotesingle", 0x0027), ("quotedblleft", 0x201C), ("guillemotleft", 0x00AB),
+    ("guilsinglleft", 0x2039), ("guilsinglright", 0x203A), ("fi", 0xFB01), ("fl", 0xFB02),
+    ("endash", 0x2013), ("dagger", 0x2020), ("daggerdbl", 0x2021), ("periodcentered", 0x00B7),
+    ("paragraph", 0x00B6), ("bullet", 0x2022), ("quotesinglbase", 0x201A),
+    ("quotedblbase", 0x201E), ("quotedblright", 0x201D), ("guillemotright", 0x00BB),
+    ("ellipsis", 0x2026), ("perthousand", 0x2030), ("questiondown", 0x00BF),
+    ("grave", 0x0060), ("acute", 0x00B4), ("circumflex", 0x02C6), ("tilde", 0x02DC),
+    ("macron", 0x00AF), ("breve", 0x02D8), ("dotaccent", 0x02D9), ("dieresis", 0x00A8),
+    ("ring", 0x02DA), ("cedilla", 0x00B8), ("hungarumlaut", 0x02DD), ("ogonek", 0x02DB),
+    ("caron", 0x02C7), ("emdash", 0x2014), ("AE", 0x00C6), ("ordfeminine", 0x00AA),
+    ("Lslash", 0x0141), ("Oslash", 0x00D8), ("OE", 0x0152), ("ordmasculine", 0x00BA),
+    ("ae", 0x00E6), ("dotlessi", 0x0131), ("lslash", 0x0142), ("oslash", 0x00F8),
+    ("oe", 0x0153), ("germandbls", 0x00DF), ("onesuperior", 0x00B9), ("logicalnot", 0x00AC),
+    ("mu", 0x00B5), ("trademark", 0x2122), ("Eth", 0x00D0), ("onehalf", 0x00BD),
+    ("plusminus", 0x00B1), ("Thorn", 0x00DE), ("onequarter", 0x00BC), ("divide", 0x00F7),
+    ("brokenbar", 0x00A6), ("degree", 0x00B0), ("thorn", 0x00FE), ("threequarters", 0x00BE),
+    ("twosuperior", 0x00B2), ("registered", 0x00AE), ("minus", 0x2212), ("eth", 0x00F0),
+    ("multiply", 0x00D7), ("threesuperior", 0x00B3), ("copyright", 0x00A9),
+    ("Aacute", 0x00C1), ("Acircumflex", 0x00C2), ("Adieresis", 0x00C4), ("Agrave", 0x00C0),
+    ("Aring", 0x00C5), ("Atilde", 0x00C3), ("Ccedilla", 0x00C7), ("Eacute", 0x00C9),
+    ("Ecircumflex", 0x00CA), ("Edieresis", 0x00CB), ("Egrave", 0x00C8), ("Iacute", 0x00CD),
+    ("Icircumflex", 0x00CE), ("Idieresis", 0x00CF), ("Igrave", 0x00CC), ("Ntilde", 0x00D1),
+    ("Oacute", 0x00D3), ("Ocircumflex", 0x00D4), ("Odieresis", 0x00D6), ("Ograve", 0x00D2),
+    ("Otilde", 0x00D5), ("Scaron", 0x0160), ("Uacute", 0x00DA), ("Ucircumflex", 0x00DB),
+    ("Udieresis", 0x00DC), ("Ugrave", 0x00D9), ("Yacute", 0x00DD), ("Ydieresis", 0x0178),
+    ("Zcaron", 0x017D), ("aacute", 0x00E1), ("acircumflex", 0x00E2), ("adieresis", 0x00E4),
+    ("agrave", 0x00E0), ("aring", 0x00E5), ("atilde", 0x00E3), ("ccedilla", 0x00E7),
+    ("eacute", 0x00E9), ("ecircumflex", 0x00EA), ("edieresis", 0x00EB), ("egrave", 0x00E8),
+    ("iacute", 0x00ED), ("icircumflex", 0x00EE), ("idieresis", 0x00EF), ("igrave", 0x00EC),
+    ("ntilde", 0x00F1), ("oacute", 0x00F3), ("ocircumflex", 0x00F4), ("odieresis", 0x00F6),
+    ("ograve", 0x00F2), ("otilde", 0x00F5), ("scaron", 0x0161), ("uacute", 0x00FA),
+    ("ucircumflex", 0x00FB), ("udieresis", 0x00FC), ("ugrave", 0x00F9), ("yacute", 0x00FD),
+    ("ydieresis", 0x00FF), ("zcaron", 0x017E),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glyph_name_to_unicode() {
+        assert_eq!(glyph_name_to_unicode("Aacute"), Some(0xC1));
+        assert_eq!(glyph_name_to_unicode("uni20AC"), Some(0x20AC));
+        assert_eq!(glyph_name_to_unicode("u1F600"), Some(0x1F600));
+        assert_eq!(glyph_name_to_unicode("uniD800"), None);
+        assert_eq!(glyph_name_to_unicode("uni20ac"), None);
+        assert_eq!(glyph_name_to_unicode("A.sc"), None);
+    }
+}
@@ -0,0 +1,245 @@
+//! Validation of glyph complexity against the limits of rasterizers.
+//!
+//! Rasterizers place limits on the number of contours and points in a glyph and on how deeply
+//! composite glyphs may be nested. A font that exceeds these limits is still valid, but glyphs may
+//! fail to render in some environments. `check_glyph_limits` reports the glyphs of a `glyf` table
+//! that exceed the limits of a `RasterizerProfile` so that generated fonts can be checked before
+//! they are deployed.
+
+use std::convert::TryFrom;
+
+use crate::error::ParseError;
+use crate::recompute::component_depth;
+use crate::tables::glyf::{GlyfTable, GlyphData};
+
+/// The glyph limits of a rasterizer.
+///
+/// Contour and point counts apply to the glyph after any composite glyphs have been expanded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RasterizerProfile {
+    pub name: &'static str,
+    pub max_contours: u16,
+    pub max_points: u16,
+    pub max_component_elements: u16,
+    pub max_component_depth: u16,
+}
+
+impl RasterizerProfile {
+    /// Conservative limits for the Windows GDI TrueType rasterizer.
+    pub const WINDOWS_GDI: RasterizerProfile = RasterizerProfile {
+        name: "Windows GDI",
+        max_contours: 8192,
+        max_points: 16384,
+        max_component_elements: 255,
+        max_component_depth: 8,
+    };
+
+    /// Limits for fonts passed through the OpenType Sanitizer used by web browsers.
+    pub const OTS: RasterizerProfile = RasterizerProfile {
+        name: "OTS",
+        max_contours: 32767,
+        max_points: 65535,
+        max_component_elements: 65535,
+        max_component_depth: 16,
+    };
+
+    /// Limits for rasterizers on memory constrained embedded devices.
+    pub const EMBEDDED: RasterizerProfile = RasterizerProfile {
+        name: "embedded",
+        max_contours: 256,
+        max_points: 1024,
+        max_component_elements: 32,
+        max_component_depth: 2,
+    };
+}
+
+/// The limit of a `RasterizerProfile` that a glyph exceeds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlyphLimit {
+    Contours,
+    Points,
+    ComponentElements,
+    ComponentDepth,
+}
+
+/// A glyph that exceeds a limit of a `RasterizerProfile`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LimitViolation {
+    pub glyph_index: u16,
+    pub limit: GlyphLimit,
+    /// The value of the glyph.
+    pub value: u32,
+    /// The maximum value permitted by the profile.
+    pub maximum: u16,
+}
+
+/// Check the glyphs of `glyf` against the limits of `profile`.
+///
+/// Returns a violation for each limit exceeded by each glyph, in glyph index order. An empty
+/// `Vec` indicates that all glyphs are within the limits of the profile.
+pub fn check_glyph_limits(
+    glyf: &GlyfTable<'_>,
+    profile: &RasterizerProfile,
+) -> Result<Vec<LimitViolation>, ParseError> {
+    let num_glyphs = u16::try_from(glyf.records.len())?;
+    let mut violations = Vec::new();
+
+    for glyph_index in 0..num_glyphs {
+        let glyph = match glyf.glyph(glyph_index)? {
+            Some(glyph) => glyph,
+            None => continue,
+        };
+        let mut check = |limit, value: usize, maximum: u16| {
+            if value > usize::from(maximum) {
+                violations.push(LimitViolation {
+                    glyph_index,
+                    limit,
+                    value: u32::try_from(value).unwrap_or(std::u32::MAX),
+                    maximum,
+                });
+            }
+        };
+
+        let points = glyf.glyph_points(glyph_index)?;
+        check(
+            GlyphLimit::Contours,
+            points.end_pts_of_contours.len(),
+            profile.max_contours,
+        );
+        check(GlyphLimit::Points, points.points.len(), profile.max_points);
+
+        if let GlyphData::Composite { glyphs, .. } = &glyph.data {
+            let depth = component_depth(glyf, glyph_index, 0)?;
+            check(
+                GlyphLimit::ComponentElements,
+                glyphs.len(),
+                profile.max_component_elements,
+            );
+            check(
+                GlyphLimit::ComponentDepth,
+                usize::from(depth),
+                profile.max_component_depth,
+            );
+        }
+    }
+
+    Ok(violations)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::*;
+    use crate::tables::glyf::{
+        BoundingBox, CompositeGlyph, CompositeGlyphArgument, CompositeGlyphFlag, GlyfRecord, Glyph,
+        Point, SimpleGlyph, SimpleGlyphFlag,
+    };
+
+    const BOUNDING_BOX: BoundingBox = BoundingBox {
+        x_min: 0,
+        x_max: 0,
+        y_min: 0,
+        y_max: 0,
+    };
+
+    fn square_glyph() -> Glyph<'static> {
+        Glyph {
+            number_of_contours: 1,
+            bounding_box: BOUNDING_BOX,
+            data: GlyphData::Simple(SimpleGlyph {
+                end_pts_of_contours: vec![3],
+                instructions: vec![],
+                flags: vec![SimpleGlyphFlag::ON_CURVE_POINT; 4],
+                coordinates: vec![Point(0, 0), Point(0, 100), Point(100, 100), Point(100, 0)],
+            }),
+        }
+    }
+
+    fn composite_glyph(component_indices: &[u16]) -> Glyph<'static> {
+        let glyphs = component_indices
+            .iter()
+            .map(|&glyph_index| CompositeGlyph {
+                flags: CompositeGlyphFlag::ARG_1_AND_2_ARE_WORDS
+                    | CompositeGlyphFlag::ARGS_ARE_XY_VALUES,
+                glyph_index,
+                argument1: CompositeGlyphArgument::I16(0),
+                argument2: CompositeGlyphArgument::I16(0),
+                scale: None,
+            })
+            .collect();
+        Glyph {
+            number_of_contours: -1,
+            bounding_box: BOUNDING_BOX,
+            data: GlyphData::Composite {
+                glyphs,
+                instructions: Cow::Borrowed(&[]),
+            },
+        }
+    }
+
+    fn glyf_table() -> GlyfTable<'static> {
+        GlyfTable {
+            records: vec![
+                GlyfRecord::Empty,
+                GlyfRecord::Parsed(square_glyph()),
+                // Three squares: 3 contours, 12 points, depth 1
+                GlyfRecord::Parsed(composite_glyph(&[1, 1, 1])),
+                // Two of the above: 6 contours, 24 points, depth 2
+                GlyfRecord::Parsed(composite_glyph(&[2, 2])),
+                // Depth 3
+                GlyfRecord::Parsed(composite_glyph(&[3])),
+            ],
+        }
+    }
+
+    #[test]
+    fn within_limits() {
+        let glyf = glyf_table();
+        for profile in &[RasterizerProfile::WINDOWS_GDI, RasterizerProfile::OTS] {
+            assert_eq!(check_glyph_limits(&glyf, profile).unwrap(), vec![]);
+        }
+    }
+
+    #[test]
+    fn component_depth_exceeded() {
+        let glyf = glyf_table();
+        let violations = check_glyph_limits(&glyf, &RasterizerProfile::EMBEDDED).unwrap();
+        assert_eq!(
+            violations,
+            vec![LimitViolation {
+                glyph_index: 4,
+                limit: GlyphLimit::ComponentDepth,
+                value: 3,
+                maximum: 2,
+            }]
+        );
+    }
+
+    #[test]
+    fn custom_profile() {
+        let glyf = glyf_table();
+        let profile = RasterizerProfile {
+            name: "custom",
+            max_contours: 4,
+            max_points: 12,
+            max_component_elements: 2,
+            max_component_depth: 8,
+        };
+        let violations = check_glyph_limits(&glyf, &profile).unwrap();
+        let summary = violations
+            .iter()
+            .map(|violation| (violation.glyph_index, violation.limit, violation.value))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                (2, GlyphLimit::ComponentElements, 3),
+                (3, GlyphLimit::Contours, 6),
+                (3, GlyphLimit::Points, 24),
+                (4, GlyphLimit::Contours, 6),
+                (4, GlyphLimit::Points, 24),
+            ]
+        );
+    }
+}
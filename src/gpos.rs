@@ -9,20 +9,33 @@
 use crate::context::{ContextLookupHelper, Glyph, MatchType};
 use crate::error::ParseError;
 use crate::gdef::gdef_is_mark;
-use crate::gsub::RawGlyph;
+use crate::gsub::{lang_tag_key, RawGlyph, StretchPart};
 use crate::layout::{
-    chain_context_lookup_info, context_lookup_info, Adjust, Anchor, ChainContextLookup,
-    ContextLookup, CursivePos, GDEFTable, LangSys, LayoutCache, LayoutTable, LookupList,
-    MarkBasePos, MarkLigPos, PairPos, PosLookup, SinglePos, ValueRecord, GPOS,
+    add_feature_lookups, chain_context_lookup_info, context_lookup_info, Adjust, Anchor,
+    ChainContextLookup, ContextLookup, CursivePos, Device, GDEFTable, LangSys, LayoutCache,
+    LayoutTable, LazySubtables, MarkBasePos, MarkLigPos, PairPos, PosLookup, SinglePos,
+    ValueRecord, GPOS,
 };
 use crate::scripts;
-use crate::scripts::ScriptType;
+use crate::scripts::{DefaultFeatures, ScriptType};
+use crate::tables::kern::KernTable;
+use crate::tables::kerx::KerxTable;
+use crate::tables::vvar::ItemVariationStore;
+use crate::tables::F2Dot14;
 use crate::tag;
+use crate::trace::{self, TraceEvent};
+
+use std::collections::hash_map::Entry;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
 
 use unicode_general_category::GeneralCategory;
 
 type PosContext<'a> = ContextLookupHelper<'a, GPOS>;
 
+/// Positioning features that are only applied when requested.
+const OPTIONAL_FEATURES: &[u32] = &[tag::PALT];
+
 /// Apply glyph positioning rules to glyph `Info`.
 pub fn apply(
     gpos_cache: &LayoutCache<GPOS>,
@@ -32,85 +45,352 @@ pub fn apply(
     opt_lang_tag: Option<u32>,
     infos: &mut [Info],
 ) -> Result<(), ParseError> {
-    let gpos_table = &gpos_cache.layout_table;
+    apply_with_default_features(
+        gpos_cache,
+        opt_gdef_table,
+        &DefaultFeatures::new(),
+        kerning,
+        script_tag,
+        opt_lang_tag,
+        infos,
+    )
+}
+
+/// Apply glyph positioning rules to glyph `Info`, using the positioning features of
+/// `default_features` for `script_tag` in place of the built-in ones.
+pub fn apply_with_default_features(
+    gpos_cache: &LayoutCache<GPOS>,
+    opt_gdef_table: Option<&GDEFTable>,
+    default_features: &DefaultFeatures,
+    kerning: bool,
+    script_tag: u32,
+    opt_lang_tag: Option<u32>,
+    infos: &mut [Info],
+) -> Result<(), ParseError> {
+    let plan = GposPlan::with_default_features(
+        gpos_cache,
+        default_features,
+        kerning,
+        script_tag,
+        opt_lang_tag,
+        &[],
+    )?;
+    apply_plan(
+        gpos_cache,
+        opt_gdef_table,
+        &plan,
+        &DeviceContext::default(),
+        infos,
+    )
+}
+
+/// The lookups applied by `apply` and `apply_optional_features` for a script and language.
+///
+/// Building a plan resolves the features against the language system of the font once, so that
+/// it can be reused to position many runs of text with `apply_plan`. A plan is only valid for the
+/// `gpos_cache` it was built from.
+#[derive(Debug)]
+pub struct GposPlan {
+    script_tag: u32,
+    opt_lang_tag: Option<u32>,
+    /// The lookups of the default features, in the order they're applied. Empty for scripts
+    /// with a complex shaper, which resolve their lookups per stage.
+    lookups: Vec<usize>,
+    /// The default feature tags, resolved per stage by scripts with a complex shaper.
+    default_tags: Vec<u32>,
+    /// The lookups of the requested optional features, applied after `lookups`.
+    optional_lookups: Vec<usize>,
+}
 
-    if ScriptType::from(script_tag) == ScriptType::Indic {
-        return scripts::indic::gpos_apply_indic(
+impl GposPlan {
+    /// Build a plan for `script_tag` and `opt_lang_tag`.
+    ///
+    /// `kerning` and `feature_tags` are interpreted as by `apply` and `apply_optional_features`
+    /// respectively.
+    pub fn new(
+        gpos_cache: &LayoutCache<GPOS>,
+        kerning: bool,
+        script_tag: u32,
+        opt_lang_tag: Option<u32>,
+        feature_tags: &[u32],
+    ) -> Result<GposPlan, ParseError> {
+        GposPlan::with_default_features(
             gpos_cache,
-            &gpos_table,
-            opt_gdef_table,
+            &DefaultFeatures::new(),
+            kerning,
             script_tag,
             opt_lang_tag,
-            infos,
-        );
+            feature_tags,
+        )
     }
 
-    match gpos_table.find_script_or_default(script_tag)? {
-        None => Ok(()),
-        Some(script) => match script.find_langsys_or_default(opt_lang_tag)? {
-            None => Ok(()),
-            Some(langsys) => match ScriptType::from(script_tag) {
-                ScriptType::Arabic | ScriptType::Syriac => apply_features(
-                    &gpos_cache,
-                    &gpos_table,
+    /// Build a plan like `new`, applying the positioning features of `default_features` for
+    /// `script_tag` instead of the built-in ones.
+    pub fn with_default_features(
+        gpos_cache: &LayoutCache<GPOS>,
+        default_features: &DefaultFeatures,
+        kerning: bool,
+        script_tag: u32,
+        opt_lang_tag: Option<u32>,
+        feature_tags: &[u32],
+    ) -> Result<GposPlan, ParseError> {
+        let script_type = ScriptType::from(script_tag);
+        // Kerning can only be disabled for scripts that use the default shaper
+        let optional_kerning = script_type == ScriptType::Default;
+        let default_tags = default_features
+            .get(script_tag)
+            .gpos
+            .into_iter()
+            .filter(|&feature_tag| kerning || !optional_kerning || feature_tag != tag::KERN)
+            .collect::<Vec<_>>();
+        let mut plan = GposPlan {
+            script_tag,
+            opt_lang_tag,
+            lookups: Vec::new(),
+            default_tags: Vec::new(),
+            optional_lookups: Vec::new(),
+        };
+        if script_type == ScriptType::Indic || script_type == ScriptType::Myanmar {
+            plan.default_tags = default_tags;
+        } else {
+            plan.lookups =
+                cached_lookup_indices(gpos_cache, script_tag, opt_lang_tag, &default_tags)?;
+        }
+        let optional_tags = feature_tags
+            .iter()
+            .copied()
+            .filter(|feature_tag| OPTIONAL_FEATURES.contains(feature_tag))
+            .collect::<Vec<_>>();
+        if !optional_tags.is_empty() {
+            plan.optional_lookups =
+                cached_lookup_indices(gpos_cache, script_tag, opt_lang_tag, &optional_tags)?;
+        }
+        Ok(plan)
+    }
+}
+
+/// Perform glyph positioning according to a plan built with `GposPlan::new`.
+///
+/// Values are adjusted by their device and variation index tables for `device_context`.
+pub fn apply_plan(
+    gpos_cache: &LayoutCache<GPOS>,
+    opt_gdef_table: Option<&GDEFTable>,
+    plan: &GposPlan,
+    device_context: &DeviceContext<'_>,
+    infos: &mut [Info],
+) -> Result<(), ParseError> {
+    let gpos_table = &gpos_cache.layout_table;
+    match ScriptType::from(plan.script_tag) {
+        ScriptType::Indic => scripts::indic::gpos_apply_indic(
+            gpos_cache,
+            opt_gdef_table,
+            plan.script_tag,
+            plan.opt_lang_tag,
+            &plan.default_tags,
+            device_context,
+            infos,
+        )?,
+        ScriptType::Myanmar => scripts::myanmar::gpos_apply_myanmar(
+            gpos_cache,
+            &gpos_table,
+            opt_gdef_table,
+            plan.opt_lang_tag,
+            &plan.default_tags,
+            device_context,
+            infos,
+        )?,
+        _ => {
+            for &lookup_index in &plan.lookups {
+                gpos_apply_lookup(
+                    gpos_cache,
+                    gpos_table,
                     opt_gdef_table,
-                    &langsys,
-                    &[tag::CURS, tag::KERN, tag::MARK, tag::MKMK],
+                    device_context,
+                    lookup_index,
                     infos,
-                ),
-                ScriptType::Default => {
-                    if kerning {
-                        apply_features(
-                            &gpos_cache,
-                            &gpos_table,
-                            opt_gdef_table,
-                            &langsys,
-                            &[tag::DIST, tag::KERN, tag::MARK, tag::MKMK],
-                            infos,
-                        )
-                    } else {
-                        apply_features(
-                            &gpos_cache,
-                            &gpos_table,
-                            opt_gdef_table,
-                            &langsys,
-                            &[tag::DIST, tag::MARK, tag::MKMK],
-                            infos,
-                        )
-                    }
-                }
-                ScriptType::Indic => Ok(()),
-            },
-        },
+                )?;
+            }
+        }
+    }
+    for &lookup_index in &plan.optional_lookups {
+        gpos_apply_lookup(
+            gpos_cache,
+            gpos_table,
+            opt_gdef_table,
+            device_context,
+            lookup_index,
+            infos,
+        )?;
     }
+    Ok(())
+}
+
+/// Apply the optional positioning features, such as `palt`, present in `feature_tags`.
+///
+/// These features are not applied by `apply` as they are only enabled when requested. Other tags
+/// in `feature_tags` are ignored.
+pub fn apply_optional_features(
+    gpos_cache: &LayoutCache<GPOS>,
+    opt_gdef_table: Option<&GDEFTable>,
+    script_tag: u32,
+    opt_lang_tag: Option<u32>,
+    feature_tags: &[u32],
+    infos: &mut [Info],
+) -> Result<(), ParseError> {
+    let feature_tags = feature_tags
+        .iter()
+        .copied()
+        .filter(|feature_tag| OPTIONAL_FEATURES.contains(feature_tag))
+        .collect::<Vec<_>>();
+    if feature_tags.is_empty() {
+        return Ok(());
+    }
+
+    apply_script_features(
+        gpos_cache,
+        opt_gdef_table,
+        script_tag,
+        opt_lang_tag,
+        &feature_tags,
+        &DeviceContext::default(),
+        infos,
+    )
 }
 
 /// Apply glyph positioning using specified OpenType features.
 ///
 /// Generally use `gpos::apply`, which will enable features based on script and language. Use
 /// this method if you need more low-level control over the enabled features.
+///
+/// The lookups of the features are applied in lookup list order, and a lookup referenced by more
+/// than one feature is only applied once.
 pub fn apply_features(
     gpos_cache: &LayoutCache<GPOS>,
     gpos_table: &LayoutTable<GPOS>,
     opt_gdef_table: Option<&GDEFTable>,
     langsys: &LangSys,
     feature_tags: &[u32],
+    device_context: &DeviceContext<'_>,
     infos: &mut [Info],
 ) -> Result<(), ParseError> {
-    for feature_tag in feature_tags {
-        if let Some(feature_table) = gpos_table.find_langsys_feature(&langsys, *feature_tag)? {
-            for lookup_index in &feature_table.lookup_indices {
-                gpos_apply_lookup(
-                    gpos_cache,
-                    gpos_table,
-                    opt_gdef_table,
-                    usize::from(*lookup_index),
-                    infos,
-                )?;
+    for (lookup_index, _feature_tag) in build_lookups(gpos_table, langsys, feature_tags)? {
+        gpos_apply_lookup(
+            gpos_cache,
+            gpos_table,
+            opt_gdef_table,
+            device_context,
+            lookup_index,
+            infos,
+        )?;
+    }
+    Ok(())
+}
+
+/// Apply the lookups of `feature_tags` in the supplied script and language.
+///
+/// This is equivalent to `apply_features` with the language system found for `script_tag` and
+/// `opt_lang_tag`, but the lookups are cached in `gpos_cache` for subsequent calls.
+pub(crate) fn apply_script_features(
+    gpos_cache: &LayoutCache<GPOS>,
+    opt_gdef_table: Option<&GDEFTable>,
+    script_tag: u32,
+    opt_lang_tag: Option<u32>,
+    feature_tags: &[u32],
+    device_context: &DeviceContext<'_>,
+    infos: &mut [Info],
+) -> Result<(), ParseError> {
+    let gpos_table = &gpos_cache.layout_table;
+    for lookup_index in cached_lookup_indices(gpos_cache, script_tag, opt_lang_tag, feature_tags)? {
+        gpos_apply_lookup(
+            gpos_cache,
+            gpos_table,
+            opt_gdef_table,
+            device_context,
+            lookup_index,
+            infos,
+        )?;
+    }
+    Ok(())
+}
+
+/// Returns the lookups applied for `feature_tags` in the supplied script and language.
+///
+/// Each entry is a lookup index paired with the tag of the feature that enabled it. Entries are
+/// in the order they are applied, which is ascending lookup index, and each lookup appears once
+/// regardless of how many features reference it.
+pub fn resolved_lookups(
+    gpos_cache: &LayoutCache<GPOS>,
+    script_tag: u32,
+    opt_lang_tag: Option<u32>,
+    feature_tags: &[u32],
+) -> Result<Vec<(usize, u32)>, ParseError> {
+    let index = get_lookups_cache_index(gpos_cache, script_tag, opt_lang_tag, feature_tags)?;
+    let lookups = gpos_cache.cached_lookups.read().unwrap()[index].clone();
+    Ok(lookups)
+}
+
+fn cached_lookup_indices(
+    gpos_cache: &LayoutCache<GPOS>,
+    script_tag: u32,
+    opt_lang_tag: Option<u32>,
+    feature_tags: &[u32],
+) -> Result<Vec<usize>, ParseError> {
+    let index = get_lookups_cache_index(gpos_cache, script_tag, opt_lang_tag, feature_tags)?;
+    let cached_lookups = gpos_cache.cached_lookups.read().unwrap();
+    Ok(cached_lookups[index]
+        .iter()
+        .map(|&(lookup_index, _feature_tag)| lookup_index)
+        .collect())
+}
+
+fn get_lookups_cache_index(
+    gpos_cache: &LayoutCache<GPOS>,
+    script_tag: u32,
+    opt_lang_tag: Option<u32>,
+    feature_tags: &[u32],
+) -> Result<usize, ParseError> {
+    let key = (
+        script_tag,
+        lang_tag_key(opt_lang_tag),
+        feature_tags.to_vec(),
+    );
+    if let Some(&index) = gpos_cache.feature_tags_index.read().unwrap().get(&key) {
+        return Ok(index);
+    }
+    let index = match gpos_cache.feature_tags_index.write().unwrap().entry(key) {
+        Entry::Occupied(entry) => *entry.get(),
+        Entry::Vacant(entry) => {
+            let gpos_table = &gpos_cache.layout_table;
+            if let Some(script) = gpos_table.find_script_or_default(script_tag)? {
+                if let Some(langsys) = script.find_langsys_or_default(opt_lang_tag)? {
+                    let lookups = build_lookups(gpos_table, langsys, feature_tags)?;
+                    let mut cached_lookups = gpos_cache.cached_lookups.write().unwrap();
+                    let index = cached_lookups.len();
+                    cached_lookups.push(lookups);
+                    *entry.insert(index)
+                } else {
+                    *entry.insert(0)
+                }
+            } else {
+                *entry.insert(0)
             }
         }
+    };
+    Ok(index)
+}
+
+/// Returns the lookups of `feature_tags` in ascending lookup index order, each paired with the
+/// tag of the first feature that references it.
+fn build_lookups(
+    gpos_table: &LayoutTable<GPOS>,
+    langsys: &LangSys,
+    feature_tags: &[u32],
+) -> Result<Vec<(usize, u32)>, ParseError> {
+    let mut lookups = BTreeMap::new();
+    for &feature_tag in feature_tags {
+        add_feature_lookups(gpos_table, langsys, feature_tag, &mut lookups)?;
     }
-    Ok(())
+    Ok(lookups.into_iter().collect())
 }
 
 /// Apply basic mark processing when there is no `gpos` table available.
@@ -132,6 +412,44 @@ pub fn apply_fallback(infos: &mut [Info]) {
     }
 }
 
+/// Apply kerning from the legacy `kern` table when there is no `gpos` table available.
+///
+/// Call this after `apply_fallback`. Marks are skipped, so a pair of base glyphs is kerned even
+/// when there are marks between them.
+pub fn apply_kern_table(kern_table: &KernTable<'_>, infos: &mut [Info]) -> Result<(), ParseError> {
+    apply_kerning_adjustments(infos, |glyphs| kern_table.kerning_adjustments(glyphs))
+}
+
+/// Apply kerning from the Apple `kerx` table when there is no `gpos` table available.
+///
+/// Call this after `apply_fallback`. Marks are skipped in the same way as `apply_kern_table`.
+pub fn apply_kerx_table(kerx_table: &KerxTable<'_>, infos: &mut [Info]) -> Result<(), ParseError> {
+    apply_kerning_adjustments(infos, |glyphs| kerx_table.kerning_adjustments(glyphs))
+}
+
+fn apply_kerning_adjustments<F>(
+    infos: &mut [Info],
+    kerning_adjustments: F,
+) -> Result<(), ParseError>
+where
+    F: FnOnce(&[u16]) -> Result<Vec<i16>, ParseError>,
+{
+    let bases = (0..infos.len())
+        .filter(|&i| !infos[i].is_mark)
+        .collect::<Vec<_>>();
+    let glyphs = bases
+        .iter()
+        .map(|&i| infos[i].glyph.glyph_index)
+        .collect::<Vec<_>>();
+    let adjustments = kerning_adjustments(&glyphs)?;
+    // An adjustment moves a glyph along with the glyphs after it, so it is added to the advance of
+    // the base before it. An adjustment of the first base has nothing to move it against.
+    for (pair, &adjustment) in bases.windows(2).zip(adjustments.iter().skip(1)) {
+        infos[pair[0]].kerning = infos[pair[0]].kerning.saturating_add(adjustment);
+    }
+    Ok(())
+}
+
 fn unicodes_are_marks(unicodes: &[char]) -> bool {
     unicodes
         .iter()
@@ -140,20 +458,527 @@ fn unicodes_are_marks(unicodes: &[char]) -> bool {
         .all(|cat| cat == GeneralCategory::NonspacingMark)
 }
 
+fn unicodes_are_letters_or_marks(unicodes: &[char]) -> bool {
+    !unicodes.is_empty()
+        && unicodes
+            .iter()
+            .copied()
+            .map(unicode_general_category::get_general_category)
+            .all(|cat| match cat {
+                GeneralCategory::UppercaseLetter
+                | GeneralCategory::LowercaseLetter
+                | GeneralCategory::TitlecaseLetter
+                | GeneralCategory::ModifierLetter
+                | GeneralCategory::OtherLetter
+                | GeneralCategory::NonspacingMark
+                | GeneralCategory::SpacingMark
+                | GeneralCategory::EnclosingMark => true,
+                _ => false,
+            })
+}
+
+/// How glyphs decomposed by the `stch` feature are handled after positioning.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StretchMode {
+    /// Repeat the repeating parts so that each decomposed glyph spans the text that follows it.
+    Repeat,
+    /// Leave a single copy of each part in place, marked by `RawGlyph.stretch`, so that a
+    /// justification engine can decide how many repeats to insert.
+    Defer,
+}
+
+impl Default for StretchMode {
+    fn default() -> Self {
+        StretchMode::Repeat
+    }
+}
+
+/// Move the anchors of attachments that reference a contour point to the position of that point.
+///
+/// Anchors in format 2 name a point of the glyph outline as well as giving its coordinates, so
+/// that marks follow the outline when it is hinted or varied. The `point` function supplies the
+/// coordinates of a contour point of a glyph index, or `None` if the glyph has no such point, in
+/// which case the coordinates of the anchor are used.
+pub fn resolve_anchor_points(
+    infos: &mut [Info],
+    mut point: impl FnMut(u16, u16) -> Result<Option<(i16, i16)>, ParseError>,
+) -> Result<(), ParseError> {
+    for i in 0..infos.len() {
+        let base_glyph = match infos[i].attachment {
+            Attachment::MarkAnchor(j, _, _) | Attachment::CursiveAnchor(j, _, _)
+                if j < infos.len() =>
+            {
+                infos[j].glyph.glyph_index
+            }
+            _ => continue,
+        };
+        let glyph = infos[i].glyph.glyph_index;
+        // The first anchor belongs to the glyph attached to, the second to this glyph
+        match infos[i].attachment {
+            Attachment::MarkAnchor(_, ref mut anchor1, ref mut anchor2)
+            | Attachment::CursiveAnchor(_, ref mut anchor1, ref mut anchor2) => {
+                resolve_anchor_point(anchor1, base_glyph, &mut point)?;
+                resolve_anchor_point(anchor2, glyph, &mut point)?;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn resolve_anchor_point(
+    anchor: &mut Anchor,
+    glyph: u16,
+    point: &mut impl FnMut(u16, u16) -> Result<Option<(i16, i16)>, ParseError>,
+) -> Result<(), ParseError> {
+    if let Some(point_index) = anchor.opt_anchor_point {
+        if let Some((x, y)) = point(glyph, point_index)? {
+            anchor.x = x;
+            anchor.y = y;
+        }
+    }
+    Ok(())
+}
+
+/// Scale the advances of synthesized small capitals, which have `small_caps` set, by `x_scale`.
+///
+/// The change in advance is applied as kerning, with any kerning of the glyph scaled along with
+/// its advance. The `advance` function supplies the horizontal advance of a glyph index.
+pub fn apply_small_caps_scale(
+    infos: &mut [Info],
+    x_scale: f32,
+    advance: impl Fn(u16) -> Option<u16>,
+) {
+    for info in infos.iter_mut() {
+        if info.glyph.small_caps && !info.is_mark {
+            let advance = f32::from(advance(info.glyph.glyph_index).unwrap_or(0));
+            let scaled = (advance + f32::from(info.kerning)) * x_scale;
+            info.kerning = (scaled - advance).round() as i16;
+        }
+    }
+}
+
+/// Repeat the repeating parts of glyphs decomposed by the `stch` feature.
+///
+/// Each run of decomposed components is widened to cover the letters and marks that follow it,
+/// such as the text abbreviated by the Syriac Abbreviation Mark. The `advance` function supplies
+/// the horizontal advance of a glyph index. Where the repeats don't divide the width exactly an
+/// extra repeat is added and the repeats overlapped using negative kerning.
+///
+/// Attachment indices are updated to account for the inserted glyphs.
+pub fn apply_stretch(infos: &mut Vec<Info>, advance: impl Fn(u16) -> Option<u16>) {
+    if infos.iter().all(|info| info.glyph.stretch.is_none()) {
+        return;
+    }
+    let advance = |info: &Info| i32::from(advance(info.glyph.glyph_index).unwrap_or(0));
+
+    let mut copies = vec![0; infos.len()];
+    let mut overlaps = vec![0; infos.len()];
+    let mut i = 0;
+    while i < infos.len() {
+        if infos[i].glyph.stretch.is_none() {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < infos.len() && infos[i].glyph.stretch.is_some() {
+            i += 1;
+        }
+
+        let mut w_fixed = 0;
+        let mut w_repeating = 0;
+        let mut n_repeating = 0;
+        for info in &infos[start..i] {
+            match info.glyph.stretch {
+                Some(StretchPart::Fixed) => w_fixed += advance(info),
+                Some(StretchPart::Repeating) => {
+                    w_repeating += advance(info);
+                    n_repeating += 1;
+                }
+                None => {}
+            }
+        }
+        let w_total: i32 = infos[i..]
+            .iter()
+            .take_while(|info| unicodes_are_letters_or_marks(&info.glyph.unicodes))
+            .map(advance)
+            .sum();
+
+        let (n_copies, overlap) = stretch_copies(w_total - w_fixed, w_repeating, n_repeating);
+        for j in start..i {
+            if infos[j].glyph.stretch == Some(StretchPart::Repeating) {
+                copies[j] = n_copies;
+                overlaps[j] = i16::try_from(overlap).unwrap_or(std::i16::MAX);
+            }
+        }
+    }
+
+    let mut new_indices = Vec::with_capacity(infos.len());
+    let mut len = 0;
+    for &n_copies in &copies {
+        new_indices.push(len);
+        len += 1 + n_copies;
+    }
+    if len == infos.len() {
+        return;
+    }
+
+    let old_infos = std::mem::replace(infos, Vec::with_capacity(len));
+    for ((mut info, n_copies), overlap) in old_infos.into_iter().zip(copies).zip(overlaps) {
+        match info.attachment {
+            Attachment::MarkAnchor(ref mut index, _, _)
+            | Attachment::MarkOverprint(ref mut index)
+            | Attachment::CursiveAnchor(ref mut index, _, _) => *index = new_indices[*index],
+            Attachment::None => {}
+        }
+        let duplicates = (0..n_copies)
+            .map(|_| Info {
+                glyph: info.glyph.clone(),
+                kerning: info.kerning.saturating_sub(overlap),
+                placement: Placement::None,
+                attachment: Attachment::None,
+                is_mark: info.is_mark,
+                cursive_right_to_left: false,
+            })
+            .collect::<Vec<_>>();
+        infos.push(info);
+        infos.extend(duplicates);
+    }
+}
+
+/// Returns the number of extra copies of each repeating part, and the overlap between copies,
+/// required for repeating parts totalling `w_repeating` to cover `w_remaining`.
+fn stretch_copies(w_remaining: i32, w_repeating: i32, n_repeating: i32) -> (usize, i32) {
+    if w_repeating <= 0 || n_repeating <= 0 {
+        return (0, 0);
+    }
+
+    let mut n_copies = 0;
+    if w_remaining > w_repeating {
+        n_copies = w_remaining / w_repeating - 1;
+    }
+
+    // See if the fit can be improved by adding an extra repeat and overlapping the repeats
+    let mut overlap = 0;
+    let shortfall = w_remaining - w_repeating * (n_copies + 1);
+    if shortfall > 0 {
+        n_copies += 1;
+        let excess = (n_copies + 1) * w_repeating - w_remaining;
+        if excess > 0 {
+            overlap = excess / (n_copies * n_repeating);
+        }
+    }
+
+    (n_copies as usize, overlap)
+}
+
+/// The direction of the text being positioned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextDirection {
+    LeftToRight,
+    RightToLeft,
+}
+
+/// The resolved position of a glyph, in font units.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GlyphPosition {
+    /// The distance to move the pen after drawing the glyph.
+    pub x_advance: i32,
+    /// The vertical distance to move the pen after drawing the glyph. This is zero for horizontal
+    /// text, and negative for vertical text as the pen moves down the page.
+    pub y_advance: i32,
+    /// The horizontal offset of the glyph from the pen position.
+    pub x_offset: i32,
+    /// The vertical offset of the glyph from the baseline.
+    pub y_offset: i32,
+}
+
+/// Resolve the kerning, placements, and attachments of positioned glyphs into advances and
+/// offsets.
+///
+/// Cursive attachments adjust the advances of the joined glyphs so that exit and entry anchors
+/// meet, and offset glyphs vertically along the chain. Chains are positioned from the start of
+/// the text unless the cursive lookup has the `RIGHT_TO_LEFT` flag, in which case the last glyph
+/// of the chain sits on the baseline. Marks are zero width and offset so that their anchors meet
+/// those of their base.
+///
+/// The `advance` function supplies the horizontal advance of a glyph index. The positions are
+/// returned in the same logical order as `infos`; for right-to-left text glyphs should be drawn in
+/// reverse order.
+pub fn glyph_positions(
+    infos: &[Info],
+    direction: TextDirection,
+    advance: impl Fn(u16) -> Option<u16>,
+) -> Vec<GlyphPosition> {
+    let mut positions = infos
+        .iter()
+        .map(|info| {
+            let (x_offset, y_offset) = match info.placement {
+                Placement::Distance(dx, dy) => (dx, dy),
+                Placement::None => (0, 0),
+            };
+            let x_advance = if info.is_mark {
+                0
+            } else {
+                i32::from(advance(info.glyph.glyph_index).unwrap_or(0)) + i32::from(info.kerning)
+            };
+            GlyphPosition {
+                x_advance,
+                y_advance: 0,
+                x_offset,
+                y_offset,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // The glyph each glyph is positioned relative to, and whether it is a mark attachment
+    let mut parents: Vec<Option<(usize, bool)>> = vec![None; infos.len()];
+    for (i, info) in infos.iter().enumerate() {
+        match info.attachment {
+            Attachment::CursiveAnchor(j, ref entry, ref exit) if j < infos.len() => {
+                // Join the exit anchor of glyph i to the entry anchor of glyph j
+                let (exit_x, entry_x) = (i32::from(exit.x), i32::from(entry.x));
+                match direction {
+                    TextDirection::LeftToRight => {
+                        positions[i].x_advance = exit_x + positions[i].x_offset;
+                        let d = entry_x + positions[j].x_offset;
+                        positions[j].x_advance -= d;
+                        positions[j].x_offset -= d;
+                    }
+                    TextDirection::RightToLeft => {
+                        let d = exit_x + positions[i].x_offset;
+                        positions[i].x_advance -= d;
+                        positions[i].x_offset -= d;
+                        positions[j].x_advance = entry_x + positions[j].x_offset;
+                    }
+                }
+
+                let y_offset = i32::from(entry.y) - i32::from(exit.y);
+                let (child, parent, y_offset) = if info.cursive_right_to_left {
+                    (i, j, y_offset)
+                } else {
+                    (j, i, -y_offset)
+                };
+                // Break cycles formed by lookups chaining in opposite directions
+                if parents[parent] == Some((child, false)) {
+                    parents[parent] = None;
+                }
+                parents[child] = Some((parent, false));
+                positions[child].y_offset = y_offset;
+            }
+            Attachment::MarkAnchor(j, ref base_anchor, ref mark_anchor) if j < infos.len() => {
+                positions[i].x_offset = i32::from(base_anchor.x) - i32::from(mark_anchor.x);
+                positions[i].y_offset = i32::from(base_anchor.y) - i32::from(mark_anchor.y);
+                positions[i].x_advance = 0;
+                parents[i] = Some((j, true));
+            }
+            Attachment::MarkOverprint(j) if j < infos.len() => {
+                positions[i].x_offset = 0;
+                positions[i].y_offset = 0;
+                positions[i].x_advance = 0;
+                parents[i] = Some((j, true));
+            }
+            _ => {}
+        }
+    }
+
+    let mut resolved = vec![false; infos.len()];
+    for i in 0..infos.len() {
+        resolve_position(i, &parents, direction, &mut resolved, &mut positions);
+    }
+
+    positions
+}
+
+/// The metrics of a glyph used to position vertical text, in font units.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct VerticalMetrics {
+    /// The horizontal advance of the glyph.
+    pub h_advance: u16,
+    /// The vertical advance of the glyph.
+    pub v_advance: u16,
+    /// The y coordinate of the vertical origin of the glyph.
+    pub origin_y: i16,
+}
+
+/// Resolve the placements and attachments of glyphs positioned for vertical text into advances
+/// and offsets.
+///
+/// Each glyph is centred horizontally on the pen, with its vertical origin at the pen position.
+/// The pen moves down the page by the vertical advance of each glyph. Marks are zero height and
+/// offset so that their anchors meet those of their base. Cursive attachments have no meaning in
+/// vertical text and are ignored.
+///
+/// The `metrics` function supplies the vertical metrics of a glyph index.
+pub fn vertical_glyph_positions(
+    infos: &[Info],
+    metrics: impl Fn(u16) -> VerticalMetrics,
+) -> Vec<GlyphPosition> {
+    // Offsets in horizontal layout, relative to the horizontal origin of the base glyph each
+    // glyph is attached to
+    let mut offsets = Vec::with_capacity(infos.len());
+    let mut bases = Vec::with_capacity(infos.len());
+    for (i, info) in infos.iter().enumerate() {
+        let (offset, base) = match info.attachment {
+            Attachment::MarkAnchor(j, ref base_anchor, ref mark_anchor) if j < i => {
+                let (x, y) = offsets[j];
+                let offset = (
+                    x + i32::from(base_anchor.x) - i32::from(mark_anchor.x),
+                    y + i32::from(base_anchor.y) - i32::from(mark_anchor.y),
+                );
+                (offset, bases[j])
+            }
+            Attachment::MarkOverprint(j) if j < i => (offsets[j], bases[j]),
+            _ => match info.placement {
+                Placement::Distance(dx, dy) => ((dx, dy), i),
+                Placement::None => ((0, 0), i),
+            },
+        };
+        offsets.push(offset);
+        bases.push(base);
+    }
+
+    let mut positions = infos
+        .iter()
+        .map(|info| {
+            let y_advance = if info.is_mark {
+                0
+            } else {
+                -i32::from(metrics(info.glyph.glyph_index).v_advance)
+            };
+            GlyphPosition {
+                x_advance: 0,
+                y_advance,
+                x_offset: 0,
+                y_offset: 0,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    for (i, ((x, y), base)) in offsets.into_iter().zip(bases).enumerate() {
+        let base_metrics = metrics(infos[base].glyph.glyph_index);
+        // Offset attached glyphs back to the pen position of their base
+        let pen_offset: i32 = positions[base..i]
+            .iter()
+            .map(|position| position.y_advance)
+            .sum();
+        positions[i].x_offset = x - i32::from(base_metrics.h_advance) / 2;
+        positions[i].y_offset = y - i32::from(base_metrics.origin_y) - pen_offset;
+    }
+
+    positions
+}
+
+fn resolve_position(
+    i: usize,
+    parents: &[Option<(usize, bool)>],
+    direction: TextDirection,
+    resolved: &mut [bool],
+    positions: &mut [GlyphPosition],
+) {
+    // Walk up the attachment chain to the first resolved glyph, then resolve back down it so that
+    // each glyph is offset by the final position of its parent. Marking glyphs resolved on the way
+    // up guards against cycles.
+    let mut chain = Vec::new();
+    let mut k = i;
+    while !resolved[k] {
+        resolved[k] = true;
+        chain.push(k);
+        match parents[k] {
+            Some((j, _)) => k = j,
+            None => break,
+        }
+    }
+
+    for &k in chain.iter().rev() {
+        let (j, is_mark) = match parents[k] {
+            Some(parent) => parent,
+            None => continue,
+        };
+        positions[k].y_offset += positions[j].y_offset;
+        if !is_mark {
+            continue;
+        }
+        positions[k].x_offset += positions[j].x_offset;
+        // Offset the mark back to the pen position of its base
+        let advances = |range: std::ops::Range<usize>| -> i32 {
+            positions[range]
+                .iter()
+                .map(|position| position.x_advance)
+                .sum()
+        };
+        let x_offset = match direction {
+            TextDirection::LeftToRight if j < k => -advances(j..k),
+            TextDirection::LeftToRight => advances(k..j),
+            TextDirection::RightToLeft if j < k => advances(j + 1..k + 1),
+            TextDirection::RightToLeft => -advances(k + 1..j + 1),
+        };
+        positions[k].x_offset += x_offset;
+    }
+}
+
 fn gpos_apply_lookup(
     gpos_cache: &LayoutCache<GPOS>,
     gpos_table: &LayoutTable<GPOS>,
     opt_gdef_table: Option<&GDEFTable>,
+    device_context: &DeviceContext<'_>,
+    lookup_index: usize,
+    infos: &mut [Info],
+) -> Result<(), ParseError> {
+    let lookup_list = match gpos_table.opt_lookup_list {
+        Some(ref lookup_list) if trace::is_enabled() => lookup_list,
+        _ => {
+            return gpos_apply_lookup_untraced(
+                gpos_cache,
+                gpos_table,
+                opt_gdef_table,
+                device_context,
+                lookup_index,
+                infos,
+            )
+        }
+    };
+
+    let before = infos.iter().map(Info::position_key).collect::<Vec<_>>();
+    gpos_apply_lookup_untraced(
+        gpos_cache,
+        gpos_table,
+        opt_gdef_table,
+        device_context,
+        lookup_index,
+        infos,
+    )?;
+    let positioned = infos
+        .iter()
+        .zip(before)
+        .enumerate()
+        .filter(|(_, (info, before))| info.position_key() != *before)
+        .map(|(i, _)| i)
+        .collect();
+    let lookup = lookup_list.lookup_cache_gpos(gpos_cache, lookup_index)?;
+    trace::record(TraceEvent::Positioning {
+        lookup_index,
+        lookup_type: lookup.lookup_subtables.lookup_type(),
+        positioned,
+    });
+    Ok(())
+}
+
+fn gpos_apply_lookup_untraced(
+    gpos_cache: &LayoutCache<GPOS>,
+    gpos_table: &LayoutTable<GPOS>,
+    opt_gdef_table: Option<&GDEFTable>,
+    device_context: &DeviceContext<'_>,
     lookup_index: usize,
     infos: &mut [Info],
 ) -> Result<(), ParseError> {
     if let Some(ref lookup_list) = gpos_table.opt_lookup_list {
         let lookup = lookup_list.lookup_cache_gpos(gpos_cache, lookup_index)?;
-        let match_type = MatchType::from_lookup_flag(lookup.lookup_flag);
+        let match_type = MatchType::from_lookup_flag(lookup.lookup_flag, lookup.mark_filtering_set);
         match lookup.lookup_subtables {
             PosLookup::SinglePos(ref subtables) => {
                 forall_glyphs_match(match_type, opt_gdef_table, infos, |i, infos| {
-                    singlepos(&subtables, &mut infos[i])
+                    singlepos(&subtables, device_context, &mut infos[i])
                 })
             }
             PosLookup::PairPos(ref subtables) => {
@@ -161,36 +986,39 @@ fn gpos_apply_lookup(
                 // not repositioned, ie. if the value_format is zero, but applying the lookup
                 // regardless does not break any test cases.
                 forall_glyph_pairs_match(match_type, opt_gdef_table, infos, |i1, i2, infos| {
-                    pairpos(&subtables, i1, i2, infos)
+                    pairpos(&subtables, device_context, i1, i2, infos)
                 })
             }
             PosLookup::CursivePos(ref subtables) => forall_glyph_pairs_match(
                 MatchType::ignore_marks(),
                 opt_gdef_table,
                 infos,
-                |i1, i2, infos| cursivepos(&subtables, i1, i2, infos),
+                |i1, i2, infos| {
+                    let rtl = lookup.lookup_flag.get_rtl();
+                    cursivepos(&subtables, device_context, rtl, i1, i2, infos)
+                },
             ),
             PosLookup::MarkBasePos(ref subtables) => {
                 forall_base_mark_glyph_pairs(infos, |i1, i2, infos| {
-                    markbasepos(&subtables, i1, i2, infos)
+                    markbasepos(&subtables, device_context, i1, i2, infos)
                 })
             }
             PosLookup::MarkLigPos(ref subtables) => {
                 forall_base_mark_glyph_pairs(infos, |i1, i2, infos| {
-                    markligpos(&subtables, i1, i2, infos)
+                    markligpos(&subtables, device_context, i1, i2, infos)
                 })
             }
             PosLookup::MarkMarkPos(ref subtables) => {
                 forall_mark_mark_glyph_pairs(infos, |i1, i2, infos| {
-                    markmarkpos(&subtables, i1, i2, infos)
+                    markmarkpos(&subtables, device_context, i1, i2, infos)
                 })
             }
             PosLookup::ContextPos(ref subtables) => {
                 forall_glyphs_match(match_type, opt_gdef_table, infos, |i, infos| {
                     contextpos(
                         gpos_cache,
-                        &lookup_list,
                         opt_gdef_table,
+                        device_context,
                         match_type,
                         &subtables,
                         i,
@@ -202,8 +1030,8 @@ fn gpos_apply_lookup(
                 forall_glyphs_match(match_type, opt_gdef_table, infos, |i, infos| {
                     chaincontextpos(
                         gpos_cache,
-                        &lookup_list,
                         opt_gdef_table,
+                        device_context,
                         match_type,
                         &subtables,
                         i,
@@ -218,7 +1046,7 @@ fn gpos_apply_lookup(
 }
 
 fn gpos_lookup_singlepos(
-    subtables: &[SinglePos],
+    subtables: &LazySubtables<GPOS, SinglePos>,
     glyph_index: u16,
 ) -> Result<ValueRecord, ParseError> {
     for singlepos in subtables {
@@ -230,7 +1058,7 @@ fn gpos_lookup_singlepos(
 }
 
 fn gpos_lookup_pairpos(
-    subtables: &[PairPos],
+    subtables: &LazySubtables<GPOS, PairPos>,
     glyph_index1: u16,
     glyph_index2: u16,
 ) -> Result<Option<(ValueRecord, ValueRecord)>, ParseError> {
@@ -243,7 +1071,7 @@ fn gpos_lookup_pairpos(
 }
 
 fn gpos_lookup_cursivepos(
-    subtables: &[CursivePos],
+    subtables: &LazySubtables<GPOS, CursivePos>,
     glyph_index1: u16,
     glyph_index2: u16,
 ) -> Result<Option<(Anchor, Anchor)>, ParseError> {
@@ -256,7 +1084,7 @@ fn gpos_lookup_cursivepos(
 }
 
 fn gpos_lookup_markbasepos(
-    subtables: &[MarkBasePos],
+    subtables: &LazySubtables<GPOS, MarkBasePos>,
     glyph_index1: u16,
     glyph_index2: u16,
 ) -> Result<Option<(Anchor, Anchor)>, ParseError> {
@@ -269,7 +1097,7 @@ fn gpos_lookup_markbasepos(
 }
 
 fn gpos_lookup_markligpos(
-    subtables: &[MarkLigPos],
+    subtables: &LazySubtables<GPOS, MarkLigPos>,
     glyph_index1: u16,
     glyph_index2: u16,
     liga_component_index: u16,
@@ -287,7 +1115,7 @@ fn gpos_lookup_markligpos(
 }
 
 fn gpos_lookup_markmarkpos(
-    subtables: &[MarkBasePos],
+    subtables: &LazySubtables<GPOS, MarkBasePos>,
     glyph_index1: u16,
     glyph_index2: u16,
 ) -> Result<Option<(Anchor, Anchor)>, ParseError> {
@@ -302,7 +1130,7 @@ fn gpos_lookup_markmarkpos(
 fn gpos_lookup_contextpos<'a>(
     opt_gdef_table: Option<&GDEFTable>,
     match_type: MatchType,
-    subtables: &'a [ContextLookup<GPOS>],
+    subtables: &'a LazySubtables<GPOS, ContextLookup<GPOS>>,
     glyph_index: u16,
     i: usize,
     infos: &mut [Info],
@@ -320,7 +1148,7 @@ fn gpos_lookup_contextpos<'a>(
 fn gpos_lookup_chaincontextpos<'a>(
     opt_gdef_table: Option<&GDEFTable>,
     match_type: MatchType,
-    subtables: &'a [ChainContextLookup<GPOS>],
+    subtables: &'a LazySubtables<GPOS, ChainContextLookup<GPOS>>,
     glyph_index: u16,
     i: usize,
     infos: &mut [Info],
@@ -337,6 +1165,55 @@ fn gpos_lookup_chaincontextpos<'a>(
     Ok(None)
 }
 
+/// The size and variation instance that the device and variation index tables of `GPOS` values
+/// are applied for.
+///
+/// The default context applies neither, positioning glyphs as for the default instance of the
+/// font without adjustments for size.
+#[derive(Clone, Copy, Default)]
+pub struct DeviceContext<'a> {
+    /// The size in pixels per em that the hinting deltas of device tables are applied for, or
+    /// `None` to ignore them.
+    pub opt_ppem: Option<u16>,
+    /// The units per em of the font, used to convert hinting deltas from pixels to font units.
+    pub units_per_em: u16,
+    /// The normalised variation coordinates of the instance of a variable font.
+    pub coords: &'a [F2Dot14],
+    /// The item variation store of the `GDEF` table, which holds the deltas of variation index
+    /// tables. See `gdef::read_item_variation_store`.
+    pub opt_item_variation_store: Option<&'a ItemVariationStore<'a>>,
+}
+
+impl DeviceContext<'_> {
+    /// Returns `value` adjusted by `opt_device`, in font units.
+    fn adjust(&self, value: i16, opt_device: Option<&Device>) -> Result<i16, ParseError> {
+        let delta = match opt_device {
+            Some(Device::Hinting {
+                start_size,
+                end_size,
+                deltas,
+            }) => match self.opt_ppem {
+                Some(ppem) if ppem != 0 && (*start_size..=*end_size).contains(&ppem) => {
+                    let index = usize::from(ppem - *start_size);
+                    let pixels = deltas.get(index).copied().unwrap_or(0);
+                    f32::from(pixels) * f32::from(self.units_per_em) / f32::from(ppem)
+                }
+                _ => 0.,
+            },
+            Some(Device::VariationIndex {
+                delta_set_outer_index,
+                delta_set_inner_index,
+            }) => match self.opt_item_variation_store {
+                Some(item_variation_store) if !self.coords.is_empty() => item_variation_store
+                    .delta(*delta_set_outer_index, *delta_set_inner_index, self.coords)?,
+                _ => 0.,
+            },
+            None => return Ok(value),
+        };
+        Ok((f32::from(value) + delta).round() as i16)
+    }
+}
+
 /// Adjustment to the placement of a glyph as a result of kerning, etc.
 #[derive(Debug)]
 pub enum Placement {
@@ -368,8 +1245,10 @@ pub enum Attachment {
     MarkOverprint(usize),
     /// Cursive anchored placement.
     ///
+    /// This glyph's exit anchor is joined to the entry anchor of the following glyph.
+    ///
     /// Fields:
-    /// (exit glyph index in `Vec<Info>`, exit glyph anchor, entry glyph anchor)
+    /// (entry glyph index in `Vec<Info>`, entry glyph anchor, exit glyph anchor)
     ///
     /// https://docs.microsoft.com/en-us/typography/opentype/spec/gpos#lookup-type-3-cursive-attachment-positioning-subtable
     CursiveAnchor(usize, Anchor, Anchor),
@@ -404,6 +1283,10 @@ pub struct Info {
     /// indicated by the variant.
     pub attachment: Attachment,
     is_mark: bool,
+    /// Set when the cursive attachment came from a lookup with the `RIGHT_TO_LEFT` flag, in which
+    /// case this glyph is positioned relative to the glyph it's attached to, rather than the other
+    /// way around.
+    cursive_right_to_left: bool,
 }
 
 impl Glyph for Info {
@@ -413,6 +1296,21 @@ impl Glyph for Info {
 }
 
 impl Info {
+    /// The positioning of the glyph, for detecting changes to it when tracing.
+    fn position_key(&self) -> (i16, Option<(i32, i32)>, Option<usize>) {
+        let placement = match self.placement {
+            Placement::None => None,
+            Placement::Distance(dx, dy) => Some((dx, dy)),
+        };
+        let attached_to = match self.attachment {
+            Attachment::None => None,
+            Attachment::MarkAnchor(base, _, _)
+            | Attachment::MarkOverprint(base)
+            | Attachment::CursiveAnchor(base, _, _) => Some(base),
+        };
+        (self.kerning, placement, attached_to)
+    }
+
     pub fn init_from_glyphs(
         opt_gdef_table: Option<&GDEFTable>,
         glyphs: Vec<RawGlyph<()>>,
@@ -426,6 +1324,7 @@ impl Info {
                 placement: Placement::None,
                 attachment: Attachment::None,
                 is_mark,
+                cursive_right_to_left: false,
             };
             infos.push(info);
         }
@@ -434,6 +1333,21 @@ impl Info {
 }
 
 impl Adjust {
+    /// Returns the adjustment with the deltas of its device tables applied.
+    fn resolve(self, device_context: &DeviceContext<'_>) -> Result<Adjust, ParseError> {
+        let devices = match self.opt_devices {
+            Some(ref devices) => devices,
+            None => return Ok(self),
+        };
+        Ok(Adjust {
+            x_placement: device_context.adjust(self.x_placement, devices.x_placement.as_ref())?,
+            y_placement: device_context.adjust(self.y_placement, devices.y_placement.as_ref())?,
+            x_advance: device_context.adjust(self.x_advance, devices.x_advance.as_ref())?,
+            y_advance: device_context.adjust(self.y_advance, devices.y_advance.as_ref())?,
+            opt_devices: None,
+        })
+    }
+
     fn apply(&self, info: &mut Info) {
         if self.x_placement == 0 && self.y_placement == 0 {
             if self.x_advance != 0 && self.y_advance == 0 {
@@ -457,6 +1371,22 @@ impl Adjust {
     }
 }
 
+impl Anchor {
+    /// Returns the anchor with the deltas of its device tables applied.
+    fn resolve(self, device_context: &DeviceContext<'_>) -> Result<Anchor, ParseError> {
+        let devices = match self.opt_devices {
+            Some(ref devices) => devices,
+            None => return Ok(self),
+        };
+        Ok(Anchor {
+            x: device_context.adjust(self.x, devices.x.as_ref())?,
+            y: device_context.adjust(self.y, devices.y.as_ref())?,
+            opt_devices: None,
+            opt_anchor_point: self.opt_anchor_point,
+        })
+    }
+}
+
 fn forall_glyphs_match(
     match_type: MatchType,
     opt_gdef_table: Option<&GDEFTable>,
@@ -530,16 +1460,21 @@ fn forall_mark_mark_glyph_pairs(
     Ok(())
 }
 
-fn singlepos(subtables: &[SinglePos], i: &mut Info) -> Result<(), ParseError> {
+fn singlepos(
+    subtables: &LazySubtables<GPOS, SinglePos>,
+    device_context: &DeviceContext<'_>,
+    i: &mut Info,
+) -> Result<(), ParseError> {
     let glyph_index = i.glyph.glyph_index;
     if let Some(adj) = gpos_lookup_singlepos(subtables, glyph_index)? {
-        adj.apply(i);
+        adj.resolve(device_context)?.apply(i);
     }
     Ok(())
 }
 
 fn pairpos(
-    subtables: &[PairPos],
+    subtables: &LazySubtables<GPOS, PairPos>,
+    device_context: &DeviceContext<'_>,
     i1: usize,
     i2: usize,
     infos: &mut [Info],
@@ -551,10 +1486,10 @@ fn pairpos(
     )? {
         Some((opt_adj1, opt_adj2)) => {
             if let Some(adj1) = opt_adj1 {
-                adj1.apply(&mut infos[i1]);
+                adj1.resolve(device_context)?.apply(&mut infos[i1]);
             }
             if let Some(adj2) = opt_adj2 {
-                adj2.apply(&mut infos[i2]);
+                adj2.resolve(device_context)?.apply(&mut infos[i2]);
             }
             Ok(())
         }
@@ -563,7 +1498,9 @@ fn pairpos(
 }
 
 fn cursivepos(
-    subtables: &[CursivePos],
+    subtables: &LazySubtables<GPOS, CursivePos>,
+    device_context: &DeviceContext<'_>,
+    right_to_left: bool,
     i1: usize,
     i2: usize,
     infos: &mut [Info],
@@ -574,7 +1511,10 @@ fn cursivepos(
         infos[i2].glyph.glyph_index,
     )? {
         Some((anchor1, anchor2)) => {
+            let anchor1 = anchor1.resolve(device_context)?;
+            let anchor2 = anchor2.resolve(device_context)?;
             infos[i1].attachment = Attachment::CursiveAnchor(i2, anchor2, anchor1);
+            infos[i1].cursive_right_to_left = right_to_left;
             Ok(())
         }
         None => Ok(()),
@@ -582,7 +1522,8 @@ fn cursivepos(
 }
 
 fn markbasepos(
-    subtables: &[MarkBasePos],
+    subtables: &LazySubtables<GPOS, MarkBasePos>,
+    device_context: &DeviceContext<'_>,
     i1: usize,
     i2: usize,
     infos: &mut [Info],
@@ -593,6 +1534,8 @@ fn markbasepos(
         infos[i2].glyph.glyph_index,
     )? {
         Some((anchor1, anchor2)) => {
+            let anchor1 = anchor1.resolve(device_context)?;
+            let anchor2 = anchor2.resolve(device_context)?;
             infos[i2].attachment = Attachment::MarkAnchor(i1, anchor1, anchor2);
             infos[i2].is_mark = true;
             Ok(())
@@ -602,7 +1545,8 @@ fn markbasepos(
 }
 
 fn markligpos(
-    subtables: &[MarkLigPos],
+    subtables: &LazySubtables<GPOS, MarkLigPos>,
+    device_context: &DeviceContext<'_>,
     i1: usize,
     i2: usize,
     infos: &mut [Info],
@@ -614,6 +1558,8 @@ fn markligpos(
         infos[i2].glyph.liga_component_pos,
     )? {
         Some((anchor1, anchor2)) => {
+            let anchor1 = anchor1.resolve(device_context)?;
+            let anchor2 = anchor2.resolve(device_context)?;
             infos[i2].attachment = Attachment::MarkAnchor(i1, anchor1, anchor2);
             infos[i2].is_mark = true;
             Ok(())
@@ -623,7 +1569,8 @@ fn markligpos(
 }
 
 fn markmarkpos(
-    subtables: &[MarkBasePos],
+    subtables: &LazySubtables<GPOS, MarkBasePos>,
+    device_context: &DeviceContext<'_>,
     i1: usize,
     i2: usize,
     infos: &mut [Info],
@@ -634,6 +1581,8 @@ fn markmarkpos(
         infos[i2].glyph.glyph_index,
     )? {
         Some((anchor1, anchor2)) => {
+            let anchor1 = anchor1.resolve(device_context)?;
+            let anchor2 = anchor2.resolve(device_context)?;
             infos[i2].attachment = Attachment::MarkAnchor(i1, anchor1, anchor2);
             infos[i2].is_mark = true;
             Ok(())
@@ -644,58 +1593,41 @@ fn markmarkpos(
 
 fn contextpos<'a>(
     gpos_cache: &LayoutCache<GPOS>,
-    lookup_list: &LookupList<GPOS>,
     opt_gdef_table: Option<&GDEFTable>,
+    device_context: &DeviceContext<'_>,
     match_type: MatchType,
-    subtables: &[ContextLookup<GPOS>],
+    subtables: &LazySubtables<GPOS, ContextLookup<GPOS>>,
     i: usize,
     infos: &mut [Info],
 ) -> Result<(), ParseError> {
     let glyph_index = infos[i].glyph.glyph_index;
     match gpos_lookup_contextpos(opt_gdef_table, match_type, subtables, glyph_index, i, infos)? {
-        Some(pos) => apply_pos_context(
-            gpos_cache,
-            lookup_list,
-            opt_gdef_table,
-            match_type,
-            &pos,
-            i,
-            infos,
-        ),
+        Some(pos) => apply_pos_context(gpos_cache, opt_gdef_table, device_context, &pos, i, infos),
         None => Ok(()),
     }
 }
 
 fn chaincontextpos<'a>(
     gpos_cache: &LayoutCache<GPOS>,
-    lookup_list: &LookupList<GPOS>,
     opt_gdef_table: Option<&GDEFTable>,
+    device_context: &DeviceContext<'_>,
     match_type: MatchType,
-    subtables: &[ChainContextLookup<GPOS>],
+    subtables: &LazySubtables<GPOS, ChainContextLookup<GPOS>>,
     i: usize,
     infos: &mut [Info],
 ) -> Result<(), ParseError> {
     let glyph_index = infos[i].glyph.glyph_index;
     match gpos_lookup_chaincontextpos(opt_gdef_table, match_type, subtables, glyph_index, i, infos)?
     {
-        Some(pos) => apply_pos_context(
-            gpos_cache,
-            lookup_list,
-            opt_gdef_table,
-            match_type,
-            &pos,
-            i,
-            infos,
-        ),
+        Some(pos) => apply_pos_context(gpos_cache, opt_gdef_table, device_context, &pos, i, infos),
         None => Ok(()),
     }
 }
 
 fn apply_pos_context<'a>(
     gpos_cache: &LayoutCache<GPOS>,
-    lookup_list: &LookupList<GPOS>,
     opt_gdef_table: Option<&GDEFTable>,
-    _match_type: MatchType,
+    device_context: &DeviceContext<'_>,
     pos: &PosContext<'_>,
     i: usize,
     infos: &mut [Info],
@@ -703,8 +1635,8 @@ fn apply_pos_context<'a>(
     for (pos_index, pos_lookup_index) in pos.lookup_array {
         apply_pos(
             gpos_cache,
-            lookup_list,
             opt_gdef_table,
+            device_context,
             usize::from(*pos_index),
             usize::from(*pos_lookup_index),
             infos,
@@ -716,32 +1648,39 @@ fn apply_pos_context<'a>(
 
 fn apply_pos<'a>(
     gpos_cache: &LayoutCache<GPOS>,
-    lookup_list: &LookupList<GPOS>,
     opt_gdef_table: Option<&GDEFTable>,
+    device_context: &DeviceContext<'_>,
     pos_index: usize,
     lookup_index: usize,
     infos: &mut [Info],
     index: usize,
 ) -> Result<(), ParseError> {
+    let lookup_list = match gpos_cache.layout_table.opt_lookup_list {
+        Some(ref lookup_list) => lookup_list,
+        None => return Ok(()),
+    };
     let lookup = lookup_list.lookup_cache_gpos(gpos_cache, lookup_index)?;
-    let match_type = MatchType::from_lookup_flag(lookup.lookup_flag);
+    let match_type = MatchType::from_lookup_flag(lookup.lookup_flag, lookup.mark_filtering_set);
     let i1;
     match match_type.find_nth(opt_gdef_table, infos, index, pos_index) {
         Some(index1) => i1 = index1,
         None => return Ok(()),
     }
     match lookup.lookup_subtables {
-        PosLookup::SinglePos(ref subtables) => singlepos(&subtables, &mut infos[i1]),
+        PosLookup::SinglePos(ref subtables) => {
+            singlepos(&subtables, device_context, &mut infos[i1])
+        }
         PosLookup::PairPos(ref subtables) => {
             if let Some(i2) = match_type.find_next(opt_gdef_table, infos, i1) {
-                pairpos(&subtables, i1, i2, infos)
+                pairpos(&subtables, device_context, i1, i2, infos)
             } else {
                 Ok(())
             }
         }
         PosLookup::CursivePos(ref subtables) => {
             if let Some(i2) = match_type.find_next(opt_gdef_table, infos, i1) {
-                cursivepos(&subtables, i1, i2, infos)
+                let rtl = lookup.lookup_flag.get_rtl();
+                cursivepos(&subtables, device_context, rtl, i1, i2, infos)
             } else {
                 Ok(())
             }
@@ -750,7 +1689,7 @@ fn apply_pos<'a>(
             // FIXME is this correct?
             if let Some(base_index) = MatchType::ignore_marks().find_prev(opt_gdef_table, infos, i1)
             {
-                markbasepos(&subtables, base_index, i1, infos)
+                markbasepos(&subtables, device_context, base_index, i1, infos)
             } else {
                 Ok(())
             }
@@ -759,7 +1698,7 @@ fn apply_pos<'a>(
             // FIXME is this correct?
             if let Some(base_index) = MatchType::ignore_marks().find_prev(opt_gdef_table, infos, i1)
             {
-                markligpos(&subtables, base_index, i1, infos)
+                markligpos(&subtables, device_context, base_index, i1, infos)
             } else {
                 Ok(())
             }
@@ -767,7 +1706,7 @@ fn apply_pos<'a>(
         PosLookup::MarkMarkPos(ref subtables) => {
             // FIXME is this correct?
             if let Some(base_index) = match_type.find_prev(opt_gdef_table, infos, i1) {
-                markmarkpos(&subtables, base_index, i1, infos)
+                markmarkpos(&subtables, device_context, base_index, i1, infos)
             } else {
                 Ok(())
             }
@@ -776,3 +1715,432 @@ fn apply_pos<'a>(
         PosLookup::ChainContextPos(ref _subtables) => Ok(()),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::read::ReadScope;
+    use crate::gsub::GlyphOrigin;
+    use crate::layout::{new_layout_cache, AdjustDevices, AnchorDevices};
+    use std::sync::Arc;
+    use tinyvec::tiny_vec;
+
+    fn info(ch: char, glyph_index: u16, stretch: Option<StretchPart>) -> Info {
+        Info {
+            glyph: RawGlyph {
+                unicodes: tiny_vec![[char; 1] => ch],
+                glyph_index,
+                liga_component_pos: 0,
+                glyph_origin: GlyphOrigin::Char(ch),
+                small_caps: false,
+                multi_subst_dup: false,
+                multi_subst_component: None,
+                is_vert_alt: false,
+                fake_bold: false,
+                fake_italic: false,
+                stretch,
+                variation: None,
+                extra_data: (),
+            },
+            kerning: 0,
+            placement: Placement::None,
+            attachment: Attachment::None,
+            is_mark: false,
+            cursive_right_to_left: false,
+        }
+    }
+
+    fn anchor(x: i16, y: i16) -> Anchor {
+        Anchor {
+            x,
+            y,
+            opt_devices: None,
+            opt_anchor_point: None,
+        }
+    }
+
+    fn position(x_advance: i32, x_offset: i32, y_offset: i32) -> GlyphPosition {
+        GlyphPosition {
+            x_advance,
+            y_advance: 0,
+            x_offset,
+            y_offset,
+        }
+    }
+
+    #[test]
+    fn cursive_positions_left_to_right() {
+        let mut infos = vec![info('a', 1, None), info('b', 1, None)];
+        infos[0].attachment = Attachment::CursiveAnchor(1, anchor(50, 100), anchor(450, 20));
+
+        let positions = glyph_positions(&infos, TextDirection::LeftToRight, |_| Some(500));
+        assert_eq!(
+            positions,
+            vec![position(450, 0, 0), position(450, -50, -80)]
+        );
+    }
+
+    #[test]
+    fn cursive_positions_right_to_left_flag() {
+        // A chain of three glyphs from a lookup with the `RIGHT_TO_LEFT` flag, so the last glyph
+        // sits on the baseline and the earlier glyphs step up from it
+        let mut infos = vec![
+            info('\u{628}', 1, None),
+            info('\u{628}', 1, None),
+            info('\u{628}', 1, None),
+        ];
+        for (i, info) in infos.iter_mut().enumerate().take(2) {
+            info.attachment = Attachment::CursiveAnchor(i + 1, anchor(490, 0), anchor(10, 100));
+            info.cursive_right_to_left = true;
+        }
+
+        let positions = glyph_positions(&infos, TextDirection::RightToLeft, |_| Some(500));
+        assert_eq!(
+            positions,
+            vec![
+                position(490, -10, -200),
+                position(480, -10, -100),
+                position(490, 0, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn mark_positions() {
+        let mut infos = vec![info('\u{628}', 1, None), info('\u{64e}', 2, None)];
+        infos[1].is_mark = true;
+        infos[1].attachment = Attachment::MarkAnchor(0, anchor(250, 600), anchor(100, 50));
+        let advance = |glyph| match glyph {
+            1 => Some(500),
+            _ => Some(300),
+        };
+
+        let positions = glyph_positions(&infos, TextDirection::RightToLeft, advance);
+        assert_eq!(positions, vec![position(500, 0, 0), position(0, 150, 550)]);
+
+        let positions = glyph_positions(&infos, TextDirection::LeftToRight, advance);
+        assert_eq!(positions, vec![position(500, 0, 0), position(0, -350, 550)]);
+    }
+
+    #[test]
+    fn anchor_points() {
+        let point_anchor = |x, y, point_index| Anchor {
+            opt_anchor_point: Some(point_index),
+            ..anchor(x, y)
+        };
+        let mut infos = vec![
+            info('\u{628}', 1, None),
+            info('\u{64e}', 2, None),
+            info('\u{651}', 3, None),
+        ];
+        infos[1].attachment = Attachment::MarkAnchor(0, point_anchor(250, 600, 4), anchor(100, 50));
+        infos[2].attachment =
+            Attachment::MarkAnchor(0, point_anchor(250, 600, 9), point_anchor(100, 50, 1));
+        // Glyph 1 has 5 points and glyph 3 has 2 points
+        let mut requested = Vec::new();
+        resolve_anchor_points(&mut infos, |glyph, point_index| {
+            requested.push((glyph, point_index));
+            Ok(match (glyph, point_index) {
+                (1, 4) => Some((260, 620)),
+                (3, 1) => Some((110, 40)),
+                _ => None,
+            })
+        })
+        .unwrap();
+
+        assert_eq!(requested, vec![(1, 4), (1, 9), (3, 1)]);
+        let coordinates = |attachment: &Attachment| match attachment {
+            Attachment::MarkAnchor(_, anchor1, anchor2) => {
+                ((anchor1.x, anchor1.y), (anchor2.x, anchor2.y))
+            }
+            _ => panic!("expected MarkAnchor"),
+        };
+        assert_eq!(coordinates(&infos[1].attachment), ((260, 620), (100, 50)));
+        // Points the glyph doesn't have leave the coordinates of the anchor
+        assert_eq!(coordinates(&infos[2].attachment), ((250, 600), (110, 40)));
+    }
+
+    #[test]
+    fn device_adjustments() {
+        #[rustfmt::skip]
+        let store_data = [
+            0x00, 0x01, // format
+            0x00, 0x00, 0x00, 0x0C, // region list offset
+            0x00, 0x01, // item variation data count
+            0x00, 0x00, 0x00, 0x16, // item variation data offset
+            // Region list, one axis and one region peaking at 1.0
+            0x00, 0x01, 0x00, 0x01,
+            0x00, 0x00, 0x40, 0x00, 0x40, 0x00,
+            // Item variation data, one item with a 16-bit delta of 100
+            0x00, 0x01, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00,
+            0x00, 0x64,
+        ];
+        let store = ReadScope::new(&store_data)
+            .read::<ItemVariationStore<'_>>()
+            .unwrap();
+        let adjust = Adjust {
+            x_placement: 10,
+            y_placement: 0,
+            x_advance: -50,
+            y_advance: 0,
+            opt_devices: Some(Arc::new(AdjustDevices {
+                x_placement: Some(Device::VariationIndex {
+                    delta_set_outer_index: 0,
+                    delta_set_inner_index: 0,
+                }),
+                x_advance: Some(Device::Hinting {
+                    start_size: 10,
+                    end_size: 12,
+                    deltas: vec![1, -1, 2],
+                }),
+                ..AdjustDevices::default()
+            })),
+        };
+        let resolve = |device_context: DeviceContext<'_>| {
+            let adjust = adjust.clone().resolve(&device_context).unwrap();
+            (adjust.x_placement, adjust.x_advance)
+        };
+
+        assert_eq!(resolve(DeviceContext::default()), (10, -50));
+        let coords = [F2Dot14::new(0x2000)];
+        let device_context = DeviceContext {
+            opt_ppem: Some(11),
+            units_per_em: 1000,
+            coords: &coords,
+            opt_item_variation_store: Some(&store),
+        };
+        // 50 units from the half-way instance, and -1 pixel at 11ppem
+        assert_eq!(resolve(device_context), (60, -141));
+        // Sizes outside the range of the device table are not adjusted
+        let device_context = DeviceContext {
+            opt_ppem: Some(16),
+            ..device_context
+        };
+        assert_eq!(resolve(device_context), (60, -50));
+
+        let anchor = Anchor {
+            x: 100,
+            y: 200,
+            opt_devices: Some(Arc::new(AnchorDevices {
+                x: None,
+                y: Some(Device::VariationIndex {
+                    delta_set_outer_index: 0,
+                    delta_set_inner_index: 0,
+                }),
+            })),
+            opt_anchor_point: None,
+        };
+        let anchor = anchor.resolve(&device_context).unwrap();
+        assert_eq!((anchor.x, anchor.y), (100, 250));
+        assert!(anchor.opt_devices.is_none());
+    }
+
+    #[test]
+    fn kern_table_skips_marks() {
+        #[rustfmt::skip]
+        let kern_data = [
+            0, 0, 0, 1, // version, nTables
+            0, 0, 0, 26, 0, 1, // version, length, coverage
+            0, 2, 0, 12, 0, 1, 0, 0, // nPairs, searchRange, entrySelector, rangeShift
+            0, 1, 0, 2, 0xFF, 0xF6, // 1, 2: -10
+            0, 2, 0, 1, 0, 20, // 2, 1: 20
+        ];
+        let kern_table = ReadScope::new(&kern_data).read::<KernTable<'_>>().unwrap();
+        let mut infos = vec![
+            info('a', 1, None),
+            info('\u{301}', 3, None),
+            info('b', 2, None),
+            info('a', 1, None),
+        ];
+        apply_fallback(&mut infos);
+        apply_kern_table(&kern_table, &mut infos).unwrap();
+
+        let kerning = infos.iter().map(|info| info.kerning).collect::<Vec<_>>();
+        assert_eq!(kerning, vec![-10, 0, 20, 0]);
+    }
+
+    #[test]
+    fn kerx_table_skips_marks() {
+        #[rustfmt::skip]
+        let kerx_data = [
+            0, 2, 0, 0, 0, 0, 0, 1, // version, padding, nTables
+            0, 0, 0, 40, 0, 0, 0, 0, 0, 0, 0, 0, // length, coverage, tupleCount
+            0, 0, 0, 2, 0, 0, 0, 12, 0, 0, 0, 1, 0, 0, 0, 0, // nPairs, searchRange, entrySelector, rangeShift
+            0, 1, 0, 2, 0xFF, 0xF6, // 1, 2: -10
+            0, 2, 0, 1, 0, 20, // 2, 1: 20
+        ];
+        let kerx_table = ReadScope::new(&kerx_data).read::<KerxTable<'_>>().unwrap();
+        let mut infos = vec![
+            info('a', 1, None),
+            info('\u{301}', 3, None),
+            info('b', 2, None),
+            info('a', 1, None),
+        ];
+        apply_fallback(&mut infos);
+        apply_kerx_table(&kerx_table, &mut infos).unwrap();
+
+        let kerning = infos.iter().map(|info| info.kerning).collect::<Vec<_>>();
+        assert_eq!(kerning, vec![-10, 0, 20, 0]);
+    }
+
+    #[test]
+    fn vertical_positions() {
+        let mut infos = vec![
+            info('\u{4e00}', 1, None),
+            info('\u{3099}', 2, None),
+            info('\u{4e8c}', 1, None),
+        ];
+        infos[1].is_mark = true;
+        infos[1].attachment = Attachment::MarkAnchor(0, anchor(500, -100), anchor(100, 0));
+        infos[2].placement = Placement::Distance(0, -50);
+        let metrics = |glyph| match glyph {
+            1 => VerticalMetrics {
+                h_advance: 1000,
+                v_advance: 1000,
+                origin_y: 880,
+            },
+            _ => VerticalMetrics {
+                h_advance: 200,
+                v_advance: 0,
+                origin_y: 0,
+            },
+        };
+
+        let positions = vertical_glyph_positions(&infos, metrics);
+        let position = |y_advance, x_offset, y_offset| GlyphPosition {
+            x_advance: 0,
+            y_advance,
+            x_offset,
+            y_offset,
+        };
+        assert_eq!(
+            positions,
+            vec![
+                position(-1000, -500, -880),
+                position(0, -100, 20),
+                position(-1000, -500, -930),
+            ]
+        );
+    }
+
+    #[test]
+    fn stretch_copies_exact_fit() {
+        assert_eq!(stretch_copies(300, 100, 1), (2, 0));
+    }
+
+    #[test]
+    fn stretch_copies_overlapped_fit() {
+        // Three repeats of 100 overlapped by 25 twice covers 250
+        assert_eq!(stretch_copies(250, 100, 1), (2, 25));
+    }
+
+    #[test]
+    fn stretch_copies_too_narrow() {
+        assert_eq!(stretch_copies(50, 100, 1), (0, 0));
+        assert_eq!(stretch_copies(300, 0, 0), (0, 0));
+    }
+
+    #[test]
+    fn apply_stretch_repeats_parts() {
+        // Abbreviation mark decomposed into fixed, repeating, fixed parts, followed by
+        // three letters, a mark, and a space
+        let mut infos = vec![
+            info('\u{070F}', 1, Some(StretchPart::Fixed)),
+            info('\u{070F}', 2, Some(StretchPart::Repeating)),
+            info('\u{070F}', 1, Some(StretchPart::Fixed)),
+            info('\u{0710}', 3, None),
+            info('\u{0712}', 3, None),
+            info('\u{0713}', 3, None),
+            info('\u{0730}', 4, None),
+            info(' ', 3, None),
+        ];
+        infos[6].is_mark = true;
+        infos[6].attachment = Attachment::MarkOverprint(5);
+        let advance = |glyph| match glyph {
+            1 => Some(10),
+            2 => Some(100),
+            3 => Some(150),
+            _ => Some(0),
+        };
+
+        apply_stretch(&mut infos, advance);
+
+        // 450 - 20 fixed = 430 to cover, so 5 repeats of 100 each overlapped by 17
+        let glyphs = infos
+            .iter()
+            .map(|info| info.glyph.glyph_index)
+            .collect::<Vec<_>>();
+        assert_eq!(glyphs, vec![1, 2, 2, 2, 2, 2, 1, 3, 3, 3, 4, 3]);
+        let kerning = infos.iter().map(|info| info.kerning).collect::<Vec<_>>();
+        assert_eq!(kerning, vec![0, 0, -17, -17, -17, -17, 0, 0, 0, 0, 0, 0]);
+        match infos[10].attachment {
+            Attachment::MarkOverprint(index) => assert_eq!(index, 9),
+            _ => panic!("expected mark overprint attachment"),
+        }
+    }
+
+    #[test]
+    fn apply_stretch_without_stretch_glyphs() {
+        let mut infos = vec![info('a', 1, None), info('b', 2, None)];
+        apply_stretch(&mut infos, |_| Some(100));
+        assert_eq!(infos.len(), 2);
+    }
+
+    // A GPOS table with a single script whose default language system references `kern` twice
+    // and `mark` once. The lookups of the features overlap and are out of order.
+    fn make_overlapping_feature_gpos() -> Vec<u8> {
+        vec![
+            0x00, 0x01, 0x00, 0x00, // version
+            0x00, 0x0A, // script list offset
+            0x00, 0x22, // feature list offset
+            0x00, 0x00, // lookup list offset
+            // Script list
+            0x00, 0x01, // script count
+            0x6C, 0x61, 0x74, 0x6E, // latn
+            0x00, 0x08, // script offset
+            // Script table
+            0x00, 0x04, // default langsys offset
+            0x00, 0x00, // langsys count
+            // LangSys table
+            0x00, 0x00, // lookup order
+            0xFF, 0xFF, // required feature index
+            0x00, 0x03, // feature index count
+            0x00, 0x00, 0x00, 0x01, 0x00, 0x00, // feature indices
+            // Feature list
+            0x00, 0x02, // feature count
+            0x6B, 0x65, 0x72, 0x6E, // kern
+            0x00, 0x0E, // feature offset
+            0x6D, 0x61, 0x72, 0x6B, // mark
+            0x00, 0x16, // feature offset
+            // Feature table
+            0x00, 0x00, // feature params
+            0x00, 0x02, // lookup index count
+            0x00, 0x03, 0x00, 0x01, // lookup indices
+            // Feature table
+            0x00, 0x00, // feature params
+            0x00, 0x02, // lookup index count
+            0x00, 0x01, 0x00, 0x02, // lookup indices
+        ]
+    }
+
+    #[test]
+    fn test_resolved_lookups_lookup_index_order() {
+        let data = make_overlapping_feature_gpos();
+        let gpos = ReadScope::new(&data).read::<LayoutTable<GPOS>>().unwrap();
+        let gpos_cache = new_layout_cache(gpos);
+
+        let lookups = resolved_lookups(&gpos_cache, tag::LATN, None, &[tag::KERN, tag::MARK]);
+        assert_eq!(
+            lookups.unwrap(),
+            vec![(1, tag::KERN), (2, tag::MARK), (3, tag::KERN)]
+        );
+        let lookups = resolved_lookups(&gpos_cache, tag::LATN, None, &[tag::MARK, tag::KERN]);
+        assert_eq!(
+            lookups.unwrap(),
+            vec![(1, tag::MARK), (2, tag::MARK), (3, tag::KERN)]
+        );
+        assert!(resolved_lookups(&gpos_cache, tag::LATN, None, &[tag::DIST])
+            .unwrap()
+            .is_empty());
+    }
+}
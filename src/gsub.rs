@@ -16,25 +16,29 @@ use tinyvec::{tiny_vec, TinyVec};
 
 use crate::context::{ContextLookupHelper, Glyph, GlyphTable, MatchType};
 use crate::error::{ParseError, ShapingError};
+pub use crate::layout::gsub_closure;
 use crate::layout::{
-    chain_context_lookup_info, context_lookup_info, AlternateSet, AlternateSubst,
-    ChainContextLookup, ContextLookup, GDEFTable, LangSys, LayoutCache, LayoutTable, Ligature,
-    LigatureSubst, LookupCacheItem, LookupList, MultipleSubst, ReverseChainSingleSubst,
-    SequenceTable, SingleSubst, SubstLookup, GSUB,
+    add_feature_lookups, chain_context_lookup_info, context_lookup_info, AlternateSet,
+    AlternateSubst, ChainContextLookup, ContextLookup, GDEFTable, LangSys, LayoutCache,
+    LayoutTable, LazySubtables, Ligature, LigatureSubst, LookupCacheItem, LookupList,
+    MultipleSubst, ReverseChainSingleSubst, SequenceTable, SingleSubst, SubstLookup, GSUB,
 };
 use crate::scripts;
-use crate::scripts::ScriptType;
+use crate::scripts::{DefaultFeatures, ScriptType};
 use crate::tag;
+use crate::trace::{self, TraceEvent};
 use crate::unicode::VariationSelector;
 
 const SUBST_RECURSION_LIMIT: usize = 2;
 
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct FeatureInfo {
     pub feature_tag: u32,
     pub alternate: Option<usize>,
 }
 
 /// Type indicating the features to use when shaping text.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Features {
     /// A custom feature list.
     ///
@@ -49,6 +53,154 @@ pub enum Features {
     /// (gsub). When this variant of the `Features` enum is used some common features are enabled
     /// by default based on the script and language.
     Mask(GsubFeatureMask),
+    /// Features enabled by default, adjusted by a list of feature settings.
+    ///
+    /// The default features are the substitution features of the `DefaultFeatures` used to build
+    /// the plan, `GsubFeatureMask::default()` unless overridden, along with the features the
+    /// shaper for the script applies. Each setting then enables or disables a feature, or
+    /// selects an alternate. Later settings for the same feature take precedence over earlier
+    /// ones. Features required by the complex script shapers are always applied.
+    Settings(Vec<FeatureSetting>),
+}
+
+/// A setting for a single OpenType feature, for use with `Features::Settings`.
+///
+/// A `value` of `0` disables the feature and `1` enables it. For features that select from a
+/// set of alternates, such as `aalt` and `salt`, a value greater than zero selects the
+/// alternate with index `value - 1`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct FeatureSetting {
+    pub feature_tag: u32,
+    pub value: u32,
+}
+
+impl FeatureSetting {
+    /// Create a setting that enables `feature_tag`.
+    pub fn on(feature_tag: u32) -> Self {
+        FeatureSetting {
+            feature_tag,
+            value: 1,
+        }
+    }
+
+    /// Create a setting that disables `feature_tag`.
+    pub fn off(feature_tag: u32) -> Self {
+        FeatureSetting {
+            feature_tag,
+            value: 0,
+        }
+    }
+
+    /// Create a setting that selects alternate `index` of `feature_tag`.
+    pub fn alternate(feature_tag: u32, index: u32) -> Self {
+        FeatureSetting {
+            feature_tag,
+            value: index.saturating_add(1),
+        }
+    }
+}
+
+impl Features {
+    /// Returns the setting of `feature_tag` if it was explicitly supplied.
+    ///
+    /// For `Features::Custom` supplied features are reported as enabled.
+    pub fn setting(&self, feature_tag: u32) -> Option<u32> {
+        match self {
+            Features::Mask(_) => None,
+            Features::Custom(features_list) => features_list
+                .iter()
+                .find(|feature_info| feature_info.feature_tag == feature_tag)
+                .map(|_| 1),
+            Features::Settings(settings) => settings
+                .iter()
+                .rev()
+                .find(|setting| setting.feature_tag == feature_tag)
+                .map(|setting| setting.value),
+        }
+    }
+
+    /// Returns `true` if the feature `feature_tag` is enabled.
+    pub fn is_enabled(&self, feature_tag: u32) -> bool {
+        match self {
+            Features::Mask(feature_mask) => {
+                let mask = GsubFeatureMask::from_tag(feature_tag);
+                !mask.is_empty() && feature_mask.contains(mask)
+            }
+            Features::Custom(_) | Features::Settings(_) => {
+                self.setting(feature_tag).unwrap_or(0) != 0
+            }
+        }
+    }
+
+    /// Returns these features with `setting` applied on top of them.
+    ///
+    /// A feature mask is converted to the equivalent settings so that features without a mask
+    /// bit can be added, as these are applied for every script rather than only those using the
+    /// default shaper. Features that are supplied as a list have the feature added if it isn't
+    /// already present, unless `setting` disables it, in which case it's removed.
+    pub fn with_setting(&self, setting: FeatureSetting) -> Features {
+        match self {
+            Features::Mask(feature_mask) => {
+                let default_mask = GsubFeatureMask::default();
+                let mut settings = FEATURE_MASKS
+                    .iter()
+                    .filter(|(mask, _)| {
+                        feature_mask.contains(*mask) != default_mask.contains(*mask)
+                    })
+                    .map(|&(mask, feature_tag)| FeatureSetting {
+                        feature_tag,
+                        value: u32::from(feature_mask.contains(mask)),
+                    })
+                    .collect::<Vec<_>>();
+                settings.push(setting);
+                Features::Settings(settings)
+            }
+            Features::Custom(features_list) => {
+                let mut features_list = features_list
+                    .iter()
+                    .filter(|feature_info| feature_info.feature_tag != setting.feature_tag)
+                    .cloned()
+                    .collect::<Vec<_>>();
+                if setting.value != 0 {
+                    features_list.push(FeatureInfo {
+                        feature_tag: setting.feature_tag,
+                        alternate: Some(setting.value as usize - 1),
+                    });
+                }
+                Features::Custom(features_list)
+            }
+            Features::Settings(settings) => {
+                let mut settings = settings.clone();
+                settings.push(setting);
+                Features::Settings(settings)
+            }
+        }
+    }
+}
+
+/// Split `settings` into a feature mask, starting from `default_mask`, and a list of features not
+/// representable in the mask.
+fn resolve_settings(
+    default_mask: GsubFeatureMask,
+    settings: &[FeatureSetting],
+) -> (GsubFeatureMask, Vec<FeatureInfo>) {
+    let mut feature_mask = default_mask;
+    let mut features_list: Vec<FeatureInfo> = Vec::new();
+    for setting in settings {
+        let mask = GsubFeatureMask::from_tag(setting.feature_tag);
+        if !mask.is_empty() {
+            feature_mask.set(mask, setting.value != 0);
+            continue;
+        }
+        features_list.retain(|feature_info| feature_info.feature_tag != setting.feature_tag);
+        if setting.value != 0 {
+            features_list.push(FeatureInfo {
+                feature_tag: setting.feature_tag,
+                alternate: Some(setting.value as usize - 1),
+            });
+        }
+    }
+    (feature_mask, features_list)
 }
 
 type SubstContext<'a> = ContextLookupHelper<'a, GSUB>;
@@ -86,7 +238,13 @@ impl Ligature {
                 if match_type.match_glyph(opt_gdef_table, &glyphs[index]) {
                     matched += 1;
                     let mut matched_glyph = glyphs.remove(index);
-                    glyphs[i].unicodes.append(&mut matched_glyph.unicodes);
+                    // The extra glyphs of a multiple substitution have the characters of the
+                    // glyph they were split from, which the ligature may already have
+                    if !(matched_glyph.multi_subst_dup
+                        && glyphs[i].unicodes.ends_with(&matched_glyph.unicodes))
+                    {
+                        glyphs[i].unicodes.append(&mut matched_glyph.unicodes);
+                    }
                     glyphs[i].extra_data =
                         GlyphData::merge(glyphs[i].extra_data.clone(), matched_glyph.extra_data);
                 } else {
@@ -118,13 +276,42 @@ pub struct RawGlyph<T> {
     pub glyph_origin: GlyphOrigin,
     pub small_caps: bool,
     pub multi_subst_dup: bool,
+    /// Set on the glyphs a multiple substitution replaced a glyph with.
+    pub multi_subst_component: Option<MultipleSubstComponent>,
     pub is_vert_alt: bool,
     pub fake_bold: bool,
     pub fake_italic: bool,
+    /// Set on the components of a glyph decomposed by the `stch` feature.
+    pub stretch: Option<StretchPart>,
     pub variation: Option<VariationSelector>,
     pub extra_data: T,
 }
 
+/// The position of a glyph in the sequence of glyphs a multiple substitution replaced a glyph
+/// with.
+///
+/// All of the glyphs of the sequence have the characters of the glyph they replaced. The first
+/// glyph stands for them, while the rest are extra glyphs added by the substitution, such as marks
+/// split off a precomposed character, which also have `multi_subst_dup` set.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MultipleSubstComponent {
+    /// The index of the glyph in the sequence.
+    pub index: u16,
+    /// The number of glyphs in the sequence.
+    pub count: u16,
+}
+
+/// The role of a glyph produced by the `stch` (stretching glyph decomposition) feature.
+///
+/// Following the convention used by other shapers, the components of a decomposed glyph
+/// alternate between fixed and repeating parts, starting with a fixed part. Repeating parts
+/// are duplicated during positioning to span the required width.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StretchPart {
+    Fixed,
+    Repeating,
+}
+
 /// `merge` is called during ligature substitution (i.e. merging of glyphs),
 /// and determines how the `RawGlyph.extra_data` field should be merged
 pub trait GlyphData: Clone {
@@ -141,6 +328,25 @@ pub enum GlyphOrigin {
     Direct,
 }
 
+impl<T> RawGlyph<T> {
+    /// Returns the character the glyph was mapped from, or the character of the glyph it
+    /// replaced if it's the first glyph of a multiple substitution of such a glyph.
+    ///
+    /// This lets a character decomposed by a multiple substitution keep its properties, such as
+    /// its joining type, while the extra glyphs added by the substitution don't take them on.
+    pub fn source_char(&self) -> Option<char> {
+        match (self.glyph_origin, self.multi_subst_component) {
+            (GlyphOrigin::Char(ch), _) => Some(ch),
+            (GlyphOrigin::Direct, Some(component))
+                if component.index == 0 && !self.multi_subst_dup && self.unicodes.len() == 1 =>
+            {
+                Some(self.unicodes[0])
+            }
+            (GlyphOrigin::Direct, _) => None,
+        }
+    }
+}
+
 impl<T> Glyph for RawGlyph<T> {
     fn get_glyph_index(&self) -> u16 {
         self.glyph_index
@@ -156,7 +362,7 @@ pub fn gsub_feature_would_apply<T: GlyphData>(
     glyphs: &[RawGlyph<T>],
     i: usize,
 ) -> Result<bool, ParseError> {
-    if let Some(feature_table) = gsub_table.find_langsys_feature(langsys, feature_tag)? {
+    for feature_table in gsub_table.find_langsys_features(langsys, feature_tag)? {
         if let Some(ref lookup_list) = gsub_table.opt_lookup_list {
             for lookup_index in &feature_table.lookup_indices {
                 let lookup_index = usize::from(*lookup_index);
@@ -176,7 +382,7 @@ pub fn gsub_lookup_would_apply<T: GlyphData>(
     glyphs: &[RawGlyph<T>],
     i: usize,
 ) -> Result<bool, ParseError> {
-    let match_type = MatchType::from_lookup_flag(lookup.lookup_flag);
+    let match_type = MatchType::from_lookup_flag(lookup.lookup_flag, lookup.mark_filtering_set);
     if i < glyphs.len() && match_type.match_glyph(opt_gdef_table, &glyphs[i]) {
         return match lookup.lookup_subtables {
             SubstLookup::SingleSubst(ref subtables) => {
@@ -253,7 +459,18 @@ pub fn gsub_apply_lookup<T: GlyphData>(
 ) -> Result<usize, ParseError> {
     if let Some(ref lookup_list) = gsub_table.opt_lookup_list {
         let lookup = lookup_list.lookup_cache_gsub(gsub_cache, lookup_index)?;
-        let match_type = MatchType::from_lookup_flag(lookup.lookup_flag);
+        let glyph_indices = |glyphs: &[RawGlyph<T>]| {
+            glyphs
+                .iter()
+                .map(|glyph| glyph.glyph_index)
+                .collect::<Vec<_>>()
+        };
+        let opt_before = if trace::is_enabled() {
+            Some(glyph_indices(&glyphs[start..(start + length)]))
+        } else {
+            None
+        };
+        let match_type = MatchType::from_lookup_flag(lookup.lookup_flag, lookup.mark_filtering_set);
         match lookup.lookup_subtables {
             SubstLookup::SingleSubst(ref subtables) => {
                 for glyph in glyphs[start..(start + length)].iter_mut() {
@@ -363,12 +580,22 @@ pub fn gsub_apply_lookup<T: GlyphData>(
                 }
             }
         }
+        if let Some(before) = opt_before {
+            trace::record(TraceEvent::Substitution {
+                lookup_index,
+                lookup_type: lookup.lookup_subtables.lookup_type(),
+                feature_tag,
+                start,
+                before,
+                after: glyph_indices(&glyphs[start..(start + length)]),
+            });
+        }
     }
     Ok(length)
 }
 
 fn singlesubst_would_apply<T: GlyphData>(
-    subtables: &[SingleSubst],
+    subtables: &LazySubtables<GSUB, SingleSubst>,
     glyph: &RawGlyph<T>,
 ) -> Result<Option<u16>, ParseError> {
     let glyph_index = glyph.glyph_index;
@@ -381,7 +608,7 @@ fn singlesubst_would_apply<T: GlyphData>(
 }
 
 fn singlesubst<T: GlyphData>(
-    subtables: &[SingleSubst],
+    subtables: &LazySubtables<GSUB, SingleSubst>,
     subst_tag: u32,
     glyph: &mut RawGlyph<T>,
 ) -> Result<(), ParseError> {
@@ -396,7 +623,7 @@ fn singlesubst<T: GlyphData>(
 }
 
 fn multiplesubst_would_apply<'a, T: GlyphData>(
-    subtables: &'a [MultipleSubst],
+    subtables: &'a LazySubtables<GSUB, MultipleSubst>,
     i: usize,
     glyphs: &[RawGlyph<T>],
 ) -> Result<Option<&'a SequenceTable>, ParseError> {
@@ -410,7 +637,7 @@ fn multiplesubst_would_apply<'a, T: GlyphData>(
 }
 
 fn multiplesubst<T: GlyphData>(
-    subtables: &[MultipleSubst],
+    subtables: &LazySubtables<GSUB, MultipleSubst>,
     i: usize,
     glyphs: &mut Vec<RawGlyph<T>>,
 ) -> Result<Option<usize>, ParseError> {
@@ -418,8 +645,10 @@ fn multiplesubst<T: GlyphData>(
         Some(sequence_table) => {
             if sequence_table.substitute_glyphs.len() > 0 {
                 let first_glyph_index = sequence_table.substitute_glyphs[0];
+                let count = sequence_table.substitute_glyphs.len() as u16;
                 glyphs[i].glyph_index = first_glyph_index;
                 glyphs[i].glyph_origin = GlyphOrigin::Direct;
+                glyphs[i].multi_subst_component = Some(MultipleSubstComponent { index: 0, count });
                 for j in 1..sequence_table.substitute_glyphs.len() {
                     let output_glyph_index = sequence_table.substitute_glyphs[j];
                     let glyph = RawGlyph {
@@ -429,9 +658,14 @@ fn multiplesubst<T: GlyphData>(
                         glyph_origin: GlyphOrigin::Direct,
                         small_caps: glyphs[i].small_caps,
                         multi_subst_dup: true,
+                        multi_subst_component: Some(MultipleSubstComponent {
+                            index: j as u16,
+                            count,
+                        }),
                         is_vert_alt: glyphs[i].is_vert_alt,
                         fake_bold: glyphs[i].fake_bold,
                         fake_italic: glyphs[i].fake_italic,
+                        stretch: glyphs[i].stretch,
                         extra_data: glyphs[i].extra_data.clone(),
                         variation: glyphs[i].variation,
                     };
@@ -449,7 +683,7 @@ fn multiplesubst<T: GlyphData>(
 }
 
 fn alternatesubst_would_apply<'a, T: GlyphData>(
-    subtables: &'a [AlternateSubst],
+    subtables: &'a LazySubtables<GSUB, AlternateSubst>,
     glyph: &RawGlyph<T>,
 ) -> Result<Option<&'a AlternateSet>, ParseError> {
     let glyph_index = glyph.glyph_index;
@@ -462,12 +696,11 @@ fn alternatesubst_would_apply<'a, T: GlyphData>(
 }
 
 fn alternatesubst<T: GlyphData>(
-    subtables: &[AlternateSubst],
+    subtables: &LazySubtables<GSUB, AlternateSubst>,
     alternate: usize,
     glyph: &mut RawGlyph<T>,
 ) -> Result<(), ParseError> {
     if let Some(alternateset) = alternatesubst_would_apply(subtables, glyph)? {
-        // TODO allow users to specify which alternate glyph they want
         if alternate < alternateset.alternate_glyphs.len() {
             glyph.glyph_index = alternateset.alternate_glyphs[alternate];
             glyph.glyph_origin = GlyphOrigin::Direct;
@@ -478,7 +711,7 @@ fn alternatesubst<T: GlyphData>(
 
 fn ligaturesubst_would_apply<'a, T: GlyphData>(
     opt_gdef_table: Option<&GDEFTable>,
-    subtables: &'a [LigatureSubst],
+    subtables: &'a LazySubtables<GSUB, LigatureSubst>,
     match_type: MatchType,
     i: usize,
     glyphs: &[RawGlyph<T>],
@@ -498,7 +731,7 @@ fn ligaturesubst_would_apply<'a, T: GlyphData>(
 
 fn ligaturesubst<T: GlyphData>(
     opt_gdef_table: Option<&GDEFTable>,
-    subtables: &[LigatureSubst],
+    subtables: &LazySubtables<GSUB, LigatureSubst>,
     match_type: MatchType,
     i: usize,
     glyphs: &mut Vec<RawGlyph<T>>,
@@ -514,7 +747,7 @@ fn ligaturesubst<T: GlyphData>(
 
 fn contextsubst_would_apply<'a, T: GlyphData>(
     opt_gdef_table: Option<&GDEFTable>,
-    subtables: &'a [ContextLookup<GSUB>],
+    subtables: &'a LazySubtables<GSUB, ContextLookup<GSUB>>,
     match_type: MatchType,
     i: usize,
     glyphs: &[RawGlyph<T>],
@@ -535,7 +768,7 @@ fn contextsubst<'a, T: GlyphData>(
     gsub_cache: &LayoutCache<GSUB>,
     lookup_list: &LookupList<GSUB>,
     opt_gdef_table: Option<&GDEFTable>,
-    subtables: &[ContextLookup<GSUB>],
+    subtables: &LazySubtables<GSUB, ContextLookup<GSUB>>,
     feature_tag: u32,
     match_type: MatchType,
     i: usize,
@@ -559,7 +792,7 @@ fn contextsubst<'a, T: GlyphData>(
 
 fn chaincontextsubst_would_apply<'a, T: GlyphData>(
     opt_gdef_table: Option<&GDEFTable>,
-    subtables: &'a [ChainContextLookup<GSUB>],
+    subtables: &'a LazySubtables<GSUB, ChainContextLookup<GSUB>>,
     match_type: MatchType,
     i: usize,
     glyphs: &[RawGlyph<T>],
@@ -582,7 +815,7 @@ fn chaincontextsubst<'a, T: GlyphData>(
     gsub_cache: &LayoutCache<GSUB>,
     lookup_list: &LookupList<GSUB>,
     opt_gdef_table: Option<&GDEFTable>,
-    subtables: &[ChainContextLookup<GSUB>],
+    subtables: &LazySubtables<GSUB, ChainContextLookup<GSUB>>,
     feature_tag: u32,
     match_type: MatchType,
     i: usize,
@@ -606,7 +839,7 @@ fn chaincontextsubst<'a, T: GlyphData>(
 
 fn reversechainsinglesubst_would_apply<T: GlyphData>(
     opt_gdef_table: Option<&GDEFTable>,
-    subtables: &[ReverseChainSingleSubst],
+    subtables: &LazySubtables<GSUB, ReverseChainSingleSubst>,
     match_type: MatchType,
     i: usize,
     glyphs: &[RawGlyph<T>],
@@ -626,7 +859,7 @@ fn reversechainsinglesubst_would_apply<T: GlyphData>(
 
 fn reversechainsinglesubst<T: GlyphData>(
     opt_gdef_table: Option<&GDEFTable>,
-    subtables: &[ReverseChainSingleSubst],
+    subtables: &LazySubtables<GSUB, ReverseChainSingleSubst>,
     match_type: MatchType,
     i: usize,
     glyphs: &mut [RawGlyph<T>],
@@ -705,7 +938,7 @@ fn apply_subst<'a, T: GlyphData>(
     index: usize,
 ) -> Result<Option<isize>, ParseError> {
     let lookup = lookup_list.lookup_cache_gsub(gsub_cache, lookup_index)?;
-    let match_type = MatchType::from_lookup_flag(lookup.lookup_flag);
+    let match_type = MatchType::from_lookup_flag(lookup.lookup_flag, lookup.mark_filtering_set);
     let i = match parent_match_type.find_nth(opt_gdef_table, glyphs, index, subst_index) {
         Some(index1) => index1,
         None => return Ok(None), // FIXME error?
@@ -783,13 +1016,7 @@ fn build_lookups_custom(
 ) -> Result<BTreeMap<usize, u32>, ParseError> {
     let mut lookups = BTreeMap::new();
     for feature_info in feature_tags {
-        if let Some(feature_table) =
-            gsub_table.find_langsys_feature(langsys, feature_info.feature_tag)?
-        {
-            for lookup_index in &feature_table.lookup_indices {
-                lookups.insert(usize::from(*lookup_index), feature_info.feature_tag);
-            }
-        }
+        add_feature_lookups(gsub_table, langsys, feature_info.feature_tag, &mut lookups)?;
     }
     Ok(lookups)
 }
@@ -802,17 +1029,9 @@ fn build_lookups_default(
     let mut lookups = BTreeMap::new();
     for (feature_mask, feature_tag) in FEATURE_MASKS {
         if feature_masks.contains(*feature_mask) {
-            if let Some(feature_table) = gsub_table.find_langsys_feature(langsys, *feature_tag)? {
-                for lookup_index in &feature_table.lookup_indices {
-                    lookups.insert(usize::from(*lookup_index), *feature_tag);
-                }
-            } else if *feature_tag == tag::VRT2 {
-                let vert_tag = tag::VERT;
-                if let Some(feature_table) = gsub_table.find_langsys_feature(langsys, vert_tag)? {
-                    for lookup_index in &feature_table.lookup_indices {
-                        lookups.insert(usize::from(*lookup_index), vert_tag);
-                    }
-                }
+            let found = add_feature_lookups(gsub_table, langsys, *feature_tag, &mut lookups)?;
+            if !found && *feature_tag == tag::VRT2 {
+                add_feature_lookups(gsub_table, langsys, tag::VERT, &mut lookups)?;
             }
         }
     }
@@ -833,7 +1052,7 @@ fn make_supported_features_mask(
     Ok(feature_mask)
 }
 
-fn lang_tag_key(opt_lang_tag: Option<u32>) -> u32 {
+pub(crate) fn lang_tag_key(opt_lang_tag: Option<u32>) -> u32 {
     // `DFLT` is not a valid lang tag so we use it to indicate the default
     opt_lang_tag.unwrap_or(tag::DFLT)
 }
@@ -843,11 +1062,13 @@ fn get_supported_features(
     script_tag: u32,
     opt_lang_tag: Option<u32>,
 ) -> Result<GsubFeatureMask, ParseError> {
-    let feature_mask = match gsub_cache
-        .supported_features
-        .borrow_mut()
-        .entry((script_tag, lang_tag_key(opt_lang_tag)))
-    {
+    let key = (script_tag, lang_tag_key(opt_lang_tag));
+    if let Some(&bits) = gsub_cache.supported_features.read().unwrap().get(&key) {
+        return Ok(GsubFeatureMask::from_bits_truncate(bits));
+    }
+    // Another thread may have added the entry between releasing the read lock and acquiring the
+    // write lock, which `entry` accounts for
+    let feature_mask = match gsub_cache.supported_features.write().unwrap().entry(key) {
         Entry::Occupied(entry) => GsubFeatureMask::from_bits_truncate(*entry.get()),
         Entry::Vacant(entry) => {
             let gsub_table = &gsub_cache.layout_table;
@@ -892,7 +1113,7 @@ fn find_alternate(features_list: &[FeatureInfo], feature_tag: u32) -> Option<usi
 /// ```
 /// use std::convert::TryFrom;
 /// use std::error::Error;
-/// use std::rc::Rc;
+/// use std::sync::Arc;
 ///
 /// use allsorts::binary::read::ReadScope;
 /// use allsorts::error::ParseError;
@@ -923,7 +1144,7 @@ fn find_alternate(features_list: &[FeatureInfo], feature_tag: u32) -> Option<usi
 ///     let opt_gsub_cache = font.gsub_cache()?;
 ///     let opt_gpos_cache = font.gpos_cache()?;
 ///     let opt_gdef_table = font.gdef_table()?;
-///     let opt_gdef_table = opt_gdef_table.as_ref().map(Rc::as_ref);
+///     let opt_gdef_table = opt_gdef_table.as_ref().map(Arc::as_ref);
 ///
 ///     // Map glyphs
 ///     //
@@ -951,9 +1172,11 @@ fn find_alternate(features_list: &[FeatureInfo], feature_tag: u32) -> Option<usi
 ///                     glyph_origin: GlyphOrigin::Char(ch),
 ///                     small_caps: false,
 ///                     multi_subst_dup: false,
+///                     multi_subst_component: None,
 ///                     is_vert_alt: false,
 ///                     fake_bold: false,
 ///                     fake_italic: false,
+///                     stretch: None,
 ///                     extra_data: (),
 ///                     variation: Some(used_variation),
 ///                 };
@@ -1005,74 +1228,181 @@ pub fn apply(
     num_glyphs: u16,
     glyphs: &mut Vec<RawGlyph<()>>,
 ) -> Result<(), ShapingError> {
-    match features {
-        Features::Custom(features_list) => gsub_apply_custom(
+    let plan = GsubPlan::new(gsub_cache, script_tag, opt_lang_tag, features)?;
+    apply_plan(
+        dotted_circle_index,
+        gsub_cache,
+        opt_gdef_table,
+        &plan,
+        num_glyphs,
+        glyphs,
+    )
+}
+
+/// The lookups applied by `apply` for a script, language and set of features.
+///
+/// Building a plan resolves the features against the language system of the font once, so that
+/// it can be reused to substitute many runs of text with `apply_plan`. A plan is only valid for
+/// the `gsub_cache` it was built from.
+#[derive(Debug)]
+pub struct GsubPlan {
+    script_tag: u32,
+    opt_lang_tag: Option<u32>,
+    /// The features enabled by mask, `None` for `Features::Custom`.
+    opt_feature_mask: Option<GsubFeatureMask>,
+    /// The lookups applied by the default shaper. Empty for scripts with a complex shaper, which
+    /// resolve their lookups per stage.
+    default_lookups: Vec<(usize, u32)>,
+    /// The lookups applied to fractions when `frac` is enabled and supported.
+    opt_frac_lookups: Option<Vec<(usize, u32)>>,
+    /// Features not representable in the mask, applied after the masked features.
+    features_list: Vec<FeatureInfo>,
+    custom_lookups: Vec<(usize, u32)>,
+}
+
+impl GsubPlan {
+    pub fn new(
+        gsub_cache: &LayoutCache<GSUB>,
+        script_tag: u32,
+        opt_lang_tag: Option<u32>,
+        features: &Features,
+    ) -> Result<GsubPlan, ParseError> {
+        GsubPlan::with_default_features(
             gsub_cache,
-            opt_gdef_table,
+            &DefaultFeatures::new(),
+            script_tag,
+            opt_lang_tag,
+            features,
+        )
+    }
+
+    /// Build a plan like `new`, resolving `Features::Settings` against the substitution features
+    /// of `default_features` for `script_tag` instead of the built-in ones.
+    pub fn with_default_features(
+        gsub_cache: &LayoutCache<GSUB>,
+        default_features: &DefaultFeatures,
+        script_tag: u32,
+        opt_lang_tag: Option<u32>,
+        features: &Features,
+    ) -> Result<GsubPlan, ParseError> {
+        let (opt_feature_mask, features_list) = match features {
+            Features::Custom(features_list) => (None, features_list.clone()),
+            Features::Mask(feature_mask) => (Some(*feature_mask), Vec::new()),
+            Features::Settings(settings) => {
+                let default_mask = default_features.get(script_tag).gsub;
+                let (feature_mask, features_list) = resolve_settings(default_mask, settings);
+                (Some(feature_mask), features_list)
+            }
+        };
+
+        let mut default_lookups = Vec::new();
+        let mut opt_frac_lookups = None;
+        if let Some(mut feature_mask) = opt_feature_mask {
+            if ScriptType::from(script_tag) == ScriptType::Default {
+                feature_mask &= get_supported_features(gsub_cache, script_tag, opt_lang_tag)?;
+                if feature_mask.contains(GsubFeatureMask::FRAC) {
+                    opt_frac_lookups = Some(resolved_lookups(
+                        gsub_cache,
+                        script_tag,
+                        opt_lang_tag,
+                        feature_mask,
+                    )?);
+                    feature_mask.remove(GsubFeatureMask::FRAC);
+                }
+                default_lookups =
+                    resolved_lookups(gsub_cache, script_tag, opt_lang_tag, feature_mask)?;
+            }
+        }
+
+        let mut custom_lookups = Vec::new();
+        if !features_list.is_empty() {
+            let gsub_table = &gsub_cache.layout_table;
+            if let Some(script) = gsub_table.find_script_or_default(script_tag)? {
+                if let Some(langsys) = script.find_langsys_or_default(opt_lang_tag)? {
+                    // note: iter() returns sorted by key
+                    custom_lookups = build_lookups_custom(gsub_table, langsys, &features_list)?
+                        .into_iter()
+                        .collect();
+                }
+            }
+        }
+
+        Ok(GsubPlan {
             script_tag,
             opt_lang_tag,
+            opt_feature_mask,
+            default_lookups,
+            opt_frac_lookups,
             features_list,
-            num_glyphs,
-            glyphs,
-        ),
-        Features::Mask(feature_mask) => gsub_apply_default(
+            custom_lookups,
+        })
+    }
+}
+
+/// Perform glyph substitution according to a plan built with `GsubPlan::new`.
+///
+/// This is equivalent to calling `apply` with the script, language and features of the plan.
+pub fn apply_plan(
+    dotted_circle_index: u16,
+    gsub_cache: &LayoutCache<GSUB>,
+    opt_gdef_table: Option<&GDEFTable>,
+    plan: &GsubPlan,
+    num_glyphs: u16,
+    glyphs: &mut Vec<RawGlyph<()>>,
+) -> Result<(), ShapingError> {
+    if let Some(feature_mask) = plan.opt_feature_mask {
+        gsub_apply_default(
             dotted_circle_index,
             gsub_cache,
             opt_gdef_table,
-            script_tag,
-            opt_lang_tag,
-            *feature_mask,
+            plan,
+            feature_mask,
             num_glyphs,
             glyphs,
-        ),
+        )?;
+        if plan.features_list.is_empty() {
+            return Ok(());
+        }
     }
+    gsub_apply_custom(gsub_cache, opt_gdef_table, plan, num_glyphs, glyphs)
 }
 
 fn gsub_apply_custom(
     gsub_cache: &LayoutCache<GSUB>,
     opt_gdef_table: Option<&GDEFTable>,
-    script_tag: u32,
-    opt_lang_tag: Option<u32>,
-    features_list: &[FeatureInfo],
+    plan: &GsubPlan,
     num_glyphs: u16,
     glyphs: &mut Vec<RawGlyph<()>>,
 ) -> Result<(), ShapingError> {
     let gsub_table = &gsub_cache.layout_table;
-    if let Some(script) = gsub_table.find_script_or_default(script_tag)? {
-        if let Some(langsys) = script.find_langsys_or_default(opt_lang_tag)? {
-            let lookups = build_lookups_custom(gsub_table, langsys, features_list)?;
-
-            // note: iter() returns sorted by key
-            for (lookup_index, feature_tag) in lookups {
-                let alternate = find_alternate(features_list, feature_tag);
-                if feature_tag == tag::FINA && glyphs.len() > 0 {
-                    gsub_apply_lookup(
-                        gsub_cache,
-                        gsub_table,
-                        opt_gdef_table,
-                        lookup_index,
-                        feature_tag,
-                        alternate,
-                        glyphs,
-                        glyphs.len() - 1,
-                        1,
-                        |_| true,
-                    )?;
-                } else {
-                    gsub_apply_lookup(
-                        gsub_cache,
-                        gsub_table,
-                        opt_gdef_table,
-                        lookup_index,
-                        feature_tag,
-                        alternate,
-                        glyphs,
-                        0,
-                        glyphs.len(),
-                        |_| true,
-                    )?;
-                }
-            }
+    for &(lookup_index, feature_tag) in &plan.custom_lookups {
+        let alternate = find_alternate(&plan.features_list, feature_tag);
+        if feature_tag == tag::FINA && glyphs.len() > 0 {
+            gsub_apply_lookup(
+                gsub_cache,
+                gsub_table,
+                opt_gdef_table,
+                lookup_index,
+                feature_tag,
+                alternate,
+                glyphs,
+                glyphs.len() - 1,
+                1,
+                |_| true,
+            )?;
+        } else {
+            gsub_apply_lookup(
+                gsub_cache,
+                gsub_table,
+                opt_gdef_table,
+                lookup_index,
+                feature_tag,
+                alternate,
+                glyphs,
+                0,
+                glyphs.len(),
+                |_| true,
+            )?;
         }
     }
     replace_missing_glyphs(glyphs, num_glyphs);
@@ -1088,6 +1418,7 @@ pub fn replace_missing_glyphs<T: GlyphData>(glyphs: &mut Vec<RawGlyph<T>>, num_g
             glyph.glyph_origin = GlyphOrigin::Direct;
             glyph.small_caps = false;
             glyph.multi_subst_dup = false;
+            glyph.multi_subst_component = None;
             glyph.is_vert_alt = false;
             glyph.fake_bold = false;
             glyph.fake_italic = false;
@@ -1154,6 +1485,11 @@ bitflags! {
         const VATU = 1 << 42;
         const VRT2_OR_VERT = 1 << 43;
         const ZERO = 1 << 44;
+        const STCH = 1 << 45;
+        const LJMO = 1 << 46;
+        const VJMO = 1 << 47;
+        const TJMO = 1 << 48;
+        const RTLM = 1 << 49;
     }
 }
 
@@ -1181,6 +1517,7 @@ const FEATURE_MASKS: &[(GsubFeatureMask, u32)] = &[
     (GsubFeatureMask::INIT, tag::INIT),
     (GsubFeatureMask::ISOL, tag::ISOL),
     (GsubFeatureMask::LIGA, tag::LIGA),
+    (GsubFeatureMask::LJMO, tag::LJMO),
     (GsubFeatureMask::LNUM, tag::LNUM),
     (GsubFeatureMask::LOCL, tag::LOCL),
     (GsubFeatureMask::MEDI, tag::MEDI),
@@ -1198,9 +1535,13 @@ const FEATURE_MASKS: &[(GsubFeatureMask, u32)] = &[
     (GsubFeatureMask::RKRF, tag::RKRF),
     (GsubFeatureMask::RLIG, tag::RLIG),
     (GsubFeatureMask::RPHF, tag::RPHF),
+    (GsubFeatureMask::RTLM, tag::RTLM),
     (GsubFeatureMask::SMCP, tag::SMCP),
+    (GsubFeatureMask::STCH, tag::STCH),
+    (GsubFeatureMask::TJMO, tag::TJMO),
     (GsubFeatureMask::TNUM, tag::TNUM),
     (GsubFeatureMask::VATU, tag::VATU),
+    (GsubFeatureMask::VJMO, tag::VJMO),
     (GsubFeatureMask::VRT2_OR_VERT, tag::VRT2),
     (GsubFeatureMask::ZERO, tag::ZERO),
 ];
@@ -1231,6 +1572,7 @@ impl GsubFeatureMask {
             tag::INIT => GsubFeatureMask::INIT,
             tag::ISOL => GsubFeatureMask::ISOL,
             tag::LIGA => GsubFeatureMask::LIGA,
+            tag::LJMO => GsubFeatureMask::LJMO,
             tag::LNUM => GsubFeatureMask::LNUM,
             tag::LOCL => GsubFeatureMask::LOCL,
             tag::MEDI => GsubFeatureMask::MEDI,
@@ -1248,10 +1590,14 @@ impl GsubFeatureMask {
             tag::RKRF => GsubFeatureMask::RKRF,
             tag::RLIG => GsubFeatureMask::RLIG,
             tag::RPHF => GsubFeatureMask::RPHF,
+            tag::RTLM => GsubFeatureMask::RTLM,
             tag::SMCP => GsubFeatureMask::SMCP,
+            tag::STCH => GsubFeatureMask::STCH,
+            tag::TJMO => GsubFeatureMask::TJMO,
             tag::TNUM => GsubFeatureMask::TNUM,
             tag::VATU => GsubFeatureMask::VATU,
             tag::VERT => GsubFeatureMask::VRT2_OR_VERT,
+            tag::VJMO => GsubFeatureMask::VJMO,
             tag::VRT2 => GsubFeatureMask::VRT2_OR_VERT,
             tag::ZERO => GsubFeatureMask::ZERO,
             _ => GsubFeatureMask::empty(),
@@ -1286,19 +1632,20 @@ pub fn get_lookups_cache_index(
     opt_lang_tag: Option<u32>,
     feature_mask: GsubFeatureMask,
 ) -> Result<usize, ParseError> {
-    let index = match gsub_cache.lookups_index.borrow_mut().entry((
-        script_tag,
-        lang_tag_key(opt_lang_tag),
-        feature_mask.bits(),
-    )) {
+    let key = (script_tag, lang_tag_key(opt_lang_tag), feature_mask.bits());
+    if let Some(&index) = gsub_cache.lookups_index.read().unwrap().get(&key) {
+        return Ok(index);
+    }
+    let index = match gsub_cache.lookups_index.write().unwrap().entry(key) {
         Entry::Occupied(entry) => *entry.get(),
         Entry::Vacant(entry) => {
             let gsub_table = &gsub_cache.layout_table;
             if let Some(script) = gsub_table.find_script_or_default(script_tag)? {
                 if let Some(langsys) = script.find_langsys_or_default(opt_lang_tag)? {
                     let lookups = build_lookups_default(gsub_table, langsys, feature_mask)?;
-                    let index = gsub_cache.cached_lookups.borrow().len();
-                    gsub_cache.cached_lookups.borrow_mut().push(lookups);
+                    let mut cached_lookups = gsub_cache.cached_lookups.write().unwrap();
+                    let index = cached_lookups.len();
+                    cached_lookups.push(lookups);
                     *entry.insert(index)
                 } else {
                     *entry.insert(0)
@@ -1311,17 +1658,144 @@ pub fn get_lookups_cache_index(
     Ok(index)
 }
 
-fn gsub_apply_default(
-    dotted_circle_index: u16,
+/// Returns the lookups applied for `feature_mask` in the supplied script and language.
+///
+/// Each entry is a lookup index paired with the tag of the feature that enabled it. Entries are
+/// in the order they are applied, which is ascending lookup index, and each lookup appears once
+/// regardless of how many features reference it.
+pub fn resolved_lookups(
+    gsub_cache: &LayoutCache<GSUB>,
+    script_tag: u32,
+    opt_lang_tag: Option<u32>,
+    feature_mask: GsubFeatureMask,
+) -> Result<Vec<(usize, u32)>, ParseError> {
+    let index = get_lookups_cache_index(gsub_cache, script_tag, opt_lang_tag, feature_mask)?;
+    let lookups = gsub_cache.cached_lookups.read().unwrap()[index].clone();
+    Ok(lookups)
+}
+
+/// Returns the alternates of `glyph_index` offered by `feature_tag` in the supplied script and
+/// language.
+///
+/// This is intended for features such as `aalt`, `salt`, and `ssXX` that substitute a glyph
+/// with one of a set of alternates. The alternates come from the first lookup of the feature
+/// that covers the glyph, so the position of a glyph in the returned list is the alternate
+/// index to supply via `FeatureInfo::alternate` or `FeatureSetting::alternate` to select it
+/// when shaping. Single substitutions offer one alternate. An empty list is returned if the
+/// feature does not substitute the glyph.
+pub fn glyph_alternates(
+    gsub_cache: &LayoutCache<GSUB>,
+    script_tag: u32,
+    opt_lang_tag: Option<u32>,
+    feature_tag: u32,
+    glyph_index: u16,
+) -> Result<Vec<u16>, ParseError> {
+    let gsub_table = &gsub_cache.layout_table;
+    let langsys = match gsub_table.find_script_or_default(script_tag)? {
+        Some(script) => match script.find_langsys_or_default(opt_lang_tag)? {
+            Some(langsys) => langsys,
+            None => return Ok(Vec::new()),
+        },
+        None => return Ok(Vec::new()),
+    };
+    let lookup_list = match gsub_table.opt_lookup_list {
+        Some(ref lookup_list) => lookup_list,
+        None => return Ok(Vec::new()),
+    };
+
+    let mut lookups = BTreeMap::new();
+    add_feature_lookups(gsub_table, langsys, feature_tag, &mut lookups)?;
+    for lookup_index in lookups.keys() {
+        let lookup = lookup_list.lookup_cache_gsub(gsub_cache, *lookup_index)?;
+        match lookup.lookup_subtables {
+            SubstLookup::SingleSubst(ref subtables) => {
+                for single_subst in subtables {
+                    if let Some(output_glyph) = single_subst.apply_glyph(glyph_index)? {
+                        return Ok(vec![output_glyph]);
+                    }
+                }
+            }
+            SubstLookup::AlternateSubst(ref subtables) => {
+                for alternate_subst in subtables {
+                    if let Some(alternate_set) = alternate_subst.apply_glyph(glyph_index)? {
+                        return Ok(alternate_set.alternate_glyphs.clone());
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(Vec::new())
+}
+
+/// Apply the `stch` feature to the glyphs matching `pred`, marking the decomposed components.
+///
+/// Each matching glyph is decomposed in isolation so that the components of each decomposition
+/// can be labelled as either fixed or repeating parts.
+pub(crate) fn gsub_apply_stch<T: GlyphData>(
     gsub_cache: &LayoutCache<GSUB>,
+    gsub_table: &LayoutTable<GSUB>,
     opt_gdef_table: Option<&GDEFTable>,
     script_tag: u32,
     opt_lang_tag: Option<u32>,
-    mut feature_mask: GsubFeatureMask,
+    glyphs: &mut Vec<RawGlyph<T>>,
+    pred: impl Fn(&RawGlyph<T>) -> bool,
+) -> Result<(), ParseError> {
+    let index =
+        get_lookups_cache_index(gsub_cache, script_tag, opt_lang_tag, GsubFeatureMask::STCH)?;
+    let lookups = gsub_cache.cached_lookups.read().unwrap()[index].clone();
+    if lookups.is_empty() {
+        return Ok(());
+    }
+
+    // Iterate in reverse so that decompositions don't disturb the indices yet to be visited
+    for i in (0..glyphs.len()).rev() {
+        if !pred(&glyphs[i]) {
+            continue;
+        }
+        let mut length = 1;
+        for &(lookup_index, feature_tag) in &lookups {
+            if length == 0 {
+                break;
+            }
+            length = gsub_apply_lookup(
+                gsub_cache,
+                gsub_table,
+                opt_gdef_table,
+                lookup_index,
+                feature_tag,
+                None,
+                glyphs,
+                i,
+                length,
+                |_| true,
+            )?;
+        }
+        if length > 1 {
+            for (component, glyph) in glyphs[i..i + length].iter_mut().enumerate() {
+                glyph.stretch = Some(if component % 2 == 1 {
+                    StretchPart::Repeating
+                } else {
+                    StretchPart::Fixed
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn gsub_apply_default(
+    dotted_circle_index: u16,
+    gsub_cache: &LayoutCache<GSUB>,
+    opt_gdef_table: Option<&GDEFTable>,
+    plan: &GsubPlan,
+    feature_mask: GsubFeatureMask,
     num_glyphs: u16,
     glyphs: &mut Vec<RawGlyph<()>>,
 ) -> Result<(), ShapingError> {
     let gsub_table = &gsub_cache.layout_table;
+    let (script_tag, opt_lang_tag) = (plan.script_tag, plan.opt_lang_tag);
     match ScriptType::from(script_tag) {
         ScriptType::Arabic => scripts::arabic::gsub_apply_arabic(
             gsub_cache,
@@ -1331,6 +1805,24 @@ fn gsub_apply_default(
             opt_lang_tag,
             glyphs,
         )?,
+        ScriptType::Hangul => scripts::hangul::gsub_apply_hangul(
+            gsub_cache,
+            gsub_table,
+            opt_gdef_table,
+            script_tag,
+            opt_lang_tag,
+            feature_mask,
+            glyphs,
+        )?,
+        ScriptType::Hebrew => scripts::hebrew::gsub_apply_hebrew(
+            gsub_cache,
+            gsub_table,
+            opt_gdef_table,
+            script_tag,
+            opt_lang_tag,
+            feature_mask,
+            glyphs,
+        )?,
         ScriptType::Indic => scripts::indic::gsub_apply_indic(
             dotted_circle_index,
             gsub_cache,
@@ -1340,6 +1832,23 @@ fn gsub_apply_default(
             opt_lang_tag,
             glyphs,
         )?,
+        ScriptType::Khmer => scripts::khmer::gsub_apply_khmer(
+            dotted_circle_index,
+            gsub_cache,
+            gsub_table,
+            opt_gdef_table,
+            script_tag,
+            opt_lang_tag,
+            glyphs,
+        )?,
+        ScriptType::Myanmar => scripts::myanmar::gsub_apply_myanmar(
+            dotted_circle_index,
+            gsub_cache,
+            gsub_table,
+            opt_gdef_table,
+            opt_lang_tag,
+            glyphs,
+        )?,
         ScriptType::Syriac => scripts::syriac::gsub_apply_syriac(
             gsub_cache,
             gsub_table,
@@ -1348,31 +1857,23 @@ fn gsub_apply_default(
             opt_lang_tag,
             glyphs,
         )?,
-        ScriptType::Default => {
-            feature_mask &= get_supported_features(gsub_cache, script_tag, opt_lang_tag)?;
-            if feature_mask.contains(GsubFeatureMask::FRAC) {
-                let index_frac =
-                    get_lookups_cache_index(gsub_cache, script_tag, opt_lang_tag, feature_mask)?;
-                feature_mask.remove(GsubFeatureMask::FRAC);
-                let index =
-                    get_lookups_cache_index(gsub_cache, script_tag, opt_lang_tag, feature_mask)?;
-                let lookups = &gsub_cache.cached_lookups.borrow()[index];
-                let lookups_frac = &gsub_cache.cached_lookups.borrow()[index_frac];
-                gsub_apply_lookups_frac(
-                    gsub_cache,
-                    gsub_table,
-                    opt_gdef_table,
-                    lookups,
-                    lookups_frac,
-                    glyphs,
-                )?;
-            } else {
-                let index =
-                    get_lookups_cache_index(gsub_cache, script_tag, opt_lang_tag, feature_mask)?;
-                let lookups = &gsub_cache.cached_lookups.borrow()[index];
-                gsub_apply_lookups(gsub_cache, gsub_table, opt_gdef_table, lookups, glyphs)?;
-            }
-        }
+        ScriptType::Default => match plan.opt_frac_lookups {
+            Some(ref lookups_frac) => gsub_apply_lookups_frac(
+                gsub_cache,
+                gsub_table,
+                opt_gdef_table,
+                &plan.default_lookups,
+                lookups_frac,
+                glyphs,
+            )?,
+            None => gsub_apply_lookups(
+                gsub_cache,
+                gsub_table,
+                opt_gdef_table,
+                &plan.default_lookups,
+                glyphs,
+            )?,
+        },
     }
 
     strip_joiners(glyphs);
@@ -1500,3 +2001,519 @@ fn find_fraction(glyphs: &[RawGlyph<()>]) -> Option<(usize, usize, usize)> {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::read::ReadScope;
+    use crate::layout::new_layout_cache;
+    use rustc_hash::FxHashSet;
+
+    // A GSUB table with a single script whose default language system references two `liga`
+    // features, one of them twice. The lookups of the features overlap and are out of order.
+    fn make_duplicate_feature_gsub() -> Vec<u8> {
+        vec![
+            0x00, 0x01, 0x00, 0x00, // version
+            0x00, 0x0A, // script list offset
+            0x00, 0x22, // feature list offset
+            0x00, 0x00, // lookup list offset
+            // Script list
+            0x00, 0x01, // script count
+            0x6C, 0x61, 0x74, 0x6E, // latn
+            0x00, 0x08, // script offset
+            // Script table
+            0x00, 0x04, // default langsys offset
+            0x00, 0x00, // langsys count
+            // LangSys table
+            0x00, 0x00, // lookup order
+            0xFF, 0xFF, // required feature index
+            0x00, 0x03, // feature index count
+            0x00, 0x00, 0x00, 0x01, 0x00, 0x00, // feature indices
+            // Feature list
+            0x00, 0x02, // feature count
+            0x6C, 0x69, 0x67, 0x61, // liga
+            0x00, 0x0E, // feature offset
+            0x6C, 0x69, 0x67, 0x61, // liga
+            0x00, 0x16, // feature offset
+            // Feature table
+            0x00, 0x00, // feature params
+            0x00, 0x02, // lookup index count
+            0x00, 0x03, 0x00, 0x01, // lookup indices
+            // Feature table
+            0x00, 0x00, // feature params
+            0x00, 0x02, // lookup index count
+            0x00, 0x01, 0x00, 0x02, // lookup indices
+        ]
+    }
+
+    #[test]
+    fn test_resolved_lookups_duplicate_features() {
+        let data = make_duplicate_feature_gsub();
+        let gsub = ReadScope::new(&data).read::<LayoutTable<GSUB>>().unwrap();
+        let gsub_cache = new_layout_cache(gsub);
+
+        let lookups = resolved_lookups(&gsub_cache, tag::LATN, None, GsubFeatureMask::LIGA);
+        assert_eq!(
+            lookups.unwrap(),
+            vec![(1, tag::LIGA), (2, tag::LIGA), (3, tag::LIGA)]
+        );
+    }
+
+    #[test]
+    fn test_find_langsys_features_skips_repeated_index() {
+        let data = make_duplicate_feature_gsub();
+        let gsub = ReadScope::new(&data).read::<LayoutTable<GSUB>>().unwrap();
+        let script = gsub.find_script(tag::LATN).unwrap().unwrap();
+        let langsys = script.default_langsys_record().unwrap();
+
+        let features = gsub.find_langsys_features(langsys, tag::LIGA).unwrap();
+        assert_eq!(features.len(), 2);
+        assert!(gsub
+            .find_langsys_features(langsys, tag::CLIG)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_resolve_settings() {
+        let ss01 = tag::from_string("ss01").unwrap();
+        let settings = vec![
+            FeatureSetting::off(tag::LIGA),
+            FeatureSetting::on(tag::SMCP),
+            FeatureSetting::on(ss01),
+            FeatureSetting::alternate(tag::AALT, 2),
+            FeatureSetting::off(ss01),
+        ];
+        let (feature_mask, features_list) = resolve_settings(GsubFeatureMask::default(), &settings);
+        assert!(!feature_mask.contains(GsubFeatureMask::LIGA));
+        assert!(feature_mask.contains(GsubFeatureMask::SMCP | GsubFeatureMask::CCMP));
+        assert_eq!(features_list.len(), 1);
+        assert_eq!(features_list[0].feature_tag, tag::AALT);
+        assert_eq!(features_list[0].alternate, Some(2));
+
+        let features = Features::Settings(settings);
+        assert_eq!(features.setting(ss01), Some(0));
+        assert_eq!(features.setting(tag::AALT), Some(3));
+        assert_eq!(features.setting(tag::CALT), None);
+    }
+
+    #[test]
+    fn test_features_with_setting() {
+        let features = Features::Mask(
+            (GsubFeatureMask::default() | GsubFeatureMask::SMCP) - GsubFeatureMask::LIGA,
+        );
+        let features = features.with_setting(FeatureSetting::on(tag::SUPS));
+        assert_eq!(
+            features,
+            Features::Settings(vec![
+                FeatureSetting::off(tag::LIGA),
+                FeatureSetting::on(tag::SMCP),
+                FeatureSetting::on(tag::SUPS),
+            ])
+        );
+        let (feature_mask, features_list) = match &features {
+            Features::Settings(settings) => resolve_settings(GsubFeatureMask::default(), settings),
+            _ => unreachable!(),
+        };
+        assert_eq!(
+            feature_mask,
+            (GsubFeatureMask::default() | GsubFeatureMask::SMCP) - GsubFeatureMask::LIGA
+        );
+        assert_eq!(features_list.len(), 1);
+
+        let features = features.with_setting(FeatureSetting::off(tag::SUPS));
+        assert!(!features.is_enabled(tag::SUPS));
+    }
+
+    // A GSUB table with an `aalt` feature offering three alternates of glyph 10 and a `salt`
+    // feature substituting glyph 10 with glyph 15.
+    fn make_alternates_gsub() -> Vec<u8> {
+        vec![
+            0x00, 0x01, 0x00, 0x00, // version
+            0x00, 0x0A, // script list offset
+            0x00, 0x20, // feature list offset
+            0x00, 0x3A, // lookup list offset
+            // Script list
+            0x00, 0x01, // script count
+            0x6C, 0x61, 0x74, 0x6E, // latn
+            0x00, 0x08, // script offset
+            // Script table
+            0x00, 0x04, // default langsys offset
+            0x00, 0x00, // langsys count
+            // LangSys table
+            0x00, 0x00, // lookup order
+            0xFF, 0xFF, // required feature index
+            0x00, 0x02, // feature index count
+            0x00, 0x00, 0x00, 0x01, // feature indices
+            // Feature list
+            0x00, 0x02, // feature count
+            0x61, 0x61, 0x6C, 0x74, // aalt
+            0x00, 0x0E, // feature offset
+            0x73, 0x61, 0x6C, 0x74, // salt
+            0x00, 0x14, // feature offset
+            // Feature table
+            0x00, 0x00, // feature params
+            0x00, 0x01, // lookup index count
+            0x00, 0x00, // lookup indices
+            // Feature table
+            0x00, 0x00, // feature params
+            0x00, 0x01, // lookup index count
+            0x00, 0x01, // lookup indices
+            // Lookup list
+            0x00, 0x02, // lookup count
+            0x00, 0x06, 0x00, 0x24, // lookup offsets
+            // Lookup table
+            0x00, 0x03, // lookup type
+            0x00, 0x00, // lookup flag
+            0x00, 0x01, // subtable count
+            0x00, 0x08, // subtable offset
+            // Alternate substitution subtable
+            0x00, 0x01, // format
+            0x00, 0x10, // coverage offset
+            0x00, 0x01, // alternate set count
+            0x00, 0x08, // alternate set offset
+            // Alternate set
+            0x00, 0x03, // glyph count
+            0x00, 0x14, 0x00, 0x15, 0x00, 0x16, // alternate glyphs
+            // Coverage table
+            0x00, 0x01, // format
+            0x00, 0x01, // glyph count
+            0x00, 0x0A, // glyph array
+            // Lookup table
+            0x00, 0x01, // lookup type
+            0x00, 0x00, // lookup flag
+            0x00, 0x01, // subtable count
+            0x00, 0x08, // subtable offset
+            // Single substitution subtable
+            0x00, 0x01, // format
+            0x00, 0x06, // coverage offset
+            0x00, 0x05, // delta glyph index
+            // Coverage table
+            0x00, 0x01, // format
+            0x00, 0x01, // glyph count
+            0x00, 0x0A, // glyph array
+        ]
+    }
+
+    #[test]
+    fn test_glyph_alternates() {
+        let data = make_alternates_gsub();
+        let gsub = ReadScope::new(&data).read::<LayoutTable<GSUB>>().unwrap();
+        let gsub_cache = new_layout_cache(gsub);
+
+        let alternates = glyph_alternates(&gsub_cache, tag::LATN, None, tag::AALT, 10);
+        assert_eq!(alternates.unwrap(), vec![20, 21, 22]);
+        let alternates = glyph_alternates(&gsub_cache, tag::LATN, None, tag::SALT, 10);
+        assert_eq!(alternates.unwrap(), vec![15]);
+        let alternates = glyph_alternates(&gsub_cache, tag::LATN, None, tag::AALT, 11);
+        assert!(alternates.unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_apply_selected_alternate() {
+        let data = make_alternates_gsub();
+        let gsub = ReadScope::new(&data).read::<LayoutTable<GSUB>>().unwrap();
+        let gsub_cache = new_layout_cache(gsub);
+
+        let glyph = RawGlyph {
+            unicodes: tiny_vec![],
+            glyph_index: 10,
+            liga_component_pos: 0,
+            glyph_origin: GlyphOrigin::Direct,
+            small_caps: false,
+            multi_subst_dup: false,
+            multi_subst_component: None,
+            is_vert_alt: false,
+            fake_bold: false,
+            fake_italic: false,
+            stretch: None,
+            variation: None,
+            extra_data: (),
+        };
+        let features = Features::Settings(vec![FeatureSetting::alternate(tag::AALT, 2)]);
+        let mut glyphs = vec![glyph];
+        apply(
+            0,
+            &gsub_cache,
+            None,
+            tag::LATN,
+            None,
+            &features,
+            100,
+            &mut glyphs,
+        )
+        .unwrap();
+        assert_eq!(glyphs[0].glyph_index, 22);
+    }
+
+    /// A `calt` feature with a reverse chaining substitution of glyph 10 with 30 when followed
+    /// by 20 or 30.
+    fn make_reverse_chain_gsub() -> Vec<u8> {
+        vec![
+            0x00, 0x01, 0x00, 0x00, // version
+            0x00, 0x0A, // script list offset
+            0x00, 0x1E, // feature list offset
+            0x00, 0x2C, // lookup list offset
+            // Script list
+            0x00, 0x01, // script count
+            0x6C, 0x61, 0x74, 0x6E, // latn
+            0x00, 0x08, // script offset
+            // Script table
+            0x00, 0x04, // default langsys offset
+            0x00, 0x00, // langsys count
+            // LangSys table
+            0x00, 0x00, // lookup order
+            0xFF, 0xFF, // required feature index
+            0x00, 0x01, // feature index count
+            0x00, 0x00, // feature indices
+            // Feature list
+            0x00, 0x01, // feature count
+            0x63, 0x61, 0x6C, 0x74, // calt
+            0x00, 0x08, // feature offset
+            // Feature table
+            0x00, 0x00, // feature params
+            0x00, 0x01, // lookup index count
+            0x00, 0x00, // lookup indices
+            // Lookup list
+            0x00, 0x01, // lookup count
+            0x00, 0x04, // lookup offsets
+            // Lookup table
+            0x00, 0x08, // lookup type
+            0x00, 0x00, // lookup flag
+            0x00, 0x01, // subtable count
+            0x00, 0x08, // subtable offset
+            // Reverse chaining contextual single substitution subtable
+            0x00, 0x01, // format
+            0x00, 0x0E, // coverage offset
+            0x00, 0x00, // backtrack glyph count
+            0x00, 0x01, // lookahead glyph count
+            0x00, 0x14, // lookahead coverage offsets
+            0x00, 0x01, // glyph count
+            0x00, 0x1E, // substitute glyph IDs
+            // Coverage table
+            0x00, 0x01, // format
+            0x00, 0x01, // glyph count
+            0x00, 0x0A, // glyph array
+            // Lookahead coverage table
+            0x00, 0x01, // format
+            0x00, 0x02, // glyph count
+            0x00, 0x14, 0x00, 0x1E, // glyph array
+        ]
+    }
+
+    #[test]
+    fn test_apply_reverse_chain_single_subst() {
+        let data = make_reverse_chain_gsub();
+        let gsub = ReadScope::new(&data).read::<LayoutTable<GSUB>>().unwrap();
+        let gsub_cache = new_layout_cache(gsub);
+
+        // Glyphs are substituted from the end of the run, so each substitution provides the
+        // context for the one preceding it
+        let mut glyphs = [10, 10, 20, 10]
+            .iter()
+            .map(|&glyph_index| RawGlyph {
+                unicodes: tiny_vec![],
+                glyph_index,
+                liga_component_pos: 0,
+                glyph_origin: GlyphOrigin::Direct,
+                small_caps: false,
+                multi_subst_dup: false,
+                multi_subst_component: None,
+                is_vert_alt: false,
+                fake_bold: false,
+                fake_italic: false,
+                stretch: None,
+                variation: None,
+                extra_data: (),
+            })
+            .collect::<Vec<_>>();
+        let length = gsub_apply_lookup(
+            &gsub_cache,
+            &gsub_cache.layout_table,
+            None,
+            0,
+            tag::CALT,
+            None,
+            &mut glyphs,
+            0,
+            4,
+            |_| true,
+        )
+        .unwrap();
+        assert_eq!(length, 4);
+        let glyph_indices = glyphs
+            .iter()
+            .map(|glyph| glyph.glyph_index)
+            .collect::<Vec<_>>();
+        assert_eq!(glyph_indices, vec![30, 30, 20, 10]);
+    }
+
+    /// A `ccmp` feature with a multiple substitution of glyph 10 with 11 and 12, and a ligature
+    /// substitution of 11 and 12 with 13.
+    fn make_decompose_gsub() -> Vec<u8> {
+        vec![
+            0x00, 0x01, 0x00, 0x00, // version
+            0x00, 0x0A, // script list offset
+            0x00, 0x1E, // feature list offset
+            0x00, 0x2E, // lookup list offset
+            // Script list
+            0x00, 0x01, // script count
+            0x6C, 0x61, 0x74, 0x6E, // latn
+            0x00, 0x08, // script offset
+            // Script table
+            0x00, 0x04, // default langsys offset
+            0x00, 0x00, // langsys count
+            // LangSys table
+            0x00, 0x00, // lookup order
+            0xFF, 0xFF, // required feature index
+            0x00, 0x01, // feature index count
+            0x00, 0x00, // feature indices
+            // Feature list
+            0x00, 0x01, // feature count
+            0x63, 0x63, 0x6D, 0x70, // ccmp
+            0x00, 0x08, // feature offset
+            // Feature table
+            0x00, 0x00, // feature params
+            0x00, 0x02, // lookup index count
+            0x00, 0x00, 0x00, 0x01, // lookup indices
+            // Lookup list
+            0x00, 0x02, // lookup count
+            0x00, 0x06, 0x00, 0x22, // lookup offsets
+            // Lookup table
+            0x00, 0x02, // lookup type
+            0x00, 0x00, // lookup flag
+            0x00, 0x01, // subtable count
+            0x00, 0x08, // subtable offset
+            // Multiple substitution subtable
+            0x00, 0x01, // format
+            0x00, 0x0E, // coverage offset
+            0x00, 0x01, // sequence count
+            0x00, 0x08, // sequence offsets
+            // Sequence table
+            0x00, 0x02, // glyph count
+            0x00, 0x0B, 0x00, 0x0C, // substitute glyph IDs
+            // Coverage table
+            0x00, 0x01, // format
+            0x00, 0x01, // glyph count
+            0x00, 0x0A, // glyph array
+            // Lookup table
+            0x00, 0x04, // lookup type
+            0x00, 0x00, // lookup flag
+            0x00, 0x01, // subtable count
+            0x00, 0x08, // subtable offset
+            // Ligature substitution subtable
+            0x00, 0x01, // format
+            0x00, 0x12, // coverage offset
+            0x00, 0x01, // ligature set count
+            0x00, 0x08, // ligature set offsets
+            // Ligature set
+            0x00, 0x01, // ligature count
+            0x00, 0x04, // ligature offsets
+            // Ligature table
+            0x00, 0x0D, // ligature glyph
+            0x00, 0x02, // component count
+            0x00, 0x0C, // component glyph IDs
+            // Coverage table
+            0x00, 0x01, // format
+            0x00, 0x01, // glyph count
+            0x00, 0x0B, // glyph array
+        ]
+    }
+
+    #[test]
+    fn test_multiple_subst_components() {
+        let data = make_decompose_gsub();
+        let gsub = ReadScope::new(&data).read::<LayoutTable<GSUB>>().unwrap();
+        let gsub_cache = new_layout_cache(gsub);
+
+        let mut glyphs = vec![RawGlyph {
+            unicodes: tiny_vec![[char; 1] => 'a'],
+            glyph_index: 10,
+            liga_component_pos: 0,
+            glyph_origin: GlyphOrigin::Char('a'),
+            small_caps: false,
+            multi_subst_dup: false,
+            multi_subst_component: None,
+            is_vert_alt: false,
+            fake_bold: false,
+            fake_italic: false,
+            stretch: None,
+            variation: None,
+            extra_data: (),
+        }];
+        let apply_lookup = |lookup_index, glyphs: &mut Vec<RawGlyph<()>>| {
+            let length = glyphs.len();
+            gsub_apply_lookup(
+                &gsub_cache,
+                &gsub_cache.layout_table,
+                None,
+                lookup_index,
+                tag::CCMP,
+                None,
+                glyphs,
+                0,
+                length,
+                |_| true,
+            )
+            .unwrap();
+        };
+
+        apply_lookup(0, &mut glyphs);
+        let components = glyphs
+            .iter()
+            .map(|glyph| (glyph.glyph_index, glyph.multi_subst_component))
+            .collect::<Vec<_>>();
+        let component = |index| Some(MultipleSubstComponent { index, count: 2 });
+        assert_eq!(components, vec![(11, component(0)), (12, component(1))]);
+        assert_eq!(glyphs[0].source_char(), Some('a'));
+        assert_eq!(glyphs[1].source_char(), None);
+        assert_eq!(glyphs[1].unicodes.as_slice(), &['a']);
+
+        // Recombining the glyphs doesn't duplicate the character
+        apply_lookup(1, &mut glyphs);
+        assert_eq!(glyphs.len(), 1);
+        assert_eq!(glyphs[0].glyph_index, 13);
+        assert_eq!(glyphs[0].unicodes.as_slice(), &['a']);
+    }
+
+    #[test]
+    fn test_gsub_closure_reverse_chain_single_subst() {
+        let data = make_reverse_chain_gsub();
+        let gsub = ReadScope::new(&data).read::<LayoutTable<GSUB>>().unwrap();
+        let gsub_cache = new_layout_cache(gsub);
+
+        // The lookahead context can't be matched
+        let initial_glyph_set = [10].iter().copied().collect::<FxHashSet<u16>>();
+        let closure = gsub_closure(&gsub_cache, &initial_glyph_set, &[tag::CALT], &[tag::LATN]);
+        assert_eq!(closure.unwrap(), initial_glyph_set);
+
+        let initial_glyph_set = [10, 20].iter().copied().collect::<FxHashSet<u16>>();
+        let closure = gsub_closure(&gsub_cache, &initial_glyph_set, &[tag::CALT], &[tag::LATN]);
+        let mut closure = closure.unwrap().into_iter().collect::<Vec<_>>();
+        closure.sort();
+        assert_eq!(closure, vec![10, 20, 30]);
+    }
+
+    #[test]
+    fn test_gsub_closure() {
+        let data = make_alternates_gsub();
+        let gsub = ReadScope::new(&data).read::<LayoutTable<GSUB>>().unwrap();
+        let gsub_cache = new_layout_cache(gsub);
+        let initial_glyph_set = [3, 10].iter().copied().collect::<FxHashSet<u16>>();
+
+        let closure = gsub_closure(&gsub_cache, &initial_glyph_set, &[tag::SALT], &[tag::LATN]);
+        let mut closure = closure.unwrap().into_iter().collect::<Vec<_>>();
+        closure.sort();
+        assert_eq!(closure, vec![3, 10, 15]);
+
+        let features = [tag::AALT, tag::SALT];
+        let closure = gsub_closure(&gsub_cache, &initial_glyph_set, &features, &[tag::LATN]);
+        let mut closure = closure.unwrap().into_iter().collect::<Vec<_>>();
+        closure.sort();
+        assert_eq!(closure, vec![3, 10, 15, 20, 21, 22]);
+
+        // Script not present in the font
+        let closure = gsub_closure(&gsub_cache, &initial_glyph_set, &features, &[tag::ARAB]);
+        assert_eq!(closure.unwrap(), initial_glyph_set);
+    }
+}
@@ -0,0 +1,488 @@
+#![deny(missing_docs)]
+
+//! Instancing of variable fonts.
+//!
+//! `instance` produces a static TrueType font from a variable font at a location in its design
+//! space. The outlines are varied with the deltas of `gvar`, including the phantom points that
+//! vary the advance of each glyph. The variation tables are dropped from the result, along with
+//! the tables that rewriting the font invalidates, such as `DSIG`. The names of the font are
+//! rewritten for the instance from its `STAT` table, as for
+//! `NameTableBuilder::update_instance_names`, and the weight and width classes in `OS/2` are set
+//! from the `wght` and `wdth` coordinates. `batch` instances many fonts at once.
+//!
+//! Only fonts with `glyf` outlines are supported. The hinting instructions, the `cvt` table, and
+//! the metrics held in `MVAR` and `VVAR` are left at their default values. The variations of the
+//! layout tables are left in place, they are inert in a font without an `fvar` table.
+
+pub mod batch;
+
+use std::convert::TryFrom;
+
+use crate::binary::read::ReadScope;
+use crate::error::{ParseError, ReadWriteError};
+use crate::recompute::{self, FontTables};
+use crate::sfnt::{FontBuilder, DEFAULT_DROP_TABLES, VARIATION_TABLES};
+use crate::tables::avar::AvarTable;
+use crate::tables::fvar::{ClampedAxis, FvarTable};
+use crate::tables::glyf::{
+    BoundingBox, CompositeGlyphArgument, CompositeGlyphFlag, GlyfRecord, GlyfTable, GlyphData,
+    Point,
+};
+use crate::tables::gvar::GvarTable;
+use crate::tables::loca::LocaTable;
+use crate::tables::os2::Os2;
+use crate::tables::stat::StatTable;
+use crate::tables::{
+    F2Dot14, FontTableProvider, HeadTable, HheaTable, HmtxTable, MaxpTable, NameTable,
+    NameTableBuilder, TTF_MAGIC,
+};
+use crate::tag;
+
+/// A static font produced by `instance`.
+#[derive(Debug, Clone)]
+pub struct InstancedFont {
+    /// The data of the font.
+    pub data: Vec<u8>,
+    /// The normalised coordinates the font was instanced at, one per axis of the variable font.
+    pub coords: Vec<F2Dot14>,
+    /// The axes whose requested coordinates were outside of their range.
+    pub clamped_axes: Vec<ClampedAxis>,
+}
+
+/// Instance the variable font in `provider` at `location`.
+///
+/// `location` holds `(axis_tag, value)` pairs in user coordinates, such as `(tag!(b"wght"), 700.)`.
+/// Axes of the font that aren't listed are set to their default value, and tags that aren't axes
+/// of the font are ignored. Values outside the range of an axis are clamped to it and reported in
+/// `InstancedFont::clamped_axes`.
+///
+/// Fails with `ParseError::MissingValue` if the font has no `fvar` table, and
+/// `ParseError::NotImplemented` if it doesn't have `glyf` outlines.
+pub fn instance(
+    provider: &impl FontTableProvider,
+    location: &[(u32, f32)],
+) -> Result<InstancedFont, ReadWriteError> {
+    let fvar_data = provider.read_table_data(tag::FVAR)?;
+    let fvar = ReadScope::new(&fvar_data).read::<FvarTable>()?;
+    let (user_coords, clamped_axes) = fvar.clamp_coordinates(&requested_coords(&fvar, location));
+    let coords = normalized_coords(provider, &fvar, &user_coords)?;
+    if !provider.has_table(tag::GLYF) {
+        return Err(ParseError::NotImplemented.into());
+    }
+
+    let head_data = provider.read_table_data(tag::HEAD)?;
+    let head = ReadScope::new(&head_data).read::<HeadTable>()?;
+    let maxp_data = provider.read_table_data(tag::MAXP)?;
+    let maxp = ReadScope::new(&maxp_data).read::<MaxpTable>()?;
+    let hhea_data = provider.read_table_data(tag::HHEA)?;
+    let hhea = ReadScope::new(&hhea_data).read::<HheaTable>()?;
+    let num_glyphs = usize::from(maxp.num_glyphs);
+    let hmtx_data = provider.read_table_data(tag::HMTX)?;
+    let hmtx = ReadScope::new(&hmtx_data)
+        .read_dep::<HmtxTable<'_>>((num_glyphs, usize::from(hhea.num_h_metrics)))?;
+    let loca_data = provider.read_table_data(tag::LOCA)?;
+    let loca = ReadScope::new(&loca_data)
+        .read_dep::<LocaTable<'_>>((num_glyphs, head.index_to_loc_format))?;
+    let glyf_data = provider.read_table_data(tag::GLYF)?;
+    let glyf = ReadScope::new(&glyf_data).read_dep::<GlyfTable<'_>>(&loca)?;
+    let gvar_data = provider.table_data(tag::GVAR)?;
+    let opt_gvar = gvar_data
+        .as_ref()
+        .map(|gvar_data| ReadScope::new(gvar_data).read_dep::<GvarTable<'_>>(num_glyphs))
+        .transpose()?;
+
+    let mut records = Vec::with_capacity(num_glyphs);
+    let mut metrics = hmtx.metrics()?;
+    for (glyph_index, metric) in metrics.iter_mut().enumerate() {
+        let glyph_index = u16::try_from(glyph_index).map_err(ParseError::from)?;
+        let (record, advance_delta) = match &opt_gvar {
+            Some(gvar) => instance_glyph(&glyf, gvar, &coords, glyph_index)?,
+            None => {
+                let record = glyf.records.get(usize::from(glyph_index));
+                (record.cloned().ok_or(ParseError::BadIndex)?, 0.)
+            }
+        };
+        records.push(record);
+        metric.advance_width = round_u16(f32::from(metric.advance_width) + advance_delta);
+    }
+    let mut glyf = GlyfTable { records };
+    recalculate_composite_bounds(&mut glyf)?;
+
+    let hmtx = HmtxTable::from_metrics(metrics);
+    let mut tables = FontTables {
+        head,
+        hhea: HheaTable {
+            num_h_metrics: u16::try_from(hmtx.h_metrics.len()).map_err(ParseError::from)?,
+            ..hhea
+        },
+        maxp,
+        hmtx,
+        glyf,
+    };
+    recompute::recompute_globals(&mut tables)?;
+    let FontTables {
+        head,
+        hhea,
+        maxp,
+        hmtx,
+        glyf,
+    } = tables;
+
+    let mut builder = FontBuilder::new(TTF_MAGIC);
+    builder.add_table::<_, HheaTable>(tag::HHEA, &hhea, ())?;
+    builder.add_table::<_, HmtxTable<'_>>(tag::HMTX, &hmtx, ())?;
+    if let Some(name) = instance_names(provider, &fvar, &user_coords)? {
+        builder.add_table::<_, NameTableBuilder>(tag::NAME, &name, ())?;
+    }
+    if let Some(os2) = instance_os2(provider, &fvar, &user_coords)? {
+        builder.add_table::<_, Os2>(tag::OS_2, &os2, ())?;
+    }
+    let drop_tables = [&DEFAULT_DROP_TABLES[..], &VARIATION_TABLES[..]].concat();
+    builder.copy_tables_with_glyph_ids(provider, &drop_tables)?;
+    let data = builder.add_packed_glyf_tables(head, maxp, glyf)?.data()?;
+
+    Ok(InstancedFont {
+        data,
+        coords,
+        clamped_axes,
+    })
+}
+
+/// Returns the user coordinates of `location`, one per axis of `fvar`, with the axes that aren't
+/// listed at their default value.
+fn requested_coords(fvar: &FvarTable, location: &[(u32, f32)]) -> Vec<f32> {
+    fvar.axes
+        .iter()
+        .map(|axis| {
+            location
+                .iter()
+                .find(|&&(axis_tag, _)| axis_tag == axis.axis_tag)
+                .map_or(axis.default_value, |&(_, value)| value)
+        })
+        .collect()
+}
+
+/// Convert `user_coords` to normalised coordinates, mapping them through `avar` if present.
+fn normalized_coords(
+    provider: &impl FontTableProvider,
+    fvar: &FvarTable,
+    user_coords: &[f32],
+) -> Result<Vec<F2Dot14>, ParseError> {
+    let (coords, _clamped_axes) = fvar.normalize(user_coords);
+    match provider.table_data(tag::AVAR)? {
+        Some(avar_data) => ReadScope::new(&avar_data)
+            .read::<AvarTable<'_>>()?
+            .map_coordinates(&coords),
+        None => Ok(coords),
+    }
+}
+
+/// Rebuild the `name` table for an instance at `user_coords`.
+///
+/// The names are updated from the `STAT` table as described for
+/// `NameTableBuilder::update_instance_names`. Without a `STAT` table the names are kept, apart
+/// from the variations PostScript name prefix. Returns `None` if the font has no `name` table.
+fn instance_names(
+    provider: &impl FontTableProvider,
+    fvar: &FvarTable,
+    user_coords: &[f32],
+) -> Result<Option<NameTableBuilder>, ReadWriteError> {
+    let name_data = match provider.table_data(tag::NAME)? {
+        Some(name_data) => name_data,
+        None => return Ok(None),
+    };
+    let name = ReadScope::new(&name_data).read::<NameTable<'_>>()?;
+    let mut builder = NameTableBuilder::from_table(&name)?;
+    match provider.table_data(tag::STAT)? {
+        Some(stat_data) => {
+            let stat = ReadScope::new(&stat_data).read::<StatTable>()?;
+            builder.update_instance_names(fvar, &stat, user_coords)?;
+        }
+        None => {
+            builder.remove_name(NameTable::VARIATIONS_POSTSCRIPT_NAME_PREFIX);
+        }
+    }
+    Ok(Some(builder))
+}
+
+/// Returns the `OS/2` table of the font with its weight and width classes set from the `wght`
+/// and `wdth` coordinates in `user_coords`, or `None` if the font has no `OS/2` table.
+fn instance_os2(
+    provider: &impl FontTableProvider,
+    fvar: &FvarTable,
+    user_coords: &[f32],
+) -> Result<Option<Os2>, ParseError> {
+    let os2_data = match provider.table_data(tag::OS_2)? {
+        Some(os2_data) => os2_data,
+        None => return Ok(None),
+    };
+    let mut os2 = ReadScope::new(&os2_data).read_dep::<Os2>(os2_data.len())?;
+    for (axis_tag, value) in fvar.location(user_coords) {
+        if axis_tag == tag!(b"wght") {
+            os2.us_weight_class = round_u16(value.max(1.).min(1000.));
+        } else if axis_tag == tag!(b"wdth") {
+            os2.us_width_class = width_class(value);
+        }
+    }
+    Ok(Some(os2))
+}
+
+/// Returns the `OS/2` width class closest to the `wdth` axis value `width`, a percentage of the
+/// normal width.
+fn width_class(width: f32) -> u16 {
+    const WIDTHS: [f32; 9] = [50., 62.5, 75., 87.5, 100., 112.5, 125., 150., 200.];
+
+    let mut class = 1;
+    for (index, &class_width) in WIDTHS.iter().enumerate().skip(1) {
+        // Widths halfway between two classes round up
+        if width >= (WIDTHS[index - 1] + class_width) / 2. {
+            class = index + 1;
+        }
+    }
+    class as u16
+}
+
+/// Apply the deltas of `glyph_index` at `coords`, returning the varied glyph and the change in
+/// its advance.
+///
+/// The outline is moved horizontally so that the origin phantom point stays at zero, keeping the
+/// left side bearing equal to the `xMin` of the glyph.
+fn instance_glyph<'a>(
+    glyf: &GlyfTable<'a>,
+    gvar: &GvarTable<'_>,
+    coords: &[F2Dot14],
+    glyph_index: u16,
+) -> Result<(GlyfRecord<'a>, f32), ParseError> {
+    let mut glyph = match glyf.glyph(glyph_index)? {
+        Some(glyph) => glyph.into_owned(),
+        None => {
+            let phantom = gvar.phantom_point_deltas(glyph_index, coords, 0)?;
+            return Ok((GlyfRecord::Empty, phantom[1].0 - phantom[0].0));
+        }
+    };
+
+    let advance_delta = match &mut glyph.data {
+        GlyphData::Simple(simple_glyph) => {
+            let deltas = gvar.glyph_deltas(
+                glyph_index,
+                coords,
+                &simple_glyph.coordinates,
+                &simple_glyph.end_pts_of_contours,
+            )?;
+            let phantom =
+                gvar.phantom_point_deltas(glyph_index, coords, simple_glyph.coordinates.len())?;
+            let origin_x = phantom[0].0;
+            for (point, &(dx, dy)) in simple_glyph.coordinates.iter_mut().zip(&deltas) {
+                *point = Point(
+                    round_i16(f32::from(point.0) + dx - origin_x),
+                    round_i16(f32::from(point.1) + dy),
+                );
+            }
+            phantom[1].0 - origin_x
+        }
+        GlyphData::Composite { glyphs, .. } => {
+            let offsets = glyphs
+                .iter()
+                .map(|composite_glyph| {
+                    Point(
+                        round_i16(composite_glyph.argument1.value() as f32),
+                        round_i16(composite_glyph.argument2.value() as f32),
+                    )
+                })
+                .collect::<Vec<_>>();
+            let deltas = gvar.glyph_deltas(glyph_index, coords, &offsets, &[])?;
+            let phantom = gvar.phantom_point_deltas(glyph_index, coords, glyphs.len())?;
+            let origin_x = phantom[0].0;
+            for ((composite_glyph, offset), &(dx, dy)) in
+                glyphs.iter_mut().zip(&offsets).zip(&deltas)
+            {
+                // Components positioned by matching points follow the points they're matched to
+                if !composite_glyph.flags.args_are_xy_values() {
+                    continue;
+                }
+                composite_glyph.argument1 =
+                    CompositeGlyphArgument::I16(round_i16(f32::from(offset.0) + dx - origin_x));
+                composite_glyph.argument2 =
+                    CompositeGlyphArgument::I16(round_i16(f32::from(offset.1) + dy));
+                composite_glyph.flags |= CompositeGlyphFlag::ARG_1_AND_2_ARE_WORDS;
+            }
+            phantom[1].0 - origin_x
+        }
+    };
+    glyph.recalculate_bounds()?;
+
+    Ok((GlyfRecord::Parsed(glyph), advance_delta))
+}
+
+/// Update the bounding boxes of the composite glyphs of `glyf` to match their varied components.
+fn recalculate_composite_bounds(glyf: &mut GlyfTable<'_>) -> Result<(), ParseError> {
+    for glyph_index in 0..glyf.records.len() {
+        if !glyf.records[glyph_index].is_composite()? {
+            continue;
+        }
+        let points = glyf.glyph_points(u16::try_from(glyph_index)?)?;
+        if let Some(glyph) = glyf.records[glyph_index].glyph_mut()? {
+            glyph.bounding_box = if points.points.is_empty() {
+                BoundingBox::empty()
+            } else {
+                BoundingBox::from_points(&points.points)
+            };
+        }
+    }
+    Ok(())
+}
+
+/// Round `value` to the nearest `i16`, saturating at the bounds of the type.
+fn round_i16(value: f32) -> i16 {
+    value
+        .round()
+        .max(f32::from(std::i16::MIN))
+        .min(f32::from(std::i16::MAX)) as i16
+}
+
+/// Round `value` to the nearest `u16`, saturating at the bounds of the type.
+fn round_u16(value: f32) -> u16 {
+    value.round().max(0.).min(f32::from(std::u16::MAX)) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font_data::FontData;
+    use crate::tables::glyf::GlyphPoints;
+    use crate::tables::OpenTypeFont;
+    use crate::tests::{read_fixture, variable_font};
+
+    fn glyph_metrics(data: &[u8], glyph_index: u16) -> (Option<GlyphPoints>, u16, i16) {
+        let font_file = ReadScope::new(data).read::<FontData<'_>>().unwrap();
+        let provider = font_file.table_provider(0).unwrap();
+        assert!(!provider.has_table(tag::FVAR));
+        assert!(!provider.has_table(tag::GVAR));
+        let mut font = crate::Font::new(provider).unwrap().unwrap();
+        let outline = font.outline(glyph_index, &[]).unwrap();
+        let advance = font.horizontal_advance(glyph_index).unwrap();
+        let hmtx_data = font.font_table_provider.read_table_data(tag::HMTX).unwrap();
+        let hmtx = ReadScope::new(&hmtx_data)
+            .read_dep::<HmtxTable<'_>>((3, usize::from(font.hhea_table.num_h_metrics)))
+            .unwrap();
+        let lsb = hmtx.metrics().unwrap()[usize::from(glyph_index)].lsb;
+        (outline, advance, lsb)
+    }
+
+    #[test]
+    fn test_instance() {
+        let data = variable_font();
+        let font_file = ReadScope::new(&data).read::<OpenTypeFont<'_>>().unwrap();
+        let provider = font_file.table_provider(0).unwrap();
+
+        let xs = |outline: Option<GlyphPoints>| {
+            outline
+                .unwrap()
+                .points
+                .iter()
+                .map(|point| point.0)
+                .collect::<Vec<_>>()
+        };
+
+        let black = instance(&provider, &[(tag!(b"wght"), 900.)]).unwrap();
+        assert_eq!(black.coords, vec![F2Dot14::from_f32(1.)]);
+        assert!(black.clamped_axes.is_empty());
+        let (outline, advance, lsb) = glyph_metrics(&black.data, 2);
+        assert_eq!(xs(outline), vec![1771, 2017, 1894]);
+        assert_eq!(advance, 4739);
+        assert_eq!(lsb, 1771);
+        assert_eq!(glyph_metrics(&black.data, 1).1, 1636);
+
+        let semi_bold = instance(&provider, &[(tag!(b"wght"), 650.)]).unwrap();
+        let (outline, advance, _) = glyph_metrics(&semi_bold.data, 2);
+        assert_eq!(xs(outline), vec![1766, 2012, 1889]);
+        assert_eq!(advance, 4729);
+
+        // Axes that aren't listed are set to their default
+        let regular = instance(&provider, &[(tag!(b"wdth"), 50.)]).unwrap();
+        let (outline, advance, _) = glyph_metrics(&regular.data, 2);
+        assert_eq!(xs(outline), vec![1761, 2007, 1884]);
+        assert_eq!(advance, 4719);
+
+        let clamped = instance(&provider, &[(tag!(b"wght"), 1000.)]).unwrap();
+        assert_eq!(clamped.clamped_axes.len(), 1);
+        assert_eq!(clamped.clamped_axes[0].clamped, 900.);
+        assert_eq!(clamped.data, black.data);
+
+        let os2 = |data: &[u8]| {
+            let font_file = ReadScope::new(data).read::<OpenTypeFont<'_>>().unwrap();
+            let provider = font_file.table_provider(0).unwrap();
+            assert!(!provider.has_table(tag::DSIG));
+            let os2_data = provider.read_table_data(tag::OS_2).unwrap();
+            ReadScope::new(&os2_data)
+                .read_dep::<Os2>(os2_data.len())
+                .unwrap()
+        };
+        assert_eq!(os2(&black.data).us_weight_class, 900);
+        assert_eq!(os2(&semi_bold.data).us_weight_class, 650);
+        assert_eq!(os2(&regular.data).us_width_class, 5);
+
+        // The names are rewritten for the instance from the STAT table
+        let name = |data: &[u8], name_id| {
+            let font_file = ReadScope::new(data).read::<OpenTypeFont<'_>>().unwrap();
+            let provider = font_file.table_provider(0).unwrap();
+            let name_data = provider.read_table_data(tag::NAME).unwrap();
+            let name = ReadScope::new(&name_data).read::<NameTable<'_>>().unwrap();
+            let builder = NameTableBuilder::from_table(&name).unwrap();
+            builder.name(3, 1, 0x409, name_id)
+        };
+        let bold = instance(&provider, &[(tag!(b"wght"), 700.)]).unwrap();
+        assert_eq!(name(&bold.data, 2), Some(String::from("Bold")));
+        assert_eq!(
+            name(&bold.data, 6),
+            Some(String::from("WOFFTestTTFVF-Bold"))
+        );
+        assert_eq!(name(&bold.data, 25), None);
+        assert_eq!(
+            name(&black.data, 1),
+            Some(String::from("WOFF Test TTF Black"))
+        );
+        assert_eq!(name(&black.data, 2), Some(String::from("Regular")));
+        assert_eq!(
+            name(&black.data, 6),
+            Some(String::from("WOFFTestTTFVF-Black"))
+        );
+        assert_eq!(name(&black.data, 17), Some(String::from("Black")));
+    }
+
+    #[test]
+    fn test_variable_font_fixture() {
+        // The fixture is used by allsorts-tool, which can't build it
+        let fixture = read_fixture("tests/fonts/opentype/test-font-variable.ttf");
+        assert!(
+            fixture == variable_font(),
+            "test-font-variable.ttf is out of date"
+        );
+    }
+
+    #[test]
+    fn test_width_class() {
+        assert_eq!(width_class(25.), 1);
+        assert_eq!(width_class(75.), 3);
+        assert_eq!(width_class(80.), 3);
+        assert_eq!(width_class(81.25), 4);
+        assert_eq!(width_class(100.), 5);
+        assert_eq!(width_class(175.), 9);
+        assert_eq!(width_class(300.), 9);
+    }
+
+    #[test]
+    fn test_instance_not_variable() {
+        let buffer = read_fixture("tests/fonts/opentype/test-font.ttf");
+        let font_file = ReadScope::new(&buffer).read::<OpenTypeFont<'_>>().unwrap();
+        let provider = font_file.table_provider(0).unwrap();
+        assert!(matches_missing(instance(&provider, &[])));
+    }
+
+    fn matches_missing(result: Result<InstancedFont, ReadWriteError>) -> bool {
+        match result {
+            Err(ReadWriteError::Read(err)) => err == ParseError::MissingValue,
+            _ => false,
+        }
+    }
+}
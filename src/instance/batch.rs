@@ -0,0 +1,333 @@
+//! Instancing of many variable fonts at once.
+//!
+//! `instance_fonts` reads each font of a `BatchInput`, instances it as described by an
+//! `InstanceSpec` and writes the instances to an output directory. With the `rayon` feature the
+//! fonts are processed in parallel. Failures are reported per font in a `FontReport` rather than
+//! ending the batch, and a callback is notified as each font completes, which makes it suitable
+//! as the backend of build tooling.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::binary::read::ReadScope;
+use crate::error::ReadWriteError;
+use crate::font_data::FontData;
+use crate::get_name::fontcode_get_name;
+use crate::instance::{self, InstancedFont};
+use crate::tables::fvar::{ClampedAxis, FvarTable};
+use crate::tables::FontTableProvider;
+use crate::tag::{self, DisplayTag};
+
+/// File extensions of the fonts that `BatchInput::Directory` picks up.
+const FONT_EXTENSIONS: [&str; 4] = ["otf", "ttf", "woff", "woff2"];
+
+/// The fonts to instance.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BatchInput {
+    /// Every font in the directory with a `.ttf`, `.otf`, `.woff` or `.woff2` extension.
+    /// Subdirectories are not searched.
+    Directory(PathBuf),
+    /// The listed font files.
+    Files(Vec<PathBuf>),
+}
+
+/// The instances to produce from each font.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InstanceSpec {
+    /// A single instance at a location given as `(axis_tag, value)` pairs in user coordinates,
+    /// as for `instance::instance`.
+    Location(Vec<(u32, f32)>),
+    /// An instance at each of the named instances in the `fvar` table of the font.
+    NamedInstances,
+}
+
+/// The progress of a batch, passed to the callback of `instance_fonts` as each font completes.
+#[derive(Debug)]
+pub struct Progress<'a> {
+    /// The report of the font that completed.
+    pub report: &'a FontReport,
+    /// The number of fonts that have completed, including this one.
+    pub completed: usize,
+    /// The number of fonts in the batch.
+    pub total: usize,
+}
+
+/// The outcome of instancing a single font of a batch.
+#[derive(Debug)]
+pub struct FontReport {
+    /// The path of the font.
+    pub path: PathBuf,
+    /// The instances that were written, or the reason the font could not be instanced.
+    pub result: Result<Vec<InstanceReport>, BatchError>,
+}
+
+/// An instance written by `instance_fonts`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstanceReport {
+    /// The name of the instance, used in the name of its file.
+    pub name: String,
+    /// The location of the instance as `(axis_tag, value)` pairs in user coordinates.
+    pub location: Vec<(u32, f32)>,
+    /// The path the instance was written to.
+    pub output: PathBuf,
+    /// The axes whose requested coordinates were outside of their range.
+    pub clamped_axes: Vec<ClampedAxis>,
+}
+
+/// Errors that prevent a font of a batch from being instanced.
+#[derive(Debug)]
+pub enum BatchError {
+    /// Reading the font or writing an instance failed.
+    Io(io::Error),
+    /// The font has no `fvar` table.
+    NotVariable,
+    /// `InstanceSpec::NamedInstances` was requested but the font has no named instances.
+    NoNamedInstances,
+    /// The font could not be parsed or instanced.
+    Font(ReadWriteError),
+}
+
+/// Instance the fonts of `input` as described by `spec`, writing the instances to `output_dir`.
+///
+/// Each instance is written to a file named after the font and the instance, such as
+/// `Example-Bold.ttf` for the named instance `Bold` of `Example.ttf`. Instances at a
+/// `InstanceSpec::Location` are named after their coordinates, such as `Example-wght700.ttf`.
+/// Only the first font of a collection is instanced, and instances are always written as
+/// uncompressed TrueType fonts.
+///
+/// `progress` is called as each font completes, from the thread that processed it. A report is
+/// returned for each font, in the order of `input`. An error is returned only when `input` is a
+/// directory that can't be read, or when `output_dir` can't be created.
+pub fn instance_fonts<F>(
+    input: &BatchInput,
+    spec: &InstanceSpec,
+    output_dir: &Path,
+    progress: F,
+) -> Result<Vec<FontReport>, io::Error>
+where
+    F: Fn(Progress<'_>) + Sync,
+{
+    let paths = match input {
+        BatchInput::Directory(dir) => font_files(dir)?,
+        BatchInput::Files(paths) => paths.clone(),
+    };
+    fs::create_dir_all(output_dir)?;
+
+    let total = paths.len();
+    let completed = AtomicUsize::new(0);
+    let process = |path: &PathBuf| {
+        let report = FontReport {
+            path: path.clone(),
+            result: instance_file(path, spec, output_dir),
+        };
+        progress(Progress {
+            report: &report,
+            completed: completed.fetch_add(1, Ordering::SeqCst) + 1,
+            total,
+        });
+        report
+    };
+
+    #[cfg(feature = "rayon")]
+    let reports = paths.par_iter().map(process).collect();
+    #[cfg(not(feature = "rayon"))]
+    let reports = paths.iter().map(process).collect();
+    Ok(reports)
+}
+
+/// Returns the font files directly inside `dir`, sorted by path.
+fn font_files(dir: &Path) -> Result<Vec<PathBuf>, io::Error> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_font = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map_or(false, |extension| {
+                FONT_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str())
+            });
+        if is_font && path.is_file() {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+fn instance_file(
+    path: &Path,
+    spec: &InstanceSpec,
+    output_dir: &Path,
+) -> Result<Vec<InstanceReport>, BatchError> {
+    let data = fs::read(path)?;
+    let font_file = ReadScope::new(&data)
+        .read::<FontData<'_>>()
+        .map_err(ReadWriteError::from)?;
+    let provider = font_file.table_provider(0)?;
+    let fvar_data = match provider
+        .table_data(tag::FVAR)
+        .map_err(ReadWriteError::from)?
+    {
+        Some(fvar_data) => fvar_data,
+        None => return Err(BatchError::NotVariable),
+    };
+    let fvar = ReadScope::new(&fvar_data)
+        .read::<FvarTable>()
+        .map_err(ReadWriteError::from)?;
+
+    let instances = match spec {
+        InstanceSpec::Location(location) => vec![(location_name(location), location.clone())],
+        InstanceSpec::NamedInstances => {
+            if fvar.instances.is_empty() {
+                return Err(BatchError::NoNamedInstances);
+            }
+            let name_data = provider
+                .table_data(tag::NAME)
+                .map_err(ReadWriteError::from)?;
+            fvar.instances
+                .iter()
+                .enumerate()
+                .map(|(index, instance)| {
+                    let location = fvar.location(&instance.coordinates);
+                    let name = match &name_data {
+                        Some(name_data) => fontcode_get_name(name_data, instance.subfamily_name_id)
+                            .map_err(ReadWriteError::from)?
+                            .and_then(|name| name.into_string().ok()),
+                        None => None,
+                    };
+                    let name = name.unwrap_or_else(|| format!("instance{}", index));
+                    Ok((name, location))
+                })
+                .collect::<Result<Vec<_>, BatchError>>()?
+        }
+    };
+
+    let stem = path
+        .file_stem()
+        .map_or_else(|| "font".into(), |stem| stem.to_string_lossy());
+    instances
+        .into_iter()
+        .map(|(name, location)| {
+            let InstancedFont {
+                data, clamped_axes, ..
+            } = instance::instance(&provider, &location)?;
+            let output = output_dir.join(format!("{}-{}.ttf", stem, file_name_part(&name)));
+            fs::write(&output, data)?;
+            Ok(InstanceReport {
+                name,
+                location,
+                output,
+                clamped_axes,
+            })
+        })
+        .collect()
+}
+
+/// Name an instance after its location, such as `wght700-wdth75`.
+fn location_name(location: &[(u32, f32)]) -> String {
+    if location.is_empty() {
+        return String::from("default");
+    }
+    location
+        .iter()
+        .map(|&(axis_tag, value)| format!("{}{}", DisplayTag(axis_tag), value))
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Remove the characters of `name` that aren't safe in a file name.
+fn file_name_part(name: &str) -> String {
+    name.chars()
+        .filter(|&ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_' || ch == '.')
+        .collect()
+}
+
+impl From<io::Error> for BatchError {
+    fn from(error: io::Error) -> Self {
+        BatchError::Io(error)
+    }
+}
+
+impl From<ReadWriteError> for BatchError {
+    fn from(error: ReadWriteError) -> Self {
+        BatchError::Font(error)
+    }
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BatchError::Io(err) => write!(f, "io error: {}", err),
+            BatchError::NotVariable => write!(f, "font is not a variable font"),
+            BatchError::NoNamedInstances => write!(f, "font has no named instances"),
+            BatchError::Font(err) => write!(f, "font error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for BatchError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tag;
+    use crate::tests::variable_font;
+
+    #[test]
+    fn test_location_name() {
+        assert_eq!(location_name(&[]), "default");
+        let location = [(tag!(b"wght"), 700.), (tag!(b"wdth"), 87.5)];
+        assert_eq!(location_name(&location), "wght700-wdth87.5");
+        assert_eq!(file_name_part("Semi Bold/Italic"), "SemiBoldItalic");
+    }
+
+    #[test]
+    fn test_instance_fonts() {
+        let dir = std::env::temp_dir().join(format!("allsorts-batch-{}", std::process::id()));
+        let input_dir = dir.join("input");
+        let output_dir = dir.join("output");
+        fs::create_dir_all(&input_dir).unwrap();
+        let variable = input_dir.join("Variable.ttf");
+        fs::write(&variable, variable_font()).unwrap();
+        let not_variable = PathBuf::from("tests/fonts/opentype/test-font.ttf");
+        let input = BatchInput::Files(vec![variable.clone(), not_variable.clone()]);
+
+        let calls = AtomicUsize::new(0);
+        let reports = instance_fonts(&input, &InstanceSpec::NamedInstances, &output_dir, |p| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            assert_eq!(p.total, 2);
+        })
+        .unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        assert_eq!(reports[0].path, variable);
+        let instances = reports[0].result.as_ref().unwrap();
+        assert_eq!(instances.len(), 1);
+        assert_eq!(instances[0].name, "Black");
+        assert_eq!(instances[0].location, vec![(tag!(b"wght"), 900.)]);
+        assert_eq!(instances[0].output, output_dir.join("Variable-Black.ttf"));
+        assert!(instances[0].output.is_file());
+
+        assert_eq!(reports[1].path, not_variable);
+        match reports[1].result {
+            Err(BatchError::NotVariable) => {}
+            ref result => panic!("expected NotVariable, got {:?}", result),
+        }
+
+        let input = BatchInput::Directory(input_dir);
+        let spec = InstanceSpec::Location(vec![(tag!(b"wght"), 700.)]);
+        let reports = instance_fonts(&input, &spec, &output_dir, |_| {}).unwrap();
+        assert_eq!(reports.len(), 1);
+        let instances = reports[0].result.as_ref().unwrap();
+        assert_eq!(instances[0].output, output_dir.join("Variable-wght700.ttf"));
+        assert!(instances[0].output.is_file());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
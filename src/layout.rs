@@ -7,25 +7,59 @@ use crate::binary::read::{
     CheckIndex, ReadArray, ReadBinary, ReadBinaryDep, ReadCache, ReadCtxt, ReadFixedSizeDep,
     ReadFrom, ReadScope, ReadScopeOwned,
 };
-use crate::binary::U16Be;
+use crate::binary::{U16Be, U24Be, U32Be};
 use crate::size;
 use crate::tag;
 use log::warn;
-use std::cell::RefCell;
-use std::collections::HashMap;
+use once_cell::sync::OnceCell;
+use rustc_hash::FxHashSet;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::convert::TryFrom;
 use std::marker::PhantomData;
-use std::rc::Rc;
+use std::sync::{Arc, Mutex, RwLock, Weak};
 use std::u16;
 
 pub enum GSUB {}
 pub enum GPOS {}
 
+/// The delta format of a device table holding a variation index.
+const VARIATION_INDEX_FORMAT: u16 = 0x8000;
+
 pub struct GDEFTable {
     pub opt_glyph_classdef: Option<ClassDef>,
     // pub opt_attach_list: Option<ReadScope<'a>>,
-    // pub opt_lig_caret_list: Option<ReadScope<'a>>,
+    pub opt_lig_caret_list: Option<LigCaretList>,
     pub opt_mark_attach_classdef: Option<ClassDef>,
-    // TODO read additional GDEF 1.2 and 1.3 fields
+    pub opt_mark_glyph_sets: Option<MarkGlyphSets>,
+}
+
+/// The mark glyph sets of a `GDEF` table, used by lookups with the `UseMarkFilteringSet` flag to
+/// skip marks that are not in the set.
+pub struct MarkGlyphSets {
+    coverages: Vec<Coverage>,
+}
+
+/// The positions of the carets between the components of ligature glyphs.
+pub struct LigCaretList {
+    coverage: Coverage,
+    lig_glyphs: Vec<Vec<CaretValue>>,
+}
+
+/// The position of a caret within a ligature glyph.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CaretValue {
+    /// A coordinate in font units.
+    Coordinate(i16),
+    /// The index of a contour point of the glyph outline, whose position gives the caret
+    /// coordinate after hinting.
+    ContourPoint(u16),
+    /// A coordinate in font units that varies with the delta at `delta_set_outer_index`,
+    /// `delta_set_inner_index` in the item variation store of the `GDEF` table.
+    VariableCoordinate {
+        coordinate: i16,
+        delta_set_outer_index: u16,
+        delta_set_inner_index: u16,
+    },
 }
 
 // GSUB and GPOS tables have the same top-level structure
@@ -70,10 +104,61 @@ pub struct FeatureRecord {
 }
 
 pub struct FeatureTable {
-    _feature_params: usize, // reserved field, should be zero
+    /// The parameters of the `size`, stylistic set and character variant features.
+    pub opt_feature_params: Option<FeatureParams>,
     pub lookup_indices: Vec<u16>,
 }
 
+/// The parameters of a feature, giving the names of stylistic sets and character variants for
+/// display, and the range of sizes a design is intended for.
+///
+/// Name IDs refer to strings in the `name` table.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FeatureParams {
+    /// The parameters of the `size` feature.
+    Size(SizeParams),
+    /// The parameters of a stylistic set feature, `ss01` to `ss20`.
+    StylisticSet {
+        /// The name of the stylistic set for display in a user interface.
+        ui_name_id: u16,
+    },
+    /// The parameters of a character variant feature, `cv01` to `cv99`.
+    CharacterVariant(CharacterVariantParams),
+}
+
+/// The design size of a font and the range of sizes it is intended for, in decipoints.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SizeParams {
+    /// The size the font was designed for.
+    pub design_size: u16,
+    /// Identifies the fonts of a family that differ only in their intended size range, or zero
+    /// if there is no range.
+    pub subfamily_id: u16,
+    /// The name of the size range for display in a menu.
+    pub subfamily_name_id: u16,
+    /// The smallest size the font is intended for, exclusive.
+    pub range_start: u16,
+    /// The largest size the font is intended for, inclusive.
+    pub range_end: u16,
+}
+
+/// The names and characters of a character variant feature.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CharacterVariantParams {
+    /// The name of the feature for display in a user interface, or zero if it has none.
+    pub ui_label_name_id: u16,
+    /// Tooltip text for the feature, or zero if it has none.
+    pub tooltip_name_id: u16,
+    /// Sample text illustrating the feature, or zero if it has none.
+    pub sample_text_name_id: u16,
+    /// The number of feature parameters that have names.
+    pub num_named_parameters: u16,
+    /// The name of the first named parameter, the names of the others follow consecutively.
+    pub first_param_ui_label_name_id: u16,
+    /// The characters the feature has variants for.
+    pub characters: Vec<u32>,
+}
+
 pub struct LookupList<T> {
     scope_owned: ReadScopeOwned,
     lookup_offsets: Vec<u16>,
@@ -85,6 +170,7 @@ pub struct Lookup<'a, T: LayoutTableType> {
     lookup_type: LookupType<T>,
     pub lookup_flag: u16,
     subtable_offsets: ReadArray<'a, U16Be>,
+    pub mark_filtering_set: Option<u16>,
     phantom: PhantomData<T>,
 }
 
@@ -114,7 +200,7 @@ pub enum LookupType<T: LayoutTableType> {
     Extension,
 }
 
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum SubstLookupType {
     SingleSubst,
     MultipleSubst,
@@ -125,7 +211,7 @@ pub enum SubstLookupType {
     ReverseChainSingleSubst,
 }
 
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum PosLookupType {
     SinglePos,
     PairPos,
@@ -138,24 +224,71 @@ pub enum PosLookupType {
 }
 
 pub enum SubstLookup {
-    SingleSubst(Vec<SingleSubst>),
-    MultipleSubst(Vec<MultipleSubst>),
-    AlternateSubst(Vec<AlternateSubst>),
-    LigatureSubst(Vec<LigatureSubst>),
-    ContextSubst(Vec<ContextLookup<GSUB>>),
-    ChainContextSubst(Vec<ChainContextLookup<GSUB>>),
-    ReverseChainSingleSubst(Vec<ReverseChainSingleSubst>),
+    SingleSubst(LazySubtables<GSUB, SingleSubst>),
+    MultipleSubst(LazySubtables<GSUB, MultipleSubst>),
+    AlternateSubst(LazySubtables<GSUB, AlternateSubst>),
+    LigatureSubst(LazySubtables<GSUB, LigatureSubst>),
+    ContextSubst(LazySubtables<GSUB, ContextLookup<GSUB>>),
+    ChainContextSubst(LazySubtables<GSUB, ChainContextLookup<GSUB>>),
+    ReverseChainSingleSubst(LazySubtables<GSUB, ReverseChainSingleSubst>),
 }
 
 pub enum PosLookup {
-    SinglePos(Vec<SinglePos>),
-    PairPos(Vec<PairPos>),
-    CursivePos(Vec<CursivePos>),
-    MarkBasePos(Vec<MarkBasePos>),
-    MarkLigPos(Vec<MarkLigPos>),
-    MarkMarkPos(Vec<MarkBasePos>),
-    ContextPos(Vec<ContextLookup<GPOS>>),
-    ChainContextPos(Vec<ChainContextLookup<GPOS>>),
+    SinglePos(LazySubtables<GPOS, SinglePos>),
+    PairPos(LazySubtables<GPOS, PairPos>),
+    CursivePos(LazySubtables<GPOS, CursivePos>),
+    MarkBasePos(LazySubtables<GPOS, MarkBasePos>),
+    MarkLigPos(LazySubtables<GPOS, MarkLigPos>),
+    MarkMarkPos(LazySubtables<GPOS, MarkBasePos>),
+    ContextPos(LazySubtables<GPOS, ContextLookup<GPOS>>),
+    ChainContextPos(LazySubtables<GPOS, ChainContextLookup<GPOS>>),
+}
+
+impl SubstLookup {
+    pub fn lookup_type(&self) -> SubstLookupType {
+        match self {
+            SubstLookup::SingleSubst(_) => SubstLookupType::SingleSubst,
+            SubstLookup::MultipleSubst(_) => SubstLookupType::MultipleSubst,
+            SubstLookup::AlternateSubst(_) => SubstLookupType::AlternateSubst,
+            SubstLookup::LigatureSubst(_) => SubstLookupType::LigatureSubst,
+            SubstLookup::ContextSubst(_) => SubstLookupType::ContextSubst,
+            SubstLookup::ChainContextSubst(_) => SubstLookupType::ChainContextSubst,
+            SubstLookup::ReverseChainSingleSubst(_) => SubstLookupType::ReverseChainSingleSubst,
+        }
+    }
+}
+
+impl PosLookup {
+    pub fn lookup_type(&self) -> PosLookupType {
+        match self {
+            PosLookup::SinglePos(_) => PosLookupType::SinglePos,
+            PosLookup::PairPos(_) => PosLookupType::PairPos,
+            PosLookup::CursivePos(_) => PosLookupType::CursivePos,
+            PosLookup::MarkBasePos(_) => PosLookupType::MarkBasePos,
+            PosLookup::MarkLigPos(_) => PosLookupType::MarkLigPos,
+            PosLookup::MarkMarkPos(_) => PosLookupType::MarkMarkPos,
+            PosLookup::ContextPos(_) => PosLookupType::ContextPos,
+            PosLookup::ChainContextPos(_) => PosLookupType::ChainContextPos,
+        }
+    }
+}
+
+/// The subtables of a lookup, which are decoded the first time they are used.
+///
+/// Lookups in large fonts can have thousands of subtables, of which only a few may apply to the
+/// text being shaped. Only the offsets of the subtables are read when the lookup is cached, each
+/// subtable is decoded and cached individually when it is first accessed. Subtables that fail to
+/// decode are skipped with a warning.
+pub struct LazySubtables<T: LayoutTableType, S> {
+    cache: Weak<LayoutCacheData<T>>,
+    /// The offsets of the subtables from the start of the lookup list
+    offsets: Vec<usize>,
+    subtables: Vec<OnceCell<Option<S>>>,
+}
+
+pub struct LazySubtablesIter<'b, T: LayoutTableType, S> {
+    subtables: &'b LazySubtables<T, S>,
+    index: usize,
 }
 
 pub trait LayoutTableType: Sized {
@@ -172,10 +305,10 @@ impl<'a> ReadBinary<'a> for GDEFTable {
 
         let major_version = ctxt.read_u16be()?;
         ctxt.check(major_version == 1)?;
-        let _minor_version = ctxt.read_u16be()?;
+        let minor_version = ctxt.read_u16be()?;
         let glyph_classdef_offset = usize::from(ctxt.read_u16be()?);
         let _attach_list_offset = usize::from(ctxt.read_u16be()?);
-        let _lig_caret_list_offset = usize::from(ctxt.read_u16be()?);
+        let lig_caret_list_offset = usize::from(ctxt.read_u16be()?);
         // MarkAttachClassDef was added to GDEF in OpenType 1.2 but they did not change the GDEF
         // version. This means that it's not possible to know from the version alone whether the
         // field should be read. Some implementations use GSUB/GPOS to determine if it should be
@@ -185,6 +318,11 @@ impl<'a> ReadBinary<'a> for GDEFTable {
         //
         // See: https://github.com/yeslogic/prince/issues/297 for more detail.
         let mark_attach_classdef_offset = usize::from(ctxt.read_u16be()?);
+        let mark_glyph_sets_def_offset = if minor_version >= 2 {
+            usize::from(ctxt.read_u16be()?)
+        } else {
+            0
+        };
 
         let gdef_header_size = 6 * size::U16;
 
@@ -207,16 +345,12 @@ impl<'a> ReadBinary<'a> for GDEFTable {
                     Some(table.offset(attach_list_offset))
                 };
 
-                let opt_lig_caret_list = if lig_caret_list_offset >= table.data().len() {
-                    return Err(ParseError::BadOffset);
-                } else if lig_caret_list_offset == 0 {
-                    None
-                } else if lig_caret_list_offset < gdef_header_size {
-                    return Err(ParseError::BadOffset);
-                } else {
-                    Some(table.offset(lig_caret_list_offset))
-                };
         */
+        let opt_lig_caret_list = if lig_caret_list_offset < gdef_header_size {
+            None
+        } else {
+            Some(table.offset(lig_caret_list_offset).read::<LigCaretList>()?)
+        };
         let opt_mark_attach_classdef = if mark_attach_classdef_offset == 0 {
             None
         } else if mark_attach_classdef_offset < gdef_header_size {
@@ -228,16 +362,141 @@ impl<'a> ReadBinary<'a> for GDEFTable {
                     .read::<ClassDef>()?,
             )
         };
+        let opt_mark_glyph_sets = if mark_glyph_sets_def_offset < gdef_header_size {
+            None
+        } else {
+            Some(
+                table
+                    .offset(mark_glyph_sets_def_offset)
+                    .read::<MarkGlyphSets>()?,
+            )
+        };
 
         Ok(GDEFTable {
             opt_glyph_classdef,
             // opt_attach_list,
-            // opt_lig_caret_list,
+            opt_lig_caret_list,
             opt_mark_attach_classdef,
+            opt_mark_glyph_sets,
+        })
+    }
+}
+
+impl<'a> ReadBinary<'a> for LigCaretList {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let table = ctxt.scope();
+        let coverage_offset = usize::from(ctxt.read_u16be()?);
+        let coverage = table.offset(coverage_offset).read::<Coverage>()?;
+        let lig_glyph_count = usize::from(ctxt.read_u16be()?);
+        let lig_glyph_offsets = ctxt.read_array::<U16Be>(lig_glyph_count)?;
+        let mut lig_glyphs = Vec::with_capacity(lig_glyph_count);
+        for lig_glyph_offset in &lig_glyph_offsets {
+            let lig_glyph = table.offset(usize::from(lig_glyph_offset));
+            let mut lig_glyph_ctxt = lig_glyph.ctxt();
+            let caret_count = usize::from(lig_glyph_ctxt.read_u16be()?);
+            let caret_value_offsets = lig_glyph_ctxt.read_array::<U16Be>(caret_count)?;
+            let caret_values = caret_value_offsets
+                .iter()
+                .map(|offset| lig_glyph.offset(usize::from(offset)).read::<CaretValue>())
+                .collect::<Result<Vec<_>, _>>()?;
+            lig_glyphs.push(caret_values);
+        }
+        Ok(LigCaretList {
+            coverage,
+            lig_glyphs,
         })
     }
 }
 
+impl<'a> ReadBinary<'a> for CaretValue {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let table = ctxt.scope();
+        match ctxt.read_u16be()? {
+            1 => Ok(CaretValue::Coordinate(ctxt.read_i16be()?)),
+            2 => Ok(CaretValue::ContourPoint(ctxt.read_u16be()?)),
+            3 => {
+                let coordinate = ctxt.read_i16be()?;
+                let device_offset = usize::from(ctxt.read_u16be()?);
+                if device_offset == 0 {
+                    return Ok(CaretValue::Coordinate(coordinate));
+                }
+                let mut device = table.offset(device_offset).ctxt();
+                let delta_set_outer_index = device.read_u16be()?;
+                let delta_set_inner_index = device.read_u16be()?;
+                let delta_format = device.read_u16be()?;
+                // Device tables with hinting deltas are not supported, their coordinate is used
+                // as is
+                if delta_format == VARIATION_INDEX_FORMAT {
+                    Ok(CaretValue::VariableCoordinate {
+                        coordinate,
+                        delta_set_outer_index,
+                        delta_set_inner_index,
+                    })
+                } else {
+                    Ok(CaretValue::Coordinate(coordinate))
+                }
+            }
+            _ => Err(ParseError::BadVersion),
+        }
+    }
+}
+
+impl LigCaretList {
+    /// Returns the caret values of `glyph`, or `None` if it has no carets.
+    pub fn caret_values(&self, glyph: u16) -> Option<&[CaretValue]> {
+        let index = self.coverage.glyph_coverage_value(glyph)?;
+        self.lig_glyphs
+            .get(usize::from(index))
+            .map(|caret_values| caret_values.as_slice())
+    }
+}
+
+impl<'a> ReadBinary<'a> for MarkGlyphSets {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let table = ctxt.scope();
+        let format = ctxt.read_u16be()?;
+        ctxt.check(format == 1)?;
+        let mark_glyph_set_count = usize::from(ctxt.read_u16be()?);
+        let coverage_offsets = ctxt.read_array::<U32Be>(mark_glyph_set_count)?;
+        let coverages = coverage_offsets
+            .iter()
+            .map(|offset| {
+                let offset = usize::try_from(offset)?;
+                table.offset(offset).read::<Coverage>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(MarkGlyphSets { coverages })
+    }
+}
+
+impl MarkGlyphSets {
+    /// Returns the number of mark glyph sets.
+    pub fn len(&self) -> usize {
+        self.coverages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.coverages.is_empty()
+    }
+
+    /// Returns `true` if `glyph` is in the mark glyph set at `set_index`.
+    ///
+    /// Returns `false` if there is no set at `set_index`.
+    pub fn contains(&self, set_index: u16, glyph: u16) -> bool {
+        self.coverages
+            .get(usize::from(set_index))
+            .map_or(false, |coverage| {
+                coverage.glyph_coverage_value(glyph).is_some()
+            })
+    }
+}
+
 impl<'a, T> ReadBinary<'a> for LayoutTable<T> {
     type HostType = Self;
 
@@ -372,7 +631,7 @@ impl<'a> ReadBinaryDep<'a> for FeatureRecord {
         let feature_offset = ctxt.read_u16be()?;
         let feature_table = scope
             .offset(usize::from(feature_offset))
-            .read::<FeatureTable>()?;
+            .read_dep::<FeatureTable>((feature_tag, scope))?;
         Ok(FeatureRecord {
             feature_tag,
             feature_table,
@@ -386,20 +645,136 @@ impl<'a> ReadFixedSizeDep<'a> for FeatureRecord {
     }
 }
 
-impl<'a> ReadBinary<'a> for FeatureTable {
+impl<'a> ReadBinaryDep<'a> for FeatureTable {
+    /// The tag of the feature and the scope of the feature list.
+    type Args = (u32, ReadScope<'a>);
     type HostType = Self;
 
-    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
-        let _feature_params = usize::from(ctxt.read_u16be()?);
+    fn read_dep(ctxt: &mut ReadCtxt<'a>, args: Self::Args) -> Result<Self, ParseError> {
+        let (feature_tag, feature_list_scope) = args;
+        let scope = ctxt.scope();
+        let feature_params_offset = usize::from(ctxt.read_u16be()?);
         let lookup_index_count = usize::from(ctxt.read_u16be()?);
         let lookup_indices = ctxt.read_array::<U16Be>(lookup_index_count)?.to_vec();
+
+        // Feature parameters are informational, so ones that can't be read are ignored
+        let opt_feature_params = if feature_params_offset == 0 {
+            None
+        } else if feature_tag == tag::SIZE {
+            // Some early fonts have the offset from the start of the feature list
+            let read_size = |scope: &ReadScope<'a>| {
+                scope
+                    .offset(feature_params_offset)
+                    .read::<SizeParams>()
+                    .ok()
+                    .filter(SizeParams::is_valid)
+            };
+            read_size(&scope)
+                .or_else(|| read_size(&feature_list_scope))
+                .map(FeatureParams::Size)
+        } else if is_numbered_feature(feature_tag, *b"ss") {
+            let mut params = scope.offset(feature_params_offset).ctxt();
+            match (params.read_u16be(), params.read_u16be()) {
+                (Ok(0), Ok(ui_name_id)) => Some(FeatureParams::StylisticSet { ui_name_id }),
+                _ => None,
+            }
+        } else if is_numbered_feature(feature_tag, *b"cv") {
+            scope
+                .offset(feature_params_offset)
+                .read::<CharacterVariantParams>()
+                .ok()
+                .map(FeatureParams::CharacterVariant)
+        } else {
+            None
+        };
+
         Ok(FeatureTable {
-            _feature_params,
+            opt_feature_params,
             lookup_indices,
         })
     }
 }
 
+/// Returns `true` if `feature_tag` is `prefix` followed by two digits, such as `ss01`.
+fn is_numbered_feature(feature_tag: u32, prefix: [u8; 2]) -> bool {
+    let bytes = feature_tag.to_be_bytes();
+    bytes[..2] == prefix && bytes[2].is_ascii_digit() && bytes[3].is_ascii_digit()
+}
+
+impl<'a> ReadBinary<'a> for SizeParams {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        Ok(SizeParams {
+            design_size: ctxt.read_u16be()?,
+            subfamily_id: ctxt.read_u16be()?,
+            subfamily_name_id: ctxt.read_u16be()?,
+            range_start: ctxt.read_u16be()?,
+            range_end: ctxt.read_u16be()?,
+        })
+    }
+}
+
+impl SizeParams {
+    /// Returns `true` if the parameters are consistent, which is used to detect parameters read
+    /// from the wrong offset.
+    fn is_valid(&self) -> bool {
+        if self.design_size == 0 {
+            false
+        } else if self.subfamily_id == 0 && self.subfamily_name_id == 0 {
+            self.range_start == 0 && self.range_end == 0
+        } else {
+            self.range_start <= self.design_size
+                && self.design_size <= self.range_end
+                && (256..=32767).contains(&self.subfamily_name_id)
+        }
+    }
+
+    /// Returns `true` if the font is intended for text of `point_size` points.
+    ///
+    /// Without a size range the font is only intended for its design size.
+    pub fn is_intended_for(&self, point_size: f32) -> bool {
+        let decipoints = point_size * 10.;
+        if self.range_end == 0 {
+            (decipoints - f32::from(self.design_size)).abs() < 0.5
+        } else {
+            f32::from(self.range_start) < decipoints && decipoints <= f32::from(self.range_end)
+        }
+    }
+}
+
+impl<'a> ReadBinary<'a> for CharacterVariantParams {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let format = ctxt.read_u16be()?;
+        ctxt.check(format == 0)?;
+        let ui_label_name_id = ctxt.read_u16be()?;
+        let tooltip_name_id = ctxt.read_u16be()?;
+        let sample_text_name_id = ctxt.read_u16be()?;
+        let num_named_parameters = ctxt.read_u16be()?;
+        let first_param_ui_label_name_id = ctxt.read_u16be()?;
+        let char_count = usize::from(ctxt.read_u16be()?);
+        let characters = ctxt.read_array::<U24Be>(char_count)?.to_vec();
+        Ok(CharacterVariantParams {
+            ui_label_name_id,
+            tooltip_name_id,
+            sample_text_name_id,
+            num_named_parameters,
+            first_param_ui_label_name_id,
+            characters,
+        })
+    }
+}
+
+impl CharacterVariantParams {
+    /// The name IDs of the named parameters of the feature, in order.
+    pub fn param_ui_label_name_ids(&self) -> impl Iterator<Item = u16> {
+        let first = self.first_param_ui_label_name_id;
+        (0..self.num_named_parameters).filter_map(move |i| first.checked_add(i))
+    }
+}
+
 impl<'a, T> ReadBinary<'a> for LookupList<T> {
     type HostType = Self;
 
@@ -438,24 +813,87 @@ impl LangSys {
 }
 
 impl<T> LayoutTable<T> {
+    /// Returns the tags of the scripts in the script list.
+    pub fn script_tags(&self) -> Vec<u32> {
+        self.opt_script_list
+            .iter()
+            .flat_map(|script_list| &script_list.script_records)
+            .map(|script_record| script_record.script_tag)
+            .collect()
+    }
+
+    /// Returns the tags of the features in the feature list, without duplicates.
+    pub fn feature_tags(&self) -> Vec<u32> {
+        let mut feature_tags = self
+            .opt_feature_list
+            .iter()
+            .flat_map(|feature_list| &feature_list.feature_records)
+            .map(|feature_record| feature_record.feature_tag)
+            .collect::<Vec<_>>();
+        feature_tags.sort_unstable();
+        feature_tags.dedup();
+        feature_tags
+    }
+
+    /// Returns the parameters of the first feature in the feature list with the tag
+    /// `feature_tag` that has them.
+    pub fn feature_params(&self, feature_tag: u32) -> Option<&FeatureParams> {
+        self.opt_feature_list
+            .iter()
+            .flat_map(|feature_list| &feature_list.feature_records)
+            .filter(|feature_record| feature_record.feature_tag == feature_tag)
+            .find_map(|feature_record| feature_record.feature_table.opt_feature_params.as_ref())
+    }
+
+    /// Find the script table of `script_tag`.
+    ///
+    /// For the Indic scripts and Myanmar, which have two tags, the version 2 tag such as `dev2` is
+    /// tried before the version 1 tag such as `deva`, whichever of them is passed.
     pub fn find_script(&self, script_tag: u32) -> Result<Option<&ScriptTable>, ParseError> {
+        Ok(self
+            .find_script_and_tag(script_tag)?
+            .map(|(_tag, script_table)| script_table))
+    }
+
+    /// Find the script table of `script_tag` as for `find_script`, also returning the tag of the
+    /// script that was found.
+    pub fn find_script_and_tag(
+        &self,
+        script_tag: u32,
+    ) -> Result<Option<(u32, &ScriptTable)>, ParseError> {
         if let Some(ref script_list) = self.opt_script_list {
-            if let Some(ref script_table) = script_list.find_script(script_tag)? {
-                return Ok(Some(script_table));
+            let (versions, unversioned) = (versioned_script_tags(script_tag), [script_tag]);
+            let script_tags = match versions {
+                Some(ref versions) => &versions[..],
+                None => &unversioned[..],
+            };
+            for &script_tag in script_tags {
+                if let Some(script_table) = script_list.find_script(script_tag)? {
+                    return Ok(Some((script_tag, script_table)));
+                }
             }
         }
         Ok(None)
     }
 
+    /// Find the script table of `script_tag` as for `find_script`, falling back to the default
+    /// script.
+    ///
+    /// The `DFLT` script is tried first, then `dflt` which some fonts use by mistake, and finally
+    /// `latn`, which some older fonts put their features under regardless of the script they
+    /// support.
     pub fn find_script_or_default(
         &self,
         script_tag: u32,
     ) -> Result<Option<&ScriptTable>, ParseError> {
+        if let Some(script_table) = self.find_script(script_tag)? {
+            return Ok(Some(script_table));
+        }
         if let Some(ref script_list) = self.opt_script_list {
-            if let Some(ref script_table) = script_list.find_script(script_tag)? {
-                return Ok(Some(&script_table));
-            } else {
-                return script_list.find_script(tag::DFLT);
+            for &default_tag in &[tag::DFLT, tag!(b"dflt"), tag::LATN] {
+                if let Some(script_table) = script_list.find_script(default_tag)? {
+                    return Ok(Some(script_table));
+                }
             }
         }
         Ok(None)
@@ -478,6 +916,33 @@ impl<T> LayoutTable<T> {
         Ok(None)
     }
 
+    /// Find all features referenced by `langsys` that have the tag `feature_tag`.
+    ///
+    /// A language system may reference more than one feature record with the same tag, in which
+    /// case all of them are returned in the order they are referenced. Feature indices that are
+    /// referenced more than once are only returned the first time.
+    pub fn find_langsys_features(
+        &self,
+        langsys: &LangSys,
+        feature_tag: u32,
+    ) -> Result<Vec<&FeatureTable>, ParseError> {
+        let mut feature_tables = Vec::new();
+        if let Some(ref feature_list) = self.opt_feature_list {
+            let mut seen = BTreeSet::new();
+            for feature_index in &langsys.feature_indices {
+                if !seen.insert(*feature_index) {
+                    continue;
+                }
+                let feature_record =
+                    feature_list.nth_feature_record(usize::from(*feature_index))?;
+                if feature_record.feature_tag == feature_tag {
+                    feature_tables.push(&feature_record.feature_table);
+                }
+            }
+        }
+        Ok(feature_tables)
+    }
+
     pub fn feature_by_index(&self, feature_index: u16) -> Result<&FeatureRecord, ParseError> {
         if let Some(ref feature_list) = self.opt_feature_list {
             let feature_record = feature_list.nth_feature_record(usize::from(feature_index))?;
@@ -488,6 +953,25 @@ impl<T> LayoutTable<T> {
     }
 }
 
+/// Returns the version 2 and version 1 tags of the Indic scripts and Myanmar, given either of
+/// them.
+fn versioned_script_tags(script_tag: u32) -> Option<[u32; 2]> {
+    let tags = match script_tag {
+        tag::DEVA | tag::DEV2 => [tag::DEV2, tag::DEVA],
+        tag::BENG | tag::BNG2 => [tag::BNG2, tag::BENG],
+        tag::GURU | tag::GUR2 => [tag::GUR2, tag::GURU],
+        tag::GUJR | tag::GJR2 => [tag::GJR2, tag::GUJR],
+        tag::ORYA | tag::ORY2 => [tag::ORY2, tag::ORYA],
+        tag::TAML | tag::TML2 => [tag::TML2, tag::TAML],
+        tag::TELU | tag::TEL2 => [tag::TEL2, tag::TELU],
+        tag::KNDA | tag::KND2 => [tag::KND2, tag::KNDA],
+        tag::MLYM | tag::MLM2 => [tag::MLM2, tag::MLYM],
+        tag::MYMR | tag::MYM2 => [tag::MYM2, tag::MYMR],
+        _ => return None,
+    };
+    Some(tags)
+}
+
 impl ScriptList {
     pub fn find_script(&self, script_tag: u32) -> Result<Option<&ScriptTable>, ParseError> {
         for script_record in &self.script_records {
@@ -513,6 +997,16 @@ impl ScriptTable {
         Ok(None)
     }
 
+    /// Iterate over the default language system, if present, followed by the language systems
+    /// of each language record.
+    pub fn langsys_iter(&self) -> impl Iterator<Item = &LangSys> {
+        self.opt_default_langsys.iter().chain(
+            self.langsys_records
+                .iter()
+                .map(|langsys_record| &langsys_record.langsys_table),
+        )
+    }
+
     pub fn find_langsys_or_default(
         &self,
         opt_lang_tag: Option<u32>,
@@ -559,6 +1053,21 @@ impl<T: LayoutTableType> LookupList<T> {
             .offset(usize::from(lookup_table_offset))
             .read::<Lookup<'_, T>>()
     }
+
+    fn lazy_subtables<S>(
+        &self,
+        cache: &LayoutCache<T>,
+        lookup_index: usize,
+        lookup: &Lookup<'_, T>,
+    ) -> Result<LazySubtables<T, S>, ParseError> {
+        let lookup_table_offset = usize::from(self.lookup_offsets[lookup_index]);
+        let offsets = lookup
+            .subtable_offsets()?
+            .into_iter()
+            .map(|subtable_offset| lookup_table_offset + subtable_offset)
+            .collect();
+        Ok(LazySubtables::new(cache, offsets))
+    }
 }
 
 impl LookupList<GSUB> {
@@ -566,18 +1075,21 @@ impl LookupList<GSUB> {
         &self,
         cache: &LayoutCache<GSUB>,
         lookup_index: usize,
-    ) -> Result<Rc<LookupCacheItem<SubstLookup>>, ParseError> {
-        let lookup_vec = &mut cache.lookup_cache.borrow_mut();
+    ) -> Result<Arc<LookupCacheItem<SubstLookup>>, ParseError> {
+        if let Some(Some(lookup_cache_item)) = cache.lookup_cache.read().unwrap().get(lookup_index)
+        {
+            return Ok(Arc::clone(lookup_cache_item));
+        }
+        // The lookup is read without holding the lock. If another thread reads the same lookup
+        // concurrently the first one stored is kept.
+        let lookup_cache_item = Arc::new(self.read_lookup_gsub(cache, lookup_index)?);
+        let mut lookup_vec = cache.lookup_cache.write().unwrap();
         if lookup_index >= lookup_vec.len() {
             lookup_vec.resize(lookup_index + 1, None);
         }
-        if let Some(ref lookup_cache_item) = lookup_vec[lookup_index] {
-            Ok(Rc::clone(lookup_cache_item))
-        } else {
-            let lookup_cache_item = Rc::new(self.read_lookup_gsub(cache, lookup_index)?);
-            lookup_vec[lookup_index] = Some(Rc::clone(&lookup_cache_item));
-            Ok(lookup_cache_item)
-        }
+        Ok(Arc::clone(
+            lookup_vec[lookup_index].get_or_insert(lookup_cache_item),
+        ))
     }
 
     fn read_lookup_gsub(
@@ -589,30 +1101,31 @@ impl LookupList<GSUB> {
         let lookup_flag = LookupFlag(lookup.lookup_flag);
         let lookup_type = lookup.get_lookup_type()?;
         let lookup_subtables = match lookup_type {
-            SubstLookupType::SingleSubst => {
-                SubstLookup::SingleSubst(lookup.read_subtables::<SingleSubst>(cache)?)
-            }
-            SubstLookupType::MultipleSubst => {
-                SubstLookup::MultipleSubst(lookup.read_subtables::<MultipleSubst>(cache)?)
-            }
-            SubstLookupType::AlternateSubst => {
-                SubstLookup::AlternateSubst(lookup.read_subtables::<AlternateSubst>(cache)?)
-            }
-            SubstLookupType::LigatureSubst => {
-                SubstLookup::LigatureSubst(lookup.read_subtables::<LigatureSubst>(cache)?)
-            }
-            SubstLookupType::ContextSubst => {
-                SubstLookup::ContextSubst(lookup.read_subtables::<ContextLookup<GSUB>>(cache)?)
-            }
+            SubstLookupType::SingleSubst => SubstLookup::SingleSubst(
+                self.lazy_subtables::<SingleSubst>(cache, lookup_index, &lookup)?,
+            ),
+            SubstLookupType::MultipleSubst => SubstLookup::MultipleSubst(
+                self.lazy_subtables::<MultipleSubst>(cache, lookup_index, &lookup)?,
+            ),
+            SubstLookupType::AlternateSubst => SubstLookup::AlternateSubst(
+                self.lazy_subtables::<AlternateSubst>(cache, lookup_index, &lookup)?,
+            ),
+            SubstLookupType::LigatureSubst => SubstLookup::LigatureSubst(
+                self.lazy_subtables::<LigatureSubst>(cache, lookup_index, &lookup)?,
+            ),
+            SubstLookupType::ContextSubst => SubstLookup::ContextSubst(
+                self.lazy_subtables::<ContextLookup<GSUB>>(cache, lookup_index, &lookup)?,
+            ),
             SubstLookupType::ChainContextSubst => SubstLookup::ChainContextSubst(
-                lookup.read_subtables::<ChainContextLookup<GSUB>>(cache)?,
+                self.lazy_subtables::<ChainContextLookup<GSUB>>(cache, lookup_index, &lookup)?,
             ),
             SubstLookupType::ReverseChainSingleSubst => SubstLookup::ReverseChainSingleSubst(
-                lookup.read_subtables::<ReverseChainSingleSubst>(cache)?,
+                self.lazy_subtables::<ReverseChainSingleSubst>(cache, lookup_index, &lookup)?,
             ),
         };
         Ok(LookupCacheItem {
             lookup_flag,
+            mark_filtering_set: lookup.mark_filtering_set,
             lookup_subtables,
         })
     }
@@ -623,18 +1136,19 @@ impl LookupList<GPOS> {
         &self,
         cache: &LayoutCache<GPOS>,
         lookup_index: usize,
-    ) -> Result<Rc<LookupCacheItem<PosLookup>>, ParseError> {
-        let lookup_vec = &mut cache.lookup_cache.borrow_mut();
+    ) -> Result<Arc<LookupCacheItem<PosLookup>>, ParseError> {
+        if let Some(Some(lookup_cache_item)) = cache.lookup_cache.read().unwrap().get(lookup_index)
+        {
+            return Ok(Arc::clone(lookup_cache_item));
+        }
+        let lookup_cache_item = Arc::new(self.read_lookup_gpos(cache, lookup_index)?);
+        let mut lookup_vec = cache.lookup_cache.write().unwrap();
         if lookup_index >= lookup_vec.len() {
             lookup_vec.resize(lookup_index + 1, None);
         }
-        if let Some(ref lookup_cache_item) = lookup_vec[lookup_index] {
-            Ok(Rc::clone(&lookup_cache_item))
-        } else {
-            let lookup_cache_item = Rc::new(self.read_lookup_gpos(cache, lookup_index)?);
-            lookup_vec[lookup_index] = Some(Rc::clone(&lookup_cache_item));
-            Ok(lookup_cache_item)
-        }
+        Ok(Arc::clone(
+            lookup_vec[lookup_index].get_or_insert(lookup_cache_item),
+        ))
     }
 
     fn read_lookup_gpos(
@@ -645,32 +1159,38 @@ impl LookupList<GPOS> {
         let lookup = self.lookup(lookup_index)?;
         let lookup_flag = LookupFlag(lookup.lookup_flag);
         let lookup_type = lookup.get_lookup_type()?;
-        let lookup_subtables = match lookup_type {
-            PosLookupType::SinglePos => {
-                PosLookup::SinglePos(lookup.read_subtables::<SinglePos>(cache)?)
-            }
-            PosLookupType::PairPos => PosLookup::PairPos(lookup.read_subtables::<PairPos>(cache)?),
-            PosLookupType::CursivePos => {
-                PosLookup::CursivePos(lookup.read_subtables::<CursivePos>(cache)?)
-            }
-            PosLookupType::MarkBasePos => {
-                PosLookup::MarkBasePos(lookup.read_subtables::<MarkBasePos>(cache)?)
-            }
-            PosLookupType::MarkLigPos => {
-                PosLookup::MarkLigPos(lookup.read_subtables::<MarkLigPos>(cache)?)
-            }
-            PosLookupType::MarkMarkPos => {
-                PosLookup::MarkMarkPos(lookup.read_subtables::<MarkBasePos>(cache)?)
-            }
-            PosLookupType::ContextPos => {
-                PosLookup::ContextPos(lookup.read_subtables::<ContextLookup<GPOS>>(cache)?)
-            }
-            PosLookupType::ChainContextPos => PosLookup::ChainContextPos(
-                lookup.read_subtables::<ChainContextLookup<GPOS>>(cache)?,
-            ),
-        };
+        let lookup_subtables =
+            match lookup_type {
+                PosLookupType::SinglePos => PosLookup::SinglePos(
+                    self.lazy_subtables::<SinglePos>(cache, lookup_index, &lookup)?,
+                ),
+                PosLookupType::PairPos => PosLookup::PairPos(self.lazy_subtables::<PairPos>(
+                    cache,
+                    lookup_index,
+                    &lookup,
+                )?),
+                PosLookupType::CursivePos => PosLookup::CursivePos(
+                    self.lazy_subtables::<CursivePos>(cache, lookup_index, &lookup)?,
+                ),
+                PosLookupType::MarkBasePos => PosLookup::MarkBasePos(
+                    self.lazy_subtables::<MarkBasePos>(cache, lookup_index, &lookup)?,
+                ),
+                PosLookupType::MarkLigPos => PosLookup::MarkLigPos(
+                    self.lazy_subtables::<MarkLigPos>(cache, lookup_index, &lookup)?,
+                ),
+                PosLookupType::MarkMarkPos => PosLookup::MarkMarkPos(
+                    self.lazy_subtables::<MarkBasePos>(cache, lookup_index, &lookup)?,
+                ),
+                PosLookupType::ContextPos => PosLookup::ContextPos(
+                    self.lazy_subtables::<ContextLookup<GPOS>>(cache, lookup_index, &lookup)?,
+                ),
+                PosLookupType::ChainContextPos => PosLookup::ChainContextPos(
+                    self.lazy_subtables::<ChainContextLookup<GPOS>>(cache, lookup_index, &lookup)?,
+                ),
+            };
         Ok(LookupCacheItem {
             lookup_flag,
+            mark_filtering_set: lookup.mark_filtering_set,
             lookup_subtables,
         })
     }
@@ -730,19 +1250,33 @@ impl<'a, T: LayoutTableType> Lookup<'a, T> {
         Ok(None)
     }
 
-    pub fn read_subtables<S: ReadBinaryDep<'a, Args = LayoutCache<T>>>(
-        &self,
-        cache: &LayoutCache<T>,
-    ) -> Result<Vec<S::HostType>, ParseError> {
-        let mut subtables = Vec::new();
-        let subtable_iter = self.smart_subtable_iter()?;
-        for subtable_result in subtable_iter {
-            match subtable_result?.read_dep::<S>(Rc::clone(cache)) {
-                Ok(subtable) => subtables.push(subtable),
-                Err(err) => warn!("skipping invalid subtable: {}", err),
+    /// Returns the offsets of the subtables from the start of the lookup table.
+    ///
+    /// Extension subtables are followed to the offset of the subtable they refer to.
+    fn subtable_offsets(&self) -> Result<Vec<usize>, ParseError> {
+        let lookup_type = self.get_lookup_type()?;
+        let mut offsets = Vec::with_capacity(self.subtable_offsets.len());
+        for subtable_offset in self.subtable_offsets.iter() {
+            let subtable_offset = usize::from(subtable_offset);
+            match self.lookup_type {
+                LookupType::Normal(_) => offsets.push(subtable_offset),
+                LookupType::Extension => {
+                    let ext_subtable = self
+                        .scope
+                        .offset(subtable_offset)
+                        .read::<ExtensionSubst<'_, T>>()?;
+                    if ext_subtable.extension_lookup_type != lookup_type {
+                        return Err(ParseError::BadVersion);
+                    }
+                    let extension_offset = usize::try_from(ext_subtable.extension_offset)?;
+                    let offset = subtable_offset
+                        .checked_add(extension_offset)
+                        .ok_or(ParseError::BadOffset)?;
+                    offsets.push(offset);
+                }
             }
         }
-        Ok(subtables)
+        Ok(offsets)
     }
 }
 
@@ -756,11 +1290,17 @@ impl<'a, T: LayoutTableType> ReadBinary<'a> for Lookup<'a, T> {
         let lookup_flag = ctxt.read_u16be()?;
         let subtable_count = usize::from(ctxt.read_u16be()?);
         let subtable_offsets = ctxt.read_array::<U16Be>(subtable_count)?;
+        let mark_filtering_set = if LookupFlag(lookup_flag).get_use_mark_filtering_set() {
+            Some(ctxt.read_u16be()?)
+        } else {
+            None
+        };
         Ok(Lookup {
             scope,
             lookup_type,
             lookup_flag,
             subtable_offsets,
+            mark_filtering_set,
             phantom: PhantomData,
         })
     }
@@ -902,11 +1442,11 @@ impl LayoutTableType for GPOS {
 
 pub enum SingleSubst {
     Format1 {
-        coverage: Rc<Coverage>,
+        coverage: Arc<Coverage>,
         delta_glyph_index: i16,
     },
     Format2 {
-        coverage: Rc<Coverage>,
+        coverage: Arc<Coverage>,
         substitute_glyph_array: Vec<u16>,
     },
 }
@@ -922,7 +1462,7 @@ impl<'a> ReadBinaryDep<'a> for SingleSubst {
                 let coverage_offset = usize::from(ctxt.read_u16be()?);
                 let coverage = subtable
                     .offset(coverage_offset)
-                    .read_cache::<Coverage>(&mut cache.coverages.borrow_mut())?;
+                    .read_cache::<Coverage>(&mut cache.coverages.lock().unwrap())?;
                 let delta_glyph_index = ctxt.read_i16be()?;
                 Ok(SingleSubst::Format1 {
                     coverage,
@@ -933,7 +1473,7 @@ impl<'a> ReadBinaryDep<'a> for SingleSubst {
                 let coverage_offset = usize::from(ctxt.read_u16be()?);
                 let coverage = subtable
                     .offset(coverage_offset)
-                    .read_cache::<Coverage>(&mut cache.coverages.borrow_mut())?;
+                    .read_cache::<Coverage>(&mut cache.coverages.lock().unwrap())?;
                 let glyph_count = ctxt.read_u16be()?;
                 let substitute_glyph_array =
                     ctxt.read_array::<U16Be>(usize::from(glyph_count))?.to_vec();
@@ -979,7 +1519,7 @@ impl SingleSubst {
 }
 
 pub struct MultipleSubst {
-    coverage: Rc<Coverage>,
+    coverage: Arc<Coverage>,
     sequences: Vec<SequenceTable>,
 }
 
@@ -998,7 +1538,7 @@ impl<'a> ReadBinaryDep<'a> for MultipleSubst {
                 let coverage_offset = usize::from(ctxt.read_u16be()?);
                 let coverage = scope
                     .offset(coverage_offset)
-                    .read_cache::<Coverage>(&mut cache.coverages.borrow_mut())?;
+                    .read_cache::<Coverage>(&mut cache.coverages.lock().unwrap())?;
                 let sequence_count = usize::from(ctxt.read_u16be()?);
                 let sequence_offsets = ctxt.read_array::<U16Be>(sequence_count)?;
                 let sequences = read_objects::<SequenceTable>(&scope, sequence_offsets)?;
@@ -1039,7 +1579,7 @@ impl<'a> ReadBinary<'a> for SequenceTable {
 }
 
 pub struct AlternateSubst {
-    coverage: Rc<Coverage>,
+    coverage: Arc<Coverage>,
     alternatesets: Vec<AlternateSet>,
 }
 
@@ -1058,7 +1598,7 @@ impl<'a> ReadBinaryDep<'a> for AlternateSubst {
                 let coverage_offset = usize::from(ctxt.read_u16be()?);
                 let coverage = scope
                     .offset(coverage_offset)
-                    .read_cache::<Coverage>(&mut cache.coverages.borrow_mut())?;
+                    .read_cache::<Coverage>(&mut cache.coverages.lock().unwrap())?;
                 let alternateset_count = usize::from(ctxt.read_u16be()?);
                 let alternateset_offsets = ctxt.read_array::<U16Be>(alternateset_count)?;
                 let alternatesets = read_objects::<AlternateSet>(&scope, alternateset_offsets)?;
@@ -1098,7 +1638,7 @@ impl<'a> ReadBinary<'a> for AlternateSet {
 }
 
 pub struct LigatureSubst {
-    coverage: Rc<Coverage>,
+    coverage: Arc<Coverage>,
     ligaturesets: Vec<LigatureSet>,
 }
 
@@ -1122,7 +1662,7 @@ impl<'a> ReadBinaryDep<'a> for LigatureSubst {
                 let coverage_offset = usize::from(ctxt.read_u16be()?);
                 let coverage = scope
                     .offset(coverage_offset)
-                    .read_cache::<Coverage>(&mut cache.coverages.borrow_mut())?;
+                    .read_cache::<Coverage>(&mut cache.coverages.lock().unwrap())?;
                 let ligatureset_count = usize::from(ctxt.read_u16be()?);
                 let ligatureset_offsets = ctxt.read_array::<U16Be>(ligatureset_count)?;
                 let ligaturesets = read_objects::<LigatureSet>(&scope, ligatureset_offsets)?;
@@ -1240,20 +1780,96 @@ fn ith_bit_set(flags: u16, i: u16) -> bool {
 
 pub type ValueRecord = Option<Adjust>;
 
-#[derive(Copy, Clone)]
+#[derive(Clone)]
 pub struct Adjust {
     pub x_placement: i16,
     pub y_placement: i16,
     pub x_advance: i16,
     pub y_advance: i16,
+    /// The device or variation index tables adjusting the values, if any.
+    pub opt_devices: Option<Arc<AdjustDevices>>,
+}
+
+/// The device or variation index tables of the values of an `Adjust`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AdjustDevices {
+    pub x_placement: Option<Device>,
+    pub y_placement: Option<Device>,
+    pub x_advance: Option<Device>,
+    pub y_advance: Option<Device>,
+}
+
+/// A device or variation index table, adjusting a value of a `ValueRecord` or `Anchor`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Device {
+    /// Adjustments in pixels for each size from `start_size` to `end_size` pixels per em,
+    /// used to fine-tune positions at small sizes.
+    Hinting {
+        start_size: u16,
+        end_size: u16,
+        deltas: Vec<i8>,
+    },
+    /// The delta-set in the item variation store of the `GDEF` table that adjusts the value
+    /// in the instances of a variable font.
+    VariationIndex {
+        delta_set_outer_index: u16,
+        delta_set_inner_index: u16,
+    },
+}
+
+impl Device {
+    /// Read the device table at `offset` from `scope`.
+    ///
+    /// Returns `None` if `offset` is zero or the table has an unknown delta format.
+    fn read_offset(scope: &ReadScope<'_>, offset: u16) -> Result<Option<Device>, ParseError> {
+        if offset == 0 {
+            return Ok(None);
+        }
+        let mut ctxt = scope.offset(usize::from(offset)).ctxt();
+        let start_size = ctxt.read_u16be()?;
+        let end_size = ctxt.read_u16be()?;
+        match ctxt.read_u16be()? {
+            delta_format @ 1..=3 => {
+                // Deltas are signed 2, 4 or 8-bit values packed into 16-bit words, most
+                // significant bits first
+                let bits = 1 << delta_format;
+                let per_word = 16 / bits;
+                let count = if end_size >= start_size {
+                    usize::from(end_size - start_size) + 1
+                } else {
+                    0
+                };
+                let mut deltas = Vec::with_capacity(count);
+                let mut word = 0;
+                for i in 0..count {
+                    if i % per_word == 0 {
+                        word = ctxt.read_u16be()?;
+                    }
+                    let value = (word << (bits * (i % per_word))) as i16;
+                    deltas.push((value >> (16 - bits)) as i8);
+                }
+                Ok(Some(Device::Hinting {
+                    start_size,
+                    end_size,
+                    deltas,
+                }))
+            }
+            VARIATION_INDEX_FORMAT => Ok(Some(Device::VariationIndex {
+                delta_set_outer_index: start_size,
+                delta_set_inner_index: end_size,
+            })),
+            _ => Ok(None),
+        }
+    }
 }
 
 impl<'a> ReadBinaryDep<'a> for ValueRecord {
-    type Args = ValueFormat;
+    /// The value format and the scope of the table that offsets to device tables are from.
+    type Args = (ValueFormat, ReadScope<'a>);
     type HostType = Self;
 
-    fn read_dep(ctxt: &mut ReadCtxt<'a>, args: ValueFormat) -> Result<Self, ParseError> {
-        let value_format = args;
+    fn read_dep(ctxt: &mut ReadCtxt<'a>, args: Self::Args) -> Result<Self, ParseError> {
+        let (value_format, scope) = args;
         if value_format.is_zero() {
             return Ok(None);
         }
@@ -1277,49 +1893,89 @@ impl<'a> ReadBinaryDep<'a> for ValueRecord {
         } else {
             0
         };
+        let mut devices = AdjustDevices::default();
         if value_format.has_x_placement_device() {
-            let _ = ctxt.read_i16be()?;
+            devices.x_placement = Device::read_offset(&scope, ctxt.read_u16be()?)?;
         }
         if value_format.has_y_placement_device() {
-            let _ = ctxt.read_i16be()?;
+            devices.y_placement = Device::read_offset(&scope, ctxt.read_u16be()?)?;
         }
         if value_format.has_x_advance_device() {
-            let _ = ctxt.read_i16be()?;
+            devices.x_advance = Device::read_offset(&scope, ctxt.read_u16be()?)?;
         }
         if value_format.has_y_advance_device() {
-            let _ = ctxt.read_i16be()?;
+            devices.y_advance = Device::read_offset(&scope, ctxt.read_u16be()?)?;
         }
+        let opt_devices = if devices == AdjustDevices::default() {
+            None
+        } else {
+            Some(Arc::new(devices))
+        };
         Ok(Some(Adjust {
             x_placement: x_pla,
             y_placement: y_pla,
             x_advance: x_adv,
             y_advance: y_adv,
+            opt_devices,
         }))
     }
 }
 
 impl<'a> ReadFixedSizeDep<'a> for ValueRecord {
-    fn size(value_format: ValueFormat) -> usize {
+    fn size((value_format, _scope): Self::Args) -> usize {
         value_format.size()
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug)]
 pub struct Anchor {
     pub x: i16,
     pub y: i16,
+    /// The device or variation index tables adjusting the coordinates, if any.
+    ///
+    /// Anchors of an `Attachment` have had their adjustments applied and have none.
+    pub opt_devices: Option<Arc<AnchorDevices>>,
+    /// The index of a contour point of the glyph outline that the anchor is placed at, in
+    /// preference to `x` and `y`. See `gpos::resolve_anchor_points`.
+    pub opt_anchor_point: Option<u16>,
+}
+
+/// The device or variation index tables of the coordinates of an `Anchor`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AnchorDevices {
+    pub x: Option<Device>,
+    pub y: Option<Device>,
 }
 
 impl<'a> ReadBinary<'a> for Anchor {
     type HostType = Self;
 
     fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let scope = ctxt.scope();
         match ctxt.read_u16be()? {
-            1 | 2 | 3 => {
+            format @ 1..=3 => {
                 let x = ctxt.read_i16be()?;
                 let y = ctxt.read_i16be()?;
-                // Doesn't read other fields because we don't use them
-                Ok(Anchor { x, y })
+                let mut opt_devices = None;
+                let mut opt_anchor_point = None;
+                if format == 2 {
+                    opt_anchor_point = Some(ctxt.read_u16be()?);
+                } else if format == 3 {
+                    let x_device = Device::read_offset(&scope, ctxt.read_u16be()?)?;
+                    let y_device = Device::read_offset(&scope, ctxt.read_u16be()?)?;
+                    if x_device.is_some() || y_device.is_some() {
+                        opt_devices = Some(Arc::new(AnchorDevices {
+                            x: x_device,
+                            y: y_device,
+                        }));
+                    }
+                }
+                Ok(Anchor {
+                    x,
+                    y,
+                    opt_devices,
+                    opt_anchor_point,
+                })
             }
             _ => Err(ParseError::BadVersion),
         }
@@ -1328,11 +1984,11 @@ impl<'a> ReadBinary<'a> for Anchor {
 
 pub enum SinglePos {
     Format1 {
-        coverage: Rc<Coverage>,
+        coverage: Arc<Coverage>,
         value_record: ValueRecord,
     },
     Format2 {
-        coverage: Rc<Coverage>,
+        coverage: Arc<Coverage>,
         value_records: Vec<ValueRecord>,
     },
 }
@@ -1349,9 +2005,9 @@ impl<'a> ReadBinaryDep<'a> for SinglePos {
                 let coverage_offset = usize::from(ctxt.read_u16be()?);
                 let coverage = scope
                     .offset(coverage_offset)
-                    .read_cache::<Coverage>(&mut cache.coverages.borrow_mut())?;
+                    .read_cache::<Coverage>(&mut cache.coverages.lock().unwrap())?;
                 let value_format = ctxt.read::<ValueFormat>()?;
-                let value_record = ctxt.read_dep::<ValueRecord>(value_format)?;
+                let value_record = ctxt.read_dep::<ValueRecord>((value_format, scope))?;
                 Ok(SinglePos::Format1 {
                     coverage,
                     value_record,
@@ -1361,11 +2017,11 @@ impl<'a> ReadBinaryDep<'a> for SinglePos {
                 let coverage_offset = usize::from(ctxt.read_u16be()?);
                 let coverage = scope
                     .offset(coverage_offset)
-                    .read_cache::<Coverage>(&mut cache.coverages.borrow_mut())?;
+                    .read_cache::<Coverage>(&mut cache.coverages.lock().unwrap())?;
                 let value_format = ctxt.read::<ValueFormat>()?;
                 let value_count = usize::from(ctxt.read_u16be()?);
                 let value_records = ctxt
-                    .read_array_dep::<ValueRecord>(value_count, value_format)?
+                    .read_array_dep::<ValueRecord>(value_count, (value_format, scope))?
                     .read_to_vec()?;
                 Ok(SinglePos::Format2 {
                     coverage,
@@ -1382,10 +2038,10 @@ impl SinglePos {
         match *self {
             SinglePos::Format1 {
                 ref coverage,
-                value_record,
+                ref value_record,
             } => {
                 if coverage.glyph_coverage_value(glyph).is_some() {
-                    Ok(value_record)
+                    Ok(value_record.clone())
                 } else {
                     Ok(None)
                 }
@@ -1397,7 +2053,7 @@ impl SinglePos {
                 if let Some(coverage_index) = coverage.glyph_coverage_value(glyph) {
                     let coverage_index = usize::from(coverage_index);
                     value_records.check_index(coverage_index)?;
-                    Ok(value_records[coverage_index])
+                    Ok(value_records[coverage_index].clone())
                 } else {
                     Ok(None)
                 }
@@ -1408,13 +2064,13 @@ impl SinglePos {
 
 pub enum PairPos {
     Format1 {
-        coverage: Rc<Coverage>,
+        coverage: Arc<Coverage>,
         pairsets: Vec<PairSet>,
     },
     Format2 {
-        coverage: Rc<Coverage>,
-        classdef1: Rc<ClassDef>,
-        classdef2: Rc<ClassDef>,
+        coverage: Arc<Coverage>,
+        classdef1: Arc<ClassDef>,
+        classdef2: Arc<ClassDef>,
         class2_count: usize,
         class1_records: Vec<Class1Record>,
     },
@@ -1432,7 +2088,7 @@ impl<'a> ReadBinaryDep<'a> for PairPos {
                 let coverage_offset = usize::from(ctxt.read_u16be()?);
                 let coverage = scope
                     .offset(coverage_offset)
-                    .read_cache::<Coverage>(&mut cache.coverages.borrow_mut())?;
+                    .read_cache::<Coverage>(&mut cache.coverages.lock().unwrap())?;
                 let value_format1 = ctxt.read::<ValueFormat>()?;
                 let value_format2 = ctxt.read::<ValueFormat>()?;
                 let pairset_count = usize::from(ctxt.read_u16be()?);
@@ -1448,23 +2104,23 @@ impl<'a> ReadBinaryDep<'a> for PairPos {
                 let coverage_offset = usize::from(ctxt.read_u16be()?);
                 let coverage = scope
                     .offset(coverage_offset)
-                    .read_cache::<Coverage>(&mut cache.coverages.borrow_mut())?;
+                    .read_cache::<Coverage>(&mut cache.coverages.lock().unwrap())?;
                 let value_format1 = ctxt.read::<ValueFormat>()?;
                 let value_format2 = ctxt.read::<ValueFormat>()?;
                 let classdef1_offset = usize::from(ctxt.read_u16be()?);
                 let classdef2_offset = usize::from(ctxt.read_u16be()?);
                 let classdef1 = scope
                     .offset(classdef1_offset)
-                    .read_cache::<ClassDef>(&mut cache.classdefs.borrow_mut())?;
+                    .read_cache::<ClassDef>(&mut cache.classdefs.lock().unwrap())?;
                 let classdef2 = scope
                     .offset(classdef2_offset)
-                    .read_cache::<ClassDef>(&mut cache.classdefs.borrow_mut())?;
+                    .read_cache::<ClassDef>(&mut cache.classdefs.lock().unwrap())?;
                 let class1_count = usize::from(ctxt.read_u16be()?);
                 let class2_count = usize::from(ctxt.read_u16be()?);
                 let class1_records = ctxt
                     .read_array_dep::<Class1Record>(
                         class1_count,
-                        (class2_count, value_format1, value_format2),
+                        (class2_count, value_format1, value_format2, scope.clone()),
                     )?
                     .read_to_vec()?;
                 Ok(PairPos::Format2 {
@@ -1489,9 +2145,14 @@ impl<'a> ReadBinaryDep<'a> for PairSet {
     type HostType = Self;
 
     fn read_dep(ctxt: &mut ReadCtxt<'a>, args: Self::Args) -> Result<Self, ParseError> {
+        let (value_format1, value_format2) = args;
+        let scope = ctxt.scope();
         let pair_value_count = usize::from(ctxt.read_u16be()?);
         let pair_value_records = ctxt
-            .read_array_dep::<PairValueRecord>(pair_value_count, args)?
+            .read_array_dep::<PairValueRecord>(
+                pair_value_count,
+                (value_format1, value_format2, scope),
+            )?
             .read_to_vec()?;
         Ok(PairSet { pair_value_records })
     }
@@ -1504,14 +2165,14 @@ pub struct PairValueRecord {
 }
 
 impl<'a> ReadBinaryDep<'a> for PairValueRecord {
-    type Args = (ValueFormat, ValueFormat);
+    type Args = (ValueFormat, ValueFormat, ReadScope<'a>);
     type HostType = Self;
 
     fn read_dep(ctxt: &mut ReadCtxt<'a>, args: Self::Args) -> Result<Self, ParseError> {
-        let (value_format1, value_format2) = args;
+        let (value_format1, value_format2, scope) = args;
         let second_glyph = ctxt.read_u16be()?;
-        let value_record1 = ctxt.read_dep::<ValueRecord>(value_format1)?;
-        let value_record2 = ctxt.read_dep::<ValueRecord>(value_format2)?;
+        let value_record1 = ctxt.read_dep::<ValueRecord>((value_format1, scope.clone()))?;
+        let value_record2 = ctxt.read_dep::<ValueRecord>((value_format2, scope))?;
         Ok(PairValueRecord {
             second_glyph,
             value_record1,
@@ -1521,7 +2182,7 @@ impl<'a> ReadBinaryDep<'a> for PairValueRecord {
 }
 
 impl<'a> ReadFixedSizeDep<'a> for PairValueRecord {
-    fn size((value_format1, value_format2): Self::Args) -> usize {
+    fn size((value_format1, value_format2, _scope): Self::Args) -> usize {
         size::U16 + value_format1.size() + value_format2.size()
     }
 }
@@ -1531,21 +2192,21 @@ pub struct Class1Record {
 }
 
 impl<'a> ReadBinaryDep<'a> for Class1Record {
-    type Args = (usize, ValueFormat, ValueFormat);
+    type Args = (usize, ValueFormat, ValueFormat, ReadScope<'a>);
     type HostType = Self;
 
     fn read_dep(ctxt: &mut ReadCtxt<'a>, args: Self::Args) -> Result<Self, ParseError> {
-        let (class2_count, value_format1, value_format2) = args;
+        let (class2_count, value_format1, value_format2, scope) = args;
         let class2_records = ctxt
-            .read_array_dep::<Class2Record>(class2_count, (value_format1, value_format2))?
+            .read_array_dep::<Class2Record>(class2_count, (value_format1, value_format2, scope))?
             .read_to_vec()?;
         Ok(Class1Record { class2_records })
     }
 }
 
 impl<'a> ReadFixedSizeDep<'a> for Class1Record {
-    fn size((class2_count, value_format1, value_format2): Self::Args) -> usize {
-        class2_count * Class2Record::size((value_format1, value_format2))
+    fn size((class2_count, value_format1, value_format2, scope): Self::Args) -> usize {
+        class2_count * Class2Record::size((value_format1, value_format2, scope))
     }
 }
 
@@ -1555,13 +2216,13 @@ pub struct Class2Record {
 }
 
 impl<'a> ReadBinaryDep<'a> for Class2Record {
-    type Args = (ValueFormat, ValueFormat);
+    type Args = (ValueFormat, ValueFormat, ReadScope<'a>);
     type HostType = Self;
 
     fn read_dep(ctxt: &mut ReadCtxt<'a>, args: Self::Args) -> Result<Self, ParseError> {
-        let (value_format1, value_format2) = args;
-        let value_record1 = ctxt.read_dep::<ValueRecord>(value_format1)?;
-        let value_record2 = ctxt.read_dep::<ValueRecord>(value_format2)?;
+        let (value_format1, value_format2, scope) = args;
+        let value_record1 = ctxt.read_dep::<ValueRecord>((value_format1, scope.clone()))?;
+        let value_record2 = ctxt.read_dep::<ValueRecord>((value_format2, scope))?;
         Ok(Class2Record {
             value_record1,
             value_record2,
@@ -1570,7 +2231,7 @@ impl<'a> ReadBinaryDep<'a> for Class2Record {
 }
 
 impl<'a> ReadFixedSizeDep<'a> for Class2Record {
-    fn size((value_format1, value_format2): Self::Args) -> usize {
+    fn size((value_format1, value_format2, _scope): Self::Args) -> usize {
         value_format1.size() + value_format2.size()
     }
 }
@@ -1593,8 +2254,8 @@ impl PairPos {
                     for pair_value_record in &pairset.pair_value_records {
                         if pair_value_record.second_glyph == glyph2 {
                             return Ok(Some((
-                                pair_value_record.value_record1,
-                                pair_value_record.value_record2,
+                                pair_value_record.value_record1.clone(),
+                                pair_value_record.value_record2.clone(),
                             )));
                         }
                     }
@@ -1616,8 +2277,8 @@ impl PairPos {
                     if class1_value < class1_records.len() && class2_value < class2_count {
                         let class1_record = &class1_records[class1_value];
                         let class2_record = &class1_record.class2_records[class2_value];
-                        let adj1 = class2_record.value_record1;
-                        let adj2 = class2_record.value_record2;
+                        let adj1 = class2_record.value_record1.clone();
+                        let adj2 = class2_record.value_record2.clone();
                         Ok(Some((adj1, adj2)))
                     } else {
                         Err(ParseError::BadIndex)
@@ -1631,7 +2292,7 @@ impl PairPos {
 }
 
 pub struct CursivePos {
-    coverage: Rc<Coverage>,
+    coverage: Arc<Coverage>,
     entry_exit_records: Vec<EntryExitRecord>,
 }
 
@@ -1647,7 +2308,7 @@ impl<'a> ReadBinaryDep<'a> for CursivePos {
                 let coverage_offset = usize::from(ctxt.read_u16be()?);
                 let coverage = scope
                     .offset(coverage_offset)
-                    .read_cache::<Coverage>(&mut cache.coverages.borrow_mut())?;
+                    .read_cache::<Coverage>(&mut cache.coverages.lock().unwrap())?;
                 let entry_exit_count = usize::from(ctxt.read_u16be()?);
                 let entry_exit_records = ctxt
                     .read_array_dep::<EntryExitRecord>(entry_exit_count, scope.clone())?
@@ -1717,9 +2378,9 @@ impl CursivePos {
                 self.entry_exit_records.check_index(coverage_index2)?;
                 let entry_exit1 = &self.entry_exit_records[coverage_index1];
                 let entry_exit2 = &self.entry_exit_records[coverage_index2];
-                match (entry_exit1.exit_anchor, entry_exit2.entry_anchor) {
+                match (&entry_exit1.exit_anchor, &entry_exit2.entry_anchor) {
                     (Some(glyph1_exit), Some(glyph2_entry)) => {
-                        Ok(Some((glyph1_exit, glyph2_entry)))
+                        Ok(Some((glyph1_exit.clone(), glyph2_entry.clone())))
                     }
                     _ => Ok(None),
                 }
@@ -1731,8 +2392,8 @@ impl CursivePos {
 
 // also used for MarkToMark tables
 pub struct MarkBasePos {
-    mark_coverage: Rc<Coverage>,
-    base_coverage: Rc<Coverage>,
+    mark_coverage: Arc<Coverage>,
+    base_coverage: Arc<Coverage>,
     mark_class_count: usize,
     mark_array: MarkArray,
     base_array: BaseArray,
@@ -1754,10 +2415,10 @@ impl<'a> ReadBinaryDep<'a> for MarkBasePos {
                 let base_array_offset = usize::from(ctxt.read_u16be()?);
                 let mark_coverage = scope
                     .offset(mark_coverage_offset)
-                    .read_cache::<Coverage>(&mut cache.coverages.borrow_mut())?;
+                    .read_cache::<Coverage>(&mut cache.coverages.lock().unwrap())?;
                 let base_coverage = scope
                     .offset(base_coverage_offset)
-                    .read_cache::<Coverage>(&mut cache.coverages.borrow_mut())?;
+                    .read_cache::<Coverage>(&mut cache.coverages.lock().unwrap())?;
                 let mark_array = scope.offset(mark_array_offset).read::<MarkArray>()?;
                 let base_array = scope
                     .offset(base_array_offset)
@@ -1878,10 +2539,10 @@ impl MarkBasePos {
                 let mark_record = &self.mark_array.mark_records[mark_coverage_index];
                 let mark_class = usize::from(mark_record.mark_class);
                 if mark_class < self.mark_class_count {
-                    let mark_anchor = mark_record.mark_anchor;
+                    let mark_anchor = &mark_record.mark_anchor;
                     let base_record = &self.base_array.base_records[base_coverage_index];
-                    if let Some(base_anchor) = base_record.base_anchors[mark_class] {
-                        Ok(Some((base_anchor, mark_anchor)))
+                    if let Some(base_anchor) = &base_record.base_anchors[mark_class] {
+                        Ok(Some((base_anchor.clone(), mark_anchor.clone())))
                     } else {
                         Ok(None)
                     }
@@ -1895,8 +2556,8 @@ impl MarkBasePos {
 }
 
 pub struct MarkLigPos {
-    mark_coverage: Rc<Coverage>,
-    liga_coverage: Rc<Coverage>,
+    mark_coverage: Arc<Coverage>,
+    liga_coverage: Arc<Coverage>,
     mark_class_count: usize,
     mark_array: MarkArray,
     ligature_array: LigatureArray,
@@ -1917,10 +2578,10 @@ impl<'a> ReadBinaryDep<'a> for MarkLigPos {
                 let liga_array_offset = usize::from(ctxt.read_u16be()?);
                 let mark_coverage = scope
                     .offset(mark_coverage_offset)
-                    .read_cache::<Coverage>(&mut cache.coverages.borrow_mut())?;
+                    .read_cache::<Coverage>(&mut cache.coverages.lock().unwrap())?;
                 let liga_coverage = scope
                     .offset(liga_coverage_offset)
-                    .read_cache::<Coverage>(&mut cache.coverages.borrow_mut())?;
+                    .read_cache::<Coverage>(&mut cache.coverages.lock().unwrap())?;
                 let mark_array = scope.offset(mark_array_offset).read::<MarkArray>()?;
                 let ligature_array = scope
                     .offset(liga_array_offset)
@@ -2022,8 +2683,8 @@ impl MarkLigPos {
                     let liga_attach = &self.ligature_array.ligature_attaches[liga_coverage_index];
                     if liga_component_index < liga_attach.component_records.len() {
                         let component_record = &liga_attach.component_records[liga_component_index];
-                        if let Some(liga_anchor) = component_record.ligature_anchors[mark_class] {
-                            Ok(Some((liga_anchor, mark_record.mark_anchor)))
+                        if let Some(liga_anchor) = &component_record.ligature_anchors[mark_class] {
+                            Ok(Some((liga_anchor.clone(), mark_record.mark_anchor.clone())))
                         } else {
                             Ok(None)
                         }
@@ -2041,18 +2702,18 @@ impl MarkLigPos {
 
 pub enum ContextLookup<T: LayoutTableType> {
     Format1 {
-        coverage: Rc<Coverage>,
+        coverage: Arc<Coverage>,
         subrulesets: Vec<Option<SubRuleSet>>,
         phantom: PhantomData<T>,
     },
     Format2 {
-        coverage: Rc<Coverage>,
-        classdef: Rc<ClassDef>,
+        coverage: Arc<Coverage>,
+        classdef: Arc<ClassDef>,
         subclasssets: Vec<Option<SubClassSet>>,
         phantom: PhantomData<T>,
     },
     Format3 {
-        coverages: Vec<Rc<Coverage>>,
+        coverages: Vec<Arc<Coverage>>,
         lookup_records: Vec<(u16, u16)>,
         phantom: PhantomData<T>,
     },
@@ -2078,22 +2739,22 @@ pub struct SubClassRule {
 
 pub enum ChainContextLookup<T: LayoutTableType> {
     Format1 {
-        coverage: Rc<Coverage>,
+        coverage: Arc<Coverage>,
         chainsubrulesets: Vec<Option<ChainSubRuleSet>>,
         phantom: PhantomData<T>,
     },
     Format2 {
-        coverage: Rc<Coverage>,
-        backtrack_classdef: Rc<ClassDef>,
-        input_classdef: Rc<ClassDef>,
-        lookahead_classdef: Rc<ClassDef>,
+        coverage: Arc<Coverage>,
+        backtrack_classdef: Arc<ClassDef>,
+        input_classdef: Arc<ClassDef>,
+        lookahead_classdef: Arc<ClassDef>,
         chainsubclasssets: Vec<Option<ChainSubClassSet>>,
         phantom: PhantomData<T>,
     },
     Format3 {
-        backtrack_coverages: Vec<Rc<Coverage>>,
-        input_coverages: Vec<Rc<Coverage>>,
-        lookahead_coverages: Vec<Rc<Coverage>>,
+        backtrack_coverages: Vec<Arc<Coverage>>,
+        input_coverages: Vec<Arc<Coverage>>,
+        lookahead_coverages: Vec<Arc<Coverage>>,
         lookup_records: Vec<(u16, u16)>,
         phantom: PhantomData<T>,
     },
@@ -2121,6 +2782,60 @@ pub struct ChainSubClassRule {
     lookup_records: Vec<(u16, u16)>,
 }
 
+impl<T: LayoutTableType> ContextLookup<T> {
+    /// Returns the indices of the lookups referenced by the rules of this subtable.
+    pub fn lookup_indices(&self) -> Vec<u16> {
+        let lookup_records: Vec<&(u16, u16)> = match self {
+            ContextLookup::Format1 { subrulesets, .. } => subrulesets
+                .iter()
+                .flatten()
+                .flat_map(|subruleset| &subruleset.subrules)
+                .flat_map(|subrule| &subrule.lookup_records)
+                .collect(),
+            ContextLookup::Format2 { subclasssets, .. } => subclasssets
+                .iter()
+                .flatten()
+                .flat_map(|subclassset| &subclassset.subclassrules)
+                .flat_map(|subclassrule| &subclassrule.lookup_records)
+                .collect(),
+            ContextLookup::Format3 { lookup_records, .. } => lookup_records.iter().collect(),
+        };
+        lookup_records
+            .into_iter()
+            .map(|(_sequence_index, lookup_index)| *lookup_index)
+            .collect()
+    }
+}
+
+impl<T: LayoutTableType> ChainContextLookup<T> {
+    /// Returns the indices of the lookups referenced by the rules of this subtable.
+    pub fn lookup_indices(&self) -> Vec<u16> {
+        let lookup_records: Vec<&(u16, u16)> = match self {
+            ChainContextLookup::Format1 {
+                chainsubrulesets, ..
+            } => chainsubrulesets
+                .iter()
+                .flatten()
+                .flat_map(|chainsubruleset| &chainsubruleset.chainsubrules)
+                .flat_map(|chainsubrule| &chainsubrule.lookup_records)
+                .collect(),
+            ChainContextLookup::Format2 {
+                chainsubclasssets, ..
+            } => chainsubclasssets
+                .iter()
+                .flatten()
+                .flat_map(|chainsubclassset| &chainsubclassset.chainsubclassrules)
+                .flat_map(|chainsubclassrule| &chainsubclassrule.lookup_records)
+                .collect(),
+            ChainContextLookup::Format3 { lookup_records, .. } => lookup_records.iter().collect(),
+        };
+        lookup_records
+            .into_iter()
+            .map(|(_sequence_index, lookup_index)| *lookup_index)
+            .collect()
+    }
+}
+
 impl<'a, T: LayoutTableType> ReadBinaryDep<'a> for ContextLookup<T> {
     type HostType = Self;
     type Args = LayoutCache<T>;
@@ -2135,7 +2850,7 @@ impl<'a, T: LayoutTableType> ReadBinaryDep<'a> for ContextLookup<T> {
                 let subrulesets = read_objects_nullable::<SubRuleSet>(&scope, subruleset_offsets)?;
                 let coverage = scope
                     .offset(coverage_offset)
-                    .read_cache::<Coverage>(&mut cache.coverages.borrow_mut())?;
+                    .read_cache::<Coverage>(&mut cache.coverages.lock().unwrap())?;
                 Ok(ContextLookup::Format1 {
                     coverage,
                     subrulesets,
@@ -2152,10 +2867,10 @@ impl<'a, T: LayoutTableType> ReadBinaryDep<'a> for ContextLookup<T> {
                     read_objects_nullable::<SubClassSet>(&scope, subclassset_offsets)?;
                 let coverage = scope
                     .offset(coverage_offset)
-                    .read_cache::<Coverage>(&mut cache.coverages.borrow_mut())?;
+                    .read_cache::<Coverage>(&mut cache.coverages.lock().unwrap())?;
                 let classdef = scope
                     .offset(classdef_offset)
-                    .read_cache::<ClassDef>(&mut cache.classdefs.borrow_mut())?;
+                    .read_cache::<ClassDef>(&mut cache.classdefs.lock().unwrap())?;
                 Ok(ContextLookup::Format2 {
                     coverage,
                     classdef,
@@ -2186,11 +2901,11 @@ pub enum ReverseChainSingleSubst {
     /// Format 1
     Format1 {
         /// Coverage table for the single input glyph
-        coverage: Rc<Coverage>,
+        coverage: Arc<Coverage>,
         /// Array of backtrack sequence coverages, ordered by glyph sequence
-        backtrack_coverages: Vec<Rc<Coverage>>,
+        backtrack_coverages: Vec<Arc<Coverage>>,
         /// Array of lookahead sequence coverages, ordered by glyph sequence
-        lookahead_coverages: Vec<Rc<Coverage>>,
+        lookahead_coverages: Vec<Arc<Coverage>>,
         /// Array of substitute glyphs, ordered by coverage index
         substitute_glyphs: Vec<u16>,
     },
@@ -2214,11 +2929,11 @@ impl<'a> ReadBinaryDep<'a> for ReverseChainSingleSubst {
                 let substitute_glyphs = ctxt.read_array::<U16Be>(glyph_count)?.to_vec();
                 let coverage = scope
                     .offset(coverage_offset)
-                    .read_cache::<Coverage>(&mut cache.coverages.borrow_mut())?;
+                    .read_cache::<Coverage>(&mut cache.coverages.lock().unwrap())?;
                 let backtrack_coverages =
-                    read_coverages(&scope, Rc::clone(&cache), backtrack_coverage_offsets)?;
+                    read_coverages(&scope, Arc::clone(&cache), backtrack_coverage_offsets)?;
                 let lookahead_coverages =
-                    read_coverages(&scope, Rc::clone(&cache), lookahead_coverage_offsets)?;
+                    read_coverages(&scope, Arc::clone(&cache), lookahead_coverage_offsets)?;
 
                 ctxt.check(coverage.glyph_count() == glyph_count)?;
                 Ok(ReverseChainSingleSubst::Format1 {
@@ -2280,12 +2995,12 @@ fn read_coverages<'a, T: LayoutTableType>(
     scope: &ReadScope<'a>,
     cache: LayoutCache<T>,
     offsets: ReadArray<'a, U16Be>,
-) -> Result<Vec<Rc<Coverage>>, ParseError> {
+) -> Result<Vec<Arc<Coverage>>, ParseError> {
     let mut coverages = Vec::with_capacity(offsets.len());
     for coverage_offset in &offsets {
         let coverage = scope
             .offset(usize::from(coverage_offset))
-            .read_cache::<Coverage>(&mut cache.coverages.borrow_mut())?;
+            .read_cache::<Coverage>(&mut cache.coverages.lock().unwrap())?;
         coverages.push(coverage);
     }
     Ok(coverages)
@@ -2363,7 +3078,7 @@ impl<'a, T: LayoutTableType> ReadBinaryDep<'a> for ChainContextLookup<T> {
                     read_objects_nullable::<ChainSubRuleSet>(&scope, chainsubruleset_offsets)?;
                 let coverage = scope
                     .offset(coverage_offset)
-                    .read_cache::<Coverage>(&mut cache.coverages.borrow_mut())?;
+                    .read_cache::<Coverage>(&mut cache.coverages.lock().unwrap())?;
                 Ok(ChainContextLookup::Format1 {
                     coverage,
                     chainsubrulesets,
@@ -2382,16 +3097,16 @@ impl<'a, T: LayoutTableType> ReadBinaryDep<'a> for ChainContextLookup<T> {
                     read_objects_nullable::<ChainSubClassSet>(&scope, chainsubclassset_offsets)?;
                 let coverage = scope
                     .offset(coverage_offset)
-                    .read_cache::<Coverage>(&mut cache.coverages.borrow_mut())?;
+                    .read_cache::<Coverage>(&mut cache.coverages.lock().unwrap())?;
                 let backtrack_classdef = scope
                     .offset(backtrack_classdef_offset)
-                    .read_cache::<ClassDef>(&mut cache.classdefs.borrow_mut())?;
+                    .read_cache::<ClassDef>(&mut cache.classdefs.lock().unwrap())?;
                 let input_classdef = scope
                     .offset(input_classdef_offset)
-                    .read_cache::<ClassDef>(&mut cache.classdefs.borrow_mut())?;
+                    .read_cache::<ClassDef>(&mut cache.classdefs.lock().unwrap())?;
                 let lookahead_classdef = scope
                     .offset(lookahead_classdef_offset)
-                    .read_cache::<ClassDef>(&mut cache.classdefs.borrow_mut())?;
+                    .read_cache::<ClassDef>(&mut cache.classdefs.lock().unwrap())?;
                 Ok(ChainContextLookup::Format2 {
                     coverage,
                     backtrack_classdef,
@@ -2412,11 +3127,11 @@ impl<'a, T: LayoutTableType> ReadBinaryDep<'a> for ChainContextLookup<T> {
                 let lookup_count = usize::from(ctxt.read_u16be()?);
                 let lookup_records = ctxt.read_array::<(U16Be, U16Be)>(lookup_count)?.to_vec();
                 let backtrack_coverages =
-                    read_coverages(&scope, Rc::clone(&cache), backtrack_coverage_offsets)?;
+                    read_coverages(&scope, Arc::clone(&cache), backtrack_coverage_offsets)?;
                 let input_coverages =
-                    read_coverages(&scope, Rc::clone(&cache), input_coverage_offsets)?;
+                    read_coverages(&scope, Arc::clone(&cache), input_coverage_offsets)?;
                 let lookahead_coverages =
-                    read_coverages(&scope, Rc::clone(&cache), lookahead_coverage_offsets)?;
+                    read_coverages(&scope, Arc::clone(&cache), lookahead_coverage_offsets)?;
                 Ok(ChainContextLookup::Format3 {
                     backtrack_coverages,
                     input_coverages,
@@ -2547,7 +3262,7 @@ pub fn context_lookup_info<'a, T, Table: LayoutTableType>(
                         let match_context = MatchContext {
                             backtrack_table: GlyphTable::Empty,
                             input_table: GlyphTable::ByClassDef(
-                                Rc::clone(classdef),
+                                Arc::clone(classdef),
                                 &subclassrule.input_sequence,
                             ),
                             lookahead_table: GlyphTable::Empty,
@@ -2647,15 +3362,15 @@ pub fn chain_context_lookup_info<'a, T, Table: LayoutTableType>(
                     for chainsubclassrule in &chainsubclassset.chainsubclassrules {
                         let match_context = MatchContext {
                             backtrack_table: GlyphTable::ByClassDef(
-                                Rc::clone(backtrack_classdef),
+                                Arc::clone(backtrack_classdef),
                                 &chainsubclassrule.backtrack_sequence,
                             ),
                             input_table: GlyphTable::ByClassDef(
-                                Rc::clone(input_classdef),
+                                Arc::clone(input_classdef),
                                 &chainsubclassrule.input_sequence,
                             ),
                             lookahead_table: GlyphTable::ByClassDef(
-                                Rc::clone(lookahead_classdef),
+                                Arc::clone(lookahead_classdef),
                                 &chainsubclassrule.lookahead_sequence,
                             ),
                         };
@@ -2731,6 +3446,25 @@ impl ReverseChainSingleSubst {
             },
         }
     }
+
+    /// Returns `true` if each glyph of the backtrack and lookahead context is covered by a glyph
+    /// in `glyphs`.
+    pub fn context_intersects(&self, glyphs: &FxHashSet<u16>) -> bool {
+        match self {
+            ReverseChainSingleSubst::Format1 {
+                backtrack_coverages,
+                lookahead_coverages,
+                ..
+            } => backtrack_coverages
+                .iter()
+                .chain(lookahead_coverages.iter())
+                .all(|coverage| {
+                    glyphs
+                        .iter()
+                        .any(|&glyph| coverage.glyph_coverage_value(glyph).is_some())
+                }),
+        }
+    }
 }
 
 pub enum Coverage {
@@ -2931,52 +3665,315 @@ impl ClassDef {
     }
 }
 
-pub type LayoutCache<T> = Rc<LayoutCacheData<T>>;
+/// Shared cache of the parsed lookups of a layout table
+///
+/// The cache is filled in as lookups are used. It can be shared between threads, allowing text
+/// to be shaped concurrently with the same font.
+pub type LayoutCache<T> = Arc<LayoutCacheData<T>>;
+
+impl<T: LayoutTableType, S> LazySubtables<T, S> {
+    fn new(cache: &LayoutCache<T>, offsets: Vec<usize>) -> Self {
+        let subtables = offsets.iter().map(|_| OnceCell::new()).collect();
+        LazySubtables {
+            cache: Arc::downgrade(cache),
+            offsets,
+            subtables,
+        }
+    }
+
+    /// The number of subtables, including any that fail to decode.
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+}
+
+impl<T: LayoutTableType, S> LazySubtables<T, S>
+where
+    S: for<'a> ReadBinaryDep<'a, Args = LayoutCache<T>, HostType = S>,
+{
+    /// Returns the subtable at `index`, decoding it if this is the first time it is accessed.
+    ///
+    /// `None` is returned if `index` is out of bounds or the subtable is invalid.
+    pub fn get(&self, index: usize) -> Option<&S> {
+        self.subtables
+            .get(index)?
+            .get_or_init(|| self.read_subtable(index))
+            .as_ref()
+    }
+
+    pub fn iter(&self) -> LazySubtablesIter<'_, T, S> {
+        LazySubtablesIter {
+            subtables: self,
+            index: 0,
+        }
+    }
+
+    fn read_subtable(&self, index: usize) -> Option<S> {
+        // The cache owns the lookup list that the subtables are read from
+        let cache = self.cache.upgrade()?;
+        let lookup_list = cache.layout_table.opt_lookup_list.as_ref()?;
+        let subtable = lookup_list.scope_owned.scope().offset(self.offsets[index]);
+        match subtable.read_dep::<S>(Arc::clone(&cache)) {
+            Ok(subtable) => Some(subtable),
+            Err(err) => {
+                warn!("skipping invalid subtable: {}", err);
+                None
+            }
+        }
+    }
+}
+
+impl<'b, T: LayoutTableType, S> Iterator for LazySubtablesIter<'b, T, S>
+where
+    S: for<'a> ReadBinaryDep<'a, Args = LayoutCache<T>, HostType = S>,
+{
+    type Item = &'b S;
+
+    fn next(&mut self) -> Option<&'b S> {
+        while self.index < self.subtables.len() {
+            let index = self.index;
+            self.index += 1;
+            if let Some(subtable) = self.subtables.get(index) {
+                return Some(subtable);
+            }
+        }
+        None
+    }
+}
+
+impl<'b, T: LayoutTableType, S> IntoIterator for &'b LazySubtables<T, S>
+where
+    S: for<'a> ReadBinaryDep<'a, Args = LayoutCache<T>, HostType = S>,
+{
+    type Item = &'b S;
+    type IntoIter = LazySubtablesIter<'b, T, S>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+pub type LookupCache<T> = Vec<Option<Arc<LookupCacheItem<T>>>>;
 
-pub type LookupCache<T> = Vec<Option<Rc<LookupCacheItem<T>>>>;
+/// (script_tag, lang_tag, feature tags)
+pub type FeatureTagsKey = (u32, u32, Vec<u32>);
 
 pub struct LookupCacheItem<T> {
     pub lookup_flag: LookupFlag,
+    pub mark_filtering_set: Option<u16>,
     pub lookup_subtables: T,
 }
 
 pub struct LayoutCacheData<T: LayoutTableType> {
     pub layout_table: LayoutTable<T>,
-    coverages: RefCell<ReadCache<Coverage>>,
-    classdefs: RefCell<ReadCache<ClassDef>>,
-    lookup_cache: RefCell<LookupCache<T::LookupType>>,
+    coverages: Mutex<ReadCache<Coverage>>,
+    classdefs: Mutex<ReadCache<ClassDef>>,
+    lookup_cache: RwLock<LookupCache<T::LookupType>>,
 
     /// maps (script_tag, opt_lang_tag) to GsubFeatureMask
     /// opt_lang_tag = None is represented as `DFLT`
-    pub supported_features: RefCell<HashMap<(u32, u32), u64>>,
+    pub supported_features: RwLock<HashMap<(u32, u32), u64>>,
 
     /// maps (script_tag, lang_tag, GsubFeatureMask) to cached_lookups index
-    pub lookups_index: RefCell<HashMap<(u32, u32, u64), usize>>,
+    pub lookups_index: RwLock<HashMap<(u32, u32, u64), usize>>,
 
-    pub cached_lookups: RefCell<Vec<Vec<(usize, u32)>>>,
+    /// maps (script_tag, lang_tag, feature tags) to cached_lookups index, for GPOS features
+    /// which are selected by tag rather than GsubFeatureMask
+    pub feature_tags_index: RwLock<HashMap<FeatureTagsKey, usize>>,
+
+    /// Lookups are cloned out of this vector rather than borrowed so that the lock is not held
+    /// while they are applied.
+    pub cached_lookups: RwLock<Vec<Vec<(usize, u32)>>>,
 }
 
 pub fn new_layout_cache<T: LayoutTableType>(layout_table: LayoutTable<T>) -> LayoutCache<T> {
-    let coverages = RefCell::new(ReadCache::new());
-    let classdefs = RefCell::new(ReadCache::new());
-    let lookup_cache = RefCell::new(Vec::new());
-    let supported_features = RefCell::new(HashMap::new());
-    let lookups_index = RefCell::new(HashMap::new());
-    let cached_lookups = RefCell::new(vec![Vec::new()]);
-    Rc::new(LayoutCacheData {
+    let coverages = Mutex::new(ReadCache::new());
+    let classdefs = Mutex::new(ReadCache::new());
+    let lookup_cache = RwLock::new(Vec::new());
+    let supported_features = RwLock::new(HashMap::new());
+    let lookups_index = RwLock::new(HashMap::new());
+    let feature_tags_index = RwLock::new(HashMap::new());
+    let cached_lookups = RwLock::new(vec![Vec::new()]);
+    Arc::new(LayoutCacheData {
         layout_table,
         coverages,
         classdefs,
         lookup_cache,
         supported_features,
         lookups_index,
+        feature_tags_index,
         cached_lookups,
     })
 }
 
+/// Add the lookups of every feature in `langsys` tagged `feature_tag` to `lookups`.
+///
+/// Lookups are keyed by lookup index so each one is applied once, in lookup list order. When a
+/// lookup is referenced by more than one feature it keeps the tag of the first feature added,
+/// rather than whichever the font happens to list last. Returns `false` if `langsys` has no
+/// feature with the tag.
+pub(crate) fn add_feature_lookups<T>(
+    layout_table: &LayoutTable<T>,
+    langsys: &LangSys,
+    feature_tag: u32,
+    lookups: &mut BTreeMap<usize, u32>,
+) -> Result<bool, ParseError> {
+    let feature_tables = layout_table.find_langsys_features(langsys, feature_tag)?;
+    for feature_table in &feature_tables {
+        for lookup_index in &feature_table.lookup_indices {
+            lookups
+                .entry(usize::from(*lookup_index))
+                .or_insert(feature_tag);
+        }
+    }
+    Ok(!feature_tables.is_empty())
+}
+
+/// Compute the set of glyphs reachable from `initial_glyph_set` through glyph substitution.
+///
+/// The lookups of `features` in every language system of `scripts` are considered, along with
+/// the lookups they reference from contextual substitutions. Starting from
+/// `initial_glyph_set`, glyphs produced by single, multiple, alternate, ligature and reverse
+/// chaining substitutions are added until no new glyphs are found. Ligatures are only added
+/// when all of their components are in the set, and reverse chaining substitutions when each
+/// glyph of their backtrack and lookahead context could be in the set. Other contexts are not
+/// evaluated, so the result may contain glyphs that are unreachable in practice.
+///
+/// This is used when subsetting so that glyphs substituted in during shaping are retained.
+pub fn gsub_closure(
+    gsub_cache: &LayoutCache<GSUB>,
+    initial_glyph_set: &FxHashSet<u16>,
+    features: &[u32],
+    scripts: &[u32],
+) -> Result<FxHashSet<u16>, ParseError> {
+    let gsub_table = &gsub_cache.layout_table;
+    let mut glyphs = initial_glyph_set.clone();
+    let lookup_list = match gsub_table.opt_lookup_list {
+        Some(ref lookup_list) => lookup_list,
+        None => return Ok(glyphs),
+    };
+
+    let mut feature_lookups = BTreeMap::new();
+    for script_tag in scripts {
+        if let Some(script) = gsub_table.find_script(*script_tag)? {
+            for langsys in script.langsys_iter() {
+                for feature_tag in features {
+                    add_feature_lookups(gsub_table, langsys, *feature_tag, &mut feature_lookups)?;
+                }
+            }
+        }
+    }
+
+    // Add the lookups referenced by contextual lookups
+    let mut lookup_indices = BTreeSet::new();
+    let mut pending = feature_lookups.keys().copied().collect::<Vec<_>>();
+    while let Some(lookup_index) = pending.pop() {
+        if !lookup_indices.insert(lookup_index) {
+            continue;
+        }
+        let lookup = lookup_list.lookup_cache_gsub(gsub_cache, lookup_index)?;
+        let nested = match lookup.lookup_subtables {
+            SubstLookup::ContextSubst(ref subtables) => subtables
+                .iter()
+                .flat_map(|subtable| subtable.lookup_indices())
+                .collect(),
+            SubstLookup::ChainContextSubst(ref subtables) => subtables
+                .iter()
+                .flat_map(|subtable| subtable.lookup_indices())
+                .collect(),
+            _ => Vec::new(),
+        };
+        pending.extend(nested.into_iter().map(usize::from));
+    }
+
+    loop {
+        let mut new_glyphs = Vec::new();
+        for lookup_index in &lookup_indices {
+            let lookup = lookup_list.lookup_cache_gsub(gsub_cache, *lookup_index)?;
+            for &glyph_index in &glyphs {
+                closure_lookup(
+                    &lookup.lookup_subtables,
+                    &glyphs,
+                    glyph_index,
+                    &mut new_glyphs,
+                )?;
+            }
+        }
+        let count = glyphs.len();
+        glyphs.extend(new_glyphs);
+        if glyphs.len() == count {
+            break;
+        }
+    }
+
+    Ok(glyphs)
+}
+
+/// Push the glyphs `lookup` can substitute for `glyph_index` onto `new_glyphs`.
+fn closure_lookup(
+    lookup: &SubstLookup,
+    glyphs: &FxHashSet<u16>,
+    glyph_index: u16,
+    new_glyphs: &mut Vec<u16>,
+) -> Result<(), ParseError> {
+    match lookup {
+        SubstLookup::SingleSubst(subtables) => {
+            for single_subst in subtables {
+                new_glyphs.extend(single_subst.apply_glyph(glyph_index)?);
+            }
+        }
+        SubstLookup::MultipleSubst(subtables) => {
+            for multiple_subst in subtables {
+                if let Some(sequence_table) = multiple_subst.apply_glyph(glyph_index)? {
+                    new_glyphs.extend(&sequence_table.substitute_glyphs);
+                }
+            }
+        }
+        SubstLookup::AlternateSubst(subtables) => {
+            for alternate_subst in subtables {
+                if let Some(alternate_set) = alternate_subst.apply_glyph(glyph_index)? {
+                    new_glyphs.extend(&alternate_set.alternate_glyphs);
+                }
+            }
+        }
+        SubstLookup::LigatureSubst(subtables) => {
+            for ligature_subst in subtables {
+                if let Some(ligature_set) = ligature_subst.apply_glyph(glyph_index)? {
+                    for ligature in &ligature_set.ligatures {
+                        if ligature
+                            .component_glyphs
+                            .iter()
+                            .all(|component| glyphs.contains(component))
+                        {
+                            new_glyphs.push(ligature.ligature_glyph);
+                        }
+                    }
+                }
+            }
+        }
+        SubstLookup::ContextSubst(_) | SubstLookup::ChainContextSubst(_) => {}
+        SubstLookup::ReverseChainSingleSubst(subtables) => {
+            for reversechainsinglesubst in subtables {
+                // The substitution can only occur if glyphs matching its context are reachable
+                if reversechainsinglesubst.context_intersects(glyphs) {
+                    new_glyphs.extend(reversechainsinglesubst.apply_glyph(glyph_index, |_| true)?);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::error::ParseErrorKind;
+    use crate::tables::{FontTableProvider, OpenTypeFont};
+    use crate::tests::read_fixture;
 
     fn make_gdef_header(glyph_classdef_offset: u16) -> Vec<u8> {
         let mut data = vec![
@@ -3015,8 +4012,244 @@ mod tests {
         let data = make_gdef_header(1000);
         match ReadScope::new(&data).read::<GDEFTable>() {
             Ok(_) => panic!("expected error got success"),
-            Err(ParseError::BadEof) => {}
+            Err(err) if err.kind() == ParseErrorKind::BadEof => {}
             Err(err) => panic!("expeceted ParseError::BadEof got {:?}", err),
         }
     }
+
+    #[test]
+    fn test_read_value_record_device() {
+        let data = [
+            0xFF, 0xCE, // x advance
+            0x00, 0x04, // x advance device offset
+            0x00, 0x0A, // start size
+            0x00, 0x0D, // end size
+            0x00, 0x01, // delta format
+            0x72, 0x00, // deltas 1, -1, 0, -2
+        ];
+        let scope = ReadScope::new(&data);
+        let value_record = scope
+            .ctxt()
+            .read_dep::<ValueRecord>((ValueFormat(0x44), scope.clone()))
+            .unwrap()
+            .unwrap();
+        assert_eq!(value_record.x_advance, -50);
+        assert_eq!(
+            value_record.opt_devices.as_ref().map(Arc::as_ref),
+            Some(&AdjustDevices {
+                x_advance: Some(Device::Hinting {
+                    start_size: 10,
+                    end_size: 13,
+                    deltas: vec![1, -1, 0, -2],
+                }),
+                ..AdjustDevices::default()
+            })
+        );
+
+        // Without device tables
+        let value_record = scope
+            .ctxt()
+            .read_dep::<ValueRecord>((ValueFormat(0x04), scope.clone()))
+            .unwrap()
+            .unwrap();
+        assert!(value_record.opt_devices.is_none());
+    }
+
+    #[test]
+    fn test_read_anchor_device() {
+        let data = [
+            0x00, 0x03, // format
+            0x00, 0x64, // x
+            0x00, 0xC8, // y
+            0x00, 0x0A, // x device offset
+            0x00, 0x10, // y device offset
+            0x00, 0x00, // delta set outer index
+            0x00, 0x01, // delta set inner index
+            0x80, 0x00, // delta format
+            0x00, 0x0C, // start size
+            0x00, 0x0D, // end size
+            0x00, 0x03, // delta format
+            0x02, 0xFD, // deltas 2, -3
+        ];
+        let anchor = ReadScope::new(&data).read::<Anchor>().unwrap();
+        assert_eq!((anchor.x, anchor.y), (100, 200));
+        assert_eq!(
+            anchor.opt_devices.as_ref().map(Arc::as_ref),
+            Some(&AnchorDevices {
+                x: Some(Device::VariationIndex {
+                    delta_set_outer_index: 0,
+                    delta_set_inner_index: 1,
+                }),
+                y: Some(Device::Hinting {
+                    start_size: 12,
+                    end_size: 13,
+                    deltas: vec![2, -3],
+                }),
+            })
+        );
+    }
+
+    #[test]
+    fn test_read_anchor_point() {
+        let data = [
+            0x00, 0x02, // format
+            0x00, 0x64, // x
+            0x00, 0xC8, // y
+            0x00, 0x07, // anchor point
+        ];
+        let anchor = ReadScope::new(&data).read::<Anchor>().unwrap();
+        assert_eq!((anchor.x, anchor.y), (100, 200));
+        assert_eq!(anchor.opt_anchor_point, Some(7));
+        assert!(anchor.opt_devices.is_none());
+    }
+
+    #[test]
+    fn test_read_size_feature_params() {
+        let feature_list = |params_offset| {
+            vec![
+                0x00,
+                0x01, // feature count
+                b's',
+                b'i',
+                b'z',
+                b'e', // feature tag
+                0x00,
+                0x08, // feature offset
+                0x00,
+                params_offset, // feature params offset
+                0x00,
+                0x00, // lookup index count
+                0x00,
+                0x64, // design size
+                0x00,
+                0x01, // subfamily id
+                0x01,
+                0x00, // subfamily name id
+                0x00,
+                0x50, // range start
+                0x00,
+                0x78, // range end
+            ]
+        };
+        let expected = FeatureParams::Size(SizeParams {
+            design_size: 100,
+            subfamily_id: 1,
+            subfamily_name_id: 256,
+            range_start: 80,
+            range_end: 120,
+        });
+
+        // Offset from the feature table, and from the feature list as in some early fonts
+        for &params_offset in &[4, 12] {
+            let data = feature_list(params_offset);
+            let feature_list = ReadScope::new(&data).read::<FeatureList>().unwrap();
+            let feature_table = &feature_list.feature_records[0].feature_table;
+            assert_eq!(feature_table.opt_feature_params.as_ref(), Some(&expected));
+        }
+
+        let size = match expected {
+            FeatureParams::Size(size) => size,
+            _ => unreachable!(),
+        };
+        assert!(size.is_intended_for(12.));
+        assert!(!size.is_intended_for(8.));
+        assert!(!size.is_intended_for(12.5));
+    }
+
+    #[test]
+    fn test_read_character_variant_feature_params() {
+        let data = [
+            0x00, 0x00, // format
+            0x01, 0x00, // feature UI label name id
+            0x00, 0x00, // feature UI tooltip text name id
+            0x01, 0x01, // sample text name id
+            0x00, 0x02, // num named parameters
+            0x01, 0x02, // first param UI label name id
+            0x00, 0x02, // char count
+            0x00, 0x00, 0x61, // character
+            0x01, 0xF6, 0x00, // character
+        ];
+        let params = ReadScope::new(&data)
+            .read::<CharacterVariantParams>()
+            .unwrap();
+        assert_eq!(params.ui_label_name_id, 256);
+        assert_eq!(params.sample_text_name_id, 257);
+        assert_eq!(
+            params.param_ui_label_name_ids().collect::<Vec<_>>(),
+            vec![258, 259]
+        );
+        assert_eq!(params.characters, vec![0x61, 0x1F600]);
+    }
+
+    #[test]
+    fn test_find_script_fallback() {
+        let read_gsub = |path| {
+            let buffer = read_fixture(path);
+            let font = ReadScope::new(&buffer).read::<OpenTypeFont<'_>>().unwrap();
+            let provider = font.table_provider(0).unwrap();
+            let gsub_data = provider.read_table_data(tag::GSUB).unwrap();
+            ReadScope::new(&gsub_data)
+                .read::<LayoutTable<GSUB>>()
+                .unwrap()
+        };
+        let find_tag = |gsub: &LayoutTable<GSUB>, script_tag| {
+            gsub.find_script_and_tag(script_tag)
+                .unwrap()
+                .map(|(tag, _)| tag)
+        };
+
+        // `dev2`, `deva` and `latn`
+        let gsub = read_gsub("tests/fonts/noto/NotoSansDevanagari-Regular.ttf");
+        assert_eq!(find_tag(&gsub, tag::DEVA), Some(tag::DEV2));
+        assert_eq!(find_tag(&gsub, tag::DEV2), Some(tag::DEV2));
+        assert_eq!(find_tag(&gsub, tag::ARAB), None);
+        let latn = gsub.find_script(tag::LATN).unwrap().unwrap();
+        let default = gsub.find_script_or_default(tag::ARAB).unwrap().unwrap();
+        assert!(std::ptr::eq(default, latn));
+
+        // Only `deva`
+        let gsub = read_gsub("tests/fonts/devanagari/lohit_hi.ttf");
+        assert_eq!(find_tag(&gsub, tag::DEV2), Some(tag::DEVA));
+
+        // `DFLT` and `latn`
+        let gsub = read_gsub("tests/fonts/opentype/Klei.otf");
+        let dflt = gsub.find_script(tag::DFLT).unwrap().unwrap();
+        let default = gsub.find_script_or_default(tag::ARAB).unwrap().unwrap();
+        assert!(std::ptr::eq(default, dflt));
+    }
+
+    #[test]
+    fn test_lazy_subtables() {
+        let buffer = read_fixture("tests/fonts/opentype/Klei.otf");
+        let font = ReadScope::new(&buffer).read::<OpenTypeFont<'_>>().unwrap();
+        let provider = font.table_provider(0).unwrap();
+        let gsub_data = provider.read_table_data(tag::GSUB).unwrap();
+        let gsub = ReadScope::new(&gsub_data)
+            .read::<LayoutTable<GSUB>>()
+            .unwrap();
+        let cache = new_layout_cache(gsub);
+        let lookup_list = cache.layout_table.opt_lookup_list.as_ref().unwrap();
+
+        let lookup = lookup_list.lookup_cache_gsub(&cache, 0).unwrap();
+        let subtables = match &lookup.lookup_subtables {
+            SubstLookup::LigatureSubst(subtables) => subtables,
+            _ => panic!("expected LigatureSubst"),
+        };
+        let decoded = || {
+            subtables
+                .subtables
+                .iter()
+                .map(|subtable| subtable.get().is_some())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(subtables.len(), 5);
+        assert_eq!(decoded(), vec![false; 5]);
+
+        assert!(subtables.get(2).is_some());
+        assert_eq!(decoded(), vec![false, false, true, false, false]);
+        assert!(subtables.get(5).is_none());
+
+        assert_eq!(subtables.iter().count(), 5);
+        assert_eq!(decoded(), vec![true; 5]);
+    }
 }
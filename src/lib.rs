@@ -49,10 +49,9 @@
 //!
 //! We don't currently support:
 //!
-//! * Shaping Hebrew, Tibetan, and Mongolian.
-//! * Apple's [morx table](https://developer.apple.com/fonts/TrueType-Reference-Manual/RM06/Chap6morx.html).
-//! * Emoji.
-//! * Unicode normalisation.
+//! * Shaping Tibetan and Mongolian.
+//! * Unicode normalisation during shaping. Text can be normalised ahead of shaping with
+//!   [Font::map_text](font/struct.Font.html#method.map_text).
 //!
 //! Known limitations:
 //!
@@ -87,37 +86,93 @@
 //!
 //! Build with `cargo build` and run the tests with `cargo test`.
 //!
+//! ## Cargo Features
+//!
+//! Parsing, the [Font](font/struct.Font.html) type, and WOFF/WOFF2 decoding are always built.
+//! The remaining capabilities are split into features, all enabled by default, so that size
+//! sensitive builds such as WebAssembly can leave out what they don't use:
+//!
+//! * `shaping` — `gsub`, `gpos`, `morx`, script itemisation, and the shaping methods of `Font`.
+//! * `subsetting` — the `subset` and `webfont` modules.
+//! * `variations` — applying `gvar` deltas to glyph outlines, and the `instance` module for
+//!   writing static instances of variable fonts. Without it outlines are always those of the
+//!   default instance.
+//! * `bitmap` — embedded bitmap (`sbix`, `CBDT`, `EBDT`) and `SVG` glyphs, and colour glyph
+//!   lookup.
+//! * `boolean-ops` — the `boolean_ops` module for detecting and removing overlapping contours.
+//! * `woff2-writer` — WOFF2 encoding with `woff2::write_woff2`, which pulls in the Brotli
+//!   encoder.
+//!
+//! The optional `rayon` feature adds `Font::shape_runs_parallel` and makes
+//! `instance::batch::instance_fonts` process its fonts in parallel.
+//!
+//! A WebAssembly font subsetter only needs `default-features = false, features = ["subsetting"]`.
+//!
 //! ## License
 //!
 //! Allsorts is distributed under the terms of the Apache License (Version 2.0).
 //!
 //! See [LICENSE](https://github.com/yeslogic/allsorts/blob/master/LICENSE) for details.
 
+#[cfg(all(feature = "shaping", feature = "unicode-bidi"))]
+pub mod bidi;
 pub mod big5;
 pub mod binary;
+#[cfg(feature = "bitmap")]
 pub mod bitmap;
+#[cfg(feature = "boolean-ops")]
+pub mod boolean_ops;
+#[cfg(feature = "capi")]
+pub mod capi;
 pub mod cff;
+#[cfg(feature = "shaping")]
+pub mod charmap;
 pub mod checksum;
+pub mod collection;
 pub mod context;
+pub mod decoration;
 pub mod error;
+#[cfg(feature = "shaping")]
+pub mod fallback;
 pub mod font;
 pub mod font_data;
 pub mod gdef;
 pub mod get_name;
 pub mod glyph_info;
+pub mod glyph_limits;
+#[cfg(feature = "shaping")]
 pub mod gpos;
+#[cfg(feature = "shaping")]
 pub mod gsub;
+#[cfg(feature = "variations")]
+pub mod instance;
 pub mod layout;
 pub mod macroman;
+#[cfg(feature = "shaping")]
+pub mod morx;
+pub mod outline_cache;
 pub mod post;
+pub mod recompute;
+#[cfg(feature = "shaping")]
+pub mod script_itemizer;
+#[cfg(feature = "shaping")]
 pub mod scripts;
+pub mod sfnt;
 pub mod size;
+#[cfg(feature = "subsetting")]
 pub mod subset;
 pub mod tables;
 pub mod tag;
 #[cfg(test)]
 pub mod tests;
+#[cfg(feature = "shaping")]
+pub mod trace;
+pub mod ttx;
+pub mod type1;
 pub mod unicode;
+pub mod validate;
+#[cfg(feature = "subsetting")]
+pub mod webfont;
 pub mod woff;
 pub mod woff2;
 
@@ -0,0 +1,758 @@
+//! Glyph substitution (`morx`) implementation.
+//!
+//! > The extended glyph metamorphosis table allows you to specify a set of transformations that
+//! > can apply to the glyphs of your font.
+//!
+//! — <https://developer.apple.com/fonts/TrueType-Reference-Manual/RM06/Chap6morx.html>
+//!
+//! Apple fonts often carry their substitutions in a `morx` table rather than `GSUB`. `Font::shape`
+//! applies the `morx` table, if present, to fonts without a `GSUB` table.
+
+use std::convert::TryFrom;
+
+use tinyvec::tiny_vec;
+
+use crate::error::ParseError;
+use crate::gsub::{Features, GlyphData, GlyphOrigin, RawGlyph};
+use crate::tables::aat::{self, ExtendedStateTable, StateEntry};
+use crate::tables::morx::{
+    ContextualSubtable, InsertionSubtable, LigatureSubtable, MorxCoverage, MorxSubtableData,
+    MorxTable,
+};
+use crate::tag;
+
+/// Flag of all state table entries to process the current glyph again.
+const DONT_ADVANCE: u16 = 0x4000;
+
+/// Rearrangement entry flag to mark the current glyph as the start of the range.
+const MARK_FIRST: u16 = 0x8000;
+/// Rearrangement entry flag to mark the current glyph as the end of the range.
+const MARK_LAST: u16 = 0x2000;
+/// Mask of a rearrangement entry for the rearrangement to perform.
+const VERB_MASK: u16 = 0x000F;
+
+/// Contextual and insertion entry flag to mark the current glyph.
+const SET_MARK: u16 = 0x8000;
+/// Contextual entry value for no substitution.
+const NO_SUBSTITUTION: u16 = 0xFFFF;
+
+/// Ligature entry flag to push the current glyph onto the component stack.
+const SET_COMPONENT: u16 = 0x8000;
+/// Ligature entry flag to perform the ligature actions of the entry.
+const PERFORM_ACTION: u16 = 0x2000;
+/// Ligature action flag for the last action of the entry.
+const LIG_ACTION_LAST: u32 = 0x8000_0000;
+/// Ligature action flag to store the ligature in place of the current component.
+const LIG_ACTION_STORE: u32 = 0x4000_0000;
+/// Mask of a ligature action for the signed offset into the component table.
+const LIG_ACTION_OFFSET: u32 = 0x3FFF_FFFF;
+/// The maximum depth of the ligature component stack.
+const MAX_COMPONENTS: usize = 64;
+
+/// Insertion entry flag to insert before rather than after the current glyph.
+const CURRENT_INSERT_BEFORE: u16 = 0x0800;
+/// Insertion entry flag to insert before rather than after the marked glyph.
+const MARKED_INSERT_BEFORE: u16 = 0x0400;
+/// Mask of an insertion entry for the number of glyphs to insert at the current glyph.
+const CURRENT_INSERT_COUNT: u16 = 0x03E0;
+/// Mask of an insertion entry for the number of glyphs to insert at the marked glyph.
+const MARKED_INSERT_COUNT: u16 = 0x001F;
+/// Insertion entry value for no insertion.
+const NO_INSERTION: u16 = 0xFFFF;
+/// The number of glyphs that insertion subtables may grow the glyphs to.
+const MAX_GLYPHS: usize = 0xFFFF;
+
+/// OpenType features that have an equivalent AAT feature.
+///
+/// Each entry holds the OpenType feature tag, the AAT feature type, and the AAT selectors that
+/// turn the feature on and off.
+const FEATURE_MAPPING: &[(u32, u16, u16, u16)] = &[
+    (tag::C2SC, 38, 1, 0),
+    (tag::CALT, 36, 0, 1),
+    (tag::CLIG, 1, 18, 19),
+    (tag::DLIG, 1, 4, 5),
+    (tag::FRAC, 11, 2, 0),
+    (tag::LIGA, 1, 2, 3),
+    (tag::SMCP, 37, 1, 0),
+];
+
+/// Apply the `morx` table to `glyphs`.
+///
+/// The subtables of each chain are applied if they're enabled by the default flags of the chain,
+/// adjusted by any features in `features` that have an AAT equivalent. Subtables that only apply
+/// to vertical text are skipped. Glyphs deleted by the subtables are removed once all chains have
+/// been applied, with their characters added to the glyph before them.
+pub fn apply<T: GlyphData>(
+    morx_table: &MorxTable<'_>,
+    features: &Features,
+    glyphs: &mut Vec<RawGlyph<T>>,
+) -> Result<(), ParseError> {
+    let selectors = feature_selectors(features);
+    for chain in &morx_table.chains {
+        let flags = chain.flags(&selectors);
+        for subtable in &chain.subtables {
+            if subtable.sub_feature_flags & flags == 0
+                || (subtable.coverage.contains(MorxCoverage::VERTICAL)
+                    && !subtable.coverage.contains(MorxCoverage::ALL_DIRECTIONS))
+            {
+                continue;
+            }
+
+            let reverse = subtable.coverage.contains(MorxCoverage::DESCENDING);
+            if reverse {
+                glyphs.reverse();
+            }
+            let res = apply_subtable(&subtable.data, glyphs);
+            if reverse {
+                glyphs.reverse();
+            }
+            res?;
+        }
+    }
+    remove_deleted_glyphs(glyphs);
+    Ok(())
+}
+
+/// Returns the AAT feature selectors of the features in `features` that have an AAT equivalent.
+fn feature_selectors(features: &Features) -> Vec<(u16, u16)> {
+    FEATURE_MAPPING
+        .iter()
+        .filter_map(|&(feature_tag, feature_type, on, off)| {
+            features
+                .setting(feature_tag)
+                .map(|value| (feature_type, if value != 0 { on } else { off }))
+        })
+        .collect()
+}
+
+fn apply_subtable<T: GlyphData>(
+    data: &MorxSubtableData<'_>,
+    glyphs: &mut Vec<RawGlyph<T>>,
+) -> Result<(), ParseError> {
+    match data {
+        MorxSubtableData::Rearrangement(state_table) => rearrangement(state_table, glyphs),
+        MorxSubtableData::Contextual(subtable) => contextual(subtable, glyphs),
+        MorxSubtableData::Ligature(subtable) => ligature(subtable, glyphs),
+        MorxSubtableData::Noncontextual(lookup_table) => {
+            for glyph in glyphs.iter_mut() {
+                if glyph.glyph_index == aat::DELETED_GLYPH {
+                    continue;
+                }
+                if let Some(glyph_index) = lookup_table.lookup(glyph.glyph_index)? {
+                    substitute(glyph, glyph_index);
+                }
+            }
+            Ok(())
+        }
+        MorxSubtableData::Insertion(subtable) => insertion(subtable, glyphs),
+        MorxSubtableData::Unsupported { .. } => Ok(()),
+    }
+}
+
+/// Runs the state machine of a subtable over `glyphs`.
+///
+/// `transition` is called with each entry, along with the index of the current glyph, which it
+/// may update when glyphs are inserted. `data_size` is the size of the subtable specific data of
+/// each entry.
+fn drive<T, F>(
+    state_table: &ExtendedStateTable<'_>,
+    data_size: usize,
+    glyphs: &mut Vec<RawGlyph<T>>,
+    mut transition: F,
+) -> Result<(), ParseError>
+where
+    F: FnMut(&StateEntry<'_>, &mut Vec<RawGlyph<T>>, &mut usize) -> Result<(), ParseError>,
+{
+    let mut state = aat::STATE_START_OF_TEXT;
+    let mut index = 0;
+    let mut dont_advance_count = 0;
+    loop {
+        let class = match glyphs.get(index) {
+            Some(glyph) => state_table.class(glyph.glyph_index)?,
+            None => aat::CLASS_END_OF_TEXT,
+        };
+        let entry = state_table.entry(state, class, data_size)?;
+        transition(&entry, glyphs, &mut index)?;
+        state = entry.new_state;
+
+        if index >= glyphs.len() {
+            break;
+        }
+        if entry.flags & DONT_ADVANCE == 0 || dont_advance_count >= aat::MAX_DONT_ADVANCE {
+            index += 1;
+            dont_advance_count = 0;
+        } else {
+            dont_advance_count += 1;
+        }
+    }
+    Ok(())
+}
+
+fn rearrangement<T: GlyphData>(
+    state_table: &ExtendedStateTable<'_>,
+    glyphs: &mut Vec<RawGlyph<T>>,
+) -> Result<(), ParseError> {
+    let (mut start, mut end) = (0, 0);
+    drive(state_table, 0, glyphs, |entry, glyphs, &mut index| {
+        if entry.flags & MARK_FIRST != 0 {
+            start = index;
+        }
+        if entry.flags & MARK_LAST != 0 {
+            end = (index + 1).min(glyphs.len());
+        }
+        let verb = entry.flags & VERB_MASK;
+        if verb != 0 && start < end {
+            rearrange(&mut glyphs[start..end], verb);
+        }
+        Ok(())
+    })
+}
+
+/// Rearranges `glyphs` according to `verb`.
+///
+/// Verbs move up to two glyphs from the start of the range (A and B) to its end, and up to two
+/// glyphs from the end of the range (C and D) to its start, optionally reversing them.
+fn rearrange<T>(glyphs: &mut [T], verb: u16) {
+    // The number of glyphs moved from the start and from the end, and whether they're reversed
+    let (start, reverse_start, end, reverse_end) = match verb {
+        1 => (1, false, 0, false),  // Ax => xA
+        2 => (0, false, 1, false),  // xD => Dx
+        3 => (1, false, 1, false),  // AxD => DxA
+        4 => (2, false, 0, false),  // ABx => xAB
+        5 => (2, true, 0, false),   // ABx => xBA
+        6 => (0, false, 2, false),  // xCD => CDx
+        7 => (0, false, 2, true),   // xCD => DCx
+        8 => (1, false, 2, false),  // AxCD => CDxA
+        9 => (1, false, 2, true),   // AxCD => DCxA
+        10 => (2, false, 1, false), // ABxD => DxAB
+        11 => (2, true, 1, false),  // ABxD => DxBA
+        12 => (2, false, 2, false), // ABxCD => CDxAB
+        13 => (2, true, 2, false),  // ABxCD => CDxBA
+        14 => (2, false, 2, true),  // ABxCD => DCxAB
+        15 => (2, true, 2, true),   // ABxCD => DCxBA
+        _ => return,
+    };
+    let len = glyphs.len();
+    if len < start + end {
+        return;
+    }
+    glyphs.rotate_left(start);
+    glyphs[..len - start].rotate_right(end);
+    if reverse_start {
+        glyphs[len - start..].reverse();
+    }
+    if reverse_end {
+        glyphs[..end].reverse();
+    }
+}
+
+fn contextual<T: GlyphData>(
+    subtable: &ContextualSubtable<'_>,
+    glyphs: &mut Vec<RawGlyph<T>>,
+) -> Result<(), ParseError> {
+    let mut mark: Option<usize> = None;
+    drive(
+        &subtable.state_table,
+        4,
+        glyphs,
+        |entry, glyphs, &mut index| {
+            let mut data = entry.data.ctxt();
+            let mark_index = data.read_u16be()?;
+            let current_index = data.read_u16be()?;
+
+            if mark_index != NO_SUBSTITUTION {
+                if let Some(glyph) = mark.and_then(|mark| glyphs.get_mut(mark)) {
+                    if let Some(glyph_index) =
+                        subtable.substitution(mark_index, glyph.glyph_index)?
+                    {
+                        substitute(glyph, glyph_index);
+                    }
+                }
+            }
+            if current_index != NO_SUBSTITUTION {
+                // At the end of the text the last glyph is substituted
+                let current = index.min(glyphs.len().saturating_sub(1));
+                if let Some(glyph) = glyphs.get_mut(current) {
+                    if let Some(glyph_index) =
+                        subtable.substitution(current_index, glyph.glyph_index)?
+                    {
+                        substitute(glyph, glyph_index);
+                    }
+                }
+            }
+            if entry.flags & SET_MARK != 0 {
+                mark = Some(index);
+            }
+            Ok(())
+        },
+    )
+}
+
+fn ligature<T: GlyphData>(
+    subtable: &LigatureSubtable<'_>,
+    glyphs: &mut Vec<RawGlyph<T>>,
+) -> Result<(), ParseError> {
+    let mut components: Vec<usize> = Vec::new();
+    drive(
+        &subtable.state_table,
+        2,
+        glyphs,
+        |entry, glyphs, &mut index| {
+            let action_index = entry.data.ctxt().read_u16be()?;
+
+            if entry.flags & SET_COMPONENT != 0 {
+                // The same glyph is not pushed twice when it was not advanced past
+                if components.last() == Some(&index) {
+                    components.pop();
+                }
+                if components.len() == MAX_COMPONENTS {
+                    components.remove(0);
+                }
+                components.push(index);
+            }
+
+            if entry.flags & PERFORM_ACTION == 0 || components.is_empty() || index >= glyphs.len() {
+                return Ok(());
+            }
+            let mut action_index = usize::from(action_index);
+            let mut cursor = components.len();
+            let mut ligature_index = 0;
+            loop {
+                if cursor == 0 {
+                    // The actions popped more components than were pushed
+                    components.clear();
+                    break;
+                }
+                cursor -= 1;
+                let position = components[cursor];
+                let action = subtable.lig_action(action_index)?;
+                action_index += 1;
+
+                // Sign extend the 30-bit offset
+                let mut offset = action & LIG_ACTION_OFFSET;
+                if offset & 0x2000_0000 != 0 {
+                    offset |= 0xC000_0000;
+                }
+                let component_index =
+                    i64::from(glyphs[position].glyph_index) + i64::from(offset as i32);
+                ligature_index +=
+                    usize::from(subtable.component(usize::try_from(component_index)?)?);
+
+                if action & (LIG_ACTION_STORE | LIG_ACTION_LAST) != 0 {
+                    let ligature = subtable.ligature(ligature_index)?;
+                    substitute(&mut glyphs[position], ligature);
+                    // The components after the ligature glyph are deleted
+                    while components.len() > cursor + 1 {
+                        if let Some(component) = components.pop() {
+                            glyphs[component].glyph_index = aat::DELETED_GLYPH;
+                        }
+                    }
+                }
+                if action & LIG_ACTION_LAST != 0 {
+                    break;
+                }
+            }
+            Ok(())
+        },
+    )
+}
+
+fn insertion<T: GlyphData>(
+    subtable: &InsertionSubtable<'_>,
+    glyphs: &mut Vec<RawGlyph<T>>,
+) -> Result<(), ParseError> {
+    let mut mark = 0;
+    drive(&subtable.state_table, 4, glyphs, |entry, glyphs, index| {
+        let mut data = entry.data.ctxt();
+        let current_insert_index = data.read_u16be()?;
+        let marked_insert_index = data.read_u16be()?;
+        let flags = entry.flags;
+        let mark_location = *index;
+
+        if marked_insert_index != NO_INSERTION {
+            let count = usize::from(flags & MARKED_INSERT_COUNT);
+            let mark = mark.min(glyphs.len());
+            let position = if flags & MARKED_INSERT_BEFORE != 0 || mark == glyphs.len() {
+                mark
+            } else {
+                mark + 1
+            };
+            insert_glyphs(subtable, glyphs, position, marked_insert_index, count)?;
+            if position <= *index {
+                *index += count;
+            }
+        }
+
+        if flags & SET_MARK != 0 {
+            mark = mark_location;
+        }
+
+        if current_insert_index != NO_INSERTION {
+            let count = usize::from((flags & CURRENT_INSERT_COUNT) >> 5);
+            let position = if flags & CURRENT_INSERT_BEFORE != 0 || *index >= glyphs.len() {
+                *index
+            } else {
+                *index + 1
+            };
+            insert_glyphs(subtable, glyphs, position, current_insert_index, count)?;
+            // Inserted glyphs are processed next when the current glyph is not advanced past
+            if flags & DONT_ADVANCE == 0 {
+                *index += count;
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Inserts `count` glyphs from the insertion glyph table of `subtable` at `position`.
+///
+/// Inserted glyphs take their properties from the glyph they're inserted next to, but have no
+/// characters.
+fn insert_glyphs<T: GlyphData>(
+    subtable: &InsertionSubtable<'_>,
+    glyphs: &mut Vec<RawGlyph<T>>,
+    position: usize,
+    insert_index: u16,
+    count: usize,
+) -> Result<(), ParseError> {
+    if glyphs.len() + count > MAX_GLYPHS {
+        return Err(ParseError::LimitExceeded);
+    }
+    let template = match glyphs.get(position).or_else(|| glyphs.last()) {
+        Some(glyph) => glyph.clone(),
+        None => return Ok(()),
+    };
+    let inserted = subtable
+        .insertion_glyphs(insert_index, count)?
+        .iter()
+        .map(|glyph_index| RawGlyph {
+            unicodes: tiny_vec![],
+            glyph_index,
+            liga_component_pos: 0,
+            glyph_origin: GlyphOrigin::Direct,
+            small_caps: template.small_caps,
+            multi_subst_dup: false,
+            multi_subst_component: None,
+            is_vert_alt: template.is_vert_alt,
+            fake_bold: template.fake_bold,
+            fake_italic: template.fake_italic,
+            stretch: None,
+            variation: None,
+            extra_data: template.extra_data.clone(),
+        })
+        .collect::<Vec<_>>();
+    glyphs.splice(position..position, inserted);
+    Ok(())
+}
+
+fn substitute<T>(glyph: &mut RawGlyph<T>, glyph_index: u16) {
+    glyph.glyph_index = glyph_index;
+    glyph.glyph_origin = GlyphOrigin::Direct;
+}
+
+/// Removes deleted glyphs, adding their characters to the glyph before them, or after them if
+/// there is none.
+fn remove_deleted_glyphs<T: GlyphData>(glyphs: &mut Vec<RawGlyph<T>>) {
+    let mut i = 0;
+    while i < glyphs.len() {
+        if glyphs[i].glyph_index != aat::DELETED_GLYPH {
+            i += 1;
+            continue;
+        }
+        let mut deleted = glyphs.remove(i);
+        if i > 0 {
+            let glyph = &mut glyphs[i - 1];
+            glyph.unicodes.append(&mut deleted.unicodes);
+            glyph.extra_data = GlyphData::merge(glyph.extra_data.clone(), deleted.extra_data);
+        } else if let Some(glyph) = glyphs.get_mut(i) {
+            deleted.unicodes.append(&mut glyph.unicodes);
+            glyph.unicodes = deleted.unicodes;
+            glyph.extra_data = GlyphData::merge(deleted.extra_data, glyph.extra_data.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::read::ReadScope;
+    use crate::binary::write::{WriteBinary, WriteBuffer, WriteContext};
+    use crate::binary::U32Be;
+    use crate::gsub::FeatureSetting;
+    use crate::tests::write_u16s;
+
+    fn glyph(glyph_index: u16, ch: char) -> RawGlyph<()> {
+        RawGlyph {
+            unicodes: tiny_vec![[char; 1] => ch],
+            glyph_index,
+            liga_component_pos: 0,
+            glyph_origin: GlyphOrigin::Char(ch),
+            small_caps: false,
+            multi_subst_dup: false,
+            multi_subst_component: None,
+            is_vert_alt: false,
+            fake_bold: false,
+            fake_italic: false,
+            stretch: None,
+            variation: None,
+            extra_data: (),
+        }
+    }
+
+    fn glyph_indices(glyphs: &[RawGlyph<()>]) -> Vec<u16> {
+        glyphs.iter().map(|glyph| glyph.glyph_index).collect()
+    }
+
+    fn u16s(values: &[u16]) -> Vec<u8> {
+        let mut w = WriteBuffer::new();
+        write_u16s(&mut w, values);
+        w.into_inner()
+    }
+
+    fn u32s(values: &[u32]) -> Vec<u8> {
+        let mut w = WriteBuffer::new();
+        for &value in values {
+            U32Be::write(&mut w, value).unwrap();
+        }
+        w.into_inner()
+    }
+
+    /// Builds an extended state table, followed by `tables` with their offsets in the header.
+    ///
+    /// Glyphs from `first_glyph` are assigned `classes`, `states` are the rows of the state
+    /// array, and `entries` are the entries of the entry table.
+    fn state_table(
+        first_glyph: u16,
+        classes: &[u16],
+        states: &[&[u16]],
+        entries: &[&[u16]],
+        tables: &[Vec<u8>],
+    ) -> Vec<u8> {
+        let n_classes = states[0].len();
+        let class_table_offset = 16 + 4 * tables.len();
+        let state_array_offset = class_table_offset + 6 + 2 * classes.len();
+        let entry_table_offset = state_array_offset + 2 * n_classes * states.len();
+        let mut table_offset =
+            entry_table_offset + entries.iter().map(|entry| 2 * entry.len()).sum::<usize>();
+
+        let mut w = WriteBuffer::new();
+        for &offset in &[
+            n_classes,
+            class_table_offset,
+            state_array_offset,
+            entry_table_offset,
+        ] {
+            U32Be::write(&mut w, offset as u32).unwrap();
+        }
+        for table in tables {
+            U32Be::write(&mut w, table_offset as u32).unwrap();
+            table_offset += table.len();
+        }
+        write_u16s(&mut w, &[8, first_glyph, classes.len() as u16]);
+        write_u16s(&mut w, classes);
+        for state in states {
+            write_u16s(&mut w, state);
+        }
+        for entry in entries {
+            write_u16s(&mut w, entry);
+        }
+        for table in tables {
+            w.write_bytes(table).unwrap();
+        }
+        w.into_inner()
+    }
+
+    /// Builds a `morx` table with a single chain holding `subtables` of the given types.
+    ///
+    /// Subtable `i` has sub-feature flag `1 << i`, and all subtables are enabled by default.
+    fn morx_table(subtables: &[(u8, Vec<u8>)], features: &[(u16, u16, u32, u32)]) -> Vec<u8> {
+        let subtables_length = subtables
+            .iter()
+            .map(|(_, body)| 12 + body.len())
+            .sum::<usize>();
+        let mut w = WriteBuffer::new();
+        // Header: version, unused, nChains
+        write_u16s(&mut w, &[2, 0]);
+        U32Be::write(&mut w, 1u32).unwrap();
+        // Chain header: defaultFlags, chainLength, nFeatureEntries, nSubtables
+        let chain_length = 16 + 12 * features.len() + subtables_length;
+        for &value in &[
+            (1u32 << subtables.len()) - 1,
+            chain_length as u32,
+            features.len() as u32,
+            subtables.len() as u32,
+        ] {
+            U32Be::write(&mut w, value).unwrap();
+        }
+        for &(feature_type, feature_setting, enable_flags, disable_flags) in features {
+            write_u16s(&mut w, &[feature_type, feature_setting]);
+            U32Be::write(&mut w, enable_flags).unwrap();
+            U32Be::write(&mut w, disable_flags).unwrap();
+        }
+        for (i, (subtable_type, body)) in subtables.iter().enumerate() {
+            U32Be::write(&mut w, 12 + body.len() as u32).unwrap();
+            U32Be::write(&mut w, u32::from(*subtable_type)).unwrap();
+            U32Be::write(&mut w, 1u32 << i).unwrap();
+            w.write_bytes(body).unwrap();
+        }
+        w.into_inner()
+    }
+
+    fn apply_morx(data: &[u8], features: &Features, glyphs: &mut Vec<RawGlyph<()>>) {
+        let morx = ReadScope::new(data).read::<MorxTable<'_>>().unwrap();
+        apply(&morx, features, glyphs).unwrap();
+    }
+
+    #[test]
+    fn test_noncontextual() {
+        // Substitutes glyphs 10 and 11 with 20 and 21, disabled by turning off common ligatures
+        let lookup = u16s(&[8, 10, 2, 20, 21]);
+        let data = morx_table(&[(4, lookup)], &[(1, 3, 0, !1)]);
+
+        let mut glyphs = vec![glyph(10, 'a'), glyph(11, 'b'), glyph(12, 'c')];
+        apply_morx(&data, &Features::Mask(Default::default()), &mut glyphs);
+        assert_eq!(glyph_indices(&glyphs), vec![20, 21, 12]);
+        assert_eq!(glyphs[0].glyph_origin, GlyphOrigin::Direct);
+
+        let mut glyphs = vec![glyph(10, 'a'), glyph(11, 'b'), glyph(12, 'c')];
+        let features = Features::Settings(vec![FeatureSetting::off(tag::LIGA)]);
+        apply_morx(&data, &features, &mut glyphs);
+        assert_eq!(glyph_indices(&glyphs), vec![10, 11, 12]);
+    }
+
+    #[test]
+    fn test_rearrangement() {
+        // Moves glyph 10 after the glyph 11 that follows it
+        let subtable = state_table(
+            10,
+            &[4, 5],
+            &[
+                &[0, 0, 0, 0, 1, 0],
+                &[0, 0, 0, 0, 1, 0],
+                &[0, 0, 0, 0, 1, 2],
+            ],
+            &[&[0, 0], &[2, MARK_FIRST], &[0, MARK_LAST | 1]],
+            &[],
+        );
+        let data = morx_table(&[(0, subtable)], &[]);
+
+        let mut glyphs = vec![
+            glyph(5, 'a'),
+            glyph(10, 'b'),
+            glyph(11, 'c'),
+            glyph(11, 'd'),
+        ];
+        apply_morx(&data, &Features::Mask(Default::default()), &mut glyphs);
+        assert_eq!(glyph_indices(&glyphs), vec![5, 11, 10, 11]);
+        assert_eq!(glyphs[1].unicodes[0], 'c');
+        assert_eq!(glyphs[2].unicodes[0], 'b');
+    }
+
+    #[test]
+    fn test_rearrange_verbs() {
+        let mut glyphs = ['A', 'B', 'x', 'C', 'D'];
+        rearrange(&mut glyphs, 15);
+        assert_eq!(glyphs, ['D', 'C', 'x', 'B', 'A']);
+
+        let mut glyphs = ['A', 'x', 'y', 'C', 'D'];
+        rearrange(&mut glyphs, 9);
+        assert_eq!(glyphs, ['D', 'C', 'x', 'y', 'A']);
+
+        let mut glyphs = ['A', 'B', 'x'];
+        rearrange(&mut glyphs, 5);
+        assert_eq!(glyphs, ['x', 'B', 'A']);
+
+        // Ranges too short for the verb are left unchanged
+        let mut glyphs = ['A', 'x'];
+        rearrange(&mut glyphs, 12);
+        assert_eq!(glyphs, ['A', 'x']);
+    }
+
+    #[test]
+    fn test_contextual() {
+        // Substitutes glyph 10 with 20, and glyph 11 with 21, when 10 is followed by 11
+        let substitution_tables =
+            [u32s(&[8, 16]), u16s(&[8, 10, 1, 20]), u16s(&[8, 11, 1, 21])].concat();
+        let subtable = state_table(
+            10,
+            &[4, 5],
+            &[
+                &[0, 0, 0, 0, 1, 0],
+                &[0, 0, 0, 0, 1, 0],
+                &[0, 0, 0, 0, 1, 2],
+            ],
+            &[
+                &[0, 0, NO_SUBSTITUTION, NO_SUBSTITUTION],
+                &[2, SET_MARK, NO_SUBSTITUTION, NO_SUBSTITUTION],
+                &[0, 0, 0, 1],
+            ],
+            &[substitution_tables],
+        );
+        let data = morx_table(&[(1, subtable)], &[]);
+
+        let mut glyphs = vec![
+            glyph(10, 'a'),
+            glyph(11, 'b'),
+            glyph(10, 'c'),
+            glyph(12, 'd'),
+        ];
+        apply_morx(&data, &Features::Mask(Default::default()), &mut glyphs);
+        assert_eq!(glyph_indices(&glyphs), vec![20, 21, 10, 12]);
+    }
+
+    #[test]
+    fn test_ligature() {
+        // Forms ligature 30 from glyphs 10 and 11
+        let offset = (-10i32 as u32) & LIG_ACTION_OFFSET;
+        let lig_actions = u32s(&[offset, LIG_ACTION_LAST | offset]);
+        let subtable = state_table(
+            10,
+            &[4, 5],
+            &[
+                &[0, 0, 0, 0, 1, 0],
+                &[0, 0, 0, 0, 1, 0],
+                &[0, 0, 0, 0, 1, 2],
+            ],
+            &[
+                &[0, 0, 0],
+                &[2, SET_COMPONENT, 0],
+                &[0, SET_COMPONENT | PERFORM_ACTION, 0],
+            ],
+            &[lig_actions, u16s(&[0, 1]), u16s(&[0, 30])],
+        );
+        let data = morx_table(&[(2, subtable)], &[]);
+
+        let mut glyphs = vec![
+            glyph(10, 'f'),
+            glyph(11, 'i'),
+            glyph(12, 'x'),
+            glyph(10, 'f'),
+        ];
+        apply_morx(&data, &Features::Mask(Default::default()), &mut glyphs);
+        assert_eq!(glyph_indices(&glyphs), vec![30, 12, 10]);
+        assert_eq!(glyphs[0].unicodes.as_slice(), &['f', 'i']);
+        assert_eq!(glyphs[0].glyph_origin, GlyphOrigin::Direct);
+    }
+
+    #[test]
+    fn test_insertion() {
+        // Inserts glyph 40 after each glyph 10
+        let subtable = state_table(
+            10,
+            &[4],
+            &[&[0, 0, 0, 0, 1], &[0, 0, 0, 0, 1]],
+            &[
+                &[0, 0, NO_INSERTION, NO_INSERTION],
+                &[0, 1 << 5, 0, NO_INSERTION],
+            ],
+            &[u16s(&[40])],
+        );
+        let data = morx_table(&[(5, subtable)], &[]);
+
+        let mut glyphs = vec![glyph(10, 'a'), glyph(12, 'b'), glyph(10, 'c')];
+        apply_morx(&data, &Features::Mask(Default::default()), &mut glyphs);
+        assert_eq!(glyph_indices(&glyphs), vec![10, 40, 12, 10, 40]);
+        assert!(glyphs[1].unicodes.is_empty());
+        assert_eq!(glyphs[1].glyph_origin, GlyphOrigin::Direct);
+    }
+}
@@ -0,0 +1,169 @@
+//! Least-recently-used cache of glyph outlines.
+//!
+//! Interpreting a glyph outline, particularly one that has to have variations applied, is much
+//! more expensive than looking it up again. Applications that repeatedly draw the same glyphs,
+//! such as editors and terminals, can use an `OutlineCache` to only interpret each glyph once per
+//! variation instance.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::tables::F2Dot14;
+
+/// Identifies an outline in an `OutlineCache`: a glyph at particular normalised variation
+/// coordinates.
+///
+/// An empty `coords` refers to the default instance.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct OutlineKey {
+    pub glyph: u16,
+    pub coords: Vec<F2Dot14>,
+}
+
+/// A cache of glyph outlines that evicts the least recently used outline when full.
+///
+/// The absence of an outline (E.g. for a space glyph) is cached as well, as `None`.
+pub struct OutlineCache<O> {
+    capacity: usize,
+    /// The cached outlines and the tick at which they were last used.
+    entries: HashMap<OutlineKey, (Option<O>, u64)>,
+    /// Maps the tick at which each entry was last used to its key, in order of use.
+    recency: BTreeMap<u64, OutlineKey>,
+    tick: u64,
+}
+
+impl<O: Clone> OutlineCache<O> {
+    /// Create a cache that holds up to `capacity` outlines.
+    pub fn new(capacity: usize) -> Self {
+        OutlineCache {
+            capacity,
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+        }
+    }
+
+    /// The maximum number of outlines held by the cache.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The number of outlines currently held by the cache.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Remove all outlines from the cache.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+    }
+
+    /// Look up the outline of `glyph` at `coords`, marking it as recently used.
+    ///
+    /// The outer `Option` is `None` if the outline is not in the cache.
+    pub fn get(&mut self, glyph: u16, coords: &[F2Dot14]) -> Option<Option<O>> {
+        let key = OutlineKey {
+            glyph,
+            coords: coords.to_vec(),
+        };
+        let tick = self.next_tick();
+        let (outline, last_used) = self.entries.get_mut(&key)?;
+        let key = self.recency.remove(last_used)?;
+        *last_used = tick;
+        self.recency.insert(tick, key);
+        Some(outline.clone())
+    }
+
+    /// Add the outline of `glyph` at `coords` to the cache, evicting the least recently used
+    /// outline if the cache is full.
+    pub fn insert(&mut self, glyph: u16, coords: &[F2Dot14], outline: Option<O>) {
+        if self.capacity == 0 {
+            return;
+        }
+        let key = OutlineKey {
+            glyph,
+            coords: coords.to_vec(),
+        };
+        let tick = self.next_tick();
+        if let Some((_, last_used)) = self.entries.get(&key) {
+            self.recency.remove(last_used);
+        } else if self.entries.len() >= self.capacity {
+            let oldest = self.recency.keys().next().copied();
+            if let Some(oldest_key) = oldest.and_then(|tick| self.recency.remove(&tick)) {
+                self.entries.remove(&oldest_key);
+            }
+        }
+        self.recency.insert(tick, key.clone());
+        self.entries.insert(key, (outline, tick));
+    }
+
+    /// Return the cached outline of `glyph` at `coords`, or call `load` to obtain it and cache the
+    /// result if it was not present.
+    ///
+    /// Errors from `load` are returned without being cached.
+    pub fn get_or_load<E>(
+        &mut self,
+        glyph: u16,
+        coords: &[F2Dot14],
+        load: impl FnOnce() -> Result<Option<O>, E>,
+    ) -> Result<Option<O>, E> {
+        if let Some(outline) = self.get(glyph, coords) {
+            return Ok(outline);
+        }
+        let outline = load()?;
+        self.insert(glyph, coords, outline.clone());
+        Ok(outline)
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lru_eviction() {
+        let mut cache = OutlineCache::new(2);
+        let bold = [F2Dot14::new(0x4000)];
+        cache.insert(1, &[], Some("a"));
+        cache.insert(1, &bold, Some("a bold"));
+        assert_eq!(cache.len(), 2);
+
+        // Using glyph 1 makes glyph 1 at `bold` the least recently used
+        assert_eq!(cache.get(1, &[]), Some(Some("a")));
+        cache.insert(2, &[], None);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(1, &bold), None);
+        assert_eq!(cache.get(1, &[]), Some(Some("a")));
+        assert_eq!(cache.get(2, &[]), Some(None));
+    }
+
+    #[test]
+    fn test_get_or_load() {
+        let mut cache = OutlineCache::new(4);
+        let mut loads = 0;
+        for _ in 0..3 {
+            let outline = cache.get_or_load(7, &[], || -> Result<_, ()> {
+                loads += 1;
+                Ok(Some(vec![(0, 0), (10, 10)]))
+            });
+            assert_eq!(outline, Ok(Some(vec![(0, 0), (10, 10)])));
+        }
+        assert_eq!(loads, 1);
+
+        // Errors are not cached
+        assert_eq!(
+            cache.get_or_load(8, &[], || Err("bad glyph")),
+            Err("bad glyph")
+        );
+        assert_eq!(cache.len(), 1);
+    }
+}
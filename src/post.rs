@@ -1,9 +1,12 @@
 //! `post` table parsing and writing.
 
-use crate::binary::read::{ReadArray, ReadBinary, ReadCtxt};
+use crate::binary::read::{ReadArrayCow, ReadBinary, ReadCtxt};
 use crate::binary::write::{WriteBinary, WriteContext};
 use crate::binary::{I16Be, I32Be, U16Be, U32Be, U8};
 use crate::error::{ParseError, WriteError};
+use crate::tables::Version16Dot16;
+use std::borrow::Cow;
+use std::convert::TryFrom;
 use std::str;
 
 pub struct PostTable<'a> {
@@ -11,8 +14,9 @@ pub struct PostTable<'a> {
     pub opt_sub_table: Option<SubTable<'a>>,
 }
 
+#[derive(Clone)]
 pub struct Header {
-    pub version: i32,
+    pub version: Version16Dot16,
     pub italic_angle: i32,
     pub underline_position: i16,
     pub underline_thickness: i16,
@@ -25,7 +29,7 @@ pub struct Header {
 
 pub struct SubTable<'a> {
     pub num_glyphs: u16,
-    pub glyph_name_index: ReadArray<'a, U16Be>,
+    pub glyph_name_index: ReadArrayCow<'a, U16Be>,
     pub names: Vec<PascalString<'a>>,
 }
 
@@ -37,7 +41,7 @@ impl<'a> ReadBinary<'a> for Header {
     type HostType = Self;
 
     fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
-        let version = ctxt.read_i32be()?;
+        let version = ctxt.read::<Version16Dot16>()?;
         let italic_angle = ctxt.read_i32be()?;
         let underline_position = ctxt.read_i16be()?;
         let underline_thickness = ctxt.read_i16be()?;
@@ -65,7 +69,7 @@ impl<'a> WriteBinary<&Self> for Header {
     type Output = ();
 
     fn write<C: WriteContext>(ctxt: &mut C, table: &Header) -> Result<(), WriteError> {
-        I32Be::write(ctxt, table.version)?;
+        Version16Dot16::write(ctxt, table.version)?;
         I32Be::write(ctxt, table.italic_angle)?;
         I16Be::write(ctxt, table.underline_position)?;
         I16Be::write(ctxt, table.underline_thickness)?;
@@ -84,11 +88,12 @@ impl<'a> ReadBinary<'a> for PostTable<'a> {
 
     fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
         let header = ctxt.read::<Header>()?;
-        let opt_sub_table = match header.version {
-            0x00020000 => {
+        let opt_sub_table = match (header.version.major(), header.version.minor()) {
+            (2, 0) => {
                 // May include some Format 1 glyphs
                 let num_glyphs = ctxt.read_u16be()?;
-                let glyph_name_index = ctxt.read_array(usize::from(num_glyphs))?;
+                let glyph_name_index =
+                    ReadArrayCow::Borrowed(ctxt.read_array(usize::from(num_glyphs))?);
 
                 let num_names = glyph_name_index
                     .iter()
@@ -109,7 +114,7 @@ impl<'a> ReadBinary<'a> for PostTable<'a> {
                 })
             }
             // TODO Handle post version 1.0, 2.5, 3.0
-            0x00010000 | 0x00025000 | 0x00030000 => None,
+            (1, 0) | (2, 5) | (3, 0) => None,
             _ => return Err(ParseError::BadVersion),
         };
 
@@ -138,7 +143,7 @@ impl<'a> WriteBinary<&Self> for SubTable<'a> {
 
     fn write<C: WriteContext>(ctxt: &mut C, table: &SubTable<'a>) -> Result<(), WriteError> {
         U16Be::write(ctxt, table.num_glyphs)?;
-        <&ReadArray<'_, _>>::write(ctxt, &table.glyph_name_index)?;
+        ReadArrayCow::write(ctxt, &table.glyph_name_index)?;
         for name in &table.names {
             PascalString::write(ctxt, name)?;
         }
@@ -170,12 +175,12 @@ impl<'a> PostTable<'a> {
             }
         }
 
-        match &self.header.version {
-            0x00010000 if usize::from(glyph_index) < FORMAT_1_NAMES.len() => {
+        match (self.header.version.major(), self.header.version.minor()) {
+            (1, 0) if usize::from(glyph_index) < FORMAT_1_NAMES.len() => {
                 let name = FORMAT_1_NAMES[usize::from(glyph_index)];
                 Ok(Some(name))
             }
-            0x00020000 => match &self.opt_sub_table {
+            (2, 0) => match &self.opt_sub_table {
                 Some(sub_table) => {
                     let name_index = sub_table
                         .glyph_name_index
@@ -185,7 +190,8 @@ impl<'a> PostTable<'a> {
                         Ok(Some(FORMAT_1_NAMES[usize::from(name_index)]))
                     } else {
                         let index = usize::from(name_index) - FORMAT_1_NAMES.len();
-                        let pascal_string = &sub_table.names[index];
+                        let pascal_string =
+                            sub_table.names.get(index).ok_or(ParseError::BadIndex)?;
 
                         match str::from_utf8(pascal_string.bytes) {
                             Ok(name) => Ok(Some(name)),
@@ -199,6 +205,100 @@ impl<'a> PostTable<'a> {
             _ => Ok(None),
         }
     }
+
+    /// Returns the name of `glyph_index`, synthesizing one if the table does not name it.
+    ///
+    /// Version 3.0 tables contain no names. In that case, and for glyphs out of range of the
+    /// table, a name of the form `gN` is synthesized where `N` is the glyph index. Glyph 0 is
+    /// always `.notdef`.
+    pub fn glyph_name_or_synthesized(&self, glyph_index: u16) -> Result<Cow<'a, str>, ParseError> {
+        match self.glyph_name(glyph_index)? {
+            Some(name) => Ok(Cow::from(name)),
+            None if glyph_index == 0 => Ok(Cow::from(".notdef")),
+            None => Ok(Cow::from(format!("g{}", glyph_index))),
+        }
+    }
+
+    /// Returns a copy of this table that only names the glyphs in `glyph_ids`, renumbered in the
+    /// order they are supplied.
+    ///
+    /// Version 2.0 tables keep the names of the retained glyphs, with unused custom names dropped
+    /// and the name indices renumbered. Version 1.0 tables are converted to version 2.0, since the
+    /// standard Macintosh ordering no longer holds once glyphs are renumbered. Tables of any other
+    /// version become version 3.0, which has no names.
+    pub fn subset(&self, glyph_ids: &[u16]) -> Result<PostTable<'a>, ParseError> {
+        let num_glyphs = u16::try_from(glyph_ids.len())?;
+        let version = self.header.version;
+        let opt_sub_table = match (version.major(), version.minor()) {
+            (1, 0) => {
+                let glyph_name_index = glyph_ids
+                    .iter()
+                    .map(|&glyph_id| {
+                        if usize::from(glyph_id) < FORMAT_1_NAMES.len() {
+                            glyph_id
+                        } else {
+                            0
+                        }
+                    })
+                    .collect();
+                Some(SubTable {
+                    num_glyphs,
+                    glyph_name_index: ReadArrayCow::Owned(glyph_name_index),
+                    names: Vec::new(),
+                })
+            }
+            (2, 0) => {
+                let sub_table = self.opt_sub_table.as_ref().ok_or(ParseError::BadValue)?;
+                let mut glyph_name_index = Vec::with_capacity(glyph_ids.len());
+                let mut names: Vec<PascalString<'a>> = Vec::new();
+                for &glyph_id in glyph_ids {
+                    let name_index = if glyph_id < sub_table.num_glyphs {
+                        sub_table.glyph_name_index.get_item(usize::from(glyph_id))
+                    } else {
+                        0
+                    };
+                    let name_index = match usize::from(name_index).checked_sub(FORMAT_1_NAMES.len())
+                    {
+                        None => name_index,
+                        Some(index) => {
+                            let name = sub_table.names.get(index).ok_or(ParseError::BadIndex)?;
+                            let new_index = match names
+                                .iter()
+                                .position(|existing| existing.bytes == name.bytes)
+                            {
+                                Some(new_index) => new_index,
+                                None => {
+                                    names.push(PascalString { bytes: name.bytes });
+                                    names.len() - 1
+                                }
+                            };
+                            u16::try_from(FORMAT_1_NAMES.len() + new_index)?
+                        }
+                    };
+                    glyph_name_index.push(name_index);
+                }
+                Some(SubTable {
+                    num_glyphs,
+                    glyph_name_index: ReadArrayCow::Owned(glyph_name_index),
+                    names,
+                })
+            }
+            _ => None,
+        };
+
+        let version = if opt_sub_table.is_some() {
+            Version16Dot16::new(2, 0)
+        } else {
+            Version16Dot16::new(3, 0)
+        };
+        Ok(PostTable {
+            header: Header {
+                version,
+                ..self.header.clone()
+            },
+            opt_sub_table,
+        })
+    }
 }
 
 static FORMAT_1_NAMES: &'static [&'static str; 258] = &[
@@ -461,3 +561,85 @@ static FORMAT_1_NAMES: &'static [&'static str; 258] = &[
     "ccaron",
     "dcroat",
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::read::ReadScope;
+    use crate::binary::write::WriteBuffer;
+
+    fn make_post_table_v2() -> Vec<u8> {
+        let mut data = vec![
+            0x00, 0x02, 0x00, 0x00, // version
+            0x00, 0x00, 0x00, 0x00, // italic angle
+            0xFF, 0x9C, // underline position
+            0x00, 0x32, // underline thickness
+            0x00, 0x00, 0x00, 0x00, // is fixed pitch
+            0x00, 0x00, 0x00, 0x00, // min mem type 42
+            0x00, 0x00, 0x00, 0x00, // max mem type 42
+            0x00, 0x00, 0x00, 0x00, // min mem type 1
+            0x00, 0x00, 0x00, 0x00, // max mem type 1
+            0x00, 0x04, // num glyphs
+            0x00, 0x00, 0x01, 0x02, 0x00, 0x24, 0x01, 0x03, // glyph name indices
+        ];
+        data.extend_from_slice(b"\x03foo\x03bar");
+        data
+    }
+
+    #[test]
+    fn test_glyph_name_v2() {
+        let data = make_post_table_v2();
+        let post = ReadScope::new(&data).read::<PostTable<'_>>().unwrap();
+
+        assert_eq!(post.glyph_name(0).unwrap(), Some(".notdef"));
+        assert_eq!(post.glyph_name(1).unwrap(), Some("foo"));
+        assert_eq!(post.glyph_name(2).unwrap(), Some("A"));
+        assert_eq!(post.glyph_name(3).unwrap(), Some("bar"));
+        assert_eq!(post.glyph_name(4).unwrap(), None);
+    }
+
+    #[test]
+    fn test_subset_v2() {
+        let data = make_post_table_v2();
+        let post = ReadScope::new(&data).read::<PostTable<'_>>().unwrap();
+        let subset = post.subset(&[0, 3, 2]).unwrap();
+
+        let mut ctxt = WriteBuffer::new();
+        PostTable::write(&mut ctxt, &subset).unwrap();
+        let subset_data = ctxt.into_inner();
+        let subset = ReadScope::new(&subset_data)
+            .read::<PostTable<'_>>()
+            .unwrap();
+
+        let sub_table = subset.opt_sub_table.as_ref().unwrap();
+        assert_eq!(sub_table.num_glyphs, 3);
+        assert_eq!(sub_table.names.len(), 1);
+        assert_eq!(subset.glyph_name(1).unwrap(), Some("bar"));
+        assert_eq!(subset.glyph_name(2).unwrap(), Some("A"));
+        assert_eq!(subset.header.underline_position, -100);
+    }
+
+    #[test]
+    fn test_subset_v1_converts_to_v2() {
+        let mut data = make_post_table_v2();
+        data.truncate(32);
+        data[1] = 0x01; // version 1.0
+        let post = ReadScope::new(&data).read::<PostTable<'_>>().unwrap();
+        let subset = post.subset(&[0, 36]).unwrap();
+
+        assert_eq!(subset.header.version, Version16Dot16::new(2, 0));
+        assert_eq!(subset.glyph_name(1).unwrap(), Some("A"));
+    }
+
+    #[test]
+    fn test_glyph_name_synthesized_v3() {
+        let mut data = make_post_table_v2();
+        data.truncate(32);
+        data[1] = 0x03; // version 3.0
+        let post = ReadScope::new(&data).read::<PostTable<'_>>().unwrap();
+
+        assert_eq!(post.glyph_name_or_synthesized(0).unwrap(), ".notdef");
+        assert_eq!(post.glyph_name_or_synthesized(7).unwrap(), "g7");
+        assert!(post.subset(&[0, 7]).unwrap().opt_sub_table.is_none());
+    }
+}
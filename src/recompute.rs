@@ -0,0 +1,373 @@
+//! Recalculation of font-wide values after glyphs have been edited.
+//!
+//! Subsetting, instancing, and outline conversion all change the glyphs of a font, which leaves
+//! the summary values in the `head`, `hhea`, and `maxp` tables stale. `recompute_globals`
+//! recalculates these values from the `glyf` and `hmtx` tables, while `recompute_maxp` only
+//! updates `maxp`, for use when writing the `glyf` table.
+
+use std::cmp;
+use std::convert::TryFrom;
+
+use crate::error::ParseError;
+use crate::tables::glyf::{BoundingBox, GlyfTable, GlyphData, COMPOSITE_GLYPH_RECURSION_LIMIT};
+use crate::tables::{HeadTable, HheaTable, HmtxTable, LongHorMetric, MaxpTable};
+
+/// The tables of a TrueType font that `recompute_globals` reads and updates.
+pub struct FontTables<'a> {
+    pub head: HeadTable,
+    pub hhea: HheaTable,
+    pub maxp: MaxpTable,
+    pub hmtx: HmtxTable<'a>,
+    pub glyf: GlyfTable<'a>,
+}
+
+/// Recalculate the font-wide values that are derived from the glyphs.
+///
+/// The following values are updated:
+///
+/// * `head`: `xMin`, `yMin`, `xMax`, `yMax`.
+/// * `hhea`: `advanceWidthMax`, `minLeftSideBearing`, `minRightSideBearing`, `xMaxExtent`,
+///   `numberOfHMetrics`.
+/// * `maxp`: `numGlyphs` and, for version 1.0 tables, `maxPoints`, `maxContours`,
+///   `maxCompositePoints`, `maxCompositeContours`, `maxComponentElements`, `maxComponentDepth`.
+/// * `hmtx`: left side bearings are set to the `xMin` of each glyph with contours and the metrics
+///   are stored using the minimum number of `longHorMetric` records.
+///
+/// Bounds are calculated from the glyph outlines rather than the bounding boxes stored in each
+/// glyph, so they remain correct after outlines have been edited.
+pub fn recompute_globals(tables: &mut FontTables<'_>) -> Result<(), ParseError> {
+    let num_glyphs = u16::try_from(tables.glyf.records.len())?;
+    let num_h_metrics = tables.hhea.num_h_metrics;
+
+    let mut font_bbox: Option<BoundingBox> = None;
+    let mut advance_width_max = 0;
+    let mut min_left_side_bearing: Option<i16> = None;
+    let mut min_right_side_bearing: Option<i16> = None;
+    let mut x_max_extent: Option<i16> = None;
+    let mut h_metrics = Vec::with_capacity(usize::from(num_glyphs));
+
+    for glyph_index in 0..num_glyphs {
+        let advance_width = tables.hmtx.horizontal_advance(glyph_index, num_h_metrics)?;
+        advance_width_max = cmp::max(advance_width_max, advance_width);
+
+        if tables.glyf.glyph(glyph_index)?.is_none() {
+            h_metrics.push(LongHorMetric {
+                advance_width,
+                lsb: 0,
+            });
+            continue;
+        }
+
+        let points = tables.glyf.glyph_points(glyph_index)?;
+        let lsb = if points.points.is_empty() {
+            0
+        } else {
+            let bbox = BoundingBox::from_points(&points.points);
+            // The left side bearing is set to x_min so the extent is x_max
+            let extent = bbox.x_max;
+            let rsb = i16::try_from(i32::from(advance_width) - i32::from(bbox.x_max))?;
+            min_left_side_bearing = Some(min_option(min_left_side_bearing, bbox.x_min));
+            min_right_side_bearing = Some(min_option(min_right_side_bearing, rsb));
+            x_max_extent = Some(cmp::max(x_max_extent.unwrap_or(extent), extent));
+            let x_min = bbox.x_min;
+            font_bbox = Some(match font_bbox {
+                Some(font_bbox) => union(font_bbox, bbox),
+                None => bbox,
+            });
+            x_min
+        };
+        h_metrics.push(LongHorMetric { advance_width, lsb });
+    }
+
+    let font_bbox = font_bbox.unwrap_or(BoundingBox {
+        x_min: 0,
+        x_max: 0,
+        y_min: 0,
+        y_max: 0,
+    });
+    tables.head.x_min = font_bbox.x_min;
+    tables.head.y_min = font_bbox.y_min;
+    tables.head.x_max = font_bbox.x_max;
+    tables.head.y_max = font_bbox.y_max;
+
+    // Trailing glyphs that share the advance of the last record don't need their own record
+    let hmtx = HmtxTable::from_metrics(h_metrics);
+
+    tables.hhea.advance_width_max = advance_width_max;
+    tables.hhea.min_left_side_bearing = min_left_side_bearing.unwrap_or(0);
+    tables.hhea.min_right_side_bearing = min_right_side_bearing.unwrap_or(0);
+    tables.hhea.x_max_extent = x_max_extent.unwrap_or(0);
+    tables.hhea.num_h_metrics = u16::try_from(hmtx.h_metrics.len())?;
+    tables.hmtx = hmtx;
+
+    recompute_maxp(&tables.glyf, &mut tables.maxp)
+}
+
+/// Recalculate the values of the `maxp` table that are derived from the glyphs of `glyf`.
+///
+/// `numGlyphs` is updated and, for version 1.0 tables, `maxPoints`, `maxContours`,
+/// `maxCompositePoints`, `maxCompositeContours`, `maxComponentElements` and
+/// `maxComponentDepth`. The values that depend on instructions are left as they are.
+pub fn recompute_maxp(glyf: &GlyfTable<'_>, maxp: &mut MaxpTable) -> Result<(), ParseError> {
+    let num_glyphs = u16::try_from(glyf.records.len())?;
+    let mut values = MaxpValues::default();
+    if maxp.version1_sub_table.is_some() {
+        for glyph_index in 0..num_glyphs {
+            let glyph = match glyf.glyph(glyph_index)? {
+                Some(glyph) => glyph,
+                None => continue,
+            };
+            let points = glyf.glyph_points(glyph_index)?;
+            let num_points = u16::try_from(points.points.len())?;
+            let num_contours = u16::try_from(points.end_pts_of_contours.len())?;
+            match &glyph.data {
+                GlyphData::Simple(_) => {
+                    values.max_points = cmp::max(values.max_points, num_points);
+                    values.max_contours = cmp::max(values.max_contours, num_contours);
+                }
+                GlyphData::Composite { glyphs, .. } => {
+                    let num_components = u16::try_from(glyphs.len())?;
+                    let depth = component_depth(glyf, glyph_index, 0)?;
+                    values.max_composite_points = cmp::max(values.max_composite_points, num_points);
+                    values.max_composite_contours =
+                        cmp::max(values.max_composite_contours, num_contours);
+                    values.max_component_elements =
+                        cmp::max(values.max_component_elements, num_components);
+                    values.max_component_depth = cmp::max(values.max_component_depth, depth);
+                }
+            }
+        }
+    }
+
+    maxp.num_glyphs = num_glyphs;
+    if let Some(sub_table) = maxp.version1_sub_table.as_mut() {
+        sub_table.max_points = values.max_points;
+        sub_table.max_contours = values.max_contours;
+        sub_table.max_composite_points = values.max_composite_points;
+        sub_table.max_composite_contours = values.max_composite_contours;
+        sub_table.max_component_elements = values.max_component_elements;
+        sub_table.max_component_depth = values.max_component_depth;
+    }
+
+    Ok(())
+}
+
+#[derive(Default)]
+struct MaxpValues {
+    max_points: u16,
+    max_contours: u16,
+    max_composite_points: u16,
+    max_composite_contours: u16,
+    max_component_elements: u16,
+    max_component_depth: u16,
+}
+
+/// The number of levels of composite glyphs below `glyph_index`.
+///
+/// Simple and empty glyphs have a depth of 0, composites of simple glyphs have a depth of 1.
+pub(crate) fn component_depth(
+    glyf: &GlyfTable<'_>,
+    glyph_index: u16,
+    depth: usize,
+) -> Result<u16, ParseError> {
+    if depth > COMPOSITE_GLYPH_RECURSION_LIMIT {
+        return Err(ParseError::LimitExceeded);
+    }
+
+    match glyf.glyph(glyph_index)? {
+        Some(glyph) => match &glyph.data {
+            GlyphData::Simple(_) => Ok(0),
+            GlyphData::Composite { glyphs, .. } => {
+                let mut max_depth = 0;
+                for composite_glyph in glyphs {
+                    let component = component_depth(glyf, composite_glyph.glyph_index, depth + 1)?;
+                    max_depth = cmp::max(max_depth, component);
+                }
+                Ok(max_depth + 1)
+            }
+        },
+        None => Ok(0),
+    }
+}
+
+fn min_option(current: Option<i16>, value: i16) -> i16 {
+    current.map_or(value, |current| cmp::min(current, value))
+}
+
+fn union(a: BoundingBox, b: BoundingBox) -> BoundingBox {
+    BoundingBox {
+        x_min: cmp::min(a.x_min, b.x_min),
+        x_max: cmp::max(a.x_max, b.x_max),
+        y_min: cmp::min(a.y_min, b.y_min),
+        y_max: cmp::max(a.y_max, b.y_max),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::*;
+    use crate::binary::read::{ReadArrayCow, ReadScope};
+    use crate::tables::glyf::{
+        CompositeGlyph, CompositeGlyphArgument, CompositeGlyphFlag, GlyfRecord, Glyph, Point,
+        SimpleGlyph, SimpleGlyphFlag,
+    };
+    use crate::tables::MaxpVersion1SubTable;
+
+    fn square_glyph() -> Glyph<'static> {
+        Glyph {
+            number_of_contours: 1,
+            // Deliberately stale, recompute_globals should use the points
+            bounding_box: BoundingBox {
+                x_min: 0,
+                x_max: 0,
+                y_min: 0,
+                y_max: 0,
+            },
+            data: GlyphData::Simple(SimpleGlyph {
+                end_pts_of_contours: vec![3],
+                instructions: vec![],
+                flags: vec![SimpleGlyphFlag::ON_CURVE_POINT; 4],
+                coordinates: vec![Point(10, 0), Point(10, 200), Point(100, 200), Point(100, 0)],
+            }),
+        }
+    }
+
+    fn composite_glyph() -> Glyph<'static> {
+        Glyph {
+            number_of_contours: -1,
+            bounding_box: BoundingBox {
+                x_min: 0,
+                x_max: 0,
+                y_min: 0,
+                y_max: 0,
+            },
+            data: GlyphData::Composite {
+                glyphs: vec![CompositeGlyph {
+                    flags: CompositeGlyphFlag::ARG_1_AND_2_ARE_WORDS
+                        | CompositeGlyphFlag::ARGS_ARE_XY_VALUES,
+                    glyph_index: 1,
+                    argument1: CompositeGlyphArgument::I16(450),
+                    argument2: CompositeGlyphArgument::I16(-20),
+                    scale: None,
+                }],
+                instructions: Cow::Borrowed(&[]),
+            },
+        }
+    }
+
+    fn font_tables() -> FontTables<'static> {
+        let head_data = include_bytes!("../tests/fonts/opentype/head.bin");
+        let head = ReadScope::new(head_data).read::<HeadTable>().unwrap();
+        let hhea = HheaTable {
+            ascender: 800,
+            descender: -200,
+            line_gap: 0,
+            advance_width_max: 0,
+            min_left_side_bearing: 0,
+            min_right_side_bearing: 0,
+            x_max_extent: 0,
+            caret_slope_rise: 1,
+            caret_slope_run: 0,
+            caret_offset: 0,
+            num_h_metrics: 4,
+        };
+        let maxp = MaxpTable {
+            num_glyphs: 4,
+            version1_sub_table: Some(MaxpVersion1SubTable {
+                max_points: 0,
+                max_contours: 0,
+                max_composite_points: 0,
+                max_composite_contours: 0,
+                max_zones: 2,
+                max_twilight_points: 0,
+                max_storage: 0,
+                max_function_defs: 0,
+                max_instruction_defs: 0,
+                max_stack_elements: 0,
+                max_size_of_instructions: 0,
+                max_component_elements: 0,
+                max_component_depth: 0,
+            }),
+        };
+        let metric = |advance_width| LongHorMetric {
+            advance_width,
+            lsb: 0,
+        };
+        let hmtx = HmtxTable {
+            h_metrics: ReadArrayCow::Owned(vec![
+                metric(500),
+                metric(600),
+                metric(600),
+                metric(600),
+            ]),
+            left_side_bearings: ReadArrayCow::Owned(vec![]),
+        };
+        let glyf = GlyfTable {
+            records: vec![
+                GlyfRecord::Empty,
+                GlyfRecord::Parsed(square_glyph()),
+                GlyfRecord::Parsed(composite_glyph()),
+                GlyfRecord::Empty,
+            ],
+        };
+
+        FontTables {
+            head,
+            hhea,
+            maxp,
+            hmtx,
+            glyf,
+        }
+    }
+
+    #[test]
+    fn recompute() {
+        let mut tables = font_tables();
+        recompute_globals(&mut tables).unwrap();
+
+        let head = &tables.head;
+        assert_eq!(
+            (head.x_min, head.y_min, head.x_max, head.y_max),
+            (10, -20, 550, 200)
+        );
+
+        let hhea = &tables.hhea;
+        assert_eq!(hhea.advance_width_max, 600);
+        assert_eq!(hhea.min_left_side_bearing, 10);
+        assert_eq!(hhea.min_right_side_bearing, 50);
+        assert_eq!(hhea.x_max_extent, 550);
+        assert_eq!(hhea.num_h_metrics, 2);
+
+        let hmtx = &tables.hmtx;
+        assert_eq!(hmtx.h_metrics.len(), 2);
+        assert_eq!(hmtx.h_metrics.get_item(1).lsb, 10);
+        assert_eq!(
+            hmtx.left_side_bearings.iter().collect::<Vec<_>>(),
+            vec![460, 0]
+        );
+
+        let maxp = tables.maxp.version1_sub_table.as_ref().unwrap();
+        assert_eq!(tables.maxp.num_glyphs, 4);
+        assert_eq!((maxp.max_points, maxp.max_contours), (4, 1));
+        assert_eq!(
+            (maxp.max_composite_points, maxp.max_composite_contours),
+            (4, 1)
+        );
+        assert_eq!(maxp.max_component_elements, 1);
+        assert_eq!(maxp.max_component_depth, 1);
+    }
+
+    #[test]
+    fn recompute_empty() {
+        let mut tables = font_tables();
+        tables.glyf.records = vec![GlyfRecord::Empty];
+        recompute_globals(&mut tables).unwrap();
+
+        assert_eq!(tables.head.x_max, 0);
+        assert_eq!(tables.hhea.num_h_metrics, 1);
+        assert_eq!(tables.hhea.advance_width_max, 500);
+        assert_eq!(tables.maxp.num_glyphs, 1);
+    }
+}
@@ -0,0 +1,412 @@
+#![deny(missing_docs)]
+
+//! Splitting text into runs of a single script.
+//!
+//! Text mixing scripts, such as Latin with Arabic or Japanese, needs to be split into runs that
+//! are shaped separately with the appropriate OpenType script tag. Characters shared by several
+//! scripts, such as spaces, punctuation and digits, are resolved to the script of the surrounding
+//! text, and combining marks to the script of their base. Paired brackets are resolved to the
+//! script of the text enclosing them, so that both brackets of a pair end up in the same script.
+//!
+//! Scripts are determined by Unicode block, with exceptions for the common and inherited
+//! characters within blocks. Only scripts with an OpenType script tag that is commonly supported
+//! are recognised; characters of other scripts are treated as common.
+
+use std::ops::Range;
+
+use crate::error::ShapingError;
+use crate::font::{Font, MatchingPresentation};
+use crate::gpos::Info;
+use crate::gsub::Features;
+use crate::tables::FontTableProvider;
+use crate::tag;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum CharScript {
+    /// Used by several scripts, takes the script of the surrounding text.
+    Common,
+    /// A combining mark, takes the script of its base.
+    Inherited,
+    /// Belongs to the script with the OpenType script tag.
+    Script(u32),
+}
+
+/// Ranges of characters belonging to a script, sorted and non-overlapping. Characters not in a
+/// range are common.
+///
+/// Hiragana is included in the `kana` script, as OpenType shares one tag between Hiragana and
+/// Katakana.
+const SCRIPT_RANGES: &[(char, char, CharScript)] = &[
+    ('\u{0041}', '\u{005A}', CharScript::Script(tag::LATN)),
+    ('\u{0061}', '\u{007A}', CharScript::Script(tag::LATN)),
+    ('\u{00AA}', '\u{00AA}', CharScript::Script(tag::LATN)),
+    ('\u{00BA}', '\u{00BA}', CharScript::Script(tag::LATN)),
+    ('\u{00C0}', '\u{00D6}', CharScript::Script(tag::LATN)),
+    ('\u{00D8}', '\u{00F6}', CharScript::Script(tag::LATN)),
+    ('\u{00F8}', '\u{02AF}', CharScript::Script(tag::LATN)),
+    ('\u{0300}', '\u{036F}', CharScript::Inherited),
+    ('\u{0370}', '\u{0373}', CharScript::Script(tag::GREK)),
+    ('\u{0375}', '\u{037D}', CharScript::Script(tag::GREK)),
+    ('\u{037F}', '\u{0384}', CharScript::Script(tag::GREK)),
+    ('\u{0386}', '\u{0386}', CharScript::Script(tag::GREK)),
+    ('\u{0388}', '\u{03FF}', CharScript::Script(tag::GREK)),
+    ('\u{0400}', '\u{052F}', CharScript::Script(tag::CYRL)),
+    ('\u{0531}', '\u{0588}', CharScript::Script(tag::ARMN)),
+    ('\u{058A}', '\u{058F}', CharScript::Script(tag::ARMN)),
+    ('\u{0591}', '\u{05FF}', CharScript::Script(tag::HEBR)),
+    ('\u{0600}', '\u{0604}', CharScript::Script(tag::ARAB)),
+    ('\u{0606}', '\u{060B}', CharScript::Script(tag::ARAB)),
+    ('\u{060D}', '\u{061A}', CharScript::Script(tag::ARAB)),
+    ('\u{061C}', '\u{061E}', CharScript::Script(tag::ARAB)),
+    ('\u{0620}', '\u{063F}', CharScript::Script(tag::ARAB)),
+    ('\u{0641}', '\u{064A}', CharScript::Script(tag::ARAB)),
+    ('\u{064B}', '\u{0655}', CharScript::Inherited),
+    ('\u{0656}', '\u{066F}', CharScript::Script(tag::ARAB)),
+    ('\u{0670}', '\u{0670}', CharScript::Inherited),
+    ('\u{0671}', '\u{06DC}', CharScript::Script(tag::ARAB)),
+    ('\u{06DE}', '\u{06FF}', CharScript::Script(tag::ARAB)),
+    ('\u{0700}', '\u{074F}', CharScript::Script(tag::SYRC)),
+    ('\u{0750}', '\u{077F}', CharScript::Script(tag::ARAB)),
+    ('\u{0780}', '\u{07BF}', CharScript::Script(tag::THAA)),
+    ('\u{07C0}', '\u{07FF}', CharScript::Script(tag::NKO)),
+    ('\u{08A0}', '\u{08FF}', CharScript::Script(tag::ARAB)),
+    ('\u{0900}', '\u{0950}', CharScript::Script(tag::DEVA)),
+    ('\u{0951}', '\u{0954}', CharScript::Inherited),
+    ('\u{0955}', '\u{0963}', CharScript::Script(tag::DEVA)),
+    ('\u{0966}', '\u{097F}', CharScript::Script(tag::DEVA)),
+    ('\u{0980}', '\u{09FF}', CharScript::Script(tag::BENG)),
+    ('\u{0A00}', '\u{0A7F}', CharScript::Script(tag::GURU)),
+    ('\u{0A80}', '\u{0AFF}', CharScript::Script(tag::GUJR)),
+    ('\u{0B00}', '\u{0B7F}', CharScript::Script(tag::ORYA)),
+    ('\u{0B80}', '\u{0BFF}', CharScript::Script(tag::TAML)),
+    ('\u{0C00}', '\u{0C7F}', CharScript::Script(tag::TELU)),
+    ('\u{0C80}', '\u{0CFF}', CharScript::Script(tag::KNDA)),
+    ('\u{0D00}', '\u{0D7F}', CharScript::Script(tag::MLYM)),
+    ('\u{0D80}', '\u{0DFF}', CharScript::Script(tag::SINH)),
+    ('\u{0E01}', '\u{0E3E}', CharScript::Script(tag::THAI)),
+    ('\u{0E40}', '\u{0E7F}', CharScript::Script(tag::THAI)),
+    ('\u{0E80}', '\u{0EFF}', CharScript::Script(tag::LAO)),
+    ('\u{0F00}', '\u{0FD4}', CharScript::Script(tag::TIBT)),
+    ('\u{0FD9}', '\u{0FFF}', CharScript::Script(tag::TIBT)),
+    ('\u{1000}', '\u{109F}', CharScript::Script(tag::MYMR)),
+    ('\u{10A0}', '\u{10FA}', CharScript::Script(tag::GEOR)),
+    ('\u{10FC}', '\u{10FF}', CharScript::Script(tag::GEOR)),
+    ('\u{1100}', '\u{11FF}', CharScript::Script(tag::HANG)),
+    ('\u{1200}', '\u{139F}', CharScript::Script(tag::ETHI)),
+    ('\u{1780}', '\u{17FF}', CharScript::Script(tag::KHMR)),
+    ('\u{1800}', '\u{1801}', CharScript::Script(tag::MONG)),
+    ('\u{1804}', '\u{1804}', CharScript::Script(tag::MONG)),
+    ('\u{1806}', '\u{18AF}', CharScript::Script(tag::MONG)),
+    ('\u{19E0}', '\u{19FF}', CharScript::Script(tag::KHMR)),
+    ('\u{1AB0}', '\u{1AFF}', CharScript::Inherited),
+    ('\u{1C90}', '\u{1CBF}', CharScript::Script(tag::GEOR)),
+    ('\u{1CD0}', '\u{1CD2}', CharScript::Inherited),
+    ('\u{1CD4}', '\u{1CE0}', CharScript::Inherited),
+    ('\u{1D00}', '\u{1D7F}', CharScript::Script(tag::LATN)),
+    ('\u{1DC0}', '\u{1DFF}', CharScript::Inherited),
+    ('\u{1E00}', '\u{1EFF}', CharScript::Script(tag::LATN)),
+    ('\u{1F00}', '\u{1FFF}', CharScript::Script(tag::GREK)),
+    ('\u{200C}', '\u{200D}', CharScript::Inherited),
+    ('\u{20D0}', '\u{20FF}', CharScript::Inherited),
+    ('\u{2C60}', '\u{2C7F}', CharScript::Script(tag::LATN)),
+    ('\u{2D00}', '\u{2D2F}', CharScript::Script(tag::GEOR)),
+    ('\u{2D80}', '\u{2DDF}', CharScript::Script(tag::ETHI)),
+    ('\u{2DE0}', '\u{2DFF}', CharScript::Script(tag::CYRL)),
+    ('\u{2E80}', '\u{2FDF}', CharScript::Script(tag::HANI)),
+    ('\u{3005}', '\u{3005}', CharScript::Script(tag::HANI)),
+    ('\u{3007}', '\u{3007}', CharScript::Script(tag::HANI)),
+    ('\u{3021}', '\u{3029}', CharScript::Script(tag::HANI)),
+    ('\u{302A}', '\u{302D}', CharScript::Inherited),
+    ('\u{3038}', '\u{303B}', CharScript::Script(tag::HANI)),
+    ('\u{3041}', '\u{3096}', CharScript::Script(tag::KANA)),
+    ('\u{3099}', '\u{309A}', CharScript::Inherited),
+    ('\u{309D}', '\u{309F}', CharScript::Script(tag::KANA)),
+    ('\u{30A1}', '\u{30FA}', CharScript::Script(tag::KANA)),
+    ('\u{30FD}', '\u{30FF}', CharScript::Script(tag::KANA)),
+    ('\u{3105}', '\u{312F}', CharScript::Script(tag::BOPO)),
+    ('\u{3131}', '\u{318E}', CharScript::Script(tag::HANG)),
+    ('\u{31A0}', '\u{31BF}', CharScript::Script(tag::BOPO)),
+    ('\u{31F0}', '\u{31FF}', CharScript::Script(tag::KANA)),
+    ('\u{3400}', '\u{4DBF}', CharScript::Script(tag::HANI)),
+    ('\u{4E00}', '\u{9FFF}', CharScript::Script(tag::HANI)),
+    ('\u{A640}', '\u{A69F}', CharScript::Script(tag::CYRL)),
+    ('\u{A722}', '\u{A7FF}', CharScript::Script(tag::LATN)),
+    ('\u{A960}', '\u{A97F}', CharScript::Script(tag::HANG)),
+    ('\u{A9E0}', '\u{A9FE}', CharScript::Script(tag::MYMR)),
+    ('\u{AA60}', '\u{AA7F}', CharScript::Script(tag::MYMR)),
+    ('\u{AB30}', '\u{AB5A}', CharScript::Script(tag::LATN)),
+    ('\u{AB5C}', '\u{AB64}', CharScript::Script(tag::LATN)),
+    ('\u{AC00}', '\u{D7FF}', CharScript::Script(tag::HANG)),
+    ('\u{F900}', '\u{FAFF}', CharScript::Script(tag::HANI)),
+    ('\u{FB00}', '\u{FB06}', CharScript::Script(tag::LATN)),
+    ('\u{FB13}', '\u{FB17}', CharScript::Script(tag::ARMN)),
+    ('\u{FB1D}', '\u{FB4F}', CharScript::Script(tag::HEBR)),
+    ('\u{FB50}', '\u{FD3D}', CharScript::Script(tag::ARAB)),
+    ('\u{FD40}', '\u{FDFF}', CharScript::Script(tag::ARAB)),
+    ('\u{FE00}', '\u{FE0F}', CharScript::Inherited),
+    ('\u{FE20}', '\u{FE2F}', CharScript::Inherited),
+    ('\u{FE70}', '\u{FEFC}', CharScript::Script(tag::ARAB)),
+    ('\u{FF21}', '\u{FF3A}', CharScript::Script(tag::LATN)),
+    ('\u{FF41}', '\u{FF5A}', CharScript::Script(tag::LATN)),
+    ('\u{FF66}', '\u{FF6F}', CharScript::Script(tag::KANA)),
+    ('\u{FF71}', '\u{FF9D}', CharScript::Script(tag::KANA)),
+    ('\u{FFA0}', '\u{FFDC}', CharScript::Script(tag::HANG)),
+    ('\u{20000}', '\u{2FA1F}', CharScript::Script(tag::HANI)),
+    ('\u{30000}', '\u{3134F}', CharScript::Script(tag::HANI)),
+    ('\u{E0100}', '\u{E01EF}', CharScript::Inherited),
+];
+
+/// Pairs of brackets that are resolved to the same script.
+const BRACKET_PAIRS: &[(char, char)] = &[
+    ('(', ')'),
+    ('[', ']'),
+    ('{', '}'),
+    ('\u{00AB}', '\u{00BB}'), // « »
+    ('\u{2039}', '\u{203A}'), // ‹ ›
+    ('\u{3008}', '\u{3009}'), // 〈 〉
+    ('\u{300A}', '\u{300B}'), // 《 》
+    ('\u{300C}', '\u{300D}'), // 「 」
+    ('\u{300E}', '\u{300F}'), // 『 』
+    ('\u{3010}', '\u{3011}'), // 【 】
+    ('\u{3014}', '\u{3015}'), // 〔 〕
+    ('\u{FF08}', '\u{FF09}'), // fullwidth parentheses
+    ('\u{FF3B}', '\u{FF3D}'), // fullwidth square brackets
+    ('\u{FF5B}', '\u{FF5D}'), // fullwidth curly brackets
+];
+
+/// A run of text in a single script.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScriptRun {
+    /// The byte range of the run in the text.
+    pub range: Range<usize>,
+    /// The OpenType script tag of the run.
+    ///
+    /// This is `DFLT` for text made up entirely of common characters. Scripts with two OpenType
+    /// tags, such as the Indic scripts, use the original tag as `Font::shape` selects the newer
+    /// one when the font supports it.
+    pub script_tag: u32,
+}
+
+/// A script run that has been shaped.
+#[derive(Debug)]
+pub struct ShapedScriptRun {
+    /// The byte range of the run in the text.
+    pub range: Range<usize>,
+    /// The OpenType script tag the run was shaped with.
+    pub script_tag: u32,
+    /// The shaped glyphs of the run.
+    pub infos: Vec<Info>,
+}
+
+/// Returns the OpenType script tag of `ch`.
+///
+/// Returns `None` for characters that are common to several scripts or are combining marks,
+/// which take the script of the surrounding text.
+pub fn script_tag(ch: char) -> Option<u32> {
+    match char_script(ch) {
+        CharScript::Script(script_tag) => Some(script_tag),
+        CharScript::Common | CharScript::Inherited => None,
+    }
+}
+
+fn char_script(ch: char) -> CharScript {
+    let (mut lo, mut hi) = (0, SCRIPT_RANGES.len());
+    while lo < hi {
+        let mid = (lo + hi) / 2;
+        let (start, end, script) = SCRIPT_RANGES[mid];
+        if ch < start {
+            hi = mid;
+        } else if ch > end {
+            lo = mid + 1;
+        } else {
+            return script;
+        }
+    }
+    CharScript::Common
+}
+
+/// Split `text` into runs of a single script.
+///
+/// Common and inherited characters are added to the run they're in, with those at the start of
+/// the text taking the script of the first run. Brackets take the script of the text they're
+/// within, and a closing bracket takes the script of its opening bracket.
+pub fn itemize(text: &str) -> Vec<ScriptRun> {
+    let mut runs = Vec::new();
+    let mut run_start = 0;
+    let mut run_script: Option<u32> = None;
+    // Open brackets awaiting their closing bracket, and the script of the text they're within
+    let mut brackets: Vec<(char, Option<u32>)> = Vec::new();
+
+    for (i, ch) in text.char_indices() {
+        let script = match char_script(ch) {
+            CharScript::Inherited => continue,
+            CharScript::Script(script) => script,
+            CharScript::Common => {
+                if let Some(&(_, close)) = BRACKET_PAIRS.iter().find(|&&(open, _)| open == ch) {
+                    brackets.push((close, run_script));
+                    continue;
+                }
+                match brackets.iter().rposition(|&(close, _)| close == ch) {
+                    Some(index) => {
+                        let (_, bracket_script) = brackets[index];
+                        brackets.truncate(index);
+                        match bracket_script {
+                            Some(script) => script,
+                            None => continue,
+                        }
+                    }
+                    None => continue,
+                }
+            }
+        };
+
+        match run_script {
+            None => {
+                // Resolve the brackets opened in the common text at the start of the run
+                for (_, bracket_script) in brackets.iter_mut() {
+                    *bracket_script = Some(script);
+                }
+                run_script = Some(script);
+            }
+            Some(current) if current != script => {
+                runs.push(ScriptRun {
+                    range: run_start..i,
+                    script_tag: current,
+                });
+                run_start = i;
+                run_script = Some(script);
+            }
+            Some(_) => {}
+        }
+    }
+
+    if !text.is_empty() {
+        runs.push(ScriptRun {
+            range: run_start..text.len(),
+            script_tag: run_script.unwrap_or(tag::DFLT),
+        });
+    }
+    runs
+}
+
+/// Shape text that may contain several scripts.
+///
+/// `text` is split into script runs with `itemize`, which are mapped to glyphs and shaped
+/// separately, dispatching each to the shaper for its script. The language system
+/// `opt_lang_tag` is used for every run, falling back to the default language system of scripts
+/// the font has no entry for.
+///
+/// For text mixing directions the runs should be further split into directional runs, see the
+/// `bidi` module.
+pub fn shape_itemized<T: FontTableProvider>(
+    font: &mut Font<T>,
+    text: &str,
+    opt_lang_tag: Option<u32>,
+    features: &Features,
+    kerning: bool,
+) -> Result<Vec<ShapedScriptRun>, ShapingError> {
+    let mut shaped_runs = Vec::new();
+    for run in itemize(text) {
+        let glyphs = font.map_glyphs(&text[run.range.clone()], MatchingPresentation::NotRequired);
+        let infos = font.shape(glyphs, run.script_tag, opt_lang_tag, features, kerning)?;
+        shaped_runs.push(ShapedScriptRun {
+            range: run.range,
+            script_tag: run.script_tag,
+            infos,
+        });
+    }
+    Ok(shaped_runs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::read::ReadScope;
+    use crate::gsub::GsubFeatureMask;
+    use crate::tables::OpenTypeFont;
+    use crate::tests::read_fixture;
+
+    fn runs(text: &str) -> Vec<(&str, u32)> {
+        itemize(text)
+            .into_iter()
+            .map(|run| (&text[run.range], run.script_tag))
+            .collect()
+    }
+
+    #[test]
+    fn test_script_tag() {
+        assert_eq!(script_tag('a'), Some(tag::LATN));
+        assert_eq!(script_tag('\u{00D7}'), None);
+        assert_eq!(script_tag('\u{0628}'), Some(tag::ARAB));
+        assert_eq!(script_tag('\u{064B}'), None);
+        assert_eq!(script_tag('\u{3042}'), Some(tag::KANA));
+        assert_eq!(script_tag('\u{4E00}'), Some(tag::HANI));
+        assert_eq!(script_tag('\u{0915}'), Some(tag::DEVA));
+        assert_eq!(script_tag('\u{0964}'), None);
+        assert_eq!(script_tag('1'), None);
+    }
+
+    #[test]
+    fn test_itemize() {
+        assert_eq!(runs(""), vec![]);
+        assert_eq!(runs("123 ..."), vec![("123 ...", tag::DFLT)]);
+        assert_eq!(runs("abc def"), vec![("abc def", tag::LATN)]);
+        // Leading common characters take the script of the first run
+        assert_eq!(
+            runs("1. \u{0628}\u{062A} abc"),
+            vec![("1. \u{0628}\u{062A} ", tag::ARAB), ("abc", tag::LATN)]
+        );
+        // Combining marks stay with their base
+        assert_eq!(
+            runs("e\u{0301}\u{0915}\u{094D}"),
+            vec![("e\u{0301}", tag::LATN), ("\u{0915}\u{094D}", tag::DEVA)]
+        );
+        // Hiragana, Katakana and Han
+        assert_eq!(
+            runs("\u{3072}\u{30AB}\u{4E00}"),
+            vec![("\u{3072}\u{30AB}", tag::KANA), ("\u{4E00}", tag::HANI)]
+        );
+    }
+
+    #[test]
+    fn test_itemize_brackets() {
+        // The closing bracket takes the script of the opening bracket
+        assert_eq!(
+            runs("abc (\u{05D0}\u{05D1}) def"),
+            vec![
+                ("abc (", tag::LATN),
+                ("\u{05D0}\u{05D1}", tag::HEBR),
+                (") def", tag::LATN)
+            ]
+        );
+        // Brackets at the start of the text are resolved with the first run
+        assert_eq!(
+            runs("(\u{0628}) abc"),
+            vec![("(\u{0628}) ", tag::ARAB), ("abc", tag::LATN)]
+        );
+    }
+
+    #[test]
+    fn test_shape_itemized() {
+        let buffer = read_fixture("tests/fonts/noto/NotoNaskhArabic-Regular.ttf");
+        let opentype_file = ReadScope::new(&buffer).read::<OpenTypeFont<'_>>().unwrap();
+        let provider = opentype_file.table_provider(0).unwrap();
+        let mut font = Font::new(Box::new(provider)).unwrap().unwrap();
+
+        let text = "\u{0628}\u{062A} a";
+        let runs = shape_itemized(
+            &mut font,
+            text,
+            None,
+            &Features::Mask(GsubFeatureMask::default()),
+            true,
+        )
+        .unwrap();
+        let scripts = runs.iter().map(|run| run.script_tag).collect::<Vec<_>>();
+        assert_eq!(scripts, vec![tag::ARAB, tag::LATN]);
+        // The Arabic letters are joined, so take their initial and final forms
+        let (beh, _) = font.lookup_glyph_index('\u{0628}', MatchingPresentation::NotRequired, None);
+        assert_eq!(runs[0].infos.len(), 3);
+        assert_ne!(runs[0].infos[0].glyph.glyph_index, beh);
+    }
+}
@@ -4,7 +4,7 @@
 //! <https://github.com/n8willis/opentype-shaping-documents/blob/master/opentype-shaping-arabic-general.md>
 
 use crate::error::{ParseError, ShapingError};
-use crate::gsub::{self, GlyphData, GlyphOrigin, GsubFeatureMask, RawGlyph};
+use crate::gsub::{self, GlyphData, GsubFeatureMask, RawGlyph};
 use crate::layout::{GDEFTable, LayoutCache, LayoutTable, GSUB};
 use crate::tag;
 
@@ -51,16 +51,29 @@ impl ArabicGlyph {
     fn set_feature_tag(&mut self, feature_tag: u32) {
         self.extra_data.feature_tag = feature_tag
     }
+
+    /// The modified combining class of the glyph, or zero if it doesn't map to a single mark.
+    fn combining_class(&self) -> u8 {
+        if self.unicodes.len() == 1 {
+            modified_combining_class(self.unicodes[0])
+        } else {
+            0
+        }
+    }
+
+    fn is_modifier_combining_mark(&self) -> bool {
+        self.unicodes.len() == 1 && MODIFIER_COMBINING_MARKS.contains(&self.unicodes[0])
+    }
 }
 
 impl From<&RawGlyph<()>> for ArabicGlyph {
     fn from(raw_glyph: &RawGlyph<()>) -> ArabicGlyph {
         // Since there's no `Char` to work out the `ArabicGlyph`s joining type when the glyph's
-        // `glyph_origin` is `GlyphOrigin::Direct`, we fallback to `JoiningType::NonJoining` as
-        // the safest approach
-        let joining_type = match raw_glyph.glyph_origin {
-            GlyphOrigin::Char(c) => get_joining_type(c),
-            GlyphOrigin::Direct => JoiningType::NonJoining,
+        // `glyph_origin` is `GlyphOrigin::Direct`, other than for the first glyph of a decomposed
+        // character, we fallback to `JoiningType::NonJoining` as the safest approach
+        let joining_type = match raw_glyph.source_char() {
+            Some(c) => get_joining_type(c),
+            None => JoiningType::NonJoining,
         };
 
         ArabicGlyph {
@@ -70,9 +83,11 @@ impl From<&RawGlyph<()>> for ArabicGlyph {
             glyph_origin: raw_glyph.glyph_origin,
             small_caps: raw_glyph.small_caps,
             multi_subst_dup: raw_glyph.multi_subst_dup,
+            multi_subst_component: raw_glyph.multi_subst_component,
             is_vert_alt: raw_glyph.is_vert_alt,
             fake_bold: raw_glyph.fake_bold,
             fake_italic: raw_glyph.fake_italic,
+            stretch: raw_glyph.stretch,
             variation: raw_glyph.variation,
             extra_data: ArabicData {
                 joining_type,
@@ -93,15 +108,45 @@ impl From<&ArabicGlyph> for RawGlyph<()> {
             glyph_origin: arabic_glyph.glyph_origin,
             small_caps: arabic_glyph.small_caps,
             multi_subst_dup: arabic_glyph.multi_subst_dup,
+            multi_subst_component: arabic_glyph.multi_subst_component,
             is_vert_alt: arabic_glyph.is_vert_alt,
             fake_bold: arabic_glyph.fake_bold,
             variation: arabic_glyph.variation,
             fake_italic: arabic_glyph.fake_italic,
+            stretch: arabic_glyph.stretch,
             extra_data: (),
         }
     }
 }
 
+/// Language-form substitution features, paired with whether they apply to all glyphs.
+pub(crate) const LANGUAGE_FEATURES: &[(GsubFeatureMask, bool)] = &[
+    (GsubFeatureMask::LOCL, true),
+    (GsubFeatureMask::ISOL, false),
+    (GsubFeatureMask::FINA, false),
+    (GsubFeatureMask::MEDI, false),
+    (GsubFeatureMask::INIT, false),
+    (GsubFeatureMask::RLIG, true),
+    (GsubFeatureMask::RCLT, true),
+    (GsubFeatureMask::CALT, true),
+];
+
+/// Typographic-form substitution features.
+pub(crate) const TYPOGRAPHIC_FEATURES: &[GsubFeatureMask] =
+    &[GsubFeatureMask::LIGA, GsubFeatureMask::MSET];
+
+/// The substitution features that may be applied when shaping Arabic.
+pub(crate) fn gsub_features() -> GsubFeatureMask {
+    LANGUAGE_FEATURES
+        .iter()
+        .map(|&(feature_mask, _)| feature_mask)
+        .chain(TYPOGRAPHIC_FEATURES.iter().cloned())
+        .fold(
+            GsubFeatureMask::CCMP | GsubFeatureMask::STCH,
+            |mask, feature_mask| mask | feature_mask,
+        )
+}
+
 pub fn gsub_apply_arabic(
     gsub_cache: &LayoutCache<GSUB>,
     gsub_table: &LayoutTable<GSUB>,
@@ -163,20 +208,20 @@ pub fn gsub_apply_arabic(
 
     // 3. Applying the stch feature
     //
-    // TODO hold off for future generalised solution (including the Syriac Abbreviation Mark)
+    // The decomposed components are marked as fixed or repeating parts; repeating them to fill
+    // the required width happens after positioning, see `gpos::apply_stretch`.
 
-    // 4. Applying the language-form substitution features from GSUB
+    gsub::gsub_apply_stch(
+        gsub_cache,
+        gsub_table,
+        gdef_table,
+        script_tag,
+        lang_tag,
+        arabic_glyphs,
+        |_| true,
+    )?;
 
-    const LANGUAGE_FEATURES: &'static [(GsubFeatureMask, bool)] = &[
-        (GsubFeatureMask::LOCL, true),
-        (GsubFeatureMask::ISOL, false),
-        (GsubFeatureMask::FINA, false),
-        (GsubFeatureMask::MEDI, false),
-        (GsubFeatureMask::INIT, false),
-        (GsubFeatureMask::RLIG, true),
-        (GsubFeatureMask::RCLT, true),
-        (GsubFeatureMask::CALT, true),
-    ];
+    // 4. Applying the language-form substitution features from GSUB
 
     for &(feature_mask, is_global) in LANGUAGE_FEATURES {
         apply_lookups(
@@ -196,9 +241,6 @@ pub fn gsub_apply_arabic(
     // Note that we skip `GSUB`'s `DLIG` and `CSWH` features as results would differ from other
     // Arabic shapers
 
-    const TYPOGRAPHIC_FEATURES: &'static [GsubFeatureMask] =
-        &[GsubFeatureMask::LIGA, GsubFeatureMask::MSET];
-
     for &feature_mask in TYPOGRAPHIC_FEATURES {
         apply_lookups(
             feature_mask,
@@ -213,14 +255,140 @@ pub fn gsub_apply_arabic(
     }
 
     // 6. Mark reordering
-    //
-    // This is currently not implemented as results would then differ from other Arabic shapers
+
+    reorder_marks(arabic_glyphs);
 
     *raw_glyphs = arabic_glyphs.iter().map(RawGlyph::from).collect();
 
     Ok(())
 }
 
+/// Modifier combining marks, from Unicode Technical Report #53.
+const MODIFIER_COMBINING_MARKS: &[char] = &[
+    '\u{0654}', // ARABIC HAMZA ABOVE
+    '\u{0655}', // ARABIC HAMZA BELOW
+    '\u{0658}', // ARABIC MARK NOON GHUNNA
+    '\u{06DC}', // ARABIC SMALL HIGH SEEN
+    '\u{06E3}', // ARABIC SMALL LOW SEEN
+    '\u{06E7}', // ARABIC SMALL HIGH YEH
+    '\u{06E8}', // ARABIC SMALL HIGH NOON
+    '\u{08CA}', // ARABIC SMALL HIGH FARSI YEH
+    '\u{08CB}', // ARABIC SMALL HIGH YEH BARREE WITH TWO DOTS BELOW
+    '\u{08CD}', // ARABIC SMALL HIGH ZAH
+    '\u{08CE}', // ARABIC LARGE ROUND DOT ABOVE
+    '\u{08CF}', // ARABIC LARGE ROUND DOT BELOW
+    '\u{08D3}', // ARABIC SMALL LOW WAW
+    '\u{08F3}', // ARABIC SMALL HIGH WAW
+];
+
+/// Returns the canonical combining class of the Arabic marks, modified so that shadda sorts
+/// before the other harakat as it does in other Arabic shapers.
+fn modified_combining_class(ch: char) -> u8 {
+    match ch {
+        // Shadda
+        '\u{0651}' => 26,
+        '\u{0610}'..='\u{0617}' => 230,
+        '\u{0618}' => 30,
+        '\u{0619}' => 31,
+        '\u{061A}' => 32,
+        '\u{064B}' => 27,
+        '\u{064C}' => 28,
+        '\u{064D}' => 29,
+        '\u{064E}' => 30,
+        '\u{064F}' => 31,
+        '\u{0650}' => 32,
+        '\u{0652}' => 34,
+        '\u{0653}'..='\u{0654}' => 230,
+        '\u{0655}'..='\u{0656}' => 220,
+        '\u{0657}'..='\u{065B}' => 230,
+        '\u{065C}' => 220,
+        '\u{065D}'..='\u{065E}' => 230,
+        '\u{065F}' => 220,
+        '\u{0670}' => 35,
+        '\u{06D6}'..='\u{06DC}' => 230,
+        '\u{06DF}'..='\u{06E2}' => 230,
+        '\u{06E3}' => 220,
+        '\u{06E4}' => 230,
+        '\u{06E7}'..='\u{06E8}' => 230,
+        '\u{06EA}' => 220,
+        '\u{06EB}'..='\u{06EC}' => 230,
+        '\u{06ED}' => 220,
+        '\u{0898}' => 230,
+        '\u{0899}'..='\u{089B}' => 220,
+        '\u{089C}'..='\u{089F}' => 230,
+        '\u{08CA}'..='\u{08CE}' => 230,
+        '\u{08CF}'..='\u{08D3}' => 220,
+        '\u{08D4}'..='\u{08E1}' => 230,
+        '\u{08E3}' => 220,
+        '\u{08E4}'..='\u{08E5}' => 230,
+        '\u{08E6}' => 220,
+        '\u{08E7}'..='\u{08E8}' => 230,
+        '\u{08E9}' => 220,
+        '\u{08EA}'..='\u{08EC}' => 230,
+        '\u{08ED}'..='\u{08EF}' => 220,
+        '\u{08F0}' => 27,
+        '\u{08F1}' => 28,
+        '\u{08F2}' => 29,
+        '\u{08F3}'..='\u{08F5}' => 230,
+        '\u{08F6}' => 220,
+        '\u{08F7}'..='\u{08F8}' => 230,
+        '\u{08F9}'..='\u{08FA}' => 220,
+        '\u{08FB}'..='\u{08FF}' => 230,
+        _ => 0,
+    }
+}
+
+/// Reorder each sequence of marks into combining class order, then move any modifier combining
+/// marks to the front of the sequence following the Arabic Mark Transient Reordering Algorithm.
+///
+/// <https://www.unicode.org/reports/tr53/>
+fn reorder_marks(arabic_glyphs: &mut [ArabicGlyph]) {
+    let mut i = 0;
+    while i < arabic_glyphs.len() {
+        if arabic_glyphs[i].combining_class() == 0 {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < arabic_glyphs.len() && arabic_glyphs[i].combining_class() != 0 {
+            i += 1;
+        }
+        let marks = &mut arabic_glyphs[start..i];
+        // `sort_by_key` is stable so marks of the same class retain their order
+        marks.sort_by_key(ArabicGlyph::combining_class);
+        reorder_modifier_combining_marks(marks);
+    }
+}
+
+fn reorder_modifier_combining_marks(marks: &mut [ArabicGlyph]) {
+    let mut start = 0;
+    let mut i = 0;
+    for &combining_class in &[220, 230] {
+        while i < marks.len() && marks[i].combining_class() < combining_class {
+            i += 1;
+        }
+        if i == marks.len() {
+            break;
+        }
+        if marks[i].combining_class() > combining_class {
+            continue;
+        }
+        let mut j = i;
+        while j < marks.len()
+            && marks[j].combining_class() == combining_class
+            && marks[j].is_modifier_combining_mark()
+        {
+            j += 1;
+        }
+        if i == j {
+            continue;
+        }
+        marks[start..j].rotate_right(j - i);
+        start += j - i;
+        i = j;
+    }
+}
+
 fn apply_lookups(
     feature_mask: GsubFeatureMask,
     gsub_cache: &LayoutCache<GSUB>,
@@ -232,9 +400,9 @@ fn apply_lookups(
     pred: impl Fn(&RawGlyph<ArabicData>, u32) -> bool + Copy,
 ) -> Result<(), ParseError> {
     let index = gsub::get_lookups_cache_index(gsub_cache, script_tag, lang_tag, feature_mask)?;
-    let lookups = &gsub_cache.cached_lookups.borrow()[index];
+    let lookups = gsub_cache.cached_lookups.read().unwrap()[index].clone();
 
-    for &(lookup_index, feature_tag) in lookups {
+    for &(lookup_index, feature_tag) in &lookups {
         gsub::gsub_apply_lookup(
             gsub_cache,
             gsub_table,
@@ -251,3 +419,69 @@ fn apply_lookups(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gsub::GlyphOrigin;
+    use tinyvec::tiny_vec;
+
+    fn glyphs(text: &str) -> Vec<ArabicGlyph> {
+        text.chars()
+            .map(|ch| {
+                ArabicGlyph::from(&RawGlyph {
+                    unicodes: tiny_vec![[char; 1] => ch],
+                    glyph_index: 0,
+                    liga_component_pos: 0,
+                    glyph_origin: GlyphOrigin::Char(ch),
+                    small_caps: false,
+                    multi_subst_dup: false,
+                    multi_subst_component: None,
+                    is_vert_alt: false,
+                    fake_bold: false,
+                    fake_italic: false,
+                    stretch: None,
+                    variation: None,
+                    extra_data: (),
+                })
+            })
+            .collect()
+    }
+
+    fn reordered(text: &str) -> String {
+        let mut arabic_glyphs = glyphs(text);
+        reorder_marks(&mut arabic_glyphs);
+        arabic_glyphs.iter().map(|g| g.unicodes[0]).collect()
+    }
+
+    #[test]
+    fn shadda_sorts_first() {
+        // beh, kasra, shadda -> beh, shadda, kasra
+        assert_eq!(reordered("\u{628}\u{650}\u{651}"), "\u{628}\u{651}\u{650}");
+        // beh, fatha, shadda, teh marbuta, fatha, shadda
+        assert_eq!(
+            reordered("\u{628}\u{64e}\u{651}\u{629}\u{64e}\u{651}"),
+            "\u{628}\u{651}\u{64e}\u{629}\u{651}\u{64e}"
+        );
+    }
+
+    #[test]
+    fn same_class_order_retained() {
+        assert_eq!(reordered("\u{629}\u{650}\u{650}"), "\u{629}\u{650}\u{650}");
+        // subscript alef and dot below are class 220 but not modifier combining marks
+        assert_eq!(reordered("\u{628}\u{656}\u{65c}"), "\u{628}\u{656}\u{65c}");
+    }
+
+    #[test]
+    fn modifier_combining_marks_move_forward() {
+        // alef, fatha, hamza above -> alef, hamza above, fatha
+        assert_eq!(reordered("\u{627}\u{64e}\u{654}"), "\u{627}\u{654}\u{64e}");
+        // alef, kasra, hamza below -> alef, hamza below, kasra
+        assert_eq!(reordered("\u{627}\u{650}\u{655}"), "\u{627}\u{655}\u{650}");
+        // beh, shadda, small high seen, madda -> small high seen moved ahead of shadda
+        assert_eq!(
+            reordered("\u{628}\u{651}\u{6dc}\u{653}"),
+            "\u{628}\u{6dc}\u{651}\u{653}"
+        );
+    }
+}
@@ -0,0 +1,353 @@
+//! Implementation of font shaping for Hangul
+//!
+//! Code herein follows the HarfBuzz Hangul shaper. Sequences of conjoining jamo are composed into
+//! precomposed syllables when the font has glyphs for them. Jamo that remain are shaped with the
+//! `ljmo`, `vjmo`, and `tjmo` features, which fonts with conjoining jamo use to select the forms
+//! of each jamo that combine into a syllable.
+
+use crate::error::{ParseError, ShapingError};
+use crate::gsub::{self, GlyphData, GlyphOrigin, GsubFeatureMask, RawGlyph};
+use crate::layout::{GDEFTable, LayoutCache, LayoutTable, GSUB};
+
+const S_BASE: u32 = 0xAC00;
+const L_BASE: u32 = 0x1100;
+const V_BASE: u32 = 0x1161;
+const T_BASE: u32 = 0x11A7;
+const L_COUNT: u32 = 19;
+const V_COUNT: u32 = 21;
+const T_COUNT: u32 = 28;
+const N_COUNT: u32 = V_COUNT * T_COUNT;
+const S_COUNT: u32 = L_COUNT * N_COUNT;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Jamo {
+    Leading,
+    Vowel,
+    Trailing,
+}
+
+#[derive(Clone)]
+struct HangulData {
+    mask: GsubFeatureMask,
+}
+
+impl GlyphData for HangulData {
+    fn merge(data1: HangulData, _data2: HangulData) -> HangulData {
+        data1
+    }
+}
+
+type HangulGlyph = RawGlyph<HangulData>;
+
+/// The features used to select the conjoining forms of jamo.
+fn jamo_features() -> GsubFeatureMask {
+    GsubFeatureMask::LJMO | GsubFeatureMask::VJMO | GsubFeatureMask::TJMO
+}
+
+/// The substitution features that may be applied when shaping Hangul.
+pub(crate) fn gsub_features() -> GsubFeatureMask {
+    GsubFeatureMask::default() | jamo_features()
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// Preprocessing
+/////////////////////////////////////////////////////////////////////////////
+
+/// Preprocess Hangul character sequences. This function should be called
+/// prior to mapping Hangul characters to their corresponding glyphs.
+///
+/// Sequences of a leading consonant, vowel, and optional trailing consonant are composed into a
+/// precomposed syllable, as is a precomposed syllable without a trailing consonant followed by a
+/// trailing consonant. A sequence is only composed if `has_glyph` returns `true` for the
+/// syllable, otherwise it is left to be shaped as conjoining jamo.
+pub fn preprocess_hangul(cs: &mut Vec<char>, mut has_glyph: impl FnMut(char) -> bool) {
+    let mut i = 0;
+    while i < cs.len() {
+        let (syllable, len) = match compose(cs, i) {
+            Some(composed) => composed,
+            None => {
+                i += 1;
+                continue;
+            }
+        };
+        if has_glyph(syllable) {
+            cs[i] = syllable;
+            cs.drain(i + 1..i + len);
+        }
+        i += 1;
+    }
+}
+
+/// Returns the syllable composed from the characters at `i` and the number of characters it
+/// replaces.
+fn compose(cs: &[char], i: usize) -> Option<(char, usize)> {
+    let first = u32::from(*cs.get(i)?);
+    let second = u32::from(*cs.get(i + 1)?);
+    let trailing = cs
+        .get(i + 2)
+        .map(|&ch| u32::from(ch))
+        .filter(|&ch| is_modern_trailing(ch));
+
+    if (L_BASE..L_BASE + L_COUNT).contains(&first) && (V_BASE..V_BASE + V_COUNT).contains(&second) {
+        let lv = S_BASE + ((first - L_BASE) * V_COUNT + (second - V_BASE)) * T_COUNT;
+        // If there's a trailing consonant compose all three or nothing, composing only the
+        // leading consonant and vowel would leave the trailing consonant with nothing to join
+        match trailing {
+            Some(t) => std::char::from_u32(lv + t - T_BASE).map(|ch| (ch, 3)),
+            None => std::char::from_u32(lv).map(|ch| (ch, 2)),
+        }
+    } else if (S_BASE..S_BASE + S_COUNT).contains(&first)
+        && is_lv_syllable(first)
+        && is_modern_trailing(second)
+    {
+        std::char::from_u32(first + second - T_BASE).map(|ch| (ch, 2))
+    } else {
+        None
+    }
+}
+
+/// Returns `true` if the precomposed syllable `ch` has no trailing consonant.
+fn is_lv_syllable(ch: u32) -> bool {
+    let t_index = (ch - S_BASE) % T_COUNT;
+    t_index == 0
+}
+
+fn is_modern_trailing(ch: u32) -> bool {
+    (T_BASE + 1..T_BASE + T_COUNT).contains(&ch)
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// Shaping
+/////////////////////////////////////////////////////////////////////////////
+
+/// Marks the jamo of each syllable with the feature that selects its conjoining form, then
+/// applies the features of `feature_mask` along with the jamo features.
+pub fn gsub_apply_hangul(
+    gsub_cache: &LayoutCache<GSUB>,
+    gsub_table: &LayoutTable<GSUB>,
+    gdef_table: Option<&GDEFTable>,
+    script_tag: u32,
+    lang_tag: Option<u32>,
+    feature_mask: GsubFeatureMask,
+    glyphs: &mut Vec<RawGlyph<()>>,
+) -> Result<(), ShapingError> {
+    let mut hangul_glyphs = glyphs.iter().map(to_hangul_glyph).collect::<Vec<_>>();
+    mark_jamo(&mut hangul_glyphs);
+    apply_features(
+        gsub_cache,
+        gsub_table,
+        gdef_table,
+        script_tag,
+        lang_tag,
+        feature_mask | jamo_features(),
+        &mut hangul_glyphs,
+    )?;
+    *glyphs = hangul_glyphs.into_iter().map(from_hangul_glyph).collect();
+
+    Ok(())
+}
+
+/// Mark the jamo that form syllables: a leading consonant followed by one or more vowels and
+/// optionally trailing consonants. Jamo outside of a syllable are not marked.
+fn mark_jamo(glyphs: &mut [HangulGlyph]) {
+    let jamo = glyphs.iter().map(glyph_jamo).collect::<Vec<_>>();
+    let mut i = 0;
+    while i < glyphs.len() {
+        if jamo[i] != Some(Jamo::Leading) {
+            i += 1;
+            continue;
+        }
+        let mut end = i + 1;
+        while jamo.get(end) == Some(&Some(Jamo::Leading)) {
+            end += 1;
+        }
+        let vowels_start = end;
+        while jamo.get(end) == Some(&Some(Jamo::Vowel)) {
+            end += 1;
+        }
+        if end == vowels_start {
+            // Leading consonants without a vowel don't form a syllable
+            i = end;
+            continue;
+        }
+        while jamo.get(end) == Some(&Some(Jamo::Trailing)) {
+            end += 1;
+        }
+        for (glyph, kind) in glyphs[i..end].iter_mut().zip(&jamo[i..end]) {
+            glyph.extra_data.mask = match kind {
+                Some(Jamo::Leading) => GsubFeatureMask::LJMO,
+                Some(Jamo::Vowel) => GsubFeatureMask::VJMO,
+                Some(Jamo::Trailing) => GsubFeatureMask::TJMO,
+                None => GsubFeatureMask::empty(),
+            };
+        }
+        i = end;
+    }
+}
+
+/// Apply the lookups of `features` in lookup order. The jamo features are only applied to the
+/// glyphs marked for them.
+fn apply_features(
+    gsub_cache: &LayoutCache<GSUB>,
+    gsub_table: &LayoutTable<GSUB>,
+    gdef_table: Option<&GDEFTable>,
+    script_tag: u32,
+    lang_tag: Option<u32>,
+    features: GsubFeatureMask,
+    glyphs: &mut Vec<HangulGlyph>,
+) -> Result<(), ParseError> {
+    let index = gsub::get_lookups_cache_index(gsub_cache, script_tag, lang_tag, features)?;
+    let lookups = gsub_cache.cached_lookups.read().unwrap()[index].clone();
+
+    for &(lookup_index, feature_tag) in &lookups {
+        let feature_mask = GsubFeatureMask::from_tag(feature_tag);
+        let is_jamo_feature = jamo_features().intersects(feature_mask);
+        gsub::gsub_apply_lookup(
+            gsub_cache,
+            gsub_table,
+            gdef_table,
+            lookup_index,
+            feature_tag,
+            None,
+            glyphs,
+            0,
+            glyphs.len(),
+            |g| !is_jamo_feature || g.extra_data.mask.contains(feature_mask),
+        )?;
+    }
+
+    Ok(())
+}
+
+fn glyph_jamo(glyph: &HangulGlyph) -> Option<Jamo> {
+    match glyph.glyph_origin {
+        GlyphOrigin::Char(ch) => jamo(ch),
+        GlyphOrigin::Direct => None,
+    }
+}
+
+fn jamo(ch: char) -> Option<Jamo> {
+    match ch {
+        '\u{1100}'..='\u{115F}' | '\u{A960}'..='\u{A97C}' => Some(Jamo::Leading),
+        '\u{1160}'..='\u{11A7}' | '\u{D7B0}'..='\u{D7C6}' => Some(Jamo::Vowel),
+        '\u{11A8}'..='\u{11FF}' | '\u{D7CB}'..='\u{D7FB}' => Some(Jamo::Trailing),
+        _ => None,
+    }
+}
+
+fn to_hangul_glyph(glyph: &RawGlyph<()>) -> HangulGlyph {
+    HangulGlyph {
+        unicodes: glyph.unicodes.clone(),
+        glyph_index: glyph.glyph_index,
+        liga_component_pos: glyph.liga_component_pos,
+        glyph_origin: glyph.glyph_origin,
+        small_caps: glyph.small_caps,
+        multi_subst_dup: glyph.multi_subst_dup,
+        multi_subst_component: glyph.multi_subst_component,
+        is_vert_alt: glyph.is_vert_alt,
+        fake_bold: glyph.fake_bold,
+        fake_italic: glyph.fake_italic,
+        stretch: glyph.stretch,
+        variation: glyph.variation,
+        extra_data: HangulData {
+            mask: GsubFeatureMask::empty(),
+        },
+    }
+}
+
+fn from_hangul_glyph(glyph: HangulGlyph) -> RawGlyph<()> {
+    RawGlyph {
+        unicodes: glyph.unicodes,
+        glyph_index: glyph.glyph_index,
+        liga_component_pos: glyph.liga_component_pos,
+        glyph_origin: glyph.glyph_origin,
+        small_caps: glyph.small_caps,
+        multi_subst_dup: glyph.multi_subst_dup,
+        multi_subst_component: glyph.multi_subst_component,
+        is_vert_alt: glyph.is_vert_alt,
+        fake_bold: glyph.fake_bold,
+        fake_italic: glyph.fake_italic,
+        stretch: glyph.stretch,
+        variation: glyph.variation,
+        extra_data: (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tinyvec::tiny_vec;
+
+    fn preprocess(s: &str, has_glyph: impl FnMut(char) -> bool) -> String {
+        let mut cs = s.chars().collect();
+        preprocess_hangul(&mut cs, has_glyph);
+        cs.into_iter().collect()
+    }
+
+    fn hangul_glyph(ch: char) -> HangulGlyph {
+        to_hangul_glyph(&RawGlyph {
+            unicodes: tiny_vec![[char; 1] => ch],
+            glyph_index: 0,
+            liga_component_pos: 0,
+            glyph_origin: GlyphOrigin::Char(ch),
+            small_caps: false,
+            multi_subst_dup: false,
+            multi_subst_component: None,
+            is_vert_alt: false,
+            fake_bold: false,
+            fake_italic: false,
+            stretch: None,
+            variation: None,
+            extra_data: (),
+        })
+    }
+
+    #[test]
+    fn compose_syllables() {
+        // ᄒ + ᅡ + ᆫ = 한, ᄀ + ᅳ + ᆯ = 글
+        assert_eq!(
+            preprocess("\u{1112}\u{1161}\u{11AB}\u{1100}\u{1173}\u{11AF}", |_| true),
+            "\u{D55C}\u{AE00}"
+        );
+        // ᄀ + ᅡ = 가
+        assert_eq!(preprocess("\u{1100}\u{1161}", |_| true), "\u{AC00}");
+        // 가 + ᆨ = 각
+        assert_eq!(preprocess("\u{AC00}\u{11A8}", |_| true), "\u{AC01}");
+        // Old jamo are not composed
+        assert_eq!(preprocess("\u{1113}\u{1161}", |_| true), "\u{1113}\u{1161}");
+    }
+
+    #[test]
+    fn compose_requires_glyph() {
+        let jamo = "\u{1112}\u{1161}\u{11AB}";
+        assert_eq!(preprocess(jamo, |_| false), jamo);
+        // The leading consonant and vowel aren't composed on their own when followed by a
+        // trailing consonant
+        assert_eq!(preprocess(jamo, |ch| ch == '\u{D558}'), jamo);
+        assert_eq!(
+            preprocess("\u{AC00}\u{11A8}", |_| false),
+            "\u{AC00}\u{11A8}"
+        );
+    }
+
+    #[test]
+    fn mark_syllable_jamo() {
+        let mut glyphs = "\u{1100}\u{1161}\u{11A8}\u{1161}x\u{1100}"
+            .chars()
+            .map(hangul_glyph)
+            .collect::<Vec<_>>();
+        mark_jamo(&mut glyphs);
+        let masks = glyphs.iter().map(|g| g.extra_data.mask).collect::<Vec<_>>();
+        assert_eq!(
+            masks,
+            vec![
+                GsubFeatureMask::LJMO,
+                GsubFeatureMask::VJMO,
+                GsubFeatureMask::TJMO,
+                GsubFeatureMask::empty(),
+                GsubFeatureMask::empty(),
+                GsubFeatureMask::empty(),
+            ]
+        );
+    }
+}
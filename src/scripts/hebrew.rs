@@ -0,0 +1,235 @@
+//! Implementation of font shaping for Hebrew
+//!
+//! Code herein follows the HarfBuzz Hebrew shaper. Fonts that don't position marks with `gpos`
+//! often have glyphs for the Alphabetic Presentation Forms instead, so letters combined with a
+//! dagesh, holam, shin or sin dot, and a few other points can be composed into those forms.
+
+use crate::error::{ParseError, ShapingError};
+use crate::gsub::{self, GsubFeatureMask, RawGlyph};
+use crate::layout::{GDEFTable, LayoutCache, LayoutTable, GSUB};
+use crate::tag;
+
+const DAGESH: char = '\u{05BC}';
+const HOLAM: char = '\u{05B9}';
+const SHIN_DOT: char = '\u{05C1}';
+const SIN_DOT: char = '\u{05C2}';
+const PATAH: char = '\u{05B7}';
+const QAMATS: char = '\u{05B8}';
+const HIRIQ: char = '\u{05B4}';
+const RAFE: char = '\u{05BF}';
+
+/// Presentation forms of the letters Alef to Tav with a dagesh, or zero where there is none.
+const DAGESH_FORMS: [u16; 27] = [
+    0xFB30, // Alef
+    0xFB31, // Bet
+    0xFB32, // Gimel
+    0xFB33, // Dalet
+    0xFB34, // He
+    0xFB35, // Vav
+    0xFB36, // Zayin
+    0x0000, // Het
+    0xFB38, // Tet
+    0xFB39, // Yod
+    0xFB3A, // Final Kaf
+    0xFB3B, // Kaf
+    0xFB3C, // Lamed
+    0x0000, // Final Mem
+    0xFB3E, // Mem
+    0x0000, // Final Nun
+    0xFB40, // Nun
+    0xFB41, // Samekh
+    0x0000, // Ayin
+    0xFB43, // Final Pe
+    0xFB44, // Pe
+    0x0000, // Final Tsadi
+    0xFB46, // Tsadi
+    0xFB47, // Qof
+    0xFB48, // Resh
+    0xFB49, // Shin
+    0xFB4A, // Tav
+];
+
+/// Features applied before the remaining features, so that compound characters are composed
+/// and decomposed before ligatures and contextual alternates see them.
+fn ccmp_features() -> GsubFeatureMask {
+    GsubFeatureMask::CCMP | GsubFeatureMask::LOCL
+}
+
+/// The substitution features that may be applied when shaping Hebrew.
+pub(crate) fn gsub_features() -> GsubFeatureMask {
+    GsubFeatureMask::default()
+}
+
+/// The positioning features applied when shaping Hebrew.
+pub(crate) const GPOS_FEATURES: &[u32] = &[tag::KERN, tag::MARK, tag::MKMK];
+
+/////////////////////////////////////////////////////////////////////////////
+// Preprocessing
+/////////////////////////////////////////////////////////////////////////////
+
+/// Preprocess Hebrew character sequences. This function should be called
+/// prior to mapping Hebrew characters to their corresponding glyphs.
+///
+/// Letters followed by points that have a precomposed presentation form are composed into that
+/// form if `has_glyph` returns `true` for it. This is a fallback for fonts that lack mark
+/// positioning; fonts that position marks with `gpos` should be shaped without it.
+pub fn preprocess_hebrew(cs: &mut Vec<char>, mut has_glyph: impl FnMut(char) -> bool) {
+    let mut i = 0;
+    while i < cs.len() {
+        let mut j = i + 1;
+        while j < cs.len() && is_point(cs[j]) {
+            match compose(cs[i], cs[j]) {
+                Some(composed) if has_glyph(composed) => {
+                    cs[i] = composed;
+                    cs.remove(j);
+                }
+                _ => j += 1,
+            }
+        }
+        i = j;
+    }
+}
+
+/// Returns the presentation form of `base` combined with `mark`, if there is one.
+fn compose(base: char, mark: char) -> Option<char> {
+    let composed = match (base, mark) {
+        ('\u{05D0}'..='\u{05EA}', DAGESH) => DAGESH_FORMS[(u32::from(base) - 0x05D0) as usize],
+        ('\u{05D5}', HOLAM) => 0xFB4B,
+        ('\u{05E9}', SHIN_DOT) => 0xFB2A,
+        ('\u{05E9}', SIN_DOT) => 0xFB2B,
+        ('\u{FB49}', SHIN_DOT) => 0xFB2C,
+        ('\u{FB49}', SIN_DOT) => 0xFB2D,
+        ('\u{FB2A}', DAGESH) => 0xFB2C,
+        ('\u{FB2B}', DAGESH) => 0xFB2D,
+        ('\u{05D0}', PATAH) => 0xFB2E,
+        ('\u{05D0}', QAMATS) => 0xFB2F,
+        ('\u{05F2}', PATAH) => 0xFB1F,
+        ('\u{05D9}', HIRIQ) => 0xFB1D,
+        ('\u{05D1}', RAFE) => 0xFB4C,
+        ('\u{05DB}', RAFE) => 0xFB4D,
+        ('\u{05E4}', RAFE) => 0xFB4E,
+        _ => 0,
+    };
+    match composed {
+        0 => None,
+        composed => std::char::from_u32(u32::from(composed)),
+    }
+}
+
+fn is_point(ch: char) -> bool {
+    match ch {
+        '\u{0591}'..='\u{05BD}'
+        | '\u{05BF}'
+        | '\u{05C1}'
+        | '\u{05C2}'
+        | '\u{05C4}'
+        | '\u{05C5}'
+        | '\u{05C7}' => true,
+        _ => false,
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// Shaping
+/////////////////////////////////////////////////////////////////////////////
+
+/// Applies `ccmp` and `locl`, then the remaining features of `feature_mask`.
+pub fn gsub_apply_hebrew(
+    gsub_cache: &LayoutCache<GSUB>,
+    gsub_table: &LayoutTable<GSUB>,
+    gdef_table: Option<&GDEFTable>,
+    script_tag: u32,
+    lang_tag: Option<u32>,
+    feature_mask: GsubFeatureMask,
+    glyphs: &mut Vec<RawGlyph<()>>,
+) -> Result<(), ShapingError> {
+    let shaping_data = HebrewShapingData {
+        gsub_cache,
+        gsub_table,
+        gdef_table,
+        script_tag,
+        lang_tag,
+    };
+    shaping_data.apply_features(ccmp_features(), glyphs)?;
+    shaping_data.apply_features(feature_mask - ccmp_features(), glyphs)?;
+
+    Ok(())
+}
+
+struct HebrewShapingData<'tables> {
+    gsub_cache: &'tables LayoutCache<GSUB>,
+    gsub_table: &'tables LayoutTable<GSUB>,
+    gdef_table: Option<&'tables GDEFTable>,
+    script_tag: u32,
+    lang_tag: Option<u32>,
+}
+
+impl HebrewShapingData<'_> {
+    /// Apply the lookups of `features` in lookup order.
+    fn apply_features(
+        &self,
+        features: GsubFeatureMask,
+        glyphs: &mut Vec<RawGlyph<()>>,
+    ) -> Result<(), ParseError> {
+        let index = gsub::get_lookups_cache_index(
+            self.gsub_cache,
+            self.script_tag,
+            self.lang_tag,
+            features,
+        )?;
+        let lookups = self.gsub_cache.cached_lookups.read().unwrap()[index].clone();
+
+        for &(lookup_index, feature_tag) in &lookups {
+            gsub::gsub_apply_lookup(
+                self.gsub_cache,
+                self.gsub_table,
+                self.gdef_table,
+                lookup_index,
+                feature_tag,
+                None,
+                glyphs,
+                0,
+                glyphs.len(),
+                |_| true,
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preprocess(s: &str, has_glyph: impl FnMut(char) -> bool) -> String {
+        let mut cs = s.chars().collect();
+        preprocess_hebrew(&mut cs, has_glyph);
+        cs.into_iter().collect()
+    }
+
+    #[test]
+    fn compose_presentation_forms() {
+        // Bet with dagesh
+        assert_eq!(preprocess("\u{05D1}\u{05BC}", |_| true), "\u{FB31}");
+        // Vav with holam
+        assert_eq!(preprocess("\u{05D5}\u{05B9}", |_| true), "\u{FB4B}");
+        // Shin with dagesh and shin dot, composed in two steps
+        assert_eq!(preprocess("\u{05E9}\u{05BC}\u{05C1}", |_| true), "\u{FB2C}");
+        // Shin with sin dot and a vowel that has no presentation form
+        assert_eq!(
+            preprocess("\u{05E9}\u{05C2}\u{05B8}\u{05D0}", |_| true),
+            "\u{FB2B}\u{05B8}\u{05D0}"
+        );
+        // Het has no form with dagesh
+        assert_eq!(preprocess("\u{05D7}\u{05BC}", |_| true), "\u{05D7}\u{05BC}");
+    }
+
+    #[test]
+    fn compose_requires_glyph() {
+        let text = "\u{05E9}\u{05BC}\u{05C1}";
+        assert_eq!(preprocess(text, |_| false), text);
+        // Shin with dagesh is available but not with the shin dot as well
+        assert_eq!(preprocess(text, |ch| ch == '\u{FB49}'), "\u{FB49}\u{05C1}");
+    }
+}
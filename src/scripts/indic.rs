@@ -1,7 +1,7 @@
 //! Implementation of font shaping for Indic scripts
 
 use crate::error::{IndicError, ParseError, ShapingError};
-use crate::gpos::{self, Info};
+use crate::gpos::{self, DeviceContext, Info};
 use crate::gsub::{self, GlyphData, GlyphOrigin, GsubFeatureMask, RawGlyph};
 use crate::layout::{GDEFTable, LangSys, LayoutCache, LayoutTable, GPOS, GSUB};
 use crate::tinyvec::tiny_vec;
@@ -1301,18 +1301,23 @@ pub fn gsub_apply_indic<'data>(
         return Err(IndicError::EmptyBuffer.into());
     }
 
-    // Currently, the script tag that gets passed from Mercury is the Indic1 tag.
-    // Map this to the Indic2 tag, as we want to check if a font supports it
+    // Currently, the script tag that gets passed from Mercury is the Indic1 tag. The Indic2 tag
+    // is looked for first, and determines the shaping model if the font supports it.
     let indic2_tag = indic2_tag(indic1_tag);
 
     // Priority: Indic2 > Indic1 > Default
-    let (script_tag, shaping_model, script_table) = match gsub_table.find_script(indic2_tag)? {
-        Some(script_table) => (indic2_tag, ShapingModel::Indic2, script_table),
+    let (script_tag, script_table) = match gsub_table.find_script_and_tag(indic1_tag)? {
+        Some(found) => found,
         None => match gsub_table.find_script_or_default(indic1_tag)? {
-            Some(script_table) => (indic1_tag, ShapingModel::Indic1, script_table),
+            Some(script_table) => (indic1_tag, script_table),
             None => return Ok(()),
         },
     };
+    let shaping_model = if script_tag == indic2_tag {
+        ShapingModel::Indic2
+    } else {
+        ShapingModel::Indic1
+    };
 
     let langsys = match script_table.find_langsys_or_default(lang_tag)? {
         Some(langsys) => langsys,
@@ -1434,9 +1439,11 @@ fn insert_dotted_circle(
         glyph_origin: GlyphOrigin::Char(DOTTED_CIRCLE),
         small_caps: false,
         multi_subst_dup: false,
+        multi_subst_component: None,
         is_vert_alt: false,
         fake_bold: false,
         fake_italic: false,
+        stretch: None,
         variation: None,
         extra_data: IndicData {
             pos: None,
@@ -2084,9 +2091,9 @@ fn apply_basic_features(
 ) -> Result<(), ParseError> {
     for feature in BasicFeature::ALL {
         let index = shaping_data.get_lookups_cache_index(feature.mask())?;
-        let lookups = &shaping_data.gsub_cache.cached_lookups.borrow()[index];
+        let lookups = shaping_data.gsub_cache.cached_lookups.read().unwrap()[index].clone();
 
-        for &(lookup_index, feature_tag) in lookups {
+        for &(lookup_index, feature_tag) in &lookups {
             shaping_data.apply_lookup(lookup_index, feature_tag, glyphs, |g| {
                 feature.is_global() || g.has_mask(feature.mask())
             })?;
@@ -2368,6 +2375,35 @@ fn final_reph_index(
 // Remaining substitution features
 /////////////////////////////////////////////////////////////////////////////
 
+fn presentation_features() -> GsubFeatureMask {
+    GsubFeatureMask::PRES
+        | GsubFeatureMask::ABVS
+        | GsubFeatureMask::BLWS
+        | GsubFeatureMask::PSTS
+        | GsubFeatureMask::HALN
+        | GsubFeatureMask::CALT
+}
+
+/// The substitution features that may be applied when shaping Indic scripts.
+pub(crate) fn gsub_features() -> GsubFeatureMask {
+    BasicFeature::ALL
+        .iter()
+        .fold(presentation_features(), |mask, feature| {
+            mask | feature.mask()
+        })
+        | GsubFeatureMask::INIT
+}
+
+/// The positioning features applied when shaping Indic scripts.
+pub(crate) const GPOS_FEATURES: &[u32] = &[
+    tag::KERN,
+    tag::MARK,
+    tag::MKMK,
+    tag::DIST,
+    tag::ABVM,
+    tag::BLWM,
+];
+
 /// Apply remaining substitution features after final reordering.
 ///
 /// If the syllable is the first in a word, applies the INIT feature.
@@ -2379,12 +2415,7 @@ fn apply_presentation_features(
     is_first_syllable: bool,
     glyphs: &mut Vec<RawGlyphIndic>,
 ) -> Result<(), ParseError> {
-    let mut features = GsubFeatureMask::PRES
-        | GsubFeatureMask::ABVS
-        | GsubFeatureMask::BLWS
-        | GsubFeatureMask::PSTS
-        | GsubFeatureMask::HALN
-        | GsubFeatureMask::CALT;
+    let mut features = presentation_features();
 
     if let Some(glyph) = glyphs.first_mut() {
         if is_first_syllable && glyph.has_pos(Pos::PrebaseMatra) {
@@ -2393,9 +2424,9 @@ fn apply_presentation_features(
         }
     }
     let index = shaping_data.get_lookups_cache_index(features)?;
-    let lookups = &shaping_data.gsub_cache.cached_lookups.borrow()[index];
+    let lookups = shaping_data.gsub_cache.cached_lookups.read().unwrap()[index].clone();
 
-    for &(lookup_index, feature_tag) in lookups {
+    for &(lookup_index, feature_tag) in &lookups {
         shaping_data.apply_lookup(lookup_index, feature_tag, glyphs, |g| {
             feature_tag != tag::INIT || g.has_mask(GsubFeatureMask::INIT)
         })?;
@@ -2408,45 +2439,23 @@ fn apply_presentation_features(
 // Positioning
 /////////////////////////////////////////////////////////////////////////////
 
-/// Apply positioning features.
+/// Apply the positioning features `feature_tags`, usually `GPOS_FEATURES`.
 pub fn gpos_apply_indic(
     gpos_cache: &LayoutCache<GPOS>,
-    gpos_table: &LayoutTable<GPOS>,
     gdef_table: Option<&GDEFTable>,
     indic1_tag: u32,
     opt_lang_tag: Option<u32>,
+    feature_tags: &[u32],
+    device_context: &DeviceContext<'_>,
     infos: &mut [Info],
 ) -> Result<(), ParseError> {
-    let indic2_tag = indic2_tag(indic1_tag);
-
-    let script_table = match gpos_table.find_script(indic2_tag)? {
-        Some(script_table) => script_table,
-        None => match gpos_table.find_script_or_default(indic1_tag)? {
-            Some(script_table) => script_table,
-            None => return Ok(()),
-        },
-    };
-
-    let langsys = match script_table.find_langsys_or_default(opt_lang_tag)? {
-        Some(langsys) => langsys,
-        None => return Ok(()),
-    };
-
-    const FEATURES: &[u32] = &[
-        tag::KERN,
-        tag::MARK,
-        tag::MKMK,
-        tag::DIST,
-        tag::ABVM,
-        tag::BLWM,
-    ];
-
-    gpos::apply_features(
-        &gpos_cache,
-        gpos_table,
+    gpos::apply_script_features(
+        gpos_cache,
         gdef_table,
-        &langsys,
-        FEATURES,
+        indic1_tag,
+        opt_lang_tag,
+        feature_tags,
+        device_context,
         infos,
     )
 }
@@ -2463,9 +2472,11 @@ fn to_raw_glyph_indic(glyph: &RawGlyph<()>) -> RawGlyphIndic {
         glyph_origin: glyph.glyph_origin,
         small_caps: glyph.small_caps,
         multi_subst_dup: glyph.multi_subst_dup,
+        multi_subst_component: glyph.multi_subst_component,
         is_vert_alt: glyph.is_vert_alt,
         fake_bold: glyph.fake_bold,
         fake_italic: glyph.fake_italic,
+        stretch: glyph.stretch,
         variation: glyph.variation,
         extra_data: IndicData {
             pos: None,
@@ -2482,9 +2493,11 @@ fn from_raw_glyph_indic(glyph: RawGlyphIndic) -> RawGlyph<()> {
         glyph_origin: glyph.glyph_origin,
         small_caps: glyph.small_caps,
         multi_subst_dup: glyph.multi_subst_dup,
+        multi_subst_component: glyph.multi_subst_component,
         is_vert_alt: glyph.is_vert_alt,
         fake_bold: glyph.fake_bold,
         fake_italic: glyph.fake_italic,
+        stretch: glyph.stretch,
         variation: glyph.variation,
         extra_data: (),
     }
@@ -0,0 +1,630 @@
+//! Implementation of font shaping for Khmer scripts
+//!
+//! Code herein follows the HarfBuzz Khmer shaper and the syllable model described at:
+//! <https://docs.microsoft.com/en-us/typography/script-development/khmer>
+
+use crate::error::{ParseError, ShapingError};
+use crate::gsub::{self, GlyphData, GlyphOrigin, GsubFeatureMask, RawGlyph};
+use crate::layout::{GDEFTable, LayoutCache, LayoutTable, GSUB};
+use crate::tinyvec::tiny_vec;
+use crate::DOTTED_CIRCLE;
+
+use log::debug;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Category {
+    /// Consonants and independent vowels.
+    Consonant,
+    /// Khmer Letter Ro, which takes a pre-base form when subscripted.
+    Ra,
+    /// The register shifters and Robat, which may follow the base consonant directly.
+    Robatic,
+    /// Above-base signs that may be preceded by joiners.
+    Xgroup,
+    /// Post-base signs that end a syllable.
+    Ygroup,
+    /// Khmer Sign Coeng, which subscripts the following consonant.
+    Coeng,
+    VowelPre,
+    VowelAbove,
+    VowelBelow,
+    VowelPost,
+    /// Zero width joiner and non-joiner.
+    Joiner,
+    Placeholder,
+    DottedCircle,
+    Other,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Syllable {
+    Consonant,
+    Broken,
+}
+
+#[derive(Clone)]
+struct KhmerData {
+    category: Category,
+    mask: GsubFeatureMask,
+}
+
+impl GlyphData for KhmerData {
+    fn merge(data1: KhmerData, _data2: KhmerData) -> KhmerData {
+        data1
+    }
+}
+
+type KhmerGlyph = RawGlyph<KhmerData>;
+
+impl KhmerGlyph {
+    fn is(&self, category: Category) -> bool {
+        self.extra_data.category == category
+    }
+
+    fn has_mask(&self, mask: GsubFeatureMask) -> bool {
+        self.extra_data.mask.contains(mask)
+    }
+
+    fn add_mask(&mut self, mask: GsubFeatureMask) {
+        self.extra_data.mask.insert(mask)
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// Preprocessing
+/////////////////////////////////////////////////////////////////////////////
+
+/// Preprocess Khmer character sequences. This function should be called
+/// prior to mapping Khmer characters to their corresponding glyphs.
+///
+/// Split vowels are decomposed so that their pre-base part can be reordered.
+pub fn preprocess_khmer(cs: &mut Vec<char>) {
+    let mut i = 0;
+    while i < cs.len() {
+        match cs[i] {
+            '\u{17BE}' | '\u{17BF}' | '\u{17C0}' | '\u{17C4}' | '\u{17C5}' => {
+                cs.insert(i, '\u{17C1}');
+                i += 2;
+            }
+            _ => i += 1,
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// Syllable matching
+/////////////////////////////////////////////////////////////////////////////
+
+fn is(cs: &[Category], i: usize, category: Category) -> bool {
+    cs.get(i) == Some(&category)
+}
+
+// c = Consonant | Ra
+fn match_c(cs: &[Category], i: usize) -> Option<usize> {
+    match cs.get(i) {
+        Some(Category::Consonant) | Some(Category::Ra) => Some(i + 1),
+        _ => None,
+    }
+}
+
+// cn = c (Joiner? Robatic)?
+fn match_cn(cs: &[Category], i: usize) -> Option<usize> {
+    let i = match_c(cs, i)?;
+    if is(cs, i, Category::Robatic) {
+        Some(i + 1)
+    } else if is(cs, i, Category::Joiner) && is(cs, i + 1, Category::Robatic) {
+        Some(i + 2)
+    } else {
+        Some(i)
+    }
+}
+
+fn match_optional(cs: &[Category], i: usize, category: Category) -> usize {
+    if is(cs, i, category) {
+        i + 1
+    } else {
+        i
+    }
+}
+
+// xgroup = (Joiner* Xgroup)*
+fn match_xgroup(cs: &[Category], mut i: usize) -> usize {
+    loop {
+        let mut j = i;
+        while is(cs, j, Category::Joiner) {
+            j += 1;
+        }
+        if !is(cs, j, Category::Xgroup) {
+            return i;
+        }
+        i = j + 1;
+    }
+}
+
+// ygroup = Ygroup*
+fn match_ygroup(cs: &[Category], mut i: usize) -> usize {
+    while is(cs, i, Category::Ygroup) {
+        i += 1;
+    }
+    i
+}
+
+// matra_group = VowelPre? xgroup VowelBelow? xgroup (Joiner? VowelAbove)? xgroup VowelPost?
+fn match_matra_group(cs: &[Category], i: usize) -> usize {
+    let i = match_optional(cs, i, Category::VowelPre);
+    let i = match_xgroup(cs, i);
+    let i = match_optional(cs, i, Category::VowelBelow);
+    let mut i = match_xgroup(cs, i);
+    if is(cs, i, Category::VowelAbove) {
+        i += 1;
+    } else if is(cs, i, Category::Joiner) && is(cs, i + 1, Category::VowelAbove) {
+        i += 2;
+    }
+    let i = match_xgroup(cs, i);
+    match_optional(cs, i, Category::VowelPost)
+}
+
+// syllable_tail = xgroup matra_group xgroup (Coeng c)? ygroup
+fn match_syllable_tail(cs: &[Category], i: usize) -> usize {
+    let i = match_xgroup(cs, i);
+    let i = match_matra_group(cs, i);
+    let mut i = match_xgroup(cs, i);
+    if is(cs, i, Category::Coeng) {
+        if let Some(j) = match_c(cs, i + 1) {
+            i = j;
+        }
+    }
+    match_ygroup(cs, i)
+}
+
+// broken_cluster = (Coeng cn)* (Coeng | syllable_tail)
+fn match_broken_cluster(cs: &[Category], mut i: usize) -> usize {
+    while is(cs, i, Category::Coeng) {
+        match match_cn(cs, i + 1) {
+            Some(j) => i = j,
+            None => break,
+        }
+    }
+    let tail = match_syllable_tail(cs, i);
+    if is(cs, i, Category::Coeng) {
+        tail.max(i + 1)
+    } else {
+        tail
+    }
+}
+
+// consonant_syllable = (cn | Placeholder | DottedCircle) broken_cluster
+fn match_consonant_syllable(cs: &[Category]) -> Option<usize> {
+    let i = match cs.first() {
+        Some(Category::Placeholder) | Some(Category::DottedCircle) => 1,
+        _ => match_cn(cs, 0)?,
+    };
+    Some(match_broken_cluster(cs, i))
+}
+
+fn match_syllable(cs: &[Category]) -> Option<(usize, Syllable)> {
+    if let Some(len) = match_consonant_syllable(cs) {
+        return Some((len, Syllable::Consonant));
+    }
+    match match_broken_cluster(cs, 0) {
+        0 => None,
+        len => Some((len, Syllable::Broken)),
+    }
+}
+
+/// Splits the input glyph buffer and collects it into a vector of Khmer syllables.
+fn to_khmer_syllables(glyphs: &[RawGlyph<()>]) -> Vec<(Vec<KhmerGlyph>, Option<Syllable>)> {
+    let glyphs = glyphs.iter().map(to_khmer_glyph).collect::<Vec<_>>();
+    let categories = glyphs
+        .iter()
+        .map(|g| g.extra_data.category)
+        .collect::<Vec<_>>();
+    let mut syllables: Vec<(Vec<KhmerGlyph>, Option<Syllable>)> = Vec::new();
+
+    let mut start = 0;
+    while start < glyphs.len() {
+        let cs = &categories[start..];
+        let len = match match_syllable(cs) {
+            Some((len, syllable_type)) => {
+                syllables.push((glyphs[start..start + len].to_vec(), Some(syllable_type)));
+                len
+            }
+            None => {
+                let invalid_glyph = glyphs[start].clone();
+                match syllables.last_mut() {
+                    // Collect consecutive invalid glyphs into a single syllable
+                    Some((invalid_syllable, None)) => invalid_syllable.push(invalid_glyph),
+                    _ => syllables.push((vec![invalid_glyph], None)),
+                }
+                1
+            }
+        };
+        start += len;
+    }
+
+    syllables
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// Shaping
+/////////////////////////////////////////////////////////////////////////////
+
+/// Features applied to the whole syllable before reordering.
+fn pre_reorder_features() -> GsubFeatureMask {
+    GsubFeatureMask::LOCL | GsubFeatureMask::CCMP
+}
+
+/// Features applied to the glyphs marked for them during reordering.
+fn basic_features() -> GsubFeatureMask {
+    GsubFeatureMask::PREF
+        | GsubFeatureMask::BLWF
+        | GsubFeatureMask::ABVF
+        | GsubFeatureMask::PSTF
+        | GsubFeatureMask::CFAR
+}
+
+/// Features applied to all glyphs after the basic features.
+fn presentation_features() -> GsubFeatureMask {
+    GsubFeatureMask::PRES
+        | GsubFeatureMask::ABVS
+        | GsubFeatureMask::BLWS
+        | GsubFeatureMask::PSTS
+        | GsubFeatureMask::CLIG
+        | GsubFeatureMask::CALT
+}
+
+/// The substitution features that may be applied when shaping Khmer.
+pub(crate) fn gsub_features() -> GsubFeatureMask {
+    pre_reorder_features() | basic_features() | presentation_features()
+}
+
+struct KhmerShapingData<'tables> {
+    gsub_cache: &'tables LayoutCache<GSUB>,
+    gsub_table: &'tables LayoutTable<GSUB>,
+    gdef_table: Option<&'tables GDEFTable>,
+    script_tag: u32,
+    lang_tag: Option<u32>,
+}
+
+impl KhmerShapingData<'_> {
+    /// Apply the lookups of `features` in lookup order, to the glyphs accepted by `pred`.
+    fn apply_features(
+        &self,
+        features: GsubFeatureMask,
+        glyphs: &mut Vec<KhmerGlyph>,
+        pred: impl Fn(&KhmerGlyph, GsubFeatureMask) -> bool,
+    ) -> Result<(), ParseError> {
+        let index = gsub::get_lookups_cache_index(
+            self.gsub_cache,
+            self.script_tag,
+            self.lang_tag,
+            features,
+        )?;
+        let lookups = self.gsub_cache.cached_lookups.read().unwrap()[index].clone();
+
+        for &(lookup_index, feature_tag) in &lookups {
+            let feature_mask = GsubFeatureMask::from_tag(feature_tag);
+            gsub::gsub_apply_lookup(
+                self.gsub_cache,
+                self.gsub_table,
+                self.gdef_table,
+                lookup_index,
+                feature_tag,
+                None,
+                glyphs,
+                0,
+                glyphs.len(),
+                |g| pred(g, feature_mask),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Does the following:
+///   * Splits syllables
+///   * Inserts dotted circles into broken syllables
+///   * Reorders Coeng Ro and pre-base vowels
+///   * Applies basic features to the glyphs marked for them
+///   * Applies presentation features
+pub fn gsub_apply_khmer(
+    dotted_circle_index: u16,
+    gsub_cache: &LayoutCache<GSUB>,
+    gsub_table: &LayoutTable<GSUB>,
+    gdef_table: Option<&GDEFTable>,
+    script_tag: u32,
+    lang_tag: Option<u32>,
+    glyphs: &mut Vec<RawGlyph<()>>,
+) -> Result<(), ShapingError> {
+    match gsub_table.find_script_or_default(script_tag)? {
+        Some(s) => {
+            if s.find_langsys_or_default(lang_tag)?.is_none() {
+                return Ok(());
+            }
+        }
+        None => return Ok(()),
+    }
+
+    let shaping_data = KhmerShapingData {
+        gsub_cache,
+        gsub_table,
+        gdef_table,
+        script_tag,
+        lang_tag,
+    };
+
+    let mut syllables = to_khmer_syllables(glyphs);
+    for (syllable, syllable_type) in syllables.iter_mut() {
+        if let Err(err) =
+            shape_syllable(dotted_circle_index, &shaping_data, syllable, *syllable_type)
+        {
+            debug!("gsub apply khmer: {}", err);
+        }
+    }
+
+    *glyphs = syllables
+        .into_iter()
+        .flat_map(|(s, _)| s.into_iter())
+        .map(from_khmer_glyph)
+        .collect();
+
+    Ok(())
+}
+
+fn shape_syllable(
+    dotted_circle_index: u16,
+    shaping_data: &KhmerShapingData<'_>,
+    syllable: &mut Vec<KhmerGlyph>,
+    syllable_type: Option<Syllable>,
+) -> Result<(), ParseError> {
+    if syllable_type == Some(Syllable::Broken) && dotted_circle_index != 0 {
+        syllable.insert(0, dotted_circle(dotted_circle_index));
+    }
+
+    shaping_data.apply_features(pre_reorder_features(), syllable, |_, _| true)?;
+    if syllable_type.is_some() {
+        reorder_syllable(syllable);
+    }
+    shaping_data.apply_features(basic_features(), syllable, |g, mask| g.has_mask(mask))?;
+    shaping_data.apply_features(presentation_features(), syllable, |_, _| true)
+}
+
+/// Marks glyphs for the basic features, moves Coeng Ro to the start of the syllable where it
+/// takes its pre-base form, and moves pre-base vowels to the start of the syllable.
+fn reorder_syllable(glyphs: &mut [KhmerGlyph]) {
+    for glyph in glyphs.iter_mut().skip(1) {
+        glyph.add_mask(GsubFeatureMask::BLWF | GsubFeatureMask::ABVF | GsubFeatureMask::PSTF);
+    }
+
+    let mut num_coengs = 0;
+    for i in 1..glyphs.len() {
+        if glyphs[i].is(Category::Coeng) && num_coengs <= 2 && i + 1 < glyphs.len() {
+            num_coengs += 1;
+            if glyphs[i + 1].is(Category::Ra) {
+                glyphs[i].add_mask(GsubFeatureMask::PREF);
+                glyphs[i + 1].add_mask(GsubFeatureMask::PREF);
+                glyphs[..i + 2].rotate_right(2);
+
+                // The glyphs following Coeng Ro may take a distinct form, which allows
+                // Coeng Ro followed by another subscript to be distinguished from the reverse
+                for glyph in glyphs[i + 2..].iter_mut() {
+                    glyph.add_mask(GsubFeatureMask::CFAR);
+                }
+
+                num_coengs = 2;
+            }
+        } else if glyphs[i].is(Category::VowelPre) {
+            glyphs[..=i].rotate_right(1);
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// Helper functions
+/////////////////////////////////////////////////////////////////////////////
+
+fn dotted_circle(dotted_circle_index: u16) -> KhmerGlyph {
+    KhmerGlyph {
+        unicodes: tiny_vec![[char; 1] => DOTTED_CIRCLE],
+        glyph_index: dotted_circle_index,
+        liga_component_pos: 0,
+        glyph_origin: GlyphOrigin::Char(DOTTED_CIRCLE),
+        small_caps: false,
+        multi_subst_dup: false,
+        multi_subst_component: None,
+        is_vert_alt: false,
+        fake_bold: false,
+        fake_italic: false,
+        stretch: None,
+        variation: None,
+        extra_data: KhmerData {
+            category: Category::DottedCircle,
+            mask: GsubFeatureMask::empty(),
+        },
+    }
+}
+
+fn to_khmer_glyph(glyph: &RawGlyph<()>) -> KhmerGlyph {
+    let category = match glyph.glyph_origin {
+        GlyphOrigin::Char(ch) => khmer_category(ch),
+        GlyphOrigin::Direct => Category::Other,
+    };
+    KhmerGlyph {
+        unicodes: glyph.unicodes.clone(),
+        glyph_index: glyph.glyph_index,
+        liga_component_pos: glyph.liga_component_pos,
+        glyph_origin: glyph.glyph_origin,
+        small_caps: glyph.small_caps,
+        multi_subst_dup: glyph.multi_subst_dup,
+        multi_subst_component: glyph.multi_subst_component,
+        is_vert_alt: glyph.is_vert_alt,
+        fake_bold: glyph.fake_bold,
+        fake_italic: glyph.fake_italic,
+        stretch: glyph.stretch,
+        variation: glyph.variation,
+        extra_data: KhmerData {
+            category,
+            mask: GsubFeatureMask::empty(),
+        },
+    }
+}
+
+fn from_khmer_glyph(glyph: KhmerGlyph) -> RawGlyph<()> {
+    RawGlyph {
+        unicodes: glyph.unicodes,
+        glyph_index: glyph.glyph_index,
+        liga_component_pos: glyph.liga_component_pos,
+        glyph_origin: glyph.glyph_origin,
+        small_caps: glyph.small_caps,
+        multi_subst_dup: glyph.multi_subst_dup,
+        multi_subst_component: glyph.multi_subst_component,
+        is_vert_alt: glyph.is_vert_alt,
+        fake_bold: glyph.fake_bold,
+        fake_italic: glyph.fake_italic,
+        stretch: glyph.stretch,
+        variation: glyph.variation,
+        extra_data: (),
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// Khmer character tables
+/////////////////////////////////////////////////////////////////////////////
+
+fn khmer_category(ch: char) -> Category {
+    match ch {
+        '\u{179A}' => Category::Ra,
+        '\u{1780}'..='\u{17B3}' => Category::Consonant,
+        '\u{17B6}' => Category::VowelPost,
+        '\u{17B7}'..='\u{17BA}' => Category::VowelAbove,
+        '\u{17BB}'..='\u{17BD}' => Category::VowelBelow,
+        // The remaining parts of split vowels, see `preprocess_khmer`
+        '\u{17BE}' => Category::VowelAbove,
+        '\u{17BF}' | '\u{17C0}' => Category::VowelPost,
+        '\u{17C1}'..='\u{17C3}' => Category::VowelPre,
+        '\u{17C4}' | '\u{17C5}' => Category::VowelPost,
+        '\u{17C6}' => Category::Xgroup,
+        '\u{17C7}' | '\u{17C8}' => Category::Ygroup,
+        '\u{17C9}' | '\u{17CA}' | '\u{17CC}' => Category::Robatic,
+        '\u{17CB}' | '\u{17CD}'..='\u{17D1}' | '\u{17D3}' | '\u{17DD}' => Category::Xgroup,
+        '\u{17D2}' => Category::Coeng,
+        '\u{17E0}'..='\u{17E9}' | '\u{00A0}' => Category::Placeholder,
+        '\u{200C}' | '\u{200D}' => Category::Joiner,
+        DOTTED_CIRCLE => Category::DottedCircle,
+        _ => Category::Other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw_glyph(ch: char) -> RawGlyph<()> {
+        RawGlyph {
+            unicodes: tiny_vec![[char; 1] => ch],
+            glyph_index: 0,
+            liga_component_pos: 0,
+            glyph_origin: GlyphOrigin::Char(ch),
+            small_caps: false,
+            multi_subst_dup: false,
+            multi_subst_component: None,
+            is_vert_alt: false,
+            fake_bold: false,
+            fake_italic: false,
+            stretch: None,
+            variation: None,
+            extra_data: (),
+        }
+    }
+
+    fn syllables(s: &str) -> Vec<(String, Option<Syllable>)> {
+        let mut cs = s.chars().collect();
+        preprocess_khmer(&mut cs);
+        let glyphs = cs.into_iter().map(raw_glyph).collect::<Vec<_>>();
+        to_khmer_syllables(&glyphs)
+            .into_iter()
+            .map(|(syllable, syllable_type)| (chars(&syllable), syllable_type))
+            .collect()
+    }
+
+    fn chars(glyphs: &[KhmerGlyph]) -> String {
+        glyphs.iter().map(|g| g.unicodes[0]).collect()
+    }
+
+    fn reorder(s: &str) -> String {
+        let mut cs = s.chars().collect();
+        preprocess_khmer(&mut cs);
+        let glyphs = cs.into_iter().map(raw_glyph).collect::<Vec<_>>();
+        let mut syllable = to_khmer_syllables(&glyphs).remove(0).0;
+        reorder_syllable(&mut syllable);
+        chars(&syllable)
+    }
+
+    #[test]
+    fn test_split_vowel() {
+        let mut cs = vec!['\u{1780}', '\u{17BE}'];
+        preprocess_khmer(&mut cs);
+        assert_eq!(cs, vec!['\u{1780}', '\u{17C1}', '\u{17BE}']);
+    }
+
+    #[test]
+    fn test_syllables() {
+        // KA, COENG, KHA, VOWEL SIGN AA; KA with TRIISAP and NIKAHIT
+        assert_eq!(
+            syllables("\u{1780}\u{17D2}\u{1781}\u{17B6}\u{1780}\u{17CA}\u{17C6}"),
+            vec![
+                (
+                    "\u{1780}\u{17D2}\u{1781}\u{17B6}".to_string(),
+                    Some(Syllable::Consonant)
+                ),
+                (
+                    "\u{1780}\u{17CA}\u{17C6}".to_string(),
+                    Some(Syllable::Consonant)
+                ),
+            ]
+        );
+
+        // A vowel sign without a base
+        assert_eq!(
+            syllables("a\u{17B6}"),
+            vec![
+                ("a".to_string(), None),
+                ("\u{17B6}".to_string(), Some(Syllable::Broken)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reorder_pre_base_vowel() {
+        // KA, VOWEL SIGN E
+        assert_eq!(reorder("\u{1780}\u{17C1}"), "\u{17C1}\u{1780}");
+        // KA, VOWEL SIGN OE, decomposed into E and OE
+        assert_eq!(reorder("\u{1780}\u{17BE}"), "\u{17C1}\u{1780}\u{17BE}");
+    }
+
+    #[test]
+    fn test_reorder_coeng_ro() {
+        // KA, COENG, RO, VOWEL SIGN E
+        let mut syllable = to_khmer_syllables(
+            &"\u{1780}\u{17D2}\u{179A}\u{17C1}"
+                .chars()
+                .map(raw_glyph)
+                .collect::<Vec<_>>(),
+        )
+        .remove(0)
+        .0;
+        reorder_syllable(&mut syllable);
+        assert_eq!(chars(&syllable), "\u{17C1}\u{17D2}\u{179A}\u{1780}");
+        assert!(syllable[1].has_mask(GsubFeatureMask::PREF));
+        assert!(syllable[2].has_mask(GsubFeatureMask::PREF));
+        assert!(!syllable[3].has_mask(GsubFeatureMask::PREF));
+        assert!(syllable[0].has_mask(GsubFeatureMask::CFAR));
+
+        // Only the first two subscripts are considered
+        assert_eq!(
+            reorder("\u{1780}\u{17D2}\u{1781}\u{17D2}\u{179A}"),
+            "\u{17D2}\u{179A}\u{1780}\u{17D2}\u{1781}"
+        );
+    }
+}
@@ -1,16 +1,27 @@
 pub mod arabic;
+pub mod hangul;
+pub mod hebrew;
 pub mod indic;
+pub mod khmer;
+pub mod myanmar;
 pub mod syriac;
+pub mod thai;
 
+use crate::gsub::GsubFeatureMask;
 use crate::tag;
 
+use std::collections::HashMap;
 use std::convert::From;
 
-#[derive(std::cmp::PartialEq)]
+#[derive(Copy, Clone, Debug, std::cmp::PartialEq, Eq)]
 pub enum ScriptType {
     Arabic,
     Default,
+    Hangul,
+    Hebrew,
     Indic,
+    Khmer,
+    Myanmar,
     Syriac,
 }
 
@@ -21,6 +32,8 @@ impl From<u32> for ScriptType {
             tag::LATN => ScriptType::Default,
             tag::CYRL => ScriptType::Default,
             tag::GREK => ScriptType::Default,
+            tag::HANG => ScriptType::Hangul,
+            tag::HEBR => ScriptType::Hebrew,
             tag::DEVA => ScriptType::Indic,
             tag::BENG => ScriptType::Indic,
             tag::GURU => ScriptType::Indic,
@@ -30,8 +43,188 @@ impl From<u32> for ScriptType {
             tag::TELU => ScriptType::Indic,
             tag::KNDA => ScriptType::Indic,
             tag::MLYM => ScriptType::Indic,
+            tag::KHMR => ScriptType::Khmer,
+            tag::MYMR => ScriptType::Myanmar,
+            tag::MYM2 => ScriptType::Myanmar,
             tag::SYRC => ScriptType::Syriac,
             _ => ScriptType::Default,
         }
     }
 }
+
+/// The features applied by default when shaping a script.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScriptFeatures {
+    /// Substitution features.
+    pub gsub: GsubFeatureMask,
+    /// Positioning feature tags, in the order they are applied.
+    pub gpos: Vec<u32>,
+}
+
+impl ScriptFeatures {
+    /// The features built into the shaper for `script_type`.
+    pub fn builtin(script_type: ScriptType) -> ScriptFeatures {
+        match script_type {
+            ScriptType::Arabic => ScriptFeatures {
+                gsub: arabic::gsub_features(),
+                gpos: vec![tag::CURS, tag::KERN, tag::MARK, tag::MKMK],
+            },
+            ScriptType::Default => ScriptFeatures {
+                gsub: GsubFeatureMask::default(),
+                gpos: vec![tag::DIST, tag::KERN, tag::MARK, tag::MKMK],
+            },
+            ScriptType::Hangul => ScriptFeatures {
+                gsub: hangul::gsub_features(),
+                gpos: vec![tag::DIST, tag::KERN, tag::MARK, tag::MKMK],
+            },
+            ScriptType::Hebrew => ScriptFeatures {
+                gsub: hebrew::gsub_features(),
+                gpos: hebrew::GPOS_FEATURES.to_vec(),
+            },
+            ScriptType::Indic => ScriptFeatures {
+                gsub: indic::gsub_features(),
+                gpos: indic::GPOS_FEATURES.to_vec(),
+            },
+            ScriptType::Khmer => ScriptFeatures {
+                gsub: khmer::gsub_features(),
+                gpos: vec![
+                    tag::KERN,
+                    tag::MARK,
+                    tag::MKMK,
+                    tag::DIST,
+                    tag::ABVM,
+                    tag::BLWM,
+                ],
+            },
+            ScriptType::Myanmar => ScriptFeatures {
+                gsub: myanmar::gsub_features(),
+                gpos: myanmar::GPOS_FEATURES.to_vec(),
+            },
+            ScriptType::Syriac => ScriptFeatures {
+                gsub: syriac::gsub_features(),
+                gpos: vec![tag::CURS, tag::KERN, tag::MARK, tag::MKMK],
+            },
+        }
+    }
+}
+
+/// Mapping from script tag to the features applied by default when shaping that script.
+///
+/// Scripts without an entry use the built-in features for their `ScriptType`. Entries can be
+/// added at runtime to align shaping with the conventions of a particular platform, and take
+/// effect when set on a font with `Font::set_default_features`, or passed to
+/// `gpos::apply_with_default_features`, `GposPlan::with_default_features` or
+/// `GsubPlan::with_default_features`.
+#[derive(Clone, Debug, Default)]
+pub struct DefaultFeatures {
+    overrides: HashMap<u32, ScriptFeatures>,
+}
+
+impl DefaultFeatures {
+    /// Create a new mapping containing only the built-in features.
+    pub fn new() -> Self {
+        DefaultFeatures::default()
+    }
+
+    /// Returns the default features for `script_tag`.
+    pub fn get(&self, script_tag: u32) -> ScriptFeatures {
+        match self.overrides.get(&script_tag) {
+            Some(features) => features.clone(),
+            None => ScriptFeatures::builtin(ScriptType::from(script_tag)),
+        }
+    }
+
+    /// Set the default features for `script_tag`, returning the previous override if any.
+    pub fn insert(&mut self, script_tag: u32, features: ScriptFeatures) -> Option<ScriptFeatures> {
+        self.overrides.insert(script_tag, features)
+    }
+
+    /// Remove the override for `script_tag`, reverting it to the built-in features.
+    pub fn remove(&mut self, script_tag: u32) -> Option<ScriptFeatures> {
+        self.overrides.remove(&script_tag)
+    }
+
+    /// Returns an iterator over the script tags that have been overridden and their features.
+    pub fn overrides(&self) -> impl Iterator<Item = (u32, &ScriptFeatures)> {
+        self.overrides
+            .iter()
+            .map(|(&script_tag, features)| (script_tag, features))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_features() {
+        let arabic = ScriptFeatures::builtin(ScriptType::Arabic);
+        assert!(arabic.gsub.contains(
+            GsubFeatureMask::CCMP
+                | GsubFeatureMask::ISOL
+                | GsubFeatureMask::INIT
+                | GsubFeatureMask::MSET
+        ));
+        assert!(!arabic.gsub.contains(GsubFeatureMask::DLIG));
+
+        let syriac = ScriptFeatures::builtin(ScriptType::Syriac);
+        assert!(syriac
+            .gsub
+            .contains(GsubFeatureMask::MED2 | GsubFeatureMask::FIN3));
+
+        let indic = ScriptFeatures::builtin(ScriptType::Indic);
+        assert!(indic
+            .gsub
+            .contains(GsubFeatureMask::RPHF | GsubFeatureMask::PRES));
+        assert!(indic.gpos.contains(&tag::ABVM));
+
+        let hangul = ScriptFeatures::builtin(ScriptType::from(tag::HANG));
+        assert!(hangul
+            .gsub
+            .contains(GsubFeatureMask::LJMO | GsubFeatureMask::VJMO | GsubFeatureMask::TJMO));
+
+        let hebrew = ScriptFeatures::builtin(ScriptType::from(tag::HEBR));
+        assert!(hebrew.gsub.contains(GsubFeatureMask::CCMP));
+        assert!(hebrew.gpos.contains(&tag::MARK));
+
+        let khmer = ScriptFeatures::builtin(ScriptType::from(tag::KHMR));
+        assert!(khmer
+            .gsub
+            .contains(GsubFeatureMask::PREF | GsubFeatureMask::CFAR));
+
+        let myanmar = ScriptFeatures::builtin(ScriptType::from(tag::MYM2));
+        assert!(myanmar
+            .gsub
+            .contains(GsubFeatureMask::RPHF | GsubFeatureMask::PSTF));
+
+        let default = ScriptFeatures::builtin(ScriptType::Default);
+        assert_eq!(default.gsub, GsubFeatureMask::default());
+    }
+
+    #[test]
+    fn override_features() {
+        let mut defaults = DefaultFeatures::new();
+        assert_eq!(
+            defaults.get(tag::LATN),
+            ScriptFeatures::builtin(ScriptType::Default)
+        );
+
+        let features = ScriptFeatures {
+            gsub: GsubFeatureMask::default() | GsubFeatureMask::SMCP,
+            gpos: vec![tag::KERN],
+        };
+        assert!(defaults.insert(tag::LATN, features.clone()).is_none());
+        assert_eq!(defaults.get(tag::LATN), features);
+        assert_eq!(
+            defaults.get(tag::CYRL),
+            ScriptFeatures::builtin(ScriptType::Default)
+        );
+        assert_eq!(defaults.overrides().count(), 1);
+
+        assert_eq!(defaults.remove(tag::LATN), Some(features));
+        assert_eq!(
+            defaults.get(tag::LATN),
+            ScriptFeatures::builtin(ScriptType::Default)
+        );
+    }
+}
@@ -0,0 +1,822 @@
+//! Implementation of font shaping for Myanmar scripts
+//!
+//! Code herein follows the HarfBuzz Myanmar shaper and the syllable model described at:
+//! <https://docs.microsoft.com/en-us/typography/script-development/myanmar>
+
+use crate::error::{ParseError, ShapingError};
+use crate::gpos::{self, DeviceContext, Info};
+use crate::gsub::{self, GlyphData, GlyphOrigin, GsubFeatureMask, RawGlyph};
+use crate::layout::{GDEFTable, LayoutCache, LayoutTable, GPOS, GSUB};
+use crate::tinyvec::tiny_vec;
+use crate::{tag, DOTTED_CIRCLE};
+
+use log::debug;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Category {
+    Consonant,
+    /// Consonants that form Kinzi when followed by Asat and Virama.
+    Ra,
+    IndependentVowel,
+    Digit,
+    Placeholder,
+    DottedCircle,
+    /// The invisible Virama, which stacks the following consonant.
+    Virama,
+    /// The visible Virama.
+    Asat,
+    /// Anusvara.
+    Anusvara,
+    DotBelow,
+    MedialYa,
+    MedialRa,
+    MedialWa,
+    MedialHa,
+    MedialMonLa,
+    VowelPre,
+    VowelAbove,
+    VowelBelow,
+    VowelPost,
+    /// Pwo Karen tone marks.
+    PwoTone,
+    /// Visarga and Shan tone marks.
+    SyllableModifier,
+    VariationSelector,
+    /// Zero width joiner and non-joiner.
+    Joiner,
+    Other,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Syllable {
+    Consonant,
+    Broken,
+}
+
+/// Positions of glyphs in a reordered syllable, in the order they are sorted into.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Pos {
+    PrebaseMatra,
+    PrebaseConsonant,
+    SyllableBase,
+    AfterMain,
+    BeforeSubjoined,
+    BelowBase,
+    AfterSubjoined,
+}
+
+#[derive(Clone)]
+struct MyanmarData {
+    category: Category,
+    pos: Option<Pos>,
+}
+
+impl GlyphData for MyanmarData {
+    fn merge(data1: MyanmarData, _data2: MyanmarData) -> MyanmarData {
+        data1
+    }
+}
+
+type MyanmarGlyph = RawGlyph<MyanmarData>;
+
+impl MyanmarGlyph {
+    fn is(&self, category: Category) -> bool {
+        self.extra_data.category == category
+    }
+
+    fn is_consonant(&self) -> bool {
+        match self.extra_data.category {
+            Category::Consonant
+            | Category::Ra
+            | Category::IndependentVowel
+            | Category::Placeholder
+            | Category::DottedCircle => true,
+            _ => false,
+        }
+    }
+
+    fn pos(&self) -> Option<Pos> {
+        self.extra_data.pos
+    }
+
+    fn set_pos(&mut self, pos: Pos) {
+        self.extra_data.pos = Some(pos)
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// Syllable matching
+/////////////////////////////////////////////////////////////////////////////
+
+fn is(cs: &[Category], i: usize, category: Category) -> bool {
+    cs.get(i) == Some(&category)
+}
+
+fn match_optional(cs: &[Category], i: usize, category: Category) -> usize {
+    if is(cs, i, category) {
+        i + 1
+    } else {
+        i
+    }
+}
+
+fn match_repeat(cs: &[Category], mut i: usize, category: Category) -> usize {
+    while is(cs, i, category) {
+        i += 1;
+    }
+    i
+}
+
+// c = Consonant | Ra
+fn match_c(cs: &[Category], i: usize) -> Option<usize> {
+    match cs.get(i) {
+        Some(Category::Consonant) | Some(Category::Ra) => Some(i + 1),
+        _ => None,
+    }
+}
+
+// kinzi = Ra Asat Virama
+fn match_kinzi(cs: &[Category], i: usize) -> Option<usize> {
+    if is(cs, i, Category::Ra) && is(cs, i + 1, Category::Asat) && is(cs, i + 2, Category::Virama) {
+        Some(i + 3)
+    } else {
+        None
+    }
+}
+
+// (DotBelow Asat?)?
+fn match_dot_below(cs: &[Category], i: usize) -> usize {
+    if is(cs, i, Category::DotBelow) {
+        match_optional(cs, i + 1, Category::Asat)
+    } else {
+        i
+    }
+}
+
+// medial_group = MedialYa? Asat? MedialRa?
+//                ((MedialWa MedialHa? MedialMonLa? | MedialHa MedialMonLa? | MedialMonLa) Asat?)?
+fn match_medial_group(cs: &[Category], i: usize) -> usize {
+    let i = match_optional(cs, i, Category::MedialYa);
+    let i = match_optional(cs, i, Category::Asat);
+    let i = match_optional(cs, i, Category::MedialRa);
+    let j = if is(cs, i, Category::MedialWa) {
+        let j = match_optional(cs, i + 1, Category::MedialHa);
+        match_optional(cs, j, Category::MedialMonLa)
+    } else if is(cs, i, Category::MedialHa) {
+        match_optional(cs, i + 1, Category::MedialMonLa)
+    } else {
+        match_optional(cs, i, Category::MedialMonLa)
+    };
+    if j > i {
+        match_optional(cs, j, Category::Asat)
+    } else {
+        i
+    }
+}
+
+// main_vowel_group = (VowelPre VariationSelector?)* VowelAbove* VowelBelow* Anusvara*
+//                    (DotBelow Asat?)?
+fn match_main_vowel_group(cs: &[Category], mut i: usize) -> usize {
+    while is(cs, i, Category::VowelPre) {
+        i = match_optional(cs, i + 1, Category::VariationSelector);
+    }
+    let i = match_repeat(cs, i, Category::VowelAbove);
+    let i = match_repeat(cs, i, Category::VowelBelow);
+    let i = match_repeat(cs, i, Category::Anusvara);
+    match_dot_below(cs, i)
+}
+
+// post_vowel_group = VowelPost MedialHa? MedialMonLa? Asat* VowelAbove* Anusvara*
+//                    (DotBelow Asat?)?
+fn match_post_vowel_group(cs: &[Category], i: usize) -> Option<usize> {
+    if !is(cs, i, Category::VowelPost) {
+        return None;
+    }
+    let i = match_optional(cs, i + 1, Category::MedialHa);
+    let i = match_optional(cs, i, Category::MedialMonLa);
+    let i = match_repeat(cs, i, Category::Asat);
+    let i = match_repeat(cs, i, Category::VowelAbove);
+    let i = match_repeat(cs, i, Category::Anusvara);
+    Some(match_dot_below(cs, i))
+}
+
+// pwo_tone_group = PwoTone Anusvara* DotBelow? Asat?
+fn match_pwo_tone_group(cs: &[Category], i: usize) -> Option<usize> {
+    if !is(cs, i, Category::PwoTone) {
+        return None;
+    }
+    let i = match_repeat(cs, i + 1, Category::Anusvara);
+    let i = match_optional(cs, i, Category::DotBelow);
+    Some(match_optional(cs, i, Category::Asat))
+}
+
+// complex_syllable_tail = Asat* medial_group main_vowel_group post_vowel_group*
+//                         pwo_tone_group* SyllableModifier* Joiner?
+fn match_complex_syllable_tail(cs: &[Category], i: usize) -> usize {
+    let i = match_repeat(cs, i, Category::Asat);
+    let i = match_medial_group(cs, i);
+    let mut i = match_main_vowel_group(cs, i);
+    while let Some(j) = match_post_vowel_group(cs, i) {
+        i = j;
+    }
+    while let Some(j) = match_pwo_tone_group(cs, i) {
+        i = j;
+    }
+    let i = match_repeat(cs, i, Category::SyllableModifier);
+    match_optional(cs, i, Category::Joiner)
+}
+
+// syllable_tail = (Virama (c | IndependentVowel) VariationSelector?)*
+//                 (Virama | complex_syllable_tail)
+fn match_syllable_tail(cs: &[Category], mut i: usize) -> usize {
+    while is(cs, i, Category::Virama) {
+        let j = match match_c(cs, i + 1) {
+            Some(j) => j,
+            None if is(cs, i + 1, Category::IndependentVowel) => i + 2,
+            None => break,
+        };
+        i = match_optional(cs, j, Category::VariationSelector);
+    }
+    let tail = match_complex_syllable_tail(cs, i);
+    if is(cs, i, Category::Virama) {
+        tail.max(i + 1)
+    } else {
+        tail
+    }
+}
+
+// consonant_syllable = kinzi? (c | IndependentVowel | Digit | Placeholder | DottedCircle)
+//                      VariationSelector? syllable_tail
+fn match_consonant_syllable(cs: &[Category]) -> Option<usize> {
+    match_kinzi(cs, 0)
+        .and_then(|i| match_base(cs, i))
+        .or_else(|| match_base(cs, 0))
+}
+
+fn match_base(cs: &[Category], i: usize) -> Option<usize> {
+    let i = match cs.get(i) {
+        Some(Category::Consonant)
+        | Some(Category::Ra)
+        | Some(Category::IndependentVowel)
+        | Some(Category::Digit)
+        | Some(Category::Placeholder)
+        | Some(Category::DottedCircle) => i + 1,
+        _ => return None,
+    };
+    let i = match_optional(cs, i, Category::VariationSelector);
+    Some(match_syllable_tail(cs, i))
+}
+
+// broken_cluster = kinzi? VariationSelector? syllable_tail
+fn match_broken_cluster(cs: &[Category]) -> usize {
+    let i = match_kinzi(cs, 0).unwrap_or(0);
+    let i = match_optional(cs, i, Category::VariationSelector);
+    match_syllable_tail(cs, i)
+}
+
+fn match_syllable(cs: &[Category]) -> Option<(usize, Syllable)> {
+    let consonant_len = match_consonant_syllable(cs).unwrap_or(0);
+    let broken_len = match_broken_cluster(cs);
+    if consonant_len == 0 && broken_len == 0 {
+        None
+    } else if consonant_len >= broken_len {
+        Some((consonant_len, Syllable::Consonant))
+    } else {
+        Some((broken_len, Syllable::Broken))
+    }
+}
+
+/// Splits the input glyph buffer and collects it into a vector of Myanmar syllables.
+fn to_myanmar_syllables(glyphs: &[RawGlyph<()>]) -> Vec<(Vec<MyanmarGlyph>, Option<Syllable>)> {
+    let glyphs = glyphs.iter().map(to_myanmar_glyph).collect::<Vec<_>>();
+    let categories = glyphs
+        .iter()
+        .map(|g| g.extra_data.category)
+        .collect::<Vec<_>>();
+    let mut syllables: Vec<(Vec<MyanmarGlyph>, Option<Syllable>)> = Vec::new();
+
+    let mut start = 0;
+    while start < glyphs.len() {
+        let cs = &categories[start..];
+        let len = match match_syllable(cs) {
+            Some((len, syllable_type)) => {
+                syllables.push((glyphs[start..start + len].to_vec(), Some(syllable_type)));
+                len
+            }
+            None => {
+                let invalid_glyph = glyphs[start].clone();
+                match syllables.last_mut() {
+                    // Collect consecutive invalid glyphs into a single syllable
+                    Some((invalid_syllable, None)) => invalid_syllable.push(invalid_glyph),
+                    _ => syllables.push((vec![invalid_glyph], None)),
+                }
+                1
+            }
+        };
+        start += len;
+    }
+
+    syllables
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// Shaping
+/////////////////////////////////////////////////////////////////////////////
+
+/// Features applied to the whole syllable before reordering.
+fn pre_reorder_features() -> GsubFeatureMask {
+    GsubFeatureMask::LOCL | GsubFeatureMask::CCMP
+}
+
+/// Features applied one at a time after reordering, in their required order.
+const BASIC_FEATURES: &[GsubFeatureMask] = &[
+    GsubFeatureMask::RPHF,
+    GsubFeatureMask::PREF,
+    GsubFeatureMask::BLWF,
+    GsubFeatureMask::PSTF,
+];
+
+/// Features applied to all glyphs after the basic features.
+fn presentation_features() -> GsubFeatureMask {
+    GsubFeatureMask::PRES
+        | GsubFeatureMask::ABVS
+        | GsubFeatureMask::BLWS
+        | GsubFeatureMask::PSTS
+        | GsubFeatureMask::CLIG
+        | GsubFeatureMask::CALT
+}
+
+/// The substitution features that may be applied when shaping Myanmar.
+pub(crate) fn gsub_features() -> GsubFeatureMask {
+    BASIC_FEATURES.iter().fold(
+        pre_reorder_features() | presentation_features(),
+        |mask, &feature_mask| mask | feature_mask,
+    )
+}
+
+/// The positioning features applied when shaping Myanmar.
+pub(crate) const GPOS_FEATURES: &[u32] = &[
+    tag::KERN,
+    tag::MARK,
+    tag::MKMK,
+    tag::DIST,
+    tag::ABVM,
+    tag::BLWM,
+];
+
+struct MyanmarShapingData<'tables> {
+    gsub_cache: &'tables LayoutCache<GSUB>,
+    gsub_table: &'tables LayoutTable<GSUB>,
+    gdef_table: Option<&'tables GDEFTable>,
+    script_tag: u32,
+    lang_tag: Option<u32>,
+}
+
+impl MyanmarShapingData<'_> {
+    fn apply_features(
+        &self,
+        features: GsubFeatureMask,
+        glyphs: &mut Vec<MyanmarGlyph>,
+    ) -> Result<(), ParseError> {
+        let index = gsub::get_lookups_cache_index(
+            self.gsub_cache,
+            self.script_tag,
+            self.lang_tag,
+            features,
+        )?;
+        let lookups = self.gsub_cache.cached_lookups.read().unwrap()[index].clone();
+
+        for &(lookup_index, feature_tag) in &lookups {
+            gsub::gsub_apply_lookup(
+                self.gsub_cache,
+                self.gsub_table,
+                self.gdef_table,
+                lookup_index,
+                feature_tag,
+                None,
+                glyphs,
+                0,
+                glyphs.len(),
+                |_| true,
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the script tag to shape with, preferring the `mym2` tag used by fonts built for the
+/// Myanmar shaping model.
+fn shaping_script_tag<T>(table: &LayoutTable<T>) -> Result<Option<u32>, ParseError> {
+    if let Some((script_tag, _)) = table.find_script_and_tag(tag::MYMR)? {
+        Ok(Some(script_tag))
+    } else if table.find_script_or_default(tag::MYMR)?.is_some() {
+        Ok(Some(tag::MYMR))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Does the following:
+///   * Splits syllables
+///   * Inserts dotted circles into broken syllables
+///   * Reorders Kinzi, medial Ra, and pre-base vowels
+///   * Applies basic features
+///   * Applies presentation features
+pub fn gsub_apply_myanmar(
+    dotted_circle_index: u16,
+    gsub_cache: &LayoutCache<GSUB>,
+    gsub_table: &LayoutTable<GSUB>,
+    gdef_table: Option<&GDEFTable>,
+    lang_tag: Option<u32>,
+    glyphs: &mut Vec<RawGlyph<()>>,
+) -> Result<(), ShapingError> {
+    let script_tag = match shaping_script_tag(gsub_table)? {
+        Some(script_tag) => script_tag,
+        None => return Ok(()),
+    };
+    match gsub_table.find_script_or_default(script_tag)? {
+        Some(s) => {
+            if s.find_langsys_or_default(lang_tag)?.is_none() {
+                return Ok(());
+            }
+        }
+        None => return Ok(()),
+    }
+
+    let shaping_data = MyanmarShapingData {
+        gsub_cache,
+        gsub_table,
+        gdef_table,
+        script_tag,
+        lang_tag,
+    };
+
+    let mut syllables = to_myanmar_syllables(glyphs);
+    for (syllable, syllable_type) in syllables.iter_mut() {
+        if let Err(err) =
+            shape_syllable(dotted_circle_index, &shaping_data, syllable, *syllable_type)
+        {
+            debug!("gsub apply myanmar: {}", err);
+        }
+    }
+
+    *glyphs = syllables
+        .into_iter()
+        .flat_map(|(s, _)| s.into_iter())
+        .map(from_myanmar_glyph)
+        .collect();
+
+    Ok(())
+}
+
+fn shape_syllable(
+    dotted_circle_index: u16,
+    shaping_data: &MyanmarShapingData<'_>,
+    syllable: &mut Vec<MyanmarGlyph>,
+    syllable_type: Option<Syllable>,
+) -> Result<(), ParseError> {
+    if syllable_type == Some(Syllable::Broken) && dotted_circle_index != 0 {
+        syllable.insert(0, dotted_circle(dotted_circle_index));
+    }
+
+    shaping_data.apply_features(pre_reorder_features(), syllable)?;
+    if syllable_type.is_some() {
+        reorder_syllable(syllable);
+    }
+    for &feature_mask in BASIC_FEATURES {
+        shaping_data.apply_features(feature_mask, syllable)?;
+    }
+    shaping_data.apply_features(presentation_features(), syllable)
+}
+
+/// Assigns each glyph a position relative to the base consonant and sorts the syllable by
+/// position.
+///
+/// Kinzi is moved after the base consonant, and medial Ra and pre-base vowels before it.
+fn reorder_syllable(glyphs: &mut [MyanmarGlyph]) {
+    let has_kinzi = glyphs.len() >= 3
+        && glyphs[0].is(Category::Ra)
+        && glyphs[1].is(Category::Asat)
+        && glyphs[2].is(Category::Virama);
+    let limit = if has_kinzi { 3 } else { 0 };
+    let base = glyphs[limit..]
+        .iter()
+        .position(MyanmarGlyph::is_consonant)
+        .map_or(limit, |i| i + limit);
+
+    let mut i = 0;
+    while i < limit {
+        glyphs[i].set_pos(Pos::AfterMain);
+        i += 1;
+    }
+    while i < base {
+        glyphs[i].set_pos(Pos::PrebaseConsonant);
+        i += 1;
+    }
+    if i < glyphs.len() {
+        glyphs[i].set_pos(Pos::SyllableBase);
+        i += 1;
+    }
+
+    let mut pos = Pos::AfterMain;
+    for i in i..glyphs.len() {
+        let category = glyphs[i].extra_data.category;
+        let glyph_pos = match category {
+            Category::MedialRa => Pos::PrebaseConsonant,
+            Category::VowelPre => Pos::PrebaseMatra,
+            Category::VariationSelector => glyphs[i - 1].pos().unwrap_or(pos),
+            Category::VowelBelow if pos == Pos::AfterMain => {
+                pos = Pos::BelowBase;
+                pos
+            }
+            Category::Anusvara if pos == Pos::BelowBase => Pos::BeforeSubjoined,
+            Category::VowelBelow if pos == Pos::BelowBase => pos,
+            _ if pos == Pos::BelowBase => {
+                pos = Pos::AfterSubjoined;
+                pos
+            }
+            _ => pos,
+        };
+        glyphs[i].set_pos(glyph_pos);
+    }
+
+    // A stable sort keeps glyphs with the same position in their original order
+    glyphs.sort_by_key(|g| g.pos());
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// Positioning
+/////////////////////////////////////////////////////////////////////////////
+
+/// Apply the positioning features `feature_tags`, usually `GPOS_FEATURES`.
+pub fn gpos_apply_myanmar(
+    gpos_cache: &LayoutCache<GPOS>,
+    gpos_table: &LayoutTable<GPOS>,
+    gdef_table: Option<&GDEFTable>,
+    opt_lang_tag: Option<u32>,
+    feature_tags: &[u32],
+    device_context: &DeviceContext<'_>,
+    infos: &mut [Info],
+) -> Result<(), ParseError> {
+    let script_tag = match shaping_script_tag(gpos_table)? {
+        Some(script_tag) => script_tag,
+        None => return Ok(()),
+    };
+    gpos::apply_script_features(
+        gpos_cache,
+        gdef_table,
+        script_tag,
+        opt_lang_tag,
+        feature_tags,
+        device_context,
+        infos,
+    )
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// Helper functions
+/////////////////////////////////////////////////////////////////////////////
+
+fn dotted_circle(dotted_circle_index: u16) -> MyanmarGlyph {
+    MyanmarGlyph {
+        unicodes: tiny_vec![[char; 1] => DOTTED_CIRCLE],
+        glyph_index: dotted_circle_index,
+        liga_component_pos: 0,
+        glyph_origin: GlyphOrigin::Char(DOTTED_CIRCLE),
+        small_caps: false,
+        multi_subst_dup: false,
+        multi_subst_component: None,
+        is_vert_alt: false,
+        fake_bold: false,
+        fake_italic: false,
+        stretch: None,
+        variation: None,
+        extra_data: MyanmarData {
+            category: Category::DottedCircle,
+            pos: None,
+        },
+    }
+}
+
+fn to_myanmar_glyph(glyph: &RawGlyph<()>) -> MyanmarGlyph {
+    let category = match glyph.glyph_origin {
+        GlyphOrigin::Char(ch) => myanmar_category(ch),
+        GlyphOrigin::Direct => Category::Other,
+    };
+    MyanmarGlyph {
+        unicodes: glyph.unicodes.clone(),
+        glyph_index: glyph.glyph_index,
+        liga_component_pos: glyph.liga_component_pos,
+        glyph_origin: glyph.glyph_origin,
+        small_caps: glyph.small_caps,
+        multi_subst_dup: glyph.multi_subst_dup,
+        multi_subst_component: glyph.multi_subst_component,
+        is_vert_alt: glyph.is_vert_alt,
+        fake_bold: glyph.fake_bold,
+        fake_italic: glyph.fake_italic,
+        stretch: glyph.stretch,
+        variation: glyph.variation,
+        extra_data: MyanmarData {
+            category,
+            pos: None,
+        },
+    }
+}
+
+fn from_myanmar_glyph(glyph: MyanmarGlyph) -> RawGlyph<()> {
+    RawGlyph {
+        unicodes: glyph.unicodes,
+        glyph_index: glyph.glyph_index,
+        liga_component_pos: glyph.liga_component_pos,
+        glyph_origin: glyph.glyph_origin,
+        small_caps: glyph.small_caps,
+        multi_subst_dup: glyph.multi_subst_dup,
+        multi_subst_component: glyph.multi_subst_component,
+        is_vert_alt: glyph.is_vert_alt,
+        fake_bold: glyph.fake_bold,
+        fake_italic: glyph.fake_italic,
+        stretch: glyph.stretch,
+        variation: glyph.variation,
+        extra_data: (),
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// Myanmar character tables
+/////////////////////////////////////////////////////////////////////////////
+
+#[rustfmt::skip]
+fn myanmar_category(ch: char) -> Category {
+    use Category::*;
+
+    match ch {
+        '\u{1004}' | '\u{101B}' | '\u{105A}' => Ra,
+        '\u{1000}'..='\u{1020}' => Consonant,
+        '\u{1021}'..='\u{102A}' => IndependentVowel,
+        '\u{102B}' | '\u{102C}' => VowelPost,
+        '\u{102D}' | '\u{102E}' => VowelAbove,
+        '\u{102F}' | '\u{1030}' => VowelBelow,
+        '\u{1031}' => VowelPre,
+        '\u{1032}'..='\u{1035}' => VowelAbove,
+        '\u{1036}' => Anusvara,
+        '\u{1037}' => DotBelow,
+        '\u{1038}' => SyllableModifier,
+        '\u{1039}' => Virama,
+        '\u{103A}' => Asat,
+        '\u{103B}' => MedialYa,
+        '\u{103C}' => MedialRa,
+        '\u{103D}' => MedialWa,
+        '\u{103E}' => MedialHa,
+        '\u{103F}' => Consonant,
+        '\u{1040}'..='\u{1049}' => Digit,
+        '\u{104E}' => Consonant,
+        '\u{1050}' | '\u{1051}' => Consonant,
+        '\u{1052}'..='\u{1055}' => IndependentVowel,
+        '\u{1056}' | '\u{1057}' => VowelPost,
+        '\u{1058}' | '\u{1059}' => VowelBelow,
+        '\u{105B}'..='\u{105D}' => Consonant,
+        '\u{105E}' | '\u{105F}' => MedialYa,
+        '\u{1060}' => MedialMonLa,
+        '\u{1061}' => Consonant,
+        '\u{1062}' => VowelPost,
+        '\u{1063}' | '\u{1064}' => PwoTone,
+        '\u{1065}' | '\u{1066}' => Consonant,
+        '\u{1067}' | '\u{1068}' => VowelPost,
+        '\u{1069}'..='\u{106D}' => PwoTone,
+        '\u{106E}'..='\u{1070}' => Consonant,
+        '\u{1071}'..='\u{1074}' => VowelAbove,
+        '\u{1075}'..='\u{1081}' => Consonant,
+        '\u{1082}' => MedialWa,
+        '\u{1083}' => VowelPost,
+        '\u{1084}' => VowelPre,
+        '\u{1085}' | '\u{1086}' => VowelAbove,
+        '\u{1087}'..='\u{108D}' => SyllableModifier,
+        '\u{108E}' => Consonant,
+        '\u{108F}' => SyllableModifier,
+        '\u{1090}'..='\u{1099}' => Digit,
+        '\u{109A}' | '\u{109B}' => SyllableModifier,
+        '\u{109C}' => VowelPost,
+        '\u{109D}' => VowelAbove,
+        // Myanmar Extended-B
+        '\u{A9E0}'..='\u{A9E4}' => Consonant,
+        '\u{A9E5}' => VowelAbove,
+        '\u{A9E7}'..='\u{A9EF}' => Consonant,
+        '\u{A9F0}'..='\u{A9F9}' => Digit,
+        '\u{A9FA}'..='\u{A9FE}' => Consonant,
+        // Myanmar Extended-A
+        '\u{AA60}'..='\u{AA6F}' | '\u{AA71}'..='\u{AA76}' | '\u{AA7A}' => Consonant,
+        '\u{AA7B}'..='\u{AA7D}' => SyllableModifier,
+        '\u{FE00}'..='\u{FE0F}' => VariationSelector,
+        '\u{00A0}' => Placeholder,
+        '\u{200C}' | '\u{200D}' => Joiner,
+        DOTTED_CIRCLE => DottedCircle,
+        _ => Other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw_glyph(ch: char) -> RawGlyph<()> {
+        RawGlyph {
+            unicodes: tiny_vec![[char; 1] => ch],
+            glyph_index: 0,
+            liga_component_pos: 0,
+            glyph_origin: GlyphOrigin::Char(ch),
+            small_caps: false,
+            multi_subst_dup: false,
+            multi_subst_component: None,
+            is_vert_alt: false,
+            fake_bold: false,
+            fake_italic: false,
+            stretch: None,
+            variation: None,
+            extra_data: (),
+        }
+    }
+
+    fn chars(glyphs: &[MyanmarGlyph]) -> String {
+        glyphs.iter().map(|g| g.unicodes[0]).collect()
+    }
+
+    fn syllables(s: &str) -> Vec<(String, Option<Syllable>)> {
+        let glyphs = s.chars().map(raw_glyph).collect::<Vec<_>>();
+        to_myanmar_syllables(&glyphs)
+            .into_iter()
+            .map(|(syllable, syllable_type)| (chars(&syllable), syllable_type))
+            .collect()
+    }
+
+    fn reorder(s: &str) -> String {
+        let glyphs = s.chars().map(raw_glyph).collect::<Vec<_>>();
+        let mut syllable = to_myanmar_syllables(&glyphs).remove(0).0;
+        reorder_syllable(&mut syllable);
+        chars(&syllable)
+    }
+
+    #[test]
+    fn test_syllables() {
+        // KA, MEDIAL RA, VOWEL SIGN E, VOWEL SIGN AA, ASAT; GA, VIRAMA, GA
+        assert_eq!(
+            syllables("\u{1000}\u{103C}\u{1031}\u{102C}\u{103A}\u{1002}\u{1039}\u{1002}"),
+            vec![
+                (
+                    "\u{1000}\u{103C}\u{1031}\u{102C}\u{103A}".to_string(),
+                    Some(Syllable::Consonant)
+                ),
+                (
+                    "\u{1002}\u{1039}\u{1002}".to_string(),
+                    Some(Syllable::Consonant)
+                ),
+            ]
+        );
+
+        // A vowel sign without a base
+        assert_eq!(
+            syllables("\u{1031}"),
+            vec![("\u{1031}".to_string(), Some(Syllable::Broken))]
+        );
+        assert_eq!(syllables("ab"), vec![("ab".to_string(), None)]);
+    }
+
+    #[test]
+    fn test_reorder_medial_ra_and_pre_base_vowel() {
+        // KA, MEDIAL RA, VOWEL SIGN E
+        assert_eq!(
+            reorder("\u{1000}\u{103C}\u{1031}"),
+            "\u{1031}\u{103C}\u{1000}"
+        );
+    }
+
+    #[test]
+    fn test_reorder_kinzi() {
+        // NGA, ASAT, VIRAMA (Kinzi), GA, VOWEL SIGN I
+        assert_eq!(
+            reorder("\u{1004}\u{103A}\u{1039}\u{1002}\u{102D}"),
+            "\u{1002}\u{1004}\u{103A}\u{1039}\u{102D}"
+        );
+    }
+
+    #[test]
+    fn test_reorder_below_base() {
+        // KA, VOWEL SIGN U, ANUSVARA, DOT BELOW
+        let glyphs = "\u{1000}\u{102F}\u{1036}\u{1037}"
+            .chars()
+            .map(raw_glyph)
+            .collect::<Vec<_>>();
+        let mut syllable = to_myanmar_syllables(&glyphs).remove(0).0;
+        reorder_syllable(&mut syllable);
+        assert_eq!(
+            syllable.iter().map(|g| g.pos()).collect::<Vec<_>>(),
+            vec![
+                Some(Pos::SyllableBase),
+                Some(Pos::BeforeSubjoined),
+                Some(Pos::BelowBase),
+                Some(Pos::AfterSubjoined),
+            ]
+        );
+        assert_eq!(chars(&syllable), "\u{1000}\u{1036}\u{102F}\u{1037}");
+    }
+}
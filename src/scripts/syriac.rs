@@ -11,6 +11,8 @@ use crate::tag;
 use std::convert::From;
 use unicode_joining_type::{get_joining_group, get_joining_type, JoiningGroup, JoiningType};
 
+const SYRIAC_ABBREVIATION_MARK: char = '\u{070F}';
+
 #[derive(Clone)]
 struct SyriacData {
     joining_group: JoiningGroup,
@@ -37,6 +39,10 @@ impl SyriacGlyph {
         self.extra_data.joining_group == JoiningGroup::DalathRish
     }
 
+    fn is_abbreviation_mark(&self) -> bool {
+        self.glyph_origin == GlyphOrigin::Char(SYRIAC_ABBREVIATION_MARK)
+    }
+
     fn is_transparent(&self) -> bool {
         self.extra_data.joining_type == JoiningType::Transparent || self.multi_subst_dup
     }
@@ -69,17 +75,17 @@ impl SyriacGlyph {
 impl From<&RawGlyph<()>> for SyriacGlyph {
     fn from(raw_glyph: &RawGlyph<()>) -> SyriacGlyph {
         // Since there's no `Char` to work out the `SyriacGlyph`s joining type when the glyph's
-        // `glyph_origin` is `GlyphOrigin::Direct`, we fallback to `JoiningType::NonJoining` as
-        // the safest approach
-        let joining_type = match raw_glyph.glyph_origin {
-            GlyphOrigin::Char(c) => get_joining_type(c),
-            GlyphOrigin::Direct => JoiningType::NonJoining,
+        // `glyph_origin` is `GlyphOrigin::Direct`, other than for the first glyph of a decomposed
+        // character, we fallback to `JoiningType::NonJoining` as the safest approach
+        let joining_type = match raw_glyph.source_char() {
+            Some(c) => get_joining_type(c),
+            None => JoiningType::NonJoining,
         };
 
         // As above, we'll fallback onto `JoiningType::NoJoiningGroup`
-        let joining_group = match raw_glyph.glyph_origin {
-            GlyphOrigin::Char(c) => get_joining_group(c),
-            GlyphOrigin::Direct => JoiningGroup::NoJoiningGroup,
+        let joining_group = match raw_glyph.source_char() {
+            Some(c) => get_joining_group(c),
+            None => JoiningGroup::NoJoiningGroup,
         };
 
         SyriacGlyph {
@@ -89,9 +95,11 @@ impl From<&RawGlyph<()>> for SyriacGlyph {
             glyph_origin: raw_glyph.glyph_origin,
             small_caps: raw_glyph.small_caps,
             multi_subst_dup: raw_glyph.multi_subst_dup,
+            multi_subst_component: raw_glyph.multi_subst_component,
             is_vert_alt: raw_glyph.is_vert_alt,
             fake_bold: raw_glyph.fake_bold,
             fake_italic: raw_glyph.fake_italic,
+            stretch: raw_glyph.stretch,
             variation: raw_glyph.variation,
             extra_data: SyriacData {
                 joining_group,
@@ -113,15 +121,46 @@ impl From<&SyriacGlyph> for RawGlyph<()> {
             glyph_origin: syriac_glyph.glyph_origin,
             small_caps: syriac_glyph.small_caps,
             multi_subst_dup: syriac_glyph.multi_subst_dup,
+            multi_subst_component: syriac_glyph.multi_subst_component,
             is_vert_alt: syriac_glyph.is_vert_alt,
             fake_bold: syriac_glyph.fake_bold,
             variation: syriac_glyph.variation,
             fake_italic: syriac_glyph.fake_italic,
+            stretch: syriac_glyph.stretch,
             extra_data: (),
         }
     }
 }
 
+/// Language-form substitution features, paired with whether they apply to all glyphs.
+pub(crate) const LANGUAGE_FEATURES: &[(GsubFeatureMask, bool)] = &[
+    (GsubFeatureMask::LOCL, true),
+    (GsubFeatureMask::ISOL, false),
+    (GsubFeatureMask::FINA, false),
+    (GsubFeatureMask::FIN2, false),
+    (GsubFeatureMask::FIN3, false),
+    (GsubFeatureMask::MEDI, false),
+    (GsubFeatureMask::MED2, false),
+    (GsubFeatureMask::INIT, false),
+    (GsubFeatureMask::RLIG, true),
+    (GsubFeatureMask::CALT, true),
+];
+
+/// Typographic-form substitution features.
+pub(crate) const TYPOGRAPHIC_FEATURES: &[GsubFeatureMask] = &[GsubFeatureMask::LIGA];
+
+/// The substitution features that may be applied when shaping Syriac.
+pub(crate) fn gsub_features() -> GsubFeatureMask {
+    LANGUAGE_FEATURES
+        .iter()
+        .map(|&(feature_mask, _)| feature_mask)
+        .chain(TYPOGRAPHIC_FEATURES.iter().cloned())
+        .fold(
+            GsubFeatureMask::CCMP | GsubFeatureMask::STCH,
+            |mask, feature_mask| mask | feature_mask,
+        )
+}
+
 pub fn gsub_apply_syriac(
     gsub_cache: &LayoutCache<GSUB>,
     gsub_table: &LayoutTable<GSUB>,
@@ -205,22 +244,21 @@ pub fn gsub_apply_syriac(
 
     // 3. Applying the stch feature
     //
-    // TODO hold off for future generalised solution (including Kashidas)
+    // This decomposes the Syriac Abbreviation Mark into its component parts. Repeating the
+    // extender component to span the abbreviated text requires glyph advances, so that is left to
+    // positioning, see `gpos::apply_stretch`.
 
-    // 4. Applying the language-form substitution features from GSUB
+    gsub::gsub_apply_stch(
+        gsub_cache,
+        gsub_table,
+        gdef_table,
+        script_tag,
+        lang_tag,
+        syriac_glyphs,
+        |g| g.is_abbreviation_mark(),
+    )?;
 
-    const LANGUAGE_FEATURES: &'static [(GsubFeatureMask, bool)] = &[
-        (GsubFeatureMask::LOCL, true),
-        (GsubFeatureMask::ISOL, false),
-        (GsubFeatureMask::FINA, false),
-        (GsubFeatureMask::FIN2, false),
-        (GsubFeatureMask::FIN3, false),
-        (GsubFeatureMask::MEDI, false),
-        (GsubFeatureMask::MED2, false),
-        (GsubFeatureMask::INIT, false),
-        (GsubFeatureMask::RLIG, true),
-        (GsubFeatureMask::CALT, true),
-    ];
+    // 4. Applying the language-form substitution features from GSUB
 
     for &(feature_mask, is_global) in LANGUAGE_FEATURES {
         apply_lookups(
@@ -239,16 +277,18 @@ pub fn gsub_apply_syriac(
     //
     // Note that we skip `GSUB`'s `DLIG` feature as it should be off by default
 
-    apply_lookups(
-        GsubFeatureMask::LIGA,
-        gsub_cache,
-        gsub_table,
-        gdef_table,
-        script_tag,
-        lang_tag,
-        syriac_glyphs,
-        |_, _| true,
-    )?;
+    for &feature_mask in TYPOGRAPHIC_FEATURES {
+        apply_lookups(
+            feature_mask,
+            gsub_cache,
+            gsub_table,
+            gdef_table,
+            script_tag,
+            lang_tag,
+            syriac_glyphs,
+            |_, _| true,
+        )?;
+    }
 
     // 6. Mark reordering
     //
@@ -270,9 +310,9 @@ fn apply_lookups(
     pred: impl Fn(&RawGlyph<SyriacData>, u32) -> bool + Copy,
 ) -> Result<(), ParseError> {
     let index = gsub::get_lookups_cache_index(gsub_cache, script_tag, lang_tag, feature_mask)?;
-    let lookups = &gsub_cache.cached_lookups.borrow()[index];
+    let lookups = gsub_cache.cached_lookups.read().unwrap()[index].clone();
 
-    for &(lookup_index, feature_tag) in lookups {
+    for &(lookup_index, feature_tag) in &lookups {
         gsub::gsub_apply_lookup(
             gsub_cache,
             gsub_table,
@@ -0,0 +1,330 @@
+//! Implementation of font shaping for Thai and Lao
+//!
+//! Code herein follows the HarfBuzz Thai shaper. Thai and Lao are otherwise shaped with the
+//! default shaper; this module provides the character preprocessing they need.
+//!
+//! Older Thai fonts have no `gsub` rules for Thai. Instead they carry glyphs for shifted marks
+//! and consonants without descenders in the Private Use Area (PUA), at code points defined by
+//! Windows and Mac OS. `pua_fallback_thai` selects these glyphs, for fonts that lack `gsub`
+//! support for Thai.
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Consonant {
+    /// A consonant without an ascender or descender.
+    Normal,
+    /// A consonant with an ascender.
+    Ascender,
+    /// A consonant with a descender that is removed when a below-base vowel is attached.
+    RemovableDescender,
+    /// A consonant with a descender that pushes below-base vowels down.
+    StrictDescender,
+    /// Not a consonant.
+    None,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Mark {
+    AboveVowel,
+    BelowVowel,
+    Tone,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Action {
+    Nop,
+    /// Shift down.
+    ShiftDown,
+    /// Shift left.
+    ShiftLeft,
+    /// Shift down and left.
+    ShiftDownLeft,
+    /// Remove the descender of the base consonant.
+    RemoveDescender,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum AboveState {
+    /// After a base.
+    Base,
+    /// After a base with an ascender.
+    BaseAscender,
+    /// After a mark on a base with an ascender.
+    MarkAscender,
+    /// After the final mark.
+    Final,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum BelowState {
+    /// After a base without a descender.
+    None,
+    /// After a base with a descender that can be removed.
+    Removable,
+    /// After a base with a descender that can't be removed, or a below-base vowel.
+    Strict,
+}
+
+/// PUA code points for `(character, Windows, Mac)`, when shifted down.
+const SHIFT_DOWN_FORMS: &[(char, char, char)] = &[
+    ('\u{0E48}', '\u{F70A}', '\u{F88B}'), // MAI EK
+    ('\u{0E49}', '\u{F70B}', '\u{F88E}'), // MAI THO
+    ('\u{0E4A}', '\u{F70C}', '\u{F891}'), // MAI TRI
+    ('\u{0E4B}', '\u{F70D}', '\u{F894}'), // MAI CHATTAWA
+    ('\u{0E4C}', '\u{F70E}', '\u{F897}'), // THANTHAKHAT
+    ('\u{0E38}', '\u{F718}', '\u{F89B}'), // SARA U
+    ('\u{0E39}', '\u{F719}', '\u{F89C}'), // SARA UU
+    ('\u{0E3A}', '\u{F71A}', '\u{F89D}'), // PHINTHU
+];
+
+/// PUA code points for `(character, Windows, Mac)`, when shifted down and left.
+const SHIFT_DOWN_LEFT_FORMS: &[(char, char, char)] = &[
+    ('\u{0E48}', '\u{F705}', '\u{F88C}'), // MAI EK
+    ('\u{0E49}', '\u{F706}', '\u{F88F}'), // MAI THO
+    ('\u{0E4A}', '\u{F707}', '\u{F892}'), // MAI TRI
+    ('\u{0E4B}', '\u{F708}', '\u{F895}'), // MAI CHATTAWA
+    ('\u{0E4C}', '\u{F709}', '\u{F898}'), // THANTHAKHAT
+];
+
+/// PUA code points for `(character, Windows, Mac)`, when shifted left.
+const SHIFT_LEFT_FORMS: &[(char, char, char)] = &[
+    ('\u{0E48}', '\u{F713}', '\u{F88A}'), // MAI EK
+    ('\u{0E49}', '\u{F714}', '\u{F88D}'), // MAI THO
+    ('\u{0E4A}', '\u{F715}', '\u{F890}'), // MAI TRI
+    ('\u{0E4B}', '\u{F716}', '\u{F893}'), // MAI CHATTAWA
+    ('\u{0E4C}', '\u{F717}', '\u{F896}'), // THANTHAKHAT
+    ('\u{0E31}', '\u{F710}', '\u{F884}'), // MAI HAN-AKAT
+    ('\u{0E34}', '\u{F701}', '\u{F885}'), // SARA I
+    ('\u{0E35}', '\u{F702}', '\u{F886}'), // SARA II
+    ('\u{0E36}', '\u{F703}', '\u{F887}'), // SARA UE
+    ('\u{0E37}', '\u{F704}', '\u{F888}'), // SARA UEE
+    ('\u{0E47}', '\u{F712}', '\u{F889}'), // MAITAIKHU
+    ('\u{0E4D}', '\u{F711}', '\u{F899}'), // NIKHAHIT
+];
+
+/// PUA code points for `(character, Windows, Mac)`, with the descender removed.
+const REMOVE_DESCENDER_FORMS: &[(char, char, char)] = &[
+    ('\u{0E0D}', '\u{F70F}', '\u{F89A}'), // YO YING
+    ('\u{0E10}', '\u{F700}', '\u{F89E}'), // THO THAN
+];
+
+/////////////////////////////////////////////////////////////////////////////
+// Preprocessing
+/////////////////////////////////////////////////////////////////////////////
+
+/// Preprocess Thai and Lao character sequences. This function should be called
+/// prior to mapping Thai or Lao characters to their corresponding glyphs.
+///
+/// SARA AM is decomposed into NIKHAHIT and SARA AA. The NIKHAHIT is moved before any above-base
+/// marks preceding it so that it's positioned on the base consonant, beneath the marks.
+pub fn preprocess_thai(cs: &mut Vec<char>) {
+    let mut i = 0;
+    while i < cs.len() {
+        let ch = u32::from(cs[i]);
+        if ch & !0x80 != 0x0E33 {
+            i += 1;
+            continue;
+        }
+
+        let nikhahit = std::char::from_u32(ch - 0x0E33 + 0x0E4D).unwrap();
+        let sara_aa = std::char::from_u32(ch - 1).unwrap();
+        let mut start = i;
+        while start > 0 && is_above_base_mark(cs[start - 1]) {
+            start -= 1;
+        }
+        cs[i] = sara_aa;
+        cs.insert(start, nikhahit);
+        i += 2;
+    }
+}
+
+fn is_above_base_mark(ch: char) -> bool {
+    match u32::from(ch) & !0x80 {
+        0x0E31 | 0x0E34..=0x0E37 | 0x0E3B | 0x0E47..=0x0E4E => true,
+        _ => false,
+    }
+}
+
+/// Substitute Thai characters with the PUA forms of shifted marks and consonants without
+/// descenders. This function should be called after `preprocess_thai` and prior to mapping
+/// characters to their corresponding glyphs, for fonts that don't support Thai in `gsub`.
+///
+/// The Windows forms are used if `has_glyph` returns `true` for them, otherwise the Mac forms are
+/// used if present. Characters are left unchanged when the font has neither.
+pub fn pua_fallback_thai(cs: &mut [char], mut has_glyph: impl FnMut(char) -> bool) {
+    let mut above_state = AboveState::Final;
+    let mut below_state = BelowState::Strict;
+    let mut base = 0;
+
+    for i in 0..cs.len() {
+        let mark = match mark_type(cs[i]) {
+            Some(mark) => mark,
+            None => {
+                let consonant = consonant_type(cs[i]);
+                above_state = above_start_state(consonant);
+                below_state = below_start_state(consonant);
+                base = i;
+                continue;
+            }
+        };
+
+        let (above_action, next_above_state) = above_transition(above_state, mark);
+        let (below_action, next_below_state) = below_transition(below_state, mark);
+        above_state = next_above_state;
+        below_state = next_below_state;
+
+        // At least one of the actions is always `Nop`
+        let action = if above_action != Action::Nop {
+            above_action
+        } else {
+            below_action
+        };
+        let index = if action == Action::RemoveDescender {
+            base
+        } else {
+            i
+        };
+        cs[index] = pua_shape(cs[index], action, &mut has_glyph);
+    }
+}
+
+fn pua_shape(ch: char, action: Action, has_glyph: &mut impl FnMut(char) -> bool) -> char {
+    let forms = match action {
+        Action::Nop => return ch,
+        Action::ShiftDown => SHIFT_DOWN_FORMS,
+        Action::ShiftLeft => SHIFT_LEFT_FORMS,
+        Action::ShiftDownLeft => SHIFT_DOWN_LEFT_FORMS,
+        Action::RemoveDescender => REMOVE_DESCENDER_FORMS,
+    };
+    match forms.iter().find(|&&(form_ch, _, _)| form_ch == ch) {
+        Some(&(_, windows, _)) if has_glyph(windows) => windows,
+        Some(&(_, _, mac)) if has_glyph(mac) => mac,
+        _ => ch,
+    }
+}
+
+fn above_start_state(consonant: Consonant) -> AboveState {
+    match consonant {
+        Consonant::Ascender => AboveState::BaseAscender,
+        Consonant::None => AboveState::Final,
+        _ => AboveState::Base,
+    }
+}
+
+fn below_start_state(consonant: Consonant) -> BelowState {
+    match consonant {
+        Consonant::Normal | Consonant::Ascender => BelowState::None,
+        Consonant::RemovableDescender => BelowState::Removable,
+        Consonant::StrictDescender | Consonant::None => BelowState::Strict,
+    }
+}
+
+fn above_transition(state: AboveState, mark: Mark) -> (Action, AboveState) {
+    match (state, mark) {
+        (AboveState::Base, Mark::AboveVowel) => (Action::Nop, AboveState::Final),
+        (AboveState::Base, Mark::BelowVowel) => (Action::Nop, AboveState::Base),
+        (AboveState::Base, Mark::Tone) => (Action::ShiftDown, AboveState::Final),
+        (AboveState::BaseAscender, Mark::AboveVowel) => {
+            (Action::ShiftLeft, AboveState::MarkAscender)
+        }
+        (AboveState::BaseAscender, Mark::BelowVowel) => (Action::Nop, AboveState::BaseAscender),
+        (AboveState::BaseAscender, Mark::Tone) => (Action::ShiftDownLeft, AboveState::MarkAscender),
+        (AboveState::MarkAscender, Mark::AboveVowel) => (Action::Nop, AboveState::Final),
+        (AboveState::MarkAscender, Mark::BelowVowel) => (Action::Nop, AboveState::MarkAscender),
+        (AboveState::MarkAscender, Mark::Tone) => (Action::ShiftLeft, AboveState::Final),
+        (AboveState::Final, _) => (Action::Nop, AboveState::Final),
+    }
+}
+
+fn below_transition(state: BelowState, mark: Mark) -> (Action, BelowState) {
+    match (state, mark) {
+        (BelowState::None, Mark::BelowVowel) => (Action::Nop, BelowState::Strict),
+        (BelowState::Removable, Mark::BelowVowel) => (Action::RemoveDescender, BelowState::Strict),
+        (BelowState::Strict, Mark::BelowVowel) => (Action::ShiftDown, BelowState::Strict),
+        (state, _) => (Action::Nop, state),
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// Thai character tables
+/////////////////////////////////////////////////////////////////////////////
+
+fn consonant_type(ch: char) -> Consonant {
+    match ch {
+        '\u{0E1B}' | '\u{0E1D}' | '\u{0E1F}' => Consonant::Ascender,
+        '\u{0E0D}' | '\u{0E10}' => Consonant::RemovableDescender,
+        '\u{0E0E}' | '\u{0E0F}' => Consonant::StrictDescender,
+        '\u{0E01}'..='\u{0E2E}' => Consonant::Normal,
+        _ => Consonant::None,
+    }
+}
+
+fn mark_type(ch: char) -> Option<Mark> {
+    match ch {
+        '\u{0E31}' | '\u{0E34}'..='\u{0E37}' | '\u{0E47}' | '\u{0E4D}'..='\u{0E4E}' => {
+            Some(Mark::AboveVowel)
+        }
+        '\u{0E38}'..='\u{0E3A}' => Some(Mark::BelowVowel),
+        '\u{0E48}'..='\u{0E4C}' => Some(Mark::Tone),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preprocess(s: &str) -> String {
+        let mut cs = s.chars().collect();
+        preprocess_thai(&mut cs);
+        cs.into_iter().collect()
+    }
+
+    fn pua_fallback(s: &str, has_glyph: impl FnMut(char) -> bool) -> String {
+        let mut cs = s.chars().collect::<Vec<_>>();
+        pua_fallback_thai(&mut cs, has_glyph);
+        cs.into_iter().collect()
+    }
+
+    #[test]
+    fn decompose_sara_am() {
+        // KO KAI, SARA AM
+        assert_eq!(preprocess("\u{0E01}\u{0E33}"), "\u{0E01}\u{0E4D}\u{0E32}");
+        // NO NU, MAI THO, SARA AM: NIKHAHIT moves before the tone mark
+        assert_eq!(
+            preprocess("\u{0E19}\u{0E49}\u{0E33}"),
+            "\u{0E19}\u{0E4D}\u{0E49}\u{0E32}"
+        );
+        // Lao
+        assert_eq!(preprocess("\u{0E81}\u{0EB3}"), "\u{0E81}\u{0ECD}\u{0EB2}");
+    }
+
+    #[test]
+    fn pua_fallback_forms() {
+        // YO YING with SARA U loses its descender, the Mac form is used when the font lacks the
+        // Windows one
+        assert_eq!(
+            pua_fallback("\u{0E0D}\u{0E38}", |ch| ch == '\u{F89A}'),
+            "\u{F89A}\u{0E38}"
+        );
+        // PO PLA with SARA I and MAI EK: the vowel shifts left, the tone mark shifts left
+        assert_eq!(
+            pua_fallback("\u{0E1B}\u{0E34}\u{0E48}", |_| true),
+            "\u{0E1B}\u{F701}\u{F713}"
+        );
+        // KO KAI with MAI EK: the tone mark shifts down
+        assert_eq!(
+            pua_fallback("\u{0E01}\u{0E48}", |_| true),
+            "\u{0E01}\u{F70A}"
+        );
+        // THO THAN with SARA U, font has no PUA glyphs
+        assert_eq!(
+            pua_fallback("\u{0E10}\u{0E38}", |_| false),
+            "\u{0E10}\u{0E38}"
+        );
+        // DO CHADA with SARA U: the vowel shifts down below the descender
+        assert_eq!(
+            pua_fallback("\u{0E0E}\u{0E38}", |_| true),
+            "\u{0E0E}\u{F718}"
+        );
+    }
+}
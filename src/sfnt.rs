@@ -0,0 +1,579 @@
+//! Writing of OpenType and TrueType font files.
+//!
+//! <https://docs.microsoft.com/en-us/typography/opentype/spec/otff#organization-of-an-opentype-font>
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::num::Wrapping;
+
+#[cfg(any(feature = "subsetting", feature = "variations"))]
+use crate::binary::read::ReadScope;
+use crate::binary::write::{Placeholder, WriteBinary, WriteBinaryDep, WriteBuffer, WriteContext};
+use crate::binary::{long_align, U16Be, U32Be};
+use crate::checksum::{self, CHECK_SUM_ADJUSTMENT_OFFSET};
+use crate::error::{ReadWriteError, WriteError};
+use crate::tables::glyf::GlyfTable;
+use crate::tables::loca;
+#[cfg(any(feature = "subsetting", feature = "variations"))]
+use crate::tables::{FontTableProvider, MaxpTable};
+use crate::tables::{HeadTable, IndexToLocFormat, TableRecord, CFF_MAGIC};
+use crate::tag;
+
+/// Size of the offset table that precedes the table records of a font.
+pub(crate) const OFFSET_TABLE_SIZE: usize = 12;
+
+/// Recommended order of the tables in a TrueType font.
+///
+/// <https://docs.microsoft.com/en-us/typography/opentype/spec/recom#optimized-table-ordering>
+const TRUETYPE_TABLE_ORDER: [u32; 20] = [
+    tag::HEAD,
+    tag::HHEA,
+    tag::MAXP,
+    tag::OS_2,
+    tag::HMTX,
+    tag::LTSH,
+    tag::VDMX,
+    tag::HDMX,
+    tag::CMAP,
+    tag::FPGM,
+    tag::PREP,
+    tag::CVT,
+    tag::LOCA,
+    tag::GLYF,
+    tag::KERN,
+    tag::NAME,
+    tag::POST,
+    tag::GASP,
+    tag::PCLT,
+    tag::DSIG,
+];
+
+/// Recommended order of the tables in a CFF font.
+const CFF_TABLE_ORDER: [u32; 8] = [
+    tag::HEAD,
+    tag::HHEA,
+    tag::MAXP,
+    tag::OS_2,
+    tag::NAME,
+    tag::CMAP,
+    tag::POST,
+    tag::CFF,
+];
+
+/// Tables that are dropped by default when copying tables into a rebuilt font, as rewriting the
+/// font invalidates them: the digital signature, and the device metrics that are only valid for
+/// the original glyphs.
+#[cfg(any(feature = "subsetting", feature = "variations"))]
+pub const DEFAULT_DROP_TABLES: [u32; 3] = [tag::DSIG, tag::HDMX, tag::LTSH];
+
+/// Tables that hold the variations of a variable font. They are only valid together, as they
+/// refer to the axes defined by `fvar` and vary the outlines and metrics of the other tables.
+#[cfg(any(feature = "subsetting", feature = "variations"))]
+pub(crate) const VARIATION_TABLES: [u32; 7] = [
+    tag::AVAR,
+    tag::CVAR,
+    tag::FVAR,
+    tag::GVAR,
+    tag::HVAR,
+    tag::MVAR,
+    tag::VVAR,
+];
+
+/// Tables that refer to glyph ids. They are never copied into a rebuilt font as is, only added
+/// when they have been rebuilt for the new glyph ids.
+#[cfg(feature = "subsetting")]
+pub(crate) const GLYPH_DEPENDENT_TABLES: [u32; 43] = [
+    tag::BDAT,
+    tag::BLOC,
+    tag::BSLN,
+    tag::CBDT,
+    tag::CBLC,
+    tag::CFF,
+    tag::CFF2,
+    tag::CMAP,
+    tag::COLR,
+    tag::EBDT,
+    tag::EBLC,
+    tag::EBSC,
+    tag::GDEF,
+    tag::GLAT,
+    tag::GLOC,
+    tag::GLYF,
+    tag::GPOS,
+    tag::GSUB,
+    tag::GVAR,
+    tag::HDMX,
+    tag::HMTX,
+    tag::HVAR,
+    tag::JSTF,
+    tag::JUST,
+    tag::KERN,
+    tag::KERX,
+    tag::LCAR,
+    tag::LOCA,
+    tag::LTSH,
+    tag::MATH,
+    tag::MORT,
+    tag::MORX,
+    tag::OPBD,
+    tag::POST,
+    tag::PROP,
+    tag::SBIX,
+    tag::SILF,
+    tag::SVG,
+    tag::VHEA,
+    tag::VMTX,
+    tag::VORG,
+    tag::VVAR,
+    tag::ZAPF,
+];
+
+pub(crate) struct FontBuilder {
+    sfnt_version: u32,
+    tables: BTreeMap<u32, WriteBuffer>,
+}
+
+pub(crate) struct FontBuilderWithHead {
+    inner: FontBuilder,
+    check_sum_adjustment: Placeholder<U32Be, u32>,
+    index_to_loc_format: IndexToLocFormat,
+}
+
+struct TaggedBuffer {
+    tag: u32,
+    buffer: WriteBuffer,
+}
+
+struct OrderedTables {
+    tables: Vec<TaggedBuffer>,
+    checksum: Wrapping<u32>,
+}
+
+/// Write a font file containing `tables`.
+///
+/// `tables` is a list of table tags and the data of the table. The table directory is sorted by
+/// tag and the table data is laid out in the order recommended by the OpenType specification,
+/// with tables that have no recommended position following in tag order. Each table is padded to
+/// a 4-byte boundary, the table checksums are calculated, and `checkSumAdjustment` is set in the
+/// `head` table if present.
+pub fn write_font(sfnt_version: u32, tables: &[(u32, &[u8])]) -> Result<Vec<u8>, ReadWriteError> {
+    let mut directory = tables.to_vec();
+    directory.sort_by_key(|&(table_tag, _)| table_tag);
+    if directory.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+        return Err(WriteError::BadValue.into());
+    }
+
+    let order = if sfnt_version == CFF_MAGIC {
+        &CFF_TABLE_ORDER[..]
+    } else {
+        &TRUETYPE_TABLE_ORDER[..]
+    };
+    let mut layout = directory.clone();
+    layout.sort_by_key(|&(table_tag, _)| {
+        let position = order.iter().position(|&tag| tag == table_tag);
+        (position.unwrap_or(order.len()), table_tag)
+    });
+
+    let mut w = WriteBuffer::new();
+    write_offset_table(&mut w, sfnt_version, directory.len())?;
+    let mut offsets = Vec::with_capacity(layout.len());
+    let mut offset = OFFSET_TABLE_SIZE + directory.len() * TableRecord::SIZE;
+    for &(table_tag, data) in &layout {
+        offsets.push((table_tag, offset));
+        offset += long_align(data.len());
+    }
+    let mut head_offset = None;
+    for &(table_tag, data) in &directory {
+        let offset = offsets
+            .iter()
+            .find(|&&(offset_tag, _)| offset_tag == table_tag)
+            .map(|&(_, offset)| offset)
+            .unwrap();
+        if table_tag == tag::HEAD {
+            head_offset = Some(offset);
+        }
+        let record = TableRecord {
+            table_tag,
+            checksum: checksum::sfnt_table_checksum(table_tag, data)?.0,
+            offset: u32::try_from(offset).map_err(WriteError::from)?,
+            length: u32::try_from(data.len()).map_err(WriteError::from)?,
+        };
+        TableRecord::write(&mut w, &record)?;
+    }
+    for &(_, data) in &layout {
+        w.write_bytes(data)?;
+        w.write_zeros(long_align(data.len()) - data.len())?;
+    }
+
+    let mut font = w.into_inner();
+    if let Some(head_offset) = head_offset {
+        let check_sum_adjustment = checksum::check_sum_adjustment(&font, head_offset)?;
+        let field = head_offset + CHECK_SUM_ADJUSTMENT_OFFSET;
+        if let Some(field) = font.get_mut(field..field + 4) {
+            field.copy_from_slice(&check_sum_adjustment.to_be_bytes());
+        }
+    }
+
+    Ok(font)
+}
+
+/// Write the offset table that starts a font with `num_tables` tables.
+pub(crate) fn write_offset_table(
+    w: &mut WriteBuffer,
+    sfnt_version: u32,
+    num_tables: usize,
+) -> Result<(), WriteError> {
+    let num_tables = u16::try_from(num_tables)?;
+    let n = max_power_of_2(num_tables);
+    let search_range = (1 << n) * 16;
+    let entry_selector = n;
+    let range_shift = num_tables * 16 - search_range;
+
+    U32Be::write(w, sfnt_version)?;
+    U16Be::write(w, num_tables)?;
+    U16Be::write(w, search_range)?;
+    U16Be::write(w, entry_selector)?;
+    U16Be::write(w, range_shift)?;
+
+    Ok(())
+}
+
+impl FontBuilder {
+    pub fn new(sfnt_version: u32) -> Self {
+        FontBuilder {
+            sfnt_version,
+            tables: BTreeMap::new(),
+        }
+    }
+
+    pub fn add_table<HostType, T: WriteBinaryDep<HostType>>(
+        &mut self,
+        tag: u32,
+        table: HostType,
+        args: T::Args,
+    ) -> Result<T::Output, ReadWriteError> {
+        assert_ne!(tag, tag::HEAD, "head table must use add_head_table");
+        assert_ne!(tag, tag::GLYF, "glyf table must use add_glyf_table");
+
+        self.add_table_inner::<HostType, T>(tag, table, args)
+    }
+
+    fn add_table_inner<HostType, T: WriteBinaryDep<HostType>>(
+        &mut self,
+        tag: u32,
+        table: HostType,
+        args: T::Args,
+    ) -> Result<T::Output, ReadWriteError> {
+        let mut buffer = WriteBuffer::new();
+        let output = T::write_dep(&mut buffer, table, args)?;
+        self.tables.insert(tag, buffer);
+
+        Ok(output)
+    }
+
+    /// Copy the tables of `provider` that have not been added to the builder as is.
+    ///
+    /// `head`, `maxp`, the tables that refer to glyph ids, and the tables in `drop_tables` are
+    /// skipped. The glyph variations held in `gvar` and the metrics variations refer to glyph ids,
+    /// so the rest of the variation tables are skipped too, along with the `STAT` table of a
+    /// variable font, leaving a static font. Nothing is copied if `provider` is unable to list its
+    /// tables.
+    #[cfg(feature = "subsetting")]
+    pub fn copy_tables(
+        &mut self,
+        provider: &impl FontTableProvider,
+        drop_tables: &[u32],
+    ) -> Result<(), ReadWriteError> {
+        let is_variable = provider.has_table(tag::FVAR);
+        self.copy_tables_except(provider, |tag| {
+            GLYPH_DEPENDENT_TABLES.contains(&tag)
+                || drop_tables.contains(&tag)
+                || VARIATION_TABLES.contains(&tag)
+                || (is_variable && tag == tag::STAT)
+        })
+    }
+
+    /// Copy the tables of `provider` that have not been added to the builder as is, including
+    /// the tables that refer to glyph ids.
+    ///
+    /// This is for fonts rebuilt with the same glyph ids, such as the instances of a variable
+    /// font. `head`, `maxp`, `glyf`, `loca`, and the tables in `drop_tables` are skipped. Nothing
+    /// is copied if `provider` is unable to list its tables.
+    #[cfg(feature = "variations")]
+    pub fn copy_tables_with_glyph_ids(
+        &mut self,
+        provider: &impl FontTableProvider,
+        drop_tables: &[u32],
+    ) -> Result<(), ReadWriteError> {
+        self.copy_tables_except(provider, |tag| drop_tables.contains(&tag))
+    }
+
+    #[cfg(any(feature = "subsetting", feature = "variations"))]
+    fn copy_tables_except(
+        &mut self,
+        provider: &impl FontTableProvider,
+        skip: impl Fn(u32) -> bool,
+    ) -> Result<(), ReadWriteError> {
+        let tags = match provider.table_tags() {
+            Some(tags) => tags,
+            None => return Ok(()),
+        };
+        for tag in tags {
+            // These are added through `add_head_table` and `add_packed_glyf_tables`
+            let is_rebuilt = [tag::HEAD, tag::MAXP, tag::GLYF, tag::LOCA].contains(&tag);
+            if is_rebuilt || self.tables.contains_key(&tag) || skip(tag) {
+                continue;
+            }
+            if let Some(data) = provider.table_data(tag)? {
+                self.add_table::<_, ReadScope<'_>>(tag, ReadScope::new(&data), ())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn add_head_table(
+        mut self,
+        table: &HeadTable,
+    ) -> Result<FontBuilderWithHead, ReadWriteError> {
+        let placeholder = self.add_table_inner::<_, HeadTable>(tag::HEAD, table, ())?;
+
+        Ok(FontBuilderWithHead {
+            inner: self,
+            check_sum_adjustment: placeholder,
+            index_to_loc_format: table.index_to_loc_format,
+        })
+    }
+
+    /// Add the `head`, `maxp`, `glyf` and `loca` tables, with the glyphs written by
+    /// `GlyfTable::write_packed`.
+    ///
+    /// The `loca` format of `head` and the values of `maxp` derived from the glyphs are updated
+    /// before the tables are added.
+    #[cfg(any(feature = "subsetting", feature = "variations"))]
+    pub fn add_packed_glyf_tables(
+        mut self,
+        mut head: HeadTable,
+        mut maxp: MaxpTable,
+        glyf: GlyfTable<'_>,
+    ) -> Result<FontBuilderWithHead, ReadWriteError> {
+        let mut glyf_buffer = WriteBuffer::new();
+        let loca = GlyfTable::write_packed(&mut glyf_buffer, glyf, &mut head, &mut maxp)?;
+        self.tables.insert(tag::GLYF, glyf_buffer);
+        self.add_table_inner::<_, loca::owned::LocaTable>(
+            tag::LOCA,
+            loca,
+            head.index_to_loc_format,
+        )?;
+        self.add_table_inner::<_, MaxpTable>(tag::MAXP, &maxp, ())?;
+        self.add_head_table(&head)
+    }
+}
+
+impl FontBuilderWithHead {
+    pub fn add_glyf_table(&mut self, table: GlyfTable<'_>) -> Result<(), ReadWriteError> {
+        let loca = self.inner.add_table_inner::<_, GlyfTable<'_>>(
+            tag::GLYF,
+            table,
+            self.index_to_loc_format,
+        )?;
+        self.inner.add_table_inner::<_, loca::owned::LocaTable>(
+            tag::LOCA,
+            loca,
+            self.index_to_loc_format,
+        )?;
+
+        Ok(())
+    }
+
+    /// Returns a `Vec<u8>` containing the built font
+    pub fn data(mut self) -> Result<Vec<u8>, ReadWriteError> {
+        let mut font = WriteBuffer::new();
+
+        self.write_offset_table(&mut font)?;
+        let table_offset =
+            long_align(self.inner.tables.len() * TableRecord::SIZE + font.bytes_written());
+
+        // Add tables in desired order
+        let mut ordered_tables = self.write_table_directory(&mut font)?;
+
+        // pad
+        let length = font.bytes_written();
+        let padded_length = long_align(length);
+        assert_eq!(
+            padded_length, table_offset,
+            "offset after writing table directory is not at expected position"
+        );
+        font.write_zeros(padded_length - length)?;
+
+        // Fill in check_sum_adjustment in the head table. the magic number comes from the OpenType spec.
+        let headers_checksum = checksum::table_checksum(font.bytes())?;
+        let checksum =
+            Wrapping(checksum::FONT_CHECKSUM_MAGIC) - (headers_checksum + ordered_tables.checksum);
+
+        // Write out the font tables
+        let mut placeholder = Some(self.check_sum_adjustment);
+        for TaggedBuffer { tag, buffer } in ordered_tables.tables.iter_mut() {
+            if *tag == tag::HEAD {
+                buffer.write_placeholder(placeholder.take().unwrap(), checksum.0)?;
+            }
+            font.write_bytes(buffer.bytes())?;
+        }
+
+        Ok(font.into_inner())
+    }
+
+    fn write_offset_table(&self, font: &mut WriteBuffer) -> Result<(), WriteError> {
+        write_offset_table(font, self.inner.sfnt_version, self.inner.tables.len())
+    }
+
+    fn write_table_directory(
+        &mut self,
+        font: &mut WriteBuffer,
+    ) -> Result<OrderedTables, ReadWriteError> {
+        let mut tables = Vec::with_capacity(self.inner.tables.len());
+        let mut checksum = Wrapping(0);
+        let mut table_offset =
+            long_align(self.inner.tables.len() * TableRecord::SIZE + font.bytes_written());
+
+        let tags = self.inner.tables.keys().cloned().collect::<Vec<_>>();
+        for tag in tags {
+            if let Some(mut table) = self.inner.tables.remove(&tag) {
+                let length = table.len();
+                let padded_length = long_align(length);
+                table.write_zeros(padded_length - length)?;
+
+                let table_checksum = checksum::table_checksum(table.bytes())?;
+                checksum += table_checksum;
+
+                let record = TableRecord {
+                    table_tag: tag,
+                    checksum: table_checksum.0,
+                    offset: u32::try_from(table_offset).map_err(WriteError::from)?,
+                    length: u32::try_from(length).map_err(WriteError::from)?,
+                };
+
+                table_offset += padded_length;
+                TableRecord::write(font, &record)?;
+                tables.push(TaggedBuffer { tag, buffer: table });
+            }
+        }
+
+        Ok(OrderedTables { tables, checksum })
+    }
+}
+
+/// Calculate the maximum power of 2 that is <= num
+pub(crate) fn max_power_of_2(num: u16) -> u16 {
+    15u16.saturating_sub(num.leading_zeros() as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::read::ReadScope;
+    use crate::checksum::ChecksumMismatch;
+    use crate::tables::{OpenTypeData, OpenTypeFont};
+    use crate::tests::read_fixture;
+
+    fn read_tables(data: &[u8]) -> (u32, Vec<(u32, Vec<u8>)>) {
+        let font = ReadScope::new(data).read::<OpenTypeFont<'_>>().unwrap();
+        let offset_table = font.offset_table(0).unwrap();
+        let tables = offset_table
+            .table_records
+            .iter()
+            .map(|record| {
+                let table = record.read_table(&font.scope).unwrap();
+                (record.table_tag, table.data().to_vec())
+            })
+            .collect();
+        (offset_table.sfnt_version, tables)
+    }
+
+    #[test]
+    fn test_write_font() {
+        for path in &[
+            "tests/fonts/opentype/test-font.ttf",
+            "tests/fonts/opentype/Klei.otf",
+        ] {
+            let (sfnt_version, tables) = read_tables(&read_fixture(path));
+            let tables_ref = tables
+                .iter()
+                .map(|(tag, data)| (*tag, data.as_slice()))
+                .collect::<Vec<_>>();
+            let data = write_font(sfnt_version, &tables_ref).unwrap();
+
+            let font = ReadScope::new(&data).read::<OpenTypeFont<'_>>().unwrap();
+            assert_eq!(font.verify_checksums(0).unwrap(), Vec::new());
+            let offset_table = match &font.data {
+                OpenTypeData::Single(offset_table) => offset_table,
+                OpenTypeData::Collection(_) => panic!("expected a single font"),
+            };
+            // The table data starts with head
+            let head = offset_table.find_table_record(tag::HEAD).unwrap();
+            let first_offset = offset_table
+                .table_records
+                .iter()
+                .map(|record| record.offset)
+                .min()
+                .unwrap();
+            assert_eq!(head.offset, first_offset);
+            // The tables other than head are unchanged
+            for (table_tag, table) in read_tables(&data).1 {
+                let (_, expected) = tables.iter().find(|(tag, _)| *tag == table_tag).unwrap();
+                if table_tag != tag::HEAD {
+                    assert_eq!(&table, expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_verify_checksums_mismatch() {
+        let (sfnt_version, tables) = read_tables(&read_fixture("tests/fonts/opentype/Klei.otf"));
+        let tables_ref = tables
+            .iter()
+            .map(|(tag, data)| (*tag, data.as_slice()))
+            .collect::<Vec<_>>();
+        let mut data = write_font(sfnt_version, &tables_ref).unwrap();
+
+        // Corrupt the name table
+        let font = ReadScope::new(&data).read::<OpenTypeFont<'_>>().unwrap();
+        let record = font
+            .offset_table(0)
+            .unwrap()
+            .find_table_record(tag::NAME)
+            .unwrap();
+        data[record.offset as usize] ^= 0xFF;
+
+        let font = ReadScope::new(&data).read::<OpenTypeFont<'_>>().unwrap();
+        let mismatches = font.verify_checksums(0).unwrap();
+        assert_eq!(mismatches.len(), 2);
+        match mismatches[0] {
+            ChecksumMismatch::Table { table_tag, .. } => assert_eq!(table_tag, tag::NAME),
+            _ => panic!("expected a table checksum mismatch"),
+        }
+        match mismatches[1] {
+            ChecksumMismatch::CheckSumAdjustment { .. } => {}
+            _ => panic!("expected a checkSumAdjustment mismatch"),
+        }
+    }
+
+    #[test]
+    fn test_write_font_duplicate_tables() {
+        let tables = [(tag::NAME, &[0u8; 4][..]), (tag::NAME, &[1u8; 4][..])];
+        assert!(write_font(tag::OTTO, &tables).is_err());
+    }
+
+    #[test]
+    fn test_max_power_of_2() {
+        assert_eq!(max_power_of_2(0), 0);
+        assert_eq!(max_power_of_2(1), 0);
+        assert_eq!(max_power_of_2(2), 1);
+        assert_eq!(max_power_of_2(4), 2);
+        assert_eq!(max_power_of_2(8), 3);
+        assert_eq!(max_power_of_2(16), 4);
+        assert_eq!(max_power_of_2(49), 5);
+        assert_eq!(max_power_of_2(std::u16::MAX), 15);
+    }
+}
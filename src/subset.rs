@@ -2,46 +2,135 @@
 
 //! Font subsetting.
 
-use std::collections::BTreeMap;
+mod layout;
+
+use std::collections::BTreeSet;
 use std::convert::TryFrom;
-use std::num::Wrapping;
 
-use itertools::Itertools;
+use log::warn;
+use rustc_hash::{FxHashMap, FxHashSet};
 
 use crate::binary::read::{ReadArrayCow, ReadScope};
-use crate::binary::write::{Placeholder, WriteBinary};
-use crate::binary::write::{WriteBinaryDep, WriteBuffer, WriteContext};
-use crate::binary::{long_align, U16Be, U32Be};
+use crate::binary::write::{WriteBinary, WriteBuffer};
+#[cfg(feature = "bitmap")]
+use crate::bitmap::cbdt::{CBDTTable, CBLCSubset, CBLCTable};
+#[cfg(feature = "bitmap")]
+use crate::bitmap::sbix::Sbix;
 use crate::cff::CFF;
 use crate::error::{ParseError, ReadWriteError, WriteError};
+use crate::font::{read_cmap_subtable, Encoding};
+use crate::get_name::fontcode_get_name;
+use crate::layout::{gsub_closure, new_layout_cache, LayoutTable, GSUB};
 use crate::post::PostTable;
+use crate::sfnt::FontBuilder;
+pub use crate::sfnt::DEFAULT_DROP_TABLES;
+use crate::tables::cmap::owned::create_unicode_cmap_table;
+use crate::tables::cmap::Cmap;
 use crate::tables::glyf::GlyfTable;
-use crate::tables::loca::{self, LocaTable};
+use crate::tables::loca::LocaTable;
+use crate::tables::os2::Os2;
+use crate::tables::vorg::VorgTable;
 use crate::tables::{
-    self, cmap, FontTableProvider, HeadTable, HheaTable, HmtxTable, IndexToLocFormat, MaxpTable,
-    TableRecord,
+    self, cmap, FontTableProvider, HeadTable, HheaTable, HmtxTable, MaxpTable, NameTable,
+    NameTableBuilder, Version16Dot16,
 };
-use crate::{checksum, tag};
+use crate::tag;
+use crate::tag::DisplayTag;
+
+/// Tables and settings for the font produced by `subset_ttf` and `subset_cff`.
+struct SubsetTarget<'a> {
+    cmap: Option<cmap::owned::Cmap>,
+    opt_os2: Option<&'a Os2>,
+    opt_name: Option<NameTableBuilder>,
+    retain_glyph_ids: bool,
+    layout_tables: LayoutTables,
+    #[cfg(feature = "bitmap")]
+    bitmap_tables: bool,
+    /// When set, the remaining tables of the font are copied, except for those listed.
+    opt_drop_tables: Option<&'a [u32]>,
+}
 
-struct FontBuilder {
-    sfnt_version: u32,
-    tables: BTreeMap<u32, WriteBuffer>,
+/// How the layout tables are carried over to a subset font.
+#[derive(Clone, Copy, PartialEq)]
+enum LayoutTables {
+    Drop,
+    Copy,
+    Subset,
 }
 
-struct FontBuilderWithHead {
-    inner: FontBuilder,
-    check_sum_adjustment: Placeholder<U32Be, u32>,
-    index_to_loc_format: IndexToLocFormat,
+/// The glyphs to retain when subsetting with [subset_font](fn.subset_font.html).
+#[derive(Debug, Clone)]
+pub enum SubsetInput {
+    /// Retain the glyphs with these ids.
+    Glyphs(Vec<u16>),
+    /// Retain the glyphs that the `cmap` table maps these Unicode code points to.
+    Unicodes(BTreeSet<u32>),
 }
 
-struct TaggedBuffer {
-    tag: u32,
-    buffer: WriteBuffer,
+/// Configuration for [subset_font](fn.subset_font.html).
+#[derive(Debug, Clone)]
+pub struct SubsetOptions {
+    /// Also retain the glyphs that `GSUB` can substitute for the retained glyphs.
+    pub layout_closure: bool,
+    /// The `GSUB` features considered when computing the closure. When `None` every feature in
+    /// the font is considered.
+    pub layout_features: Option<Vec<u32>>,
+    /// Keep the ids of the retained glyphs instead of renumbering them.
+    ///
+    /// Glyphs that are not retained are replaced with empty glyphs, up to the highest retained
+    /// glyph id, so that shaped text and documents that refer to glyphs by id remain valid. The
+    /// `GDEF`, `GPOS` and `GSUB` tables are copied as is since the glyph ids they refer to don't
+    /// change. This produces a larger font than renumbering.
+    pub retain_glyph_ids: bool,
+    /// Include the `GDEF`, `GPOS` and `GSUB` tables in the subset font. Unless glyph ids are
+    /// retained they are rebuilt for the retained glyphs, dropping the lookups, features and
+    /// language systems that no longer apply to any of them.
+    pub layout_tables: bool,
+    /// Include the embedded bitmap tables (`sbix`, `CBLC`/`CBDT` and `EBLC`/`EBDT`) in the subset
+    /// font, with their strikes rebuilt for the retained glyphs. Without the `bitmap` feature
+    /// these tables are always left out.
+    pub bitmap_tables: bool,
+    /// Copy the tables that are not rebuilt for the subset font, such as `gasp` or tables unknown
+    /// to allsorts, into the subset font as is. Tables that refer to glyph ids are never copied,
+    /// since they would be invalid for the retained glyphs. As the glyph variations of a variable
+    /// font are not subset, its variation tables and `STAT` table are never copied either, and
+    /// the subset font holds the default instance.
+    pub preserve_tables: bool,
+    /// Tables that are not copied when `preserve_tables` is set. Defaults to
+    /// [DEFAULT_DROP_TABLES](constant.DEFAULT_DROP_TABLES.html).
+    pub drop_tables: Vec<u32>,
 }
 
-struct OrderedTables {
-    tables: Vec<TaggedBuffer>,
-    checksum: Wrapping<u32>,
+impl Default for SubsetOptions {
+    fn default() -> Self {
+        SubsetOptions {
+            layout_closure: true,
+            layout_features: None,
+            retain_glyph_ids: false,
+            layout_tables: true,
+            bitmap_tables: true,
+            preserve_tables: true,
+            drop_tables: DEFAULT_DROP_TABLES.to_vec(),
+        }
+    }
+}
+
+/// A font subset for embedding in a PDF as a CIDFont, produced by
+/// [subset_for_pdf](fn.subset_for_pdf.html).
+///
+/// The CIDs used in the content stream are the glyph ids of the original font.
+#[derive(Debug, Clone)]
+pub struct PdfSubset {
+    /// The subset font.
+    pub font: Vec<u8>,
+    /// The PostScript name of the subset font with the subset tag prefix, such as
+    /// `ABCDEF+Klei`. This is the `BaseFont` of the font dictionaries.
+    pub postscript_name: String,
+    /// The contents of the `CIDToGIDMap` stream, which holds the big-endian glyph id of the subset
+    /// font for each CID. Only used for TrueType based (`CIDFontType2`) fonts.
+    pub cid_to_gid_map: Vec<u8>,
+    /// The `ToUnicode` CMap, mapping CIDs to the characters they represent.
+    pub to_unicode: String,
 }
 
 /// Subset this font so that it only contains the glyphs with the supplied `glyph_ids`.
@@ -50,10 +139,13 @@ pub fn subset(
     glyph_ids: &[u16],
     cmap0: Option<Box<[u8; 256]>>,
 ) -> Result<Vec<u8>, ReadWriteError> {
+    let cmap = cmap0
+        .map(|cmap0| create_cmap_table(glyph_ids, cmap0))
+        .transpose()?;
     if provider.has_table(tag::CFF) {
-        subset_cff(provider, glyph_ids, cmap0, true)
+        subset_cff(provider, glyph_ids, SubsetTarget::new(cmap), true)
     } else {
-        subset_ttf(provider, glyph_ids, cmap0)
+        subset_ttf(provider, glyph_ids, SubsetTarget::new(cmap))
     }
 }
 
@@ -74,14 +166,305 @@ pub fn prince_subset(
             convert_cff_to_cid_if_more_than_255_glyphs,
         )
     } else {
-        subset_ttf(provider, glyph_ids, cmap0)
+        let cmap = cmap0
+            .map(|cmap0| create_cmap_table(glyph_ids, cmap0))
+            .transpose()?;
+        subset_ttf(provider, glyph_ids, SubsetTarget::new(cmap))
+    }
+}
+
+/// Subset this font so that it only contains the glyphs selected by `input`, producing a
+/// standalone font.
+///
+/// Glyph 0 (`.notdef`) is always retained, along with the glyphs reachable through `GSUB` when
+/// `options.layout_closure` is set, and the components of retained composite glyphs. Glyphs are
+/// renumbered in ascending order of their original id, unless `options.retain_glyph_ids` is set.
+/// A Unicode `cmap` table is built for the
+/// retained glyphs and the `glyf`/`loca` or `CFF` and `VORG`, `hmtx`, `hhea`, `maxp`, `post` and
+/// `OS/2` tables are rewritten for the new glyph ids. The `name` table and TrueType hinting tables
+/// are copied. The layout and embedded bitmap tables are subset as described for
+/// `options.layout_tables` and `options.bitmap_tables`. Other tables are copied as is when
+/// `options.preserve_tables` is set.
+///
+/// When `input` is `SubsetInput::Glyphs` each retained glyph keeps one of the code points that
+/// map to it in the original `cmap` table.
+pub fn subset_font(
+    provider: &impl FontTableProvider,
+    input: &SubsetInput,
+    options: &SubsetOptions,
+) -> Result<Vec<u8>, ReadWriteError> {
+    subset_font_with_name(provider, input, options, None)
+}
+
+fn subset_font_with_name(
+    provider: &impl FontTableProvider,
+    input: &SubsetInput,
+    options: &SubsetOptions,
+    opt_name: Option<NameTableBuilder>,
+) -> Result<Vec<u8>, ReadWriteError> {
+    let cmap_data = provider.table_data(tag::CMAP)?;
+    let opt_cmap = cmap_data
+        .as_ref()
+        .map(|data| ReadScope::new(data).read::<Cmap<'_>>())
+        .transpose()?;
+    let opt_subtable = match &opt_cmap {
+        Some(cmap) => match read_cmap_subtable(cmap)? {
+            Some((Encoding::Unicode, subtable)) => Some(subtable),
+            _ => None,
+        },
+        None => None,
+    };
+
+    let mut glyph_set = FxHashSet::default();
+    glyph_set.insert(0);
+    let mut mappings = Vec::new();
+    match input {
+        SubsetInput::Glyphs(glyph_ids) => {
+            glyph_set.extend(glyph_ids.iter().copied());
+            if let Some(subtable) = &opt_subtable {
+                mappings.extend(
+                    subtable
+                        .mappings()?
+                        .into_iter()
+                        .filter(|(glyph_id, _)| *glyph_id != 0 && glyph_set.contains(glyph_id))
+                        .map(|(glyph_id, ch)| (ch, glyph_id)),
+                );
+            }
+        }
+        SubsetInput::Unicodes(unicodes) => {
+            let subtable = opt_subtable.ok_or(ParseError::MissingValue)?;
+            for &ch in unicodes {
+                match subtable.map_glyph(ch)? {
+                    Some(glyph_id) if glyph_id != 0 => mappings.push((ch, glyph_id)),
+                    _ => (),
+                }
+            }
+            glyph_set.extend(mappings.iter().map(|&(_, glyph_id)| glyph_id));
+        }
+    }
+    mappings.sort();
+
+    if options.layout_closure {
+        if let Some(gsub_data) = provider.table_data(tag::GSUB)? {
+            let gsub = ReadScope::new(&gsub_data).read::<LayoutTable<GSUB>>()?;
+            let features = options
+                .layout_features
+                .clone()
+                .unwrap_or_else(|| gsub.feature_tags());
+            let scripts = gsub.script_tags();
+            let gsub_cache = new_layout_cache(gsub);
+            glyph_set = gsub_closure(&gsub_cache, &glyph_set, &features, &scripts)?;
+        }
+    }
+
+    let mut glyph_ids = glyph_set.into_iter().collect::<Vec<_>>();
+    glyph_ids.sort();
+
+    // The subset font holds the requested glyphs in ascending order, followed by any glyphs
+    // pulled in by composite glyphs.
+    let mappings = if options.retain_glyph_ids {
+        mappings
+    } else {
+        mappings
+            .into_iter()
+            .map(|(ch, glyph_id)| {
+                let new_id = glyph_ids.binary_search(&glyph_id).unwrap_or_default();
+                Ok((ch, u16::try_from(new_id)?))
+            })
+            .collect::<Result<Vec<_>, WriteError>>()?
+    };
+    let cmap = create_unicode_cmap_table(&mappings);
+
+    let opt_os2 = match provider.table_data(tag::OS_2)? {
+        Some(os2_data) => {
+            let mut os2 = ReadScope::new(&os2_data).read_dep::<Os2>(os2_data.len())?;
+            if let (Some(&(first, _)), Some(&(last, _))) = (mappings.first(), mappings.last()) {
+                os2.us_first_char_index = u16::try_from(first).unwrap_or(0xFFFF);
+                os2.us_last_char_index = u16::try_from(last).unwrap_or(0xFFFF);
+            }
+            Some(os2)
+        }
+        None => None,
+    };
+
+    let target = SubsetTarget {
+        cmap: Some(cmap),
+        opt_os2: opt_os2.as_ref(),
+        opt_name,
+        retain_glyph_ids: options.retain_glyph_ids,
+        layout_tables: match (options.layout_tables, options.retain_glyph_ids) {
+            (false, _) => LayoutTables::Drop,
+            (true, true) => LayoutTables::Copy,
+            (true, false) => LayoutTables::Subset,
+        },
+        #[cfg(feature = "bitmap")]
+        bitmap_tables: options.bitmap_tables,
+        opt_drop_tables: if options.preserve_tables {
+            Some(options.drop_tables.as_slice())
+        } else {
+            None
+        },
+    };
+    if provider.has_table(tag::CFF) {
+        subset_cff(provider, &glyph_ids, target, true)
+    } else {
+        subset_ttf(provider, &glyph_ids, target)
     }
 }
 
+/// Subset this font for embedding in a PDF as a CIDFont, retaining the glyphs with the supplied
+/// `glyph_ids`.
+///
+/// The glyph ids of the original font are used as CIDs. Along with the subset font, the
+/// `CIDToGIDMap` mapping CIDs to the renumbered glyphs and a `ToUnicode` CMap derived from the
+/// `cmap` table are produced. The PostScript name in the `name` table is prefixed with a subset
+/// tag of six uppercase letters derived from `glyph_ids`, so the same glyphs always produce the
+/// same name.
+pub fn subset_for_pdf(
+    provider: &impl FontTableProvider,
+    glyph_ids: &[u16],
+) -> Result<PdfSubset, ReadWriteError> {
+    let mut glyph_ids = glyph_ids.to_vec();
+    glyph_ids.push(0);
+    glyph_ids.sort();
+    glyph_ids.dedup();
+
+    let postscript_name = format!(
+        "{}+{}",
+        subset_tag(&glyph_ids),
+        postscript_name(provider)?.unwrap_or_else(|| String::from("Font"))
+    );
+    let opt_name = match provider.table_data(tag::NAME)? {
+        Some(name_data) => {
+            let name = ReadScope::new(&name_data).read::<NameTable<'_>>()?;
+            let mut builder = NameTableBuilder::from_table(&name)?;
+            builder.postscript_name(&postscript_name)?;
+            Some(builder)
+        }
+        None => None,
+    };
+
+    let options = SubsetOptions {
+        layout_closure: false,
+        layout_tables: false,
+        bitmap_tables: false,
+        preserve_tables: false,
+        ..SubsetOptions::default()
+    };
+    let input = SubsetInput::Glyphs(glyph_ids.clone());
+    let font = subset_font_with_name(provider, &input, &options, opt_name)?;
+
+    // The subset font holds the glyphs in ascending order of their original id
+    let max_cid = glyph_ids.last().copied().unwrap_or(0);
+    let mut cid_to_gid_map = Vec::with_capacity((usize::from(max_cid) + 1) * 2);
+    for cid in 0..=max_cid {
+        let gid = glyph_ids.binary_search(&cid).unwrap_or(0);
+        let gid = u16::try_from(gid).map_err(ParseError::from)?;
+        cid_to_gid_map.extend_from_slice(&gid.to_be_bytes());
+    }
+
+    let mut mappings = Vec::new();
+    if let Some(cmap_data) = provider.table_data(tag::CMAP)? {
+        let cmap = ReadScope::new(&cmap_data).read::<Cmap<'_>>()?;
+        if let Some((Encoding::Unicode, subtable)) = read_cmap_subtable(&cmap)? {
+            mappings = subtable
+                .mappings()?
+                .into_iter()
+                .filter(|(glyph_id, _)| *glyph_id != 0 && glyph_ids.binary_search(glyph_id).is_ok())
+                .filter_map(|(glyph_id, ch)| std::char::from_u32(ch).map(|ch| (glyph_id, ch)))
+                .collect::<Vec<_>>();
+            mappings.sort();
+        }
+    }
+
+    Ok(PdfSubset {
+        font,
+        postscript_name,
+        cid_to_gid_map,
+        to_unicode: to_unicode_cmap(&mappings),
+    })
+}
+
+/// Derive a subset tag of six uppercase letters from `glyph_ids`.
+fn subset_tag(glyph_ids: &[u16]) -> String {
+    // FNV-1a, which unlike the standard library hasher is guaranteed to be stable
+    let mut hash = 0xcbf29ce484222325_u64;
+    for byte in glyph_ids
+        .iter()
+        .flat_map(|glyph_id| glyph_id.to_be_bytes().to_vec())
+    {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    (0..6)
+        .map(|_| {
+            let letter = char::from(b'A' + (hash % 26) as u8);
+            hash /= 26;
+            letter
+        })
+        .collect()
+}
+
+/// Read the PostScript name of the font, removing characters that are not permitted in it.
+fn postscript_name(provider: &impl FontTableProvider) -> Result<Option<String>, ParseError> {
+    let name_data = match provider.table_data(tag::NAME)? {
+        Some(name_data) => name_data,
+        None => return Ok(None),
+    };
+    let name = fontcode_get_name(&name_data, NameTable::POSTSCRIPT_NAME)?;
+    Ok(name
+        .map(|name| {
+            name.to_string_lossy()
+                .chars()
+                .filter(|&c| c.is_ascii_graphic() && !"[](){}<>/%".contains(c))
+                .take(63 - 7)
+                .collect::<String>()
+        })
+        .filter(|name| !name.is_empty()))
+}
+
+/// Build a `ToUnicode` CMap from `mappings` of CIDs to characters, which must be sorted by CID.
+fn to_unicode_cmap(mappings: &[(u16, char)]) -> String {
+    // The number of entries in a bfchar block is limited to 100
+    const MAX_BFCHAR_ENTRIES: usize = 100;
+
+    let mut cmap = String::from(
+        "/CIDInit /ProcSet findresource begin\n\
+         12 dict begin\n\
+         begincmap\n\
+         /CIDSystemInfo << /Registry (Adobe) /Ordering (UCS) /Supplement 0 >> def\n\
+         /CMapName /Adobe-Identity-UCS def\n\
+         /CMapType 2 def\n\
+         1 begincodespacerange\n\
+         <0000> <FFFF>\n\
+         endcodespacerange\n",
+    );
+    for chunk in mappings.chunks(MAX_BFCHAR_ENTRIES) {
+        cmap.push_str(&format!("{} beginbfchar\n", chunk.len()));
+        for &(cid, ch) in chunk {
+            let mut utf16 = [0; 2];
+            let unicode = ch
+                .encode_utf16(&mut utf16)
+                .iter()
+                .map(|unit| format!("{:04X}", unit))
+                .collect::<String>();
+            cmap.push_str(&format!("<{:04X}> <{}>\n", cid, unicode));
+        }
+        cmap.push_str("endbfchar\n");
+    }
+    cmap.push_str(
+        "endcmap\n\
+         CMapName currentdict /CMap defineresource pop\n\
+         end\n\
+         end\n",
+    );
+    cmap
+}
+
 fn subset_ttf(
     provider: &impl FontTableProvider,
     glyph_ids: &[u16],
-    cmap0: Option<Box<[u8; 256]>>,
+    target: SubsetTarget<'_>,
 ) -> Result<Vec<u8>, ReadWriteError> {
     if glyph_ids.get(0) != Some(&0) {
         // glyph index 0 is the .notdef glyph, the fallback, it must always be first
@@ -89,7 +472,7 @@ fn subset_ttf(
     }
 
     let head = ReadScope::new(&provider.read_table_data(tag::HEAD)?).read::<HeadTable>()?;
-    let mut maxp = ReadScope::new(&provider.read_table_data(tag::MAXP)?).read::<MaxpTable>()?;
+    let maxp = ReadScope::new(&provider.read_table_data(tag::MAXP)?).read::<MaxpTable>()?;
     let loca_data = provider.read_table_data(tag::LOCA)?;
     let loca = ReadScope::new(&loca_data)
         .read_dep::<LocaTable<'_>>((usize::from(maxp.num_glyphs), head.index_to_loc_format))?;
@@ -102,19 +485,22 @@ fn subset_ttf(
         usize::from(hhea.num_h_metrics),
     ))?;
 
-    // Build a new post table with version set to 3, which does not contain any additional
-    // PostScript data
     let post_data = provider.read_table_data(tag::POST)?;
-    let mut post = ReadScope::new(&post_data).read::<PostTable<'_>>()?;
-    post.header.version = 0x00030000; // version 3.0
-    post.opt_sub_table = None;
+    let post = ReadScope::new(&post_data).read::<PostTable<'_>>()?;
 
     // Build the new glyf table
-    let (glyf, new_to_old_glyph_id) = glyf.subset(glyph_ids)?;
+    let (glyf, new_to_old_glyph_id) = if target.retain_glyph_ids {
+        glyf.subset_retaining_ids(glyph_ids)?
+    } else {
+        glyf.subset(glyph_ids)?
+    };
 
-    // Build new maxp table
+    // Build a new post table, retaining the names of the glyphs that were kept. This must happen
+    // after subsetting glyf since composite glyphs may pull in additional glyphs.
+    let post = post.subset(&new_to_old_glyph_id)?;
+
+    // The maxp table is updated when the glyf table is written
     let num_glyphs = u16::try_from(glyf.records.len()).map_err(ParseError::from)?;
-    maxp.num_glyphs = num_glyphs;
 
     // Build new hhea table
     let num_h_metrics = usize::from(hhea.num_h_metrics);
@@ -136,9 +522,7 @@ fn subset_ttf(
 
     // Build the new font
     let mut builder = FontBuilder::new(0x00010000_u32);
-    if let Some(cmap0) = cmap0 {
-        // Build a new cmap table
-        let cmap = create_cmap_table(glyph_ids, cmap0)?;
+    if let Some(cmap) = target.cmap {
         builder.add_table::<_, cmap::owned::Cmap>(tag::CMAP, cmap, ())?;
     }
     if let Some(cvt) = cvt {
@@ -149,23 +533,39 @@ fn subset_ttf(
     }
     builder.add_table::<_, HheaTable>(tag::HHEA, &hhea, ())?;
     builder.add_table::<_, HmtxTable<'_>>(tag::HMTX, &hmtx, ())?;
-    builder.add_table::<_, MaxpTable>(tag::MAXP, &maxp, ())?;
-    if let Some(name) = name {
+    if let Some(name) = &target.opt_name {
+        builder.add_table::<_, NameTableBuilder>(tag::NAME, name, ())?;
+    } else if let Some(name) = name {
         builder.add_table::<_, ReadScope<'_>>(tag::NAME, ReadScope::new(&name), ())?;
     }
+    if let Some(os2) = target.opt_os2 {
+        builder.add_table::<_, Os2>(tag::OS_2, os2, ())?;
+    }
     builder.add_table::<_, PostTable<'_>>(tag::POST, &post, ())?;
     if let Some(prep) = prep {
         builder.add_table::<_, ReadScope<'_>>(tag::PREP, ReadScope::new(&prep), ())?;
     }
-    let mut builder = builder.add_head_table(&head)?;
-    builder.add_glyf_table(glyf)?;
+    add_layout_tables(
+        provider,
+        &mut builder,
+        target.layout_tables,
+        &new_to_old_glyph_id,
+    )?;
+    #[cfg(feature = "bitmap")]
+    if target.bitmap_tables {
+        add_bitmap_tables(provider, &mut builder, &new_to_old_glyph_id)?;
+    }
+    if let Some(drop_tables) = target.opt_drop_tables {
+        builder.copy_tables(provider, drop_tables)?;
+    }
+    let builder = builder.add_packed_glyf_tables(head, maxp, glyf)?;
     builder.data()
 }
 
 fn subset_cff(
     provider: &impl FontTableProvider,
     glyph_ids: &[u16],
-    cmap0: Option<Box<[u8; 256]>>,
+    target: SubsetTarget<'_>,
     convert_cff_to_cid_if_more_than_255_glyphs: bool,
 ) -> Result<Vec<u8>, ReadWriteError> {
     let cff_data = provider.read_table_data(tag::CFF)?;
@@ -188,12 +588,15 @@ fn subset_cff(
     // PostScript data
     let post_data = provider.read_table_data(tag::POST)?;
     let mut post = ReadScope::new(&post_data).read::<PostTable<'_>>()?;
-    post.header.version = 0x00030000; // version 3.0
+    post.header.version = Version16Dot16::new(3, 0);
     post.opt_sub_table = None;
 
     // Build the new CFF table
-    let (cff, new_to_old_glyph_id) =
-        cff.subset(glyph_ids, convert_cff_to_cid_if_more_than_255_glyphs)?;
+    let (cff, new_to_old_glyph_id) = if target.retain_glyph_ids {
+        cff.subset_retaining_ids(glyph_ids, convert_cff_to_cid_if_more_than_255_glyphs)?
+    } else {
+        cff.subset(glyph_ids, convert_cff_to_cid_if_more_than_255_glyphs)?
+    };
 
     // Build new maxp table
     let num_glyphs = u16::try_from(new_to_old_glyph_id.len()).map_err(ParseError::from)?;
@@ -220,9 +623,7 @@ fn subset_cff(
 
     // Build the new font
     let mut builder = FontBuilder::new(tag::OTTO);
-    if let Some(cmap0) = cmap0 {
-        // Build a new cmap table
-        let cmap = create_cmap_table(glyph_ids, cmap0)?;
+    if let Some(cmap) = target.cmap {
         builder.add_table::<_, cmap::owned::Cmap>(tag::CMAP, cmap, ())?;
     }
     if let Some(cvt) = cvt {
@@ -234,15 +635,38 @@ fn subset_cff(
     builder.add_table::<_, HheaTable>(tag::HHEA, &hhea, ())?;
     builder.add_table::<_, HmtxTable<'_>>(tag::HMTX, &hmtx, ())?;
     builder.add_table::<_, MaxpTable>(tag::MAXP, &maxp, ())?;
-    if let Some(name) = name {
+    if let Some(name) = &target.opt_name {
+        builder.add_table::<_, NameTableBuilder>(tag::NAME, name, ())?;
+    } else if let Some(name) = name {
         builder.add_table::<_, ReadScope<'_>>(tag::NAME, ReadScope::new(&name), ())?;
     }
-    builder.add_table::<_, ReadScope<'_>>(tag::OS_2, ReadScope::new(&os_2), ())?;
+    match target.opt_os2 {
+        Some(os2) => builder.add_table::<_, Os2>(tag::OS_2, os2, ())?,
+        None => builder.add_table::<_, ReadScope<'_>>(tag::OS_2, ReadScope::new(&os_2), ())?,
+    }
     builder.add_table::<_, PostTable<'_>>(tag::POST, &post, ())?;
     if let Some(prep) = prep {
         builder.add_table::<_, ReadScope<'_>>(tag::PREP, ReadScope::new(&prep), ())?;
     }
     builder.add_table::<_, CFF<'_>>(tag::CFF, &cff, ())?;
+    if let Some(vorg_data) = provider.table_data(tag::VORG)? {
+        let vorg = ReadScope::new(&vorg_data).read::<VorgTable<'_>>()?;
+        let vorg = vorg.subset(&new_to_old_glyph_id)?;
+        builder.add_table::<_, VorgTable<'_>>(tag::VORG, &vorg, ())?;
+    }
+    add_layout_tables(
+        provider,
+        &mut builder,
+        target.layout_tables,
+        &new_to_old_glyph_id,
+    )?;
+    #[cfg(feature = "bitmap")]
+    if target.bitmap_tables {
+        add_bitmap_tables(provider, &mut builder, &new_to_old_glyph_id)?;
+    }
+    if let Some(drop_tables) = target.opt_drop_tables {
+        builder.copy_tables(provider, drop_tables)?;
+    }
     let builder = builder.add_head_table(&head)?;
     builder.data()
 }
@@ -337,6 +761,121 @@ fn create_cmap_table(
     })
 }
 
+impl<'a> SubsetTarget<'a> {
+    fn new(cmap: Option<cmap::owned::Cmap>) -> Self {
+        SubsetTarget {
+            cmap,
+            opt_os2: None,
+            opt_name: None,
+            retain_glyph_ids: false,
+            layout_tables: LayoutTables::Drop,
+            #[cfg(feature = "bitmap")]
+            bitmap_tables: false,
+            opt_drop_tables: None,
+        }
+    }
+}
+
+/// Add the layout tables to `builder`, either copied as is or subset for the glyphs in
+/// `new_to_old_glyph_id`.
+///
+/// A table that can't be subset is left out of the subset font rather than failing the subset.
+fn add_layout_tables(
+    provider: &impl FontTableProvider,
+    builder: &mut FontBuilder,
+    layout_tables: LayoutTables,
+    new_to_old_glyph_id: &[u16],
+) -> Result<(), ReadWriteError> {
+    if layout_tables == LayoutTables::Drop {
+        return Ok(());
+    }
+    let mut glyph_map = FxHashMap::default();
+    for (new_id, &old_id) in new_to_old_glyph_id.iter().enumerate() {
+        glyph_map.insert(old_id, u16::try_from(new_id).map_err(WriteError::from)?);
+    }
+    for &tag in &[tag::GDEF, tag::GPOS, tag::GSUB] {
+        let data = match provider.table_data(tag)? {
+            Some(data) => data,
+            None => continue,
+        };
+        if layout_tables == LayoutTables::Copy {
+            builder.add_table::<_, ReadScope<'_>>(tag, ReadScope::new(&data), ())?;
+            continue;
+        }
+        let result = match tag {
+            tag::GDEF => layout::subset_gdef_table(&data, &glyph_map).map(Some),
+            _ => layout::subset_layout_table(&data, tag == tag::GPOS, &glyph_map),
+        };
+        match result {
+            Ok(Some(table)) => {
+                builder.add_table::<_, ReadScope<'_>>(tag, ReadScope::new(&table), ())?;
+            }
+            Ok(None) => {}
+            Err(err) => warn!("unable to subset {} table: {}", DisplayTag(tag), err),
+        }
+    }
+    Ok(())
+}
+
+/// Add the embedded bitmap tables to `builder`, subset for the glyphs in `new_to_old_glyph_id`.
+///
+/// As with the layout tables, a table that can't be subset is left out of the subset font rather
+/// than failing the subset.
+#[cfg(feature = "bitmap")]
+fn add_bitmap_tables(
+    provider: &impl FontTableProvider,
+    builder: &mut FontBuilder,
+    new_to_old_glyph_id: &[u16],
+) -> Result<(), ReadWriteError> {
+    for &(location_tag, data_tag) in &[(tag::CBLC, tag::CBDT), (tag::EBLC, tag::EBDT)] {
+        let location_data = provider.table_data(location_tag)?;
+        let bitmap_data = provider.table_data(data_tag)?;
+        let (location_data, bitmap_data) = match (location_data, bitmap_data) {
+            (Some(location_data), Some(bitmap_data)) => (location_data, bitmap_data),
+            _ => continue,
+        };
+        match subset_cblc(&location_data, &bitmap_data, new_to_old_glyph_id) {
+            Ok(Some(CBLCSubset { cblc, cbdt })) => {
+                builder.add_table::<_, ReadScope<'_>>(location_tag, ReadScope::new(&cblc), ())?;
+                builder.add_table::<_, ReadScope<'_>>(data_tag, ReadScope::new(&cbdt), ())?;
+            }
+            Ok(None) => {}
+            Err(err) => warn!(
+                "unable to subset {} table: {}",
+                DisplayTag(location_tag),
+                err
+            ),
+        }
+    }
+
+    if let Some(sbix_data) = provider.table_data(tag::SBIX)? {
+        let maxp = ReadScope::new(&provider.read_table_data(tag::MAXP)?).read::<MaxpTable>()?;
+        let result = ReadScope::new(&sbix_data)
+            .read_dep::<Sbix<'_>>(usize::from(maxp.num_glyphs))
+            .map_err(ReadWriteError::from)
+            .and_then(|sbix| sbix.subset(new_to_old_glyph_id));
+        match result {
+            Ok(sbix) => {
+                builder.add_table::<_, ReadScope<'_>>(tag::SBIX, ReadScope::new(&sbix), ())?
+            }
+            Err(err) => warn!("unable to subset {} table: {}", DisplayTag(tag::SBIX), err),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "bitmap")]
+fn subset_cblc(
+    location_data: &[u8],
+    bitmap_data: &[u8],
+    new_to_old_glyph_id: &[u16],
+) -> Result<Option<CBLCSubset>, ReadWriteError> {
+    let cblc = ReadScope::new(location_data).read::<CBLCTable<'_>>()?;
+    let cbdt = ReadScope::new(bitmap_data).read::<CBDTTable<'_>>()?;
+    cblc.subset(&cbdt, new_to_old_glyph_id)
+}
+
 fn create_hmtx_table<'b>(
     hmtx: &HmtxTable<'_>,
     glyph_count: usize,
@@ -366,169 +905,22 @@ fn create_hmtx_table<'b>(
     })
 }
 
-impl FontBuilder {
-    pub fn new(sfnt_version: u32) -> Self {
-        FontBuilder {
-            sfnt_version,
-            tables: BTreeMap::new(),
-        }
-    }
-
-    pub fn add_table<HostType, T: WriteBinaryDep<HostType>>(
-        &mut self,
-        tag: u32,
-        table: HostType,
-        args: T::Args,
-    ) -> Result<T::Output, ReadWriteError> {
-        assert_ne!(tag, tag::HEAD, "head table must use add_head_table");
-        assert_ne!(tag, tag::GLYF, "glyf table must use add_glyf_table");
-
-        self.add_table_inner::<HostType, T>(tag, table, args)
-    }
-
-    fn add_table_inner<HostType, T: WriteBinaryDep<HostType>>(
-        &mut self,
-        tag: u32,
-        table: HostType,
-        args: T::Args,
-    ) -> Result<T::Output, ReadWriteError> {
-        let mut buffer = WriteBuffer::new();
-        let output = T::write_dep(&mut buffer, table, args)?;
-        self.tables.insert(tag, buffer);
-
-        Ok(output)
-    }
-
-    pub fn add_head_table(
-        mut self,
-        table: &HeadTable,
-    ) -> Result<FontBuilderWithHead, ReadWriteError> {
-        let placeholder = self.add_table_inner::<_, HeadTable>(tag::HEAD, &table, ())?;
-
-        Ok(FontBuilderWithHead {
-            inner: self,
-            check_sum_adjustment: placeholder,
-            index_to_loc_format: table.index_to_loc_format,
-        })
-    }
-}
-
-impl FontBuilderWithHead {
-    pub fn add_glyf_table(&mut self, table: GlyfTable<'_>) -> Result<(), ReadWriteError> {
-        let loca = self.inner.add_table_inner::<_, GlyfTable<'_>>(
-            tag::GLYF,
-            table,
-            self.index_to_loc_format,
-        )?;
-        self.inner.add_table_inner::<_, loca::owned::LocaTable>(
-            tag::LOCA,
-            loca,
-            self.index_to_loc_format,
-        )?;
-
-        Ok(())
-    }
-
-    /// Returns a `Vec<u8>` containing the built font
-    pub fn data(mut self) -> Result<Vec<u8>, ReadWriteError> {
-        let mut font = WriteBuffer::new();
-
-        self.write_offset_table(&mut font)?;
-        let table_offset =
-            long_align(self.inner.tables.len() * TableRecord::SIZE + font.bytes_written());
-
-        // Add tables in desired order
-        let mut ordered_tables = self.write_table_directory(&mut font)?;
-
-        // pad
-        let length = font.bytes_written();
-        let padded_length = long_align(length);
-        assert_eq!(
-            padded_length, table_offset,
-            "offset after writing table directory is not at expected position"
-        );
-        font.write_zeros(padded_length - length)?;
-
-        // Fill in check_sum_adjustment in the head table. the magic number comes from the OpenType spec.
-        let headers_checksum = checksum::table_checksum(font.bytes())?;
-        let checksum = Wrapping(0xB1B0AFBA) - (headers_checksum + ordered_tables.checksum);
-
-        // Write out the font tables
-        let mut placeholder = Some(self.check_sum_adjustment);
-        for TaggedBuffer { tag, buffer } in ordered_tables.tables.iter_mut() {
-            if *tag == tag::HEAD {
-                buffer.write_placeholder(placeholder.take().unwrap(), checksum.0)?;
-            }
-            font.write_bytes(buffer.bytes())?;
-        }
-
-        Ok(font.into_inner())
-    }
-
-    fn write_offset_table(&self, font: &mut WriteBuffer) -> Result<(), WriteError> {
-        let num_tables = u16::try_from(self.inner.tables.len())?;
-        let n = max_power_of_2(num_tables);
-        let search_range = (1 << n) * 16;
-        let entry_selector = n;
-        let range_shift = num_tables * 16 - search_range;
-
-        U32Be::write(font, self.inner.sfnt_version)?;
-        U16Be::write(font, num_tables)?;
-        U16Be::write(font, search_range)?;
-        U16Be::write(font, entry_selector)?;
-        U16Be::write(font, range_shift)?;
-
-        Ok(())
-    }
-
-    fn write_table_directory(
-        &mut self,
-        font: &mut WriteBuffer,
-    ) -> Result<OrderedTables, ReadWriteError> {
-        let mut tables = Vec::with_capacity(self.inner.tables.len());
-        let mut checksum = Wrapping(0);
-        let mut table_offset =
-            long_align(self.inner.tables.len() * TableRecord::SIZE + font.bytes_written());
-
-        let tags = self.inner.tables.keys().cloned().collect_vec();
-        for tag in tags {
-            if let Some(mut table) = self.inner.tables.remove(&tag) {
-                let length = table.len();
-                let padded_length = long_align(length);
-                table.write_zeros(padded_length - length)?;
-
-                let table_checksum = checksum::table_checksum(table.bytes())?;
-                checksum += table_checksum;
-
-                let record = TableRecord {
-                    table_tag: tag,
-                    checksum: table_checksum.0,
-                    offset: u32::try_from(table_offset).map_err(WriteError::from)?,
-                    length: u32::try_from(length).map_err(WriteError::from)?,
-                };
-
-                table_offset += padded_length;
-                TableRecord::write(font, &record)?;
-                tables.push(TaggedBuffer {
-                    tag: tag,
-                    buffer: table,
-                });
-            }
-        }
-
-        Ok(OrderedTables { tables, checksum })
-    }
-}
-
-/// Calculate the maximum power of 2 that is <= num
-fn max_power_of_2(num: u16) -> u16 {
-    15u16.saturating_sub(num.leading_zeros() as u16)
-}
-
 #[cfg(test)]
 mod tests {
+    use std::borrow::Cow;
+
     use super::*;
+    #[cfg(feature = "bitmap")]
+    use crate::bitmap::cbdt;
+    #[cfg(feature = "bitmap")]
+    use crate::bitmap::BitDepth;
+    #[cfg(feature = "shaping")]
+    use crate::font::{Font, MatchingPresentation};
     use crate::font_data::FontData;
+    #[cfg(feature = "shaping")]
+    use crate::gpos::{Attachment, Info};
+    #[cfg(feature = "shaping")]
+    use crate::gsub::Features;
     use crate::tables::glyf::GlyphData;
     use crate::tables::glyf::{
         BoundingBox, CompositeGlyph, CompositeGlyphArgument, CompositeGlyphFlag, GlyfRecord, Glyph,
@@ -653,7 +1045,7 @@ mod tests {
                                 scale: None,
                             },
                         ],
-                        instructions: &[],
+                        instructions: Cow::Borrowed(&[]),
                     },
                 }),
                 GlyfRecord::Parsed(Glyph {
@@ -976,7 +1368,8 @@ mod tests {
         let glyph_ids = [0, 9999];
 
         match subset(&opentype_file.table_provider(0).unwrap(), &glyph_ids, None) {
-            Err(ReadWriteError::Read(ParseError::BadIndex)) => {}
+            Err(ReadWriteError::Read(err))
+                if err.kind() == crate::error::ParseErrorKind::BadIndex => {}
             _ => panic!("expected ReadWriteError::Read(ParseError::BadIndex) got somthing else"),
         }
     }
@@ -1009,15 +1402,463 @@ mod tests {
         assert!(whole_font(&provider, &tags).is_ok());
     }
 
+    fn read_subset_cmap_and_os2(data: &[u8]) -> (u16, Vec<Option<u16>>, Os2) {
+        let font_file = ReadScope::new(data).read::<FontData<'_>>().unwrap();
+        let provider = font_file.table_provider(0).unwrap();
+        let maxp = ReadScope::new(&provider.read_table_data(tag::MAXP).unwrap())
+            .read::<MaxpTable>()
+            .unwrap();
+        let cmap_data = provider.read_table_data(tag::CMAP).unwrap();
+        let cmap = ReadScope::new(&cmap_data).read::<Cmap<'_>>().unwrap();
+        let (_, subtable) = read_cmap_subtable(&cmap).unwrap().unwrap();
+        let glyphs = [u32::from(' '), u32::from('F'), u32::from('j')]
+            .iter()
+            .map(|&ch| subtable.map_glyph(ch).unwrap())
+            .collect();
+        let os2_data = provider.read_table_data(tag::OS_2).unwrap();
+        let os2 = ReadScope::new(&os2_data)
+            .read_dep::<Os2>(os2_data.len())
+            .unwrap();
+        (maxp.num_glyphs, glyphs, os2)
+    }
+
+    #[test]
+    fn test_subset_font_glyphs() {
+        let buffer = read_fixture("tests/fonts/opentype/test-font.ttf");
+        let opentype_file = ReadScope::new(&buffer).read::<OpenTypeFont<'_>>().unwrap();
+        let provider = opentype_file.table_provider(0).unwrap();
+
+        // Glyph 2 is mapped from 'F'
+        let input = SubsetInput::Glyphs(vec![2]);
+        let data = subset_font(&provider, &input, &SubsetOptions::default()).unwrap();
+        let (num_glyphs, glyphs, os2) = read_subset_cmap_and_os2(&data);
+        assert_eq!(num_glyphs, 2);
+        assert_eq!(glyphs, vec![None, Some(1), None]);
+        assert_eq!(os2.us_first_char_index, u16::from(b'F'));
+        assert_eq!(os2.us_last_char_index, u16::from(b'F'));
+    }
+
+    #[test]
+    fn test_subset_font_unicodes() {
+        let buffer = read_fixture("tests/fonts/opentype/Klei.otf");
+        let opentype_file = ReadScope::new(&buffer).read::<OpenTypeFont<'_>>().unwrap();
+        let provider = opentype_file.table_provider(0).unwrap();
+
+        let input = SubsetInput::Unicodes("jiffy".chars().map(u32::from).collect());
+        let options = SubsetOptions {
+            layout_closure: false,
+            ..SubsetOptions::default()
+        };
+        let data = subset_font(&provider, &input, &options).unwrap();
+        let (num_glyphs, glyphs, os2) = read_subset_cmap_and_os2(&data);
+        // .notdef, f, i, j, y
+        assert_eq!(num_glyphs, 5);
+        assert_eq!(glyphs, vec![None, None, Some(3)]);
+        assert_eq!(os2.us_first_char_index, u16::from(b'f'));
+        assert_eq!(os2.us_last_char_index, u16::from(b'y'));
+
+        // The closure retains the ff ligature
+        let data = subset_font(&provider, &input, &SubsetOptions::default()).unwrap();
+        let (num_glyphs, _, _) = read_subset_cmap_and_os2(&data);
+        assert!(num_glyphs > 5);
+    }
+
+    #[test]
+    fn test_subset_variable_font() {
+        let buffer = crate::tests::variable_font();
+        let opentype_file = ReadScope::new(&buffer).read::<OpenTypeFont<'_>>().unwrap();
+        let provider = opentype_file.table_provider(0).unwrap();
+        let input = SubsetInput::Glyphs(vec![0, 2]);
+        let data = subset_font(&provider, &input, &SubsetOptions::default()).unwrap();
+
+        // The variation tables are dropped together, leaving the default instance
+        let font_file = ReadScope::new(&data).read::<OpenTypeFont<'_>>().unwrap();
+        let subset_provider = font_file.table_provider(0).unwrap();
+        let tags = subset_provider.table_tags().unwrap();
+        for tag in &[tag::FVAR, tag::GVAR, tag::STAT] {
+            assert!(!tags.contains(tag), "{} was copied", DisplayTag(*tag));
+        }
+        assert!(tags.contains(&tag::NAME));
+        let glyf_data = subset_provider.read_table_data(tag::GLYF).unwrap();
+        let loca_data = subset_provider.read_table_data(tag::LOCA).unwrap();
+        let head = ReadScope::new(&subset_provider.read_table_data(tag::HEAD).unwrap())
+            .read::<HeadTable>()
+            .unwrap();
+        let loca = ReadScope::new(&loca_data)
+            .read_dep::<LocaTable<'_>>((2, head.index_to_loc_format))
+            .unwrap();
+        let glyf = ReadScope::new(&glyf_data)
+            .read_dep::<GlyfTable<'_>>(&loca)
+            .unwrap();
+        let glyph = glyf.glyph(1).unwrap().unwrap();
+        assert_eq!(glyph.bounding_box.x_min, 1761);
+    }
+
+    #[test]
+    fn test_subset_font_preserve_tables() {
+        let buffer = read_fixture("tests/fonts/gurmukhi/Saab.ttf");
+        let opentype_file = ReadScope::new(&buffer).read::<OpenTypeFont<'_>>().unwrap();
+        let provider = opentype_file.table_provider(0).unwrap();
+        let input = SubsetInput::Glyphs(vec![1, 2, 3]);
+        let subset_tags = |options: &SubsetOptions| {
+            let data = subset_font(&provider, &input, options).unwrap();
+            let font = ReadScope::new(&data).read::<OpenTypeFont<'_>>().unwrap();
+            let provider = font.table_provider(0).unwrap();
+            provider.table_tags().unwrap()
+        };
+
+        // gasp and VDMX are copied, the tables invalidated by subsetting are not
+        let tags = subset_tags(&SubsetOptions::default());
+        assert!(tags.contains(&tag::GASP));
+        assert!(tags.contains(&tag::VDMX));
+        for tag in &DEFAULT_DROP_TABLES {
+            assert!(!tags.contains(tag), "{} was copied", DisplayTag(*tag));
+        }
+
+        let options = SubsetOptions {
+            drop_tables: vec![tag::GASP],
+            ..SubsetOptions::default()
+        };
+        let tags = subset_tags(&options);
+        assert!(!tags.contains(&tag::GASP));
+        assert!(tags.contains(&tag::VDMX));
+        // Glyph dependent tables are dropped even when not listed
+        assert!(!tags.contains(&tag::HDMX));
+
+        let options = SubsetOptions {
+            preserve_tables: false,
+            ..SubsetOptions::default()
+        };
+        let tags = subset_tags(&options);
+        assert!(!tags.contains(&tag::GASP));
+        assert!(!tags.contains(&tag::VDMX));
+    }
+
+    #[cfg(feature = "bitmap")]
+    #[test]
+    fn test_subset_font_bitmap_tables() {
+        let buffer = read_fixture("tests/fonts/opentype/TerminusTTF-4.47.0.ttf");
+        let opentype_file = ReadScope::new(&buffer).read::<OpenTypeFont<'_>>().unwrap();
+        let provider = opentype_file.table_provider(0).unwrap();
+        // Glyph 10 is ampersand
+        let input = SubsetInput::Glyphs(vec![10]);
+
+        let data = subset_font(&provider, &input, &SubsetOptions::default()).unwrap();
+        let font = ReadScope::new(&data).read::<OpenTypeFont<'_>>().unwrap();
+        let subset_provider = font.table_provider(0).unwrap();
+        let eblc_data = subset_provider.read_table_data(tag::EBLC).unwrap();
+        let eblc = ReadScope::new(&eblc_data).read::<CBLCTable<'_>>().unwrap();
+        let ebdt_data = subset_provider.read_table_data(tag::EBDT).unwrap();
+        let ebdt = ReadScope::new(&ebdt_data).read::<CBDTTable<'_>>().unwrap();
+        assert_eq!(eblc.bitmap_sizes.len(), 9);
+        let strike = eblc
+            .find_strike(1, 30, BitDepth::ThirtyTwo)
+            .expect("no matching strike");
+        assert!(cbdt::lookup(1, &strike, &ebdt).unwrap().is_some());
+
+        let options = SubsetOptions {
+            bitmap_tables: false,
+            ..SubsetOptions::default()
+        };
+        let data = subset_font(&provider, &input, &options).unwrap();
+        let font = ReadScope::new(&data).read::<OpenTypeFont<'_>>().unwrap();
+        let subset_provider = font.table_provider(0).unwrap();
+        assert!(!subset_provider.has_table(tag::EBLC));
+        assert!(!subset_provider.has_table(tag::EBDT));
+    }
+
+    #[test]
+    fn test_subset_font_retain_glyph_ids() {
+        let buffer = read_fixture("tests/fonts/opentype/test-font.ttf");
+        let opentype_file = ReadScope::new(&buffer).read::<OpenTypeFont<'_>>().unwrap();
+        let provider = opentype_file.table_provider(0).unwrap();
+
+        let input = SubsetInput::Glyphs(vec![2]);
+        let options = SubsetOptions {
+            retain_glyph_ids: true,
+            ..SubsetOptions::default()
+        };
+        let data = subset_font(&provider, &input, &options).unwrap();
+        let (num_glyphs, glyphs, _) = read_subset_cmap_and_os2(&data);
+        assert_eq!(num_glyphs, 3);
+        assert_eq!(glyphs, vec![None, Some(2), None]);
+
+        let font_file = ReadScope::new(&data).read::<FontData<'_>>().unwrap();
+        let provider = font_file.table_provider(0).unwrap();
+        let head = ReadScope::new(&provider.read_table_data(tag::HEAD).unwrap())
+            .read::<HeadTable>()
+            .unwrap();
+        let loca_data = provider.read_table_data(tag::LOCA).unwrap();
+        let loca = ReadScope::new(&loca_data)
+            .read_dep::<LocaTable<'_>>((3, head.index_to_loc_format))
+            .unwrap();
+        let glyf_data = provider.read_table_data(tag::GLYF).unwrap();
+        let glyf = ReadScope::new(&glyf_data)
+            .read_dep::<GlyfTable<'_>>(&loca)
+            .unwrap();
+        assert!(glyf.glyph(1).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_subset_font_retain_glyph_ids_cff() {
+        let buffer = read_fixture("tests/fonts/opentype/Klei.otf");
+        let opentype_file = ReadScope::new(&buffer).read::<OpenTypeFont<'_>>().unwrap();
+        let provider = opentype_file.table_provider(0).unwrap();
+
+        let input = SubsetInput::Unicodes(std::iter::once(u32::from('j')).collect());
+        let options = SubsetOptions {
+            layout_closure: false,
+            retain_glyph_ids: true,
+            ..SubsetOptions::default()
+        };
+        let data = subset_font(&provider, &input, &options).unwrap();
+        let (num_glyphs, glyphs, _) = read_subset_cmap_and_os2(&data);
+        assert_eq!(num_glyphs, 76);
+        assert_eq!(glyphs, vec![None, None, Some(75)]);
+
+        let font_file = ReadScope::new(&data).read::<FontData<'_>>().unwrap();
+        let provider = font_file.table_provider(0).unwrap();
+        assert!(provider.has_table(tag::GSUB));
+        assert!(provider.has_table(tag::GPOS));
+    }
+
+    #[test]
+    fn test_subset_font_vorg() {
+        let buffer = read_fixture("tests/fonts/noto/NotoSansJP-Regular.otf");
+        let opentype_file = ReadScope::new(&buffer).read::<OpenTypeFont<'_>>().unwrap();
+        let provider = opentype_file.table_provider(0).unwrap();
+
+        let input = SubsetInput::Glyphs(vec![6896, 6897, 8140]);
+        let options = SubsetOptions {
+            layout_closure: false,
+            ..SubsetOptions::default()
+        };
+        let data = subset_font(&provider, &input, &options).unwrap();
+
+        let font_file = ReadScope::new(&data).read::<FontData<'_>>().unwrap();
+        let provider = font_file.table_provider(0).unwrap();
+        let vorg_data = provider.read_table_data(tag::VORG).unwrap();
+        let vorg = ReadScope::new(&vorg_data).read::<VorgTable<'_>>().unwrap();
+        assert_eq!(vorg.default_vert_origin_y, 880);
+        assert_eq!(vorg.vert_origin_y(0), 880);
+        assert_eq!(vorg.vert_origin_y(1), 860);
+        assert_eq!(vorg.vert_origin_y(2), 880);
+        assert_eq!(vorg.vert_origin_y(3), 1880);
+    }
+
+    #[cfg(feature = "shaping")]
+    #[test]
+    fn test_subset_font_layout_tables() {
+        let buffer = read_fixture("tests/fonts/opentype/Klei.otf");
+        let opentype_file = ReadScope::new(&buffer).read::<OpenTypeFont<'_>>().unwrap();
+        let provider = opentype_file.table_provider(0).unwrap();
+
+        let shape = |data: &[u8]| {
+            let font_file = ReadScope::new(data).read::<FontData<'_>>().unwrap();
+            let provider = font_file.table_provider(0).unwrap();
+            let mut font = Font::new(provider).unwrap().unwrap();
+            let glyphs = font.map_glyphs("jiffy", MatchingPresentation::NotRequired);
+            font.shape(
+                glyphs,
+                tag::LATN,
+                None,
+                &Features::Settings(Vec::new()),
+                true,
+            )
+            .unwrap()
+            .len()
+        };
+
+        // The ff ligature is retained by the closure and still applied after subsetting
+        let input = SubsetInput::Unicodes("jiffy".chars().map(u32::from).collect());
+        let data = subset_font(&provider, &input, &SubsetOptions::default()).unwrap();
+        assert_eq!(shape(&data), 4);
+
+        let options = SubsetOptions {
+            layout_tables: false,
+            ..SubsetOptions::default()
+        };
+        let data = subset_font(&provider, &input, &options).unwrap();
+        assert_eq!(shape(&data), 5);
+    }
+
+    /// Shape `text` with the font at `path` and with a subset of it for the characters of `text`,
+    /// and check that each glyph is positioned the same way in both.
+    ///
+    /// Returns the glyphs shaped with the original font along with the data of both fonts.
+    #[cfg(feature = "shaping")]
+    fn assert_subset_positions(
+        path: &str,
+        text: &str,
+        script_tag: u32,
+    ) -> (Vec<Info>, Vec<u8>, Vec<u8>) {
+        let shape = |data: &[u8]| {
+            let font_file = ReadScope::new(data).read::<FontData<'_>>().unwrap();
+            let provider = font_file.table_provider(0).unwrap();
+            let mut font = Font::new(provider).unwrap().unwrap();
+            let glyphs = font.map_glyphs(text, MatchingPresentation::NotRequired);
+            let infos = font
+                .shape(
+                    glyphs,
+                    script_tag,
+                    None,
+                    &Features::Settings(Vec::new()),
+                    true,
+                )
+                .unwrap();
+            // Glyph ids change when subsetting, so glyphs are identified by their characters
+            let positions = infos
+                .iter()
+                .map(|info| {
+                    format!(
+                        "{:?} {:?} {} {:?} {:?}",
+                        info.glyph.unicodes,
+                        font.horizontal_advance(info.glyph.glyph_index),
+                        info.kerning,
+                        info.placement,
+                        info.attachment
+                    )
+                })
+                .collect::<Vec<_>>();
+            (infos, positions)
+        };
+
+        let buffer = read_fixture(path);
+        let opentype_file = ReadScope::new(&buffer).read::<OpenTypeFont<'_>>().unwrap();
+        let provider = opentype_file.table_provider(0).unwrap();
+        let input = SubsetInput::Unicodes(text.chars().map(u32::from).collect());
+        let data = subset_font(&provider, &input, &SubsetOptions::default()).unwrap();
+
+        let (infos, positions) = shape(&buffer);
+        let (_, subset_positions) = shape(&data);
+        assert_eq!(subset_positions, positions);
+        (infos, buffer, data)
+    }
+
+    /// The number of lookups in the `GSUB` or `GPOS` table `tag` of the font in `data`.
+    #[cfg(feature = "shaping")]
+    fn lookup_count(data: &[u8], tag: u32) -> u16 {
+        let font_file = ReadScope::new(data).read::<FontData<'_>>().unwrap();
+        let provider = font_file.table_provider(0).unwrap();
+        let table = provider.read_table_data(tag).unwrap();
+        let scope = ReadScope::new(&table);
+        let lookup_list_offset = scope.offset(8).ctxt().read_u16be().unwrap();
+        scope
+            .offset(usize::from(lookup_list_offset))
+            .ctxt()
+            .read_u16be()
+            .unwrap()
+    }
+
+    #[cfg(feature = "shaping")]
+    #[test]
+    fn test_subset_gpos_pair_pos() {
+        // Klei kerns with both glyph pairs (format 1) and class pairs (format 2)
+        let (infos, _, _) =
+            assert_subset_positions("tests/fonts/opentype/Klei.otf", "AVATAR", tag::LATN);
+        assert!(infos.iter().any(|info| info.kerning != 0));
+    }
+
+    #[cfg(feature = "shaping")]
+    #[test]
+    fn test_subset_gpos_mark_pos() {
+        // Scheherazade has mark-to-base and mark-to-mark attachment, with lookups that filter
+        // marks by GDEF mark attachment class and mark glyph set, and chained contextual
+        // positioning lookups that apply nested lookups.
+        let (infos, buffer, data) = assert_subset_positions(
+            "tests/fonts/arabic/Scheherazade-Regular.ttf",
+            "بِسْمِ ٱللَّهِ ٱلرَّحْمَٰنِ",
+            tag::ARAB,
+        );
+        assert!(infos.iter().any(|info| match info.attachment {
+            Attachment::MarkAnchor(..) => true,
+            _ => false,
+        }));
+        // Lookups for glyphs outside the subset are dropped, and the remaining lookups renumbered
+        assert!(lookup_count(&data, tag::GPOS) < lookup_count(&buffer, tag::GPOS));
+        assert!(lookup_count(&data, tag::GSUB) < lookup_count(&buffer, tag::GSUB));
+    }
+
+    #[cfg(feature = "shaping")]
+    #[test]
+    fn test_subset_gpos_cursive_pos() {
+        let (infos, _, _) = assert_subset_positions(
+            "tests/fonts/arabic/NafeesNastaleeq.ttf",
+            "بہت پیار",
+            tag::ARAB,
+        );
+        assert!(infos.iter().any(|info| match info.attachment {
+            Attachment::CursiveAnchor(..) => true,
+            _ => false,
+        }));
+    }
+
+    #[cfg(feature = "shaping")]
+    #[test]
+    fn test_subset_gpos_mark_lig_pos() {
+        // The lam-alef ligature carries a mark on each of its components
+        let (infos, _, _) = assert_subset_positions(
+            "tests/fonts/noto/NotoNaskhArabic-Regular.ttf",
+            "لَاُ لِأَ",
+            tag::ARAB,
+        );
+        assert!(infos.iter().any(|info| match info.attachment {
+            Attachment::MarkAnchor(..) => true,
+            _ => false,
+        }));
+    }
+
+    #[cfg(feature = "shaping")]
+    #[test]
+    fn test_subset_contextual_lookups() {
+        // Noto Sans Devanagari reorders and forms conjuncts with contextual substitutions and
+        // positions marks with chained contextual positioning, which apply nested lookups that
+        // are renumbered by subsetting.
+        let (_, buffer, data) = assert_subset_positions(
+            "tests/fonts/noto/NotoSansDevanagari-Regular.ttf",
+            "क्षत्रिय हिन्दी र्कि",
+            tag::DEVA,
+        );
+        assert!(lookup_count(&data, tag::GSUB) < lookup_count(&buffer, tag::GSUB));
+    }
+
+    #[test]
+    fn test_subset_for_pdf() {
+        let buffer = read_fixture("tests/fonts/opentype/test-font.ttf");
+        let opentype_file = ReadScope::new(&buffer).read::<OpenTypeFont<'_>>().unwrap();
+        let provider = opentype_file.table_provider(0).unwrap();
+
+        let pdf_subset = subset_for_pdf(&provider, &[2]).unwrap();
+        assert_eq!(pdf_subset.cid_to_gid_map, vec![0, 0, 0, 0, 0, 1]);
+        assert!(pdf_subset
+            .to_unicode
+            .contains("1 beginbfchar\n<0002> <0046>\nendbfchar"));
+
+        let original_name = postscript_name(&provider).unwrap().unwrap();
+        let (tag, name) = pdf_subset.postscript_name.split_at(7);
+        assert!(tag[..6].bytes().all(|b| b.is_ascii_uppercase()));
+        assert_eq!(&tag[6..], "+");
+        assert_eq!(name, original_name);
+
+        let font_file = ReadScope::new(&pdf_subset.font)
+            .read::<FontData<'_>>()
+            .unwrap();
+        let subset_provider = font_file.table_provider(0).unwrap();
+        assert_eq!(
+            postscript_name(&subset_provider).unwrap().unwrap(),
+            pdf_subset.postscript_name
+        );
+
+        // The tag only depends on the glyphs
+        let again = subset_for_pdf(&provider, &[2, 0]).unwrap();
+        assert_eq!(again.postscript_name, pdf_subset.postscript_name);
+        let other = subset_for_pdf(&provider, &[1, 2]).unwrap();
+        assert_ne!(other.postscript_name, pdf_subset.postscript_name);
+    }
+
     #[test]
-    fn test_max_power_of_2() {
-        assert_eq!(max_power_of_2(0), 0);
-        assert_eq!(max_power_of_2(1), 0);
-        assert_eq!(max_power_of_2(2), 1);
-        assert_eq!(max_power_of_2(4), 2);
-        assert_eq!(max_power_of_2(8), 3);
-        assert_eq!(max_power_of_2(16), 4);
-        assert_eq!(max_power_of_2(49), 5);
-        assert_eq!(max_power_of_2(std::u16::MAX), 15);
+    fn test_to_unicode_cmap_surrogates() {
+        let cmap = to_unicode_cmap(&[(3, 'A'), (7, '\u{1F600}')]);
+        assert!(cmap.contains("2 beginbfchar\n<0003> <0041>\n<0007> <D83DDE00>\nendbfchar\n"));
     }
 }
@@ -0,0 +1,1910 @@
+//! Subsetting of the `GDEF`, `GPOS` and `GSUB` tables.
+//!
+//! The tables are read at the binary level and rebuilt for the glyphs that are retained. Lookup
+//! subtables that no longer apply to any retained glyph are dropped, followed by lookups without
+//! subtables, features without lookups and language systems without features. Coverage and class
+//! definition tables are rebuilt for the new glyph ids.
+//!
+//! Device and variation index tables are not retained, anchors are written in format 1, and the
+//! `GDEF` attachment point and ligature caret lists, item variation store and `FeatureVariations`
+//! are dropped.
+
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+
+use rustc_hash::FxHashMap;
+
+use crate::binary::read::{ReadCtxt, ReadScope};
+use crate::binary::U16Be;
+use crate::error::{ParseError, ReadWriteError, WriteError};
+
+const GSUB_EXTENSION: u16 = 7;
+const GPOS_EXTENSION: u16 = 9;
+const USE_MARK_FILTERING_SET: u16 = 0x0010;
+const NO_REQUIRED_FEATURE: u16 = 0xFFFF;
+/// The non-device fields of a `ValueRecord`.
+const VALUE_FORMAT_VALUES: u16 = 0x000F;
+
+/// Identifies an `Object` in a `Graph`.
+type ObjectId = usize;
+
+/// A table being built, which may refer to other tables by offset.
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+struct Object {
+    data: Vec<u8>,
+    links: Vec<Link>,
+}
+
+/// An offset at `position` in an `Object` to another `Object`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct Link {
+    position: usize,
+    width: usize,
+    target: ObjectId,
+}
+
+/// A set of `Object`s that is packed into a single table.
+///
+/// Identical objects are only stored once.
+#[derive(Default)]
+struct Graph {
+    objects: Vec<Object>,
+    ids: FxHashMap<Object, ObjectId>,
+}
+
+impl Object {
+    fn new() -> Self {
+        Object::default()
+    }
+
+    fn u16(&mut self, value: u16) -> &mut Self {
+        self.data.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    fn i16(&mut self, value: i16) -> &mut Self {
+        self.data.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    fn u32(&mut self, value: u32) -> &mut Self {
+        self.data.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    fn u16_len(&mut self, len: usize) -> Result<&mut Self, WriteError> {
+        Ok(self.u16(u16::try_from(len)?))
+    }
+
+    fn offset(&mut self, width: usize, opt_target: Option<ObjectId>) -> &mut Self {
+        if let Some(target) = opt_target {
+            self.links.push(Link {
+                position: self.data.len(),
+                width,
+                target,
+            });
+        }
+        self.data.resize(self.data.len() + width, 0);
+        self
+    }
+
+    fn offset16(&mut self, opt_target: Option<ObjectId>) -> &mut Self {
+        self.offset(2, opt_target)
+    }
+
+    fn offset32(&mut self, opt_target: Option<ObjectId>) -> &mut Self {
+        self.offset(4, opt_target)
+    }
+}
+
+impl Graph {
+    fn add(&mut self, object: Object) -> ObjectId {
+        if let Some(&id) = self.ids.get(&object) {
+            return id;
+        }
+        let id = self.objects.len();
+        self.objects.push(object.clone());
+        self.ids.insert(object, id);
+        id
+    }
+
+    /// Lay out the objects reachable from `root` so that every object follows the objects that
+    /// refer to it, and resolve the offsets between them.
+    ///
+    /// Objects are placed breadth first, which keeps the tables that are close to the root, and
+    /// so hold the most offsets, close together.
+    fn pack(&self, root: ObjectId) -> Result<Vec<u8>, WriteError> {
+        let mut in_degree = vec![0usize; self.objects.len()];
+        let mut reachable = vec![false; self.objects.len()];
+        let mut stack = vec![root];
+        reachable[root] = true;
+        while let Some(id) = stack.pop() {
+            for link in &self.objects[id].links {
+                in_degree[link.target] += 1;
+                if !reachable[link.target] {
+                    reachable[link.target] = true;
+                    stack.push(link.target);
+                }
+            }
+        }
+
+        let mut order = Vec::new();
+        let mut queue = VecDeque::new();
+        queue.push_back(root);
+        while let Some(id) = queue.pop_front() {
+            order.push(id);
+            for link in &self.objects[id].links {
+                in_degree[link.target] -= 1;
+                if in_degree[link.target] == 0 {
+                    queue.push_back(link.target);
+                }
+            }
+        }
+
+        let mut positions = vec![0; self.objects.len()];
+        let mut len = 0;
+        for &id in &order {
+            positions[id] = len;
+            len += self.objects[id].data.len();
+        }
+
+        let mut data = Vec::with_capacity(len);
+        for &id in &order {
+            let object = &self.objects[id];
+            let start = data.len();
+            data.extend_from_slice(&object.data);
+            for link in &object.links {
+                let offset = positions[link.target] - positions[id];
+                let position = start + link.position;
+                if link.width == 2 {
+                    let offset = u16::try_from(offset)?;
+                    data[position..position + 2].copy_from_slice(&offset.to_be_bytes());
+                } else {
+                    let offset = u32::try_from(offset)?;
+                    data[position..position + 4].copy_from_slice(&offset.to_be_bytes());
+                }
+            }
+        }
+        Ok(data)
+    }
+}
+
+/// Rebuild the `GSUB` or `GPOS` table in `data` for the glyphs in `glyph_map`, which maps the
+/// glyph ids of the retained glyphs to their ids in the subset font.
+///
+/// Returns `None` if no lookups remain.
+pub(crate) fn subset_layout_table(
+    data: &[u8],
+    is_gpos: bool,
+    glyph_map: &FxHashMap<u16, u16>,
+) -> Result<Option<Vec<u8>>, ReadWriteError> {
+    let layout = match subset_layout(data, is_gpos, glyph_map)? {
+        Some(layout) => layout,
+        None => return Ok(None),
+    };
+    match layout.build(false) {
+        Ok(data) => Ok(Some(data)),
+        // Lookup subtables are too far from their lookups for 16-bit offsets, so wrap them in
+        // extension subtables with 32-bit offsets.
+        Err(_) => Ok(Some(layout.build(true)?)),
+    }
+}
+
+/// Subset the script, feature and lookup lists of the `GSUB` or `GPOS` table in `data`.
+fn subset_layout(
+    data: &[u8],
+    is_gpos: bool,
+    glyph_map: &FxHashMap<u16, u16>,
+) -> Result<Option<SubsetLayout>, ReadWriteError> {
+    let scope = ReadScope::new(data);
+    let mut ctxt = scope.ctxt();
+    let major_version = ctxt.read_u16be()?;
+    let _minor_version = ctxt.read_u16be()?;
+    if major_version != 1 {
+        return Err(ReadWriteError::Read(ParseError::BadVersion));
+    }
+    let script_list_offset = usize::from(ctxt.read_u16be()?);
+    let feature_list_offset = usize::from(ctxt.read_u16be()?);
+    let lookup_list_offset = usize::from(ctxt.read_u16be()?);
+
+    let lookup_list_scope = scope.offset(lookup_list_offset);
+    let mut lookup_ctxt = lookup_list_scope.ctxt();
+    let lookup_count = usize::from(lookup_ctxt.read_u16be()?);
+    let lookup_offsets = read_u16s(&mut lookup_ctxt, lookup_count)?;
+
+    // Whether a lookup survives only depends on the glyphs it applies to, so the lookups are
+    // subset once to find the new lookup indices, and again to rewrite the nested lookup indices
+    // of contextual lookups.
+    let mut subsetter = LayoutSubsetter {
+        glyph_map,
+        opt_lookup_map: None,
+        is_gpos,
+    };
+    let mut lookup_map = Vec::with_capacity(lookup_count);
+    let mut next_lookup_index = 0u16;
+    for &offset in &lookup_offsets {
+        let lookup_scope = lookup_list_scope.offset(usize::from(offset));
+        if subsetter.subset_lookup(lookup_scope)?.is_some() {
+            lookup_map.push(Some(next_lookup_index));
+            next_lookup_index = next_lookup_index
+                .checked_add(1)
+                .ok_or(WriteError::BadValue)?;
+        } else {
+            lookup_map.push(None);
+        }
+    }
+    if next_lookup_index == 0 {
+        return Ok(None);
+    }
+    subsetter.opt_lookup_map = Some(&lookup_map[..]);
+    let mut lookups = Vec::new();
+    for &offset in &lookup_offsets {
+        let lookup_scope = lookup_list_scope.offset(usize::from(offset));
+        if let Some(lookup) = subsetter.subset_lookup(lookup_scope)? {
+            lookups.push(lookup);
+        }
+    }
+
+    let (features, feature_map) =
+        subset_feature_list(scope.offset(feature_list_offset), &lookup_map)?;
+    let scripts = subset_script_list(scope.offset(script_list_offset), &feature_map)?;
+
+    Ok(Some(SubsetLayout {
+        is_gpos,
+        scripts,
+        features,
+        lookups,
+    }))
+}
+
+/// Rebuild the `GDEF` table in `data` for the glyphs in `glyph_map`.
+pub(crate) fn subset_gdef_table(
+    data: &[u8],
+    glyph_map: &FxHashMap<u16, u16>,
+) -> Result<Vec<u8>, ReadWriteError> {
+    let scope = ReadScope::new(data);
+    let mut ctxt = scope.ctxt();
+    let major_version = ctxt.read_u16be()?;
+    let minor_version = ctxt.read_u16be()?;
+    if major_version != 1 {
+        return Err(ReadWriteError::Read(ParseError::BadVersion));
+    }
+    let glyph_class_def_offset = ctxt.read_u16be()?;
+    let _attach_list_offset = ctxt.read_u16be()?;
+    let _lig_caret_list_offset = ctxt.read_u16be()?;
+    let mark_attach_class_def_offset = ctxt.read_u16be()?;
+    let mark_glyph_sets_def_offset = if minor_version >= 2 {
+        ctxt.read_u16be()?
+    } else {
+        0
+    };
+
+    let subsetter = LayoutSubsetter {
+        glyph_map,
+        opt_lookup_map: None,
+        is_gpos: false,
+    };
+    let mut graph = Graph::default();
+    let glyph_class_def = subsetter.subset_class_def(&mut graph, &scope, glyph_class_def_offset)?;
+    let mark_attach_class_def =
+        subsetter.subset_class_def(&mut graph, &scope, mark_attach_class_def_offset)?;
+
+    // Mark glyph sets are referred to by index from lookups, so empty sets are kept.
+    let mark_glyph_sets_def = if mark_glyph_sets_def_offset != 0 {
+        let sets_scope = scope.offset(usize::from(mark_glyph_sets_def_offset));
+        let mut sets_ctxt = sets_scope.ctxt();
+        let format = sets_ctxt.read_u16be()?;
+        if format != 1 {
+            return Err(ReadWriteError::Read(ParseError::BadVersion));
+        }
+        let count = usize::from(sets_ctxt.read_u16be()?);
+        let mut sets = Object::new();
+        sets.u16(1).u16_len(count)?;
+        for _ in 0..count {
+            let offset = sets_ctxt.read_u32be()?;
+            let offset = usize::try_from(offset).map_err(ParseError::from)?;
+            let glyphs = read_coverage(sets_scope.offset(offset))?;
+            let mut glyphs = glyphs
+                .iter()
+                .filter_map(|&glyph| subsetter.glyph(glyph))
+                .collect::<Vec<_>>();
+            glyphs.sort();
+            let coverage = write_coverage(&mut graph, &glyphs)?;
+            sets.offset32(Some(coverage));
+        }
+        Some(graph.add(sets))
+    } else {
+        None
+    };
+
+    let mut gdef = Object::new();
+    gdef.u16(1)
+        .u16(if mark_glyph_sets_def.is_some() { 2 } else { 0 })
+        .offset16(glyph_class_def)
+        .offset16(None)
+        .offset16(None)
+        .offset16(mark_attach_class_def);
+    if mark_glyph_sets_def.is_some() {
+        gdef.offset16(mark_glyph_sets_def);
+    }
+    let root = graph.add(gdef);
+    Ok(graph.pack(root)?)
+}
+
+/// The subset script, feature and lookup lists of a `GSUB` or `GPOS` table.
+struct SubsetLayout {
+    is_gpos: bool,
+    scripts: Vec<SubsetScript>,
+    features: Vec<SubsetFeature>,
+    lookups: Vec<SubsetLookup>,
+}
+
+/// A lookup that has been subset, with each subtable packed on its own.
+struct SubsetLookup {
+    lookup_type: u16,
+    lookup_flag: u16,
+    subtables: Vec<Vec<u8>>,
+    mark_filtering_set: Option<u16>,
+}
+
+struct SubsetFeature {
+    tag: u32,
+    params: Option<Vec<u8>>,
+    lookup_indices: Vec<u16>,
+}
+
+struct SubsetLangSys {
+    required_feature_index: u16,
+    feature_indices: Vec<u16>,
+}
+
+struct SubsetScript {
+    tag: u32,
+    default_lang_sys: Option<SubsetLangSys>,
+    lang_sys_records: Vec<(u32, SubsetLangSys)>,
+}
+
+impl SubsetLayout {
+    /// Pack the table, wrapping every lookup subtable in an extension subtable if
+    /// `use_extensions` is set.
+    fn build(&self, use_extensions: bool) -> Result<Vec<u8>, WriteError> {
+        let opt_extension_type = match (use_extensions, self.is_gpos) {
+            (false, _) => None,
+            (true, true) => Some(GPOS_EXTENSION),
+            (true, false) => Some(GSUB_EXTENSION),
+        };
+        build_layout_table(
+            &self.scripts,
+            &self.features,
+            &self.lookups,
+            opt_extension_type,
+        )
+    }
+}
+
+fn build_layout_table(
+    scripts: &[SubsetScript],
+    features: &[SubsetFeature],
+    lookups: &[SubsetLookup],
+    opt_extension_type: Option<u16>,
+) -> Result<Vec<u8>, WriteError> {
+    let mut graph = Graph::default();
+
+    let mut script_list = Object::new();
+    script_list.u16_len(scripts.len())?;
+    for script in scripts {
+        let mut script_table = Object::new();
+        let default_lang_sys = match &script.default_lang_sys {
+            Some(lang_sys) => Some(add_lang_sys(&mut graph, lang_sys)?),
+            None => None,
+        };
+        script_table
+            .offset16(default_lang_sys)
+            .u16_len(script.lang_sys_records.len())?;
+        for (tag, lang_sys) in &script.lang_sys_records {
+            let lang_sys = add_lang_sys(&mut graph, lang_sys)?;
+            script_table.u32(*tag).offset16(Some(lang_sys));
+        }
+        let script_table = graph.add(script_table);
+        script_list.u32(script.tag).offset16(Some(script_table));
+    }
+    let script_list = graph.add(script_list);
+
+    let mut feature_list = Object::new();
+    feature_list.u16_len(features.len())?;
+    for feature in features {
+        let params = feature.params.as_ref().map(|params| {
+            graph.add(Object {
+                data: params.clone(),
+                links: Vec::new(),
+            })
+        });
+        let mut feature_table = Object::new();
+        feature_table
+            .offset16(params)
+            .u16_len(feature.lookup_indices.len())?;
+        for &lookup_index in &feature.lookup_indices {
+            feature_table.u16(lookup_index);
+        }
+        let feature_table = graph.add(feature_table);
+        feature_list.u32(feature.tag).offset16(Some(feature_table));
+    }
+    let feature_list = graph.add(feature_list);
+
+    let mut lookup_list = Object::new();
+    lookup_list.u16_len(lookups.len())?;
+    for lookup in lookups {
+        let mut lookup_table = Object::new();
+        let lookup_type = opt_extension_type.unwrap_or(lookup.lookup_type);
+        lookup_table
+            .u16(lookup_type)
+            .u16(lookup.lookup_flag)
+            .u16_len(lookup.subtables.len())?;
+        for subtable in &lookup.subtables {
+            let subtable = graph.add(Object {
+                data: subtable.clone(),
+                links: Vec::new(),
+            });
+            match opt_extension_type {
+                Some(_) => {
+                    let mut extension = Object::new();
+                    extension
+                        .u16(1)
+                        .u16(lookup.lookup_type)
+                        .offset32(Some(subtable));
+                    let extension = graph.add(extension);
+                    lookup_table.offset16(Some(extension));
+                }
+                None => {
+                    lookup_table.offset16(Some(subtable));
+                }
+            }
+        }
+        if let Some(mark_filtering_set) = lookup.mark_filtering_set {
+            lookup_table.u16(mark_filtering_set);
+        }
+        let lookup_table = graph.add(lookup_table);
+        lookup_list.offset16(Some(lookup_table));
+    }
+    let lookup_list = graph.add(lookup_list);
+
+    let mut header = Object::new();
+    header
+        .u16(1)
+        .u16(0)
+        .offset16(Some(script_list))
+        .offset16(Some(feature_list))
+        .offset16(Some(lookup_list));
+    let root = graph.add(header);
+    graph.pack(root)
+}
+
+fn add_lang_sys(graph: &mut Graph, lang_sys: &SubsetLangSys) -> Result<ObjectId, WriteError> {
+    let mut table = Object::new();
+    table
+        .offset16(None)
+        .u16(lang_sys.required_feature_index)
+        .u16_len(lang_sys.feature_indices.len())?;
+    for &feature_index in &lang_sys.feature_indices {
+        table.u16(feature_index);
+    }
+    Ok(graph.add(table))
+}
+
+/// Subset the `FeatureList` table, returning the features that still have lookups and the new
+/// index of each feature.
+fn subset_feature_list(
+    scope: ReadScope<'_>,
+    lookup_map: &[Option<u16>],
+) -> Result<(Vec<SubsetFeature>, Vec<Option<u16>>), ReadWriteError> {
+    let mut ctxt = scope.ctxt();
+    let feature_count = usize::from(ctxt.read_u16be()?);
+    let mut features = Vec::new();
+    let mut feature_map = Vec::with_capacity(feature_count);
+    for _ in 0..feature_count {
+        let tag = ctxt.read_u32be()?;
+        let offset = usize::from(ctxt.read_u16be()?);
+        let feature_scope = scope.offset(offset);
+        let mut feature_ctxt = feature_scope.ctxt();
+        let params_offset = usize::from(feature_ctxt.read_u16be()?);
+        let lookup_count = usize::from(feature_ctxt.read_u16be()?);
+        let lookup_indices = read_u16s(&mut feature_ctxt, lookup_count)?
+            .into_iter()
+            .filter_map(|index| lookup_map.get(usize::from(index)).and_then(|index| *index))
+            .collect::<Vec<_>>();
+        if lookup_indices.is_empty() {
+            feature_map.push(None);
+            continue;
+        }
+        feature_map.push(Some(
+            u16::try_from(features.len()).map_err(WriteError::from)?,
+        ));
+        features.push(SubsetFeature {
+            tag,
+            params: feature_params(tag, feature_scope, params_offset)?,
+            lookup_indices,
+        });
+    }
+    Ok((features, feature_map))
+}
+
+/// The feature parameters of the stylistic set features, which hold the name id of the UI name
+/// of the feature. Parameters of other features are dropped.
+fn feature_params(
+    tag: u32,
+    feature_scope: ReadScope<'_>,
+    params_offset: usize,
+) -> Result<Option<Vec<u8>>, ParseError> {
+    let [s, s2, _, _] = tag.to_be_bytes();
+    if params_offset == 0 || (s, s2) != (b's', b's') {
+        return Ok(None);
+    }
+    let params = feature_scope.offset_length(params_offset, 4)?;
+    Ok(Some(params.data().to_vec()))
+}
+
+fn subset_script_list(
+    scope: ReadScope<'_>,
+    feature_map: &[Option<u16>],
+) -> Result<Vec<SubsetScript>, ParseError> {
+    let mut ctxt = scope.ctxt();
+    let script_count = usize::from(ctxt.read_u16be()?);
+    let mut scripts = Vec::new();
+    for _ in 0..script_count {
+        let tag = ctxt.read_u32be()?;
+        let script_scope = scope.offset(usize::from(ctxt.read_u16be()?));
+        let mut script_ctxt = script_scope.ctxt();
+        let default_lang_sys_offset = usize::from(script_ctxt.read_u16be()?);
+        let default_lang_sys = if default_lang_sys_offset != 0 {
+            subset_lang_sys(script_scope.offset(default_lang_sys_offset), feature_map)?
+        } else {
+            None
+        };
+        let lang_sys_count = usize::from(script_ctxt.read_u16be()?);
+        let mut lang_sys_records = Vec::new();
+        for _ in 0..lang_sys_count {
+            let lang_sys_tag = script_ctxt.read_u32be()?;
+            let offset = usize::from(script_ctxt.read_u16be()?);
+            if let Some(lang_sys) = subset_lang_sys(script_scope.offset(offset), feature_map)? {
+                lang_sys_records.push((lang_sys_tag, lang_sys));
+            }
+        }
+        if default_lang_sys.is_some() || !lang_sys_records.is_empty() {
+            scripts.push(SubsetScript {
+                tag,
+                default_lang_sys,
+                lang_sys_records,
+            });
+        }
+    }
+    Ok(scripts)
+}
+
+fn subset_lang_sys(
+    scope: ReadScope<'_>,
+    feature_map: &[Option<u16>],
+) -> Result<Option<SubsetLangSys>, ParseError> {
+    let mut ctxt = scope.ctxt();
+    let _lookup_order = ctxt.read_u16be()?;
+    let required_feature_index = ctxt.read_u16be()?;
+    let feature_count = usize::from(ctxt.read_u16be()?);
+    let map_feature = |index: u16| feature_map.get(usize::from(index)).and_then(|index| *index);
+    let required_feature_index = map_feature(required_feature_index).unwrap_or(NO_REQUIRED_FEATURE);
+    let feature_indices = read_u16s(&mut ctxt, feature_count)?
+        .into_iter()
+        .filter_map(map_feature)
+        .collect::<Vec<_>>();
+    if required_feature_index == NO_REQUIRED_FEATURE && feature_indices.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(SubsetLangSys {
+            required_feature_index,
+            feature_indices,
+        }))
+    }
+}
+
+/// Subsets lookups and their subtables for the retained glyphs.
+struct LayoutSubsetter<'a> {
+    glyph_map: &'a FxHashMap<u16, u16>,
+    /// The new index of each lookup. When `None` all lookups are assumed to be retained.
+    opt_lookup_map: Option<&'a [Option<u16>]>,
+    is_gpos: bool,
+}
+
+/// A subtable of a `SequenceContext` or `ChainedSequenceContext` lookup.
+#[derive(Clone, Copy, PartialEq)]
+enum Context {
+    Sequence,
+    Chained,
+}
+
+impl<'a> LayoutSubsetter<'a> {
+    fn glyph(&self, glyph: u16) -> Option<u16> {
+        self.glyph_map.get(&glyph).copied()
+    }
+
+    fn glyphs(&self, glyphs: &[u16]) -> Option<Vec<u16>> {
+        glyphs.iter().map(|&glyph| self.glyph(glyph)).collect()
+    }
+
+    fn lookup_index(&self, index: u16) -> Option<u16> {
+        match self.opt_lookup_map {
+            Some(lookup_map) => lookup_map.get(usize::from(index)).and_then(|index| *index),
+            None => Some(index),
+        }
+    }
+
+    /// The retained glyphs of the coverage table at `offset` from `scope`, paired with their
+    /// coverage index in the original table.
+    fn coverage(
+        &self,
+        scope: &ReadScope<'_>,
+        offset: u16,
+    ) -> Result<Vec<(usize, u16)>, ParseError> {
+        let glyphs = read_coverage(scope.offset(usize::from(offset)))?;
+        let mut coverage = glyphs
+            .iter()
+            .enumerate()
+            .filter_map(|(index, &glyph)| self.glyph(glyph).map(|glyph| (index, glyph)))
+            .collect::<Vec<_>>();
+        // Glyphs pulled in by composite glyphs follow the requested glyphs in the subset font,
+        // so the new glyph ids are not necessarily in the original order.
+        coverage.sort_by_key(|&(_, glyph)| glyph);
+        Ok(coverage)
+    }
+
+    fn subset_class_def(
+        &self,
+        graph: &mut Graph,
+        scope: &ReadScope<'_>,
+        offset: u16,
+    ) -> Result<Option<ObjectId>, ReadWriteError> {
+        if offset == 0 {
+            return Ok(None);
+        }
+        let classes = read_class_def(scope.offset(usize::from(offset)))?
+            .into_iter()
+            .filter_map(|(glyph, class)| self.glyph(glyph).map(|glyph| (glyph, class)))
+            .collect::<Vec<_>>();
+        Ok(Some(write_class_def(graph, classes)?))
+    }
+
+    fn subset_lookup(&self, scope: ReadScope<'_>) -> Result<Option<SubsetLookup>, ReadWriteError> {
+        let mut ctxt = scope.ctxt();
+        let mut lookup_type = ctxt.read_u16be()?;
+        let lookup_flag = ctxt.read_u16be()?;
+        let subtable_count = usize::from(ctxt.read_u16be()?);
+        let subtable_offsets = read_u16s(&mut ctxt, subtable_count)?;
+        let mark_filtering_set = if lookup_flag & USE_MARK_FILTERING_SET != 0 {
+            Some(ctxt.read_u16be()?)
+        } else {
+            None
+        };
+
+        let mut subtables = Vec::new();
+        for offset in subtable_offsets {
+            let mut subtable_scope = scope.offset(usize::from(offset));
+            let mut subtable_type = lookup_type;
+            if self.is_extension(lookup_type) {
+                let mut extension_ctxt = subtable_scope.ctxt();
+                let _format = extension_ctxt.read_u16be()?;
+                subtable_type = extension_ctxt.read_u16be()?;
+                let extension_offset =
+                    usize::try_from(extension_ctxt.read_u32be()?).map_err(ParseError::from)?;
+                subtable_scope = subtable_scope.offset(extension_offset);
+            }
+            let mut graph = Graph::default();
+            if let Some(root) = self.subset_subtable(&mut graph, subtable_type, subtable_scope)? {
+                subtables.push(graph.pack(root)?);
+                lookup_type = subtable_type;
+            }
+        }
+
+        if subtables.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(SubsetLookup {
+                lookup_type,
+                lookup_flag,
+                subtables,
+                mark_filtering_set,
+            }))
+        }
+    }
+
+    fn is_extension(&self, lookup_type: u16) -> bool {
+        if self.is_gpos {
+            lookup_type == GPOS_EXTENSION
+        } else {
+            lookup_type == GSUB_EXTENSION
+        }
+    }
+
+    /// Subset a lookup subtable into `graph`, returning `None` if it no longer applies to any
+    /// retained glyph.
+    ///
+    /// Subtables of unknown types or formats are dropped.
+    fn subset_subtable(
+        &self,
+        graph: &mut Graph,
+        lookup_type: u16,
+        scope: ReadScope<'_>,
+    ) -> Result<Option<ObjectId>, ReadWriteError> {
+        match (self.is_gpos, lookup_type) {
+            (false, 1) => self.subset_single_subst(graph, scope),
+            (false, 2) => self.subset_sequence_subst(graph, scope, |glyphs| self.glyphs(glyphs)),
+            (false, 3) => self.subset_sequence_subst(graph, scope, |glyphs| {
+                let alternates = glyphs
+                    .iter()
+                    .filter_map(|&glyph| self.glyph(glyph))
+                    .collect::<Vec<_>>();
+                Some(alternates).filter(|alternates| !alternates.is_empty())
+            }),
+            (false, 4) => self.subset_ligature_subst(graph, scope),
+            (false, 5) | (true, 7) => self.subset_context(graph, scope, Context::Sequence),
+            (false, 6) | (true, 8) => self.subset_context(graph, scope, Context::Chained),
+            (false, 8) => self.subset_reverse_chain_single_subst(graph, scope),
+            (true, 1) => self.subset_single_pos(graph, scope),
+            (true, 2) => self.subset_pair_pos(graph, scope),
+            (true, 3) => self.subset_cursive_pos(graph, scope),
+            (true, 4) | (true, 6) => self.subset_mark_base_pos(graph, scope),
+            (true, 5) => self.subset_mark_lig_pos(graph, scope),
+            _ => Ok(None),
+        }
+    }
+
+    fn subset_single_subst(
+        &self,
+        graph: &mut Graph,
+        scope: ReadScope<'_>,
+    ) -> Result<Option<ObjectId>, ReadWriteError> {
+        let mut ctxt = scope.ctxt();
+        let format = ctxt.read_u16be()?;
+        let coverage = read_coverage(scope.offset(usize::from(ctxt.read_u16be()?)))?;
+        let substitutes = match format {
+            1 => {
+                let delta = ctxt.read_i16be()?;
+                coverage
+                    .iter()
+                    .map(|&glyph| glyph.wrapping_add(delta as u16))
+                    .collect::<Vec<_>>()
+            }
+            2 => {
+                let glyph_count = usize::from(ctxt.read_u16be()?);
+                read_u16s(&mut ctxt, glyph_count)?
+            }
+            _ => return Ok(None),
+        };
+
+        let mut pairs = Vec::new();
+        for (&glyph, &substitute) in coverage.iter().zip(substitutes.iter()) {
+            if let (Some(glyph), Some(substitute)) = (self.glyph(glyph), self.glyph(substitute)) {
+                pairs.push((glyph, substitute));
+            }
+        }
+        if pairs.is_empty() {
+            return Ok(None);
+        }
+        pairs.sort();
+
+        let glyphs = pairs.iter().map(|&(glyph, _)| glyph).collect::<Vec<_>>();
+        let coverage = write_coverage(graph, &glyphs)?;
+        let delta = pairs[0].1.wrapping_sub(pairs[0].0);
+        let mut table = Object::new();
+        if pairs
+            .iter()
+            .all(|&(glyph, substitute)| substitute.wrapping_sub(glyph) == delta)
+        {
+            table.u16(1).offset16(Some(coverage)).u16(delta);
+        } else {
+            table.u16(2).offset16(Some(coverage)).u16_len(pairs.len())?;
+            for &(_, substitute) in &pairs {
+                table.u16(substitute);
+            }
+        }
+        Ok(Some(graph.add(table)))
+    }
+
+    /// Subset a `MultipleSubst` or `AlternateSubst` subtable, which share the same layout.
+    ///
+    /// Sequences of a `MultipleSubst` are dropped unless all of their glyphs are retained, while
+    /// the alternates of an `AlternateSubst` are filtered.
+    fn subset_sequence_subst(
+        &self,
+        graph: &mut Graph,
+        scope: ReadScope<'_>,
+        subset_sequence: impl Fn(&[u16]) -> Option<Vec<u16>>,
+    ) -> Result<Option<ObjectId>, ReadWriteError> {
+        let mut ctxt = scope.ctxt();
+        let format = ctxt.read_u16be()?;
+        if format != 1 {
+            return Ok(None);
+        }
+        let coverage = self.coverage(&scope, ctxt.read_u16be()?)?;
+        let sequence_count = usize::from(ctxt.read_u16be()?);
+        let sequence_offsets = read_u16s(&mut ctxt, sequence_count)?;
+
+        let mut glyphs = Vec::new();
+        let mut sequences = Vec::new();
+        for (index, glyph) in coverage {
+            let offset = *sequence_offsets.get(index).ok_or(ParseError::BadIndex)?;
+            let mut sequence_ctxt = scope.offset(usize::from(offset)).ctxt();
+            let glyph_count = usize::from(sequence_ctxt.read_u16be()?);
+            let sequence = read_u16s(&mut sequence_ctxt, glyph_count)?;
+            if let Some(sequence) = subset_sequence(&sequence) {
+                let mut table = Object::new();
+                table.u16_len(sequence.len())?;
+                for glyph in sequence {
+                    table.u16(glyph);
+                }
+                glyphs.push(glyph);
+                sequences.push(graph.add(table));
+            }
+        }
+        if glyphs.is_empty() {
+            return Ok(None);
+        }
+
+        let coverage = write_coverage(graph, &glyphs)?;
+        let mut table = Object::new();
+        table
+            .u16(1)
+            .offset16(Some(coverage))
+            .u16_len(sequences.len())?;
+        for sequence in sequences {
+            table.offset16(Some(sequence));
+        }
+        Ok(Some(graph.add(table)))
+    }
+
+    fn subset_ligature_subst(
+        &self,
+        graph: &mut Graph,
+        scope: ReadScope<'_>,
+    ) -> Result<Option<ObjectId>, ReadWriteError> {
+        let mut ctxt = scope.ctxt();
+        let format = ctxt.read_u16be()?;
+        if format != 1 {
+            return Ok(None);
+        }
+        let coverage = self.coverage(&scope, ctxt.read_u16be()?)?;
+        let ligature_set_count = usize::from(ctxt.read_u16be()?);
+        let ligature_set_offsets = read_u16s(&mut ctxt, ligature_set_count)?;
+
+        let mut glyphs = Vec::new();
+        let mut ligature_sets = Vec::new();
+        for (index, glyph) in coverage {
+            let offset = *ligature_set_offsets
+                .get(index)
+                .ok_or(ParseError::BadIndex)?;
+            let set_scope = scope.offset(usize::from(offset));
+            let mut set_ctxt = set_scope.ctxt();
+            let ligature_count = usize::from(set_ctxt.read_u16be()?);
+            let mut ligatures = Vec::new();
+            for ligature_offset in read_u16s(&mut set_ctxt, ligature_count)? {
+                let mut ligature_ctxt = set_scope.offset(usize::from(ligature_offset)).ctxt();
+                let ligature_glyph = ligature_ctxt.read_u16be()?;
+                let component_count = usize::from(ligature_ctxt.read_u16be()?);
+                let components = read_u16s(&mut ligature_ctxt, component_count.saturating_sub(1))?;
+                if let (Some(ligature_glyph), Some(components)) =
+                    (self.glyph(ligature_glyph), self.glyphs(&components))
+                {
+                    let mut ligature = Object::new();
+                    ligature.u16(ligature_glyph).u16_len(components.len() + 1)?;
+                    for component in components {
+                        ligature.u16(component);
+                    }
+                    ligatures.push(graph.add(ligature));
+                }
+            }
+            if !ligatures.is_empty() {
+                let mut ligature_set = Object::new();
+                ligature_set.u16_len(ligatures.len())?;
+                for ligature in ligatures {
+                    ligature_set.offset16(Some(ligature));
+                }
+                glyphs.push(glyph);
+                ligature_sets.push(graph.add(ligature_set));
+            }
+        }
+        if glyphs.is_empty() {
+            return Ok(None);
+        }
+
+        let coverage = write_coverage(graph, &glyphs)?;
+        let mut table = Object::new();
+        table
+            .u16(1)
+            .offset16(Some(coverage))
+            .u16_len(ligature_sets.len())?;
+        for ligature_set in ligature_sets {
+            table.offset16(Some(ligature_set));
+        }
+        Ok(Some(graph.add(table)))
+    }
+
+    fn subset_reverse_chain_single_subst(
+        &self,
+        graph: &mut Graph,
+        scope: ReadScope<'_>,
+    ) -> Result<Option<ObjectId>, ReadWriteError> {
+        let mut ctxt = scope.ctxt();
+        let format = ctxt.read_u16be()?;
+        if format != 1 {
+            return Ok(None);
+        }
+        let coverage = self.coverage(&scope, ctxt.read_u16be()?)?;
+        let backtrack_count = usize::from(ctxt.read_u16be()?);
+        let backtrack = read_u16s(&mut ctxt, backtrack_count)?;
+        let lookahead_count = usize::from(ctxt.read_u16be()?);
+        let lookahead = read_u16s(&mut ctxt, lookahead_count)?;
+        let glyph_count = usize::from(ctxt.read_u16be()?);
+        let substitutes = read_u16s(&mut ctxt, glyph_count)?;
+
+        let mut glyphs = Vec::new();
+        let mut new_substitutes = Vec::new();
+        for (index, glyph) in coverage {
+            let substitute = *substitutes.get(index).ok_or(ParseError::BadIndex)?;
+            if let Some(substitute) = self.glyph(substitute) {
+                glyphs.push(glyph);
+                new_substitutes.push(substitute);
+            }
+        }
+        if glyphs.is_empty() {
+            return Ok(None);
+        }
+        let (backtrack, lookahead) = match (
+            self.subset_coverages(graph, &scope, &backtrack)?,
+            self.subset_coverages(graph, &scope, &lookahead)?,
+        ) {
+            (Some(backtrack), Some(lookahead)) => (backtrack, lookahead),
+            _ => return Ok(None),
+        };
+
+        let coverage = write_coverage(graph, &glyphs)?;
+        let mut table = Object::new();
+        table.u16(1).offset16(Some(coverage));
+        write_offsets16(&mut table, &backtrack)?;
+        write_offsets16(&mut table, &lookahead)?;
+        table.u16_len(new_substitutes.len())?;
+        for substitute in new_substitutes {
+            table.u16(substitute);
+        }
+        Ok(Some(graph.add(table)))
+    }
+
+    /// Subset the coverage tables of a format 3 context subtable, returning `None` if any of them
+    /// no longer covers a retained glyph.
+    fn subset_coverages(
+        &self,
+        graph: &mut Graph,
+        scope: &ReadScope<'_>,
+        offsets: &[u16],
+    ) -> Result<Option<Vec<ObjectId>>, ReadWriteError> {
+        let mut coverages = Vec::with_capacity(offsets.len());
+        for &offset in offsets {
+            let glyphs = self
+                .coverage(scope, offset)?
+                .into_iter()
+                .map(|(_, glyph)| glyph)
+                .collect::<Vec<_>>();
+            if glyphs.is_empty() {
+                return Ok(None);
+            }
+            coverages.push(write_coverage(graph, &glyphs)?);
+        }
+        Ok(Some(coverages))
+    }
+
+    fn subset_context(
+        &self,
+        graph: &mut Graph,
+        scope: ReadScope<'_>,
+        context: Context,
+    ) -> Result<Option<ObjectId>, ReadWriteError> {
+        let mut ctxt = scope.ctxt();
+        match ctxt.read_u16be()? {
+            1 => self.subset_context_glyphs(graph, scope, ctxt, context),
+            2 => self.subset_context_classes(graph, scope, ctxt, context),
+            3 => self.subset_context_coverages(graph, scope, ctxt, context),
+            _ => Ok(None),
+        }
+    }
+
+    /// Read a `SequenceLookupRecord` array, dropping records for lookups that are not retained.
+    fn lookup_records(
+        &self,
+        ctxt: &mut ReadCtxt<'_>,
+        count: usize,
+    ) -> Result<Vec<(u16, u16)>, ParseError> {
+        let mut records = Vec::with_capacity(count);
+        for _ in 0..count {
+            let sequence_index = ctxt.read_u16be()?;
+            let lookup_index = ctxt.read_u16be()?;
+            if let Some(lookup_index) = self.lookup_index(lookup_index) {
+                records.push((sequence_index, lookup_index));
+            }
+        }
+        Ok(records)
+    }
+
+    /// Subset a rule of a format 1 or 2 context subtable. Rules of format 1 subtables are dropped
+    /// unless all of their glyphs are retained; the classes of format 2 subtables are kept as is.
+    fn subset_rule(
+        &self,
+        graph: &mut Graph,
+        scope: ReadScope<'_>,
+        context: Context,
+        map_values: &impl Fn(&[u16]) -> Option<Vec<u16>>,
+    ) -> Result<Option<ObjectId>, ReadWriteError> {
+        let mut ctxt = scope.ctxt();
+        let mut rule = Object::new();
+        if context == Context::Chained {
+            let backtrack_count = usize::from(ctxt.read_u16be()?);
+            let backtrack = read_u16s(&mut ctxt, backtrack_count)?;
+            let input_count = usize::from(ctxt.read_u16be()?);
+            let input = read_u16s(&mut ctxt, input_count.saturating_sub(1))?;
+            let lookahead_count = usize::from(ctxt.read_u16be()?);
+            let lookahead = read_u16s(&mut ctxt, lookahead_count)?;
+            let record_count = usize::from(ctxt.read_u16be()?);
+            let records = self.lookup_records(&mut ctxt, record_count)?;
+            let (backtrack, input, lookahead) = match (
+                map_values(&backtrack),
+                map_values(&input),
+                map_values(&lookahead),
+            ) {
+                (Some(backtrack), Some(input), Some(lookahead)) => (backtrack, input, lookahead),
+                _ => return Ok(None),
+            };
+            write_u16s(&mut rule, &backtrack)?;
+            rule.u16_len(input.len() + 1)?;
+            for value in input {
+                rule.u16(value);
+            }
+            write_u16s(&mut rule, &lookahead)?;
+            write_lookup_records(&mut rule, &records)?;
+        } else {
+            let input_count = usize::from(ctxt.read_u16be()?);
+            let record_count = usize::from(ctxt.read_u16be()?);
+            let input = read_u16s(&mut ctxt, input_count.saturating_sub(1))?;
+            let records = self.lookup_records(&mut ctxt, record_count)?;
+            let input = match map_values(&input) {
+                Some(input) => input,
+                None => return Ok(None),
+            };
+            rule.u16_len(input.len() + 1)?.u16_len(records.len())?;
+            for value in input {
+                rule.u16(value);
+            }
+            for (sequence_index, lookup_index) in records {
+                rule.u16(sequence_index).u16(lookup_index);
+            }
+        }
+        Ok(Some(graph.add(rule)))
+    }
+
+    /// Subset a rule set of a format 1 or 2 context subtable, returning `None` if no rules
+    /// remain.
+    fn subset_rule_set(
+        &self,
+        graph: &mut Graph,
+        scope: ReadScope<'_>,
+        context: Context,
+        map_values: &impl Fn(&[u16]) -> Option<Vec<u16>>,
+    ) -> Result<Option<ObjectId>, ReadWriteError> {
+        let mut ctxt = scope.ctxt();
+        let rule_count = usize::from(ctxt.read_u16be()?);
+        let mut rules = Vec::new();
+        for offset in read_u16s(&mut ctxt, rule_count)? {
+            let rule_scope = scope.offset(usize::from(offset));
+            if let Some(rule) = self.subset_rule(graph, rule_scope, context, map_values)? {
+                rules.push(rule);
+            }
+        }
+        if rules.is_empty() {
+            return Ok(None);
+        }
+        let mut rule_set = Object::new();
+        write_offsets16(&mut rule_set, &rules)?;
+        Ok(Some(graph.add(rule_set)))
+    }
+
+    fn subset_context_glyphs(
+        &self,
+        graph: &mut Graph,
+        scope: ReadScope<'_>,
+        mut ctxt: ReadCtxt<'_>,
+        context: Context,
+    ) -> Result<Option<ObjectId>, ReadWriteError> {
+        let coverage = self.coverage(&scope, ctxt.read_u16be()?)?;
+        let rule_set_count = usize::from(ctxt.read_u16be()?);
+        let rule_set_offsets = read_u16s(&mut ctxt, rule_set_count)?;
+
+        let map_values = |glyphs: &[u16]| self.glyphs(glyphs);
+        let mut glyphs = Vec::new();
+        let mut rule_sets = Vec::new();
+        for (index, glyph) in coverage {
+            let offset = *rule_set_offsets.get(index).ok_or(ParseError::BadIndex)?;
+            if offset == 0 {
+                continue;
+            }
+            let rule_set_scope = scope.offset(usize::from(offset));
+            if let Some(rule_set) =
+                self.subset_rule_set(graph, rule_set_scope, context, &map_values)?
+            {
+                glyphs.push(glyph);
+                rule_sets.push(rule_set);
+            }
+        }
+        if glyphs.is_empty() {
+            return Ok(None);
+        }
+
+        let coverage = write_coverage(graph, &glyphs)?;
+        let mut table = Object::new();
+        table.u16(1).offset16(Some(coverage));
+        write_offsets16(&mut table, &rule_sets)?;
+        Ok(Some(graph.add(table)))
+    }
+
+    fn subset_context_classes(
+        &self,
+        graph: &mut Graph,
+        scope: ReadScope<'_>,
+        mut ctxt: ReadCtxt<'_>,
+        context: Context,
+    ) -> Result<Option<ObjectId>, ReadWriteError> {
+        let coverage = self.coverage(&scope, ctxt.read_u16be()?)?;
+        if coverage.is_empty() {
+            return Ok(None);
+        }
+        let class_def_offsets = match context {
+            Context::Sequence => vec![ctxt.read_u16be()?],
+            Context::Chained => read_u16s(&mut ctxt, 3)?,
+        };
+        let rule_set_count = usize::from(ctxt.read_u16be()?);
+        let rule_set_offsets = read_u16s(&mut ctxt, rule_set_count)?;
+
+        let map_values = |classes: &[u16]| Some(classes.to_vec());
+        let mut rule_sets = Vec::with_capacity(rule_set_offsets.len());
+        for offset in rule_set_offsets {
+            let rule_set = if offset != 0 {
+                let rule_set_scope = scope.offset(usize::from(offset));
+                self.subset_rule_set(graph, rule_set_scope, context, &map_values)?
+            } else {
+                None
+            };
+            rule_sets.push(rule_set);
+        }
+        // Trailing class sets without rules can be dropped, earlier ones are indexed by class.
+        while let Some(None) = rule_sets.last() {
+            rule_sets.pop();
+        }
+        if rule_sets.is_empty() {
+            return Ok(None);
+        }
+
+        let glyphs = coverage
+            .into_iter()
+            .map(|(_, glyph)| glyph)
+            .collect::<Vec<_>>();
+        let coverage = write_coverage(graph, &glyphs)?;
+        let mut table = Object::new();
+        table.u16(2).offset16(Some(coverage));
+        for offset in class_def_offsets {
+            let class_def = self.subset_class_def(graph, &scope, offset)?;
+            table.offset16(class_def);
+        }
+        table.u16_len(rule_sets.len())?;
+        for rule_set in rule_sets {
+            table.offset16(rule_set);
+        }
+        Ok(Some(graph.add(table)))
+    }
+
+    fn subset_context_coverages(
+        &self,
+        graph: &mut Graph,
+        scope: ReadScope<'_>,
+        mut ctxt: ReadCtxt<'_>,
+        context: Context,
+    ) -> Result<Option<ObjectId>, ReadWriteError> {
+        let mut table = Object::new();
+        table.u16(3);
+        match context {
+            Context::Sequence => {
+                let glyph_count = usize::from(ctxt.read_u16be()?);
+                let record_count = usize::from(ctxt.read_u16be()?);
+                let input = read_u16s(&mut ctxt, glyph_count)?;
+                let records = self.lookup_records(&mut ctxt, record_count)?;
+                let input = match self.subset_coverages(graph, &scope, &input)? {
+                    Some(input) => input,
+                    None => return Ok(None),
+                };
+                table.u16_len(input.len())?.u16_len(records.len())?;
+                for coverage in input {
+                    table.offset16(Some(coverage));
+                }
+                for (sequence_index, lookup_index) in records {
+                    table.u16(sequence_index).u16(lookup_index);
+                }
+            }
+            Context::Chained => {
+                let mut sequences = Vec::with_capacity(3);
+                for _ in 0..3 {
+                    let count = usize::from(ctxt.read_u16be()?);
+                    let offsets = read_u16s(&mut ctxt, count)?;
+                    match self.subset_coverages(graph, &scope, &offsets)? {
+                        Some(coverages) => sequences.push(coverages),
+                        None => return Ok(None),
+                    }
+                }
+                let record_count = usize::from(ctxt.read_u16be()?);
+                let records = self.lookup_records(&mut ctxt, record_count)?;
+                for coverages in sequences {
+                    write_offsets16(&mut table, &coverages)?;
+                }
+                write_lookup_records(&mut table, &records)?;
+            }
+        }
+        Ok(Some(graph.add(table)))
+    }
+
+    fn subset_single_pos(
+        &self,
+        graph: &mut Graph,
+        scope: ReadScope<'_>,
+    ) -> Result<Option<ObjectId>, ReadWriteError> {
+        let mut ctxt = scope.ctxt();
+        let format = ctxt.read_u16be()?;
+        let coverage = self.coverage(&scope, ctxt.read_u16be()?)?;
+        let value_format = ctxt.read_u16be()?;
+        if coverage.is_empty() {
+            return Ok(None);
+        }
+        let glyphs = coverage.iter().map(|&(_, glyph)| glyph).collect::<Vec<_>>();
+        let new_value_format = value_format & VALUE_FORMAT_VALUES;
+        let mut table = Object::new();
+        match format {
+            1 => {
+                let value = ValueRecord::read(&mut ctxt, value_format)?;
+                let coverage = write_coverage(graph, &glyphs)?;
+                table.u16(1).offset16(Some(coverage)).u16(new_value_format);
+                value.write(&mut table, new_value_format);
+            }
+            2 => {
+                let value_count = usize::from(ctxt.read_u16be()?);
+                let values = (0..value_count)
+                    .map(|_| ValueRecord::read(&mut ctxt, value_format))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let coverage_id = write_coverage(graph, &glyphs)?;
+                table
+                    .u16(2)
+                    .offset16(Some(coverage_id))
+                    .u16(new_value_format)
+                    .u16_len(coverage.len())?;
+                for (index, _) in coverage {
+                    values
+                        .get(index)
+                        .ok_or(ParseError::BadIndex)?
+                        .write(&mut table, new_value_format);
+                }
+            }
+            _ => return Ok(None),
+        }
+        Ok(Some(graph.add(table)))
+    }
+
+    fn subset_pair_pos(
+        &self,
+        graph: &mut Graph,
+        scope: ReadScope<'_>,
+    ) -> Result<Option<ObjectId>, ReadWriteError> {
+        let mut ctxt = scope.ctxt();
+        let format = ctxt.read_u16be()?;
+        let coverage = self.coverage(&scope, ctxt.read_u16be()?)?;
+        let value_format1 = ctxt.read_u16be()?;
+        let value_format2 = ctxt.read_u16be()?;
+        let new_value_format1 = value_format1 & VALUE_FORMAT_VALUES;
+        let new_value_format2 = value_format2 & VALUE_FORMAT_VALUES;
+        if coverage.is_empty() {
+            return Ok(None);
+        }
+
+        let mut table = Object::new();
+        match format {
+            1 => {
+                let pair_set_count = usize::from(ctxt.read_u16be()?);
+                let pair_set_offsets = read_u16s(&mut ctxt, pair_set_count)?;
+                let mut glyphs = Vec::new();
+                let mut pair_sets = Vec::new();
+                for (index, glyph) in coverage {
+                    let offset = *pair_set_offsets.get(index).ok_or(ParseError::BadIndex)?;
+                    let mut pair_set_ctxt = scope.offset(usize::from(offset)).ctxt();
+                    let pair_count = usize::from(pair_set_ctxt.read_u16be()?);
+                    let mut pairs = Vec::new();
+                    for _ in 0..pair_count {
+                        let second_glyph = pair_set_ctxt.read_u16be()?;
+                        let value1 = ValueRecord::read(&mut pair_set_ctxt, value_format1)?;
+                        let value2 = ValueRecord::read(&mut pair_set_ctxt, value_format2)?;
+                        if let Some(second_glyph) = self.glyph(second_glyph) {
+                            pairs.push((second_glyph, value1, value2));
+                        }
+                    }
+                    if !pairs.is_empty() {
+                        pairs.sort_by_key(|&(second_glyph, _, _)| second_glyph);
+                        let mut pair_set = Object::new();
+                        pair_set.u16_len(pairs.len())?;
+                        for (second_glyph, value1, value2) in pairs {
+                            pair_set.u16(second_glyph);
+                            value1.write(&mut pair_set, new_value_format1);
+                            value2.write(&mut pair_set, new_value_format2);
+                        }
+                        glyphs.push(glyph);
+                        pair_sets.push(graph.add(pair_set));
+                    }
+                }
+                if glyphs.is_empty() {
+                    return Ok(None);
+                }
+                let coverage = write_coverage(graph, &glyphs)?;
+                table
+                    .u16(1)
+                    .offset16(Some(coverage))
+                    .u16(new_value_format1)
+                    .u16(new_value_format2);
+                write_offsets16(&mut table, &pair_sets)?;
+            }
+            2 => {
+                let class_def1_offset = ctxt.read_u16be()?;
+                let class_def2_offset = ctxt.read_u16be()?;
+                let class1_count = ctxt.read_u16be()?;
+                let class2_count = ctxt.read_u16be()?;
+                let glyphs = coverage.iter().map(|&(_, glyph)| glyph).collect::<Vec<_>>();
+                let coverage = write_coverage(graph, &glyphs)?;
+                let class_def1 = self.subset_class_def(graph, &scope, class_def1_offset)?;
+                let class_def2 = self.subset_class_def(graph, &scope, class_def2_offset)?;
+                table
+                    .u16(2)
+                    .offset16(Some(coverage))
+                    .u16(new_value_format1)
+                    .u16(new_value_format2)
+                    .offset16(class_def1)
+                    .offset16(class_def2)
+                    .u16(class1_count)
+                    .u16(class2_count);
+                for _ in 0..usize::from(class1_count) * usize::from(class2_count) {
+                    let value1 = ValueRecord::read(&mut ctxt, value_format1)?;
+                    let value2 = ValueRecord::read(&mut ctxt, value_format2)?;
+                    value1.write(&mut table, new_value_format1);
+                    value2.write(&mut table, new_value_format2);
+                }
+            }
+            _ => return Ok(None),
+        }
+        Ok(Some(graph.add(table)))
+    }
+
+    fn subset_cursive_pos(
+        &self,
+        graph: &mut Graph,
+        scope: ReadScope<'_>,
+    ) -> Result<Option<ObjectId>, ReadWriteError> {
+        let mut ctxt = scope.ctxt();
+        let format = ctxt.read_u16be()?;
+        if format != 1 {
+            return Ok(None);
+        }
+        let coverage = self.coverage(&scope, ctxt.read_u16be()?)?;
+        let entry_exit_count = usize::from(ctxt.read_u16be()?);
+        let entry_exits = read_u16s(&mut ctxt, entry_exit_count * 2)?;
+        if coverage.is_empty() {
+            return Ok(None);
+        }
+
+        let glyphs = coverage.iter().map(|&(_, glyph)| glyph).collect::<Vec<_>>();
+        let coverage_id = write_coverage(graph, &glyphs)?;
+        let mut table = Object::new();
+        table
+            .u16(1)
+            .offset16(Some(coverage_id))
+            .u16_len(coverage.len())?;
+        for (index, _) in coverage {
+            let anchors = entry_exits
+                .get(index * 2..index * 2 + 2)
+                .ok_or(ParseError::BadIndex)?;
+            for &offset in anchors {
+                let anchor = add_anchor(graph, &scope, offset)?;
+                table.offset16(anchor);
+            }
+        }
+        Ok(Some(graph.add(table)))
+    }
+
+    /// Subset a `MarkBasePos` or `MarkMarkPos` subtable, which share the same layout.
+    fn subset_mark_base_pos(
+        &self,
+        graph: &mut Graph,
+        scope: ReadScope<'_>,
+    ) -> Result<Option<ObjectId>, ReadWriteError> {
+        let mut ctxt = scope.ctxt();
+        let format = ctxt.read_u16be()?;
+        if format != 1 {
+            return Ok(None);
+        }
+        let mark_coverage = self.coverage(&scope, ctxt.read_u16be()?)?;
+        let base_coverage = self.coverage(&scope, ctxt.read_u16be()?)?;
+        let mark_class_count = ctxt.read_u16be()?;
+        let mark_array_offset = usize::from(ctxt.read_u16be()?);
+        let base_array_offset = usize::from(ctxt.read_u16be()?);
+        if mark_coverage.is_empty() || base_coverage.is_empty() {
+            return Ok(None);
+        }
+
+        let mark_array =
+            self.subset_mark_array(graph, scope.offset(mark_array_offset), &mark_coverage)?;
+
+        let base_array_scope = scope.offset(base_array_offset);
+        let mut base_array_ctxt = base_array_scope.ctxt();
+        let base_count = usize::from(base_array_ctxt.read_u16be()?);
+        let class_count = usize::from(mark_class_count);
+        let base_anchors = read_u16s(&mut base_array_ctxt, base_count * class_count)?;
+        let mut base_array = Object::new();
+        base_array.u16_len(base_coverage.len())?;
+        for &(index, _) in &base_coverage {
+            let anchors = base_anchors
+                .get(index * class_count..(index + 1) * class_count)
+                .ok_or(ParseError::BadIndex)?;
+            for &offset in anchors {
+                let anchor = add_anchor(graph, &base_array_scope, offset)?;
+                base_array.offset16(anchor);
+            }
+        }
+        let base_array = graph.add(base_array);
+
+        let mark_glyphs = mark_coverage
+            .iter()
+            .map(|&(_, glyph)| glyph)
+            .collect::<Vec<_>>();
+        let base_glyphs = base_coverage
+            .iter()
+            .map(|&(_, glyph)| glyph)
+            .collect::<Vec<_>>();
+        let mark_coverage = write_coverage(graph, &mark_glyphs)?;
+        let base_coverage = write_coverage(graph, &base_glyphs)?;
+        let mut table = Object::new();
+        table
+            .u16(1)
+            .offset16(Some(mark_coverage))
+            .offset16(Some(base_coverage))
+            .u16(mark_class_count)
+            .offset16(Some(mark_array))
+            .offset16(Some(base_array));
+        Ok(Some(graph.add(table)))
+    }
+
+    fn subset_mark_lig_pos(
+        &self,
+        graph: &mut Graph,
+        scope: ReadScope<'_>,
+    ) -> Result<Option<ObjectId>, ReadWriteError> {
+        let mut ctxt = scope.ctxt();
+        let format = ctxt.read_u16be()?;
+        if format != 1 {
+            return Ok(None);
+        }
+        let mark_coverage = self.coverage(&scope, ctxt.read_u16be()?)?;
+        let ligature_coverage = self.coverage(&scope, ctxt.read_u16be()?)?;
+        let mark_class_count = ctxt.read_u16be()?;
+        let mark_array_offset = usize::from(ctxt.read_u16be()?);
+        let ligature_array_offset = usize::from(ctxt.read_u16be()?);
+        if mark_coverage.is_empty() || ligature_coverage.is_empty() {
+            return Ok(None);
+        }
+
+        let mark_array =
+            self.subset_mark_array(graph, scope.offset(mark_array_offset), &mark_coverage)?;
+
+        let ligature_array_scope = scope.offset(ligature_array_offset);
+        let mut ligature_array_ctxt = ligature_array_scope.ctxt();
+        let ligature_count = usize::from(ligature_array_ctxt.read_u16be()?);
+        let ligature_attach_offsets = read_u16s(&mut ligature_array_ctxt, ligature_count)?;
+        let class_count = usize::from(mark_class_count);
+        let mut ligature_array = Object::new();
+        ligature_array.u16_len(ligature_coverage.len())?;
+        for &(index, _) in &ligature_coverage {
+            let offset = *ligature_attach_offsets
+                .get(index)
+                .ok_or(ParseError::BadIndex)?;
+            let attach_scope = ligature_array_scope.offset(usize::from(offset));
+            let mut attach_ctxt = attach_scope.ctxt();
+            let component_count = usize::from(attach_ctxt.read_u16be()?);
+            let mut ligature_attach = Object::new();
+            ligature_attach.u16_len(component_count)?;
+            for offset in read_u16s(&mut attach_ctxt, component_count * class_count)? {
+                let anchor = add_anchor(graph, &attach_scope, offset)?;
+                ligature_attach.offset16(anchor);
+            }
+            let ligature_attach = graph.add(ligature_attach);
+            ligature_array.offset16(Some(ligature_attach));
+        }
+        let ligature_array = graph.add(ligature_array);
+
+        let mark_glyphs = mark_coverage
+            .iter()
+            .map(|&(_, glyph)| glyph)
+            .collect::<Vec<_>>();
+        let ligature_glyphs = ligature_coverage
+            .iter()
+            .map(|&(_, glyph)| glyph)
+            .collect::<Vec<_>>();
+        let mark_coverage = write_coverage(graph, &mark_glyphs)?;
+        let ligature_coverage = write_coverage(graph, &ligature_glyphs)?;
+        let mut table = Object::new();
+        table
+            .u16(1)
+            .offset16(Some(mark_coverage))
+            .offset16(Some(ligature_coverage))
+            .u16(mark_class_count)
+            .offset16(Some(mark_array))
+            .offset16(Some(ligature_array));
+        Ok(Some(graph.add(table)))
+    }
+
+    fn subset_mark_array(
+        &self,
+        graph: &mut Graph,
+        scope: ReadScope<'_>,
+        mark_coverage: &[(usize, u16)],
+    ) -> Result<ObjectId, ReadWriteError> {
+        let mut ctxt = scope.ctxt();
+        let mark_count = usize::from(ctxt.read_u16be()?);
+        let mark_records = read_u16s(&mut ctxt, mark_count * 2)?;
+        let mut mark_array = Object::new();
+        mark_array.u16_len(mark_coverage.len())?;
+        for &(index, _) in mark_coverage {
+            let record = mark_records
+                .get(index * 2..index * 2 + 2)
+                .ok_or(ParseError::BadIndex)?;
+            let anchor = add_anchor(graph, &scope, record[1])?;
+            mark_array.u16(record[0]).offset16(anchor);
+        }
+        Ok(graph.add(mark_array))
+    }
+}
+
+/// The non-device fields of a GPOS `ValueRecord`.
+struct ValueRecord {
+    values: [u16; 4],
+}
+
+impl ValueRecord {
+    fn read(ctxt: &mut ReadCtxt<'_>, value_format: u16) -> Result<ValueRecord, ParseError> {
+        let mut values = [0; 4];
+        for (bit, value) in values.iter_mut().enumerate() {
+            if value_format & (1 << bit) != 0 {
+                *value = ctxt.read_u16be()?;
+            }
+        }
+        // The device table offsets are skipped
+        for bit in 4..8 {
+            if value_format & (1 << bit) != 0 {
+                ctxt.read_u16be()?;
+            }
+        }
+        Ok(ValueRecord { values })
+    }
+
+    fn write(&self, object: &mut Object, value_format: u16) {
+        for (bit, &value) in self.values.iter().enumerate() {
+            if value_format & (1 << bit) != 0 {
+                object.u16(value);
+            }
+        }
+    }
+}
+
+/// Add the anchor table at `offset` from `scope`.
+///
+/// The contour point of a format 2 anchor is kept, since glyph outlines are not changed by
+/// subsetting. Format 3 anchors lose their device tables and become format 1 anchors.
+fn add_anchor(
+    graph: &mut Graph,
+    scope: &ReadScope<'_>,
+    offset: u16,
+) -> Result<Option<ObjectId>, ParseError> {
+    if offset == 0 {
+        return Ok(None);
+    }
+    let mut ctxt = scope.offset(usize::from(offset)).ctxt();
+    let format = ctxt.read_u16be()?;
+    if !(1..=3).contains(&format) {
+        return Err(ParseError::BadVersion);
+    }
+    let x = ctxt.read_i16be()?;
+    let y = ctxt.read_i16be()?;
+    let mut anchor = Object::new();
+    if format == 2 {
+        let anchor_point = ctxt.read_u16be()?;
+        anchor.u16(2).i16(x).i16(y).u16(anchor_point);
+    } else {
+        anchor.u16(1).i16(x).i16(y);
+    }
+    Ok(Some(graph.add(anchor)))
+}
+
+fn read_u16s(ctxt: &mut ReadCtxt<'_>, count: usize) -> Result<Vec<u16>, ParseError> {
+    Ok(ctxt.read_array::<U16Be>(count)?.iter().collect())
+}
+
+fn write_u16s(object: &mut Object, values: &[u16]) -> Result<(), WriteError> {
+    object.u16_len(values.len())?;
+    for &value in values {
+        object.u16(value);
+    }
+    Ok(())
+}
+
+fn write_offsets16(object: &mut Object, targets: &[ObjectId]) -> Result<(), WriteError> {
+    object.u16_len(targets.len())?;
+    for &target in targets {
+        object.offset16(Some(target));
+    }
+    Ok(())
+}
+
+fn write_lookup_records(object: &mut Object, records: &[(u16, u16)]) -> Result<(), WriteError> {
+    object.u16_len(records.len())?;
+    for &(sequence_index, lookup_index) in records {
+        object.u16(sequence_index).u16(lookup_index);
+    }
+    Ok(())
+}
+
+/// Read the glyphs of a coverage table in coverage index order.
+fn read_coverage(scope: ReadScope<'_>) -> Result<Vec<u16>, ParseError> {
+    let mut ctxt = scope.ctxt();
+    match ctxt.read_u16be()? {
+        1 => {
+            let glyph_count = usize::from(ctxt.read_u16be()?);
+            read_u16s(&mut ctxt, glyph_count)
+        }
+        2 => {
+            let range_count = usize::from(ctxt.read_u16be()?);
+            let mut glyphs = Vec::new();
+            for _ in 0..range_count {
+                let start_glyph = ctxt.read_u16be()?;
+                let end_glyph = ctxt.read_u16be()?;
+                let _start_coverage_index = ctxt.read_u16be()?;
+                if start_glyph > end_glyph {
+                    return Err(ParseError::BadValue);
+                }
+                glyphs.extend(start_glyph..=end_glyph);
+            }
+            Ok(glyphs)
+        }
+        _ => Err(ParseError::BadVersion),
+    }
+}
+
+/// Add a coverage table for `glyphs`, which must be sorted, in whichever format is smaller.
+fn write_coverage(graph: &mut Graph, glyphs: &[u16]) -> Result<ObjectId, WriteError> {
+    let mut ranges: Vec<(u16, u16)> = Vec::new();
+    for &glyph in glyphs {
+        match ranges.last_mut() {
+            Some((_, end)) if u32::from(*end) + 1 == u32::from(glyph) => *end = glyph,
+            _ => ranges.push((glyph, glyph)),
+        }
+    }
+
+    let mut coverage = Object::new();
+    if ranges.len() * 6 < glyphs.len() * 2 {
+        coverage.u16(2).u16_len(ranges.len())?;
+        let mut coverage_index = 0;
+        for (start, end) in ranges {
+            coverage.u16(start).u16(end).u16_len(coverage_index)?;
+            coverage_index += usize::from(end - start) + 1;
+        }
+    } else {
+        coverage.u16(1);
+        write_u16s(&mut coverage, glyphs)?;
+    }
+    Ok(graph.add(coverage))
+}
+
+/// Read the glyphs of a class definition table and their classes.
+fn read_class_def(scope: ReadScope<'_>) -> Result<Vec<(u16, u16)>, ParseError> {
+    let mut ctxt = scope.ctxt();
+    match ctxt.read_u16be()? {
+        1 => {
+            let start_glyph = ctxt.read_u16be()?;
+            let glyph_count = usize::from(ctxt.read_u16be()?);
+            let classes = read_u16s(&mut ctxt, glyph_count)?;
+            Ok((start_glyph..=0xFFFF).zip(classes).collect())
+        }
+        2 => {
+            let range_count = usize::from(ctxt.read_u16be()?);
+            let mut classes = Vec::new();
+            for _ in 0..range_count {
+                let start_glyph = ctxt.read_u16be()?;
+                let end_glyph = ctxt.read_u16be()?;
+                let class = ctxt.read_u16be()?;
+                // A range that ends before it starts matches no glyphs when shaping, so it is
+                // left out rather than rejecting the table.
+                if start_glyph > end_glyph {
+                    continue;
+                }
+                classes.extend((start_glyph..=end_glyph).map(|glyph| (glyph, class)));
+            }
+            Ok(classes)
+        }
+        _ => Err(ParseError::BadVersion),
+    }
+}
+
+/// Add a class definition table for `classes`, in whichever format is smaller. Glyphs in class 0
+/// are omitted.
+fn write_class_def(
+    graph: &mut Graph,
+    mut classes: Vec<(u16, u16)>,
+) -> Result<ObjectId, WriteError> {
+    classes.retain(|&(_, class)| class != 0);
+    classes.sort();
+
+    let mut ranges: Vec<(u16, u16, u16)> = Vec::new();
+    for &(glyph, class) in &classes {
+        match ranges.last_mut() {
+            Some((_, end, range_class))
+                if u32::from(*end) + 1 == u32::from(glyph) && *range_class == class =>
+            {
+                *end = glyph
+            }
+            _ => ranges.push((glyph, glyph, class)),
+        }
+    }
+
+    let mut class_def = Object::new();
+    let span = match (classes.first(), classes.last()) {
+        (Some(&(first, _)), Some(&(last, _))) => usize::from(last - first) + 1,
+        _ => 0,
+    };
+    if ranges.len() * 6 < span * 2 {
+        class_def.u16(2).u16_len(ranges.len())?;
+        for (start, end, class) in ranges {
+            class_def.u16(start).u16(end).u16(class);
+        }
+    } else {
+        let start_glyph = classes.first().map_or(0, |&(glyph, _)| glyph);
+        let mut values = vec![0; span];
+        for (glyph, class) in classes {
+            values[usize::from(glyph - start_glyph)] = class;
+        }
+        class_def.u16(1).u16(start_glyph);
+        write_u16s(&mut class_def, &values)?;
+    }
+    Ok(graph.add(class_def))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "shaping")]
+    use crate::font::{Font, MatchingPresentation};
+    #[cfg(feature = "shaping")]
+    use crate::font_data::FontData;
+    #[cfg(feature = "shaping")]
+    use crate::gsub::Features;
+    #[cfg(feature = "shaping")]
+    use crate::sfnt::write_font;
+    #[cfg(feature = "shaping")]
+    use crate::tables::{FontTableProvider, OpenTypeFont, CFF_MAGIC};
+    #[cfg(feature = "shaping")]
+    use crate::tag;
+    #[cfg(feature = "shaping")]
+    use crate::tests::read_fixture;
+
+    #[test]
+    fn test_coverage_and_class_def_round_trip() {
+        let mut graph = Graph::default();
+        let glyphs = vec![3, 4, 5, 6, 7, 20];
+        let coverage = write_coverage(&mut graph, &glyphs).unwrap();
+        let data = graph.pack(coverage).unwrap();
+        assert_eq!(read_coverage(ReadScope::new(&data)).unwrap(), glyphs);
+
+        let classes = vec![(9, 1), (3, 2), (4, 2), (5, 0), (6, 1)];
+        let class_def = write_class_def(&mut graph, classes).unwrap();
+        let data = graph.pack(class_def).unwrap();
+        assert_eq!(
+            read_class_def(ReadScope::new(&data))
+                .unwrap()
+                .into_iter()
+                .filter(|&(_, class)| class != 0)
+                .collect::<Vec<_>>(),
+            vec![(3, 2), (4, 2), (6, 1), (9, 1)]
+        );
+    }
+
+    #[test]
+    fn test_pack_shared_objects() {
+        let mut graph = Graph::default();
+        let mut leaf = Object::new();
+        leaf.u16(0xABCD);
+        let leaf = graph.add(leaf);
+        let mut child = Object::new();
+        child.offset16(Some(leaf));
+        let child = graph.add(child);
+        let mut root = Object::new();
+        root.offset16(Some(child)).offset32(Some(leaf));
+        let root = graph.add(root);
+
+        // The leaf follows both objects that refer to it
+        let data = graph.pack(root).unwrap();
+        assert_eq!(data, vec![0, 6, 0, 0, 0, 8, 0, 2, 0xAB, 0xCD]);
+    }
+
+    #[cfg(feature = "shaping")]
+    #[test]
+    fn test_subset_layout_extension_lookups() {
+        let buffer = read_fixture("tests/fonts/opentype/Klei.otf");
+        let opentype_file = ReadScope::new(&buffer).read::<OpenTypeFont<'_>>().unwrap();
+        let provider = opentype_file.table_provider(0).unwrap();
+        let gpos = provider.read_table_data(tag::GPOS).unwrap();
+        let glyph_map = (0..=std::u16::MAX).map(|glyph| (glyph, glyph)).collect();
+        let layout = subset_layout(&gpos, true, &glyph_map).unwrap().unwrap();
+        let extension_gpos = layout.build(true).unwrap();
+
+        // Every lookup is an extension lookup
+        let scope = ReadScope::new(&extension_gpos);
+        let lookup_list_offset = scope.offset(8).ctxt().read_u16be().unwrap();
+        let lookup_list_scope = scope.offset(usize::from(lookup_list_offset));
+        let mut ctxt = lookup_list_scope.ctxt();
+        let lookup_count = usize::from(ctxt.read_u16be().unwrap());
+        let lookup_offsets = read_u16s(&mut ctxt, lookup_count).unwrap();
+        assert!(!lookup_offsets.is_empty());
+        for offset in lookup_offsets {
+            let lookup_type = lookup_list_scope
+                .offset(usize::from(offset))
+                .ctxt()
+                .read_u16be()
+                .unwrap();
+            assert_eq!(lookup_type, GPOS_EXTENSION);
+        }
+
+        let mut tables = Vec::new();
+        for tag in provider.table_tags().unwrap() {
+            let data = if tag == tag::GPOS {
+                extension_gpos.clone()
+            } else {
+                provider.read_table_data(tag).unwrap().into_owned()
+            };
+            tables.push((tag, data));
+        }
+        let tables = tables
+            .iter()
+            .map(|(tag, data)| (*tag, data.as_slice()))
+            .collect::<Vec<_>>();
+        let data = write_font(CFF_MAGIC, &tables).unwrap();
+
+        let kerning = |data: &[u8]| {
+            let font_file = ReadScope::new(data).read::<FontData<'_>>().unwrap();
+            let provider = font_file.table_provider(0).unwrap();
+            let mut font = Font::new(provider).unwrap().unwrap();
+            let glyphs = font.map_glyphs("AVATAR", MatchingPresentation::NotRequired);
+            font.shape(
+                glyphs,
+                tag::LATN,
+                None,
+                &Features::Settings(Vec::new()),
+                true,
+            )
+            .unwrap()
+            .iter()
+            .map(|info| info.kerning)
+            .collect::<Vec<_>>()
+        };
+        let expected = kerning(&buffer);
+        assert!(expected.iter().any(|&kerning| kerning != 0));
+        assert_eq!(kerning(&data), expected);
+    }
+}
@@ -1,18 +1,48 @@
 //! OpenType font table parsing and writing.
 
+pub mod aat;
+pub mod avar;
+pub mod base;
 pub mod cmap;
+pub mod colr;
+pub mod cpal;
+pub mod fvar;
+pub mod gasp;
 pub mod glyf;
+#[cfg(feature = "variations")]
+pub mod gvar;
+pub mod hdmx;
+pub mod just;
+pub mod kern;
+pub mod kerx;
 pub mod loca;
+pub mod ltsh;
+pub mod math;
+pub mod meta;
+pub mod morx;
+pub mod mvar;
 pub mod os2;
+pub mod stat;
+#[cfg(feature = "bitmap")]
 pub mod svg;
+pub mod vdmx;
+pub mod vorg;
+pub mod vvar;
+pub mod zapf;
+
+use bitflags::bitflags;
 
 use crate::binary::read::{
     CheckIndex, ReadArray, ReadArrayCow, ReadBinary, ReadBinaryDep, ReadCtxt, ReadFrom, ReadScope,
 };
 use crate::binary::write::{Placeholder, WriteBinary, WriteContext};
 use crate::binary::{I16Be, I64Be, U16Be, U32Be};
+use crate::checksum::{self, ChecksumMismatch};
 use crate::error::{ParseError, WriteError};
+use crate::macroman::{char_to_macroman, macroman_to_char};
 use crate::size;
+use crate::tables::fvar::FvarTable;
+use crate::tables::stat::StatTable;
 use crate::tag;
 
 use std::borrow::Cow;
@@ -44,16 +74,31 @@ pub trait FontTableProvider {
     fn has_table<'a>(&'a self, tag: u32) -> bool;
 
     fn read_table_data<'a>(&'a self, tag: u32) -> Result<Cow<'a, [u8]>, ParseError> {
-        self.table_data(tag)?.ok_or(ParseError::MissingValue)
+        self.table_data(tag)?
+            .ok_or_else(|| ParseError::MissingValue.with_table(tag))
+    }
+
+    /// Return the tags of the tables in the font, if the provider is able to list them.
+    fn table_tags(&self) -> Option<Vec<u32>> {
+        None
     }
 }
 
 /// The F2DOT14 format consists of a signed, 2’s complement integer and an unsigned fraction.
 ///
 /// To compute the actual value, take the integer and add the fraction.
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct F2Dot14(u16);
 
+/// A version number packed into 32 bits, as used by the `maxp` and `post` tables.
+///
+/// The major version is held in the upper 16 bits and the minor version in the next 4 bits, so
+/// version 2.5 is `0x00025000`. Some fonts encode the minor version as a true 16.16 `Fixed`
+/// fraction instead (E.g. 0.5 as `0x00008000`). Such values are canonicalised when read so that
+/// they compare equal to their intended version and are written back out in the expected form.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version16Dot16(u32);
+
 /// The size of the offsets in the `loca` table
 ///
 /// <https://docs.microsoft.com/en-us/typography/opentype/spec/loca>
@@ -237,6 +282,7 @@ pub struct NameTable<'a> {
 }
 
 /// Record within the `name` table
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct NameRecord {
     pub platform_id: u16,
     pub encoding_id: u16,
@@ -257,23 +303,75 @@ impl<'a> OpenTypeFont<'a> {
         &'a self,
         index: usize,
     ) -> Result<OffsetTableFontProvider<'a>, ParseError> {
-        match &self.data {
-            OpenTypeData::Single(offset_table) => Ok(OffsetTableFontProvider {
-                offset_table: Cow::Borrowed(offset_table),
+        self.offset_table(index)
+            .map(|offset_table| OffsetTableFontProvider {
+                offset_table,
                 scope: self.scope.clone(),
-            }),
+            })
+    }
+
+    /// Consume the font and return a `FontTableProvider` for the font at `index`.
+    pub(crate) fn into_table_provider(
+        self,
+        index: usize,
+    ) -> Result<OffsetTableFontProvider<'a>, ParseError> {
+        let offset_table = self.offset_table(index)?.into_owned();
+        Ok(OffsetTableFontProvider {
+            offset_table: Cow::Owned(offset_table),
+            scope: self.scope,
+        })
+    }
+
+    /// Returns the offset table of the font at `index`.
+    ///
+    /// For a single font `index` is ignored.
+    pub fn offset_table(&self, index: usize) -> Result<Cow<'_, OffsetTable<'a>>, ParseError> {
+        match &self.data {
+            OpenTypeData::Single(offset_table) => Ok(Cow::Borrowed(offset_table)),
             OpenTypeData::Collection(ttc) => ttc
                 .offset_tables
                 .check_index(index)
                 .and_then(|()| ttc.offset_tables.read_item(index))
                 .and_then(|offset| usize::try_from(offset).map_err(ParseError::from))
                 .and_then(|offset| self.scope.offset(offset).read::<OffsetTable<'_>>())
-                .map(|offset_table| OffsetTableFontProvider {
-                    offset_table: Cow::Owned(offset_table),
-                    scope: self.scope.clone(),
-                }),
+                .map(Cow::Owned),
         }
     }
+
+    /// Check the table checksums of the font at `index` against the table data.
+    ///
+    /// For a single font `checkSumAdjustment` in the `head` table is verified too. It is not
+    /// checked for fonts in a collection since its value is not well defined there. Returns the
+    /// checksums that do not match, which is empty if the font is valid.
+    pub fn verify_checksums(&self, index: usize) -> Result<Vec<ChecksumMismatch>, ParseError> {
+        let offset_table = self.offset_table(index)?;
+        let mut mismatches = Vec::new();
+        for record in offset_table.table_records.iter() {
+            let table = record.read_table(&self.scope)?;
+            let calculated = checksum::sfnt_table_checksum(record.table_tag, table.data())?.0;
+            if calculated != record.checksum {
+                mismatches.push(ChecksumMismatch::Table {
+                    table_tag: record.table_tag,
+                    stored: record.checksum,
+                    calculated,
+                });
+            }
+        }
+
+        if let OpenTypeData::Single(_) = self.data {
+            if let Some(record) = offset_table.find_table_record(tag::HEAD) {
+                let head_offset = usize::try_from(record.offset)?;
+                let head = self.scope.offset(head_offset).read::<HeadTable>()?;
+                let stored = head.check_sum_adjustment;
+                let calculated = checksum::check_sum_adjustment(self.scope.data(), head_offset)?;
+                if calculated != stored {
+                    mismatches.push(ChecksumMismatch::CheckSumAdjustment { stored, calculated });
+                }
+            }
+        }
+
+        Ok(mismatches)
+    }
 }
 
 impl<'a> ReadBinary<'a> for OpenTypeFont<'a> {
@@ -308,7 +406,7 @@ impl<'a> ReadBinary<'a> for TTCHeader<'a> {
             TTCF_MAGIC => {
                 let major_version = ctxt.read_u16be()?;
                 let minor_version = ctxt.read_u16be()?;
-                ctxt.check(major_version == 1 || major_version == 2)?;
+                ctxt.check_field(major_version == 1 || major_version == 2, "majorVersion")?;
                 let num_fonts = usize::try_from(ctxt.read_u32be()?)?;
                 let offset_tables = ctxt.read_array::<U32Be>(num_fonts)?;
                 // TODO read digital signature fields in TTCHeader version 2
@@ -358,6 +456,16 @@ impl<'a> FontTableProvider for OffsetTableFontProvider<'a> {
     fn has_table<'b>(&'b self, tag: u32) -> bool {
         self.offset_table.find_table_record(tag).is_some()
     }
+
+    fn table_tags(&self) -> Option<Vec<u32>> {
+        Some(
+            self.offset_table
+                .table_records
+                .iter()
+                .map(|record| record.table_tag)
+                .collect(),
+        )
+    }
 }
 
 impl<'a> ReadFrom<'a> for TableRecord {
@@ -428,7 +536,7 @@ impl<'a> ReadBinary<'a> for HeadTable {
         let font_revision = ctxt.read::<U32Be>()?; // TODO: Parse this as a 'Fixed' 16.16 value
         let check_sum_adjustment = ctxt.read::<U32Be>()?;
         let magic_number = ctxt.read::<U32Be>()?;
-        ctxt.check(magic_number == 0x5F0F3CF5)?;
+        ctxt.check_field(magic_number == 0x5F0F3CF5, "magicNumber")?;
         let flags = ctxt.read::<U16Be>()?;
         let units_per_em = ctxt.read::<U16Be>()?;
         let created = ctxt.read::<I64Be>()?;
@@ -497,23 +605,75 @@ impl<'a> WriteBinary<&Self> for HeadTable {
     }
 }
 
+bitflags! {
+    /// Flags stored in the `flags` field of the `head` table.
+    ///
+    /// <https://docs.microsoft.com/en-us/typography/opentype/spec/head>
+    pub struct HeadFlags: u16 {
+        /// Baseline for font at y=0.
+        const BASELINE_AT_Y_0 = 1 << 0;
+        /// Left sidebearing point at x=0.
+        const LEFT_SIDEBEARING_AT_X_0 = 1 << 1;
+        /// Instructions may depend on point size.
+        const INSTRUCTIONS_DEPEND_ON_POINT_SIZE = 1 << 2;
+        /// Force ppem to integer values for all internal scaler math.
+        const FORCE_INTEGER_PPEM = 1 << 3;
+        /// Instructions may alter advance width.
+        const INSTRUCTIONS_ALTER_ADVANCE_WIDTH = 1 << 4;
+        /// Used by Apple to indicate that the font is intended to be laid out vertically.
+        const VERTICAL_LAYOUT = 1 << 5;
+        /// Used by Apple to indicate that the font requires layout for correct rendering.
+        const REQUIRES_LAYOUT = 1 << 7;
+        /// Used by Apple to indicate that the font has metamorphosis effects by default.
+        const METAMORPHOSIS_EFFECTS = 1 << 8;
+        /// Used by Apple to indicate that the font contains strong right-to-left glyphs.
+        const STRONG_RIGHT_TO_LEFT = 1 << 9;
+        /// Used by Apple to indicate that the font contains Indic-style rearrangement effects.
+        const INDIC_REARRANGEMENT = 1 << 10;
+        /// Font data is lossless as a result of having been compressed and decompressed.
+        const LOSSLESS = 1 << 11;
+        /// Font converted to produce compatible metrics.
+        const CONVERTED = 1 << 12;
+        /// Font optimised for ClearType.
+        const CLEARTYPE_OPTIMISED = 1 << 13;
+        /// The glyphs of the font are generic symbols for code point ranges, as in a last resort
+        /// font.
+        const LAST_RESORT = 1 << 14;
+    }
+}
+
+bitflags! {
+    /// Style flags stored in the `macStyle` field of the `head` table.
+    ///
+    /// <https://docs.microsoft.com/en-us/typography/opentype/spec/head>
+    pub struct MacStyle: u16 {
+        const BOLD = 1 << 0;
+        const ITALIC = 1 << 1;
+        const UNDERLINE = 1 << 2;
+        const OUTLINE = 1 << 3;
+        const SHADOW = 1 << 4;
+        const CONDENSED = 1 << 5;
+        const EXTENDED = 1 << 6;
+    }
+}
+
 impl HeadTable {
-    // macStyle:
-    // Bit 0: Bold (if set to 1);
-    // Bit 1: Italic (if set to 1)
-    // Bit 2: Underline (if set to 1)
-    // Bit 3: Outline (if set to 1)
-    // Bit 4: Shadow (if set to 1)
-    // Bit 5: Condensed (if set to 1)
-    // Bit 6: Extended (if set to 1)
-    // Bits 7–15: Reserved (set to 0).
-    // https://docs.microsoft.com/en-us/typography/opentype/spec/head
+    /// The `flags` field as flags. Reserved bits are discarded.
+    pub fn head_flags(&self) -> HeadFlags {
+        HeadFlags::from_bits_truncate(self.flags)
+    }
+
+    /// The `macStyle` field as flags. Reserved bits are discarded.
+    pub fn mac_style_flags(&self) -> MacStyle {
+        MacStyle::from_bits_truncate(self.mac_style)
+    }
+
     pub fn is_bold(&self) -> bool {
-        self.mac_style & 1 != 0
+        self.mac_style_flags().contains(MacStyle::BOLD)
     }
 
     pub fn is_italic(&self) -> bool {
-        self.mac_style & 2 != 0
+        self.mac_style_flags().contains(MacStyle::ITALIC)
     }
 }
 
@@ -523,7 +683,7 @@ impl<'a> ReadBinary<'a> for HheaTable {
     fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
         let major_version = ctxt.read_u16be()?;
         let _minor_version = ctxt.read_u16be()?;
-        ctxt.check(major_version == 1)?;
+        ctxt.check_field(major_version == 1, "majorVersion")?;
         let ascender = ctxt.read_i16be()?;
         let descender = ctxt.read_i16be()?;
         let line_gap = ctxt.read_i16be()?;
@@ -539,7 +699,7 @@ impl<'a> ReadBinary<'a> for HheaTable {
         let _reserved3 = ctxt.read_i16be()?;
         let _reserved4 = ctxt.read_i16be()?;
         let metric_data_format = ctxt.read_i16be()?;
-        ctxt.check(metric_data_format == 0)?;
+        ctxt.check_field(metric_data_format == 0, "metricDataFormat")?;
         let num_h_metrics = ctxt.read_u16be()?;
 
         Ok(HheaTable {
@@ -619,6 +779,74 @@ impl<'a> WriteBinary<&Self> for HmtxTable<'a> {
 }
 
 impl<'a> HmtxTable<'a> {
+    /// Create a table holding `metrics`, one per glyph, using the fewest `longHorMetric` records.
+    ///
+    /// Trailing glyphs that share the advance of the last record only store their side bearing.
+    /// The `numberOfHMetrics` field of `hhea`, or `numOfLongVerMetrics` of `vhea`, must be set to
+    /// the length of `h_metrics` of the returned table.
+    pub fn from_metrics(mut metrics: Vec<LongHorMetric>) -> Self {
+        let num_long_metrics = match metrics.last() {
+            Some(last) => {
+                metrics
+                    .iter()
+                    .rposition(|metric| metric.advance_width != last.advance_width)
+                    .map_or(0, |index| index + 1)
+                    + 1
+            }
+            None => 0,
+        };
+        let left_side_bearings = metrics[num_long_metrics..]
+            .iter()
+            .map(|metric| metric.lsb)
+            .collect();
+        metrics.truncate(num_long_metrics);
+
+        HmtxTable {
+            h_metrics: ReadArrayCow::Owned(metrics),
+            left_side_bearings: ReadArrayCow::Owned(left_side_bearings),
+        }
+    }
+
+    /// Returns the advance and side bearing of each glyph.
+    pub fn metrics(&self) -> Result<Vec<LongHorMetric>, ParseError> {
+        let last = match self.h_metrics.len() {
+            0 if self.left_side_bearings.is_empty() => return Ok(Vec::new()),
+            0 => return Err(ParseError::BadIndex),
+            len => self.h_metrics.read_item(len - 1)?,
+        };
+        let mut metrics = Vec::with_capacity(self.h_metrics.len() + self.left_side_bearings.len());
+        for index in 0..self.h_metrics.len() {
+            metrics.push(self.h_metrics.read_item(index)?);
+        }
+        for index in 0..self.left_side_bearings.len() {
+            metrics.push(LongHorMetric {
+                advance_width: last.advance_width,
+                lsb: self.left_side_bearings.read_item(index)?,
+            });
+        }
+        Ok(metrics)
+    }
+
+    /// Write `table` into `ctxt` using the fewest `longHorMetric` records, as for `from_metrics`.
+    ///
+    /// `hhea`, or `vhea` when writing a `vmtx` table, has `num_h_metrics` set to the number of
+    /// records written. The table must hold metrics for exactly `num_glyphs` glyphs, the value
+    /// from `maxp`.
+    pub fn write_compact<C: WriteContext>(
+        ctxt: &mut C,
+        table: &HmtxTable<'a>,
+        hhea: &mut HheaTable,
+        num_glyphs: u16,
+    ) -> Result<(), WriteError> {
+        let metrics = table.metrics().map_err(|_| WriteError::BadValue)?;
+        if metrics.len() != usize::from(num_glyphs) {
+            return Err(WriteError::BadValue);
+        }
+        let table = HmtxTable::from_metrics(metrics);
+        hhea.num_h_metrics = u16::try_from(table.h_metrics.len())?;
+        HmtxTable::write(ctxt, &table)
+    }
+
     pub fn horizontal_advance(&self, glyph_id: u16, num_h_metrics: u16) -> Result<u16, ParseError> {
         // As an optimization, the number of records can be less than the number of glyphs, in
         // which case the advance width value of the last record applies to all remaining glyph
@@ -658,9 +886,9 @@ impl<'a> ReadBinary<'a> for MaxpTable {
     type HostType = Self;
 
     fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
-        let version = ctxt.read_u32be()?;
+        let version = ctxt.read::<Version16Dot16>()?;
         let num_glyphs = ctxt.read_u16be()?;
-        let sub_table = if version == 0x00010000 {
+        let sub_table = if version.major() >= 1 {
             Some(ctxt.read::<MaxpVersion1SubTable>()?)
         } else {
             None
@@ -677,11 +905,11 @@ impl<'a> WriteBinary<&Self> for MaxpTable {
 
     fn write<C: WriteContext>(ctxt: &mut C, table: &MaxpTable) -> Result<(), WriteError> {
         if let Some(sub_table) = &table.version1_sub_table {
-            U32Be::write(ctxt, 0x00010000u32)?; // version 1.0
+            Version16Dot16::write(ctxt, Version16Dot16::new(1, 0))?;
             U16Be::write(ctxt, table.num_glyphs)?;
             MaxpVersion1SubTable::write(ctxt, sub_table)?;
         } else {
-            U32Be::write(ctxt, 0x00005000u32)?; // version 0.5
+            Version16Dot16::write(ctxt, Version16Dot16::new(0, 5))?;
             U16Be::write(ctxt, table.num_glyphs)?;
         }
         Ok(())
@@ -851,6 +1079,641 @@ impl<'a> WriteBinary for LangTagRecord {
     }
 }
 
+impl<'a> NameTable<'a> {
+    pub const COPYRIGHT_NOTICE: u16 = 0;
+    pub const FONT_FAMILY_NAME: u16 = 1;
+    pub const FONT_SUBFAMILY_NAME: u16 = 2;
+    pub const UNIQUE_IDENTIFIER: u16 = 3;
+    pub const FULL_FONT_NAME: u16 = 4;
+    pub const VERSION_STRING: u16 = 5;
+    pub const POSTSCRIPT_NAME: u16 = 6;
+    pub const TYPOGRAPHIC_FAMILY_NAME: u16 = 16;
+    pub const TYPOGRAPHIC_SUBFAMILY_NAME: u16 = 17;
+    pub const VARIATIONS_POSTSCRIPT_NAME_PREFIX: u16 = 25;
+
+    /// The language ID referring to the first language-tag record.
+    pub const FIRST_LANG_TAG_ID: u16 = 0x8000;
+
+    /// Returns the BCP 47 language tag that `language_id` refers to.
+    ///
+    /// Language IDs of `FIRST_LANG_TAG_ID` and above refer to the language-tag records of a
+    /// format 1 table. Returns `None` for other language IDs and IDs without a record.
+    pub fn lang_tag(&self, language_id: u16) -> Result<Option<String>, ParseError> {
+        let lang_tag_records = match &self.opt_langtag_records {
+            Some(lang_tag_records) if language_id >= Self::FIRST_LANG_TAG_ID => lang_tag_records,
+            _ => return Ok(None),
+        };
+        let index = usize::from(language_id - Self::FIRST_LANG_TAG_ID);
+        if index >= lang_tag_records.len() {
+            return Ok(None);
+        }
+
+        // Language tags are always UTF-16BE
+        let record = lang_tag_records.get_item(index);
+        let data = self
+            .string_storage
+            .offset_length(usize::from(record.offset), usize::from(record.length))?
+            .data();
+        let units = data
+            .chunks_exact(2)
+            .map(|unit| u16::from_be_bytes([unit[0], unit[1]]));
+        let lang_tag = std::char::decode_utf16(units)
+            .collect::<Result<String, _>>()
+            .map_err(|_| ParseError::BadValue)?;
+        Ok(Some(lang_tag))
+    }
+}
+
+/// Builder for editing and writing `name` tables.
+///
+/// Strings are held encoded for the platform and encoding of their record. Records set through
+/// the builder are encoded as UTF-16BE for the Unicode and Windows platforms and Mac OS Roman for
+/// the Macintosh platform.
+#[derive(Clone, Debug, Default)]
+pub struct NameTableBuilder {
+    records: Vec<NameTableBuilderRecord>,
+    lang_tags: Vec<Vec<u8>>,
+}
+
+#[derive(Clone, Debug)]
+struct NameTableBuilderRecord {
+    platform_id: u16,
+    encoding_id: u16,
+    language_id: u16,
+    name_id: u16,
+    data: Vec<u8>,
+}
+
+impl NameTableBuilder {
+    /// Platform, encoding, and language used when adding a name that has no existing records:
+    /// Windows, Unicode BMP, English (United States).
+    const DEFAULT_RECORD: (u16, u16, u16) = (3, 1, 0x409);
+
+    /// Create an empty builder.
+    pub fn new() -> Self {
+        NameTableBuilder::default()
+    }
+
+    /// Create a builder holding a copy of the records in `table`.
+    pub fn from_table(table: &NameTable<'_>) -> Result<Self, ParseError> {
+        let records = table
+            .name_records
+            .iter()
+            .map(|record| {
+                let data = table
+                    .string_storage
+                    .offset_length(usize::from(record.offset), usize::from(record.length))?;
+                Ok(NameTableBuilderRecord {
+                    platform_id: record.platform_id,
+                    encoding_id: record.encoding_id,
+                    language_id: record.language_id,
+                    name_id: record.name_id,
+                    data: data.data().to_vec(),
+                })
+            })
+            .collect::<Result<Vec<_>, ParseError>>()?;
+        let lang_tags = match &table.opt_langtag_records {
+            Some(lang_tag_records) => lang_tag_records
+                .iter()
+                .map(|record| {
+                    let data = table
+                        .string_storage
+                        .offset_length(usize::from(record.offset), usize::from(record.length))?;
+                    Ok(data.data().to_vec())
+                })
+                .collect::<Result<Vec<_>, ParseError>>()?,
+            None => Vec::new(),
+        };
+
+        Ok(NameTableBuilder { records, lang_tags })
+    }
+
+    /// Returns the value of the record identified by `platform_id`, `encoding_id`,
+    /// `language_id`, and `name_id`.
+    ///
+    /// Returns `None` if there is no such record or its encoding is not supported.
+    pub fn name(
+        &self,
+        platform_id: u16,
+        encoding_id: u16,
+        language_id: u16,
+        name_id: u16,
+    ) -> Option<String> {
+        self.records
+            .iter()
+            .find(|record| {
+                record.platform_id == platform_id
+                    && record.encoding_id == encoding_id
+                    && record.language_id == language_id
+                    && record.name_id == name_id
+            })
+            .and_then(|record| decode_name(platform_id, encoding_id, &record.data))
+    }
+
+    /// Add or replace the record identified by `platform_id`, `encoding_id`, `language_id`, and
+    /// `name_id`.
+    ///
+    /// Returns `WriteError::NotImplemented` if the platform and encoding is not supported and
+    /// `WriteError::BadValue` if `value` cannot be represented in the encoding.
+    pub fn set_name(
+        &mut self,
+        platform_id: u16,
+        encoding_id: u16,
+        language_id: u16,
+        name_id: u16,
+        value: &str,
+    ) -> Result<&mut Self, WriteError> {
+        let data = encode_name(platform_id, encoding_id, value)?;
+        match self.records.iter_mut().find(|record| {
+            record.platform_id == platform_id
+                && record.encoding_id == encoding_id
+                && record.language_id == language_id
+                && record.name_id == name_id
+        }) {
+            Some(record) => record.data = data,
+            None => self.records.push(NameTableBuilderRecord {
+                platform_id,
+                encoding_id,
+                language_id,
+                name_id,
+                data,
+            }),
+        }
+
+        Ok(self)
+    }
+
+    /// Returns the language ID referring to the BCP 47 language tag `lang_tag`, such as
+    /// `"en-AU"`, adding a language-tag record for it if there is none.
+    ///
+    /// Writing a table with language-tag records makes it a format 1 table.
+    pub fn lang_tag_id(&mut self, lang_tag: &str) -> Result<u16, WriteError> {
+        let data = encode_name(0, 0, lang_tag)?;
+        let index = match self.lang_tags.iter().position(|tag| *tag == data) {
+            Some(index) => index,
+            None => {
+                self.lang_tags.push(data);
+                self.lang_tags.len() - 1
+            }
+        };
+        u16::try_from(index)
+            .ok()
+            .and_then(|index| NameTable::FIRST_LANG_TAG_ID.checked_add(index))
+            .ok_or(WriteError::BadValue)
+    }
+
+    /// Add or replace the record identified by `platform_id`, `encoding_id`, `lang_tag`, and
+    /// `name_id`, where `lang_tag` is a BCP 47 language tag.
+    ///
+    /// Language tags can only be used by the Unicode and Windows platforms,
+    /// `WriteError::BadValue` is returned for other platforms.
+    pub fn set_name_for_lang_tag(
+        &mut self,
+        platform_id: u16,
+        encoding_id: u16,
+        lang_tag: &str,
+        name_id: u16,
+        value: &str,
+    ) -> Result<&mut Self, WriteError> {
+        if platform_id != 0 && platform_id != 3 {
+            return Err(WriteError::BadValue);
+        }
+        let language_id = self.lang_tag_id(lang_tag)?;
+        self.set_name(platform_id, encoding_id, language_id, name_id, value)
+    }
+
+    /// Replace the value of every record with `name_id`.
+    ///
+    /// Records that cannot hold `value`, such as Macintosh records when `value` contains
+    /// characters outside Mac OS Roman, are removed. If no records remain a Windows Unicode BMP
+    /// record for English (United States) is added.
+    pub fn replace_name(&mut self, name_id: u16, value: &str) -> Result<&mut Self, WriteError> {
+        let mut replaced = false;
+        self.records.retain(|record| {
+            record.name_id != name_id
+                || encode_name(record.platform_id, record.encoding_id, value).is_ok()
+        });
+        for record in self.records.iter_mut() {
+            if record.name_id == name_id {
+                record.data = encode_name(record.platform_id, record.encoding_id, value)?;
+                replaced = true;
+            }
+        }
+
+        if !replaced {
+            let (platform_id, encoding_id, language_id) = Self::DEFAULT_RECORD;
+            self.set_name(platform_id, encoding_id, language_id, name_id, value)?;
+        }
+
+        Ok(self)
+    }
+
+    /// Remove all records with `name_id`.
+    pub fn remove_name(&mut self, name_id: u16) -> &mut Self {
+        self.records.retain(|record| record.name_id != name_id);
+        self
+    }
+
+    /// Retain only the records whose name ID satisfies the predicate `f`.
+    pub fn retain_names<F: FnMut(u16) -> bool>(&mut self, mut f: F) -> &mut Self {
+        self.records.retain(|record| f(record.name_id));
+        self
+    }
+
+    /// Replace the font family name (name ID 1).
+    pub fn family_name(&mut self, value: &str) -> Result<&mut Self, WriteError> {
+        self.replace_name(NameTable::FONT_FAMILY_NAME, value)
+    }
+
+    /// Replace the font subfamily name (name ID 2).
+    pub fn subfamily_name(&mut self, value: &str) -> Result<&mut Self, WriteError> {
+        self.replace_name(NameTable::FONT_SUBFAMILY_NAME, value)
+    }
+
+    /// Replace the unique font identifier (name ID 3).
+    pub fn unique_identifier(&mut self, value: &str) -> Result<&mut Self, WriteError> {
+        self.replace_name(NameTable::UNIQUE_IDENTIFIER, value)
+    }
+
+    /// Replace the PostScript name (name ID 6).
+    ///
+    /// The PostScript name is limited to 63 printable ASCII characters, excluding
+    /// `[](){}<>/%`. `WriteError::BadValue` is returned if `value` does not meet these
+    /// restrictions.
+    pub fn postscript_name(&mut self, value: &str) -> Result<&mut Self, WriteError> {
+        let valid = value.len() <= 63
+            && value
+                .bytes()
+                .all(|b| (33..=126).contains(&b) && !b"[](){}<>/%".contains(&b));
+        if !valid {
+            return Err(WriteError::BadValue);
+        }
+
+        self.replace_name(NameTable::POSTSCRIPT_NAME, value)
+    }
+
+    /// Update the names of a variable font for a static instance at `coordinates`.
+    ///
+    /// `coordinates` are the user coordinates of the instance, one per `fvar` axis as in
+    /// `InstanceRecord`. The style name is made of the names of the `STAT` axis values at the
+    /// instance, in axis order, leaving out elidable names such as "Regular" and the names of
+    /// values on hidden axes, which aren't meant to be seen by users. The font family (1), subfamily
+    /// (2), unique identifier (3), full font (4), PostScript (6), and typographic family and
+    /// subfamily (16, 17) names are then rewritten following the conventions of the fontTools
+    /// instancer:
+    ///
+    /// * Styles made only of Regular, Italic, Bold, and Bold Italic are held in the subfamily
+    ///   name, and the typographic names are removed. Other styles are held in the typographic
+    ///   subfamily name, with the rest of the style appended to the family name, so "Condensed
+    ///   Bold" becomes family "Family Condensed" and subfamily "Bold".
+    /// * When all names are elided the `STAT` elided fallback name is used, usually "Regular".
+    /// * The PostScript name is derived from the variations PostScript name prefix (25) or family
+    ///   name and the style name. Names longer than 63 characters are shortened with a hash so
+    ///   that they remain unique.
+    ///
+    /// The names of each platform, encoding, and language are updated separately, and those
+    /// without a family, subfamily and elided fallback name are left as they are. The variations
+    /// PostScript name prefix is removed, as the instance is no longer variable.
+    pub fn update_instance_names(
+        &mut self,
+        fvar: &FvarTable,
+        stat: &StatTable,
+        coordinates: &[f32],
+    ) -> Result<&mut Self, WriteError> {
+        const RIBBI: [&str; 4] = ["Regular", "Italic", "Bold", "Bold Italic"];
+
+        let hidden_axis_tags = fvar
+            .axes
+            .iter()
+            .filter(|axis| axis.is_hidden())
+            .map(|axis| axis.axis_tag)
+            .collect::<Vec<_>>();
+        let is_hidden = |axis_index: u16| {
+            stat.design_axes
+                .get(usize::from(axis_index))
+                .map(|axis| hidden_axis_tags.contains(&axis.axis_tag))
+                .unwrap_or(false)
+        };
+        let axis_value_name_ids = stat
+            .axis_values_at(&fvar.location(coordinates))
+            .iter()
+            .filter(|axis_value| !axis_value.axis_indices().into_iter().all(is_hidden))
+            .map(|axis_value| axis_value.value_name_id)
+            .collect::<Vec<_>>();
+        let elided_name_id = stat
+            .elided_fallback_name_id
+            .unwrap_or(NameTable::FONT_SUBFAMILY_NAME);
+
+        let mut platforms = self
+            .records
+            .iter()
+            .map(|record| (record.platform_id, record.encoding_id, record.language_id))
+            .collect::<Vec<_>>();
+        platforms.sort_unstable();
+        platforms.dedup();
+
+        let mut updates = Vec::new();
+        for (platform_id, encoding_id, language_id) in platforms {
+            let get = |name_id| self.name(platform_id, encoding_id, language_id, name_id);
+            // Whether a name is one of the four styles, as judged by its English name
+            let is_ribbi = |name_id| {
+                self.name(3, 1, 0x409, name_id)
+                    .or_else(|| get(name_id))
+                    .map(|name| RIBBI.contains(&name.as_str()))
+                    .unwrap_or(false)
+            };
+            let join = |name_ids: &[u16]| {
+                name_ids
+                    .iter()
+                    .map(|&name_id| get(name_id))
+                    .collect::<Option<Vec<_>>>()
+                    .map(|names| names.join(" "))
+            };
+
+            let (family_name, elided_name) = match (
+                get(NameTable::FONT_FAMILY_NAME),
+                get(NameTable::FONT_SUBFAMILY_NAME),
+                get(elided_name_id),
+            ) {
+                (Some(family_name), Some(_), Some(elided_name)) => (family_name, elided_name),
+                _ => continue,
+            };
+            let (ribbi, non_ribbi): (Vec<u16>, Vec<u16>) = axis_value_name_ids
+                .iter()
+                .partition(|&&name_id| is_ribbi(name_id));
+            let (mut subfamily_name, family_suffix, all) =
+                match (join(&ribbi), join(&non_ribbi), join(&axis_value_name_ids)) {
+                    (Some(ribbi), Some(non_ribbi), Some(all)) => (ribbi, non_ribbi, all),
+                    _ => continue,
+                };
+            let mut typo_subfamily_name = if non_ribbi.is_empty() {
+                None
+            } else {
+                Some(all)
+            };
+            if subfamily_name.is_empty() && typo_subfamily_name.is_none() {
+                if is_ribbi(elided_name_id) {
+                    subfamily_name = elided_name;
+                } else {
+                    typo_subfamily_name = Some(elided_name);
+                }
+            }
+            if subfamily_name.is_empty() {
+                subfamily_name = String::from("Regular");
+            }
+
+            let current_family_name =
+                get(NameTable::TYPOGRAPHIC_FAMILY_NAME).unwrap_or(family_name);
+            let mut names = Vec::new();
+            let (new_family_name, new_style_name) = match typo_subfamily_name {
+                Some(typo_subfamily_name) => {
+                    let family_name = format!("{} {}", current_family_name, family_suffix);
+                    names.push((NameTable::FONT_FAMILY_NAME, family_name.trim().to_string()));
+                    names.push((
+                        NameTable::TYPOGRAPHIC_FAMILY_NAME,
+                        current_family_name.clone(),
+                    ));
+                    names.push((
+                        NameTable::TYPOGRAPHIC_SUBFAMILY_NAME,
+                        typo_subfamily_name.clone(),
+                    ));
+                    (current_family_name, typo_subfamily_name)
+                }
+                None => {
+                    names.push((NameTable::FONT_FAMILY_NAME, current_family_name.clone()));
+                    (current_family_name, subfamily_name.clone())
+                }
+            };
+            names.push((NameTable::FONT_SUBFAMILY_NAME, subfamily_name));
+
+            let full_name = format!("{} {}", new_family_name, new_style_name);
+            let prefix = get(NameTable::VARIATIONS_POSTSCRIPT_NAME_PREFIX)
+                .unwrap_or_else(|| new_family_name.clone());
+            let postscript_name = instance_postscript_name(&prefix, &new_style_name);
+            if let Some(unique_id) = get(NameTable::UNIQUE_IDENTIFIER) {
+                let unique_id = [
+                    (NameTable::FULL_FONT_NAME, &full_name),
+                    (NameTable::POSTSCRIPT_NAME, &postscript_name),
+                ]
+                .iter()
+                .find_map(|&(name_id, new_name)| {
+                    get(name_id)
+                        .filter(|old_name| !old_name.is_empty() && unique_id.contains(old_name))
+                        .map(|old_name| unique_id.replace(&old_name, new_name))
+                })
+                .unwrap_or_else(|| {
+                    // Use the version number from a version string such as "Version 1.002"
+                    let version = get(NameTable::VERSION_STRING).unwrap_or_default();
+                    let version = version
+                        .trim_start_matches("Version")
+                        .split(|ch: char| ch == ';' || ch.is_whitespace())
+                        .find(|part| !part.is_empty())
+                        .unwrap_or("")
+                        .to_string();
+                    format!("{};{}", version, postscript_name)
+                });
+                names.push((NameTable::UNIQUE_IDENTIFIER, unique_id));
+            }
+            names.push((NameTable::FULL_FONT_NAME, full_name));
+            names.push((NameTable::POSTSCRIPT_NAME, postscript_name));
+
+            updates.push(((platform_id, encoding_id, language_id), names));
+        }
+
+        for ((platform_id, encoding_id, language_id), names) in updates {
+            let has_typographic_names = names
+                .iter()
+                .any(|&(name_id, _)| name_id == NameTable::TYPOGRAPHIC_SUBFAMILY_NAME);
+            if !has_typographic_names {
+                self.records.retain(|record| {
+                    (record.platform_id, record.encoding_id, record.language_id)
+                        != (platform_id, encoding_id, language_id)
+                        || (record.name_id != NameTable::TYPOGRAPHIC_FAMILY_NAME
+                            && record.name_id != NameTable::TYPOGRAPHIC_SUBFAMILY_NAME)
+                });
+            }
+            for (name_id, value) in names {
+                self.set_name(platform_id, encoding_id, language_id, name_id, &value)?;
+            }
+        }
+        self.remove_name(NameTable::VARIATIONS_POSTSCRIPT_NAME_PREFIX);
+
+        Ok(self)
+    }
+}
+
+/// Make the PostScript name of an instance from `prefix` and `style_name`.
+///
+/// Only letters, digits, and hyphens are kept. Following Adobe Technical Note #5902, names longer
+/// than 63 characters are replaced by the start of the prefix, a hash of the name, and "...".
+fn instance_postscript_name(prefix: &str, style_name: &str) -> String {
+    const MAX_LEN: usize = 63;
+    const HASH_LEN: usize = 16;
+
+    let name = format!("{}-{}", prefix, style_name)
+        .chars()
+        .filter(|ch| ch.is_ascii_alphanumeric() || *ch == '-')
+        .collect::<String>();
+    if name.len() <= MAX_LEN {
+        return name;
+    }
+
+    // 64-bit FNV-1a
+    let hash = name.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    });
+    let prefix = prefix
+        .chars()
+        .filter(|ch| ch.is_ascii_alphanumeric() || *ch == '-')
+        .take(MAX_LEN - HASH_LEN - "-...".len())
+        .collect::<String>();
+    format!("{}-{:016X}...", prefix, hash)
+}
+
+impl WriteBinary<&Self> for NameTableBuilder {
+    type Output = ();
+
+    fn write<C: WriteContext>(ctxt: &mut C, name: &NameTableBuilder) -> Result<(), WriteError> {
+        // Records must be sorted by platform ID, encoding ID, language ID, and then name ID
+        let mut records = name.records.iter().collect::<Vec<_>>();
+        records.sort_by_key(|record| {
+            (
+                record.platform_id,
+                record.encoding_id,
+                record.language_id,
+                record.name_id,
+            )
+        });
+
+        let format: u16 = if name.lang_tags.is_empty() { 0 } else { 1 };
+        U16Be::write(ctxt, format)?;
+        U16Be::write(ctxt, u16::try_from(records.len())?)?; // count
+        let string_offset = ctxt.placeholder::<U16Be, _>()?;
+
+        // Identical strings share storage
+        let mut string_storage: Vec<u8> = Vec::new();
+        let mut add_string = |data: &[u8]| -> Result<(u16, u16), WriteError> {
+            let existing = if data.is_empty() {
+                Some(0)
+            } else {
+                string_storage
+                    .windows(data.len())
+                    .position(|window| window == data)
+            };
+            let offset = existing.unwrap_or_else(|| {
+                let offset = string_storage.len();
+                string_storage.extend_from_slice(data);
+                offset
+            });
+            Ok((u16::try_from(data.len())?, u16::try_from(offset)?))
+        };
+
+        for record in records {
+            let (length, offset) = add_string(&record.data)?;
+            NameRecord::write(
+                ctxt,
+                NameRecord {
+                    platform_id: record.platform_id,
+                    encoding_id: record.encoding_id,
+                    language_id: record.language_id,
+                    name_id: record.name_id,
+                    length,
+                    offset,
+                },
+            )?;
+        }
+
+        if format > 0 {
+            U16Be::write(ctxt, u16::try_from(name.lang_tags.len())?)?; // lang_tag_count
+            for lang_tag in &name.lang_tags {
+                let (length, offset) = add_string(lang_tag)?;
+                LangTagRecord::write(ctxt, LangTagRecord { length, offset })?;
+            }
+        }
+
+        ctxt.write_placeholder(string_offset, u16::try_from(ctxt.bytes_written())?)?;
+        ctxt.write_bytes(&string_storage)?;
+
+        Ok(())
+    }
+}
+
+/// Encode `value` for storage in a `name` record with `platform_id` and `encoding_id`.
+fn encode_name(platform_id: u16, encoding_id: u16, value: &str) -> Result<Vec<u8>, WriteError> {
+    match (platform_id, encoding_id) {
+        // Unicode; Windows Symbol, Unicode BMP, and Unicode full repertoire
+        (0, _) | (3, 0) | (3, 1) | (3, 10) => Ok(value
+            .encode_utf16()
+            .flat_map(|unit| unit.to_be_bytes().to_vec())
+            .collect()),
+        // Macintosh Roman
+        (1, 0) => value
+            .chars()
+            .map(|ch| char_to_macroman(ch).ok_or(WriteError::BadValue))
+            .collect(),
+        _ => Err(WriteError::NotImplemented),
+    }
+}
+
+/// Decode a string held in the encoding of `platform_id` and `encoding_id`.
+///
+/// Returns `None` if the encoding is not supported or `data` is not valid in it.
+pub(crate) fn decode_name(platform_id: u16, encoding_id: u16, data: &[u8]) -> Option<String> {
+    match (platform_id, encoding_id) {
+        // Unicode; Windows Symbol, Unicode BMP, and Unicode full repertoire
+        (0, _) | (3, 0) | (3, 1) | (3, 10) => {
+            let chunks = data.chunks_exact(2);
+            if !chunks.remainder().is_empty() {
+                return None;
+            }
+            let units = chunks.map(|unit| u16::from_be_bytes([unit[0], unit[1]]));
+            std::char::decode_utf16(units)
+                .collect::<Result<String, _>>()
+                .ok()
+        }
+        // Macintosh Roman
+        (1, 0) => data.iter().map(|&byte| macroman_to_char(byte)).collect(),
+        _ => None,
+    }
+}
+
+/// Serializes the records with their strings decoded. The string of a record in an unsupported
+/// encoding is serialized as `None`.
+#[cfg(feature = "serde")]
+impl serde::Serialize for NameTableBuilder {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let lang_tags = self
+            .lang_tags
+            .iter()
+            .map(|data| decode_name(0, 0, data))
+            .collect::<Vec<_>>();
+        let mut state = serializer.serialize_struct("NameTableBuilder", 2)?;
+        state.serialize_field("records", &self.records)?;
+        state.serialize_field("lang_tags", &lang_tags)?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for NameTableBuilderRecord {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("NameRecord", 5)?;
+        state.serialize_field("platform_id", &self.platform_id)?;
+        state.serialize_field("encoding_id", &self.encoding_id)?;
+        state.serialize_field("language_id", &self.language_id)?;
+        state.serialize_field("name_id", &self.name_id)?;
+        state.serialize_field(
+            "value",
+            &decode_name(self.platform_id, self.encoding_id, &self.data),
+        )?;
+        state.end()
+    }
+}
+
 impl<'a> ReadFrom<'a> for F2Dot14 {
     type ReadType = U16Be;
 
@@ -859,6 +1722,12 @@ impl<'a> ReadFrom<'a> for F2Dot14 {
     }
 }
 
+impl From<F2Dot14> for f32 {
+    fn from(value: F2Dot14) -> f32 {
+        f32::from(value.0 as i16) / 16384.0
+    }
+}
+
 impl WriteBinary for F2Dot14 {
     type Output = ();
 
@@ -896,6 +1765,74 @@ impl F2Dot14 {
     pub fn new(value: u16) -> Self {
         F2Dot14(value)
     }
+
+    /// Convert `value` to the nearest representable value, saturating at the limits of the
+    /// range -2 to just under 2. NaN is converted to 0.
+    pub fn from_f32(value: f32) -> Self {
+        let scaled = (value * 16384.0).round();
+        let raw = if scaled.is_nan() {
+            0
+        } else if scaled <= f32::from(std::i16::MIN) {
+            std::i16::MIN
+        } else if scaled >= f32::from(std::i16::MAX) {
+            std::i16::MAX
+        } else {
+            scaled as i16
+        };
+        F2Dot14(raw as u16)
+    }
+}
+
+impl<'a> ReadFrom<'a> for Version16Dot16 {
+    type ReadType = U32Be;
+
+    fn from(value: u32) -> Self {
+        Version16Dot16::from_raw(value)
+    }
+}
+
+impl WriteBinary for Version16Dot16 {
+    type Output = ();
+
+    fn write<C: WriteContext>(ctxt: &mut C, version: Self) -> Result<(), WriteError> {
+        U32Be::write(ctxt, version.0)
+    }
+}
+
+impl Version16Dot16 {
+    /// Construct a version from its major and minor parts. `minor` must be in the range 0–9.
+    pub const fn new(major: u16, minor: u8) -> Self {
+        Version16Dot16(((major as u32) << 16) | (((minor & 0xF) as u32) << 12))
+    }
+
+    /// Construct a version from the raw value read from a font, canonicalising it if required.
+    pub fn from_raw(value: u32) -> Self {
+        let major = value >> 16;
+        let fraction = value & 0xFFFF;
+        // A fraction of 0x8000 is taken to be the Fixed value 0.5 since no table has a minor
+        // version of 8.
+        let minor = if fraction & 0x0FFF == 0 && fraction < 0xA000 && fraction != 0x8000 {
+            fraction >> 12
+        } else {
+            // Not a packed minor version, interpret it as a Fixed fraction rounded to the nearest
+            // tenth instead.
+            std::cmp::min((fraction * 10 + 0x8000) >> 16, 9)
+        };
+        Version16Dot16((major << 16) | (minor << 12))
+    }
+
+    pub fn major(self) -> u16 {
+        (self.0 >> 16) as u16
+    }
+
+    pub fn minor(self) -> u8 {
+        ((self.0 >> 12) & 0xF) as u8
+    }
+
+    /// The canonical raw value of this version.
+    pub fn raw(self) -> u32 {
+        self.0
+    }
 }
 
 impl<T: FontTableProvider> FontTableProvider for Box<T> {
@@ -906,13 +1843,23 @@ impl<T: FontTableProvider> FontTableProvider for Box<T> {
     fn has_table<'a>(&'a self, tag: u32) -> bool {
         self.as_ref().has_table(tag)
     }
+
+    fn table_tags(&self) -> Option<Vec<u32>> {
+        self.as_ref().table_tags()
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{HeadTable, HmtxTable, NameTable};
+    use super::{
+        instance_postscript_name, FvarTable, HeadTable, HheaTable, HmtxTable, MaxpTable, NameTable,
+        NameTableBuilder, StatTable, Version16Dot16,
+    };
     use crate::binary::read::ReadScope;
     use crate::binary::write::{WriteBinary, WriteBuffer, WriteContext};
+    use crate::get_name::fontcode_get_name;
+    use crate::tables::fvar::{AxisFlags, VariationAxisRecord};
+    use crate::tag;
 
     #[test]
     fn test_write_head_table() {
@@ -945,6 +1892,49 @@ mod tests {
         assert_eq!(ctxt.bytes(), &hmtx_data[..]);
     }
 
+    #[test]
+    fn test_write_compact_hmtx_table() {
+        let hmtx_data = include_bytes!("../tests/fonts/opentype/hmtx.bin");
+        let hmtx = ReadScope::new(hmtx_data)
+            .read_dep::<HmtxTable<'_>>((1264, 1264))
+            .unwrap();
+        let mut metrics = hmtx.metrics().unwrap();
+        // Give the trailing glyphs the same advance so that they collapse into side bearings
+        for metric in &mut metrics[1200..] {
+            metric.advance_width = 500;
+        }
+        let hmtx = HmtxTable::from_metrics(metrics.clone());
+        let mut hhea = HheaTable {
+            ascender: 800,
+            descender: -200,
+            line_gap: 0,
+            advance_width_max: 0,
+            min_left_side_bearing: 0,
+            min_right_side_bearing: 0,
+            x_max_extent: 0,
+            caret_slope_rise: 1,
+            caret_slope_run: 0,
+            caret_offset: 0,
+            num_h_metrics: 1264,
+        };
+
+        let mut ctxt = WriteBuffer::new();
+        HmtxTable::write_compact(&mut ctxt, &hmtx, &mut hhea, 1264).unwrap();
+        assert!(hhea.num_h_metrics <= 1201);
+        assert_eq!(
+            ctxt.bytes_written(),
+            usize::from(hhea.num_h_metrics) * 4 + (1264 - usize::from(hhea.num_h_metrics)) * 2
+        );
+        let written = ReadScope::new(ctxt.bytes())
+            .read_dep::<HmtxTable<'_>>((1264, usize::from(hhea.num_h_metrics)))
+            .unwrap();
+        assert_eq!(written.metrics().unwrap(), metrics);
+
+        // The table must hold a metric for every glyph
+        let mut ctxt = WriteBuffer::new();
+        assert!(HmtxTable::write_compact(&mut ctxt, &hmtx, &mut hhea, 1000).is_err());
+    }
+
     #[test]
     fn test_write_name_table() {
         // Read a name table in, then write it back out and compare it
@@ -956,4 +1946,329 @@ mod tests {
 
         assert_eq!(ctxt.bytes(), &name_data[..]);
     }
+
+    #[test]
+    fn test_name_table_builder_replace() {
+        let name_data = include_bytes!("../tests/fonts/opentype/name.bin");
+        let name = ReadScope::new(name_data).read::<NameTable<'_>>().unwrap();
+        let mut builder = NameTableBuilder::from_table(&name).unwrap();
+        builder
+            .family_name("Renamed Sans")
+            .unwrap()
+            .subfamily_name("Bold")
+            .unwrap()
+            .unique_identifier("1.000;Renamed Sans Bold")
+            .unwrap()
+            .postscript_name("RenamedSans-Bold")
+            .unwrap();
+
+        let mut ctxt = WriteBuffer::new();
+        NameTableBuilder::write(&mut ctxt, &builder).unwrap();
+        let data = ctxt.into_inner();
+
+        let get_name = |name_id| {
+            fontcode_get_name(&data, name_id)
+                .unwrap()
+                .map(|name| name.into_string().unwrap())
+        };
+        assert_eq!(
+            get_name(NameTable::FONT_FAMILY_NAME).unwrap(),
+            "Renamed Sans"
+        );
+        assert_eq!(get_name(NameTable::FONT_SUBFAMILY_NAME).unwrap(), "Bold");
+        assert_eq!(
+            get_name(NameTable::UNIQUE_IDENTIFIER).unwrap(),
+            "1.000;Renamed Sans Bold"
+        );
+        assert_eq!(
+            get_name(NameTable::POSTSCRIPT_NAME).unwrap(),
+            "RenamedSans-Bold"
+        );
+
+        // Records for other name IDs are retained
+        let original = |name_id| fontcode_get_name(&name_data[..], name_id).unwrap();
+        assert_eq!(
+            get_name(NameTable::VERSION_STRING),
+            original(NameTable::VERSION_STRING).map(|name| name.into_string().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_name_table_builder_encodings() {
+        let mut builder = NameTableBuilder::new();
+        builder.set_name(1, 0, 0, 1, "Caf\u{e9}").unwrap();
+        builder.set_name(3, 1, 0x409, 1, "Caf\u{e9}").unwrap();
+        builder
+            .set_name(3, 1, 0x409, 1, "Caf\u{e9} \u{2713}")
+            .unwrap();
+        assert!(builder.set_name(1, 0, 0, 2, "\u{2713}").is_err());
+        assert!(builder.set_name(3, 2, 0x409, 2, "Bold").is_err());
+
+        let mut ctxt = WriteBuffer::new();
+        NameTableBuilder::write(&mut ctxt, &builder).unwrap();
+        let data = ctxt.into_inner();
+        let name = ReadScope::new(&data).read::<NameTable<'_>>().unwrap();
+
+        assert_eq!(name.name_records.len(), 2);
+        let strings = name
+            .name_records
+            .iter()
+            .map(|record| {
+                let scope = name
+                    .string_storage
+                    .offset_length(usize::from(record.offset), usize::from(record.length))
+                    .unwrap();
+                (record.platform_id, scope.data().to_vec())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(strings[0], (1, b"Caf\x8E".to_vec()));
+        assert_eq!(
+            strings[1],
+            (
+                3,
+                vec![0, b'C', 0, b'a', 0, b'f', 0, 0xE9, 0, b' ', 0x27, 0x13]
+            )
+        );
+    }
+
+    #[test]
+    fn test_name_table_builder_lang_tags() {
+        let mut builder = NameTableBuilder::new();
+        builder.set_name(3, 1, 0x409, 1, "Colour Sans").unwrap();
+        builder
+            .set_name_for_lang_tag(3, 1, "en-AU", 1, "Colour Sans AU")
+            .unwrap();
+        builder
+            .set_name_for_lang_tag(0, 4, "ja-Hira", 1, "\u{304B}\u{306A}")
+            .unwrap();
+        assert_eq!(builder.lang_tag_id("en-AU").unwrap(), 0x8000);
+        assert!(builder
+            .set_name_for_lang_tag(1, 0, "en-AU", 1, "Colour Sans")
+            .is_err());
+
+        let mut ctxt = WriteBuffer::new();
+        NameTableBuilder::write(&mut ctxt, &builder).unwrap();
+        let data = ctxt.into_inner();
+        let name = ReadScope::new(&data).read::<NameTable<'_>>().unwrap();
+
+        assert_eq!(name.opt_langtag_records.as_ref().unwrap().len(), 2);
+        let language_ids = name
+            .name_records
+            .iter()
+            .map(|record| record.language_id)
+            .collect::<Vec<_>>();
+        assert_eq!(language_ids, vec![0x8001, 0x409, 0x8000]);
+        assert_eq!(name.lang_tag(0x8000).unwrap(), Some(String::from("en-AU")));
+        assert_eq!(
+            name.lang_tag(0x8001).unwrap(),
+            Some(String::from("ja-Hira"))
+        );
+        assert_eq!(name.lang_tag(0x8002).unwrap(), None);
+        assert_eq!(name.lang_tag(0x409).unwrap(), None);
+
+        // Language tags survive a round trip through the builder
+        let builder = NameTableBuilder::from_table(&name).unwrap();
+        let mut ctxt = WriteBuffer::new();
+        NameTableBuilder::write(&mut ctxt, &builder).unwrap();
+        assert_eq!(ctxt.into_inner(), data);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_name_table_builder_serialize() {
+        let mut builder = NameTableBuilder::new();
+        builder.set_name(1, 0, 0, 1, "Caf\u{e9}").unwrap();
+        builder
+            .set_name_for_lang_tag(3, 1, "en-AU", 1, "Colour Sans AU")
+            .unwrap();
+
+        let json = serde_json::to_value(&builder).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "records": [
+                    {
+                        "platform_id": 1,
+                        "encoding_id": 0,
+                        "language_id": 0,
+                        "name_id": 1,
+                        "value": "Caf\u{e9}",
+                    },
+                    {
+                        "platform_id": 3,
+                        "encoding_id": 1,
+                        "language_id": 0x8000,
+                        "name_id": 1,
+                        "value": "Colour Sans AU",
+                    },
+                ],
+                "lang_tags": ["en-AU"],
+            })
+        );
+    }
+
+    #[test]
+    fn test_name_table_builder_invalid_postscript_name() {
+        let mut builder = NameTableBuilder::new();
+        assert!(builder.postscript_name("Has Space").is_err());
+        assert!(builder.postscript_name("Bad(Name)").is_err());
+        assert!(builder.postscript_name(&"A".repeat(64)).is_err());
+        assert!(builder.postscript_name("Good-Name").is_ok());
+    }
+
+    #[test]
+    fn test_update_instance_names() {
+        let stat_data = crate::tables::stat::tests::stat_data();
+        let stat = ReadScope::new(&stat_data).read::<StatTable>().unwrap();
+        let axis = |axis_tag, flags| VariationAxisRecord {
+            axis_tag,
+            min_value: 0.,
+            default_value: 100.,
+            max_value: 1000.,
+            flags,
+            axis_name_id: 0,
+        };
+        let mut fvar = FvarTable {
+            axes: vec![axis(tag!(b"wght"), 0), axis(tag!(b"wdth"), 0)],
+            instances: Vec::new(),
+        };
+        let variable_names = || {
+            let mut builder = NameTableBuilder::new();
+            let names = [
+                (1, "Test Sans"),
+                (2, "Regular"),
+                (3, "1.000;TEST;TestSans-Regular"),
+                (4, "Test Sans Regular"),
+                (5, "Version 1.000"),
+                (6, "TestSans-Regular"),
+                (25, "TestSansVF"),
+                (256, "Regular"),
+                (257, "Bold"),
+                (258, "Light"),
+                (259, "Condensed"),
+                (260, "Normal"),
+                (261, "Bold Condensed"),
+            ];
+            for &(name_id, value) in &names {
+                builder.set_name(3, 1, 0x409, name_id, value).unwrap();
+            }
+            builder.set_name(1, 0, 0, 1, "Test Sans").unwrap();
+            builder
+        };
+        let names = |builder: &NameTableBuilder| {
+            [1, 2, 3, 4, 6, 16, 17, 25]
+                .iter()
+                .map(|&name_id| builder.name(3, 1, 0x409, name_id))
+                .collect::<Vec<_>>()
+        };
+        let some = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| Some(name.to_string()))
+                .collect::<Vec<_>>()
+        };
+
+        // A style of the four basic styles stays in the subfamily name
+        let mut builder = variable_names();
+        builder
+            .update_instance_names(&fvar, &stat, &[700., 100.])
+            .unwrap();
+        let mut expected = some(&[
+            "Test Sans",
+            "Bold",
+            "1.000;TEST;TestSansVF-Bold",
+            "Test Sans Bold",
+            "TestSansVF-Bold",
+        ]);
+        expected.extend(vec![None, None, None]);
+        assert_eq!(names(&builder), expected);
+        // Records of other platforms without a subfamily name are left alone
+        assert_eq!(builder.name(1, 0, 0, 1), Some(String::from("Test Sans")));
+        assert_eq!(builder.name(1, 0, 0, 6), None);
+
+        // Other styles use the typographic names
+        let mut builder = variable_names();
+        builder.remove_name(NameTable::VARIATIONS_POSTSCRIPT_NAME_PREFIX);
+        builder
+            .update_instance_names(&fvar, &stat, &[320., 75.])
+            .unwrap();
+        let mut expected = some(&[
+            "Test Sans Light Condensed",
+            "Regular",
+            "1.000;TEST;TestSans-LightCondensed",
+            "Test Sans Light Condensed",
+            "TestSans-LightCondensed",
+            "Test Sans",
+            "Light Condensed",
+        ]);
+        expected.push(None);
+        assert_eq!(names(&builder), expected);
+
+        // With every name elided the fallback name is used
+        let mut builder = variable_names();
+        builder.remove_name(NameTable::VARIATIONS_POSTSCRIPT_NAME_PREFIX);
+        builder
+            .update_instance_names(&fvar, &stat, &[400., 100.])
+            .unwrap();
+        assert_eq!(builder.name(3, 1, 0x409, 2), Some(String::from("Regular")));
+        assert_eq!(
+            builder.name(3, 1, 0x409, 6),
+            Some(String::from("TestSans-Regular"))
+        );
+
+        // Values on hidden axes are left out of the names
+        fvar.axes[1].flags = AxisFlags::HIDDEN_AXIS.bits();
+        let mut builder = variable_names();
+        builder.remove_name(NameTable::VARIATIONS_POSTSCRIPT_NAME_PREFIX);
+        builder
+            .update_instance_names(&fvar, &stat, &[320., 75.])
+            .unwrap();
+        assert_eq!(
+            builder.name(3, 1, 0x409, 4),
+            Some(String::from("Test Sans Light"))
+        );
+    }
+
+    #[test]
+    fn test_instance_postscript_name() {
+        assert_eq!(
+            instance_postscript_name("Test Sans", "Bold (Narrow)"),
+            "TestSans-BoldNarrow"
+        );
+        let long = instance_postscript_name(&"Family".repeat(8), "Extra Condensed Black Italic");
+        assert_eq!(long.len(), 63);
+        assert!(long.starts_with("FamilyFamily"));
+        assert!(long.ends_with("..."));
+        assert_ne!(
+            long,
+            instance_postscript_name(&"Family".repeat(8), "Extra Condensed Black")
+        );
+    }
+
+    #[test]
+    fn test_version16dot16_canonical() {
+        let version = Version16Dot16::from_raw(0x00025000);
+        assert_eq!(version, Version16Dot16::new(2, 5));
+        assert_eq!((version.major(), version.minor()), (2, 5));
+        assert_eq!(Version16Dot16::from_raw(0x00010000).raw(), 0x00010000);
+    }
+
+    #[test]
+    fn test_version16dot16_fixed_quirk() {
+        // 0.5 and 2.5 encoded as Fixed values rather than packed versions
+        assert_eq!(Version16Dot16::from_raw(0x00008000).raw(), 0x00005000);
+        assert_eq!(Version16Dot16::from_raw(0x00028000).raw(), 0x00025000);
+    }
+
+    #[test]
+    fn test_read_maxp_fixed_version() {
+        let maxp_data = [0x00, 0x00, 0x80, 0x00, 0x01, 0x00];
+        let maxp = ReadScope::new(&maxp_data).read::<MaxpTable>().unwrap();
+        assert_eq!(maxp.num_glyphs, 256);
+        assert!(maxp.version1_sub_table.is_none());
+
+        let mut ctxt = WriteBuffer::new();
+        MaxpTable::write(&mut ctxt, &maxp).unwrap();
+        assert_eq!(ctxt.bytes(), &[0x00, 0x00, 0x50, 0x00, 0x01, 0x00]);
+    }
 }
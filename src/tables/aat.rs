@@ -0,0 +1,311 @@
+#![deny(missing_docs)]
+
+//! Common structures of Apple Advanced Typography tables.
+//!
+//! AAT tables such as `kerx` and `morx` share lookup tables, which map glyphs to values, and
+//! extended state tables, which drive finite state machines over a glyph sequence.
+//!
+//! <https://developer.apple.com/fonts/TrueType-Reference-Manual/RM06/Chap6Tables.html>
+
+use std::convert::TryFrom;
+
+use crate::binary::read::{ReadBinary, ReadCtxt, ReadScope};
+use crate::binary::U16Be;
+use crate::error::ParseError;
+
+/// The class of the end of the glyph sequence.
+pub const CLASS_END_OF_TEXT: u16 = 0;
+/// The class of glyphs that are not in the class table.
+pub const CLASS_OUT_OF_BOUNDS: u16 = 1;
+/// The class of deleted glyphs.
+pub const CLASS_DELETED_GLYPH: u16 = 2;
+/// The class of the end of a line.
+pub const CLASS_END_OF_LINE: u16 = 3;
+
+/// The glyph index used by AAT tables to mark a deleted glyph.
+pub const DELETED_GLYPH: u16 = 0xFFFF;
+
+/// The state that a state machine starts processing text in.
+pub const STATE_START_OF_TEXT: u16 = 0;
+
+/// The number of times a state machine may stay on the same glyph before it is forced to advance.
+///
+/// This guards against state tables that loop without consuming glyphs.
+pub const MAX_DONT_ADVANCE: usize = 64;
+
+/// An AAT lookup table, mapping glyphs to 16-bit values.
+///
+/// <https://developer.apple.com/fonts/TrueType-Reference-Manual/RM06/Chap6Tables.html#LookupTables>
+#[derive(Clone)]
+pub struct LookupTable<'a> {
+    /// The format of the lookup table.
+    pub format: u16,
+    scope: ReadScope<'a>,
+}
+
+/// The header of an extended state table, as used by `kerx` and `morx`.
+///
+/// <https://developer.apple.com/fonts/TrueType-Reference-Manual/RM06/Chap6Tables.html#StateTables>
+#[derive(Clone)]
+pub struct ExtendedStateTable<'a> {
+    /// The number of classes, which is the width of each row of the state array.
+    pub n_classes: u32,
+    /// Maps glyphs to their class.
+    pub class_table: LookupTable<'a>,
+    state_array: ReadScope<'a>,
+    entry_table: ReadScope<'a>,
+}
+
+/// An entry of a state table.
+pub struct StateEntry<'a> {
+    /// The state to move to after processing this entry.
+    pub new_state: u16,
+    /// The flags of the entry, their meaning depends on the table.
+    pub flags: u16,
+    /// The table specific data following the flags.
+    pub data: ReadScope<'a>,
+}
+
+impl<'a> ReadBinary<'a> for LookupTable<'a> {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let scope = ctxt.scope();
+        let format = ctxt.read_u16be()?;
+        match format {
+            0 | 2 | 4 | 6 | 8 | 10 => Ok(LookupTable { format, scope }),
+            _ => Err(ParseError::BadValue),
+        }
+    }
+}
+
+impl<'a> LookupTable<'a> {
+    /// Returns the value of `glyph` in the lookup table, or `None` if it has no value.
+    pub fn lookup(&self, glyph: u16) -> Result<Option<u16>, ParseError> {
+        let mut ctxt = self.scope.offset(2).ctxt();
+        match self.format {
+            // Simple array, indexed by glyph
+            0 => {
+                let mut ctxt = self.scope.offset(2 + 2 * usize::from(glyph)).ctxt();
+                Ok(ctxt.read_u16be().ok())
+            }
+            // Segment single and segment array
+            2 | 4 => {
+                let segment = match self.binary_search(&mut ctxt, glyph, true)? {
+                    Some(segment) => segment,
+                    None => return Ok(None),
+                };
+                let mut ctxt = segment.ctxt();
+                let _last_glyph = ctxt.read_u16be()?;
+                let first_glyph = ctxt.read_u16be()?;
+                let value = ctxt.read_u16be()?;
+                if self.format == 2 {
+                    return Ok(Some(value));
+                }
+                // The value is the offset to an array of values for the glyphs in the segment
+                let index = usize::from(glyph - first_glyph);
+                let mut ctxt = self.scope.offset(usize::from(value) + 2 * index).ctxt();
+                Ok(Some(ctxt.read_u16be()?))
+            }
+            // Single table
+            6 => match self.binary_search(&mut ctxt, glyph, false)? {
+                Some(entry) => {
+                    let mut ctxt = entry.offset(2).ctxt();
+                    Ok(Some(ctxt.read_u16be()?))
+                }
+                None => Ok(None),
+            },
+            // Trimmed array
+            8 => {
+                let first_glyph = ctxt.read_u16be()?;
+                let glyph_count = ctxt.read_u16be()?;
+                match glyph.checked_sub(first_glyph) {
+                    Some(index) if index < glyph_count => {
+                        let values = ctxt.read_array::<U16Be>(usize::from(glyph_count))?;
+                        Ok(Some(values.get_item(usize::from(index))))
+                    }
+                    _ => Ok(None),
+                }
+            }
+            // Extended trimmed array
+            10 => {
+                let unit_size = ctxt.read_u16be()?;
+                let first_glyph = ctxt.read_u16be()?;
+                let glyph_count = ctxt.read_u16be()?;
+                let index = match glyph.checked_sub(first_glyph) {
+                    Some(index) if index < glyph_count => usize::from(index),
+                    _ => return Ok(None),
+                };
+                let mut ctxt = ctxt.scope().offset(index * usize::from(unit_size)).ctxt();
+                match unit_size {
+                    1 => Ok(Some(u16::from(ctxt.read_u8()?))),
+                    2 => Ok(Some(ctxt.read_u16be()?)),
+                    _ => Err(ParseError::NotImplemented),
+                }
+            }
+            _ => Err(ParseError::BadValue),
+        }
+    }
+
+    /// Searches the units of a binary search table for `glyph`.
+    ///
+    /// Units start with a glyph, or a last and first glyph if `segments` is `true`. Returns the
+    /// scope of the unit containing `glyph`.
+    fn binary_search(
+        &self,
+        ctxt: &mut ReadCtxt<'a>,
+        glyph: u16,
+        segments: bool,
+    ) -> Result<Option<ReadScope<'a>>, ParseError> {
+        let unit_size = usize::from(ctxt.read_u16be()?);
+        let n_units = usize::from(ctxt.read_u16be()?);
+        let _search_range = ctxt.read_u16be()?;
+        let _entry_selector = ctxt.read_u16be()?;
+        let _range_shift = ctxt.read_u16be()?;
+        ctxt.check(unit_size >= if segments { 6 } else { 4 })?;
+        let units = ctxt.read_scope(unit_size * n_units)?;
+
+        let (mut lo, mut hi) = (0, n_units);
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            let unit = units.offset_length(mid * unit_size, unit_size)?;
+            let mut unit_ctxt = unit.ctxt();
+            let last_glyph = unit_ctxt.read_u16be()?;
+            let first_glyph = if segments {
+                unit_ctxt.read_u16be()?
+            } else {
+                last_glyph
+            };
+            if glyph < first_glyph {
+                hi = mid;
+            } else if glyph > last_glyph {
+                lo = mid + 1;
+            } else if last_glyph == 0xFFFF {
+                // Sentinel unit terminating the table
+                return Ok(None);
+            } else {
+                return Ok(Some(unit));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<'a> ReadBinary<'a> for ExtendedStateTable<'a> {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let scope = ctxt.scope();
+        let n_classes = ctxt.read_u32be()?;
+        let class_table_offset = usize::try_from(ctxt.read_u32be()?)?;
+        let state_array_offset = usize::try_from(ctxt.read_u32be()?)?;
+        let entry_table_offset = usize::try_from(ctxt.read_u32be()?)?;
+        ctxt.check(n_classes >= 4)?;
+        let class_table = scope.offset(class_table_offset).read::<LookupTable<'a>>()?;
+
+        Ok(ExtendedStateTable {
+            n_classes,
+            class_table,
+            state_array: scope.offset(state_array_offset),
+            entry_table: scope.offset(entry_table_offset),
+        })
+    }
+}
+
+impl<'a> ExtendedStateTable<'a> {
+    /// Returns the class of `glyph`.
+    pub fn class(&self, glyph: u16) -> Result<u16, ParseError> {
+        if glyph == DELETED_GLYPH {
+            return Ok(CLASS_DELETED_GLYPH);
+        }
+        Ok(self
+            .class_table
+            .lookup(glyph)?
+            .unwrap_or(CLASS_OUT_OF_BOUNDS))
+    }
+
+    /// Returns the entry for `class` in `state`.
+    ///
+    /// `data_size` is the size of the table specific data that follows the new state and flags of
+    /// each entry.
+    pub fn entry(
+        &self,
+        state: u16,
+        class: u16,
+        data_size: usize,
+    ) -> Result<StateEntry<'a>, ParseError> {
+        let n_classes = usize::try_from(self.n_classes)?;
+        let class = if u32::from(class) < self.n_classes {
+            usize::from(class)
+        } else {
+            usize::from(CLASS_OUT_OF_BOUNDS)
+        };
+        let index_offset = (usize::from(state) * n_classes + class) * 2;
+        let entry_index = self.state_array.offset(index_offset).ctxt().read_u16be()?;
+
+        let entry_size = 4 + data_size;
+        let entry = self
+            .entry_table
+            .offset_length(usize::from(entry_index) * entry_size, entry_size)?;
+        let mut ctxt = entry.ctxt();
+        let new_state = ctxt.read_u16be()?;
+        let flags = ctxt.read_u16be()?;
+        Ok(StateEntry {
+            new_state,
+            flags,
+            data: entry.offset(4),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::write::WriteBuffer;
+    use crate::tests::write_u16s;
+
+    fn u16s(values: &[u16]) -> Vec<u8> {
+        let mut w = WriteBuffer::new();
+        write_u16s(&mut w, values);
+        w.into_inner()
+    }
+
+    fn lookup(data: &[u8], glyph: u16) -> Option<u16> {
+        let table = ReadScope::new(data).read::<LookupTable<'_>>().unwrap();
+        table.lookup(glyph).unwrap()
+    }
+
+    #[test]
+    fn test_lookup_formats() {
+        let format0 = u16s(&[0, 5, 6, 7]);
+        assert_eq!(lookup(&format0, 1), Some(6));
+        assert_eq!(lookup(&format0, 3), None);
+
+        // Segments 10..=12 and 20..=20, followed by a sentinel
+        let format2 = u16s(&[2, 6, 3, 12, 1, 6, 12, 10, 4, 20, 20, 5, 0xFFFF, 0xFFFF, 0]);
+        assert_eq!(lookup(&format2, 11), Some(4));
+        assert_eq!(lookup(&format2, 20), Some(5));
+        assert_eq!(lookup(&format2, 13), None);
+        assert_eq!(lookup(&format2, 9), None);
+
+        // Segment 10..=11 with values at offset 24
+        let format4 = u16s(&[4, 6, 2, 12, 1, 0, 11, 10, 24, 0xFFFF, 0xFFFF, 0, 8, 9]);
+        assert_eq!(lookup(&format4, 10), Some(8));
+        assert_eq!(lookup(&format4, 11), Some(9));
+        assert_eq!(lookup(&format4, 12), None);
+
+        let format6 = u16s(&[6, 4, 2, 8, 1, 0, 3, 30, 7, 70]);
+        assert_eq!(lookup(&format6, 3), Some(30));
+        assert_eq!(lookup(&format6, 7), Some(70));
+        assert_eq!(lookup(&format6, 5), None);
+
+        let format8 = u16s(&[8, 5, 2, 50, 60]);
+        assert_eq!(lookup(&format8, 6), Some(60));
+        assert_eq!(lookup(&format8, 4), None);
+        assert_eq!(lookup(&format8, 7), None);
+
+        let format10 = u16s(&[10, 2, 5, 2, 50, 60]);
+        assert_eq!(lookup(&format10, 5), Some(50));
+        assert_eq!(lookup(&format10, 7), None);
+    }
+}
@@ -0,0 +1,302 @@
+#![deny(missing_docs)]
+
+//! `avar` table parsing.
+//!
+//! The `avar` table modifies the default normalisation of the user coordinates of a variable
+//! font. Version 1 maps each axis through a piecewise linear function. Version 2 additionally
+//! holds deltas in an item variation store that adjust each normalised coordinate based on the
+//! coordinates of all axes, allowing non-linear interpolation across axes.
+//!
+//! <https://docs.microsoft.com/en-us/typography/opentype/spec/avar>
+
+use std::convert::TryFrom;
+
+use crate::binary::read::{ReadArray, ReadBinary, ReadCtxt, ReadFrom};
+use crate::error::ParseError;
+use crate::tables::vvar::{DeltaSetIndexMap, ItemVariationStore};
+use crate::tables::F2Dot14;
+
+/// The `avar` axis variations table.
+pub struct AvarTable<'a> {
+    /// The major version of the table, 1 or 2.
+    pub major_version: u16,
+    /// The segment map of each axis, in the order of the axes in `fvar`.
+    pub segment_maps: Vec<SegmentMap<'a>>,
+    /// Maps axis indices to the deltas of their coordinates.
+    ///
+    /// When absent the axis index is used as the inner index, with an outer index of zero.
+    /// Always absent in version 1.
+    pub axis_index_map: Option<DeltaSetIndexMap<'a>>,
+    /// The deltas of the normalised coordinates, always absent in version 1.
+    pub item_variation_store: Option<ItemVariationStore<'a>>,
+}
+
+/// A piecewise linear mapping of the normalised coordinates of an axis.
+pub struct SegmentMap<'a> {
+    /// The points of the mapping, sorted by `from_coordinate`.
+    pub axis_value_maps: ReadArray<'a, AxisValueMap>,
+}
+
+/// A single point of a segment map.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AxisValueMap {
+    /// The default normalised coordinate.
+    pub from_coordinate: F2Dot14,
+    /// The modified normalised coordinate.
+    pub to_coordinate: F2Dot14,
+}
+
+impl<'a> ReadBinary<'a> for AvarTable<'a> {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let scope = ctxt.scope();
+        let major_version = ctxt.read_u16be()?;
+        ctxt.check_version(major_version == 1 || major_version == 2)?;
+        let _minor_version = ctxt.read_u16be()?;
+        let _reserved = ctxt.read_u16be()?;
+        let axis_count = ctxt.read_u16be()?;
+        let segment_maps = (0..axis_count)
+            .map(|_| ctxt.read::<SegmentMap<'a>>())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let (axis_index_map, item_variation_store) = if major_version >= 2 {
+            let axis_index_map_offset = ctxt.read_u32be()?;
+            let item_variation_store_offset = ctxt.read_u32be()?;
+            let axis_index_map = match axis_index_map_offset {
+                0 => None,
+                offset => Some(
+                    scope
+                        .offset(usize::try_from(offset)?)
+                        .read::<DeltaSetIndexMap<'a>>()?,
+                ),
+            };
+            let item_variation_store = match item_variation_store_offset {
+                0 => None,
+                offset => Some(
+                    scope
+                        .offset(usize::try_from(offset)?)
+                        .read::<ItemVariationStore<'a>>()?,
+                ),
+            };
+            (axis_index_map, item_variation_store)
+        } else {
+            (None, None)
+        };
+
+        Ok(AvarTable {
+            major_version,
+            segment_maps,
+            axis_index_map,
+            item_variation_store,
+        })
+    }
+}
+
+impl<'a> ReadBinary<'a> for SegmentMap<'a> {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let position_map_count = ctxt.read_u16be()?;
+        let axis_value_maps = ctxt.read_array::<AxisValueMap>(usize::from(position_map_count))?;
+        Ok(SegmentMap { axis_value_maps })
+    }
+}
+
+impl<'a> ReadFrom<'a> for AxisValueMap {
+    type ReadType = (F2Dot14, F2Dot14);
+
+    fn from((from_coordinate, to_coordinate): (F2Dot14, F2Dot14)) -> Self {
+        AxisValueMap {
+            from_coordinate,
+            to_coordinate,
+        }
+    }
+}
+
+impl<'a> AvarTable<'a> {
+    /// Apply the table to the default normalised coordinates `coords`, one per axis, such as
+    /// those returned by `FvarTable::normalize`.
+    ///
+    /// Each coordinate is first mapped through the segment map of its axis. In version 2 the
+    /// deltas held for each axis are then evaluated at the mapped coordinates and added to them,
+    /// with the result clamped to the range -1 to 1.
+    pub fn map_coordinates(&self, coords: &[F2Dot14]) -> Result<Vec<F2Dot14>, ParseError> {
+        let mapped = coords
+            .iter()
+            .enumerate()
+            .map(
+                |(axis_index, &coord)| match self.segment_maps.get(axis_index) {
+                    Some(segment_map) => F2Dot14::from_f32(segment_map.map(f32::from(coord))),
+                    None => coord,
+                },
+            )
+            .collect::<Vec<_>>();
+
+        let item_variation_store = match self.item_variation_store {
+            Some(ref item_variation_store) => item_variation_store,
+            None => return Ok(mapped),
+        };
+        mapped
+            .iter()
+            .enumerate()
+            .map(|(axis_index, &coord)| {
+                let (outer, inner) = match self.axis_index_map {
+                    Some(ref mapping) => mapping.get(u32::try_from(axis_index)?)?,
+                    None => (0, u16::try_from(axis_index)?),
+                };
+                // Deltas are in units of the normalised coordinates, 1/16384
+                let delta = item_variation_store.delta(outer, inner, &mapped)?;
+                let value = f32::from(coord) + delta.round() / 16384.;
+                let value = if value.abs() > 1. {
+                    value.signum()
+                } else {
+                    value
+                };
+                Ok(F2Dot14::from_f32(value))
+            })
+            .collect()
+    }
+}
+
+impl<'a> SegmentMap<'a> {
+    /// Map the normalised coordinate `coord` through the segment map.
+    ///
+    /// Coordinates beyond the first or last point are offset by the same amount as that point.
+    /// An empty map leaves coordinates unchanged.
+    pub fn map(&self, coord: f32) -> f32 {
+        let mut prev: Option<(f32, f32)> = None;
+        for axis_value_map in self.axis_value_maps.iter() {
+            let from = f32::from(axis_value_map.from_coordinate);
+            let to = f32::from(axis_value_map.to_coordinate);
+            if coord == from {
+                return to;
+            }
+            if coord < from {
+                return match prev {
+                    Some((prev_from, prev_to)) => {
+                        prev_to + (to - prev_to) * (coord - prev_from) / (from - prev_from)
+                    }
+                    None => coord + to - from,
+                };
+            }
+            prev = Some((from, to));
+        }
+        match prev {
+            Some((from, to)) => coord + to - from,
+            None => coord,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::read::ReadScope;
+    use crate::binary::write::{WriteBinary, WriteBuffer, WriteContext};
+    use crate::binary::{I16Be, U16Be, U32Be};
+
+    /// Builds a version 1 `avar` table with one axis, mapping 0.5 to 0.25.
+    fn avar_v1_data() -> Vec<u8> {
+        let mut w = WriteBuffer::new();
+        U16Be::write(&mut w, 1u16).unwrap();
+        U16Be::write(&mut w, 0u16).unwrap();
+        U16Be::write(&mut w, 0u16).unwrap();
+        U16Be::write(&mut w, 1u16).unwrap(); // axis count
+        U16Be::write(&mut w, 4u16).unwrap(); // position map count
+        for &(from, to) in &[
+            (0xC000u16, 0xC000u16),
+            (0, 0),
+            (0x2000, 0x1000),
+            (0x4000, 0x4000),
+        ] {
+            U16Be::write(&mut w, from).unwrap();
+            U16Be::write(&mut w, to).unwrap();
+        }
+        w.into_inner()
+    }
+
+    /// Builds a version 2 `avar` table with two axes and empty segment maps. The coordinate of
+    /// the second axis is increased by up to 0.25 as the coordinate of the first axis increases
+    /// towards 1.0.
+    fn avar_v2_data() -> Vec<u8> {
+        let mut w = WriteBuffer::new();
+        // Header
+        U16Be::write(&mut w, 2u16).unwrap();
+        U16Be::write(&mut w, 0u16).unwrap();
+        U16Be::write(&mut w, 0u16).unwrap();
+        U16Be::write(&mut w, 2u16).unwrap(); // axis count
+        U16Be::write(&mut w, 0u16).unwrap(); // axis 0 position map count
+        U16Be::write(&mut w, 0u16).unwrap(); // axis 1 position map count
+        U32Be::write(&mut w, 0u32).unwrap(); // axis index map
+        U32Be::write(&mut w, 20u32).unwrap(); // item variation store
+
+        // Item variation store at 20
+        U16Be::write(&mut w, 1u16).unwrap();
+        U32Be::write(&mut w, 12u32).unwrap(); // region list
+        U16Be::write(&mut w, 1u16).unwrap();
+        U32Be::write(&mut w, 28u32).unwrap(); // item variation data
+
+        // Region list at 32, a single region peaking at 1.0 on the first axis
+        U16Be::write(&mut w, 2u16).unwrap(); // axis count
+        U16Be::write(&mut w, 1u16).unwrap(); // region count
+        U16Be::write(&mut w, 0u16).unwrap();
+        U16Be::write(&mut w, 0x4000u16).unwrap();
+        U16Be::write(&mut w, 0x4000u16).unwrap();
+        w.write_zeros(6).unwrap();
+
+        // Item variation data at 48, one item per axis
+        U16Be::write(&mut w, 2u16).unwrap();
+        U16Be::write(&mut w, 1u16).unwrap();
+        U16Be::write(&mut w, 1u16).unwrap();
+        U16Be::write(&mut w, 0u16).unwrap();
+        I16Be::write(&mut w, 0i16).unwrap();
+        I16Be::write(&mut w, 0x1000i16).unwrap();
+
+        w.into_inner()
+    }
+
+    #[test]
+    fn test_segment_map() {
+        let data = avar_v1_data();
+        let avar = ReadScope::new(&data).read::<AvarTable<'_>>().unwrap();
+        assert_eq!(avar.major_version, 1);
+        assert!(avar.item_variation_store.is_none());
+        let segment_map = &avar.segment_maps[0];
+        assert_eq!(segment_map.map(0.5), 0.25);
+        assert_eq!(segment_map.map(0.75), 0.625);
+        assert_eq!(segment_map.map(-0.5), -0.5);
+        assert_eq!(segment_map.map(1.5), 1.5);
+
+        let coords = avar
+            .map_coordinates(&[F2Dot14::new(0x2000), F2Dot14::new(0x2000)])
+            .unwrap();
+        assert_eq!(coords, vec![F2Dot14::new(0x1000), F2Dot14::new(0x2000)]);
+    }
+
+    #[test]
+    fn test_map_coordinates_v2() {
+        let data = avar_v2_data();
+        let avar = ReadScope::new(&data).read::<AvarTable<'_>>().unwrap();
+        assert_eq!(avar.major_version, 2);
+        assert!(avar.axis_index_map.is_none());
+
+        let map = |coords: [f32; 2]| -> Vec<f32> {
+            let coords = coords
+                .iter()
+                .map(|&c| F2Dot14::from_f32(c))
+                .collect::<Vec<_>>();
+            avar.map_coordinates(&coords)
+                .unwrap()
+                .into_iter()
+                .map(f32::from)
+                .collect()
+        };
+        assert_eq!(map([0., 0.]), vec![0., 0.]);
+        assert_eq!(map([1., 0.]), vec![1., 0.25]);
+        assert_eq!(map([0.5, 0.]), vec![0.5, 0.125]);
+        assert_eq!(map([0., 0.5]), vec![0., 0.5]);
+        // Coordinates are clamped after the deltas are applied
+        assert_eq!(map([1., 1.]), vec![1., 1.]);
+    }
+}
@@ -0,0 +1,470 @@
+#![deny(missing_docs)]
+
+//! `BASE` table parsing.
+//!
+//! The `BASE` table holds the positions of the baselines used by each script in a font, such as
+//! the Roman baseline used by Latin and the ideographic baselines used by CJK scripts. This allows
+//! text in different scripts, as well as inline images, to be aligned when mixed on a line.
+//!
+//! Device table and contour point adjustments of coordinates are not applied.
+//!
+//! <https://docs.microsoft.com/en-us/typography/opentype/spec/base>
+
+use crate::binary::read::{ReadArray, ReadBinary, ReadCtxt, ReadFrom, ReadScope};
+use crate::binary::{U16Be, U32Be};
+use crate::error::ParseError;
+use crate::tag;
+
+/// The `BASE` table.
+pub struct BaseTable<'a> {
+    /// Baseline data for horizontal text, if present.
+    pub horiz_axis: Option<BaseAxis<'a>>,
+    /// Baseline data for vertical text, if present.
+    pub vert_axis: Option<BaseAxis<'a>>,
+}
+
+/// Baseline data for one text direction.
+pub struct BaseAxis<'a> {
+    /// The baseline tags used by the scripts of the axis, such as `romn` and `ideo`, sorted
+    /// alphabetically.
+    pub baseline_tags: ReadArray<'a, U32Be>,
+    scope: ReadScope<'a>,
+    base_script_records: ReadArray<'a, BaseScriptRecord>,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct BaseScriptRecord {
+    base_script_tag: u32,
+    base_script_offset: u16,
+}
+
+/// Baseline data for a script.
+pub struct BaseScript<'a> {
+    scope: ReadScope<'a>,
+    base_values_offset: u16,
+    default_min_max_offset: u16,
+    base_lang_sys_records: ReadArray<'a, BaseLangSysRecord>,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct BaseLangSysRecord {
+    base_lang_sys_tag: u32,
+    min_max_offset: u16,
+}
+
+/// The positions of the baselines of a script.
+pub struct BaseValues {
+    /// The index into `BaseAxis::baseline_tags` of the baseline the script uses by default.
+    pub default_baseline_index: u16,
+    /// The coordinates of the baselines, in the same order as `BaseAxis::baseline_tags`.
+    pub base_coords: Vec<i16>,
+}
+
+/// The minimum and maximum extents of the glyphs of a script or language system.
+///
+/// For the horizontal axis these are heights, for the vertical axis widths. An extent is `None`
+/// when the font doesn't specify it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MinMax {
+    /// The minimum extent.
+    pub min_coord: Option<i16>,
+    /// The maximum extent.
+    pub max_coord: Option<i16>,
+}
+
+impl<'a> ReadBinary<'a> for BaseTable<'a> {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let scope = ctxt.scope();
+        let major_version = ctxt.read_u16be()?;
+        ctxt.check_version(major_version == 1)?;
+        let _minor_version = ctxt.read_u16be()?;
+        let horiz_axis_offset = ctxt.read_u16be()?;
+        let vert_axis_offset = ctxt.read_u16be()?;
+        // Version 1.1 adds an item variation store, which is not used
+
+        let horiz_axis = read_optional::<BaseAxis<'_>>(&scope, horiz_axis_offset)?;
+        let vert_axis = read_optional::<BaseAxis<'_>>(&scope, vert_axis_offset)?;
+
+        Ok(BaseTable {
+            horiz_axis,
+            vert_axis,
+        })
+    }
+}
+
+impl<'a> ReadBinary<'a> for BaseAxis<'a> {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let scope = ctxt.scope();
+        let base_tag_list_offset = ctxt.read_u16be()?;
+        let base_script_list_offset = ctxt.read_u16be()?;
+
+        let baseline_tags = if base_tag_list_offset == 0 {
+            ReadArray::empty()
+        } else {
+            let mut ctxt = scope.offset(usize::from(base_tag_list_offset)).ctxt();
+            let base_tag_count = ctxt.read_u16be()?;
+            ctxt.read_array::<U32Be>(usize::from(base_tag_count))?
+        };
+
+        let scope = scope.offset(usize::from(base_script_list_offset));
+        let mut ctxt = scope.ctxt();
+        let base_script_count = ctxt.read_u16be()?;
+        let base_script_records =
+            ctxt.read_array::<BaseScriptRecord>(usize::from(base_script_count))?;
+
+        Ok(BaseAxis {
+            baseline_tags,
+            scope,
+            base_script_records,
+        })
+    }
+}
+
+impl<'a> ReadFrom<'a> for BaseScriptRecord {
+    type ReadType = (U32Be, U16Be);
+
+    fn from((base_script_tag, base_script_offset): (u32, u16)) -> Self {
+        BaseScriptRecord {
+            base_script_tag,
+            base_script_offset,
+        }
+    }
+}
+
+impl<'a> ReadBinary<'a> for BaseScript<'a> {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let scope = ctxt.scope();
+        let base_values_offset = ctxt.read_u16be()?;
+        let default_min_max_offset = ctxt.read_u16be()?;
+        let base_lang_sys_count = ctxt.read_u16be()?;
+        let base_lang_sys_records =
+            ctxt.read_array::<BaseLangSysRecord>(usize::from(base_lang_sys_count))?;
+
+        Ok(BaseScript {
+            scope,
+            base_values_offset,
+            default_min_max_offset,
+            base_lang_sys_records,
+        })
+    }
+}
+
+impl<'a> ReadFrom<'a> for BaseLangSysRecord {
+    type ReadType = (U32Be, U16Be);
+
+    fn from((base_lang_sys_tag, min_max_offset): (u32, u16)) -> Self {
+        BaseLangSysRecord {
+            base_lang_sys_tag,
+            min_max_offset,
+        }
+    }
+}
+
+impl<'a> ReadBinary<'a> for BaseValues {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let scope = ctxt.scope();
+        let default_baseline_index = ctxt.read_u16be()?;
+        let base_coord_count = ctxt.read_u16be()?;
+        let base_coord_offsets = ctxt.read_array::<U16Be>(usize::from(base_coord_count))?;
+        let base_coords = base_coord_offsets
+            .iter()
+            .map(|offset| read_base_coord(&scope, offset))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(BaseValues {
+            default_baseline_index,
+            base_coords,
+        })
+    }
+}
+
+impl<'a> ReadBinary<'a> for MinMax {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let scope = ctxt.scope();
+        let min_coord_offset = ctxt.read_u16be()?;
+        let max_coord_offset = ctxt.read_u16be()?;
+        // Feature specific extents follow, these are not used
+
+        let min_coord = match min_coord_offset {
+            0 => None,
+            offset => Some(read_base_coord(&scope, offset)?),
+        };
+        let max_coord = match max_coord_offset {
+            0 => None,
+            offset => Some(read_base_coord(&scope, offset)?),
+        };
+
+        Ok(MinMax {
+            min_coord,
+            max_coord,
+        })
+    }
+}
+
+impl<'a> BaseTable<'a> {
+    /// Returns the baseline data for the supplied text direction.
+    pub fn axis(&self, vertical: bool) -> Option<&BaseAxis<'a>> {
+        if vertical {
+            self.vert_axis.as_ref()
+        } else {
+            self.horiz_axis.as_ref()
+        }
+    }
+}
+
+impl<'a> BaseAxis<'a> {
+    /// Returns the baseline data of `script_tag`.
+    ///
+    /// The `DFLT` script is used if the script has no data of its own.
+    pub fn find_script_or_default(
+        &self,
+        script_tag: u32,
+    ) -> Result<Option<BaseScript<'a>>, ParseError> {
+        match self.find_script(script_tag)? {
+            Some(base_script) => Ok(Some(base_script)),
+            None => self.find_script(tag::DFLT),
+        }
+    }
+
+    fn find_script(&self, script_tag: u32) -> Result<Option<BaseScript<'a>>, ParseError> {
+        match self
+            .base_script_records
+            .iter()
+            .find(|record| record.base_script_tag == script_tag)
+        {
+            Some(record) => self
+                .scope
+                .offset(usize::from(record.base_script_offset))
+                .read::<BaseScript<'_>>()
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the coordinate of the baseline `baseline_tag` for `script_tag`.
+    ///
+    /// Returns `None` if the font doesn't define the baseline for the script.
+    pub fn baseline(&self, script_tag: u32, baseline_tag: u32) -> Result<Option<i16>, ParseError> {
+        let index = match self
+            .baseline_tags
+            .iter()
+            .position(|tag_value| tag_value == baseline_tag)
+        {
+            Some(index) => index,
+            None => return Ok(None),
+        };
+        let base_values = match self.find_script_or_default(script_tag)? {
+            Some(base_script) => base_script.base_values()?,
+            None => None,
+        };
+        Ok(base_values.and_then(|base_values| base_values.base_coords.get(index).copied()))
+    }
+
+    /// Returns the tag of the baseline `script_tag` uses by default, such as `romn` for Latin.
+    pub fn default_baseline(&self, script_tag: u32) -> Result<Option<u32>, ParseError> {
+        let base_values = match self.find_script_or_default(script_tag)? {
+            Some(base_script) => base_script.base_values()?,
+            None => None,
+        };
+        let index = match base_values {
+            Some(base_values) => usize::from(base_values.default_baseline_index),
+            None => return Ok(None),
+        };
+        if index < self.baseline_tags.len() {
+            Ok(Some(self.baseline_tags.get_item(index)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Returns the distance from the default baseline of `script_tag` to `baseline_tag`, which
+    /// is positive when `baseline_tag` is above the default baseline.
+    ///
+    /// This is the amount to move glyphs of the script down by so that `baseline_tag` sits on the
+    /// baseline of the line. For example, ideographs aligned with surrounding Latin text are
+    /// moved by the offset of the `romn` baseline for `hani`.
+    pub fn baseline_offset(
+        &self,
+        script_tag: u32,
+        baseline_tag: u32,
+    ) -> Result<Option<i16>, ParseError> {
+        let default_baseline = match self.default_baseline(script_tag)? {
+            Some(default_baseline) => default_baseline,
+            None => return Ok(None),
+        };
+        let default_coord = self.baseline(script_tag, default_baseline)?;
+        let coord = self.baseline(script_tag, baseline_tag)?;
+        match (default_coord, coord) {
+            (Some(default_coord), Some(coord)) => Ok(Some(coord.saturating_sub(default_coord))),
+            _ => Ok(None),
+        }
+    }
+
+    /// Returns the extents of the glyphs of `script_tag` and the language system `opt_lang_tag`.
+    ///
+    /// The extents of the script are used if there are none for the language system.
+    pub fn min_max(
+        &self,
+        script_tag: u32,
+        opt_lang_tag: Option<u32>,
+    ) -> Result<Option<MinMax>, ParseError> {
+        match self.find_script_or_default(script_tag)? {
+            Some(base_script) => base_script.min_max(opt_lang_tag),
+            None => Ok(None),
+        }
+    }
+}
+
+impl<'a> BaseScript<'a> {
+    /// Returns the positions of the baselines of the script, if present.
+    pub fn base_values(&self) -> Result<Option<BaseValues>, ParseError> {
+        read_optional::<BaseValues>(&self.scope, self.base_values_offset)
+    }
+
+    /// Returns the extents of the glyphs of the language system `opt_lang_tag`, falling back to
+    /// those of the script.
+    pub fn min_max(&self, opt_lang_tag: Option<u32>) -> Result<Option<MinMax>, ParseError> {
+        let lang_sys_record = opt_lang_tag.and_then(|lang_tag| {
+            self.base_lang_sys_records
+                .iter()
+                .find(|record| record.base_lang_sys_tag == lang_tag)
+        });
+        let offset = match lang_sys_record {
+            Some(record) if record.min_max_offset != 0 => record.min_max_offset,
+            _ => self.default_min_max_offset,
+        };
+        read_optional::<MinMax>(&self.scope, offset)
+    }
+}
+
+fn read_base_coord(scope: &ReadScope<'_>, offset: u16) -> Result<i16, ParseError> {
+    let mut ctxt = scope.offset(usize::from(offset)).ctxt();
+    match ctxt.read_u16be()? {
+        // Formats 2 and 3 add a contour point and device table respectively, which aren't used
+        1..=3 => Ok(ctxt.read_i16be()?),
+        _ => Err(ParseError::BadVersion),
+    }
+}
+
+fn read_optional<'a, T: ReadBinary<'a>>(
+    scope: &ReadScope<'a>,
+    offset: u16,
+) -> Result<Option<T::HostType>, ParseError> {
+    if offset == 0 {
+        Ok(None)
+    } else {
+        scope.offset(usize::from(offset)).read::<T>().map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::write::{WriteBinary, WriteBuffer};
+    use crate::tests::write_u16s;
+
+    fn jan() -> u32 {
+        tag::from_string("JAN ").unwrap()
+    }
+
+    // A table with a horizontal axis using the `ideo` and `romn` baselines. Latin uses the Roman
+    // baseline at 0, and the ideographic baseline at -120. Han uses the ideographic baseline,
+    // with extents overridden for Japanese.
+    fn base_table_data() -> Vec<u8> {
+        let mut w = WriteBuffer::new();
+        // Header
+        write_u16s(&mut w, &[1, 0, 8, 0]);
+        // Axis at 8: tag list, script list
+        write_u16s(&mut w, &[4, 14]);
+        // BaseTagList at 12
+        write_u16s(&mut w, &[2]);
+        U32Be::write(&mut w, tag::IDEO).unwrap();
+        U32Be::write(&mut w, tag::ROMN).unwrap();
+        // BaseScriptList at 22
+        write_u16s(&mut w, &[2]);
+        U32Be::write(&mut w, tag::HANI).unwrap();
+        write_u16s(&mut w, &[14]);
+        U32Be::write(&mut w, tag::LATN).unwrap();
+        write_u16s(&mut w, &[66]);
+        // BaseScript for hani at 36: values, default min max, lang sys records
+        write_u16s(&mut w, &[12, 28, 1]);
+        U32Be::write(&mut w, jan()).unwrap();
+        write_u16s(&mut w, &[38]);
+        // BaseValues at 48: default index 0, two coords
+        write_u16s(&mut w, &[0, 2, 8, 12]);
+        write_u16s(&mut w, &[1, 0xFFE2_u16]); // ideo: -30
+        write_u16s(&mut w, &[1, 90]); // romn: 90
+                                      // Default MinMax at 64: min, max, feature count, min coord
+        write_u16s(&mut w, &[6, 0, 0, 1, 0xFF88_u16]); // min: -120
+                                                       // MinMax for JAN at 74
+        write_u16s(&mut w, &[6, 10, 0, 1, 0xFF9C_u16, 1, 880]); // min: -100, max: 880
+                                                                // BaseScript for latn at 88
+        write_u16s(&mut w, &[6, 0, 0]);
+        // BaseValues at 94: default index 1
+        write_u16s(&mut w, &[1, 2, 8, 12]);
+        write_u16s(&mut w, &[1, 0xFF88_u16]); // ideo: -120
+        write_u16s(&mut w, &[1, 0]); // romn: 0
+
+        w.into_inner()
+    }
+
+    #[test]
+    fn test_baselines() {
+        let data = base_table_data();
+        let base = ReadScope::new(&data).read::<BaseTable<'_>>().unwrap();
+        assert!(base.vert_axis.is_none());
+        let axis = base.axis(false).unwrap();
+
+        assert_eq!(axis.default_baseline(tag::HANI).unwrap(), Some(tag::IDEO));
+        assert_eq!(axis.default_baseline(tag::LATN).unwrap(), Some(tag::ROMN));
+        assert_eq!(axis.baseline(tag::HANI, tag::IDEO).unwrap(), Some(-30));
+        assert_eq!(axis.baseline(tag::HANI, tag::ROMN).unwrap(), Some(90));
+        assert_eq!(axis.baseline(tag::LATN, tag::IDEO).unwrap(), Some(-120));
+        assert_eq!(axis.baseline(tag::LATN, tag::HANG).unwrap(), None);
+        // No data for Cyrillic and no DFLT script
+        assert_eq!(axis.baseline(tag::CYRL, tag::ROMN).unwrap(), None);
+
+        // The Roman baseline of ideographs is 120 units above their ideographic baseline
+        assert_eq!(
+            axis.baseline_offset(tag::HANI, tag::ROMN).unwrap(),
+            Some(120)
+        );
+        assert_eq!(axis.baseline_offset(tag::LATN, tag::ROMN).unwrap(), Some(0));
+    }
+
+    #[test]
+    fn test_min_max() {
+        let data = base_table_data();
+        let base = ReadScope::new(&data).read::<BaseTable<'_>>().unwrap();
+        let axis = base.axis(false).unwrap();
+
+        let min_max = MinMax {
+            min_coord: Some(-120),
+            max_coord: None,
+        };
+        assert_eq!(axis.min_max(tag::HANI, None).unwrap(), Some(min_max));
+        assert_eq!(
+            axis.min_max(tag::HANI, Some(tag::from_string("KOR ").unwrap()))
+                .unwrap(),
+            Some(min_max)
+        );
+        assert_eq!(
+            axis.min_max(tag::HANI, Some(jan())).unwrap(),
+            Some(MinMax {
+                min_coord: Some(-100),
+                max_coord: Some(880),
+            })
+        );
+        assert_eq!(axis.min_max(tag::LATN, None).unwrap(), None);
+    }
+}
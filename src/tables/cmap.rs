@@ -13,7 +13,7 @@ use itertools::izip;
 
 use crate::binary::read::{CheckIndex, ReadArray, ReadBinary, ReadCtxt, ReadFrom, ReadScope};
 use crate::binary::write::{WriteBinary, WriteContext};
-use crate::binary::{I16Be, U16Be, U32Be, U8};
+use crate::binary::{I16Be, U16Be, U24Be, U32Be, U8};
 use crate::error::{ParseError, WriteError};
 use crate::size;
 
@@ -49,6 +49,8 @@ impl EncodingId {
 
     pub const MACINTOSH_APPLE_ROMAN: EncodingId = EncodingId(0);
     pub const MACINTOSH_UNICODE_UCS4: EncodingId = EncodingId(4);
+
+    pub const UNICODE_VARIATION_SEQUENCES: EncodingId = EncodingId(5);
 }
 
 pub struct Cmap<'a> {
@@ -119,13 +121,37 @@ pub struct SequentialMapGroup {
     start_glyph_id: u32,
 }
 
+/// A format 14 `cmap` subtable, which maps Unicode variation sequences to glyphs.
+///
+/// <https://docs.microsoft.com/en-us/typography/opentype/spec/cmap#format-14-unicode-variation-sequences>
+pub struct CmapSubtableFormat14<'a> {
+    scope: ReadScope<'a>,
+    var_selector_records: ReadArray<'a, VariationSelectorRecord>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct VariationSelectorRecord {
+    var_selector: u32,
+    default_uvs_offset: u32,
+    non_default_uvs_offset: u32,
+}
+
+/// The glyph a variation sequence maps to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum VariationSequenceGlyph {
+    /// The sequence uses the glyph the base character maps to in the Unicode subtable.
+    Default,
+    /// The sequence uses the supplied glyph.
+    Glyph(u16),
+}
+
 impl<'a> ReadBinary<'a> for Cmap<'a> {
     type HostType = Self;
 
     fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
         let scope = ctxt.scope();
         let version = ctxt.read_u16be()?;
-        ctxt.check(version == 0)?;
+        ctxt.check_field(version == 0, "version")?;
         let num_tables = usize::from(ctxt.read_u16be()?);
         let encoding_records = ctxt.read_array::<EncodingRecord>(num_tables)?;
         Ok(Cmap {
@@ -154,7 +180,7 @@ impl<'a> ReadBinary<'a> for CmapSubtable<'a> {
         match subtable_format {
             0 => {
                 let length = usize::from(ctxt.read_u16be()?);
-                ctxt.check(length >= 3 * size::U16 + 256)?;
+                ctxt.check_field(length >= 3 * size::U16 + 256, "length")?;
                 let language = ctxt.read_u16be()?;
                 let glyph_id_array = ctxt.read_array::<U8>(256)?;
                 Ok(CmapSubtable::Format0 {
@@ -186,7 +212,7 @@ impl<'a> ReadBinary<'a> for CmapSubtable<'a> {
                 let length = usize::from(ctxt.read_u16be()?);
                 let language = ctxt.read_u16be()?;
                 let seg_count_x2 = usize::from(ctxt.read_u16be()?);
-                ctxt.check((seg_count_x2 & 1) == 0)?;
+                ctxt.check_field((seg_count_x2 & 1) == 0, "segCountX2")?;
                 let seg_count = seg_count_x2 >> 1;
                 let _search_range = ctxt.read_u16be()?;
                 let _entry_selector = ctxt.read_u16be()?;
@@ -196,9 +222,9 @@ impl<'a> ReadBinary<'a> for CmapSubtable<'a> {
                 let start_codes = ctxt.read_array::<U16Be>(seg_count)?;
                 let id_deltas = ctxt.read_array::<I16Be>(seg_count)?;
                 let id_range_offsets = ctxt.read_array::<U16Be>(seg_count)?;
-                ctxt.check(length >= (8 + (4 * seg_count)) * size::U16)?;
+                ctxt.check_field(length >= (8 + (4 * seg_count)) * size::U16, "length")?;
                 let remaining = length - ((8 + (4 * seg_count)) * size::U16);
-                ctxt.check((remaining & 1) == 0)?;
+                ctxt.check_field((remaining & 1) == 0, "length")?;
                 let num_indices = remaining >> 1;
                 let glyph_id_array = ctxt.read_array::<U16Be>(num_indices)?;
                 Ok(CmapSubtable::Format4 {
@@ -224,7 +250,7 @@ impl<'a> ReadBinary<'a> for CmapSubtable<'a> {
             }
             10 => {
                 let reserved = ctxt.read_u16be()?;
-                ctxt.check(reserved == 0)?;
+                ctxt.check_field(reserved == 0, "reserved")?;
                 let _length = ctxt.read_u32be()?;
                 let language = ctxt.read_u32be()?;
                 let start_char_code = ctxt.read_u32be()?;
@@ -238,7 +264,7 @@ impl<'a> ReadBinary<'a> for CmapSubtable<'a> {
             }
             12 => {
                 let reserved = ctxt.read_u16be()?;
-                ctxt.check(reserved == 0)?;
+                ctxt.check_field(reserved == 0, "reserved")?;
                 let _length = ctxt.read_u32be()?;
                 let language = ctxt.read_u32be()?;
                 let num_groups = usize::try_from(ctxt.read_u32be()?)?;
@@ -414,6 +440,26 @@ impl SubHeader {
     }
 }
 
+impl SequentialMapGroup {
+    /// Map the characters `start_char_code..=end_char_code` to consecutive glyphs starting at
+    /// `start_glyph_id`.
+    pub fn new(start_char_code: u32, end_char_code: u32, start_glyph_id: u32) -> Self {
+        SequentialMapGroup {
+            start_char_code,
+            end_char_code,
+            start_glyph_id,
+        }
+    }
+
+    pub fn start_char_code(&self) -> u32 {
+        self.start_char_code
+    }
+
+    pub fn end_char_code(&self) -> u32 {
+        self.end_char_code
+    }
+}
+
 impl<'a> ReadFrom<'a> for SequentialMapGroup {
     type ReadType = (U32Be, U32Be, U32Be);
     fn from((start_char_code, end_char_code, start_glyph_id): (u32, u32, u32)) -> Self {
@@ -771,6 +817,94 @@ impl<'a> CmapSubtable<'a> {
     }
 }
 
+impl<'a> ReadBinary<'a> for CmapSubtableFormat14<'a> {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let scope = ctxt.scope();
+        let format = ctxt.read_u16be()?;
+        ctxt.check_field(format == 14, "format")?;
+        let _length = ctxt.read_u32be()?;
+        let num_var_selector_records = usize::try_from(ctxt.read_u32be()?)?;
+        let var_selector_records =
+            ctxt.read_array::<VariationSelectorRecord>(num_var_selector_records)?;
+
+        Ok(CmapSubtableFormat14 {
+            scope,
+            var_selector_records,
+        })
+    }
+}
+
+impl<'a> ReadFrom<'a> for VariationSelectorRecord {
+    type ReadType = (U24Be, U32Be, U32Be);
+
+    fn from((var_selector, default_uvs_offset, non_default_uvs_offset): (u32, u32, u32)) -> Self {
+        VariationSelectorRecord {
+            var_selector,
+            default_uvs_offset,
+            non_default_uvs_offset,
+        }
+    }
+}
+
+impl<'a> CmapSubtableFormat14<'a> {
+    /// Look up the glyph for the variation sequence of `ch` followed by the variation selector
+    /// `var_selector`.
+    ///
+    /// Returns `None` if the font doesn't support the sequence.
+    pub fn map_variation_sequence(
+        &self,
+        ch: u32,
+        var_selector: u32,
+    ) -> Result<Option<VariationSequenceGlyph>, ParseError> {
+        let record = match self
+            .var_selector_records
+            .iter()
+            .find(|record| record.var_selector == var_selector)
+        {
+            Some(record) => record,
+            None => return Ok(None),
+        };
+
+        if record.default_uvs_offset != 0 {
+            let mut ctxt = self
+                .scope
+                .offset(usize::try_from(record.default_uvs_offset)?)
+                .ctxt();
+            let num_unicode_value_ranges = usize::try_from(ctxt.read_u32be()?)?;
+            let ranges = ctxt.read_array::<(U24Be, U8)>(num_unicode_value_ranges)?;
+            let in_range = ranges
+                .iter()
+                .any(|(start_unicode_value, additional_count)| {
+                    (start_unicode_value..=start_unicode_value + u32::from(additional_count))
+                        .contains(&ch)
+                });
+            if in_range {
+                return Ok(Some(VariationSequenceGlyph::Default));
+            }
+        }
+
+        if record.non_default_uvs_offset != 0 {
+            let mut ctxt = self
+                .scope
+                .offset(usize::try_from(record.non_default_uvs_offset)?)
+                .ctxt();
+            let num_uvs_mappings = usize::try_from(ctxt.read_u32be()?)?;
+            let mappings = ctxt.read_array::<(U24Be, U16Be)>(num_uvs_mappings)?;
+            let glyph_id = mappings
+                .iter()
+                .find(|&(unicode_value, _)| unicode_value == ch)
+                .map(|(_, glyph_id)| glyph_id);
+            if let Some(glyph_id) = glyph_id {
+                return Ok(Some(VariationSequenceGlyph::Glyph(glyph_id)));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
 // For converting cmap format 4 offsets to indexes into the glyph id array.
 fn offset_to_index(
     i: usize,
@@ -793,17 +927,21 @@ fn offset_to_index(
 }
 
 pub mod owned {
+    use std::collections::BTreeMap;
+
     use super::{
         offset_to_index, size, Format4Calculator, I16Be, ParseError, SequentialMapGroup, TryFrom,
         U16Be, U32Be, WriteBinary, WriteContext, WriteError,
     };
 
     #[derive(Clone)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize))]
     pub struct Cmap {
         pub encoding_records: Vec<EncodingRecord>,
     }
 
     #[derive(Clone)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize))]
     pub struct EncodingRecord {
         pub platform_id: u16,
         pub encoding_id: u16,
@@ -845,6 +983,77 @@ pub mod owned {
     }
 
     impl CmapSubtable {
+        /// The format number of the sub-table.
+        pub fn format(&self) -> u16 {
+            match self {
+                CmapSubtable::Format0 { .. } => 0,
+                CmapSubtable::Format4 { .. } => 4,
+                CmapSubtable::Format6 { .. } => 6,
+                CmapSubtable::Format10 { .. } => 10,
+                CmapSubtable::Format12 { .. } => 12,
+            }
+        }
+
+        pub fn language(&self) -> u32 {
+            match *self {
+                CmapSubtable::Format0 { language, .. }
+                | CmapSubtable::Format4 { language, .. }
+                | CmapSubtable::Format6 { language, .. } => u32::from(language),
+                CmapSubtable::Format10 { language, .. }
+                | CmapSubtable::Format12 { language, .. } => language,
+            }
+        }
+
+        /// Extract all the mappings from char codes to glyph indexes in the sub-table.
+        ///
+        /// Char codes that map to the `.notdef` glyph (0) are omitted.
+        pub fn char_mappings(&self) -> Result<BTreeMap<u32, u16>, ParseError> {
+            // Range of char codes covered by an array of glyph ids starting at `start`
+            let contiguous = |start: u32, len: usize| match len {
+                0 => Vec::new(),
+                len => vec![(start, start.saturating_add(len as u32 - 1))],
+            };
+            let ranges = match self {
+                CmapSubtable::Format0 { glyph_id_array, .. } => contiguous(0, glyph_id_array.len()),
+                CmapSubtable::Format4 {
+                    end_codes,
+                    start_codes,
+                    ..
+                } => start_codes
+                    .iter()
+                    .zip(end_codes.iter())
+                    .map(|(&start, &end)| (u32::from(start), u32::from(end)))
+                    .collect(),
+                CmapSubtable::Format6 {
+                    first_code,
+                    glyph_id_array,
+                    ..
+                } => contiguous(u32::from(*first_code), glyph_id_array.len()),
+                CmapSubtable::Format10 {
+                    start_char_code,
+                    glyph_id_array,
+                    ..
+                } => contiguous(*start_char_code, glyph_id_array.len()),
+                CmapSubtable::Format12 { groups, .. } => groups
+                    .iter()
+                    .map(|group| (group.start_char_code, group.end_char_code))
+                    .collect(),
+            };
+
+            let mut mappings = BTreeMap::new();
+            for (start, end) in ranges {
+                for ch in start..=end {
+                    match self.map_glyph(ch)? {
+                        Some(0) | None => {}
+                        Some(glyph_id) => {
+                            mappings.insert(ch, glyph_id);
+                        }
+                    }
+                }
+            }
+            Ok(mappings)
+        }
+
         pub fn map_glyph(&self, ch: u32) -> Result<Option<u16>, ParseError> {
             // NOTE: Currently a duplicate of `super::CmapSubtable::map_glyph`
             match *self {
@@ -942,6 +1151,101 @@ pub mod owned {
         }
     }
 
+    /// Build a Unicode `cmap` table from `mappings`, which must be sorted by character.
+    ///
+    /// A format 4 subtable is built for the Basic Multilingual Plane, along with a format 12
+    /// subtable if there are any supplementary characters.
+    pub(crate) fn create_unicode_cmap_table(mappings: &[(u32, u16)]) -> Cmap {
+        let mut encoding_records = vec![EncodingRecord {
+            platform_id: 3, // Windows platform
+            encoding_id: 1, // Unicode BMP
+            sub_table: create_format4_subtable(mappings, 0),
+        }];
+
+        if mappings.iter().any(|&(ch, _)| ch > 0xFFFF) {
+            encoding_records.push(EncodingRecord {
+                platform_id: 3,  // Windows platform
+                encoding_id: 10, // Unicode full repertoire
+                sub_table: create_format12_subtable(mappings, 0),
+            });
+        }
+
+        Cmap { encoding_records }
+    }
+
+    /// Build a format 4 `cmap` subtable from the Basic Multilingual Plane characters in `mappings`,
+    /// which must be sorted by character.
+    pub(crate) fn create_format4_subtable(mappings: &[(u32, u16)], language: u16) -> CmapSubtable {
+        let mut start_codes = Vec::new();
+        let mut end_codes = Vec::new();
+        let mut id_deltas = Vec::new();
+        let mut prev = None;
+        for &(ch, glyph_id) in mappings.iter().filter(|&&(ch, _)| ch < 0xFFFF) {
+            let ch = ch as u16;
+            if prev == Some((ch.wrapping_sub(1), glyph_id.wrapping_sub(1))) {
+                *end_codes.last_mut().unwrap() = ch;
+            } else {
+                start_codes.push(ch);
+                end_codes.push(ch);
+                id_deltas.push(glyph_id.wrapping_sub(ch) as i16);
+            }
+            prev = Some((ch, glyph_id));
+        }
+        // The last segment must map 0xFFFF to .notdef
+        start_codes.push(0xFFFF);
+        end_codes.push(0xFFFF);
+        id_deltas.push(1);
+
+        CmapSubtable::Format4 {
+            language,
+            id_range_offsets: vec![0; start_codes.len()],
+            end_codes,
+            start_codes,
+            id_deltas,
+            glyph_id_array: Vec::new(),
+        }
+    }
+
+    /// Build a format 12 `cmap` subtable from `mappings`, which must be sorted by character.
+    pub(crate) fn create_format12_subtable(mappings: &[(u32, u16)], language: u32) -> CmapSubtable {
+        let mut groups: Vec<(u32, u32, u32)> = Vec::new();
+        for &(ch, glyph_id) in mappings {
+            let glyph_id = u32::from(glyph_id);
+            match groups.last_mut() {
+                Some((start, end, start_glyph_id))
+                    if *end + 1 == ch && *start_glyph_id + (ch - *start) == glyph_id =>
+                {
+                    *end = ch
+                }
+                _ => groups.push((ch, ch, glyph_id)),
+            }
+        }
+
+        CmapSubtable::Format12 {
+            language,
+            groups: groups
+                .into_iter()
+                .map(|(start, end, glyph_id)| SequentialMapGroup::new(start, end, glyph_id))
+                .collect(),
+        }
+    }
+
+    /// Serializes the format, language, and the mappings of the sub-table as returned by
+    /// `char_mappings`.
+    #[cfg(feature = "serde")]
+    impl serde::Serialize for CmapSubtable {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            use serde::ser::{Error, SerializeStruct};
+
+            let mappings = self.char_mappings().map_err(S::Error::custom)?;
+            let mut state = serializer.serialize_struct("CmapSubtable", 3)?;
+            state.serialize_field("format", &self.format())?;
+            state.serialize_field("language", &self.language())?;
+            state.serialize_field("mappings", &mappings)?;
+            state.end()
+        }
+    }
+
     impl<'a> WriteBinary<Self> for Cmap {
         type Output = ();
 
@@ -955,16 +1259,14 @@ pub mod owned {
             for record in &table.encoding_records {
                 U16Be::write(ctxt, record.platform_id)?;
                 U16Be::write(ctxt, record.encoding_id)?;
-                let offset = ctxt.placeholder::<U32Be, _>()?;
+                let offset = ctxt.offset_placeholder::<U32Be, u32>(start)?;
                 offsets.push(offset);
             }
 
             // sub-tables
-            for (record, placeholder) in table.encoding_records.into_iter().zip(offsets.into_iter())
-            {
-                let offset = u32::try_from(ctxt.bytes_written() - start)?;
+            for (record, offset) in table.encoding_records.into_iter().zip(offsets.into_iter()) {
+                ctxt.write_offset(offset)?;
                 CmapSubtable::write(ctxt, record.sub_table)?;
-                ctxt.write_placeholder(placeholder, offset)?;
             }
 
             Ok(())
@@ -1130,6 +1432,56 @@ mod tests {
         f(cmap_subtable);
     }
 
+    #[test]
+    fn test_variation_sequences_format14() {
+        let font_buffer = read_fixture("tests/fonts/noto/NotoSansJP-Regular.otf");
+        let opentype_file = ReadScope::new(&font_buffer)
+            .read::<OpenTypeFont<'_>>()
+            .unwrap();
+        let otf = match opentype_file.data {
+            OpenTypeData::Single(offset_table) => offset_table,
+            OpenTypeData::Collection(_) => panic!("expected an OTF font"),
+        };
+        let cmap = otf
+            .read_table(&opentype_file.scope, tag::CMAP)
+            .unwrap()
+            .unwrap()
+            .read::<Cmap<'_>>()
+            .unwrap();
+        let encoding_record = cmap
+            .find_subtable(PlatformId::UNICODE, EncodingId::UNICODE_VARIATION_SEQUENCES)
+            .unwrap();
+        let subtable = cmap
+            .scope
+            .offset(usize::try_from(encoding_record.offset).unwrap())
+            .read::<CmapSubtableFormat14<'_>>()
+            .unwrap();
+
+        // Standardized variation sequence
+        assert_eq!(
+            subtable.map_variation_sequence(0x4FAE, 0xFE00).unwrap(),
+            Some(VariationSequenceGlyph::Glyph(6808))
+        );
+        // Ideographic variation sequences
+        assert_eq!(
+            subtable.map_variation_sequence(0x4E08, 0xE0101).unwrap(),
+            Some(VariationSequenceGlyph::Glyph(7187))
+        );
+        assert_eq!(
+            subtable.map_variation_sequence(0x4E00, 0xE0100).unwrap(),
+            Some(VariationSequenceGlyph::Default)
+        );
+        // Unsupported sequences
+        assert_eq!(
+            subtable.map_variation_sequence(0x4E00, 0xFE00).unwrap(),
+            None
+        );
+        assert_eq!(
+            subtable.map_variation_sequence(0x4E00, 0xFE0F).unwrap(),
+            None
+        );
+    }
+
     #[test]
     fn test_mappings_format0() {
         with_cmap_subtable(
@@ -1221,4 +1573,50 @@ mod tests {
             },
         );
     }
+
+    #[test]
+    fn test_owned_char_mappings() {
+        with_cmap_subtable(
+            "tests/fonts/opentype/TwitterColorEmoji-SVGinOT.ttf",
+            PlatformId::UNICODE,
+            EncodingId(3),
+            |cmap_subtable| {
+                let owned = cmap_subtable.to_owned().unwrap();
+                let mappings = owned.char_mappings().unwrap();
+                let soccer_ball = cmap_subtable.map_glyph('⚽' as u32).unwrap().unwrap();
+                assert_eq!(mappings[&('⚽' as u32)], soccer_ball);
+                assert!(mappings.values().all(|&glyph_id| glyph_id != 0));
+                assert_eq!(
+                    mappings.len(),
+                    cmap_subtable
+                        .mappings()
+                        .unwrap()
+                        .keys()
+                        .filter(|&&glyph_id| glyph_id != 0)
+                        .count()
+                );
+            },
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serialize_owned_subtable() {
+        with_cmap_subtable(
+            "tests/fonts/opentype/TwitterColorEmoji-SVGinOT.ttf",
+            PlatformId::UNICODE,
+            EncodingId(3),
+            |cmap_subtable| {
+                let owned = cmap_subtable.to_owned().unwrap();
+                let json = serde_json::to_value(&owned).unwrap();
+                let soccer_ball = cmap_subtable.map_glyph('⚽' as u32).unwrap().unwrap();
+                assert_eq!(json["format"], 4);
+                assert_eq!(json["language"], 0);
+                assert_eq!(
+                    json["mappings"][('⚽' as u32).to_string()],
+                    u64::from(soccer_ball)
+                );
+            },
+        );
+    }
 }
@@ -0,0 +1,1786 @@
+#![deny(missing_docs)]
+
+//! `COLR` table parsing.
+//!
+//! The `COLR` table describes color glyphs. Version 0 defines a color glyph as a stack of layers,
+//! each an outline glyph filled with a solid color. Version 1 adds a graph of paint tables
+//! supporting gradients, transforms and compositing, along with variations of the paint
+//! parameters in variable fonts. Colors are referenced by their index in a `CPAL` palette.
+//!
+//! <https://docs.microsoft.com/en-us/typography/opentype/spec/colr>
+
+use std::convert::TryFrom;
+
+use crate::binary::read::{ReadArray, ReadBinary, ReadCtxt, ReadFrom, ReadScope};
+use crate::binary::{U16Be, U24Be, U32Be};
+use crate::error::ParseError;
+use crate::tables::cpal::{CpalTable, Rgba};
+use crate::tables::vvar::{DeltaSetIndexMap, ItemVariationStore};
+use crate::tables::F2Dot14;
+
+/// The maximum depth of nested paints followed when reading a paint graph.
+const MAX_PAINT_DEPTH: usize = 64;
+
+/// The maximum number of paints read from a paint graph.
+const MAX_PAINTS: usize = 0x10000;
+
+/// The `varIndexBase` value indicating that a paint is not varied.
+const NO_VARIATION_INDEX: u32 = 0xFFFF_FFFF;
+
+/// The `COLR` color table.
+pub struct ColrTable<'a> {
+    /// The version of the table, 0 or 1.
+    pub version: u16,
+    base_glyph_records: ReadArray<'a, BaseGlyphRecord>,
+    layer_records: ReadArray<'a, LayerRecord>,
+    base_glyph_list: Option<BaseGlyphList<'a>>,
+    layer_list: Option<LayerList<'a>>,
+    clip_list: Option<ClipList<'a>>,
+    var_index_map: Option<DeltaSetIndexMap<'a>>,
+    item_variation_store: Option<ItemVariationStore<'a>>,
+}
+
+/// The layers of a version 0 color glyph.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct BaseGlyphRecord {
+    glyph_id: u16,
+    first_layer_index: u16,
+    num_layers: u16,
+}
+
+/// A layer of a version 0 color glyph.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LayerRecord {
+    /// The glyph whose outline is filled.
+    pub glyph_id: u16,
+    /// The index of the color in the palette, or `0xFFFF` for the text foreground color.
+    pub palette_index: u16,
+}
+
+/// The version 1 color glyphs, mapping glyph ids to their root paint.
+struct BaseGlyphList<'a> {
+    scope: ReadScope<'a>,
+    records: ReadArray<'a, (U16Be, U32Be)>,
+}
+
+/// The paints referenced by `PaintColrLayers`.
+struct LayerList<'a> {
+    scope: ReadScope<'a>,
+    paint_offsets: ReadArray<'a, U32Be>,
+}
+
+/// The clip boxes of version 1 color glyphs.
+struct ClipList<'a> {
+    scope: ReadScope<'a>,
+    clips: ReadArray<'a, (U16Be, U16Be, U24Be)>,
+}
+
+/// A rectangle that bounds the drawing of a color glyph, in font units.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ClipBox {
+    /// Minimum x of the box.
+    pub x_min: f32,
+    /// Minimum y of the box.
+    pub y_min: f32,
+    /// Maximum x of the box.
+    pub x_max: f32,
+    /// Maximum y of the box.
+    pub y_max: f32,
+}
+
+/// A node of the paint graph of a color glyph.
+///
+/// Coordinates and distances are in font units. Angles are in multiples of 180°, counter-clockwise.
+/// Alpha values are between 0 and 1, and are multiplied with the alpha of the palette entry.
+/// Variations have been applied to all values.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Paint {
+    /// Layers painted in order, bottom to top, and composited with `CompositeMode::SrcOver`.
+    Layers(Vec<Paint>),
+    /// Fill with a solid color.
+    Solid {
+        /// The index of the color in the palette, or `0xFFFF` for the text foreground color.
+        palette_index: u16,
+        /// The alpha multiplied with the alpha of the color.
+        alpha: f32,
+    },
+    /// Fill with a linear gradient.
+    ///
+    /// The gradient runs from `p0` to `p1`, rotated so that it is perpendicular to the line from
+    /// `p0` to `p2`.
+    LinearGradient {
+        /// The colors of the gradient.
+        color_line: ColorLine,
+        /// x of the start point.
+        x0: f32,
+        /// y of the start point.
+        y0: f32,
+        /// x of the end point.
+        x1: f32,
+        /// y of the end point.
+        y1: f32,
+        /// x of the rotation point.
+        x2: f32,
+        /// y of the rotation point.
+        y2: f32,
+    },
+    /// Fill with a radial gradient between two circles.
+    RadialGradient {
+        /// The colors of the gradient.
+        color_line: ColorLine,
+        /// x of the center of the start circle.
+        x0: f32,
+        /// y of the center of the start circle.
+        y0: f32,
+        /// Radius of the start circle.
+        radius0: f32,
+        /// x of the center of the end circle.
+        x1: f32,
+        /// y of the center of the end circle.
+        y1: f32,
+        /// Radius of the end circle.
+        radius1: f32,
+    },
+    /// Fill with a sweep gradient around a center point.
+    SweepGradient {
+        /// The colors of the gradient.
+        color_line: ColorLine,
+        /// x of the center.
+        center_x: f32,
+        /// y of the center.
+        center_y: f32,
+        /// The angle of color stop offset 0.
+        start_angle: f32,
+        /// The angle of color stop offset 1.
+        end_angle: f32,
+    },
+    /// Fill the outline of a glyph with `paint`.
+    Glyph {
+        /// The glyph that defines the clip region.
+        glyph_id: u16,
+        /// The fill.
+        paint: Box<Paint>,
+    },
+    /// Paint the version 1 color glyph `glyph_id`.
+    ColrGlyph {
+        /// The color glyph that is painted.
+        glyph_id: u16,
+        /// The root paint of the color glyph.
+        paint: Box<Paint>,
+    },
+    /// Apply an affine transform to `paint`.
+    Transform {
+        /// The transform.
+        transform: Affine2x3,
+        /// The paint that is transformed.
+        paint: Box<Paint>,
+    },
+    /// Translate `paint`.
+    Translate {
+        /// The translation along the x axis.
+        dx: f32,
+        /// The translation along the y axis.
+        dy: f32,
+        /// The paint that is translated.
+        paint: Box<Paint>,
+    },
+    /// Scale `paint` about `center`, or the origin if `None`.
+    Scale {
+        /// The scale factor along the x axis.
+        scale_x: f32,
+        /// The scale factor along the y axis.
+        scale_y: f32,
+        /// The center of the scaling.
+        center: Option<(f32, f32)>,
+        /// The paint that is scaled.
+        paint: Box<Paint>,
+    },
+    /// Rotate `paint` about `center`, or the origin if `None`.
+    Rotate {
+        /// The rotation angle.
+        angle: f32,
+        /// The center of the rotation.
+        center: Option<(f32, f32)>,
+        /// The paint that is rotated.
+        paint: Box<Paint>,
+    },
+    /// Skew `paint` about `center`, or the origin if `None`.
+    Skew {
+        /// The skew angle along the x axis, counter-clockwise from the y axis.
+        x_skew_angle: f32,
+        /// The skew angle along the y axis, counter-clockwise from the x axis.
+        y_skew_angle: f32,
+        /// The center of the skew.
+        center: Option<(f32, f32)>,
+        /// The paint that is skewed.
+        paint: Box<Paint>,
+    },
+    /// Composite `source` onto `backdrop`.
+    Composite {
+        /// The source of the composition.
+        source: Box<Paint>,
+        /// How the source is combined with the backdrop.
+        mode: CompositeMode,
+        /// The backdrop of the composition.
+        backdrop: Box<Paint>,
+    },
+}
+
+/// The colors of a gradient.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorLine {
+    /// How the gradient is extended beyond the first and last stops.
+    pub extend: Extend,
+    /// The color stops, in the order they appear in the font.
+    pub stops: Vec<ColorStop>,
+}
+
+/// A color at a position along a gradient.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ColorStop {
+    /// The position of the stop along the gradient.
+    pub stop_offset: f32,
+    /// The index of the color in the palette, or `0xFFFF` for the text foreground color.
+    pub palette_index: u16,
+    /// The alpha multiplied with the alpha of the color.
+    pub alpha: f32,
+}
+
+/// How a gradient is extended beyond its first and last stops.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Extend {
+    /// Use the color of the nearest stop.
+    Pad,
+    /// Repeat the gradient.
+    Repeat,
+    /// Repeat the gradient, reversing every other repetition.
+    Reflect,
+}
+
+/// A 2x3 affine transform, mapping `(x, y)` to
+/// `(xx * x + xy * y + dx, yx * x + yy * y + dy)`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Affine2x3 {
+    /// x component of the transformed x axis.
+    pub xx: f32,
+    /// y component of the transformed x axis.
+    pub yx: f32,
+    /// x component of the transformed y axis.
+    pub xy: f32,
+    /// y component of the transformed y axis.
+    pub yy: f32,
+    /// Translation along the x axis.
+    pub dx: f32,
+    /// Translation along the y axis.
+    pub dy: f32,
+}
+
+/// The compositing and blending modes of `PaintComposite`.
+///
+/// The Porter-Duff modes are described by the
+/// [Compositing and Blending specification](https://www.w3.org/TR/compositing-1/), as are the
+/// separable and non-separable blend modes that follow them.
+#[allow(missing_docs)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CompositeMode {
+    Clear,
+    Src,
+    Dest,
+    SrcOver,
+    DestOver,
+    SrcIn,
+    DestIn,
+    SrcOut,
+    DestOut,
+    SrcAtop,
+    DestAtop,
+    Xor,
+    Plus,
+    Screen,
+    Overlay,
+    Darken,
+    Lighten,
+    ColorDodge,
+    ColorBurn,
+    HardLight,
+    SoftLight,
+    Difference,
+    Exclusion,
+    Multiply,
+    Hue,
+    Saturation,
+    Color,
+    Luminosity,
+}
+
+/// A layer of a flattened color glyph: an area filled with a single solid color or gradient.
+///
+/// Produced by [ColrTable::flatten](struct.ColrTable.html#method.flatten) for renderers that
+/// can't draw a paint graph directly.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlatLayer {
+    /// The glyph outlines that bound the layer. The filled area is the intersection of the
+    /// outlines, which is usually a single outline. When empty the fill is bounded only by the
+    /// clip box of the color glyph.
+    pub outlines: Vec<Outline>,
+    /// How the layer is filled.
+    pub fill: Fill,
+}
+
+/// A glyph outline placed in device space.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Outline {
+    /// The glyph whose outline is used.
+    pub glyph_id: u16,
+    /// The transform from the font units of the glyph to device space.
+    pub transform: Affine2x3,
+}
+
+/// The fill of a flattened layer, with its colors resolved from the palette.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fill {
+    /// A solid color.
+    Solid(Rgba),
+    /// A linear gradient from `p0` to `p1` in device space, perpendicular to the line between
+    /// them.
+    LinearGradient {
+        /// The start point, where the offset of the gradient is 0.
+        p0: (f32, f32),
+        /// The end point, where the offset of the gradient is 1.
+        p1: (f32, f32),
+        /// The colors of the gradient, sorted by offset.
+        stops: Vec<GradientStop>,
+        /// How the gradient is extended beyond its first and last stops.
+        extend: Extend,
+    },
+    /// A radial gradient between two circles, which are mapped to device space by `transform`.
+    ///
+    /// The circles are not in device space since they become ellipses under transforms that
+    /// don't preserve angles.
+    RadialGradient {
+        /// The center of the start circle.
+        c0: (f32, f32),
+        /// The radius of the start circle.
+        r0: f32,
+        /// The center of the end circle.
+        c1: (f32, f32),
+        /// The radius of the end circle.
+        r1: f32,
+        /// The colors of the gradient, sorted by offset.
+        stops: Vec<GradientStop>,
+        /// How the gradient is extended beyond its first and last stops.
+        extend: Extend,
+        /// The transform from the space of the gradient to device space.
+        transform: Affine2x3,
+    },
+    /// A sweep gradient around `center`, which is mapped to device space by `transform`.
+    SweepGradient {
+        /// The center of the gradient.
+        center: (f32, f32),
+        /// The angle of offset 0, in multiples of 180°, counter-clockwise.
+        start_angle: f32,
+        /// The angle of offset 1, in multiples of 180°, counter-clockwise.
+        end_angle: f32,
+        /// The colors of the gradient, sorted by offset.
+        stops: Vec<GradientStop>,
+        /// How the gradient is extended beyond its first and last stops.
+        extend: Extend,
+        /// The transform from the space of the gradient to device space.
+        transform: Affine2x3,
+    },
+}
+
+/// A color at a position along a flattened gradient.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GradientStop {
+    /// The position of the stop along the gradient.
+    pub offset: f32,
+    /// The color of the stop, including the alpha of the paint.
+    pub color: Rgba,
+}
+
+/// The palette and transform used when flattening a color glyph.
+struct Flattener<'a, 'b> {
+    cpal: &'b CpalTable<'a>,
+    palette_index: u16,
+    foreground: Rgba,
+    /// The outlines enclosing the paint being flattened.
+    outlines: Vec<Outline>,
+    layers: Vec<FlatLayer>,
+}
+
+/// The encoding of a value of a paint table.
+#[derive(Copy, Clone)]
+enum ValueType {
+    FWord,
+    UFWord,
+    F2Dot14,
+    Fixed,
+}
+
+/// State of the paint graph being read.
+struct PaintReader<'a, 'b> {
+    colr: &'b ColrTable<'a>,
+    coords: &'b [F2Dot14],
+    /// The color glyphs being read, used to detect cycles.
+    glyph_stack: Vec<u16>,
+    depth: usize,
+    num_paints: usize,
+}
+
+impl<'a> ReadBinary<'a> for ColrTable<'a> {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let scope = ctxt.scope();
+        let version = ctxt.read_u16be()?;
+        ctxt.check_version(version <= 1)?;
+        let num_base_glyph_records = ctxt.read_u16be()?;
+        let base_glyph_records_offset = ctxt.read_u32be()?;
+        let layer_records_offset = ctxt.read_u32be()?;
+        let num_layer_records = ctxt.read_u16be()?;
+
+        let base_glyph_records = match base_glyph_records_offset {
+            0 => ReadArray::empty(),
+            offset => scope
+                .offset(usize::try_from(offset)?)
+                .ctxt()
+                .read_array::<BaseGlyphRecord>(usize::from(num_base_glyph_records))?,
+        };
+        let layer_records = match layer_records_offset {
+            0 => ReadArray::empty(),
+            offset => scope
+                .offset(usize::try_from(offset)?)
+                .ctxt()
+                .read_array::<LayerRecord>(usize::from(num_layer_records))?,
+        };
+
+        let mut colr = ColrTable {
+            version,
+            base_glyph_records,
+            layer_records,
+            base_glyph_list: None,
+            layer_list: None,
+            clip_list: None,
+            var_index_map: None,
+            item_variation_store: None,
+        };
+        if version == 0 {
+            return Ok(colr);
+        }
+
+        let base_glyph_list_offset = ctxt.read_u32be()?;
+        let layer_list_offset = ctxt.read_u32be()?;
+        let clip_list_offset = ctxt.read_u32be()?;
+        let var_index_map_offset = ctxt.read_u32be()?;
+        let item_variation_store_offset = ctxt.read_u32be()?;
+        let read_offset = |offset: u32| -> Result<Option<ReadScope<'a>>, ParseError> {
+            match offset {
+                0 => Ok(None),
+                offset => Ok(Some(scope.offset(usize::try_from(offset)?))),
+            }
+        };
+
+        if let Some(list_scope) = read_offset(base_glyph_list_offset)? {
+            let mut list_ctxt = list_scope.ctxt();
+            let num_records = usize::try_from(list_ctxt.read_u32be()?)?;
+            colr.base_glyph_list = Some(BaseGlyphList {
+                scope: list_scope,
+                records: list_ctxt.read_array::<(U16Be, U32Be)>(num_records)?,
+            });
+        }
+        if let Some(list_scope) = read_offset(layer_list_offset)? {
+            let mut list_ctxt = list_scope.ctxt();
+            let num_layers = usize::try_from(list_ctxt.read_u32be()?)?;
+            colr.layer_list = Some(LayerList {
+                scope: list_scope,
+                paint_offsets: list_ctxt.read_array::<U32Be>(num_layers)?,
+            });
+        }
+        if let Some(list_scope) = read_offset(clip_list_offset)? {
+            let mut list_ctxt = list_scope.ctxt();
+            let format = list_ctxt.read_u8()?;
+            list_ctxt.check(format == 1)?;
+            let num_clips = usize::try_from(list_ctxt.read_u32be()?)?;
+            colr.clip_list = Some(ClipList {
+                scope: list_scope,
+                clips: list_ctxt.read_array::<(U16Be, U16Be, U24Be)>(num_clips)?,
+            });
+        }
+        if let Some(map_scope) = read_offset(var_index_map_offset)? {
+            colr.var_index_map = Some(map_scope.read::<DeltaSetIndexMap<'a>>()?);
+        }
+        if let Some(store_scope) = read_offset(item_variation_store_offset)? {
+            colr.item_variation_store = Some(store_scope.read::<ItemVariationStore<'a>>()?);
+        }
+
+        Ok(colr)
+    }
+}
+
+impl<'a> ReadFrom<'a> for BaseGlyphRecord {
+    type ReadType = (U16Be, U16Be, U16Be);
+
+    fn from((glyph_id, first_layer_index, num_layers): (u16, u16, u16)) -> Self {
+        BaseGlyphRecord {
+            glyph_id,
+            first_layer_index,
+            num_layers,
+        }
+    }
+}
+
+impl<'a> ReadFrom<'a> for LayerRecord {
+    type ReadType = (U16Be, U16Be);
+
+    fn from((glyph_id, palette_index): (u16, u16)) -> Self {
+        LayerRecord {
+            glyph_id,
+            palette_index,
+        }
+    }
+}
+
+impl<'a> ColrTable<'a> {
+    /// Returns the layers of the version 0 color glyph `glyph_id`, bottom layer first.
+    ///
+    /// Returns `None` if `glyph_id` is not a version 0 color glyph.
+    pub fn layers(&self, glyph_id: u16) -> Result<Option<Vec<LayerRecord>>, ParseError> {
+        let record = match self
+            .base_glyph_records
+            .iter()
+            .find(|record| record.glyph_id == glyph_id)
+        {
+            Some(record) => record,
+            None => return Ok(None),
+        };
+        let start = usize::from(record.first_layer_index);
+        let end = start + usize::from(record.num_layers);
+        if end > self.layer_records.len() {
+            return Err(ParseError::BadIndex);
+        }
+
+        Ok(Some(
+            (start..end)
+                .map(|index| self.layer_records.get_item(index))
+                .collect(),
+        ))
+    }
+
+    /// Returns the paint graph of the color glyph `glyph_id` at the normalised variation
+    /// coordinates `coords`.
+    ///
+    /// The version 1 paint graph is returned if the glyph has one, otherwise version 0 layers are
+    /// returned as `Paint::Layers` of `Paint::Glyph` filled with `Paint::Solid`. `PaintColrLayers`
+    /// and `PaintColrGlyph` are expanded, so the returned graph is a tree. An error is returned if
+    /// the graph contains a cycle, or is excessively large.
+    ///
+    /// Returns `None` if `glyph_id` is not a color glyph.
+    pub fn paint(&self, glyph_id: u16, coords: &[F2Dot14]) -> Result<Option<Paint>, ParseError> {
+        match self.paint_v1(glyph_id, coords)? {
+            Some(paint) => Ok(Some(paint)),
+            None => self.paint_v0(glyph_id),
+        }
+    }
+
+    /// Returns the version 1 paint graph of the color glyph `glyph_id` at the normalised
+    /// variation coordinates `coords`, as described for [paint](#method.paint).
+    ///
+    /// Returns `None` if `glyph_id` is not a version 1 color glyph.
+    pub fn paint_v1(&self, glyph_id: u16, coords: &[F2Dot14]) -> Result<Option<Paint>, ParseError> {
+        let mut reader = PaintReader {
+            colr: self,
+            coords,
+            glyph_stack: Vec::new(),
+            depth: 0,
+            num_paints: 0,
+        };
+        reader.read_colr_glyph(glyph_id)
+    }
+
+    /// Returns the layers of the version 0 color glyph `glyph_id` as `Paint::Layers` of
+    /// `Paint::Glyph` filled with `Paint::Solid`.
+    ///
+    /// Returns `None` if `glyph_id` is not a version 0 color glyph.
+    pub fn paint_v0(&self, glyph_id: u16) -> Result<Option<Paint>, ParseError> {
+        let layers = match self.layers(glyph_id)? {
+            Some(layers) => layers,
+            None => return Ok(None),
+        };
+        let paints = layers
+            .into_iter()
+            .map(|layer| Paint::Glyph {
+                glyph_id: layer.glyph_id,
+                paint: Box::new(Paint::Solid {
+                    palette_index: layer.palette_index,
+                    alpha: 1.,
+                }),
+            })
+            .collect();
+        Ok(Some(Paint::Layers(paints)))
+    }
+
+    /// Returns the clip box of the version 1 color glyph `glyph_id` at the normalised variation
+    /// coordinates `coords`.
+    ///
+    /// Returns `None` if the glyph has no clip box.
+    pub fn clip_box(
+        &self,
+        glyph_id: u16,
+        coords: &[F2Dot14],
+    ) -> Result<Option<ClipBox>, ParseError> {
+        let clip_list = match &self.clip_list {
+            Some(clip_list) => clip_list,
+            None => return Ok(None),
+        };
+        let offset = match clip_list
+            .clips
+            .iter()
+            .find(|&(start, end, _)| (start..=end).contains(&glyph_id))
+        {
+            Some((_, _, offset)) => offset,
+            None => return Ok(None),
+        };
+
+        let mut ctxt = clip_list.scope.offset(usize::try_from(offset)?).ctxt();
+        let format = ctxt.read_u8()?;
+        ctxt.check((1..=2).contains(&format))?;
+        let values = self.read_values(&mut ctxt, &[ValueType::FWord; 4], format == 2, coords)?;
+        Ok(Some(ClipBox {
+            x_min: values[0],
+            y_min: values[1],
+            x_max: values[2],
+            y_max: values[3],
+        }))
+    }
+
+    /// Read values of the types in `kinds`, followed by `varIndexBase` if `variable` is set,
+    /// and apply their deltas.
+    fn read_values(
+        &self,
+        ctxt: &mut ReadCtxt<'a>,
+        kinds: &[ValueType],
+        variable: bool,
+        coords: &[F2Dot14],
+    ) -> Result<Vec<f32>, ParseError> {
+        let mut values = kinds
+            .iter()
+            .map(|&kind| read_value(ctxt, kind))
+            .collect::<Result<Vec<_>, _>>()?;
+        if variable {
+            let var_index_base = ctxt.read_u32be()?;
+            self.apply_deltas(&mut values, kinds, var_index_base, coords)?;
+        }
+        Ok(values)
+    }
+
+    /// Add the deltas at `var_index_base` onwards to `values`.
+    fn apply_deltas(
+        &self,
+        values: &mut [f32],
+        kinds: &[ValueType],
+        var_index_base: u32,
+        coords: &[F2Dot14],
+    ) -> Result<(), ParseError> {
+        let store = match &self.item_variation_store {
+            Some(store) if var_index_base != NO_VARIATION_INDEX && !coords.is_empty() => store,
+            _ => return Ok(()),
+        };
+        for (i, (value, &kind)) in values.iter_mut().zip(kinds).enumerate() {
+            let var_index = var_index_base
+                .checked_add(u32::try_from(i)?)
+                .ok_or(ParseError::BadIndex)?;
+            let (outer, inner) = match &self.var_index_map {
+                Some(map) => map.get(var_index)?,
+                None => (
+                    u16::try_from(var_index >> 16)?,
+                    u16::try_from(var_index & 0xFFFF)?,
+                ),
+            };
+            *value += store.delta(outer, inner, coords)? * kind.scale();
+        }
+        Ok(())
+    }
+}
+
+impl<'a, 'b> PaintReader<'a, 'b> {
+    /// Read the paint graph of the version 1 color glyph `glyph_id`.
+    fn read_colr_glyph(&mut self, glyph_id: u16) -> Result<Option<Paint>, ParseError> {
+        let colr = self.colr;
+        let base_glyph_list = match &colr.base_glyph_list {
+            Some(base_glyph_list) => base_glyph_list,
+            None => return Ok(None),
+        };
+        let offset = match base_glyph_list
+            .records
+            .iter()
+            .find(|&(record_glyph_id, _)| record_glyph_id == glyph_id)
+        {
+            Some((_, offset)) => offset,
+            None => return Ok(None),
+        };
+        if self.glyph_stack.contains(&glyph_id) {
+            return Err(ParseError::BadValue);
+        }
+
+        self.glyph_stack.push(glyph_id);
+        let paint = self.read_paint(base_glyph_list.scope.offset(usize::try_from(offset)?))?;
+        self.glyph_stack.pop();
+        Ok(Some(paint))
+    }
+
+    fn read_paint(&mut self, scope: ReadScope<'a>) -> Result<Paint, ParseError> {
+        self.num_paints += 1;
+        if self.depth >= MAX_PAINT_DEPTH || self.num_paints > MAX_PAINTS {
+            return Err(ParseError::LimitExceeded);
+        }
+        self.depth += 1;
+        let paint = self.read_paint_inner(scope);
+        self.depth -= 1;
+        paint
+    }
+
+    fn read_paint_inner(&mut self, scope: ReadScope<'a>) -> Result<Paint, ParseError> {
+        use ValueType::{F2Dot14, FWord, UFWord};
+
+        let colr = self.colr;
+        let coords = self.coords;
+        let mut ctxt = scope.ctxt();
+        let format = ctxt.read_u8()?;
+        let variable = format % 2 == 1;
+        let paint = match format {
+            1 => {
+                let num_layers = ctxt.read_u8()?;
+                let first_layer_index = usize::try_from(ctxt.read_u32be()?)?;
+                let layer_list = colr.layer_list.as_ref().ok_or(ParseError::MissingValue)?;
+                let end = first_layer_index + usize::from(num_layers);
+                if end > layer_list.paint_offsets.len() {
+                    return Err(ParseError::BadIndex);
+                }
+                let mut layers = Vec::with_capacity(usize::from(num_layers));
+                for index in first_layer_index..end {
+                    let offset = layer_list.paint_offsets.get_item(index);
+                    let layer_scope = layer_list.scope.offset(usize::try_from(offset)?);
+                    layers.push(self.read_paint(layer_scope)?);
+                }
+                Paint::Layers(layers)
+            }
+            2 | 3 => {
+                let palette_index = ctxt.read_u16be()?;
+                let values = colr.read_values(&mut ctxt, &[F2Dot14], format == 3, coords)?;
+                Paint::Solid {
+                    palette_index,
+                    alpha: values[0],
+                }
+            }
+            4 | 5 => {
+                let color_line = self.read_color_line(&scope, &mut ctxt, format == 5)?;
+                let values = colr.read_values(&mut ctxt, &[FWord; 6], format == 5, coords)?;
+                Paint::LinearGradient {
+                    color_line,
+                    x0: values[0],
+                    y0: values[1],
+                    x1: values[2],
+                    y1: values[3],
+                    x2: values[4],
+                    y2: values[5],
+                }
+            }
+            6 | 7 => {
+                let color_line = self.read_color_line(&scope, &mut ctxt, format == 7)?;
+                let kinds = [FWord, FWord, UFWord, FWord, FWord, UFWord];
+                let values = colr.read_values(&mut ctxt, &kinds, format == 7, coords)?;
+                Paint::RadialGradient {
+                    color_line,
+                    x0: values[0],
+                    y0: values[1],
+                    radius0: values[2],
+                    x1: values[3],
+                    y1: values[4],
+                    radius1: values[5],
+                }
+            }
+            8 | 9 => {
+                let color_line = self.read_color_line(&scope, &mut ctxt, format == 9)?;
+                let kinds = [FWord, FWord, F2Dot14, F2Dot14];
+                let values = colr.read_values(&mut ctxt, &kinds, format == 9, coords)?;
+                Paint::SweepGradient {
+                    color_line,
+                    center_x: values[0],
+                    center_y: values[1],
+                    start_angle: values[2],
+                    end_angle: values[3],
+                }
+            }
+            10 => {
+                let paint = self.read_child(&scope, &mut ctxt)?;
+                let glyph_id = ctxt.read_u16be()?;
+                Paint::Glyph { glyph_id, paint }
+            }
+            11 => {
+                let glyph_id = ctxt.read_u16be()?;
+                let paint = self
+                    .read_colr_glyph(glyph_id)?
+                    .ok_or(ParseError::BadIndex)?;
+                Paint::ColrGlyph {
+                    glyph_id,
+                    paint: Box::new(paint),
+                }
+            }
+            12 | 13 => {
+                let paint = self.read_child(&scope, &mut ctxt)?;
+                let offset = ctxt.read::<U24Be>()?;
+                let mut transform_ctxt = scope.offset(usize::try_from(offset)?).ctxt();
+                let values = colr.read_values(
+                    &mut transform_ctxt,
+                    &[ValueType::Fixed; 6],
+                    format == 13,
+                    coords,
+                )?;
+                let transform = Affine2x3 {
+                    xx: values[0],
+                    yx: values[1],
+                    xy: values[2],
+                    yy: values[3],
+                    dx: values[4],
+                    dy: values[5],
+                };
+                Paint::Transform { transform, paint }
+            }
+            14..=31 => {
+                let paint = self.read_child(&scope, &mut ctxt)?;
+                let kinds: &[ValueType] = match format {
+                    14 | 15 => &[FWord, FWord],
+                    16 | 17 | 28 | 29 => &[F2Dot14, F2Dot14],
+                    18 | 19 | 30 | 31 => &[F2Dot14, F2Dot14, FWord, FWord],
+                    20 | 21 | 24 | 25 => &[F2Dot14],
+                    _ => &[F2Dot14, FWord, FWord],
+                };
+                let values = colr.read_values(&mut ctxt, kinds, variable, coords)?;
+                let center = match values.len() {
+                    3 => Some((values[1], values[2])),
+                    4 => Some((values[2], values[3])),
+                    _ => None,
+                };
+                match format {
+                    14 | 15 => Paint::Translate {
+                        dx: values[0],
+                        dy: values[1],
+                        paint,
+                    },
+                    16..=19 => Paint::Scale {
+                        scale_x: values[0],
+                        scale_y: values[1],
+                        center,
+                        paint,
+                    },
+                    20..=23 => Paint::Scale {
+                        scale_x: values[0],
+                        scale_y: values[0],
+                        center,
+                        paint,
+                    },
+                    24..=27 => Paint::Rotate {
+                        angle: values[0],
+                        center,
+                        paint,
+                    },
+                    _ => Paint::Skew {
+                        x_skew_angle: values[0],
+                        y_skew_angle: values[1],
+                        center,
+                        paint,
+                    },
+                }
+            }
+            32 => {
+                let source = self.read_child(&scope, &mut ctxt)?;
+                let mode = CompositeMode::new(ctxt.read_u8()?)?;
+                let backdrop = self.read_child(&scope, &mut ctxt)?;
+                Paint::Composite {
+                    source,
+                    mode,
+                    backdrop,
+                }
+            }
+            _ => return Err(ParseError::BadVersion),
+        };
+
+        Ok(paint)
+    }
+
+    /// Read the paint at the 24-bit offset read from `ctxt`, relative to `scope`.
+    fn read_child(
+        &mut self,
+        scope: &ReadScope<'a>,
+        ctxt: &mut ReadCtxt<'a>,
+    ) -> Result<Box<Paint>, ParseError> {
+        let offset = ctxt.read::<U24Be>()?;
+        if offset == 0 {
+            return Err(ParseError::BadOffset);
+        }
+        self.read_paint(scope.offset(usize::try_from(offset)?))
+            .map(Box::new)
+    }
+
+    /// Read the color line at the 24-bit offset read from `ctxt`, relative to `scope`.
+    fn read_color_line(
+        &self,
+        scope: &ReadScope<'a>,
+        ctxt: &mut ReadCtxt<'a>,
+        variable: bool,
+    ) -> Result<ColorLine, ParseError> {
+        let offset = ctxt.read::<U24Be>()?;
+        let mut line_ctxt = scope.offset(usize::try_from(offset)?).ctxt();
+        let extend = match line_ctxt.read_u8()? {
+            1 => Extend::Repeat,
+            2 => Extend::Reflect,
+            // Unknown values are treated as pad
+            _ => Extend::Pad,
+        };
+        let num_stops = line_ctxt.read_u16be()?;
+        let kinds = [ValueType::F2Dot14; 2];
+        let mut stops = Vec::with_capacity(usize::from(num_stops));
+        for _ in 0..num_stops {
+            let mut values = [read_value(&mut line_ctxt, ValueType::F2Dot14)?, 0.];
+            let palette_index = line_ctxt.read_u16be()?;
+            values[1] = read_value(&mut line_ctxt, ValueType::F2Dot14)?;
+            if variable {
+                let var_index_base = line_ctxt.read_u32be()?;
+                self.colr
+                    .apply_deltas(&mut values, &kinds, var_index_base, self.coords)?;
+            }
+            stops.push(ColorStop {
+                stop_offset: values[0],
+                palette_index,
+                alpha: values[1],
+            });
+        }
+
+        Ok(ColorLine { extend, stops })
+    }
+}
+
+impl<'a> ColrTable<'a> {
+    /// Flatten the color glyph `glyph_id` at the normalised variation coordinates `coords` into
+    /// layers filled with a single color or gradient, bottom layer first.
+    ///
+    /// Colors are taken from the palette at `palette_index` of `cpal`, with `foreground` used for
+    /// the text foreground color. `transform` maps font units to device space.
+    ///
+    /// Returns `ParseError::NotImplemented` if the glyph composites paints with a mode other than
+    /// `Clear`, `Src`, `Dest`, `SrcOver` or `DestOver`, since the result can't be represented as
+    /// independent layers. Returns `None` if `glyph_id` is not a color glyph.
+    pub fn flatten(
+        &self,
+        glyph_id: u16,
+        coords: &[F2Dot14],
+        cpal: &CpalTable<'_>,
+        palette_index: u16,
+        foreground: Rgba,
+        transform: &Affine2x3,
+    ) -> Result<Option<Vec<FlatLayer>>, ParseError> {
+        let paint = match self.paint(glyph_id, coords)? {
+            Some(paint) => paint,
+            None => return Ok(None),
+        };
+        let mut flattener = Flattener {
+            cpal,
+            palette_index,
+            foreground,
+            outlines: Vec::new(),
+            layers: Vec::new(),
+        };
+        flattener.flatten(&paint, transform)?;
+        Ok(Some(flattener.layers))
+    }
+}
+
+impl<'a, 'b> Flattener<'a, 'b> {
+    fn flatten(&mut self, paint: &Paint, transform: &Affine2x3) -> Result<(), ParseError> {
+        match paint {
+            Paint::Layers(paints) => {
+                for paint in paints {
+                    self.flatten(paint, transform)?;
+                }
+            }
+            Paint::Glyph { glyph_id, paint } => {
+                self.outlines.push(Outline {
+                    glyph_id: *glyph_id,
+                    transform: *transform,
+                });
+                self.flatten(paint, transform)?;
+                self.outlines.pop();
+            }
+            Paint::ColrGlyph { paint, .. } => self.flatten(paint, transform)?,
+            Paint::Transform {
+                transform: paint_transform,
+                paint,
+            } => self.flatten(paint, &transform.multiply(paint_transform))?,
+            Paint::Translate { dx, dy, paint } => {
+                let translate = Affine2x3::translate(*dx, *dy);
+                self.flatten(paint, &transform.multiply(&translate))?
+            }
+            Paint::Scale {
+                scale_x,
+                scale_y,
+                center,
+                paint,
+            } => {
+                let scale = Affine2x3 {
+                    xx: *scale_x,
+                    yy: *scale_y,
+                    ..Affine2x3::identity()
+                };
+                let transform = transform.multiply(&around_center(&scale, *center));
+                self.flatten(paint, &transform)?
+            }
+            Paint::Rotate {
+                angle,
+                center,
+                paint,
+            } => {
+                let (sin, cos) = (angle * std::f32::consts::PI).sin_cos();
+                let rotate = Affine2x3 {
+                    xx: cos,
+                    yx: sin,
+                    xy: -sin,
+                    yy: cos,
+                    dx: 0.,
+                    dy: 0.,
+                };
+                let transform = transform.multiply(&around_center(&rotate, *center));
+                self.flatten(paint, &transform)?
+            }
+            Paint::Skew {
+                x_skew_angle,
+                y_skew_angle,
+                center,
+                paint,
+            } => {
+                let skew = Affine2x3 {
+                    xx: 1.,
+                    yx: (y_skew_angle * std::f32::consts::PI).tan(),
+                    xy: -(x_skew_angle * std::f32::consts::PI).tan(),
+                    yy: 1.,
+                    dx: 0.,
+                    dy: 0.,
+                };
+                let transform = transform.multiply(&around_center(&skew, *center));
+                self.flatten(paint, &transform)?
+            }
+            Paint::Composite {
+                source,
+                mode,
+                backdrop,
+            } => match mode {
+                CompositeMode::Clear => {}
+                CompositeMode::Src => self.flatten(source, transform)?,
+                CompositeMode::Dest => self.flatten(backdrop, transform)?,
+                CompositeMode::SrcOver => {
+                    self.flatten(backdrop, transform)?;
+                    self.flatten(source, transform)?;
+                }
+                CompositeMode::DestOver => {
+                    self.flatten(source, transform)?;
+                    self.flatten(backdrop, transform)?;
+                }
+                _ => return Err(ParseError::NotImplemented),
+            },
+            Paint::Solid {
+                palette_index,
+                alpha,
+            } => {
+                let color = self.color(*palette_index, *alpha)?;
+                self.push_layer(Fill::Solid(color));
+            }
+            Paint::LinearGradient {
+                color_line,
+                x0,
+                y0,
+                x1,
+                y1,
+                x2,
+                y2,
+            } => {
+                // The gradient runs along the line from p0 perpendicular to p0p2, with the colors
+                // constant along lines parallel to p0p2. Find the end point on that line, then map
+                // the gradient to device space, where those lines remain parallel.
+                let normal = (*y2 - *y0, *x0 - *x2);
+                let p1 = project((*x0, *y0), (*x1, *y1), normal);
+                let p0 = transform.transform_point((*x0, *y0));
+                let direction = transform.transform_vector((*x2 - *x0, *y2 - *y0));
+                let p1 = project(
+                    p0,
+                    transform.transform_point(p1),
+                    (direction.1, -direction.0),
+                );
+                let fill = Fill::LinearGradient {
+                    p0,
+                    p1,
+                    stops: self.stops(color_line)?,
+                    extend: color_line.extend,
+                };
+                self.push_layer(fill);
+            }
+            Paint::RadialGradient {
+                color_line,
+                x0,
+                y0,
+                radius0,
+                x1,
+                y1,
+                radius1,
+            } => {
+                let fill = Fill::RadialGradient {
+                    c0: (*x0, *y0),
+                    r0: *radius0,
+                    c1: (*x1, *y1),
+                    r1: *radius1,
+                    stops: self.stops(color_line)?,
+                    extend: color_line.extend,
+                    transform: *transform,
+                };
+                self.push_layer(fill);
+            }
+            Paint::SweepGradient {
+                color_line,
+                center_x,
+                center_y,
+                start_angle,
+                end_angle,
+            } => {
+                let fill = Fill::SweepGradient {
+                    center: (*center_x, *center_y),
+                    start_angle: *start_angle,
+                    end_angle: *end_angle,
+                    stops: self.stops(color_line)?,
+                    extend: color_line.extend,
+                    transform: *transform,
+                };
+                self.push_layer(fill);
+            }
+        }
+        Ok(())
+    }
+
+    fn push_layer(&mut self, fill: Fill) {
+        self.layers.push(FlatLayer {
+            outlines: self.outlines.clone(),
+            fill,
+        });
+    }
+
+    fn color(&self, palette_index: u16, alpha: f32) -> Result<Rgba, ParseError> {
+        let color = self
+            .cpal
+            .resolve_color(self.palette_index, palette_index, self.foreground)?;
+        Ok(color.multiply_alpha(alpha))
+    }
+
+    fn stops(&self, color_line: &ColorLine) -> Result<Vec<GradientStop>, ParseError> {
+        let mut stops = color_line
+            .stops
+            .iter()
+            .map(|stop| {
+                Ok(GradientStop {
+                    offset: stop.stop_offset,
+                    color: self.color(stop.palette_index, stop.alpha)?,
+                })
+            })
+            .collect::<Result<Vec<_>, ParseError>>()?;
+        stops.sort_by(|a, b| {
+            a.offset
+                .partial_cmp(&b.offset)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        Ok(stops)
+    }
+}
+
+impl Affine2x3 {
+    /// The transform that leaves points unchanged.
+    pub fn identity() -> Affine2x3 {
+        Affine2x3 {
+            xx: 1.,
+            yx: 0.,
+            xy: 0.,
+            yy: 1.,
+            dx: 0.,
+            dy: 0.,
+        }
+    }
+
+    /// A translation by `dx`, `dy`.
+    pub fn translate(dx: f32, dy: f32) -> Affine2x3 {
+        Affine2x3 {
+            dx,
+            dy,
+            ..Affine2x3::identity()
+        }
+    }
+
+    /// Returns the transform that applies `other`, followed by `self`.
+    pub fn multiply(&self, other: &Affine2x3) -> Affine2x3 {
+        Affine2x3 {
+            xx: self.xx * other.xx + self.xy * other.yx,
+            yx: self.yx * other.xx + self.yy * other.yx,
+            xy: self.xx * other.xy + self.xy * other.yy,
+            yy: self.yx * other.xy + self.yy * other.yy,
+            dx: self.xx * other.dx + self.xy * other.dy + self.dx,
+            dy: self.yx * other.dx + self.yy * other.dy + self.dy,
+        }
+    }
+
+    /// Apply the transform to the point `(x, y)`.
+    pub fn transform_point(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        (
+            self.xx * x + self.xy * y + self.dx,
+            self.yx * x + self.yy * y + self.dy,
+        )
+    }
+
+    /// Apply the transform to the vector `(x, y)`, ignoring the translation.
+    fn transform_vector(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        (self.xx * x + self.xy * y, self.yx * x + self.yy * y)
+    }
+}
+
+/// Returns `transform` applied about `center` rather than the origin.
+fn around_center(transform: &Affine2x3, center: Option<(f32, f32)>) -> Affine2x3 {
+    match center {
+        Some((x, y)) => Affine2x3::translate(x, y)
+            .multiply(transform)
+            .multiply(&Affine2x3::translate(-x, -y)),
+        None => *transform,
+    }
+}
+
+/// Project `point` onto the line through `origin` in the direction `direction`.
+///
+/// Returns `point` if `direction` is zero.
+fn project(origin: (f32, f32), point: (f32, f32), direction: (f32, f32)) -> (f32, f32) {
+    let length_squared = direction.0 * direction.0 + direction.1 * direction.1;
+    if length_squared == 0. {
+        return point;
+    }
+    let v = (point.0 - origin.0, point.1 - origin.1);
+    let t = (v.0 * direction.0 + v.1 * direction.1) / length_squared;
+    (origin.0 + direction.0 * t, origin.1 + direction.1 * t)
+}
+
+impl ValueType {
+    /// The size of one unit of a delta relative to the value.
+    fn scale(self) -> f32 {
+        match self {
+            ValueType::FWord | ValueType::UFWord => 1.,
+            ValueType::F2Dot14 => 1. / 16384.,
+            ValueType::Fixed => 1. / 65536.,
+        }
+    }
+}
+
+fn read_value(ctxt: &mut ReadCtxt<'_>, kind: ValueType) -> Result<f32, ParseError> {
+    let value = match kind {
+        ValueType::FWord => f32::from(ctxt.read_i16be()?),
+        ValueType::UFWord => f32::from(ctxt.read_u16be()?),
+        ValueType::F2Dot14 => f32::from(ctxt.read::<F2Dot14>()?),
+        ValueType::Fixed => ctxt.read_i32be()? as f32 / 65536.,
+    };
+    Ok(value)
+}
+
+impl CompositeMode {
+    fn new(mode: u8) -> Result<Self, ParseError> {
+        let mode = match mode {
+            0 => CompositeMode::Clear,
+            1 => CompositeMode::Src,
+            2 => CompositeMode::Dest,
+            3 => CompositeMode::SrcOver,
+            4 => CompositeMode::DestOver,
+            5 => CompositeMode::SrcIn,
+            6 => CompositeMode::DestIn,
+            7 => CompositeMode::SrcOut,
+            8 => CompositeMode::DestOut,
+            9 => CompositeMode::SrcAtop,
+            10 => CompositeMode::DestAtop,
+            11 => CompositeMode::Xor,
+            12 => CompositeMode::Plus,
+            13 => CompositeMode::Screen,
+            14 => CompositeMode::Overlay,
+            15 => CompositeMode::Darken,
+            16 => CompositeMode::Lighten,
+            17 => CompositeMode::ColorDodge,
+            18 => CompositeMode::ColorBurn,
+            19 => CompositeMode::HardLight,
+            20 => CompositeMode::SoftLight,
+            21 => CompositeMode::Difference,
+            22 => CompositeMode::Exclusion,
+            23 => CompositeMode::Multiply,
+            24 => CompositeMode::Hue,
+            25 => CompositeMode::Saturation,
+            26 => CompositeMode::Color,
+            27 => CompositeMode::Luminosity,
+            _ => return Err(ParseError::BadValue),
+        };
+        Ok(mode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::write::{OffsetPlaceholder, WriteBinary, WriteBuffer, WriteContext};
+    use crate::binary::{I16Be, U8};
+
+    fn paint_offset(w: &mut WriteBuffer, base: usize) -> OffsetPlaceholder<U24Be, u32> {
+        w.offset_placeholder::<U24Be, u32>(base).unwrap()
+    }
+
+    fn write_solid(w: &mut WriteBuffer, palette_index: u16, alpha: u16) {
+        U8::write(w, 2u8).unwrap();
+        U16Be::write(w, palette_index).unwrap();
+        U16Be::write(w, alpha).unwrap();
+    }
+
+    /// Builds a version 1 `COLR` table with one axis.
+    ///
+    /// * Glyph 1 is two layers: glyph 10 filled with palette entry 0, and glyph 11 filled with a
+    ///   linear gradient, rotated by 90° around (50, 60).
+    /// * Glyph 2 is glyph 1 composited over palette entry 2 with an alpha of 0.5, varying to 1.0.
+    /// * Glyph 3 paints itself.
+    /// * Glyph 5 is a version 0 color glyph of glyphs 6 and 7.
+    ///
+    /// Glyphs 1 and 2 have a clip box whose minimum x varies from -10 to 0.
+    fn colr_data() -> Vec<u8> {
+        let mut w = WriteBuffer::new();
+        U16Be::write(&mut w, 1u16).unwrap();
+        U16Be::write(&mut w, 1u16).unwrap();
+        let base_glyph_records = w.offset_placeholder::<U32Be, u32>(0).unwrap();
+        let layer_records = w.offset_placeholder::<U32Be, u32>(0).unwrap();
+        U16Be::write(&mut w, 2u16).unwrap();
+        let base_glyph_list = w.offset_placeholder::<U32Be, u32>(0).unwrap();
+        let layer_list = w.offset_placeholder::<U32Be, u32>(0).unwrap();
+        let clip_list = w.offset_placeholder::<U32Be, u32>(0).unwrap();
+        U32Be::write(&mut w, 0u32).unwrap(); // var index map
+        let item_variation_store = w.offset_placeholder::<U32Be, u32>(0).unwrap();
+
+        // Version 0 records
+        w.write_offset(base_glyph_records).unwrap();
+        for &value in &[5u16, 0, 2] {
+            U16Be::write(&mut w, value).unwrap();
+        }
+        w.write_offset(layer_records).unwrap();
+        for &value in &[6u16, 0, 7, 1] {
+            U16Be::write(&mut w, value).unwrap();
+        }
+
+        // Base glyph list
+        let start = w.bytes_written();
+        w.write_offset(base_glyph_list).unwrap();
+        U32Be::write(&mut w, 3u32).unwrap();
+        let mut paints = Vec::new();
+        for glyph_id in 1..=3u16 {
+            U16Be::write(&mut w, glyph_id).unwrap();
+            paints.push(w.offset_placeholder::<U32Be, u32>(start).unwrap());
+        }
+        let mut paints = paints.into_iter();
+        // Glyph 1: PaintColrLayers
+        w.write_offset(paints.next().unwrap()).unwrap();
+        U8::write(&mut w, 1u8).unwrap();
+        U8::write(&mut w, 2u8).unwrap();
+        U32Be::write(&mut w, 0u32).unwrap();
+        // Glyph 2: PaintComposite
+        w.write_offset(paints.next().unwrap()).unwrap();
+        let paint_start = w.bytes_written();
+        U8::write(&mut w, 32u8).unwrap();
+        let source = paint_offset(&mut w, paint_start);
+        U8::write(&mut w, 3u8).unwrap(); // src over
+        let backdrop = paint_offset(&mut w, paint_start);
+        w.write_offset(source).unwrap();
+        U8::write(&mut w, 11u8).unwrap();
+        U16Be::write(&mut w, 1u16).unwrap();
+        w.write_offset(backdrop).unwrap();
+        U8::write(&mut w, 3u8).unwrap();
+        U16Be::write(&mut w, 2u16).unwrap();
+        U16Be::write(&mut w, 0x2000u16).unwrap(); // 0.5
+        U32Be::write(&mut w, 0u32).unwrap(); // var index base
+                                             // Glyph 3: PaintColrGlyph of itself
+        w.write_offset(paints.next().unwrap()).unwrap();
+        U8::write(&mut w, 11u8).unwrap();
+        U16Be::write(&mut w, 3u16).unwrap();
+
+        // Layer list
+        let start = w.bytes_written();
+        w.write_offset(layer_list).unwrap();
+        U32Be::write(&mut w, 2u32).unwrap();
+        let first_layer = w.offset_placeholder::<U32Be, u32>(start).unwrap();
+        let second_layer = w.offset_placeholder::<U32Be, u32>(start).unwrap();
+        // PaintGlyph filled with PaintSolid
+        w.write_offset(first_layer).unwrap();
+        let paint_start = w.bytes_written();
+        U8::write(&mut w, 10u8).unwrap();
+        let paint = paint_offset(&mut w, paint_start);
+        U16Be::write(&mut w, 10u16).unwrap();
+        w.write_offset(paint).unwrap();
+        write_solid(&mut w, 0, 0x4000);
+        // PaintRotateAroundCenter of PaintGlyph filled with PaintLinearGradient
+        w.write_offset(second_layer).unwrap();
+        let paint_start = w.bytes_written();
+        U8::write(&mut w, 26u8).unwrap();
+        let paint = paint_offset(&mut w, paint_start);
+        U16Be::write(&mut w, 0x2000u16).unwrap(); // 0.5
+        I16Be::write(&mut w, 50i16).unwrap();
+        I16Be::write(&mut w, 60i16).unwrap();
+        w.write_offset(paint).unwrap();
+        let paint_start = w.bytes_written();
+        U8::write(&mut w, 10u8).unwrap();
+        let paint = paint_offset(&mut w, paint_start);
+        U16Be::write(&mut w, 11u16).unwrap();
+        w.write_offset(paint).unwrap();
+        let paint_start = w.bytes_written();
+        U8::write(&mut w, 4u8).unwrap();
+        let color_line = paint_offset(&mut w, paint_start);
+        for &value in &[0i16, 0, 100, 0, 0, 100] {
+            I16Be::write(&mut w, value).unwrap();
+        }
+        w.write_offset(color_line).unwrap();
+        U8::write(&mut w, 2u8).unwrap(); // reflect
+        U16Be::write(&mut w, 2u16).unwrap();
+        for &value in &[0u16, 3, 0x4000, 0x4000, 4, 0x2000] {
+            U16Be::write(&mut w, value).unwrap();
+        }
+
+        // Clip list
+        let start = w.bytes_written();
+        w.write_offset(clip_list).unwrap();
+        U8::write(&mut w, 1u8).unwrap();
+        U32Be::write(&mut w, 1u32).unwrap();
+        U16Be::write(&mut w, 1u16).unwrap();
+        U16Be::write(&mut w, 2u16).unwrap();
+        let clip_box = paint_offset(&mut w, start);
+        w.write_offset(clip_box).unwrap();
+        U8::write(&mut w, 2u8).unwrap();
+        for &value in &[-10i16, -20, 110, 120] {
+            I16Be::write(&mut w, value).unwrap();
+        }
+        U32Be::write(&mut w, 1u32).unwrap(); // var index base
+
+        // Item variation store with a single region peaking at 1.0. Item 0 holds the alpha delta
+        // of glyph 2, and item 1 the x_min delta of the clip box.
+        let start = w.bytes_written();
+        w.write_offset(item_variation_store).unwrap();
+        U16Be::write(&mut w, 1u16).unwrap();
+        let region_list = w.offset_placeholder::<U32Be, u32>(start).unwrap();
+        U16Be::write(&mut w, 1u16).unwrap();
+        let item_variation_data = w.offset_placeholder::<U32Be, u32>(start).unwrap();
+        w.write_offset(region_list).unwrap();
+        for &value in &[1u16, 1, 0, 0x4000, 0x4000] {
+            U16Be::write(&mut w, value).unwrap();
+        }
+        w.write_offset(item_variation_data).unwrap();
+        for &value in &[2u16, 1, 1, 0] {
+            U16Be::write(&mut w, value).unwrap();
+        }
+        I16Be::write(&mut w, 0x2000i16).unwrap();
+        I16Be::write(&mut w, 10i16).unwrap();
+
+        w.into_inner()
+    }
+
+    fn glyph1_paint() -> Paint {
+        Paint::Layers(vec![
+            Paint::Glyph {
+                glyph_id: 10,
+                paint: Box::new(Paint::Solid {
+                    palette_index: 0,
+                    alpha: 1.,
+                }),
+            },
+            Paint::Rotate {
+                angle: 0.5,
+                center: Some((50., 60.)),
+                paint: Box::new(Paint::Glyph {
+                    glyph_id: 11,
+                    paint: Box::new(Paint::LinearGradient {
+                        color_line: ColorLine {
+                            extend: Extend::Reflect,
+                            stops: vec![
+                                ColorStop {
+                                    stop_offset: 0.,
+                                    palette_index: 3,
+                                    alpha: 1.,
+                                },
+                                ColorStop {
+                                    stop_offset: 1.,
+                                    palette_index: 4,
+                                    alpha: 0.5,
+                                },
+                            ],
+                        },
+                        x0: 0.,
+                        y0: 0.,
+                        x1: 100.,
+                        y1: 0.,
+                        x2: 0.,
+                        y2: 100.,
+                    }),
+                }),
+            },
+        ])
+    }
+
+    #[test]
+    fn test_read_paint() {
+        let data = colr_data();
+        let colr = ReadScope::new(&data).read::<ColrTable<'_>>().unwrap();
+        assert_eq!(colr.version, 1);
+        assert_eq!(colr.paint(1, &[]).unwrap(), Some(glyph1_paint()));
+
+        let composite = |alpha| Paint::Composite {
+            source: Box::new(Paint::ColrGlyph {
+                glyph_id: 1,
+                paint: Box::new(glyph1_paint()),
+            }),
+            mode: CompositeMode::SrcOver,
+            backdrop: Box::new(Paint::Solid {
+                palette_index: 2,
+                alpha,
+            }),
+        };
+        assert_eq!(colr.paint(2, &[]).unwrap(), Some(composite(0.5)));
+        let full = [F2Dot14::new(0x4000)];
+        assert_eq!(colr.paint(2, &full).unwrap(), Some(composite(1.)));
+
+        // Not a color glyph
+        assert_eq!(colr.paint(4, &[]).unwrap(), None);
+    }
+
+    #[test]
+    fn test_read_paint_cycle() {
+        let data = colr_data();
+        let colr = ReadScope::new(&data).read::<ColrTable<'_>>().unwrap();
+        assert!(colr.paint(3, &[]).is_err());
+    }
+
+    #[test]
+    fn test_layers() {
+        let data = colr_data();
+        let colr = ReadScope::new(&data).read::<ColrTable<'_>>().unwrap();
+        let layers = colr.layers(5).unwrap().unwrap();
+        assert_eq!(
+            layers,
+            vec![
+                LayerRecord {
+                    glyph_id: 6,
+                    palette_index: 0
+                },
+                LayerRecord {
+                    glyph_id: 7,
+                    palette_index: 1
+                },
+            ]
+        );
+        match colr.paint(5, &[]).unwrap() {
+            Some(Paint::Layers(paints)) => assert_eq!(paints.len(), 2),
+            _ => panic!("expected layers"),
+        }
+        assert!(colr.layers(1).unwrap().is_none());
+
+        // The paint of each version can be requested separately
+        assert!(colr.paint_v1(5, &[]).unwrap().is_none());
+        assert!(colr.paint_v0(5).unwrap().is_some());
+        assert!(colr.paint_v1(1, &[]).unwrap().is_some());
+        assert!(colr.paint_v0(1).unwrap().is_none());
+    }
+
+    fn cpal_data() -> Vec<u8> {
+        let mut w = WriteBuffer::new();
+        for &value in &[0u16, 5, 1, 5] {
+            U16Be::write(&mut w, value).unwrap();
+        }
+        U32Be::write(&mut w, 14u32).unwrap();
+        U16Be::write(&mut w, 0u16).unwrap();
+        // Palette entries 0 to 4 in BGRA order
+        w.write_bytes(&[0, 0, 255, 255]).unwrap();
+        w.write_bytes(&[0, 255, 0, 255]).unwrap();
+        w.write_bytes(&[255, 0, 0, 255]).unwrap();
+        w.write_bytes(&[0, 0, 0, 255]).unwrap();
+        w.write_bytes(&[255, 255, 255, 255]).unwrap();
+        w.into_inner()
+    }
+
+    fn rgba(red: u8, green: u8, blue: u8, alpha: u8) -> Rgba {
+        Rgba {
+            red,
+            green,
+            blue,
+            alpha,
+        }
+    }
+
+    fn assert_point_eq(actual: (f32, f32), expected: (f32, f32)) {
+        assert!(
+            (actual.0 - expected.0).abs() < 1e-3 && (actual.1 - expected.1).abs() < 1e-3,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_flatten() {
+        let colr_data = colr_data();
+        let colr = ReadScope::new(&colr_data).read::<ColrTable<'_>>().unwrap();
+        let cpal_data = cpal_data();
+        let cpal = ReadScope::new(&cpal_data).read::<CpalTable<'_>>().unwrap();
+        let foreground = rgba(1, 2, 3, 255);
+        let scale = Affine2x3 {
+            xx: 2.,
+            yy: 2.,
+            ..Affine2x3::identity()
+        };
+
+        let layers = colr
+            .flatten(2, &[], &cpal, 0, foreground, &scale)
+            .unwrap()
+            .unwrap();
+        assert_eq!(layers.len(), 3);
+
+        // The backdrop of the composite has no outline
+        assert!(layers[0].outlines.is_empty());
+        assert_eq!(layers[0].fill, Fill::Solid(rgba(0, 0, 255, 128)));
+
+        assert_eq!(
+            layers[1].outlines,
+            vec![Outline {
+                glyph_id: 10,
+                transform: scale
+            }]
+        );
+        assert_eq!(layers[1].fill, Fill::Solid(rgba(255, 0, 0, 255)));
+
+        // Glyph 11 rotated by 90° around (50, 60)
+        let outline = layers[2].outlines[0];
+        assert_eq!(outline.glyph_id, 11);
+        assert_point_eq(outline.transform.transform_point((0., 0.)), (220., 20.));
+        assert_point_eq(outline.transform.transform_point((100., 0.)), (220., 220.));
+        match &layers[2].fill {
+            Fill::LinearGradient {
+                p0,
+                p1,
+                stops,
+                extend,
+            } => {
+                assert_point_eq(*p0, (220., 20.));
+                assert_point_eq(*p1, (220., 220.));
+                assert_eq!(*extend, Extend::Reflect);
+                assert_eq!(
+                    stops,
+                    &vec![
+                        GradientStop {
+                            offset: 0.,
+                            color: rgba(0, 0, 0, 255),
+                        },
+                        GradientStop {
+                            offset: 1.,
+                            color: rgba(255, 255, 255, 128),
+                        },
+                    ]
+                );
+            }
+            fill => panic!("expected a linear gradient, got {:?}", fill),
+        }
+
+        // Version 0 glyphs are flattened too
+        let layers = colr
+            .flatten(5, &[], &cpal, 0, foreground, &Affine2x3::identity())
+            .unwrap()
+            .unwrap();
+        assert_eq!(layers.len(), 2);
+        assert_eq!(layers[1].fill, Fill::Solid(rgba(0, 255, 0, 255)));
+        assert!(colr
+            .flatten(4, &[], &cpal, 0, foreground, &Affine2x3::identity())
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_flatten_linear_gradient_skew() {
+        // A horizontal gradient skewed along x keeps its colors constant along the skewed lines
+        let paint = Paint::Skew {
+            x_skew_angle: -0.25,
+            y_skew_angle: 0.,
+            center: None,
+            paint: Box::new(Paint::LinearGradient {
+                color_line: ColorLine {
+                    extend: Extend::Pad,
+                    stops: Vec::new(),
+                },
+                x0: 0.,
+                y0: 0.,
+                x1: 100.,
+                y1: 50.,
+                x2: 0.,
+                y2: 100.,
+            }),
+        };
+        let cpal_data = cpal_data();
+        let cpal = ReadScope::new(&cpal_data).read::<CpalTable<'_>>().unwrap();
+        let mut flattener = Flattener {
+            cpal: &cpal,
+            palette_index: 0,
+            foreground: rgba(0, 0, 0, 255),
+            outlines: Vec::new(),
+            layers: Vec::new(),
+        };
+        flattener.flatten(&paint, &Affine2x3::identity()).unwrap();
+        match &flattener.layers[0].fill {
+            Fill::LinearGradient { p0, p1, .. } => {
+                assert_point_eq(*p0, (0., 0.));
+                // (0, 100) is mapped to (100, 100) so the isolines run at 45°
+                assert_point_eq(*p1, (50., -50.));
+            }
+            fill => panic!("expected a linear gradient, got {:?}", fill),
+        }
+    }
+
+    #[test]
+    fn test_flatten_unsupported_composite() {
+        let paint = Paint::Composite {
+            source: Box::new(Paint::Layers(Vec::new())),
+            mode: CompositeMode::Multiply,
+            backdrop: Box::new(Paint::Layers(Vec::new())),
+        };
+        let cpal_data = cpal_data();
+        let cpal = ReadScope::new(&cpal_data).read::<CpalTable<'_>>().unwrap();
+        let mut flattener = Flattener {
+            cpal: &cpal,
+            palette_index: 0,
+            foreground: rgba(0, 0, 0, 255),
+            outlines: Vec::new(),
+            layers: Vec::new(),
+        };
+        assert!(flattener.flatten(&paint, &Affine2x3::identity()).is_err());
+    }
+
+    #[test]
+    fn test_clip_box() {
+        let data = colr_data();
+        let colr = ReadScope::new(&data).read::<ColrTable<'_>>().unwrap();
+        let clip_box = ClipBox {
+            x_min: -10.,
+            y_min: -20.,
+            x_max: 110.,
+            y_max: 120.,
+        };
+        assert_eq!(colr.clip_box(2, &[]).unwrap(), Some(clip_box));
+        let clip_box = ClipBox {
+            x_min: 0.,
+            ..clip_box
+        };
+        let full = [F2Dot14::new(0x4000)];
+        assert_eq!(colr.clip_box(1, &full).unwrap(), Some(clip_box));
+        assert_eq!(colr.clip_box(3, &full).unwrap(), None);
+    }
+}
@@ -0,0 +1,359 @@
+#![deny(missing_docs)]
+
+//! `CPAL` table parsing.
+//!
+//! The `CPAL` table holds the color palettes used by the color glyphs of the `COLR` table. All
+//! palettes have the same number of entries, and the color glyphs refer to colors by their entry
+//! index, so the palette used can be changed without changing the glyphs.
+//!
+//! <https://docs.microsoft.com/en-us/typography/opentype/spec/cpal>
+
+use std::convert::TryFrom;
+
+use bitflags::bitflags;
+
+use crate::binary::read::{
+    CheckIndex, ReadArray, ReadBinary, ReadCtxt, ReadFrom, ReadScope, ReadUnchecked,
+};
+use crate::binary::{U16Be, U32Be};
+use crate::error::ParseError;
+
+/// The palette entry index that refers to the text foreground color.
+pub const FOREGROUND_PALETTE_INDEX: u16 = 0xFFFF;
+
+/// The name id indicating that a palette or palette entry has no label.
+const NO_NAME_ID: u16 = 0xFFFF;
+
+/// The `CPAL` color palette table.
+pub struct CpalTable<'a> {
+    /// The version of the table, 0 or 1.
+    pub version: u16,
+    /// The number of entries in each palette.
+    pub num_palette_entries: u16,
+    color_record_indices: ReadArray<'a, U16Be>,
+    color_records: ReadArray<'a, Rgba>,
+    palette_types: Option<ReadArray<'a, U32Be>>,
+    palette_labels: Option<ReadArray<'a, U16Be>>,
+    palette_entry_labels: Option<ReadArray<'a, U16Be>>,
+}
+
+/// A color in the sRGB color space, with unpremultiplied alpha.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Rgba {
+    /// Red component.
+    pub red: u8,
+    /// Green component.
+    pub green: u8,
+    /// Blue component.
+    pub blue: u8,
+    /// Alpha component, 0 is transparent and 255 opaque.
+    pub alpha: u8,
+}
+
+bitflags! {
+    /// Flags describing the intended use of a palette.
+    pub struct PaletteType: u32 {
+        /// The palette is appropriate to use when displaying the font on a light background.
+        const USABLE_WITH_LIGHT_BACKGROUND = 1 << 0;
+        /// The palette is appropriate to use when displaying the font on a dark background.
+        const USABLE_WITH_DARK_BACKGROUND = 1 << 1;
+    }
+}
+
+impl<'a> ReadBinary<'a> for CpalTable<'a> {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let scope = ctxt.scope();
+        let version = ctxt.read_u16be()?;
+        ctxt.check_version(version <= 1)?;
+        let num_palette_entries = ctxt.read_u16be()?;
+        let num_palettes = usize::from(ctxt.read_u16be()?);
+        let num_color_records = usize::from(ctxt.read_u16be()?);
+        let color_records_array_offset = usize::try_from(ctxt.read_u32be()?)?;
+        let color_record_indices = ctxt.read_array::<U16Be>(num_palettes)?;
+        for index in color_record_indices.iter() {
+            ctxt.check(usize::from(index) + usize::from(num_palette_entries) <= num_color_records)?;
+        }
+        let color_records = scope
+            .offset(color_records_array_offset)
+            .ctxt()
+            .read_array::<Rgba>(num_color_records)?;
+
+        let mut cpal = CpalTable {
+            version,
+            num_palette_entries,
+            color_record_indices,
+            color_records,
+            palette_types: None,
+            palette_labels: None,
+            palette_entry_labels: None,
+        };
+        if version == 0 {
+            return Ok(cpal);
+        }
+
+        let palette_types_array_offset = ctxt.read_u32be()?;
+        let palette_labels_array_offset = ctxt.read_u32be()?;
+        let palette_entry_labels_array_offset = ctxt.read_u32be()?;
+        cpal.palette_types = read_array(&scope, palette_types_array_offset, num_palettes)?;
+        cpal.palette_labels = read_array(&scope, palette_labels_array_offset, num_palettes)?;
+        cpal.palette_entry_labels = read_array(
+            &scope,
+            palette_entry_labels_array_offset,
+            usize::from(num_palette_entries),
+        )?;
+
+        Ok(cpal)
+    }
+}
+
+/// Read the array of `count` items at `offset` from `scope`, if `offset` is not zero.
+fn read_array<'a, T: ReadUnchecked<'a>>(
+    scope: &ReadScope<'a>,
+    offset: u32,
+    count: usize,
+) -> Result<Option<ReadArray<'a, T>>, ParseError> {
+    match offset {
+        0 => Ok(None),
+        offset => {
+            let array = scope
+                .offset(usize::try_from(offset)?)
+                .ctxt()
+                .read_array::<T>(count)?;
+            Ok(Some(array))
+        }
+    }
+}
+
+impl<'a> ReadFrom<'a> for Rgba {
+    type ReadType = U32Be;
+
+    fn from(bgra: u32) -> Self {
+        let [blue, green, red, alpha] = bgra.to_be_bytes();
+        Rgba {
+            red,
+            green,
+            blue,
+            alpha,
+        }
+    }
+}
+
+impl<'a> CpalTable<'a> {
+    /// The number of palettes in the table.
+    pub fn num_palettes(&self) -> usize {
+        self.color_record_indices.len()
+    }
+
+    /// Returns the colors of the palette at `palette_index`.
+    pub fn palette(&self, palette_index: u16) -> Result<Vec<Rgba>, ParseError> {
+        let start = self.first_color_record(palette_index)?;
+        let end = start + usize::from(self.num_palette_entries);
+        Ok((start..end)
+            .map(|index| self.color_records.get_item(index))
+            .collect())
+    }
+
+    /// Returns the color of entry `entry_index` of the palette at `palette_index`.
+    ///
+    /// `foreground` is returned for `FOREGROUND_PALETTE_INDEX`, which color glyphs use to refer
+    /// to the color of the text.
+    pub fn resolve_color(
+        &self,
+        palette_index: u16,
+        entry_index: u16,
+        foreground: Rgba,
+    ) -> Result<Rgba, ParseError> {
+        if entry_index == FOREGROUND_PALETTE_INDEX {
+            return Ok(foreground);
+        }
+        if entry_index >= self.num_palette_entries {
+            return Err(ParseError::BadIndex);
+        }
+        let start = self.first_color_record(palette_index)?;
+        Ok(self
+            .color_records
+            .get_item(start + usize::from(entry_index)))
+    }
+
+    /// Returns the type of the palette at `palette_index`.
+    ///
+    /// The type is empty for version 0 tables, and tables without palette types.
+    pub fn palette_type(&self, palette_index: u16) -> Result<PaletteType, ParseError> {
+        match &self.palette_types {
+            Some(palette_types) => {
+                let index = usize::from(palette_index);
+                palette_types.check_index(index)?;
+                Ok(PaletteType::from_bits_truncate(
+                    palette_types.get_item(index),
+                ))
+            }
+            None => Ok(PaletteType::empty()),
+        }
+    }
+
+    /// Returns the `name` table id of the label of the palette at `palette_index`.
+    pub fn palette_label(&self, palette_index: u16) -> Result<Option<u16>, ParseError> {
+        read_label(&self.palette_labels, palette_index)
+    }
+
+    /// Returns the `name` table id of the label of palette entry `entry_index`, which applies to
+    /// that entry in all palettes.
+    pub fn palette_entry_label(&self, entry_index: u16) -> Result<Option<u16>, ParseError> {
+        read_label(&self.palette_entry_labels, entry_index)
+    }
+
+    /// The index of the first color record of the palette at `palette_index`.
+    fn first_color_record(&self, palette_index: u16) -> Result<usize, ParseError> {
+        let index = usize::from(palette_index);
+        self.color_record_indices.check_index(index)?;
+        Ok(usize::from(self.color_record_indices.get_item(index)))
+    }
+}
+
+fn read_label(
+    labels: &Option<ReadArray<'_, U16Be>>,
+    index: u16,
+) -> Result<Option<u16>, ParseError> {
+    match labels {
+        Some(labels) => {
+            let index = usize::from(index);
+            labels.check_index(index)?;
+            match labels.get_item(index) {
+                NO_NAME_ID => Ok(None),
+                name_id => Ok(Some(name_id)),
+            }
+        }
+        None => Ok(None),
+    }
+}
+
+impl Rgba {
+    /// Returns this color with its alpha multiplied by `alpha`, which is clamped to 0 to 1.
+    ///
+    /// This is used to apply the alpha of `COLR` paints to palette colors.
+    pub fn multiply_alpha(self, alpha: f32) -> Rgba {
+        let scaled = (f32::from(self.alpha) * alpha).round();
+        let alpha = if scaled > 0. {
+            scaled.min(255.) as u8
+        } else {
+            0
+        };
+        Rgba { alpha, ..self }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::write::{WriteBinary, WriteBuffer, WriteContext};
+
+    const RED: Rgba = Rgba {
+        red: 255,
+        green: 0,
+        blue: 0,
+        alpha: 255,
+    };
+    const BLUE: Rgba = Rgba {
+        red: 0,
+        green: 0,
+        blue: 255,
+        alpha: 128,
+    };
+    const WHITE: Rgba = Rgba {
+        red: 255,
+        green: 255,
+        blue: 255,
+        alpha: 255,
+    };
+
+    /// Builds a `CPAL` table with two palettes of two entries, where the second palette shares a
+    /// color record with the first.
+    fn cpal_data(version: u16) -> Vec<u8> {
+        let mut w = WriteBuffer::new();
+        U16Be::write(&mut w, version).unwrap();
+        U16Be::write(&mut w, 2u16).unwrap(); // num palette entries
+        U16Be::write(&mut w, 2u16).unwrap(); // num palettes
+        U16Be::write(&mut w, 3u16).unwrap(); // num color records
+        let color_records = w.offset_placeholder::<U32Be, u32>(0).unwrap();
+        U16Be::write(&mut w, 0u16).unwrap();
+        U16Be::write(&mut w, 1u16).unwrap();
+        if version == 1 {
+            let palette_types = w.offset_placeholder::<U32Be, u32>(0).unwrap();
+            let palette_labels = w.offset_placeholder::<U32Be, u32>(0).unwrap();
+            U32Be::write(&mut w, 0u32).unwrap(); // palette entry labels
+            w.write_offset(palette_types).unwrap();
+            U32Be::write(&mut w, 1u32).unwrap();
+            U32Be::write(&mut w, 2u32).unwrap();
+            w.write_offset(palette_labels).unwrap();
+            U16Be::write(&mut w, 256u16).unwrap();
+            U16Be::write(&mut w, NO_NAME_ID).unwrap();
+        }
+        w.write_offset(color_records).unwrap();
+        w.write_bytes(&[0, 0, 255, 255]).unwrap(); // red
+        w.write_bytes(&[255, 0, 0, 128]).unwrap(); // blue
+        w.write_bytes(&[255, 255, 255, 255]).unwrap(); // white
+        w.into_inner()
+    }
+
+    #[test]
+    fn test_read_cpal_v0() {
+        let data = cpal_data(0);
+        let cpal = ReadScope::new(&data).read::<CpalTable<'_>>().unwrap();
+        assert_eq!(cpal.num_palettes(), 2);
+        assert_eq!(cpal.palette(0).unwrap(), vec![RED, BLUE]);
+        assert_eq!(cpal.palette(1).unwrap(), vec![BLUE, WHITE]);
+        assert!(cpal.palette(2).is_err());
+        assert_eq!(cpal.palette_type(0).unwrap(), PaletteType::empty());
+        assert_eq!(cpal.palette_label(0).unwrap(), None);
+    }
+
+    #[test]
+    fn test_read_cpal_v1() {
+        let data = cpal_data(1);
+        let cpal = ReadScope::new(&data).read::<CpalTable<'_>>().unwrap();
+        assert_eq!(cpal.palette(1).unwrap(), vec![BLUE, WHITE]);
+        assert_eq!(
+            cpal.palette_type(0).unwrap(),
+            PaletteType::USABLE_WITH_LIGHT_BACKGROUND
+        );
+        assert_eq!(
+            cpal.palette_type(1).unwrap(),
+            PaletteType::USABLE_WITH_DARK_BACKGROUND
+        );
+        assert_eq!(cpal.palette_label(0).unwrap(), Some(256));
+        assert_eq!(cpal.palette_label(1).unwrap(), None);
+        assert_eq!(cpal.palette_entry_label(0).unwrap(), None);
+    }
+
+    #[test]
+    fn test_resolve_color() {
+        let data = cpal_data(1);
+        let cpal = ReadScope::new(&data).read::<CpalTable<'_>>().unwrap();
+        let black = Rgba {
+            red: 0,
+            green: 0,
+            blue: 0,
+            alpha: 255,
+        };
+        assert_eq!(cpal.resolve_color(0, 0, black).unwrap(), RED);
+        assert_eq!(cpal.resolve_color(1, 1, black).unwrap(), WHITE);
+        assert_eq!(
+            cpal.resolve_color(1, FOREGROUND_PALETTE_INDEX, black)
+                .unwrap(),
+            black
+        );
+        assert!(cpal.resolve_color(0, 2, black).is_err());
+        assert!(cpal.resolve_color(2, 0, black).is_err());
+        assert_eq!(RED.multiply_alpha(0.5).alpha, 128);
+    }
+
+    #[test]
+    fn test_read_cpal_bad_index() {
+        let mut data = cpal_data(0);
+        // Point the second palette past the end of the color records
+        data[14] = 2;
+        assert!(ReadScope::new(&data).read::<CpalTable<'_>>().is_err());
+    }
+}
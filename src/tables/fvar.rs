@@ -0,0 +1,417 @@
+#![deny(missing_docs)]
+
+//! `fvar` table parsing.
+//!
+//! The `fvar` table defines the axes of variation of a variable font, and the named instances
+//! that are predefined at particular positions along those axes.
+//!
+//! <https://docs.microsoft.com/en-us/typography/opentype/spec/fvar>
+
+use std::convert::TryFrom;
+
+use bitflags::bitflags;
+
+use crate::binary::read::{ReadBinary, ReadCtxt};
+use crate::error::ParseError;
+use crate::tables::F2Dot14;
+
+/// The size of a variation axis record. The specification fixes it at 20 bytes.
+const AXIS_SIZE: u16 = 20;
+
+/// The `fvar` font variations table.
+pub struct FvarTable {
+    /// The axes of variation, in the order used by variation coordinates.
+    pub axes: Vec<VariationAxisRecord>,
+    /// The named instances of the font.
+    pub instances: Vec<InstanceRecord>,
+}
+
+/// An axis of variation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariationAxisRecord {
+    /// The tag identifying the axis, such as `wght`.
+    pub axis_tag: u32,
+    /// The minimum coordinate value of the axis.
+    pub min_value: f32,
+    /// The default coordinate value of the axis.
+    pub default_value: f32,
+    /// The maximum coordinate value of the axis.
+    pub max_value: f32,
+    /// Axis qualifiers, see `axis_flags`.
+    pub flags: u16,
+    /// The `name` table ID of the display name of the axis.
+    pub axis_name_id: u16,
+}
+
+/// An axis whose requested user coordinate was outside of its range, or not a number.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClampedAxis {
+    /// The index of the axis in `FvarTable::axes`.
+    pub axis_index: usize,
+    /// The tag identifying the axis.
+    pub axis_tag: u32,
+    /// The requested user coordinate.
+    pub requested: f32,
+    /// The user coordinate that was used instead.
+    pub clamped: f32,
+}
+
+/// A named instance of a variable font.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstanceRecord {
+    /// The `name` table ID of the subfamily name of the instance.
+    pub subfamily_name_id: u16,
+    /// Reserved flags, see `instance_flags`.
+    pub flags: u16,
+    /// The user coordinates of the instance, one per axis.
+    pub coordinates: Vec<f32>,
+    /// The `name` table ID of the PostScript name of the instance, if present.
+    pub post_script_name_id: Option<u16>,
+}
+
+bitflags! {
+    /// Flags stored in the `flags` field of an axis record.
+    pub struct AxisFlags: u16 {
+        /// The axis should not be exposed directly in user interfaces.
+        const HIDDEN_AXIS = 0x0001;
+    }
+}
+
+bitflags! {
+    /// Flags stored in the `flags` field of an instance record.
+    ///
+    /// No flags are currently defined, all bits are reserved and should be zero.
+    pub struct InstanceFlags: u16 {}
+}
+
+impl<'a> ReadBinary<'a> for FvarTable {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let scope = ctxt.scope();
+        let major_version = ctxt.read_u16be()?;
+        ctxt.check_version(major_version == 1)?;
+        let _minor_version = ctxt.read_u16be()?;
+        let axes_array_offset = ctxt.read_u16be()?;
+        let _reserved = ctxt.read_u16be()?;
+        let axis_count = ctxt.read_u16be()?;
+        let axis_size = ctxt.read_u16be()?;
+        ctxt.check_field(axis_size == AXIS_SIZE, "axisSize")?;
+        let instance_count = ctxt.read_u16be()?;
+        let instance_size = usize::from(ctxt.read_u16be()?);
+        // Each instance holds two u16 fields and a Fixed coordinate per axis, optionally followed
+        // by the PostScript name ID
+        let coordinates_size = 4 + usize::from(axis_count) * 4;
+        let has_post_script_name_id = instance_size == coordinates_size + 2;
+        ctxt.check_field(
+            instance_size == coordinates_size || has_post_script_name_id,
+            "instanceSize",
+        )?;
+
+        let mut ctxt = scope.offset(usize::from(axes_array_offset)).ctxt();
+        let axes = (0..axis_count)
+            .map(|_| {
+                Ok(VariationAxisRecord {
+                    axis_tag: ctxt.read_u32be()?,
+                    min_value: read_fixed(&mut ctxt)?,
+                    default_value: read_fixed(&mut ctxt)?,
+                    max_value: read_fixed(&mut ctxt)?,
+                    flags: ctxt.read_u16be()?,
+                    axis_name_id: ctxt.read_u16be()?,
+                })
+            })
+            .collect::<Result<Vec<_>, ParseError>>()?;
+        // The instances follow the axes
+        let instances = (0..instance_count)
+            .map(|_| {
+                let subfamily_name_id = ctxt.read_u16be()?;
+                let flags = ctxt.read_u16be()?;
+                let coordinates = (0..axis_count)
+                    .map(|_| read_fixed(&mut ctxt))
+                    .collect::<Result<Vec<_>, ParseError>>()?;
+                let post_script_name_id = if has_post_script_name_id {
+                    Some(ctxt.read_u16be()?)
+                } else {
+                    None
+                };
+                Ok(InstanceRecord {
+                    subfamily_name_id,
+                    flags,
+                    coordinates,
+                    post_script_name_id,
+                })
+            })
+            .collect::<Result<Vec<_>, ParseError>>()?;
+
+        Ok(FvarTable { axes, instances })
+    }
+}
+
+impl VariationAxisRecord {
+    /// The `flags` field as flags. Reserved bits are discarded.
+    pub fn axis_flags(&self) -> AxisFlags {
+        AxisFlags::from_bits_truncate(self.flags)
+    }
+
+    /// The minimum and maximum value of the axis.
+    ///
+    /// The range is widened to include the default value when the font gives an invalid range
+    /// that doesn't.
+    pub fn range(&self) -> (f32, f32) {
+        (
+            self.min_value.min(self.default_value),
+            self.max_value.max(self.default_value),
+        )
+    }
+
+    /// Map the user coordinate `value` to a normalised coordinate from -1 to 1, with 0 at the
+    /// default value of the axis. Values outside the range of the axis are clamped to it.
+    pub fn normalize(&self, value: f32) -> f32 {
+        let (min, max) = self.range();
+        let default = self.default_value;
+        let normalized = if value < default {
+            (value.max(min) - default) / (default - min)
+        } else if value > default {
+            (value.min(max) - default) / (max - default)
+        } else {
+            0.
+        };
+        // NaN is mapped to the default
+        if normalized.is_nan() {
+            0.
+        } else {
+            normalized
+        }
+    }
+
+    /// Returns `true` if the axis should be hidden from users, such as an axis that is only
+    /// meant to be set by the application.
+    pub fn is_hidden(&self) -> bool {
+        self.axis_flags().contains(AxisFlags::HIDDEN_AXIS)
+    }
+}
+
+impl InstanceRecord {
+    /// The `flags` field as flags. Reserved bits are discarded.
+    pub fn instance_flags(&self) -> InstanceFlags {
+        InstanceFlags::from_bits_truncate(self.flags)
+    }
+}
+
+impl FvarTable {
+    /// Returns the number of axes, as a `u16` like the `axisCount` field it was read from.
+    pub fn axis_count(&self) -> u16 {
+        // The axes were read from a u16 count so this can't fail
+        u16::try_from(self.axes.len()).unwrap_or(std::u16::MAX)
+    }
+
+    /// Returns an iterator over the axes that aren't hidden, for presenting to users.
+    pub fn visible_axes(&self) -> impl Iterator<Item = &VariationAxisRecord> {
+        self.axes.iter().filter(|axis| !axis.is_hidden())
+    }
+
+    /// Clamp user `coordinates`, one per axis, to the range of each axis.
+    ///
+    /// Missing coordinates are set to the default value of their axis, and extra coordinates are
+    /// dropped. NaN coordinates are replaced by the default value and reported as clamped. The
+    /// axes whose coordinates were changed are returned alongside the coordinates.
+    pub fn clamp_coordinates(&self, coordinates: &[f32]) -> (Vec<f32>, Vec<ClampedAxis>) {
+        let mut clamped_axes = Vec::new();
+        let clamped = self
+            .axes
+            .iter()
+            .enumerate()
+            .map(|(axis_index, axis)| {
+                let requested = match coordinates.get(axis_index) {
+                    Some(&requested) => requested,
+                    None => return axis.default_value,
+                };
+                let (min, max) = axis.range();
+                let clamped = if requested.is_nan() {
+                    axis.default_value
+                } else if requested < min {
+                    min
+                } else if requested > max {
+                    max
+                } else {
+                    requested
+                };
+                if clamped != requested || requested.is_nan() {
+                    clamped_axes.push(ClampedAxis {
+                        axis_index,
+                        axis_tag: axis.axis_tag,
+                        requested,
+                        clamped,
+                    });
+                }
+                clamped
+            })
+            .collect();
+        (clamped, clamped_axes)
+    }
+
+    /// Convert user `coordinates`, one per axis, to normalised coordinates, clamping them to the
+    /// range of each axis first as for `clamp_coordinates`.
+    ///
+    /// The normalised coordinates are suitable for applying the deltas of `gvar` and other
+    /// variation tables once mapped through `AvarTable::map_coordinates`, if the font has an
+    /// `avar` table. The axes whose coordinates were clamped are returned alongside the
+    /// normalised coordinates.
+    pub fn normalize(&self, coordinates: &[f32]) -> (Vec<F2Dot14>, Vec<ClampedAxis>) {
+        let (coordinates, clamped_axes) = self.clamp_coordinates(coordinates);
+        let normalized = self
+            .axes
+            .iter()
+            .zip(coordinates)
+            .map(|(axis, value)| F2Dot14::from_f32(axis.normalize(value)))
+            .collect();
+        (normalized, clamped_axes)
+    }
+
+    /// Returns the location of an instance with `coordinates`, one per axis as in
+    /// `InstanceRecord`, as `(axis_tag, value)` pairs.
+    pub fn location(&self, coordinates: &[f32]) -> Vec<(u32, f32)> {
+        self.axes
+            .iter()
+            .zip(coordinates)
+            .map(|(axis, &value)| (axis.axis_tag, value))
+            .collect()
+    }
+}
+
+/// Read a 16.16 `Fixed` value.
+pub(crate) fn read_fixed(ctxt: &mut ReadCtxt<'_>) -> Result<f32, ParseError> {
+    Ok(ctxt.read_i32be()? as f32 / 65536.)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::read::ReadScope;
+    use crate::tag;
+
+    fn fvar_data(instance_size: u16, post_script_name_id: bool) -> Vec<u8> {
+        let mut data = vec![
+            0, 1, 0, 0, // version 1.0
+            0, 16, // axesArrayOffset
+            0, 2, // reserved
+            0, 1, // axisCount
+            0, 20, // axisSize
+            0, 1, // instanceCount
+        ];
+        data.extend_from_slice(&instance_size.to_be_bytes());
+        data.extend_from_slice(b"wght");
+        data.extend_from_slice(&[0, 100, 0, 0]); // minValue
+        data.extend_from_slice(&[1, 144, 0, 0]); // defaultValue
+        data.extend_from_slice(&[3, 132, 0, 0]); // maxValue
+        data.extend_from_slice(&[0, 0, 1, 0]); // flags, axisNameID
+        data.extend_from_slice(&[1, 1, 0, 0]); // subfamilyNameID, flags
+        data.extend_from_slice(&[2, 188, 128, 0]); // coordinates
+        if post_script_name_id {
+            data.extend_from_slice(&[1, 2]);
+        }
+        data
+    }
+
+    #[test]
+    fn test_read_fvar() {
+        let data = fvar_data(10, true);
+        let fvar = ReadScope::new(&data).read::<FvarTable>().unwrap();
+        assert_eq!(fvar.axis_count(), 1);
+        assert_eq!(
+            fvar.axes[0],
+            VariationAxisRecord {
+                axis_tag: tag!(b"wght"),
+                min_value: 100.,
+                default_value: 400.,
+                max_value: 900.,
+                flags: 0,
+                axis_name_id: 256,
+            }
+        );
+        assert_eq!(
+            fvar.instances,
+            vec![InstanceRecord {
+                subfamily_name_id: 257,
+                flags: 0,
+                coordinates: vec![700.5],
+                post_script_name_id: Some(258),
+            }]
+        );
+        assert!(fvar.instances[0].instance_flags().is_empty());
+
+        let data = fvar_data(8, false);
+        let fvar = ReadScope::new(&data).read::<FvarTable>().unwrap();
+        assert_eq!(fvar.instances[0].post_script_name_id, None);
+    }
+
+    #[test]
+    fn test_hidden_axes() {
+        let mut data = fvar_data(10, true);
+        let fvar = ReadScope::new(&data).read::<FvarTable>().unwrap();
+        assert!(!fvar.axes[0].is_hidden());
+        assert_eq!(fvar.visible_axes().count(), 1);
+        assert_eq!(fvar.location(&[700.]), vec![(tag!(b"wght"), 700.)]);
+
+        // Set HIDDEN_AXIS and a reserved bit in the flags of the axis
+        data[33] = 0x81;
+        let fvar = ReadScope::new(&data).read::<FvarTable>().unwrap();
+        assert_eq!(fvar.axes[0].axis_flags(), AxisFlags::HIDDEN_AXIS);
+        assert!(fvar.axes[0].is_hidden());
+        assert_eq!(fvar.visible_axes().count(), 0);
+    }
+
+    #[test]
+    fn test_normalize() {
+        let data = fvar_data(10, true);
+        let fvar = ReadScope::new(&data).read::<FvarTable>().unwrap();
+        let axis = &fvar.axes[0];
+        assert_eq!(axis.normalize(400.), 0.);
+        assert_eq!(axis.normalize(250.), -0.5);
+        assert_eq!(axis.normalize(900.), 1.);
+        assert_eq!(axis.normalize(2000.), 1.);
+        assert_eq!(axis.normalize(std::f32::NAN), 0.);
+
+        let (coords, clamped) = fvar.normalize(&[650.]);
+        assert_eq!(coords, vec![F2Dot14::new(0x2000)]);
+        assert!(clamped.is_empty());
+        let (coords, clamped) = fvar.normalize(&[1000., 700.]);
+        assert_eq!(coords, vec![F2Dot14::new(0x4000)]);
+        assert_eq!(
+            clamped,
+            vec![ClampedAxis {
+                axis_index: 0,
+                axis_tag: tag!(b"wght"),
+                requested: 1000.,
+                clamped: 900.,
+            }]
+        );
+        let (coords, clamped) = fvar.normalize(&[]);
+        assert_eq!(coords, vec![F2Dot14::new(0)]);
+        assert!(clamped.is_empty());
+        let (coords, clamped) = fvar.clamp_coordinates(&[std::f32::NAN]);
+        assert_eq!(coords, vec![400.]);
+        assert_eq!(clamped.len(), 1);
+    }
+
+    #[test]
+    fn test_normalize_invalid_axis() {
+        let axis = VariationAxisRecord {
+            axis_tag: tag!(b"wdth"),
+            min_value: 100.,
+            default_value: 100.,
+            max_value: 50.,
+            flags: 0,
+            axis_name_id: 256,
+        };
+        assert_eq!(axis.range(), (100., 100.));
+        assert_eq!(axis.normalize(75.), 0.);
+        assert_eq!(axis.normalize(125.), 0.);
+    }
+
+    #[test]
+    fn test_read_fvar_bad_instance_size() {
+        let data = fvar_data(12, true);
+        assert!(ReadScope::new(&data).read::<FvarTable>().is_err());
+    }
+}
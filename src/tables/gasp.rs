@@ -0,0 +1,134 @@
+#![deny(missing_docs)]
+
+//! `gasp` table parsing.
+//!
+//! The `gasp` table describes the grid-fitting and anti-aliasing behaviour the font designer
+//! intended for rendering glyphs at different sizes.
+//!
+//! <https://docs.microsoft.com/en-us/typography/opentype/spec/gasp>
+
+use bitflags::bitflags;
+
+use crate::binary::read::{ReadArray, ReadBinary, ReadCtxt, ReadFrom};
+use crate::binary::U16Be;
+use crate::error::ParseError;
+
+/// The `gasp` grid-fitting and scan-conversion procedure table.
+pub struct GaspTable<'a> {
+    /// The version of the table, 0 or 1.
+    pub version: u16,
+    /// The ranges of the table, sorted by increasing maximum ppem.
+    pub ranges: ReadArray<'a, GaspRange>,
+}
+
+/// The rendering behaviour for a range of sizes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct GaspRange {
+    /// The upper limit of the range, in ppem.
+    pub range_max_ppem: u16,
+    /// The raw flags describing the desired rasterizer behaviour.
+    pub range_gasp_behavior: u16,
+}
+
+bitflags! {
+    /// Flags describing the desired rasterizer behaviour for a range of sizes.
+    pub struct GaspBehavior: u16 {
+        /// Use grid-fitting.
+        const GRIDFIT = 1 << 0;
+        /// Use grayscale rendering.
+        const DOGRAY = 1 << 1;
+        /// Use grid-fitting with ClearType symmetric smoothing. Only in version 1 tables.
+        const SYMMETRIC_GRIDFIT = 1 << 2;
+        /// Use smoothing along multiple axes with ClearType. Only in version 1 tables.
+        const SYMMETRIC_SMOOTHING = 1 << 3;
+    }
+}
+
+impl<'a> ReadBinary<'a> for GaspTable<'a> {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let version = ctxt.read_u16be()?;
+        ctxt.check_version(version <= 1)?;
+        let num_ranges = ctxt.read_u16be()?;
+        let ranges = ctxt.read_array::<GaspRange>(usize::from(num_ranges))?;
+
+        Ok(GaspTable { version, ranges })
+    }
+}
+
+impl<'a> ReadFrom<'a> for GaspRange {
+    type ReadType = (U16Be, U16Be);
+
+    fn from((range_max_ppem, range_gasp_behavior): (u16, u16)) -> Self {
+        GaspRange {
+            range_max_ppem,
+            range_gasp_behavior,
+        }
+    }
+}
+
+impl<'a> GaspTable<'a> {
+    /// Returns the rasterizer behaviour for glyphs rendered at `ppem`.
+    ///
+    /// Returns `None` if `ppem` is larger than the last range of the table. Flags that are not
+    /// defined for the version of the table are ignored.
+    pub fn gasp_behavior(&self, ppem: u16) -> Option<GaspBehavior> {
+        let range = self
+            .ranges
+            .iter()
+            .find(|range| ppem <= range.range_max_ppem)?;
+        let behavior = GaspBehavior::from_bits_truncate(range.range_gasp_behavior);
+        if self.version == 0 {
+            Some(behavior & (GaspBehavior::GRIDFIT | GaspBehavior::DOGRAY))
+        } else {
+            Some(behavior)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::read::ReadScope;
+    use crate::binary::write::{WriteBinary, WriteBuffer};
+    use crate::font_data::FontData;
+    use crate::tables::FontTableProvider;
+    use crate::tag;
+    use crate::tests::read_fixture;
+
+    #[test]
+    fn test_gasp_behavior() {
+        let buffer = read_fixture("tests/fonts/telugu/lohit_te.ttf");
+        let scope = ReadScope::new(&buffer);
+        let font_file = scope.read::<FontData<'_>>().unwrap();
+        let provider = font_file.table_provider(0).unwrap();
+        let gasp_data = provider.read_table_data(tag::GASP).unwrap();
+        let gasp = ReadScope::new(&gasp_data).read::<GaspTable<'_>>().unwrap();
+
+        assert_eq!(gasp.version, 0);
+        assert_eq!(gasp.ranges.len(), 1);
+        assert_eq!(gasp.gasp_behavior(12), Some(GaspBehavior::DOGRAY));
+        assert_eq!(gasp.gasp_behavior(0xFFFF), Some(GaspBehavior::DOGRAY));
+    }
+
+    #[test]
+    fn test_gasp_ranges() {
+        let mut w = WriteBuffer::new();
+        for &value in &[1u16, 3, 8, 0x0A, 16, 0x05, 20, 0x0F] {
+            U16Be::write(&mut w, value).unwrap();
+        }
+        let gasp = ReadScope::new(w.bytes()).read::<GaspTable<'_>>().unwrap();
+
+        assert_eq!(
+            gasp.gasp_behavior(8),
+            Some(GaspBehavior::DOGRAY | GaspBehavior::SYMMETRIC_SMOOTHING)
+        );
+        assert_eq!(
+            gasp.gasp_behavior(9),
+            Some(GaspBehavior::GRIDFIT | GaspBehavior::SYMMETRIC_GRIDFIT)
+        );
+        assert_eq!(gasp.gasp_behavior(20), Some(GaspBehavior::all()));
+        assert_eq!(gasp.gasp_behavior(21), None);
+    }
+}
@@ -5,8 +5,12 @@
 //!
 //! — <https://docs.microsoft.com/en-us/typography/opentype/spec/glyf>
 
+use std::borrow::Cow;
 use std::convert::TryFrom;
 use std::iter;
+#[cfg(not(feature = "variations"))]
+use std::marker::PhantomData;
+use std::ops::Range;
 
 use bitflags::bitflags;
 use itertools::Itertools;
@@ -14,10 +18,13 @@ use log::warn;
 
 use crate::binary::read::{ReadBinary, ReadBinaryDep, ReadCtxt, ReadFrom, ReadScope};
 use crate::binary::write::{WriteBinary, WriteBinaryDep, WriteContext};
-use crate::binary::{word_align, I16Be, U16Be, I8, U8};
-use crate::error::{ParseError, WriteError};
-use crate::tables::loca::{owned, LocaTable};
-use crate::tables::{F2Dot14, IndexToLocFormat};
+use crate::binary::{I16Be, U16Be, I8, U8};
+use crate::error::{ParseError, ParseErrorKind, ReadWriteError, WriteError};
+use crate::recompute::recompute_maxp;
+#[cfg(feature = "variations")]
+use crate::tables::gvar::GvarTable;
+use crate::tables::loca::{owned, GlyphPadding, LocaTable};
+use crate::tables::{F2Dot14, HeadTable, IndexToLocFormat, MaxpTable};
 
 bitflags! {
     #[rustfmt::skip]
@@ -28,6 +35,7 @@ bitflags! {
         const REPEAT_FLAG                          = 0b00001000;
         const X_IS_SAME_OR_POSITIVE_X_SHORT_VECTOR = 0b00010000;
         const Y_IS_SAME_OR_POSITIVE_Y_SHORT_VECTOR = 0b00100000;
+        const OVERLAP_SIMPLE                       = 0b01000000;
     }
 }
 
@@ -98,7 +106,7 @@ pub enum GlyphData<'a> {
     Simple(SimpleGlyph),
     Composite {
         glyphs: Vec<CompositeGlyph>,
-        instructions: &'a [u8],
+        instructions: Cow<'a, [u8]>,
     },
 }
 
@@ -142,6 +150,22 @@ pub struct CompositeGlyphs {
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Point(pub i16, pub i16);
 
+/// The points of a glyph outline in TrueType form.
+///
+/// Unlike a segment based outline this retains the on-curve and off-curve (quadratic control)
+/// points exactly as they are numbered in the font, which is what hinting and point-compatible
+/// interpolation operate on. The points of composite glyphs are the points of their components,
+/// in component order, with each component's transform applied.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct GlyphPoints {
+    /// The points of all contours in font units.
+    pub points: Vec<Point>,
+    /// `true` for each point that is on the curve, `false` for off-curve control points.
+    pub on_curve: Vec<bool>,
+    /// The index of the last point of each contour.
+    pub end_pts_of_contours: Vec<u16>,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct BoundingBox {
     pub x_min: i16,
@@ -170,7 +194,7 @@ impl<'a> ReadBinaryDep<'a> for GlyfTable<'a> {
                     let glyph_scope = ctxt.scope().offset_length(offset, usize::try_from(length)?);
                     match glyph_scope {
                         Ok(scope) => Ok(GlyfRecord::Present(scope)),
-                        Err(ParseError::BadEof) => {
+                        Err(err) if err.kind() == ParseErrorKind::BadEof => {
                             // The length specified by `loca` is beyond the end of the `glyf`
                             // table. Try parsing the glyph without a length limit to see if it's
                             // valid. This is a workaround for a font where the last `loca` offset
@@ -233,12 +257,27 @@ impl<'a> WriteBinaryDep<Self> for GlyfTable<'a> {
     /// > fonttools for years and never ever heard a complaint whatsoever.
     ///
     /// So with this in mind we implement 16-bit alignment when `index_to_loc_format` is 0,
-    /// and no alignment/padding otherwise.
+    /// and no alignment/padding otherwise. Use `GlyfTable::write_with_padding` to select the
+    /// padding explicitly.
     fn write_dep<C: WriteContext>(
         ctxt: &mut C,
         table: GlyfTable<'a>,
         index_to_loc_format: IndexToLocFormat,
     ) -> Result<Self::Output, WriteError> {
+        GlyfTable::write_with_padding(ctxt, table, GlyphPadding::for_format(index_to_loc_format))
+    }
+}
+
+impl<'a> GlyfTable<'a> {
+    /// Write this glyf table into `ctxt`, padding each glyph according to `padding`.
+    ///
+    /// Returns the `loca` table describing the written glyphs. Note that glyphs written without
+    /// padding can only be indexed by a long format `loca` table.
+    pub fn write_with_padding<C: WriteContext>(
+        ctxt: &mut C,
+        table: GlyfTable<'a>,
+        padding: GlyphPadding,
+    ) -> Result<owned::LocaTable, WriteError> {
         let mut offsets: Vec<u32> = Vec::with_capacity(table.records.len() + 1);
 
         let start = ctxt.bytes_written();
@@ -253,11 +292,8 @@ impl<'a> WriteBinaryDep<Self> for GlyfTable<'a> {
                 GlyfRecord::Parsed(glyph) => Glyph::write(ctxt, glyph)?,
             }
 
-            if index_to_loc_format == IndexToLocFormat::Short {
-                let length = ctxt.bytes_written() - offset;
-                let padded_length = word_align(length);
-                ctxt.write_zeros(padded_length - length)?;
-            }
+            let length = ctxt.bytes_written() - offset;
+            ctxt.write_zeros(padding.padded_length(length) - length)?;
         }
 
         // Add the final loca entry
@@ -265,6 +301,25 @@ impl<'a> WriteBinaryDep<Self> for GlyfTable<'a> {
 
         Ok(owned::LocaTable { offsets })
     }
+
+    /// Write this glyf table into `ctxt` with each glyph padded to 4-byte alignment, updating
+    /// `head` and `maxp` to describe the written glyphs.
+    ///
+    /// `head.index_to_loc_format` is set to the short format when the glyph data is small enough
+    /// for it and the long format otherwise, and the values of `maxp` that are derived from the
+    /// glyphs are recalculated with `recompute_maxp`. Returns the `loca` table describing the
+    /// written glyphs, which should be written with the updated `head.index_to_loc_format`.
+    pub fn write_packed<C: WriteContext>(
+        ctxt: &mut C,
+        table: GlyfTable<'a>,
+        head: &mut HeadTable,
+        maxp: &mut MaxpTable,
+    ) -> Result<owned::LocaTable, ReadWriteError> {
+        recompute_maxp(&table, maxp)?;
+        let loca = GlyfTable::write_with_padding(ctxt, table, GlyphPadding::FourByte)?;
+        head.index_to_loc_format = loca.smallest_format();
+        Ok(loca)
+    }
 }
 
 impl<'a> ReadBinary<'a> for Glyph<'a> {
@@ -300,7 +355,7 @@ impl<'a> ReadBinary<'a> for Glyph<'a> {
                 bounding_box,
                 data: GlyphData::Composite {
                     glyphs: glyphs.glyphs,
-                    instructions,
+                    instructions: Cow::Borrowed(instructions),
                 },
             })
         }
@@ -326,7 +381,7 @@ impl<'a> WriteBinary for Glyph<'a> {
                 }
                 if has_instructions {
                     U16Be::write(ctxt, u16::try_from(instructions.len())?)?;
-                    ctxt.write_bytes(instructions)?;
+                    ctxt.write_bytes(&instructions)?;
                 }
             }
         }
@@ -402,15 +457,18 @@ impl<'a> ReadBinaryDep<'a> for SimpleGlyph {
 
         // The x and y coordinates are stored as deltas against the previous point, with the first
         // one being implicitly against (0, 0). Here we resolve these deltas into absolute (x, y)
-        // values and combine them into Points.
+        // values and combine them into Points. Coordinates that overflow `i16` are rejected.
+        let mut prev_point = Point(0, 0);
         let coordinates = x_coordinates
             .into_iter()
             .zip(y_coordinates.into_iter())
-            .scan(Point(0, 0), |prev_point, (x, y)| {
-                *prev_point = Point(prev_point.0 + x, prev_point.1 + y);
-                Some(*prev_point)
+            .map(|(x, y)| {
+                let x = prev_point.0.checked_add(x).ok_or(ParseError::BadValue)?;
+                let y = prev_point.1.checked_add(y).ok_or(ParseError::BadValue)?;
+                prev_point = Point(x, y);
+                Ok(prev_point)
             })
-            .collect();
+            .collect::<Result<_, ParseError>>()?;
 
         Ok(SimpleGlyph {
             end_pts_of_contours,
@@ -436,23 +494,29 @@ impl<'a> WriteBinary for SimpleGlyph {
         // TODO: try to compact the values written
 
         // flags
-        let mask = SimpleGlyphFlag::ON_CURVE_POINT; // ON_CURVE_POINT is the only flag that needs to carry through
-        for flag in glyph.flags {
+        // ON_CURVE_POINT is the only flag that needs to carry through, apart from OVERLAP_SIMPLE,
+        // which is only meaningful on the first flag.
+        for (i, flag) in glyph.flags.into_iter().enumerate() {
+            let mask = if i == 0 {
+                SimpleGlyphFlag::ON_CURVE_POINT | SimpleGlyphFlag::OVERLAP_SIMPLE
+            } else {
+                SimpleGlyphFlag::ON_CURVE_POINT
+            };
             U8::write(ctxt, (flag & mask).bits())?;
         }
 
         // x coordinates
-        let mut prev_x = 0;
+        let mut prev_x = 0i16;
         for Point(x, _) in &glyph.coordinates {
-            let delta_x = x - prev_x;
+            let delta_x = x.checked_sub(prev_x).ok_or(WriteError::BadValue)?;
             I16Be::write(ctxt, delta_x)?;
             prev_x = *x;
         }
 
         // y coordinates
-        let mut prev_y = 0;
+        let mut prev_y = 0i16;
         for Point(_, y) in &glyph.coordinates {
-            let delta_y = y - prev_y;
+            let delta_y = y.checked_sub(prev_y).ok_or(WriteError::BadValue)?;
             I16Be::write(ctxt, delta_y)?;
             prev_y = *y;
         }
@@ -680,6 +744,9 @@ impl<'a> WriteBinary for BoundingBox {
     }
 }
 
+/// Maximum depth of nested composite glyphs followed by `GlyfTable::glyph_points`.
+pub(crate) const COMPOSITE_GLYPH_RECURSION_LIMIT: usize = 8;
+
 struct SubsetGlyph<'a> {
     old_id: u16,
     record: GlyfRecord<'a>,
@@ -744,6 +811,457 @@ impl<'a> GlyfTable<'a> {
 
         Ok((GlyfTable { records }, new_to_old_id))
     }
+
+    /// Returns a copy of this table that only contains the glyphs specified by `glyph_ids`,
+    /// keeping their ids.
+    ///
+    /// Glyphs that are not retained are replaced with empty glyphs, up to the highest retained
+    /// glyph id. The components of retained composite glyphs are also retained. Returns the
+    /// table along with the old id of each glyph, which is the same as its new id.
+    pub fn subset_retaining_ids(
+        &self,
+        glyph_ids: &[u16],
+    ) -> Result<(GlyfTable<'a>, Vec<u16>), ParseError> {
+        let mut retained = Vec::new();
+        let mut pending = glyph_ids.to_vec();
+        while let Some(glyph_id) = pending.pop() {
+            let index = usize::from(glyph_id);
+            if index >= retained.len() {
+                retained.resize(index + 1, false);
+            }
+            if retained[index] {
+                continue;
+            }
+            let record = self.records.get(index).ok_or(ParseError::BadIndex)?;
+            if record.is_composite()? {
+                let mut record = record.clone();
+                record.parse()?;
+                if let GlyfRecord::Parsed(Glyph {
+                    data: GlyphData::Composite { glyphs, .. },
+                    ..
+                }) = &record
+                {
+                    pending.extend(glyphs.iter().map(|glyph| glyph.glyph_index));
+                }
+            }
+            retained[index] = true;
+        }
+
+        let records = retained
+            .iter()
+            .zip(self.records.iter())
+            .map(|(&retain, record)| {
+                if retain {
+                    record.clone()
+                } else {
+                    GlyfRecord::Empty
+                }
+            })
+            .collect::<Vec<_>>();
+        let new_to_old_id = (0..records.len())
+            .map(u16::try_from)
+            .collect::<Result<_, _>>()?;
+
+        Ok((GlyfTable { records }, new_to_old_id))
+    }
+}
+
+impl<'a> GlyfTable<'a> {
+    /// Returns the points of the outline of `glyph_index` along with the contour end indices.
+    ///
+    /// Composite glyphs are resolved, so the result is the same as if the glyph had been a simple
+    /// glyph. Empty glyphs produce an empty set of points.
+    /// Returns the glyph at `glyph_index`, parsing it if necessary.
+    ///
+    /// Returns `None` if the glyph is empty.
+    pub fn glyph(&self, glyph_index: u16) -> Result<Option<Cow<'_, Glyph<'a>>>, ParseError> {
+        match self.records.get(usize::from(glyph_index)) {
+            Some(GlyfRecord::Empty) => Ok(None),
+            Some(GlyfRecord::Present(scope)) => Ok(Some(Cow::Owned(scope.read::<Glyph<'a>>()?))),
+            Some(GlyfRecord::Parsed(glyph)) => Ok(Some(Cow::Borrowed(glyph))),
+            None => Err(ParseError::BadIndex),
+        }
+    }
+
+    pub fn glyph_points(&self, glyph_index: u16) -> Result<GlyphPoints, ParseError> {
+        let mut glyph_points = GlyphPoints::default();
+        self.append_glyph_points(glyph_index, None, 0, &mut glyph_points)?;
+        Ok(glyph_points)
+    }
+
+    /// Returns the points of the outline of `glyph_index` at the normalised variation
+    /// coordinates `coords`, with the deltas from `gvar` applied.
+    ///
+    /// The deltas of composite glyphs move the offsets of their components. Components that are
+    /// positioned by matching points are moved by the deltas of those points instead.
+    #[cfg(feature = "variations")]
+    pub fn glyph_points_at(
+        &self,
+        glyph_index: u16,
+        gvar: &GvarTable<'_>,
+        coords: &[F2Dot14],
+    ) -> Result<GlyphPoints, ParseError> {
+        let mut glyph_points = GlyphPoints::default();
+        self.append_glyph_points(glyph_index, Some((gvar, coords)), 0, &mut glyph_points)?;
+        Ok(glyph_points)
+    }
+
+    fn append_glyph_points(
+        &self,
+        glyph_index: u16,
+        opt_variations: Option<Variations<'_, '_>>,
+        depth: usize,
+        glyph_points: &mut GlyphPoints,
+    ) -> Result<(), ParseError> {
+        if depth > COMPOSITE_GLYPH_RECURSION_LIMIT {
+            return Err(ParseError::LimitExceeded);
+        }
+
+        let glyph = match self.glyph(glyph_index)? {
+            Some(glyph) => glyph,
+            None => return Ok(()),
+        };
+
+        match &glyph.data {
+            GlyphData::Simple(simple_glyph) => {
+                let offset = glyph_points.points.len();
+                let coordinates = match variation_deltas(
+                    opt_variations,
+                    glyph_index,
+                    &simple_glyph.coordinates,
+                    &simple_glyph.end_pts_of_contours,
+                )? {
+                    Some(deltas) => Cow::Owned(apply_deltas(&simple_glyph.coordinates, &deltas)),
+                    None => Cow::Borrowed(&simple_glyph.coordinates),
+                };
+                glyph_points.append_contours(
+                    &coordinates,
+                    simple_glyph.flags.iter().map(|flag| flag.is_on_curve()),
+                    simple_glyph
+                        .end_pts_of_contours
+                        .iter()
+                        .map(|&end| offset + usize::from(end)),
+                )?;
+            }
+            GlyphData::Composite { glyphs, .. } => {
+                let deltas = if opt_variations.is_some() {
+                    let offsets = glyphs
+                        .iter()
+                        .map(|composite_glyph| {
+                            Point(
+                                composite_glyph.argument1.value() as i16,
+                                composite_glyph.argument2.value() as i16,
+                            )
+                        })
+                        .collect::<Vec<_>>();
+                    variation_deltas(opt_variations, glyph_index, &offsets, &[])?
+                        .unwrap_or_default()
+                } else {
+                    Vec::new()
+                };
+                for (i, composite_glyph) in glyphs.iter().enumerate() {
+                    let mut component = GlyphPoints::default();
+                    self.append_glyph_points(
+                        composite_glyph.glyph_index,
+                        opt_variations,
+                        depth + 1,
+                        &mut component,
+                    )?;
+                    match deltas.get(i) {
+                        Some(&(dx, dy)) if composite_glyph.flags.args_are_xy_values() => {
+                            let mut composite_glyph = composite_glyph.clone();
+                            composite_glyph.argument1 = CompositeGlyphArgument::I16(
+                                (composite_glyph.argument1.value() as f32 + dx).round() as i16,
+                            );
+                            composite_glyph.argument2 = CompositeGlyphArgument::I16(
+                                (composite_glyph.argument2.value() as f32 + dy).round() as i16,
+                            );
+                            composite_glyph
+                                .transform(&glyph_points.points, &mut component.points)?;
+                        }
+                        _ => composite_glyph
+                            .transform(&glyph_points.points, &mut component.points)?,
+                    }
+
+                    let offset = glyph_points.points.len();
+                    glyph_points.append_contours(
+                        &component.points,
+                        component.on_curve.iter().copied(),
+                        component
+                            .end_pts_of_contours
+                            .iter()
+                            .map(|&end| offset + usize::from(end)),
+                    )?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl GlyphPoints {
+    /// Returns the x coordinates, in ascending order, where the outline crosses the horizontal
+    /// line at `y`.
+    ///
+    /// Curves are approximated by line segments.
+    pub fn horizontal_crossings(&self, y: f32) -> Vec<f32> {
+        let mut crossings = Vec::new();
+        for polygon in self.polygons() {
+            for edge in polygon.windows(2) {
+                let ((x0, y0), (x1, y1)) = (edge[0], edge[1]);
+                if (y0 <= y && y < y1) || (y1 <= y && y < y0) {
+                    crossings.push(x0 + (y - y0) * (x1 - x0) / (y1 - y0));
+                }
+            }
+        }
+        crossings.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        crossings
+    }
+
+    /// Approximate each contour of the outline with a closed polygon.
+    ///
+    /// Curves are approximated by line segments. The first and last points of each polygon are
+    /// the same.
+    pub fn polygons(&self) -> Vec<Vec<(f32, f32)>> {
+        let mut polygons = Vec::with_capacity(self.end_pts_of_contours.len());
+        let mut start = 0;
+        for &end in &self.end_pts_of_contours {
+            let end = usize::from(end) + 1;
+            if end <= start || end > self.points.len() {
+                break;
+            }
+            polygons.push(flatten_contour(
+                &self.points[start..end],
+                &self.on_curve[start..end],
+            ));
+            start = end;
+        }
+        polygons
+    }
+
+    fn append_contours(
+        &mut self,
+        points: &[Point],
+        on_curve: impl Iterator<Item = bool>,
+        end_pts_of_contours: impl Iterator<Item = usize>,
+    ) -> Result<(), ParseError> {
+        self.points.extend_from_slice(points);
+        self.on_curve.extend(on_curve);
+        for end in end_pts_of_contours {
+            self.end_pts_of_contours.push(u16::try_from(end)?);
+        }
+        Ok(())
+    }
+}
+
+/// Move `points` by `deltas`, rounding to the nearest font unit.
+/// The `gvar` table and normalised coordinates used to vary an outline.
+#[cfg(feature = "variations")]
+type Variations<'b, 'a> = (&'b GvarTable<'a>, &'b [F2Dot14]);
+
+/// Outlines can't be varied without the `variations` feature, so this can't be constructed.
+#[cfg(not(feature = "variations"))]
+type Variations<'b, 'a> = (std::convert::Infallible, PhantomData<(&'b (), &'a ())>);
+
+#[cfg(feature = "variations")]
+fn variation_deltas(
+    opt_variations: Option<Variations<'_, '_>>,
+    glyph_index: u16,
+    points: &[Point],
+    end_pts_of_contours: &[u16],
+) -> Result<Option<Vec<(f32, f32)>>, ParseError> {
+    match opt_variations {
+        Some((gvar, coords)) => gvar
+            .glyph_deltas(glyph_index, coords, points, end_pts_of_contours)
+            .map(Some),
+        None => Ok(None),
+    }
+}
+
+#[cfg(not(feature = "variations"))]
+fn variation_deltas(
+    opt_variations: Option<Variations<'_, '_>>,
+    _glyph_index: u16,
+    _points: &[Point],
+    _end_pts_of_contours: &[u16],
+) -> Result<Option<Vec<(f32, f32)>>, ParseError> {
+    match opt_variations {
+        Some((never, _)) => match never {},
+        None => Ok(None),
+    }
+}
+
+fn apply_deltas(points: &[Point], deltas: &[(f32, f32)]) -> Vec<Point> {
+    points
+        .iter()
+        .zip(deltas)
+        .map(|(point, &(dx, dy))| {
+            Point(
+                (f32::from(point.0) + dx).round() as i16,
+                (f32::from(point.1) + dy).round() as i16,
+            )
+        })
+        .collect()
+}
+
+/// Approximate a TrueType contour with a closed polygon.
+///
+/// Each quadratic segment is divided into four line segments. The first and last points of the
+/// returned polygon are the same.
+fn flatten_contour(points: &[Point], on_curve: &[bool]) -> Vec<(f32, f32)> {
+    const CURVE_STEPS: usize = 4;
+
+    let n = points.len();
+    if n == 0 {
+        return Vec::new();
+    }
+    let point = |i: usize| (f32::from(points[i].0), f32::from(points[i].1));
+    let mid = |(x0, y0): (f32, f32), (x1, y1): (f32, f32)| ((x0 + x1) / 2., (y0 + y1) / 2.);
+    let quad = |polygon: &mut Vec<(f32, f32)>, control: (f32, f32), to: (f32, f32)| {
+        let from = polygon[polygon.len() - 1];
+        for step in 1..=CURVE_STEPS {
+            let t = step as f32 / CURVE_STEPS as f32;
+            let (a, b, c) = ((1. - t) * (1. - t), 2. * (1. - t) * t, t * t);
+            polygon.push((
+                a * from.0 + b * control.0 + c * to.0,
+                a * from.1 + b * control.1 + c * to.1,
+            ));
+        }
+    };
+
+    // Start at an on-curve point, or the implied point between the last and first points if there
+    // are none.
+    let (start, order): (_, Vec<usize>) = match on_curve.iter().position(|&on| on) {
+        Some(first) => (point(first), (1..=n).map(|i| (first + i) % n).collect()),
+        None => (mid(point(n - 1), point(0)), (0..n).collect()),
+    };
+    let mut polygon = vec![start];
+    let mut control = None;
+    for i in order {
+        if on_curve[i] {
+            match control.take() {
+                Some(control) => quad(&mut polygon, control, point(i)),
+                None => polygon.push(point(i)),
+            }
+        } else {
+            if let Some(control) = control {
+                quad(&mut polygon, control, mid(control, point(i)));
+            }
+            control = Some(point(i));
+        }
+    }
+    if let Some(control) = control {
+        quad(&mut polygon, control, start);
+    }
+
+    polygon
+}
+
+/// Estimate the dominant vertical stem width of a font from a sample of its glyph outlines.
+///
+/// Glyphs with straight vertical stems such as `l`, `I`, and `H` make good samples. The width of
+/// each stem crossed by horizontal lines at one and three quarters of each glyph's height is
+/// measured, and the median is returned. This is suitable for populating the `StemV` entry of a
+/// PDF font descriptor or the `StdVW` CFF hint when the font does not supply one.
+///
+/// Returns `None` if no stems were found.
+pub fn estimate_vertical_stem_width(outlines: &[GlyphPoints]) -> Option<u16> {
+    let mut widths = Vec::new();
+    for outline in outlines {
+        if outline.points.is_empty() {
+            continue;
+        }
+        let bounding_box = BoundingBox::from_points(&outline.points);
+        let y_min = f32::from(bounding_box.y_min);
+        let height = f32::from(bounding_box.y_max) - y_min;
+        for &fraction in &[0.25, 0.75] {
+            let crossings = outline.horizontal_crossings(y_min + height * fraction);
+            widths.extend(
+                crossings
+                    .chunks_exact(2)
+                    .map(|pair| (pair[1] - pair[0]).round() as u16)
+                    .filter(|&width| width > 0),
+            );
+        }
+    }
+
+    widths.sort_unstable();
+    widths.get(widths.len().saturating_sub(1) / 2).copied()
+}
+
+impl CompositeGlyph {
+    /// Apply the scale and offset of this component to `points`.
+    ///
+    /// `parent_points` are the points of the composite glyph accumulated so far, which are
+    /// required when the component is positioned by matching points rather than by offset.
+    fn transform(&self, parent_points: &[Point], points: &mut [Point]) -> Result<(), ParseError> {
+        let (a, b, c, d) = match &self.scale {
+            None => (1.0, 0.0, 0.0, 1.0),
+            Some(CompositeGlyphScale::Scale(scale)) => {
+                let scale = f32::from(*scale);
+                (scale, 0.0, 0.0, scale)
+            }
+            Some(CompositeGlyphScale::XY { x_scale, y_scale }) => {
+                (f32::from(*x_scale), 0.0, 0.0, f32::from(*y_scale))
+            }
+            Some(CompositeGlyphScale::Matrix(matrix)) => (
+                f32::from(matrix[0][0]),
+                f32::from(matrix[0][1]),
+                f32::from(matrix[1][0]),
+                f32::from(matrix[1][1]),
+            ),
+        };
+        let apply = |x: f32, y: f32| (a * x + c * y, b * x + d * y);
+
+        for point in points.iter_mut() {
+            let (x, y) = apply(f32::from(point.0), f32::from(point.1));
+            *point = Point(x.round() as i16, y.round() as i16);
+        }
+
+        let (dx, dy) = if self.flags.args_are_xy_values() {
+            let dx = self.argument1.value() as f32;
+            let dy = self.argument2.value() as f32;
+            let (dx, dy) = if self.flags.scaled_component_offset()
+                && !self.flags.unscaled_component_offset()
+            {
+                apply(dx, dy)
+            } else {
+                (dx, dy)
+            };
+            (dx.round() as i16, dy.round() as i16)
+        } else {
+            // The arguments are point numbers. The component is positioned so that its point
+            // argument2 lies on the point argument1 of the glyph so far.
+            let parent_index = usize::try_from(self.argument1.value())?;
+            let child_index = usize::try_from(self.argument2.value())?;
+            let parent = parent_points
+                .get(parent_index)
+                .ok_or(ParseError::BadIndex)?;
+            let child = points.get(child_index).ok_or(ParseError::BadIndex)?;
+            (
+                parent.0.wrapping_sub(child.0),
+                parent.1.wrapping_sub(child.1),
+            )
+        };
+
+        for point in points.iter_mut() {
+            *point = Point(point.0.wrapping_add(dx), point.1.wrapping_add(dy));
+        }
+
+        Ok(())
+    }
+}
+
+impl CompositeGlyphArgument {
+    pub(crate) fn value(&self) -> i32 {
+        match *self {
+            CompositeGlyphArgument::U8(value) => i32::from(value),
+            CompositeGlyphArgument::I8(value) => i32::from(value),
+            CompositeGlyphArgument::U16(value) => i32::from(value),
+            CompositeGlyphArgument::I16(value) => i32::from(value),
+        }
+    }
 }
 
 impl<'a> GlyfRecord<'a> {
@@ -766,6 +1284,65 @@ impl<'a> GlyfRecord<'a> {
         }
         Ok(())
     }
+
+    /// Parse this record if necessary and return the glyph for editing, or `None` if it's empty.
+    pub fn glyph_mut(&mut self) -> Result<Option<&mut Glyph<'a>>, ParseError> {
+        self.parse()?;
+        match self {
+            GlyfRecord::Parsed(glyph) => Ok(Some(glyph)),
+            GlyfRecord::Empty | GlyfRecord::Present(_) => Ok(None),
+        }
+    }
+}
+
+impl<'a> Glyph<'a> {
+    /// Create a glyph from the outline `glyph`, calculating its number of contours and bounding
+    /// box.
+    pub fn from_simple_glyph(glyph: SimpleGlyph) -> Result<Glyph<'static>, ParseError> {
+        let mut glyph = Glyph {
+            number_of_contours: 0,
+            bounding_box: BoundingBox::empty(),
+            data: GlyphData::Simple(glyph),
+        };
+        glyph.recalculate_bounds()?;
+        Ok(glyph)
+    }
+
+    /// Update the number of contours and bounding box of a simple glyph to match its outline.
+    ///
+    /// This needs to be called after editing the points of a simple glyph, before it's written.
+    /// The bounding box of a composite glyph depends on its components, so it's left unchanged.
+    pub fn recalculate_bounds(&mut self) -> Result<(), ParseError> {
+        if let GlyphData::Simple(simple_glyph) = &self.data {
+            self.number_of_contours = i16::try_from(simple_glyph.end_pts_of_contours.len())
+                .map_err(|_| ParseError::LimitExceeded)?;
+            self.bounding_box = if simple_glyph.coordinates.is_empty() {
+                BoundingBox::empty()
+            } else {
+                simple_glyph.bounding_box()
+            };
+        }
+        Ok(())
+    }
+
+    /// Returns a copy of this glyph that doesn't borrow from the font data.
+    pub fn into_owned(self) -> Glyph<'static> {
+        let data = match self.data {
+            GlyphData::Simple(simple_glyph) => GlyphData::Simple(simple_glyph),
+            GlyphData::Composite {
+                glyphs,
+                instructions,
+            } => GlyphData::Composite {
+                glyphs,
+                instructions: Cow::Owned(instructions.into_owned()),
+            },
+        };
+        Glyph {
+            number_of_contours: self.number_of_contours,
+            bounding_box: self.bounding_box,
+            data,
+        }
+    }
 }
 
 impl CompositeGlyphFlag {
@@ -796,6 +1373,14 @@ impl CompositeGlyphFlag {
     pub fn we_have_instructions(self) -> bool {
         self & Self::WE_HAVE_INSTRUCTIONS == Self::WE_HAVE_INSTRUCTIONS
     }
+
+    pub fn scaled_component_offset(self) -> bool {
+        self & Self::SCALED_COMPONENT_OFFSET == Self::SCALED_COMPONENT_OFFSET
+    }
+
+    pub fn unscaled_component_offset(self) -> bool {
+        self & Self::UNSCALED_COMPONENT_OFFSET == Self::UNSCALED_COMPONENT_OFFSET
+    }
 }
 
 impl BoundingBox {
@@ -834,22 +1419,154 @@ impl BoundingBox {
     }
 }
 
+impl BoundingBox {
+    pub(crate) fn empty() -> Self {
+        BoundingBox {
+            x_min: 0,
+            x_max: 0,
+            y_min: 0,
+            y_max: 0,
+        }
+    }
+}
+
 impl SimpleGlyph {
     pub fn bounding_box(&self) -> BoundingBox {
         BoundingBox::from_points(&self.coordinates)
     }
+
+    /// Returns `true` if the glyph is flagged as having overlapping contours.
+    pub fn has_overlap_flag(&self) -> bool {
+        self.flags
+            .first()
+            .map(|flag| flag.contains(SimpleGlyphFlag::OVERLAP_SIMPLE))
+            .unwrap_or(false)
+    }
+
+    /// Set or clear the `OVERLAP_SIMPLE` flag, which tells rasterizers that contours overlap.
+    ///
+    /// The flag is stored with the first point, so it has no effect on a glyph without points.
+    pub fn set_overlap_flag(&mut self, overlap: bool) {
+        if let Some(flag) = self.flags.first_mut() {
+            flag.set(SimpleGlyphFlag::OVERLAP_SIMPLE, overlap);
+        }
+    }
+
+    /// The number of contours in the outline.
+    pub fn number_of_contours(&self) -> usize {
+        self.end_pts_of_contours.len()
+    }
+
+    /// Returns the range of `coordinates` and `flags` holding the points of contour `index`.
+    pub fn contour(&self, index: usize) -> Option<Range<usize>> {
+        let end = usize::from(*self.end_pts_of_contours.get(index)?) + 1;
+        let start = match index.checked_sub(1) {
+            Some(prev) => usize::from(self.end_pts_of_contours[prev]) + 1,
+            None => 0,
+        };
+        Some(start..end)
+    }
+
+    /// Returns an iterator over the ranges of points of each contour, as for `contour`.
+    pub fn contours(&self) -> impl Iterator<Item = Range<usize>> + '_ {
+        (0..self.number_of_contours()).filter_map(move |index| self.contour(index))
+    }
+
+    /// Append a contour made of `points`, each paired with whether it's on the curve.
+    ///
+    /// Empty contours are ignored. Returns an error if the outline would have more points than
+    /// can be numbered.
+    pub fn push_contour<I>(&mut self, points: I) -> Result<(), ParseError>
+    where
+        I: IntoIterator<Item = (Point, bool)>,
+    {
+        let start = self.coordinates.len();
+        for (point, on_curve) in points {
+            let flag = if on_curve {
+                SimpleGlyphFlag::ON_CURVE_POINT
+            } else {
+                SimpleGlyphFlag::empty()
+            };
+            self.coordinates.push(point);
+            self.flags.push(flag);
+        }
+        if self.coordinates.len() == start {
+            return Ok(());
+        }
+        match u16::try_from(self.coordinates.len() - 1) {
+            Ok(end) => {
+                self.end_pts_of_contours.push(end);
+                Ok(())
+            }
+            Err(_) => {
+                self.coordinates.truncate(start);
+                self.flags.truncate(start);
+                Err(ParseError::LimitExceeded)
+            }
+        }
+    }
+
+    /// Remove contour `index` and its points.
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn remove_contour(&mut self, index: usize) {
+        let range = self.contour(index).expect("contour index out of bounds");
+        let len = range.len();
+        self.coordinates.drain(range.clone());
+        self.flags.drain(range);
+        self.end_pts_of_contours.remove(index);
+        // The cast is safe as the points after the removed contour were numbered by a u16
+        for end in &mut self.end_pts_of_contours[index..] {
+            *end -= len as u16;
+        }
+    }
+
+    /// Retain only the contours for which `f` returns `true` when passed their points and flags.
+    pub fn retain_contours<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&[Point], &[SimpleGlyphFlag]) -> bool,
+    {
+        let mut index = 0;
+        while let Some(range) = self.contour(index) {
+            if f(&self.coordinates[range.clone()], &self.flags[range]) {
+                index += 1;
+            } else {
+                self.remove_contour(index);
+            }
+        }
+    }
+
+    /// Replace each point of the outline with the result of calling `f` with it.
+    ///
+    /// This can be used to move, scale, or round the points. Any instructions of the glyph are
+    /// likely to be invalidated by such changes, so they should usually be cleared too.
+    pub fn map_points<F>(&mut self, mut f: F)
+    where
+        F: FnMut(Point) -> Point,
+    {
+        for point in self.coordinates.iter_mut() {
+            *point = f(*point);
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{BoundingBox, GlyfRecord, GlyfTable, IndexToLocFormat, Point};
+    use std::borrow::Cow;
+
+    use super::{
+        estimate_vertical_stem_width, BoundingBox, GlyfRecord, GlyfTable, GlyphPoints,
+        IndexToLocFormat, Point,
+    };
     use crate::binary::read::ReadScope;
     use crate::binary::write::{WriteBinary, WriteBinaryDep, WriteBuffer, WriteContext};
+    use crate::error::ParseError;
     use crate::tables::glyf::{
-        CompositeGlyph, CompositeGlyphArgument, CompositeGlyphFlag, Glyph, GlyphData, SimpleGlyph,
-        SimpleGlyphFlag,
+        CompositeGlyph, CompositeGlyphArgument, CompositeGlyphFlag, CompositeGlyphScale, Glyph,
+        GlyphData, SimpleGlyph, SimpleGlyphFlag,
     };
-    use crate::tables::loca::{owned, LocaTable};
+    use crate::tables::loca::{owned, GlyphPadding, LocaTable};
+    use crate::tables::{F2Dot14, HeadTable, MaxpTable};
 
     fn simple_glyph_fixture() -> Glyph<'static> {
         let simple_glyph = SimpleGlyph {
@@ -962,7 +1679,7 @@ mod tests {
                         scale: None,
                     },
                 ],
-                instructions,
+                instructions: Cow::Borrowed(instructions),
             },
         }
     }
@@ -981,6 +1698,64 @@ mod tests {
         assert_eq!(BoundingBox::from_points(&points), expected);
     }
 
+    fn rectangle(x_min: i16, y_min: i16, x_max: i16, y_max: i16) -> GlyphPoints {
+        GlyphPoints {
+            points: vec![
+                Point(x_min, y_min),
+                Point(x_min, y_max),
+                Point(x_max, y_max),
+                Point(x_max, y_min),
+            ],
+            on_curve: vec![true; 4],
+            end_pts_of_contours: vec![3],
+        }
+    }
+
+    #[test]
+    fn horizontal_crossings() {
+        let mut glyph_points = rectangle(10, 0, 90, 700);
+        let mut second = rectangle(410, 0, 490, 700);
+        glyph_points.points.append(&mut second.points);
+        glyph_points.on_curve.append(&mut second.on_curve);
+        glyph_points.end_pts_of_contours.push(7);
+
+        assert_eq!(
+            glyph_points.horizontal_crossings(350.),
+            vec![10., 90., 410., 490.]
+        );
+        assert!(glyph_points.horizontal_crossings(800.).is_empty());
+    }
+
+    #[test]
+    fn horizontal_crossings_curve() {
+        // A diamond made of off-curve points only, so every point is on an implied curve
+        let glyph_points = GlyphPoints {
+            points: vec![
+                Point(0, 100),
+                Point(100, 200),
+                Point(200, 100),
+                Point(100, 0),
+            ],
+            on_curve: vec![false; 4],
+            end_pts_of_contours: vec![3],
+        };
+        let crossings = glyph_points.horizontal_crossings(100.);
+        assert_eq!(crossings.len(), 2);
+        assert!(crossings[0] > 0. && crossings[0] < 100.);
+        assert!(crossings[1] > 100. && crossings[1] < 200.);
+    }
+
+    #[test]
+    fn estimate_stem_width() {
+        let outlines = vec![
+            rectangle(200, 0, 282, 700),
+            rectangle(200, -200, 280, 700),
+            GlyphPoints::default(),
+        ];
+        assert_eq!(estimate_vertical_stem_width(&outlines), Some(80));
+        assert_eq!(estimate_vertical_stem_width(&[]), None);
+    }
+
     #[test]
     fn write_glyf_table_loca_sanity_check() {
         let glyf = GlyfTable {
@@ -992,6 +1767,68 @@ mod tests {
         assert_eq!(loca.offsets.len(), num_glyphs + 1);
     }
 
+    #[test]
+    fn write_glyf_table_four_byte_padding() {
+        let glyph = Glyph {
+            number_of_contours: 1,
+            bounding_box: BoundingBox {
+                x_min: 0,
+                x_max: 0,
+                y_min: 0,
+                y_max: 0,
+            },
+            data: GlyphData::Simple(SimpleGlyph {
+                end_pts_of_contours: vec![0],
+                instructions: vec![],
+                flags: vec![SimpleGlyphFlag::ON_CURVE_POINT],
+                coordinates: vec![Point(0, 0)],
+            }),
+        };
+        let glyf = GlyfTable {
+            records: vec![GlyfRecord::Empty, GlyfRecord::Parsed(glyph)],
+        };
+        let mut buffer = WriteBuffer::new();
+        let loca = GlyfTable::write_with_padding(&mut buffer, glyf, GlyphPadding::FourByte)
+            .expect("unable to write glyf");
+
+        assert!(loca.offsets.iter().all(|offset| offset % 4 == 0));
+        assert_eq!(buffer.bytes_written(), 20);
+        assert_eq!(loca.offsets, vec![0, 0, 20]);
+    }
+
+    #[test]
+    fn write_packed_glyf_table() {
+        let head_data = include_bytes!("../../tests/fonts/opentype/head.bin");
+        let mut head = ReadScope::new(head_data).read::<HeadTable>().unwrap();
+        head.index_to_loc_format = IndexToLocFormat::Long;
+        let mut maxp = MaxpTable {
+            num_glyphs: 0,
+            version1_sub_table: None,
+        };
+        let glyf = GlyfTable {
+            records: vec![
+                GlyfRecord::Empty,
+                GlyfRecord::Parsed(simple_glyph_fixture()),
+            ],
+        };
+        let mut buffer = WriteBuffer::new();
+        let loca = GlyfTable::write_packed(&mut buffer, glyf, &mut head, &mut maxp).unwrap();
+        assert!(loca.offsets.iter().all(|offset| offset % 4 == 0));
+        assert_eq!(head.index_to_loc_format, IndexToLocFormat::Short);
+        assert_eq!(maxp.num_glyphs, 2);
+
+        // Glyph data beyond the reach of the short format requires the long format
+        let glyf = GlyfTable {
+            records: vec![GlyfRecord::Parsed(simple_glyph_fixture()); 4000],
+        };
+        let mut buffer = WriteBuffer::new();
+        let loca = GlyfTable::write_packed(&mut buffer, glyf, &mut head, &mut maxp).unwrap();
+        assert!(buffer.bytes_written() > 2 * 0xFFFF);
+        assert_eq!(loca.offsets.last(), Some(&(buffer.bytes_written() as u32)));
+        assert_eq!(head.index_to_loc_format, IndexToLocFormat::Long);
+        assert_eq!(maxp.num_glyphs, 4000);
+    }
+
     #[test]
     fn write_composite_glyf_instructions() {
         let glyph = composite_glyph_fixture(&[1, 2, 3, 4]);
@@ -1004,7 +1841,7 @@ mod tests {
             Ok(Glyph {
                 data: GlyphData::Composite { instructions, .. },
                 ..
-            }) => assert_eq!(instructions, vec![1, 2, 3, 4].as_slice()),
+            }) => assert_eq!(instructions.as_ref(), &[1, 2, 3, 4]),
             _ => panic!("did not read back expected instructions"),
         }
     }
@@ -1070,6 +1907,22 @@ mod tests {
         assert_eq!(glyph, expected);
     }
 
+    #[test]
+    fn simple_glyph_coordinate_overflow() {
+        let glyph_data = &[
+            0, 1, // end points of contours
+            0, 0, // instruction length
+            0x21, 0x21, // flags: on curve, long x, same y
+            0x7F, 0xFF, // x coordinates
+            0, 1,
+        ];
+
+        assert_eq!(
+            ReadScope::new(glyph_data).read_dep::<SimpleGlyph>(1),
+            Err(ParseError::BadValue)
+        );
+    }
+
     #[test]
     fn write_simple_glyph_with_zero_contours() {
         let glyph = SimpleGlyph {
@@ -1125,9 +1978,188 @@ mod tests {
             Ok(Glyph {
                 data: GlyphData::Composite { instructions, .. },
                 ..
-            }) => assert_eq!(instructions, &[]),
+            }) => assert!(instructions.is_empty()),
             Ok(_) => panic!("did not read back expected glyph"),
             Err(_) => panic!("unable to read back glyph"),
         }
     }
+
+    #[test]
+    fn edit_simple_glyph_contours() {
+        let square = |x: i16, size: i16| {
+            vec![
+                (Point(x, 0), true),
+                (Point(x + size, 0), true),
+                (Point(x + size, size), true),
+                (Point(x, size), true),
+            ]
+        };
+        let mut outline = SimpleGlyph {
+            end_pts_of_contours: vec![],
+            instructions: vec![],
+            flags: vec![],
+            coordinates: vec![],
+        };
+        outline.push_contour(square(0, 100)).unwrap();
+        outline.push_contour(vec![]).unwrap();
+        outline.push_contour(square(200, 50)).unwrap();
+        outline.push_contour(square(300, 10)).unwrap();
+        assert_eq!(outline.end_pts_of_contours, vec![3, 7, 11]);
+        assert_eq!(
+            outline.contours().collect::<Vec<_>>(),
+            vec![0..4, 4..8, 8..12]
+        );
+        assert_eq!(outline.contour(3), None);
+
+        // Drop the smallest contour, then the first one
+        outline.retain_contours(|points, _flags| points[1].0 - points[0].0 > 10);
+        outline.remove_contour(0);
+        assert_eq!(outline.end_pts_of_contours, vec![3]);
+        assert_eq!(outline.coordinates[0], Point(200, 0));
+
+        outline.map_points(|Point(x, y)| Point(x - 200, y * 2));
+        let mut glyph = Glyph::from_simple_glyph(outline).unwrap();
+        assert_eq!(glyph.number_of_contours, 1);
+        assert_eq!(
+            glyph.bounding_box,
+            BoundingBox {
+                x_min: 0,
+                x_max: 50,
+                y_min: 0,
+                y_max: 100,
+            }
+        );
+
+        let mut buffer = WriteBuffer::new();
+        Glyph::write(&mut buffer, glyph.clone()).unwrap();
+        let read_back = ReadScope::new(buffer.bytes()).read::<Glyph<'_>>().unwrap();
+        assert_eq!(read_back, glyph);
+
+        // Points whose deltas don't fit in the table can't be written
+        if let GlyphData::Simple(outline) = &mut glyph.data {
+            outline
+                .push_contour(vec![(Point(-30000, 0), true), (Point(30000, 0), true)])
+                .unwrap();
+        }
+        glyph.recalculate_bounds().unwrap();
+        assert_eq!(glyph.number_of_contours, 2);
+        assert!(Glyph::write(&mut WriteBuffer::new(), glyph).is_err());
+    }
+
+    #[test]
+    fn edit_parsed_glyf_record() {
+        let mut buffer = WriteBuffer::new();
+        Glyph::write(&mut buffer, composite_glyph_fixture(&[1, 2])).unwrap();
+        let mut record = GlyfRecord::Present(ReadScope::new(buffer.bytes()));
+        if let Some(Glyph {
+            data: GlyphData::Composite { instructions, .. },
+            ..
+        }) = record.glyph_mut().unwrap()
+        {
+            instructions.to_mut().push(3);
+        }
+        let glyph = match record {
+            GlyfRecord::Parsed(glyph) => glyph.into_owned(),
+            _ => panic!("record was not parsed"),
+        };
+        match glyph.data {
+            GlyphData::Composite { instructions, .. } => {
+                assert_eq!(instructions.as_ref(), &[1, 2, 3])
+            }
+            _ => panic!("expected composite glyph"),
+        }
+        assert_eq!(GlyfRecord::Empty.glyph_mut().unwrap(), None);
+    }
+
+    #[test]
+    fn test_simple_glyph_points() {
+        let glyf = GlyfTable {
+            records: vec![
+                GlyfRecord::Empty,
+                GlyfRecord::Parsed(simple_glyph_fixture()),
+            ],
+        };
+
+        assert_eq!(glyf.glyph_points(0).unwrap(), GlyphPoints::default());
+
+        let glyph_points = glyf.glyph_points(1).unwrap();
+        assert_eq!(glyph_points.points.len(), 9);
+        assert_eq!(glyph_points.points[0], Point(433, 77));
+        assert_eq!(
+            glyph_points.on_curve,
+            &[true, false, true, false, true, false, true, false, true]
+        );
+        assert_eq!(glyph_points.end_pts_of_contours, &[8]);
+    }
+
+    #[test]
+    fn test_composite_glyph_points() {
+        let component = |argument1, scale| CompositeGlyph {
+            flags: CompositeGlyphFlag::ARG_1_AND_2_ARE_WORDS
+                | CompositeGlyphFlag::ARGS_ARE_XY_VALUES,
+            glyph_index: 0,
+            argument1: CompositeGlyphArgument::I16(argument1),
+            argument2: CompositeGlyphArgument::I16(0),
+            scale,
+        };
+        let composite = Glyph {
+            number_of_contours: -1,
+            bounding_box: BoundingBox {
+                x_min: 0,
+                x_max: 0,
+                y_min: 0,
+                y_max: 0,
+            },
+            data: GlyphData::Composite {
+                glyphs: vec![
+                    component(0, None),
+                    // 0x2000 is 0.5 in F2Dot14
+                    component(100, Some(CompositeGlyphScale::Scale(F2Dot14::new(0x2000)))),
+                ],
+                instructions: Cow::Borrowed(&[]),
+            },
+        };
+        let glyf = GlyfTable {
+            records: vec![
+                GlyfRecord::Parsed(simple_glyph_fixture()),
+                GlyfRecord::Parsed(composite),
+            ],
+        };
+
+        let glyph_points = glyf.glyph_points(1).unwrap();
+        assert_eq!(glyph_points.points.len(), 18);
+        assert_eq!(glyph_points.points[0], Point(433, 77));
+        // 433 * 0.5 + 100, 77 * 0.5 rounded
+        assert_eq!(glyph_points.points[9], Point(317, 39));
+        assert!(glyph_points.on_curve[9]);
+        assert_eq!(glyph_points.end_pts_of_contours, &[8, 17]);
+    }
+
+    #[test]
+    fn test_recursive_composite_glyph_points() {
+        let composite = Glyph {
+            number_of_contours: -1,
+            bounding_box: BoundingBox {
+                x_min: 0,
+                x_max: 0,
+                y_min: 0,
+                y_max: 0,
+            },
+            data: GlyphData::Composite {
+                glyphs: vec![CompositeGlyph {
+                    flags: CompositeGlyphFlag::ARGS_ARE_XY_VALUES,
+                    glyph_index: 0,
+                    argument1: CompositeGlyphArgument::I8(0),
+                    argument2: CompositeGlyphArgument::I8(0),
+                    scale: None,
+                }],
+                instructions: Cow::Borrowed(&[]),
+            },
+        };
+        let glyf = GlyfTable {
+            records: vec![GlyfRecord::Parsed(composite)],
+        };
+
+        assert!(glyf.glyph_points(0).is_err());
+    }
 }
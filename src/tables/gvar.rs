@@ -0,0 +1,931 @@
+#![deny(missing_docs)]
+
+//! `gvar` table parsing.
+//!
+//! The table header and shared tuples are read eagerly. The tuple variations of a glyph, their
+//! point numbers and deltas, are decoded on request.
+//!
+//! <https://docs.microsoft.com/en-us/typography/opentype/spec/gvar>
+
+use std::convert::TryFrom;
+
+use log::warn;
+
+use crate::binary::read::{ReadArray, ReadBinaryDep, ReadCtxt, ReadScope};
+use crate::binary::{I16Be, U16Be, U32Be};
+use crate::error::ParseError;
+use crate::tables::glyf::Point;
+use crate::tables::F2Dot14;
+
+/// Flag indicating that glyph variation data offsets are 32-bit.
+const LONG_OFFSETS: u16 = 0x0001;
+
+/// Flag indicating that the glyph's tuple variations share a set of point numbers.
+const SHARED_POINT_NUMBERS: u16 = 0x8000;
+
+/// Mask for the tuple variation count in a glyph variation data header.
+const COUNT_MASK: u16 = 0x0FFF;
+
+/// Flag indicating that a tuple variation header holds its peak tuple.
+const EMBEDDED_PEAK_TUPLE: u16 = 0x8000;
+
+/// Flag indicating that a tuple variation header holds an intermediate region.
+const INTERMEDIATE_REGION: u16 = 0x4000;
+
+/// Flag indicating that a tuple variation has its own point numbers.
+const PRIVATE_POINT_NUMBERS: u16 = 0x2000;
+
+/// Mask for the shared tuple index in a tuple variation header.
+const TUPLE_INDEX_MASK: u16 = 0x0FFF;
+
+/// Flag indicating that a point number count takes two bytes.
+const POINTS_ARE_WORDS: u8 = 0x80;
+
+/// Mask for the number of point numbers in a run, minus one.
+const POINT_RUN_COUNT_MASK: u8 = 0x7F;
+
+/// Flag indicating that the deltas in a run are zero and not stored.
+const DELTAS_ARE_ZERO: u8 = 0x80;
+
+/// Flag indicating that the deltas in a run are 16-bit.
+const DELTAS_ARE_WORDS: u8 = 0x40;
+
+/// Mask for the number of deltas in a run, minus one.
+const DELTA_RUN_COUNT_MASK: u8 = 0x3F;
+
+/// Glyph variations table.
+pub struct GvarTable<'a> {
+    /// Major version of the table, always 1.
+    pub major_version: u16,
+    /// Minor version of the table, always 0.
+    pub minor_version: u16,
+    /// The number of variation axes in the font.
+    pub axis_count: u16,
+    /// The number of shared tuple records.
+    pub shared_tuple_count: u16,
+    shared_tuples: ReadArray<'a, I16Be>,
+    glyph_variation_data: ReadScope<'a>,
+    glyph_variation_data_offsets: GlyphVariationDataOffsets<'a>,
+}
+
+enum GlyphVariationDataOffsets<'a> {
+    Short(ReadArray<'a, U16Be>),
+    Long(ReadArray<'a, U32Be>),
+}
+
+/// The size of the variation data of a glyph, read from its headers.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct GlyphVariationSize {
+    /// The number of tuple variation headers.
+    pub tuple_variation_count: u16,
+    /// The size in bytes of the serialized glyph variation data, including its headers.
+    pub data_size: usize,
+}
+
+/// How point numbers beyond the number of points in a glyph are handled.
+///
+/// Such point numbers only occur in malformed fonts.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PointNumberMode {
+    /// Fail with `ParseError::BadIndex`.
+    Strict,
+    /// Drop the point numbers, along with their deltas, and record a warning.
+    Lenient,
+}
+
+/// The decoded variation data of a glyph.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TupleVariationStore {
+    /// The tuple variations, in the order they appear in the table.
+    pub tuple_variations: Vec<TupleVariation>,
+    /// Point numbers that were dropped in `PointNumberMode::Lenient`.
+    pub warnings: Vec<PointNumberWarning>,
+}
+
+/// The deltas applied to a glyph's points for one region of the design space.
+///
+/// Coordinates are F2Dot14 values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TupleVariation {
+    /// Index into the shared tuples, if the peak tuple is not embedded.
+    pub shared_tuple_index: Option<u16>,
+    /// The peak tuple, if embedded in the header.
+    pub peak_tuple: Option<Vec<i16>>,
+    /// The start and end tuples of the region, if it is an intermediate region.
+    pub intermediate_region: Option<(Vec<i16>, Vec<i16>)>,
+    /// The points the deltas apply to.
+    pub point_numbers: PointNumbers,
+    /// The x and y delta of each point.
+    pub deltas: Vec<(i16, i16)>,
+}
+
+/// The points a tuple variation applies to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PointNumbers {
+    /// Every point in the glyph, in order.
+    All,
+    /// The listed points, in the same order as the deltas.
+    Specific(Vec<u16>),
+}
+
+/// A point number that was dropped because it exceeded the number of points in the glyph.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PointNumberWarning {
+    /// Index of the tuple variation the point number was dropped from.
+    pub tuple_variation_index: usize,
+    /// The out of range point number.
+    pub point_number: u16,
+    /// The number of points in the glyph.
+    pub num_points: u16,
+}
+
+impl<'a> GvarTable<'a> {
+    /// The number of glyphs in the table.
+    pub fn glyph_count(&self) -> usize {
+        self.glyph_variation_data_offsets.len().saturating_sub(1)
+    }
+
+    /// Returns the number of tuple variations and the size of the variation data for the
+    /// supplied glyph, without decoding the variation data.
+    ///
+    /// Glyphs without variation data have a count and size of zero.
+    pub fn glyph_variation_size(&self, glyph_id: u16) -> Result<GlyphVariationSize, ParseError> {
+        let scope = self.glyph_data_scope(glyph_id)?;
+        let data_size = scope.data().len();
+        if data_size == 0 {
+            return Ok(GlyphVariationSize {
+                tuple_variation_count: 0,
+                data_size,
+            });
+        }
+
+        let mut ctxt = scope.ctxt();
+        let tuple_variation_count = ctxt.read_u16be()? & COUNT_MASK;
+        Ok(GlyphVariationSize {
+            tuple_variation_count,
+            data_size,
+        })
+    }
+
+    /// Decode the tuple variations of the supplied glyph.
+    ///
+    /// `num_points` is the number of points in the glyph, including the four phantom points.
+    /// Returns `None` if the glyph has no variation data.
+    pub fn tuple_variation_store(
+        &self,
+        glyph_id: u16,
+        num_points: u16,
+        mode: PointNumberMode,
+    ) -> Result<Option<TupleVariationStore>, ParseError> {
+        let scope = self.glyph_data_scope(glyph_id)?;
+        if scope.data().is_empty() {
+            return Ok(None);
+        }
+
+        let mut ctxt = scope.ctxt();
+        let tuple_variation_count = ctxt.read_u16be()?;
+        let data_offset = usize::from(ctxt.read_u16be()?);
+        let axis_count = usize::from(self.axis_count);
+        let headers = (0..tuple_variation_count & COUNT_MASK)
+            .map(|_| read_tuple_variation_header(&mut ctxt, axis_count))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut data = scope.offset(data_offset).ctxt();
+        let shared_point_numbers = if tuple_variation_count & SHARED_POINT_NUMBERS != 0 {
+            read_packed_point_numbers(&mut data)?
+        } else {
+            PointNumbers::All
+        };
+
+        let mut tuple_variations = Vec::with_capacity(headers.len());
+        let mut warnings = Vec::new();
+        for (tuple_variation_index, header) in headers.into_iter().enumerate() {
+            let mut ctxt = data.read_scope(header.variation_data_size)?.ctxt();
+            let point_numbers = if header.tuple_index & PRIVATE_POINT_NUMBERS != 0 {
+                read_packed_point_numbers(&mut ctxt)?
+            } else {
+                shared_point_numbers.clone()
+            };
+            let count = match &point_numbers {
+                PointNumbers::All => usize::from(num_points),
+                PointNumbers::Specific(points) => points.len(),
+            };
+            let x_deltas = read_packed_deltas(&mut ctxt, count)?;
+            let y_deltas = read_packed_deltas(&mut ctxt, count)?;
+            let mut deltas = x_deltas.into_iter().zip(y_deltas).collect::<Vec<_>>();
+
+            let point_numbers = match point_numbers {
+                PointNumbers::All => PointNumbers::All,
+                PointNumbers::Specific(points) => {
+                    if points.iter().all(|&point| point < num_points) {
+                        PointNumbers::Specific(points)
+                    } else if mode == PointNumberMode::Strict {
+                        return Err(ParseError::BadIndex);
+                    } else {
+                        let (points, kept_deltas): (Vec<u16>, Vec<_>) = points
+                            .into_iter()
+                            .zip(deltas)
+                            .filter(|&(point_number, _)| {
+                                if point_number < num_points {
+                                    return true;
+                                }
+                                warn!(
+                                    "glyph {}: dropping point number {} beyond {} points",
+                                    glyph_id, point_number, num_points
+                                );
+                                warnings.push(PointNumberWarning {
+                                    tuple_variation_index,
+                                    point_number,
+                                    num_points,
+                                });
+                                false
+                            })
+                            .unzip();
+                        deltas = kept_deltas;
+                        PointNumbers::Specific(points)
+                    }
+                }
+            };
+
+            let shared_tuple_index = if header.peak_tuple.is_none() {
+                Some(header.tuple_index & TUPLE_INDEX_MASK)
+            } else {
+                None
+            };
+            tuple_variations.push(TupleVariation {
+                shared_tuple_index,
+                peak_tuple: header.peak_tuple,
+                intermediate_region: header.intermediate_region,
+                point_numbers,
+                deltas,
+            });
+        }
+
+        Ok(Some(TupleVariationStore {
+            tuple_variations,
+            warnings,
+        }))
+    }
+
+    /// Compute the deltas of the points of a glyph at the normalised variation coordinates
+    /// `coords`, returning one delta per point.
+    ///
+    /// For simple glyphs `points` are the points of the outline, excluding phantom points, and
+    /// the deltas of points that are not referenced by a tuple variation are inferred from the
+    /// nearest referenced points of the same contour. For composite glyphs `points` holds the
+    /// offset of each component and `end_pts_of_contours` is empty, in which case unreferenced
+    /// points have a delta of zero.
+    pub fn glyph_deltas(
+        &self,
+        glyph_id: u16,
+        coords: &[F2Dot14],
+        points: &[Point],
+        end_pts_of_contours: &[u16],
+    ) -> Result<Vec<(f32, f32)>, ParseError> {
+        let mut deltas = vec![(0., 0.); points.len()];
+        // Four phantom points follow the points of the glyph
+        let num_points = u16::try_from(points.len() + 4)?;
+        let store =
+            match self.tuple_variation_store(glyph_id, num_points, PointNumberMode::Lenient)? {
+                Some(store) => store,
+                None => return Ok(deltas),
+            };
+
+        for tuple_variation in &store.tuple_variations {
+            let scalar = self.tuple_scalar(tuple_variation, coords)?;
+            if scalar == 0. {
+                continue;
+            }
+            let mut tuple_deltas = vec![None; points.len()];
+            match &tuple_variation.point_numbers {
+                PointNumbers::All => {
+                    for (delta, &(x, y)) in tuple_deltas.iter_mut().zip(&tuple_variation.deltas) {
+                        *delta = Some((f32::from(x), f32::from(y)));
+                    }
+                }
+                PointNumbers::Specific(point_numbers) => {
+                    for (&point, &(x, y)) in point_numbers.iter().zip(&tuple_variation.deltas) {
+                        if let Some(delta) = tuple_deltas.get_mut(usize::from(point)) {
+                            *delta = Some((f32::from(x), f32::from(y)));
+                        }
+                    }
+                    infer_deltas(points, end_pts_of_contours, &mut tuple_deltas);
+                }
+            }
+            for (delta, tuple_delta) in deltas.iter_mut().zip(tuple_deltas) {
+                if let Some((x, y)) = tuple_delta {
+                    delta.0 += x * scalar;
+                    delta.1 += y * scalar;
+                }
+            }
+        }
+
+        Ok(deltas)
+    }
+
+    /// Compute the deltas of the four phantom points of a glyph at the normalised variation
+    /// coordinates `coords`.
+    ///
+    /// `num_points` is the number of points of the glyph excluding the phantom points, as passed
+    /// to `glyph_deltas`. The phantom points are, in order, the horizontal origin and advance
+    /// followed by the vertical origin and advance. Phantom points that are not referenced by a
+    /// tuple variation have a delta of zero.
+    pub fn phantom_point_deltas(
+        &self,
+        glyph_id: u16,
+        coords: &[F2Dot14],
+        num_points: usize,
+    ) -> Result<[(f32, f32); 4], ParseError> {
+        let mut deltas = [(0., 0.); 4];
+        let store = match self.tuple_variation_store(
+            glyph_id,
+            u16::try_from(num_points + 4)?,
+            PointNumberMode::Lenient,
+        )? {
+            Some(store) => store,
+            None => return Ok(deltas),
+        };
+
+        for tuple_variation in &store.tuple_variations {
+            let scalar = self.tuple_scalar(tuple_variation, coords)?;
+            if scalar == 0. {
+                continue;
+            }
+            let mut add = |point: usize, (x, y): (i16, i16)| {
+                if let Some(delta) = point
+                    .checked_sub(num_points)
+                    .and_then(|phantom| deltas.get_mut(phantom))
+                {
+                    delta.0 += f32::from(x) * scalar;
+                    delta.1 += f32::from(y) * scalar;
+                }
+            };
+            match &tuple_variation.point_numbers {
+                PointNumbers::All => {
+                    for (point, &delta) in tuple_variation.deltas.iter().enumerate() {
+                        add(point, delta);
+                    }
+                }
+                PointNumbers::Specific(point_numbers) => {
+                    for (&point, &delta) in point_numbers.iter().zip(&tuple_variation.deltas) {
+                        add(usize::from(point), delta);
+                    }
+                }
+            }
+        }
+
+        Ok(deltas)
+    }
+
+    /// Returns the scalar that the deltas of `tuple_variation` are multiplied by at `coords`.
+    fn tuple_scalar(
+        &self,
+        tuple_variation: &TupleVariation,
+        coords: &[F2Dot14],
+    ) -> Result<f32, ParseError> {
+        let peak_tuple = match (
+            &tuple_variation.peak_tuple,
+            tuple_variation.shared_tuple_index,
+        ) {
+            (Some(peak_tuple), _) => peak_tuple.clone(),
+            (None, Some(index)) => {
+                let axis_count = usize::from(self.axis_count);
+                let start = usize::from(index) * axis_count;
+                if start + axis_count > self.shared_tuples.len() {
+                    return Err(ParseError::BadIndex);
+                }
+                (start..start + axis_count)
+                    .map(|i| self.shared_tuples.get_item(i))
+                    .collect()
+            }
+            (None, None) => return Err(ParseError::MissingValue),
+        };
+
+        let to_f32 = |value: i16| f32::from(value) / 16384.;
+        let mut scalar = 1.;
+        for (axis, &peak) in peak_tuple.iter().enumerate() {
+            if peak == 0 {
+                continue;
+            }
+            let peak = to_f32(peak);
+            let coord = coords.get(axis).copied().map_or(0., f32::from);
+            if coord == peak {
+                continue;
+            }
+            if coord == 0. {
+                return Ok(0.);
+            }
+            match &tuple_variation.intermediate_region {
+                Some((start, end)) => {
+                    let start = to_f32(start[axis]);
+                    let end = to_f32(end[axis]);
+                    if coord < start || coord > end {
+                        return Ok(0.);
+                    } else if coord < peak {
+                        scalar *= (coord - start) / (peak - start);
+                    } else {
+                        scalar *= (end - coord) / (end - peak);
+                    }
+                }
+                None => {
+                    if coord < peak.min(0.) || coord > peak.max(0.) {
+                        return Ok(0.);
+                    }
+                    scalar *= coord / peak;
+                }
+            }
+        }
+        Ok(scalar)
+    }
+
+    fn glyph_data_scope(&self, glyph_id: u16) -> Result<ReadScope<'a>, ParseError> {
+        let index = usize::from(glyph_id);
+        if index >= self.glyph_count() {
+            return Err(ParseError::BadIndex);
+        }
+        let start = self.glyph_variation_data_offsets.get(index);
+        let end = self.glyph_variation_data_offsets.get(index + 1);
+        let data_size = end.checked_sub(start).ok_or(ParseError::BadOffset)?;
+        if data_size == 0 {
+            return Ok(ReadScope::new(&[]));
+        }
+        self.glyph_variation_data.offset_length(start, data_size)
+    }
+}
+
+struct TupleVariationHeader {
+    variation_data_size: usize,
+    tuple_index: u16,
+    peak_tuple: Option<Vec<i16>>,
+    intermediate_region: Option<(Vec<i16>, Vec<i16>)>,
+}
+
+fn read_tuple_variation_header(
+    ctxt: &mut ReadCtxt<'_>,
+    axis_count: usize,
+) -> Result<TupleVariationHeader, ParseError> {
+    let variation_data_size = usize::from(ctxt.read_u16be()?);
+    let tuple_index = ctxt.read_u16be()?;
+    let peak_tuple = if tuple_index & EMBEDDED_PEAK_TUPLE != 0 {
+        Some(read_tuple(ctxt, axis_count)?)
+    } else {
+        None
+    };
+    let intermediate_region = if tuple_index & INTERMEDIATE_REGION != 0 {
+        Some((read_tuple(ctxt, axis_count)?, read_tuple(ctxt, axis_count)?))
+    } else {
+        None
+    };
+    Ok(TupleVariationHeader {
+        variation_data_size,
+        tuple_index,
+        peak_tuple,
+        intermediate_region,
+    })
+}
+
+fn read_tuple(ctxt: &mut ReadCtxt<'_>, axis_count: usize) -> Result<Vec<i16>, ParseError> {
+    let tuple = (0..axis_count)
+        .map(|_| ctxt.read_i16be())
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(tuple)
+}
+
+fn read_packed_point_numbers(ctxt: &mut ReadCtxt<'_>) -> Result<PointNumbers, ParseError> {
+    let first = ctxt.read_u8()?;
+    let count = if first & POINTS_ARE_WORDS == 0 {
+        usize::from(first)
+    } else {
+        usize::from(first & POINT_RUN_COUNT_MASK) << 8 | usize::from(ctxt.read_u8()?)
+    };
+    if count == 0 {
+        return Ok(PointNumbers::All);
+    }
+
+    // Point numbers are stored as differences from the previous point number
+    let mut points = Vec::with_capacity(count);
+    let mut point = 0u16;
+    while points.len() < count {
+        let control = ctxt.read_u8()?;
+        let run_count = usize::from(control & POINT_RUN_COUNT_MASK) + 1;
+        for _ in 0..run_count.min(count - points.len()) {
+            let delta = if control & POINTS_ARE_WORDS == 0 {
+                u16::from(ctxt.read_u8()?)
+            } else {
+                ctxt.read_u16be()?
+            };
+            point = point.checked_add(delta).ok_or(ParseError::BadValue)?;
+            points.push(point);
+        }
+    }
+    Ok(PointNumbers::Specific(points))
+}
+
+fn read_packed_deltas(ctxt: &mut ReadCtxt<'_>, count: usize) -> Result<Vec<i16>, ParseError> {
+    let mut deltas = Vec::with_capacity(count);
+    while deltas.len() < count {
+        let control = ctxt.read_u8()?;
+        let run_count = usize::from(control & DELTA_RUN_COUNT_MASK) + 1;
+        for _ in 0..run_count.min(count - deltas.len()) {
+            let delta = if control & DELTAS_ARE_ZERO != 0 {
+                0
+            } else if control & DELTAS_ARE_WORDS != 0 {
+                ctxt.read_i16be()?
+            } else {
+                i16::from(ctxt.read_i8()?)
+            };
+            deltas.push(delta);
+        }
+    }
+    Ok(deltas)
+}
+
+/// Infer the deltas of the points of each contour without a delta from the nearest points before
+/// and after them that have one (interpolation of untouched points).
+fn infer_deltas(points: &[Point], end_pts_of_contours: &[u16], deltas: &mut [Option<(f32, f32)>]) {
+    let mut start = 0;
+    for &end in end_pts_of_contours {
+        let end = usize::from(end) + 1;
+        if end <= start || end > points.len() {
+            break;
+        }
+        let touched = (start..end)
+            .filter(|&i| deltas[i].is_some())
+            .collect::<Vec<_>>();
+        if !touched.is_empty() && touched.len() < end - start {
+            for i in start..end {
+                if deltas[i].is_some() {
+                    continue;
+                }
+                // The nearest touched points before and after, wrapping around the contour
+                let prev = touched
+                    .iter()
+                    .rev()
+                    .find(|&&t| t < i)
+                    .or_else(|| touched.last())
+                    .copied()
+                    .unwrap_or(i);
+                let next = touched
+                    .iter()
+                    .find(|&&t| t > i)
+                    .or_else(|| touched.first())
+                    .copied()
+                    .unwrap_or(i);
+                let (prev_delta, next_delta) = match (deltas[prev], deltas[next]) {
+                    (Some(prev_delta), Some(next_delta)) => (prev_delta, next_delta),
+                    _ => continue,
+                };
+                let x = infer_delta(
+                    points[i].0,
+                    (points[prev].0, prev_delta.0),
+                    (points[next].0, next_delta.0),
+                );
+                let y = infer_delta(
+                    points[i].1,
+                    (points[prev].1, prev_delta.1),
+                    (points[next].1, next_delta.1),
+                );
+                deltas[i] = Some((x, y));
+            }
+        }
+        start = end;
+    }
+}
+
+/// Infer the delta of a coordinate from two reference coordinates and their deltas.
+fn infer_delta(coord: i16, (coord1, delta1): (i16, f32), (coord2, delta2): (i16, f32)) -> f32 {
+    let ((lo, lo_delta), (hi, hi_delta)) = if coord1 <= coord2 {
+        ((coord1, delta1), (coord2, delta2))
+    } else {
+        ((coord2, delta2), (coord1, delta1))
+    };
+    if lo == hi {
+        if delta1 == delta2 {
+            delta1
+        } else {
+            0.
+        }
+    } else if coord <= lo {
+        lo_delta
+    } else if coord >= hi {
+        hi_delta
+    } else {
+        let t = (i32::from(coord) - i32::from(lo)) as f32 / (i32::from(hi) - i32::from(lo)) as f32;
+        lo_delta + t * (hi_delta - lo_delta)
+    }
+}
+
+impl<'a> GlyphVariationDataOffsets<'a> {
+    fn len(&self) -> usize {
+        match self {
+            GlyphVariationDataOffsets::Short(offsets) => offsets.len(),
+            GlyphVariationDataOffsets::Long(offsets) => offsets.len(),
+        }
+    }
+
+    fn get(&self, index: usize) -> usize {
+        match self {
+            // Short offsets are stored divided by 2
+            GlyphVariationDataOffsets::Short(offsets) => usize::from(offsets.get_item(index)) * 2,
+            GlyphVariationDataOffsets::Long(offsets) => offsets.get_item(index) as usize,
+        }
+    }
+}
+
+impl<'a> ReadBinaryDep<'a> for GvarTable<'a> {
+    type Args = usize;
+    type HostType = Self;
+
+    /// Read the `gvar` table.
+    ///
+    /// `num_glyphs` should be read from the `maxp` table.
+    fn read_dep(ctxt: &mut ReadCtxt<'a>, num_glyphs: usize) -> Result<Self, ParseError> {
+        let scope = ctxt.scope();
+        let major_version = ctxt.read_u16be()?;
+        ctxt.check(major_version == 1)?;
+        let minor_version = ctxt.read_u16be()?;
+        let axis_count = ctxt.read_u16be()?;
+        let shared_tuple_count = ctxt.read_u16be()?;
+        let shared_tuples_offset = usize::try_from(ctxt.read_u32be()?)?;
+        let glyph_count = usize::from(ctxt.read_u16be()?);
+        ctxt.check(glyph_count == num_glyphs)?;
+        let flags = ctxt.read_u16be()?;
+        let glyph_variation_data_array_offset = usize::try_from(ctxt.read_u32be()?)?;
+        let glyph_variation_data_offsets = if flags & LONG_OFFSETS == 0 {
+            GlyphVariationDataOffsets::Short(ctxt.read_array::<U16Be>(glyph_count + 1)?)
+        } else {
+            GlyphVariationDataOffsets::Long(ctxt.read_array::<U32Be>(glyph_count + 1)?)
+        };
+        let glyph_variation_data = scope.offset(glyph_variation_data_array_offset);
+        let shared_tuples = if shared_tuple_count == 0 {
+            ReadArray::empty()
+        } else {
+            let len = usize::from(shared_tuple_count)
+                .checked_mul(usize::from(axis_count))
+                .ok_or(ParseError::LimitExceeded)?;
+            scope
+                .offset(shared_tuples_offset)
+                .ctxt()
+                .read_array::<I16Be>(len)?
+        };
+
+        Ok(GvarTable {
+            major_version,
+            minor_version,
+            axis_count,
+            shared_tuple_count,
+            shared_tuples,
+            glyph_variation_data,
+            glyph_variation_data_offsets,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::write::{WriteBinary, WriteBuffer, WriteContext};
+
+    fn gvar_data(long_offsets: bool) -> Vec<u8> {
+        let mut w = WriteBuffer::new();
+        U16Be::write(&mut w, 1u16).unwrap(); // majorVersion
+        U16Be::write(&mut w, 0u16).unwrap(); // minorVersion
+        U16Be::write(&mut w, 2u16).unwrap(); // axisCount
+        U16Be::write(&mut w, 0u16).unwrap(); // sharedTupleCount
+        U32Be::write(&mut w, 0u32).unwrap(); // sharedTuplesOffset
+        U16Be::write(&mut w, 3u16).unwrap(); // glyphCount
+        U16Be::write(&mut w, u16::from(long_offsets)).unwrap(); // flags
+        let offsets_size = if long_offsets { 4 * 4 } else { 4 * 2 };
+        U32Be::write(&mut w, 20u32 + offsets_size).unwrap(); // glyphVariationDataArrayOffset
+
+        // Glyph 0 has no variations, glyph 1 has 12 bytes of data, glyph 2 has 8
+        for &offset in &[0u32, 0, 12, 20] {
+            if long_offsets {
+                U32Be::write(&mut w, offset).unwrap();
+            } else {
+                U16Be::write(&mut w, (offset / 2) as u16).unwrap();
+            }
+        }
+
+        // Glyph 1: two tuples with shared point numbers, headers only
+        U16Be::write(&mut w, 0x8002u16).unwrap(); // tupleVariationCount
+        U16Be::write(&mut w, 12u16).unwrap(); // dataOffset
+        w.write_zeros(8).unwrap();
+        // Glyph 2: one tuple
+        U16Be::write(&mut w, 0x0001u16).unwrap();
+        U16Be::write(&mut w, 8u16).unwrap();
+        w.write_zeros(4).unwrap();
+        w.into_inner()
+    }
+
+    #[test]
+    fn glyph_variation_size() {
+        for &long_offsets in &[false, true] {
+            let data = gvar_data(long_offsets);
+            let gvar = ReadScope::new(&data).read_dep::<GvarTable<'_>>(3).unwrap();
+            assert_eq!(gvar.axis_count, 2);
+            assert_eq!(gvar.glyph_count(), 3);
+
+            let sizes = (0..3)
+                .map(|glyph_id| gvar.glyph_variation_size(glyph_id).unwrap())
+                .map(|size| (size.tuple_variation_count, size.data_size))
+                .collect::<Vec<_>>();
+            assert_eq!(sizes, vec![(0, 0), (2, 12), (1, 8)]);
+            assert!(gvar.glyph_variation_size(3).is_err());
+        }
+    }
+
+    // A gvar table with a single glyph and two axes
+    fn single_glyph_gvar_data(glyph_data: &[u8]) -> Vec<u8> {
+        let mut w = WriteBuffer::new();
+        U16Be::write(&mut w, 1u16).unwrap(); // majorVersion
+        U16Be::write(&mut w, 0u16).unwrap(); // minorVersion
+        U16Be::write(&mut w, 2u16).unwrap(); // axisCount
+        U16Be::write(&mut w, 0u16).unwrap(); // sharedTupleCount
+        U32Be::write(&mut w, 0u32).unwrap(); // sharedTuplesOffset
+        U16Be::write(&mut w, 1u16).unwrap(); // glyphCount
+        U16Be::write(&mut w, LONG_OFFSETS).unwrap(); // flags
+        U32Be::write(&mut w, 28u32).unwrap(); // glyphVariationDataArrayOffset
+        U32Be::write(&mut w, 0u32).unwrap();
+        U32Be::write(&mut w, glyph_data.len() as u32).unwrap();
+        w.write_bytes(glyph_data).unwrap();
+        w.into_inner()
+    }
+
+    // One tuple with an embedded peak and private point numbers 1, 5, and 9
+    fn out_of_range_points_glyph_data() -> Vec<u8> {
+        let mut w = WriteBuffer::new();
+        U16Be::write(&mut w, 1u16).unwrap(); // tupleVariationCount
+        U16Be::write(&mut w, 12u16).unwrap(); // dataOffset
+        U16Be::write(&mut w, 10u16).unwrap(); // variationDataSize
+        U16Be::write(&mut w, EMBEDDED_PEAK_TUPLE | PRIVATE_POINT_NUMBERS).unwrap(); // tupleIndex
+        U16Be::write(&mut w, 0x4000u16).unwrap(); // peakTuple
+        U16Be::write(&mut w, 0u16).unwrap();
+        w.write_bytes(&[3, 0x02, 1, 4, 4]).unwrap(); // point numbers
+        w.write_bytes(&[0x02, 10, 0xEC, 30]).unwrap(); // x deltas: 10, -20, 30
+        w.write_bytes(&[DELTAS_ARE_ZERO | 0x02]).unwrap(); // y deltas
+        w.into_inner()
+    }
+
+    #[test]
+    fn tuple_variation_store_all_points() {
+        let mut w = WriteBuffer::new();
+        U16Be::write(&mut w, SHARED_POINT_NUMBERS | 1).unwrap(); // tupleVariationCount
+        U16Be::write(&mut w, 8u16).unwrap(); // dataOffset
+        U16Be::write(&mut w, 6u16).unwrap(); // variationDataSize
+        U16Be::write(&mut w, 3u16).unwrap(); // tupleIndex
+        w.write_bytes(&[0]).unwrap(); // shared point numbers: all points
+        w.write_bytes(&[DELTAS_ARE_WORDS | 0x01, 0x01, 0x00, 0xFF, 0xFF])
+            .unwrap(); // x deltas
+        w.write_bytes(&[DELTAS_ARE_ZERO | 0x01]).unwrap(); // y deltas
+        w.write_zeros(1).unwrap();
+        let data = single_glyph_gvar_data(&w.into_inner());
+
+        let gvar = ReadScope::new(&data).read_dep::<GvarTable<'_>>(1).unwrap();
+        let store = gvar
+            .tuple_variation_store(0, 2, PointNumberMode::Strict)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            store.tuple_variations,
+            vec![TupleVariation {
+                shared_tuple_index: Some(3),
+                peak_tuple: None,
+                intermediate_region: None,
+                point_numbers: PointNumbers::All,
+                deltas: vec![(256, 0), (-1, 0)],
+            }]
+        );
+        assert!(store.warnings.is_empty());
+    }
+
+    #[test]
+    fn tuple_variation_store_out_of_range_points() {
+        let data = single_glyph_gvar_data(&out_of_range_points_glyph_data());
+        let gvar = ReadScope::new(&data).read_dep::<GvarTable<'_>>(1).unwrap();
+
+        assert_eq!(
+            gvar.tuple_variation_store(0, 8, PointNumberMode::Strict),
+            Err(ParseError::BadIndex)
+        );
+
+        let store = gvar
+            .tuple_variation_store(0, 8, PointNumberMode::Lenient)
+            .unwrap()
+            .unwrap();
+        let tuple_variation = &store.tuple_variations[0];
+        assert_eq!(tuple_variation.peak_tuple, Some(vec![0x4000, 0]));
+        assert_eq!(tuple_variation.shared_tuple_index, None);
+        assert_eq!(
+            tuple_variation.point_numbers,
+            PointNumbers::Specific(vec![1, 5])
+        );
+        assert_eq!(tuple_variation.deltas, vec![(10, 0), (-20, 0)]);
+        assert_eq!(
+            store.warnings,
+            vec![PointNumberWarning {
+                tuple_variation_index: 0,
+                point_number: 9,
+                num_points: 8,
+            }]
+        );
+
+        // All points in range
+        let store = gvar
+            .tuple_variation_store(0, 10, PointNumberMode::Strict)
+            .unwrap()
+            .unwrap();
+        assert_eq!(store.tuple_variations[0].deltas.len(), 3);
+    }
+
+    #[test]
+    fn phantom_point_deltas() {
+        let data = single_glyph_gvar_data(&out_of_range_points_glyph_data());
+        let gvar = ReadScope::new(&data).read_dep::<GvarTable<'_>>(1).unwrap();
+
+        // With four outline points, point 5 is the horizontal advance phantom point
+        let full = [F2Dot14::from_f32(1.), F2Dot14::from_f32(0.)];
+        let deltas = gvar.phantom_point_deltas(0, &full, 4).unwrap();
+        assert_eq!(deltas, [(0., 0.), (-20., 0.), (0., 0.), (0., 0.)]);
+        let half = [F2Dot14::from_f32(0.5), F2Dot14::from_f32(0.)];
+        let deltas = gvar.phantom_point_deltas(0, &half, 4).unwrap();
+        assert_eq!(deltas, [(0., 0.), (-10., 0.), (0., 0.), (0., 0.)]);
+        let deltas = gvar.phantom_point_deltas(0, &[], 4).unwrap();
+        assert_eq!(deltas, [(0., 0.); 4]);
+    }
+
+    #[test]
+    fn tuple_variation_store_point_number_overflow() {
+        let mut w = WriteBuffer::new();
+        U16Be::write(&mut w, 1u16).unwrap(); // tupleVariationCount
+        U16Be::write(&mut w, 12u16).unwrap(); // dataOffset
+        U16Be::write(&mut w, 6u16).unwrap(); // variationDataSize
+        U16Be::write(&mut w, EMBEDDED_PEAK_TUPLE | PRIVATE_POINT_NUMBERS).unwrap(); // tupleIndex
+        U16Be::write(&mut w, 0x4000u16).unwrap(); // peakTuple
+        U16Be::write(&mut w, 0u16).unwrap();
+        // Point numbers 0xFFFF and 0xFFFF + 2
+        w.write_bytes(&[2, POINTS_ARE_WORDS | 0x01, 0xFF, 0xFF, 0x00, 0x02])
+            .unwrap();
+        let data = single_glyph_gvar_data(&w.into_inner());
+        let gvar = ReadScope::new(&data).read_dep::<GvarTable<'_>>(1).unwrap();
+
+        assert_eq!(
+            gvar.tuple_variation_store(0, 8, PointNumberMode::Lenient),
+            Err(ParseError::BadValue)
+        );
+    }
+
+    #[test]
+    fn glyph_deltas() {
+        // A square contour where only the corners at (0, 0) and (100, 100) have deltas, and a
+        // point between them that is inferred
+        let points = [Point(0, 0), Point(50, 50), Point(100, 100), Point(100, 0)];
+        let mut w = WriteBuffer::new();
+        U16Be::write(&mut w, 1u16).unwrap(); // tupleVariationCount
+        U16Be::write(&mut w, 8u16).unwrap(); // dataOffset
+        U16Be::write(&mut w, 9u16).unwrap(); // variationDataSize
+        U16Be::write(&mut w, PRIVATE_POINT_NUMBERS).unwrap(); // tupleIndex: shared tuple 0
+        w.write_bytes(&[2, 0x01, 0, 2]).unwrap(); // point numbers 0 and 2
+        w.write_bytes(&[0x01, 10, 30]).unwrap(); // x deltas
+        w.write_bytes(&[DELTAS_ARE_ZERO | 0x01]).unwrap(); // y deltas
+        w.write_zeros(1).unwrap();
+        let glyph_data = w.into_inner();
+
+        let mut w = WriteBuffer::new();
+        U16Be::write(&mut w, 1u16).unwrap(); // majorVersion
+        U16Be::write(&mut w, 0u16).unwrap(); // minorVersion
+        U16Be::write(&mut w, 1u16).unwrap(); // axisCount
+        U16Be::write(&mut w, 1u16).unwrap(); // sharedTupleCount
+        U32Be::write(&mut w, 28u32).unwrap(); // sharedTuplesOffset
+        U16Be::write(&mut w, 1u16).unwrap(); // glyphCount
+        U16Be::write(&mut w, LONG_OFFSETS).unwrap(); // flags
+        U32Be::write(&mut w, 30u32).unwrap(); // glyphVariationDataArrayOffset
+        U32Be::write(&mut w, 0u32).unwrap();
+        U32Be::write(&mut w, glyph_data.len() as u32).unwrap();
+        U16Be::write(&mut w, 0x4000u16).unwrap(); // shared tuple 0: peak at 1.0
+        w.write_bytes(&glyph_data).unwrap();
+        let data = w.into_inner();
+
+        let gvar = ReadScope::new(&data).read_dep::<GvarTable<'_>>(1).unwrap();
+        let deltas = |coord| {
+            gvar.glyph_deltas(0, &[F2Dot14::new(coord)], &points, &[3])
+                .unwrap()
+        };
+        assert_eq!(
+            deltas(0x4000),
+            vec![(10., 0.), (20., 0.), (30., 0.), (30., 0.)]
+        );
+        assert_eq!(
+            deltas(0x2000),
+            vec![(5., 0.), (10., 0.), (15., 0.), (15., 0.)]
+        );
+        // Outside of the region
+        assert_eq!(deltas(0xC000), vec![(0., 0.); 4]);
+        // Composite glyphs don't infer deltas
+        assert_eq!(
+            gvar.glyph_deltas(0, &[F2Dot14::new(0x4000)], &points, &[])
+                .unwrap(),
+            vec![(10., 0.), (0., 0.), (30., 0.), (0., 0.)]
+        );
+    }
+}
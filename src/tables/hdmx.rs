@@ -0,0 +1,118 @@
+#![deny(missing_docs)]
+
+//! `hdmx` table parsing.
+//!
+//! The `hdmx` table holds the advance widths of glyphs, in pixels, after they have been
+//! grid-fitted at particular sizes. It allows the advances to be determined without hinting the
+//! glyphs.
+//!
+//! <https://docs.microsoft.com/en-us/typography/opentype/spec/hdmx>
+
+use std::convert::TryFrom;
+
+use crate::binary::read::{CheckIndex, ReadArray, ReadBinaryDep, ReadCtxt};
+use crate::binary::U8;
+use crate::error::ParseError;
+
+/// The `hdmx` horizontal device metrics table.
+pub struct HdmxTable<'a> {
+    /// The version of the table, always 0.
+    pub version: u16,
+    /// The device records of the table, sorted by pixel size.
+    pub records: Vec<DeviceRecord<'a>>,
+}
+
+/// The advance widths of all glyphs at a single size.
+pub struct DeviceRecord<'a> {
+    /// The size the widths apply to, in ppem.
+    pub pixel_size: u8,
+    /// The maximum width of all glyphs at this size.
+    pub max_width: u8,
+    /// The advance widths of the glyphs, indexed by glyph.
+    pub widths: ReadArray<'a, U8>,
+}
+
+impl<'a> ReadBinaryDep<'a> for HdmxTable<'a> {
+    type Args = usize; // num_glyphs
+    type HostType = Self;
+
+    fn read_dep(ctxt: &mut ReadCtxt<'a>, num_glyphs: usize) -> Result<Self, ParseError> {
+        let version = ctxt.read_u16be()?;
+        ctxt.check_version(version == 0)?;
+        let num_records = usize::try_from(ctxt.read_i16be()?)?;
+        let size_device_record = usize::try_from(ctxt.read_i32be()?)?;
+        // Each record holds the pixel size and maximum width followed by the widths
+        ctxt.check(size_device_record >= num_glyphs + 2)?;
+        let mut records = Vec::with_capacity(num_records);
+        for _ in 0..num_records {
+            let mut record_ctxt = ctxt.read_scope(size_device_record)?.ctxt();
+            let pixel_size = record_ctxt.read_u8()?;
+            let max_width = record_ctxt.read_u8()?;
+            let widths = record_ctxt.read_array::<U8>(num_glyphs)?;
+            records.push(DeviceRecord {
+                pixel_size,
+                max_width,
+                widths,
+            });
+        }
+
+        Ok(HdmxTable { version, records })
+    }
+}
+
+impl<'a> HdmxTable<'a> {
+    /// Returns the device record for `ppem`, if the table has one.
+    pub fn record(&self, ppem: u16) -> Option<&DeviceRecord<'a>> {
+        self.records
+            .iter()
+            .find(|record| u16::from(record.pixel_size) == ppem)
+    }
+
+    /// Returns the advance width in pixels of `glyph_id` at `ppem`.
+    ///
+    /// Returns `None` if the table has no record for `ppem` or `glyph_id` is out of range.
+    pub fn hdmx_advance(&self, glyph_id: u16, ppem: u16) -> Option<u8> {
+        let record = self.record(ppem)?;
+        let index = usize::from(glyph_id);
+        record.widths.check_index(index).ok()?;
+        Some(record.widths.get_item(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::read::ReadScope;
+    use crate::font_data::FontData;
+    use crate::tables::{FontTableProvider, MaxpTable};
+    use crate::tag;
+    use crate::tests::read_fixture;
+
+    #[test]
+    fn test_hdmx_advance() {
+        let buffer = read_fixture("tests/fonts/gurmukhi/Saab.ttf");
+        let scope = ReadScope::new(&buffer);
+        let font_file = scope.read::<FontData<'_>>().unwrap();
+        let provider = font_file.table_provider(0).unwrap();
+        let maxp_data = provider.read_table_data(tag::MAXP).unwrap();
+        let maxp = ReadScope::new(&maxp_data).read::<MaxpTable>().unwrap();
+        let hdmx_data = provider.read_table_data(tag::HDMX).unwrap();
+        let hdmx = ReadScope::new(&hdmx_data)
+            .read_dep::<HdmxTable<'_>>(usize::from(maxp.num_glyphs))
+            .unwrap();
+
+        assert_eq!(hdmx.records.len(), 23);
+        let record = hdmx.record(9).unwrap();
+        assert_eq!(record.max_width, 12);
+        assert_eq!(
+            record.widths.iter().take(6).collect::<Vec<_>>(),
+            vec![5, 0, 5, 5, 8, 5]
+        );
+        assert_eq!(hdmx.hdmx_advance(4, 12), Some(11));
+        assert_eq!(hdmx.hdmx_advance(4, 16), Some(14));
+        assert_eq!(hdmx.hdmx_advance(5, 75), Some(37));
+        // Out of range glyph and size without a record
+        assert_eq!(hdmx.hdmx_advance(maxp.num_glyphs, 12), None);
+        assert_eq!(hdmx.hdmx_advance(4, 1), None);
+    }
+}
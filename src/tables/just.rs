@@ -0,0 +1,294 @@
+#![deny(missing_docs)]
+
+//! `just` table parsing.
+//!
+//! The `just` table is an Apple Advanced Typography table that describes how glyphs may grow or
+//! shrink when justifying a line. Glyphs are assigned justification classes by a state table, and
+//! each class has limits on how much it may grow or shrink along with a priority that determines
+//! the order in which classes are adjusted.
+//!
+//! The width delta clusters, which hold the limits and priorities, are parsed. The justification
+//! class and postcompensation state tables are exposed as raw data.
+//!
+//! <https://developer.apple.com/fonts/TrueType-Reference-Manual/RM06/Chap6just.html>
+
+use bitflags::bitflags;
+
+use crate::binary::read::{ReadBinary, ReadCtxt, ReadScope};
+use crate::error::ParseError;
+
+/// Size of a width delta pair: a class, four limits, and two sets of flags.
+const WIDTH_DELTA_PAIR_SIZE: usize = 4 + 4 * 4 + 2 * 2;
+
+bitflags! {
+    /// Flags controlling how a justification class grows or shrinks.
+    pub struct JustificationFlags: u16 {
+        /// Use the priority in these flags, overriding the glyph's priority.
+        const OVERRIDE_PRIORITY = 0x8000;
+        /// Use the limits in this entry, overriding the glyph's limits.
+        const OVERRIDE_LIMITS = 0x4000;
+        /// Use the unlimited flag in these flags, overriding the glyph's.
+        const OVERRIDE_UNLIMITED = 0x2000;
+        /// The glyph may grow or shrink without limit once lower priorities are exhausted.
+        const UNLIMITED = 0x1000;
+        /// Mask for the justification priority.
+        const PRIORITY_MASK = 0x0003;
+    }
+}
+
+/// The order in which justification classes are adjusted, highest priority first.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum JustificationPriority {
+    /// Kashidas are adjusted first.
+    Kashida,
+    /// Then whitespace.
+    Whitespace,
+    /// Then the spacing between characters.
+    InterCharacter,
+    /// Glyphs with the null priority are adjusted last.
+    Null,
+}
+
+/// The `just` justification table.
+pub struct JustTable<'a> {
+    /// The version of the table.
+    pub version: u32,
+    /// Justification data for horizontal text.
+    pub horizontal: Option<Justification<'a>>,
+    /// Justification data for vertical text.
+    pub vertical: Option<Justification<'a>>,
+}
+
+/// Justification data for one text direction.
+pub struct Justification<'a> {
+    /// The justification class state table, from its start to the end of the `just` table.
+    pub class_table: Option<&'a [u8]>,
+    /// The width delta clusters, in the order they appear in the table.
+    pub width_delta_clusters: Vec<WidthDeltaCluster>,
+    /// The postcompensation table, from its start to the end of the `just` table.
+    pub postcompensation_table: Option<&'a [u8]>,
+}
+
+/// A group of width delta pairs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WidthDeltaCluster {
+    /// Offset of the cluster from the start of the width delta cluster table.
+    ///
+    /// The actions of the justification class state table refer to clusters by this offset.
+    pub offset: usize,
+    /// The growing and shrinking behaviour of each justification class in the cluster.
+    pub pairs: Vec<WidthDeltaPair>,
+}
+
+/// The growing and shrinking behaviour of a justification class.
+///
+/// Limits are 16.16 fixed-point values in ems.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WidthDeltaPair {
+    /// The justification class.
+    pub just_class: u32,
+    /// How much the glyph may grow on the left or top side.
+    pub before_grow_limit: i32,
+    /// How much the glyph may shrink on the left or top side.
+    pub before_shrink_limit: i32,
+    /// How much the glyph may grow on the right or bottom side.
+    pub after_grow_limit: i32,
+    /// How much the glyph may shrink on the right or bottom side.
+    pub after_shrink_limit: i32,
+    /// Flags that apply when the line is growing.
+    pub grow_flags: JustificationFlags,
+    /// Flags that apply when the line is shrinking.
+    pub shrink_flags: JustificationFlags,
+}
+
+impl JustificationFlags {
+    /// The justification priority held in these flags.
+    pub fn priority(self) -> JustificationPriority {
+        match (self & JustificationFlags::PRIORITY_MASK).bits() {
+            0 => JustificationPriority::Kashida,
+            1 => JustificationPriority::Whitespace,
+            2 => JustificationPriority::InterCharacter,
+            _ => JustificationPriority::Null,
+        }
+    }
+}
+
+impl<'a> ReadBinary<'a> for JustTable<'a> {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let scope = ctxt.scope();
+        let version = ctxt.read_u32be()?;
+        ctxt.check(version >> 16 == 1)?;
+        let format = ctxt.read_u16be()?;
+        ctxt.check(format == 0)?;
+        let horizontal_offset = usize::from(ctxt.read_u16be()?);
+        let vertical_offset = usize::from(ctxt.read_u16be()?);
+
+        let horizontal = read_justification(&scope, horizontal_offset)?;
+        let vertical = read_justification(&scope, vertical_offset)?;
+
+        Ok(JustTable {
+            version,
+            horizontal,
+            vertical,
+        })
+    }
+}
+
+fn read_justification<'a>(
+    scope: &ReadScope<'a>,
+    offset: usize,
+) -> Result<Option<Justification<'a>>, ParseError> {
+    if offset == 0 {
+        return Ok(None);
+    }
+
+    let mut ctxt = scope.offset(offset).ctxt();
+    let class_table_offset = usize::from(ctxt.read_u16be()?);
+    let wdc_table_offset = usize::from(ctxt.read_u16be()?);
+    let pc_table_offset = usize::from(ctxt.read_u16be()?);
+    let table_data = |offset| {
+        if offset == 0 {
+            None
+        } else {
+            Some(scope.offset(offset).data())
+        }
+    };
+
+    let width_delta_clusters = if wdc_table_offset == 0 {
+        Vec::new()
+    } else {
+        // The clusters run until the next table or the end of the data
+        let end = [class_table_offset, pc_table_offset]
+            .iter()
+            .copied()
+            .filter(|&offset| offset > wdc_table_offset)
+            .min()
+            .unwrap_or_else(|| scope.data().len());
+        let wdc_scope =
+            scope.offset_length(wdc_table_offset, end.saturating_sub(wdc_table_offset))?;
+        read_width_delta_clusters(wdc_scope)?
+    };
+
+    Ok(Some(Justification {
+        class_table: table_data(class_table_offset),
+        width_delta_clusters,
+        postcompensation_table: table_data(pc_table_offset),
+    }))
+}
+
+fn read_width_delta_clusters(scope: ReadScope<'_>) -> Result<Vec<WidthDeltaCluster>, ParseError> {
+    let data_len = scope.data().len();
+    let mut ctxt = scope.ctxt();
+    let mut clusters = Vec::new();
+    let mut offset = 0;
+    while data_len - offset >= 4 {
+        let count = ctxt.read_u32be()?;
+        if count == 0 {
+            // Padding at the end of the table
+            break;
+        }
+        let pairs = (0..count)
+            .map(|_| read_width_delta_pair(&mut ctxt))
+            .collect::<Result<Vec<_>, _>>()?;
+        let len = 4 + pairs.len() * WIDTH_DELTA_PAIR_SIZE;
+        clusters.push(WidthDeltaCluster { offset, pairs });
+        offset += len;
+    }
+    Ok(clusters)
+}
+
+fn read_width_delta_pair(ctxt: &mut ReadCtxt<'_>) -> Result<WidthDeltaPair, ParseError> {
+    let just_class = ctxt.read_u32be()?;
+    let before_grow_limit = ctxt.read_i32be()?;
+    let before_shrink_limit = ctxt.read_i32be()?;
+    let after_grow_limit = ctxt.read_i32be()?;
+    let after_shrink_limit = ctxt.read_i32be()?;
+    let grow_flags = JustificationFlags::from_bits_truncate(ctxt.read_u16be()?);
+    let shrink_flags = JustificationFlags::from_bits_truncate(ctxt.read_u16be()?);
+    Ok(WidthDeltaPair {
+        just_class,
+        before_grow_limit,
+        before_shrink_limit,
+        after_grow_limit,
+        after_shrink_limit,
+        grow_flags,
+        shrink_flags,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::write::{WriteBinary, WriteBuffer};
+    use crate::binary::{I32Be, U16Be, U32Be};
+
+    fn write_pair(w: &mut WriteBuffer, just_class: u32, limit: i32, flags: u16) {
+        U32Be::write(w, just_class).unwrap();
+        for _ in 0..4 {
+            I32Be::write(w, limit).unwrap();
+        }
+        U16Be::write(w, flags).unwrap();
+        U16Be::write(w, flags).unwrap();
+    }
+
+    #[test]
+    fn read_just_table() {
+        let mut w = WriteBuffer::new();
+        U32Be::write(&mut w, 0x0001_0000u32).unwrap(); // version
+        U16Be::write(&mut w, 0u16).unwrap(); // format
+        U16Be::write(&mut w, 10u16).unwrap(); // horizOffset
+        U16Be::write(&mut w, 0u16).unwrap(); // vertOffset
+
+        // Justification header at 10, clusters at 16
+        U16Be::write(&mut w, 0u16).unwrap(); // justClassTableOffset
+        U16Be::write(&mut w, 16u16).unwrap(); // wdcTableOffset
+        U16Be::write(&mut w, 0u16).unwrap(); // pcTableOffset
+
+        // Cluster with kashida and whitespace classes
+        U32Be::write(&mut w, 2u32).unwrap();
+        write_pair(&mut w, 1, 0x0001_0000, 0x0000);
+        write_pair(&mut w, 2, 0x0000_8000, 0x1001);
+        // Cluster with an unlimited inter-character class
+        U32Be::write(&mut w, 1u32).unwrap();
+        write_pair(&mut w, 3, 0, 0x3002);
+        let data = w.into_inner();
+
+        let just = ReadScope::new(&data).read::<JustTable<'_>>().unwrap();
+        assert!(just.vertical.is_none());
+        let horizontal = just.horizontal.unwrap();
+        assert!(horizontal.class_table.is_none());
+        assert!(horizontal.postcompensation_table.is_none());
+
+        let clusters = &horizontal.width_delta_clusters;
+        assert_eq!(clusters.len(), 2);
+        assert_eq!(clusters[0].offset, 0);
+        assert_eq!(clusters[1].offset, 52);
+
+        let pairs = &clusters[0].pairs;
+        assert_eq!(pairs[0].just_class, 1);
+        assert_eq!(pairs[0].after_grow_limit, 0x0001_0000);
+        assert_eq!(
+            pairs[0].grow_flags.priority(),
+            JustificationPriority::Kashida
+        );
+        assert_eq!(
+            pairs[1].shrink_flags.priority(),
+            JustificationPriority::Whitespace
+        );
+        assert!(pairs[1]
+            .shrink_flags
+            .contains(JustificationFlags::UNLIMITED));
+
+        let pair = &clusters[1].pairs[0];
+        assert_eq!(pair.just_class, 3);
+        assert_eq!(
+            pair.grow_flags.priority(),
+            JustificationPriority::InterCharacter
+        );
+        assert!(pair
+            .grow_flags
+            .contains(JustificationFlags::OVERRIDE_UNLIMITED | JustificationFlags::UNLIMITED));
+    }
+}
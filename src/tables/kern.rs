@@ -0,0 +1,624 @@
+#![deny(missing_docs)]
+
+//! `kern` table parsing.
+//!
+//! The `kern` table holds the legacy kerning data of a font. Fonts with a `GPOS` table should
+//! use the `kern` feature instead, but several fonts only carry kerning in this table.
+//!
+//! Both the OpenType and Apple variants of the table header are supported. Kerning values can be
+//! looked up in format 0, 2, and 3 subtables, which hold pairs of glyphs. Format 1 subtables hold a
+//! state machine that kerns glyphs based on their context, so they are only applied to whole glyph
+//! sequences by [KernTable::kerning_adjustments](struct.KernTable.html#method.kerning_adjustments).
+//!
+//! <https://docs.microsoft.com/en-us/typography/opentype/spec/kern>
+//! <https://developer.apple.com/fonts/TrueType-Reference-Manual/RM06/Chap6kern.html>
+
+use std::convert::TryFrom;
+
+use bitflags::bitflags;
+
+use crate::binary::read::{CheckIndex, ReadArray, ReadBinary, ReadCtxt, ReadFrom, ReadScope};
+use crate::binary::{I16Be, U16Be, U8};
+use crate::error::ParseError;
+use crate::tables::aat;
+
+/// Flag of a format 1 entry to push the current glyph onto the kerning stack.
+const PUSH: u16 = 0x8000;
+/// Flag of a format 1 entry to process the current glyph again.
+const DONT_ADVANCE: u16 = 0x4000;
+/// Mask of a format 1 entry for the offset of its kerning values.
+const VALUE_OFFSET_MASK: u16 = 0x3FFF;
+/// The maximum depth of the kerning stack of format 1 subtables.
+pub(crate) const MAX_KERNING_STACK: usize = 8;
+
+/// The `kern` table.
+pub struct KernTable<'a> {
+    /// The subtables of the table, in the order they are applied.
+    pub subtables: Vec<KernSubtable<'a>>,
+}
+
+/// A subtable of the `kern` table.
+pub struct KernSubtable<'a> {
+    /// Describes how the kerning values of the subtable are applied.
+    pub coverage: KernCoverage,
+    /// The kerning data of the subtable.
+    pub data: KernSubtableData<'a>,
+}
+
+bitflags! {
+    /// The coverage of a `kern` subtable.
+    ///
+    /// The flags are normalised from the OpenType and Apple variants of the table.
+    pub struct KernCoverage: u8 {
+        /// The subtable holds horizontal kerning values.
+        const HORIZONTAL = 1 << 0;
+        /// The subtable holds minimum values rather than kerning values.
+        const MINIMUM = 1 << 1;
+        /// The kerning is perpendicular to the flow of the text.
+        const CROSS_STREAM = 1 << 2;
+        /// The values of the subtable replace the accumulated value rather than adding to it.
+        const OVERRIDE = 1 << 3;
+        /// The subtable holds kerning values for a variation of the font.
+        const VARIATION = 1 << 4;
+    }
+}
+
+/// The kerning data of a `kern` subtable.
+pub enum KernSubtableData<'a> {
+    /// Ordered list of kerning pairs.
+    Format0 {
+        /// The kerning pairs, sorted by left then right glyph.
+        pairs: ReadArray<'a, KernPair>,
+    },
+    /// State table for contextual kerning.
+    Format1 {
+        /// The scope of the state table, which follows the subtable header.
+        scope: ReadScope<'a>,
+    },
+    /// Two-dimensional array of class-based kerning values.
+    Format2 {
+        /// The scope of the subtable, which the offsets are relative to.
+        scope: ReadScope<'a>,
+        /// Offset to the left-hand class table.
+        left_class_offset: u16,
+        /// Offset to the right-hand class table.
+        right_class_offset: u16,
+        /// Offset to the kerning array.
+        array_offset: u16,
+    },
+    /// Compact two-dimensional array of class-based kerning values.
+    Format3 {
+        /// The kerning values, indexed by `kern_indices`.
+        kern_values: ReadArray<'a, I16Be>,
+        /// The left-hand class of each glyph.
+        left_classes: ReadArray<'a, U8>,
+        /// The right-hand class of each glyph.
+        right_classes: ReadArray<'a, U8>,
+        /// The index into `kern_values` of each pair of classes, by left then right class.
+        kern_indices: ReadArray<'a, U8>,
+        /// The number of right-hand classes.
+        right_class_count: u8,
+    },
+    /// A subtable format that is not supported.
+    Unsupported {
+        /// The format of the subtable.
+        format: u8,
+    },
+}
+
+/// A kerning pair of a format 0 `kern` subtable.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct KernPair {
+    /// The glyph index of the left-hand glyph.
+    pub left: u16,
+    /// The glyph index of the right-hand glyph.
+    pub right: u16,
+    /// The kerning value in font units.
+    pub value: i16,
+}
+
+impl<'a> ReadBinary<'a> for KernTable<'a> {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let version = ctxt.read_u16be()?;
+        let (is_apple, num_tables) = match version {
+            0 => (false, u32::from(ctxt.read_u16be()?)),
+            1 => {
+                // Apple tables have a 32-bit 1.0 version
+                let minor_version = ctxt.read_u16be()?;
+                ctxt.check_version(minor_version == 0)?;
+                (true, ctxt.read_u32be()?)
+            }
+            _ => return Err(ParseError::BadVersion),
+        };
+
+        let mut subtables = Vec::new();
+        for _ in 0..num_tables {
+            let scope = ctxt.scope();
+            let (length, coverage, format, header_size) = if is_apple {
+                let length = usize::try_from(ctxt.read_u32be()?)?;
+                let coverage = ctxt.read_u8()?;
+                let format = ctxt.read_u8()?;
+                let _tuple_index = ctxt.read_u16be()?;
+                let mut flags = KernCoverage::empty();
+                flags.set(KernCoverage::HORIZONTAL, coverage & 0x80 == 0);
+                flags.set(KernCoverage::CROSS_STREAM, coverage & 0x40 != 0);
+                flags.set(KernCoverage::VARIATION, coverage & 0x20 != 0);
+                (length, flags, format, 8)
+            } else {
+                let _version = ctxt.read_u16be()?;
+                let length = usize::from(ctxt.read_u16be()?);
+                let coverage = ctxt.read_u16be()?;
+                let flags = KernCoverage::from_bits_truncate((coverage & 0xF) as u8);
+                (length, flags, (coverage >> 8) as u8, 6)
+            };
+
+            let data = match format {
+                0 => {
+                    let n_pairs = ctxt.read_u16be()?;
+                    let _search_range = ctxt.read_u16be()?;
+                    let _entry_selector = ctxt.read_u16be()?;
+                    let _range_shift = ctxt.read_u16be()?;
+                    let pairs = ctxt.read_array::<KernPair>(usize::from(n_pairs))?;
+                    KernSubtableData::Format0 { pairs }
+                }
+                1 => KernSubtableData::Format1 {
+                    scope: ctxt.scope(),
+                },
+                2 => {
+                    let _row_width = ctxt.read_u16be()?;
+                    let left_class_offset = ctxt.read_u16be()?;
+                    let right_class_offset = ctxt.read_u16be()?;
+                    let array_offset = ctxt.read_u16be()?;
+                    KernSubtableData::Format2 {
+                        scope: scope.clone(),
+                        left_class_offset,
+                        right_class_offset,
+                        array_offset,
+                    }
+                }
+                3 => {
+                    let glyph_count = ctxt.read_u16be()?;
+                    let kern_value_count = ctxt.read_u8()?;
+                    let left_class_count = ctxt.read_u8()?;
+                    let right_class_count = ctxt.read_u8()?;
+                    let _flags = ctxt.read_u8()?;
+                    let kern_values = ctxt.read_array::<I16Be>(usize::from(kern_value_count))?;
+                    let left_classes = ctxt.read_array::<U8>(usize::from(glyph_count))?;
+                    let right_classes = ctxt.read_array::<U8>(usize::from(glyph_count))?;
+                    let kern_indices = ctxt.read_array::<U8>(
+                        usize::from(left_class_count)
+                            .checked_mul(usize::from(right_class_count))
+                            .ok_or(ParseError::LimitExceeded)?,
+                    )?;
+                    KernSubtableData::Format3 {
+                        kern_values,
+                        left_classes,
+                        right_classes,
+                        kern_indices,
+                        right_class_count,
+                    }
+                }
+                _ => KernSubtableData::Unsupported { format },
+            };
+            subtables.push(KernSubtable { coverage, data });
+
+            // The length of large format 0 subtables overflows the 16-bit field in OpenType
+            // tables, so the last subtable is not skipped past.
+            if subtables.len() < num_tables as usize {
+                let length = length.max(header_size);
+                *ctxt = scope.offset(length).ctxt();
+            }
+        }
+
+        Ok(KernTable { subtables })
+    }
+}
+
+impl<'a> ReadFrom<'a> for KernPair {
+    type ReadType = (U16Be, U16Be, I16Be);
+
+    fn from((left, right, value): (u16, u16, i16)) -> Self {
+        KernPair { left, right, value }
+    }
+}
+
+impl<'a> KernTable<'a> {
+    /// Returns the horizontal kerning value of the pair of glyphs `left` and `right`.
+    ///
+    /// Values from all applicable subtables are accumulated. Subtables holding minimum values,
+    /// cross-stream kerning, or kerning for font variations are ignored. Returns `None` if no
+    /// subtable has a value for the pair.
+    pub fn horizontal_kerning(&self, left: u16, right: u16) -> Option<i16> {
+        let mut kerning = None;
+        for subtable in self.horizontal_subtables() {
+            if let Some(value) = subtable.kerning(left, right) {
+                kerning = if subtable.coverage.contains(KernCoverage::OVERRIDE) {
+                    Some(value)
+                } else {
+                    Some(kerning.unwrap_or(0i16).saturating_add(value))
+                };
+            }
+        }
+        kerning
+    }
+
+    /// Returns the horizontal kerning adjustments of the sequence of glyphs `glyphs`.
+    ///
+    /// The result holds a value for each glyph, which is the distance that glyph, and all the
+    /// glyphs that follow it, are moved along the baseline. Pair kerning of a glyph with the glyph
+    /// before it is included, as is contextual kerning from format 1 subtables. The same
+    /// subtables as [horizontal_kerning](#method.horizontal_kerning) are applied.
+    pub fn kerning_adjustments(&self, glyphs: &[u16]) -> Result<Vec<i16>, ParseError> {
+        let mut adjustments = vec![None; glyphs.len()];
+        for subtable in self.horizontal_subtables() {
+            let subtable_adjustments = match subtable.data {
+                KernSubtableData::Format1 { ref scope } => contextual_kerning(scope, glyphs)?,
+                _ => pair_adjustments(glyphs, |left, right| Ok(subtable.kerning(left, right)))?,
+            };
+            let is_override = subtable.coverage.contains(KernCoverage::OVERRIDE);
+            combine_adjustments(&mut adjustments, &subtable_adjustments, is_override);
+        }
+        Ok(adjustments
+            .into_iter()
+            .map(|value| value.unwrap_or(0))
+            .collect())
+    }
+
+    fn horizontal_subtables(&self) -> impl Iterator<Item = &KernSubtable<'a>> {
+        let ignored = KernCoverage::MINIMUM | KernCoverage::CROSS_STREAM | KernCoverage::VARIATION;
+        self.subtables.iter().filter(move |subtable| {
+            subtable.coverage.contains(KernCoverage::HORIZONTAL)
+                && !subtable.coverage.intersects(ignored)
+        })
+    }
+}
+
+impl<'a> KernSubtable<'a> {
+    /// Returns the kerning value of the pair of glyphs `left` and `right` in this subtable.
+    ///
+    /// Format 1 subtables do not hold kerning values for pairs, so `None` is returned for them.
+    pub fn kerning(&self, left: u16, right: u16) -> Option<i16> {
+        match self.data {
+            KernSubtableData::Format0 { ref pairs } => {
+                let (mut lo, mut hi) = (0, pairs.len());
+                while lo < hi {
+                    let mid = (lo + hi) / 2;
+                    let pair = pairs.get_item(mid);
+                    match (pair.left, pair.right).cmp(&(left, right)) {
+                        std::cmp::Ordering::Less => lo = mid + 1,
+                        std::cmp::Ordering::Greater => hi = mid,
+                        std::cmp::Ordering::Equal => return Some(pair.value),
+                    }
+                }
+                None
+            }
+            KernSubtableData::Format2 {
+                ref scope,
+                left_class_offset,
+                right_class_offset,
+                array_offset,
+            } => {
+                // Class values are offsets from the start of the subtable, with left-hand values
+                // pre-multiplied by the row width and including the offset of the array.
+                let left_class = class_value(scope, left_class_offset, left)?;
+                let right_class = class_value(scope, right_class_offset, right)?;
+                let offset = usize::from(left_class) + usize::from(right_class);
+                if offset < usize::from(array_offset) {
+                    return None;
+                }
+                scope.offset(offset).read::<I16Be>().ok()
+            }
+            KernSubtableData::Format3 {
+                ref kern_values,
+                ref left_classes,
+                ref right_classes,
+                ref kern_indices,
+                right_class_count,
+            } => {
+                let (left, right) = (usize::from(left), usize::from(right));
+                left_classes.check_index(left).ok()?;
+                right_classes.check_index(right).ok()?;
+                let left_class = usize::from(left_classes.get_item(left));
+                let right_class = usize::from(right_classes.get_item(right));
+                if right_class >= usize::from(right_class_count) {
+                    return None;
+                }
+                let index = left_class * usize::from(right_class_count) + right_class;
+                kern_indices.check_index(index).ok()?;
+                let value_index = usize::from(kern_indices.get_item(index));
+                kern_values.check_index(value_index).ok()?;
+                Some(kern_values.get_item(value_index))
+            }
+            KernSubtableData::Format1 { .. } | KernSubtableData::Unsupported { .. } => None,
+        }
+    }
+}
+
+/// Collects the kerning of each glyph in `glyphs` with the glyph before it.
+pub(crate) fn pair_adjustments<F>(
+    glyphs: &[u16],
+    mut kerning: F,
+) -> Result<Vec<Option<i16>>, ParseError>
+where
+    F: FnMut(u16, u16) -> Result<Option<i16>, ParseError>,
+{
+    let mut adjustments = vec![None; glyphs.len()];
+    for (i, pair) in glyphs.windows(2).enumerate() {
+        adjustments[i + 1] = kerning(pair[0], pair[1])?;
+    }
+    Ok(adjustments)
+}
+
+/// Accumulates the adjustments of a subtable into the adjustments of the previous subtables.
+pub(crate) fn combine_adjustments(
+    adjustments: &mut [Option<i16>],
+    subtable_adjustments: &[Option<i16>],
+    is_override: bool,
+) {
+    for (adjustment, &value) in adjustments.iter_mut().zip(subtable_adjustments) {
+        if let Some(value) = value {
+            *adjustment = if is_override {
+                Some(value)
+            } else {
+                Some(adjustment.unwrap_or(0).saturating_add(value))
+            };
+        }
+    }
+}
+
+/// Adds `value` to the adjustment of the glyph at `index`, if it is within the glyph sequence.
+pub(crate) fn add_adjustment(adjustments: &mut [Option<i16>], index: usize, value: i16) {
+    if let Some(adjustment) = adjustments.get_mut(index) {
+        *adjustment = Some(adjustment.unwrap_or(0).saturating_add(value));
+    }
+}
+
+/// Runs the state machine of a format 1 subtable over `glyphs`.
+///
+/// Glyphs are pushed onto the kerning stack by entries with the push flag. Entries with a value
+/// offset pop glyphs off the stack, applying a kerning value to each, until a value with its lowest
+/// bit set is reached.
+fn contextual_kerning(
+    scope: &ReadScope<'_>,
+    glyphs: &[u16],
+) -> Result<Vec<Option<i16>>, ParseError> {
+    let mut ctxt = scope.ctxt();
+    let state_size = usize::from(ctxt.read_u16be()?);
+    let class_table_offset = usize::from(ctxt.read_u16be()?);
+    let state_array_offset = usize::from(ctxt.read_u16be()?);
+    let entry_table_offset = usize::from(ctxt.read_u16be()?);
+    let _value_table_offset = ctxt.read_u16be()?;
+    ctxt.check(state_size >= 4)?;
+
+    let mut class_ctxt = scope.offset(class_table_offset).ctxt();
+    let first_glyph = class_ctxt.read_u16be()?;
+    let n_glyphs = class_ctxt.read_u16be()?;
+    let classes = class_ctxt.read_array::<U8>(usize::from(n_glyphs))?;
+    let class_of = |glyph: u16| {
+        if glyph == aat::DELETED_GLYPH {
+            return aat::CLASS_DELETED_GLYPH;
+        }
+        match glyph.checked_sub(first_glyph) {
+            Some(index) if index < n_glyphs => u16::from(classes.get_item(usize::from(index))),
+            _ => aat::CLASS_OUT_OF_BOUNDS,
+        }
+    };
+
+    let mut adjustments = vec![None; glyphs.len()];
+    let mut stack = Vec::with_capacity(MAX_KERNING_STACK);
+    let mut state = usize::from(aat::STATE_START_OF_TEXT);
+    let mut index = 0;
+    let mut dont_advance_count = 0;
+    loop {
+        let class = match glyphs.get(index) {
+            Some(&glyph) => usize::from(class_of(glyph)),
+            None => usize::from(aat::CLASS_END_OF_TEXT),
+        };
+        let class = if class < state_size {
+            class
+        } else {
+            usize::from(aat::CLASS_OUT_OF_BOUNDS)
+        };
+        let row = scope.offset(state_array_offset + state * state_size);
+        let entry_index = usize::from(row.offset(class).ctxt().read_u8()?);
+        let mut entry = scope.offset(entry_table_offset + entry_index * 4).ctxt();
+        let new_state = usize::from(entry.read_u16be()?);
+        let flags = entry.read_u16be()?;
+
+        if flags & PUSH != 0 {
+            if stack.len() < MAX_KERNING_STACK {
+                stack.push(index);
+            } else {
+                stack.clear();
+            }
+        }
+
+        let value_offset = usize::from(flags & VALUE_OFFSET_MASK);
+        if value_offset != 0 {
+            let mut values = scope.offset(value_offset).ctxt();
+            while let Some(kerned_index) = stack.pop() {
+                let value = values.read_i16be()?;
+                add_adjustment(&mut adjustments, kerned_index, value & !1);
+                if value & 1 != 0 {
+                    break;
+                }
+            }
+        }
+
+        // New states are offsets to the state's row in the state array
+        state = new_state
+            .checked_sub(state_array_offset)
+            .ok_or(ParseError::BadOffset)?
+            / state_size;
+
+        if index >= glyphs.len() {
+            break;
+        }
+        if flags & DONT_ADVANCE == 0 || dont_advance_count >= aat::MAX_DONT_ADVANCE {
+            index += 1;
+            dont_advance_count = 0;
+        } else {
+            dont_advance_count += 1;
+        }
+    }
+
+    Ok(adjustments)
+}
+
+fn class_value(scope: &ReadScope<'_>, class_table_offset: u16, glyph: u16) -> Option<u16> {
+    let mut ctxt = scope.offset(usize::from(class_table_offset)).ctxt();
+    let first_glyph = ctxt.read_u16be().ok()?;
+    let n_glyphs = ctxt.read_u16be().ok()?;
+    let index = glyph.checked_sub(first_glyph)?;
+    if index >= n_glyphs {
+        return None;
+    }
+    let values = ctxt.read_array::<U16Be>(usize::from(n_glyphs)).ok()?;
+    Some(values.get_item(usize::from(index)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::write::{WriteBinary, WriteBuffer};
+    use crate::binary::U32Be;
+    use crate::font_data::FontData;
+    use crate::tables::FontTableProvider;
+    use crate::tag;
+    use crate::tests::read_fixture;
+
+    #[test]
+    fn test_format0() {
+        let buffer = read_fixture("tests/fonts/arabic/ae_Arab.ttf");
+        let scope = ReadScope::new(&buffer);
+        let font_file = scope.read::<FontData<'_>>().unwrap();
+        let provider = font_file.table_provider(0).unwrap();
+        let kern_data = provider.read_table_data(tag::KERN).unwrap();
+        let kern = ReadScope::new(&kern_data).read::<KernTable<'_>>().unwrap();
+
+        assert_eq!(kern.subtables.len(), 1);
+        assert_eq!(kern.subtables[0].coverage, KernCoverage::HORIZONTAL);
+        assert_eq!(kern.horizontal_kerning(18, 57), Some(-64));
+        assert_eq!(kern.horizontal_kerning(62, 78), Some(-32));
+        assert_eq!(kern.horizontal_kerning(321, 19), Some(-32));
+        assert_eq!(kern.horizontal_kerning(78, 62), None);
+    }
+
+    #[test]
+    fn test_format2() {
+        let mut w = WriteBuffer::new();
+        // Header: version, nTables
+        U16Be::write(&mut w, 0u16).unwrap();
+        U16Be::write(&mut w, 1u16).unwrap();
+        // Subtable header: version, length, coverage (format 2, horizontal)
+        U16Be::write(&mut w, 0u16).unwrap();
+        U16Be::write(&mut w, 36u16).unwrap();
+        U16Be::write(&mut w, 0x0201u16).unwrap();
+        // rowWidth, leftClassTable, rightClassTable, kerningArray
+        U16Be::write(&mut w, 4u16).unwrap();
+        U16Be::write(&mut w, 14u16).unwrap();
+        U16Be::write(&mut w, 22u16).unwrap();
+        U16Be::write(&mut w, 28u16).unwrap();
+        // Left class table: glyphs 10 and 11, classes 0 and 1
+        U16Be::write(&mut w, 10u16).unwrap();
+        U16Be::write(&mut w, 2u16).unwrap();
+        U16Be::write(&mut w, 28u16).unwrap();
+        U16Be::write(&mut w, 32u16).unwrap();
+        // Right class table: glyph 20, class 1
+        U16Be::write(&mut w, 20u16).unwrap();
+        U16Be::write(&mut w, 1u16).unwrap();
+        U16Be::write(&mut w, 2u16).unwrap();
+        // Kerning array, 2x2
+        I16Be::write(&mut w, 0i16).unwrap();
+        I16Be::write(&mut w, -10i16).unwrap();
+        I16Be::write(&mut w, 0i16).unwrap();
+        I16Be::write(&mut w, -50i16).unwrap();
+        let data = w.into_inner();
+
+        let kern = ReadScope::new(&data).read::<KernTable<'_>>().unwrap();
+        assert_eq!(kern.horizontal_kerning(10, 20), Some(-10));
+        assert_eq!(kern.horizontal_kerning(11, 20), Some(-50));
+        assert_eq!(kern.horizontal_kerning(12, 20), None);
+        assert_eq!(kern.horizontal_kerning(11, 21), None);
+    }
+
+    #[test]
+    fn test_format1() {
+        let mut w = WriteBuffer::new();
+        // Apple header: version 1.0, nTables
+        U16Be::write(&mut w, 1u16).unwrap();
+        U16Be::write(&mut w, 0u16).unwrap();
+        U32Be::write(&mut w, 1u32).unwrap();
+        // Subtable header: length, coverage (horizontal), format, tupleIndex
+        U32Be::write(&mut w, 68u32).unwrap();
+        U8::write(&mut w, 0u8).unwrap();
+        U8::write(&mut w, 1u8).unwrap();
+        U16Be::write(&mut w, 0u16).unwrap();
+        // State table header: stateSize, classTable, stateArray, entryTable, valueTable
+        for &value in &[6u16, 10, 26, 44, 56] {
+            U16Be::write(&mut w, value).unwrap();
+        }
+        // Class table: glyph 10 is class 4, glyph 20 is class 5, padded to an even length
+        U16Be::write(&mut w, 10u16).unwrap();
+        U16Be::write(&mut w, 11u16).unwrap();
+        for &class in &[4u8, 1, 1, 1, 1, 1, 1, 1, 1, 1, 5, 0] {
+            U8::write(&mut w, class).unwrap();
+        }
+        // State array: start of text, start of line, and after glyph 10
+        for &entry in &[0u8, 0, 0, 0, 1, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 1, 2] {
+            U8::write(&mut w, entry).unwrap();
+        }
+        // Entry table: new state, flags. Entry 2 pushes glyph 20 and kerns the stack.
+        for &value in &[26u16, 0, 38, PUSH, 26, PUSH | 56] {
+            U16Be::write(&mut w, value).unwrap();
+        }
+        // Kerning values: -40 for glyph 20, then 0 for glyph 10, ending the list
+        I16Be::write(&mut w, -40i16).unwrap();
+        I16Be::write(&mut w, 1i16).unwrap();
+        let data = w.into_inner();
+
+        let kern = ReadScope::new(&data).read::<KernTable<'_>>().unwrap();
+        assert_eq!(kern.horizontal_kerning(10, 20), None);
+        assert_eq!(
+            kern.kerning_adjustments(&[10, 20, 5, 10, 20, 20]).unwrap(),
+            vec![0, -40, 0, 0, -40, 0]
+        );
+        assert_eq!(kern.kerning_adjustments(&[]).unwrap(), Vec::<i16>::new());
+    }
+
+    #[test]
+    fn test_format3() {
+        let mut w = WriteBuffer::new();
+        // Header: version, nTables
+        U16Be::write(&mut w, 0u16).unwrap();
+        U16Be::write(&mut w, 1u16).unwrap();
+        // Subtable header: version, length, coverage (format 3, horizontal)
+        U16Be::write(&mut w, 0u16).unwrap();
+        U16Be::write(&mut w, 26u16).unwrap();
+        U16Be::write(&mut w, 0x0301u16).unwrap();
+        // glyphCount, kernValueCount, leftClassCount, rightClassCount, flags
+        U16Be::write(&mut w, 3u16).unwrap();
+        for &value in &[2u8, 2, 2, 0] {
+            U8::write(&mut w, value).unwrap();
+        }
+        // Kerning values
+        I16Be::write(&mut w, 0i16).unwrap();
+        I16Be::write(&mut w, -30i16).unwrap();
+        // Left classes, right classes, and kerning indices
+        for &value in &[0u8, 1, 0, 0, 0, 1, 0, 0, 0, 1] {
+            U8::write(&mut w, value).unwrap();
+        }
+        let data = w.into_inner();
+
+        let kern = ReadScope::new(&data).read::<KernTable<'_>>().unwrap();
+        assert_eq!(kern.horizontal_kerning(1, 2), Some(-30));
+        assert_eq!(kern.horizontal_kerning(0, 2), Some(0));
+        assert_eq!(kern.horizontal_kerning(1, 3), None);
+        assert_eq!(
+            kern.kerning_adjustments(&[2, 1, 2]).unwrap(),
+            vec![0, 0, -30]
+        );
+    }
+}
@@ -0,0 +1,413 @@
+#![deny(missing_docs)]
+
+//! `kerx` table parsing.
+//!
+//! The `kerx` table is the Apple Advanced Typography extended kerning table. It supersedes the
+//! `kern` table in fonts shipped with macOS, and is often the only source of kerning in them.
+//!
+//! Kerning values can be looked up in format 0 and format 2 subtables, which hold pairs of
+//! glyphs. Format 1 subtables hold a state machine that kerns glyphs based on their context, so
+//! they are only applied to whole glyph sequences by
+//! [KerxTable::kerning_adjustments](struct.KerxTable.html#method.kerning_adjustments). The
+//! control point and anchor attachment subtables of format 4, and the format 6 subtables, are
+//! skipped.
+//!
+//! <https://developer.apple.com/fonts/TrueType-Reference-Manual/RM06/Chap6kerx.html>
+
+use std::convert::TryFrom;
+
+use bitflags::bitflags;
+
+use crate::binary::read::{ReadArray, ReadBinary, ReadCtxt, ReadScope};
+use crate::error::ParseError;
+use crate::tables::aat::{self, ExtendedStateTable, LookupTable};
+use crate::tables::kern::{self, KernPair};
+
+/// Flag of a format 1 entry to push the current glyph onto the kerning stack.
+const PUSH: u16 = 0x8000;
+/// Flag of a format 1 entry to process the current glyph again.
+const DONT_ADVANCE: u16 = 0x4000;
+/// Flag of a format 1 entry to clear the kerning stack.
+const RESET: u16 = 0x2000;
+/// Value index of a format 1 entry that has no kerning values.
+const NO_VALUE_INDEX: u16 = 0xFFFF;
+
+/// The `kerx` table.
+pub struct KerxTable<'a> {
+    /// The version of the table.
+    pub version: u16,
+    /// The subtables of the table, in the order they are applied.
+    pub subtables: Vec<KerxSubtable<'a>>,
+}
+
+/// A subtable of the `kerx` table.
+pub struct KerxSubtable<'a> {
+    /// Describes how the kerning values of the subtable are applied.
+    pub coverage: KerxCoverage,
+    /// The number of font variation tuples the kerning values are given for.
+    pub tuple_count: u32,
+    /// The kerning data of the subtable.
+    pub data: KerxSubtableData<'a>,
+}
+
+bitflags! {
+    /// The coverage of a `kerx` subtable.
+    pub struct KerxCoverage: u32 {
+        /// The subtable holds vertical kerning values.
+        const VERTICAL = 0x8000_0000;
+        /// The kerning is perpendicular to the flow of the text.
+        const CROSS_STREAM = 0x4000_0000;
+        /// The subtable holds kerning values for a variation of the font.
+        const VARIATION = 0x2000_0000;
+    }
+}
+
+/// The kerning data of a `kerx` subtable.
+pub enum KerxSubtableData<'a> {
+    /// Ordered list of kerning pairs.
+    Format0 {
+        /// The kerning pairs, sorted by left then right glyph.
+        pairs: ReadArray<'a, KernPair>,
+    },
+    /// State table for contextual kerning.
+    Format1 {
+        /// The state machine of the subtable.
+        state_table: ExtendedStateTable<'a>,
+        /// The kerning values, indexed by the entries of the state table.
+        values: ReadScope<'a>,
+    },
+    /// Two-dimensional array of class-based kerning values.
+    Format2 {
+        /// Maps left-hand glyphs to the index of their row in the kerning array.
+        left_class_table: LookupTable<'a>,
+        /// Maps right-hand glyphs to their column in the kerning array.
+        right_class_table: LookupTable<'a>,
+        /// The kerning array.
+        array: ReadScope<'a>,
+    },
+    /// A subtable format that is not supported.
+    Unsupported {
+        /// The format of the subtable.
+        format: u8,
+    },
+}
+
+impl<'a> ReadBinary<'a> for KerxTable<'a> {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let version = ctxt.read_u16be()?;
+        ctxt.check_version((2..=4).contains(&version))?;
+        let _padding = ctxt.read_u16be()?;
+        let n_tables = ctxt.read_u32be()?;
+
+        let mut subtables = Vec::new();
+        for _ in 0..n_tables {
+            let scope = ctxt.scope();
+            let length = usize::try_from(ctxt.read_u32be()?)?;
+            let coverage = ctxt.read_u32be()?;
+            let tuple_count = ctxt.read_u32be()?;
+            let format = (coverage & 0xFF) as u8;
+
+            let data = match format {
+                0 => {
+                    let n_pairs = usize::try_from(ctxt.read_u32be()?)?;
+                    let _search_range = ctxt.read_u32be()?;
+                    let _entry_selector = ctxt.read_u32be()?;
+                    let _range_shift = ctxt.read_u32be()?;
+                    let pairs = ctxt.read_array::<KernPair>(n_pairs)?;
+                    KerxSubtableData::Format0 { pairs }
+                }
+                1 => {
+                    // Offsets are relative to the state table header following the subtable header
+                    let state_scope = ctxt.scope();
+                    let state_table = ctxt.read::<ExtendedStateTable<'a>>()?;
+                    let value_offset = usize::try_from(ctxt.read_u32be()?)?;
+                    KerxSubtableData::Format1 {
+                        state_table,
+                        values: state_scope.offset(value_offset),
+                    }
+                }
+                2 => {
+                    let _row_width = ctxt.read_u32be()?;
+                    let left_class_offset = usize::try_from(ctxt.read_u32be()?)?;
+                    let right_class_offset = usize::try_from(ctxt.read_u32be()?)?;
+                    let array_offset = usize::try_from(ctxt.read_u32be()?)?;
+                    KerxSubtableData::Format2 {
+                        left_class_table: scope
+                            .offset(left_class_offset)
+                            .read::<LookupTable<'a>>()?,
+                        right_class_table: scope
+                            .offset(right_class_offset)
+                            .read::<LookupTable<'a>>()?,
+                        array: scope.offset(array_offset),
+                    }
+                }
+                _ => KerxSubtableData::Unsupported { format },
+            };
+            subtables.push(KerxSubtable {
+                coverage: KerxCoverage::from_bits_truncate(coverage),
+                tuple_count,
+                data,
+            });
+
+            ctxt.check(length >= 12)?;
+            *ctxt = scope.offset(length).ctxt();
+        }
+
+        Ok(KerxTable { version, subtables })
+    }
+}
+
+impl<'a> KerxTable<'a> {
+    /// Returns the horizontal kerning value of the pair of glyphs `left` and `right`.
+    ///
+    /// Values from all applicable subtables are accumulated. Subtables holding vertical or
+    /// cross-stream kerning, or kerning for font variations, are ignored. Returns `None` if no
+    /// subtable has a value for the pair.
+    pub fn horizontal_kerning(&self, left: u16, right: u16) -> Option<i16> {
+        let mut kerning = None;
+        for subtable in self.horizontal_subtables() {
+            if let Ok(Some(value)) = subtable.kerning(left, right) {
+                kerning = Some(kerning.unwrap_or(0i16).saturating_add(value));
+            }
+        }
+        kerning
+    }
+
+    /// Returns the horizontal kerning adjustments of the sequence of glyphs `glyphs`.
+    ///
+    /// The result holds a value for each glyph, which is the distance that glyph, and all the
+    /// glyphs that follow it, are moved along the baseline. Pair kerning of a glyph with the glyph
+    /// before it is included, as is contextual kerning from format 1 subtables. The same
+    /// subtables as [horizontal_kerning](#method.horizontal_kerning) are applied.
+    pub fn kerning_adjustments(&self, glyphs: &[u16]) -> Result<Vec<i16>, ParseError> {
+        let mut adjustments = vec![None; glyphs.len()];
+        for subtable in self.horizontal_subtables() {
+            let subtable_adjustments = match subtable.data {
+                KerxSubtableData::Format1 {
+                    ref state_table,
+                    ref values,
+                } => contextual_kerning(state_table, values, glyphs)?,
+                _ => kern::pair_adjustments(glyphs, |left, right| subtable.kerning(left, right))?,
+            };
+            kern::combine_adjustments(&mut adjustments, &subtable_adjustments, false);
+        }
+        Ok(adjustments
+            .into_iter()
+            .map(|value| value.unwrap_or(0))
+            .collect())
+    }
+
+    fn horizontal_subtables(&self) -> impl Iterator<Item = &KerxSubtable<'a>> {
+        let ignored = KerxCoverage::VERTICAL | KerxCoverage::CROSS_STREAM | KerxCoverage::VARIATION;
+        self.subtables
+            .iter()
+            .filter(move |subtable| !subtable.coverage.intersects(ignored))
+    }
+}
+
+impl<'a> KerxSubtable<'a> {
+    /// Returns the kerning value of the pair of glyphs `left` and `right` in this subtable.
+    ///
+    /// Format 1 subtables do not hold kerning values for pairs, so `None` is returned for them.
+    pub fn kerning(&self, left: u16, right: u16) -> Result<Option<i16>, ParseError> {
+        match self.data {
+            KerxSubtableData::Format0 { ref pairs } => {
+                let (mut lo, mut hi) = (0, pairs.len());
+                while lo < hi {
+                    let mid = (lo + hi) / 2;
+                    let pair = pairs.get_item(mid);
+                    match (pair.left, pair.right).cmp(&(left, right)) {
+                        std::cmp::Ordering::Less => lo = mid + 1,
+                        std::cmp::Ordering::Greater => hi = mid,
+                        std::cmp::Ordering::Equal => return Ok(Some(pair.value)),
+                    }
+                }
+                Ok(None)
+            }
+            KerxSubtableData::Format2 {
+                ref left_class_table,
+                ref right_class_table,
+                ref array,
+            } => {
+                // The left-hand values are pre-multiplied by the number of columns, so the sum
+                // of the two is the index into the kerning array. Glyphs without a class use the
+                // first row or column.
+                let left_class = left_class_table.lookup(left)?.unwrap_or(0);
+                let right_class = right_class_table.lookup(right)?.unwrap_or(0);
+                let index = usize::from(left_class) + usize::from(right_class);
+                let value = array.offset(index * 2).ctxt().read_i16be()?;
+                Ok(Some(value))
+            }
+            KerxSubtableData::Format1 { .. } | KerxSubtableData::Unsupported { .. } => Ok(None),
+        }
+    }
+}
+
+/// Runs the state machine of a format 1 subtable over `glyphs`.
+///
+/// Glyphs are pushed onto the kerning stack by entries with the push flag. Entries with a value
+/// index pop glyphs off the stack, applying a kerning value to each, until a value with its lowest
+/// bit set is reached.
+fn contextual_kerning(
+    state_table: &ExtendedStateTable<'_>,
+    values: &ReadScope<'_>,
+    glyphs: &[u16],
+) -> Result<Vec<Option<i16>>, ParseError> {
+    let mut adjustments = vec![None; glyphs.len()];
+    let mut stack = Vec::with_capacity(kern::MAX_KERNING_STACK);
+    let mut state = aat::STATE_START_OF_TEXT;
+    let mut index = 0;
+    let mut dont_advance_count = 0;
+    loop {
+        let class = match glyphs.get(index) {
+            Some(&glyph) => state_table.class(glyph)?,
+            None => aat::CLASS_END_OF_TEXT,
+        };
+        let entry = state_table.entry(state, class, 2)?;
+        let value_index = entry.data.ctxt().read_u16be()?;
+
+        if entry.flags & RESET != 0 {
+            stack.clear();
+        }
+        if entry.flags & PUSH != 0 {
+            if stack.len() < kern::MAX_KERNING_STACK {
+                stack.push(index);
+            } else {
+                stack.clear();
+            }
+        }
+
+        if value_index != NO_VALUE_INDEX {
+            let mut values = values.offset(usize::from(value_index) * 2).ctxt();
+            while let Some(kerned_index) = stack.pop() {
+                let value = values.read_i16be()?;
+                kern::add_adjustment(&mut adjustments, kerned_index, value & !1);
+                if value & 1 != 0 {
+                    break;
+                }
+            }
+        }
+
+        state = entry.new_state;
+
+        if index >= glyphs.len() {
+            break;
+        }
+        if entry.flags & DONT_ADVANCE == 0 || dont_advance_count >= aat::MAX_DONT_ADVANCE {
+            index += 1;
+            dont_advance_count = 0;
+        } else {
+            dont_advance_count += 1;
+        }
+    }
+
+    Ok(adjustments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::write::{WriteBinary, WriteBuffer, WriteContext};
+    use crate::binary::{I16Be, U16Be, U32Be};
+    use crate::tests::write_u16s;
+
+    fn kerx_table(coverage: u32, body: &[u8]) -> Vec<u8> {
+        let mut w = WriteBuffer::new();
+        // Header: version, padding, nTables
+        U16Be::write(&mut w, 2u16).unwrap();
+        U16Be::write(&mut w, 0u16).unwrap();
+        U32Be::write(&mut w, 1u32).unwrap();
+        // Subtable header: length, coverage, tupleCount
+        U32Be::write(&mut w, 12 + body.len() as u32).unwrap();
+        U32Be::write(&mut w, coverage).unwrap();
+        U32Be::write(&mut w, 0u32).unwrap();
+        w.write_bytes(body).unwrap();
+        w.into_inner()
+    }
+
+    #[test]
+    fn test_format0() {
+        let mut w = WriteBuffer::new();
+        for &value in &[2u32, 12, 1, 0] {
+            U32Be::write(&mut w, value).unwrap();
+        }
+        write_u16s(&mut w, &[3, 4]);
+        I16Be::write(&mut w, -20i16).unwrap();
+        write_u16s(&mut w, &[3, 7]);
+        I16Be::write(&mut w, 15i16).unwrap();
+        let data = kerx_table(0, w.bytes());
+
+        let kerx = ReadScope::new(&data).read::<KerxTable<'_>>().unwrap();
+        assert_eq!(kerx.subtables.len(), 1);
+        assert_eq!(kerx.horizontal_kerning(3, 4), Some(-20));
+        assert_eq!(kerx.horizontal_kerning(3, 7), Some(15));
+        assert_eq!(kerx.horizontal_kerning(4, 3), None);
+        assert_eq!(
+            kerx.kerning_adjustments(&[3, 7, 3, 4]).unwrap(),
+            vec![0, 15, 0, -20]
+        );
+
+        // Vertical subtables are not applied to horizontal text
+        let data = kerx_table(KerxCoverage::VERTICAL.bits(), w.bytes());
+        let kerx = ReadScope::new(&data).read::<KerxTable<'_>>().unwrap();
+        assert_eq!(kerx.horizontal_kerning(3, 4), None);
+    }
+
+    #[test]
+    fn test_format1() {
+        let mut w = WriteBuffer::new();
+        // nClasses, classTable, stateArray, entryTable, valueTable
+        for &value in &[6u32, 20, 48, 84, 102] {
+            U32Be::write(&mut w, value).unwrap();
+        }
+        // Class table, a trimmed array lookup: glyph 10 is class 4, glyph 20 is class 5
+        write_u16s(&mut w, &[8, 10, 11, 4, 1, 1, 1, 1, 1, 1, 1, 1, 1, 5]);
+        // State array: start of text, start of line, and after glyph 10
+        write_u16s(
+            &mut w,
+            &[0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 1, 2],
+        );
+        // Entry table: new state, flags, value index. Entry 2 pushes glyph 20 and kerns the
+        // stack.
+        write_u16s(
+            &mut w,
+            &[0, 0, NO_VALUE_INDEX, 2, PUSH, NO_VALUE_INDEX, 0, PUSH, 0],
+        );
+        // Kerning values: -40 for glyph 20, then 0 for glyph 10, ending the list
+        I16Be::write(&mut w, -40i16).unwrap();
+        I16Be::write(&mut w, 1i16).unwrap();
+        let data = kerx_table(1, w.bytes());
+
+        let kerx = ReadScope::new(&data).read::<KerxTable<'_>>().unwrap();
+        assert_eq!(kerx.horizontal_kerning(10, 20), None);
+        assert_eq!(
+            kerx.kerning_adjustments(&[10, 20, 5, 10, 20, 20]).unwrap(),
+            vec![0, -40, 0, 0, -40, 0]
+        );
+    }
+
+    #[test]
+    fn test_format2() {
+        let mut w = WriteBuffer::new();
+        // rowWidth, leftClassTable, rightClassTable, array
+        for &value in &[4u32, 28, 38, 46] {
+            U32Be::write(&mut w, value).unwrap();
+        }
+        // Left class table: glyph 10 is row 0, glyph 11 is row 1 (two columns)
+        write_u16s(&mut w, &[8, 10, 2, 0, 2]);
+        // Right class table: glyph 20 is column 1
+        write_u16s(&mut w, &[8, 20, 1, 1]);
+        // Kerning array, 2x2
+        for &value in &[0i16, -10, 0, -50] {
+            I16Be::write(&mut w, value).unwrap();
+        }
+        let data = kerx_table(2, w.bytes());
+
+        let kerx = ReadScope::new(&data).read::<KerxTable<'_>>().unwrap();
+        assert_eq!(kerx.horizontal_kerning(10, 20), Some(-10));
+        assert_eq!(kerx.horizontal_kerning(11, 20), Some(-50));
+        assert_eq!(kerx.horizontal_kerning(11, 21), Some(0));
+    }
+}
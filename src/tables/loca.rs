@@ -7,7 +7,7 @@
 
 use crate::binary::read::{ReadArray, ReadBinaryDep, ReadCtxt};
 use crate::binary::write::{WriteBinary, WriteContext};
-use crate::binary::{U16Be, U32Be};
+use crate::binary::{long_align, word_align, U16Be, U32Be};
 use crate::error::{ParseError, WriteError};
 use crate::tables::IndexToLocFormat;
 
@@ -25,6 +25,20 @@ pub enum LocaOffsets<'a> {
     Long(ReadArray<'a, U32Be>),
 }
 
+/// Alignment applied to each glyph when writing the `glyf` table.
+///
+/// The short `loca` format stores offsets divided by two, so glyphs written with it must be at
+/// least 2-byte aligned. The long format places no requirement on alignment.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GlyphPadding {
+    /// Glyphs are not padded.
+    None,
+    /// Glyphs are padded to a multiple of 2 bytes.
+    TwoByte,
+    /// Glyphs are padded to a multiple of 4 bytes.
+    FourByte,
+}
+
 pub struct LocaOffsetsIter<'a, 'b> {
     offsets: &'b LocaOffsets<'a>,
     index: usize,
@@ -80,6 +94,28 @@ impl<'a> LocaTable<'a> {
     }
 }
 
+impl GlyphPadding {
+    /// The padding used by default for `index_to_loc_format`.
+    ///
+    /// This is the minimum padding that the format permits: 2-byte for short offsets and none for
+    /// long offsets.
+    pub fn for_format(index_to_loc_format: IndexToLocFormat) -> Self {
+        match index_to_loc_format {
+            IndexToLocFormat::Short => GlyphPadding::TwoByte,
+            IndexToLocFormat::Long => GlyphPadding::None,
+        }
+    }
+
+    /// Returns the length of a glyph of `len` bytes once padding is applied.
+    pub fn padded_length(self, len: usize) -> usize {
+        match self {
+            GlyphPadding::None => len,
+            GlyphPadding::TwoByte => word_align(len),
+            GlyphPadding::FourByte => long_align(len),
+        }
+    }
+}
+
 impl<'a> LocaOffsets<'a> {
     pub fn iter<'b>(&'b self) -> LocaOffsetsIter<'a, 'b> {
         LocaOffsetsIter {
@@ -94,23 +130,28 @@ impl<'a> LocaOffsets<'a> {
             LocaOffsets::Long(array) => array.len(),
         }
     }
+
+    /// Returns the offset at `index` in bytes, or `None` if `index` is out of bounds.
+    pub fn get(&self, index: usize) -> Option<u32> {
+        if index < self.len() {
+            let offset = match self {
+                LocaOffsets::Short(array) => u32::from(array.get_item(index)) * 2,
+                LocaOffsets::Long(array) => array.get_item(index),
+            };
+            Some(offset)
+        } else {
+            None
+        }
+    }
 }
 
 impl<'a, 'b> Iterator for LocaOffsetsIter<'a, 'b> {
     type Item = u32;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.index < self.offsets.len() {
-            let item = match self.offsets {
-                LocaOffsets::Short(array) => u32::from(array.get_item(self.index)) * 2,
-                LocaOffsets::Long(array) => array.get_item(self.index),
-            };
-
-            self.index += 1;
-            Some(item)
-        } else {
-            None
-        }
+        let item = self.offsets.get(self.index)?;
+        self.index += 1;
+        Some(item)
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
@@ -138,6 +179,46 @@ pub mod owned {
         pub offsets: Vec<u32>,
     }
 
+    impl LocaTable {
+        /// Check that the offsets are monotonically increasing.
+        ///
+        /// Each glyph's length is the difference between consecutive offsets so a decreasing
+        /// offset would describe a glyph with negative length.
+        pub fn check_offsets(&self) -> Result<(), WriteError> {
+            if self.offsets.windows(2).all(|pair| pair[0] <= pair[1]) {
+                Ok(())
+            } else {
+                Err(WriteError::BadValue)
+            }
+        }
+
+        /// Returns `true` if the offsets can be stored in the short `loca` format.
+        ///
+        /// All offsets must be even and no greater than `2 * 0xFFFF`.
+        pub fn fits_short_format(&self) -> bool {
+            self.offsets
+                .iter()
+                .all(|&offset| offset & 1 == 0 && offset / 2 <= u32::from(std::u16::MAX))
+        }
+
+        /// Returns the smallest format that is able to hold the offsets.
+        pub fn smallest_format(&self) -> IndexToLocFormat {
+            if self.fits_short_format() {
+                IndexToLocFormat::Short
+            } else {
+                IndexToLocFormat::Long
+            }
+        }
+    }
+
+    impl<'a> From<&super::LocaTable<'a>> for LocaTable {
+        fn from(loca: &super::LocaTable<'a>) -> Self {
+            LocaTable {
+                offsets: loca.offsets.iter().collect(),
+            }
+        }
+    }
+
     impl<'a> WriteBinaryDep<Self> for LocaTable {
         type Output = ();
         type Args = IndexToLocFormat;
@@ -147,6 +228,8 @@ pub mod owned {
             loca: LocaTable,
             index_to_loc_format: Self::Args,
         ) -> Result<(), WriteError> {
+            loca.check_offsets()?;
+
             // 0 for short offsets (Offset16), 1 for long (Offset32).
             match index_to_loc_format {
                 IndexToLocFormat::Short => {
@@ -175,3 +258,89 @@ pub mod owned {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{owned, GlyphPadding, LocaTable};
+    use crate::binary::read::ReadScope;
+    use crate::binary::write::{WriteBinaryDep, WriteBuffer};
+    use crate::error::WriteError;
+    use crate::tables::IndexToLocFormat;
+
+    fn round_trip(offsets: Vec<u32>, index_to_loc_format: IndexToLocFormat) -> Vec<u32> {
+        let num_glyphs = offsets.len() - 1;
+        let mut buffer = WriteBuffer::new();
+        owned::LocaTable::write_dep(
+            &mut buffer,
+            owned::LocaTable { offsets },
+            index_to_loc_format,
+        )
+        .unwrap();
+        let data = buffer.into_inner();
+        let loca = ReadScope::new(&data)
+            .read_dep::<LocaTable<'_>>((num_glyphs, index_to_loc_format))
+            .unwrap();
+
+        owned::LocaTable::from(&loca).offsets
+    }
+
+    #[test]
+    fn round_trip_short() {
+        let offsets = vec![0, 0, 12, 40, 131070];
+        assert_eq!(
+            round_trip(offsets.clone(), IndexToLocFormat::Short),
+            offsets
+        );
+    }
+
+    #[test]
+    fn round_trip_long() {
+        let offsets = vec![0, 0, 13, 41, 131071, 200000];
+        assert_eq!(round_trip(offsets.clone(), IndexToLocFormat::Long), offsets);
+    }
+
+    #[test]
+    fn write_non_monotonic_offsets() {
+        let loca = owned::LocaTable {
+            offsets: vec![0, 20, 10],
+        };
+        let mut buffer = WriteBuffer::new();
+        match owned::LocaTable::write_dep(&mut buffer, loca, IndexToLocFormat::Long) {
+            Err(WriteError::BadValue) => {}
+            _ => panic!("expected WriteError::BadValue"),
+        }
+    }
+
+    #[test]
+    fn write_short_odd_offset() {
+        let loca = owned::LocaTable {
+            offsets: vec![0, 3],
+        };
+        let mut buffer = WriteBuffer::new();
+        assert!(owned::LocaTable::write_dep(&mut buffer, loca, IndexToLocFormat::Short).is_err());
+    }
+
+    #[test]
+    fn smallest_format() {
+        let short = owned::LocaTable {
+            offsets: vec![0, 2, 131070],
+        };
+        let odd = owned::LocaTable {
+            offsets: vec![0, 3],
+        };
+        let large = owned::LocaTable {
+            offsets: vec![0, 131072],
+        };
+        assert_eq!(short.smallest_format(), IndexToLocFormat::Short);
+        assert_eq!(odd.smallest_format(), IndexToLocFormat::Long);
+        assert_eq!(large.smallest_format(), IndexToLocFormat::Long);
+    }
+
+    #[test]
+    fn padded_length() {
+        assert_eq!(GlyphPadding::None.padded_length(5), 5);
+        assert_eq!(GlyphPadding::TwoByte.padded_length(5), 6);
+        assert_eq!(GlyphPadding::FourByte.padded_length(5), 8);
+        assert_eq!(GlyphPadding::FourByte.padded_length(8), 8);
+    }
+}
@@ -0,0 +1,72 @@
+#![deny(missing_docs)]
+
+//! `LTSH` table parsing.
+//!
+//! The `LTSH` table holds, for each glyph, the size from which its advance width scales linearly,
+//! that is, the size above which hinting no longer changes the advance.
+//!
+//! <https://docs.microsoft.com/en-us/typography/opentype/spec/ltsh>
+
+use crate::binary::read::{CheckIndex, ReadArray, ReadBinary, ReadCtxt};
+use crate::binary::U8;
+use crate::error::ParseError;
+
+/// The `LTSH` linear threshold table.
+pub struct LtshTable<'a> {
+    /// The version of the table, always 0.
+    pub version: u16,
+    /// The linear thresholds of the glyphs, in ppem, indexed by glyph.
+    pub y_pels: ReadArray<'a, U8>,
+}
+
+impl<'a> ReadBinary<'a> for LtshTable<'a> {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let version = ctxt.read_u16be()?;
+        ctxt.check_version(version == 0)?;
+        let num_glyphs = ctxt.read_u16be()?;
+        let y_pels = ctxt.read_array::<U8>(usize::from(num_glyphs))?;
+
+        Ok(LtshTable { version, y_pels })
+    }
+}
+
+impl<'a> LtshTable<'a> {
+    /// Returns the size in ppem from which the advance width of `glyph_id` scales linearly.
+    ///
+    /// A threshold of 1 means the glyph always scales linearly. Returns `None` if `glyph_id` is
+    /// out of range.
+    pub fn linear_threshold(&self, glyph_id: u16) -> Option<u8> {
+        let index = usize::from(glyph_id);
+        self.y_pels.check_index(index).ok()?;
+        Some(self.y_pels.get_item(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::read::ReadScope;
+    use crate::font_data::FontData;
+    use crate::tables::FontTableProvider;
+    use crate::tag;
+    use crate::tests::read_fixture;
+
+    #[test]
+    fn test_linear_threshold() {
+        let buffer = read_fixture("tests/fonts/gurmukhi/Saab.ttf");
+        let scope = ReadScope::new(&buffer);
+        let font_file = scope.read::<FontData<'_>>().unwrap();
+        let provider = font_file.table_provider(0).unwrap();
+        let ltsh_data = provider.read_table_data(tag::LTSH).unwrap();
+        let ltsh = ReadScope::new(&ltsh_data).read::<LtshTable<'_>>().unwrap();
+
+        assert_eq!(ltsh.y_pels.len(), 198);
+        assert_eq!(ltsh.linear_threshold(0), Some(75));
+        assert_eq!(ltsh.linear_threshold(1), Some(1));
+        assert_eq!(ltsh.linear_threshold(4), Some(55));
+        assert_eq!(ltsh.linear_threshold(8), Some(47));
+        assert_eq!(ltsh.linear_threshold(198), None);
+    }
+}
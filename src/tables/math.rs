@@ -0,0 +1,931 @@
+#![deny(missing_docs)]
+
+//! `MATH` table parsing.
+//!
+//! The `MATH` table holds the constants and per-glyph data needed to lay out mathematical
+//! formulas, including the variants and assemblies used to build stretched delimiters and
+//! accents.
+//!
+//! Device table adjustments of `MathValueRecord`s are not applied, and the `MathKernInfo` table
+//! is not read.
+//!
+//! <https://docs.microsoft.com/en-us/typography/opentype/spec/math>
+
+use std::convert::TryFrom;
+
+use crate::binary::read::{ReadArray, ReadBinary, ReadCtxt, ReadFrom, ReadScope};
+use crate::binary::{I16Be, U16Be};
+use crate::error::ParseError;
+use crate::layout::Coverage;
+
+/// The maximum number of parts in a glyph assembly built by `MathVariants::stretch`.
+const MAX_ASSEMBLY_PARTS: usize = 256;
+
+/// The `MATH` table.
+pub struct MathTable<'a> {
+    /// Global constants used to position elements of formulas.
+    pub constants: MathConstants,
+    /// Per-glyph positioning information.
+    pub glyph_info: MathGlyphInfo<'a>,
+    /// Size variants and assemblies of stretchable glyphs.
+    pub variants: MathVariants<'a>,
+}
+
+/// Global constants of the `MATH` table, in font units unless noted otherwise.
+///
+/// The fields are named after the corresponding fields of the `MathConstants` table in the
+/// OpenType specification, which describes their use.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MathConstants {
+    /// `scriptPercentScaleDown`, as a percentage
+    pub script_percent_scale_down: i16,
+    /// `scriptScriptPercentScaleDown`, as a percentage
+    pub script_script_percent_scale_down: i16,
+    /// `delimitedSubFormulaMinHeight`
+    pub delimited_sub_formula_min_height: u16,
+    /// `displayOperatorMinHeight`
+    pub display_operator_min_height: u16,
+    /// `mathLeading`
+    pub math_leading: i16,
+    /// `axisHeight`
+    pub axis_height: i16,
+    /// `accentBaseHeight`
+    pub accent_base_height: i16,
+    /// `flattenedAccentBaseHeight`
+    pub flattened_accent_base_height: i16,
+    /// `subscriptShiftDown`
+    pub subscript_shift_down: i16,
+    /// `subscriptTopMax`
+    pub subscript_top_max: i16,
+    /// `subscriptBaselineDropMin`
+    pub subscript_baseline_drop_min: i16,
+    /// `superscriptShiftUp`
+    pub superscript_shift_up: i16,
+    /// `superscriptShiftUpCramped`
+    pub superscript_shift_up_cramped: i16,
+    /// `superscriptBottomMin`
+    pub superscript_bottom_min: i16,
+    /// `superscriptBaselineDropMax`
+    pub superscript_baseline_drop_max: i16,
+    /// `subSuperscriptGapMin`
+    pub sub_superscript_gap_min: i16,
+    /// `superscriptBottomMaxWithSubscript`
+    pub superscript_bottom_max_with_subscript: i16,
+    /// `spaceAfterScript`
+    pub space_after_script: i16,
+    /// `upperLimitGapMin`
+    pub upper_limit_gap_min: i16,
+    /// `upperLimitBaselineRiseMin`
+    pub upper_limit_baseline_rise_min: i16,
+    /// `lowerLimitGapMin`
+    pub lower_limit_gap_min: i16,
+    /// `lowerLimitBaselineDropMin`
+    pub lower_limit_baseline_drop_min: i16,
+    /// `stackTopShiftUp`
+    pub stack_top_shift_up: i16,
+    /// `stackTopDisplayStyleShiftUp`
+    pub stack_top_display_style_shift_up: i16,
+    /// `stackBottomShiftDown`
+    pub stack_bottom_shift_down: i16,
+    /// `stackBottomDisplayStyleShiftDown`
+    pub stack_bottom_display_style_shift_down: i16,
+    /// `stackGapMin`
+    pub stack_gap_min: i16,
+    /// `stackDisplayStyleGapMin`
+    pub stack_display_style_gap_min: i16,
+    /// `stretchStackTopShiftUp`
+    pub stretch_stack_top_shift_up: i16,
+    /// `stretchStackBottomShiftDown`
+    pub stretch_stack_bottom_shift_down: i16,
+    /// `stretchStackGapAboveMin`
+    pub stretch_stack_gap_above_min: i16,
+    /// `stretchStackGapBelowMin`
+    pub stretch_stack_gap_below_min: i16,
+    /// `fractionNumeratorShiftUp`
+    pub fraction_numerator_shift_up: i16,
+    /// `fractionNumeratorDisplayStyleShiftUp`
+    pub fraction_numerator_display_style_shift_up: i16,
+    /// `fractionDenominatorShiftDown`
+    pub fraction_denominator_shift_down: i16,
+    /// `fractionDenominatorDisplayStyleShiftDown`
+    pub fraction_denominator_display_style_shift_down: i16,
+    /// `fractionNumeratorGapMin`
+    pub fraction_numerator_gap_min: i16,
+    /// `fractionNumDisplayStyleGapMin`
+    pub fraction_num_display_style_gap_min: i16,
+    /// `fractionRuleThickness`
+    pub fraction_rule_thickness: i16,
+    /// `fractionDenominatorGapMin`
+    pub fraction_denominator_gap_min: i16,
+    /// `fractionDenomDisplayStyleGapMin`
+    pub fraction_denom_display_style_gap_min: i16,
+    /// `skewedFractionHorizontalGap`
+    pub skewed_fraction_horizontal_gap: i16,
+    /// `skewedFractionVerticalGap`
+    pub skewed_fraction_vertical_gap: i16,
+    /// `overbarVerticalGap`
+    pub overbar_vertical_gap: i16,
+    /// `overbarRuleThickness`
+    pub overbar_rule_thickness: i16,
+    /// `overbarExtraAscender`
+    pub overbar_extra_ascender: i16,
+    /// `underbarVerticalGap`
+    pub underbar_vertical_gap: i16,
+    /// `underbarRuleThickness`
+    pub underbar_rule_thickness: i16,
+    /// `underbarExtraDescender`
+    pub underbar_extra_descender: i16,
+    /// `radicalVerticalGap`
+    pub radical_vertical_gap: i16,
+    /// `radicalDisplayStyleVerticalGap`
+    pub radical_display_style_vertical_gap: i16,
+    /// `radicalRuleThickness`
+    pub radical_rule_thickness: i16,
+    /// `radicalExtraAscender`
+    pub radical_extra_ascender: i16,
+    /// `radicalKernBeforeDegree`
+    pub radical_kern_before_degree: i16,
+    /// `radicalKernAfterDegree`
+    pub radical_kern_after_degree: i16,
+    /// `radicalDegreeBottomRaisePercent`, as a percentage
+    pub radical_degree_bottom_raise_percent: i16,
+}
+
+/// Per-glyph information of the `MATH` table.
+pub struct MathGlyphInfo<'a> {
+    italics_correction: Option<MathValueTable<'a>>,
+    top_accent_attachment: Option<MathValueTable<'a>>,
+    extended_shape_coverage: Option<Coverage>,
+}
+
+/// Values indexed by the coverage index of a glyph.
+struct MathValueTable<'a> {
+    coverage: Coverage,
+    values: ReadArray<'a, MathValueRecord>,
+}
+
+/// A value of the `MATH` table, without its device table.
+#[derive(Copy, Clone)]
+struct MathValueRecord {
+    value: i16,
+}
+
+/// Size variants and assemblies of stretchable glyphs.
+pub struct MathVariants<'a> {
+    /// The minimum overlap of connecting glyph parts in an assembly.
+    pub min_connector_overlap: u16,
+    vertical: Option<MathGlyphConstructions<'a>>,
+    horizontal: Option<MathGlyphConstructions<'a>>,
+}
+
+struct MathGlyphConstructions<'a> {
+    scope: ReadScope<'a>,
+    coverage: Coverage,
+    offsets: ReadArray<'a, U16Be>,
+}
+
+/// The direction a glyph is stretched in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StretchDirection {
+    /// Stretched vertically, such as for parentheses and other delimiters.
+    Vertical,
+    /// Stretched horizontally, such as for accents and arrows.
+    Horizontal,
+}
+
+/// The size variants and assembly of a stretchable glyph.
+pub struct MathGlyphConstruction<'a> {
+    /// Recipe for building the glyph from parts, if the glyph can be assembled.
+    pub glyph_assembly: Option<GlyphAssembly<'a>>,
+    /// The size variants of the glyph, in increasing size.
+    pub variants: ReadArray<'a, MathGlyphVariantRecord>,
+}
+
+/// A size variant of a stretchable glyph.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MathGlyphVariantRecord {
+    /// The glyph index of the variant.
+    pub variant_glyph: u16,
+    /// The advance of the variant in the direction of stretching.
+    pub advance_measurement: u16,
+}
+
+/// Recipe for building a stretched glyph from parts.
+pub struct GlyphAssembly<'a> {
+    /// The italics correction of the assembled glyph.
+    pub italics_correction: i16,
+    /// The parts of the assembly, from bottom to top or left to right.
+    pub parts: ReadArray<'a, GlyphPart>,
+}
+
+/// A part of a glyph assembly.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct GlyphPart {
+    /// The glyph index of the part.
+    pub glyph_id: u16,
+    /// The length of the connector at the start of the part.
+    pub start_connector_length: u16,
+    /// The length of the connector at the end of the part.
+    pub end_connector_length: u16,
+    /// The advance of the part in the direction of stretching.
+    pub full_advance: u16,
+    /// Part flags, see `GlyphPart::EXTENDER_FLAG`.
+    pub part_flags: u16,
+}
+
+/// A glyph stretched to cover a target size.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StretchedGlyph {
+    /// A single glyph, either a size variant or the original glyph.
+    Variant {
+        /// The glyph index.
+        glyph_index: u16,
+        /// The advance of the glyph in the direction of stretching.
+        advance: u16,
+    },
+    /// A glyph built from parts.
+    Assembly {
+        /// The parts to draw.
+        parts: Vec<AssembledPart>,
+        /// The total size of the assembly in the direction of stretching.
+        size: i32,
+        /// The italics correction of the assembly.
+        italics_correction: i16,
+    },
+}
+
+/// A glyph part placed in a stretched glyph.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct AssembledPart {
+    /// The glyph index of the part.
+    pub glyph_index: u16,
+    /// The offset of the part from the start of the assembly, upwards for vertical assemblies
+    /// and rightwards for horizontal ones.
+    pub offset: i32,
+}
+
+impl<'a> ReadBinary<'a> for MathTable<'a> {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let scope = ctxt.scope();
+        let major_version = ctxt.read_u16be()?;
+        ctxt.check_version(major_version == 1)?;
+        let _minor_version = ctxt.read_u16be()?;
+        let math_constants_offset = ctxt.read_u16be()?;
+        let math_glyph_info_offset = ctxt.read_u16be()?;
+        let math_variants_offset = ctxt.read_u16be()?;
+
+        let constants = scope
+            .offset(usize::from(math_constants_offset))
+            .read::<MathConstants>()?;
+        let glyph_info = scope
+            .offset(usize::from(math_glyph_info_offset))
+            .read::<MathGlyphInfo<'_>>()?;
+        let variants = scope
+            .offset(usize::from(math_variants_offset))
+            .read::<MathVariants<'_>>()?;
+
+        Ok(MathTable {
+            constants,
+            glyph_info,
+            variants,
+        })
+    }
+}
+
+impl<'a> ReadBinary<'a> for MathConstants {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let script_percent_scale_down = ctxt.read_i16be()?;
+        let script_script_percent_scale_down = ctxt.read_i16be()?;
+        let delimited_sub_formula_min_height = ctxt.read_u16be()?;
+        let display_operator_min_height = ctxt.read_u16be()?;
+        let math_leading = ctxt.read::<MathValueRecord>()?.value;
+        let axis_height = ctxt.read::<MathValueRecord>()?.value;
+        let accent_base_height = ctxt.read::<MathValueRecord>()?.value;
+        let flattened_accent_base_height = ctxt.read::<MathValueRecord>()?.value;
+        let subscript_shift_down = ctxt.read::<MathValueRecord>()?.value;
+        let subscript_top_max = ctxt.read::<MathValueRecord>()?.value;
+        let subscript_baseline_drop_min = ctxt.read::<MathValueRecord>()?.value;
+        let superscript_shift_up = ctxt.read::<MathValueRecord>()?.value;
+        let superscript_shift_up_cramped = ctxt.read::<MathValueRecord>()?.value;
+        let superscript_bottom_min = ctxt.read::<MathValueRecord>()?.value;
+        let superscript_baseline_drop_max = ctxt.read::<MathValueRecord>()?.value;
+        let sub_superscript_gap_min = ctxt.read::<MathValueRecord>()?.value;
+        let superscript_bottom_max_with_subscript = ctxt.read::<MathValueRecord>()?.value;
+        let space_after_script = ctxt.read::<MathValueRecord>()?.value;
+        let upper_limit_gap_min = ctxt.read::<MathValueRecord>()?.value;
+        let upper_limit_baseline_rise_min = ctxt.read::<MathValueRecord>()?.value;
+        let lower_limit_gap_min = ctxt.read::<MathValueRecord>()?.value;
+        let lower_limit_baseline_drop_min = ctxt.read::<MathValueRecord>()?.value;
+        let stack_top_shift_up = ctxt.read::<MathValueRecord>()?.value;
+        let stack_top_display_style_shift_up = ctxt.read::<MathValueRecord>()?.value;
+        let stack_bottom_shift_down = ctxt.read::<MathValueRecord>()?.value;
+        let stack_bottom_display_style_shift_down = ctxt.read::<MathValueRecord>()?.value;
+        let stack_gap_min = ctxt.read::<MathValueRecord>()?.value;
+        let stack_display_style_gap_min = ctxt.read::<MathValueRecord>()?.value;
+        let stretch_stack_top_shift_up = ctxt.read::<MathValueRecord>()?.value;
+        let stretch_stack_bottom_shift_down = ctxt.read::<MathValueRecord>()?.value;
+        let stretch_stack_gap_above_min = ctxt.read::<MathValueRecord>()?.value;
+        let stretch_stack_gap_below_min = ctxt.read::<MathValueRecord>()?.value;
+        let fraction_numerator_shift_up = ctxt.read::<MathValueRecord>()?.value;
+        let fraction_numerator_display_style_shift_up = ctxt.read::<MathValueRecord>()?.value;
+        let fraction_denominator_shift_down = ctxt.read::<MathValueRecord>()?.value;
+        let fraction_denominator_display_style_shift_down = ctxt.read::<MathValueRecord>()?.value;
+        let fraction_numerator_gap_min = ctxt.read::<MathValueRecord>()?.value;
+        let fraction_num_display_style_gap_min = ctxt.read::<MathValueRecord>()?.value;
+        let fraction_rule_thickness = ctxt.read::<MathValueRecord>()?.value;
+        let fraction_denominator_gap_min = ctxt.read::<MathValueRecord>()?.value;
+        let fraction_denom_display_style_gap_min = ctxt.read::<MathValueRecord>()?.value;
+        let skewed_fraction_horizontal_gap = ctxt.read::<MathValueRecord>()?.value;
+        let skewed_fraction_vertical_gap = ctxt.read::<MathValueRecord>()?.value;
+        let overbar_vertical_gap = ctxt.read::<MathValueRecord>()?.value;
+        let overbar_rule_thickness = ctxt.read::<MathValueRecord>()?.value;
+        let overbar_extra_ascender = ctxt.read::<MathValueRecord>()?.value;
+        let underbar_vertical_gap = ctxt.read::<MathValueRecord>()?.value;
+        let underbar_rule_thickness = ctxt.read::<MathValueRecord>()?.value;
+        let underbar_extra_descender = ctxt.read::<MathValueRecord>()?.value;
+        let radical_vertical_gap = ctxt.read::<MathValueRecord>()?.value;
+        let radical_display_style_vertical_gap = ctxt.read::<MathValueRecord>()?.value;
+        let radical_rule_thickness = ctxt.read::<MathValueRecord>()?.value;
+        let radical_extra_ascender = ctxt.read::<MathValueRecord>()?.value;
+        let radical_kern_before_degree = ctxt.read::<MathValueRecord>()?.value;
+        let radical_kern_after_degree = ctxt.read::<MathValueRecord>()?.value;
+        let radical_degree_bottom_raise_percent = ctxt.read_i16be()?;
+
+        Ok(MathConstants {
+            script_percent_scale_down,
+            script_script_percent_scale_down,
+            delimited_sub_formula_min_height,
+            display_operator_min_height,
+            math_leading,
+            axis_height,
+            accent_base_height,
+            flattened_accent_base_height,
+            subscript_shift_down,
+            subscript_top_max,
+            subscript_baseline_drop_min,
+            superscript_shift_up,
+            superscript_shift_up_cramped,
+            superscript_bottom_min,
+            superscript_baseline_drop_max,
+            sub_superscript_gap_min,
+            superscript_bottom_max_with_subscript,
+            space_after_script,
+            upper_limit_gap_min,
+            upper_limit_baseline_rise_min,
+            lower_limit_gap_min,
+            lower_limit_baseline_drop_min,
+            stack_top_shift_up,
+            stack_top_display_style_shift_up,
+            stack_bottom_shift_down,
+            stack_bottom_display_style_shift_down,
+            stack_gap_min,
+            stack_display_style_gap_min,
+            stretch_stack_top_shift_up,
+            stretch_stack_bottom_shift_down,
+            stretch_stack_gap_above_min,
+            stretch_stack_gap_below_min,
+            fraction_numerator_shift_up,
+            fraction_numerator_display_style_shift_up,
+            fraction_denominator_shift_down,
+            fraction_denominator_display_style_shift_down,
+            fraction_numerator_gap_min,
+            fraction_num_display_style_gap_min,
+            fraction_rule_thickness,
+            fraction_denominator_gap_min,
+            fraction_denom_display_style_gap_min,
+            skewed_fraction_horizontal_gap,
+            skewed_fraction_vertical_gap,
+            overbar_vertical_gap,
+            overbar_rule_thickness,
+            overbar_extra_ascender,
+            underbar_vertical_gap,
+            underbar_rule_thickness,
+            underbar_extra_descender,
+            radical_vertical_gap,
+            radical_display_style_vertical_gap,
+            radical_rule_thickness,
+            radical_extra_ascender,
+            radical_kern_before_degree,
+            radical_kern_after_degree,
+            radical_degree_bottom_raise_percent,
+        })
+    }
+}
+
+impl<'a> ReadFrom<'a> for MathValueRecord {
+    type ReadType = (I16Be, U16Be);
+
+    fn from((value, _device_offset): (i16, u16)) -> Self {
+        MathValueRecord { value }
+    }
+}
+
+impl<'a> ReadBinary<'a> for MathGlyphInfo<'a> {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let scope = ctxt.scope();
+        let math_italics_correction_info_offset = ctxt.read_u16be()?;
+        let math_top_accent_attachment_offset = ctxt.read_u16be()?;
+        let extended_shape_coverage_offset = ctxt.read_u16be()?;
+        let _math_kern_info_offset = ctxt.read_u16be()?;
+
+        let italics_correction =
+            read_optional::<MathValueTable<'_>>(&scope, math_italics_correction_info_offset)?;
+        let top_accent_attachment =
+            read_optional::<MathValueTable<'_>>(&scope, math_top_accent_attachment_offset)?;
+        let extended_shape_coverage =
+            read_optional::<Coverage>(&scope, extended_shape_coverage_offset)?;
+
+        Ok(MathGlyphInfo {
+            italics_correction,
+            top_accent_attachment,
+            extended_shape_coverage,
+        })
+    }
+}
+
+impl<'a> ReadBinary<'a> for MathValueTable<'a> {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let scope = ctxt.scope();
+        let coverage_offset = ctxt.read_u16be()?;
+        let count = ctxt.read_u16be()?;
+        let values = ctxt.read_array::<MathValueRecord>(usize::from(count))?;
+        let coverage = scope
+            .offset(usize::from(coverage_offset))
+            .read::<Coverage>()?;
+
+        Ok(MathValueTable { coverage, values })
+    }
+}
+
+impl<'a> ReadBinary<'a> for MathVariants<'a> {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let scope = ctxt.scope();
+        let min_connector_overlap = ctxt.read_u16be()?;
+        let vert_glyph_coverage_offset = ctxt.read_u16be()?;
+        let horiz_glyph_coverage_offset = ctxt.read_u16be()?;
+        let vert_glyph_count = ctxt.read_u16be()?;
+        let horiz_glyph_count = ctxt.read_u16be()?;
+        let vert_glyph_construction_offsets =
+            ctxt.read_array::<U16Be>(usize::from(vert_glyph_count))?;
+        let horiz_glyph_construction_offsets =
+            ctxt.read_array::<U16Be>(usize::from(horiz_glyph_count))?;
+
+        let vertical =
+            read_optional::<Coverage>(&scope, vert_glyph_coverage_offset)?.map(|coverage| {
+                MathGlyphConstructions {
+                    scope: scope.clone(),
+                    coverage,
+                    offsets: vert_glyph_construction_offsets,
+                }
+            });
+        let horizontal =
+            read_optional::<Coverage>(&scope, horiz_glyph_coverage_offset)?.map(|coverage| {
+                MathGlyphConstructions {
+                    scope: scope.clone(),
+                    coverage,
+                    offsets: horiz_glyph_construction_offsets,
+                }
+            });
+
+        Ok(MathVariants {
+            min_connector_overlap,
+            vertical,
+            horizontal,
+        })
+    }
+}
+
+impl<'a> ReadBinary<'a> for MathGlyphConstruction<'a> {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let scope = ctxt.scope();
+        let glyph_assembly_offset = ctxt.read_u16be()?;
+        let variant_count = ctxt.read_u16be()?;
+        let variants = ctxt.read_array::<MathGlyphVariantRecord>(usize::from(variant_count))?;
+        let glyph_assembly = read_optional::<GlyphAssembly<'_>>(&scope, glyph_assembly_offset)?;
+
+        Ok(MathGlyphConstruction {
+            glyph_assembly,
+            variants,
+        })
+    }
+}
+
+impl<'a> ReadFrom<'a> for MathGlyphVariantRecord {
+    type ReadType = (U16Be, U16Be);
+
+    fn from((variant_glyph, advance_measurement): (u16, u16)) -> Self {
+        MathGlyphVariantRecord {
+            variant_glyph,
+            advance_measurement,
+        }
+    }
+}
+
+impl<'a> ReadBinary<'a> for GlyphAssembly<'a> {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let italics_correction = ctxt.read::<MathValueRecord>()?.value;
+        let part_count = ctxt.read_u16be()?;
+        let parts = ctxt.read_array::<GlyphPart>(usize::from(part_count))?;
+
+        Ok(GlyphAssembly {
+            italics_correction,
+            parts,
+        })
+    }
+}
+
+impl<'a> ReadFrom<'a> for GlyphPart {
+    type ReadType = ((U16Be, U16Be, U16Be), (U16Be, U16Be));
+
+    fn from(
+        ((glyph_id, start_connector_length, end_connector_length), (full_advance, part_flags)): (
+            (u16, u16, u16),
+            (u16, u16),
+        ),
+    ) -> Self {
+        GlyphPart {
+            glyph_id,
+            start_connector_length,
+            end_connector_length,
+            full_advance,
+            part_flags,
+        }
+    }
+}
+
+impl GlyphPart {
+    /// Flag indicating the part can be repeated to extend the assembly.
+    pub const EXTENDER_FLAG: u16 = 0x0001;
+
+    /// Returns `true` if the part can be repeated to extend the assembly.
+    pub fn is_extender(&self) -> bool {
+        self.part_flags & Self::EXTENDER_FLAG != 0
+    }
+}
+
+impl<'a> MathGlyphInfo<'a> {
+    /// Returns the italics correction of `glyph`, if it has one.
+    pub fn italics_correction(&self, glyph: u16) -> Option<i16> {
+        self.italics_correction.as_ref()?.get(glyph)
+    }
+
+    /// Returns the horizontal position of the attachment point for accents placed above
+    /// `glyph`, if it has one.
+    ///
+    /// Glyphs without an attachment point should have accents centred over them.
+    pub fn top_accent_attachment(&self, glyph: u16) -> Option<i16> {
+        self.top_accent_attachment.as_ref()?.get(glyph)
+    }
+
+    /// Returns `true` if `glyph` is an extended shape, such as a large operator or a size variant
+    /// of a delimiter, that should be treated as having a height equal to its bounding box for
+    /// positioning scripts.
+    pub fn is_extended_shape(&self, glyph: u16) -> bool {
+        self.extended_shape_coverage
+            .as_ref()
+            .and_then(|coverage| coverage.glyph_coverage_value(glyph))
+            .is_some()
+    }
+}
+
+impl<'a> MathValueTable<'a> {
+    fn get(&self, glyph: u16) -> Option<i16> {
+        let index = usize::from(self.coverage.glyph_coverage_value(glyph)?);
+        if index < self.values.len() {
+            Some(self.values.get_item(index).value)
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a> MathVariants<'a> {
+    /// Returns the size variants and assembly of `glyph` when stretched in `direction`.
+    pub fn construction(
+        &self,
+        glyph: u16,
+        direction: StretchDirection,
+    ) -> Result<Option<MathGlyphConstruction<'a>>, ParseError> {
+        let constructions = match direction {
+            StretchDirection::Vertical => &self.vertical,
+            StretchDirection::Horizontal => &self.horizontal,
+        };
+        let constructions = match constructions {
+            Some(constructions) => constructions,
+            None => return Ok(None),
+        };
+        let index = match constructions.coverage.glyph_coverage_value(glyph) {
+            Some(index) => usize::from(index),
+            None => return Ok(None),
+        };
+        if index >= constructions.offsets.len() {
+            return Err(ParseError::BadIndex);
+        }
+        let offset = constructions.offsets.get_item(index);
+        constructions
+            .scope
+            .offset(usize::from(offset))
+            .read::<MathGlyphConstruction<'_>>()
+            .map(Some)
+    }
+
+    /// Stretch `glyph` in `direction` so that it covers at least `target_size`.
+    ///
+    /// The smallest size variant covering `target_size` is used if there is one. Otherwise the
+    /// glyph is assembled from parts, repeating the extenders as few times as necessary and then
+    /// spreading the overlap of the parts evenly so the assembly is as close to `target_size` as
+    /// the connector lengths allow. If the glyph can't be assembled the largest variant is used.
+    ///
+    /// `advance` supplies the advance of `glyph` in the direction of stretching, which is used
+    /// when the glyph has no construction.
+    pub fn stretch(
+        &self,
+        glyph: u16,
+        direction: StretchDirection,
+        target_size: u16,
+        advance: u16,
+    ) -> Result<StretchedGlyph, ParseError> {
+        let construction = match self.construction(glyph, direction)? {
+            Some(construction) => construction,
+            None => {
+                return Ok(StretchedGlyph::Variant {
+                    glyph_index: glyph,
+                    advance,
+                })
+            }
+        };
+
+        let mut largest = StretchedGlyph::Variant {
+            glyph_index: glyph,
+            advance,
+        };
+        for variant in construction.variants.iter() {
+            if variant.advance_measurement >= target_size {
+                return Ok(StretchedGlyph::Variant {
+                    glyph_index: variant.variant_glyph,
+                    advance: variant.advance_measurement,
+                });
+            }
+            largest = StretchedGlyph::Variant {
+                glyph_index: variant.variant_glyph,
+                advance: variant.advance_measurement,
+            };
+        }
+
+        match construction.glyph_assembly {
+            Some(assembly) => Ok(self
+                .assemble(&assembly, i32::from(target_size))
+                .unwrap_or(largest)),
+            None => Ok(largest),
+        }
+    }
+
+    fn assemble(&self, assembly: &GlyphAssembly<'_>, target_size: i32) -> Option<StretchedGlyph> {
+        let min_overlap = i32::from(self.min_connector_overlap);
+        let (mut fixed_count, mut fixed_advance) = (0, 0);
+        let (mut extender_count, mut extender_advance) = (0, 0);
+        for part in assembly.parts.iter() {
+            if part.is_extender() {
+                extender_count += 1;
+                extender_advance += i32::from(part.full_advance);
+            } else {
+                fixed_count += 1;
+                fixed_advance += i32::from(part.full_advance);
+            }
+        }
+
+        // Find the number of times the extenders need to be repeated, with parts overlapping by
+        // the minimum amount
+        let fixed_size = fixed_advance - (fixed_count - 1).max(0) * min_overlap;
+        let extender_growth = extender_advance - extender_count * min_overlap;
+        let mut repeats = if fixed_size >= target_size {
+            0
+        } else if extender_growth > 0 {
+            (target_size - fixed_size + extender_growth - 1) / extender_growth
+        } else {
+            0
+        };
+        if fixed_count == 0 {
+            repeats = repeats.max(1);
+        }
+        let part_count = fixed_count + repeats * extender_count;
+        if part_count == 0 || usize::try_from(part_count).ok()? > MAX_ASSEMBLY_PARTS {
+            return None;
+        }
+
+        let parts = assembly
+            .parts
+            .iter()
+            .flat_map(|part| {
+                let count = if part.is_extender() { repeats } else { 1 };
+                vec![part; count as usize]
+            })
+            .collect::<Vec<_>>();
+
+        // Spread the excess size over the connections between parts, overlapping each by at least
+        // the minimum and at most the length of the shorter connector
+        let overlap = if parts.len() > 1 {
+            let total_advance = parts
+                .iter()
+                .map(|part| i32::from(part.full_advance))
+                .sum::<i32>();
+            let connections = parts.len() as i32 - 1;
+            let max_overlap = parts
+                .windows(2)
+                .map(|pair| {
+                    pair[0]
+                        .end_connector_length
+                        .min(pair[1].start_connector_length)
+                })
+                .min()
+                .map(i32::from)
+                .unwrap_or(0)
+                .max(min_overlap);
+            ((total_advance - target_size) / connections)
+                .max(min_overlap)
+                .min(max_overlap)
+        } else {
+            0
+        };
+
+        let mut offset = 0;
+        let mut assembled = Vec::with_capacity(parts.len());
+        for part in &parts {
+            assembled.push(AssembledPart {
+                glyph_index: part.glyph_id,
+                offset,
+            });
+            offset += i32::from(part.full_advance) - overlap;
+        }
+
+        Some(StretchedGlyph::Assembly {
+            parts: assembled,
+            size: offset + overlap,
+            italics_correction: assembly.italics_correction,
+        })
+    }
+}
+
+fn read_optional<'a, T: ReadBinary<'a>>(
+    scope: &ReadScope<'a>,
+    offset: u16,
+) -> Result<Option<T::HostType>, ParseError> {
+    if offset == 0 {
+        Ok(None)
+    } else {
+        scope.offset(usize::from(offset)).read::<T>().map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::write::WriteBuffer;
+    use crate::tests::write_u16s;
+
+    const CONSTANTS_SIZE: u16 = 4 * 2 + 51 * 4 + 2;
+
+    // Builds a MATH table where glyph 1 has an italics correction of 50, glyph 2 a top accent
+    // attachment of 300 and is an extended shape, and glyph 3 is a vertical delimiter with two
+    // size variants and an assembly of a bottom, extender and top part.
+    fn math_table_data() -> Vec<u8> {
+        let mut w = WriteBuffer::new();
+        let glyph_info_offset = 10 + CONSTANTS_SIZE;
+        let variants_offset = glyph_info_offset + 42;
+        write_u16s(&mut w, &[1, 0, 10, glyph_info_offset, variants_offset]);
+
+        // MathConstants
+        write_u16s(&mut w, &[80, 60, 1500, 1300]);
+        for i in 0..51 {
+            // Each constant is its index, with a NULL device table
+            write_u16s(&mut w, &[i, 0]);
+        }
+        write_u16s(&mut w, &[65]);
+
+        // MathGlyphInfo
+        write_u16s(&mut w, &[8, 22, 36, 0]);
+        // MathItalicsCorrectionInfo: coverage offset, count, value record
+        write_u16s(&mut w, &[8, 1, 50, 0]);
+        write_u16s(&mut w, &[1, 1, 1]); // coverage [1]
+                                        // MathTopAccentAttachment
+        write_u16s(&mut w, &[8, 1, 300, 0]);
+        write_u16s(&mut w, &[1, 1, 2]); // coverage [2]
+                                        // ExtendedShapeCoverage
+        write_u16s(&mut w, &[1, 1, 2]);
+
+        // MathVariants: overlap, vertical coverage, horizontal coverage, counts, construction
+        write_u16s(&mut w, &[20, 12, 0, 1, 0, 18]);
+        write_u16s(&mut w, &[1, 1, 3]); // vertical coverage [3]
+                                        // MathGlyphConstruction: assembly offset, variant count, variants
+        write_u16s(&mut w, &[12, 2, 10, 1000, 11, 1500]);
+        // GlyphAssembly: italics correction, part count, parts
+        write_u16s(&mut w, &[0, 0, 3]);
+        write_u16s(&mut w, &[20, 0, 100, 500, 0]); // bottom
+        write_u16s(&mut w, &[21, 100, 100, 500, 1]); // extender
+        write_u16s(&mut w, &[22, 100, 0, 500, 0]); // top
+
+        w.into_inner()
+    }
+
+    #[test]
+    fn test_read_math_table() {
+        let data = math_table_data();
+        let math = ReadScope::new(&data).read::<MathTable<'_>>().unwrap();
+
+        assert_eq!(math.constants.script_percent_scale_down, 80);
+        assert_eq!(math.constants.display_operator_min_height, 1300);
+        assert_eq!(math.constants.math_leading, 0);
+        assert_eq!(math.constants.axis_height, 1);
+        assert_eq!(math.constants.radical_kern_after_degree, 50);
+        assert_eq!(math.constants.radical_degree_bottom_raise_percent, 65);
+
+        assert_eq!(math.glyph_info.italics_correction(1), Some(50));
+        assert_eq!(math.glyph_info.italics_correction(2), None);
+        assert_eq!(math.glyph_info.top_accent_attachment(2), Some(300));
+        assert!(math.glyph_info.is_extended_shape(2));
+        assert!(!math.glyph_info.is_extended_shape(3));
+
+        assert_eq!(math.variants.min_connector_overlap, 20);
+        let construction = math
+            .variants
+            .construction(3, StretchDirection::Vertical)
+            .unwrap()
+            .unwrap();
+        assert_eq!(construction.variants.len(), 2);
+        assert_eq!(construction.glyph_assembly.unwrap().parts.len(), 3);
+        assert!(math
+            .variants
+            .construction(3, StretchDirection::Horizontal)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_stretch() {
+        let data = math_table_data();
+        let math = ReadScope::new(&data).read::<MathTable<'_>>().unwrap();
+        let variants = &math.variants;
+
+        // Covered by the smallest size variant
+        assert_eq!(
+            variants
+                .stretch(3, StretchDirection::Vertical, 800, 900)
+                .unwrap(),
+            StretchedGlyph::Variant {
+                glyph_index: 10,
+                advance: 1000,
+            }
+        );
+        // Covered by a larger size variant
+        assert_eq!(
+            variants
+                .stretch(3, StretchDirection::Vertical, 1200, 900)
+                .unwrap(),
+            StretchedGlyph::Variant {
+                glyph_index: 11,
+                advance: 1500,
+            }
+        );
+        // Not stretchable
+        assert_eq!(
+            variants
+                .stretch(4, StretchDirection::Vertical, 1200, 900)
+                .unwrap(),
+            StretchedGlyph::Variant {
+                glyph_index: 4,
+                advance: 900,
+            }
+        );
+
+        // Assembled with the extender repeated twice and the overlap spread over the connections
+        match variants
+            .stretch(3, StretchDirection::Vertical, 1800, 900)
+            .unwrap()
+        {
+            StretchedGlyph::Assembly { parts, size, .. } => {
+                let glyphs = parts
+                    .iter()
+                    .map(|part| part.glyph_index)
+                    .collect::<Vec<_>>();
+                assert_eq!(glyphs, vec![20, 21, 21, 22]);
+                assert_eq!(parts[1].offset, 434);
+                assert_eq!(size, 1802);
+            }
+            stretched => panic!("expected assembly, got {:?}", stretched),
+        }
+    }
+}
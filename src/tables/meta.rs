@@ -0,0 +1,271 @@
+#![deny(missing_docs)]
+
+//! `meta` table parsing and writing.
+//!
+//! The `meta` table holds metadata about the font as tagged blocks of data. The registered
+//! `dlng` and `slng` entries list the languages the font was designed for and the languages it
+//! is able to support.
+//!
+//! <https://docs.microsoft.com/en-us/typography/opentype/spec/meta>
+
+use std::convert::TryFrom;
+use std::str;
+
+use crate::binary::read::{ReadArray, ReadBinary, ReadCtxt, ReadFrom, ReadScope};
+use crate::binary::write::{WriteBinary, WriteContext};
+use crate::binary::U32Be;
+use crate::error::{ParseError, WriteError};
+use crate::size;
+use crate::tag;
+
+use self::owned::MetaTable as OwnedMetaTable;
+
+/// The `meta` metadata table.
+pub struct MetaTable<'a> {
+    /// The version of the table, always 1.
+    pub version: u32,
+    /// The flags of the table, currently unused.
+    pub flags: u32,
+    /// The data maps of the table.
+    pub data_maps: ReadArray<'a, DataMap>,
+    scope: ReadScope<'a>,
+}
+
+/// A record locating the metadata for a tag.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DataMap {
+    /// The tag of the metadata.
+    pub tag: u32,
+    /// The offset of the metadata from the start of the table.
+    pub data_offset: u32,
+    /// The length of the metadata in bytes.
+    pub data_length: u32,
+}
+
+impl<'a> ReadBinary<'a> for MetaTable<'a> {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let scope = ctxt.scope();
+        let version = ctxt.read_u32be()?;
+        ctxt.check_version(version == 1)?;
+        let flags = ctxt.read_u32be()?;
+        let _reserved = ctxt.read_u32be()?;
+        let data_maps_count = usize::try_from(ctxt.read_u32be()?)?;
+        let data_maps = ctxt.read_array::<DataMap>(data_maps_count)?;
+
+        Ok(MetaTable {
+            version,
+            flags,
+            data_maps,
+            scope,
+        })
+    }
+}
+
+impl<'a> ReadFrom<'a> for DataMap {
+    type ReadType = (U32Be, U32Be, U32Be);
+
+    fn from((tag, data_offset, data_length): (u32, u32, u32)) -> Self {
+        DataMap {
+            tag,
+            data_offset,
+            data_length,
+        }
+    }
+}
+
+impl<'a> MetaTable<'a> {
+    /// Returns the metadata for `tag`, if present.
+    pub fn data(&self, tag: u32) -> Result<Option<&'a [u8]>, ParseError> {
+        match self.data_maps.iter().find(|data_map| data_map.tag == tag) {
+            Some(data_map) => {
+                let offset = usize::try_from(data_map.data_offset)?;
+                let length = usize::try_from(data_map.data_length)?;
+                Ok(Some(self.scope.offset_length(offset, length)?.data()))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the languages the font was designed for, from the `dlng` entry.
+    ///
+    /// The languages are ScriptLangTags, such as `Latn` or `zh-Hant`.
+    pub fn design_languages(&self) -> Result<Option<Vec<String>>, ParseError> {
+        self.data(tag::DLNG)?.map(parse_languages).transpose()
+    }
+
+    /// Returns the languages the font is able to support, from the `slng` entry.
+    pub fn supported_languages(&self) -> Result<Option<Vec<String>>, ParseError> {
+        self.data(tag::SLNG)?.map(parse_languages).transpose()
+    }
+
+    /// Returns an `owned::MetaTable` holding a copy of the metadata of this table.
+    pub fn to_owned(&self) -> Result<OwnedMetaTable, ParseError> {
+        let mut table = OwnedMetaTable::default();
+        for data_map in self.data_maps.iter() {
+            let offset = usize::try_from(data_map.data_offset)?;
+            let length = usize::try_from(data_map.data_length)?;
+            let data = self.scope.offset_length(offset, length)?.data();
+            table.set_data(data_map.tag, data.to_vec());
+        }
+        Ok(table)
+    }
+}
+
+/// Parses a comma separated list of ScriptLangTags.
+fn parse_languages(data: &[u8]) -> Result<Vec<String>, ParseError> {
+    let languages = str::from_utf8(data).map_err(|_| ParseError::BadValue)?;
+    Ok(languages
+        .split(',')
+        .map(str::trim)
+        .filter(|language| !language.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+/// Owned version of the `meta` table, used for writing.
+pub mod owned {
+    use super::{
+        parse_languages, size, tag, ParseError, TryFrom, U32Be, WriteBinary, WriteContext,
+        WriteError,
+    };
+
+    /// The `meta` metadata table.
+    #[derive(Debug, Clone, PartialEq, Eq, Default)]
+    pub struct MetaTable {
+        /// The metadata of the table, in the order it is written.
+        pub data_maps: Vec<DataMap>,
+    }
+
+    /// The metadata for a tag.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct DataMap {
+        /// The tag of the metadata.
+        pub tag: u32,
+        /// The metadata.
+        pub data: Vec<u8>,
+    }
+
+    impl MetaTable {
+        /// Returns the metadata for `tag`, if present.
+        pub fn data(&self, tag: u32) -> Option<&[u8]> {
+            self.data_maps
+                .iter()
+                .find(|data_map| data_map.tag == tag)
+                .map(|data_map| data_map.data.as_slice())
+        }
+
+        /// Sets the metadata for `tag`, replacing any existing metadata for it.
+        pub fn set_data(&mut self, tag: u32, data: Vec<u8>) {
+            match self
+                .data_maps
+                .iter_mut()
+                .find(|data_map| data_map.tag == tag)
+            {
+                Some(data_map) => data_map.data = data,
+                None => self.data_maps.push(DataMap { tag, data }),
+            }
+        }
+
+        /// Returns the languages the font was designed for, from the `dlng` entry.
+        pub fn design_languages(&self) -> Result<Option<Vec<String>>, ParseError> {
+            self.data(tag::DLNG).map(parse_languages).transpose()
+        }
+
+        /// Returns the languages the font is able to support, from the `slng` entry.
+        pub fn supported_languages(&self) -> Result<Option<Vec<String>>, ParseError> {
+            self.data(tag::SLNG).map(parse_languages).transpose()
+        }
+
+        /// Sets the languages the font was designed for.
+        pub fn set_design_languages<S: AsRef<str>>(&mut self, languages: &[S]) {
+            self.set_data(tag::DLNG, join_languages(languages));
+        }
+
+        /// Sets the languages the font is able to support.
+        pub fn set_supported_languages<S: AsRef<str>>(&mut self, languages: &[S]) {
+            self.set_data(tag::SLNG, join_languages(languages));
+        }
+    }
+
+    fn join_languages<S: AsRef<str>>(languages: &[S]) -> Vec<u8> {
+        languages
+            .iter()
+            .map(|language| language.as_ref().trim())
+            .collect::<Vec<_>>()
+            .join(",")
+            .into_bytes()
+    }
+
+    impl WriteBinary<&Self> for MetaTable {
+        type Output = ();
+
+        fn write<C: WriteContext>(ctxt: &mut C, table: &MetaTable) -> Result<(), WriteError> {
+            U32Be::write(ctxt, 1u32)?; // version
+            U32Be::write(ctxt, 0u32)?; // flags
+            U32Be::write(ctxt, 0u32)?; // reserved
+            U32Be::write(ctxt, u32::try_from(table.data_maps.len())?)?;
+
+            // The data follows the header and data maps
+            let mut data_offset = 4 * size::U32 + table.data_maps.len() * 3 * size::U32;
+            for data_map in &table.data_maps {
+                U32Be::write(ctxt, data_map.tag)?;
+                U32Be::write(ctxt, u32::try_from(data_offset)?)?;
+                U32Be::write(ctxt, u32::try_from(data_map.data.len())?)?;
+                data_offset += data_map.data.len();
+            }
+            for data_map in &table.data_maps {
+                ctxt.write_bytes(&data_map.data)?;
+            }
+
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::write::WriteBuffer;
+
+    #[test]
+    fn test_write_read_languages() {
+        let mut table = OwnedMetaTable::default();
+        table.set_design_languages(&["Latn", "Cyrl"]);
+        table.set_supported_languages(&["Latn", "Cyrl", "Grek"]);
+        table.set_data(tag::TRAK, b"private".to_vec());
+        // Setting the languages again replaces the existing entry
+        table.set_design_languages(&["Latn", " zh-Hant "]);
+
+        let mut w = WriteBuffer::new();
+        OwnedMetaTable::write(&mut w, &table).unwrap();
+        let meta = ReadScope::new(w.bytes()).read::<MetaTable<'_>>().unwrap();
+
+        assert_eq!(meta.data_maps.len(), 3);
+        assert_eq!(
+            meta.design_languages().unwrap(),
+            Some(vec![String::from("Latn"), String::from("zh-Hant")])
+        );
+        assert_eq!(
+            meta.supported_languages().unwrap(),
+            Some(vec![
+                String::from("Latn"),
+                String::from("Cyrl"),
+                String::from("Grek")
+            ])
+        );
+        assert_eq!(meta.data(tag::TRAK).unwrap(), Some(&b"private"[..]));
+        assert_eq!(meta.to_owned().unwrap(), table);
+    }
+
+    #[test]
+    fn test_parse_languages() {
+        let languages = parse_languages(b"Latn, Cyrl,,Hant ").unwrap();
+        assert_eq!(languages, vec!["Latn", "Cyrl", "Hant"]);
+        assert!(parse_languages(&[0xFF, 0xFE]).is_err());
+
+        let table = OwnedMetaTable::default();
+        assert_eq!(table.design_languages().unwrap(), None);
+    }
+}
@@ -0,0 +1,327 @@
+#![deny(missing_docs)]
+
+//! `morx` table parsing.
+//!
+//! The `morx` table is the Apple Advanced Typography extended glyph metamorphosis table, which
+//! fills the role of `GSUB` in fonts built for macOS. It holds chains of subtables, each of which
+//! is enabled by the feature flags of its chain.
+//!
+//! Rearrangement, contextual, ligature, noncontextual, and insertion subtables are parsed. See
+//! the [morx](../../morx/index.html) module for applying the table to glyphs.
+//!
+//! <https://developer.apple.com/fonts/TrueType-Reference-Manual/RM06/Chap6morx.html>
+
+use std::convert::TryFrom;
+
+use bitflags::bitflags;
+
+use crate::binary::read::{ReadArray, ReadBinary, ReadCtxt, ReadFrom, ReadScope};
+use crate::binary::{U16Be, U32Be};
+use crate::error::ParseError;
+use crate::tables::aat::{ExtendedStateTable, LookupTable};
+
+/// The `morx` table.
+pub struct MorxTable<'a> {
+    /// The version of the table.
+    pub version: u16,
+    /// The chains of the table, in the order they are applied.
+    pub chains: Vec<Chain<'a>>,
+}
+
+/// A chain of `morx` subtables.
+pub struct Chain<'a> {
+    /// The subtable feature flags that are enabled by default.
+    pub default_flags: u32,
+    /// The features that change the enabled subtable feature flags.
+    pub features: ReadArray<'a, ChainFeature>,
+    /// The subtables of the chain, in the order they are applied.
+    pub subtables: Vec<MorxSubtable<'a>>,
+}
+
+/// A feature of a `morx` chain.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ChainFeature {
+    /// The AAT feature type.
+    pub feature_type: u16,
+    /// The AAT feature selector.
+    pub feature_setting: u16,
+    /// The subtable feature flags to enable when the feature is selected.
+    pub enable_flags: u32,
+    /// The subtable feature flags to keep when the feature is selected, others are disabled.
+    pub disable_flags: u32,
+}
+
+/// A subtable of a `morx` chain.
+pub struct MorxSubtable<'a> {
+    /// Describes the text the subtable is applied to.
+    pub coverage: MorxCoverage,
+    /// The subtable is applied if these flags intersect the enabled flags of the chain.
+    pub sub_feature_flags: u32,
+    /// The data of the subtable.
+    pub data: MorxSubtableData<'a>,
+}
+
+bitflags! {
+    /// The coverage of a `morx` subtable.
+    pub struct MorxCoverage: u32 {
+        /// The subtable is only applied to vertical text.
+        const VERTICAL = 0x8000_0000;
+        /// The glyphs are processed in descending order.
+        const DESCENDING = 0x4000_0000;
+        /// The subtable is applied to text of any direction.
+        const ALL_DIRECTIONS = 0x2000_0000;
+        /// The glyphs are processed in logical rather than layout order.
+        const LOGICAL = 0x1000_0000;
+    }
+}
+
+/// The data of a `morx` subtable.
+pub enum MorxSubtableData<'a> {
+    /// Reorders glyphs within a marked range.
+    Rearrangement(ExtendedStateTable<'a>),
+    /// Substitutes glyphs based on their context.
+    Contextual(ContextualSubtable<'a>),
+    /// Replaces sequences of glyphs with ligatures.
+    Ligature(LigatureSubtable<'a>),
+    /// Substitutes glyphs regardless of their context.
+    Noncontextual(LookupTable<'a>),
+    /// Inserts glyphs before or after the current and marked glyphs.
+    Insertion(InsertionSubtable<'a>),
+    /// A subtable type that is not supported.
+    Unsupported {
+        /// The type of the subtable.
+        subtable_type: u8,
+    },
+}
+
+/// A contextual glyph substitution subtable.
+pub struct ContextualSubtable<'a> {
+    /// The state machine of the subtable.
+    pub state_table: ExtendedStateTable<'a>,
+    substitution_tables: ReadScope<'a>,
+}
+
+/// A ligature subtable.
+pub struct LigatureSubtable<'a> {
+    /// The state machine of the subtable.
+    pub state_table: ExtendedStateTable<'a>,
+    lig_actions: ReadScope<'a>,
+    components: ReadScope<'a>,
+    ligatures: ReadScope<'a>,
+}
+
+/// A glyph insertion subtable.
+pub struct InsertionSubtable<'a> {
+    /// The state machine of the subtable.
+    pub state_table: ExtendedStateTable<'a>,
+    insertion_glyphs: ReadScope<'a>,
+}
+
+impl<'a> ReadBinary<'a> for MorxTable<'a> {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let version = ctxt.read_u16be()?;
+        ctxt.check_version(version == 2 || version == 3)?;
+        let _unused = ctxt.read_u16be()?;
+        let n_chains = ctxt.read_u32be()?;
+
+        let mut chains = Vec::new();
+        for _ in 0..n_chains {
+            let chain_scope = ctxt.scope();
+            let default_flags = ctxt.read_u32be()?;
+            let chain_length = usize::try_from(ctxt.read_u32be()?)?;
+            let n_feature_entries = usize::try_from(ctxt.read_u32be()?)?;
+            let n_subtables = ctxt.read_u32be()?;
+            let features = ctxt.read_array::<ChainFeature>(n_feature_entries)?;
+
+            let mut subtables = Vec::new();
+            for _ in 0..n_subtables {
+                let subtable_scope = ctxt.scope();
+                let length = usize::try_from(ctxt.read_u32be()?)?;
+                let coverage = ctxt.read_u32be()?;
+                let sub_feature_flags = ctxt.read_u32be()?;
+                ctxt.check(length >= 12)?;
+                let data = read_subtable_data(ctxt.scope(), (coverage & 0xFF) as u8)?;
+                subtables.push(MorxSubtable {
+                    coverage: MorxCoverage::from_bits_truncate(coverage),
+                    sub_feature_flags,
+                    data,
+                });
+                *ctxt = subtable_scope.offset(length).ctxt();
+            }
+
+            chains.push(Chain {
+                default_flags,
+                features,
+                subtables,
+            });
+            // Version 3 chains are followed by coverage tables that are not needed here
+            *ctxt = chain_scope.offset(chain_length).ctxt();
+        }
+
+        Ok(MorxTable { version, chains })
+    }
+}
+
+/// Reads the data of a subtable, `scope` starts after the subtable header.
+fn read_subtable_data<'a>(
+    scope: ReadScope<'a>,
+    subtable_type: u8,
+) -> Result<MorxSubtableData<'a>, ParseError> {
+    // The offsets in the state table headers are relative to the start of the header
+    let mut ctxt = scope.ctxt();
+    let data = match subtable_type {
+        0 => MorxSubtableData::Rearrangement(ctxt.read::<ExtendedStateTable<'a>>()?),
+        1 => {
+            let state_table = ctxt.read::<ExtendedStateTable<'a>>()?;
+            let substitution_table_offset = usize::try_from(ctxt.read_u32be()?)?;
+            MorxSubtableData::Contextual(ContextualSubtable {
+                state_table,
+                substitution_tables: scope.offset(substitution_table_offset),
+            })
+        }
+        2 => {
+            let state_table = ctxt.read::<ExtendedStateTable<'a>>()?;
+            let lig_action_offset = usize::try_from(ctxt.read_u32be()?)?;
+            let component_offset = usize::try_from(ctxt.read_u32be()?)?;
+            let ligature_offset = usize::try_from(ctxt.read_u32be()?)?;
+            MorxSubtableData::Ligature(LigatureSubtable {
+                state_table,
+                lig_actions: scope.offset(lig_action_offset),
+                components: scope.offset(component_offset),
+                ligatures: scope.offset(ligature_offset),
+            })
+        }
+        4 => MorxSubtableData::Noncontextual(ctxt.read::<LookupTable<'a>>()?),
+        5 => {
+            let state_table = ctxt.read::<ExtendedStateTable<'a>>()?;
+            let insertion_action_offset = usize::try_from(ctxt.read_u32be()?)?;
+            MorxSubtableData::Insertion(InsertionSubtable {
+                state_table,
+                insertion_glyphs: scope.offset(insertion_action_offset),
+            })
+        }
+        _ => MorxSubtableData::Unsupported { subtable_type },
+    };
+    Ok(data)
+}
+
+impl<'a> ReadFrom<'a> for ChainFeature {
+    type ReadType = ((U16Be, U16Be), (U32Be, U32Be));
+
+    fn from(
+        ((feature_type, feature_setting), (enable_flags, disable_flags)): ((u16, u16), (u32, u32)),
+    ) -> Self {
+        ChainFeature {
+            feature_type,
+            feature_setting,
+            enable_flags,
+            disable_flags,
+        }
+    }
+}
+
+impl<'a> Chain<'a> {
+    /// Returns the subtable feature flags enabled by the AAT feature `selectors`.
+    ///
+    /// Each selector is a pair of feature type and feature setting. Starting from the default
+    /// flags of the chain, the flags of each feature matching a selector are applied in turn.
+    pub fn flags(&self, selectors: &[(u16, u16)]) -> u32 {
+        let mut flags = self.default_flags;
+        for &(feature_type, feature_setting) in selectors {
+            for feature in self.features.iter() {
+                if feature.feature_type == feature_type
+                    && feature.feature_setting == feature_setting
+                {
+                    flags = (flags & feature.disable_flags) | feature.enable_flags;
+                }
+            }
+        }
+        flags
+    }
+}
+
+impl<'a> ContextualSubtable<'a> {
+    /// Returns the substitute of `glyph` in the substitution table at `table_index`.
+    pub fn substitution(&self, table_index: u16, glyph: u16) -> Result<Option<u16>, ParseError> {
+        let mut ctxt = self
+            .substitution_tables
+            .offset(usize::from(table_index) * 4)
+            .ctxt();
+        let offset = usize::try_from(ctxt.read_u32be()?)?;
+        let lookup_table = self
+            .substitution_tables
+            .offset(offset)
+            .read::<LookupTable<'_>>()?;
+        lookup_table.lookup(glyph)
+    }
+}
+
+impl<'a> LigatureSubtable<'a> {
+    /// Returns the ligature action at `index`.
+    pub fn lig_action(&self, index: usize) -> Result<u32, ParseError> {
+        Ok(self.lig_actions.offset(index * 4).ctxt().read_u32be()?)
+    }
+
+    /// Returns the value of the component table at `index`.
+    pub fn component(&self, index: usize) -> Result<u16, ParseError> {
+        Ok(self.components.offset(index * 2).ctxt().read_u16be()?)
+    }
+
+    /// Returns the ligature glyph at `index`.
+    pub fn ligature(&self, index: usize) -> Result<u16, ParseError> {
+        Ok(self.ligatures.offset(index * 2).ctxt().read_u16be()?)
+    }
+}
+
+impl<'a> InsertionSubtable<'a> {
+    /// Returns the `count` glyphs to insert starting at `index` of the insertion glyph table.
+    pub fn insertion_glyphs(
+        &self,
+        index: u16,
+        count: usize,
+    ) -> Result<ReadArray<'a, U16Be>, ParseError> {
+        self.insertion_glyphs
+            .offset(usize::from(index) * 2)
+            .ctxt()
+            .read_array::<U16Be>(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_morx() {
+        #[rustfmt::skip]
+        let morx_data = [
+            0, 3, 0, 0, 0, 0, 0, 1, // version, unused, nChains
+            0, 0, 0, 3, 0, 0, 0, 54, 0, 0, 0, 1, 0, 0, 0, 1, // defaultFlags, chainLength, nFeatureEntries, nSubtables
+            0, 1, 0, 3, 0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFE, // featureType, featureSetting, enableFlags, disableFlags
+            0, 0, 0, 22, 0x20, 0, 0, 4, 0, 0, 0, 1, // length, coverage, subFeatureFlags
+            0, 8, 0, 10, 0, 2, 0, 20, 0, 21, // trimmed array lookup
+            0, 0, 0, 0, // subtable glyph coverage offsets
+        ];
+        let morx = ReadScope::new(&morx_data).read::<MorxTable<'_>>().unwrap();
+        assert_eq!(morx.version, 3);
+        assert_eq!(morx.chains.len(), 1);
+
+        let chain = &morx.chains[0];
+        assert_eq!(chain.flags(&[]), 3);
+        assert_eq!(chain.flags(&[(1, 3)]), 2);
+        assert_eq!(chain.flags(&[(1, 2)]), 3);
+        assert_eq!(chain.subtables.len(), 1);
+
+        let subtable = &chain.subtables[0];
+        assert_eq!(subtable.coverage, MorxCoverage::ALL_DIRECTIONS);
+        assert_eq!(subtable.sub_feature_flags, 1);
+        match subtable.data {
+            MorxSubtableData::Noncontextual(ref lookup_table) => {
+                assert_eq!(lookup_table.lookup(11).unwrap(), Some(21));
+            }
+            _ => panic!("expected noncontextual subtable"),
+        }
+    }
+}
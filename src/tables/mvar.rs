@@ -0,0 +1,200 @@
+#![deny(missing_docs)]
+
+//! `MVAR` table parsing.
+//!
+//! The `MVAR` table holds variations of font-wide metrics and other values held in tables such
+//! as `OS/2`, `hhea` and `post` of a variable font. Each value is identified by a tag and refers
+//! to deltas in an item variation store.
+//!
+//! <https://docs.microsoft.com/en-us/typography/opentype/spec/mvar>
+
+use crate::binary::read::{ReadBinary, ReadCtxt};
+use crate::error::ParseError;
+use crate::tables::vvar::ItemVariationStore;
+use crate::tables::F2Dot14;
+use crate::tag;
+
+/// The size in bytes of the value records defined by version 1.0 of the table.
+const VALUE_RECORD_SIZE: u16 = 8;
+
+/// `strs`, the strikeout size in `OS/2`.
+pub const STRIKEOUT_SIZE: u32 = tag!(b"strs");
+/// `stro`, the strikeout position in `OS/2`.
+pub const STRIKEOUT_OFFSET: u32 = tag!(b"stro");
+/// `unds`, the underline thickness in `post`.
+pub const UNDERLINE_SIZE: u32 = tag!(b"unds");
+/// `undo`, the underline position in `post`.
+pub const UNDERLINE_OFFSET: u32 = tag!(b"undo");
+
+/// The `MVAR` metrics variations table.
+pub struct MvarTable<'a> {
+    /// The deltas of the values, absent if the table has no value records.
+    pub item_variation_store: Option<ItemVariationStore<'a>>,
+    /// The values with variations, sorted by tag.
+    pub value_records: Vec<ValueRecord>,
+}
+
+/// A value with variations, referring to its deltas in the item variation store.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ValueRecord {
+    /// The tag identifying the value, such as `UNDERLINE_OFFSET`.
+    pub value_tag: u32,
+    /// The outer index of the deltas of the value.
+    pub delta_set_outer_index: u16,
+    /// The inner index of the deltas of the value.
+    pub delta_set_inner_index: u16,
+}
+
+impl<'a> ReadBinary<'a> for MvarTable<'a> {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let scope = ctxt.scope();
+        let major_version = ctxt.read_u16be()?;
+        ctxt.check_version(major_version == 1)?;
+        let _minor_version = ctxt.read_u16be()?;
+        let _reserved = ctxt.read_u16be()?;
+        let value_record_size = ctxt.read_u16be()?;
+        let value_record_count = ctxt.read_u16be()?;
+        let item_variation_store_offset = ctxt.read_u16be()?;
+
+        let mut value_records = Vec::with_capacity(usize::from(value_record_count));
+        if value_record_count > 0 {
+            ctxt.check(value_record_size >= VALUE_RECORD_SIZE)?;
+            for _ in 0..value_record_count {
+                let mut record_ctxt = ctxt.read_scope(usize::from(value_record_size))?.ctxt();
+                value_records.push(ValueRecord {
+                    value_tag: record_ctxt.read_u32be()?,
+                    delta_set_outer_index: record_ctxt.read_u16be()?,
+                    delta_set_inner_index: record_ctxt.read_u16be()?,
+                });
+            }
+        }
+
+        let item_variation_store = match item_variation_store_offset {
+            0 => None,
+            offset => Some(
+                scope
+                    .offset(usize::from(offset))
+                    .read::<ItemVariationStore<'a>>()?,
+            ),
+        };
+
+        Ok(MvarTable {
+            item_variation_store,
+            value_records,
+        })
+    }
+}
+
+impl<'a> MvarTable<'a> {
+    /// Returns the delta to the value identified by `value_tag` at the normalised variation
+    /// coordinates `coords`.
+    ///
+    /// Returns `None` if the table has no variations for the value.
+    pub fn value_delta(
+        &self,
+        value_tag: u32,
+        coords: &[F2Dot14],
+    ) -> Result<Option<f32>, ParseError> {
+        let record = match self
+            .value_records
+            .iter()
+            .find(|record| record.value_tag == value_tag)
+        {
+            Some(record) => record,
+            None => return Ok(None),
+        };
+        match self.item_variation_store {
+            Some(ref item_variation_store) => item_variation_store
+                .delta(
+                    record.delta_set_outer_index,
+                    record.delta_set_inner_index,
+                    coords,
+                )
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns `value` with the delta for `value_tag` at the normalised variation coordinates
+    /// `coords` applied, rounded to the nearest unit.
+    pub fn apply(&self, value_tag: u32, value: i16, coords: &[F2Dot14]) -> Result<i16, ParseError> {
+        match self.value_delta(value_tag, coords)? {
+            Some(delta) => Ok((f32::from(value) + delta).round() as i16),
+            None => Ok(value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::read::ReadScope;
+    use crate::binary::write::{WriteBinary, WriteBuffer, WriteContext};
+    use crate::binary::{I16Be, U16Be, U32Be};
+
+    /// Builds an `MVAR` table with one axis and a single region peaking at 1.0. The underline
+    /// position has a delta of -50 and the strikeout size a delta of 20. The value records are
+    /// padded to 10 bytes to check that the record size is respected.
+    fn mvar_data() -> Vec<u8> {
+        let mut w = WriteBuffer::new();
+        // Header
+        U16Be::write(&mut w, 1u16).unwrap();
+        U16Be::write(&mut w, 0u16).unwrap();
+        U16Be::write(&mut w, 0u16).unwrap();
+        U16Be::write(&mut w, 10u16).unwrap(); // value record size
+        U16Be::write(&mut w, 2u16).unwrap(); // value record count
+        U16Be::write(&mut w, 32u16).unwrap(); // item variation store
+
+        // Value records at 12
+        U32Be::write(&mut w, STRIKEOUT_SIZE).unwrap();
+        U16Be::write(&mut w, 0u16).unwrap();
+        U16Be::write(&mut w, 1u16).unwrap();
+        w.write_zeros(2).unwrap();
+        U32Be::write(&mut w, UNDERLINE_OFFSET).unwrap();
+        U16Be::write(&mut w, 0u16).unwrap();
+        U16Be::write(&mut w, 0u16).unwrap();
+        w.write_zeros(2).unwrap();
+
+        // Item variation store at 32
+        U16Be::write(&mut w, 1u16).unwrap();
+        U32Be::write(&mut w, 12u32).unwrap(); // region list
+        U16Be::write(&mut w, 1u16).unwrap();
+        U32Be::write(&mut w, 22u32).unwrap(); // item variation data
+
+        // Region list at 44
+        U16Be::write(&mut w, 1u16).unwrap(); // axis count
+        U16Be::write(&mut w, 1u16).unwrap(); // region count
+        U16Be::write(&mut w, 0u16).unwrap(); // start 0.0
+        U16Be::write(&mut w, 0x4000u16).unwrap(); // peak 1.0
+        U16Be::write(&mut w, 0x4000u16).unwrap(); // end 1.0
+
+        // Item variation data at 54, two items of one 16-bit delta each
+        U16Be::write(&mut w, 2u16).unwrap();
+        U16Be::write(&mut w, 1u16).unwrap();
+        U16Be::write(&mut w, 1u16).unwrap();
+        U16Be::write(&mut w, 0u16).unwrap();
+        I16Be::write(&mut w, -50i16).unwrap();
+        I16Be::write(&mut w, 20i16).unwrap();
+
+        w.into_inner()
+    }
+
+    #[test]
+    fn test_value_delta() {
+        let data = mvar_data();
+        let mvar = ReadScope::new(&data).read::<MvarTable<'_>>().unwrap();
+        let full = [F2Dot14::new(0x4000)];
+        let half = [F2Dot14::new(0x2000)];
+
+        assert_eq!(
+            mvar.value_delta(UNDERLINE_OFFSET, &full).unwrap(),
+            Some(-50.)
+        );
+        assert_eq!(mvar.value_delta(STRIKEOUT_SIZE, &half).unwrap(), Some(10.));
+        assert_eq!(mvar.value_delta(UNDERLINE_SIZE, &full).unwrap(), None);
+        assert_eq!(mvar.apply(UNDERLINE_OFFSET, -100, &half).unwrap(), -125);
+        assert_eq!(mvar.apply(UNDERLINE_OFFSET, -100, &[]).unwrap(), -100);
+    }
+}
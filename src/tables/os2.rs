@@ -5,14 +5,20 @@
 //! — <https://docs.microsoft.com/en-us/typography/opentype/spec/os2>
 
 use std::convert::TryInto;
+use std::fmt;
+
+use bitflags::bitflags;
 
 use crate::binary::read::{ReadBinaryDep, ReadCtxt};
+use crate::binary::write::{WriteBinary, WriteContext};
 use crate::binary::{I16Be, U16Be, U32Be};
-use crate::error::ParseError;
+use crate::error::{ParseError, WriteError};
+use crate::tables::MacStyle;
 
 /// `OS/2` table
 ///
 /// <https://docs.microsoft.com/en-us/typography/opentype/spec/os2>
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Os2 {
     pub version: u16,
     pub x_avg_char_width: i16,
@@ -50,6 +56,7 @@ pub struct Os2 {
     pub version5: Option<Version5>,
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Version0 {
     pub s_typo_ascender: i16,
     pub s_typo_descender: i16,
@@ -58,11 +65,13 @@ pub struct Version0 {
     pub us_win_descent: u16,
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Version1 {
     pub ul_code_page_range1: u32,
     pub ul_code_page_range2: u32,
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Version2to4 {
     pub sx_height: i16,
     pub s_cap_height: i16,
@@ -71,11 +80,349 @@ pub struct Version2to4 {
     pub us_max_context: u16,
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Version5 {
     pub us_lower_optical_point_size: u16,
     pub us_upper_optical_point_size: u16,
 }
 
+bitflags! {
+    /// Font selection flags stored in the `fsSelection` field.
+    pub struct FsSelection: u16 {
+        /// Font contains italic or oblique glyphs.
+        const ITALIC = 1 << 0;
+        /// Glyphs are underscored.
+        const UNDERSCORE = 1 << 1;
+        /// Glyphs have their foreground and background reversed.
+        const NEGATIVE = 1 << 2;
+        /// Outline (hollow) glyphs.
+        const OUTLINED = 1 << 3;
+        /// Glyphs are overstruck.
+        const STRIKEOUT = 1 << 4;
+        /// Glyphs are emboldened.
+        const BOLD = 1 << 5;
+        /// Glyphs are in the standard weight/style for the font.
+        const REGULAR = 1 << 6;
+        /// If set, it is strongly recommended that applications use `sTypoAscender`,
+        /// `sTypoDescender` and `sTypoLineGap` as the default line spacing for this font.
+        const USE_TYPO_METRICS = 1 << 7;
+        /// The font has name table strings consistent with a weight/width/slope family.
+        const WWS = 1 << 8;
+        /// Font contains oblique glyphs.
+        const OBLIQUE = 1 << 9;
+    }
+}
+
+impl Os2 {
+    /// The `fsSelection` field as flags. Reserved bits are discarded.
+    pub fn fs_selection_flags(&self) -> FsSelection {
+        FsSelection::from_bits_truncate(self.fs_selection)
+    }
+}
+
+impl FsSelection {
+    pub fn use_typo_metrics(self) -> bool {
+        self & Self::USE_TYPO_METRICS == Self::USE_TYPO_METRICS
+    }
+
+    /// Build the `fsSelection` flags for the style described by the `macStyle` flags of `head`.
+    ///
+    /// `REGULAR` is set when the style is neither bold nor italic.
+    pub fn from_mac_style(mac_style: MacStyle) -> FsSelection {
+        let mut fs_selection = FsSelection::empty();
+        fs_selection.set(FsSelection::BOLD, mac_style.contains(MacStyle::BOLD));
+        fs_selection.set(FsSelection::ITALIC, mac_style.contains(MacStyle::ITALIC));
+        fs_selection.set(
+            FsSelection::UNDERSCORE,
+            mac_style.contains(MacStyle::UNDERLINE),
+        );
+        fs_selection.set(FsSelection::OUTLINED, mac_style.contains(MacStyle::OUTLINE));
+        if !mac_style.intersects(MacStyle::BOLD | MacStyle::ITALIC) {
+            fs_selection |= FsSelection::REGULAR;
+        }
+        fs_selection
+    }
+
+    /// The `macStyle` flags of `head` that correspond to these flags.
+    pub fn mac_style(self) -> MacStyle {
+        let mut mac_style = MacStyle::empty();
+        mac_style.set(MacStyle::BOLD, self.contains(FsSelection::BOLD));
+        mac_style.set(MacStyle::ITALIC, self.contains(FsSelection::ITALIC));
+        mac_style.set(MacStyle::UNDERLINE, self.contains(FsSelection::UNDERSCORE));
+        mac_style.set(MacStyle::OUTLINE, self.contains(FsSelection::OUTLINED));
+        mac_style
+    }
+
+    /// Returns `true` if the bold and italic bits agree with those of `mac_style`, as the
+    /// specification requires.
+    pub fn is_consistent_with(self, mac_style: MacStyle) -> bool {
+        let style = MacStyle::BOLD | MacStyle::ITALIC;
+        self.mac_style() & style == mac_style & style
+    }
+}
+
+/// PANOSE classification of a font.
+///
+/// The meaning of each digit after the first depends on the family kind. The digits with typed
+/// values here follow the definitions for Latin Text fonts, which is by far the most common kind.
+/// The remaining digits are kept as their raw values.
+///
+/// <https://monotype.github.io/panose/pan1.htm>
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Panose {
+    pub family_kind: FamilyKind,
+    pub serif_style: SerifStyle,
+    pub weight: Weight,
+    pub proportion: Proportion,
+    pub contrast: u8,
+    pub stroke_variation: u8,
+    pub arm_style: u8,
+    pub letterform: u8,
+    pub midline: u8,
+    pub x_height: u8,
+}
+
+/// Builder for `Panose` values. Digits that are not set are `Any` (0).
+#[derive(Copy, Clone, Debug, Default)]
+pub struct PanoseBuilder {
+    digits: [u8; 10],
+}
+
+macro_rules! panose_digit {
+    ($(#[$attr:meta])* $name:ident { $($value:literal => $variant:ident, $display:expr;)* }) => {
+        $(#[$attr])*
+        #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize))]
+        pub enum $name {
+            $($variant,)*
+            /// A value not defined by the PANOSE specification.
+            Other(u8),
+        }
+
+        impl From<u8> for $name {
+            fn from(value: u8) -> Self {
+                match value {
+                    $($value => $name::$variant,)*
+                    _ => $name::Other(value),
+                }
+            }
+        }
+
+        impl From<$name> for u8 {
+            fn from(value: $name) -> u8 {
+                match value {
+                    $($name::$variant => $value,)*
+                    $name::Other(value) => value,
+                }
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                match self {
+                    $($name::$variant => f.write_str($display),)*
+                    $name::Other(value) => write!(f, "Unknown ({})", value),
+                }
+            }
+        }
+    };
+}
+
+panose_digit! {
+    /// PANOSE digit 1: the kind of family.
+    FamilyKind {
+        0 => Any, "Any";
+        1 => NoFit, "No Fit";
+        2 => LatinText, "Latin Text";
+        3 => LatinHandWritten, "Latin Hand Written";
+        4 => LatinDecorative, "Latin Decorative";
+        5 => LatinSymbol, "Latin Symbol";
+    }
+}
+
+panose_digit! {
+    /// PANOSE digit 2 for Latin Text: the style of the serifs.
+    SerifStyle {
+        0 => Any, "Any";
+        1 => NoFit, "No Fit";
+        2 => Cove, "Cove";
+        3 => ObtuseCove, "Obtuse Cove";
+        4 => SquareCove, "Square Cove";
+        5 => ObtuseSquareCove, "Obtuse Square Cove";
+        6 => Square, "Square";
+        7 => Thin, "Thin";
+        8 => Oval, "Oval";
+        9 => Exaggerated, "Exaggerated";
+        10 => Triangle, "Triangle";
+        11 => NormalSans, "Normal Sans";
+        12 => ObtuseSans, "Obtuse Sans";
+        13 => PerpendicularSans, "Perpendicular Sans";
+        14 => Flared, "Flared";
+        15 => Rounded, "Rounded";
+    }
+}
+
+panose_digit! {
+    /// PANOSE digit 3 for Latin Text: the weight.
+    Weight {
+        0 => Any, "Any";
+        1 => NoFit, "No Fit";
+        2 => VeryLight, "Very Light";
+        3 => Light, "Light";
+        4 => Thin, "Thin";
+        5 => Book, "Book";
+        6 => Medium, "Medium";
+        7 => Demi, "Demi";
+        8 => Bold, "Bold";
+        9 => Heavy, "Heavy";
+        10 => Black, "Black";
+        11 => ExtraBlack, "Extra Black";
+    }
+}
+
+panose_digit! {
+    /// PANOSE digit 4 for Latin Text: the proportion.
+    Proportion {
+        0 => Any, "Any";
+        1 => NoFit, "No Fit";
+        2 => OldStyle, "Old Style";
+        3 => Modern, "Modern";
+        4 => EvenWidth, "Even Width";
+        5 => Extended, "Extended";
+        6 => Condensed, "Condensed";
+        7 => VeryExtended, "Very Extended";
+        8 => VeryCondensed, "Very Condensed";
+        9 => Monospaced, "Monospaced";
+    }
+}
+
+impl From<[u8; 10]> for Panose {
+    fn from(digits: [u8; 10]) -> Self {
+        Panose {
+            family_kind: FamilyKind::from(digits[0]),
+            serif_style: SerifStyle::from(digits[1]),
+            weight: Weight::from(digits[2]),
+            proportion: Proportion::from(digits[3]),
+            contrast: digits[4],
+            stroke_variation: digits[5],
+            arm_style: digits[6],
+            letterform: digits[7],
+            midline: digits[8],
+            x_height: digits[9],
+        }
+    }
+}
+
+impl From<Panose> for [u8; 10] {
+    fn from(panose: Panose) -> Self {
+        [
+            u8::from(panose.family_kind),
+            u8::from(panose.serif_style),
+            u8::from(panose.weight),
+            u8::from(panose.proportion),
+            panose.contrast,
+            panose.stroke_variation,
+            panose.arm_style,
+            panose.letterform,
+            panose.midline,
+            panose.x_height,
+        ]
+    }
+}
+
+/// Formats the classification as its ten digits separated by spaces, E.g. `2 11 6 3 0 0 0 0 0 0`.
+impl fmt::Display for Panose {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = <[u8; 10]>::from(*self);
+        for (i, digit) in digits.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{}", digit)?;
+        }
+        Ok(())
+    }
+}
+
+impl Panose {
+    /// Returns `true` if the font is classified as a monospaced Latin Text font.
+    pub fn is_monospaced(&self) -> bool {
+        self.family_kind == FamilyKind::LatinText && self.proportion == Proportion::Monospaced
+    }
+
+    /// Returns a measure of how different this classification is from `other`.
+    ///
+    /// The result is the sum of the differences of each digit that is specified (neither `Any`
+    /// nor `No Fit`) in both classifications, so a lower value is a closer match. `None` is
+    /// returned if the family kinds are both specified but differ, as the remaining digits are
+    /// not comparable in that case.
+    pub fn distance(&self, other: &Panose) -> Option<u32> {
+        let digits = <[u8; 10]>::from(*self);
+        let other_digits = <[u8; 10]>::from(*other);
+        let specified = |digit: u8| digit > 1;
+        if specified(digits[0]) && specified(other_digits[0]) && digits[0] != other_digits[0] {
+            return None;
+        }
+        let distance = digits
+            .iter()
+            .zip(other_digits.iter())
+            .skip(1)
+            .filter(|&(&a, &b)| specified(a) && specified(b))
+            .map(|(&a, &b)| u32::from(if a > b { a - b } else { b - a }))
+            .sum();
+        Some(distance)
+    }
+}
+
+impl PanoseBuilder {
+    pub fn new() -> Self {
+        PanoseBuilder::default()
+    }
+
+    pub fn family_kind(mut self, family_kind: FamilyKind) -> Self {
+        self.digits[0] = u8::from(family_kind);
+        self
+    }
+
+    pub fn serif_style(mut self, serif_style: SerifStyle) -> Self {
+        self.digits[1] = u8::from(serif_style);
+        self
+    }
+
+    pub fn weight(mut self, weight: Weight) -> Self {
+        self.digits[2] = u8::from(weight);
+        self
+    }
+
+    pub fn proportion(mut self, proportion: Proportion) -> Self {
+        self.digits[3] = u8::from(proportion);
+        self
+    }
+
+    /// Set the digit at `index` (0–9) to a raw value.
+    ///
+    /// This is used for the digits without typed values, and for families other than Latin
+    /// Text. Indices out of range are ignored.
+    pub fn digit(mut self, index: usize, value: u8) -> Self {
+        if let Some(digit) = self.digits.get_mut(index) {
+            *digit = value;
+        }
+        self
+    }
+
+    pub fn build(self) -> Panose {
+        Panose::from(self.digits)
+    }
+}
+
+impl Os2 {
+    /// The PANOSE classification of the font.
+    pub fn panose(&self) -> Panose {
+        Panose::from(self.panose)
+    }
+}
+
 impl<'a> ReadBinaryDep<'a> for Os2 {
     type HostType = Self;
     type Args = usize;
@@ -203,14 +550,132 @@ impl<'a> ReadBinaryDep<'a> for Os2 {
     }
 }
 
+impl WriteBinary<&Self> for Os2 {
+    type Output = ();
+
+    fn write<C: WriteContext>(ctxt: &mut C, table: &Os2) -> Result<(), WriteError> {
+        U16Be::write(ctxt, table.version)?;
+        I16Be::write(ctxt, table.x_avg_char_width)?;
+        U16Be::write(ctxt, table.us_weight_class)?;
+        U16Be::write(ctxt, table.us_width_class)?;
+        U16Be::write(ctxt, table.fs_type)?;
+        I16Be::write(ctxt, table.y_subscript_x_size)?;
+        I16Be::write(ctxt, table.y_subscript_y_size)?;
+        I16Be::write(ctxt, table.y_subscript_x_offset)?;
+        I16Be::write(ctxt, table.y_subscript_y_offset)?;
+        I16Be::write(ctxt, table.y_superscript_x_size)?;
+        I16Be::write(ctxt, table.y_superscript_y_size)?;
+        I16Be::write(ctxt, table.y_superscript_x_offset)?;
+        I16Be::write(ctxt, table.y_superscript_y_offset)?;
+        I16Be::write(ctxt, table.y_strikeout_size)?;
+        I16Be::write(ctxt, table.y_strikeout_position)?;
+        I16Be::write(ctxt, table.s_family_class)?;
+        ctxt.write_bytes(&table.panose)?;
+        U32Be::write(ctxt, table.ul_unicode_range1)?;
+        U32Be::write(ctxt, table.ul_unicode_range2)?;
+        U32Be::write(ctxt, table.ul_unicode_range3)?;
+        U32Be::write(ctxt, table.ul_unicode_range4)?;
+        U32Be::write(ctxt, table.ach_vend_id)?;
+        U16Be::write(ctxt, table.fs_selection)?;
+        U16Be::write(ctxt, table.us_first_char_index)?;
+        U16Be::write(ctxt, table.us_last_char_index)?;
+
+        // The version specific fields can only be written if the preceding ones are present
+        if let Some(version0) = &table.version0 {
+            I16Be::write(ctxt, version0.s_typo_ascender)?;
+            I16Be::write(ctxt, version0.s_typo_descender)?;
+            I16Be::write(ctxt, version0.s_typo_line_gap)?;
+            U16Be::write(ctxt, version0.us_win_ascent)?;
+            U16Be::write(ctxt, version0.us_win_descent)?;
+        } else {
+            return Ok(());
+        }
+
+        if let Some(version1) = &table.version1 {
+            U32Be::write(ctxt, version1.ul_code_page_range1)?;
+            U32Be::write(ctxt, version1.ul_code_page_range2)?;
+        } else {
+            return Ok(());
+        }
+
+        if let Some(version2to4) = &table.version2to4 {
+            I16Be::write(ctxt, version2to4.sx_height)?;
+            I16Be::write(ctxt, version2to4.s_cap_height)?;
+            U16Be::write(ctxt, version2to4.us_default_char)?;
+            U16Be::write(ctxt, version2to4.us_break_char)?;
+            U16Be::write(ctxt, version2to4.us_max_context)?;
+        } else {
+            return Ok(());
+        }
+
+        if let Some(version5) = &table.version5 {
+            U16Be::write(ctxt, version5.us_lower_optical_point_size)?;
+            U16Be::write(ctxt, version5.us_upper_optical_point_size)?;
+        }
+
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::binary::read::ReadScope;
+    use crate::binary::write::WriteBuffer;
     use crate::tables::{FontTableProvider, OpenTypeFont};
     use crate::tag;
     use crate::tests::read_fixture;
 
+    #[test]
+    fn test_fs_selection_mac_style() {
+        let mac_style = MacStyle::BOLD | MacStyle::ITALIC | MacStyle::SHADOW;
+        let fs_selection = FsSelection::from_mac_style(mac_style);
+        assert_eq!(fs_selection, FsSelection::BOLD | FsSelection::ITALIC);
+        assert_eq!(fs_selection.mac_style(), MacStyle::BOLD | MacStyle::ITALIC);
+        assert!(fs_selection.is_consistent_with(mac_style));
+        assert!(!fs_selection.is_consistent_with(MacStyle::BOLD));
+
+        let regular = FsSelection::from_mac_style(MacStyle::UNDERLINE);
+        assert_eq!(regular, FsSelection::REGULAR | FsSelection::UNDERSCORE);
+        assert!(regular.is_consistent_with(MacStyle::empty()));
+    }
+
+    #[test]
+    fn test_panose_round_trip() {
+        let digits = [2, 11, 6, 9, 2, 2, 4, 3, 2, 4];
+        let panose = Panose::from(digits);
+        assert_eq!(panose.family_kind, FamilyKind::LatinText);
+        assert_eq!(panose.serif_style, SerifStyle::NormalSans);
+        assert_eq!(panose.weight, Weight::Medium);
+        assert_eq!(panose.proportion, Proportion::Monospaced);
+        assert!(panose.is_monospaced());
+        assert_eq!(<[u8; 10]>::from(panose), digits);
+        assert_eq!(panose.to_string(), "2 11 6 9 2 2 4 3 2 4");
+        assert_eq!(panose.weight.to_string(), "Medium");
+        assert_eq!(Weight::from(42).to_string(), "Unknown (42)");
+    }
+
+    #[test]
+    fn test_panose_builder_and_distance() {
+        let bold = PanoseBuilder::new()
+            .family_kind(FamilyKind::LatinText)
+            .weight(Weight::Bold)
+            .build();
+        let book = PanoseBuilder::new()
+            .family_kind(FamilyKind::LatinText)
+            .serif_style(SerifStyle::Cove)
+            .weight(Weight::Book)
+            .build();
+        let decorative = PanoseBuilder::new()
+            .family_kind(FamilyKind::LatinDecorative)
+            .build();
+
+        // serif style is Any in `bold` so only the weight is compared
+        assert_eq!(bold.distance(&book), Some(3));
+        assert_eq!(bold.distance(&decorative), None);
+        assert_eq!(book.distance(&book), Some(0));
+    }
+
     #[test]
     #[cfg(feature = "prince")]
     fn test_read() {
@@ -228,4 +693,19 @@ mod tests {
         assert!(os_2.version2to4.is_none());
         assert!(os_2.version5.is_none());
     }
+
+    #[test]
+    fn test_write_round_trip() {
+        let buffer = read_fixture("tests/fonts/opentype/Klei.otf");
+        let opentype_file = ReadScope::new(&buffer).read::<OpenTypeFont<'_>>().unwrap();
+        let provider = opentype_file.table_provider(0).unwrap();
+        let os_2_data = provider.read_table_data(tag::OS_2).unwrap();
+        let os_2 = ReadScope::new(&os_2_data)
+            .read_dep::<Os2>(os_2_data.len())
+            .unwrap();
+
+        let mut buffer = WriteBuffer::new();
+        Os2::write(&mut buffer, &os_2).unwrap();
+        assert_eq!(buffer.bytes(), &*os_2_data);
+    }
 }
@@ -0,0 +1,389 @@
+#![deny(missing_docs)]
+
+//! `STAT` table parsing.
+//!
+//! The style attributes table describes the design axes of a font family and names positions and
+//! ranges along them, such as "Bold" for a weight of 700. The names are used to build the style
+//! names of instances of a variable font, such as "Condensed Bold".
+//!
+//! <https://docs.microsoft.com/en-us/typography/opentype/spec/stat>
+
+use std::convert::TryFrom;
+
+use bitflags::bitflags;
+
+use crate::binary::read::{ReadBinary, ReadCtxt};
+use crate::binary::U16Be;
+use crate::error::ParseError;
+use crate::tables::fvar::read_fixed;
+
+/// The size of a design axis record defined by version 1.0 of the table.
+const AXIS_RECORD_SIZE: u16 = 8;
+
+/// The `STAT` style attributes table.
+pub struct StatTable {
+    /// The design axes, referred to by index from axis values.
+    pub design_axes: Vec<AxisRecord>,
+    /// The named positions and ranges along the design axes.
+    pub axis_values: Vec<AxisValue>,
+    /// The `name` table ID of the name to use when all axis values of a style are elided,
+    /// usually "Regular". `None` for version 1.0 tables, which don't have it.
+    pub elided_fallback_name_id: Option<u16>,
+}
+
+/// A design axis.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AxisRecord {
+    /// The tag identifying the axis, such as `wght`.
+    pub axis_tag: u32,
+    /// The `name` table ID of the display name of the axis.
+    pub axis_name_id: u16,
+    /// The position of the axis when ordering the names of axis values in a style name.
+    pub axis_ordering: u16,
+}
+
+bitflags! {
+    /// Flags of an axis value.
+    pub struct AxisValueFlags: u16 {
+        /// The value is used by another font in the family with the same position on this axis.
+        const OLDER_SIBLING_FONT_ATTRIBUTE = 0x0001;
+        /// The name of the value may be left out when composing a style name, such as "Regular".
+        const ELIDABLE_AXIS_VALUE_NAME = 0x0002;
+    }
+}
+
+/// A named position or range on one or more design axes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AxisValue {
+    /// Flags describing the value.
+    pub flags: AxisValueFlags,
+    /// The `name` table ID of the name of the value, such as "Bold".
+    pub value_name_id: u16,
+    /// The position of the value.
+    pub data: AxisValueData,
+}
+
+/// The position of an axis value, as given by the different formats of axis value table.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AxisValueData {
+    /// Format 1, a single value on one axis.
+    Value {
+        /// The index of the axis in `design_axes`.
+        axis_index: u16,
+        /// The value on the axis.
+        value: f32,
+    },
+    /// Format 2, a range of values on one axis.
+    Range {
+        /// The index of the axis in `design_axes`.
+        axis_index: u16,
+        /// The nominal value of the range.
+        nominal_value: f32,
+        /// The minimum value of the range.
+        range_min_value: f32,
+        /// The maximum value of the range.
+        range_max_value: f32,
+    },
+    /// Format 3, a single value on one axis with a value linked to it, such as bold for regular.
+    LinkedValue {
+        /// The index of the axis in `design_axes`.
+        axis_index: u16,
+        /// The value on the axis.
+        value: f32,
+        /// The linked value on the axis.
+        linked_value: f32,
+    },
+    /// Format 4, a combination of values on several axes, as `(axis_index, value)` pairs.
+    MultipleValues(Vec<(u16, f32)>),
+}
+
+impl<'a> ReadBinary<'a> for StatTable {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let scope = ctxt.scope();
+        let major_version = ctxt.read_u16be()?;
+        ctxt.check_version(major_version == 1)?;
+        let minor_version = ctxt.read_u16be()?;
+        let design_axis_size = ctxt.read_u16be()?;
+        ctxt.check_field(design_axis_size >= AXIS_RECORD_SIZE, "designAxisSize")?;
+        let design_axis_count = ctxt.read_u16be()?;
+        let design_axes_offset = ctxt.read_u32be()?;
+        let axis_value_count = ctxt.read_u16be()?;
+        let offset_to_axis_value_offsets = ctxt.read_u32be()?;
+        let elided_fallback_name_id = if minor_version >= 1 {
+            Some(ctxt.read_u16be()?)
+        } else {
+            None
+        };
+
+        let design_axes = if design_axis_count > 0 {
+            let mut ctxt = scope.offset(usize::try_from(design_axes_offset)?).ctxt();
+            (0..design_axis_count)
+                .map(|_| {
+                    let record = AxisRecord {
+                        axis_tag: ctxt.read_u32be()?,
+                        axis_name_id: ctxt.read_u16be()?,
+                        axis_ordering: ctxt.read_u16be()?,
+                    };
+                    // Later versions may add fields to the record
+                    let _ = ctxt.read_slice(usize::from(design_axis_size - AXIS_RECORD_SIZE))?;
+                    Ok(record)
+                })
+                .collect::<Result<Vec<_>, ParseError>>()?
+        } else {
+            Vec::new()
+        };
+
+        let axis_values = if axis_value_count > 0 {
+            let offsets_scope = scope.offset(usize::try_from(offset_to_axis_value_offsets)?);
+            let offsets = offsets_scope
+                .ctxt()
+                .read_array::<U16Be>(usize::from(axis_value_count))?;
+            offsets
+                .iter()
+                .map(|offset| {
+                    offsets_scope
+                        .offset(usize::from(offset))
+                        .read::<AxisValue>()
+                })
+                .collect::<Result<Vec<_>, ParseError>>()?
+        } else {
+            Vec::new()
+        };
+
+        Ok(StatTable {
+            design_axes,
+            axis_values,
+            elided_fallback_name_id,
+        })
+    }
+}
+
+impl<'a> ReadBinary<'a> for AxisValue {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let format = ctxt.read_u16be()?;
+        let (flags, value_name_id, data) = match format {
+            1..=3 => {
+                let axis_index = ctxt.read_u16be()?;
+                let flags = ctxt.read_u16be()?;
+                let value_name_id = ctxt.read_u16be()?;
+                let value = read_fixed(ctxt)?;
+                let data = match format {
+                    1 => AxisValueData::Value { axis_index, value },
+                    2 => AxisValueData::Range {
+                        axis_index,
+                        nominal_value: value,
+                        range_min_value: read_fixed(ctxt)?,
+                        range_max_value: read_fixed(ctxt)?,
+                    },
+                    _ => AxisValueData::LinkedValue {
+                        axis_index,
+                        value,
+                        linked_value: read_fixed(ctxt)?,
+                    },
+                };
+                (flags, value_name_id, data)
+            }
+            4 => {
+                let axis_count = ctxt.read_u16be()?;
+                let flags = ctxt.read_u16be()?;
+                let value_name_id = ctxt.read_u16be()?;
+                let values = (0..axis_count)
+                    .map(|_| Ok((ctxt.read_u16be()?, read_fixed(ctxt)?)))
+                    .collect::<Result<Vec<_>, ParseError>>()?;
+                (flags, value_name_id, AxisValueData::MultipleValues(values))
+            }
+            _ => return Err(ParseError::BadVersion),
+        };
+
+        Ok(AxisValue {
+            flags: AxisValueFlags::from_bits_truncate(flags),
+            value_name_id,
+            data,
+        })
+    }
+}
+
+impl AxisValue {
+    /// Returns `true` if the name of the value may be left out of style names.
+    pub fn is_elidable(&self) -> bool {
+        self.flags
+            .contains(AxisValueFlags::ELIDABLE_AXIS_VALUE_NAME)
+    }
+
+    /// The indices of the design axes the value is on.
+    pub fn axis_indices(&self) -> Vec<u16> {
+        match &self.data {
+            AxisValueData::Value { axis_index, .. }
+            | AxisValueData::Range { axis_index, .. }
+            | AxisValueData::LinkedValue { axis_index, .. } => vec![*axis_index],
+            AxisValueData::MultipleValues(values) => {
+                values.iter().map(|&(axis_index, _)| axis_index).collect()
+            }
+        }
+    }
+}
+
+impl StatTable {
+    /// Returns the axis values that name the position `location`, in the order of their axes.
+    ///
+    /// `location` holds the user coordinate of each axis as `(axis_tag, value)` pairs. Values on
+    /// axes that aren't in `location` always match. Elidable values are left out, and where
+    /// several values name the same axis only the first is returned, with format 4 values that
+    /// cover the most axes taking precedence. These are the values whose names make up the style
+    /// name of the instance at `location`.
+    pub fn axis_values_at(&self, location: &[(u32, f32)]) -> Vec<&AxisValue> {
+        let coordinate = |axis_index: u16| {
+            let axis = self.design_axes.get(usize::from(axis_index))?;
+            location
+                .iter()
+                .find(|&&(tag, _)| tag == axis.axis_tag)
+                .map(|&(_, value)| value)
+        };
+        // Whether the coordinate of an axis is within a range, treating missing axes as matching
+        let in_range = |axis_index: u16, min: f32, max: f32| match coordinate(axis_index) {
+            Some(coord) => min <= coord && coord <= max,
+            None => true,
+        };
+        let matches = |axis_value: &AxisValue| match &axis_value.data {
+            AxisValueData::Value { axis_index, value }
+            | AxisValueData::LinkedValue {
+                axis_index, value, ..
+            } => in_range(*axis_index, *value, *value),
+            AxisValueData::Range {
+                axis_index,
+                range_min_value,
+                range_max_value,
+                ..
+            } => in_range(*axis_index, *range_min_value, *range_max_value),
+            AxisValueData::MultipleValues(values) => values
+                .iter()
+                .all(|&(axis_index, value)| in_range(axis_index, value, value)),
+        };
+        let mut candidates = self
+            .axis_values
+            .iter()
+            .filter(|axis_value| !axis_value.is_elidable() && matches(axis_value))
+            .collect::<Vec<_>>();
+        // Format 4 values come first, those covering the most axes first of all. The sort is
+        // stable so values are otherwise kept in table order.
+        candidates.sort_by_key(|axis_value| match &axis_value.data {
+            AxisValueData::MultipleValues(values) => std::cmp::Reverse(values.len()),
+            _ => std::cmp::Reverse(0),
+        });
+
+        let mut seen_axes = Vec::new();
+        let mut results = Vec::new();
+        for axis_value in candidates {
+            let axis_indices = axis_value.axis_indices();
+            if axis_indices
+                .iter()
+                .any(|axis_index| seen_axes.contains(axis_index))
+            {
+                continue;
+            }
+            seen_axes.extend_from_slice(&axis_indices);
+            if let Some(&first_axis) = axis_indices.iter().min() {
+                results.push((first_axis, axis_value));
+            }
+        }
+        results.sort_by_key(|&(axis_index, _)| axis_index);
+        results
+            .into_iter()
+            .map(|(_, axis_value)| axis_value)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::binary::read::ReadScope;
+    use crate::tag;
+
+    /// A `STAT` table with `wght` and `wdth` axes, and names 256–261 for Regular (elidable),
+    /// Bold, Light to Medium, Condensed, Normal width (elidable), and a Bold Condensed combination.
+    pub(crate) fn stat_data() -> Vec<u8> {
+        let mut data = vec![
+            0, 1, 0, 1, // version 1.1
+            0, 8, // designAxisSize
+            0, 2, // designAxisCount
+            0, 0, 0, 20, // designAxesOffset
+            0, 6, // axisValueCount
+            0, 0, 0, 36, // offsetToAxisValueOffsets
+            0, 2, // elidedFallbackNameID
+        ];
+        data.extend_from_slice(b"wght");
+        data.extend_from_slice(&[1, 0, 0, 0]); // axisNameID, axisOrdering
+        data.extend_from_slice(b"wdth");
+        data.extend_from_slice(&[1, 1, 0, 1]);
+        // Offsets to the axis values, which follow the 12 byte offset array
+        data.extend_from_slice(&[0, 12, 0, 24, 0, 36, 0, 56, 0, 68, 0, 80]);
+        // Format 1, wght, elidable, Regular 400
+        data.extend_from_slice(&[0, 1, 0, 0, 0, 2, 1, 0, 1, 144, 0, 0]);
+        // Format 1, wght, Bold 700
+        data.extend_from_slice(&[0, 1, 0, 0, 0, 0, 1, 1, 2, 188, 0, 0]);
+        // Format 2, wght, Light to Medium, nominal 300, range 250–350
+        data.extend_from_slice(&[0, 2, 0, 0, 0, 0, 1, 2]);
+        data.extend_from_slice(&[1, 44, 0, 0, 0, 250, 0, 0, 1, 94, 0, 0]);
+        // Format 1, wdth, Condensed 75
+        data.extend_from_slice(&[0, 1, 0, 1, 0, 0, 1, 3, 0, 75, 0, 0]);
+        // Format 1, wdth, elidable, Normal 100
+        data.extend_from_slice(&[0, 1, 0, 1, 0, 2, 1, 4, 0, 100, 0, 0]);
+        // Format 4, Bold Condensed, wght 700 and wdth 75
+        data.extend_from_slice(&[0, 4, 0, 2, 0, 0, 1, 5]);
+        data.extend_from_slice(&[0, 0, 2, 188, 0, 0, 0, 1, 0, 75, 0, 0]);
+        data
+    }
+
+    #[test]
+    fn test_read_stat() {
+        let data = stat_data();
+        let stat = ReadScope::new(&data).read::<StatTable>().unwrap();
+        assert_eq!(stat.elided_fallback_name_id, Some(2));
+        assert_eq!(
+            stat.design_axes[1],
+            AxisRecord {
+                axis_tag: tag!(b"wdth"),
+                axis_name_id: 257,
+                axis_ordering: 1,
+            }
+        );
+        assert_eq!(stat.axis_values.len(), 6);
+        assert!(stat.axis_values[0].is_elidable());
+        assert_eq!(
+            stat.axis_values[2].data,
+            AxisValueData::Range {
+                axis_index: 0,
+                nominal_value: 300.,
+                range_min_value: 250.,
+                range_max_value: 350.,
+            }
+        );
+        assert_eq!(
+            stat.axis_values[5].data,
+            AxisValueData::MultipleValues(vec![(0, 700.), (1, 75.)])
+        );
+    }
+
+    #[test]
+    fn test_axis_values_at() {
+        let data = stat_data();
+        let stat = ReadScope::new(&data).read::<StatTable>().unwrap();
+        let names = |location: &[(u32, f32)]| {
+            stat.axis_values_at(location)
+                .iter()
+                .map(|axis_value| axis_value.value_name_id)
+                .collect::<Vec<_>>()
+        };
+        let (wght, wdth) = (tag!(b"wght"), tag!(b"wdth"));
+        assert_eq!(names(&[(wght, 400.), (wdth, 100.)]), Vec::<u16>::new());
+        assert_eq!(names(&[(wght, 700.), (wdth, 100.)]), vec![257]);
+        assert_eq!(names(&[(wght, 320.), (wdth, 75.)]), vec![258, 259]);
+        // The format 4 value names both axes
+        assert_eq!(names(&[(wght, 700.), (wdth, 75.)]), vec![261]);
+    }
+}
@@ -4,6 +4,7 @@
 //!
 //! <https://docs.microsoft.com/en-us/typography/opentype/spec/SVG>
 
+use std::borrow::Cow;
 use std::convert::TryFrom;
 use std::io::Read;
 
@@ -58,12 +59,48 @@ impl<'a> SvgTable<'a> {
     pub fn lookup_glyph(&self, glyph_id: u16) -> Result<Option<SVGDocumentRecord<'a>>, ParseError> {
         for record in self.document_records.iter_res() {
             let record = record?;
-            if glyph_id >= record.start_glyph_id && glyph_id <= record.end_glyph_id {
+            if (record.start_glyph_id..=record.end_glyph_id).contains(&glyph_id) {
                 return Ok(Some(record));
             }
         }
         Ok(None)
     }
+
+    /// Returns the SVG document that contains the glyph `glyph_id`, decompressed if necessary.
+    ///
+    /// A document may hold several glyphs, in which case the glyph is the element with the id
+    /// `glyph<glyph_id>`. Returns `None` if the glyph does not have an SVG document.
+    pub fn svg_document_for_glyph(
+        &self,
+        glyph_id: u16,
+    ) -> Result<Option<Cow<'a, [u8]>>, ParseError> {
+        match self.lookup_glyph(glyph_id)? {
+            Some(record) => record.document().map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+impl<'a> SVGDocumentRecord<'a> {
+    /// Returns `true` if the document is gzip compressed.
+    pub fn is_compressed(&self) -> bool {
+        // &[0x1F, 0x8B, 0x08] is a gzip member header indicating "deflate" as the compression
+        // method. See section 2.3.1 of https://www.ietf.org/rfc/rfc1952.txt
+        self.svg_document.starts_with(GZIP_HEADER)
+    }
+
+    /// Returns the SVG document, inflating it if it is compressed.
+    pub fn document(&self) -> Result<Cow<'a, [u8]>, ParseError> {
+        if self.is_compressed() {
+            let mut gz = GzDecoder::new(self.svg_document);
+            let mut uncompressed = Vec::with_capacity(self.svg_document.len());
+            gz.read_to_end(&mut uncompressed)
+                .map_err(|_err| ParseError::CompressionError)?;
+            Ok(Cow::Owned(uncompressed))
+        } else {
+            Ok(Cow::Borrowed(self.svg_document))
+        }
+    }
 }
 
 impl<'a> ReadBinary<'a> for SvgTable<'a> {
@@ -121,18 +158,7 @@ impl<'a> TryFrom<&SVGDocumentRecord<'a>> for BitmapGlyph {
     type Error = ParseError;
 
     fn try_from(svg_record: &SVGDocumentRecord<'a>) -> Result<Self, ParseError> {
-        // If the document is compressed then inflate it. &[0x1F, 0x8B, 0x08] is a gzip member
-        // header indicating "deflate" as the compression method. See section 2.3.1 of
-        // https://www.ietf.org/rfc/rfc1952.txt
-        let data = if svg_record.svg_document.starts_with(GZIP_HEADER) {
-            let mut gz = GzDecoder::new(svg_record.svg_document);
-            let mut uncompressed = Vec::with_capacity(svg_record.svg_document.len());
-            gz.read_to_end(&mut uncompressed)
-                .map_err(|_err| ParseError::CompressionError)?;
-            uncompressed.into_boxed_slice()
-        } else {
-            Box::from(svg_record.svg_document)
-        };
+        let data = svg_record.document()?.into_owned().into_boxed_slice();
 
         let encapsulated = EncapsulatedBitmap {
             format: EncapsulatedFormat::Svg,
@@ -222,4 +248,34 @@ mod tests {
             _ => panic!("did not get expected result"),
         }
     }
+
+    #[test]
+    fn test_svg_document_for_glyph() {
+        let buffer = read_fixture("tests/fonts/svg/gzipped.ttf");
+        let scope = ReadScope::new(&buffer);
+        let font_file = scope
+            .read::<FontData<'_>>()
+            .expect("unable to parse font file");
+        let table_provider = font_file
+            .table_provider(0)
+            .expect("unable to create font provider");
+        let svg_data = table_provider
+            .read_table_data(tag::SVG)
+            .expect("unable to read SVG table data");
+        let svg = ReadScope::new(&svg_data).read::<SvgTable<'_>>().unwrap();
+        let record = svg
+            .document_records
+            .iter_res()
+            .into_iter()
+            .nth(0)
+            .unwrap()
+            .unwrap();
+        assert!(record.is_compressed());
+
+        for glyph_id in record.start_glyph_id..=record.end_glyph_id {
+            let doc = svg.svg_document_for_glyph(glyph_id).unwrap().unwrap();
+            assert!(doc.starts_with(br#"<?xml version="1.0" encoding="UTF-8"?><svg"#));
+        }
+        assert!(svg.svg_document_for_glyph(0).unwrap().is_none());
+    }
 }
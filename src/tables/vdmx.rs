@@ -0,0 +1,229 @@
+#![deny(missing_docs)]
+
+//! `VDMX` table parsing.
+//!
+//! The `VDMX` table holds the maximum and minimum y extents, in pixels, of all glyphs after they
+//! have been grid-fitted at particular sizes. It allows the line height to be determined without
+//! hinting every glyph.
+//!
+//! <https://docs.microsoft.com/en-us/typography/opentype/spec/vdmx>
+
+use crate::binary::read::{ReadArray, ReadBinary, ReadCtxt, ReadFrom};
+use crate::binary::{I16Be, U16Be, U8};
+use crate::error::ParseError;
+
+/// The `VDMX` vertical device metrics table.
+pub struct VdmxTable<'a> {
+    /// The version of the table, 0 or 1.
+    pub version: u16,
+    /// The aspect ratios covered by the table.
+    pub ratios: ReadArray<'a, RatioRange>,
+    /// The groups of the table, one for each entry of `ratios`.
+    pub groups: Vec<VdmxGroup<'a>>,
+}
+
+/// A range of aspect ratios, as x to y ratios.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RatioRange {
+    /// The character set the ratio applies to.
+    pub char_set: u8,
+    /// The x ratio. A ratio of 0 matches all aspect ratios.
+    pub x_ratio: u8,
+    /// The starting y ratio.
+    pub y_start_ratio: u8,
+    /// The ending y ratio.
+    pub y_end_ratio: u8,
+}
+
+/// The y extents for a range of sizes.
+pub struct VdmxGroup<'a> {
+    /// The smallest size in the group, in ppem.
+    pub start_size: u8,
+    /// The largest size in the group, in ppem.
+    pub end_size: u8,
+    /// The y extents of the sizes in the group, sorted by size.
+    pub entries: ReadArray<'a, VdmxRecord>,
+}
+
+/// The y extents of all glyphs at a single size.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct VdmxRecord {
+    /// The size the extents apply to, in ppem.
+    pub y_pel_height: u16,
+    /// The maximum y extent, in pixels.
+    pub y_max: i16,
+    /// The minimum y extent, in pixels.
+    pub y_min: i16,
+}
+
+impl<'a> ReadBinary<'a> for VdmxTable<'a> {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let scope = ctxt.scope();
+        let version = ctxt.read_u16be()?;
+        ctxt.check_version(version <= 1)?;
+        let _num_recs = ctxt.read_u16be()?;
+        let num_ratios = usize::from(ctxt.read_u16be()?);
+        let ratios = ctxt.read_array::<RatioRange>(num_ratios)?;
+        let offsets = ctxt.read_array::<U16Be>(num_ratios)?;
+        let groups = offsets
+            .iter()
+            .map(|offset| scope.offset(usize::from(offset)).read::<VdmxGroup<'a>>())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(VdmxTable {
+            version,
+            ratios,
+            groups,
+        })
+    }
+}
+
+impl<'a> ReadBinary<'a> for VdmxGroup<'a> {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let recs = ctxt.read_u16be()?;
+        let start_size = ctxt.read_u8()?;
+        let end_size = ctxt.read_u8()?;
+        let entries = ctxt.read_array::<VdmxRecord>(usize::from(recs))?;
+
+        Ok(VdmxGroup {
+            start_size,
+            end_size,
+            entries,
+        })
+    }
+}
+
+impl<'a> ReadFrom<'a> for RatioRange {
+    type ReadType = ((U8, U8), (U8, U8));
+
+    fn from(((char_set, x_ratio), (y_start_ratio, y_end_ratio)): ((u8, u8), (u8, u8))) -> Self {
+        RatioRange {
+            char_set,
+            x_ratio,
+            y_start_ratio,
+            y_end_ratio,
+        }
+    }
+}
+
+impl<'a> ReadFrom<'a> for VdmxRecord {
+    type ReadType = (U16Be, I16Be, I16Be);
+
+    fn from((y_pel_height, y_max, y_min): (u16, i16, i16)) -> Self {
+        VdmxRecord {
+            y_pel_height,
+            y_max,
+            y_min,
+        }
+    }
+}
+
+impl RatioRange {
+    /// Returns `true` if the aspect ratio `x_ratio` to `y_ratio` is in this range.
+    pub fn matches(&self, x_ratio: u16, y_ratio: u16) -> bool {
+        if self.x_ratio == 0 {
+            return true;
+        }
+        // Compare y / x against the range by cross-multiplying to avoid division
+        let y = u32::from(y_ratio) * u32::from(self.x_ratio);
+        let start = u32::from(self.y_start_ratio) * u32::from(x_ratio);
+        let end = u32::from(self.y_end_ratio) * u32::from(x_ratio);
+        (start..=end).contains(&y)
+    }
+}
+
+impl<'a> VdmxGroup<'a> {
+    /// Returns the record for `ppem`, if the group has one.
+    pub fn record(&self, ppem: u16) -> Option<VdmxRecord> {
+        if ppem < u16::from(self.start_size) || ppem > u16::from(self.end_size) {
+            return None;
+        }
+        let (mut lo, mut hi) = (0, self.entries.len());
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            let record = self.entries.get_item(mid);
+            if record.y_pel_height < ppem {
+                lo = mid + 1;
+            } else if record.y_pel_height > ppem {
+                hi = mid;
+            } else {
+                return Some(record);
+            }
+        }
+        None
+    }
+}
+
+impl<'a> VdmxTable<'a> {
+    /// Returns the y extents of glyphs at `ppem` on a device with square pixels.
+    pub fn vdmx_extents(&self, ppem: u16) -> Option<VdmxRecord> {
+        self.vdmx_extents_for_ratio(ppem, 1, 1)
+    }
+
+    /// Returns the y extents of glyphs at `ppem` on a device with an `x_ratio` to `y_ratio`
+    /// aspect ratio.
+    ///
+    /// The first ratio range of the table that matches the aspect ratio is used. Returns `None`
+    /// if there is no matching range, or the matching group has no record for `ppem`.
+    pub fn vdmx_extents_for_ratio(
+        &self,
+        ppem: u16,
+        x_ratio: u16,
+        y_ratio: u16,
+    ) -> Option<VdmxRecord> {
+        let index = self
+            .ratios
+            .iter()
+            .position(|ratio| ratio.matches(x_ratio, y_ratio))?;
+        self.groups.get(index)?.record(ppem)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::read::ReadScope;
+    use crate::font_data::FontData;
+    use crate::tables::FontTableProvider;
+    use crate::tag;
+    use crate::tests::read_fixture;
+
+    #[test]
+    fn test_vdmx_extents() {
+        let buffer = read_fixture("tests/fonts/gurmukhi/Saab.ttf");
+        let scope = ReadScope::new(&buffer);
+        let font_file = scope.read::<FontData<'_>>().unwrap();
+        let provider = font_file.table_provider(0).unwrap();
+        let vdmx_data = provider.read_table_data(tag::VDMX).unwrap();
+        let vdmx = ReadScope::new(&vdmx_data).read::<VdmxTable<'_>>().unwrap();
+
+        assert_eq!(vdmx.version, 0);
+        assert_eq!(vdmx.ratios.len(), 1);
+        assert_eq!(vdmx.groups[0].start_size, 8);
+        assert_eq!(vdmx.groups[0].end_size, 255);
+        assert_eq!(vdmx.groups[0].entries.len(), 248);
+        assert_eq!(
+            vdmx.vdmx_extents(12),
+            Some(VdmxRecord {
+                y_pel_height: 12,
+                y_max: 12,
+                y_min: -8
+            })
+        );
+        assert_eq!(
+            vdmx.vdmx_extents(100),
+            Some(VdmxRecord {
+                y_pel_height: 100,
+                y_max: 100,
+                y_min: -67
+            })
+        );
+        assert_eq!(vdmx.vdmx_extents(7), None);
+        // The only ratio range is 1:1
+        assert_eq!(vdmx.vdmx_extents_for_ratio(12, 2, 1), None);
+    }
+}
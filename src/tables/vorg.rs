@@ -0,0 +1,203 @@
+#![deny(missing_docs)]
+
+//! `VORG` table parsing and writing.
+//!
+//! The `VORG` table holds the y coordinate of the vertical origin of the glyphs of a CFF font,
+//! which is needed to position glyphs when laying out vertical text.
+//!
+//! <https://docs.microsoft.com/en-us/typography/opentype/spec/vorg>
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+
+use crate::binary::read::{ReadArrayCow, ReadBinary, ReadCtxt, ReadFrom};
+use crate::binary::write::{WriteBinary, WriteContext};
+use crate::binary::{I16Be, U16Be};
+use crate::error::{ParseError, WriteError};
+
+/// The `VORG` vertical origin table.
+pub struct VorgTable<'a> {
+    /// The y coordinate of the vertical origin of glyphs not listed in `vert_origin_y_metrics`.
+    pub default_vert_origin_y: i16,
+    /// The vertical origins of individual glyphs, sorted by glyph index.
+    pub vert_origin_y_metrics: ReadArrayCow<'a, VertOriginYMetrics>,
+}
+
+/// The vertical origin of a single glyph.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct VertOriginYMetrics {
+    /// The glyph index.
+    pub glyph_index: u16,
+    /// The y coordinate of the vertical origin of the glyph.
+    pub vert_origin_y: i16,
+}
+
+impl<'a> ReadBinary<'a> for VorgTable<'a> {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let major_version = ctxt.read_u16be()?;
+        ctxt.check_version(major_version == 1)?;
+        let _minor_version = ctxt.read_u16be()?;
+        let default_vert_origin_y = ctxt.read_i16be()?;
+        let num_vert_origin_y_metrics = ctxt.read_u16be()?;
+        let vert_origin_y_metrics =
+            ctxt.read_array::<VertOriginYMetrics>(usize::from(num_vert_origin_y_metrics))?;
+
+        Ok(VorgTable {
+            default_vert_origin_y,
+            vert_origin_y_metrics: ReadArrayCow::Borrowed(vert_origin_y_metrics),
+        })
+    }
+}
+
+impl<'a> WriteBinary<&Self> for VorgTable<'a> {
+    type Output = ();
+
+    fn write<C: WriteContext>(ctxt: &mut C, table: &VorgTable<'a>) -> Result<(), WriteError> {
+        U16Be::write(ctxt, 1u16)?; // major_version
+        U16Be::write(ctxt, 0u16)?; // minor_version
+        I16Be::write(ctxt, table.default_vert_origin_y)?;
+        U16Be::write(ctxt, u16::try_from(table.vert_origin_y_metrics.len())?)?;
+        ReadArrayCow::write(ctxt, &table.vert_origin_y_metrics)?;
+
+        Ok(())
+    }
+}
+
+impl<'a> ReadFrom<'a> for VertOriginYMetrics {
+    type ReadType = (U16Be, I16Be);
+
+    fn from((glyph_index, vert_origin_y): (u16, i16)) -> Self {
+        VertOriginYMetrics {
+            glyph_index,
+            vert_origin_y,
+        }
+    }
+}
+
+impl WriteBinary for VertOriginYMetrics {
+    type Output = ();
+
+    fn write<C: WriteContext>(ctxt: &mut C, metrics: VertOriginYMetrics) -> Result<(), WriteError> {
+        U16Be::write(ctxt, metrics.glyph_index)?;
+        I16Be::write(ctxt, metrics.vert_origin_y)?;
+
+        Ok(())
+    }
+}
+
+impl<'a> VorgTable<'a> {
+    /// Returns the y coordinate of the vertical origin of `glyph_index`.
+    pub fn vert_origin_y(&self, glyph_index: u16) -> i16 {
+        let (mut lo, mut hi) = (0, self.vert_origin_y_metrics.len());
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            let metrics = self.vert_origin_y_metrics.get_item(mid);
+            if metrics.glyph_index < glyph_index {
+                lo = mid + 1;
+            } else if metrics.glyph_index > glyph_index {
+                hi = mid;
+            } else {
+                return metrics.vert_origin_y;
+            }
+        }
+        self.default_vert_origin_y
+    }
+
+    /// Build a new `VORG` table for the glyphs of a subset font.
+    ///
+    /// `new_to_old_glyph_id` maps the glyph ids of the subset font to the glyph ids of this font.
+    /// The default origin is recomputed as the most common origin of the retained glyphs, so
+    /// that the fewest glyphs need an entry.
+    pub fn subset(&self, new_to_old_glyph_id: &[u16]) -> Result<VorgTable<'static>, ParseError> {
+        let origins = new_to_old_glyph_id
+            .iter()
+            .map(|&old_glyph_id| self.vert_origin_y(old_glyph_id))
+            .collect::<Vec<_>>();
+        let mut counts = BTreeMap::new();
+        for &origin in &origins {
+            *counts.entry(origin).or_insert(0) += 1;
+        }
+        // Keep the current default if it's as common as any other origin
+        let default_vert_origin_y = counts
+            .iter()
+            .max_by_key(|&(&origin, &count)| (count, origin == self.default_vert_origin_y))
+            .map(|(&origin, _)| origin)
+            .unwrap_or(self.default_vert_origin_y);
+
+        let mut vert_origin_y_metrics = Vec::new();
+        for (new_glyph_id, &vert_origin_y) in origins.iter().enumerate() {
+            if vert_origin_y != default_vert_origin_y {
+                vert_origin_y_metrics.push(VertOriginYMetrics {
+                    glyph_index: u16::try_from(new_glyph_id)?,
+                    vert_origin_y,
+                });
+            }
+        }
+
+        Ok(VorgTable {
+            default_vert_origin_y,
+            vert_origin_y_metrics: ReadArrayCow::Owned(vert_origin_y_metrics),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::read::ReadScope;
+    use crate::binary::write::WriteBuffer;
+    use crate::font_data::FontData;
+    use crate::tables::FontTableProvider;
+    use crate::tag;
+    use crate::tests::read_fixture;
+
+    #[test]
+    fn test_vert_origin_y() {
+        let buffer = read_fixture("tests/fonts/noto/NotoSansJP-Regular.otf");
+        let scope = ReadScope::new(&buffer);
+        let font_file = scope.read::<FontData<'_>>().unwrap();
+        let provider = font_file.table_provider(0).unwrap();
+        let vorg_data = provider.read_table_data(tag::VORG).unwrap();
+        let vorg = ReadScope::new(&vorg_data).read::<VorgTable<'_>>().unwrap();
+
+        assert_eq!(vorg.default_vert_origin_y, 880);
+        assert_eq!(vorg.vert_origin_y_metrics.len(), 69);
+        assert_eq!(vorg.vert_origin_y(6896), 860);
+        assert_eq!(vorg.vert_origin_y(6909), 873);
+        assert_eq!(vorg.vert_origin_y(8140), 1880);
+        // Glyphs without an entry use the default
+        assert_eq!(vorg.vert_origin_y(0), 880);
+        assert_eq!(vorg.vert_origin_y(6897), 880);
+    }
+
+    #[test]
+    fn test_subset_write_read() {
+        let buffer = read_fixture("tests/fonts/noto/NotoSansJP-Regular.otf");
+        let scope = ReadScope::new(&buffer);
+        let font_file = scope.read::<FontData<'_>>().unwrap();
+        let provider = font_file.table_provider(0).unwrap();
+        let vorg_data = provider.read_table_data(tag::VORG).unwrap();
+        let vorg = ReadScope::new(&vorg_data).read::<VorgTable<'_>>().unwrap();
+
+        let subset = vorg.subset(&[0, 6896, 6897, 8140]).unwrap();
+        let mut w = WriteBuffer::new();
+        VorgTable::write(&mut w, &subset).unwrap();
+        let vorg = ReadScope::new(w.bytes()).read::<VorgTable<'_>>().unwrap();
+
+        assert_eq!(vorg.default_vert_origin_y, 880);
+        assert_eq!(vorg.vert_origin_y_metrics.len(), 2);
+        assert_eq!(vorg.vert_origin_y(0), 880);
+        assert_eq!(vorg.vert_origin_y(1), 860);
+        assert_eq!(vorg.vert_origin_y(2), 880);
+        assert_eq!(vorg.vert_origin_y(3), 1880);
+
+        // The most common origin becomes the default
+        let subset = vorg.subset(&[1, 1, 3, 0]).unwrap();
+        assert_eq!(subset.default_vert_origin_y, 860);
+        assert_eq!(subset.vert_origin_y_metrics.len(), 2);
+        assert_eq!(subset.vert_origin_y(2), 1880);
+        assert_eq!(subset.vert_origin_y(3), 880);
+    }
+}
@@ -0,0 +1,461 @@
+#![deny(missing_docs)]
+
+//! `VVAR` table parsing.
+//!
+//! The `VVAR` table holds variations of the vertical metrics of the glyphs of a variable font.
+//! The deltas are stored in an item variation store, which is also used by other tables that
+//! hold variations, such as `HVAR` and `MVAR`.
+//!
+//! <https://docs.microsoft.com/en-us/typography/opentype/spec/vvar>
+//! <https://docs.microsoft.com/en-us/typography/opentype/spec/otvarcommonformats>
+
+use std::convert::TryFrom;
+
+use crate::binary::read::{ReadArray, ReadBinary, ReadCtxt, ReadFrom, ReadScope};
+use crate::binary::{U16Be, U32Be};
+use crate::error::ParseError;
+use crate::tables::F2Dot14;
+
+/// Flag in `word_delta_count` indicating that deltas are 32-bit and 16-bit, rather than 16-bit
+/// and 8-bit.
+const LONG_WORDS: u16 = 0x8000;
+
+/// Mask of the bits of `word_delta_count` holding the count of the wider deltas.
+const WORD_DELTA_COUNT_MASK: u16 = 0x7FFF;
+
+/// Mask of the bits of a delta-set index map entry format holding the number of inner index bits,
+/// minus one.
+const INNER_INDEX_BIT_COUNT_MASK: u8 = 0x0F;
+
+/// Mask of the bits of a delta-set index map entry format holding the size of an entry, minus one.
+const MAP_ENTRY_SIZE_MASK: u8 = 0x30;
+
+/// The `VVAR` vertical metrics variations table.
+pub struct VvarTable<'a> {
+    /// The deltas of the vertical metrics.
+    pub item_variation_store: ItemVariationStore<'a>,
+    /// Maps glyph indices to the deltas of their advance heights.
+    ///
+    /// When absent the glyph index is used as the inner index, with an outer index of zero.
+    pub advance_height_mapping: Option<DeltaSetIndexMap<'a>>,
+    /// Maps glyph indices to the deltas of their top side bearings.
+    pub tsb_mapping: Option<DeltaSetIndexMap<'a>>,
+    /// Maps glyph indices to the deltas of their bottom side bearings.
+    pub bsb_mapping: Option<DeltaSetIndexMap<'a>>,
+    /// Maps glyph indices to the deltas of their vertical origins, as held in `VORG`.
+    pub vorg_mapping: Option<DeltaSetIndexMap<'a>>,
+}
+
+/// A store of deltas, each referenced by an outer and inner index.
+pub struct ItemVariationStore<'a> {
+    axis_count: u16,
+    /// The regions of the design space that deltas apply to, `axis_count` entries per region.
+    regions: ReadArray<'a, RegionAxisCoordinates>,
+    item_variation_data: Vec<ItemVariationData<'a>>,
+}
+
+/// The extent of a region of the design space along a single axis.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RegionAxisCoordinates {
+    /// The start of the region.
+    pub start_coord: F2Dot14,
+    /// The peak of the region, where the deltas apply in full.
+    pub peak_coord: F2Dot14,
+    /// The end of the region.
+    pub end_coord: F2Dot14,
+}
+
+struct ItemVariationData<'a> {
+    item_count: u16,
+    word_delta_count: u16,
+    region_indexes: ReadArray<'a, U16Be>,
+    delta_sets: ReadScope<'a>,
+}
+
+/// Maps indices, such as glyph indices, to the outer and inner indices of an item variation store.
+pub struct DeltaSetIndexMap<'a> {
+    entry_format: u8,
+    map_count: u32,
+    map_data: ReadScope<'a>,
+}
+
+impl<'a> ReadBinary<'a> for VvarTable<'a> {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let scope = ctxt.scope();
+        let major_version = ctxt.read_u16be()?;
+        ctxt.check_version(major_version == 1)?;
+        let _minor_version = ctxt.read_u16be()?;
+        let item_variation_store_offset = ctxt.read_u32be()?;
+        let advance_height_mapping_offset = ctxt.read_u32be()?;
+        let tsb_mapping_offset = ctxt.read_u32be()?;
+        let bsb_mapping_offset = ctxt.read_u32be()?;
+        let vorg_mapping_offset = ctxt.read_u32be()?;
+
+        let item_variation_store = scope
+            .offset(usize::try_from(item_variation_store_offset)?)
+            .read::<ItemVariationStore<'a>>()?;
+        let read_mapping = |offset: u32| -> Result<Option<DeltaSetIndexMap<'a>>, ParseError> {
+            match offset {
+                0 => Ok(None),
+                offset => scope
+                    .offset(usize::try_from(offset)?)
+                    .read::<DeltaSetIndexMap<'a>>()
+                    .map(Some),
+            }
+        };
+
+        Ok(VvarTable {
+            item_variation_store,
+            advance_height_mapping: read_mapping(advance_height_mapping_offset)?,
+            tsb_mapping: read_mapping(tsb_mapping_offset)?,
+            bsb_mapping: read_mapping(bsb_mapping_offset)?,
+            vorg_mapping: read_mapping(vorg_mapping_offset)?,
+        })
+    }
+}
+
+impl<'a> VvarTable<'a> {
+    /// Returns the delta to the advance height of `glyph_index` at the normalised variation
+    /// coordinates `coords`.
+    pub fn advance_height_delta(
+        &self,
+        glyph_index: u16,
+        coords: &[F2Dot14],
+    ) -> Result<f32, ParseError> {
+        let (outer, inner) = match self.advance_height_mapping {
+            Some(ref mapping) => mapping.get(u32::from(glyph_index))?,
+            None => (0, glyph_index),
+        };
+        self.item_variation_store.delta(outer, inner, coords)
+    }
+
+    /// Returns the delta to the vertical origin of `glyph_index` at the normalised variation
+    /// coordinates `coords`.
+    ///
+    /// Returns `None` if the table has no vertical origin deltas.
+    pub fn vert_origin_delta(
+        &self,
+        glyph_index: u16,
+        coords: &[F2Dot14],
+    ) -> Result<Option<f32>, ParseError> {
+        match self.vorg_mapping {
+            Some(ref mapping) => {
+                let (outer, inner) = mapping.get(u32::from(glyph_index))?;
+                self.item_variation_store
+                    .delta(outer, inner, coords)
+                    .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+impl<'a> ReadBinary<'a> for ItemVariationStore<'a> {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let scope = ctxt.scope();
+        let format = ctxt.read_u16be()?;
+        ctxt.check(format == 1)?;
+        let variation_region_list_offset = ctxt.read_u32be()?;
+        let item_variation_data_count = ctxt.read_u16be()?;
+        let item_variation_data_offsets =
+            ctxt.read_array::<U32Be>(usize::from(item_variation_data_count))?;
+
+        let mut region_ctxt = scope
+            .offset(usize::try_from(variation_region_list_offset)?)
+            .ctxt();
+        let axis_count = region_ctxt.read_u16be()?;
+        let region_count = region_ctxt.read_u16be()?;
+        let regions = region_ctxt.read_array::<RegionAxisCoordinates>(
+            usize::from(region_count)
+                .checked_mul(usize::from(axis_count))
+                .ok_or(ParseError::LimitExceeded)?,
+        )?;
+
+        let item_variation_data = item_variation_data_offsets
+            .iter()
+            .map(|offset| {
+                scope
+                    .offset(usize::try_from(offset)?)
+                    .read::<ItemVariationData<'a>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(ItemVariationStore {
+            axis_count,
+            regions,
+            item_variation_data,
+        })
+    }
+}
+
+impl<'a> ReadFrom<'a> for RegionAxisCoordinates {
+    type ReadType = (F2Dot14, F2Dot14, F2Dot14);
+
+    fn from((start_coord, peak_coord, end_coord): (F2Dot14, F2Dot14, F2Dot14)) -> Self {
+        RegionAxisCoordinates {
+            start_coord,
+            peak_coord,
+            end_coord,
+        }
+    }
+}
+
+impl<'a> ReadBinary<'a> for ItemVariationData<'a> {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let item_count = ctxt.read_u16be()?;
+        let word_delta_count = ctxt.read_u16be()?;
+        let region_index_count = ctxt.read_u16be()?;
+        let region_indexes = ctxt.read_array::<U16Be>(usize::from(region_index_count))?;
+        ctxt.check(word_delta_count & WORD_DELTA_COUNT_MASK <= region_index_count)?;
+
+        let row_size = row_size(word_delta_count, region_indexes.len());
+        let delta_sets_len = usize::from(item_count)
+            .checked_mul(row_size)
+            .ok_or(ParseError::LimitExceeded)?;
+        let delta_sets = ctxt.read_scope(delta_sets_len)?;
+
+        Ok(ItemVariationData {
+            item_count,
+            word_delta_count,
+            region_indexes,
+            delta_sets,
+        })
+    }
+}
+
+impl<'a> ItemVariationData<'a> {
+    /// Returns the deltas of the item at `inner`, paired with their region indices.
+    fn deltas(&self, inner: u16) -> Result<Vec<(u16, i32)>, ParseError> {
+        if inner >= self.item_count {
+            return Ok(Vec::new());
+        }
+        let row_size = row_size(self.word_delta_count, self.region_indexes.len());
+        let mut ctxt = self
+            .delta_sets
+            .offset_length(usize::from(inner) * row_size, row_size)?
+            .ctxt();
+        let long_words = self.word_delta_count & LONG_WORDS != 0;
+        let word_count = usize::from(self.word_delta_count & WORD_DELTA_COUNT_MASK);
+        let mut deltas = Vec::with_capacity(self.region_indexes.len());
+        for (i, region_index) in self.region_indexes.iter().enumerate() {
+            let delta = match (long_words, i < word_count) {
+                (true, true) => ctxt.read_i32be()?,
+                (true, false) => i32::from(ctxt.read_i16be()?),
+                (false, true) => i32::from(ctxt.read_i16be()?),
+                (false, false) => i32::from(ctxt.read_i8()?),
+            };
+            deltas.push((region_index, delta));
+        }
+        Ok(deltas)
+    }
+}
+
+/// The size in bytes of a single delta set.
+fn row_size(word_delta_count: u16, region_index_count: usize) -> usize {
+    let word_size = if word_delta_count & LONG_WORDS != 0 {
+        4
+    } else {
+        2
+    };
+    let word_count = usize::from(word_delta_count & WORD_DELTA_COUNT_MASK);
+    word_count * word_size + (region_index_count - word_count) * (word_size / 2)
+}
+
+impl<'a> ItemVariationStore<'a> {
+    /// Returns the delta of the item at `outer`, `inner` at the normalised variation coordinates
+    /// `coords`.
+    ///
+    /// Coordinates for axes beyond the end of `coords` are taken to be the default, zero.
+    pub fn delta(&self, outer: u16, inner: u16, coords: &[F2Dot14]) -> Result<f32, ParseError> {
+        let data = match self.item_variation_data.get(usize::from(outer)) {
+            Some(data) => data,
+            None => return Ok(0.),
+        };
+        let mut delta = 0.;
+        for (region_index, region_delta) in data.deltas(inner)? {
+            let scalar = self.region_scalar(region_index, coords)?;
+            if scalar != 0. {
+                delta += scalar * region_delta as f32;
+            }
+        }
+        Ok(delta)
+    }
+
+    /// Returns the scalar applied to deltas for the region at `region_index`.
+    fn region_scalar(&self, region_index: u16, coords: &[F2Dot14]) -> Result<f32, ParseError> {
+        let axis_count = usize::from(self.axis_count);
+        let start = usize::from(region_index) * axis_count;
+        if start + axis_count > self.regions.len() {
+            return Err(ParseError::BadIndex);
+        }
+
+        let mut scalar = 1.;
+        for axis in 0..axis_count {
+            let region = self.regions.get_item(start + axis);
+            let coord = coords.get(axis).copied().map_or(0., f32::from);
+            let (start, peak, end) = (
+                f32::from(region.start_coord),
+                f32::from(region.peak_coord),
+                f32::from(region.end_coord),
+            );
+            // Axes with invalid regions, or a peak of zero, don't affect the scalar
+            if peak == 0. || start > peak || peak > end || (start < 0. && end > 0.) {
+                continue;
+            }
+            if coord < start || coord > end {
+                return Ok(0.);
+            }
+            if coord < peak {
+                scalar *= (coord - start) / (peak - start);
+            } else if coord > peak {
+                scalar *= (end - coord) / (end - peak);
+            }
+        }
+        Ok(scalar)
+    }
+}
+
+impl<'a> ReadBinary<'a> for DeltaSetIndexMap<'a> {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let format = ctxt.read_u8()?;
+        let entry_format = ctxt.read_u8()?;
+        let map_count = match format {
+            0 => u32::from(ctxt.read_u16be()?),
+            1 => ctxt.read_u32be()?,
+            _ => return Err(ParseError::BadVersion),
+        };
+        let entry_size = usize::from(((entry_format & MAP_ENTRY_SIZE_MASK) >> 4) + 1);
+        let map_data = ctxt.read_scope(usize::try_from(map_count)? * entry_size)?;
+
+        Ok(DeltaSetIndexMap {
+            entry_format,
+            map_count,
+            map_data,
+        })
+    }
+}
+
+impl<'a> DeltaSetIndexMap<'a> {
+    /// Returns the outer and inner indices that `index` maps to.
+    ///
+    /// Indices beyond the end of the map use the last entry.
+    pub fn get(&self, index: u32) -> Result<(u16, u16), ParseError> {
+        if self.map_count == 0 {
+            return Ok((0, 0));
+        }
+        let index = usize::try_from(index.min(self.map_count - 1))?;
+        let entry_size = usize::from(((self.entry_format & MAP_ENTRY_SIZE_MASK) >> 4) + 1);
+        let bytes = self
+            .map_data
+            .offset_length(index * entry_size, entry_size)?
+            .data();
+        let entry = bytes
+            .iter()
+            .fold(0u32, |entry, &byte| (entry << 8) | u32::from(byte));
+        let inner_bits = u32::from(self.entry_format & INNER_INDEX_BIT_COUNT_MASK) + 1;
+        let outer = entry >> inner_bits;
+        let inner = entry & ((1 << inner_bits) - 1);
+
+        Ok((u16::try_from(outer)?, u16::try_from(inner)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::write::{WriteBinary, WriteBuffer, WriteContext};
+    use crate::binary::{I16Be, U8};
+
+    /// Builds a `VVAR` table with one axis and a single region peaking at 1.0. Glyph 1 has an
+    /// advance height delta of 100 and glyph 2 a delta of -40, mapped through a delta-set index
+    /// map. Glyph 1 has a vertical origin delta of 50.
+    fn vvar_data() -> Vec<u8> {
+        let mut w = WriteBuffer::new();
+        // Header
+        U16Be::write(&mut w, 1u16).unwrap();
+        U16Be::write(&mut w, 0u16).unwrap();
+        U32Be::write(&mut w, 24u32).unwrap(); // item variation store
+        U32Be::write(&mut w, 74u32).unwrap(); // advance height mapping
+        U32Be::write(&mut w, 0u32).unwrap(); // tsb mapping
+        U32Be::write(&mut w, 0u32).unwrap(); // bsb mapping
+        U32Be::write(&mut w, 81u32).unwrap(); // vorg mapping
+
+        // Item variation store at 24
+        U16Be::write(&mut w, 1u16).unwrap();
+        U32Be::write(&mut w, 12u32).unwrap(); // region list
+        U16Be::write(&mut w, 1u16).unwrap();
+        U32Be::write(&mut w, 22u32).unwrap(); // item variation data
+
+        // Region list at 36
+        U16Be::write(&mut w, 1u16).unwrap(); // axis count
+        U16Be::write(&mut w, 1u16).unwrap(); // region count
+        U16Be::write(&mut w, 0u16).unwrap(); // start 0.0
+        U16Be::write(&mut w, 0x4000u16).unwrap(); // peak 1.0
+        U16Be::write(&mut w, 0x4000u16).unwrap(); // end 1.0
+
+        // Item variation data at 46, three items of one 16-bit delta each
+        U16Be::write(&mut w, 3u16).unwrap();
+        U16Be::write(&mut w, 1u16).unwrap();
+        U16Be::write(&mut w, 1u16).unwrap();
+        U16Be::write(&mut w, 0u16).unwrap();
+        I16Be::write(&mut w, 100i16).unwrap();
+        I16Be::write(&mut w, -40i16).unwrap();
+        I16Be::write(&mut w, 50i16).unwrap();
+        w.write_zeros(14).unwrap();
+
+        // Advance height mapping at 74: glyph 0 to item 2, glyph 1 to item 0, glyph 2 to item 1
+        U8::write(&mut w, 0u8).unwrap();
+        U8::write(&mut w, 0x07u8).unwrap(); // 1 byte entries, 8 inner bits
+        U16Be::write(&mut w, 3u16).unwrap();
+        U8::write(&mut w, 2u8).unwrap();
+        U8::write(&mut w, 0u8).unwrap();
+        U8::write(&mut w, 1u8).unwrap();
+
+        // Vertical origin mapping at 81: every glyph to item 2
+        U8::write(&mut w, 0u8).unwrap();
+        U8::write(&mut w, 0x07u8).unwrap();
+        U16Be::write(&mut w, 1u16).unwrap();
+        U8::write(&mut w, 2u8).unwrap();
+
+        w.into_inner()
+    }
+
+    #[test]
+    fn test_advance_height_delta() {
+        let data = vvar_data();
+        let vvar = ReadScope::new(&data).read::<VvarTable<'_>>().unwrap();
+        let full = [F2Dot14::new(0x4000)];
+        let half = [F2Dot14::new(0x2000)];
+
+        assert_eq!(vvar.advance_height_delta(1, &full).unwrap(), 100.);
+        assert_eq!(vvar.advance_height_delta(2, &full).unwrap(), -40.);
+        assert_eq!(vvar.advance_height_delta(1, &half).unwrap(), 50.);
+        // Outside of the region, and at the default instance
+        assert_eq!(
+            vvar.advance_height_delta(1, &[F2Dot14::new(0xC000)])
+                .unwrap(),
+            0.
+        );
+        assert_eq!(vvar.advance_height_delta(1, &[]).unwrap(), 0.);
+        // Glyphs beyond the end of the map use the last entry
+        assert_eq!(vvar.advance_height_delta(7, &full).unwrap(), -40.);
+    }
+
+    #[test]
+    fn test_vert_origin_delta() {
+        let data = vvar_data();
+        let vvar = ReadScope::new(&data).read::<VvarTable<'_>>().unwrap();
+        let full = [F2Dot14::new(0x4000)];
+
+        assert_eq!(vvar.vert_origin_delta(1, &full).unwrap(), Some(50.));
+        assert!(vvar.tsb_mapping.is_none());
+    }
+}
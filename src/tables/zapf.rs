@@ -0,0 +1,294 @@
+#![deny(missing_docs)]
+
+//! `Zapf` table parsing.
+//!
+//! The `Zapf` table is an Apple Advanced Typography table that holds information about the
+//! glyphs of a font: the Unicode characters they represent, their names, the features that
+//! produce them, and the groups of related glyphs they belong to.
+//!
+//! <https://developer.apple.com/fonts/TrueType-Reference-Manual/RM06/Chap6Zapf.html>
+
+use std::convert::TryFrom;
+
+use crate::binary::read::{ReadArray, ReadBinaryDep, ReadCtxt, ReadScope};
+use crate::binary::U16Be;
+use crate::binary::U32Be;
+use crate::error::ParseError;
+
+/// Offset value indicating the absence of a group or feature record.
+const NO_OFFSET: u32 = 0xFFFF_FFFF;
+
+/// Flag set in the first field of a group record that is a group of groups.
+const GROUP_INFO_GROUP: u16 = 0x4000;
+
+/// Kind names below this value hold a string, the rest hold a 16-bit value.
+const FIRST_DATA_KIND: u8 = 64;
+
+/// The `Zapf` glyph information table.
+pub struct ZapfTable<'a> {
+    scope: ReadScope<'a>,
+    /// Offset from the start of the table to the extra info space, which group and feature
+    /// offsets are relative to.
+    extra_info: usize,
+    glyph_info_offsets: ReadArray<'a, U32Be>,
+}
+
+/// Information about a single glyph from the `Zapf` table.
+pub struct GlyphInfo<'a> {
+    /// The groups of related glyphs this glyph belongs to.
+    pub groups: Vec<GroupInfo<'a>>,
+    /// The features that produce this glyph, if known.
+    pub features: Option<FeatureInfo<'a>>,
+    /// The Unicode code units this glyph represents.
+    pub unicodes: ReadArray<'a, U16Be>,
+    /// The names and identifiers of this glyph.
+    pub names: Vec<KindName<'a>>,
+}
+
+/// A group of related glyphs, such as the alternates for a character.
+pub struct GroupInfo<'a> {
+    /// Index of the `name` table entry naming the group.
+    pub name_index: u16,
+    /// The glyphs in the group.
+    pub glyphs: ReadArray<'a, U16Be>,
+}
+
+/// The features that produce a glyph.
+pub struct FeatureInfo<'a> {
+    /// The glyph context the features apply in.
+    pub context: u16,
+    /// AAT feature type and setting pairs.
+    pub features: ReadArray<'a, (U16Be, U16Be)>,
+}
+
+/// A name or identifier for a glyph.
+#[derive(Debug, PartialEq, Eq)]
+pub enum KindName<'a> {
+    /// A string name, such as the universal name (kind 0), Apple name (kind 1), Adobe name
+    /// (kind 2), AFII name (kind 3), or Unicode name (kind 4). Names are typically ASCII.
+    Name {
+        /// The kind of name.
+        kind: u8,
+        /// The bytes of the name.
+        name: &'a [u8],
+    },
+    /// A numeric identifier, such as a CID (kind 64).
+    Data {
+        /// The kind of identifier.
+        kind: u8,
+        /// The identifier.
+        data: u16,
+    },
+}
+
+impl<'a> ZapfTable<'a> {
+    /// The number of glyphs described by the table.
+    pub fn num_glyphs(&self) -> usize {
+        self.glyph_info_offsets.len()
+    }
+
+    /// Read the information for the supplied `glyph_id`.
+    ///
+    /// Returns `None` if the table has no entry for the glyph.
+    pub fn glyph_info(&self, glyph_id: u16) -> Result<Option<GlyphInfo<'a>>, ParseError> {
+        let index = usize::from(glyph_id);
+        if index >= self.glyph_info_offsets.len() {
+            return Ok(None);
+        }
+        let offset = self.glyph_info_offsets.get_item(index);
+        if offset == 0 || offset == NO_OFFSET {
+            return Ok(None);
+        }
+
+        let mut ctxt = self.scope.offset(usize::try_from(offset)?).ctxt();
+        let group_offset = ctxt.read_u32be()?;
+        let feature_offset = ctxt.read_u32be()?;
+        let num_unicodes = usize::from(ctxt.read_u16be()?);
+        let unicodes = ctxt.read_array::<U16Be>(num_unicodes)?;
+        let num_names = usize::from(ctxt.read_u16be()?);
+        let names = (0..num_names)
+            .map(|_| read_kind_name(&mut ctxt))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let groups = if group_offset == NO_OFFSET {
+            Vec::new()
+        } else {
+            self.read_groups(group_offset)?
+        };
+        let features = if feature_offset == NO_OFFSET {
+            None
+        } else {
+            let mut ctxt = self.extra_info_scope(feature_offset)?.ctxt();
+            let context = ctxt.read_u16be()?;
+            let num_features = usize::from(ctxt.read_u16be()?);
+            let features = ctxt.read_array::<(U16Be, U16Be)>(num_features)?;
+            Some(FeatureInfo { context, features })
+        };
+
+        Ok(Some(GlyphInfo {
+            groups,
+            features,
+            unicodes,
+            names,
+        }))
+    }
+
+    fn extra_info_scope(&self, offset: u32) -> Result<ReadScope<'a>, ParseError> {
+        let offset = self
+            .extra_info
+            .checked_add(usize::try_from(offset)?)
+            .ok_or(ParseError::BadOffset)?;
+        Ok(self.scope.offset(offset))
+    }
+
+    fn read_groups(&self, offset: u32) -> Result<Vec<GroupInfo<'a>>, ParseError> {
+        let mut ctxt = self.extra_info_scope(offset)?.ctxt();
+        let flags = ctxt.read_u16be()?;
+        if flags & GROUP_INFO_GROUP == 0 {
+            return Ok(vec![read_group_info(flags, &mut ctxt)?]);
+        }
+
+        // A group of groups holds offsets to the individual groups
+        let num_groups = usize::from(ctxt.read_u16be()?);
+        let group_offsets = ctxt.read_array::<U32Be>(num_groups)?;
+        group_offsets
+            .iter()
+            .map(|group_offset| {
+                let mut ctxt = self.extra_info_scope(group_offset)?.ctxt();
+                let name_index = ctxt.read_u16be()?;
+                read_group_info(name_index, &mut ctxt)
+            })
+            .collect()
+    }
+}
+
+impl<'a> GlyphInfo<'a> {
+    /// Returns the first name of the supplied `kind`, if present.
+    pub fn name(&self, kind: u8) -> Option<&'a [u8]> {
+        self.names.iter().find_map(|name| match *name {
+            KindName::Name {
+                kind: name_kind,
+                name,
+            } if name_kind == kind => Some(name),
+            _ => None,
+        })
+    }
+}
+
+fn read_group_info<'a>(
+    name_index: u16,
+    ctxt: &mut ReadCtxt<'a>,
+) -> Result<GroupInfo<'a>, ParseError> {
+    let num_glyphs = usize::from(ctxt.read_u16be()?);
+    let glyphs = ctxt.read_array::<U16Be>(num_glyphs)?;
+    Ok(GroupInfo {
+        name_index: name_index & !GROUP_INFO_GROUP,
+        glyphs,
+    })
+}
+
+fn read_kind_name<'a>(ctxt: &mut ReadCtxt<'a>) -> Result<KindName<'a>, ParseError> {
+    let kind = ctxt.read_u8()?;
+    if kind < FIRST_DATA_KIND {
+        let length = usize::from(ctxt.read_u8()?);
+        let name = ctxt.read_slice(length)?;
+        Ok(KindName::Name { kind, name })
+    } else {
+        let data = ctxt.read_u16be()?;
+        Ok(KindName::Data { kind, data })
+    }
+}
+
+impl<'a> ReadBinaryDep<'a> for ZapfTable<'a> {
+    type Args = usize;
+    type HostType = Self;
+
+    /// Read the `Zapf` table.
+    ///
+    /// `num_glyphs` should be read from the `maxp` table.
+    fn read_dep(ctxt: &mut ReadCtxt<'a>, num_glyphs: usize) -> Result<Self, ParseError> {
+        let scope = ctxt.scope();
+        let version = ctxt.read_u32be()?;
+        ctxt.check(version >> 16 == 1 || version >> 16 == 2)?;
+        let extra_info = usize::try_from(ctxt.read_u32be()?)?;
+        let glyph_info_offsets = ctxt.read_array::<U32Be>(num_glyphs)?;
+
+        Ok(ZapfTable {
+            scope,
+            extra_info,
+            glyph_info_offsets,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::write::{WriteBinary, WriteBuffer, WriteContext};
+
+    // Two glyphs: glyph 0 has no info, glyph 1 represents U+0041 with a name, belongs to a group
+    // of alternates and is produced by a feature.
+    fn zapf_data() -> Vec<u8> {
+        let mut w = WriteBuffer::new();
+        U32Be::write(&mut w, 0x0002_0000u32).unwrap(); // version
+        U32Be::write(&mut w, 16u32).unwrap(); // extraInfo
+        U32Be::write(&mut w, 0u32).unwrap(); // glyph 0
+        U32Be::write(&mut w, 34u32).unwrap(); // glyph 1
+
+        // Extra info at 16: group at 0, features at 10
+        U16Be::write(&mut w, 258u16).unwrap(); // nameIndex
+        U16Be::write(&mut w, 3u16).unwrap(); // nGlyphs
+        for glyph in &[1u16, 5, 6] {
+            U16Be::write(&mut w, *glyph).unwrap();
+        }
+        U16Be::write(&mut w, 0u16).unwrap(); // context
+        U16Be::write(&mut w, 1u16).unwrap(); // nAATFeatures
+        U16Be::write(&mut w, 14u16).unwrap(); // featureType
+        U16Be::write(&mut w, 1u16).unwrap(); // featureSetting
+
+        // Glyph info at 34
+        U32Be::write(&mut w, 0u32).unwrap(); // groupOffset
+        U32Be::write(&mut w, 10u32).unwrap(); // featOffset
+        U16Be::write(&mut w, 1u16).unwrap(); // n16BitUnicodes
+        U16Be::write(&mut w, 0x41u16).unwrap();
+        U16Be::write(&mut w, 2u16).unwrap(); // nNames
+        w.write_bytes(&[0, 1, b'A']).unwrap();
+        w.write_bytes(&[64, 0x12, 0x34]).unwrap();
+        w.into_inner()
+    }
+
+    #[test]
+    fn read_glyph_info() {
+        let data = zapf_data();
+        let zapf = ReadScope::new(&data).read_dep::<ZapfTable<'_>>(2).unwrap();
+        assert_eq!(zapf.num_glyphs(), 2);
+        assert!(zapf.glyph_info(0).unwrap().is_none());
+        assert!(zapf.glyph_info(2).unwrap().is_none());
+
+        let info = zapf.glyph_info(1).unwrap().unwrap();
+        assert_eq!(info.unicodes.to_vec(), vec![0x41]);
+        assert_eq!(
+            info.names,
+            vec![
+                KindName::Name {
+                    kind: 0,
+                    name: b"A"
+                },
+                KindName::Data {
+                    kind: 64,
+                    data: 0x1234
+                },
+            ]
+        );
+        assert_eq!(info.name(0), Some(&b"A"[..]));
+        assert_eq!(info.name(1), None);
+
+        assert_eq!(info.groups.len(), 1);
+        assert_eq!(info.groups[0].name_index, 258);
+        assert_eq!(info.groups[0].glyphs.to_vec(), vec![1, 5, 6]);
+
+        let features = info.features.unwrap();
+        assert_eq!(features.context, 0);
+        assert_eq!(features.features.to_vec(), vec![(14, 1)]);
+    }
+}
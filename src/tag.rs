@@ -2,6 +2,8 @@
 //!
 //! See also the [`tag!`](../macro.tag.html) macro for creating tags from a byte string.
 
+pub mod registry;
+
 use crate::error::ParseError;
 use std::{fmt, str};
 
@@ -89,6 +91,8 @@ impl fmt::Debug for DisplayTag {
     }
 }
 
+/// `aalt`
+pub const AALT: u32 = tag!(b"aalt");
 /// `abvf`
 pub const ABVF: u32 = tag!(b"abvf");
 /// `abvm`
@@ -103,6 +107,8 @@ pub const AFRC: u32 = tag!(b"afrc");
 pub const AKHN: u32 = tag!(b"akhn");
 /// `arab`
 pub const ARAB: u32 = tag!(b"arab");
+/// `armn`
+pub const ARMN: u32 = tag!(b"armn");
 /// `avar`
 pub const AVAR: u32 = tag!(b"avar");
 /// `BASE`
@@ -121,6 +127,8 @@ pub const BLWM: u32 = tag!(b"blwm");
 pub const BLWS: u32 = tag!(b"blws");
 /// `bng2`
 pub const BNG2: u32 = tag!(b"bng2");
+/// `bopo`
+pub const BOPO: u32 = tag!(b"bopo");
 /// `bsln`
 pub const BSLN: u32 = tag!(b"bsln");
 /// `c2sc`
@@ -137,6 +145,8 @@ pub const CCMP: u32 = tag!(b"ccmp");
 pub const CFAR: u32 = tag!(b"cfar");
 /// `CFF `
 pub const CFF: u32 = tag!(b"CFF ");
+/// `CFF2`
+pub const CFF2: u32 = tag!(b"CFF2");
 /// `cjct`
 pub const CJCT: u32 = tag!(b"cjct");
 /// `clig`
@@ -165,6 +175,10 @@ pub const DFLT: u32 = tag!(b"DFLT");
 pub const DIST: u32 = tag!(b"dist");
 /// `dlig`
 pub const DLIG: u32 = tag!(b"dlig");
+/// `dlng`
+pub const DLNG: u32 = tag!(b"dlng");
+/// `DSIG`
+pub const DSIG: u32 = tag!(b"DSIG");
 /// `dupe`
 pub const DUPE: u32 = tag!(b"dupe");
 /// `EBDT`
@@ -173,6 +187,8 @@ pub const EBDT: u32 = tag!(b"EBDT");
 pub const EBLC: u32 = tag!(b"EBLC");
 /// `EBSC`
 pub const EBSC: u32 = tag!(b"EBSC");
+/// `ethi`
+pub const ETHI: u32 = tag!(b"ethi");
 /// `FAR`
 pub const FAR: u32 = tag!(b"FAR ");
 /// `fdsc`
@@ -199,6 +215,8 @@ pub const FVAR: u32 = tag!(b"fvar");
 pub const GASP: u32 = tag!(b"gasp");
 /// `GDEF`
 pub const GDEF: u32 = tag!(b"GDEF");
+/// `geor`
+pub const GEOR: u32 = tag!(b"geor");
 /// `gjr2`
 pub const GJR2: u32 = tag!(b"gjr2");
 /// `Glat`
@@ -225,10 +243,16 @@ pub const GVAR: u32 = tag!(b"gvar");
 pub const HALF: u32 = tag!(b"half");
 /// `haln`
 pub const HALN: u32 = tag!(b"haln");
+/// `hang`
+pub const HANG: u32 = tag!(b"hang");
+/// `hani`
+pub const HANI: u32 = tag!(b"hani");
 /// `hdmx`
 pub const HDMX: u32 = tag!(b"hdmx");
 /// `head`
 pub const HEAD: u32 = tag!(b"head");
+/// `hebr`
+pub const HEBR: u32 = tag!(b"hebr");
 /// `hhea`
 pub const HHEA: u32 = tag!(b"hhea");
 /// `hlig`
@@ -237,6 +261,16 @@ pub const HLIG: u32 = tag!(b"hlig");
 pub const HMTX: u32 = tag!(b"hmtx");
 /// `hsty`
 pub const HSTY: u32 = tag!(b"hsty");
+/// `HVAR`
+pub const HVAR: u32 = tag!(b"HVAR");
+/// `icfb`
+pub const ICFB: u32 = tag!(b"icfb");
+/// `icft`
+pub const ICFT: u32 = tag!(b"icft");
+/// `ideo`
+pub const IDEO: u32 = tag!(b"ideo");
+/// `idtp`
+pub const IDTP: u32 = tag!(b"idtp");
 /// `init`
 pub const INIT: u32 = tag!(b"init");
 /// `isol`
@@ -247,18 +281,28 @@ pub const JPG: u32 = tag!(b"jpg ");
 pub const JSTF: u32 = tag!(b"JSTF");
 /// `just`
 pub const JUST: u32 = tag!(b"just");
+/// `kana`
+pub const KANA: u32 = tag!(b"kana");
 /// `kern`
 pub const KERN: u32 = tag!(b"kern");
+/// `kerx`
+pub const KERX: u32 = tag!(b"kerx");
+/// `khmr`
+pub const KHMR: u32 = tag!(b"khmr");
 /// `knd2`
 pub const KND2: u32 = tag!(b"knd2");
 /// `knda`
 pub const KNDA: u32 = tag!(b"knda");
+/// `lao `
+pub const LAO: u32 = tag!(b"lao ");
 /// `latn`
 pub const LATN: u32 = tag!(b"latn");
 /// `lcar`
 pub const LCAR: u32 = tag!(b"lcar");
 /// `liga`
 pub const LIGA: u32 = tag!(b"liga");
+/// `ljmo`
+pub const LJMO: u32 = tag!(b"ljmo");
 /// `lnum`
 pub const LNUM: u32 = tag!(b"lnum");
 /// `loca`
@@ -277,20 +321,32 @@ pub const MAXP: u32 = tag!(b"maxp");
 pub const MED2: u32 = tag!(b"med2");
 /// `medi`
 pub const MEDI: u32 = tag!(b"medi");
+/// `meta`
+pub const META: u32 = tag!(b"meta");
 /// `mkmk`
 pub const MKMK: u32 = tag!(b"mkmk");
 /// `mlm2`
 pub const MLM2: u32 = tag!(b"mlm2");
 /// `mlym`
 pub const MLYM: u32 = tag!(b"mlym");
+/// `mong`
+pub const MONG: u32 = tag!(b"mong");
 /// `mort`
 pub const MORT: u32 = tag!(b"mort");
 /// `morx`
 pub const MORX: u32 = tag!(b"morx");
 /// `mset`
 pub const MSET: u32 = tag!(b"mset");
+/// `MVAR`
+pub const MVAR: u32 = tag!(b"MVAR");
+/// `mym2`
+pub const MYM2: u32 = tag!(b"mym2");
+/// `mymr`
+pub const MYMR: u32 = tag!(b"mymr");
 /// `name`
 pub const NAME: u32 = tag!(b"name");
+/// `nko `
+pub const NKO: u32 = tag!(b"nko ");
 /// `nukt`
 pub const NUKT: u32 = tag!(b"nukt");
 /// `onum`
@@ -307,6 +363,8 @@ pub const ORYA: u32 = tag!(b"orya");
 pub const OS_2: u32 = tag!(b"OS/2");
 /// `OTTO`
 pub const OTTO: u32 = tag!(b"OTTO");
+/// `palt`
+pub const PALT: u32 = tag!(b"palt");
 /// `PCLT`
 pub const PCLT: u32 = tag!(b"PCLT");
 /// `pnum`
@@ -333,8 +391,14 @@ pub const RCLT: u32 = tag!(b"rclt");
 pub const RKRF: u32 = tag!(b"rkrf");
 /// `rlig`
 pub const RLIG: u32 = tag!(b"rlig");
+/// `romn`
+pub const ROMN: u32 = tag!(b"romn");
 /// `rphf`
 pub const RPHF: u32 = tag!(b"rphf");
+/// `rtlm`
+pub const RTLM: u32 = tag!(b"rtlm");
+/// `salt`
+pub const SALT: u32 = tag!(b"salt");
 /// `sbix`
 pub const SBIX: u32 = tag!(b"sbix");
 /// `Silf`
@@ -343,10 +407,22 @@ pub const SILF: u32 = tag!(b"Silf");
 pub const SILL: u32 = tag!(b"Sill");
 /// `sinh`
 pub const SINH: u32 = tag!(b"sinh");
+/// `size`
+pub const SIZE: u32 = tag!(b"size");
+/// `slng`
+pub const SLNG: u32 = tag!(b"slng");
 /// `smcp`
 pub const SMCP: u32 = tag!(b"smcp");
+/// `STAT`
+pub const STAT: u32 = tag!(b"STAT");
 /// `SND`
 pub const SND: u32 = tag!(b"SND ");
+/// `stch`
+pub const STCH: u32 = tag!(b"stch");
+/// `subs`
+pub const SUBS: u32 = tag!(b"subs");
+/// `sups`
+pub const SUPS: u32 = tag!(b"sups");
 /// `SVG `
 pub const SVG: u32 = tag!(b"SVG ");
 /// `syrc`
@@ -357,10 +433,18 @@ pub const TAML: u32 = tag!(b"taml");
 pub const TEL2: u32 = tag!(b"tel2");
 /// `telu`
 pub const TELU: u32 = tag!(b"telu");
+/// `thaa`
+pub const THAA: u32 = tag!(b"thaa");
+/// `thai`
+pub const THAI: u32 = tag!(b"thai");
+/// `tibt`
+pub const TIBT: u32 = tag!(b"tibt");
 /// `tiff`
 pub const TIFF: u32 = tag!(b"tiff");
 /// `tml2`
 pub const TML2: u32 = tag!(b"tml2");
+/// `tjmo`
+pub const TJMO: u32 = tag!(b"tjmo");
 /// `tnum`
 pub const TNUM: u32 = tag!(b"tnum");
 /// `trak`
@@ -377,12 +461,16 @@ pub const VDMX: u32 = tag!(b"VDMX");
 pub const VERT: u32 = tag!(b"vert");
 /// `vhea`
 pub const VHEA: u32 = tag!(b"vhea");
+/// `vjmo`
+pub const VJMO: u32 = tag!(b"vjmo");
 /// `vmtx`
 pub const VMTX: u32 = tag!(b"vmtx");
 /// `VORG`
 pub const VORG: u32 = tag!(b"VORG");
 /// `vrt2`
 pub const VRT2: u32 = tag!(b"vrt2");
+/// `VVAR`
+pub const VVAR: u32 = tag!(b"VVAR");
 /// `Zapf`
 pub const ZAPF: u32 = tag!(b"Zapf");
 /// `zero`
@@ -0,0 +1,392 @@
+#![deny(missing_docs)]
+
+//! Conversion between ISO 15924 script codes and BCP 47 language tags and the tags OpenType uses
+//! for scripts and language systems.
+//!
+//! OpenType script tags are mostly the ISO 15924 code in lower case, but there are exceptions:
+//! the Indic scripts and Myanmar have a second, newer tag such as `dev2` for Devanagari, some
+//! tags are padded with spaces such as `lao `, and Hiragana and Katakana share `kana`. Scripts
+//! that text doesn't belong to in particular, such as `Zyyy` (common), use the `DFLT` script.
+//!
+//! OpenType language system tags are unrelated to ISO 639 language codes and have to be looked
+//! up. The default language system, `dflt` in feature files, is represented by `None` throughout
+//! this crate, which is what `language_tag` returns for languages without a language system.
+//!
+//! ```
+//! use allsorts::tag;
+//! use allsorts::tag::registry;
+//!
+//! assert_eq!(registry::script_tag("Deva"), Some(tag::DEVA));
+//! assert_eq!(registry::script_tags("Deva"), vec![tag::DEV2, tag::DEVA]);
+//! assert_eq!(registry::language_tag("nl-BE"), Some(tag!(b"NLD ")));
+//! assert_eq!(registry::language_tag("und"), None);
+//! ```
+
+use crate::tag;
+
+/// ISO 15924 codes whose OpenType script tags aren't the code in lower case, with the tags in
+/// order of preference.
+///
+/// Sorted by code.
+const SCRIPTS: &[(&str, &[u32])] = &[
+    ("Aran", &[tag::ARAB]),
+    ("Beng", &[tag::BNG2, tag::BENG]),
+    ("Cyrs", &[tag::CYRL]),
+    ("Deva", &[tag::DEV2, tag::DEVA]),
+    ("Geok", &[tag::GEOR]),
+    ("Gujr", &[tag::GJR2, tag::GUJR]),
+    ("Guru", &[tag::GUR2, tag::GURU]),
+    ("Hans", &[tag::HANI]),
+    ("Hant", &[tag::HANI]),
+    ("Hira", &[tag::KANA]),
+    ("Hrkt", &[tag::KANA]),
+    ("Knda", &[tag::KND2, tag::KNDA]),
+    ("Laoo", &[tag::LAO]),
+    ("Latf", &[tag::LATN]),
+    ("Latg", &[tag::LATN]),
+    ("Mlym", &[tag::MLM2, tag::MLYM]),
+    ("Mymr", &[tag::MYM2, tag::MYMR]),
+    ("Nkoo", &[tag::NKO]),
+    ("Orya", &[tag::ORY2, tag::ORYA]),
+    ("Syre", &[tag::SYRC]),
+    ("Syrj", &[tag::SYRC]),
+    ("Syrn", &[tag::SYRC]),
+    ("Taml", &[tag::TML2, tag::TAML]),
+    ("Telu", &[tag::TEL2, tag::TELU]),
+    ("Vaii", &[tag!(b"vai ")]),
+    ("Yiii", &[tag!(b"yi  ")]),
+    ("Zinh", &[tag::DFLT]),
+    ("Zmth", &[tag!(b"math")]),
+    ("Zyyy", &[tag::DFLT]),
+    ("Zzzz", &[tag::DFLT]),
+];
+
+/// BCP 47 primary language subtags and their OpenType language system tags.
+///
+/// Sorted by subtag. Chinese is handled separately as its language system depends on the region
+/// and script.
+const LANGUAGES: &[(&str, u32)] = &[
+    ("af", tag!(b"AFK ")),
+    ("am", tag!(b"AMH ")),
+    ("an", tag!(b"ARG ")),
+    ("ar", tag!(b"ARA ")),
+    ("as", tag!(b"ASM ")),
+    ("ast", tag!(b"AST ")),
+    ("av", tag!(b"AVR ")),
+    ("ay", tag!(b"AYM ")),
+    ("az", tag!(b"AZE ")),
+    ("ba", tag!(b"BSH ")),
+    ("be", tag!(b"BEL ")),
+    ("bg", tag!(b"BGR ")),
+    ("bm", tag!(b"BMB ")),
+    ("bn", tag!(b"BEN ")),
+    ("bo", tag!(b"TIB ")),
+    ("br", tag!(b"BRE ")),
+    ("bs", tag!(b"BOS ")),
+    ("ca", tag!(b"CAT ")),
+    ("ce", tag!(b"CHE ")),
+    ("chr", tag!(b"CHR ")),
+    ("co", tag!(b"COS ")),
+    ("cs", tag!(b"CSY ")),
+    ("cv", tag!(b"CHU ")),
+    ("cy", tag!(b"WEL ")),
+    ("da", tag!(b"DAN ")),
+    ("de", tag!(b"DEU ")),
+    ("dv", tag!(b"DIV ")),
+    ("dz", tag!(b"DZN ")),
+    ("el", tag!(b"ELL ")),
+    ("en", tag!(b"ENG ")),
+    ("eo", tag!(b"NTO ")),
+    ("es", tag!(b"ESP ")),
+    ("et", tag!(b"ETI ")),
+    ("eu", tag!(b"EUQ ")),
+    ("fa", tag::FAR),
+    ("ff", tag!(b"FUL ")),
+    ("fi", tag!(b"FIN ")),
+    ("fil", tag!(b"PIL ")),
+    ("fj", tag!(b"FJI ")),
+    ("fo", tag!(b"FOS ")),
+    ("fr", tag!(b"FRA ")),
+    ("fur", tag!(b"FRL ")),
+    ("fy", tag!(b"FRI ")),
+    ("ga", tag!(b"IRI ")),
+    ("gd", tag!(b"GAE ")),
+    ("gl", tag!(b"GAL ")),
+    ("gn", tag!(b"GUA ")),
+    ("gu", tag!(b"GUJ ")),
+    ("gv", tag!(b"MNX ")),
+    ("ha", tag!(b"HAU ")),
+    ("haw", tag!(b"HAW ")),
+    ("he", tag!(b"IWR ")),
+    ("hi", tag!(b"HIN ")),
+    ("hr", tag!(b"HRV ")),
+    ("ht", tag!(b"HAI ")),
+    ("hu", tag!(b"HUN ")),
+    ("hy", tag!(b"HYE ")),
+    ("id", tag!(b"IND ")),
+    ("ig", tag!(b"IBO ")),
+    ("ii", tag!(b"YIM ")),
+    ("is", tag!(b"ISL ")),
+    ("it", tag!(b"ITA ")),
+    ("iu", tag!(b"INU ")),
+    ("ja", tag!(b"JAN ")),
+    ("jv", tag!(b"JAV ")),
+    ("ka", tag!(b"KAT ")),
+    ("kk", tag!(b"KAZ ")),
+    ("kl", tag!(b"GRN ")),
+    ("km", tag!(b"KHM ")),
+    ("kn", tag!(b"KAN ")),
+    ("ko", tag!(b"KOR ")),
+    ("kok", tag!(b"KOK ")),
+    ("ks", tag!(b"KSH ")),
+    ("ku", tag!(b"KUR ")),
+    ("ky", tag!(b"KIR ")),
+    ("la", tag!(b"LAT ")),
+    ("lb", tag!(b"LTZ ")),
+    ("lo", tag!(b"LAO ")),
+    ("lt", tag!(b"LTH ")),
+    ("lv", tag!(b"LVI ")),
+    ("mai", tag!(b"MTH ")),
+    ("mg", tag!(b"MLG ")),
+    ("mi", tag!(b"MRI ")),
+    ("mk", tag!(b"MKD ")),
+    ("ml", tag!(b"MAL ")),
+    ("mn", tag!(b"MNG ")),
+    ("mni", tag!(b"MNI ")),
+    ("mr", tag!(b"MAR ")),
+    ("ms", tag!(b"MLY ")),
+    ("mt", tag!(b"MTS ")),
+    ("my", tag!(b"BRM ")),
+    ("nb", tag!(b"NOR ")),
+    ("ne", tag!(b"NEP ")),
+    ("nl", tag!(b"NLD ")),
+    ("nn", tag!(b"NYN ")),
+    ("no", tag!(b"NOR ")),
+    ("oc", tag!(b"OCI ")),
+    ("om", tag!(b"ORO ")),
+    ("or", tag!(b"ORI ")),
+    ("pa", tag!(b"PAN ")),
+    ("pl", tag!(b"PLK ")),
+    ("ps", tag!(b"PAS ")),
+    ("pt", tag!(b"PTG ")),
+    ("qu", tag!(b"QUZ ")),
+    ("rm", tag!(b"RMS ")),
+    ("ro", tag!(b"ROM ")),
+    ("ru", tag!(b"RUS ")),
+    ("rw", tag!(b"RUA ")),
+    ("sa", tag!(b"SAN ")),
+    ("sat", tag!(b"SAT ")),
+    ("sd", tag::SND),
+    ("se", tag!(b"NSM ")),
+    ("si", tag!(b"SNH ")),
+    ("sk", tag!(b"SKY ")),
+    ("sl", tag!(b"SLV ")),
+    ("sm", tag!(b"SMO ")),
+    ("so", tag!(b"SML ")),
+    ("sq", tag!(b"SQI ")),
+    ("sr", tag!(b"SRB ")),
+    ("st", tag!(b"SOT ")),
+    ("su", tag!(b"SUN ")),
+    ("sv", tag!(b"SVE ")),
+    ("sw", tag!(b"SWK ")),
+    ("syr", tag!(b"SYR ")),
+    ("ta", tag!(b"TAM ")),
+    ("te", tag!(b"TEL ")),
+    ("tg", tag!(b"TAJ ")),
+    ("th", tag!(b"THA ")),
+    ("ti", tag!(b"TGY ")),
+    ("tk", tag!(b"TKM ")),
+    ("tl", tag!(b"TGL ")),
+    ("tn", tag!(b"TNA ")),
+    ("to", tag!(b"TGN ")),
+    ("tr", tag!(b"TRK ")),
+    ("ts", tag!(b"TSG ")),
+    ("tt", tag!(b"TAT ")),
+    ("ug", tag!(b"UYG ")),
+    ("uk", tag!(b"UKR ")),
+    ("ur", tag::URD),
+    ("uz", tag!(b"UZB ")),
+    ("vi", tag!(b"VIT ")),
+    ("wo", tag!(b"WLF ")),
+    ("xh", tag!(b"XHS ")),
+    ("yi", tag!(b"JII ")),
+    ("yo", tag!(b"YBA ")),
+    ("zu", tag!(b"ZUL ")),
+];
+
+/// Chinese, Simplified
+const ZHS: u32 = tag!(b"ZHS ");
+/// Chinese, Traditional
+const ZHT: u32 = tag!(b"ZHT ");
+/// Chinese, Traditional, Hong Kong SAR
+const ZHH: u32 = tag!(b"ZHH ");
+
+/// Returns the OpenType script tag to shape text in the ISO 15924 script `script_code` with,
+/// such as `deva` for `Deva`.
+///
+/// This is the tag accepted by `Font::shape`, which uses the newer tag of the Indic scripts
+/// itself when the font supports it. Returns `None` if `script_code` isn't a valid code.
+pub fn script_tag(script_code: &str) -> Option<u32> {
+    script_tags(script_code).last().copied()
+}
+
+/// Returns the OpenType script tags of the ISO 15924 script `script_code`, in the order they
+/// should be looked for in a font, such as `dev2` and then `deva` for `Devanagari`.
+///
+/// The code is case insensitive. Returns an empty list if `script_code` isn't a valid code.
+pub fn script_tags(script_code: &str) -> Vec<u32> {
+    let code = script_code.as_bytes();
+    if code.len() != 4 || !code.iter().all(u8::is_ascii_alphabetic) {
+        return Vec::new();
+    }
+    let mut code = [
+        code[0].to_ascii_uppercase(),
+        code[1].to_ascii_lowercase(),
+        code[2].to_ascii_lowercase(),
+        code[3].to_ascii_lowercase(),
+    ];
+    let found = SCRIPTS.binary_search_by(|(entry, _)| entry.as_bytes().cmp(&code[..]));
+    match found {
+        Ok(index) => SCRIPTS[index].1.to_vec(),
+        Err(_) => {
+            code[0] = code[0].to_ascii_lowercase();
+            vec![tag::tag_from_bytes(code)]
+        }
+    }
+}
+
+/// Returns the ISO 15924 code of the script with the OpenType script tag `script_tag`.
+///
+/// `DFLT` gives `Zyyy` and `kana` gives `Kana`. Returns `None` if `script_tag` isn't a
+/// script tag.
+pub fn script_code(script_tag: u32) -> Option<String> {
+    let exception = SCRIPTS
+        .iter()
+        .find(|(_, tags)| tags[0] == script_tag && tags.len() > 1);
+    if let Some((code, _)) = exception {
+        return Some((*code).to_string());
+    }
+    let code = match script_tag {
+        tag::DFLT => "Zyyy",
+        tag::KANA => "Kana",
+        tag::HANI => "Hani",
+        tag::LAO => "Laoo",
+        tag::NKO => "Nkoo",
+        _ if script_tag == tag!(b"vai ") => "Vaii",
+        _ if script_tag == tag!(b"yi  ") => "Yiii",
+        _ if script_tag == tag!(b"math") => "Zmth",
+        _ => {
+            let bytes = script_tag.to_be_bytes();
+            if !bytes.iter().all(u8::is_ascii_lowercase) {
+                return None;
+            }
+            let mut code = String::from_utf8_lossy(&bytes).into_owned();
+            code[..1].make_ascii_uppercase();
+            return Some(code);
+        }
+    };
+    Some(code.to_string())
+}
+
+/// Returns the OpenType language system tag of the BCP 47 language tag `language`, such as
+/// `NLD ` for `nl-BE`.
+///
+/// Only the primary language subtag is used, except for Chinese where the script and region
+/// subtags choose between Simplified, Traditional and Hong Kong Chinese. Returns `None`, the
+/// default language system, for `und` and languages without a language system tag.
+pub fn language_tag(language: &str) -> Option<u32> {
+    let language = language.to_ascii_lowercase();
+    let mut subtags = language.split(&['-', '_'][..]);
+    let primary = subtags.next()?;
+    match primary {
+        "zh" | "cmn" => Some(chinese_language_tag(subtags)),
+        "yue" => Some(ZHH),
+        _ => LANGUAGES
+            .binary_search_by(|(subtag, _)| (*subtag).cmp(primary))
+            .ok()
+            .map(|index| LANGUAGES[index].1),
+    }
+}
+
+fn chinese_language_tag<'a>(subtags: impl Iterator<Item = &'a str>) -> u32 {
+    let mut lang_tag = ZHS;
+    for subtag in subtags {
+        match subtag {
+            "hant" | "tw" => lang_tag = ZHT,
+            "hk" | "mo" => return ZHH,
+            "hans" | "cn" | "sg" => lang_tag = ZHS,
+            // Private use and extension subtags follow
+            "x" | "u" | "t" => break,
+            _ => {}
+        }
+    }
+    lang_tag
+}
+
+/// Returns the BCP 47 language tag of the language with the OpenType language system tag
+/// `lang_tag`, or `None` if it isn't known.
+pub fn language_code(lang_tag: u32) -> Option<&'static str> {
+    match lang_tag {
+        ZHS => Some("zh-Hans"),
+        ZHT => Some("zh-Hant"),
+        ZHH => Some("zh-HK"),
+        _ => LANGUAGES
+            .iter()
+            .find(|(_, entry)| *entry == lang_tag)
+            .map(|(subtag, _)| *subtag),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tables_are_sorted() {
+        assert!(SCRIPTS.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert!(LANGUAGES.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    }
+
+    #[test]
+    fn test_script_tags() {
+        assert_eq!(script_tags("Latn"), vec![tag::LATN]);
+        assert_eq!(script_tags("latn"), vec![tag::LATN]);
+        assert_eq!(script_tags("Mlym"), vec![tag::MLM2, tag::MLYM]);
+        assert_eq!(script_tags("Laoo"), vec![tag::LAO]);
+        assert_eq!(script_tags("Zyyy"), vec![tag::DFLT]);
+        assert_eq!(script_tags("Lat"), Vec::<u32>::new());
+        assert_eq!(script_tag("Knda"), Some(tag::KNDA));
+        assert_eq!(script_tag("Hira"), Some(tag::KANA));
+    }
+
+    #[test]
+    fn test_script_code() {
+        assert_eq!(script_code(tag::LATN), Some(String::from("Latn")));
+        assert_eq!(script_code(tag::DEV2), Some(String::from("Deva")));
+        assert_eq!(script_code(tag::DEVA), Some(String::from("Deva")));
+        assert_eq!(script_code(tag::LAO), Some(String::from("Laoo")));
+        assert_eq!(script_code(tag::DFLT), Some(String::from("Zyyy")));
+        assert_eq!(script_code(tag::FAR), None);
+    }
+
+    #[test]
+    fn test_language_tag() {
+        assert_eq!(language_tag("en"), Some(tag!(b"ENG ")));
+        assert_eq!(language_tag("EN-us"), Some(tag!(b"ENG ")));
+        assert_eq!(language_tag("sd_PK"), Some(tag::SND));
+        assert_eq!(language_tag("fil"), Some(tag!(b"PIL ")));
+        assert_eq!(language_tag("zh"), Some(ZHS));
+        assert_eq!(language_tag("zh-TW"), Some(ZHT));
+        assert_eq!(language_tag("zh-Hant-HK"), Some(ZHH));
+        assert_eq!(language_tag("und"), None);
+        assert_eq!(language_tag(""), None);
+    }
+
+    #[test]
+    fn test_language_code() {
+        assert_eq!(language_code(tag::URD), Some("ur"));
+        assert_eq!(language_code(tag!(b"NOR ")), Some("nb"));
+        assert_eq!(language_code(ZHT), Some("zh-Hant"));
+        assert_eq!(language_code(tag!(b"XXX ")), None);
+    }
+}
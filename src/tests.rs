@@ -1,3 +1,150 @@
 //! Shared test code.
 
 include!("../tests/common.rs");
+
+use crate::binary::read::ReadScope;
+use crate::binary::write::{WriteBinary, WriteBuffer, WriteContext};
+use crate::binary::{U16Be, U32Be};
+use crate::sfnt;
+use crate::tables::{FontTableProvider, NameTable, NameTableBuilder, OpenTypeFont, TTF_MAGIC};
+use crate::tag;
+
+/// Write `values` to `w` as big-endian `u16`s, for building table fixtures.
+pub fn write_u16s(w: &mut WriteBuffer, values: &[u16]) {
+    for &value in values {
+        U16Be::write(w, value).unwrap();
+    }
+}
+
+/// An `fvar` table with a `wght` axis from 100 to 900, defaulting to 400, and a named instance
+/// Black at 900.
+fn fvar_data() -> Vec<u8> {
+    let mut w = WriteBuffer::new();
+    for &value in &[1u16, 0, 16, 2, 1, 20, 1, 8] {
+        U16Be::write(&mut w, value).unwrap();
+    }
+    U32Be::write(&mut w, tag!(b"wght")).unwrap();
+    for &value in &[100u32, 400, 900] {
+        U32Be::write(&mut w, value << 16).unwrap();
+    }
+    U16Be::write(&mut w, 0u16).unwrap(); // flags
+    U16Be::write(&mut w, 256u16).unwrap(); // axisNameID
+    U16Be::write(&mut w, 258u16).unwrap(); // subfamilyNameID
+    U16Be::write(&mut w, 0u16).unwrap(); // flags
+    U32Be::write(&mut w, 900u32 << 16).unwrap();
+    w.into_inner()
+}
+
+/// A `gvar` table for the three glyphs of test-font.ttf. At `wght` 900 the advance of the
+/// empty glyph 1 increases by 100, and the points of glyph 2 move right by 10 with its
+/// advance increasing by 20.
+fn gvar_data() -> Vec<u8> {
+    // One tuple peaking at 1.0 with private point numbers covering all points
+    let glyph_data = |x_deltas: &[u8]| {
+        let mut w = WriteBuffer::new();
+        U16Be::write(&mut w, 1u16).unwrap(); // tupleVariationCount
+        U16Be::write(&mut w, 10u16).unwrap(); // dataOffset
+        U16Be::write(&mut w, 3 + x_deltas.len() as u16).unwrap(); // variationDataSize
+        U16Be::write(&mut w, 0xA000u16).unwrap(); // tupleIndex
+        U16Be::write(&mut w, 0x4000u16).unwrap(); // peakTuple
+        w.write_bytes(&[0]).unwrap(); // all points
+        w.write_bytes(&[x_deltas.len() as u8 - 1]).unwrap();
+        w.write_bytes(x_deltas).unwrap();
+        w.write_bytes(&[0x80 | (x_deltas.len() as u8 - 1)]).unwrap(); // zero y deltas
+        w.into_inner()
+    };
+    let glyph1 = glyph_data(&[0, 100, 0, 0]);
+    let glyph2 = glyph_data(&[10, 10, 10, 0, 20, 0, 0]);
+
+    let mut w = WriteBuffer::new();
+    U16Be::write(&mut w, 1u16).unwrap(); // majorVersion
+    U16Be::write(&mut w, 0u16).unwrap(); // minorVersion
+    U16Be::write(&mut w, 1u16).unwrap(); // axisCount
+    U16Be::write(&mut w, 0u16).unwrap(); // sharedTupleCount
+    U32Be::write(&mut w, 0u32).unwrap(); // sharedTuplesOffset
+    U16Be::write(&mut w, 3u16).unwrap(); // glyphCount
+    U16Be::write(&mut w, 1u16).unwrap(); // flags, long offsets
+    U32Be::write(&mut w, 36u32).unwrap(); // glyphVariationDataArrayOffset
+    let end1 = glyph1.len() as u32;
+    for &offset in &[0, 0, end1, end1 + glyph2.len() as u32] {
+        U32Be::write(&mut w, offset).unwrap();
+    }
+    w.write_bytes(&glyph1).unwrap();
+    w.write_bytes(&glyph2).unwrap();
+    w.into_inner()
+}
+
+/// A `STAT` table naming `wght` 400 Regular (elidable), 700 Bold and 900 Black.
+fn stat_data() -> Vec<u8> {
+    let mut data = vec![
+        0, 1, 0, 1, // version 1.1
+        0, 8, // designAxisSize
+        0, 1, // designAxisCount
+        0, 0, 0, 20, // designAxesOffset
+        0, 3, // axisValueCount
+        0, 0, 0, 28, // offsetToAxisValueOffsets
+        0, 2, // elidedFallbackNameID
+    ];
+    data.extend_from_slice(b"wght");
+    data.extend_from_slice(&[1, 0, 0, 0]); // axisNameID, axisOrdering
+    data.extend_from_slice(&[0, 6, 0, 18, 0, 30]);
+    // Format 1 axis values: Regular 400 (elidable), Bold 700, and Black 900
+    data.extend_from_slice(&[0, 1, 0, 0, 0, 2, 0, 2, 1, 144, 0, 0]);
+    data.extend_from_slice(&[0, 1, 0, 0, 0, 0, 1, 1, 2, 188, 0, 0]);
+    data.extend_from_slice(&[0, 1, 0, 0, 0, 0, 1, 2, 3, 132, 0, 0]);
+    data
+}
+
+/// The `name` table of test-font.ttf with the names of `fvar_data` and `stat_data`, and a
+/// variations PostScript name prefix.
+fn name_data(provider: &impl FontTableProvider) -> Vec<u8> {
+    let name_data = provider.read_table_data(tag::NAME).unwrap();
+    let name = ReadScope::new(&name_data).read::<NameTable<'_>>().unwrap();
+    let mut builder = NameTableBuilder::from_table(&name).unwrap();
+    let names = [
+        (25, "WOFFTestTTFVF"),
+        (256, "Weight"),
+        (257, "Bold"),
+        (258, "Black"),
+    ];
+    for &(platform_id, encoding_id, language_id) in &[(1, 0, 0), (3, 1, 0x409)] {
+        for &(name_id, value) in &names {
+            builder
+                .set_name(platform_id, encoding_id, language_id, name_id, value)
+                .unwrap();
+        }
+    }
+    let mut w = WriteBuffer::new();
+    NameTableBuilder::write(&mut w, &builder).unwrap();
+    w.into_inner()
+}
+
+/// test-font.ttf made variable with the tables of `fvar_data`, `gvar_data`, `stat_data` and
+/// `name_data`, and signed with an empty `DSIG` table.
+pub fn variable_font() -> Vec<u8> {
+    let buffer = read_fixture("tests/fonts/opentype/test-font.ttf");
+    let font_file = ReadScope::new(&buffer).read::<OpenTypeFont<'_>>().unwrap();
+    let provider = font_file.table_provider(0).unwrap();
+    let fvar = fvar_data();
+    let gvar = gvar_data();
+    let stat = stat_data();
+    let name = name_data(&provider);
+    let mut tables = Vec::new();
+    for table_tag in provider.table_tags().unwrap() {
+        if table_tag != tag::NAME {
+            tables.push((table_tag, provider.read_table_data(table_tag).unwrap()));
+        }
+    }
+    let mut tables = tables
+        .iter()
+        .map(|(table_tag, data)| (*table_tag, &data[..]))
+        .collect::<Vec<_>>();
+    tables.push((tag::FVAR, &fvar));
+    tables.push((tag::GVAR, &gvar));
+    tables.push((tag::NAME, &name));
+    tables.push((tag::STAT, &stat));
+    // An empty signature, which is invalidated by instancing
+    let dsig = [0, 0, 0, 1, 0, 0, 0, 0];
+    tables.push((tag::DSIG, &dsig));
+    sfnt::write_font(TTF_MAGIC, &tables).unwrap()
+}
@@ -0,0 +1,169 @@
+#![deny(missing_docs)]
+
+//! Tracing of the lookups applied while shaping.
+//!
+//! Tracing is opt-in. Shaping performed within `trace` records a `TraceEvent` for each `GSUB`
+//! and `GPOS` lookup that is applied, noting the glyphs it substituted or positioned. This is
+//! intended for debugging fonts, such as finding out why a ligature wasn't formed: the trace
+//! shows whether the lookup of the `liga` feature was applied to the glyphs and what the
+//! glyphs were at the time.
+//!
+//! Events are recorded for the current thread only, so runs shaped on other threads, such as by
+//! `Font::shape_runs_parallel`, are not traced. Lookups applied from within a contextual lookup
+//! are included in the event for the contextual lookup.
+//!
+//! ```
+//! use allsorts::font::{MatchingPresentation, OwnedFont};
+//! use allsorts::gsub::{Features, GsubFeatureMask};
+//! use allsorts::tag;
+//! use allsorts::trace;
+//!
+//! let buffer = std::fs::read("tests/fonts/opentype/Klei.otf").unwrap();
+//! let mut font = OwnedFont::from_data(buffer, 0).unwrap().unwrap();
+//! let glyphs = font.map_glyphs("jiffy", MatchingPresentation::NotRequired);
+//! let features = Features::Mask(GsubFeatureMask::default());
+//! let (infos, trace) = trace::trace(|| font.shape(glyphs, tag::LATN, None, &features, true));
+//! assert_eq!(infos.unwrap().len(), 4);
+//! assert!(trace
+//!     .substitutions()
+//!     .any(|event| event.feature_tag() == Some(tag::LIGA)));
+//! ```
+
+use std::cell::RefCell;
+
+use crate::layout::{PosLookupType, SubstLookupType};
+
+thread_local! {
+    static EVENTS: RefCell<Option<Vec<TraceEvent>>> = RefCell::default();
+}
+
+/// A lookup applied while shaping.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TraceEvent {
+    /// A `GSUB` lookup was applied to a range of glyphs.
+    Substitution {
+        /// The index of the lookup in the `GSUB` lookup list.
+        lookup_index: usize,
+        /// The type of the lookup.
+        lookup_type: SubstLookupType,
+        /// The feature that enabled the lookup.
+        feature_tag: u32,
+        /// The index of the first glyph of the range in the glyph run.
+        start: usize,
+        /// The glyph indices of the range before the lookup was applied.
+        before: Vec<u16>,
+        /// The glyph indices of the range after the lookup was applied.
+        after: Vec<u16>,
+    },
+    /// A `GPOS` lookup was applied to the glyph run.
+    Positioning {
+        /// The index of the lookup in the `GPOS` lookup list.
+        lookup_index: usize,
+        /// The type of the lookup.
+        lookup_type: PosLookupType,
+        /// The indices in the glyph run of the glyphs whose positioning was changed.
+        positioned: Vec<usize>,
+    },
+}
+
+/// The lookups applied while shaping, in the order they were applied.
+#[derive(Debug, Clone, Default)]
+pub struct ShapingTrace {
+    /// The recorded events.
+    pub events: Vec<TraceEvent>,
+}
+
+impl TraceEvent {
+    /// Returns `true` if the lookup changed the glyphs or their positioning.
+    pub fn matched(&self) -> bool {
+        match self {
+            TraceEvent::Substitution { before, after, .. } => before != after,
+            TraceEvent::Positioning { positioned, .. } => !positioned.is_empty(),
+        }
+    }
+
+    /// The feature that enabled the lookup, if known.
+    ///
+    /// `GPOS` lookups are not currently attributed to a feature.
+    pub fn feature_tag(&self) -> Option<u32> {
+        match self {
+            TraceEvent::Substitution { feature_tag, .. } => Some(*feature_tag),
+            TraceEvent::Positioning { .. } => None,
+        }
+    }
+}
+
+impl ShapingTrace {
+    /// The `GSUB` lookups that substituted glyphs.
+    pub fn substitutions(&self) -> impl Iterator<Item = &TraceEvent> {
+        self.events.iter().filter(|event| match event {
+            TraceEvent::Substitution { .. } => event.matched(),
+            TraceEvent::Positioning { .. } => false,
+        })
+    }
+
+    /// The `GPOS` lookups that positioned glyphs.
+    pub fn positionings(&self) -> impl Iterator<Item = &TraceEvent> {
+        self.events.iter().filter(|event| match event {
+            TraceEvent::Substitution { .. } => false,
+            TraceEvent::Positioning { .. } => event.matched(),
+        })
+    }
+}
+
+/// Call `f`, recording the lookups applied by shaping performed within it on this thread.
+///
+/// Calls may be nested, in which case the events are recorded by the innermost call.
+pub fn trace<R>(f: impl FnOnce() -> R) -> (R, ShapingTrace) {
+    let outer = EVENTS.with(|events| events.replace(Some(Vec::new())));
+    let result = f();
+    let events = EVENTS.with(|events| events.replace(outer));
+    let trace = ShapingTrace {
+        events: events.unwrap_or_default(),
+    };
+    (result, trace)
+}
+
+/// Returns `true` if lookups applied on this thread are being recorded.
+pub(crate) fn is_enabled() -> bool {
+    EVENTS.with(|events| events.borrow().is_some())
+}
+
+/// Record `event` if tracing is enabled.
+pub(crate) fn record(event: TraceEvent) {
+    EVENTS.with(|events| {
+        if let Some(events) = events.borrow_mut().as_mut() {
+            events.push(event);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(before: Vec<u16>, after: Vec<u16>) -> TraceEvent {
+        TraceEvent::Substitution {
+            lookup_index: 0,
+            lookup_type: SubstLookupType::SingleSubst,
+            feature_tag: 0,
+            start: 0,
+            before,
+            after,
+        }
+    }
+
+    #[test]
+    fn test_trace_nested() {
+        assert!(!is_enabled());
+        record(event(vec![1], vec![2]));
+        let ((), outer) = trace(|| {
+            record(event(vec![1], vec![2]));
+            let ((), inner) = trace(|| record(event(vec![3], vec![3])));
+            assert_eq!(inner.events.len(), 1);
+            assert_eq!(inner.substitutions().count(), 0);
+        });
+        assert!(!is_enabled());
+        assert_eq!(outer.events, vec![event(vec![1], vec![2])]);
+    }
+}
@@ -0,0 +1,1147 @@
+//! Conversion of tables to and from the TTX format of fontTools.
+//!
+//! [TTX](https://fonttools.readthedocs.io/en/latest/ttx.html) is an XML representation of the
+//! tables of a font that is widely used for inspecting fonts and for writing test fixtures.
+//! `dump` writes the supported tables of a font as TTX, and `compile` builds the binary tables
+//! from the supported tables of a TTX document.
+//!
+//! The supported tables are `head`, `hhea`, `maxp`, `cmap`, and `name`. Other tables are left out
+//! by `dump` and ignored by `compile`. The `cmap` formats that can be compiled are 0, 4, 6, and 12.
+
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::fmt::Display;
+
+use crate::binary::read::ReadScope;
+use crate::binary::write::{WriteBinary, WriteBuffer, WriteContext};
+use crate::error::{ParseError, ReadWriteError, WriteError};
+use crate::font::Font;
+use crate::tables::cmap::owned::{create_format12_subtable, create_format4_subtable};
+use crate::tables::cmap::{self, Cmap, CmapSubtable};
+use crate::tables::{
+    decode_name, FontTableProvider, HeadTable, HheaTable, IndexToLocFormat, MaxpTable,
+    MaxpVersion1SubTable, NameTable, NameTableBuilder,
+};
+use crate::tag;
+
+/// Seconds from the `head` table epoch, 1904-01-01, to the Unix epoch.
+const SECONDS_1904_TO_1970: i64 = 2_082_844_800;
+
+const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Elements nested deeper than this are rejected by `compile`.
+const MAX_DEPTH: usize = 32;
+
+/// Write the supported tables of `font` as a TTX document.
+pub fn dump<T: FontTableProvider>(font: &Font<T>) -> Result<String, ParseError> {
+    let provider = &font.font_table_provider;
+    let glyph_ids = (0..font.num_glyphs()).collect::<Vec<_>>();
+    let glyph_names = font.glyph_names(&glyph_ids);
+
+    let mut xml = XmlWriter::new();
+    let sfnt_version = if provider.has_table(tag::CFF) {
+        "OTTO"
+    } else {
+        "\\x00\\x01\\x00\\x00"
+    };
+    xml.start("ttFont", &[("sfntVersion", sfnt_version.to_string())]);
+
+    xml.blank_line();
+    xml.start("GlyphOrder", &[]);
+    xml.comment("The 'id' attribute is only for humans; it is ignored when parsed.");
+    for (glyph_id, name) in glyph_names.iter().enumerate() {
+        xml.empty(
+            "GlyphID",
+            &[("id", glyph_id.to_string()), ("name", name.to_string())],
+        );
+    }
+    xml.end("GlyphOrder");
+
+    if let Some(data) = provider.table_data(tag::HEAD)? {
+        let head = ReadScope::new(&data).read::<HeadTable>()?;
+        dump_head(&mut xml, &head);
+    }
+    if let Some(data) = provider.table_data(tag::HHEA)? {
+        let hhea = ReadScope::new(&data).read::<HheaTable>()?;
+        dump_hhea(&mut xml, &hhea);
+    }
+    if let Some(data) = provider.table_data(tag::MAXP)? {
+        let maxp = ReadScope::new(&data).read::<MaxpTable>()?;
+        dump_maxp(&mut xml, &maxp);
+    }
+    if let Some(data) = provider.table_data(tag::CMAP)? {
+        let cmap = ReadScope::new(&data).read::<Cmap<'_>>()?;
+        dump_cmap(&mut xml, &cmap, &glyph_names)?;
+    }
+    if let Some(data) = provider.table_data(tag::NAME)? {
+        let name = ReadScope::new(&data).read::<NameTable<'_>>()?;
+        dump_name(&mut xml, &name)?;
+    }
+
+    xml.blank_line();
+    xml.end("ttFont");
+    Ok(xml.finish())
+}
+
+/// Build the binary data of the supported tables in the TTX document `ttx`, keyed by table tag.
+pub fn compile(ttx: &str) -> Result<BTreeMap<u32, Vec<u8>>, ReadWriteError> {
+    let root = XmlParser::parse(ttx)?;
+    if root.name != "ttFont" {
+        return Err(ParseError::BadValue.with_offset(0).into());
+    }
+
+    let mut glyph_order = HashMap::new();
+    if let Some(element) = root.child("GlyphOrder") {
+        for (glyph_id, glyph) in element.children("GlyphID").enumerate() {
+            let glyph_id = u16::try_from(glyph_id).map_err(ParseError::from)?;
+            glyph_order.insert(glyph.attribute("name")?, glyph_id);
+        }
+    }
+
+    let mut tables = BTreeMap::new();
+    for element in &root.children {
+        let (table_tag, data) = match element.name.as_str() {
+            "head" => (tag::HEAD, compile_head(element)),
+            "hhea" => (tag::HHEA, compile_hhea(element)),
+            "maxp" => (tag::MAXP, compile_maxp(element)),
+            "cmap" => (tag::CMAP, compile_cmap(element, &glyph_order)),
+            "name" => (tag::NAME, compile_name(element)),
+            _ => continue,
+        };
+        let data = data.map_err(|err| match err {
+            ReadWriteError::Read(err) => ReadWriteError::Read(err.with_table(table_tag)),
+            err => err,
+        })?;
+        tables.insert(table_tag, data);
+    }
+    Ok(tables)
+}
+
+fn dump_head(xml: &mut XmlWriter, head: &HeadTable) {
+    let version = (u32::from(head.major_version) << 16) | u32::from(head.minor_version);
+
+    xml.blank_line();
+    xml.start("head", &[]);
+    xml.comment("Most of this table will be recalculated by the compiler");
+    xml.value("tableVersion", format_fixed(version as i32));
+    xml.value("fontRevision", format_fixed(head.font_revision as i32));
+    xml.value("checkSumAdjustment", format_hex(head.check_sum_adjustment));
+    xml.value("magicNumber", format_hex(head.magic_number));
+    xml.value("flags", format_binary(head.flags));
+    xml.value("unitsPerEm", head.units_per_em);
+    xml.value("created", format_timestamp(head.created));
+    xml.value("modified", format_timestamp(head.modified));
+    xml.value("xMin", head.x_min);
+    xml.value("yMin", head.y_min);
+    xml.value("xMax", head.x_max);
+    xml.value("yMax", head.y_max);
+    xml.value("macStyle", format_binary(head.mac_style));
+    xml.value("lowestRecPPEM", head.lowest_rec_ppem);
+    xml.value("fontDirectionHint", head.font_direction_hint);
+    let index_to_loc_format = match head.index_to_loc_format {
+        IndexToLocFormat::Short => 0,
+        IndexToLocFormat::Long => 1,
+    };
+    xml.value("indexToLocFormat", index_to_loc_format);
+    xml.value("glyphDataFormat", head.glyph_data_format);
+    xml.end("head");
+}
+
+fn dump_hhea(xml: &mut XmlWriter, hhea: &HheaTable) {
+    xml.blank_line();
+    xml.start("hhea", &[]);
+    xml.value("tableVersion", "0x00010000");
+    xml.value("ascent", hhea.ascender);
+    xml.value("descent", hhea.descender);
+    xml.value("lineGap", hhea.line_gap);
+    xml.value("advanceWidthMax", hhea.advance_width_max);
+    xml.value("minLeftSideBearing", hhea.min_left_side_bearing);
+    xml.value("minRightSideBearing", hhea.min_right_side_bearing);
+    xml.value("xMaxExtent", hhea.x_max_extent);
+    xml.value("caretSlopeRise", hhea.caret_slope_rise);
+    xml.value("caretSlopeRun", hhea.caret_slope_run);
+    xml.value("caretOffset", hhea.caret_offset);
+    for reserved in &["reserved0", "reserved1", "reserved2", "reserved3"] {
+        xml.value(reserved, 0);
+    }
+    xml.value("metricDataFormat", 0);
+    xml.value("numberOfHMetrics", hhea.num_h_metrics);
+    xml.end("hhea");
+}
+
+fn dump_maxp(xml: &mut XmlWriter, maxp: &MaxpTable) {
+    xml.blank_line();
+    xml.start("maxp", &[]);
+    xml.comment("Most of this table will be recalculated by the compiler");
+    match &maxp.version1_sub_table {
+        Some(sub_table) => {
+            xml.value("tableVersion", "0x10000");
+            xml.value("numGlyphs", maxp.num_glyphs);
+            xml.value("maxPoints", sub_table.max_points);
+            xml.value("maxContours", sub_table.max_contours);
+            xml.value("maxCompositePoints", sub_table.max_composite_points);
+            xml.value("maxCompositeContours", sub_table.max_composite_contours);
+            xml.value("maxZones", sub_table.max_zones);
+            xml.value("maxTwilightPoints", sub_table.max_twilight_points);
+            xml.value("maxStorage", sub_table.max_storage);
+            xml.value("maxFunctionDefs", sub_table.max_function_defs);
+            xml.value("maxInstructionDefs", sub_table.max_instruction_defs);
+            xml.value("maxStackElements", sub_table.max_stack_elements);
+            xml.value("maxSizeOfInstructions", sub_table.max_size_of_instructions);
+            xml.value("maxComponentElements", sub_table.max_component_elements);
+            xml.value("maxComponentDepth", sub_table.max_component_depth);
+        }
+        None => {
+            xml.value("tableVersion", "0x5000");
+            xml.value("numGlyphs", maxp.num_glyphs);
+        }
+    }
+    xml.end("maxp");
+}
+
+fn dump_cmap<N: AsRef<str>>(
+    xml: &mut XmlWriter,
+    cmap: &Cmap<'_>,
+    glyph_names: &[N],
+) -> Result<(), ParseError> {
+    xml.blank_line();
+    xml.start("cmap", &[]);
+    xml.empty("tableVersion", &[("version", "0".to_string())]);
+    for record in cmap.encoding_records() {
+        let sub_table = cmap
+            .scope
+            .offset(usize::try_from(record.offset)?)
+            .read::<CmapSubtable<'_>>()?;
+        let sub_table = match sub_table.to_owned() {
+            Some(sub_table) => sub_table,
+            None => {
+                xml.comment("cmap subtable in an unsupported format");
+                continue;
+            }
+        };
+
+        let mut attributes = vec![
+            ("platformID", record.platform_id.to_string()),
+            ("platEncID", record.encoding_id.to_string()),
+        ];
+        let mappings = match &sub_table {
+            // All 256 codes of a format 0 subtable are listed, including those mapped to .notdef
+            cmap::owned::CmapSubtable::Format0 { glyph_id_array, .. } => glyph_id_array
+                .iter()
+                .enumerate()
+                .map(|(ch, &glyph_id)| (ch as u32, u16::from(glyph_id)))
+                .collect(),
+            cmap::owned::CmapSubtable::Format10 { .. } => {
+                xml.comment("cmap subtable in an unsupported format");
+                continue;
+            }
+            cmap::owned::CmapSubtable::Format12 { groups, .. } => {
+                attributes.push(("format", "12".to_string()));
+                attributes.push(("reserved", "0".to_string()));
+                attributes.push(("length", (16 + 12 * groups.len()).to_string()));
+                sub_table.char_mappings()?
+            }
+            _ => sub_table.char_mappings()?,
+        };
+        attributes.push(("language", sub_table.language().to_string()));
+        if let cmap::owned::CmapSubtable::Format12 { groups, .. } = &sub_table {
+            attributes.push(("nGroups", groups.len().to_string()));
+        }
+
+        let element_name = format!("cmap_format_{}", sub_table.format());
+        xml.start(&element_name, &attributes);
+        for (ch, glyph_id) in mappings {
+            let name = glyph_names
+                .get(usize::from(glyph_id))
+                .map(|name| name.as_ref().to_string())
+                .unwrap_or_else(|| format!("glyph{:05}", glyph_id));
+            xml.empty("map", &[("code", format!("{:#x}", ch)), ("name", name)]);
+        }
+        xml.end(&element_name);
+    }
+    xml.end("cmap");
+    Ok(())
+}
+
+fn dump_name(xml: &mut XmlWriter, name: &NameTable<'_>) -> Result<(), ParseError> {
+    xml.blank_line();
+    xml.start("name", &[]);
+    for record in &name.name_records {
+        let data = name
+            .string_storage
+            .offset_length(usize::from(record.offset), usize::from(record.length))?;
+        let value = match decode_name(record.platform_id, record.encoding_id, data.data()) {
+            Some(value) => value,
+            None => {
+                xml.comment(&format!(
+                    "name {} in an unsupported encoding (platform {}, encoding {})",
+                    record.name_id, record.platform_id, record.encoding_id
+                ));
+                continue;
+            }
+        };
+        xml.start(
+            "namerecord",
+            &[
+                ("nameID", record.name_id.to_string()),
+                ("platformID", record.platform_id.to_string()),
+                ("platEncID", record.encoding_id.to_string()),
+                ("langID", format!("{:#x}", record.language_id)),
+            ],
+        );
+        xml.text(&value);
+        xml.end("namerecord");
+    }
+    xml.end("name");
+    Ok(())
+}
+
+fn compile_head(element: &Element) -> Result<Vec<u8>, ReadWriteError> {
+    let version = parse_fixed(element.value("tableVersion")?)
+        .ok_or_else(|| ParseError::BadValue.with_field("tableVersion"))? as u32;
+    let font_revision = parse_fixed(element.value("fontRevision")?)
+        .ok_or_else(|| ParseError::BadValue.with_field("fontRevision"))?;
+    let index_to_loc_format = match element.number::<u8>("indexToLocFormat")? {
+        0 => IndexToLocFormat::Short,
+        1 => IndexToLocFormat::Long,
+        _ => return Err(ParseError::BadValue.with_field("indexToLocFormat").into()),
+    };
+    let head = HeadTable {
+        major_version: (version >> 16) as u16,
+        minor_version: version as u16,
+        font_revision: font_revision as u32,
+        check_sum_adjustment: element.number("checkSumAdjustment")?,
+        magic_number: element.number("magicNumber")?,
+        flags: element.binary("flags")?,
+        units_per_em: element.number("unitsPerEm")?,
+        created: element.timestamp("created")?,
+        modified: element.timestamp("modified")?,
+        x_min: element.number("xMin")?,
+        y_min: element.number("yMin")?,
+        x_max: element.number("xMax")?,
+        y_max: element.number("yMax")?,
+        mac_style: element.binary("macStyle")?,
+        lowest_rec_ppem: element.number("lowestRecPPEM")?,
+        font_direction_hint: element.number("fontDirectionHint")?,
+        index_to_loc_format,
+        glyph_data_format: element.number("glyphDataFormat")?,
+    };
+
+    let mut ctxt = WriteBuffer::new();
+    let placeholder = HeadTable::write(&mut ctxt, &head)?;
+    ctxt.write_placeholder(placeholder, head.check_sum_adjustment)?;
+    Ok(ctxt.into_inner())
+}
+
+fn compile_hhea(element: &Element) -> Result<Vec<u8>, ReadWriteError> {
+    let hhea = HheaTable {
+        ascender: element.number("ascent")?,
+        descender: element.number("descent")?,
+        line_gap: element.number("lineGap")?,
+        advance_width_max: element.number("advanceWidthMax")?,
+        min_left_side_bearing: element.number("minLeftSideBearing")?,
+        min_right_side_bearing: element.number("minRightSideBearing")?,
+        x_max_extent: element.number("xMaxExtent")?,
+        caret_slope_rise: element.number("caretSlopeRise")?,
+        caret_slope_run: element.number("caretSlopeRun")?,
+        caret_offset: element.number("caretOffset")?,
+        num_h_metrics: element.number("numberOfHMetrics")?,
+    };
+
+    let mut ctxt = WriteBuffer::new();
+    HheaTable::write(&mut ctxt, &hhea)?;
+    Ok(ctxt.into_inner())
+}
+
+fn compile_maxp(element: &Element) -> Result<Vec<u8>, ReadWriteError> {
+    let version1_sub_table = match element.number::<u32>("tableVersion")? {
+        0x5000 => None,
+        0x10000 => Some(MaxpVersion1SubTable {
+            max_points: element.number("maxPoints")?,
+            max_contours: element.number("maxContours")?,
+            max_composite_points: element.number("maxCompositePoints")?,
+            max_composite_contours: element.number("maxCompositeContours")?,
+            max_zones: element.number("maxZones")?,
+            max_twilight_points: element.number("maxTwilightPoints")?,
+            max_storage: element.number("maxStorage")?,
+            max_function_defs: element.number("maxFunctionDefs")?,
+            max_instruction_defs: element.number("maxInstructionDefs")?,
+            max_stack_elements: element.number("maxStackElements")?,
+            max_size_of_instructions: element.number("maxSizeOfInstructions")?,
+            max_component_elements: element.number("maxComponentElements")?,
+            max_component_depth: element.number("maxComponentDepth")?,
+        }),
+        _ => return Err(ParseError::BadVersion.with_field("tableVersion").into()),
+    };
+    let maxp = MaxpTable {
+        num_glyphs: element.number("numGlyphs")?,
+        version1_sub_table,
+    };
+
+    let mut ctxt = WriteBuffer::new();
+    MaxpTable::write(&mut ctxt, &maxp)?;
+    Ok(ctxt.into_inner())
+}
+
+fn compile_cmap(
+    element: &Element,
+    glyph_order: &HashMap<&str, u16>,
+) -> Result<Vec<u8>, ReadWriteError> {
+    let mut encoding_records = Vec::new();
+    for sub_table in &element.children {
+        const PREFIX: &str = "cmap_format_";
+        if !sub_table.name.starts_with(PREFIX) {
+            continue;
+        }
+        let format = &sub_table.name[PREFIX.len()..];
+        let platform_id = sub_table.number_attribute("platformID")?;
+        let encoding_id = sub_table.number_attribute("platEncID")?;
+        let mut mappings = BTreeMap::new();
+        for map in sub_table.children("map") {
+            let ch = parse_number(map.attribute("code")?)
+                .and_then(|ch| u32::try_from(ch).ok())
+                .ok_or_else(|| ParseError::BadValue.with_field("code"))?;
+            mappings.insert(ch, glyph_id(glyph_order, map.attribute("name")?)?);
+        }
+        let mappings = mappings.into_iter().collect::<Vec<_>>();
+        let language = sub_table.number_attribute::<u32>("language")?;
+        let language16 = u16::try_from(language).map_err(WriteError::from)?;
+
+        let sub_table = match format {
+            "0" => {
+                let mut glyph_id_array = Box::new([0; 256]);
+                for &(ch, glyph_id) in &mappings {
+                    let index = usize::try_from(ch).map_err(WriteError::from)?;
+                    if index >= glyph_id_array.len() {
+                        return Err(WriteError::BadValue.into());
+                    }
+                    glyph_id_array[index] = u8::try_from(glyph_id).map_err(WriteError::from)?;
+                }
+                cmap::owned::CmapSubtable::Format0 {
+                    language: language16,
+                    glyph_id_array,
+                }
+            }
+            "4" => create_format4_subtable(&mappings, language16),
+            "6" => {
+                let first_code = mappings.first().map_or(0, |&(ch, _)| ch);
+                let last_code = mappings.last().map_or(0, |&(ch, _)| ch);
+                let first_code = u16::try_from(first_code).map_err(WriteError::from)?;
+                let last_code = u16::try_from(last_code).map_err(WriteError::from)?;
+                let mut glyph_id_array = if mappings.is_empty() {
+                    Vec::new()
+                } else {
+                    vec![0; usize::from(last_code - first_code) + 1]
+                };
+                for &(ch, glyph_id) in &mappings {
+                    glyph_id_array[(ch - u32::from(first_code)) as usize] = glyph_id;
+                }
+                cmap::owned::CmapSubtable::Format6 {
+                    language: language16,
+                    first_code,
+                    glyph_id_array,
+                }
+            }
+            "12" => create_format12_subtable(&mappings, language),
+            _ => return Err(WriteError::NotImplemented.into()),
+        };
+        encoding_records.push(cmap::owned::EncodingRecord {
+            platform_id,
+            encoding_id,
+            sub_table,
+        });
+    }
+
+    let mut ctxt = WriteBuffer::new();
+    cmap::owned::Cmap::write(&mut ctxt, cmap::owned::Cmap { encoding_records })?;
+    Ok(ctxt.into_inner())
+}
+
+fn compile_name(element: &Element) -> Result<Vec<u8>, ReadWriteError> {
+    let mut builder = NameTableBuilder::new();
+    for record in element.children("namerecord") {
+        builder.set_name(
+            record.number_attribute("platformID")?,
+            record.number_attribute("platEncID")?,
+            record.number_attribute("langID")?,
+            record.number_attribute("nameID")?,
+            record.text.trim(),
+        )?;
+    }
+
+    let mut ctxt = WriteBuffer::new();
+    NameTableBuilder::write(&mut ctxt, &builder)?;
+    Ok(ctxt.into_inner())
+}
+
+/// Look up the glyph named `name` in the glyph order, falling back on the `glyphNNNNN` names used
+/// by fontTools for glyphs without a name.
+fn glyph_id(glyph_order: &HashMap<&str, u16>, name: &str) -> Result<u16, ParseError> {
+    if let Some(&glyph_id) = glyph_order.get(name) {
+        return Ok(glyph_id);
+    }
+    if name == ".notdef" {
+        return Ok(0);
+    }
+    if name.get(..5) == Some("glyph") {
+        if let Ok(glyph_id) = name[5..].parse() {
+            return Ok(glyph_id);
+        }
+    }
+    Err(ParseError::BadValue.with_field("name"))
+}
+
+/// Parse a decimal or `0x` prefixed hexadecimal integer.
+fn parse_number(value: &str) -> Option<i64> {
+    let value = value.trim();
+    let negative = value.starts_with('-');
+    let digits = if negative { &value[1..] } else { value };
+    let hex = digits.len() > 2 && digits.as_bytes()[..2].eq_ignore_ascii_case(b"0x");
+    let number = if hex {
+        i64::from_str_radix(&digits[2..], 16).ok()?
+    } else {
+        digits.parse::<i64>().ok()?
+    };
+    Some(if negative { -number } else { number })
+}
+
+/// Format a 16.16 fixed-point number with the fewest decimal places that represent it exactly.
+fn format_fixed(value: i32) -> String {
+    let exact = f64::from(value) / 65536.0;
+    for precision in 1..=5 {
+        let formatted = format!("{:.*}", precision, exact);
+        match formatted.parse::<f64>() {
+            Ok(parsed) if (parsed * 65536.0).round() == f64::from(value) => return formatted,
+            _ => {}
+        }
+    }
+    exact.to_string()
+}
+
+/// Parse a 16.16 fixed-point number written as a decimal or a hexadecimal integer.
+fn parse_fixed(value: &str) -> Option<i32> {
+    let value = value.trim();
+    if value.starts_with("0x") {
+        return parse_number(value).and_then(|value| i32::try_from(value).ok());
+    }
+    let fixed = (value.parse::<f64>().ok()? * 65536.0).round();
+    if fixed >= f64::from(std::i32::MIN) && fixed <= f64::from(std::i32::MAX) {
+        Some(fixed as i32)
+    } else {
+        None
+    }
+}
+
+fn format_hex(value: u32) -> String {
+    format!("{:#010x}", value)
+}
+
+/// Format a 16-bit value as two space separated groups of 8 bits.
+fn format_binary(value: u16) -> String {
+    format!("{:08b} {:08b}", value >> 8, value & 0xFF)
+}
+
+fn parse_binary(value: &str) -> Option<u16> {
+    let digits = value
+        .chars()
+        .filter(|ch| !ch.is_whitespace())
+        .collect::<String>();
+    u16::from_str_radix(&digits, 2).ok()
+}
+
+/// Format seconds since 1904-01-01 in UTC like C's `asctime`, e.g. `Thu Jan  1 00:00:00 1970`.
+fn format_timestamp(timestamp: i64) -> String {
+    let seconds = timestamp.saturating_sub(SECONDS_1904_TO_1970);
+    let days = seconds.div_euclid(86_400);
+    let seconds_of_day = seconds.rem_euclid(86_400);
+    let (year, month, day) = civil_from_days(days);
+    let weekday = (days + 4).rem_euclid(7); // 1970-01-01 was a Thursday
+    format!(
+        "{} {} {:2} {:02}:{:02}:{:02} {}",
+        WEEKDAYS[weekday as usize],
+        MONTHS[month as usize - 1],
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day / 60 % 60,
+        seconds_of_day % 60,
+        year
+    )
+}
+
+/// Parse a date formatted by `format_timestamp` into seconds since 1904-01-01.
+///
+/// The day of the week is not checked.
+fn parse_timestamp(value: &str) -> Option<i64> {
+    let fields = value.split_whitespace().collect::<Vec<_>>();
+    if fields.len() != 5 {
+        return None;
+    }
+    let month = MONTHS.iter().position(|&month| month == fields[1])? as i64 + 1;
+    let day = fields[2].parse::<i64>().ok()?;
+    let year = fields[4].parse::<i64>().ok()?;
+    let time = fields[3]
+        .split(':')
+        .map(|field| field.parse::<i64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    if time.len() != 3 || !(1..=31).contains(&day) {
+        return None;
+    }
+    let days = days_from_civil(year, month, day);
+    let seconds = days
+        .checked_mul(86_400)?
+        .checked_add(time[0] * 3600 + time[1] * 60 + time[2])?;
+    seconds.checked_add(SECONDS_1904_TO_1970)
+}
+
+// Conversions between days since 1970-01-01 and dates in the proleptic Gregorian calendar, from
+// <https://howardhinnant.github.io/date_algorithms.html>
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            ch if ch.is_control() => escaped.push_str(&format!("&#x{:x};", u32::from(ch))),
+            ch => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+/// Replace the character and entity references in `text`. Returns `None` if a reference is
+/// malformed or unknown.
+fn unescape(text: &str) -> Option<String> {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        unescaped.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+        let end = rest.find(';')?;
+        let ch = match &rest[..end] {
+            "amp" => '&',
+            "lt" => '<',
+            "gt" => '>',
+            "quot" => '"',
+            "apos" => '\'',
+            reference if reference.starts_with("#x") => {
+                std::char::from_u32(u32::from_str_radix(&reference[2..], 16).ok()?)?
+            }
+            reference if reference.starts_with('#') => {
+                std::char::from_u32(reference[1..].parse().ok()?)?
+            }
+            _ => return None,
+        };
+        unescaped.push(ch);
+        rest = &rest[end + 1..];
+    }
+    unescaped.push_str(rest);
+    Some(unescaped)
+}
+
+/// Writes indented XML in the layout used by fontTools.
+struct XmlWriter {
+    xml: String,
+    depth: usize,
+}
+
+impl XmlWriter {
+    fn new() -> Self {
+        XmlWriter {
+            xml: String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n"),
+            depth: 0,
+        }
+    }
+
+    fn finish(self) -> String {
+        self.xml
+    }
+
+    fn indent(&mut self) {
+        for _ in 0..self.depth {
+            self.xml.push_str("  ");
+        }
+    }
+
+    fn tag(&mut self, name: &str, attributes: &[(&str, String)], empty: bool) {
+        self.indent();
+        self.xml.push('<');
+        self.xml.push_str(name);
+        for (attribute, value) in attributes {
+            self.xml.push(' ');
+            self.xml.push_str(attribute);
+            self.xml.push_str("=\"");
+            self.xml.push_str(&escape(value));
+            self.xml.push('"');
+        }
+        self.xml.push_str(if empty { "/>\n" } else { ">\n" });
+    }
+
+    fn start(&mut self, name: &str, attributes: &[(&str, String)]) {
+        self.tag(name, attributes, false);
+        self.depth += 1;
+    }
+
+    fn end(&mut self, name: &str) {
+        self.depth -= 1;
+        self.indent();
+        self.xml.push_str("</");
+        self.xml.push_str(name);
+        self.xml.push_str(">\n");
+    }
+
+    fn empty(&mut self, name: &str, attributes: &[(&str, String)]) {
+        self.tag(name, attributes, true);
+    }
+
+    /// Write an empty element holding `value` in its `value` attribute.
+    fn value(&mut self, name: &str, value: impl Display) {
+        self.empty(name, &[("value", value.to_string())]);
+    }
+
+    fn text(&mut self, text: &str) {
+        self.indent();
+        self.xml.push_str(&escape(text));
+        self.xml.push('\n');
+    }
+
+    fn comment(&mut self, comment: &str) {
+        self.indent();
+        self.xml.push_str("<!-- ");
+        self.xml.push_str(&comment.replace("--", "- -"));
+        self.xml.push_str(" -->\n");
+    }
+
+    fn blank_line(&mut self) {
+        self.xml.push('\n');
+    }
+}
+
+/// An XML element read by `XmlParser`.
+///
+/// The text of an element is the concatenation of all text directly within it.
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<Element>,
+    text: String,
+}
+
+impl Element {
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> + 'a {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    fn attribute(&self, name: &'static str) -> Result<&str, ParseError> {
+        self.attributes
+            .iter()
+            .find(|(attribute, _)| attribute == name)
+            .map(|(_, value)| value.as_str())
+            .ok_or_else(|| ParseError::MissingValue.with_field(name))
+    }
+
+    fn number_attribute<T: TryFrom<i64>>(&self, name: &'static str) -> Result<T, ParseError> {
+        parse_number(self.attribute(name)?)
+            .and_then(|number| T::try_from(number).ok())
+            .ok_or_else(|| ParseError::BadValue.with_field(name))
+    }
+
+    /// The `value` attribute of the child element `name`, as used by TTX for table fields.
+    fn value(&self, name: &'static str) -> Result<&str, ParseError> {
+        self.child(name)
+            .ok_or_else(|| ParseError::MissingValue.with_field(name))?
+            .attribute("value")
+            .map_err(|err| err.with_field(name))
+    }
+
+    fn number<T: TryFrom<i64>>(&self, name: &'static str) -> Result<T, ParseError> {
+        parse_number(self.value(name)?)
+            .and_then(|number| T::try_from(number).ok())
+            .ok_or_else(|| ParseError::BadValue.with_field(name))
+    }
+
+    fn binary(&self, name: &'static str) -> Result<u16, ParseError> {
+        parse_binary(self.value(name)?).ok_or_else(|| ParseError::BadValue.with_field(name))
+    }
+
+    fn timestamp(&self, name: &'static str) -> Result<i64, ParseError> {
+        parse_timestamp(self.value(name)?).ok_or_else(|| ParseError::BadValue.with_field(name))
+    }
+}
+
+/// A parser for the subset of XML used by TTX: elements, attributes, text, comments, and CDATA
+/// sections. Errors record the byte offset at which parsing failed.
+struct XmlParser<'a> {
+    xml: &'a str,
+    pos: usize,
+}
+
+impl<'a> XmlParser<'a> {
+    fn parse(xml: &'a str) -> Result<Element, ParseError> {
+        let mut parser = XmlParser { xml, pos: 0 };
+        parser.skip_prolog()?;
+        let root = parser.element(0)?;
+        parser.skip_prolog()?;
+        if parser.pos < xml.len() {
+            return Err(parser.error());
+        }
+        Ok(root)
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.xml[self.pos..]
+    }
+
+    fn error(&self) -> ParseError {
+        ParseError::BadValue.with_offset(self.pos)
+    }
+
+    fn eat(&mut self, s: &str) -> bool {
+        if self.rest().starts_with(s) {
+            self.pos += s.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, s: &str) -> Result<(), ParseError> {
+        if self.eat(s) {
+            Ok(())
+        } else {
+            Err(self.error())
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    /// Consume everything up to and including `end`, returning the text before it.
+    fn take_until(&mut self, end: &str) -> Result<&'a str, ParseError> {
+        let rest = self.rest();
+        match rest.find(end) {
+            Some(index) => {
+                self.pos += index + end.len();
+                Ok(&rest[..index])
+            }
+            None => Err(ParseError::BadEof.with_offset(self.xml.len())),
+        }
+    }
+
+    /// Skip whitespace, comments, the XML declaration, and document type declaration.
+    fn skip_prolog(&mut self) -> Result<(), ParseError> {
+        loop {
+            self.skip_whitespace();
+            if self.eat("<!--") {
+                self.take_until("-->")?;
+            } else if self.eat("<?") {
+                self.take_until("?>")?;
+            } else if self.eat("<!DOCTYPE") {
+                self.take_until(">")?;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn name(&mut self) -> Result<String, ParseError> {
+        let rest = self.rest();
+        let len = rest
+            .find(|ch: char| !(ch.is_alphanumeric() || "_-.:".contains(ch)))
+            .unwrap_or(rest.len());
+        if len == 0 {
+            return Err(self.error());
+        }
+        self.pos += len;
+        Ok(rest[..len].to_string())
+    }
+
+    fn unescape(&self, text: &str, start: usize) -> Result<String, ParseError> {
+        unescape(text).ok_or_else(|| ParseError::BadValue.with_offset(start))
+    }
+
+    fn element(&mut self, depth: usize) -> Result<Element, ParseError> {
+        if depth > MAX_DEPTH {
+            return Err(ParseError::LimitExceeded.with_offset(self.pos));
+        }
+        self.expect("<")?;
+        let mut element = Element {
+            name: self.name()?,
+            attributes: Vec::new(),
+            children: Vec::new(),
+            text: String::new(),
+        };
+
+        loop {
+            self.skip_whitespace();
+            if self.eat("/>") {
+                return Ok(element);
+            } else if self.eat(">") {
+                break;
+            }
+            let attribute = self.name()?;
+            self.skip_whitespace();
+            self.expect("=")?;
+            self.skip_whitespace();
+            let quote = if self.eat("\"") {
+                "\""
+            } else if self.eat("'") {
+                "'"
+            } else {
+                return Err(self.error());
+            };
+            let start = self.pos;
+            let value = self.take_until(quote)?;
+            let value = self.unescape(value, start)?;
+            element.attributes.push((attribute, value));
+        }
+
+        loop {
+            if self.eat("</") {
+                if self.name()? != element.name {
+                    return Err(self.error());
+                }
+                self.skip_whitespace();
+                self.expect(">")?;
+                return Ok(element);
+            } else if self.eat("<!--") {
+                self.take_until("-->")?;
+            } else if self.eat("<![CDATA[") {
+                let text = self.take_until("]]>")?;
+                element.text.push_str(text);
+            } else if self.rest().starts_with('<') {
+                element.children.push(self.element(depth + 1)?);
+            } else if self.rest().is_empty() {
+                return Err(ParseError::BadEof.with_offset(self.pos));
+            } else {
+                let start = self.pos;
+                let len = self.rest().find('<').unwrap_or_else(|| self.rest().len());
+                let text = &self.rest()[..len];
+                self.pos += len;
+                let text = self.unescape(text, start)?;
+                element.text.push_str(&text);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font::OwnedFont;
+    use crate::tables::OpenTypeFont;
+    use crate::tests::read_fixture;
+
+    fn assert_tables_match(compiled: &BTreeMap<u32, Vec<u8>>, original: &[u8]) {
+        let font_file = ReadScope::new(original).read::<OpenTypeFont<'_>>().unwrap();
+        let provider = font_file.table_provider(0).unwrap();
+        for &table_tag in &[tag::HEAD, tag::HHEA, tag::MAXP] {
+            assert_eq!(
+                compiled[&table_tag],
+                &*provider.read_table_data(table_tag).unwrap()
+            );
+        }
+
+        let names = |data: &[u8]| {
+            let name = ReadScope::new(data).read::<NameTable<'_>>().unwrap();
+            name.name_records
+                .iter()
+                .map(|record| {
+                    let data = name
+                        .string_storage
+                        .offset_length(usize::from(record.offset), usize::from(record.length))
+                        .unwrap();
+                    (
+                        record.platform_id,
+                        record.name_id,
+                        decode_name(record.platform_id, record.encoding_id, data.data()),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            names(&compiled[&tag::NAME]),
+            names(&provider.read_table_data(tag::NAME).unwrap())
+        );
+
+        let mappings = |data: &[u8]| {
+            let cmap = ReadScope::new(data).read::<Cmap<'_>>().unwrap();
+            cmap.encoding_records()
+                .map(|record| {
+                    let sub_table = cmap
+                        .scope
+                        .offset(record.offset as usize)
+                        .read::<CmapSubtable<'_>>()
+                        .unwrap();
+                    let mappings = sub_table.to_owned().unwrap().char_mappings().unwrap();
+                    (record.platform_id, record.encoding_id, mappings)
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            mappings(&compiled[&tag::CMAP]),
+            mappings(&provider.read_table_data(tag::CMAP).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_compile() {
+        let ttx = String::from_utf8(read_fixture("tests/fonts/opentype/test-font.ttx")).unwrap();
+        let compiled = compile(&ttx).unwrap();
+        let tags = compiled.keys().copied().collect::<Vec<_>>();
+        assert_eq!(
+            tags,
+            vec![tag::CMAP, tag::HEAD, tag::HHEA, tag::MAXP, tag::NAME]
+        );
+
+        let head = ReadScope::new(&compiled[&tag::HEAD])
+            .read::<HeadTable>()
+            .unwrap();
+        assert_eq!((head.major_version, head.minor_version), (1, 0));
+        assert_eq!(head.check_sum_adjustment, 0x9a9ddc76);
+        assert_eq!(head.flags, 0b1011);
+        assert_eq!(head.units_per_em, 2048);
+        assert_eq!(format_timestamp(head.modified), "Sun Dec  5 18:07:16 2010");
+        assert_eq!(head.y_min, -20);
+        let hhea = ReadScope::new(&compiled[&tag::HHEA])
+            .read::<HheaTable>()
+            .unwrap();
+        assert_eq!(hhea.advance_width_max, 4719);
+        assert_eq!(hhea.num_h_metrics, 4);
+        let maxp = ReadScope::new(&compiled[&tag::MAXP])
+            .read::<MaxpTable>()
+            .unwrap();
+        assert_eq!(maxp.num_glyphs, 4);
+        assert_eq!(maxp.version1_sub_table.unwrap().max_points, 205);
+
+        let cmap = ReadScope::new(&compiled[&tag::CMAP])
+            .read::<Cmap<'_>>()
+            .unwrap();
+        let formats = cmap
+            .encoding_records()
+            .map(|record| {
+                let sub_table = cmap
+                    .scope
+                    .offset(record.offset as usize)
+                    .read::<CmapSubtable<'_>>()
+                    .unwrap()
+                    .to_owned()
+                    .unwrap();
+                assert_eq!(sub_table.map_glyph(0x46).unwrap(), Some(2));
+                sub_table.format()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(formats, vec![4, 0, 4]);
+
+        let name = ReadScope::new(&compiled[&tag::NAME])
+            .read::<NameTable<'_>>()
+            .unwrap();
+        assert_eq!(name.name_records.len(), 14);
+    }
+
+    #[test]
+    fn test_dump_round_trip() {
+        let data = read_fixture("tests/fonts/opentype/test-font.ttf");
+        let font: OwnedFont = Font::from_data(data.clone(), 0).unwrap().unwrap();
+        let ttx = dump(&font).unwrap();
+        assert!(ttx.contains(r#"<created value="Thu Nov  4 19:38:34 2010"/>"#));
+        assert!(ttx.contains(r#"<GlyphID id="2" name="F"/>"#));
+        assert!(ttx.contains(r#"<map code="0x46" name="F"/>"#));
+        assert!(ttx.contains("      WOFF Test TTF-Regular\n"));
+
+        assert_tables_match(&compile(&ttx).unwrap(), &data);
+    }
+
+    #[test]
+    fn test_timestamp() {
+        assert_eq!(
+            format_timestamp(SECONDS_1904_TO_1970),
+            "Thu Jan  1 00:00:00 1970"
+        );
+        assert_eq!(format_timestamp(0), "Fri Jan  1 00:00:00 1904");
+        for &timestamp in &[0, SECONDS_1904_TO_1970, 3_371_654_836, -86_401] {
+            assert_eq!(
+                parse_timestamp(&format_timestamp(timestamp)),
+                Some(timestamp)
+            );
+        }
+        assert_eq!(parse_timestamp("Thu Foo  1 00:00:00 1970"), None);
+    }
+
+    #[test]
+    fn test_fixed() {
+        assert_eq!(format_fixed(0x10000), "1.0");
+        assert_eq!(format_fixed(0x18000), "1.5");
+        assert_eq!(format_fixed(-0x8000), "-0.5");
+        for &value in &[0x10000, 0x20042, -12345, 1] {
+            assert_eq!(parse_fixed(&format_fixed(value)), Some(value));
+        }
+        assert_eq!(parse_fixed("1"), Some(0x10000));
+        assert_eq!(parse_fixed("0x00010000"), Some(0x10000));
+    }
+
+    #[test]
+    fn test_xml() {
+        let root = XmlParser::parse(
+            "<?xml version=\"1.0\"?>\n<!-- comment -->\n<a x='1 &amp; 2'><b/>text &#x2713;<!-- c --></a>\n",
+        )
+        .unwrap();
+        assert_eq!(root.name, "a");
+        assert_eq!(root.attribute("x"), Ok("1 & 2"));
+        assert_eq!(root.children.len(), 1);
+        assert_eq!(root.text, "text \u{2713}");
+
+        let err = XmlParser::parse("<a><b></a>").err().unwrap();
+        assert_eq!(err.kind(), crate::error::ParseErrorKind::BadValue);
+        assert_eq!(err.offset(), Some(9));
+        assert!(XmlParser::parse("<a>&bogus;</a>").is_err());
+        assert!(XmlParser::parse("<a>").is_err());
+        assert!(XmlParser::parse(&"<a>".repeat(MAX_DEPTH + 2)).is_err());
+    }
+
+    #[test]
+    fn test_compile_errors() {
+        let err = compile("<ttFont><hhea><ascent value=\"x\"/></hhea></ttFont>").unwrap_err();
+        match err {
+            ReadWriteError::Read(err) => {
+                assert_eq!(err.table_tag(), Some(tag::HHEA));
+                assert_eq!(err.field(), Some("ascent"));
+            }
+            _ => panic!("expected a read error"),
+        }
+    }
+}
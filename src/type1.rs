@@ -0,0 +1,1973 @@
+//! Reading of PostScript Type 1 fonts.
+//!
+//! Type 1 fonts in PFA (hexadecimal) or PFB (binary) form are parsed and can be converted into a
+//! CFF flavoured OpenType font, allowing them to be used with the rest of Allsorts.
+//!
+//! Type 1 charstrings are converted to Type 2 charstrings by interpreting them. As a result
+//! subroutines are inlined, flex is converted to regular curves, and hint replacement is dropped:
+//! the union of the stem hints of each glyph is used with overlapping stems removed.
+//!
+//! Refer to the Adobe Type 1 Font Format and Technical Note #5177 for the Type 2 charstring
+//! format.
+
+use std::convert::TryFrom;
+use std::str;
+
+use crate::binary::read::{ReadArrayCow, ReadBinary, ReadCtxt, ReadScope};
+use crate::binary::write::{WriteBinary, WriteBuffer, WriteContext};
+use crate::binary::{I16Be, U16Be, U8};
+use crate::cff::{Operand, Operator, STANDARD_STRINGS};
+use crate::error::{ParseError, ReadWriteError, WriteError};
+use crate::glyph_info::glyph_name_to_unicode;
+use crate::post::{self, PostTable};
+use crate::sfnt::FontBuilder;
+use crate::tables::cmap;
+use crate::tables::cmap::owned::create_unicode_cmap_table;
+use crate::tables::os2::{FsSelection, Os2, Version0, Version1, Version2to4};
+use crate::tables::{
+    HeadTable, HheaTable, HmtxTable, IndexToLocFormat, LongHorMetric, MacStyle, MaxpTable,
+    NameTable, NameTableBuilder, Version16Dot16,
+};
+use crate::tag;
+
+/// Key used to encrypt the private part of the font.
+const EEXEC_KEY: u16 = 55665;
+/// Key used to encrypt charstrings.
+const CHARSTRING_KEY: u16 = 4330;
+const DEFAULT_LEN_IV: usize = 4;
+
+const PFB_MARKER: u8 = 0x80;
+const PFB_ASCII: u8 = 1;
+const PFB_BINARY: u8 = 2;
+const PFB_EOF: u8 = 3;
+
+/// Maximum depth of nested subroutine calls in a charstring.
+const MAX_SUBR_DEPTH: usize = 10;
+/// Maximum number of arguments on the Type 2 charstring argument stack.
+const TYPE2_MAX_ARGS: usize = 48;
+/// Maximum number of stems of each direction written to a Type 2 charstring.
+const MAX_STEMS: usize = 23;
+
+/// Number of standard strings in a CFF font, custom strings start at this SID.
+const NUM_STANDARD_STRINGS: usize = 391;
+
+/// A PostScript Type 1 font.
+#[derive(Debug, Clone)]
+pub struct Type1Font {
+    pub font_name: String,
+    pub font_info: FontInfo,
+    pub font_matrix: [f64; 6],
+    pub font_bbox: [f64; 4],
+    pub encoding: Type1Encoding,
+    pub private_dict: PrivateValues,
+    /// Decrypted subroutines.
+    pub subrs: Vec<Vec<u8>>,
+    /// Decrypted charstrings in the order they appear in the font.
+    pub glyphs: Vec<Type1Glyph>,
+}
+
+/// The values of the `FontInfo` dictionary.
+#[derive(Debug, Clone, Default)]
+pub struct FontInfo {
+    pub version: Option<String>,
+    pub notice: Option<String>,
+    pub full_name: Option<String>,
+    pub family_name: Option<String>,
+    pub weight: Option<String>,
+    pub italic_angle: f64,
+    pub is_fixed_pitch: bool,
+    pub underline_position: f64,
+    pub underline_thickness: f64,
+}
+
+/// The hinting values of the `Private` dictionary.
+#[derive(Debug, Clone, Default)]
+pub struct PrivateValues {
+    pub blue_values: Vec<f64>,
+    pub other_blues: Vec<f64>,
+    pub family_blues: Vec<f64>,
+    pub family_other_blues: Vec<f64>,
+    pub blue_scale: Option<f64>,
+    pub blue_shift: Option<f64>,
+    pub blue_fuzz: Option<f64>,
+    pub std_hw: Option<f64>,
+    pub std_vw: Option<f64>,
+    pub stem_snap_h: Vec<f64>,
+    pub stem_snap_v: Vec<f64>,
+    pub force_bold: bool,
+    pub language_group: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type1Encoding {
+    Standard,
+    /// Character code and glyph name pairs.
+    Custom(Vec<(u8, String)>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Type1Glyph {
+    pub name: String,
+    pub charstring: Vec<u8>,
+}
+
+/// The outline and metrics of a glyph, obtained by interpreting a Type 1 charstring.
+#[derive(Debug, Default)]
+struct Outline {
+    width: f64,
+    lsb: f64,
+    hstems: Vec<(f64, f64)>,
+    vstems: Vec<(f64, f64)>,
+    path: Vec<PathOp>,
+    /// The arguments to a Type 2 `endchar` representing an accented character: adx, ady, bchar,
+    /// and achar.
+    seac: Option<[f64; 4]>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum PathOp {
+    Move(f64, f64),
+    Line(f64, f64),
+    Curve([f64; 6]),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token<'a> {
+    Number(f64),
+    /// A literal name such as `/FontName`, without the slash.
+    Literal(&'a [u8]),
+    /// An executable name, including the brackets `[`, `]`, `{`, and `}`.
+    Name(&'a [u8]),
+    String(Vec<u8>),
+    /// Binary data read by `RD` or `-|`.
+    Binary(&'a [u8]),
+}
+
+struct Tokenizer<'a> {
+    data: &'a [u8],
+    pos: usize,
+    last_integer: Option<usize>,
+}
+
+impl<'a> ReadBinary<'a> for Type1Font {
+    type HostType = Self;
+
+    fn read(ctxt: &mut ReadCtxt<'a>) -> Result<Self, ParseError> {
+        let length = ctxt.scope().data().len();
+        let data = ctxt.read_slice(length)?;
+        let (cleartext, encrypted) = split_segments(data)?;
+        let private = decrypt(&encrypted, EEXEC_KEY, 4);
+
+        let mut font = Type1Font {
+            font_name: String::new(),
+            font_info: FontInfo::default(),
+            font_matrix: [0.001, 0.0, 0.0, 0.001, 0.0, 0.0],
+            font_bbox: [0.0; 4],
+            encoding: Type1Encoding::Standard,
+            private_dict: PrivateValues::default(),
+            subrs: Vec::new(),
+            glyphs: Vec::new(),
+        };
+        font.parse_cleartext(&cleartext)?;
+        font.parse_private(&private)?;
+        if font.font_name.is_empty() || font.glyphs.is_empty() {
+            return Err(ParseError::MissingValue);
+        }
+
+        Ok(font)
+    }
+}
+
+impl Type1Font {
+    /// Convert the font into a CFF flavoured OpenType font.
+    pub fn to_opentype(&self) -> Result<Vec<u8>, ReadWriteError> {
+        // .notdef must be the first glyph. Glyphs with a code in a custom encoding follow so that
+        // the encoding can be written in format 0.
+        let notdef = Type1Glyph {
+            name: String::from(".notdef"),
+            charstring: vec![139, 139, 13, 14], // 0 0 hsbw endchar
+        };
+        let mut glyphs = vec![self
+            .glyphs
+            .iter()
+            .find(|glyph| glyph.name == ".notdef")
+            .unwrap_or(&notdef)];
+        let mut codes = Vec::new();
+        if let Type1Encoding::Custom(encoding) = &self.encoding {
+            let mut encoding = encoding.clone();
+            encoding.sort_by_key(|&(code, _)| code);
+            for (code, name) in encoding {
+                if glyphs.len() > usize::from(std::u8::MAX) {
+                    break;
+                }
+                if let Some(glyph) = self.glyphs.iter().find(|glyph| glyph.name == name) {
+                    if !glyphs.iter().any(|g| g.name == glyph.name) {
+                        glyphs.push(glyph);
+                        codes.push(code);
+                    }
+                }
+            }
+        }
+        for glyph in &self.glyphs {
+            if !glyphs.iter().any(|g| g.name == glyph.name) {
+                glyphs.push(glyph);
+            }
+        }
+        let num_glyphs = u16::try_from(glyphs.len()).map_err(ParseError::from)?;
+
+        let outlines = glyphs
+            .iter()
+            .map(|glyph| self.interpret(&glyph.charstring))
+            .collect::<Result<Vec<_>, _>>()?;
+        let units_per_em = self.units_per_em();
+        let cff = self.write_cff(&glyphs, &codes, &outlines)?;
+
+        // Map glyph names to Unicode, falling back to a symbol cmap built from the encoding
+        let mut mappings = glyphs
+            .iter()
+            .enumerate()
+            .filter_map(|(glyph_id, glyph)| {
+                glyph_name_to_unicode(&glyph.name).map(|ch| (ch, glyph_id as u16))
+            })
+            .collect::<Vec<_>>();
+        mappings.sort_by_key(|&(ch, _)| ch);
+        mappings.dedup_by_key(|&mut (ch, _)| ch);
+        let cmap = if mappings.is_empty() {
+            let symbol_mappings = codes
+                .iter()
+                .enumerate()
+                .map(|(index, &code)| (0xF000 + u32::from(code), index as u16 + 1))
+                .collect::<Vec<_>>();
+            let mut cmap = create_unicode_cmap_table(&symbol_mappings);
+            cmap.encoding_records[0].encoding_id = 0; // Symbol
+            mappings = symbol_mappings;
+            cmap
+        } else {
+            create_unicode_cmap_table(&mappings)
+        };
+
+        let x_min = round_i16(self.font_bbox[0]);
+        let y_min = round_i16(self.font_bbox[1]);
+        let x_max = round_i16(self.font_bbox[2]);
+        let y_max = round_i16(self.font_bbox[3]);
+        let mut mac_style = MacStyle::empty();
+        mac_style.set(MacStyle::BOLD, self.weight_class() >= 700);
+        mac_style.set(MacStyle::ITALIC, self.font_info.italic_angle != 0.0);
+
+        let head = HeadTable {
+            major_version: 1,
+            minor_version: 0,
+            font_revision: self.font_revision(),
+            check_sum_adjustment: 0,
+            magic_number: 0x5F0F3CF5,
+            flags: 0b11, // baseline and left sidebearing point at origin
+            units_per_em,
+            created: 0,
+            modified: 0,
+            x_min,
+            y_min,
+            x_max,
+            y_max,
+            mac_style: mac_style.bits(),
+            lowest_rec_ppem: 8,
+            font_direction_hint: 2,
+            index_to_loc_format: IndexToLocFormat::Short,
+            glyph_data_format: 0,
+        };
+
+        let h_metrics = outlines
+            .iter()
+            .map(|outline| LongHorMetric {
+                advance_width: outline.width.round().max(0.0).min(f64::from(std::u16::MAX)) as u16,
+                lsb: round_i16(outline.lsb),
+            })
+            .collect::<Vec<_>>();
+        let advance_width_max = h_metrics
+            .iter()
+            .map(|metric| metric.advance_width)
+            .max()
+            .unwrap_or(0);
+        let min_left_side_bearing = h_metrics.iter().map(|metric| metric.lsb).min().unwrap_or(0);
+        let hhea = HheaTable {
+            ascender: y_max,
+            descender: y_min,
+            line_gap: 0,
+            advance_width_max,
+            min_left_side_bearing,
+            min_right_side_bearing: 0,
+            x_max_extent: x_max,
+            caret_slope_rise: 1,
+            caret_slope_run: 0,
+            caret_offset: 0,
+            num_h_metrics: num_glyphs,
+        };
+        let widths = h_metrics
+            .iter()
+            .map(|metric| u32::from(metric.advance_width))
+            .filter(|&width| width != 0)
+            .collect::<Vec<_>>();
+        let x_avg_char_width = if widths.is_empty() {
+            0
+        } else {
+            (widths.iter().sum::<u32>() / widths.len() as u32) as i16
+        };
+        let hmtx = HmtxTable {
+            h_metrics: ReadArrayCow::Owned(h_metrics),
+            left_side_bearings: ReadArrayCow::Owned(Vec::new()),
+        };
+        let maxp = MaxpTable {
+            num_glyphs,
+            version1_sub_table: None,
+        };
+
+        let upem = f64::from(units_per_em);
+        let scale = |factor: f64| (upem * factor).round() as i16;
+        let first_char = mappings.first().map_or(0, |&(ch, _)| ch.min(0xFFFF) as u16);
+        let last_char = mappings.last().map_or(0, |&(ch, _)| ch.min(0xFFFF) as u16);
+        let os2 = Os2 {
+            version: 4,
+            x_avg_char_width,
+            us_weight_class: self.weight_class(),
+            us_width_class: 5,
+            fs_type: 0,
+            y_subscript_x_size: scale(0.65),
+            y_subscript_y_size: scale(0.6),
+            y_subscript_x_offset: 0,
+            y_subscript_y_offset: scale(0.075),
+            y_superscript_x_size: scale(0.65),
+            y_superscript_y_size: scale(0.6),
+            y_superscript_x_offset: 0,
+            y_superscript_y_offset: scale(0.35),
+            y_strikeout_size: round_i16(self.font_info.underline_thickness),
+            y_strikeout_position: scale(0.25),
+            s_family_class: 0,
+            panose: [0; 10],
+            ul_unicode_range1: if first_char < 0x80 { 1 } else { 0 },
+            ul_unicode_range2: 0,
+            ul_unicode_range3: 0,
+            ul_unicode_range4: 0,
+            ach_vend_id: tag!(b"NONE"),
+            fs_selection: FsSelection::from_mac_style(mac_style).bits(),
+            us_first_char_index: first_char,
+            us_last_char_index: last_char,
+            version0: Some(Version0 {
+                s_typo_ascender: y_max,
+                s_typo_descender: y_min,
+                s_typo_line_gap: 0,
+                us_win_ascent: y_max.max(0) as u16,
+                us_win_descent: y_min.min(0).wrapping_neg() as u16,
+            }),
+            version1: Some(Version1 {
+                ul_code_page_range1: 1, // Latin 1
+                ul_code_page_range2: 0,
+            }),
+            version2to4: Some(Version2to4 {
+                sx_height: 0,
+                s_cap_height: 0,
+                us_default_char: 0,
+                us_break_char: 0x20,
+                us_max_context: 0,
+            }),
+            version5: None,
+        };
+
+        let post = PostTable {
+            header: post::Header {
+                version: Version16Dot16::new(3, 0),
+                italic_angle: (self.font_info.italic_angle * 65536.0).round() as i32,
+                underline_position: round_i16(self.font_info.underline_position),
+                underline_thickness: round_i16(self.font_info.underline_thickness),
+                is_fixed_pitch: u32::from(self.font_info.is_fixed_pitch),
+                min_mem_type_42: 0,
+                max_mem_type_42: 0,
+                min_mem_type_1: 0,
+                max_mem_type_1: 0,
+            },
+            opt_sub_table: None,
+        };
+
+        let name = self.name_table()?;
+
+        let mut builder = FontBuilder::new(tag::OTTO);
+        builder.add_table::<_, ReadScope<'_>>(tag::CFF, ReadScope::new(&cff), ())?;
+        builder.add_table::<_, cmap::owned::Cmap>(tag::CMAP, cmap, ())?;
+        builder.add_table::<_, HheaTable>(tag::HHEA, &hhea, ())?;
+        builder.add_table::<_, HmtxTable<'_>>(tag::HMTX, &hmtx, ())?;
+        builder.add_table::<_, MaxpTable>(tag::MAXP, &maxp, ())?;
+        builder.add_table::<_, NameTableBuilder>(tag::NAME, &name, ())?;
+        builder.add_table::<_, Os2>(tag::OS_2, &os2, ())?;
+        builder.add_table::<_, PostTable<'_>>(tag::POST, &post, ())?;
+        let builder = builder.add_head_table(&head)?;
+        builder.data()
+    }
+
+    fn parse_cleartext(&mut self, data: &[u8]) -> Result<(), ParseError> {
+        let tokens = Tokenizer::new(data).collect::<Vec<_>>();
+        for (index, token) in tokens.iter().enumerate() {
+            let key = match token {
+                Token::Literal(key) => *key,
+                _ => continue,
+            };
+            let rest = &tokens[index + 1..];
+            match key {
+                b"FontName" => {
+                    if let Some(Token::Literal(name)) = rest.first() {
+                        self.font_name = String::from_utf8_lossy(name).into_owned();
+                    }
+                }
+                b"version" => self.font_info.version = string_value(rest),
+                b"Notice" => self.font_info.notice = string_value(rest),
+                b"FullName" => self.font_info.full_name = string_value(rest),
+                b"FamilyName" => self.font_info.family_name = string_value(rest),
+                b"Weight" => self.font_info.weight = string_value(rest),
+                b"ItalicAngle" => self.font_info.italic_angle = number_value(rest),
+                b"UnderlinePosition" => self.font_info.underline_position = number_value(rest),
+                b"UnderlineThickness" => self.font_info.underline_thickness = number_value(rest),
+                b"isFixedPitch" => self.font_info.is_fixed_pitch = bool_value(rest),
+                b"FontMatrix" => {
+                    let values = array_value(rest);
+                    if values.len() == 6 {
+                        self.font_matrix.copy_from_slice(&values);
+                    }
+                }
+                b"FontBBox" => {
+                    let values = array_value(rest);
+                    if values.len() == 4 {
+                        self.font_bbox.copy_from_slice(&values);
+                    }
+                }
+                b"Encoding" => self.encoding = parse_encoding(rest),
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    fn parse_private(&mut self, data: &[u8]) -> Result<(), ParseError> {
+        let tokens = Tokenizer::new(data).collect::<Vec<_>>();
+        let mut len_iv = Some(DEFAULT_LEN_IV);
+        let mut subrs = Vec::new();
+        let mut glyphs = Vec::new();
+        let mut in_char_strings = false;
+        let private = &mut self.private_dict;
+        for (index, token) in tokens.iter().enumerate() {
+            let rest = &tokens[index + 1..];
+            match token {
+                Token::Literal(b"CharStrings") => in_char_strings = true,
+                Token::Literal(name) if in_char_strings => {
+                    if let (Some(Token::Number(_)), Some(Token::Binary(data))) =
+                        (rest.first(), rest.get(1))
+                    {
+                        glyphs.push((String::from_utf8_lossy(name).into_owned(), *data));
+                    }
+                }
+                Token::Name(b"dup") if !in_char_strings => {
+                    if let (
+                        Some(Token::Number(subr)),
+                        Some(Token::Number(_)),
+                        Some(Token::Binary(data)),
+                    ) = (rest.first(), rest.get(1), rest.get(2))
+                    {
+                        let subr = *subr as usize;
+                        if subrs.len() <= subr {
+                            subrs.resize(subr + 1, &[][..]);
+                        }
+                        subrs[subr] = *data;
+                    }
+                }
+                Token::Literal(key) => match *key {
+                    b"lenIV" => {
+                        let value = number_value(rest);
+                        len_iv = if value < 0.0 {
+                            None
+                        } else {
+                            Some(value as usize)
+                        };
+                    }
+                    b"BlueValues" => private.blue_values = array_value(rest),
+                    b"OtherBlues" => private.other_blues = array_value(rest),
+                    b"FamilyBlues" => private.family_blues = array_value(rest),
+                    b"FamilyOtherBlues" => private.family_other_blues = array_value(rest),
+                    b"BlueScale" => private.blue_scale = Some(number_value(rest)),
+                    b"BlueShift" => private.blue_shift = Some(number_value(rest)),
+                    b"BlueFuzz" => private.blue_fuzz = Some(number_value(rest)),
+                    b"StdHW" => private.std_hw = array_value(rest).first().cloned(),
+                    b"StdVW" => private.std_vw = array_value(rest).first().cloned(),
+                    b"StemSnapH" => private.stem_snap_h = array_value(rest),
+                    b"StemSnapV" => private.stem_snap_v = array_value(rest),
+                    b"ForceBold" => private.force_bold = bool_value(rest),
+                    b"LanguageGroup" => private.language_group = Some(number_value(rest)),
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+
+        let decrypt_charstring = |data: &[u8]| match len_iv {
+            Some(len_iv) => decrypt(data, CHARSTRING_KEY, len_iv),
+            None => data.to_vec(),
+        };
+        self.subrs = subrs.into_iter().map(&decrypt_charstring).collect();
+        self.glyphs = glyphs
+            .into_iter()
+            .map(|(name, data)| Type1Glyph {
+                name,
+                charstring: decrypt_charstring(data),
+            })
+            .collect();
+
+        Ok(())
+    }
+
+    /// Interpret a Type 1 charstring.
+    fn interpret(&self, charstring: &[u8]) -> Result<Outline, ParseError> {
+        let mut interpreter = Interpreter {
+            subrs: &self.subrs,
+            stack: Vec::new(),
+            ps_stack: Vec::new(),
+            x: 0.0,
+            y: 0.0,
+            sbx: 0.0,
+            sby: 0.0,
+            flex: None,
+            outline: Outline::default(),
+            done: false,
+        };
+        interpreter.run(charstring, 0)?;
+        Ok(interpreter.outline)
+    }
+
+    fn units_per_em(&self) -> u16 {
+        let [a, b, c, d, _, _] = self.font_matrix;
+        if a > 0.0 && b == 0.0 && c == 0.0 && (a - d).abs() < 1e-9 {
+            let units_per_em = (1.0 / a).round();
+            if (16.0..=16384.0).contains(&units_per_em) {
+                return units_per_em as u16;
+            }
+        }
+        1000
+    }
+
+    /// Returns true if the FontMatrix needs to be written to the CFF font.
+    fn has_non_default_font_matrix(&self) -> bool {
+        let units_per_em = f64::from(self.units_per_em());
+        self.font_matrix != [1.0 / units_per_em, 0.0, 0.0, 1.0 / units_per_em, 0.0, 0.0]
+            && self.font_matrix != [0.001, 0.0, 0.0, 0.001, 0.0, 0.0]
+    }
+
+    fn weight_class(&self) -> u16 {
+        let weight = self
+            .font_info
+            .weight
+            .as_ref()
+            .map(|weight| weight.to_ascii_lowercase().replace(' ', ""))
+            .unwrap_or_default();
+        match weight.as_str() {
+            "thin" | "hairline" => 100,
+            "extralight" | "ultralight" => 200,
+            "light" => 300,
+            "medium" => 500,
+            "semibold" | "demibold" | "demi" => 600,
+            "bold" => 700,
+            "extrabold" | "ultrabold" | "heavy" => 800,
+            "black" | "ultra" => 900,
+            _ => 400,
+        }
+    }
+
+    /// The font revision as a 16.16 fixed point number, parsed from the version string.
+    fn font_revision(&self) -> u32 {
+        self.font_info
+            .version
+            .as_ref()
+            .and_then(|version| version.trim().parse::<f64>().ok())
+            .map(|version| (version * 65536.0).round() as u32)
+            .unwrap_or(0x0001_0000)
+    }
+
+    fn name_table(&self) -> Result<NameTableBuilder, WriteError> {
+        let is_bold = self.weight_class() >= 700;
+        let is_italic = self.font_info.italic_angle != 0.0;
+        let subfamily = match (is_bold, is_italic) {
+            (false, false) => "Regular",
+            (true, false) => "Bold",
+            (false, true) => "Italic",
+            (true, true) => "Bold Italic",
+        };
+        let family = self
+            .font_info
+            .family_name
+            .clone()
+            .unwrap_or_else(|| self.font_name.clone());
+        let full_name = self
+            .font_info
+            .full_name
+            .clone()
+            .unwrap_or_else(|| self.font_name.clone());
+        let version = self
+            .font_info
+            .version
+            .as_ref()
+            .map(|version| format!("Version {}", version.trim()))
+            .unwrap_or_else(|| String::from("Version 1.0"));
+
+        let mut name = NameTableBuilder::new();
+        if let Some(notice) = &self.font_info.notice {
+            name.replace_name(NameTable::COPYRIGHT_NOTICE, notice)?;
+        }
+        name.family_name(&family)?
+            .subfamily_name(subfamily)?
+            .unique_identifier(&format!("{};{}", version, self.font_name))?
+            .replace_name(NameTable::FULL_FONT_NAME, &full_name)?
+            .replace_name(NameTable::VERSION_STRING, &version)?;
+        // Type 1 font names are normally valid PostScript names but not always
+        if name.postscript_name(&self.font_name).is_err() {
+            let postscript_name = self
+                .font_name
+                .bytes()
+                .filter(|b| (33u8..=126).contains(b) && !b"[](){}<>/%".contains(b))
+                .take(63)
+                .map(char::from)
+                .collect::<String>();
+            name.postscript_name(&postscript_name)?;
+        }
+
+        Ok(name)
+    }
+
+    /// Build the CFF table.
+    fn write_cff(
+        &self,
+        glyphs: &[&Type1Glyph],
+        codes: &[u8],
+        outlines: &[Outline],
+    ) -> Result<Vec<u8>, WriteError> {
+        let mut strings = Vec::new();
+        let mut sid = |string: &str| -> Result<u16, WriteError> {
+            let index = match STANDARD_STRINGS.iter().position(|s| *s == string) {
+                Some(index) => index,
+                None => {
+                    let index = match strings
+                        .iter()
+                        .position(|s: &Vec<u8>| s.as_slice() == string.as_bytes())
+                    {
+                        Some(index) => index,
+                        None => {
+                            strings.push(string.as_bytes().to_vec());
+                            strings.len() - 1
+                        }
+                    };
+                    NUM_STANDARD_STRINGS + index
+                }
+            };
+            Ok(u16::try_from(index)?)
+        };
+
+        // Top DICT entries without offsets
+        let mut top_dict = Vec::new();
+        let info = &self.font_info;
+        let string_entries = [
+            (Operator::Version, &info.version),
+            (Operator::Notice, &info.notice),
+            (Operator::FullName, &info.full_name),
+            (Operator::FamilyName, &info.family_name),
+            (Operator::Weight, &info.weight),
+        ];
+        for (operator, value) in string_entries.iter() {
+            if let Some(value) = value {
+                top_dict.push((*operator, vec![f64::from(sid(value.as_str())?)]));
+            }
+        }
+        if info.is_fixed_pitch {
+            top_dict.push((Operator::IsFixedPitch, vec![1.0]));
+        }
+        if info.italic_angle != 0.0 {
+            top_dict.push((Operator::ItalicAngle, vec![info.italic_angle]));
+        }
+        top_dict.push((Operator::UnderlinePosition, vec![info.underline_position]));
+        top_dict.push((Operator::UnderlineThickness, vec![info.underline_thickness]));
+        if self.has_non_default_font_matrix() {
+            top_dict.push((Operator::FontMatrix, self.font_matrix.to_vec()));
+        }
+        top_dict.push((Operator::FontBBox, self.font_bbox.to_vec()));
+
+        let charset = glyphs
+            .iter()
+            .skip(1)
+            .map(|glyph| sid(glyph.name.as_str()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut w = WriteBuffer::new();
+        U8::write(&mut w, 0u8)?; // format 0
+        for sid in charset {
+            U16Be::write(&mut w, sid)?;
+        }
+        let charset = w.into_inner();
+
+        let encoding = match self.encoding {
+            Type1Encoding::Standard => None,
+            Type1Encoding::Custom(_) => {
+                let mut w = WriteBuffer::new();
+                U8::write(&mut w, 0u8)?; // format 0
+                U8::write(&mut w, u8::try_from(codes.len())?)?;
+                w.write_bytes(codes)?;
+                Some(w.into_inner())
+            }
+        };
+
+        let char_strings = outlines
+            .iter()
+            .map(to_type2_charstring)
+            .collect::<Result<Vec<_>, _>>()?;
+        let private_dict = self.write_private_dict()?;
+
+        let name_index = write_index(&[self.font_name.as_bytes().to_vec()])?;
+        let string_index = write_index(&strings)?;
+        let global_subr_index = write_index(&[])?;
+        let char_strings_index = write_index(&char_strings)?;
+
+        // Offsets are written in a fixed size form so the size of the Top DICT does not depend
+        // on them.
+        let top_dict_index_len = write_index(&[write_top_dict(
+            &top_dict,
+            0,
+            0,
+            encoding.as_ref().map(|_| 0),
+            (0, 0),
+        )?])?
+        .len();
+        let char_strings_offset = 4
+            + name_index.len()
+            + top_dict_index_len
+            + string_index.len()
+            + global_subr_index.len();
+        let charset_offset = char_strings_offset + char_strings_index.len();
+        let encoding_offset = charset_offset + charset.len();
+        let private_offset =
+            encoding_offset + encoding.as_ref().map_or(0, |encoding| encoding.len());
+        let top_dict = write_top_dict(
+            &top_dict,
+            char_strings_offset,
+            charset_offset,
+            encoding.as_ref().map(|_| encoding_offset),
+            (private_dict.len(), private_offset),
+        )?;
+        let top_dict_index = write_index(&[top_dict])?;
+
+        let mut w = WriteBuffer::new();
+        w.write_bytes(&[1, 0, 4, 4])?; // major, minor, hdrSize, offSize
+        w.write_bytes(&name_index)?;
+        w.write_bytes(&top_dict_index)?;
+        w.write_bytes(&string_index)?;
+        w.write_bytes(&global_subr_index)?;
+        w.write_bytes(&char_strings_index)?;
+        w.write_bytes(&charset)?;
+        if let Some(encoding) = &encoding {
+            w.write_bytes(encoding)?;
+        }
+        w.write_bytes(&private_dict)?;
+
+        Ok(w.into_inner())
+    }
+
+    fn write_private_dict(&self) -> Result<Vec<u8>, WriteError> {
+        let private = &self.private_dict;
+        let mut w = WriteBuffer::new();
+        let deltas = [
+            (Operator::BlueValues, &private.blue_values),
+            (Operator::OtherBlues, &private.other_blues),
+            (Operator::FamilyBlues, &private.family_blues),
+            (Operator::FamilyOtherBlues, &private.family_other_blues),
+        ];
+        for (operator, values) in deltas.iter() {
+            if !values.is_empty() {
+                write_dict_entry(&mut w, *operator, &delta_encode(values))?;
+            }
+        }
+        let numbers = [
+            (Operator::StdHW, private.std_hw),
+            (Operator::StdVW, private.std_vw),
+            (Operator::BlueScale, private.blue_scale),
+            (Operator::BlueShift, private.blue_shift),
+            (Operator::BlueFuzz, private.blue_fuzz),
+            (Operator::LanguageGroup, private.language_group),
+        ];
+        for (operator, value) in numbers.iter() {
+            if let Some(value) = value {
+                write_dict_entry(&mut w, *operator, &[*value])?;
+            }
+        }
+        let stem_snaps = [
+            (Operator::StemSnapH, &private.stem_snap_h),
+            (Operator::StemSnapV, &private.stem_snap_v),
+        ];
+        for (operator, values) in stem_snaps.iter() {
+            if !values.is_empty() {
+                write_dict_entry(&mut w, *operator, &delta_encode(values))?;
+            }
+        }
+        if private.force_bold {
+            write_dict_entry(&mut w, Operator::ForceBold, &[1.0])?;
+        }
+
+        Ok(w.into_inner())
+    }
+}
+
+struct Interpreter<'a> {
+    subrs: &'a [Vec<u8>],
+    stack: Vec<f64>,
+    /// The PostScript operand stack, used to pass values back from OtherSubrs.
+    ps_stack: Vec<f64>,
+    x: f64,
+    y: f64,
+    sbx: f64,
+    sby: f64,
+    /// Points collected while in a flex sequence.
+    flex: Option<Vec<(f64, f64)>>,
+    outline: Outline,
+    done: bool,
+}
+
+impl<'a> Interpreter<'a> {
+    fn run(&mut self, charstring: &[u8], depth: usize) -> Result<(), ParseError> {
+        if depth > MAX_SUBR_DEPTH {
+            return Err(ParseError::LimitExceeded);
+        }
+
+        let mut i = 0;
+        while i < charstring.len() && !self.done {
+            let v = charstring[i];
+            i += 1;
+            match v {
+                32..=246 => self.stack.push(f64::from(i32::from(v) - 139)),
+                247..=250 => {
+                    let w = *charstring.get(i).ok_or(ParseError::BadEof)?;
+                    i += 1;
+                    self.stack
+                        .push(f64::from((i32::from(v) - 247) * 256 + i32::from(w) + 108));
+                }
+                251..=254 => {
+                    let w = *charstring.get(i).ok_or(ParseError::BadEof)?;
+                    i += 1;
+                    self.stack
+                        .push(f64::from(-(i32::from(v) - 251) * 256 - i32::from(w) - 108));
+                }
+                255 => {
+                    let bytes = charstring.get(i..i + 4).ok_or(ParseError::BadEof)?;
+                    i += 4;
+                    let value = i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                    self.stack.push(f64::from(value));
+                }
+                12 => {
+                    let op = *charstring.get(i).ok_or(ParseError::BadEof)?;
+                    i += 1;
+                    self.escape(op)?;
+                }
+                // return
+                11 => return Ok(()),
+                _ => self.operator(v, depth)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn operator(&mut self, op: u8, depth: usize) -> Result<(), ParseError> {
+        match op {
+            // hstem
+            1 => {
+                let args = self.args(2)?;
+                let (y, dy) = (args[0], args[1]);
+                self.outline.hstems.push((self.sby + y, dy));
+            }
+            // vstem
+            3 => {
+                let args = self.args(2)?;
+                let (x, dx) = (args[0], args[1]);
+                self.outline.vstems.push((self.sbx + x, dx));
+            }
+            // vmoveto
+            4 => {
+                let dy = self.args(1)?[0];
+                self.move_to(0.0, dy);
+            }
+            // rlineto
+            5 => {
+                let args = self.args(2)?;
+                let (dx, dy) = (args[0], args[1]);
+                self.line_to(dx, dy);
+            }
+            // hlineto
+            6 => {
+                let dx = self.args(1)?[0];
+                self.line_to(dx, 0.0);
+            }
+            // vlineto
+            7 => {
+                let dy = self.args(1)?[0];
+                self.line_to(0.0, dy);
+            }
+            // rrcurveto
+            8 => {
+                let args = self.args(6)?;
+                let (dx1, dy1, dx2, dy2, dx3, dy3) =
+                    (args[0], args[1], args[2], args[3], args[4], args[5]);
+                self.curve_to(dx1, dy1, dx2, dy2, dx3, dy3);
+            }
+            // closepath, closing is implied in Type 2 charstrings
+            9 => self.stack.clear(),
+            // callsubr
+            10 => {
+                let subr = self.stack.pop().ok_or(ParseError::BadValue)? as usize;
+                let subrs = self.subrs;
+                let charstring = subrs.get(subr).ok_or(ParseError::BadIndex)?;
+                self.run(charstring, depth + 1)?;
+            }
+            // hsbw
+            13 => {
+                let args = self.args(2)?;
+                let (sbx, wx) = (args[0], args[1]);
+                self.set_sidebearing(sbx, 0.0, wx);
+            }
+            // endchar
+            14 => {
+                self.stack.clear();
+                self.done = true;
+            }
+            // rmoveto
+            21 => {
+                let args = self.args(2)?;
+                let (dx, dy) = (args[0], args[1]);
+                self.move_to(dx, dy);
+            }
+            // hmoveto
+            22 => {
+                let dx = self.args(1)?[0];
+                self.move_to(dx, 0.0);
+            }
+            // vhcurveto
+            30 => {
+                let args = self.args(4)?;
+                let (dy1, dx2, dy2, dx3) = (args[0], args[1], args[2], args[3]);
+                self.curve_to(0.0, dy1, dx2, dy2, dx3, 0.0);
+            }
+            // hvcurveto
+            31 => {
+                let args = self.args(4)?;
+                let (dx1, dx2, dy2, dy3) = (args[0], args[1], args[2], args[3]);
+                self.curve_to(dx1, 0.0, dx2, dy2, 0.0, dy3);
+            }
+            _ => return Err(ParseError::BadValue),
+        }
+
+        Ok(())
+    }
+
+    fn escape(&mut self, op: u8) -> Result<(), ParseError> {
+        match op {
+            // dotsection
+            0 => self.stack.clear(),
+            // vstem3
+            1 => {
+                let args = self.args(6)?;
+                let (x0, dx0, x1, dx1, x2, dx2) =
+                    (args[0], args[1], args[2], args[3], args[4], args[5]);
+                for &(x, dx) in &[(x0, dx0), (x1, dx1), (x2, dx2)] {
+                    self.outline.vstems.push((self.sbx + x, dx));
+                }
+            }
+            // hstem3
+            2 => {
+                let args = self.args(6)?;
+                let (y0, dy0, y1, dy1, y2, dy2) =
+                    (args[0], args[1], args[2], args[3], args[4], args[5]);
+                for &(y, dy) in &[(y0, dy0), (y1, dy1), (y2, dy2)] {
+                    self.outline.hstems.push((self.sby + y, dy));
+                }
+            }
+            // seac
+            6 => {
+                let args = self.args(5)?;
+                let (asb, adx, ady, bchar, achar) = (args[0], args[1], args[2], args[3], args[4]);
+                self.outline.seac = Some([self.sbx + adx - asb, ady, bchar, achar]);
+                self.done = true;
+            }
+            // sbw
+            7 => {
+                let args = self.args(4)?;
+                let (sbx, sby, wx, _) = (args[0], args[1], args[2], args[3]);
+                self.set_sidebearing(sbx, sby, wx);
+            }
+            // div
+            12 => {
+                let b = self.stack.pop().ok_or(ParseError::BadValue)?;
+                let a = self.stack.pop().ok_or(ParseError::BadValue)?;
+                if b == 0.0 {
+                    return Err(ParseError::BadValue);
+                }
+                self.stack.push(a / b);
+            }
+            // callothersubr
+            16 => {
+                let othersubr = self.stack.pop().ok_or(ParseError::BadValue)?;
+                let n = self.stack.pop().ok_or(ParseError::BadValue)? as usize;
+                if n > self.stack.len() {
+                    return Err(ParseError::BadValue);
+                }
+                let args = self.stack.split_off(self.stack.len() - n);
+                self.call_other_subr(othersubr as i32, args)?;
+            }
+            // pop
+            17 => {
+                let value = self.ps_stack.pop().unwrap_or(0.0);
+                self.stack.push(value);
+            }
+            // setcurrentpoint
+            33 => {
+                let args = self.args(2)?;
+                let (x, y) = (args[0], args[1]);
+                self.x = x;
+                self.y = y;
+            }
+            _ => return Err(ParseError::BadValue),
+        }
+
+        Ok(())
+    }
+
+    fn call_other_subr(&mut self, othersubr: i32, args: Vec<f64>) -> Result<(), ParseError> {
+        match othersubr {
+            // End of flex
+            0 => {
+                let points = self.flex.take().ok_or(ParseError::BadValue)?;
+                if points.len() != 7 {
+                    return Err(ParseError::BadValue);
+                }
+                // The first point is the reference point, the rest are the control and end points
+                // of two curves.
+                for curve in points[1..].chunks(3) {
+                    let [(x1, y1), (x2, y2), (x3, y3)] = [curve[0], curve[1], curve[2]];
+                    self.outline
+                        .path
+                        .push(PathOp::Curve([x1, y1, x2, y2, x3, y3]));
+                }
+                let (x, y) = points[6];
+                self.x = x;
+                self.y = y;
+                // Leave the end point for the following `pop pop setcurrentpoint`
+                self.ps_stack.push(y);
+                self.ps_stack.push(x);
+            }
+            // Start of flex
+            1 => self.flex = Some(Vec::with_capacity(7)),
+            // Flex point, the point is recorded by the preceding rmoveto
+            2 => {}
+            // Hint replacement, the subroutine number is returned to the caller which then calls
+            // it to replace the hints. As stems are collected for the whole glyph this just adds
+            // more stems.
+            _ => self.ps_stack.extend(args.into_iter().rev()),
+        }
+
+        Ok(())
+    }
+
+    /// Take the last `n` arguments from the stack, clearing it.
+    fn args(&mut self, n: usize) -> Result<Vec<f64>, ParseError> {
+        if self.stack.len() < n {
+            return Err(ParseError::BadValue);
+        }
+        let args = self.stack.split_off(self.stack.len() - n);
+        self.stack.clear();
+        Ok(args)
+    }
+
+    fn set_sidebearing(&mut self, sbx: f64, sby: f64, width: f64) {
+        self.sbx = sbx;
+        self.sby = sby;
+        self.x = sbx;
+        self.y = sby;
+        self.outline.lsb = sbx;
+        self.outline.width = width;
+    }
+
+    fn move_to(&mut self, dx: f64, dy: f64) {
+        self.x += dx;
+        self.y += dy;
+        match &mut self.flex {
+            Some(points) => points.push((self.x, self.y)),
+            None => {
+                // Only the last of consecutive moves matters
+                if let Some(PathOp::Move(..)) = self.outline.path.last() {
+                    self.outline.path.pop();
+                }
+                self.outline.path.push(PathOp::Move(self.x, self.y));
+            }
+        }
+    }
+
+    fn line_to(&mut self, dx: f64, dy: f64) {
+        self.x += dx;
+        self.y += dy;
+        self.outline.path.push(PathOp::Line(self.x, self.y));
+    }
+
+    fn curve_to(&mut self, dx1: f64, dy1: f64, dx2: f64, dy2: f64, dx3: f64, dy3: f64) {
+        let (x1, y1) = (self.x + dx1, self.y + dy1);
+        let (x2, y2) = (x1 + dx2, y1 + dy2);
+        let (x3, y3) = (x2 + dx3, y2 + dy3);
+        self.x = x3;
+        self.y = y3;
+        self.outline
+            .path
+            .push(PathOp::Curve([x1, y1, x2, y2, x3, y3]));
+    }
+}
+
+/// Encode an outline as a Type 2 charstring.
+///
+/// The Private DICT written for the font uses the default `defaultWidthX` and `nominalWidthX`
+/// of zero, so the width is written as is when it is non-zero.
+fn to_type2_charstring(outline: &Outline) -> Result<Vec<u8>, WriteError> {
+    let mut charstring = WriteBuffer::new();
+    let mut width = if outline.width != 0.0 {
+        Some(outline.width)
+    } else {
+        None
+    };
+
+    let hstems = remove_overlapping_stems(&outline.hstems);
+    let vstems = remove_overlapping_stems(&outline.vstems);
+    for &(stems, op) in &[(&hstems, 1), (&vstems, 3)] {
+        if stems.is_empty() {
+            continue;
+        }
+        let mut args = width.take().into_iter().collect::<Vec<_>>();
+        let mut edge = 0.0;
+        for &(position, size) in stems {
+            args.push(position - edge);
+            args.push(size);
+            edge = position + size;
+        }
+        write_type2_op(&mut charstring, &args, op)?;
+    }
+
+    let (mut x, mut y) = (0.0, 0.0);
+    let mut delta = |to_x: f64, to_y: f64| {
+        let dx = quantize(to_x - x);
+        let dy = quantize(to_y - y);
+        x += dx;
+        y += dy;
+        [dx, dy]
+    };
+    // Consecutive lines and curves are combined into a single operator
+    let mut pending: Option<(u8, Vec<f64>)> = None;
+    let mut has_moved = false;
+    for path_op in &outline.path {
+        let (op, args) = match *path_op {
+            PathOp::Move(x, y) => (21, delta(x, y).to_vec()),
+            PathOp::Line(x, y) => (5, delta(x, y).to_vec()),
+            PathOp::Curve([x1, y1, x2, y2, x3, y3]) => {
+                let mut args = delta(x1, y1).to_vec();
+                args.extend_from_slice(&delta(x2, y2));
+                args.extend_from_slice(&delta(x3, y3));
+                (8, args)
+            }
+        };
+        if let Some((pending_op, pending_args)) = &mut pending {
+            if *pending_op == op && pending_args.len() + args.len() <= TYPE2_MAX_ARGS {
+                pending_args.extend(args);
+                continue;
+            }
+        }
+        if let Some((pending_op, pending_args)) = pending.take() {
+            write_type2_op(&mut charstring, &pending_args, pending_op)?;
+        }
+
+        if op == 21 {
+            // The width precedes the arguments of the first moveto
+            let mut move_args = width.take().into_iter().collect::<Vec<_>>();
+            move_args.extend(args);
+            write_type2_op(&mut charstring, &move_args, op)?;
+            has_moved = true;
+        } else {
+            if !has_moved {
+                // A path must start with a moveto, add one at the origin
+                let move_args = width.take().into_iter().chain(vec![0.0, 0.0]);
+                write_type2_op(&mut charstring, &move_args.collect::<Vec<_>>(), 21)?;
+                has_moved = true;
+            }
+            pending = Some((op, args));
+        }
+    }
+    if let Some((op, args)) = pending {
+        write_type2_op(&mut charstring, &args, op)?;
+    }
+
+    let mut args = width.take().into_iter().collect::<Vec<_>>();
+    if let Some(seac) = outline.seac {
+        args.extend_from_slice(&seac);
+    }
+    write_type2_op(&mut charstring, &args, 14)?;
+
+    Ok(charstring.into_inner())
+}
+
+/// Sort stems and remove any that overlap an earlier stem, as Type 2 charstrings require
+/// non-overlapping stems unless hint masks are used.
+fn remove_overlapping_stems(stems: &[(f64, f64)]) -> Vec<(f64, f64)> {
+    let mut stems = stems.to_vec();
+    stems.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    stems.dedup();
+
+    let mut result: Vec<(f64, f64)> = Vec::new();
+    for (position, size) in stems {
+        // Ghost stems have a negative size
+        let start = position.min(position + size);
+        let overlaps = match result.last() {
+            Some(&(prev_position, prev_size)) => {
+                start <= prev_position.max(prev_position + prev_size)
+            }
+            None => false,
+        };
+        if !overlaps && result.len() < MAX_STEMS {
+            result.push((position, size));
+        }
+    }
+    result
+}
+
+fn write_type2_op(w: &mut WriteBuffer, args: &[f64], op: u8) -> Result<(), WriteError> {
+    for &arg in args {
+        write_type2_number(w, arg)?;
+    }
+    U8::write(w, op)
+}
+
+fn write_type2_number(w: &mut WriteBuffer, value: f64) -> Result<(), WriteError> {
+    if value.fract() == 0.0 && (-32768.0..=32767.0).contains(&value) {
+        let value = value as i32;
+        // Casts are safe due to the range of the patterns
+        match value {
+            -107..=107 => U8::write(w, (value + 139) as u8),
+            108..=1131 => {
+                let value = value - 108;
+                U8::write(w, ((value >> 8) + 247) as u8)?;
+                U8::write(w, value as u8)
+            }
+            -1131..=-108 => {
+                let value = -value - 108;
+                U8::write(w, ((value >> 8) + 251) as u8)?;
+                U8::write(w, value as u8)
+            }
+            _ => {
+                U8::write(w, 28)?;
+                I16Be::write(w, value as i16)
+            }
+        }
+    } else {
+        // 16.16 fixed point number
+        let fixed = (value * 65536.0).round();
+        if fixed < f64::from(std::i32::MIN) || fixed > f64::from(std::i32::MAX) {
+            return Err(WriteError::BadValue);
+        }
+        U8::write(w, 255)?;
+        w.write_bytes(&(fixed as i32).to_be_bytes())
+    }
+}
+
+/// Round `value` to the precision of a Type 2 charstring number.
+fn quantize(value: f64) -> f64 {
+    let rounded = value.round();
+    if (value - rounded).abs() < 1e-6 {
+        rounded
+    } else {
+        (value * 65536.0).round() / 65536.0
+    }
+}
+
+fn write_index(objects: &[Vec<u8>]) -> Result<Vec<u8>, WriteError> {
+    let mut w = WriteBuffer::new();
+    U16Be::write(&mut w, u16::try_from(objects.len())?)?;
+    if objects.is_empty() {
+        return Ok(w.into_inner());
+    }
+
+    let data_len = objects.iter().map(Vec::len).sum::<usize>();
+    let last_offset = u32::try_from(data_len + 1)?;
+    let off_size = match last_offset {
+        0..=0xFF => 1,
+        0x100..=0xFFFF => 2,
+        0x1_0000..=0xFF_FFFF => 3,
+        _ => 4,
+    };
+    U8::write(&mut w, off_size)?;
+    let mut offset = 1u32;
+    for object in objects.iter().map(Vec::len).chain(std::iter::once(0)) {
+        w.write_bytes(&offset.to_be_bytes()[4 - usize::from(off_size)..])?;
+        offset += u32::try_from(object)?;
+    }
+    for object in objects {
+        w.write_bytes(object)?;
+    }
+
+    Ok(w.into_inner())
+}
+
+fn write_top_dict(
+    entries: &[(Operator, Vec<f64>)],
+    char_strings: usize,
+    charset: usize,
+    encoding: Option<usize>,
+    (private_len, private_offset): (usize, usize),
+) -> Result<Vec<u8>, WriteError> {
+    let mut w = WriteBuffer::new();
+    for (operator, operands) in entries {
+        write_dict_entry(&mut w, *operator, operands)?;
+    }
+    let offset = |value: usize| -> Result<Operand, WriteError> {
+        Ok(Operand::Offset(i32::try_from(value)?))
+    };
+    Operand::write(&mut w, &offset(charset)?)?;
+    Operator::write(&mut w, Operator::Charset)?;
+    if let Some(encoding) = encoding {
+        Operand::write(&mut w, &offset(encoding)?)?;
+        Operator::write(&mut w, Operator::Encoding)?;
+    }
+    Operand::write(&mut w, &offset(char_strings)?)?;
+    Operator::write(&mut w, Operator::CharStrings)?;
+    Operand::write(&mut w, &offset(private_len)?)?;
+    Operand::write(&mut w, &offset(private_offset)?)?;
+    Operator::write(&mut w, Operator::Private)?;
+
+    Ok(w.into_inner())
+}
+
+fn write_dict_entry(
+    w: &mut WriteBuffer,
+    operator: Operator,
+    operands: &[f64],
+) -> Result<(), WriteError> {
+    for &operand in operands {
+        if operand.fract() == 0.0 && operand.abs() <= f64::from(std::i32::MAX) {
+            Operand::write(w, &Operand::Integer(operand as i32))?;
+        } else {
+            write_real(w, operand)?;
+        }
+    }
+    Operator::write(w, operator)
+}
+
+/// Write a DICT real number operand.
+fn write_real(w: &mut WriteBuffer, value: f64) -> Result<(), WriteError> {
+    let mut nibbles = Vec::new();
+    for c in value.to_string().chars() {
+        nibbles.push(match c {
+            '0'..='9' => c as u8 - b'0',
+            '.' => 0xa,
+            '-' => 0xe,
+            _ => return Err(WriteError::BadValue),
+        });
+    }
+    nibbles.push(0xf);
+    if nibbles.len() % 2 == 1 {
+        nibbles.push(0xf);
+    }
+
+    U8::write(w, 30)?;
+    for pair in nibbles.chunks(2) {
+        U8::write(w, (pair[0] << 4) | pair[1])?;
+    }
+    Ok(())
+}
+
+/// Convert an array of numbers into the delta form used by CFF DICTs.
+fn delta_encode(values: &[f64]) -> Vec<f64> {
+    let mut previous = 0.0;
+    values
+        .iter()
+        .map(|&value| {
+            let delta = value - previous;
+            previous = value;
+            delta
+        })
+        .collect()
+}
+
+fn round_i16(value: f64) -> i16 {
+    value
+        .round()
+        .max(f64::from(std::i16::MIN))
+        .min(f64::from(std::i16::MAX)) as i16
+}
+
+/// Split a PFA or PFB font into its cleartext and encrypted portions.
+fn split_segments(data: &[u8]) -> Result<(Vec<u8>, Vec<u8>), ParseError> {
+    if data.first() == Some(&PFB_MARKER) {
+        let mut cleartext = Vec::new();
+        let mut encrypted = Vec::new();
+        let mut ctxt = ReadScope::new(data).ctxt();
+        loop {
+            let marker = ctxt.read_u8()?;
+            ctxt.check(marker == PFB_MARKER)?;
+            let segment_type = ctxt.read_u8()?;
+            if segment_type == PFB_EOF {
+                break;
+            }
+            let length = usize::try_from(ctxt.read_u32le()?)?;
+            let segment = ctxt.read_slice(length)?;
+            match segment_type {
+                PFB_ASCII if encrypted.is_empty() => cleartext.extend_from_slice(segment),
+                // The trailer of zeros and cleartomark
+                PFB_ASCII => {}
+                PFB_BINARY => encrypted.extend_from_slice(segment),
+                _ => return Err(ParseError::BadValue),
+            }
+            if !ctxt.bytes_available() {
+                break;
+            }
+        }
+        return Ok((cleartext, encrypted));
+    }
+
+    if !data.starts_with(b"%!") {
+        return Err(ParseError::BadVersion);
+    }
+    let eexec = find(data, b"eexec").ok_or(ParseError::MissingValue)? + b"eexec".len();
+    let cleartext = data[..eexec].to_vec();
+    let rest = &data[eexec..];
+    let start = rest
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(rest.len());
+    let trimmed = &rest[start..];
+
+    // The encrypted portion may be hexadecimal or binary, this is determined by the first four
+    // bytes. Binary data may begin with whitespace so only the end of line is skipped.
+    let is_hex = trimmed.len() >= 4 && trimmed[..4].iter().all(u8::is_ascii_hexdigit);
+    let encrypted = if is_hex {
+        let digits = trimmed
+            .iter()
+            .take_while(|b| b.is_ascii_hexdigit() || b.is_ascii_whitespace())
+            .filter_map(|&b| (b as char).to_digit(16))
+            .map(|digit| digit as u8)
+            .collect::<Vec<_>>();
+        digits
+            .chunks(2)
+            .map(|pair| (pair[0] << 4) | pair.get(1).cloned().unwrap_or(0))
+            .collect()
+    } else if rest.starts_with(b"\r\n") {
+        rest[2..].to_vec()
+    } else if rest.starts_with(b"\r") || rest.starts_with(b"\n") {
+        rest[1..].to_vec()
+    } else {
+        rest.to_vec()
+    };
+
+    Ok((cleartext, encrypted))
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Decrypt `data` with the Type 1 encryption algorithm, discarding the first `skip` bytes.
+fn decrypt(data: &[u8], key: u16, skip: usize) -> Vec<u8> {
+    let mut r = key;
+    data.iter()
+        .map(|&cipher| {
+            let plain = cipher ^ (r >> 8) as u8;
+            r = u16::from(cipher)
+                .wrapping_add(r)
+                .wrapping_mul(52845)
+                .wrapping_add(22719);
+            plain
+        })
+        .skip(skip)
+        .collect()
+}
+
+fn string_value(tokens: &[Token<'_>]) -> Option<String> {
+    match tokens.first() {
+        Some(Token::String(value)) => Some(String::from_utf8_lossy(value).into_owned()),
+        _ => None,
+    }
+}
+
+fn number_value(tokens: &[Token<'_>]) -> f64 {
+    match tokens.first() {
+        Some(Token::Number(value)) => *value,
+        _ => 0.0,
+    }
+}
+
+fn bool_value(tokens: &[Token<'_>]) -> bool {
+    tokens.first() == Some(&Token::Name(b"true"))
+}
+
+/// Read an array of numbers delimited by `[]` or `{}`.
+fn array_value(tokens: &[Token<'_>]) -> Vec<f64> {
+    let mut values = Vec::new();
+    if let Some(Token::Name(b"[")) | Some(Token::Name(b"{")) = tokens.first() {
+        for token in &tokens[1..] {
+            match token {
+                Token::Number(value) => values.push(*value),
+                _ => break,
+            }
+        }
+    }
+    values
+}
+
+fn parse_encoding(tokens: &[Token<'_>]) -> Type1Encoding {
+    match tokens.first() {
+        Some(Token::Name(b"StandardEncoding")) | None => Type1Encoding::Standard,
+        Some(_) => {
+            let mut encoding = Vec::new();
+            for (index, token) in tokens.iter().enumerate() {
+                match (token, tokens.get(index + 1), tokens.get(index + 2)) {
+                    (Token::Name(b"def"), _, _) => break,
+                    (
+                        Token::Name(b"dup"),
+                        Some(Token::Number(code)),
+                        Some(Token::Literal(name)),
+                    ) if (0.0..=255.0).contains(code) && *name != b".notdef" => {
+                        encoding.push((*code as u8, String::from_utf8_lossy(name).into_owned()));
+                    }
+                    _ => {}
+                }
+            }
+            Type1Encoding::Custom(encoding)
+        }
+    }
+}
+
+impl<'a> Tokenizer<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Tokenizer {
+            data,
+            pos: 0,
+            last_integer: None,
+        }
+    }
+
+    fn is_delimiter(b: u8) -> bool {
+        b"()<>[]{}/%".contains(&b) || b.is_ascii_whitespace() || b == 0
+    }
+
+    fn skip_whitespace_and_comments(&mut self) {
+        while let Some(&b) = self.data.get(self.pos) {
+            if b.is_ascii_whitespace() || b == 0 {
+                self.pos += 1;
+            } else if b == b'%' {
+                while let Some(&b) = self.data.get(self.pos) {
+                    if b == b'\r' || b == b'\n' {
+                        break;
+                    }
+                    self.pos += 1;
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn read_regular(&mut self) -> &'a [u8] {
+        let start = self.pos;
+        while let Some(&b) = self.data.get(self.pos) {
+            if Self::is_delimiter(b) {
+                break;
+            }
+            self.pos += 1;
+        }
+        &self.data[start..self.pos]
+    }
+
+    fn read_string(&mut self) -> Vec<u8> {
+        let mut string = Vec::new();
+        let mut depth = 1;
+        while let Some(&b) = self.data.get(self.pos) {
+            self.pos += 1;
+            match b {
+                b'(' => {
+                    depth += 1;
+                    string.push(b);
+                }
+                b')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                    string.push(b);
+                }
+                b'\\' => {
+                    let escaped = match self.data.get(self.pos) {
+                        Some(&escaped) => escaped,
+                        None => break,
+                    };
+                    self.pos += 1;
+                    match escaped {
+                        b'n' => string.push(b'\n'),
+                        b'r' => string.push(b'\r'),
+                        b't' => string.push(b'\t'),
+                        b'b' => string.push(8),
+                        b'f' => string.push(12),
+                        b'0'..=b'7' => {
+                            let mut value = u32::from(escaped - b'0');
+                            for _ in 0..2 {
+                                match self.data.get(self.pos) {
+                                    Some(&digit @ b'0'..=b'7') => {
+                                        value = value * 8 + u32::from(digit - b'0');
+                                        self.pos += 1;
+                                    }
+                                    _ => break,
+                                }
+                            }
+                            string.push(value as u8);
+                        }
+                        b'\r' | b'\n' => {}
+                        _ => string.push(escaped),
+                    }
+                }
+                _ => string.push(b),
+            }
+        }
+        string
+    }
+
+    fn read_hex_string(&mut self) -> Vec<u8> {
+        let mut digits = Vec::new();
+        while let Some(&b) = self.data.get(self.pos) {
+            self.pos += 1;
+            if b == b'>' {
+                break;
+            }
+            if let Some(digit) = (b as char).to_digit(16) {
+                digits.push(digit as u8);
+            }
+        }
+        digits
+            .chunks(2)
+            .map(|pair| (pair[0] << 4) | pair.get(1).cloned().unwrap_or(0))
+            .collect()
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        self.skip_whitespace_and_comments();
+        let b = *self.data.get(self.pos)?;
+        let last_integer = self.last_integer.take();
+        let token = match b {
+            b'(' => {
+                self.pos += 1;
+                Token::String(self.read_string())
+            }
+            b'<' if self.data.get(self.pos + 1) == Some(&b'<') => {
+                self.pos += 2;
+                Token::Name(b"<<")
+            }
+            b'>' if self.data.get(self.pos + 1) == Some(&b'>') => {
+                self.pos += 2;
+                Token::Name(b">>")
+            }
+            b'<' => {
+                self.pos += 1;
+                Token::String(self.read_hex_string())
+            }
+            b'[' | b']' | b'{' | b'}' | b')' | b'>' => {
+                self.pos += 1;
+                Token::Name(&self.data[self.pos - 1..self.pos])
+            }
+            b'/' => {
+                self.pos += 1;
+                Token::Literal(self.read_regular())
+            }
+            _ => {
+                let regular = self.read_regular();
+                match parse_number(regular) {
+                    Some(value) => {
+                        if value.fract() == 0.0 && value >= 0.0 {
+                            self.last_integer = Some(value as usize);
+                        }
+                        Token::Number(value)
+                    }
+                    None if regular == b"RD" || regular == b"-|" => match last_integer {
+                        Some(length) => {
+                            // A single space separates the operator from the binary data
+                            let start = self.pos + 1;
+                            let end = start.checked_add(length)?;
+                            let data = self.data.get(start..end)?;
+                            self.pos = end;
+                            Token::Binary(data)
+                        }
+                        None => Token::Name(regular),
+                    },
+                    // Stop at the end of the encrypted portion
+                    None if regular == b"closefile" => {
+                        self.pos = self.data.len();
+                        return None;
+                    }
+                    None => Token::Name(regular),
+                }
+            }
+        };
+        Some(token)
+    }
+}
+
+fn parse_number(token: &[u8]) -> Option<f64> {
+    let token = str::from_utf8(token).ok()?;
+    if let Some(hash) = token.find('#') {
+        let radix = token[..hash].parse::<u32>().ok()?;
+        if !(2..=36).contains(&radix) {
+            return None;
+        }
+        return i64::from_str_radix(&token[hash + 1..], radix)
+            .ok()
+            .map(|value| value as f64);
+    }
+    let first = token.bytes().next()?;
+    if !(first.is_ascii_digit() || first == b'-' || first == b'+' || first == b'.') {
+        return None;
+    }
+    token.parse::<f64>().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cff::{self, CFFVariant, CFF};
+    use crate::font::read_cmap_subtable;
+    use crate::font_data::FontData;
+    use crate::tables::cmap::Cmap;
+    use crate::tables::FontTableProvider;
+
+    // 0 500 hsbw endchar
+    const NOTDEF: &[u8] = &[139, 248, 136, 13, 14];
+    // 50 600 hsbw 20 30 hstem 50 40 vstem 0 0 rmoveto 500 0 rlineto 0 callsubr -500 0 rlineto
+    // closepath endchar
+    const GLYPH_A: &[u8] = &[
+        189, 248, 236, 13, 159, 169, 1, 189, 179, 3, 139, 139, 21, 248, 136, 139, 5, 139, 10, 252,
+        136, 139, 5, 9, 14,
+    ];
+    // 0 700 rlineto return
+    const SUBR_0: &[u8] = &[139, 249, 80, 5, 11];
+
+    const CLEARTEXT: &str = "%!PS-AdobeFont-1.0: Test-Regular 001.000
+12 dict begin
+/FontInfo 9 dict dup begin
+/version (001.000) readonly def
+/Notice (Test \\(notice\\)) readonly def
+/FullName (Test Bold) readonly def
+/FamilyName (Test) readonly def
+/Weight (Bold) readonly def
+/ItalicAngle 0 def
+/isFixedPitch false def
+/UnderlinePosition -100 def
+/UnderlineThickness 50 def
+end readonly def
+/FontName /Test-Bold def
+/Encoding 256 array
+0 1 255 {1 index exch /.notdef put} for
+dup 65 /A put
+readonly def
+/PaintType 0 def
+/FontType 1 def
+/FontMatrix [0.001 0 0 0.001 0 0] readonly def
+/FontBBox {0 -10 600 710} readonly def
+currentdict end
+currentfile eexec
+";
+
+    fn encrypt(plain: &[u8], key: u16) -> Vec<u8> {
+        let mut r = key;
+        [0u8; 4]
+            .iter()
+            .chain(plain)
+            .map(|&plain| {
+                let cipher = plain ^ (r >> 8) as u8;
+                r = u16::from(cipher)
+                    .wrapping_add(r)
+                    .wrapping_mul(52845)
+                    .wrapping_add(22719);
+                cipher
+            })
+            .collect()
+    }
+
+    fn private_section() -> Vec<u8> {
+        let mut private = Vec::new();
+        let mut charstring = |prefix: &str, data: &[u8], suffix: &str| {
+            let data = encrypt(data, CHARSTRING_KEY);
+            private.extend_from_slice(format!("{} {} RD ", prefix, data.len()).as_bytes());
+            private.extend_from_slice(&data);
+            private.extend_from_slice(suffix.as_bytes());
+        };
+        charstring(
+            "dup /Private 8 dict dup begin
+/RD{string currentfile exch readstring pop}executeonly def
+/ND{noaccess def}executeonly def
+/NP{noaccess put}executeonly def
+/BlueValues [-10 0 700 710] def
+/StdHW [30] def
+/ForceBold true def
+/lenIV 4 def
+/Subrs 1 array
+dup 0",
+            SUBR_0,
+            " NP\nND\n",
+        );
+        charstring(
+            "2 index /CharStrings 2 dict dup begin\n/.notdef",
+            NOTDEF,
+            " ND\n",
+        );
+        charstring("/A", GLYPH_A, " ND\n");
+        private.extend_from_slice(b"end\nend\nreadonly put\nnoaccess put\n");
+        private.extend_from_slice(b"dup /FontName get exch definefont pop\n");
+        private.extend_from_slice(b"mark currentfile closefile\n");
+        encrypt(&private, EEXEC_KEY)
+    }
+
+    fn pfa() -> Vec<u8> {
+        let mut pfa = CLEARTEXT.as_bytes().to_vec();
+        for line in private_section().chunks(32) {
+            for b in line {
+                pfa.extend_from_slice(format!("{:02x}", b).as_bytes());
+            }
+            pfa.push(b'\n');
+        }
+        for _ in 0..8 {
+            pfa.extend_from_slice(&[b'0'; 64]);
+            pfa.push(b'\n');
+        }
+        pfa.extend_from_slice(b"cleartomark\n");
+        pfa
+    }
+
+    fn pfb() -> Vec<u8> {
+        let mut trailer = vec![b'0'; 512];
+        trailer.extend_from_slice(b"cleartomark\n");
+        let segments = [
+            (PFB_ASCII, CLEARTEXT.as_bytes().to_vec()),
+            (PFB_BINARY, private_section()),
+            (PFB_ASCII, trailer),
+        ];
+        let mut pfb = Vec::new();
+        for (segment_type, data) in segments.iter() {
+            pfb.extend_from_slice(&[PFB_MARKER, *segment_type]);
+            pfb.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            pfb.extend_from_slice(data);
+        }
+        pfb.extend_from_slice(&[PFB_MARKER, PFB_EOF]);
+        pfb
+    }
+
+    fn assert_test_font(font: &Type1Font) {
+        assert_eq!(font.font_name, "Test-Bold");
+        assert_eq!(
+            font.font_info.family_name.as_ref().map(String::as_str),
+            Some("Test")
+        );
+        assert_eq!(
+            font.font_info.notice.as_ref().map(String::as_str),
+            Some("Test (notice)")
+        );
+        assert_eq!(font.font_info.underline_position, -100.0);
+        assert_eq!(font.font_bbox, [0.0, -10.0, 600.0, 710.0]);
+        assert_eq!(
+            font.encoding,
+            Type1Encoding::Custom(vec![(65, String::from("A"))])
+        );
+        assert_eq!(
+            font.private_dict.blue_values,
+            vec![-10.0, 0.0, 700.0, 710.0]
+        );
+        assert_eq!(font.private_dict.std_hw, Some(30.0));
+        assert!(font.private_dict.force_bold);
+        assert_eq!(font.subrs, vec![SUBR_0.to_vec()]);
+        let glyphs = font
+            .glyphs
+            .iter()
+            .map(|glyph| (glyph.name.as_str(), glyph.charstring.as_slice()))
+            .collect::<Vec<_>>();
+        assert_eq!(glyphs, vec![(".notdef", NOTDEF), ("A", GLYPH_A)]);
+    }
+
+    #[test]
+    fn test_read_pfa() {
+        let pfa = pfa();
+        let font = ReadScope::new(&pfa).read::<Type1Font>().unwrap();
+        assert_test_font(&font);
+    }
+
+    #[test]
+    fn test_read_pfb() {
+        let pfb = pfb();
+        let font = ReadScope::new(&pfb).read::<Type1Font>().unwrap();
+        assert_test_font(&font);
+    }
+
+    #[test]
+    fn test_to_opentype() {
+        let pfa = pfa();
+        let font = ReadScope::new(&pfa).read::<Type1Font>().unwrap();
+        let otf = font.to_opentype().unwrap();
+
+        let font_data = ReadScope::new(&otf).read::<FontData<'_>>().unwrap();
+        let provider = font_data.table_provider(0).unwrap();
+        let cff_data = provider.read_table_data(tag::CFF).unwrap();
+        let cff = ReadScope::new(&cff_data).read::<CFF<'_>>().unwrap();
+        let names = cff.name_index.iter().collect::<Vec<_>>();
+        assert_eq!(names, vec![&b"Test-Bold"[..]]);
+        let cff_font = &cff.fonts[0];
+        assert_eq!(cff_font.char_strings_index.len(), 2);
+        assert_eq!(
+            cff_font.char_strings_index.read_object(0),
+            Some(&[248, 136, 14][..])
+        );
+        // 600 20 30 hstem 100 40 vstem 50 0 rmoveto 500 0 0 700 -500 0 rlineto endchar
+        assert_eq!(
+            cff_font.char_strings_index.read_object(1),
+            Some(
+                &[
+                    248, 236, 159, 169, 1, 239, 179, 3, 189, 139, 21, 248, 136, 139, 139, 249, 80,
+                    252, 136, 139, 5, 14
+                ][..]
+            )
+        );
+        let sid = cff_font.charset.id_for_glyph(1).unwrap();
+        assert_eq!(cff.read_string(sid).unwrap(), "A");
+        let weight = cff_font
+            .top_dict
+            .get_i32(Operator::Weight)
+            .unwrap()
+            .unwrap();
+        assert_eq!(cff.read_string(weight as u16).unwrap(), "Bold");
+        match &cff_font.data {
+            CFFVariant::Type1(type1) => match &type1.encoding {
+                cff::Encoding::Custom(_) => {}
+                _ => panic!("expected a custom encoding"),
+            },
+            CFFVariant::CID(_) => panic!("expected a Type 1 CFF font"),
+        }
+
+        let cmap_data = provider.read_table_data(tag::CMAP).unwrap();
+        let cmap = ReadScope::new(&cmap_data).read::<Cmap<'_>>().unwrap();
+        let (_, subtable) = read_cmap_subtable(&cmap).unwrap().unwrap();
+        assert_eq!(subtable.map_glyph(u32::from('A')).unwrap(), Some(1));
+
+        let hmtx = provider.read_table_data(tag::HMTX).unwrap();
+        assert_eq!(&*hmtx, &[0x01, 0xF4, 0, 0, 0x02, 0x58, 0, 50][..]);
+        let head_data = provider.read_table_data(tag::HEAD).unwrap();
+        let head = ReadScope::new(&head_data).read::<HeadTable>().unwrap();
+        assert_eq!(head.units_per_em, 1000);
+        assert!(head.is_bold());
+    }
+}
@@ -34,6 +34,48 @@ impl TryFrom<char> for VariationSelector {
     }
 }
 
+/// ZERO WIDTH JOINER, used to join emoji into a single emoji ZWJ sequence.
+pub const ZWJ: char = '\u{200D}';
+
 pub fn bool_prop_emoji_presentation(ch: char) -> bool {
     emoji_data::EMOJI_PRESENTATION.contains_u32(ch as u32)
 }
+
+/// Returns `true` if `ch` is any of the 256 variation selectors, including those not represented
+/// by `VariationSelector`.
+pub fn is_variation_selector(ch: char) -> bool {
+    match ch {
+        '\u{FE00}'..='\u{FE0F}' | '\u{E0100}'..='\u{E01EF}' => true,
+        _ => false,
+    }
+}
+
+/// Returns `true` if `ch` continues an emoji sequence when following `prev`, which is itself part
+/// of an emoji sequence.
+///
+/// This covers emoji ZWJ sequences, emoji modifier sequences, emoji tag sequences and emoji keycap
+/// sequences.
+pub fn continues_emoji_sequence(prev: char, ch: char) -> bool {
+    match ch {
+        ZWJ => true,
+        '\u{1F3FB}'..='\u{1F3FF}' => true, // EMOJI MODIFIER FITZPATRICK TYPE-1-2..6
+        '\u{E0020}'..='\u{E007F}' => true, // TAG SPACE..CANCEL TAG
+        '\u{20E3}' => true,                // COMBINING ENCLOSING KEYCAP
+        _ => prev == ZWJ,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_continues_emoji_sequence() {
+        assert!(continues_emoji_sequence('\u{1F468}', ZWJ));
+        assert!(continues_emoji_sequence(ZWJ, '\u{2764}'));
+        assert!(continues_emoji_sequence('\u{1F44D}', '\u{1F3FB}'));
+        assert!(continues_emoji_sequence('1', '\u{20E3}'));
+        assert!(!continues_emoji_sequence('\u{1F468}', '\u{1F469}'));
+        assert!(!continues_emoji_sequence('\u{1F468}', 'a'));
+    }
+}
@@ -0,0 +1,706 @@
+//! Validation and sanitization of fonts from untrusted sources.
+//!
+//! `validate` checks structural invariants that hold within and across the tables of a font:
+//! that tables lie within the font data, that `cmap` ranges are sorted, that `loca` offsets never
+//! decrease, that the tables agree on the number of glyphs, that the variation tables agree on
+//! the number of axes, and that `head` and `OS/2` describe the same style. Problems are reported
+//! as a list of `Issue`s rather than failing on the first one. `sanitize` additionally produces a
+//! copy of the font with the tables that failed validation removed, in the spirit of the
+//! [OpenType Sanitizer](https://github.com/khaledhosny/ots).
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+
+use crate::binary::read::ReadScope;
+use crate::cff::CFF;
+use crate::checksum;
+use crate::error::{ParseError, ReadWriteError};
+use crate::post::PostTable;
+use crate::sfnt::FontBuilder;
+use crate::tables::cmap::{Cmap, CmapSubtable};
+use crate::tables::fvar::{AxisFlags, FvarTable, InstanceFlags};
+use crate::tables::glyf::GlyfTable;
+#[cfg(feature = "variations")]
+use crate::tables::gvar::GvarTable;
+use crate::tables::loca::LocaTable;
+use crate::tables::os2::{FsSelection, Os2};
+use crate::tables::{
+    HeadFlags, HeadTable, HheaTable, HmtxTable, MacStyle, MaxpTable, OffsetTable, OpenTypeFont,
+};
+use crate::tag;
+
+/// Tables that a font cannot be used without.
+const REQUIRED_TABLES: [u32; 5] = [tag::CMAP, tag::HEAD, tag::HHEA, tag::HMTX, tag::MAXP];
+
+/// Tables that `sanitize` is unable to drop from a font when they fail validation.
+const ESSENTIAL_TABLES: [u32; 9] = [
+    tag::CMAP,
+    tag::HEAD,
+    tag::HHEA,
+    tag::HMTX,
+    tag::MAXP,
+    tag::GLYF,
+    tag::LOCA,
+    tag::CFF,
+    tag::CFF2,
+];
+
+/// How serious an `Issue` is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// The font is usable but does not conform to the specification.
+    Warning,
+    /// The table can't be used safely.
+    Error,
+}
+
+/// A problem found by `validate`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Problem {
+    /// A required table is absent from the font.
+    MissingTable,
+    /// The table record places the table beyond the end of the font data.
+    OutOfBounds,
+    /// The table records are not sorted by tag, or a tag appears more than once.
+    UnsortedTableRecords,
+    /// The checksum in the table record does not match the table data.
+    ChecksumMismatch,
+    /// The table could not be parsed.
+    Malformed(ParseError),
+    /// The character ranges of the `cmap` subtable at `subtable` (an index into the encoding
+    /// records) are not in increasing order or overlap.
+    UnsortedCmapRanges { subtable: usize },
+    /// The `loca` offset of `glyph_index` is greater than that of the following glyph.
+    LocaNotMonotonic { glyph_index: u16 },
+    /// The final `loca` offset is beyond the end of the `glyf` table.
+    LocaOutOfBounds,
+    /// The number of glyphs (or metrics) in the table does not agree with `maxp`.
+    GlyphCountMismatch { expected: u16, actual: usize },
+    /// The number of axes in the table does not agree with `fvar`. `fvar` is zero when the font
+    /// has no `fvar` table.
+    AxisCountMismatch { fvar: u16, actual: u16 },
+    /// The bold and italic bits of `fsSelection` in `OS/2` disagree with `macStyle` in `head`.
+    StyleMismatch {
+        fs_selection: FsSelection,
+        mac_style: MacStyle,
+    },
+    /// Bits that are reserved, and should be zero, are set in the flags field `field`.
+    ReservedBitsSet { field: &'static str, bits: u16 },
+}
+
+/// A problem with a table of a font.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Issue {
+    pub table_tag: u32,
+    pub severity: Severity,
+    pub problem: Problem,
+}
+
+/// The issues found by `validate`, in the order they were found.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    pub issues: Vec<Issue>,
+}
+
+/// The result of `sanitize`.
+#[derive(Debug)]
+pub struct Sanitized {
+    /// The issues found in the original font.
+    pub report: ValidationReport,
+    /// The sanitized font, or `None` if a table the font can't be used without failed
+    /// validation.
+    pub data: Option<Vec<u8>>,
+}
+
+impl ValidationReport {
+    /// Returns `true` if no errors were found. Warnings are permitted.
+    pub fn is_valid(&self) -> bool {
+        self.errors().next().is_none()
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &Issue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity == Severity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &Issue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity == Severity::Warning)
+    }
+
+    /// Returns `true` if an error was found in the table identified by `table_tag`.
+    pub fn has_error_in(&self, table_tag: u32) -> bool {
+        self.errors().any(|issue| issue.table_tag == table_tag)
+    }
+}
+
+/// Check the structure of the font at `index` in `data`.
+///
+/// For a single font `index` is ignored. An `Err` is only returned if the font header or table
+/// directory can't be read, problems with individual tables are reported in the
+/// `ValidationReport`.
+pub fn validate(data: &[u8], index: usize) -> Result<ValidationReport, ParseError> {
+    let font = ReadScope::new(data).read::<OpenTypeFont<'_>>()?;
+    let offset_table = font.offset_table(index)?;
+    let mut validator = Validator::default();
+    let tables = validator.check_table_records(data, &offset_table)?;
+    validator.check_tables(&tables);
+    Ok(validator.report)
+}
+
+/// Validate the font at `index` in `data`, and build a copy of it without the tables that failed
+/// validation.
+///
+/// The copy has a new table directory and checksums, so warnings about these are resolved in it.
+pub fn sanitize(data: &[u8], index: usize) -> Result<Sanitized, ReadWriteError> {
+    let font = ReadScope::new(data).read::<OpenTypeFont<'_>>()?;
+    let offset_table = font.offset_table(index)?;
+    let mut validator = Validator::default();
+    let tables = validator.check_table_records(data, &offset_table)?;
+    validator.check_tables(&tables);
+    let report = validator.report;
+
+    if ESSENTIAL_TABLES
+        .iter()
+        .any(|&table_tag| report.has_error_in(table_tag))
+    {
+        return Ok(Sanitized { report, data: None });
+    }
+
+    let mut builder = FontBuilder::new(offset_table.sfnt_version);
+    for (&table_tag, &table) in &tables {
+        if [tag::HEAD, tag::GLYF, tag::LOCA].contains(&table_tag) || report.has_error_in(table_tag)
+        {
+            continue;
+        }
+        builder.add_table::<_, ReadScope<'_>>(table_tag, ReadScope::new(table), ())?;
+    }
+    // `head` was checked to be present and valid above
+    let head = ReadScope::new(tables[&tag::HEAD]).read::<HeadTable>()?;
+    let mut builder = builder.add_head_table(&head)?;
+    if let (Some(glyf), Some(loca)) = (tables.get(&tag::GLYF), tables.get(&tag::LOCA)) {
+        let maxp = ReadScope::new(tables[&tag::MAXP]).read::<MaxpTable>()?;
+        let loca = ReadScope::new(loca)
+            .read_dep::<LocaTable<'_>>((usize::from(maxp.num_glyphs), head.index_to_loc_format))?;
+        let glyf = ReadScope::new(glyf).read_dep::<GlyfTable<'_>>(&loca)?;
+        builder.add_glyf_table(glyf)?;
+    }
+
+    Ok(Sanitized {
+        report,
+        data: Some(builder.data()?),
+    })
+}
+
+#[derive(Default)]
+struct Validator {
+    report: ValidationReport,
+}
+
+impl Validator {
+    fn push(&mut self, table_tag: u32, severity: Severity, problem: Problem) {
+        self.report.issues.push(Issue {
+            table_tag,
+            severity,
+            problem,
+        });
+    }
+
+    /// Record the outcome of a check that parses `table_tag`, treating parse errors as errors in
+    /// the table.
+    fn check(&mut self, table_tag: u32, result: Result<(), ParseError>) {
+        if let Err(err) = result {
+            self.push(table_tag, Severity::Error, Problem::Malformed(err));
+        }
+    }
+
+    /// Check the table records and return the data of the tables that lie within `data`.
+    fn check_table_records<'a>(
+        &mut self,
+        data: &'a [u8],
+        offset_table: &OffsetTable<'_>,
+    ) -> Result<BTreeMap<u32, &'a [u8]>, ParseError> {
+        let mut tables = BTreeMap::new();
+        let mut prev_tag = 0;
+        for (index, record) in offset_table.table_records.iter().enumerate() {
+            let table_tag = record.table_tag;
+            if index > 0 && table_tag <= prev_tag {
+                self.push(table_tag, Severity::Warning, Problem::UnsortedTableRecords);
+            }
+            prev_tag = table_tag;
+
+            let start = usize::try_from(record.offset)?;
+            let end = start.checked_add(usize::try_from(record.length)?);
+            let table = match end.and_then(|end| data.get(start..end)) {
+                Some(table) => table,
+                None => {
+                    self.push(table_tag, Severity::Error, Problem::OutOfBounds);
+                    continue;
+                }
+            };
+            if checksum::sfnt_table_checksum(table_tag, table)?.0 != record.checksum {
+                self.push(table_tag, Severity::Warning, Problem::ChecksumMismatch);
+            }
+            tables.entry(table_tag).or_insert(table);
+        }
+
+        for &table_tag in REQUIRED_TABLES.iter() {
+            if !tables.contains_key(&table_tag) {
+                self.push(table_tag, Severity::Error, Problem::MissingTable);
+            }
+        }
+        // `glyf` and `loca` are only meaningful together
+        match (
+            tables.contains_key(&tag::GLYF),
+            tables.contains_key(&tag::LOCA),
+        ) {
+            (true, false) => self.push(tag::LOCA, Severity::Error, Problem::MissingTable),
+            (false, true) => self.push(tag::GLYF, Severity::Error, Problem::MissingTable),
+            _ => {}
+        }
+
+        Ok(tables)
+    }
+
+    fn check_tables(&mut self, tables: &BTreeMap<u32, &[u8]>) {
+        let table = |table_tag| tables.get(&table_tag).map(|&data| ReadScope::new(data));
+
+        let opt_head = table(tag::HEAD).and_then(|scope| match scope.read::<HeadTable>() {
+            Ok(head) => Some(head),
+            Err(err) => {
+                self.push(tag::HEAD, Severity::Error, Problem::Malformed(err));
+                None
+            }
+        });
+        if let Some(head) = &opt_head {
+            self.check_reserved_bits(tag::HEAD, "flags", head.flags, HeadFlags::all().bits());
+            self.check_reserved_bits(
+                tag::HEAD,
+                "macStyle",
+                head.mac_style,
+                MacStyle::all().bits(),
+            );
+            if let Some(scope) = table(tag::OS_2) {
+                let result = scope
+                    .read_dep::<Os2>(scope.data().len())
+                    .map(|os2| self.check_style(head, &os2));
+                self.check(tag::OS_2, result);
+            }
+        }
+        // The glyph count is needed by most of the checks that follow
+        let num_glyphs = match table(tag::MAXP).map(|scope| scope.read::<MaxpTable>()) {
+            Some(Ok(maxp)) => maxp.num_glyphs,
+            Some(Err(err)) => {
+                self.push(tag::MAXP, Severity::Error, Problem::Malformed(err));
+                return;
+            }
+            None => return,
+        };
+
+        if let Some(scope) = table(tag::HHEA) {
+            let result = self.check_hmtx(scope, table(tag::HMTX), num_glyphs);
+            self.check(tag::HHEA, result);
+        }
+        if let (Some(head), Some(loca), Some(glyf)) =
+            (opt_head, table(tag::LOCA), tables.get(&tag::GLYF))
+        {
+            let result = loca
+                .read_dep::<LocaTable<'_>>((usize::from(num_glyphs), head.index_to_loc_format))
+                .map(|loca| self.check_loca(&loca, glyf.len()));
+            self.check(tag::LOCA, result);
+        }
+        if let Some(scope) = table(tag::CMAP) {
+            let result = self.check_cmap(scope);
+            self.check(tag::CMAP, result);
+        }
+        if let Some(scope) = table(tag::POST) {
+            let result = scope.read::<PostTable<'_>>().map(|post| {
+                if let Some(sub_table) = post.opt_sub_table {
+                    self.check_glyph_count(
+                        tag::POST,
+                        Severity::Warning,
+                        num_glyphs,
+                        usize::from(sub_table.num_glyphs),
+                    );
+                }
+            });
+            self.check(tag::POST, result);
+        }
+        if let Some(scope) = table(tag::CFF) {
+            let result = scope.read::<CFF<'_>>().map(|cff| {
+                for font in &cff.fonts {
+                    self.check_glyph_count(
+                        tag::CFF,
+                        Severity::Error,
+                        num_glyphs,
+                        font.char_strings_index.len(),
+                    );
+                }
+            });
+            self.check(tag::CFF, result);
+        }
+        #[cfg(feature = "variations")]
+        if let Some(scope) = table(tag::GVAR) {
+            let result = scope
+                .read_dep::<GvarTable<'_>>(usize::from(num_glyphs))
+                .map(|_gvar| ());
+            self.check(tag::GVAR, result);
+        }
+        let result = self.check_axis_counts(table(tag::FVAR), table(tag::AVAR), table(tag::GVAR));
+        self.check(tag::FVAR, result);
+    }
+
+    /// Check `numberOfHMetrics` in `hhea` and the length of `hmtx`.
+    fn check_hmtx(
+        &mut self,
+        hhea: ReadScope<'_>,
+        opt_hmtx: Option<ReadScope<'_>>,
+        num_glyphs: u16,
+    ) -> Result<(), ParseError> {
+        let hhea = hhea.read::<HheaTable>()?;
+        if hhea.num_h_metrics == 0 || hhea.num_h_metrics > num_glyphs {
+            self.push(
+                tag::HHEA,
+                Severity::Error,
+                Problem::GlyphCountMismatch {
+                    expected: num_glyphs,
+                    actual: usize::from(hhea.num_h_metrics),
+                },
+            );
+        } else if let Some(hmtx) = opt_hmtx {
+            let result = hmtx
+                .read_dep::<HmtxTable<'_>>((
+                    usize::from(num_glyphs),
+                    usize::from(hhea.num_h_metrics),
+                ))
+                .map(|_hmtx| ());
+            self.check(tag::HMTX, result);
+        }
+        Ok(())
+    }
+
+    fn check_loca(&mut self, loca: &LocaTable<'_>, glyf_len: usize) {
+        let mut prev_offset = 0;
+        for (index, offset) in loca.offsets.iter().enumerate() {
+            if offset < prev_offset {
+                // `index` is at least 1 and at most `num_glyphs` here, so the glyph index fits
+                let glyph_index = u16::try_from(index - 1).unwrap_or(std::u16::MAX);
+                self.push(
+                    tag::LOCA,
+                    Severity::Error,
+                    Problem::LocaNotMonotonic { glyph_index },
+                );
+                return;
+            }
+            prev_offset = offset;
+        }
+        let in_bounds = match usize::try_from(prev_offset) {
+            Ok(end) => end <= glyf_len,
+            Err(_) => false,
+        };
+        if !in_bounds {
+            self.push(tag::LOCA, Severity::Error, Problem::LocaOutOfBounds);
+        }
+    }
+
+    /// Check that the ranges of the format 4 and 12 subtables are sorted and don't overlap.
+    fn check_cmap(&mut self, scope: ReadScope<'_>) -> Result<(), ParseError> {
+        let cmap = scope.read::<Cmap<'_>>()?;
+        for (subtable, record) in cmap.encoding_records().enumerate() {
+            let subtable_scope = cmap.scope.offset(usize::try_from(record.offset)?);
+            let format = subtable_scope.ctxt().read_u16be()?;
+            if format != 4 && format != 12 {
+                continue;
+            }
+            let ranges = match subtable_scope.read::<CmapSubtable<'_>>()? {
+                CmapSubtable::Format4 {
+                    start_codes,
+                    end_codes,
+                    ..
+                } => start_codes
+                    .iter()
+                    .zip(end_codes.iter())
+                    .map(|(start, end)| (u32::from(start), u32::from(end)))
+                    .collect::<Vec<_>>(),
+                CmapSubtable::Format12 { groups, .. } => groups
+                    .iter()
+                    .map(|group| (group.start_char_code(), group.end_char_code()))
+                    .collect(),
+                _ => continue,
+            };
+            let sorted = ranges.iter().all(|(start, end)| start <= end)
+                && ranges.windows(2).all(|pair| pair[0].1 < pair[1].0);
+            if !sorted {
+                self.push(
+                    tag::CMAP,
+                    Severity::Error,
+                    Problem::UnsortedCmapRanges { subtable },
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn check_glyph_count(
+        &mut self,
+        table_tag: u32,
+        severity: Severity,
+        num_glyphs: u16,
+        actual: usize,
+    ) {
+        if actual != usize::from(num_glyphs) {
+            self.push(
+                table_tag,
+                severity,
+                Problem::GlyphCountMismatch {
+                    expected: num_glyphs,
+                    actual,
+                },
+            );
+        }
+    }
+
+    /// Check that `OS/2` describes the same style as `head`.
+    fn check_style(&mut self, head: &HeadTable, os2: &Os2) {
+        let fs_selection = os2.fs_selection_flags();
+        let mac_style = head.mac_style_flags();
+        if !fs_selection.is_consistent_with(mac_style) {
+            self.push(
+                tag::OS_2,
+                Severity::Warning,
+                Problem::StyleMismatch {
+                    fs_selection,
+                    mac_style,
+                },
+            );
+        }
+        self.check_reserved_bits(
+            tag::OS_2,
+            "fsSelection",
+            os2.fs_selection,
+            FsSelection::all().bits(),
+        );
+    }
+
+    fn check_reserved_bits(
+        &mut self,
+        table_tag: u32,
+        field: &'static str,
+        value: u16,
+        defined: u16,
+    ) {
+        let bits = value & !defined;
+        if bits != 0 {
+            self.push(
+                table_tag,
+                Severity::Warning,
+                Problem::ReservedBitsSet { field, bits },
+            );
+        }
+    }
+
+    /// Check the axis and instance flags of `fvar`, and that `avar` and `gvar` have the same axis count as
+    /// `fvar`.
+    fn check_axis_counts(
+        &mut self,
+        opt_fvar: Option<ReadScope<'_>>,
+        opt_avar: Option<ReadScope<'_>>,
+        opt_gvar: Option<ReadScope<'_>>,
+    ) -> Result<(), ParseError> {
+        let fvar_axis_count = match opt_fvar {
+            Some(scope) => {
+                let fvar = scope.read::<FvarTable>()?;
+                let instance_flags = fvar
+                    .instances
+                    .iter()
+                    .fold(0, |flags, instance| flags | instance.flags);
+                self.check_reserved_bits(
+                    tag::FVAR,
+                    "flags",
+                    instance_flags,
+                    InstanceFlags::all().bits(),
+                );
+                let axis_flags = fvar.axes.iter().fold(0, |flags, axis| flags | axis.flags);
+                self.check_reserved_bits(
+                    tag::FVAR,
+                    "axis flags",
+                    axis_flags,
+                    AxisFlags::all().bits(),
+                );
+                fvar.axis_count()
+            }
+            None => 0,
+        };
+        // majorVersion, minorVersion and a reserved field precede axisCount in avar
+        // majorVersion and minorVersion precede axisCount in gvar
+        let tables = [(tag::AVAR, opt_avar, 6), (tag::GVAR, opt_gvar, 4)];
+        for (table_tag, opt_scope, axis_count_offset) in tables.iter() {
+            let scope = match opt_scope {
+                Some(scope) => scope,
+                None => continue,
+            };
+            match scope.offset(*axis_count_offset).ctxt().read_u16be() {
+                Ok(axis_count) if axis_count != fvar_axis_count => self.push(
+                    *table_tag,
+                    Severity::Error,
+                    Problem::AxisCountMismatch {
+                        fvar: fvar_axis_count,
+                        actual: axis_count,
+                    },
+                ),
+                Ok(_) => {}
+                Err(err) => self.push(
+                    *table_tag,
+                    Severity::Error,
+                    Problem::Malformed(ParseError::from(err)),
+                ),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::write::{WriteBinary, WriteBuffer};
+    use crate::binary::U16Be;
+    use crate::tables::IndexToLocFormat;
+    use crate::tests::read_fixture;
+
+    /// Returns the offset of the table record for `table_tag` in the single font `data`.
+    fn table_record_offset(data: &[u8], table_tag: u32) -> usize {
+        let font = ReadScope::new(data).read::<OpenTypeFont<'_>>().unwrap();
+        let offset_table = font.offset_table(0).unwrap();
+        let index = offset_table
+            .table_records
+            .iter()
+            .position(|record| record.table_tag == table_tag)
+            .unwrap();
+        12 + index * 16
+    }
+
+    fn table_offset(data: &[u8], table_tag: u32) -> usize {
+        let record = table_record_offset(data, table_tag);
+        let offset = ReadScope::new(&data[record + 8..])
+            .ctxt()
+            .read_u32be()
+            .unwrap();
+        usize::try_from(offset).unwrap()
+    }
+
+    fn write_u16(data: &mut [u8], offset: usize, value: u16) {
+        let mut buffer = WriteBuffer::new();
+        U16Be::write(&mut buffer, value).unwrap();
+        data[offset..offset + 2].copy_from_slice(buffer.bytes());
+    }
+
+    #[test]
+    fn test_validate_valid_fonts() {
+        for path in &[
+            "tests/fonts/opentype/Ubuntu Mono with Numderline.ttf",
+            "tests/fonts/opentype/Klei.otf",
+        ] {
+            let data = read_fixture(path);
+            let report = validate(&data, 0).unwrap();
+            assert!(report.is_valid(), "{}: {:?}", path, report);
+        }
+    }
+
+    #[test]
+    fn test_validate_loca_not_monotonic() {
+        let mut data = read_fixture("tests/fonts/opentype/Ubuntu Mono with Numderline.ttf");
+        let loca = table_offset(&data, tag::LOCA);
+        let head = ReadScope::new(&data[table_offset(&data, tag::HEAD)..])
+            .read::<HeadTable>()
+            .unwrap();
+        // Make glyph 2 start at offset 0, before glyph 1
+        match head.index_to_loc_format {
+            IndexToLocFormat::Short => write_u16(&mut data, loca + 4, 0),
+            IndexToLocFormat::Long => data[loca + 8..loca + 12].copy_from_slice(&[0, 0, 0, 0]),
+        }
+
+        let report = validate(&data, 0).unwrap();
+        assert!(report.issues.contains(&Issue {
+            table_tag: tag::LOCA,
+            severity: Severity::Error,
+            problem: Problem::LocaNotMonotonic { glyph_index: 1 },
+        }));
+        assert_eq!(sanitize(&data, 0).unwrap().data, None);
+    }
+
+    #[test]
+    fn test_validate_hhea_num_h_metrics() {
+        let mut data = read_fixture("tests/fonts/opentype/Ubuntu Mono with Numderline.ttf");
+        let hhea = table_offset(&data, tag::HHEA);
+        // numberOfHMetrics is the last field of hhea
+        write_u16(&mut data, hhea + 34, 0xFFFF);
+
+        let report = validate(&data, 0).unwrap();
+        assert!(report.has_error_in(tag::HHEA));
+    }
+
+    #[test]
+    fn test_validate_style_mismatch() {
+        let mut data = read_fixture("tests/fonts/opentype/Ubuntu Mono with Numderline.ttf");
+        let head = table_offset(&data, tag::HEAD);
+        // macStyle follows the bounding box in head. Mark the regular font as bold italic and
+        // set a reserved bit.
+        write_u16(&mut data, head + 44, 0x0103);
+
+        let report = validate(&data, 0).unwrap();
+        assert!(report.is_valid());
+        assert!(report.issues.contains(&Issue {
+            table_tag: tag::OS_2,
+            severity: Severity::Warning,
+            problem: Problem::StyleMismatch {
+                fs_selection: FsSelection::REGULAR,
+                mac_style: MacStyle::BOLD | MacStyle::ITALIC,
+            },
+        }));
+        assert!(report.issues.contains(&Issue {
+            table_tag: tag::HEAD,
+            severity: Severity::Warning,
+            problem: Problem::ReservedBitsSet {
+                field: "macStyle",
+                bits: 0x0100,
+            },
+        }));
+    }
+
+    #[test]
+    fn test_sanitize_drops_table() {
+        let mut data = read_fixture("tests/fonts/opentype/Klei.otf");
+        let record = table_record_offset(&data, tag::POST);
+        // Move the `post` table beyond the end of the font
+        data[record + 8..record + 12].copy_from_slice(&[0xFF, 0xFF, 0xFF, 0x00]);
+
+        let sanitized = sanitize(&data, 0).unwrap();
+        assert_eq!(
+            sanitized.report.errors().collect::<Vec<_>>(),
+            vec![&Issue {
+                table_tag: tag::POST,
+                severity: Severity::Error,
+                problem: Problem::OutOfBounds,
+            }]
+        );
+        let data = sanitized.data.unwrap();
+        let report = validate(&data, 0).unwrap();
+        assert_eq!(report, ValidationReport::default());
+        let font = ReadScope::new(&data).read::<OpenTypeFont<'_>>().unwrap();
+        let offset_table = font.offset_table(0).unwrap();
+        assert!(offset_table.find_table_record(tag::POST).is_none());
+        assert!(offset_table.find_table_record(tag::CFF).is_some());
+    }
+
+    #[test]
+    fn test_sanitize_rebuilds_glyf() {
+        let data = read_fixture("tests/fonts/opentype/Ubuntu Mono with Numderline.ttf");
+        let sanitized = sanitize(&data, 0).unwrap();
+        let data = sanitized.data.unwrap();
+        let report = validate(&data, 0).unwrap();
+        assert!(report.is_valid(), "{:?}", report);
+    }
+}
@@ -0,0 +1,618 @@
+#![deny(missing_docs)]
+
+//! Optimise fonts for delivery on the web.
+//!
+//! [optimize](fn.optimize.html) chains together the steps that are typically applied to a font
+//! before serving it to browsers: subsetting to the characters that will be displayed, removing
+//! TrueType hinting, removing layout tables, removing metadata that isn't needed to render, and
+//! encoding the result as a compressed web font. Each step can be enabled independently with
+//! [WebFontOptions](struct.WebFontOptions.html) and the size of the font after each step is
+//! recorded in an [OptimizeReport](struct.OptimizeReport.html).
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+use std::ops::RangeInclusive;
+
+use crate::binary::read::ReadScope;
+use crate::binary::write::{WriteBinary, WriteBinaryDep, WriteBuffer, WriteContext};
+use crate::binary::U16Be;
+use crate::error::{ParseError, ReadWriteError, WriteError};
+use crate::font::{read_cmap_subtable, Encoding};
+use crate::post::PostTable;
+use crate::sfnt::FontBuilder;
+use crate::subset;
+use crate::tables::cmap::{self, Cmap};
+use crate::tables::glyf::{CompositeGlyphFlag, GlyfRecord, GlyfTable, Glyph, GlyphData};
+use crate::tables::loca::{self, LocaTable};
+use crate::tables::{
+    FontTableProvider, HeadTable, MaxpTable, NameTable, NameTableBuilder, OffsetTable,
+    OpenTypeData, OpenTypeFont, Version16Dot16,
+};
+use crate::woff::WoffBlocks;
+use crate::{tag, woff};
+
+/// Tables that only hold TrueType hinting data.
+const HINTING_TABLES: [u32; 6] = [
+    tag::CVT,
+    tag::FPGM,
+    tag::HDMX,
+    tag::LTSH,
+    tag::PREP,
+    tag::VDMX,
+];
+
+/// OpenType and AAT layout tables.
+const LAYOUT_TABLES: [u32; 17] = [
+    tag::BASE,
+    tag::BSLN,
+    tag::FEAT,
+    tag::GDEF,
+    tag::GPOS,
+    tag::GSUB,
+    tag::JSTF,
+    tag::JUST,
+    tag::KERN,
+    tag::KERX,
+    tag::LCAR,
+    tag::MATH,
+    tag::MORT,
+    tag::MORX,
+    tag::OPBD,
+    tag::PROP,
+    tag::TRAK,
+];
+
+/// Tables that don't refer to glyph ids, which are retained when subsetting.
+const GLYPH_INDEPENDENT_TABLES: [u32; 2] = [tag::GASP, tag::OS_2];
+
+/// The `name` table ids that are kept when minimising metadata: copyright, family, subfamily,
+/// unique identifier, full name, version, and PostScript name.
+const ESSENTIAL_NAME_IDS: RangeInclusive<u16> = 0..=6;
+
+/// The format of the optimised font.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WebFontFormat {
+    /// An uncompressed OpenType font.
+    OpenType,
+    /// A WOFF 1.0 font, with tables compressed with zlib.
+    Woff,
+    /// A WOFF 2.0 font, with tables compressed with Brotli and the `glyf` table transformed.
+    ///
+    /// Requires the `woff2-writer` feature.
+    #[cfg(feature = "woff2-writer")]
+    Woff2,
+}
+
+/// Configuration for [optimize](fn.optimize.html).
+#[derive(Debug, Clone)]
+pub struct WebFontOptions {
+    /// The Unicode code points to retain. All glyphs are retained when `None`.
+    ///
+    /// Subsetting renumbers glyphs so layout tables, which refer to glyphs by id, are always
+    /// removed from a subset font.
+    pub unicodes: Option<BTreeSet<u32>>,
+    /// Remove TrueType hinting tables and glyph instructions.
+    pub strip_hints: bool,
+    /// Remove OpenType and AAT layout tables.
+    pub prune_layout: bool,
+    /// Remove `name` records other than the essential ones, glyph names in the `post` table, and
+    /// the digital signature.
+    pub minimize_metadata: bool,
+    /// The format of the optimised font.
+    pub format: WebFontFormat,
+}
+
+/// A step of the optimisation pipeline.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OptimizeStep {
+    /// Subsetting to the requested characters.
+    Subset,
+    /// Removal of hinting.
+    StripHints,
+    /// Removal of layout tables.
+    PruneLayout,
+    /// Removal of metadata.
+    MinimizeMetadata,
+    /// Encoding in the output format.
+    Encode,
+}
+
+/// The size of the font before and after a step of the pipeline.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct StepReport {
+    /// The step that was applied.
+    pub step: OptimizeStep,
+    /// The size of the font in bytes before the step.
+    pub size_before: usize,
+    /// The size of the font in bytes after the step.
+    pub size_after: usize,
+}
+
+/// The steps applied by [optimize](fn.optimize.html) in the order they were applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OptimizeReport {
+    /// The report for each step that was applied.
+    pub steps: Vec<StepReport>,
+}
+
+/// The tables of a font, held in memory so that they can be modified.
+struct FontTables {
+    sfnt_version: u32,
+    tables: BTreeMap<u32, Vec<u8>>,
+}
+
+impl Default for WebFontOptions {
+    fn default() -> Self {
+        WebFontOptions {
+            unicodes: None,
+            strip_hints: true,
+            prune_layout: false,
+            minimize_metadata: true,
+            format: WebFontFormat::Woff,
+        }
+    }
+}
+
+impl WebFontOptions {
+    /// Retain the characters of `text` when subsetting.
+    pub fn add_text(&mut self, text: &str) -> &mut Self {
+        self.unicodes
+            .get_or_insert_with(BTreeSet::new)
+            .extend(text.chars().map(u32::from));
+        self
+    }
+
+    /// Retain the code points in `range` when subsetting, like the CSS `unicode-range`
+    /// descriptor.
+    pub fn add_unicode_range(&mut self, range: RangeInclusive<u32>) -> &mut Self {
+        self.unicodes
+            .get_or_insert_with(BTreeSet::new)
+            .extend(range);
+        self
+    }
+}
+
+impl StepReport {
+    /// The number of bytes saved by the step. Negative if the font grew.
+    pub fn savings(&self) -> isize {
+        self.size_before as isize - self.size_after as isize
+    }
+}
+
+impl OptimizeReport {
+    /// The number of bytes saved by all steps.
+    pub fn total_savings(&self) -> isize {
+        self.steps.iter().map(StepReport::savings).sum()
+    }
+}
+
+/// Optimise the OpenType font in `data` for use on the web according to `options`.
+///
+/// The first font of a collection is used. Returns the optimised font along with the size of the
+/// font after each step.
+pub fn optimize(
+    data: &[u8],
+    options: &WebFontOptions,
+) -> Result<(Vec<u8>, OptimizeReport), ReadWriteError> {
+    let mut tables = FontTables::from_data(data)?;
+    let mut report = OptimizeReport { steps: Vec::new() };
+    let mut size = data.len();
+
+    if let Some(unicodes) = &options.unicodes {
+        tables = subset_tables(&tables, unicodes)?;
+        size = report.record(OptimizeStep::Subset, size, &tables)?;
+    }
+    if options.strip_hints {
+        strip_hints(&mut tables)?;
+        size = report.record(OptimizeStep::StripHints, size, &tables)?;
+    }
+    if options.prune_layout {
+        for tag in &LAYOUT_TABLES {
+            tables.tables.remove(tag);
+        }
+        size = report.record(OptimizeStep::PruneLayout, size, &tables)?;
+    }
+    if options.minimize_metadata {
+        minimize_metadata(&mut tables)?;
+        size = report.record(OptimizeStep::MinimizeMetadata, size, &tables)?;
+    }
+
+    let sfnt = tables.to_data()?;
+    let font = match options.format {
+        WebFontFormat::OpenType => sfnt,
+        WebFontFormat::Woff => woff::write_woff(&sfnt, &WoffBlocks::default())?,
+        #[cfg(feature = "woff2-writer")]
+        WebFontFormat::Woff2 => crate::woff2::write_woff2(&sfnt, true, &WoffBlocks::default())?,
+    };
+    report.steps.push(StepReport {
+        step: OptimizeStep::Encode,
+        size_before: size,
+        size_after: font.len(),
+    });
+
+    Ok((font, report))
+}
+
+impl OptimizeReport {
+    /// Record the size of `tables` after `step`, returning the new size.
+    fn record(
+        &mut self,
+        step: OptimizeStep,
+        size_before: usize,
+        tables: &FontTables,
+    ) -> Result<usize, ReadWriteError> {
+        let size_after = tables.to_data()?.len();
+        self.steps.push(StepReport {
+            step,
+            size_before,
+            size_after,
+        });
+        Ok(size_after)
+    }
+}
+
+impl FontTables {
+    fn from_data(data: &[u8]) -> Result<Self, ReadWriteError> {
+        let font = ReadScope::new(data).read::<OpenTypeFont<'_>>()?;
+        let offset_table = match &font.data {
+            OpenTypeData::Single(offset_table) => Cow::Borrowed(offset_table),
+            OpenTypeData::Collection(ttc) => {
+                let offset = ttc.offset_tables.read_item(0)?;
+                let offset_table = font
+                    .scope
+                    .offset(usize::try_from(offset).map_err(ParseError::from)?)
+                    .read::<OffsetTable<'_>>()?;
+                Cow::Owned(offset_table)
+            }
+        };
+
+        let tables = offset_table
+            .table_records
+            .iter()
+            .map(|record| {
+                let table = record.read_table(&font.scope)?;
+                Ok((record.table_tag, table.data().to_vec()))
+            })
+            .collect::<Result<_, ParseError>>()?;
+
+        Ok(FontTables {
+            sfnt_version: offset_table.sfnt_version,
+            tables,
+        })
+    }
+
+    fn table(&self, tag: u32) -> Result<&[u8], ParseError> {
+        self.tables
+            .get(&tag)
+            .map(Vec::as_slice)
+            .ok_or(ParseError::MissingValue)
+    }
+
+    fn to_data(&self) -> Result<Vec<u8>, ReadWriteError> {
+        let head = ReadScope::new(self.table(tag::HEAD)?).read::<HeadTable>()?;
+        let mut builder = FontBuilder::new(self.sfnt_version);
+        for (&tag, data) in &self.tables {
+            match tag {
+                tag::HEAD | tag::GLYF | tag::LOCA => (),
+                _ => {
+                    builder.add_table::<_, ReadScope<'_>>(tag, ReadScope::new(data), ())?;
+                }
+            }
+        }
+        let mut builder = builder.add_head_table(&head)?;
+
+        if self.tables.contains_key(&tag::GLYF) {
+            let maxp = ReadScope::new(self.table(tag::MAXP)?).read::<MaxpTable>()?;
+            let loca = ReadScope::new(self.table(tag::LOCA)?).read_dep::<LocaTable<'_>>((
+                usize::from(maxp.num_glyphs),
+                head.index_to_loc_format,
+            ))?;
+            let glyf = ReadScope::new(self.table(tag::GLYF)?).read_dep::<GlyfTable<'_>>(&loca)?;
+            builder.add_glyf_table(glyf)?;
+        }
+        builder.data()
+    }
+
+    fn write_table<HostType, T: WriteBinaryDep<HostType>>(
+        &mut self,
+        tag: u32,
+        table: HostType,
+        args: T::Args,
+    ) -> Result<T::Output, WriteError> {
+        let mut buffer = WriteBuffer::new();
+        let output = T::write_dep(&mut buffer, table, args)?;
+        self.tables.insert(tag, buffer.into_inner());
+        Ok(output)
+    }
+}
+
+impl FontTableProvider for FontTables {
+    fn table_data<'a>(&'a self, tag: u32) -> Result<Option<Cow<'a, [u8]>>, ParseError> {
+        Ok(self
+            .tables
+            .get(&tag)
+            .map(|data| Cow::Borrowed(data.as_slice())))
+    }
+
+    fn has_table(&self, tag: u32) -> bool {
+        self.tables.contains_key(&tag)
+    }
+
+    fn table_tags(&self) -> Option<Vec<u32>> {
+        Some(self.tables.keys().copied().collect())
+    }
+}
+
+fn subset_tables(
+    tables: &FontTables,
+    unicodes: &BTreeSet<u32>,
+) -> Result<FontTables, ReadWriteError> {
+    let cmap = ReadScope::new(tables.table(tag::CMAP)?).read::<Cmap<'_>>()?;
+    let subtable = match read_cmap_subtable(&cmap)? {
+        Some((Encoding::Unicode, subtable)) => subtable,
+        Some(_) => return Err(ReadWriteError::Read(ParseError::NotImplemented)),
+        None => return Err(ReadWriteError::Read(ParseError::MissingValue)),
+    };
+
+    let mut mappings = Vec::new();
+    for &ch in unicodes {
+        match subtable.map_glyph(ch)? {
+            Some(glyph_id) if glyph_id != 0 => mappings.push((ch, glyph_id)),
+            _ => (),
+        }
+    }
+
+    // glyph index 0 is the .notdef glyph, it must always be first
+    let mut glyph_ids = Vec::with_capacity(mappings.len() + 1);
+    glyph_ids.push(0);
+    glyph_ids.extend(mappings.iter().map(|&(_, glyph_id)| glyph_id));
+    glyph_ids.sort();
+    glyph_ids.dedup();
+
+    let data = subset::subset(tables, &glyph_ids, None)?;
+    let mut subset_tables = FontTables::from_data(&data)?;
+
+    // The subset font holds the requested glyphs in the order they were supplied, followed by any
+    // glyphs pulled in by composite glyphs.
+    let mappings = mappings
+        .into_iter()
+        .map(|(ch, glyph_id)| {
+            let new_id = glyph_ids.binary_search(&glyph_id).unwrap_or_default();
+            Ok((ch, u16::try_from(new_id)?))
+        })
+        .collect::<Result<Vec<_>, WriteError>>()?;
+    subset_tables.write_table::<_, cmap::owned::Cmap>(
+        tag::CMAP,
+        cmap::owned::create_unicode_cmap_table(&mappings),
+        (),
+    )?;
+
+    for &tag in &GLYPH_INDEPENDENT_TABLES {
+        if let (false, Some(data)) = (subset_tables.has_table(tag), tables.tables.get(&tag)) {
+            subset_tables.tables.insert(tag, data.clone());
+        }
+    }
+
+    Ok(subset_tables)
+}
+
+fn strip_hints(tables: &mut FontTables) -> Result<(), ReadWriteError> {
+    for tag in &HINTING_TABLES {
+        tables.tables.remove(tag);
+    }
+
+    let head = ReadScope::new(tables.table(tag::HEAD)?).read::<HeadTable>()?;
+    let mut maxp = ReadScope::new(tables.table(tag::MAXP)?).read::<MaxpTable>()?;
+    if tables.has_table(tag::GLYF) {
+        let stripped = {
+            let loca = ReadScope::new(tables.table(tag::LOCA)?).read_dep::<LocaTable<'_>>((
+                usize::from(maxp.num_glyphs),
+                head.index_to_loc_format,
+            ))?;
+            let glyf = ReadScope::new(tables.table(tag::GLYF)?).read_dep::<GlyfTable<'_>>(&loca)?;
+            glyf.records
+                .into_iter()
+                .map(strip_glyph_instructions)
+                .collect::<Result<Vec<_>, _>>()?
+        };
+        let glyf = GlyfTable {
+            records: stripped
+                .iter()
+                .map(|data| match data {
+                    Some(data) => GlyfRecord::Present(ReadScope::new(data)),
+                    None => GlyfRecord::Empty,
+                })
+                .collect(),
+        };
+        let loca =
+            tables.write_table::<_, GlyfTable<'_>>(tag::GLYF, glyf, head.index_to_loc_format)?;
+        tables.write_table::<_, loca::owned::LocaTable>(
+            tag::LOCA,
+            loca,
+            head.index_to_loc_format,
+        )?;
+    }
+
+    if let Some(sub_table) = &mut maxp.version1_sub_table {
+        sub_table.max_zones = 1;
+        sub_table.max_twilight_points = 0;
+        sub_table.max_storage = 0;
+        sub_table.max_function_defs = 0;
+        sub_table.max_instruction_defs = 0;
+        sub_table.max_stack_elements = 0;
+        sub_table.max_size_of_instructions = 0;
+        tables.write_table::<_, MaxpTable>(tag::MAXP, &maxp, ())?;
+    }
+
+    Ok(())
+}
+
+/// Returns the data of the glyph in `record` without its instructions, or `None` if the glyph
+/// is empty.
+fn strip_glyph_instructions(record: GlyfRecord<'_>) -> Result<Option<Vec<u8>>, ReadWriteError> {
+    let mut glyph = match record {
+        GlyfRecord::Empty => return Ok(None),
+        GlyfRecord::Present(scope) => {
+            let mut ctxt = scope.ctxt();
+            let number_of_contours = ctxt.read_i16be()?;
+            if number_of_contours >= 0 {
+                // Simple glyphs are rewritten byte-wise since writing a parsed glyph does not
+                // compact its points.
+                let data = scope.data();
+                let mut ctxt = scope.ctxt();
+                let header_len = 10 + 2 * usize::from(number_of_contours as u16);
+                ctxt.read_slice(header_len)?;
+                let instruction_length = usize::from(ctxt.read_u16be()?);
+                ctxt.read_slice(instruction_length)?;
+
+                let mut buffer = WriteBuffer::new();
+                buffer.write_bytes(&data[..header_len])?;
+                U16Be::write(&mut buffer, 0u16)?;
+                buffer.write_bytes(&data[header_len + 2 + instruction_length..])?;
+                return Ok(Some(buffer.into_inner()));
+            }
+            scope.read::<Glyph<'_>>()?
+        }
+        GlyfRecord::Parsed(glyph) => glyph,
+    };
+
+    match &mut glyph.data {
+        GlyphData::Simple(simple_glyph) => simple_glyph.instructions.clear(),
+        GlyphData::Composite {
+            glyphs,
+            instructions,
+        } => {
+            for composite_glyph in glyphs.iter_mut() {
+                composite_glyph
+                    .flags
+                    .remove(CompositeGlyphFlag::WE_HAVE_INSTRUCTIONS);
+            }
+            *instructions = Cow::Borrowed(&[]);
+        }
+    }
+    let mut buffer = WriteBuffer::new();
+    Glyph::write(&mut buffer, glyph)?;
+    Ok(Some(buffer.into_inner()))
+}
+
+fn minimize_metadata(tables: &mut FontTables) -> Result<(), ReadWriteError> {
+    tables.tables.remove(&tag::DSIG);
+
+    if tables.has_table(tag::NAME) {
+        let mut builder = {
+            let name = ReadScope::new(tables.table(tag::NAME)?).read::<NameTable<'_>>()?;
+            NameTableBuilder::from_table(&name)?
+        };
+        builder.retain_names(|name_id| ESSENTIAL_NAME_IDS.contains(&name_id));
+        tables.write_table::<_, NameTableBuilder>(tag::NAME, &builder, ())?;
+    }
+
+    if tables.has_table(tag::POST) {
+        let data = tables.table(tag::POST)?.to_vec();
+        let mut post = ReadScope::new(&data).read::<PostTable<'_>>()?;
+        // Version 3 does not contain glyph names
+        post.header.version = Version16Dot16::new(3, 0);
+        post.opt_sub_table = None;
+        tables.write_table::<_, PostTable<'_>>(tag::POST, &post, ())?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::read_fixture;
+    use crate::woff::WoffFont;
+    #[cfg(feature = "woff2-writer")]
+    use crate::woff2::Woff2Font;
+
+    fn glyph_count(provider: &impl FontTableProvider) -> u16 {
+        ReadScope::new(&provider.read_table_data(tag::MAXP).unwrap())
+            .read::<MaxpTable>()
+            .unwrap()
+            .num_glyphs
+    }
+
+    #[test]
+    fn optimize_subset_opentype() {
+        let buffer = read_fixture("tests/fonts/opentype/Ubuntu Mono with Numderline.ttf");
+        let mut options = WebFontOptions::default();
+        options.add_text("ab").format = WebFontFormat::OpenType;
+        let (data, report) = optimize(&buffer, &options).unwrap();
+
+        let steps = report
+            .steps
+            .iter()
+            .map(|step| step.step)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            steps,
+            vec![
+                OptimizeStep::Subset,
+                OptimizeStep::StripHints,
+                OptimizeStep::MinimizeMetadata,
+                OptimizeStep::Encode
+            ]
+        );
+        assert_eq!(report.steps[0].size_before, buffer.len());
+        assert_eq!(report.steps[3].size_after, data.len());
+        assert!(report.total_savings() > 0);
+
+        let font = ReadScope::new(&data).read::<OpenTypeFont<'_>>().unwrap();
+        let provider = font.table_provider(0).unwrap();
+        assert!(!provider.has_table(tag::FPGM));
+        assert!(!provider.has_table(tag::GSUB));
+        assert!(provider.has_table(tag::OS_2));
+        assert_eq!(glyph_count(&provider), 3);
+
+        let cmap_data = provider.read_table_data(tag::CMAP).unwrap();
+        let cmap = ReadScope::new(&cmap_data).read::<Cmap<'_>>().unwrap();
+        let (_, subtable) = read_cmap_subtable(&cmap).unwrap().unwrap();
+        assert_eq!(subtable.map_glyph(u32::from('a')).unwrap(), Some(1));
+        assert_eq!(subtable.map_glyph(u32::from('b')).unwrap(), Some(2));
+        assert_eq!(subtable.map_glyph(u32::from('c')).unwrap(), None);
+    }
+
+    #[test]
+    fn optimize_woff() {
+        let buffer = read_fixture("tests/fonts/opentype/Ubuntu Mono with Numderline.ttf");
+        let mut options = WebFontOptions::default();
+        options.add_unicode_range(0x20..=0x7E);
+        let (data, report) = optimize(&buffer, &options).unwrap();
+        assert_eq!(report.steps.last().unwrap().size_after, data.len());
+
+        let woff = ReadScope::new(&data).read::<WoffFont<'_>>().unwrap();
+        assert_eq!(
+            usize::try_from(woff.woff_header.length).unwrap(),
+            data.len()
+        );
+        let head = woff.read_table_data(tag::HEAD).unwrap();
+        assert!(ReadScope::new(&head).read::<HeadTable>().is_ok());
+        assert!(glyph_count(&woff) > 0);
+    }
+
+    #[cfg(feature = "woff2-writer")]
+    #[test]
+    fn optimize_woff2() {
+        let buffer = read_fixture("tests/fonts/opentype/Ubuntu Mono with Numderline.ttf");
+        let mut options = WebFontOptions {
+            format: WebFontFormat::Woff2,
+            ..WebFontOptions::default()
+        };
+        options.add_unicode_range(0x20..=0x7E);
+        let (data, report) = optimize(&buffer, &options).unwrap();
+        assert_eq!(report.steps.last().unwrap().size_after, data.len());
+
+        let woff2 = ReadScope::new(&data).read::<Woff2Font<'_>>().unwrap();
+        assert_eq!(
+            usize::try_from(woff2.woff_header.length).unwrap(),
+            data.len()
+        );
+        let provider = woff2.table_provider(0).unwrap();
+        let head = provider.read_table_data(tag::HEAD).unwrap();
+        assert!(ReadScope::new(&head).read::<HeadTable>().is_ok());
+        assert!(glyph_count(&provider) > 0);
+    }
+}
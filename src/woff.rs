@@ -1,19 +1,37 @@
-//! Reading of the WOFF font format.
+//! Reading and writing of the WOFF font format.
 
 use flate2::bufread::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
 
 use crate::binary::read::{ReadArray, ReadBinary, ReadBuf, ReadCtxt, ReadFrom, ReadScope};
-use crate::binary::U32Be;
-use crate::error::ParseError;
-use crate::tables::FontTableProvider;
+use crate::binary::write::{WriteBinary, WriteBuffer, WriteContext};
+use crate::binary::{long_align, U16Be, U32Be};
+use crate::error::{ParseError, ReadWriteError, WriteError};
+use crate::tables::{FontTableProvider, OpenTypeData, OpenTypeFont, TableRecord};
 
 use std::borrow::Cow;
 use std::convert::TryFrom;
-use std::io::Read;
+use std::io::{Read, Write};
 
 /// The magic number identifying a WOFF file: 'wOFF'
 pub const MAGIC: u32 = 0x774F4646;
 
+/// Size of the WOFF header.
+const HEADER_SIZE: usize = 44;
+
+/// Size of an entry in the WOFF table directory.
+const TABLE_DIRECTORY_ENTRY_SIZE: usize = 20;
+
+/// The optional blocks that follow the font tables of a WOFF or WOFF2 file.
+#[derive(Debug, Clone, Default)]
+pub struct WoffBlocks {
+    /// The extended metadata, an XML document describing the font. It is stored compressed.
+    pub extended_metadata: Option<String>,
+    /// Private data for use by the font designer or vendor. It is stored as is.
+    pub private_data: Option<Vec<u8>>,
+}
+
 #[derive(Clone)]
 pub struct WoffFont<'a> {
     pub scope: ReadScope<'a>,
@@ -113,6 +131,10 @@ impl<'a> FontTableProvider for WoffFont<'a> {
     fn has_table(&self, tag: u32) -> bool {
         self.find_table_directory_entry(tag).is_some()
     }
+
+    fn table_tags(&self) -> Option<Vec<u32>> {
+        Some(self.table_directory.iter().map(|entry| entry.tag).collect())
+    }
 }
 
 impl<'a> ReadBinary<'a> for WoffHeader {
@@ -176,6 +198,117 @@ impl<'a> ReadFrom<'a> for TableDirectoryEntry {
     }
 }
 
+/// Encode the OpenType font in `sfnt` as a WOFF 1.0 font, compressing each table with zlib.
+///
+/// Tables that don't get smaller when compressed are stored uncompressed. The extended metadata
+/// and private data in `blocks` follow the tables.
+pub fn write_woff(sfnt: &[u8], blocks: &WoffBlocks) -> Result<Vec<u8>, ReadWriteError> {
+    let font = ReadScope::new(sfnt).read::<OpenTypeFont<'_>>()?;
+    let offset_table = match &font.data {
+        OpenTypeData::Single(offset_table) => offset_table,
+        OpenTypeData::Collection(_) => return Err(ReadWriteError::Read(ParseError::BadVersion)),
+    };
+    let mut records = offset_table.table_records.iter().collect::<Vec<_>>();
+    records.sort_by_key(|record| record.table_tag);
+
+    let mut total_sfnt_size = 12 + 16 * records.len();
+    let mut tables: Vec<(TableRecord, usize, Cow<'_, [u8]>)> = Vec::with_capacity(records.len());
+    for record in records {
+        let data = record.read_table(&font.scope)?.data();
+        let compressed = zlib_compress(data)?;
+        let comp_data = if compressed.len() < data.len() {
+            Cow::Owned(compressed)
+        } else {
+            Cow::Borrowed(data)
+        };
+        total_sfnt_size += long_align(data.len());
+        tables.push((record, data.len(), comp_data));
+    }
+
+    let directory_end = HEADER_SIZE + TABLE_DIRECTORY_ENTRY_SIZE * tables.len();
+    let mut length = directory_end
+        + tables
+            .iter()
+            .map(|(_, _, comp_data)| long_align(comp_data.len()))
+            .sum::<usize>();
+    let metadata = blocks
+        .extended_metadata
+        .as_ref()
+        .map(|metadata| zlib_compress(metadata.as_bytes()))
+        .transpose()?;
+    let (meta_offset, meta_length) = match &metadata {
+        Some(compressed) => (length, compressed.len()),
+        None => (0, 0),
+    };
+    length += meta_length;
+    let (priv_offset, priv_length) = match &blocks.private_data {
+        Some(private_data) => {
+            // The private data block must start on a 4-byte boundary
+            length = long_align(length);
+            (length, private_data.len())
+        }
+        None => (0, 0),
+    };
+    length += priv_length;
+
+    let mut w = WriteBuffer::new();
+    U32Be::write(&mut w, MAGIC)?;
+    U32Be::write(&mut w, offset_table.sfnt_version)?;
+    U32Be::write(&mut w, u32_len(length)?)?;
+    U16Be::write(
+        &mut w,
+        u16::try_from(tables.len()).map_err(WriteError::from)?,
+    )?;
+    U16Be::write(&mut w, 0u16)?; // reserved
+    U32Be::write(&mut w, u32_len(total_sfnt_size)?)?;
+    U16Be::write(&mut w, 1u16)?; // majorVersion
+    U16Be::write(&mut w, 0u16)?; // minorVersion
+    U32Be::write(&mut w, u32_len(meta_offset)?)?;
+    U32Be::write(&mut w, u32_len(meta_length)?)?;
+    let meta_orig_length = blocks
+        .extended_metadata
+        .as_ref()
+        .map_or(0, |metadata| metadata.len());
+    U32Be::write(&mut w, u32_len(meta_orig_length)?)?;
+    U32Be::write(&mut w, u32_len(priv_offset)?)?;
+    U32Be::write(&mut w, u32_len(priv_length)?)?;
+
+    let mut offset = directory_end;
+    for (record, orig_length, comp_data) in &tables {
+        U32Be::write(&mut w, record.table_tag)?;
+        U32Be::write(&mut w, u32_len(offset)?)?;
+        U32Be::write(&mut w, u32_len(comp_data.len())?)?;
+        U32Be::write(&mut w, u32_len(*orig_length)?)?;
+        U32Be::write(&mut w, record.checksum)?;
+        offset += long_align(comp_data.len());
+    }
+    for (_, _, comp_data) in &tables {
+        w.write_bytes(comp_data)?;
+        w.write_zeros(long_align(comp_data.len()) - comp_data.len())?;
+    }
+    if let Some(metadata) = metadata {
+        w.write_bytes(&metadata)?;
+    }
+    if let Some(private_data) = &blocks.private_data {
+        w.write_zeros(priv_offset - w.len())?;
+        w.write_bytes(private_data)?;
+    }
+
+    Ok(w.into_inner())
+}
+
+fn zlib_compress(data: &[u8]) -> Result<Vec<u8>, ParseError> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+    encoder
+        .write_all(data)
+        .and_then(|()| encoder.finish())
+        .map_err(|_| ParseError::CompressionError)
+}
+
+fn u32_len(len: usize) -> Result<u32, WriteError> {
+    Ok(u32::try_from(len)?)
+}
+
 impl TableDirectoryEntry {
     fn is_compressed(&self) -> bool {
         self.comp_length != self.orig_length
@@ -1,6 +1,8 @@
-//! Reading of the WOFF2 font format.
+//! Reading and writing of the WOFF2 font format.
 
 mod collection;
+#[cfg(feature = "woff2-writer")]
+mod encode;
 mod lut;
 
 use std::borrow::Cow;
@@ -15,8 +17,9 @@ use self::lut::{XYTriplet, COORD_LUT, KNOWN_TABLE_TAGS};
 use crate::binary::read::{
     ReadArray, ReadArrayCow, ReadBinary, ReadBinaryDep, ReadBuf, ReadCtxt, ReadFrom, ReadScope,
 };
+use crate::binary::write::{WriteBinary, WriteContext};
 use crate::binary::{write, I16Be, U16Be, U8};
-use crate::error::{ParseError, ReadWriteError};
+use crate::error::{ParseError, ReadWriteError, WriteError};
 use crate::tables::glyf::{
     BoundingBox, CompositeGlyphs, GlyfRecord, GlyfTable, Glyph, GlyphData, Point, SimpleGlyph,
     SimpleGlyphFlag,
@@ -28,6 +31,9 @@ use crate::tables::{
 };
 use crate::{read_table, tag};
 
+#[cfg(feature = "woff2-writer")]
+pub use self::encode::write_woff2;
+
 pub const MAGIC: u32 = 0x774F4632; /* wOF2 */
 // This is the default size of the buffer in the brotli crate.
 // There's no guidance on how to choose this value.
@@ -244,6 +250,12 @@ impl FontTableProvider for Woff2TableProvider {
     fn has_table(&self, tag: u32) -> bool {
         self.tables.contains_key(&tag)
     }
+
+    fn table_tags(&self) -> Option<Vec<u32>> {
+        let mut tags = self.tables.keys().copied().collect::<Vec<_>>();
+        tags.sort();
+        Some(tags)
+    }
 }
 
 impl<'a> ReadBinary<'a> for Woff2Header {
@@ -306,7 +318,7 @@ impl<'a> ReadBinaryDep<'a> for TableDirectoryEntry {
         } else {
             Ok(KNOWN_TABLE_TAGS[usize::from(flags & BITS_0_TO_5)])
         }?;
-        let transformation_version = flags & 0xC0;
+        let transformation_version = (flags & 0xC0) >> 6;
         let orig_length = ctxt.read::<U32Base128>()?;
 
         let transform_length = match (transformation_version, tag) {
@@ -433,7 +445,7 @@ impl<'a> ReadBinaryDep<'a> for Woff2GlyfTable {
                             bounding_box,
                             data: GlyphData::Composite {
                                 glyphs: glyphs.glyphs,
-                                instructions,
+                                instructions: Cow::Borrowed(instructions),
                             },
                         })
                     }
@@ -627,6 +639,44 @@ impl<'a> ReadBinary<'a> for U32Base128 {
     }
 }
 
+impl WriteBinary<u16> for PackedU16 {
+    type Output = ();
+
+    fn write<C: WriteContext>(ctxt: &mut C, value: u16) -> Result<(), WriteError> {
+        if value < LOWEST_UCODE {
+            U8::write(ctxt, value as u8)
+        } else if value < LOWEST_UCODE * 2 {
+            U8::write(ctxt, 255u8)?;
+            U8::write(ctxt, (value - LOWEST_UCODE) as u8)
+        } else if value < LOWEST_UCODE * 2 + 256 {
+            U8::write(ctxt, 254u8)?;
+            U8::write(ctxt, (value - LOWEST_UCODE * 2) as u8)
+        } else {
+            U8::write(ctxt, 253u8)?;
+            U16Be::write(ctxt, value)
+        }
+    }
+}
+
+impl WriteBinary<u32> for U32Base128 {
+    type Output = ();
+
+    fn write<C: WriteContext>(ctxt: &mut C, value: u32) -> Result<(), WriteError> {
+        // The number of 7-bit groups needed to hold the value, with no leading zero groups
+        let mut len = 1;
+        while len < 5 && value >> (7 * len) != 0 {
+            len += 1;
+        }
+        for i in (0..len).rev() {
+            let byte = ((value >> (7 * i)) & 0x7F) as u8;
+            // The high bit is set on every byte but the last
+            let continuation = if i > 0 { 0x80 } else { 0 };
+            U8::write(ctxt, byte | continuation)?;
+        }
+        Ok(())
+    }
+}
+
 impl<'a> ReadFrom<'a> for HmtxTableFlag {
     type ReadType = U8;
 
@@ -0,0 +1,367 @@
+//! Encoding of fonts in the WOFF2 font format.
+
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::io::Cursor;
+
+use brotli::enc::backward_references::BrotliEncoderMode;
+use brotli::enc::BrotliEncoderParams;
+
+use super::lut::{COORD_LUT, KNOWN_TABLE_TAGS};
+use super::{PackedU16, U32Base128, BITS_0_TO_5, MAGIC};
+use crate::binary::read::ReadScope;
+use crate::binary::write::{WriteBinary, WriteBuffer, WriteContext};
+use crate::binary::{long_align, I16Be, U16Be, U32Be, U8};
+use crate::error::{ParseError, ReadWriteError, WriteError};
+use crate::tables::glyf::{
+    BoundingBox, CompositeGlyph, GlyfRecord, GlyfTable, Glyph, GlyphData, Point,
+};
+use crate::tables::loca::LocaTable;
+use crate::tables::{
+    FontTableProvider, HeadTable, IndexToLocFormat, MaxpTable, OpenTypeData, OpenTypeFont,
+};
+use crate::tag;
+use crate::woff::WoffBlocks;
+
+/// Size of the WOFF2 header.
+const HEADER_SIZE: usize = 48;
+/// Transformation version indicating that a `glyf` or `loca` table is not transformed.
+const NULL_TRANSFORM: u8 = 3;
+/// The highest Brotli compression quality.
+const BROTLI_QUALITY: i32 = 11;
+
+/// A table of a WOFF2 font being written.
+struct Woff2Table<'a> {
+    tag: u32,
+    orig_length: usize,
+    data: Cow<'a, [u8]>,
+    transform_length: Option<usize>,
+}
+
+/// Encode the OpenType font in `sfnt` as a WOFF 2.0 font.
+///
+/// The tables are compressed together with Brotli. When `transform_glyf` is set the `glyf` and
+/// `loca` tables of TrueType fonts are stored in the transformed form defined by the WOFF2
+/// specification, which compresses better. The extended metadata and private data in `blocks`
+/// follow the tables.
+pub fn write_woff2(
+    sfnt: &[u8],
+    transform_glyf: bool,
+    blocks: &WoffBlocks,
+) -> Result<Vec<u8>, ReadWriteError> {
+    let font = ReadScope::new(sfnt).read::<OpenTypeFont<'_>>()?;
+    let offset_table = match &font.data {
+        OpenTypeData::Single(offset_table) => offset_table,
+        OpenTypeData::Collection(_) => return Err(ReadWriteError::Read(ParseError::BadVersion)),
+    };
+
+    let mut tables = offset_table
+        .table_records
+        .iter()
+        .map(|record| {
+            let data = record.read_table(&font.scope)?.data();
+            Ok(Woff2Table {
+                tag: record.table_tag,
+                orig_length: data.len(),
+                data: Cow::Borrowed(data),
+                transform_length: None,
+            })
+        })
+        .collect::<Result<Vec<_>, ParseError>>()?;
+    tables.sort_by_key(|table| table.tag);
+
+    // The loca table must immediately follow the glyf table
+    let glyf_index = tables.iter().position(|table| table.tag == tag::GLYF);
+    let loca_index = tables.iter().position(|table| table.tag == tag::LOCA);
+    if let (Some(glyf_index), Some(loca_index)) = (glyf_index, loca_index) {
+        let loca = tables.remove(loca_index);
+        let glyf_index = if loca_index < glyf_index {
+            glyf_index - 1
+        } else {
+            glyf_index
+        };
+        tables.insert(glyf_index + 1, loca);
+
+        if transform_glyf {
+            let provider = font.table_provider(0)?;
+            let transformed = transform_glyf_table(&provider)?;
+            let glyf = &mut tables[glyf_index];
+            glyf.transform_length = Some(transformed.len());
+            glyf.data = Cow::Owned(transformed);
+            let loca = &mut tables[glyf_index + 1];
+            loca.transform_length = Some(0);
+            loca.data = Cow::Borrowed(&[]);
+        }
+    }
+
+    let mut directory = WriteBuffer::new();
+    let mut table_data = Vec::new();
+    let mut total_sfnt_size = 12 + 16 * tables.len();
+    for table in &tables {
+        let known_tag = KNOWN_TABLE_TAGS.iter().position(|&tag| tag == table.tag);
+        let transformation_version = match (table.tag, table.transform_length) {
+            (tag::GLYF, None) | (tag::LOCA, None) => NULL_TRANSFORM,
+            _ => 0,
+        };
+        match known_tag {
+            Some(index) => U8::write(&mut directory, index as u8 | transformation_version << 6)?,
+            None => {
+                U8::write(&mut directory, BITS_0_TO_5 | transformation_version << 6)?;
+                U32Be::write(&mut directory, table.tag)?;
+            }
+        }
+        U32Base128::write(&mut directory, u32_len(table.orig_length)?)?;
+        if let Some(transform_length) = table.transform_length {
+            U32Base128::write(&mut directory, u32_len(transform_length)?)?;
+        }
+        table_data.extend_from_slice(&table.data);
+        total_sfnt_size += long_align(table.orig_length);
+    }
+    let compressed = brotli_compress(&table_data, BrotliEncoderMode::BROTLI_MODE_FONT)?;
+
+    let mut length = long_align(HEADER_SIZE + directory.len() + compressed.len());
+    let metadata = blocks
+        .extended_metadata
+        .as_ref()
+        .map(|metadata| brotli_compress(metadata.as_bytes(), BrotliEncoderMode::BROTLI_MODE_TEXT))
+        .transpose()?;
+    let (meta_offset, meta_length) = match &metadata {
+        Some(compressed) => (length, compressed.len()),
+        None => (0, 0),
+    };
+    length += meta_length;
+    let (priv_offset, priv_length) = match &blocks.private_data {
+        Some(private_data) => {
+            // The private data block must start on a 4-byte boundary
+            length = long_align(length);
+            (length, private_data.len())
+        }
+        None => (0, 0),
+    };
+    length += priv_length;
+
+    let mut w = WriteBuffer::new();
+    U32Be::write(&mut w, MAGIC)?;
+    U32Be::write(&mut w, offset_table.sfnt_version)?;
+    U32Be::write(&mut w, u32_len(length)?)?;
+    U16Be::write(
+        &mut w,
+        u16::try_from(tables.len()).map_err(WriteError::from)?,
+    )?;
+    U16Be::write(&mut w, 0u16)?; // reserved
+    U32Be::write(&mut w, u32_len(total_sfnt_size)?)?;
+    U32Be::write(&mut w, u32_len(compressed.len())?)?;
+    U16Be::write(&mut w, 1u16)?; // majorVersion
+    U16Be::write(&mut w, 0u16)?; // minorVersion
+    U32Be::write(&mut w, u32_len(meta_offset)?)?;
+    U32Be::write(&mut w, u32_len(meta_length)?)?;
+    let meta_orig_length = blocks
+        .extended_metadata
+        .as_ref()
+        .map_or(0, |metadata| metadata.len());
+    U32Be::write(&mut w, u32_len(meta_orig_length)?)?;
+    U32Be::write(&mut w, u32_len(priv_offset)?)?;
+    U32Be::write(&mut w, u32_len(priv_length)?)?;
+    w.write_bytes(directory.bytes())?;
+    w.write_bytes(&compressed)?;
+    w.write_zeros(long_align(w.len()) - w.len())?;
+    if let Some(metadata) = metadata {
+        w.write_bytes(&metadata)?;
+    }
+    if let Some(private_data) = &blocks.private_data {
+        w.write_zeros(priv_offset - w.len())?;
+        w.write_bytes(private_data)?;
+    }
+
+    Ok(w.into_inner())
+}
+
+/// Build the transformed `glyf` table of the font in `provider`, which replaces both the `glyf`
+/// and `loca` tables.
+fn transform_glyf_table(provider: &impl FontTableProvider) -> Result<Vec<u8>, ReadWriteError> {
+    let head = ReadScope::new(&provider.read_table_data(tag::HEAD)?).read::<HeadTable>()?;
+    let maxp = ReadScope::new(&provider.read_table_data(tag::MAXP)?).read::<MaxpTable>()?;
+    let loca_data = provider.read_table_data(tag::LOCA)?;
+    let loca = ReadScope::new(&loca_data)
+        .read_dep::<LocaTable<'_>>((usize::from(maxp.num_glyphs), head.index_to_loc_format))?;
+    let glyf_data = provider.read_table_data(tag::GLYF)?;
+    let glyf = ReadScope::new(&glyf_data).read_dep::<GlyfTable<'_>>(&loca)?;
+
+    let num_glyphs = glyf.records.len();
+    let mut n_contour_stream = WriteBuffer::new();
+    let mut n_points_stream = WriteBuffer::new();
+    let mut flag_stream = WriteBuffer::new();
+    let mut glyph_stream = WriteBuffer::new();
+    let mut composite_stream = WriteBuffer::new();
+    // One bit per glyph, padded to a multiple of 32 bits
+    let bbox_bitmap_words = num_glyphs / 32 + usize::from(num_glyphs % 32 != 0);
+    let mut bbox_bitmap = vec![0u8; 4 * bbox_bitmap_words];
+    let mut bbox_stream = WriteBuffer::new();
+    let mut instruction_stream = WriteBuffer::new();
+
+    for (index, record) in glyf.records.into_iter().enumerate() {
+        let glyph = match record {
+            GlyfRecord::Empty => None,
+            GlyfRecord::Present(scope) => Some(scope.read::<Glyph<'_>>()?),
+            GlyfRecord::Parsed(glyph) => Some(glyph),
+        };
+        let glyph = match glyph {
+            Some(glyph) => glyph,
+            None => {
+                I16Be::write(&mut n_contour_stream, 0i16)?;
+                continue;
+            }
+        };
+
+        if let GlyphData::Simple(simple_glyph) = &glyph.data {
+            if simple_glyph.end_pts_of_contours.is_empty() {
+                I16Be::write(&mut n_contour_stream, 0i16)?;
+                continue;
+            }
+        }
+
+        let explicit_bbox = match glyph.data {
+            GlyphData::Simple(simple_glyph) => {
+                let number_of_contours = simple_glyph.end_pts_of_contours.len();
+                I16Be::write(
+                    &mut n_contour_stream,
+                    i16::try_from(number_of_contours).map_err(WriteError::from)?,
+                )?;
+                let mut start = 0u16;
+                for &end in &simple_glyph.end_pts_of_contours {
+                    let n_points = (end + 1).checked_sub(start).ok_or(WriteError::BadValue)?;
+                    PackedU16::write(&mut n_points_stream, n_points)?;
+                    start = end + 1;
+                }
+
+                let mut last_point = Point(0, 0);
+                for (flag, &point) in simple_glyph.flags.iter().zip(&simple_glyph.coordinates) {
+                    let dx = i32::from(point.0) - i32::from(last_point.0);
+                    let dy = i32::from(point.1) - i32::from(last_point.1);
+                    encode_triplet(
+                        flag.is_on_curve(),
+                        dx,
+                        dy,
+                        &mut flag_stream,
+                        &mut glyph_stream,
+                    )?;
+                    last_point = point;
+                }
+
+                let instruction_length =
+                    u16::try_from(simple_glyph.instructions.len()).map_err(WriteError::from)?;
+                PackedU16::write(&mut glyph_stream, instruction_length)?;
+                instruction_stream.write_bytes(&simple_glyph.instructions)?;
+
+                // The bounding box is only stored when it can't be computed from the points
+                glyph.bounding_box != simple_glyph.bounding_box()
+            }
+            GlyphData::Composite {
+                glyphs,
+                instructions,
+            } => {
+                I16Be::write(&mut n_contour_stream, -1i16)?;
+                let mut have_instructions = false;
+                for composite_glyph in glyphs {
+                    have_instructions |= composite_glyph.flags.we_have_instructions();
+                    CompositeGlyph::write(&mut composite_stream, composite_glyph)?;
+                }
+                if have_instructions {
+                    let instruction_length =
+                        u16::try_from(instructions.len()).map_err(WriteError::from)?;
+                    PackedU16::write(&mut glyph_stream, instruction_length)?;
+                    instruction_stream.write_bytes(&instructions)?;
+                }
+
+                // Composite glyphs must have an explicit bounding box
+                true
+            }
+        };
+        if explicit_bbox {
+            bbox_bitmap[index / 8] |= 0x80 >> (index % 8);
+            BoundingBox::write(&mut bbox_stream, glyph.bounding_box)?;
+        }
+    }
+
+    let index_format = match head.index_to_loc_format {
+        IndexToLocFormat::Short => 0u16,
+        IndexToLocFormat::Long => 1u16,
+    };
+    let mut w = WriteBuffer::new();
+    U16Be::write(&mut w, 0u16)?; // reserved
+    U16Be::write(&mut w, 0u16)?; // optionFlags
+    U16Be::write(&mut w, u16::try_from(num_glyphs).map_err(WriteError::from)?)?;
+    U16Be::write(&mut w, index_format)?;
+    U32Be::write(&mut w, u32_len(n_contour_stream.len())?)?;
+    U32Be::write(&mut w, u32_len(n_points_stream.len())?)?;
+    U32Be::write(&mut w, u32_len(flag_stream.len())?)?;
+    U32Be::write(&mut w, u32_len(glyph_stream.len())?)?;
+    U32Be::write(&mut w, u32_len(composite_stream.len())?)?;
+    U32Be::write(&mut w, u32_len(bbox_bitmap.len() + bbox_stream.len())?)?;
+    U32Be::write(&mut w, u32_len(instruction_stream.len())?)?;
+    w.write_bytes(n_contour_stream.bytes())?;
+    w.write_bytes(n_points_stream.bytes())?;
+    w.write_bytes(flag_stream.bytes())?;
+    w.write_bytes(glyph_stream.bytes())?;
+    w.write_bytes(composite_stream.bytes())?;
+    w.write_bytes(&bbox_bitmap)?;
+    w.write_bytes(bbox_stream.bytes())?;
+    w.write_bytes(instruction_stream.bytes())?;
+    Ok(w.into_inner())
+}
+
+/// Encode the point delta `(dx, dy)` using the first, and so smallest, triplet encoding in
+/// `COORD_LUT` that can represent it.
+fn encode_triplet(
+    on_curve: bool,
+    dx: i32,
+    dy: i32,
+    flag_stream: &mut WriteBuffer,
+    glyph_stream: &mut WriteBuffer,
+) -> Result<(), WriteError> {
+    let fits = |delta: i32, bits: u8, offset: u16, is_negative: bool| {
+        if delta != 0 && (delta < 0) != is_negative {
+            return None;
+        }
+        let value = delta.abs() - i32::from(offset);
+        if value >= 0 && value < 1 << bits {
+            Some(value as u32)
+        } else {
+            None
+        }
+    };
+
+    for (index, triplet) in COORD_LUT.iter().enumerate() {
+        let x = fits(dx, triplet.x_bits, triplet.delta_x, triplet.x_is_negative);
+        let y = fits(dy, triplet.y_bits, triplet.delta_y, triplet.y_is_negative);
+        if let (Some(x), Some(y)) = (x, y) {
+            let bit_count = triplet.byte_count * 8;
+            let data = (x << (bit_count - triplet.x_bits))
+                | (y << (bit_count - triplet.x_bits - triplet.y_bits));
+            // The most significant bit of the flag is clear for on-curve points
+            let on_curve_bit = if on_curve { 0 } else { 0x80 };
+            U8::write(flag_stream, index as u8 | on_curve_bit)?;
+            let bytes = data.to_be_bytes();
+            glyph_stream.write_bytes(&bytes[4 - usize::from(triplet.byte_count)..])?;
+            return Ok(());
+        }
+    }
+
+    Err(WriteError::BadValue)
+}
+
+fn brotli_compress(data: &[u8], mode: BrotliEncoderMode) -> Result<Vec<u8>, ParseError> {
+    let params = BrotliEncoderParams {
+        quality: BROTLI_QUALITY,
+        mode,
+        ..BrotliEncoderParams::default()
+    };
+    let mut compressed = Vec::new();
+    brotli::BrotliCompress(&mut Cursor::new(data), &mut compressed, &params)
+        .map_err(|_err| ParseError::CompressionError)?;
+    Ok(compressed)
+}
+
+fn u32_len(len: usize) -> Result<u32, WriteError> {
+    Ok(u32::try_from(len)?)
+}
@@ -207,7 +207,7 @@ fn gpos_test(
     }
 
     // Apply GPOS
-    // Rc<RefCell<LayoutCacheData<T>>> LayoutCache<GSUB>;
+    // Arc<LayoutCacheData<T>> LayoutCache<GPOS>;
     let cache = new_layout_cache(gpos_table);
     let script = cache
         .layout_table
@@ -225,6 +225,7 @@ fn gpos_test(
         opt_gdef_table.as_ref(),
         &langsys,
         &[features],
+        &gpos::DeviceContext::default(),
         &mut infos,
     )
     .unwrap();
@@ -331,9 +332,11 @@ fn make_direct_glyph(glyph_index: u16) -> RawGlyph<()> {
         glyph_origin: GlyphOrigin::Direct,
         small_caps: false,
         multi_subst_dup: false,
+        multi_subst_component: None,
         is_vert_alt: false,
         fake_bold: false,
         fake_italic: false,
+        stretch: None,
         extra_data: (),
         variation: None,
     }
@@ -13,7 +13,7 @@ mod arabic_tests {
     use allsorts::tables::OpenTypeFont;
     use allsorts::tag;
     use allsorts::Font;
-    use std::rc::Rc;
+    use std::sync::Arc;
 
     #[test]
     fn no_gsub_change() {
@@ -236,7 +236,7 @@ mod arabic_tests {
                         "\u{64a}\u{64e}\u{651}\u{629}",
                     ),
                     vec![
-                        42, 698, 701, 1339, 991, 80, 942, 76, 503, 78, 482, 76, 79, 524,
+                        42, 698, 701, 1339, 991, 80, 942, 76, 503, 78, 482, 79, 76, 524,
                     ],
                 ),
                 (
@@ -499,7 +499,7 @@ mod arabic_tests {
                 font.gdef_table()
                     .expect("Error getting GDEF table")
                     .as_ref()
-                    .map(Rc::as_ref),
+                    .map(Arc::as_ref),
                 tag::ARAB,
                 lang_tag,
                 &mut raw_glyphs,
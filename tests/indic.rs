@@ -7,7 +7,7 @@ mod shape;
 
 use std::io::BufRead;
 use std::path::Path;
-use std::rc::Rc;
+use std::sync::Arc;
 
 use lazy_static::lazy_static;
 use regex::Regex;
@@ -79,7 +79,7 @@ fn shape_ttf_indic<'a, T: FontTableProvider>(
         gsub::apply(
             dotted_circle_index,
             &gsub_cache,
-            gdef_table.as_ref().map(Rc::as_ref),
+            gdef_table.as_ref().map(Arc::as_ref),
             script_tag,
             opt_lang_tag,
             &Features::Mask(GsubFeatureMask::default()),
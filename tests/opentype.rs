@@ -7,7 +7,7 @@ mod shape;
 
 use std::convert::TryFrom;
 use std::path::Path;
-use std::rc::Rc;
+use std::sync::Arc;
 
 use allsorts::binary::read::ReadScope;
 use allsorts::error::ShapingError;
@@ -235,7 +235,7 @@ fn shape<'a, T: FontTableProvider>(
     gsub::apply(
         dotted_circle_index,
         &gsub_cache,
-        gdef_table.as_ref().map(Rc::as_ref),
+        gdef_table.as_ref().map(Arc::as_ref),
         script_tag,
         opt_lang_tag,
         &Features::Mask(GsubFeatureMask::default()),
@@ -35,9 +35,11 @@ pub fn make_glyph(ch: char, glyph_index: u16) -> RawGlyph<()> {
         glyph_origin: GlyphOrigin::Char(ch),
         small_caps: false,
         multi_subst_dup: false,
+        multi_subst_component: None,
         is_vert_alt: false,
         fake_bold: false,
         fake_italic: false,
+        stretch: None,
         extra_data: (),
         variation: None,
     }
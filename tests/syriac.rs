@@ -13,7 +13,7 @@ mod syriac_tests {
     use allsorts::tables::OpenTypeFont;
     use allsorts::tag;
     use allsorts::Font;
-    use std::rc::Rc;
+    use std::sync::Arc;
 
     #[test]
     fn no_gsub_change() {
@@ -69,6 +69,16 @@ mod syriac_tests {
         ])
     }
 
+    #[test]
+    fn abbreviation_mark() {
+        // Fonts without a `stch` feature leave the Syriac Abbreviation Mark unchanged
+        test(vec![(
+            "tests/fonts/noto/NotoSansSyriacEastern-Regular.ttf",
+            "\u{70F}\u{700}\u{700}",
+            vec![139, 125, 125],
+        )])
+    }
+
     #[test]
     fn rlig() {
         // Each test contains at least one glyph that should trigger `GSUB`'s `RLIG` feature
@@ -492,7 +502,7 @@ mod syriac_tests {
                 font.gdef_table()
                     .expect("Error getting GDEF table")
                     .as_ref()
-                    .map(Rc::as_ref),
+                    .map(Arc::as_ref),
                 tag::SYRC,
                 None,
                 &mut raw_glyphs,
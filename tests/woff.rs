@@ -4,8 +4,9 @@
 mod common;
 
 use allsorts::binary::read::ReadScope;
-use allsorts::error::ParseError;
-use allsorts::woff::WoffFont;
+use allsorts::error::ParseErrorKind;
+use allsorts::tables::{FontTableProvider, OpenTypeData, OpenTypeFont};
+use allsorts::woff::{write_woff, WoffBlocks, WoffFont};
 
 use crate::common::read_fixture;
 
@@ -71,7 +72,7 @@ fn test_header_reserved001() {
 
     match result {
         Ok(_) => panic!("expected Err got Ok"),
-        Err(ParseError::BadValue) => (),
+        Err(err) if err.kind() == ParseErrorKind::BadValue => (),
         Err(err) => panic!("expected ParseError::BadValue got {:?}", err),
     }
 }
@@ -91,7 +92,7 @@ fn test_directory_overlaps_001() {
 
     match result {
         Ok(_) => panic!("expected Err got Ok"),
-        Err(ParseError::BadOffset) => (),
+        Err(err) if err.kind() == ParseErrorKind::BadOffset => (),
         Err(err) => panic!("expected ParseError::BadOffset got {:?}", err),
     }
 }
@@ -111,7 +112,43 @@ fn test_tabledata_zlib_001() {
 
     match result {
         Ok(_) => panic!("expected Err got Ok"),
-        Err(ParseError::CompressionError) => (),
+        Err(err) if err.kind() == ParseErrorKind::CompressionError => (),
         Err(err) => panic!("expected ParseError::CompressionError got {:?}", err),
     }
 }
+
+#[test]
+fn test_write_woff_round_trip() {
+    let buffer = read_fixture("tests/fonts/opentype/test-font.ttf");
+    let font_file = ReadScope::new(&buffer).read::<OpenTypeFont>().unwrap();
+    let offset_table = match font_file.data {
+        OpenTypeData::Single(offset_table) => offset_table,
+        OpenTypeData::Collection(_) => unreachable!(),
+    };
+    let blocks = WoffBlocks {
+        extended_metadata: Some(metadata()),
+        private_data: Some(vec![1, 2, 3]),
+    };
+    let woff = write_woff(&buffer, &blocks).unwrap();
+    let woff_file = ReadScope::new(&woff).read::<WoffFont>().unwrap();
+
+    assert_eq!(woff_file.flavor(), offset_table.sfnt_version);
+    assert_eq!(woff_file.extended_metadata().unwrap(), Some(metadata()));
+    assert_eq!(woff_file.woff_header.length as usize, woff.len());
+    assert_eq!(woff_file.woff_header.priv_offset % 4, 0);
+    let priv_offset = woff_file.woff_header.priv_offset as usize;
+    let priv_length = woff_file.woff_header.priv_length as usize;
+    assert_eq!(&woff[priv_offset..priv_offset + priv_length], &[1, 2, 3]);
+
+    for table_record in offset_table.table_records.iter() {
+        let table_tag = table_record.table_tag;
+        assert_eq!(
+            woff_file.read_table_data(table_tag).unwrap(),
+            offset_table
+                .read_table(&font_file.scope, table_tag)
+                .unwrap()
+                .unwrap()
+                .data()
+        );
+    }
+}
@@ -3,6 +3,7 @@
 #[path = "common.rs"]
 mod common;
 
+use std::borrow::Cow;
 use std::path::Path;
 
 use allsorts::binary::read::ReadScope;
@@ -224,7 +225,7 @@ fn test_woff2_transformed_glyf_table_composite_glyph() {
                     scale: None,
                 },
             ],
-            instructions: &[],
+            instructions: Cow::Borrowed(&[]),
         },
     };
 
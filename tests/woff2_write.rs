@@ -0,0 +1,134 @@
+// Workaround rustfmt bug:
+// https://github.com/rust-lang/rustfmt/issues/3794
+#[path = "common.rs"]
+mod common;
+
+use std::borrow::Cow;
+
+use allsorts::binary::read::ReadScope;
+use allsorts::font_data::FontData;
+use allsorts::tables::glyf::{
+    GlyfRecord, GlyfTable, Glyph, GlyphData, SimpleGlyph, SimpleGlyphFlag,
+};
+use allsorts::tables::loca::LocaTable;
+use allsorts::tables::{FontTableProvider, HeadTable, MaxpTable};
+use allsorts::tag;
+use allsorts::woff::WoffBlocks;
+use allsorts::woff2::{write_woff2, Woff2Font};
+
+use crate::common::read_fixture;
+
+// Strip the parts of a glyph that are not preserved by the WOFF2 glyf transform
+fn normalise_glyph(glyph: Glyph<'_>) -> Glyph<'static> {
+    let data = match glyph.data {
+        GlyphData::Simple(simple) => GlyphData::Simple(SimpleGlyph {
+            flags: simple
+                .flags
+                .iter()
+                .map(|&flag| flag & SimpleGlyphFlag::ON_CURVE_POINT)
+                .collect(),
+            ..simple
+        }),
+        GlyphData::Composite { glyphs, .. } => GlyphData::Composite {
+            glyphs,
+            instructions: Cow::Borrowed(&[]),
+        },
+    };
+    Glyph {
+        number_of_contours: glyph.number_of_contours,
+        bounding_box: glyph.bounding_box,
+        data,
+    }
+}
+
+fn read_glyphs(provider: &impl FontTableProvider) -> Vec<Option<Glyph<'static>>> {
+    let head_data = provider.read_table_data(tag::HEAD).unwrap();
+    let head = ReadScope::new(&head_data).read::<HeadTable>().unwrap();
+    let maxp_data = provider.read_table_data(tag::MAXP).unwrap();
+    let maxp = ReadScope::new(&maxp_data).read::<MaxpTable>().unwrap();
+    let loca_data = provider.read_table_data(tag::LOCA).unwrap();
+    let loca = ReadScope::new(&loca_data)
+        .read_dep::<LocaTable>((usize::from(maxp.num_glyphs), head.index_to_loc_format))
+        .unwrap();
+    let glyf_data = provider.read_table_data(tag::GLYF).unwrap();
+    let glyf = ReadScope::new(&glyf_data)
+        .read_dep::<GlyfTable>(&loca)
+        .unwrap();
+    glyf.records
+        .into_iter()
+        .map(|record| match record {
+            GlyfRecord::Empty => None,
+            GlyfRecord::Present(scope) => Some(normalise_glyph(scope.read::<Glyph>().unwrap())),
+            GlyfRecord::Parsed(glyph) => Some(normalise_glyph(glyph)),
+        })
+        .collect()
+}
+
+#[test]
+fn test_write_woff2_round_trip() {
+    let buffer = read_fixture("tests/fonts/opentype/test-font.ttf");
+    let font_file = ReadScope::new(&buffer).read::<FontData<'_>>().unwrap();
+    let provider = font_file.table_provider(0).unwrap();
+    let expected_glyphs = read_glyphs(&provider);
+
+    for &transform_glyf in &[false, true] {
+        let woff2 = write_woff2(&buffer, transform_glyf, &WoffBlocks::default()).unwrap();
+        let woff2_font = ReadScope::new(&woff2).read::<Woff2Font>().unwrap();
+        let woff2_provider = woff2_font.table_provider(0).unwrap();
+
+        let glyf_entry = woff2_font.find_table_entry(tag::GLYF, 0).unwrap();
+        assert_eq!(glyf_entry.transform_length.is_some(), transform_glyf);
+        for &table in &[tag::HEAD, tag::MAXP, tag::CMAP, tag::HMTX] {
+            let mut actual = woff2_provider.read_table_data(table).unwrap().into_owned();
+            let mut expected = provider.read_table_data(table).unwrap().into_owned();
+            if table == tag::HEAD {
+                // The head table is rebuilt when the glyf table is transformed, which leaves
+                // checkSumAdjustment to be recalculated by the writer of the font
+                actual[8..12].copy_from_slice(&[0; 4]);
+                expected[8..12].copy_from_slice(&[0; 4]);
+            }
+            assert_eq!(actual, expected);
+        }
+        assert_eq!(read_glyphs(&woff2_provider), expected_glyphs);
+    }
+}
+
+#[test]
+fn test_write_woff2_cff() {
+    let buffer = read_fixture("tests/fonts/opentype/Klei.otf");
+    let font_file = ReadScope::new(&buffer).read::<FontData<'_>>().unwrap();
+    let provider = font_file.table_provider(0).unwrap();
+
+    let woff2 = write_woff2(&buffer, true, &WoffBlocks::default()).unwrap();
+    let woff2_font = ReadScope::new(&woff2).read::<Woff2Font>().unwrap();
+    let woff2_provider = woff2_font.table_provider(0).unwrap();
+
+    assert_eq!(woff2_font.flavor(), allsorts::tables::CFF_MAGIC);
+    assert_eq!(
+        woff2_provider.read_table_data(tag::CFF).unwrap(),
+        provider.read_table_data(tag::CFF).unwrap()
+    );
+}
+
+#[test]
+fn test_write_woff2_blocks() {
+    let buffer = read_fixture("tests/fonts/opentype/test-font.ttf");
+    let metadata =
+        String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<metadata version=\"1.0\"/>");
+    let blocks = WoffBlocks {
+        extended_metadata: Some(metadata.clone()),
+        private_data: Some(vec![1, 2, 3]),
+    };
+    let woff2 = write_woff2(&buffer, true, &blocks).unwrap();
+    let woff2_font = ReadScope::new(&woff2).read::<Woff2Font>().unwrap();
+
+    assert_eq!(woff2_font.extended_metadata().unwrap(), Some(metadata));
+    let header = &woff2_font.woff_header;
+    assert_eq!(header.length as usize, woff2.len());
+    assert_eq!(header.priv_offset % 4, 0);
+    let priv_offset = header.priv_offset as usize;
+    assert_eq!(
+        &woff2[priv_offset..priv_offset + header.priv_length as usize],
+        &[1, 2, 3]
+    );
+}